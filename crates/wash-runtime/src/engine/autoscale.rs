@@ -0,0 +1,485 @@
+//! Scaling the instances of a component with its load.
+//!
+//! Every invocation of a component runs on an instance of its own. A component
+//! declaring [`Autoscaling`] runs at most as many invocations at once as its
+//! [`InstancePool`] holds instances, between `min_instances` and
+//! `max_instances`, instead of a static `pool_size`:
+//!
+//! - An invocation takes an idle instance of the pool. When there is none, the
+//!   pool grows by one instance, or once it holds `max_instances`, the
+//!   invocation waits in the queue of the pool until an instance is released.
+//! - Every [`SCALE_WINDOW`], the pool estimates the instances its load needs:
+//!   the time invocations ran during the window divided by its length, that is
+//!   their rate times their latency, plus the queue depth. Slow invocations
+//!   thus keep more instances around than fast ones at the same rate.
+//! - Instances idle for longer than `idle_ttl_secs` are reclaimed, down to the
+//!   estimated load and never below `min_instances`.
+//!
+//! Bursty HTTP workloads get the instances they need during a burst and give
+//! them back afterwards, where a fixed pool either holds instances it doesn't
+//! need or queues requests it could serve. The memory quota of a workload, see
+//! [`crate::host::limits`], counts `max_instances` instances for the
+//! component.
+//!
+//! During a rolling update of its workload, see
+//! [`RollingUpdate`](crate::types::RollingUpdate), a pool is limited to fewer
+//! instances than `max_instances` with [`InstancePool::limit_instances`], as
+//! the instances of the old version are replaced step by step.
+//!
+//! # Backpressure
+//!
+//! The queue of a pool holds at most `queue_depth` invocations, and its
+//! [`QueuePolicy`] decides what happens to invocations once every instance is
+//! busy: they wait in the queue and are refused once it is full, they are
+//! refused right away, or they take the place of the invocation waiting the
+//! longest, which is refused instead. A refused invocation fails with
+//! [`Overloaded`], answered with `429 Too Many Requests` over HTTP. The time
+//! invocations wait is recorded in the `wasmcloud.workload.invocation.queue_time`
+//! histogram and refused invocations are counted in
+//! `wasmcloud.workload.invocation.refused`, by the reason they were refused.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::types::{Autoscaling, QueuePolicy};
+
+/// How often a pool estimates the instances its load needs
+pub const SCALE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Why an invocation was refused an instance, see the
+/// [module docs](self#backpressure).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overloaded {
+    /// Every instance was busy and the policy is [`QueuePolicy::Reject`]
+    Rejected,
+    /// The queue was full
+    QueueFull,
+    /// The invocation waited the longest when a newer one arrived to a full
+    /// queue with [`QueuePolicy::ShedOldest`]
+    Shed,
+}
+
+impl Overloaded {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Overloaded::Rejected => "rejected",
+            Overloaded::QueueFull => "queue_full",
+            Overloaded::Shed => "shed",
+        }
+    }
+}
+
+impl std::fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Overloaded::Rejected => "every instance of the component is busy",
+            Overloaded::QueueFull => "the queue of the component is full",
+            Overloaded::Shed => "shed from the queue of the component for a newer invocation",
+        })
+    }
+}
+
+impl std::error::Error for Overloaded {}
+
+/// Returns whether `err` is an invocation refused an instance.
+pub fn is_overloaded(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<Overloaded>())
+}
+
+/// The instances of an autoscaling component, see the [module docs](self).
+#[derive(Debug)]
+pub struct InstancePool {
+    min_instances: u32,
+    max_instances: u32,
+    idle_ttl: Duration,
+    queue_depth: Option<u32>,
+    queue_policy: QueuePolicy,
+    state: Mutex<PoolState>,
+}
+
+type Waiter = oneshot::Sender<Result<PoolPermit, Overloaded>>;
+
+#[derive(Debug)]
+struct PoolState {
+    /// When each idle instance was released, the most recent last
+    idle: VecDeque<Instant>,
+    busy: u32,
+    /// The invocations waiting for an instance, the oldest first
+    queue: VecDeque<Waiter>,
+    window_started: Instant,
+    /// Time the invocations that finished during the window ran
+    window_busy: Duration,
+    /// The instances the load of the last window needed
+    target: u32,
+    /// The instances a rolling update allows, below `max_instances`
+    limit: Option<u32>,
+}
+
+impl PoolState {
+    fn instances(&self) -> u32 {
+        self.idle.len() as u32 + self.busy
+    }
+
+    /// Forgets the invocations that stopped waiting and returns the others.
+    fn queued(&mut self) -> u32 {
+        self.queue.retain(|waiter| !waiter.is_closed());
+        self.queue.len() as u32
+    }
+}
+
+/// The instances of an [`InstancePool`], see [`InstancePool::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Instances the pool holds, busy or idle
+    pub instances: u32,
+    /// Instances running an invocation
+    pub busy: u32,
+    /// Invocations waiting for an instance
+    pub queued: u32,
+}
+
+/// An instance of an [`InstancePool`] held by an invocation, released to the
+/// pool when dropped.
+#[derive(Debug)]
+pub struct PoolPermit {
+    /// `None` once the permit no longer holds the instance
+    pool: Option<Arc<InstancePool>>,
+    acquired_at: Instant,
+    queue_time: Duration,
+}
+
+impl PoolPermit {
+    /// Returns how long the invocation waited for its instance.
+    pub fn queue_time(&self) -> Duration {
+        self.queue_time
+    }
+}
+
+impl Drop for PoolPermit {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release(self.acquired_at, Instant::now());
+        }
+    }
+}
+
+impl InstancePool {
+    /// Creates a pool holding `min_instances` idle instances.
+    ///
+    /// # Errors
+    /// Returns an error if `max_instances` is zero or below `min_instances`.
+    pub fn new(autoscaling: &Autoscaling) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            autoscaling.max_instances > 0,
+            "autoscaling needs a max_instances of at least 1"
+        );
+        anyhow::ensure!(
+            autoscaling.min_instances <= autoscaling.max_instances,
+            "autoscaling min_instances ({}) exceeds max_instances ({})",
+            autoscaling.min_instances,
+            autoscaling.max_instances
+        );
+        let now = Instant::now();
+        Ok(Self {
+            min_instances: autoscaling.min_instances,
+            max_instances: autoscaling.max_instances,
+            idle_ttl: Duration::from_secs(autoscaling.idle_ttl_secs),
+            queue_depth: autoscaling.queue_depth,
+            queue_policy: autoscaling.queue_policy,
+            state: Mutex::new(PoolState {
+                idle: std::iter::repeat_n(now, autoscaling.min_instances as usize).collect(),
+                busy: 0,
+                queue: VecDeque::new(),
+                window_started: now,
+                window_busy: Duration::ZERO,
+                target: autoscaling.min_instances,
+                limit: None,
+            }),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns the most instances the pool may hold, without a limit.
+    pub fn max_instances(&self) -> u32 {
+        self.max_instances
+    }
+
+    /// Returns the most instances the pool may hold now.
+    fn capacity(&self, state: &PoolState) -> u32 {
+        state
+            .limit
+            .map_or(self.max_instances, |limit| limit.min(self.max_instances))
+    }
+
+    /// Limits the pool to `limit` instances, or lifts the limit if `None`.
+    /// Idle instances beyond the limit are dropped right away, busy ones once
+    /// their invocation finishes. Queued invocations take the instances a
+    /// higher limit allows.
+    pub fn limit_instances(self: &Arc<Self>, limit: Option<u32>) {
+        let mut state = self.lock();
+        state.limit = limit;
+        let capacity = self.capacity(&state);
+        while state.instances() > capacity && state.idle.pop_front().is_some() {}
+        while state.instances() < capacity
+            && let Some(waiter) = state.queue.pop_front()
+        {
+            let permit = PoolPermit {
+                pool: Some(self.clone()),
+                acquired_at: Instant::now(),
+                queue_time: Duration::ZERO,
+            };
+            match waiter.send(Ok(permit)) {
+                Ok(()) => state.busy += 1,
+                // The invocation stopped waiting
+                Err(Ok(mut permit)) => permit.pool = None,
+                Err(Err(_)) => {}
+            }
+        }
+    }
+
+    /// Takes an idle instance, growing the pool if it has none, or waits for
+    /// one to be released once the pool holds `max_instances`, or its limit.
+    ///
+    /// # Errors
+    /// Returns [`Overloaded`] if the queue policy refuses the invocation.
+    pub async fn acquire(self: &Arc<Self>) -> Result<PoolPermit, Overloaded> {
+        let enqueued = Instant::now();
+        let instance = {
+            let mut state = self.lock();
+            self.rescale(&mut state, enqueued);
+            if state.idle.pop_back().is_some() || state.instances() < self.capacity(&state) {
+                state.busy += 1;
+                return Ok(PoolPermit {
+                    pool: Some(self.clone()),
+                    acquired_at: enqueued,
+                    queue_time: Duration::ZERO,
+                });
+            }
+            let full = self
+                .queue_depth
+                .is_some_and(|depth| state.queued() >= depth);
+            match self.queue_policy {
+                QueuePolicy::Reject => return Err(Overloaded::Rejected),
+                QueuePolicy::Queue if full => return Err(Overloaded::QueueFull),
+                QueuePolicy::ShedOldest if full => match state.queue.pop_front() {
+                    Some(oldest) => {
+                        let _ = oldest.send(Err(Overloaded::Shed));
+                    }
+                    None => return Err(Overloaded::QueueFull),
+                },
+                QueuePolicy::Queue | QueuePolicy::ShedOldest => {}
+            }
+            let (waiter, instance) = oneshot::channel();
+            state.queue.push_back(waiter);
+            instance
+        };
+        // The pool never drops waiters without answering them
+        let mut permit = instance.await.unwrap_or(Err(Overloaded::Shed))?;
+        permit.queue_time = enqueued.elapsed();
+        Ok(permit)
+    }
+
+    /// Hands the instance of an invocation that finished over to the oldest
+    /// waiting invocation, or returns it to the idle instances.
+    fn release(self: Arc<Self>, acquired_at: Instant, now: Instant) {
+        let mut state = self.lock();
+        state.window_busy += now.saturating_duration_since(acquired_at);
+        // The instance is dropped when the pool holds more than its limit
+        if state.instances() > self.capacity(&state) {
+            state.busy -= 1;
+            return;
+        }
+        while let Some(waiter) = state.queue.pop_front() {
+            let permit = PoolPermit {
+                pool: Some(self.clone()),
+                acquired_at: now,
+                queue_time: Duration::ZERO,
+            };
+            match waiter.send(Ok(permit)) {
+                Ok(()) => return,
+                // The invocation stopped waiting, the instance goes to the next one
+                Err(Ok(mut permit)) => permit.pool = None,
+                Err(Err(_)) => {}
+            }
+        }
+        state.busy -= 1;
+        state.idle.push_back(now);
+        self.rescale(&mut state, now);
+    }
+
+    /// Estimates the load of the pool once a window has passed and reclaims the
+    /// instances idle for longer than the TTL beyond it.
+    fn rescale(&self, state: &mut PoolState, now: Instant) {
+        let window = now.saturating_duration_since(state.window_started);
+        if window >= SCALE_WINDOW {
+            // The instances busy on average during the window, and at least those busy now
+            let load = (state.window_busy.as_secs_f64() / window.as_secs_f64()).ceil() as u32;
+            let target = (load.max(state.busy) + state.queued())
+                .clamp(self.min_instances, self.max_instances);
+            if target != state.target {
+                debug!(
+                    target,
+                    instances = state.instances(),
+                    queued = state.queue.len(),
+                    "instance pool load changed"
+                );
+            }
+            state.target = target;
+            state.window_started = now;
+            state.window_busy = Duration::ZERO;
+        }
+        while state.instances() > state.target
+            && state
+                .idle
+                .front()
+                .is_some_and(|released| now.saturating_duration_since(*released) >= self.idle_ttl)
+        {
+            state.idle.pop_front();
+        }
+    }
+
+    /// Returns the instances of the pool, once idle ones past their TTL are
+    /// reclaimed.
+    pub fn stats(&self) -> PoolStats {
+        let mut state = self.lock();
+        self.rescale(&mut state, Instant::now());
+        PoolStats {
+            instances: state.instances(),
+            busy: state.busy,
+            queued: state.queued(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt as _;
+
+    use super::*;
+
+    fn autoscaling(min_instances: u32, max_instances: u32) -> Autoscaling {
+        Autoscaling {
+            min_instances,
+            max_instances,
+            ..Default::default()
+        }
+    }
+
+    fn stats(instances: u32, busy: u32, queued: u32) -> PoolStats {
+        PoolStats {
+            instances,
+            busy,
+            queued,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_scales() {
+        assert!(InstancePool::new(&autoscaling(0, 0)).is_err());
+        assert!(InstancePool::new(&autoscaling(3, 2)).is_err());
+
+        let pool = Arc::new(InstancePool::new(&autoscaling(1, 2)).unwrap());
+        assert_eq!(pool.stats(), stats(1, 0, 0));
+
+        // The pool grows up to its maximum, then queues invocations
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+        assert_eq!(pool.stats(), stats(2, 2, 0));
+        let mut third = Box::pin(pool.acquire());
+        assert!((&mut third).now_or_never().is_none());
+        assert_eq!(pool.stats(), stats(2, 2, 1));
+        drop(first);
+        let third = third.await.unwrap();
+        assert_eq!(pool.stats(), stats(2, 2, 0));
+
+        // An invocation dropped while queued leaves the queue
+        assert!(pool.acquire().now_or_never().is_none());
+        assert_eq!(pool.stats(), stats(2, 2, 0));
+        drop((second, third));
+        assert_eq!(pool.stats(), stats(2, 0, 0));
+
+        // Idle instances are reclaimed after their TTL, down to the load of
+        // the last window and the minimum
+        let start = Instant::now();
+        let mut state = pool.lock();
+        state.window_started = start;
+        state.window_busy = Duration::from_millis(1500);
+        pool.rescale(&mut state, start + SCALE_WINDOW);
+        assert_eq!((state.target, state.instances()), (2, 2));
+        pool.rescale(&mut state, start + Duration::from_secs(61));
+        assert_eq!((state.target, state.instances()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_pool_limit() {
+        let pool = Arc::new(InstancePool::new(&autoscaling(2, 4)).unwrap());
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+        let _third = pool.acquire().await.unwrap();
+
+        // Busy instances beyond the limit are dropped once they finish
+        pool.limit_instances(Some(1));
+        assert_eq!(pool.stats(), stats(3, 3, 0));
+        let mut queued = Box::pin(pool.acquire());
+        assert!((&mut queued).now_or_never().is_none());
+        drop(first);
+        assert_eq!(pool.stats(), stats(2, 2, 1));
+        drop(second);
+        assert_eq!(pool.stats(), stats(1, 1, 1));
+
+        // Queued invocations take the instances a higher limit allows
+        pool.limit_instances(Some(2));
+        assert_eq!(pool.stats(), stats(2, 2, 0));
+        let _queued = queued.await.unwrap();
+        pool.limit_instances(None);
+        let _fourth = pool.acquire().await.unwrap();
+        assert_eq!(pool.stats(), stats(3, 3, 0));
+        assert_eq!(pool.max_instances(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_queue_policies() {
+        let pool = |queue_policy| {
+            Arc::new(
+                InstancePool::new(&Autoscaling {
+                    max_instances: 1,
+                    queue_depth: Some(1),
+                    queue_policy,
+                    ..Default::default()
+                })
+                .unwrap(),
+            )
+        };
+
+        let reject = pool(QueuePolicy::Reject);
+        let _busy = reject.acquire().await.unwrap();
+        assert_eq!(reject.acquire().await.unwrap_err(), Overloaded::Rejected);
+
+        let queue = pool(QueuePolicy::Queue);
+        let busy = queue.acquire().await.unwrap();
+        let mut queued = Box::pin(queue.acquire());
+        assert!((&mut queued).now_or_never().is_none());
+        assert_eq!(queue.acquire().await.unwrap_err(), Overloaded::QueueFull);
+        drop(busy);
+        assert!(queued.await.is_ok());
+
+        let shed = pool(QueuePolicy::ShedOldest);
+        let busy = shed.acquire().await.unwrap();
+        let mut oldest = Box::pin(shed.acquire());
+        assert!((&mut oldest).now_or_never().is_none());
+        let mut newest = Box::pin(shed.acquire());
+        assert!((&mut newest).now_or_never().is_none());
+        assert_eq!(oldest.await.unwrap_err(), Overloaded::Shed);
+        drop(busy);
+        assert!(newest.await.is_ok());
+    }
+}
@@ -0,0 +1,246 @@
+//! Compiled components kept on disk across host restarts.
+//!
+//! An [`Engine`](super::Engine) built with
+//! [`EngineBuilder::with_component_cache`](super::EngineBuilder::with_component_cache)
+//! keeps the bytes of every component it compiles, and the compiled artifact
+//! wasmtime serialized, in a [`ComponentCache`] directory, by the digest of
+//! the bytes, see [`component_digest`](super::component_digest). A component
+//! with a cached artifact is loaded instead of compiled, whichever workload
+//! starts it and even after the host restarted.
+//!
+//! Artifacts only load on engines of the same wasmtime version and settings,
+//! so they are kept below a subdirectory per compatibility hash of the engine:
+//! hosts with different settings can share a cache directory without
+//! overwriting each other's artifacts.
+//!
+//! Once the files of the cache exceed its maximum size, the entries used least
+//! recently are deleted. Entries are marked as used by their modification
+//! time.
+//!
+//! Artifacts are loaded as native code, the cache directory must only be
+//! writable by the host.
+
+use std::hash::{Hash as _, Hasher as _};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use tracing::{debug, warn};
+
+/// Default maximum size of a [`ComponentCache`]
+pub const DEFAULT_MAX_SIZE_MB: u64 = 2048;
+
+const MIB: u64 = 1024 * 1024;
+/// Extension of the bytes of a component
+const BYTES_EXTENSION: &str = "wasm";
+/// Extension of the compiled artifact of a component
+const ARTIFACT_EXTENSION: &str = "cwasm";
+
+/// A directory of component bytes and compiled artifacts, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentCache {
+    dir: PathBuf,
+    max_size_bytes: u64,
+}
+
+impl ComponentCache {
+    /// Creates a cache in `dir`, of at most [`DEFAULT_MAX_SIZE_MB`]. The
+    /// directory is created when the first component is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size_bytes: DEFAULT_MAX_SIZE_MB * MIB,
+        }
+    }
+
+    /// Sets the size, in MiB, the files of the cache are kept under.
+    pub fn with_max_size_mb(mut self, max_size_mb: u64) -> Self {
+        self.max_size_bytes = max_size_mb.saturating_mul(MIB);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the cache of the artifacts of `engine`, in a subdirectory
+    /// named after its compatibility hash.
+    pub(crate) fn for_engine(&self, engine: &wasmtime::Engine) -> EngineCache {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        EngineCache {
+            dir: self.dir.join(format!("{:016x}", hasher.finish())),
+            max_size_bytes: self.max_size_bytes,
+        }
+    }
+}
+
+/// The part of a [`ComponentCache`] of one engine
+#[derive(Debug, Clone)]
+pub(crate) struct EngineCache {
+    dir: PathBuf,
+    max_size_bytes: u64,
+}
+
+impl EngineCache {
+    fn path(&self, digest: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{digest}.{extension}"))
+    }
+
+    /// Returns the cached artifact of the component with `digest`, marking
+    /// it as used.
+    pub(crate) fn artifact(&self, digest: &str) -> Option<Vec<u8>> {
+        let path = self.path(digest, ARTIFACT_EXTENSION);
+        let artifact = std::fs::read(&path).ok()?;
+        touch(&path);
+        touch(&self.path(digest, BYTES_EXTENSION));
+        Some(artifact)
+    }
+
+    /// Returns the cached bytes of the component with `digest`, if they still
+    /// have that digest.
+    pub(crate) fn bytes(&self, digest: &str) -> Option<Vec<u8>> {
+        let bytes = std::fs::read(self.path(digest, BYTES_EXTENSION)).ok()?;
+        (super::component_digest(&bytes) == digest).then_some(bytes)
+    }
+
+    /// Stores the bytes and compiled artifact of a component, then evicts
+    /// entries until the cache fits its maximum size. Failures are logged, a
+    /// component that can't be cached is compiled again next time.
+    pub(crate) fn store(&self, digest: &str, bytes: &[u8], artifact: &[u8]) {
+        let stored = std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))
+            .and_then(|()| write_atomic(&self.path(digest, BYTES_EXTENSION), bytes))
+            .and_then(|()| write_atomic(&self.path(digest, ARTIFACT_EXTENSION), artifact));
+        match stored {
+            Ok(()) => debug!(digest, "cached compiled component"),
+            Err(e) => {
+                warn!(digest, "failed to cache compiled component: {e:#}");
+                return;
+            }
+        }
+        if let Err(e) = self.evict() {
+            warn!(dir = %self.dir.display(), "failed to evict cached components: {e:#}");
+        }
+    }
+
+    /// Removes a cached artifact that failed to load.
+    pub(crate) fn remove(&self, digest: &str) {
+        let _ = std::fs::remove_file(self.path(digest, ARTIFACT_EXTENSION));
+        let _ = std::fs::remove_file(self.path(digest, BYTES_EXTENSION));
+    }
+
+    /// Deletes the entries used least recently until the cache fits its
+    /// maximum size.
+    fn evict(&self) -> anyhow::Result<()> {
+        // Entries by digest, with the sum of the sizes of their files and when
+        // they were last used
+        let mut entries: std::collections::HashMap<String, (u64, SystemTime)> =
+            std::collections::HashMap::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(digest) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some(BYTES_EXTENSION | ARTIFACT_EXTENSION)
+            ) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let (size, used) = entries
+                .entry(digest.to_string())
+                .or_insert((0, SystemTime::UNIX_EPOCH));
+            *size += metadata.len();
+            *used = (*used).max(modified);
+        }
+
+        let mut total: u64 = entries.values().map(|(size, _)| size).sum();
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.sort_by_key(|(_, (_, used))| *used);
+        for (digest, (size, _)) in entries {
+            if total <= self.max_size_bytes {
+                break;
+            }
+            debug!(digest, size, "evicting cached component");
+            self.remove(&digest);
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file through a temporary file, so readers never see it half
+/// written.
+fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, data).with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("failed to write {}", path.display())
+    })
+}
+
+/// Marks a cache file as used now.
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EngineCache {
+            dir: dir.path().join("engine"),
+            max_size_bytes: 2 * MIB,
+        };
+        let bytes = |fill: u8| vec![fill; MIB as usize / 2];
+        let digest = |fill: u8| super::super::component_digest(&bytes(fill));
+
+        cache.store(&digest(1), &bytes(1), b"artifact-1");
+        assert_eq!(cache.artifact(&digest(1)).unwrap(), b"artifact-1");
+        assert_eq!(cache.bytes(&digest(1)).unwrap(), bytes(1));
+        assert!(cache.artifact(&digest(2)).is_none());
+
+        // Entry 1 is used after entry 2, so entry 2 is evicted first
+        let past = SystemTime::now() - std::time::Duration::from_secs(60);
+        cache.store(&digest(2), &bytes(2), b"artifact-2");
+        for extension in [BYTES_EXTENSION, ARTIFACT_EXTENSION] {
+            std::fs::File::options()
+                .write(true)
+                .open(cache.path(&digest(2), extension))
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+        cache.store(&digest(3), &bytes(3), b"artifact-3");
+        cache.store(&digest(4), &bytes(4), b"artifact-4");
+        assert!(cache.artifact(&digest(2)).is_none());
+        for fill in [1, 3, 4] {
+            assert!(cache.artifact(&digest(fill)).is_some(), "{fill}");
+        }
+
+        // Bytes that don't match their digest are not returned
+        std::fs::write(cache.path(&digest(3), BYTES_EXTENSION), b"tampered").unwrap();
+        assert!(cache.bytes(&digest(3)).is_none());
+    }
+
+    #[test]
+    fn test_for_engine() {
+        let cache = ComponentCache::new("/var/cache/wash/components").with_max_size_mb(16);
+        let engine = wasmtime::Engine::default();
+        let engine_cache = cache.for_engine(&engine);
+        assert!(engine_cache.dir.starts_with(cache.dir()));
+        assert_eq!(engine_cache.max_size_bytes, 16 * MIB);
+        assert_eq!(cache.for_engine(&engine).dir, engine_cache.dir);
+    }
+}
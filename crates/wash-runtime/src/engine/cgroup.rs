@@ -0,0 +1,303 @@
+//! Kernel enforcement of resource limits with cgroup v2 on Linux.
+//!
+//! The runtime caps the linear memory of every instance and can interrupt
+//! guests that run too long, see [`super::memory`] and
+//! [`EngineBuilder::with_epoch_tick`]. With [`Cgroups`], the kernel enforces
+//! the limits of workloads as well, from a cgroup v2 directory delegated to
+//! the host, e.g. with systemd's `Delegate=yes`:
+//!
+//! - Every component or service with a `cpu_limit`, in millicores, runs on
+//!   its own threads, see [`super::pool`], placed in a threaded cgroup
+//!   `<root>/<workload_id>/<component_id>` whose `cpu.max` allows
+//!   `cpu_limit / 1000` CPUs. Components on a named execution pool keep
+//!   running there, unthrottled by the kernel.
+//! - Memory can only be limited per process, so `<root>/memory.max` caps the
+//!   whole host at the memory reserved by its workloads plus a reserve for
+//!   the host itself, while every workload has a `memory_limit_mb`.
+//!
+//! The CPU time of a workload and how long it was throttled are read back
+//! from its cgroup and reported in its [`WorkloadStatus`].
+//!
+//! [`EngineBuilder::with_epoch_tick`]: super::EngineBuilder::with_epoch_tick
+//! [`WorkloadStatus`]: crate::types::WorkloadStatus
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context as _, bail, ensure};
+use tracing::{debug, warn};
+
+use crate::types::WorkloadUsage;
+
+/// Length of the `cpu.max` period, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+/// Smallest quota the kernel accepts in `cpu.max`, in microseconds
+const MIN_CPU_QUOTA_US: u64 = 1_000;
+/// Memory `memory.max` leaves to the host on top of what workloads reserve
+pub const DEFAULT_HOST_MEMORY_RESERVE_MB: u64 = 256;
+/// How long removing a cgroup is retried while its threads exit
+const REMOVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A cgroup v2 directory delegated to the host, under which the CPU of
+/// workloads and the memory of the host are limited by the kernel. See the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Cgroups {
+    root: PathBuf,
+    host_memory_reserve_bytes: u64,
+    /// Whether the root has a `memory.max` to write
+    memory: bool,
+}
+
+impl Cgroups {
+    /// Moves the host process into the cgroup at `root`, which must be a
+    /// cgroup v2 directory the host may write to.
+    ///
+    /// # Errors
+    /// Returns an error if the platform is not Linux, `root` is not a cgroup
+    /// v2 directory, its parent does not delegate the CPU controller or the
+    /// host can't move into it.
+    pub fn new(root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let root = root.into();
+        if !cfg!(target_os = "linux") {
+            bail!("cgroups are only supported on Linux");
+        }
+        let controllers = std::fs::read_to_string(root.join("cgroup.controllers"))
+            .with_context(|| format!("{} is not a cgroup v2 directory", root.display()))?;
+        let controllers: Vec<&str> = controllers.split_whitespace().collect();
+        ensure!(
+            controllers.contains(&"cpu"),
+            "the cpu controller is not available in {}, enable it in the cgroup.subtree_control of its parent",
+            root.display()
+        );
+        let memory = root.join("memory.max").is_file();
+        if !memory {
+            warn!(
+                root = %root.display(),
+                "the memory controller is not available, host memory is not limited by the kernel"
+            );
+        }
+
+        write(&root.join("cgroup.procs"), &std::process::id().to_string())?;
+        Ok(Self {
+            root,
+            host_memory_reserve_bytes: DEFAULT_HOST_MEMORY_RESERVE_MB * 1024 * 1024,
+            memory,
+        })
+    }
+
+    /// Sets the memory left to the host on top of what its workloads reserve
+    /// when the host's memory is limited. Defaults to 256 MiB.
+    pub fn with_host_memory_reserve_mb(mut self, mb: u64) -> Self {
+        self.host_memory_reserve_bytes = mb * 1024 * 1024;
+        self
+    }
+
+    /// Returns the cgroup directory of the host.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Creates the threaded cgroup of a component limited to `cpu_limit`
+    /// millicores.
+    pub(crate) fn component(
+        &self,
+        workload_id: &str,
+        component_id: &str,
+        cpu_limit: u32,
+    ) -> anyhow::Result<ComponentCgroup> {
+        let workload = self.root.join(workload_id);
+        if !workload.is_dir() {
+            // The root holds the host process, so it may only enable the CPU
+            // controller once a threaded child makes it a threaded domain
+            create_threaded(&workload)?;
+            write(&self.root.join("cgroup.subtree_control"), "+cpu")?;
+            write(&workload.join("cgroup.subtree_control"), "+cpu")?;
+        }
+        let path = workload.join(component_id);
+        create_threaded(&path)?;
+        write(&path.join("cpu.max"), &cpu_max(cpu_limit))?;
+        debug!(cgroup = %path.display(), cpu_limit, "created component cgroup");
+        Ok(ComponentCgroup { path })
+    }
+
+    /// Returns the CPU time of the components of a workload, if any of them
+    /// runs in a cgroup.
+    pub fn workload_usage(&self, workload_id: &str) -> Option<WorkloadUsage> {
+        let stat = std::fs::read_to_string(self.root.join(workload_id).join("cpu.stat")).ok()?;
+        Some(parse_cpu_stat(&stat))
+    }
+
+    /// Removes the cgroup of a workload once the threads of its components
+    /// have exited.
+    pub(crate) fn remove_workload(&self, workload_id: &str) {
+        let path = self.root.join(workload_id);
+        if path.is_dir() {
+            remove_in_background(path);
+        }
+    }
+
+    /// Caps the memory of the host at `reserved_bytes` plus the host reserve,
+    /// or lifts the cap if `None`.
+    pub(crate) fn limit_memory(&self, reserved_bytes: Option<u64>) {
+        if !self.memory {
+            return;
+        }
+        let max = match reserved_bytes {
+            Some(bytes) => bytes
+                .saturating_add(self.host_memory_reserve_bytes)
+                .to_string(),
+            None => "max".to_string(),
+        };
+        if let Err(e) = write(&self.root.join("memory.max"), &max) {
+            warn!(err = %e, "failed to limit host memory");
+        }
+    }
+}
+
+/// The threaded cgroup the threads of a component join, removed when
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct ComponentCgroup {
+    path: PathBuf,
+}
+
+impl ComponentCgroup {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ComponentCgroup {
+    fn drop(&mut self) {
+        remove_in_background(self.path.clone());
+    }
+}
+
+/// Moves the current thread into the threaded cgroup at `path`.
+pub(crate) fn join(path: &Path) -> anyhow::Result<()> {
+    write(&path.join("cgroup.threads"), &sys::thread_id().to_string())
+}
+
+/// Returns the `cpu.max` of `cpu_limit` millicores.
+fn cpu_max(cpu_limit: u32) -> String {
+    let quota = (u64::from(cpu_limit) * CPU_PERIOD_US / 1000).max(MIN_CPU_QUOTA_US);
+    format!("{quota} {CPU_PERIOD_US}")
+}
+
+/// Reads the CPU time and throttling of a `cpu.stat` file.
+fn parse_cpu_stat(stat: &str) -> WorkloadUsage {
+    let mut usage = WorkloadUsage::default();
+    for line in stat.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse() else {
+            continue;
+        };
+        match key {
+            "usage_usec" => usage.cpu_usage_us = value,
+            "throttled_usec" => usage.cpu_throttled_us = value,
+            "nr_throttled" => usage.cpu_throttled_periods = value,
+            _ => {}
+        }
+    }
+    usage
+}
+
+fn create_threaded(path: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir(path)
+        .with_context(|| format!("failed to create cgroup {}", path.display()))?;
+    write(&path.join("cgroup.type"), "threaded")
+}
+
+fn write(path: &Path, value: &str) -> anyhow::Result<()> {
+    std::fs::write(path, value)
+        .with_context(|| format!("failed to write '{value}' to {}", path.display()))
+}
+
+/// Removes the cgroup at `path` and its children, retrying while they still
+/// have threads.
+fn remove_in_background(path: PathBuf) {
+    let spawned = std::thread::Builder::new()
+        .name("wash-cgroup-cleanup".to_string())
+        .spawn(move || {
+            let deadline = std::time::Instant::now() + REMOVE_TIMEOUT;
+            loop {
+                match remove(&path) {
+                    Ok(()) => break,
+                    Err(e) if std::time::Instant::now() >= deadline => {
+                        warn!(cgroup = %path.display(), err = %e, "failed to remove cgroup");
+                        break;
+                    }
+                    Err(_) => std::thread::sleep(Duration::from_millis(100)),
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        warn!(err = %e, "failed to spawn cgroup cleanup thread");
+    }
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    let children = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in children {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove(&entry.path())?;
+        }
+    }
+    // Cgroups are removed with rmdir, their interface files go with them
+    match std::fs::remove_dir(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    pub(super) fn thread_id() -> i64 {
+        // SAFETY: gettid takes no arguments and can't fail
+        unsafe { libc::syscall(libc::SYS_gettid) }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub(super) fn thread_id() -> i64 {
+        unreachable!("cgroups are only created on Linux")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_max() {
+        assert_eq!(cpu_max(1000), "100000 100000");
+        assert_eq!(cpu_max(2500), "250000 100000");
+        assert_eq!(cpu_max(250), "25000 100000");
+        // The kernel rejects quotas below 1ms
+        assert_eq!(cpu_max(1), "1000 100000");
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let usage = parse_cpu_stat(
+            "usage_usec 1500000\nuser_usec 1000000\nsystem_usec 500000\nnr_periods 40\nnr_throttled 12\nthrottled_usec 340000\n",
+        );
+        assert_eq!(
+            usage,
+            WorkloadUsage {
+                cpu_usage_us: 1_500_000,
+                cpu_throttled_us: 340_000,
+                cpu_throttled_periods: 12,
+            }
+        );
+    }
+}
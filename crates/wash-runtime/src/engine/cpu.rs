@@ -0,0 +1,135 @@
+//! Enforcing the `cpu_limit` of components with fuel.
+//!
+//! Hosts with [`cgroups`](super::cgroup) have the kernel enforce the
+//! `cpu_limit` of components. Elsewhere, an engine built with
+//! [`EngineBuilder::with_cpu_fuel_rate`] enforces it with wasmtime fuel: every
+//! component with a `cpu_limit`, in millicores, gets a [`CpuThrottle`], a
+//! bucket refilled with `cpu_limit / 1000 * rate` fuel per second and shared by
+//! all of its invocations. At every epoch tick a guest pays for the fuel it
+//! consumed since the last one, and once the bucket is empty it sleeps until
+//! the bucket has refilled, so a component with `cpu_limit: 1000` keeps
+//! roughly one core busy however many invocations it runs at once.
+//!
+//! Fuel counts instructions, not time, so the rate, the fuel one core burns
+//! through in a second, depends on the host and the guests. Operators tune it
+//! from the fuel invocations consume, in the `fuel` of the workload activity
+//! and in the `wasmcloud.workload.invocation.fuel` histogram, against their
+//! duration. Time a guest spends waiting on the host, e.g. for an outgoing
+//! request, consumes no fuel and isn't charged.
+//!
+//! [`EngineBuilder::with_cpu_fuel_rate`]: super::EngineBuilder::with_cpu_fuel_rate
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long a component may run at full speed on a full bucket
+const BURST: Duration = Duration::from_millis(100);
+
+/// Limits a component to its `cpu_limit`, see the [module docs](self).
+#[derive(Debug)]
+pub struct CpuThrottle {
+    /// Fuel the bucket is refilled with per second
+    rate: f64,
+    /// Fuel a full bucket holds
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+    /// Time guests slept for their fuel, in microseconds
+    throttled_us: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Fuel left, negative when guests owe fuel they are sleeping for
+    fuel: f64,
+    refilled_at: Instant,
+}
+
+impl CpuThrottle {
+    /// Limits a component to `millicores`, 1000 being a core that burns
+    /// through `fuel_per_cpu_second` fuel a second.
+    pub fn new(millicores: u32, fuel_per_cpu_second: u64) -> Self {
+        let rate = fuel_per_cpu_second as f64 * f64::from(millicores) / 1000.0;
+        let capacity = rate * BURST.as_secs_f64();
+        Self {
+            rate,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                fuel: capacity,
+                refilled_at: Instant::now(),
+            }),
+            throttled_us: AtomicU64::default(),
+        }
+    }
+
+    /// Charges the component `fuel` a guest consumed, returning how long the
+    /// guest sleeps before running on.
+    pub fn charge(&self, fuel: u64) -> Duration {
+        let pause = self.charge_at(fuel, Instant::now());
+        self.throttled_us
+            .fetch_add(pause.as_micros() as u64, Ordering::Relaxed);
+        pause
+    }
+
+    fn charge_at(&self, fuel: u64, now: Instant) -> Duration {
+        if self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let mut bucket = self
+            .bucket
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.fuel = (bucket.fuel + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+        bucket.refilled_at = now;
+        bucket.fuel -= fuel as f64;
+        if bucket.fuel >= 0.0 {
+            return Duration::ZERO;
+        }
+        // Until the debt is paid back, which later charges wait for as well
+        Duration::from_secs_f64(-bucket.fuel / self.rate)
+    }
+
+    /// Returns how long guests of the component slept for their fuel in total.
+    pub fn throttled(&self) -> Duration {
+        Duration::from_micros(self.throttled_us.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charge() {
+        // Half a core of 1M fuel per second: 500K fuel per second, bursts of 50K
+        let throttle = CpuThrottle::new(500, 1_000_000);
+        let start = throttle.bucket.lock().unwrap().refilled_at;
+        let millis = |pause: Duration| (pause.as_secs_f64() * 1000.0).round() as u64;
+        assert_eq!(millis(throttle.charge_at(50_000, start)), 0);
+
+        // A second burst owes 50K fuel, a tenth of a second
+        assert_eq!(millis(throttle.charge_at(50_000, start)), 100);
+        // Which another guest waits for too
+        assert_eq!(
+            millis(throttle.charge_at(0, start + Duration::from_millis(40))),
+            60
+        );
+        assert_eq!(
+            millis(throttle.charge_at(0, start + Duration::from_millis(100))),
+            0
+        );
+
+        // Idle time refills the bucket up to a burst
+        assert_eq!(
+            millis(throttle.charge_at(50_000, start + Duration::from_secs(10))),
+            0
+        );
+        assert!(throttle.charge_at(1, start + Duration::from_secs(10)) > Duration::ZERO);
+
+        assert_eq!(
+            CpuThrottle::new(0, 1_000_000).charge(u64::MAX),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! Crash-loop detection and restart backoff.
+//!
+//! A component that traps on every invocation, or fails to instantiate, fails
+//! every request sent to it, and each failure costs an instantiation. When
+//! the engine has a [`CrashLoopPolicy`], the consecutive failures of each
+//! component of a workload are counted, and any success resets the count:
+//!
+//! - Once a component failed [`CrashLoopPolicy::threshold`] times in a row, it
+//!   is crash looping. The workload emits a
+//!   [`LifecycleEventType::CrashLoopDetected`](crate::host::events::LifecycleEventType::CrashLoopDetected)
+//!   event and reports the component in its status until it recovers.
+//! - A crash looping component backs off: its invocations fail right away
+//!   with [`CrashLoopBackoff`], without instantiating it, answered with
+//!   `503 Service Unavailable` and a `Retry-After` over HTTP. Once the backoff
+//!   elapsed a single invocation goes through to probe the component. The
+//!   backoff starts at [`CrashLoopPolicy::initial_backoff`] and doubles with
+//!   every further failure, up to [`CrashLoopPolicy::max_backoff`].
+//! - A successful invocation ends the crash loop, and the workload emits a
+//!   [`LifecycleEventType::CrashLoopRecovered`](crate::host::events::LifecycleEventType::CrashLoopRecovered)
+//!   event.
+//! - With [`CrashLoopPolicy::stop_after`], the host stops the workload once a
+//!   component failed that many times in a row, emitting a
+//!   [`LifecycleEventType::WorkloadFailed`](crate::host::events::LifecycleEventType::WorkloadFailed)
+//!   event with the reason.
+//!
+//! The service of a workload counts the same way: a service crashing, or
+//! failing to instantiate again, waits for its backoff before it restarts,
+//! within its maximum number of restarts. Invocations refused by the
+//! instance pool of an autoscaling component, see [`super::autoscale`], are
+//! not failures of the component and don't count.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::types::CrashLoopStatus;
+
+/// Consecutive failures after which a component is crash looping
+pub const DEFAULT_CRASH_LOOP_THRESHOLD: u32 = 5;
+/// Backoff after the failure a crash loop is detected on
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest backoff between two attempts of a crash looping component
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// When a component is crash looping and what the host does about it, see
+/// the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashLoopPolicy {
+    /// Consecutive failures after which a component is crash looping
+    pub threshold: u32,
+    /// Backoff after the failure the crash loop is detected on
+    pub initial_backoff: Duration,
+    /// Longest backoff, however many times the component failed
+    pub max_backoff: Duration,
+    /// Consecutive failures of a component after which its workload is
+    /// stopped, never if `None`
+    pub stop_after: Option<u32>,
+}
+
+impl Default for CrashLoopPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_CRASH_LOOP_THRESHOLD,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            stop_after: None,
+        }
+    }
+}
+
+impl CrashLoopPolicy {
+    /// Validates the policy.
+    ///
+    /// # Errors
+    /// Returns an error if the threshold, a backoff or `stop_after` is zero,
+    /// or the maximum backoff is shorter than the initial one.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.threshold > 0,
+            "crash loop threshold must be greater than zero"
+        );
+        anyhow::ensure!(
+            !self.initial_backoff.is_zero(),
+            "crash loop initial backoff must be greater than zero"
+        );
+        anyhow::ensure!(
+            self.max_backoff >= self.initial_backoff,
+            "crash loop max backoff must not be shorter than its initial backoff"
+        );
+        anyhow::ensure!(
+            self.stop_after != Some(0),
+            "crash loop stop_after must be greater than zero"
+        );
+        Ok(())
+    }
+
+    /// The backoff after a component failed `failures` times in a row.
+    fn backoff(&self, failures: u32) -> Duration {
+        let doublings = failures.saturating_sub(self.threshold);
+        let factor = 1u32.checked_shl(doublings).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// The error an invocation of a crash looping component fails with while it
+/// backs off, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashLoopBackoff {
+    pub component_id: String,
+    /// Consecutive failures of the component
+    pub failures: u32,
+    /// Time until the component is attempted again
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for CrashLoopBackoff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "component '{}' is crash looping after {} consecutive failures, retrying in {}s",
+            self.component_id,
+            self.failures,
+            self.retry_after.as_secs_f64().ceil()
+        )
+    }
+}
+
+impl std::error::Error for CrashLoopBackoff {}
+
+/// Returns the backoff `err` failed an invocation with, if the component
+/// invoked is crash looping.
+pub fn crash_loop_backoff(err: &anyhow::Error) -> Option<&CrashLoopBackoff> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<CrashLoopBackoff>())
+}
+
+/// Asks the host to stop a workload whose component failed
+/// [`CrashLoopPolicy::stop_after`] times in a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CrashLoopStop {
+    pub workload_id: String,
+    pub reason: String,
+}
+
+/// A change in the crash loop of a component, returned by
+/// [`CrashLoopTracker::failed`] and [`CrashLoopTracker::succeeded`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Transition {
+    /// The component started crash looping, backing off for `backoff`
+    Detected { failures: u32, backoff: Duration },
+    /// The component failed [`CrashLoopPolicy::stop_after`] times in a row
+    Stop { failures: u32 },
+    /// The component succeeded after failing `failures` times in a row
+    Recovered { failures: u32 },
+}
+
+#[derive(Debug, Default)]
+struct Failures {
+    consecutive: u32,
+    /// When the component may be attempted again, while it backs off
+    retry_at: Option<Instant>,
+}
+
+/// Counts the consecutive failures of the components of a workload, see the
+/// [module docs](self).
+#[derive(Debug)]
+pub(crate) struct CrashLoopTracker {
+    policy: CrashLoopPolicy,
+    components: Mutex<HashMap<String, Failures>>,
+}
+
+impl CrashLoopTracker {
+    pub(crate) fn new(policy: CrashLoopPolicy) -> Self {
+        Self {
+            policy,
+            components: Mutex::default(),
+        }
+    }
+
+    /// Checks whether `component_id` may be invoked now.
+    ///
+    /// # Errors
+    /// Returns the [`CrashLoopBackoff`] of the component while it backs off.
+    pub(crate) fn check(&self, component_id: &str) -> Result<(), CrashLoopBackoff> {
+        self.check_at(component_id, Instant::now())
+    }
+
+    fn check_at(&self, component_id: &str, now: Instant) -> Result<(), CrashLoopBackoff> {
+        let mut components = self.lock();
+        let Some(failures) = components.get_mut(component_id) else {
+            return Ok(());
+        };
+        match failures.retry_at {
+            Some(retry_at) if retry_at > now => Err(CrashLoopBackoff {
+                component_id: component_id.to_string(),
+                failures: failures.consecutive,
+                retry_after: retry_at - now,
+            }),
+            Some(_) => {
+                // Only this invocation probes the component until it finishes
+                failures.retry_at = Some(now + self.policy.backoff(failures.consecutive));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records a failure of `component_id`.
+    pub(crate) fn failed(&self, component_id: &str) -> Option<Transition> {
+        self.failed_at(component_id, Instant::now())
+    }
+
+    fn failed_at(&self, component_id: &str, now: Instant) -> Option<Transition> {
+        let mut components = self.lock();
+        let failures = components.entry(component_id.to_string()).or_default();
+        failures.consecutive = failures.consecutive.saturating_add(1);
+        let consecutive = failures.consecutive;
+        let stop = self.policy.stop_after == Some(consecutive);
+        if consecutive >= self.policy.threshold {
+            failures.retry_at = Some(now + self.policy.backoff(consecutive));
+        }
+        if stop {
+            Some(Transition::Stop {
+                failures: consecutive,
+            })
+        } else if consecutive == self.policy.threshold {
+            let backoff = self.policy.backoff(consecutive);
+            Some(Transition::Detected {
+                failures: consecutive,
+                backoff,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Records a success of `component_id`, which resets its failures.
+    pub(crate) fn succeeded(&self, component_id: &str) -> Option<Transition> {
+        let failures = self.lock().remove(component_id)?;
+        (failures.consecutive >= self.policy.threshold).then_some(Transition::Recovered {
+            failures: failures.consecutive,
+        })
+    }
+
+    /// Returns how long `component_id` backs off for, zero unless it is
+    /// crash looping.
+    pub(crate) fn retry_after(&self, component_id: &str) -> Duration {
+        let now = Instant::now();
+        self.lock()
+            .get(component_id)
+            .and_then(|failures| failures.retry_at)
+            .map(|retry_at| retry_at.saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+
+    /// Returns the crash looping components, by ID.
+    pub(crate) fn status(&self) -> Vec<CrashLoopStatus> {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> Vec<CrashLoopStatus> {
+        let mut status: Vec<CrashLoopStatus> = self
+            .lock()
+            .iter()
+            .filter(|(_, failures)| failures.consecutive >= self.policy.threshold)
+            .map(|(component_id, failures)| CrashLoopStatus {
+                component_id: component_id.clone(),
+                consecutive_failures: failures.consecutive,
+                retry_after: failures
+                    .retry_at
+                    .map(|retry_at| retry_at.saturating_duration_since(now))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        status.sort_by(|a, b| a.component_id.cmp(&b.component_id));
+        status
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Failures>> {
+        self.components
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(threshold: u32, stop_after: Option<u32>) -> CrashLoopTracker {
+        CrashLoopTracker::new(CrashLoopPolicy {
+            threshold,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            stop_after,
+        })
+    }
+
+    #[test]
+    fn test_policy_validate() {
+        assert!(CrashLoopPolicy::default().validate().is_ok());
+        let invalid = [
+            CrashLoopPolicy {
+                threshold: 0,
+                ..Default::default()
+            },
+            CrashLoopPolicy {
+                initial_backoff: Duration::ZERO,
+                ..Default::default()
+            },
+            CrashLoopPolicy {
+                max_backoff: Duration::from_millis(10),
+                ..Default::default()
+            },
+            CrashLoopPolicy {
+                stop_after: Some(0),
+                ..Default::default()
+            },
+        ];
+        for policy in invalid {
+            assert!(policy.validate().is_err(), "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = tracker(3, None).policy;
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+        assert_eq!(policy.backoff(4), Duration::from_secs(2));
+        assert_eq!(policy.backoff(5), Duration::from_secs(4));
+        assert_eq!(policy.backoff(6), Duration::from_secs(5));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_crash_loop_backs_off_and_recovers() {
+        let tracker = tracker(2, None);
+        let now = Instant::now();
+        assert_eq!(tracker.failed_at("api", now), None);
+        assert!(tracker.check_at("api", now).is_ok());
+        assert_eq!(
+            tracker.failed_at("api", now),
+            Some(Transition::Detected {
+                failures: 2,
+                backoff: Duration::from_secs(1)
+            })
+        );
+
+        let backoff = tracker.check_at("api", now).unwrap_err();
+        assert_eq!(backoff.failures, 2);
+        assert_eq!(backoff.retry_after, Duration::from_secs(1));
+        // Other components are unaffected
+        assert!(tracker.check_at("worker", now).is_ok());
+        assert_eq!(
+            tracker.status_at(now),
+            vec![CrashLoopStatus {
+                component_id: "api".to_string(),
+                consecutive_failures: 2,
+                retry_after: Duration::from_secs(1),
+            }]
+        );
+
+        // A single probe goes through once the backoff elapsed
+        let later = now + Duration::from_secs(1);
+        assert!(tracker.check_at("api", later).is_ok());
+        assert!(tracker.check_at("api", later).is_err());
+        assert_eq!(tracker.failed_at("api", later), None);
+        assert_eq!(
+            tracker.check_at("api", later).unwrap_err().retry_after,
+            Duration::from_secs(2)
+        );
+
+        assert_eq!(
+            tracker.succeeded("api"),
+            Some(Transition::Recovered { failures: 3 })
+        );
+        assert!(tracker.check_at("api", later).is_ok());
+        assert!(tracker.status_at(later).is_empty());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let tracker = tracker(2, None);
+        let now = Instant::now();
+        assert_eq!(tracker.failed_at("api", now), None);
+        // Not crash looping yet, so there is nothing to recover from
+        assert_eq!(tracker.succeeded("api"), None);
+        assert_eq!(tracker.failed_at("api", now), None);
+        assert!(tracker.check_at("api", now).is_ok());
+    }
+
+    #[test]
+    fn test_stop_after_consecutive_failures() {
+        let tracker = tracker(2, Some(3));
+        let now = Instant::now();
+        tracker.failed_at("api", now);
+        assert!(matches!(
+            tracker.failed_at("api", now),
+            Some(Transition::Detected { .. })
+        ));
+        assert_eq!(
+            tracker.failed_at("api", now),
+            Some(Transition::Stop { failures: 3 })
+        );
+        // The stop is only requested once
+        assert_eq!(tracker.failed_at("api", now), None);
+    }
+
+    #[test]
+    fn test_crash_loop_backoff_error() {
+        let err = anyhow::Error::new(CrashLoopBackoff {
+            component_id: "api".to_string(),
+            failures: 5,
+            retry_after: Duration::from_millis(1500),
+        })
+        .context("failed to invoke component");
+        assert_eq!(crash_loop_backoff(&err).unwrap().failures, 5);
+        assert!(crash_loop_backoff(&anyhow::anyhow!("guest trapped")).is_none());
+        assert_eq!(
+            crash_loop_backoff(&err).unwrap().to_string(),
+            "component 'api' is crash looping after 5 consecutive failures, retrying in 2s"
+        );
+    }
+}
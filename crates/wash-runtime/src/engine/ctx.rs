@@ -4,17 +4,30 @@
 //! for wasmtime when executing WebAssembly components. It integrates WASI
 //! interfaces, HTTP capabilities, and plugin access into a unified context.
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 use std::{any::Any, collections::HashMap, sync::Arc};
 
+use http_body_util::BodyExt as _;
+use wasmtime::StoreLimitsBuilder;
 use wasmtime::component::ResourceTable;
 use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
 use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
 
+use crate::engine::filesystem::FilesystemQuotas;
+use crate::engine::memory::MemoryLimiter;
+use crate::engine::output::LogContext;
+use crate::host::http::EgressPolicy;
+use crate::host::http::middleware::Next;
+use crate::host::logs::{ComponentLogger, LogLevel, LogRecord};
+use crate::host::metering::UsageMeter;
+use crate::host::metrics::{InstanceMetrics, WorkloadMetrics};
 use crate::plugin::HostPlugin;
 
 /// The context for a component store and linker, providing access to implementations of:
 /// - wasi@0.2 interfaces
 /// - wasi:http@0.2 interfaces
+/// - wasi:http@0.3 handlers, with the `wasip3` feature
 pub struct Ctx {
     /// Unique identifier for this component context. This is a [uuid::Uuid::new_v4] string.
     pub id: String,
@@ -28,12 +41,44 @@ pub struct Ctx {
     pub ctx: WasiCtx,
     /// The HTTP context used to provide HTTP functionality to the component.
     pub http: WasiHttpCtx,
+    /// The HTTP context of components serving `wasi:http@0.3` handlers
+    #[cfg(feature = "wasip3")]
+    http_p3: HttpP3Ctx,
     /// Plugin instances stored by string ID for access during component execution.
     /// These all implement the [`HostPlugin`] trait, but they are cast as `Arc<dyn Any + Send + Sync>`
     /// to support downcasting to the specific plugin type in [`Ctx::get_plugin`]
     plugins: HashMap<&'static str, Arc<dyn Any + Send + Sync>>,
     /// The HTTP handler for outgoing HTTP requests.
     http_handler: Option<Arc<dyn crate::host::http::HostHandler>>,
+    /// The hosts outgoing HTTP requests may be sent to
+    egress: Arc<EgressPolicy>,
+    /// Resource limits enforced on the store, see [`wasmtime::Store::limiter`]
+    pub(crate) limits: MemoryLimiter,
+    /// The log context guest output is attributed to
+    log: LogContext,
+    /// The serving component a middleware passes requests on to
+    next: Option<Next>,
+    /// Meters the invocation made in this store, see [`crate::host::metering`]
+    pub(crate) usage: Option<UsageMeter>,
+    /// Records the memory and fuel of the instance when the store is dropped
+    instance_metrics: Option<InstanceMetrics>,
+    /// Adds up the fuel of the invocations of the workload
+    fuel_total: Option<Arc<AtomicU64>>,
+    /// Captures what the component logs through `wasi:logging`, see
+    /// [`crate::host::logs`]
+    logger: Option<ComponentLogger>,
+    /// The span the outgoing HTTP requests of the component are sent in, see
+    /// [`crate::host::http::trace`]
+    trace: Option<opentelemetry::trace::SpanContext>,
+    /// Enforces the quotas of the volume mounts, see
+    /// [`crate::engine::filesystem`]
+    pub(crate) filesystem_quotas: Option<Arc<FilesystemQuotas>>,
+    /// Lists the instance of this store, see [`crate::engine::debug`]
+    #[cfg(feature = "instance-debug")]
+    debug_instance: Option<crate::engine::debug::InstanceGuard>,
+    /// When the invocation of this store started, which its invocation
+    /// timeout counts from
+    pub(crate) started: Instant,
 }
 
 impl Ctx {
@@ -42,6 +87,95 @@ impl Ctx {
         self.plugins.get(plugin_id)?.clone().downcast().ok()
     }
 
+    /// Returns the log context the stdout and stderr of the component are
+    /// attributed to.
+    pub fn log_context(&self) -> &LogContext {
+        &self.log
+    }
+
+    /// Captures a line the component logged through `wasi:logging`, see
+    /// [`crate::host::logs`]. Without a logger the line is only emitted as a
+    /// `tracing` event.
+    pub fn guest_log(&self, level: LogLevel, context: String, message: String) {
+        match &self.logger {
+            Some(logger) => logger.log(level, context, message),
+            None => LogRecord {
+                timestamp: std::time::SystemTime::now(),
+                workload_id: self.workload_id.clone(),
+                workload_name: Arc::from(""),
+                workload_namespace: Arc::from(""),
+                component_id: self.component_id.clone(),
+                level,
+                context,
+                message,
+            }
+            .trace(),
+        }
+    }
+
+    /// Holds the outgoing HTTP requests of the component to `deadline`, see
+    /// [`crate::host::http::deadline`].
+    pub fn set_deadline(&mut self, deadline: tokio::time::Instant) {
+        self.egress = Arc::new(self.egress.as_ref().clone().with_deadline(deadline));
+    }
+
+    /// Sends the outgoing HTTP requests of a middleware component addressed to
+    /// [`NEXT_AUTHORITY`](crate::host::http::middleware::NEXT_AUTHORITY) to
+    /// `next`, see [`crate::host::http::middleware`].
+    pub fn set_next(&mut self, next: Next) {
+        self.next = Some(next);
+    }
+
+    /// Propagates the trace of `span` in the outgoing HTTP requests of the
+    /// component, see [`crate::host::http::trace`].
+    pub fn set_trace(&mut self, span: opentelemetry::trace::SpanContext) {
+        self.trace = Some(span);
+    }
+
+    /// Records the export the instance of this store runs, e.g.
+    /// `wasi:http/incoming-handler#handle`, listed with the live instances
+    /// of the workload, see [`crate::engine::debug`].
+    pub fn set_invocation(&self, _export: &str) {
+        #[cfg(feature = "instance-debug")]
+        if let Some(guard) = &self.debug_instance {
+            guard.set_invocation(_export);
+        }
+    }
+
+    #[cfg(feature = "instance-debug")]
+    pub(crate) fn set_debug_instance(&mut self, guard: crate::engine::debug::InstanceGuard) {
+        self.debug_instance = Some(guard);
+    }
+
+    #[cfg(feature = "instance-debug")]
+    pub(crate) fn debug_instance(&self) -> Option<&crate::engine::debug::InstanceGuard> {
+        self.debug_instance.as_ref()
+    }
+
+    /// Starts the invocation of this store over, for a store created ahead of
+    /// its invocation, see [`crate::engine::preinit`]. Its invocation timeout
+    /// and metered duration count from now.
+    pub(crate) fn restart_invocation(&mut self) {
+        self.started = Instant::now();
+        if let Some(meter) = self.usage.as_mut() {
+            meter.restart();
+        }
+    }
+
+    /// Records the fuel the invocation made in this store consumed, see
+    /// [`crate::host::metering::record_fuel`].
+    pub(crate) fn record_fuel(&mut self, fuel: u64) {
+        if let Some(meter) = self.usage.as_mut() {
+            meter.usage.fuel = Some(fuel);
+        }
+        if let Some(metrics) = self.instance_metrics.as_mut() {
+            metrics.fuel = Some(fuel);
+        }
+        if let Some(total) = &self.fuel_total {
+            total.fetch_add(fuel, Ordering::Relaxed);
+        }
+    }
+
     /// Create a new [`CtxBuilder`] to construct a [`Ctx`]
     pub fn builder(
         workload_id: impl Into<Arc<str>>,
@@ -89,11 +223,25 @@ impl WasiHttpView for Ctx {
 
     fn send_request(
         &mut self,
-        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        mut request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
         config: wasmtime_wasi_http::types::OutgoingRequestConfig,
     ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse> {
+        if let Some(span) = &self.trace {
+            crate::host::http::trace::inject_traceparent(request.headers_mut(), span);
+        }
+        if let Some(next) = &self.next
+            && Next::matches(&request)
+        {
+            return Ok(next.send(request, config));
+        }
+        let request = match &self.usage {
+            Some(usage) => request.map(|body| usage.count_egress(body).boxed()),
+            None => request,
+        };
         match &self.http_handler {
-            Some(handler) => handler.outgoing_request(&self.workload_id, request, config),
+            Some(handler) => {
+                handler.outgoing_request(&self.workload_id, self.egress.clone(), request, config)
+            }
             None => Err(wasmtime_wasi_http::HttpError::trap(anyhow::anyhow!(
                 "http client not available"
             ))),
@@ -101,6 +249,26 @@ impl WasiHttpView for Ctx {
     }
 }
 
+/// The `wasi:http@0.3` context. Components sending `wasi:http@0.3` requests
+/// are rejected when they are loaded, as those would not go through the
+/// [`EgressPolicy`], so it only ever handles incoming requests.
+#[cfg(feature = "wasip3")]
+struct HttpP3Ctx;
+
+#[cfg(feature = "wasip3")]
+impl wasmtime_wasi_http::p3::WasiHttpCtx for HttpP3Ctx {}
+
+// Implement WasiHttpView for wasi:http@0.3
+#[cfg(feature = "wasip3")]
+impl wasmtime_wasi_http::p3::WasiHttpView for Ctx {
+    fn http(&mut self) -> wasmtime_wasi_http::p3::WasiHttpCtxView<'_> {
+        wasmtime_wasi_http::p3::WasiHttpCtxView {
+            ctx: &mut self.http_p3,
+            table: &mut self.table,
+        }
+    }
+}
+
 /// Helper struct to build a [`Ctx`] with a builder pattern
 pub struct CtxBuilder {
     id: String,
@@ -109,6 +277,14 @@ pub struct CtxBuilder {
     ctx: Option<WasiCtx>,
     plugins: HashMap<&'static str, Arc<dyn HostPlugin + Send + Sync>>,
     http_handler: Option<Arc<dyn crate::host::http::HostHandler>>,
+    egress: EgressPolicy,
+    memory_limit: Option<usize>,
+    log: LogContext,
+    usage: Option<UsageMeter>,
+    instance_metrics: Option<(Arc<WorkloadMetrics>, Vec<opentelemetry::KeyValue>)>,
+    fuel_total: Option<Arc<AtomicU64>>,
+    logger: Option<ComponentLogger>,
+    filesystem_quotas: Option<Arc<FilesystemQuotas>>,
 }
 
 impl CtxBuilder {
@@ -120,6 +296,14 @@ impl CtxBuilder {
             ctx: None,
             http_handler: None,
             plugins: HashMap::new(),
+            egress: EgressPolicy::default(),
+            memory_limit: None,
+            log: LogContext::default(),
+            usage: None,
+            instance_metrics: None,
+            fuel_total: None,
+            logger: None,
+            filesystem_quotas: None,
         }
     }
 
@@ -144,6 +328,65 @@ impl CtxBuilder {
         self
     }
 
+    /// Restricts outgoing HTTP requests to the hosts `egress` allows. All hosts
+    /// are allowed by default.
+    pub fn with_egress_policy(mut self, egress: EgressPolicy) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Limits each linear memory of the component to `bytes`. Growing a memory
+    /// beyond the limit fails the same way as running out of memory would.
+    pub fn with_memory_limit(mut self, bytes: Option<usize>) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Shares `log` with the output streams of the WASI context. Its request
+    /// id defaults to the id of the context.
+    pub fn with_log_context(mut self, log: LogContext) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Meters the invocation made in the store, see [`crate::host::metering`].
+    pub(crate) fn with_usage_meter(mut self, usage: Option<UsageMeter>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Records the memory and fuel of the instance in `metrics` with
+    /// `attributes` when the store is dropped, see [`crate::host::metrics`].
+    pub(crate) fn with_instance_metrics(
+        mut self,
+        metrics: Option<Arc<WorkloadMetrics>>,
+        attributes: Vec<opentelemetry::KeyValue>,
+    ) -> Self {
+        self.instance_metrics = metrics.map(|metrics| (metrics, attributes));
+        self
+    }
+
+    /// Adds the fuel the invocation consumes to `total`, when the engine
+    /// consumes fuel.
+    pub(crate) fn with_fuel_total(mut self, total: Arc<AtomicU64>) -> Self {
+        self.fuel_total = Some(total);
+        self
+    }
+
+    /// Captures what the component logs through `wasi:logging` with
+    /// `logger`, see [`crate::host::logs`].
+    pub fn with_logger(mut self, logger: ComponentLogger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Enforces the quotas of the volume mounts of the component with
+    /// `quotas`, see [`crate::engine::filesystem`].
+    pub(crate) fn with_filesystem_quotas(mut self, quotas: Option<Arc<FilesystemQuotas>>) -> Self {
+        self.filesystem_quotas = quotas;
+        self
+    }
+
     pub fn build(self) -> Ctx {
         let plugins = self
             .plugins
@@ -151,6 +394,16 @@ impl CtxBuilder {
             .map(|(k, v)| (k, v as Arc<dyn Any + Send + Sync>))
             .collect();
 
+        let limits = MemoryLimiter::new(StoreLimitsBuilder::new().build(), self.memory_limit);
+        let usage = self.usage.map(|usage| usage.with_memory(limits.usage()));
+        let instance_metrics = self
+            .instance_metrics
+            .map(|(metrics, attributes)| InstanceMetrics::new(metrics, attributes, limits.usage()));
+
+        if self.log.request_id().is_none() {
+            self.log.set_request_id(self.id.as_str());
+        }
+
         Ctx {
             id: self.id,
             ctx: self.ctx.unwrap_or_else(|| {
@@ -162,9 +415,24 @@ impl CtxBuilder {
             workload_id: self.workload_id,
             component_id: self.component_id,
             http: WasiHttpCtx::new(),
+            #[cfg(feature = "wasip3")]
+            http_p3: HttpP3Ctx,
             table: ResourceTable::new(),
             plugins,
             http_handler: self.http_handler,
+            egress: Arc::new(self.egress),
+            limits,
+            usage,
+            instance_metrics,
+            fuel_total: self.fuel_total,
+            logger: self.logger,
+            log: self.log,
+            next: None,
+            trace: None,
+            filesystem_quotas: self.filesystem_quotas,
+            #[cfg(feature = "instance-debug")]
+            debug_instance: None,
+            started: Instant::now(),
         }
     }
 }
@@ -0,0 +1,269 @@
+//! Inspecting the live component instances of workloads.
+//!
+//! With the `instance-debug` feature, every workload keeps a registry of the
+//! component instances alive in it, which operators list with the
+//! `workload.instances` API command to diagnose a workload whose invocations
+//! are stuck: the memory of each instance, the export it runs and for how
+//! long.
+//!
+//! An operator may also ask for the core dump of an instance with the
+//! `workload.instance.dump` API command. Instances live for a single
+//! invocation, so there are no idle instances to dump: the instance is
+//! interrupted at its next epoch tick, failing its invocation, and the
+//! wasmtime core dump of the trap is returned. Dumps need an engine with
+//! epoch interruption, see [`EngineBuilder::with_epoch_tick`], and only
+//! reach guests running wasm code, not those waiting on the host.
+//!
+//! [`EngineBuilder::with_epoch_tick`]: super::EngineBuilder::with_epoch_tick
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::engine::ctx::Ctx;
+
+/// A live component instance, see [`InstanceRegistry::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceInfo {
+    /// Identifies the instance within its workload
+    pub instance_id: u64,
+    pub component_id: String,
+    /// How long ago the instance was created
+    pub age: Duration,
+    /// Linear memory and tables the instance allocated
+    pub memory_bytes: u64,
+    /// The export the instance runs, if it was told, e.g.
+    /// `wasi:http/incoming-handler#handle`
+    pub invocation: Option<String>,
+    /// Whether a core dump of the instance was requested
+    pub dump_pending: bool,
+}
+
+/// The error an instance is interrupted with to dump its core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpRequested;
+
+impl std::fmt::Display for DumpRequested {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("instance interrupted to dump its core")
+    }
+}
+
+impl std::error::Error for DumpRequested {}
+
+/// The live instances of a workload, see the [module docs](self).
+#[derive(Debug, Default)]
+pub struct InstanceRegistry {
+    next_id: AtomicU64,
+    instances: Mutex<BTreeMap<u64, Arc<LiveInstance>>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct LiveInstance {
+    id: u64,
+    component_id: Arc<str>,
+    created_at: Instant,
+    memory: Arc<AtomicUsize>,
+    invocation: Mutex<Option<String>>,
+    dump_requested: AtomicBool,
+    dump: Mutex<Option<oneshot::Sender<anyhow::Result<Vec<u8>>>>>,
+}
+
+impl LiveInstance {
+    /// Returns whether the instance should be interrupted to dump its core.
+    pub(crate) fn dump_requested(&self) -> bool {
+        self.dump_requested.load(Ordering::Relaxed)
+    }
+
+    fn take_dump(&self) -> Option<oneshot::Sender<anyhow::Result<Vec<u8>>>> {
+        self.dump
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+    }
+}
+
+/// Keeps an instance in its registry until the store holding it is dropped.
+#[derive(Debug)]
+pub(crate) struct InstanceGuard {
+    registry: Arc<InstanceRegistry>,
+    instance: Arc<LiveInstance>,
+}
+
+impl InstanceGuard {
+    pub(crate) fn instance(&self) -> &Arc<LiveInstance> {
+        &self.instance
+    }
+
+    /// Records the export the instance runs.
+    pub(crate) fn set_invocation(&self, export: &str) {
+        *self
+            .instance
+            .invocation
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(export.to_string());
+    }
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        self.registry
+            .instances
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&self.instance.id);
+        if let Some(dump) = self.instance.take_dump() {
+            let _ = dump.send(Err(anyhow::anyhow!(
+                "instance finished before it could be dumped"
+            )));
+        }
+    }
+}
+
+impl InstanceRegistry {
+    /// Registers an instance of `component_id` whose memory usage is counted
+    /// in `memory`.
+    pub(crate) fn register(
+        self: &Arc<Self>,
+        component_id: Arc<str>,
+        memory: Arc<AtomicUsize>,
+    ) -> InstanceGuard {
+        let instance = Arc::new(LiveInstance {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            component_id,
+            created_at: Instant::now(),
+            memory,
+            invocation: Mutex::default(),
+            dump_requested: AtomicBool::default(),
+            dump: Mutex::default(),
+        });
+        self.instances
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(instance.id, instance.clone());
+        InstanceGuard {
+            registry: self.clone(),
+            instance,
+        }
+    }
+
+    /// Returns the live instances, the oldest first.
+    pub fn list(&self) -> Vec<InstanceInfo> {
+        let instances = self
+            .instances
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        instances
+            .values()
+            .map(|instance| InstanceInfo {
+                instance_id: instance.id,
+                component_id: instance.component_id.to_string(),
+                age: instance.created_at.elapsed(),
+                memory_bytes: instance.memory.load(Ordering::Relaxed) as u64,
+                invocation: instance
+                    .invocation
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .clone(),
+                dump_pending: instance.dump_requested(),
+            })
+            .collect()
+    }
+
+    /// Interrupts an instance to dump its core, returning a receiver of the
+    /// dump, or `None` if there is no such instance.
+    ///
+    /// # Errors
+    /// Returns an error if a dump of the instance is already pending.
+    pub fn request_dump(
+        &self,
+        instance_id: u64,
+    ) -> anyhow::Result<Option<oneshot::Receiver<anyhow::Result<Vec<u8>>>>> {
+        let instances = self
+            .instances
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let Some(instance) = instances.get(&instance_id) else {
+            return Ok(None);
+        };
+        let mut dump = instance
+            .dump
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        anyhow::ensure!(
+            dump.is_none(),
+            "a dump of instance {instance_id} is already pending"
+        );
+        let (sender, receiver) = oneshot::channel();
+        *dump = Some(sender);
+        instance.dump_requested.store(true, Ordering::Relaxed);
+        Ok(Some(receiver))
+    }
+}
+
+/// Sends the core dump of an instance interrupted for it, once its
+/// invocation returned `result`.
+pub(crate) fn finish_dump<T>(store: &mut wasmtime::Store<Ctx>, result: &anyhow::Result<T>) {
+    let Some(instance) = store
+        .data()
+        .debug_instance()
+        .map(|guard| guard.instance().clone())
+    else {
+        return;
+    };
+    let Some(dump) = instance.take_dump() else {
+        return;
+    };
+    let core = match result {
+        Err(e) => e.downcast_ref::<wasmtime::WasmCoreDump>(),
+        Ok(_) => None,
+    };
+    let _ = dump.send(match core {
+        Some(core) => Ok(core.serialize(&mut *store, &instance.component_id)),
+        None => Err(anyhow::anyhow!(
+            "instance finished before it could be dumped"
+        )),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registry() {
+        let registry = Arc::new(InstanceRegistry::default());
+        let memory = Arc::new(AtomicUsize::new(65536));
+        let first = registry.register("a".into(), memory.clone());
+        first.set_invocation("wasi:http/incoming-handler#handle");
+        let second = registry.register("b".into(), memory);
+
+        let instances = registry.list();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].component_id, "a");
+        assert_eq!(instances[0].memory_bytes, 65536);
+        assert_eq!(
+            instances[0].invocation.as_deref(),
+            Some("wasi:http/incoming-handler#handle")
+        );
+        assert_eq!(instances[1].invocation, None);
+
+        assert!(registry.request_dump(42).unwrap().is_none());
+        let dump = registry
+            .request_dump(instances[1].instance_id)
+            .unwrap()
+            .unwrap();
+        assert!(second.instance().dump_requested());
+        assert!(registry.request_dump(instances[1].instance_id).is_err());
+        assert!(registry.list()[1].dump_pending);
+
+        // An instance dropped before it is dumped fails the dump
+        drop(second);
+        assert!(dump.await.unwrap().is_err());
+        drop(first);
+        assert!(registry.list().is_empty());
+    }
+}
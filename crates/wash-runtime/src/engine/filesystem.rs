@@ -0,0 +1,652 @@
+//! Quotas on what guests write through their volume mounts.
+//!
+//! When a volume mount of a workload sets [`VolumeMount::max_bytes_written`] or
+//! [`VolumeMount::max_files`], the `wasi:filesystem@0.2` interfaces of its
+//! components are linked to [`add_to_linker`] instead of the plain WASI
+//! implementation. It forwards every call to `wasmtime_wasi` and tracks which
+//! descriptors were opened under a limited mount, starting from the preopens,
+//! to count against its [`MountQuota`]:
+//!
+//! - the bytes written with `descriptor.write` and through the streams of
+//!   `descriptor.write-via-stream` and `descriptor.append-via-stream`
+//! - the files, directories and links created with `descriptor.open-at`,
+//!   `descriptor.create-directory-at`, `descriptor.symlink-at` and
+//!   `descriptor.link-at`, less those removed
+//!
+//! A call that would exceed a quota fails with [`ErrorCode::Quota`], or a
+//! stream error for streams, and the first time a quota of a mount is hit the
+//! workload emits a [`LifecycleEventType::VolumeQuotaExceeded`] event.
+//! Read-only mounts need none of this, `wasmtime_wasi` refuses writes to them
+//! on its own.
+//!
+//! [`VolumeMount::max_bytes_written`]: crate::types::VolumeMount::max_bytes_written
+//! [`VolumeMount::max_files`]: crate::types::VolumeMount::max_files
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use tracing::warn;
+use wasmtime::component::{HasData, Linker, Resource};
+use wasmtime_wasi::filesystem::{WasiFilesystemCtxView, WasiFilesystemView};
+use wasmtime_wasi::p2::FsResult;
+use wasmtime_wasi::p2::bindings::filesystem::{preopens, types};
+use wasmtime_wasi_io::poll::Pollable;
+use wasmtime_wasi_io::streams::{DynOutputStream, OutputStream, StreamError, StreamResult};
+
+use crate::engine::ctx::Ctx;
+use crate::engine::volume::{MountQuota, QuotaKind};
+use crate::host::events::{LifecycleEvent, LifecycleEventType, LifecycleEvents};
+
+use types::{
+    Advice, Descriptor, DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry,
+    DirectoryEntryStream, ErrorCode, Filesize, MetadataHashValue, NewTimestamp, OpenFlags,
+    PathFlags,
+};
+
+/// Links `wasi:filesystem/types` and `wasi:filesystem/preopens` in `linker`
+/// to the quota enforcing implementation, replacing the ones of
+/// `wasmtime_wasi`.
+pub(crate) fn add_to_linker(linker: &mut Linker<Ctx>) -> anyhow::Result<()> {
+    linker.allow_shadowing(true);
+    types::add_to_linker::<Ctx, QuotaFilesystem>(linker, QuotaFilesystem::view)
+        .context("failed to add wasi:filesystem/types to linker")?;
+    preopens::add_to_linker::<Ctx, QuotaFilesystem>(linker, QuotaFilesystem::view)
+        .context("failed to add wasi:filesystem/preopens to linker")?;
+    linker.allow_shadowing(false);
+    Ok(())
+}
+
+/// The quotas of the volume mounts of one instance, see the
+/// [module docs](self).
+pub struct FilesystemQuotas {
+    workload_id: Arc<str>,
+    workload_name: Arc<str>,
+    component_id: Arc<str>,
+    /// The quota of each limited mount by its path in the guest
+    mounts: HashMap<String, Arc<MountQuota>>,
+    /// The quota of each open descriptor under a limited mount, by its handle
+    descriptors: Mutex<HashMap<u32, Arc<MountQuota>>>,
+    events: LifecycleEvents,
+}
+
+impl FilesystemQuotas {
+    /// Creates the quotas of an instance of `component_id`, or `None` if none
+    /// of `mounts` is limited.
+    pub(crate) fn new(
+        workload_id: impl Into<Arc<str>>,
+        workload_name: impl Into<Arc<str>>,
+        component_id: impl Into<Arc<str>>,
+        mounts: impl IntoIterator<Item = Arc<MountQuota>>,
+        events: LifecycleEvents,
+    ) -> Option<Arc<Self>> {
+        let mounts: HashMap<_, _> = mounts
+            .into_iter()
+            .map(|quota| (quota.mount_path().to_string(), quota))
+            .collect();
+        if mounts.is_empty() {
+            return None;
+        }
+        Some(Arc::new(Self {
+            workload_id: workload_id.into(),
+            workload_name: workload_name.into(),
+            component_id: component_id.into(),
+            mounts,
+            descriptors: Mutex::default(),
+            events,
+        }))
+    }
+
+    fn descriptors(&self) -> std::sync::MutexGuard<'_, HashMap<u32, Arc<MountQuota>>> {
+        self.descriptors
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn quota(&self, descriptor: u32) -> Option<Arc<MountQuota>> {
+        self.descriptors().get(&descriptor).cloned()
+    }
+
+    fn track(&self, descriptor: u32, quota: Arc<MountQuota>) {
+        self.descriptors().insert(descriptor, quota);
+    }
+
+    fn untrack(&self, descriptor: u32) {
+        self.descriptors().remove(&descriptor);
+    }
+
+    /// Reports that a guest hit the limit of `kind` of `quota`, once per
+    /// mount and kind.
+    fn exceeded(&self, quota: &MountQuota, kind: QuotaKind) {
+        if !quota.exceeded(kind) {
+            return;
+        }
+        warn!(
+            workload_id = %self.workload_id,
+            component_id = %self.component_id,
+            mount_path = quota.mount_path(),
+            quota = kind.as_str(),
+            "component exceeded a quota of a volume mount"
+        );
+        self.events.emit(
+            LifecycleEvent::new(
+                LifecycleEventType::VolumeQuotaExceeded,
+                self.workload_id.as_ref(),
+                format!(
+                    "component {} exceeded the {} quota of the volume mounted at {}",
+                    self.component_id,
+                    kind.as_str(),
+                    quota.mount_path()
+                ),
+            )
+            .with_workload_name(self.workload_name.as_ref())
+            .with_component_id(self.component_id.as_ref()),
+        );
+    }
+
+    fn charge_bytes(&self, quota: &MountQuota, bytes: u64) -> bool {
+        let charged = quota.charge_bytes(bytes);
+        if !charged {
+            self.exceeded(quota, QuotaKind::BytesWritten);
+        }
+        charged
+    }
+
+    fn charge_file(&self, quota: &MountQuota) -> bool {
+        let charged = quota.charge_file();
+        if !charged {
+            self.exceeded(quota, QuotaKind::Files);
+        }
+        charged
+    }
+}
+
+struct QuotaFilesystem;
+
+impl HasData for QuotaFilesystem {
+    type Data<'a> = QuotaFilesystemView<'a>;
+}
+
+impl QuotaFilesystem {
+    fn view(ctx: &mut Ctx) -> QuotaFilesystemView<'_> {
+        let quotas = ctx.filesystem_quotas.clone();
+        QuotaFilesystemView {
+            fs: ctx.filesystem(),
+            quotas,
+        }
+    }
+}
+
+/// Forwards `wasi:filesystem` to `wasmtime_wasi`, counting against the
+/// quotas of the mounts.
+struct QuotaFilesystemView<'a> {
+    fs: WasiFilesystemCtxView<'a>,
+    quotas: Option<Arc<FilesystemQuotas>>,
+}
+
+impl QuotaFilesystemView<'_> {
+    /// Returns the quota of the mount `descriptor` was opened under, if it
+    /// is limited.
+    fn quota(
+        &self,
+        descriptor: &Resource<Descriptor>,
+    ) -> Option<(Arc<FilesystemQuotas>, Arc<MountQuota>)> {
+        let quotas = self.quotas.as_ref()?;
+        let quota = quotas.quota(descriptor.rep())?;
+        Some((quotas.clone(), quota))
+    }
+
+    /// Counts a file or directory created under `descriptor`, returning the
+    /// quota to give it back to if creating it fails.
+    fn charge_file(&self, descriptor: &Resource<Descriptor>) -> FsResult<Option<Arc<MountQuota>>> {
+        let Some((quotas, quota)) = self.quota(descriptor) else {
+            return Ok(None);
+        };
+        if !quotas.charge_file(&quota) {
+            return Err(ErrorCode::Quota.into());
+        }
+        Ok(Some(quota))
+    }
+
+    /// Wraps `stream` to count the bytes written through it against the
+    /// quota of `descriptor`.
+    fn limit_stream(
+        &mut self,
+        descriptor: u32,
+        stream: Resource<DynOutputStream>,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        let Some(quotas) = self.quotas.clone() else {
+            return Ok(stream);
+        };
+        let Some(quota) = quotas.quota(descriptor) else {
+            return Ok(stream);
+        };
+        let inner = self.fs.table.delete(stream)?;
+        let stream: DynOutputStream = Box::new(QuotaOutputStream {
+            inner,
+            quota,
+            quotas,
+        });
+        Ok(self.fs.table.push(stream)?)
+    }
+}
+
+/// Releases the file `quota` counted for an entry that failed to be created.
+fn refund<T>(result: &FsResult<T>, quota: Option<Arc<MountQuota>>) {
+    if let (Err(_), Some(quota)) = (result, quota) {
+        quota.release_file();
+    }
+}
+
+impl types::Host for QuotaFilesystemView<'_> {
+    fn convert_error_code(&mut self, err: wasmtime_wasi::p2::FsError) -> anyhow::Result<ErrorCode> {
+        types::Host::convert_error_code(&mut self.fs, err)
+    }
+
+    fn filesystem_error_code(
+        &mut self,
+        err: Resource<types::Error>,
+    ) -> anyhow::Result<Option<ErrorCode>> {
+        types::Host::filesystem_error_code(&mut self.fs, err)
+    }
+}
+
+impl types::HostDescriptor for QuotaFilesystemView<'_> {
+    async fn advise(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+        len: Filesize,
+        advice: Advice,
+    ) -> FsResult<()> {
+        types::HostDescriptor::advise(&mut self.fs, fd, offset, len, advice).await
+    }
+
+    async fn sync_data(&mut self, fd: Resource<Descriptor>) -> FsResult<()> {
+        types::HostDescriptor::sync_data(&mut self.fs, fd).await
+    }
+
+    async fn get_flags(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorFlags> {
+        types::HostDescriptor::get_flags(&mut self.fs, fd).await
+    }
+
+    async fn get_type(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorType> {
+        types::HostDescriptor::get_type(&mut self.fs, fd).await
+    }
+
+    async fn set_size(&mut self, fd: Resource<Descriptor>, size: Filesize) -> FsResult<()> {
+        types::HostDescriptor::set_size(&mut self.fs, fd, size).await
+    }
+
+    async fn set_times(
+        &mut self,
+        fd: Resource<Descriptor>,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> FsResult<()> {
+        types::HostDescriptor::set_times(&mut self.fs, fd, atim, mtim).await
+    }
+
+    async fn read(
+        &mut self,
+        fd: Resource<Descriptor>,
+        len: Filesize,
+        offset: Filesize,
+    ) -> FsResult<(Vec<u8>, bool)> {
+        types::HostDescriptor::read(&mut self.fs, fd, len, offset).await
+    }
+
+    async fn write(
+        &mut self,
+        fd: Resource<Descriptor>,
+        buf: Vec<u8>,
+        offset: Filesize,
+    ) -> FsResult<Filesize> {
+        if let Some((quotas, quota)) = self.quota(&fd)
+            && !quotas.charge_bytes(&quota, buf.len() as u64)
+        {
+            return Err(ErrorCode::Quota.into());
+        }
+        types::HostDescriptor::write(&mut self.fs, fd, buf, offset).await
+    }
+
+    async fn read_directory(
+        &mut self,
+        fd: Resource<Descriptor>,
+    ) -> FsResult<Resource<DirectoryEntryStream>> {
+        types::HostDescriptor::read_directory(&mut self.fs, fd).await
+    }
+
+    async fn sync(&mut self, fd: Resource<Descriptor>) -> FsResult<()> {
+        types::HostDescriptor::sync(&mut self.fs, fd).await
+    }
+
+    async fn create_directory_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        let charged = self.charge_file(&fd)?;
+        let result = types::HostDescriptor::create_directory_at(&mut self.fs, fd, path).await;
+        refund(&result, charged);
+        result
+    }
+
+    async fn stat(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorStat> {
+        types::HostDescriptor::stat(&mut self.fs, fd).await
+    }
+
+    async fn stat_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<DescriptorStat> {
+        types::HostDescriptor::stat_at(&mut self.fs, fd, path_flags, path).await
+    }
+
+    async fn set_times_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> FsResult<()> {
+        types::HostDescriptor::set_times_at(&mut self.fs, fd, path_flags, path, atim, mtim).await
+    }
+
+    async fn link_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        old_path_flags: PathFlags,
+        old_path: String,
+        new_descriptor: Resource<Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        let charged = self.charge_file(&new_descriptor)?;
+        let result = types::HostDescriptor::link_at(
+            &mut self.fs,
+            fd,
+            old_path_flags,
+            old_path,
+            new_descriptor,
+            new_path,
+        )
+        .await;
+        refund(&result, charged);
+        result
+    }
+
+    async fn open_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        oflags: OpenFlags,
+        flags: DescriptorFlags,
+    ) -> FsResult<Resource<Descriptor>> {
+        let quota = self.quota(&fd);
+        // Only a file that doesn't exist yet counts towards the quota
+        let charged = match &quota {
+            Some(_) if oflags.contains(OpenFlags::CREATE) => {
+                let existing = types::HostDescriptor::stat_at(
+                    &mut self.fs,
+                    Resource::new_borrow(fd.rep()),
+                    path_flags,
+                    path.clone(),
+                )
+                .await;
+                match existing {
+                    Ok(_) => None,
+                    Err(_) => self.charge_file(&fd)?,
+                }
+            }
+            _ => None,
+        };
+        let result =
+            types::HostDescriptor::open_at(&mut self.fs, fd, path_flags, path, oflags, flags).await;
+        refund(&result, charged);
+        if let (Ok(opened), Some((quotas, quota))) = (&result, quota) {
+            quotas.track(opened.rep(), quota);
+        }
+        result
+    }
+
+    async fn readlink_at(&mut self, fd: Resource<Descriptor>, path: String) -> FsResult<String> {
+        types::HostDescriptor::readlink_at(&mut self.fs, fd, path).await
+    }
+
+    async fn remove_directory_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        let quota = self.quota(&fd);
+        types::HostDescriptor::remove_directory_at(&mut self.fs, fd, path).await?;
+        if let Some((_, quota)) = quota {
+            quota.release_file();
+        }
+        Ok(())
+    }
+
+    async fn rename_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        old_path: String,
+        new_fd: Resource<Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        types::HostDescriptor::rename_at(&mut self.fs, fd, old_path, new_fd, new_path).await
+    }
+
+    async fn symlink_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        src_path: String,
+        dest_path: String,
+    ) -> FsResult<()> {
+        let charged = self.charge_file(&fd)?;
+        let result = types::HostDescriptor::symlink_at(&mut self.fs, fd, src_path, dest_path).await;
+        refund(&result, charged);
+        result
+    }
+
+    async fn unlink_file_at(&mut self, fd: Resource<Descriptor>, path: String) -> FsResult<()> {
+        let quota = self.quota(&fd);
+        types::HostDescriptor::unlink_file_at(&mut self.fs, fd, path).await?;
+        if let Some((_, quota)) = quota {
+            quota.release_file();
+        }
+        Ok(())
+    }
+
+    fn read_via_stream(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<types::InputStream>> {
+        types::HostDescriptor::read_via_stream(&mut self.fs, fd, offset)
+    }
+
+    fn write_via_stream(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<types::OutputStream>> {
+        let descriptor = fd.rep();
+        let stream = types::HostDescriptor::write_via_stream(&mut self.fs, fd, offset)?;
+        self.limit_stream(descriptor, stream)
+    }
+
+    fn append_via_stream(
+        &mut self,
+        fd: Resource<Descriptor>,
+    ) -> FsResult<Resource<types::OutputStream>> {
+        let descriptor = fd.rep();
+        let stream = types::HostDescriptor::append_via_stream(&mut self.fs, fd)?;
+        self.limit_stream(descriptor, stream)
+    }
+
+    async fn is_same_object(
+        &mut self,
+        a: Resource<Descriptor>,
+        b: Resource<Descriptor>,
+    ) -> anyhow::Result<bool> {
+        types::HostDescriptor::is_same_object(&mut self.fs, a, b).await
+    }
+
+    async fn metadata_hash(&mut self, fd: Resource<Descriptor>) -> FsResult<MetadataHashValue> {
+        types::HostDescriptor::metadata_hash(&mut self.fs, fd).await
+    }
+
+    async fn metadata_hash_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<MetadataHashValue> {
+        types::HostDescriptor::metadata_hash_at(&mut self.fs, fd, path_flags, path).await
+    }
+
+    fn drop(&mut self, fd: Resource<Descriptor>) -> anyhow::Result<()> {
+        if let Some(quotas) = &self.quotas {
+            quotas.untrack(fd.rep());
+        }
+        types::HostDescriptor::drop(&mut self.fs, fd)
+    }
+}
+
+impl types::HostDirectoryEntryStream for QuotaFilesystemView<'_> {
+    async fn read_directory_entry(
+        &mut self,
+        stream: Resource<DirectoryEntryStream>,
+    ) -> FsResult<Option<DirectoryEntry>> {
+        types::HostDirectoryEntryStream::read_directory_entry(&mut self.fs, stream).await
+    }
+
+    fn drop(&mut self, stream: Resource<DirectoryEntryStream>) -> anyhow::Result<()> {
+        types::HostDirectoryEntryStream::drop(&mut self.fs, stream)
+    }
+}
+
+impl preopens::Host for QuotaFilesystemView<'_> {
+    fn get_directories(&mut self) -> anyhow::Result<Vec<(Resource<Descriptor>, String)>> {
+        let directories = preopens::Host::get_directories(&mut self.fs)?;
+        if let Some(quotas) = &self.quotas {
+            for (descriptor, path) in &directories {
+                if let Some(quota) = quotas.mounts.get(path) {
+                    quotas.track(descriptor.rep(), quota.clone());
+                }
+            }
+        }
+        Ok(directories)
+    }
+}
+
+/// An output stream of a file under a limited mount, counting the bytes
+/// written through it.
+struct QuotaOutputStream {
+    inner: DynOutputStream,
+    quota: Arc<MountQuota>,
+    quotas: Arc<FilesystemQuotas>,
+}
+
+impl QuotaOutputStream {
+    fn exceeded(&self) -> StreamError {
+        StreamError::LastOperationFailed(anyhow::anyhow!(
+            "quota of bytes written to the volume mounted at {} exceeded",
+            self.quota.mount_path()
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for QuotaOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for QuotaOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        if !self.quotas.charge_bytes(&self.quota, bytes.len() as u64) {
+            return Err(self.exceeded());
+        }
+        self.inner.write(bytes)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        let permitted = self.inner.check_write()?;
+        match self.quota.remaining_bytes() {
+            // Permitting nothing would leave the guest waiting for good
+            Some(0) => {
+                self.quotas.exceeded(&self.quota, QuotaKind::BytesWritten);
+                Err(self.exceeded())
+            }
+            Some(remaining) => Ok(permitted.min(usize::try_from(remaining).unwrap_or(usize::MAX))),
+            None => Ok(permitted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::volume::VolumeRoot;
+    use crate::types::VolumeMount;
+
+    fn quota(dir: &std::path::Path, max_bytes_written: u64) -> Arc<MountQuota> {
+        let root = VolumeRoot::new(dir).unwrap();
+        MountQuota::new(
+            &root,
+            &VolumeMount {
+                name: "data".to_string(),
+                mount_path: "/data".to_string(),
+                max_bytes_written: Some(max_bytes_written),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_quota_event_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let quota = quota(dir.path(), 4);
+        let events = LifecycleEvents::new("host", Vec::new());
+        let mut rx = events.subscribe();
+        let quotas =
+            FilesystemQuotas::new("workload", "name", "component", [quota.clone()], events)
+                .unwrap();
+
+        assert!(quotas.charge_bytes(&quota, 4));
+        assert!(!quotas.charge_bytes(&quota, 1));
+        assert!(!quotas.charge_bytes(&quota, 1));
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.event_type, LifecycleEventType::VolumeQuotaExceeded);
+        assert_eq!(event.component_id.as_deref(), Some("component"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_no_limited_mounts() {
+        assert!(
+            FilesystemQuotas::new(
+                "workload",
+                "name",
+                "component",
+                Vec::new(),
+                LifecycleEvents::default()
+            )
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Linear memory limits, growth tracking and leak detection.
+//!
+//! Every store records the total size of its linear memories as they grow.
+//! The `memory_limit_mb` of a component, capped by the host's
+//! [`EngineBuilder::with_max_memory_bytes`](super::EngineBuilder::with_max_memory_bytes), bounds that total, whatever the
+//! number of memories of the component. An instance growing beyond it traps
+//! with [`MemoryLimitExceeded`] instead of seeing the growth fail, so a guest
+//! can't keep running on an allocator that ran out of memory: its invocation
+//! fails, answered with `500 Internal Server Error` over HTTP, and its
+//! instance is discarded, or replaced with a fresh one for a service. The
+//! workload emits a
+//! [`LifecycleEventType::MemoryLimitExceeded`](crate::host::events::LifecycleEventType::MemoryLimitExceeded)
+//! event and counts the trap in `wasmcloud.workload.memory_limit_exceeded`.
+//!
+//! Linear memory never shrinks, so an instance that keeps allocating without
+//! freeing grows for as long as it lives. Components invoked on demand get a
+//! fresh instance per invocation and cannot accumulate a leak, but a workload's
+//! service keeps its instance for the lifetime of the workload.
+//!
+//! When the engine has a [`MemoryLeakPolicy`], the memory of each service
+//! instance is sampled periodically. A leak is suspected once the memory grew
+//! in every one of the last [`MemoryLeakPolicy::window`] samples and by more
+//! than [`MemoryLeakPolicy::threshold_bytes`] since the instance started. A
+//! single large allocation at startup or under a burst of load grows memory
+//! once and is not flagged.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use wasmtime::{ResourceLimiter, StoreLimits};
+
+/// Growth since startup above which a steadily growing instance is flagged
+pub const DEFAULT_LEAK_THRESHOLD_BYTES: usize = 64 * 1024 * 1024;
+/// Consecutive growing samples needed to flag an instance
+pub const DEFAULT_LEAK_WINDOW: usize = 6;
+/// How often the memory of long-lived instances is sampled
+pub const DEFAULT_LEAK_SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When a long-lived instance is suspected of leaking memory and what to do
+/// about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLeakPolicy {
+    /// Growth since the instance started, in bytes, before it can be flagged
+    pub threshold_bytes: usize,
+    /// Number of consecutive samples the memory must have grown in
+    pub window: usize,
+    /// How often memory is sampled
+    pub sample_interval: Duration,
+    /// Whether a flagged instance is replaced with a fresh one
+    pub recycle: bool,
+}
+
+impl Default for MemoryLeakPolicy {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: DEFAULT_LEAK_THRESHOLD_BYTES,
+            window: DEFAULT_LEAK_WINDOW,
+            sample_interval: DEFAULT_LEAK_SAMPLE_INTERVAL,
+            recycle: false,
+        }
+    }
+}
+
+/// The error an instance traps with when its linear memories would grow
+/// beyond its memory limit, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimitExceeded {
+    /// The limit of the instance, in bytes
+    pub limit: usize,
+    /// The total size its memories would have grown to, in bytes
+    pub desired: usize,
+}
+
+impl std::fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "instance exceeded its memory limit of {} bytes, growing its memory to {} bytes",
+            self.limit, self.desired
+        )
+    }
+}
+
+impl std::error::Error for MemoryLimitExceeded {}
+
+/// Returns whether `err` is an instance trapped for exceeding its memory
+/// limit.
+pub fn is_memory_limit_exceeded(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<MemoryLimitExceeded>())
+}
+
+/// Enforces the [`StoreLimits`] of a store and the limit on the total size of
+/// its linear memories, which it records.
+pub(crate) struct MemoryLimiter {
+    limits: StoreLimits,
+    /// The limit on the total size of the linear memories, in bytes
+    limit: Option<usize>,
+    usage: Arc<AtomicUsize>,
+}
+
+impl MemoryLimiter {
+    pub(crate) fn new(limits: StoreLimits, limit: Option<usize>) -> Self {
+        Self {
+            limits,
+            limit,
+            usage: Arc::default(),
+        }
+    }
+
+    /// Returns a handle to the current total size of the store's linear
+    /// memories, in bytes, that stays valid after the store is moved.
+    pub(crate) fn usage(&self) -> Arc<AtomicUsize> {
+        self.usage.clone()
+    }
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        // Allocating a memory on instantiation is reported as growing it from zero
+        let growth = desired.saturating_sub(current);
+        if let Some(limit) = self.limit {
+            let desired = self.usage.load(Ordering::Relaxed).saturating_add(growth);
+            if desired > limit {
+                return Err(MemoryLimitExceeded { limit, desired }.into());
+            }
+        }
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if allowed {
+            self.usage.fetch_add(growth, Ordering::Relaxed);
+        }
+        Ok(allowed)
+    }
+
+    fn memory_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+/// Detects steady memory growth from periodic samples of one instance.
+#[derive(Debug)]
+pub(crate) struct GrowthTracker {
+    policy: MemoryLeakPolicy,
+    baseline: Option<usize>,
+    /// The last `window + 1` samples, oldest first
+    samples: VecDeque<usize>,
+}
+
+impl GrowthTracker {
+    pub(crate) fn new(policy: MemoryLeakPolicy) -> Self {
+        Self {
+            policy,
+            baseline: None,
+            samples: VecDeque::with_capacity(policy.window + 1),
+        }
+    }
+
+    /// Records a sample of the instance's memory, in bytes. Returns the growth
+    /// since the first sample if a leak is suspected.
+    pub(crate) fn observe(&mut self, bytes: usize) -> Option<usize> {
+        let baseline = *self.baseline.get_or_insert(bytes);
+        if self.samples.len() > self.policy.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(bytes);
+
+        let growing = self.policy.window > 0
+            && self.samples.len() > self.policy.window
+            && self
+                .samples
+                .iter()
+                .zip(self.samples.iter().skip(1))
+                .all(|(before, after)| after > before);
+        let growth = bytes.saturating_sub(baseline);
+        (growing && growth > self.policy.threshold_bytes).then_some(growth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(threshold_bytes: usize, window: usize) -> GrowthTracker {
+        GrowthTracker::new(MemoryLeakPolicy {
+            threshold_bytes,
+            window,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_steady_growth_is_flagged() {
+        let mut tracker = tracker(250, 3);
+        assert_eq!(tracker.observe(1000), None);
+        assert_eq!(tracker.observe(1100), None);
+        assert_eq!(tracker.observe(1200), None);
+        // Grown in each of the last 3 samples and by more than 250 bytes
+        assert_eq!(tracker.observe(1300), Some(300));
+        assert_eq!(tracker.observe(1400), Some(400));
+    }
+
+    #[test]
+    fn test_plateau_is_not_flagged() {
+        let mut tracker = tracker(100, 3);
+        for bytes in [1000, 5000, 5000, 5000, 5000] {
+            assert_eq!(tracker.observe(bytes), None);
+        }
+        // Growth resumes, but the window still holds flat samples
+        assert_eq!(tracker.observe(6000), None);
+        assert_eq!(tracker.observe(7000), None);
+        assert_eq!(tracker.observe(8000), Some(7000));
+    }
+
+    #[test]
+    fn test_small_growth_is_not_flagged() {
+        let mut tracker = tracker(1000, 2);
+        for bytes in [1000, 1100, 1200, 1300, 1400] {
+            assert_eq!(tracker.observe(bytes), None);
+        }
+    }
+
+    #[test]
+    fn test_limiter_records_growth() {
+        let mut limiter =
+            MemoryLimiter::new(wasmtime::StoreLimitsBuilder::new().build(), Some(3 * 65536));
+        let usage = limiter.usage();
+        assert!(limiter.memory_growing(0, 65536, None).unwrap());
+        assert!(limiter.memory_growing(65536, 2 * 65536, None).unwrap());
+        assert_eq!(usage.load(Ordering::Relaxed), 2 * 65536);
+
+        // The limit bounds all memories together, and growth beyond it traps
+        // without being recorded
+        let err = limiter.memory_growing(0, 2 * 65536, None).unwrap_err();
+        assert!(is_memory_limit_exceeded(&err));
+        assert_eq!(
+            err.downcast_ref::<MemoryLimitExceeded>(),
+            Some(&MemoryLimitExceeded {
+                limit: 3 * 65536,
+                desired: 4 * 65536
+            })
+        );
+        assert_eq!(usage.load(Ordering::Relaxed), 2 * 65536);
+        assert!(limiter.memory_growing(0, 65536, None).unwrap());
+    }
+}
@@ -31,6 +31,7 @@
 //! #   components: vec![],
 //! #   host_interfaces: vec![],
 //! #   volumes: vec![],
+//! #   outbound: Default::default(),
 //! };
 //!
 //! let unresolved = engine.initialize_workload("workload-1", workload)?;
@@ -41,17 +42,105 @@
 
 use anyhow::{Context, bail};
 use wasmtime::PoolingAllocationConfig;
+use wasmtime::component::types::ComponentItem;
 use wasmtime::component::{Component, Linker};
 
+use crate::engine::autoscale::InstancePool;
+use crate::engine::cgroup::Cgroups;
+use crate::engine::cpu::CpuThrottle;
+use crate::engine::crash_loop::CrashLoopPolicy;
 use crate::engine::ctx::Ctx;
-use crate::engine::workload::{UnresolvedWorkload, WorkloadComponent, WorkloadService};
-use crate::types::{EmptyDirVolume, HostPathVolume, VolumeType, Workload};
+use crate::engine::memory::MemoryLeakPolicy;
+use crate::engine::pool::{ExecutionPool, ExecutionPoolConfig};
+use crate::engine::preinit::PreInitPool;
+use crate::engine::volume::{VolumeRoot, validate_mount_path};
+use crate::engine::workload::{
+    ConfigChangePolicy, UnresolvedWorkload, WorkloadComponent, WorkloadMetadata, WorkloadService,
+};
+use crate::host::http::OutboundThrottle;
+use crate::host::metering::Metering;
+use crate::types::{
+    ComponentKind, DEFAULT_INIT_EXPORT, EmptyDirVolume, HostPathVolume, LocalResources,
+    StorageMedium, VolumeType, Workload,
+};
+use crate::wit::{WitInterface, WitWorld};
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+pub mod autoscale;
+pub mod cache;
+pub mod cgroup;
+pub mod cpu;
+pub mod crash_loop;
 pub mod ctx;
+#[cfg(feature = "instance-debug")]
+pub mod debug;
+pub mod filesystem;
+pub mod memory;
+pub mod output;
+pub mod pool;
+pub mod preinit;
+pub mod provenance;
 mod value;
+pub mod volume;
 pub mod workload;
 
+/// Key in a component's local resources config declaring the wasm stack, in
+/// bytes, it needs
+pub const WASM_STACK_CONFIG_KEY: &str = "wasm_stack_bytes";
+/// Key in a component's local resources config naming the execution pool its
+/// guests run on, see [`pool`]
+pub const EXECUTION_POOL_CONFIG_KEY: &str = "execution_pool";
+/// Key in a component's local resources config limiting how long, in
+/// milliseconds, an invocation of the component may run, see
+/// [`EngineBuilder::with_invocation_timeout`]
+pub const INVOCATION_TIMEOUT_CONFIG_KEY: &str = "invocation_timeout_ms";
+/// Key in a service's local resources config deciding what happens to its
+/// running instance when its configuration changes, see
+/// [`workload::ConfigChangePolicy`]
+pub const CONFIG_CHANGE_CONFIG_KEY: &str = "config_change";
+/// How often the epoch advances when it is only enabled to interrupt guests,
+/// for invocation timeouts or CPU limits
+const DEFAULT_EPOCH_TICK: Duration = Duration::from_millis(10);
+/// Wasmtime's default maximum wasm stack
+const DEFAULT_MAX_WASM_STACK: usize = 512 * 1024;
+/// Stack reserved for host frames on top of the wasm stack of an async call,
+/// matching wasmtime's defaults
+const ASYNC_STACK_HEADROOM: usize = 1536 * 1024;
+
+/// Adds an explanation to errors caused by a guest exhausting its wasm stack,
+/// which are otherwise reported as a bare trap.
+pub fn explain_trap(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<wasmtime::Trap>() {
+        Some(wasmtime::Trap::StackOverflow) => err.context(
+            "component exhausted its wasm stack, likely through deep or unbounded recursion; \
+             the limit is set with EngineBuilder::with_max_wasm_stack",
+        ),
+        _ => err,
+    }
+}
+
+/// The error an invocation fails with once it ran longer than the invocation
+/// timeout of its component, see [`EngineBuilder::with_invocation_timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvocationTimeout(pub Duration);
+
+impl std::fmt::Display for InvocationTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invocation interrupted after its {:?} timeout", self.0)
+    }
+}
+
+impl std::error::Error for InvocationTimeout {}
+
+/// Returns whether `err` is an invocation interrupted by its timeout.
+pub fn is_invocation_timeout(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<InvocationTimeout>())
+}
+
 /// The core WebAssembly engine for executing components and workloads.
 ///
 /// The `Engine` is responsible for compiling WebAssembly components, managing
@@ -61,6 +150,61 @@ pub mod workload;
 pub struct Engine {
     // wasmtime engine
     pub(crate) inner: wasmtime::Engine,
+    /// Epoch ticks a guest may run before yielding, when epoch interruption is enabled
+    epoch_yield_ticks: Option<u64>,
+    /// How long an invocation may run unless its component sets its own timeout
+    invocation_timeout: Option<Duration>,
+    /// Fuel a CPU burns through per second, when `cpu_limit` is enforced with fuel
+    cpu_fuel_rate: Option<u64>,
+    /// Largest linear memory a component may grow to
+    max_memory_bytes: Option<usize>,
+    /// Whether components targeting WASI 0.3 and the async ABI are accepted
+    wasip3: bool,
+    /// Largest stack, in bytes, wasm code may use
+    max_wasm_stack: usize,
+    /// When long-lived instances are flagged for leaking memory
+    memory_leak_policy: Option<MemoryLeakPolicy>,
+    /// When failing components back off and their workloads are stopped
+    crash_loop_policy: Option<CrashLoopPolicy>,
+    /// Dedicated threads components can run their guests on, by name
+    execution_pools: HashMap<String, Arc<ExecutionPool>>,
+    /// Where the kernel limits the CPU of components and the memory of the host
+    cgroups: Option<Cgroups>,
+    /// Meters the usage of invocations, if enabled
+    metering: Option<Metering>,
+    compiled: CompiledComponents,
+    /// Keeps compiled components across host restarts, if set
+    cache: Option<cache::EngineCache>,
+}
+
+/// Components compiled by an [`Engine`], by the digest of their bytes, so
+/// identical components compile once and can be saved in host snapshots.
+#[derive(Clone, Default)]
+struct CompiledComponents(Arc<Mutex<HashMap<String, Component>>>);
+
+impl CompiledComponents {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Component>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for CompiledComponents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompiledComponents")
+            .field("len", &self.lock().len())
+            .finish()
+    }
+}
+
+/// Returns the hex SHA-256 digest identifying the compiled form of component
+/// `bytes`.
+pub fn component_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
 }
 
 impl Engine {
@@ -82,6 +226,280 @@ impl Engine {
         &self.inner
     }
 
+    /// Returns the number of epoch ticks a guest runs before yielding to the async
+    /// executor, or `None` if epoch interruption is disabled.
+    pub fn epoch_yield_ticks(&self) -> Option<u64> {
+        self.epoch_yield_ticks
+    }
+
+    /// Returns how long an invocation may run unless its component sets its
+    /// own timeout, if invocations are limited by default.
+    pub fn invocation_timeout(&self) -> Option<Duration> {
+        self.invocation_timeout
+    }
+
+    /// Returns the fuel a CPU burns through per second, if the `cpu_limit` of
+    /// components is enforced with fuel.
+    pub fn cpu_fuel_rate(&self) -> Option<u64> {
+        self.cpu_fuel_rate
+    }
+
+    /// Returns the largest linear memory a component may grow to, if limited.
+    pub fn max_memory_bytes(&self) -> Option<usize> {
+        self.max_memory_bytes
+    }
+
+    /// Returns the largest stack, in bytes, wasm code may use before trapping.
+    pub fn max_wasm_stack(&self) -> usize {
+        self.max_wasm_stack
+    }
+
+    /// Returns the policy for flagging long-lived instances that leak memory,
+    /// if leak detection is enabled.
+    pub fn memory_leak_policy(&self) -> Option<MemoryLeakPolicy> {
+        self.memory_leak_policy
+    }
+
+    /// Returns the policy for backing off components that keep failing, if
+    /// crash-loop detection is enabled.
+    pub fn crash_loop_policy(&self) -> Option<CrashLoopPolicy> {
+        self.crash_loop_policy
+    }
+
+    /// Returns the execution pool named `name`, if the engine has one.
+    pub fn execution_pool(&self, name: &str) -> Option<&Arc<ExecutionPool>> {
+        self.execution_pools.get(name)
+    }
+
+    /// Returns the execution pools of the engine.
+    pub fn execution_pools(&self) -> impl Iterator<Item = &Arc<ExecutionPool>> {
+        self.execution_pools.values()
+    }
+
+    /// Returns the cgroups the kernel limits components with, if any.
+    pub fn cgroups(&self) -> Option<&Cgroups> {
+        self.cgroups.as_ref()
+    }
+
+    /// Returns how invocations are metered, if they are.
+    pub fn metering(&self) -> Option<&Metering> {
+        self.metering.as_ref()
+    }
+
+    /// Returns an execution pool in a cgroup limited to the `cpu_limit` of
+    /// a component, if the engine has cgroups and the component a CPU limit.
+    fn cgroup_pool(
+        &self,
+        workload_id: &str,
+        metadata: &WorkloadMetadata,
+    ) -> anyhow::Result<Option<Arc<ExecutionPool>>> {
+        let Some(cgroups) = &self.cgroups else {
+            return Ok(None);
+        };
+        let Ok(cpu_limit @ 1..) = u32::try_from(metadata.local_resources().cpu_limit) else {
+            return Ok(None);
+        };
+        let cgroup = cgroups.component(workload_id, metadata.id(), cpu_limit)?;
+        let pool = ExecutionPool::in_cgroup(
+            &format!("{workload_id}/{}", metadata.id()),
+            cpu_limit.div_ceil(1000) as usize,
+            cgroup,
+        )?;
+        Ok(Some(Arc::new(pool)))
+    }
+
+    /// Returns the execution pool a component's `execution_pool` setting
+    /// assigns it to, if any.
+    fn execution_pool_for(
+        &self,
+        local_resources: &LocalResources,
+    ) -> anyhow::Result<Option<Arc<ExecutionPool>>> {
+        let Some(name) = local_resources.config.get(EXECUTION_POOL_CONFIG_KEY) else {
+            return Ok(None);
+        };
+        match self.execution_pools.get(name) {
+            Some(pool) => Ok(Some(pool.clone())),
+            None => bail!(
+                "component runs on execution pool '{name}', which the host does not have, \
+                 add it with EngineBuilder::with_execution_pool"
+            ),
+        }
+    }
+
+    /// Returns the throttle limiting a component to its `cpu_limit` with fuel,
+    /// unless the kernel limits it with cgroups or the engine doesn't enforce
+    /// CPU limits.
+    fn cpu_throttle_for(&self, local_resources: &LocalResources) -> Option<Arc<CpuThrottle>> {
+        let rate = self.cpu_fuel_rate?;
+        if self.cgroups.is_some() {
+            return None;
+        }
+        let millicores @ 1.. = u32::try_from(local_resources.cpu_limit).ok()? else {
+            return None;
+        };
+        Some(Arc::new(CpuThrottle::new(millicores, rate)))
+    }
+
+    /// Returns how long an invocation of a component may run: its
+    /// `invocation_timeout_ms` setting, or the default timeout of the engine
+    /// unless it is a `job`, which may run as long as it needs.
+    fn invocation_timeout_for(
+        &self,
+        local_resources: &LocalResources,
+        job: bool,
+    ) -> anyhow::Result<Option<Duration>> {
+        let timeout = match local_resources.config.get(INVOCATION_TIMEOUT_CONFIG_KEY) {
+            Some(ms) => {
+                let ms: u64 = ms
+                    .parse()
+                    .with_context(|| format!("invalid {INVOCATION_TIMEOUT_CONFIG_KEY} '{ms}'"))?;
+                if ms == 0 {
+                    bail!("{INVOCATION_TIMEOUT_CONFIG_KEY} must be greater than zero");
+                }
+                Duration::from_millis(ms)
+            }
+            None if job => return Ok(None),
+            None => return Ok(self.invocation_timeout),
+        };
+        if self.epoch_yield_ticks.is_none() {
+            bail!(
+                "component sets an invocation timeout but the host does not interrupt guests, \
+                 enable it with EngineBuilder::with_invocation_timeout or EngineBuilder::with_epoch_tick"
+            );
+        }
+        Ok(Some(timeout))
+    }
+
+    /// Checks that the host's wasm stack satisfies a component's
+    /// `wasm_stack_bytes` setting, the stack it needs to run.
+    fn check_wasm_stack(&self, local_resources: &LocalResources) -> anyhow::Result<()> {
+        let Some(requested) = local_resources.config.get(WASM_STACK_CONFIG_KEY) else {
+            return Ok(());
+        };
+        let requested: usize = requested
+            .parse()
+            .with_context(|| format!("invalid {WASM_STACK_CONFIG_KEY} '{requested}'"))?;
+        if requested > self.max_wasm_stack {
+            bail!(
+                "component requires a {requested} byte wasm stack but the host allows {} bytes, \
+                 raise it with EngineBuilder::with_max_wasm_stack",
+                self.max_wasm_stack
+            );
+        }
+        Ok(())
+    }
+
+    /// Compiles component bytes, reusing the compiled component of identical
+    /// bytes, in memory or in the component cache.
+    fn compile(&self, bytes: &[u8]) -> anyhow::Result<Component> {
+        let digest = component_digest(bytes);
+        if let Some(component) = self.compiled.lock().get(&digest) {
+            return Ok(component.clone());
+        }
+        if let Some(cache) = &self.cache
+            && let Some(artifact) = cache.artifact(&digest)
+        {
+            // SAFETY: the cache directory is only writable by the host, see
+            // the docs of `cache`
+            match unsafe { Component::deserialize(&self.inner, &artifact) } {
+                Ok(component) => {
+                    tracing::debug!(digest, "loaded compiled component from the cache");
+                    self.compiled.lock().insert(digest, component.clone());
+                    return Ok(component);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        digest,
+                        "failed to load cached component, compiling it: {e:#}"
+                    );
+                    cache.remove(&digest);
+                }
+            }
+        }
+        let component = Component::new(&self.inner, bytes)?;
+        if let Some(cache) = &self.cache {
+            match component.serialize() {
+                Ok(artifact) => cache.store(&digest, bytes, &artifact),
+                Err(e) => tracing::warn!(digest, "failed to serialize compiled component: {e:#}"),
+            }
+        }
+        self.compiled.lock().insert(digest, component.clone());
+        Ok(component)
+    }
+
+    /// Returns the bytes of the component with the given digest from the
+    /// component cache, see [`EngineBuilder::with_component_cache`].
+    pub fn cached_bytes(&self, digest: &str) -> Option<Vec<u8>> {
+        self.cache.as_ref()?.bytes(digest)
+    }
+
+    /// Compiles component bytes ahead of the workloads using them, so starting
+    /// them doesn't wait for compilation, and returns their digest, see
+    /// [`component_digest`]. The compiled component is reused until the host
+    /// prunes it.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid component or the engine
+    /// can't run it, e.g. as it targets WASI 0.3 without
+    /// [`EngineBuilder::with_wasip3`].
+    pub fn precompile(&self, bytes: &[u8]) -> anyhow::Result<String> {
+        let component = self
+            .compile(bytes)
+            .context("failed to create component from bytes")?;
+        if targets_wasip3(&component) {
+            self.add_wasip3_to_linker(&component, &mut Linker::new(&self.inner))?;
+        }
+        Ok(component_digest(bytes))
+    }
+
+    /// Returns the serialized compiled component with the given digest, see
+    /// [`component_digest`], if the engine compiled it.
+    pub fn compiled_artifact(&self, digest: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(component) = self.compiled.lock().get(digest).cloned() else {
+            return Ok(None);
+        };
+        component
+            .serialize()
+            .map(Some)
+            .context("failed to serialize compiled component")
+    }
+
+    /// Loads a compiled component returned by [`Engine::compiled_artifact`],
+    /// so components with the given digest aren't compiled again.
+    ///
+    /// # Safety
+    /// `artifact` must have been serialized by wasmtime and not be tampered
+    /// with, it is loaded as native code. Artifacts of other wasmtime versions
+    /// or engine settings are rejected with an error.
+    pub unsafe fn load_compiled_artifact(
+        &self,
+        digest: &str,
+        artifact: &[u8],
+    ) -> anyhow::Result<()> {
+        // SAFETY: upheld by the caller
+        let component = unsafe { Component::deserialize(&self.inner, artifact) }
+            .context("failed to load compiled component")?;
+        self.compiled.lock().insert(digest.to_string(), component);
+        Ok(())
+    }
+
+    /// Drops compiled components whose digest isn't in `keep`.
+    pub(crate) fn retain_compiled(&self, keep: &HashSet<String>) {
+        self.compiled
+            .lock()
+            .retain(|digest, _| keep.contains(digest));
+    }
+
+    /// Compiles the given component bytes and returns the [`WitWorld`] it imports and exports.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid component.
+    pub fn component_world(&self, bytes: impl AsRef<[u8]>) -> anyhow::Result<WitWorld> {
+        let component = Component::new(&self.inner, bytes.as_ref())
+            .context("failed to create component from bytes")?;
+        Ok(component_world(&component))
+    }
+
     /// Initializes a workload by validating and preparing all its components.
     ///
     /// This function takes a workload definition and prepares it for execution by:
@@ -114,14 +532,24 @@ impl Engine {
             service,
             volumes,
             host_interfaces,
+            outbound,
             ..
         } = workload;
+        let throttle = OutboundThrottle::new(&outbound);
+        // Instances preopen the mounts of every component, so one limited mount
+        // puts all of them behind the quota enforcing filesystem
+        let filesystem_quotas = components
+            .iter()
+            .map(|c| &c.local_resources)
+            .chain(service.as_ref().map(|s| &s.local_resources))
+            .flat_map(|resources| &resources.volume_mounts)
+            .any(|vm| vm.max_bytes_written.is_some() || vm.max_files.is_some());
 
         // Process and validate volumes - create a lookup map from volume name to validated host path
         let mut validated_volumes = std::collections::HashMap::new();
 
         for v in volumes {
-            let host_path = match v.volume_type {
+            let root = match v.volume_type {
                 VolumeType::HostPath(HostPathVolume { local_path }) => {
                     let path = PathBuf::from(&local_path);
                     if !path.is_dir() {
@@ -129,27 +557,50 @@ impl Engine {
                             "HostPath volume '{local_path}' does not exist or is not a directory",
                         );
                     }
-                    path
+                    VolumeRoot::new(&path)?
                 }
-                VolumeType::EmptyDir(EmptyDirVolume {}) => {
+                VolumeType::EmptyDir(EmptyDirVolume {
+                    medium,
+                    size_limit_bytes,
+                }) => {
                     // Create a temporary directory for the empty dir volume
-                    let temp_dir = tempfile::tempdir()
-                        .context("failed to create temp dir for empty dir volume")?;
-                    tracing::debug!(path = ?temp_dir.path(), "created temp dir for empty dir volume");
-                    temp_dir.keep()
+                    let shm = std::path::Path::new("/dev/shm");
+                    let temp_dir = match medium {
+                        StorageMedium::Memory if shm.is_dir() => tempfile::tempdir_in(shm),
+                        _ => tempfile::tempdir(),
+                    }
+                    .context("failed to create temp dir for empty dir volume")?;
+                    tracing::debug!(path = ?temp_dir.path(), ?medium, "created temp dir for empty dir volume");
+                    VolumeRoot::new(temp_dir.keep())?.with_size_limit(size_limit_bytes)
+                }
+                VolumeType::Named(_)
+                | VolumeType::Oci(_)
+                | VolumeType::Nfs(_)
+                | VolumeType::S3(_) => {
+                    anyhow::bail!(
+                        "volume '{}' must be resolved by the host before the workload is initialized",
+                        v.name
+                    );
                 }
             };
 
             // Store the validated volume for later lookup
-            validated_volumes.insert(v.name.clone(), host_path);
+            validated_volumes.insert(v.name.clone(), root);
         }
 
         // Iniitalize service
         let service = if let Some(svc) = service {
-            match self.initialize_service(id.as_ref(), &name, &namespace, svc, &validated_volumes) {
+            match self.initialize_service(
+                id.as_ref(),
+                &name,
+                &namespace,
+                svc,
+                &validated_volumes,
+                filesystem_quotas,
+            ) {
                 Ok(handle) => {
                     tracing::debug!("successfully initialized service component");
-                    Some(handle)
+                    Some(handle.with_outbound_throttle(throttle.clone()))
                 }
                 Err(e) => {
                     tracing::error!(err = ?e, "failed to initialize service component");
@@ -169,10 +620,11 @@ impl Engine {
                 &namespace,
                 component,
                 &validated_volumes,
+                filesystem_quotas,
             ) {
                 Ok(handle) => {
                     tracing::debug!("successfully initialized workload component");
-                    workload_components.push(handle);
+                    workload_components.push(handle.with_outbound_throttle(throttle.clone()));
                 }
                 Err(e) => {
                     tracing::error!(err = ?e, "failed to initialize component");
@@ -188,7 +640,46 @@ impl Engine {
             service,
             workload_components,
             host_interfaces,
-        ))
+        )
+        .with_crash_loop_policy(self.crash_loop_policy))
+    }
+
+    /// Links the WASI@0.3 interfaces for a component that targets them, alongside
+    /// the WASI@0.2 ones so the two can be mixed, and `wasi:http@0.3` if it
+    /// uses it.
+    ///
+    /// # Errors
+    /// Returns an error if the engine was built without WASI@0.3 support, or if
+    /// the component sends `wasi:http@0.3` requests, which would not go through
+    /// the egress policy of the host.
+    fn add_wasip3_to_linker(
+        &self,
+        component: &Component,
+        linker: &mut Linker<Ctx>,
+    ) -> anyhow::Result<()> {
+        if !self.wasip3 {
+            bail!(
+                "component targets WASI 0.3, which requires an engine built with EngineBuilder::with_wasip3"
+            );
+        }
+        if sends_wasi_http_p3(component) {
+            bail!(
+                "sending wasi:http@0.3 requests is not supported yet, build the component against wasi:http@0.2"
+            );
+        }
+
+        #[cfg(feature = "wasip3")]
+        {
+            wasmtime_wasi::p3::add_to_linker(linker).context("failed to add WASI@0.3 to linker")?;
+            if uses_wasi_http_p3(component) {
+                wasmtime_wasi_http::p3::add_to_linker(linker)
+                    .context("failed to add wasi:http@0.3 to linker")?;
+            }
+        }
+        #[cfg(not(feature = "wasip3"))]
+        let _ = linker;
+
+        Ok(())
     }
 
     fn initialize_service(
@@ -197,11 +688,24 @@ impl Engine {
         workload_name: impl AsRef<str>,
         workload_namespace: impl AsRef<str>,
         service: crate::types::Service,
-        validated_volumes: &std::collections::HashMap<String, PathBuf>,
+        validated_volumes: &std::collections::HashMap<String, VolumeRoot>,
+        filesystem_quotas: bool,
     ) -> anyhow::Result<WorkloadService> {
+        self.check_wasm_stack(&service.local_resources)?;
+        let execution_pool = self.execution_pool_for(&service.local_resources)?;
+        let cpu_throttle = self.cpu_throttle_for(&service.local_resources);
+        let config_change = match service.local_resources.config.get(CONFIG_CHANGE_CONFIG_KEY) {
+            Some(policy) => policy
+                .parse()
+                .with_context(|| format!("invalid {CONFIG_CHANGE_CONFIG_KEY} '{policy}'"))?,
+            None => ConfigChangePolicy::default(),
+        };
+
         // Create a wasmtime component from the bytes
-        let wasmtime_component = Component::new(&self.inner, service.bytes)
+        let wasmtime_component = self
+            .compile(&service.bytes)
             .context("failed to create component from bytes")?;
+        let provenance = provenance::read(&service.bytes);
 
         // Create a linker for this component
         let mut linker: Linker<Ctx> = Linker::new(&self.inner);
@@ -209,6 +713,14 @@ impl Engine {
         // Add WASI@0.2 interfaces to the linker
         wasmtime_wasi::p2::add_to_linker_async(&mut linker)
             .context("failed to add WASI to linker")?;
+        if filesystem_quotas {
+            filesystem::add_to_linker(&mut linker)?;
+        }
+
+        // Add WASI@0.3 interfaces to the linker for components that target them
+        if targets_wasip3(&wasmtime_component) {
+            self.add_wasip3_to_linker(&wasmtime_component, &mut linker)?;
+        }
 
         // Add HTTP interfaces to the linker if feature is enabled and component uses them
         if uses_wasi_http(&wasmtime_component) {
@@ -219,8 +731,9 @@ impl Engine {
         // Build volume mounts for this component by looking up validated volumes
         let mut component_volume_mounts = Vec::new();
         for vm in &service.local_resources.volume_mounts {
-            if let Some(host_path) = validated_volumes.get(&vm.name) {
-                component_volume_mounts.push((host_path.clone(), vm.clone()));
+            validate_mount_path(&vm.mount_path)?;
+            if let Some(root) = validated_volumes.get(&vm.name) {
+                component_volume_mounts.push((root.clone(), vm.clone()));
             } else {
                 tracing::warn!(
                     volume = %vm.name,
@@ -230,7 +743,7 @@ impl Engine {
         }
 
         // Create the WorkloadService with volume mounts
-        Ok(WorkloadService::new(
+        let service = WorkloadService::new(
             workload_id.as_ref(),
             workload_name.as_ref(),
             workload_namespace.as_ref(),
@@ -239,7 +752,19 @@ impl Engine {
             component_volume_mounts,
             service.local_resources,
             service.max_restarts,
-        ))
+        )
+        .with_epoch_yield_ticks(self.epoch_yield_ticks)
+        .with_cpu_throttle(cpu_throttle)
+        .with_max_memory_bytes(self.max_memory_bytes)
+        .with_memory_leak_policy(self.memory_leak_policy)
+        .with_config_change_policy(config_change)
+        .with_provenance(provenance)
+        .with_metering(self.metering.clone());
+        let execution_pool = match execution_pool {
+            Some(pool) => Some(pool),
+            None => self.cgroup_pool(workload_id.as_ref(), &service)?,
+        };
+        Ok(service.with_execution_pool(execution_pool))
     }
 
     /// Initialize a component that is a part of a workload, add wasi@0.2 interfaces (and
@@ -250,11 +775,17 @@ impl Engine {
         workload_name: impl AsRef<str>,
         workload_namespace: impl AsRef<str>,
         component: crate::types::Component,
-        validated_volumes: &std::collections::HashMap<String, PathBuf>,
+        validated_volumes: &std::collections::HashMap<String, VolumeRoot>,
+        filesystem_quotas: bool,
     ) -> anyhow::Result<WorkloadComponent> {
+        self.check_wasm_stack(&component.local_resources)?;
+        let execution_pool = self.execution_pool_for(&component.local_resources)?;
+
         // Create a wasmtime component from the bytes
-        let wasmtime_component = Component::new(&self.inner, component.bytes)
+        let wasmtime_component = self
+            .compile(&component.bytes)
             .context("failed to create component from bytes")?;
+        let provenance = provenance::read(&component.bytes);
 
         // Create a linker for this component
         let mut linker: Linker<Ctx> = Linker::new(&self.inner);
@@ -262,6 +793,14 @@ impl Engine {
         // Add WASI@0.2 interfaces to the linker
         wasmtime_wasi::p2::add_to_linker_async(&mut linker)
             .context("failed to add WASI to linker")?;
+        if filesystem_quotas {
+            filesystem::add_to_linker(&mut linker)?;
+        }
+
+        // Add WASI@0.3 interfaces to the linker for components that target them
+        if targets_wasip3(&wasmtime_component) {
+            self.add_wasip3_to_linker(&wasmtime_component, &mut linker)?;
+        }
 
         // Add HTTP interfaces to the linker
         if uses_wasi_http(&wasmtime_component) {
@@ -272,8 +811,9 @@ impl Engine {
         // Build volume mounts for this component by looking up validated volumes
         let mut component_volume_mounts = Vec::new();
         for vm in &component.local_resources.volume_mounts {
-            if let Some(host_path) = validated_volumes.get(&vm.name) {
-                component_volume_mounts.push((host_path.clone(), vm.clone()));
+            validate_mount_path(&vm.mount_path)?;
+            if let Some(root) = validated_volumes.get(&vm.name) {
+                component_volume_mounts.push((root.clone(), vm.clone()));
             } else {
                 tracing::warn!(
                     volume = %vm.name,
@@ -282,8 +822,32 @@ impl Engine {
             }
         }
 
+        let init_export = (component.kind == ComponentKind::Init).then(|| {
+            component
+                .init_export
+                .unwrap_or_else(|| DEFAULT_INIT_EXPORT.to_string())
+        });
+        let job = (component.kind == ComponentKind::Job).then(|| component.job.unwrap_or_default());
+        let invocation_timeout =
+            self.invocation_timeout_for(&component.local_resources, job.is_some())?;
+        let cpu_throttle = self.cpu_throttle_for(&component.local_resources);
+        let instance_pool = component
+            .autoscaling
+            .as_ref()
+            .map(InstancePool::new)
+            .transpose()?
+            .map(Arc::new);
+        let pre_init = component
+            .pre_init
+            .as_ref()
+            .map(PreInitPool::new)
+            .transpose()?
+            .map(Arc::new);
+        let middleware = component.kind == ComponentKind::Middleware;
+        let outgoing_http = component.outgoing_http;
+
         // Create the WorkloadComponent with volume mounts
-        Ok(WorkloadComponent::new(
+        let component = WorkloadComponent::new(
             workload_id.as_ref(),
             workload_name.as_ref(),
             workload_namespace.as_ref(),
@@ -294,7 +858,24 @@ impl Engine {
             // TODO: implement pooling and instance limits
             // component.pool_size,
             // component.max_invocations,
-        ))
+        )
+        .with_epoch_yield_ticks(self.epoch_yield_ticks)
+        .with_max_memory_bytes(self.max_memory_bytes)
+        .with_invocation_timeout(invocation_timeout)
+        .with_cpu_throttle(cpu_throttle)
+        .with_init_export(init_export)
+        .with_job(job)
+        .with_middleware(middleware)
+        .with_outgoing_http_limits(outgoing_http)
+        .with_instance_pool(instance_pool)
+        .with_pre_init(pre_init)
+        .with_provenance(provenance)
+        .with_metering(self.metering.clone());
+        let execution_pool = match execution_pool {
+            Some(pool) => Some(pool),
+            None => self.cgroup_pool(workload_id.as_ref(), &component)?,
+        };
+        Ok(component.with_execution_pool(execution_pool))
     }
 }
 
@@ -307,6 +888,20 @@ impl Engine {
 pub struct EngineBuilder {
     config: wasmtime::Config,
     use_pooling_allocator: Option<bool>,
+    epoch_tick: Option<Duration>,
+    epoch_yield_ticks: Option<u64>,
+    invocation_timeout: Option<Duration>,
+    cpu_fuel_rate: Option<u64>,
+    memory64: Option<bool>,
+    max_memory_bytes: Option<usize>,
+    wasip3: bool,
+    max_wasm_stack: Option<usize>,
+    memory_leak_policy: Option<MemoryLeakPolicy>,
+    crash_loop_policy: Option<CrashLoopPolicy>,
+    execution_pools: Vec<(String, ExecutionPoolConfig)>,
+    cgroups: Option<Cgroups>,
+    metering: Option<Metering>,
+    component_cache: Option<cache::ComponentCache>,
 }
 
 impl EngineBuilder {
@@ -338,6 +933,154 @@ impl EngineBuilder {
         self.config = config;
         self
     }
+
+    /// Enables epoch interruption, advancing the engine's epoch every `interval`.
+    ///
+    /// Without epoch interruption a CPU-bound guest keeps its executor thread until
+    /// it returns or calls the host. With it, a guest periodically yields so other
+    /// components sharing the host get to run. Shorter intervals improve fairness
+    /// and latency at the cost of throughput, since every yield is a context switch.
+    ///
+    /// # Arguments
+    /// * `interval` - How often the epoch advances, e.g. 10ms
+    pub fn with_epoch_tick(mut self, interval: Duration) -> Self {
+        self.epoch_tick = Some(interval);
+        self
+    }
+
+    /// Sets how many epoch ticks a guest may run between yields. Defaults to 1.
+    ///
+    /// Has no effect unless [`EngineBuilder::with_epoch_tick`] is set. A guest
+    /// runs for roughly `ticks * interval` before yielding.
+    pub fn with_epoch_yield_ticks(mut self, ticks: u64) -> Self {
+        self.epoch_yield_ticks = Some(ticks);
+        self
+    }
+
+    /// Enforces the `cpu_limit` of components with fuel, `fuel_per_cpu_second`
+    /// being the fuel a guest running on a whole CPU burns through per second.
+    /// Guests of a component that consumed more fuel than its `cpu_limit`
+    /// allows sleep until they are back under it. See [`cpu`] for details.
+    ///
+    /// Ignored when [`EngineBuilder::with_cgroups`] is set, as the kernel then
+    /// enforces `cpu_limit`. Guests are charged at epoch ticks, so this enables
+    /// epoch interruption, every 10ms unless [`EngineBuilder::with_epoch_tick`]
+    /// is set, and consumes fuel, which makes guests run slower.
+    pub fn with_cpu_fuel_rate(mut self, fuel_per_cpu_second: u64) -> Self {
+        self.cpu_fuel_rate = Some(fuel_per_cpu_second);
+        self
+    }
+
+    /// Interrupts invocations still running after `timeout`, failing them with
+    /// [`InvocationTimeout`]. HTTP requests are answered with a `504 Gateway
+    /// Timeout`, and the instance is dropped with its store, so a guest stuck
+    /// in a loop doesn't keep it.
+    ///
+    /// Components set their own timeout with the `invocation_timeout_ms` key of
+    /// their local resources config. Jobs are only limited by their own
+    /// timeout. Guests are interrupted at epoch ticks, so this enables epoch
+    /// interruption, every 10ms unless [`EngineBuilder::with_epoch_tick`] is
+    /// set, and invocations overrun their timeout by up to a tick.
+    pub fn with_invocation_timeout(mut self, timeout: Duration) -> Self {
+        self.invocation_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables or disables the memory64 proposal, which allows components with
+    /// 64-bit linear memories that can grow beyond 4 GiB. Follows wasmtime's
+    /// default when unset.
+    pub fn with_memory64(mut self, enable: bool) -> Self {
+        self.memory64 = Some(enable);
+        self
+    }
+
+    /// Limits the linear memories of every instance to `bytes` in total,
+    /// regardless of the `memory_limit_mb` a component requests, see
+    /// [`memory`].
+    ///
+    /// When the pooling allocator is in use this also sizes its memory slots,
+    /// which otherwise hold at most 4 GiB, so it must be set for memory64
+    /// components to grow beyond that.
+    pub fn with_max_memory_bytes(mut self, bytes: usize) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Sets the largest stack, in bytes, wasm code may use. Defaults to 512 KiB.
+    ///
+    /// A guest that recurses deeper than its stack allows traps with a stack
+    /// exhaustion error. The limit applies to every component on the engine;
+    /// components declare the stack they need with the `wasm_stack_bytes` key of
+    /// their local resources config and fail to start if the host allows less.
+    pub fn with_max_wasm_stack(mut self, bytes: usize) -> Self {
+        self.max_wasm_stack = Some(bytes);
+        self
+    }
+
+    /// Samples the memory of long-lived instances, such as workload services,
+    /// and flags those that keep growing as suspected leaks. Flagged instances
+    /// are reported in the workload status and replaced with a fresh instance
+    /// if [`MemoryLeakPolicy::recycle`] is set. See [`memory`] for details.
+    pub fn with_memory_leak_policy(mut self, policy: MemoryLeakPolicy) -> Self {
+        self.memory_leak_policy = Some(policy);
+        self
+    }
+
+    /// Counts the consecutive failures of every component, backing off
+    /// those that keep failing and stopping their workloads if `policy` says
+    /// so. See [`crash_loop`] for details.
+    pub fn with_crash_loop_policy(mut self, policy: CrashLoopPolicy) -> Self {
+        self.crash_loop_policy = Some(policy);
+        self
+    }
+
+    /// Adds an execution pool named `name`, dedicated threads pinned to the
+    /// CPUs of `config`. Components run their guests on it by setting the
+    /// `execution_pool` key of their local resources config to `name`. See
+    /// [`pool`] for details.
+    pub fn with_execution_pool(
+        mut self,
+        name: impl Into<String>,
+        config: ExecutionPoolConfig,
+    ) -> Self {
+        self.execution_pools.push((name.into(), config));
+        self
+    }
+
+    /// Has the kernel enforce the `cpu_limit` of components and the memory
+    /// reserved by workloads with `cgroups`. See [`cgroup`] for details.
+    pub fn with_cgroups(mut self, cgroups: Cgroups) -> Self {
+        self.cgroups = Some(cgroups);
+        self
+    }
+
+    /// Meters the usage of every invocation with `metering`, see
+    /// [`crate::host::metering`]. Guests are metered in fuel if `metering`
+    /// says so.
+    pub fn with_metering(mut self, metering: Metering) -> Self {
+        self.metering = Some(metering);
+        self
+    }
+
+    /// Keeps the components the engine compiles in `cache`, so they load
+    /// without compiling again after the host restarts, see [`cache`].
+    pub fn with_component_cache(mut self, cache: cache::ComponentCache) -> Self {
+        self.component_cache = Some(cache);
+        self
+    }
+
+    /// Accepts components built for WASI 0.3 and the component model async ABI.
+    ///
+    /// This is opt-in, and components targeting WASI 0.2 run exactly as before
+    /// whether or not it is enabled. The HTTP server serves components
+    /// exporting a `wasi:http@0.3` handler with it, and `wasi:http@0.2`
+    /// otherwise. Components sending `wasi:http@0.3` requests are rejected
+    /// when they are compiled or initialized.
+    #[cfg(feature = "wasip3")]
+    pub fn with_wasip3(mut self, enable: bool) -> Self {
+        self.wasip3 = enable;
+        self
+    }
 }
 
 impl EngineBuilder {
@@ -355,20 +1098,130 @@ impl EngineBuilder {
     pub fn build(mut self) -> anyhow::Result<Engine> {
         // Async support must be enabled
         self.config.async_support(true);
+        if let Some(enable) = self.memory64 {
+            self.config.wasm_memory64(enable);
+        }
+        #[cfg(feature = "wasip3")]
+        if self.wasip3 {
+            self.config.wasm_component_model_async(true);
+        }
+        if let Some(bytes) = self.max_wasm_stack {
+            if bytes == 0 {
+                bail!("max wasm stack must be greater than zero");
+            }
+            self.config.max_wasm_stack(bytes);
+            // Async calls run on their own stack, which must fit the wasm stack plus host frames
+            self.config
+                .async_stack_size(bytes.saturating_add(ASYNC_STACK_HEADROOM));
+        }
+        if self
+            .memory_leak_policy
+            .is_some_and(|policy| policy.sample_interval.is_zero())
+        {
+            bail!("memory leak sample interval must be greater than zero");
+        }
+        if let Some(policy) = &self.crash_loop_policy {
+            policy.validate()?;
+        }
         // The pooling allocator can be more efficient for workloads with many short-lived instances
         if let Ok(true) = use_pooling_allocator_by_default(self.use_pooling_allocator) {
             tracing::debug!("using pooling allocator by default");
+            let mut pooling = PoolingAllocationConfig::default();
+            if let Some(bytes) = self.max_memory_bytes {
+                pooling.max_memory_size(bytes);
+            }
             self.config
-                .allocation_strategy(wasmtime::InstanceAllocationStrategy::Pooling(
-                    PoolingAllocationConfig::default(),
-                ));
+                .allocation_strategy(wasmtime::InstanceAllocationStrategy::Pooling(pooling));
+        }
+
+        if self
+            .invocation_timeout
+            .is_some_and(|timeout| timeout.is_zero())
+        {
+            bail!("invocation timeout must be greater than zero");
+        }
+        if self.cpu_fuel_rate == Some(0) {
+            bail!("CPU fuel rate must be greater than zero");
+        }
+        if (self.invocation_timeout.is_some() || self.cpu_fuel_rate.is_some())
+            && self.epoch_tick.is_none()
+        {
+            self.epoch_tick = Some(DEFAULT_EPOCH_TICK);
+        }
+        let epoch_yield_ticks = match self.epoch_tick {
+            Some(interval) => {
+                if interval.is_zero() {
+                    bail!("epoch tick interval must be greater than zero");
+                }
+                self.config.epoch_interruption(true);
+                Some(self.epoch_yield_ticks.unwrap_or(1).max(1))
+            }
+            None => None,
+        };
+
+        if self.cpu_fuel_rate.is_some()
+            || self
+                .metering
+                .as_ref()
+                .is_some_and(|metering| metering.meters_fuel())
+        {
+            self.config.consume_fuel(true);
+        }
+        // Instances interrupted for a dump trap with their core, see `debug`
+        #[cfg(feature = "instance-debug")]
+        self.config.coredump_on_trap(true);
+
+        let mut execution_pools = HashMap::new();
+        for (name, config) in self.execution_pools {
+            if execution_pools.contains_key(&name) {
+                bail!("execution pool '{name}' is defined more than once");
+            }
+            let pool = ExecutionPool::new(&name, config)?;
+            execution_pools.insert(name, Arc::new(pool));
         }
 
         let inner = wasmtime::Engine::new(&self.config)?;
-        Ok(Engine { inner })
+        if let Some(interval) = self.epoch_tick {
+            spawn_epoch_ticker(&inner, interval)?;
+        }
+        Ok(Engine {
+            inner,
+            epoch_yield_ticks,
+            invocation_timeout: self.invocation_timeout,
+            cpu_fuel_rate: self.cpu_fuel_rate,
+            max_memory_bytes: self.max_memory_bytes,
+            wasip3: self.wasip3,
+            max_wasm_stack: self.max_wasm_stack.unwrap_or(DEFAULT_MAX_WASM_STACK),
+            memory_leak_policy: self.memory_leak_policy,
+            crash_loop_policy: self.crash_loop_policy,
+            execution_pools,
+            cgroups: self.cgroups,
+            metering: self.metering,
+            compiled: CompiledComponents::default(),
+            cache: self.component_cache.map(|cache| cache.for_engine(&inner)),
+        })
     }
 }
 
+/// Advances the engine's epoch every `interval` on a dedicated thread, which
+/// exits once the engine is dropped.
+fn spawn_epoch_ticker(engine: &wasmtime::Engine, interval: Duration) -> anyhow::Result<()> {
+    let engine = engine.weak();
+    std::thread::Builder::new()
+        .name("wasmtime-epoch-ticker".to_string())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(interval);
+                match engine.upgrade() {
+                    Some(engine) => engine.increment_epoch(),
+                    None => break,
+                }
+            }
+        })
+        .context("failed to spawn epoch ticker thread")?;
+    Ok(())
+}
+
 /// Helper function to determine if a component uses wasi:http interfaces
 pub fn uses_wasi_http(component: &Component) -> bool {
     imports_wasi_http(component) || exports_wasi_http(component)
@@ -390,6 +1243,129 @@ pub fn imports_wasi_http(component: &Component) -> bool {
         .any(|(import, _item)| import.starts_with("wasi:http"))
 }
 
+/// Helper function to determine if a component imports or exports any WASI@0.3 interface
+pub fn targets_wasip3(component: &Component) -> bool {
+    let ty: wasmtime::component::types::Component = component.component_type();
+    let engine = component.engine();
+
+    ty.imports(engine)
+        .map(|(name, _item)| name)
+        .chain(ty.exports(engine).map(|(name, _item)| name))
+        .any(is_wasip3_interface)
+}
+
+/// Helper function to determine if a component exports a `wasi:http@0.3`
+/// handler, which the host then serves instead of `wasi:http/incoming-handler`
+pub fn exports_wasi_http_p3(component: &Component) -> bool {
+    let ty: wasmtime::component::types::Component = component.component_type();
+    let engine = component.engine();
+
+    ty.exports(engine)
+        .any(|(export, _item)| is_wasi_http_p3(export))
+}
+
+/// Helper function to determine if a component imports or exports any
+/// `wasi:http@0.3` interface
+fn uses_wasi_http_p3(component: &Component) -> bool {
+    let ty: wasmtime::component::types::Component = component.component_type();
+    let engine = component.engine();
+
+    ty.imports(engine)
+        .map(|(name, _item)| name)
+        .chain(ty.exports(engine).map(|(name, _item)| name))
+        .any(is_wasi_http_p3)
+}
+
+/// Helper function to determine if a component imports the `wasi:http@0.3`
+/// handler, to send requests with it
+fn sends_wasi_http_p3(component: &Component) -> bool {
+    let ty: wasmtime::component::types::Component = component.component_type();
+    let engine = component.engine();
+
+    ty.imports(engine).any(|(import, _item)| {
+        import.starts_with("wasi:http/handler@") && is_wasip3_interface(import)
+    })
+}
+
+fn is_wasi_http_p3(name: &str) -> bool {
+    name.starts_with("wasi:http/") && is_wasip3_interface(name)
+}
+
+/// Returns the interfaces the engine links every component to that imports
+/// them: the wasi@0.2 interfaces, including wasi:http. Components targeting
+/// WASI@0.3 are also linked to its interfaces, see [`is_wasip3_interface`].
+/// <https://docs.rs/wasmtime-wasi/36.0.2/wasmtime_wasi/p2/index.html#wasip2-interfaces>
+pub(crate) fn builtin_interfaces() -> HashSet<WitInterface> {
+    HashSet::from([
+        "wasi:http/types,incoming-handler,outgoing-handler@0.2.0".into(),
+        "wasi:io/poll,error,streams@0.2.0".into(),
+        "wasi:clocks/monotonic-clock,wall-time@0.2.0".into(),
+        "wasi:random/random@0.2.0".into(),
+        "wasi:cli/environment,exit,stderr,stdin,stdout,terminal-input,terminal-output,terminal-stderr,terminal-stdin,terminal-stdout@0.2.0".into(),
+        "wasi:clocks/monotonic-clock,wall-clock@0.2.0".into(),
+        "wasi:filesystem/preopens,types@0.2.0".into(),
+        "wasi:random/insecure-seed,insecure,random@0.2.0".into(),
+        "wasi:sockets/instance-network,ip-name-lookup,network,tcp-create-socket,tcp,udp-create-socket,udp@0.2.0".into(),
+    ])
+}
+
+pub(crate) fn is_wasip3_interface(name: &str) -> bool {
+    name.starts_with("wasi:")
+        && name
+            .split_once('@')
+            .is_some_and(|(_, version)| version.starts_with("0.3."))
+}
+
+/// Computes the [`WitWorld`] of a component, merging interfaces when
+/// namespace:package@version matches.
+pub fn component_world(component: &Component) -> WitWorld {
+    let ty = component.component_type();
+    let engine = component.engine();
+
+    let mut imports: std::collections::HashMap<String, WitInterface> =
+        std::collections::HashMap::new();
+    for (import_name, import_item) in ty.imports(engine) {
+        if let ComponentItem::ComponentInstance(_) = import_item {
+            let interface = WitInterface::from(import_name);
+            imports
+                .entry(interface.instance())
+                .and_modify(|existing| {
+                    existing.merge(&interface);
+                })
+                .or_insert(interface);
+        } else {
+            tracing::debug!(
+                import_name,
+                "imported item is not a component instance, skipping"
+            );
+        }
+    }
+
+    let mut exports: std::collections::HashMap<String, WitInterface> =
+        std::collections::HashMap::new();
+    for (export_name, export_item) in ty.exports(engine) {
+        if let ComponentItem::ComponentInstance(_) = export_item {
+            let interface = WitInterface::from(export_name);
+            exports
+                .entry(interface.instance())
+                .and_modify(|existing| {
+                    existing.merge(&interface);
+                })
+                .or_insert(interface);
+        } else {
+            tracing::debug!(
+                export_name,
+                "exported item is not a component instance, skipping"
+            );
+        }
+    }
+
+    WitWorld {
+        imports: imports.into_values().collect(),
+        exports: exports.into_values().collect(),
+    }
+}
+
 // TL;DR this is likely best for machines that can handle the large virtual memory requirement of the pooling allocator
 // https://github.com/bytecodealliance/wasmtime/blob/b943666650696f1eb7ff8b217762b58d5ef5779d/src/commands/serve.rs#L641-L656
 fn use_pooling_allocator_by_default(enable: Option<bool>) -> anyhow::Result<bool> {
@@ -407,3 +1383,258 @@ fn use_pooling_allocator_by_default(enable: Option<bool>) -> anyhow::Result<bool
     let ty = wasmtime::MemoryType::new64(0, Some(1 << (BITS_TO_TEST - 16)));
     Ok(wasmtime::Memory::new(&mut store, ty).is_ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_settings() {
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .build()
+            .unwrap();
+        assert_eq!(engine.epoch_yield_ticks(), None);
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_epoch_tick(Duration::from_millis(10))
+            .with_epoch_yield_ticks(5)
+            .build()
+            .unwrap();
+        assert_eq!(engine.epoch_yield_ticks(), Some(5));
+
+        assert!(
+            Engine::builder()
+                .with_pooling_allocator(false)
+                .with_epoch_tick(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_memory64_settings() {
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_memory64(true)
+            .with_max_memory_bytes(8 << 30)
+            .build()
+            .unwrap();
+        assert_eq!(engine.max_memory_bytes(), Some(8 << 30));
+    }
+
+    #[test]
+    fn test_is_wasip3_interface() {
+        assert!(is_wasip3_interface("wasi:http/handler@0.3.0-rc-2025-09-16"));
+        assert!(is_wasip3_interface("wasi:cli/stdout@0.3.0"));
+        assert!(!is_wasip3_interface("wasi:http/incoming-handler@0.2.0"));
+        assert!(!is_wasip3_interface("wasmcloud:messaging/handler@0.3.0"));
+        assert!(!is_wasip3_interface("wasi:cli/stdout"));
+    }
+
+    #[test]
+    fn test_wasm_stack_settings() {
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_max_wasm_stack(1024 * 1024)
+            .build()
+            .unwrap();
+        assert_eq!(engine.max_wasm_stack(), 1024 * 1024);
+
+        let mut resources = LocalResources::default();
+        assert!(engine.check_wasm_stack(&resources).is_ok());
+        resources
+            .config
+            .insert(WASM_STACK_CONFIG_KEY.to_string(), "1048576".to_string());
+        assert!(engine.check_wasm_stack(&resources).is_ok());
+        resources
+            .config
+            .insert(WASM_STACK_CONFIG_KEY.to_string(), "2097152".to_string());
+        assert!(engine.check_wasm_stack(&resources).is_err());
+
+        let err = explain_trap(wasmtime::Trap::StackOverflow.into());
+        assert!(format!("{err:#}").contains("exhausted its wasm stack"));
+    }
+
+    #[test]
+    fn test_cpu_throttle_settings() {
+        let mut resources = LocalResources {
+            cpu_limit: 500,
+            ..Default::default()
+        };
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .build()
+            .unwrap();
+        assert!(engine.cpu_throttle_for(&resources).is_none());
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_cpu_fuel_rate(1_000_000)
+            .build()
+            .unwrap();
+        assert_eq!(engine.cpu_fuel_rate(), Some(1_000_000));
+        assert!(engine.epoch_yield_ticks().is_some());
+        assert!(engine.cpu_throttle_for(&resources).is_some());
+        resources.cpu_limit = -1;
+        assert!(engine.cpu_throttle_for(&resources).is_none());
+
+        assert!(Engine::builder().with_cpu_fuel_rate(0).build().is_err());
+    }
+
+    #[test]
+    fn test_invocation_timeout_settings() {
+        let mut resources = LocalResources::default();
+        resources
+            .config
+            .insert(INVOCATION_TIMEOUT_CONFIG_KEY.to_string(), "250".to_string());
+
+        // Guests can't be interrupted without epoch interruption
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .build()
+            .unwrap();
+        assert_eq!(
+            engine
+                .invocation_timeout_for(&LocalResources::default(), false)
+                .unwrap(),
+            None
+        );
+        assert!(engine.invocation_timeout_for(&resources, false).is_err());
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_invocation_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        assert!(engine.epoch_yield_ticks().is_some());
+        assert_eq!(
+            engine
+                .invocation_timeout_for(&LocalResources::default(), false)
+                .unwrap(),
+            Some(Duration::from_secs(5))
+        );
+        // Jobs only have the timeout they set
+        assert_eq!(
+            engine
+                .invocation_timeout_for(&LocalResources::default(), true)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            engine.invocation_timeout_for(&resources, true).unwrap(),
+            Some(Duration::from_millis(250))
+        );
+        resources
+            .config
+            .insert(INVOCATION_TIMEOUT_CONFIG_KEY.to_string(), "0".to_string());
+        assert!(engine.invocation_timeout_for(&resources, false).is_err());
+
+        assert!(
+            Engine::builder()
+                .with_invocation_timeout(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+
+        let err = anyhow::Error::new(InvocationTimeout(Duration::from_secs(5)))
+            .context("failed to handle request");
+        assert!(is_invocation_timeout(&err));
+        assert!(!is_invocation_timeout(&anyhow::anyhow!("guest trapped")));
+    }
+
+    #[test]
+    fn test_precompile() {
+        const HTTP_COUNTER_WASM: &[u8] = include_bytes!("../../tests/fixtures/http_counter.wasm");
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .build()
+            .unwrap();
+        let digest = engine.precompile(HTTP_COUNTER_WASM).unwrap();
+        assert_eq!(digest, component_digest(HTTP_COUNTER_WASM));
+        assert!(engine.compiled_artifact(&digest).unwrap().is_some());
+
+        engine.retain_compiled(&HashSet::new());
+        assert!(engine.compiled_artifact(&digest).unwrap().is_none());
+
+        assert!(engine.precompile(b"not a component").is_err());
+    }
+
+    #[test]
+    fn test_component_cache() {
+        const HTTP_COUNTER_WASM: &[u8] = include_bytes!("../../tests/fixtures/http_counter.wasm");
+
+        let dir = tempfile::tempdir().unwrap();
+        let engine = || {
+            Engine::builder()
+                .with_pooling_allocator(false)
+                .with_component_cache(cache::ComponentCache::new(dir.path()))
+                .build()
+                .unwrap()
+        };
+        let digest = engine().precompile(HTTP_COUNTER_WASM).unwrap();
+        assert_eq!(
+            engine().cached_bytes(&digest).as_deref(),
+            Some(HTTP_COUNTER_WASM)
+        );
+
+        // A new engine, as after a restart, loads the cached artifact
+        let restarted = engine();
+        assert_eq!(restarted.precompile(HTTP_COUNTER_WASM).unwrap(), digest);
+        assert!(restarted.compiled_artifact(&digest).unwrap().is_some());
+        assert!(
+            Engine::builder()
+                .with_pooling_allocator(false)
+                .build()
+                .unwrap()
+                .cached_bytes(&digest)
+                .is_none()
+        );
+    }
+
+    #[cfg(feature = "wasip3")]
+    #[test]
+    fn test_wasip3_http() {
+        let handler = wat::parse_str(
+            r#"
+            (component
+                (import "handler" (instance $handler))
+                (export "wasi:http/handler@0.3.0" (instance $handler))
+            )
+            "#,
+        )
+        .expect("failed to parse WAT");
+        let client = wat::parse_str(
+            r#"
+            (component
+                (import "wasi:http/handler@0.3.0" (instance))
+            )
+            "#,
+        )
+        .expect("failed to parse WAT");
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .with_wasip3(true)
+            .build()
+            .unwrap();
+        let compiled = engine.compile(&handler).unwrap();
+        assert!(exports_wasi_http_p3(&compiled));
+        engine.precompile(&handler).unwrap();
+        let err = engine.precompile(&client).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("sending wasi:http@0.3 requests is not supported"),
+            "{err:#}"
+        );
+
+        let engine = Engine::builder()
+            .with_pooling_allocator(false)
+            .build()
+            .unwrap();
+        let err = engine.precompile(&handler).unwrap_err();
+        assert!(err.to_string().contains("with_wasip3"), "{err:#}");
+    }
+}
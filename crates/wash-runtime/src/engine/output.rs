@@ -0,0 +1,271 @@
+//! Capturing what components write to stdout and stderr.
+//!
+//! Components write to the `wasi:cli` stdout and stderr streams of their
+//! store. Instead of passing the bytes through to the host process, where the
+//! output of concurrent invocations interleaves with each other and with host
+//! logs, every line is logged as an event with the target
+//! [`GUEST_OUTPUT_TARGET`] and the fields:
+//!
+//! - `workload_id` and `component_id` of the component writing it
+//! - `request_id` of the invocation, see [`LogContext`]
+//! - `stream`, either `stdout` or `stderr`
+//!
+//! Lines written to stdout are logged at the info level, lines written to
+//! stderr at the warn level. A line is logged once it ends, or when it
+//! exceeds [`MAX_LINE_BYTES`]. A trailing line without newline is logged when
+//! the store is dropped, so output of a store is never lost.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::task::{Context, Poll};
+
+use tokio::io::AsyncWrite;
+use tracing::{info, warn};
+use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
+
+/// Target of the log events of guest output, to filter them apart from host logs
+pub const GUEST_OUTPUT_TARGET: &str = "wash_runtime::guest";
+/// Length in bytes after which a line without newline is logged anyway
+pub const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// The log context of the invocation a store runs, shared between the
+/// [`Ctx`](crate::engine::ctx::Ctx) of the store and its output streams.
+///
+/// The request id defaults to the id of the store, which is unique per
+/// invocation. HTTP requests carrying an `X-Request-Id` header use it instead,
+/// so guest output can be correlated with the logs of other services.
+#[derive(Debug, Clone, Default)]
+pub struct LogContext {
+    request_id: Arc<RwLock<Option<Arc<str>>>>,
+}
+
+impl LogContext {
+    /// Returns the id of the invocation, if one was set.
+    pub fn request_id(&self) -> Option<Arc<str>> {
+        self.request_id
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Sets the id of the invocation output is attributed to.
+    pub fn set_request_id(&self, request_id: impl Into<Arc<str>>) {
+        *self
+            .request_id
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(request_id.into());
+    }
+}
+
+/// The stream of a component some output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn as_str(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// The stdout or stderr of a store, logging every line written to it.
+///
+/// Guests may open a new stream for every write, so the incomplete line is
+/// kept here rather than in the stream.
+#[derive(Debug, Clone)]
+pub struct GuestOutput {
+    inner: Arc<Output>,
+}
+
+impl GuestOutput {
+    /// Returns the stdout of a component of a workload.
+    pub fn stdout(
+        workload_id: impl Into<Arc<str>>,
+        component_id: impl Into<Arc<str>>,
+        log: LogContext,
+    ) -> Self {
+        Self::new(Stream::Stdout, workload_id.into(), component_id.into(), log)
+    }
+
+    /// Returns the stderr of a component of a workload.
+    pub fn stderr(
+        workload_id: impl Into<Arc<str>>,
+        component_id: impl Into<Arc<str>>,
+        log: LogContext,
+    ) -> Self {
+        Self::new(Stream::Stderr, workload_id.into(), component_id.into(), log)
+    }
+
+    fn new(stream: Stream, workload_id: Arc<str>, component_id: Arc<str>, log: LogContext) -> Self {
+        Self {
+            inner: Arc::new(Output {
+                stream,
+                workload_id,
+                component_id,
+                log,
+                pending: Mutex::default(),
+            }),
+        }
+    }
+}
+
+impl StdoutStream for GuestOutput {
+    fn async_stream(&self) -> Box<dyn AsyncWrite + Send + Sync> {
+        Box::new(OutputWriter {
+            output: self.inner.clone(),
+        })
+    }
+}
+
+impl IsTerminal for GuestOutput {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+struct Output {
+    stream: Stream,
+    workload_id: Arc<str>,
+    component_id: Arc<str>,
+    log: LogContext,
+    /// The incomplete line written last
+    pending: Mutex<Vec<u8>>,
+}
+
+impl Output {
+    fn write(&self, buf: &[u8]) {
+        let lines = {
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            split_lines(&mut pending, buf)
+        };
+        for line in lines {
+            self.log(&line);
+        }
+    }
+
+    fn log(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        let request_id = self.log.request_id();
+        let request_id = request_id.as_deref().unwrap_or_default();
+        let stream = self.stream.as_str();
+        match self.stream {
+            Stream::Stdout => info!(
+                target: GUEST_OUTPUT_TARGET,
+                workload_id = %self.workload_id,
+                component_id = %self.component_id,
+                request_id,
+                stream,
+                "{line}"
+            ),
+            Stream::Stderr => warn!(
+                target: GUEST_OUTPUT_TARGET,
+                workload_id = %self.workload_id,
+                component_id = %self.component_id,
+                request_id,
+                stream,
+                "{line}"
+            ),
+        }
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let pending = std::mem::take(
+            self.pending
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        if !pending.is_empty() {
+            self.log(&pending);
+        }
+    }
+}
+
+/// Appends `buf` to the incomplete line in `pending` and returns the lines
+/// that are complete, without their newline, or longer than
+/// [`MAX_LINE_BYTES`].
+fn split_lines(pending: &mut Vec<u8>, buf: &[u8]) -> Vec<Vec<u8>> {
+    pending.extend_from_slice(buf);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while let Some(end) = pending[start..].iter().position(|b| *b == b'\n') {
+        lines.push(pending[start..start + end].to_vec());
+        start += end + 1;
+    }
+    while pending.len() - start > MAX_LINE_BYTES {
+        lines.push(pending[start..start + MAX_LINE_BYTES].to_vec());
+        start += MAX_LINE_BYTES;
+    }
+    pending.drain(..start);
+    lines
+}
+
+/// A stream a guest opened on its stdout or stderr
+struct OutputWriter {
+    output: Arc<Output>,
+}
+
+impl AsyncWrite for OutputWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.output.write(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_lines() {
+        let mut pending = Vec::new();
+        assert_eq!(
+            split_lines(&mut pending, b"hello\nwor"),
+            vec![b"hello".to_vec()]
+        );
+        assert_eq!(pending, b"wor");
+        assert!(split_lines(&mut pending, b"ld").is_empty());
+        assert_eq!(
+            split_lines(&mut pending, b"\n\nbye\n"),
+            vec![b"world".to_vec(), Vec::new(), b"bye".to_vec()]
+        );
+        assert!(pending.is_empty());
+
+        // Long lines are split
+        let long = vec![b'a'; MAX_LINE_BYTES * 2 + 1];
+        let lines = split_lines(&mut pending, &long);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() == MAX_LINE_BYTES));
+        assert_eq!(pending, b"a");
+    }
+
+    #[test]
+    fn test_log_context() {
+        let log = LogContext::default();
+        assert_eq!(log.request_id(), None);
+        let shared = log.clone();
+        shared.set_request_id("req-1");
+        assert_eq!(log.request_id().as_deref(), Some("req-1"));
+    }
+}
@@ -0,0 +1,523 @@
+//! Execution pools pinned to CPU sets.
+//!
+//! By default guests run on the async runtime of the host, next to its
+//! networking and every other workload. On large machines a latency-sensitive
+//! workload can instead run on a dedicated [`ExecutionPool`]: a set of threads
+//! pinned to a [`CpuSet`] that optionally prefers memory local to a NUMA node,
+//! so other workloads can't steal its cores or evict its caches.
+//!
+//! Pools are created with [`EngineBuilder::with_execution_pool`] and
+//! components opt into one by name with the `execution_pool` key of their
+//! local resources config. Instantiation, invocations and the tasks a guest
+//! spawns through WASI all run on the pool's threads:
+//!
+//! ```no_run
+//! use wash_runtime::engine::Engine;
+//! use wash_runtime::engine::pool::ExecutionPoolConfig;
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! let engine = Engine::builder()
+//!     .with_execution_pool("latency", ExecutionPoolConfig::new("8-15".parse()?).with_numa_node(1))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A pool given a NUMA node but no CPUs runs on the CPUs of that node.
+//! Pinning and memory policies are only applied on Linux. Elsewhere pools
+//! still isolate their guests on dedicated threads, which the OS schedules
+//! freely.
+//!
+//! [`EngineBuilder::with_execution_pool`]: super::EngineBuilder::with_execution_pool
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context as _, bail, ensure};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::cgroup::{self, ComponentCgroup};
+
+/// A set of CPUs, written as a list of CPU numbers and inclusive ranges such
+/// as `0-3,8,10-11`, like Linux's `cpuset` and `taskset -c`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuSet(BTreeSet<usize>);
+
+impl CpuSet {
+    /// Returns the CPUs of NUMA node `node`, as listed by the kernel.
+    ///
+    /// # Errors
+    /// Returns an error if the node does not exist or the platform does not
+    /// expose its NUMA topology.
+    pub fn of_numa_node(node: u32) -> anyhow::Result<Self> {
+        let path = format!("/sys/devices/system/node/node{node}/cpulist");
+        let list = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the CPUs of NUMA node {node} from {path}"))?;
+        list.trim().parse()
+    }
+
+    /// Returns the CPUs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Returns the number of CPUs in the set.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the set has no CPUs.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns whether `cpu` is in the set.
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.0.contains(&cpu)
+    }
+}
+
+impl FromIterator<usize> for CpuSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cpus = BTreeSet::new();
+        for part in s.split(',').map(str::trim) {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let parse = |cpu: &str| {
+                cpu.trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("invalid CPU '{part}' in CPU set '{s}'"))
+            };
+            let (start, end) = (parse(start)?, parse(end)?);
+            ensure!(start <= end, "invalid CPU range '{part}' in CPU set '{s}'");
+            cpus.extend(start..=end);
+        }
+        Ok(Self(cpus))
+    }
+}
+
+impl Serialize for CpuSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cpus = self.iter().peekable();
+        let mut first = true;
+        while let Some(start) = cpus.next() {
+            let mut end = start;
+            while cpus.next_if_eq(&(end + 1)).is_some() {
+                end += 1;
+            }
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+            match end - start {
+                0 => write!(f, "{start}")?,
+                _ => write!(f, "{start}-{end}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the threads of an [`ExecutionPool`] run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ExecutionPoolConfig {
+    /// CPUs the pool's threads are pinned to, the CPUs of `numa_node` if empty
+    pub cpus: CpuSet,
+    /// NUMA node the pool's threads prefer to allocate memory from
+    pub numa_node: Option<u32>,
+    /// Number of worker threads, one per CPU by default
+    pub threads: Option<usize>,
+}
+
+impl ExecutionPoolConfig {
+    /// Creates a pool configuration pinned to `cpus`.
+    pub fn new(cpus: CpuSet) -> Self {
+        Self {
+            cpus,
+            numa_node: None,
+            threads: None,
+        }
+    }
+
+    /// Makes the pool's threads prefer allocating memory from NUMA node `node`.
+    pub fn with_numa_node(mut self, node: u32) -> Self {
+        self.numa_node = Some(node);
+        self
+    }
+
+    /// Sets the number of worker threads of the pool.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+}
+
+/// Dedicated threads that run the guests of the components assigned to them,
+/// see the [module documentation](self).
+pub struct ExecutionPool {
+    name: Arc<str>,
+    config: ExecutionPoolConfig,
+    /// Number of worker threads
+    threads: usize,
+    /// Invocations running on the pool, see [`Self::run`]
+    busy: AtomicUsize,
+    /// Only `None` while the pool is dropped
+    runtime: Option<tokio::runtime::Runtime>,
+    /// The cgroup the pool's threads are placed in, removed after the pool
+    cgroup: Option<ComponentCgroup>,
+}
+
+impl ExecutionPool {
+    /// Starts the threads of pool `name`.
+    ///
+    /// # Errors
+    /// Returns an error if the configuration is invalid, names CPUs the host
+    /// process may not run on, or the threads can't be started.
+    pub(crate) fn new(name: &str, mut config: ExecutionPoolConfig) -> anyhow::Result<Self> {
+        if config.cpus.is_empty()
+            && let Some(node) = config.numa_node
+        {
+            config.cpus = CpuSet::of_numa_node(node)
+                .with_context(|| format!("execution pool '{name}' has no CPUs"))?;
+        }
+        ensure!(
+            !config.cpus.is_empty(),
+            "execution pool '{name}' has no CPUs"
+        );
+        let threads = config.threads.unwrap_or(config.cpus.len());
+        ensure!(
+            threads > 0,
+            "execution pool '{name}' must have at least one thread"
+        );
+        if cfg!(target_os = "linux") {
+            let allowed = sys::allowed_cpus().context("failed to read the CPUs of the host")?;
+            if let Some(cpu) = config.cpus.iter().find(|cpu| !allowed.contains(*cpu)) {
+                bail!(
+                    "execution pool '{name}' uses CPU {cpu}, which the host may not run on (allowed: {allowed})"
+                );
+            }
+        } else {
+            warn!(
+                pool = name,
+                "CPU pinning and NUMA memory policies are only supported on Linux, the execution pool runs unpinned"
+            );
+        }
+
+        Self::start(name, config, threads, None)
+    }
+
+    /// Starts `threads` unpinned threads placed in `cgroup`, which limits
+    /// their CPU, see [`super::cgroup`].
+    pub(crate) fn in_cgroup(
+        name: &str,
+        threads: usize,
+        cgroup: ComponentCgroup,
+    ) -> anyhow::Result<Self> {
+        Self::start(
+            name,
+            ExecutionPoolConfig::default(),
+            threads.max(1),
+            Some(cgroup),
+        )
+    }
+
+    fn start(
+        name: &str,
+        config: ExecutionPoolConfig,
+        threads: usize,
+        cgroup: Option<ComponentCgroup>,
+    ) -> anyhow::Result<Self> {
+        let cpus = config.cpus.clone();
+        let numa_node = config.numa_node;
+        let cgroup_path = cgroup.as_ref().map(|cgroup| cgroup.path().to_path_buf());
+        let pool = Arc::<str>::from(name);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(threads)
+            .thread_name(format!("wash-pool-{name}"))
+            .on_thread_start(move || {
+                if !cfg!(target_os = "linux") {
+                    return;
+                }
+                if !cpus.is_empty()
+                    && let Err(e) = sys::pin_current_thread(&cpus)
+                {
+                    warn!(pool = %pool, err = %e, "failed to pin execution pool thread");
+                }
+                if let Some(node) = numa_node
+                    && let Err(e) = sys::prefer_numa_node(node)
+                {
+                    warn!(pool = %pool, node, err = %e, "failed to prefer NUMA-local memory");
+                }
+                if let Some(path) = &cgroup_path
+                    && let Err(e) = cgroup::join(path)
+                {
+                    warn!(pool = %pool, err = %e, "failed to move execution pool thread into its cgroup");
+                }
+            })
+            .enable_all()
+            .build()
+            .with_context(|| format!("failed to start execution pool '{name}'"))?;
+
+        Ok(Self {
+            name: name.into(),
+            config,
+            threads,
+            busy: AtomicUsize::new(0),
+            runtime: Some(runtime),
+            cgroup,
+        })
+    }
+
+    /// Returns the name of the pool.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns where the pool's threads run.
+    pub fn config(&self) -> &ExecutionPoolConfig {
+        &self.config
+    }
+
+    /// Returns the number of worker threads of the pool.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the number of futures running on the pool through
+    /// [`Self::run`], i.e. the invocations of its components.
+    pub fn busy(&self) -> usize {
+        self.busy.load(Ordering::Relaxed)
+    }
+
+    /// Spawns `future` on the pool's threads.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.runtime
+            .as_ref()
+            .expect("execution pool runtime is only taken on drop")
+            .spawn(future)
+    }
+
+    /// Runs `future` on the pool's threads and returns its output. Dropping
+    /// the returned future cancels it, like dropping `future` itself would.
+    ///
+    /// # Errors
+    /// Returns an error if `future` panicked.
+    pub async fn run<F, T>(&self, future: F) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        struct AbortOnDrop<'a, T>(JoinHandle<T>, &'a AtomicUsize);
+        impl<T> Drop for AbortOnDrop<'_, T> {
+            fn drop(&mut self) {
+                self.0.abort();
+                self.1.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        self.busy.fetch_add(1, Ordering::Relaxed);
+        let mut task = AbortOnDrop(self.spawn(future), &self.busy);
+        (&mut task.0)
+            .await
+            .with_context(|| format!("task on execution pool '{}' failed", self.name))?
+    }
+}
+
+impl fmt::Debug for ExecutionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutionPool")
+            .field("name", &self.name)
+            .field("config", &self.config)
+            .field("threads", &self.threads)
+            .field("cgroup", &self.cgroup)
+            .finish()
+    }
+}
+
+impl Drop for ExecutionPool {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks until its tasks finish, which panics when
+        // the pool is dropped from async code
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+
+    use super::CpuSet;
+
+    /// `MPOL_PREFERRED` from `linux/mempolicy.h`
+    const MPOL_PREFERRED: libc::c_int = 1;
+    /// Largest NUMA node a memory policy can name, matching the kernel's default `MAX_NUMNODES`
+    const MAX_NUMA_NODES: usize = 1024;
+
+    /// Returns the CPUs the current thread may run on.
+    pub(super) fn allowed_cpus() -> io::Result<CpuSet> {
+        // SAFETY: `cpu_set_t` is a plain bitmask, valid when zeroed
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        // SAFETY: `set` is a valid `cpu_set_t` of the given size
+        let rc =
+            unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            // SAFETY: `cpu` is within the bounds of `set`
+            .filter(|cpu| unsafe { libc::CPU_ISSET(*cpu, &set) })
+            .collect())
+    }
+
+    /// Restricts the current thread to `cpus`.
+    pub(super) fn pin_current_thread(cpus: &CpuSet) -> io::Result<()> {
+        // SAFETY: `cpu_set_t` is a plain bitmask, valid when zeroed
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for cpu in cpus.iter() {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU {cpu} is out of range"),
+                ));
+            }
+            // SAFETY: `cpu` is within the bounds of `set`
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        // SAFETY: `set` is a valid `cpu_set_t` of the given size
+        let rc =
+            unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Makes the current thread prefer allocating memory from NUMA node
+    /// `node`, falling back to other nodes when it is exhausted.
+    pub(super) fn prefer_numa_node(node: u32) -> io::Result<()> {
+        const BITS: usize = libc::c_ulong::BITS as usize;
+        let node = node as usize;
+        if node >= MAX_NUMA_NODES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("NUMA node {node} is out of range"),
+            ));
+        }
+        let mut mask = [0 as libc::c_ulong; MAX_NUMA_NODES / BITS];
+        mask[node / BITS] |= 1 << (node % BITS);
+        // The kernel ignores the last bit of the mask, so one more is passed
+        // SAFETY: `mask` holds `MAX_NUMA_NODES` bits and outlives the call
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_set_mempolicy,
+                MPOL_PREFERRED,
+                mask.as_ptr(),
+                (MAX_NUMA_NODES + 1) as libc::c_ulong,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+
+    use super::CpuSet;
+
+    pub(super) fn allowed_cpus() -> io::Result<CpuSet> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn pin_current_thread(_cpus: &CpuSet) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn prefer_numa_node(_node: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_set() {
+        let cpus: CpuSet = " 0-3, 8,10-11 ".parse().unwrap();
+        assert_eq!(cpus.iter().collect::<Vec<_>>(), [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(cpus.to_string(), "0-3,8,10-11");
+        assert_eq!("5".parse::<CpuSet>().unwrap().to_string(), "5");
+
+        for invalid in ["", "a", "3-1", "1,,2", "-1"] {
+            assert!(invalid.parse::<CpuSet>().is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execution_pool() {
+        // Pin to a CPU the test may run on
+        let cpu = if cfg!(target_os = "linux") {
+            sys::allowed_cpus().unwrap().iter().next().unwrap()
+        } else {
+            0
+        };
+        let pool =
+            ExecutionPool::new("test", ExecutionPoolConfig::new(CpuSet::from_iter([cpu]))).unwrap();
+        let thread = pool
+            .run(async { anyhow::Ok(std::thread::current().name().map(str::to_string)) })
+            .await
+            .unwrap();
+        assert_eq!(thread.as_deref(), Some("wash-pool-test"));
+        assert_eq!(pool.threads(), 1);
+        assert_eq!(pool.busy(), 0);
+        #[cfg(target_os = "linux")]
+        {
+            let pinned = pool.run(async { Ok(sys::allowed_cpus()?) }).await.unwrap();
+            assert_eq!(pinned, CpuSet::from_iter([cpu]));
+        }
+
+        let err =
+            ExecutionPool::new("empty", ExecutionPoolConfig::new(CpuSet::default())).unwrap_err();
+        assert_eq!(err.to_string(), "execution pool 'empty' has no CPUs");
+        // Dropping a pool from async code must not panic
+        drop(pool);
+    }
+}
@@ -0,0 +1,236 @@
+//! Pre-initialized instances of components.
+//!
+//! Instantiating a component and running its initialization on the first
+//! invocation of a rarely invoked route makes that invocation slow. A
+//! component declaring [`PreInit`] has its instances created ahead of its
+//! invocations instead:
+//!
+//! - When the workload starts, the host instantiates `ready` instances of the
+//!   component and runs its pre-initialization `export` on each, e.g. a
+//!   warm-up call that fills caches or parses configuration. The workload
+//!   fails to start if any of them fails.
+//! - An invocation takes a pre-initialized instance if one is ready, and only
+//!   instantiates the component itself otherwise. The host then prepares a
+//!   replacement in the background, so the next invocation finds one ready.
+//! - A taken instance serves a single invocation, like any other, and its
+//!   invocation timeout and metered duration start when it is taken. The
+//!   fuel its pre-initialization consumed isn't counted.
+//!
+//! Wasmtime can't copy the state of a live instance, so every ready instance
+//! runs the pre-initialization itself, off the path of invocations. With the
+//! pooling allocator, the linear memories of new instances are mapped
+//! copy-on-write from the component's initial state, so the instantiation
+//! itself is cheap. Components whose initialization should be snapshotted
+//! into their initial state can be pre-initialized with `wizer` before they
+//! are deployed.
+//!
+//! Pre-initialized instances hold their memory while they wait, and count
+//! towards the instance quotas of their workload, see [`crate::host::limits`].
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use wasmtime::Store;
+use wasmtime::component::Instance;
+
+use crate::engine::ctx::Ctx;
+use crate::types::PreInit;
+
+/// An instance of a component created, and initialized, ahead of its
+/// invocation.
+pub struct PreInitialized {
+    pub store: Store<Ctx>,
+    pub instance: Instance,
+}
+
+impl PreInitialized {
+    /// Starts the invocation of the instance, see the [module docs](self).
+    pub(crate) fn start(mut self) -> Self {
+        self.store.data_mut().restart_invocation();
+        self
+    }
+}
+
+/// The pre-initialized instances of a component, see the
+/// [module docs](self).
+pub struct PreInitPool {
+    export: Option<String>,
+    target: usize,
+    ready: Mutex<Vec<PreInitialized>>,
+    /// Instances being prepared
+    preparing: AtomicUsize,
+    closed: AtomicBool,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// The instances of a [`PreInitPool`], see [`PreInitPool::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreInitStats {
+    /// Instances ready to be taken
+    pub ready: usize,
+    /// Invocations that took a pre-initialized instance
+    pub hits: u64,
+    /// Invocations that found none ready
+    pub misses: u64,
+}
+
+impl std::fmt::Debug for PreInitPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreInitPool")
+            .field("export", &self.export)
+            .field("target", &self.target)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl PreInitPool {
+    pub fn new(config: &PreInit) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.ready > 0,
+            "pre-initialization must keep at least one instance ready"
+        );
+        if let Some(export) = &config.export {
+            anyhow::ensure!(
+                !export.trim().is_empty(),
+                "pre-initialization export must not be empty"
+            );
+        }
+        Ok(Self {
+            export: config.export.clone(),
+            target: config.ready as usize,
+            ready: Mutex::default(),
+            preparing: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// The export run on each instance before it serves, if any.
+    pub fn export(&self) -> Option<&str> {
+        self.export.as_deref()
+    }
+
+    /// Takes a ready instance, if there is one.
+    pub(crate) fn take(&self) -> Option<PreInitialized> {
+        let taken = self.lock().pop();
+        match &taken {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        taken
+    }
+
+    /// Reserves the preparation of the instances the pool lacks, returning
+    /// how many to prepare. Each must be handed back with [`Self::put`] or
+    /// [`Self::abandon`].
+    pub(crate) fn reserve(&self) -> usize {
+        if self.closed.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let ready = self.lock().len();
+        let mut reserved = 0;
+        let _ = self
+            .preparing
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |preparing| {
+                reserved = self.target.saturating_sub(ready + preparing);
+                Some(preparing + reserved)
+            });
+        reserved
+    }
+
+    /// Adds a prepared instance, dropping it if the pool was closed.
+    pub(crate) fn put(&self, instance: PreInitialized) {
+        self.preparing.fetch_sub(1, Ordering::Relaxed);
+        if !self.closed.load(Ordering::Relaxed) {
+            self.lock().push(instance);
+        }
+    }
+
+    /// Gives up on preparing a reserved instance.
+    pub(crate) fn abandon(&self) {
+        self.preparing.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Drops the ready instances and stops preparing new ones, once the
+    /// workload stops.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.lock().clear();
+    }
+
+    pub fn stats(&self) -> PreInitStats {
+        PreInitStats {
+            ready: self.lock().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<PreInitialized>> {
+        self.ready
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(ready: u32) -> PreInitPool {
+        PreInitPool::new(&PreInit {
+            export: Some("warm-up".to_string()),
+            ready,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_validates() {
+        assert!(
+            PreInitPool::new(&PreInit {
+                export: None,
+                ready: 0
+            })
+            .is_err()
+        );
+        assert!(
+            PreInitPool::new(&PreInit {
+                export: Some(" ".to_string()),
+                ready: 1
+            })
+            .is_err()
+        );
+        assert_eq!(pool(2).export(), Some("warm-up"));
+    }
+
+    #[test]
+    fn test_reserve_counts_instances_being_prepared() {
+        let pool = pool(3);
+        assert_eq!(pool.reserve(), 3);
+        // Every missing instance is already being prepared
+        assert_eq!(pool.reserve(), 0);
+        pool.abandon();
+        assert_eq!(pool.reserve(), 1);
+        pool.close();
+        pool.abandon();
+        assert_eq!(pool.reserve(), 0);
+    }
+
+    #[test]
+    fn test_take_counts_hits_and_misses() {
+        let pool = pool(1);
+        assert!(pool.take().is_none());
+        assert_eq!(
+            pool.stats(),
+            PreInitStats {
+                ready: 0,
+                hits: 0,
+                misses: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Where components come from.
+//!
+//! Tools building components record what went into them in custom sections:
+//!
+//! - `producers` lists the languages, tools and SDKs, with their versions,
+//!   that produced the component, see the
+//!   [tool conventions](https://github.com/WebAssembly/tool-conventions/blob/main/ProducersSection.md).
+//! - `version`, `revision`, `source`, `authors` and `licenses` hold the OCI
+//!   annotations of the component, as written by `wasm-tools metadata add`
+//!   and `wkg`.
+//!
+//! The engine reads them when a workload starts, together with the digest of
+//! the component, so the status of a workload tells what exactly runs without
+//! fetching its artifacts again. Only the sections of the outer component are
+//! read, those of the modules and components it embeds describe these.
+//! Sections that can't be parsed are skipped.
+
+use crate::types::{ComponentProducer, ComponentProvenance};
+
+/// Id of custom sections, in modules as in components
+const CUSTOM_SECTION: u8 = 0;
+
+/// Reads the provenance of component `bytes`. The id of the component is
+/// left empty.
+pub fn read(bytes: &[u8]) -> ComponentProvenance {
+    let mut provenance = ComponentProvenance {
+        digest: super::component_digest(bytes),
+        size_bytes: bytes.len() as u64,
+        ..Default::default()
+    };
+    // Magic number and version, which also tells modules from components
+    let Some(mut sections) = bytes.get(8..).map(Reader) else {
+        return provenance;
+    };
+    while !sections.is_empty() {
+        let Some((id, mut payload)) = sections.section() else {
+            break;
+        };
+        if id != CUSTOM_SECTION {
+            continue;
+        }
+        let Some(name) = payload.string() else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(payload.0);
+        match name {
+            "producers" => provenance.producers = producers(payload).unwrap_or_default(),
+            "version" => provenance.version = Some(text.into_owned()),
+            "revision" => provenance.revision = Some(text.into_owned()),
+            "source" => provenance.source = Some(text.into_owned()),
+            "authors" => provenance.authors = Some(text.into_owned()),
+            "licenses" => provenance.licenses = Some(text.into_owned()),
+            _ => {}
+        }
+    }
+    provenance
+}
+
+/// Parses a `producers` section: fields, each with a name and values.
+fn producers(mut payload: Reader<'_>) -> Option<Vec<ComponentProducer>> {
+    let mut producers = Vec::new();
+    for _ in 0..payload.u32()? {
+        let field = payload.string()?;
+        for _ in 0..payload.u32()? {
+            producers.push(ComponentProducer {
+                field: field.to_string(),
+                name: payload.string()?.to_string(),
+                version: payload.string()?.to_string(),
+            });
+        }
+    }
+    Some(producers)
+}
+
+/// Reads the encoding of a wasm binary, returning `None` past its end.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    /// Reads an unsigned LEB128 number.
+    fn u32(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = *self.bytes(1)?.first()?;
+            value |= u32::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn string(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.bytes(len)?).ok()
+    }
+
+    /// Reads a section id and its payload.
+    fn section(&mut self) -> Option<(u8, Reader<'a>)> {
+        let id = *self.bytes(1)?.first()?;
+        let len = self.u32()? as usize;
+        Some((id, Reader(self.bytes(len)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = vec![s.len() as u8];
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut contents = string(name);
+        contents.extend_from_slice(payload);
+        let mut section = vec![CUSTOM_SECTION, contents.len() as u8];
+        section.extend(contents);
+        section
+    }
+
+    #[test]
+    fn test_read_provenance() {
+        // An empty component with metadata
+        let mut component = b"\0asm\x0d\x00\x01\x00".to_vec();
+        let mut producers = vec![2];
+        producers.extend(string("language"));
+        producers.push(1);
+        producers.extend(string("Rust"));
+        producers.extend(string(""));
+        producers.extend(string("processed-by"));
+        producers.push(2);
+        producers.extend(string("rustc"));
+        producers.extend(string("1.89.0"));
+        producers.extend(string("wit-component"));
+        producers.extend(string("0.235.0"));
+        component.extend(custom_section("producers", &producers));
+        component.extend(custom_section("version", b"1.4.2"));
+        component.extend(custom_section("revision", b"9f1c2e7"));
+        component.extend(custom_section("unrelated", b"\xff"));
+
+        let provenance = read(&component);
+        assert_eq!(
+            provenance.digest,
+            super::super::component_digest(&component)
+        );
+        assert_eq!(provenance.size_bytes, component.len() as u64);
+        assert_eq!(provenance.version.as_deref(), Some("1.4.2"));
+        assert_eq!(provenance.revision.as_deref(), Some("9f1c2e7"));
+        assert_eq!(provenance.source, None);
+        assert_eq!(
+            provenance.producers,
+            vec![
+                ComponentProducer {
+                    field: "language".to_string(),
+                    name: "Rust".to_string(),
+                    version: String::new(),
+                },
+                ComponentProducer {
+                    field: "processed-by".to_string(),
+                    name: "rustc".to_string(),
+                    version: "1.89.0".to_string(),
+                },
+                ComponentProducer {
+                    field: "processed-by".to_string(),
+                    name: "wit-component".to_string(),
+                    version: "0.235.0".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let mut component = b"\0asm\x0d\x00\x01\x00".to_vec();
+        component.extend(custom_section("version", b"1.4.2"));
+        // A section claiming more bytes than there are
+        component.extend([CUSTOM_SECTION, 0x7f, 0x01]);
+        let provenance = read(&component);
+        assert_eq!(provenance.version.as_deref(), Some("1.4.2"));
+
+        assert_eq!(read(b"\0asm").version, None);
+    }
+
+    #[test]
+    fn test_read_fixture() {
+        const HTTP_COUNTER_WASM: &[u8] = include_bytes!("../../tests/fixtures/http_counter.wasm");
+
+        let provenance = read(HTTP_COUNTER_WASM);
+        assert_eq!(provenance.version, None);
+        assert!(
+            provenance.producers.iter().any(
+                |producer| producer.field == "processed-by" && producer.name == "wit-component"
+            )
+        );
+    }
+}
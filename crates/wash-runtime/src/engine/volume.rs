@@ -0,0 +1,333 @@
+//! Host directories backing the volume mounts of components.
+//!
+//! Every volume of a workload resolves to a [`VolumeRoot`], a directory on the
+//! host that is canonicalized when the workload is initialized. Instances only
+//! ever get that exact directory preopened: if it was replaced since, e.g. by a
+//! symbolic link to another part of the host filesystem, creating the instance
+//! fails instead. Within a preopen, WASI resolves paths relative to the
+//! directory and refuses `..` and symbolic links leading out of it, so a guest
+//! cannot reach anything outside of its mounted roots. Mount paths in the guest
+//! must be absolute and free of `.` and `..` components.
+//!
+//! A root may have a size limit, as set for
+//! [`EmptyDirVolume::size_limit_bytes`](crate::types::EmptyDirVolume::size_limit_bytes).
+//! Instances created once the files under the root reach it get the volume
+//! read-only, so guests can still read and remove files but not grow the volume.
+//!
+//! A mount may also limit what the guests of a workload write through it, see
+//! [`VolumeMount::max_bytes_written`] and [`VolumeMount::max_files`]. Its
+//! [`MountQuota`] is shared by every instance of the workload and enforced by
+//! [`crate::engine::filesystem`] as guests write, so a write that would exceed
+//! it fails with the `quota` error code of `wasi:filesystem`.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use anyhow::{Context as _, ensure};
+use tracing::warn;
+
+use crate::host::volumes::dir_size;
+use crate::types::VolumeMount;
+
+/// A host directory mounted into components, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeRoot {
+    /// Canonical path of the directory
+    path: PathBuf,
+    size_limit_bytes: Option<u64>,
+}
+
+impl VolumeRoot {
+    /// Creates a root for the directory at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if `path` does not exist or is not a directory.
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("volume '{}' does not exist", path.display()))?;
+        ensure!(
+            canonical.is_dir(),
+            "volume '{}' is not a directory",
+            path.display()
+        );
+        Ok(Self {
+            path: canonical,
+            size_limit_bytes: None,
+        })
+    }
+
+    /// Limits the size of the files under the root to `limit` bytes.
+    pub fn with_size_limit(mut self, limit: Option<u64>) -> Self {
+        self.size_limit_bytes = limit;
+        self
+    }
+
+    /// Returns the canonical path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size limit of the root, if any.
+    pub fn size_limit_bytes(&self) -> Option<u64> {
+        self.size_limit_bytes
+    }
+
+    /// Checks that the root still is the directory it was created for and
+    /// returns whether it is full, in which case it must be preopened
+    /// read-only.
+    ///
+    /// # Errors
+    /// Returns an error if the directory was removed or now resolves elsewhere.
+    pub(crate) async fn open(&self) -> anyhow::Result<bool> {
+        let current = tokio::fs::canonicalize(&self.path)
+            .await
+            .with_context(|| format!("volume '{}' no longer exists", self.path.display()))?;
+        ensure!(
+            current == self.path,
+            "volume '{}' now resolves to '{}', outside of its mounted root",
+            self.path.display(),
+            current.display()
+        );
+
+        let Some(limit) = self.size_limit_bytes else {
+            return Ok(false);
+        };
+        let path = self.path.clone();
+        let size = tokio::task::spawn_blocking(move || dir_size(&path))
+            .await
+            .context("failed to measure volume")?;
+        if size >= limit {
+            warn!(
+                path = %self.path.display(),
+                size,
+                limit,
+                "volume reached its size limit, mounting it read-only"
+            );
+        }
+        Ok(size >= limit)
+    }
+}
+
+/// The resource a [`MountQuota`] limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaKind {
+    /// Bytes written to files, see [`VolumeMount::max_bytes_written`]
+    BytesWritten,
+    /// Files and directories, see [`VolumeMount::max_files`]
+    Files,
+}
+
+impl QuotaKind {
+    /// Returns the name of the quota, e.g. `bytes_written`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaKind::BytesWritten => "bytes_written",
+            QuotaKind::Files => "files",
+        }
+    }
+}
+
+/// What the guests of a workload wrote through a volume mount, against the
+/// limits of the mount, see the [module docs](self).
+#[derive(Debug)]
+pub struct MountQuota {
+    mount_path: String,
+    max_bytes_written: Option<u64>,
+    max_files: Option<u64>,
+    bytes_written: AtomicU64,
+    files: AtomicU64,
+    bytes_exceeded: AtomicBool,
+    files_exceeded: AtomicBool,
+}
+
+impl MountQuota {
+    /// Creates the quota of `mount`, if it sets limits. The files already
+    /// under `root` count towards [`VolumeMount::max_files`].
+    pub fn new(root: &VolumeRoot, mount: &VolumeMount) -> Option<Arc<Self>> {
+        if mount.max_bytes_written.is_none() && mount.max_files.is_none() {
+            return None;
+        }
+        let files = match mount.max_files {
+            Some(_) => count_entries(root.path()),
+            None => 0,
+        };
+        Some(Arc::new(Self {
+            mount_path: mount.mount_path.clone(),
+            max_bytes_written: mount.max_bytes_written,
+            max_files: mount.max_files,
+            bytes_written: AtomicU64::new(0),
+            files: AtomicU64::new(files),
+            bytes_exceeded: AtomicBool::new(false),
+            files_exceeded: AtomicBool::new(false),
+        }))
+    }
+
+    /// Returns the path the volume is mounted at in the guest.
+    pub fn mount_path(&self) -> &str {
+        &self.mount_path
+    }
+
+    /// Returns the bytes written through the mount so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Returns the files and directories under the mount.
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many more bytes may be written through the mount, if it
+    /// is limited.
+    pub(crate) fn remaining_bytes(&self) -> Option<u64> {
+        self.max_bytes_written
+            .map(|max| max.saturating_sub(self.bytes_written()))
+    }
+
+    /// Counts `bytes` written, unless that would exceed the limit.
+    pub(crate) fn charge_bytes(&self, bytes: u64) -> bool {
+        charge(&self.bytes_written, self.max_bytes_written, bytes)
+    }
+
+    /// Counts a created file or directory, unless that would exceed the
+    /// limit.
+    pub(crate) fn charge_file(&self) -> bool {
+        charge(&self.files, self.max_files, 1)
+    }
+
+    /// Stops counting a removed file or directory.
+    pub(crate) fn release_file(&self) {
+        let _ = self
+            .files
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |files| {
+                Some(files.saturating_sub(1))
+            });
+    }
+
+    /// Records that a guest hit the limit of `kind`, returning `true` the
+    /// first time so the host reports it once.
+    pub(crate) fn exceeded(&self, kind: QuotaKind) -> bool {
+        let flag = match kind {
+            QuotaKind::BytesWritten => &self.bytes_exceeded,
+            QuotaKind::Files => &self.files_exceeded,
+        };
+        !flag.swap(true, Ordering::Relaxed)
+    }
+}
+
+fn charge(counter: &AtomicU64, limit: Option<u64>, amount: u64) -> bool {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(amount)
+                .filter(|total| limit.is_none_or(|limit| *total <= limit))
+        })
+        .is_ok()
+}
+
+/// Counts the files, directories and links under `path`.
+fn count_entries(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => 1 + count_entries(&entry.path()),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Checks that a guest mount path is absolute and has no `.` or `..`
+/// components.
+pub(crate) fn validate_mount_path(mount_path: &str) -> anyhow::Result<()> {
+    let path = Path::new(mount_path);
+    ensure!(
+        path.is_absolute()
+            && path
+                .components()
+                .all(|c| matches!(c, Component::RootDir | Component::Normal(_))),
+        "mount path '{mount_path}' must be absolute and must not contain '.' or '..'"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_mount_path() {
+        for valid in ["/", "/data", "/var/lib/app"] {
+            assert!(validate_mount_path(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["", "data", "./data", "/data/..", "/../etc"] {
+            assert!(validate_mount_path(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = VolumeRoot::new(dir.path())
+            .unwrap()
+            .with_size_limit(Some(4));
+        assert!(!root.open().await.unwrap());
+
+        std::fs::write(dir.path().join("file"), b"hello").unwrap();
+        assert!(root.open().await.unwrap());
+    }
+
+    #[test]
+    fn test_mount_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/file"), b"hello").unwrap();
+        let root = VolumeRoot::new(dir.path()).unwrap();
+        let mut mount = VolumeMount {
+            name: "data".to_string(),
+            mount_path: "/data".to_string(),
+            ..Default::default()
+        };
+        assert!(MountQuota::new(&root, &mount).is_none());
+
+        mount.max_bytes_written = Some(10);
+        mount.max_files = Some(3);
+        let quota = MountQuota::new(&root, &mount).unwrap();
+        assert_eq!(quota.files(), 2);
+
+        assert!(quota.charge_bytes(6));
+        assert!(!quota.charge_bytes(6));
+        assert_eq!(quota.remaining_bytes(), Some(4));
+        assert!(quota.charge_bytes(4));
+        assert_eq!(quota.remaining_bytes(), Some(0));
+
+        assert!(quota.charge_file());
+        assert!(!quota.charge_file());
+        quota.release_file();
+        assert!(quota.charge_file());
+
+        assert!(quota.exceeded(QuotaKind::Files));
+        assert!(!quota.exceeded(QuotaKind::Files));
+        assert!(quota.exceeded(QuotaKind::BytesWritten));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_replaced_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let path = dir.path().join("volume");
+        std::fs::create_dir(&path).unwrap();
+        let root = VolumeRoot::new(&path).unwrap();
+        assert!(root.open().await.is_ok());
+
+        // Swapping the directory for a link to elsewhere must not widen the preopen
+        std::fs::remove_dir(&path).unwrap();
+        std::os::unix::fs::symlink(outside.path(), &path).unwrap();
+        assert!(root.open().await.is_err());
+    }
+}
@@ -3,12 +3,15 @@
 use std::{
     collections::{HashMap, HashSet},
     ops::{Deref, DerefMut},
-    path::PathBuf,
-    sync::Arc,
-    time::Duration,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context as _, bail, ensure};
+use futures::{StreamExt as _, TryStreamExt as _};
 use tokio::{sync::RwLock, task::JoinHandle, time::timeout};
 use tracing::{debug, info, trace, warn};
 use wasmtime::component::{
@@ -18,14 +21,143 @@ use wasmtime_wasi::{DirPerms, FilePerms, WasiCtxBuilder, p2::bindings::CommandPr
 
 use crate::{
     engine::{
+        InvocationTimeout,
+        autoscale::InstancePool,
+        cpu::CpuThrottle,
+        crash_loop::{CrashLoopPolicy, CrashLoopStop, CrashLoopTracker, Transition},
         ctx::Ctx,
+        filesystem::FilesystemQuotas,
+        memory::{self, GrowthTracker, MemoryLeakPolicy},
+        output::{GuestOutput, LogContext},
+        pool::ExecutionPool,
+        preinit::{PreInitPool, PreInitialized},
         value::{lift, lower},
+        volume::{MountQuota, VolumeRoot},
+    },
+    host::{
+        HostError,
+        events::{LifecycleEvent, LifecycleEventType, LifecycleEvents},
+        http::{EgressPolicy, OutboundThrottle},
+        logs::WorkloadLogs,
+        metering::{self, Metering},
+        metrics::WorkloadMetrics,
+    },
+    plugin::{HostPlugin, forward::Forwarding},
+    types::{
+        ComponentProvenance, CrashLoopStatus, DEFAULT_INIT_EXPORT, InterfaceBinding, Job,
+        LocalResources, OutgoingHttpLimits, PoolUtilization, VolumeMount, WorkloadActivity,
     },
-    plugin::HostPlugin,
-    types::{LocalResources, VolumeMount},
-    wit::{WitInterface, WitWorld},
+    wit::{WitInterface, WitWorld, WorldMismatch},
 };
 
+/// Looks up the function `export` of `instance`, given as a function name or
+/// `interface#function`.
+fn export_func(
+    instance: &Instance,
+    store: &mut wasmtime::Store<Ctx>,
+    export: &str,
+) -> anyhow::Result<wasmtime::component::Func> {
+    let (interface, function) = match export.split_once('#') {
+        Some((interface, function)) => (Some(interface), function),
+        None => (None, export),
+    };
+    let interface_idx = match interface {
+        Some(interface) => Some(
+            instance
+                .get_export_index(&mut *store, None, interface)
+                .ok_or_else(|| {
+                    HostError::InvalidWorkload(format!("component does not export '{interface}'"))
+                })?,
+        ),
+        None => None,
+    };
+    let func = instance
+        .get_export_index(&mut *store, interface_idx.as_ref(), function)
+        .and_then(|idx| instance.get_func(&mut *store, idx))
+        .ok_or_else(|| {
+            HostError::InvalidWorkload(format!("component does not export function '{export}'"))
+        })?;
+    Ok(func)
+}
+
+/// Returns whether `host_interfaces` declare `interface`, which a component
+/// imports. A declaration without interfaces covers its whole package.
+fn declares(host_interfaces: &[WitInterface], interface: &WitInterface) -> bool {
+    host_interfaces.iter().any(|declared| {
+        declared.namespace == interface.namespace
+            && declared.package == interface.package
+            && declared.version_compatible(interface)
+            && (declared.interfaces.is_empty()
+                || declared.interfaces.is_superset(&interface.interfaces))
+    })
+}
+
+/// Orders the components of a workload for linking, so every component comes
+/// after the components exporting the interfaces it `imports`, see
+/// [`ResolvedWorkload::link_components`].
+///
+/// # Errors
+/// Returns an error naming the components if they import each other's
+/// interfaces, directly or through other components.
+fn link_order(
+    imports: &HashMap<Arc<str>, Vec<String>>,
+    interface_map: &HashMap<String, Arc<str>>,
+) -> anyhow::Result<Vec<Arc<str>>> {
+    fn visit(
+        id: &Arc<str>,
+        imports: &HashMap<Arc<str>, Vec<String>>,
+        interface_map: &HashMap<String, Arc<str>>,
+        path: &mut Vec<Arc<str>>,
+        order: &mut Vec<Arc<str>>,
+    ) -> anyhow::Result<()> {
+        if order.contains(id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|visiting| visiting == id) {
+            let cycle = path[start..]
+                .iter()
+                .chain([id])
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            bail!("components import each other's interfaces: {cycle}");
+        }
+        let mut exporters: Vec<&Arc<str>> = imports
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| interface_map.get(name))
+            .filter(|exporter| *exporter != id)
+            .collect();
+        exporters.sort();
+        exporters.dedup();
+
+        path.push(id.clone());
+        for exporter in exporters {
+            visit(exporter, imports, interface_map, path, order)?;
+        }
+        path.pop();
+        order.push(id.clone());
+        Ok(())
+    }
+
+    let mut ids: Vec<&Arc<str>> = imports.keys().collect();
+    ids.sort();
+    let mut order = Vec::with_capacity(ids.len());
+    for id in ids {
+        visit(id, imports, interface_map, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Creates the quotas of the `volume_mounts` that set limits.
+fn mount_quotas(volume_mounts: &[(VolumeRoot, VolumeMount)]) -> Vec<Arc<MountQuota>> {
+    volume_mounts
+        .iter()
+        .filter_map(|(root, mount)| MountQuota::new(root, mount))
+        .collect()
+}
+
 /// Type alias for tracking bound plugins with their matched interfaces during binding.
 /// Tuple: (plugin, matched_interfaces, component_ids)
 type BoundPluginWithInterfaces = (
@@ -50,11 +182,36 @@ pub struct WorkloadMetadata {
     /// The wasmtime [`Linker`] used to instantiate the component
     linker: Linker<Ctx>,
     /// The volume mounts requested by this component
-    volume_mounts: Vec<(PathBuf, VolumeMount)>,
+    volume_mounts: Vec<(VolumeRoot, VolumeMount)>,
+    /// The quotas of the volume mounts that set limits, shared by all
+    /// instances, see [`crate::engine::filesystem`]
+    mount_quotas: Vec<Arc<MountQuota>>,
     /// The local resources requested by this component
     local_resources: LocalResources,
     /// The plugins available to this component
     plugins: Option<HashMap<&'static str, Arc<dyn HostPlugin + Send + Sync>>>,
+    /// Epoch ticks between async yields, when the engine uses epoch interruption
+    epoch_yield_ticks: Option<u64>,
+    /// How long an invocation may run before it is interrupted
+    invocation_timeout: Option<Duration>,
+    /// Limits the guests of this component to its `cpu_limit` with fuel
+    cpu_throttle: Option<Arc<CpuThrottle>>,
+    /// Largest linear memory the host allows, regardless of the component's own limit
+    max_memory_bytes: Option<usize>,
+    /// Limits on the outgoing HTTP requests of this component
+    outgoing_http: OutgoingHttpLimits,
+    /// The outbound limits of the workload, shared by all of its components
+    outbound_throttle: Option<Arc<OutboundThrottle>>,
+    /// Dedicated threads the guests of this component run on, if any
+    execution_pool: Option<Arc<ExecutionPool>>,
+    /// Bounds the instances running at once, if the component autoscales
+    instance_pool: Option<Arc<InstancePool>>,
+    /// Instances initialized ahead of invocations, see [`crate::engine::preinit`]
+    pre_init: Option<Arc<PreInitPool>>,
+    /// Where this component comes from
+    provenance: Arc<ComponentProvenance>,
+    /// Meters the invocations of this component, if enabled
+    metering: Option<Metering>,
 }
 
 impl WorkloadMetadata {
@@ -88,11 +245,22 @@ impl WorkloadMetadata {
         &mut self.linker
     }
 
+    /// Returns the volumes mounted into this component, with the host
+    /// directories backing them.
+    pub fn volume_mounts(&self) -> &[(VolumeRoot, VolumeMount)] {
+        &self.volume_mounts
+    }
+
     /// Returns a reference to component local resources.
     pub fn local_resources(&self) -> &LocalResources {
         &self.local_resources
     }
 
+    /// Returns the execution pool the guests of this component run on, if any.
+    pub fn execution_pool(&self) -> Option<&Arc<ExecutionPool>> {
+        self.execution_pool.as_ref()
+    }
+
     /// Returns a reference to the plugins associated with this component.
     pub fn plugins(&self) -> &Option<HashMap<&'static str, Arc<dyn HostPlugin + Send + Sync>>> {
         &self.plugins
@@ -133,6 +301,22 @@ impl WorkloadMetadata {
             .collect::<Vec<_>>())
     }
 
+    /// Extracts the [`ComponentItem::ComponentInstance`]s that the component imports.
+    pub fn component_imports(&self) -> anyhow::Result<Vec<(String, ComponentItem)>> {
+        Ok(self
+            .component
+            .component_type()
+            .imports(self.component.engine())
+            .filter_map(|(name, item)| {
+                if matches!(item, ComponentItem::ComponentInstance(_)) {
+                    Some((name.to_string(), item))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>())
+    }
+
     pub fn uses_wasi_http(&self) -> bool {
         crate::engine::uses_wasi_http(&self.component)
     }
@@ -147,58 +331,45 @@ impl WorkloadMetadata {
 
     /// Computes and returns the [`WitWorld`] of this component.
     pub fn world(&self) -> WitWorld {
-        let mut imports = HashMap::new();
-        let mut exports = HashMap::new();
+        crate::engine::component_world(&self.component)
+    }
 
-        // Iterate over imports, merging interfaces when namespace:package@version matches
-        for (import_name, import_item) in self
-            .component
-            .component_type()
-            .imports(self.component.engine())
-        {
-            if let ComponentItem::ComponentInstance(_) = import_item {
-                let interface = WitInterface::from(import_name);
-                let k = interface.instance();
-                imports
-                    .entry(k)
-                    .and_modify(|existing: &mut WitInterface| {
-                        existing.merge(&interface);
-                    })
-                    .or_insert(interface);
-            } else {
-                debug!(
-                    import_name,
-                    "imported item is not a component instance, skipping"
-                );
-            }
-        }
+    /// Returns where this component comes from.
+    pub fn provenance(&self) -> &ComponentProvenance {
+        &self.provenance
+    }
 
-        // Iterate over exports, merging interfaces when namespace:package@version matches
-        for (export_name, export_item) in self
-            .component
-            .component_type()
-            .exports(self.component.engine())
-        {
-            if let ComponentItem::ComponentInstance(_) = export_item {
-                let interface = WitInterface::from(export_name);
-                let k = interface.instance();
-                exports
-                    .entry(k)
-                    .and_modify(|existing: &mut WitInterface| {
-                        existing.merge(&interface);
-                    })
-                    .or_insert(interface);
-            } else {
-                debug!(
-                    export_name,
-                    "exported item is not a component instance, skipping"
-                );
-            }
-        }
+    fn set_provenance(&mut self, provenance: ComponentProvenance) {
+        self.provenance = Arc::new(ComponentProvenance {
+            component_id: self.id.to_string(),
+            ..provenance
+        });
+    }
+}
 
-        WitWorld {
-            imports: imports.into_values().collect(),
-            exports: exports.into_values().collect(),
+/// What happens to the running instance of a service when its configuration
+/// changes, set with the `config_change` key of its local resources config.
+/// Components always read the new configuration at their next invocation,
+/// as every invocation runs on a fresh instance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigChangePolicy {
+    /// The instance is replaced with a fresh one, which reads the new
+    /// configuration when it starts (`recycle`)
+    #[default]
+    Recycle,
+    /// The instance keeps running and sees the new values the next time it
+    /// reads them (`live`)
+    Live,
+}
+
+impl std::str::FromStr for ConfigChangePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "recycle" => Ok(Self::Recycle),
+            "live" => Ok(Self::Live),
+            _ => bail!("config change policy must be 'recycle' or 'live'"),
         }
     }
 }
@@ -213,6 +384,14 @@ pub struct WorkloadService {
     max_restarts: u64,
     /// The [`JoinHandle`] for the running service
     handle: Option<Arc<JoinHandle<()>>>,
+    /// When the service instance is flagged for leaking memory
+    memory_leak_policy: Option<MemoryLeakPolicy>,
+    /// Why the running instance is suspected of leaking memory, if it is
+    memory_warning: Arc<std::sync::Mutex<Option<String>>>,
+    /// What happens to the running instance when its configuration changes
+    config_change: ConfigChangePolicy,
+    /// Notified to replace the running instance with a fresh one
+    recycle: Arc<tokio::sync::Notify>,
 }
 
 impl WorkloadService {
@@ -225,10 +404,11 @@ impl WorkloadService {
         workload_namespace: impl Into<Arc<str>>,
         component: Component,
         linker: Linker<Ctx>,
-        volume_mounts: Vec<(PathBuf, VolumeMount)>,
+        volume_mounts: Vec<(VolumeRoot, VolumeMount)>,
         local_resources: LocalResources,
         max_restarts: u64,
     ) -> Self {
+        let mount_quotas = mount_quotas(&volume_mounts);
         Self {
             metadata: WorkloadMetadata {
                 id: uuid::Uuid::new_v4().to_string().into(),
@@ -238,11 +418,27 @@ impl WorkloadService {
                 component,
                 linker,
                 volume_mounts,
+                mount_quotas,
                 local_resources,
                 plugins: None,
+                epoch_yield_ticks: None,
+                invocation_timeout: None,
+                cpu_throttle: None,
+                max_memory_bytes: None,
+                outgoing_http: OutgoingHttpLimits::default(),
+                outbound_throttle: None,
+                execution_pool: None,
+                instance_pool: None,
+                pre_init: None,
+                provenance: Arc::default(),
+                metering: None,
             },
             handle: None,
             max_restarts,
+            memory_leak_policy: None,
+            memory_warning: Arc::default(),
+            config_change: ConfigChangePolicy::default(),
+            recycle: Arc::default(),
         }
     }
 
@@ -258,6 +454,69 @@ impl WorkloadService {
     pub fn is_running(&self) -> bool {
         self.handle.is_some()
     }
+
+    /// Makes stores of this service yield to the async executor every `ticks`
+    /// epoch ticks. Must be set when the engine uses epoch interruption.
+    pub fn with_epoch_yield_ticks(mut self, ticks: Option<u64>) -> Self {
+        self.metadata.epoch_yield_ticks = ticks;
+        self
+    }
+
+    /// Limits the guests of this service to its `cpu_limit` with `throttle`,
+    /// see [`crate::engine::cpu`]. Only enforced when the engine uses epoch
+    /// interruption.
+    pub fn with_cpu_throttle(mut self, throttle: Option<Arc<CpuThrottle>>) -> Self {
+        self.metadata.cpu_throttle = throttle;
+        self
+    }
+
+    /// Caps the linear memories of this service at `bytes` in total, on top
+    /// of the `memory_limit_mb` of its local resources.
+    pub fn with_max_memory_bytes(mut self, bytes: Option<usize>) -> Self {
+        self.metadata.max_memory_bytes = bytes;
+        self
+    }
+
+    /// Samples the memory of the service instance and flags it according to
+    /// `policy`.
+    pub fn with_memory_leak_policy(mut self, policy: Option<MemoryLeakPolicy>) -> Self {
+        self.memory_leak_policy = policy;
+        self
+    }
+
+    /// Decides what happens to the running instance when the configuration
+    /// of this service changes.
+    pub fn with_config_change_policy(mut self, policy: ConfigChangePolicy) -> Self {
+        self.config_change = policy;
+        self
+    }
+
+    /// Holds the outbound connections of this service to the limits of its
+    /// workload.
+    pub fn with_outbound_throttle(mut self, throttle: Option<Arc<OutboundThrottle>>) -> Self {
+        self.metadata.outbound_throttle = throttle;
+        self
+    }
+
+    /// Runs this service on the threads of `pool` instead of the host's.
+    pub fn with_execution_pool(mut self, pool: Option<Arc<ExecutionPool>>) -> Self {
+        self.metadata.execution_pool = pool;
+        self
+    }
+
+    /// Records where this service comes from, see
+    /// [`crate::engine::provenance`].
+    pub fn with_provenance(mut self, provenance: ComponentProvenance) -> Self {
+        self.metadata.set_provenance(provenance);
+        self
+    }
+
+    /// Meters the lifetime of this service as an invocation, see
+    /// [`crate::host::metering`].
+    pub fn with_metering(mut self, metering: Option<Metering>) -> Self {
+        self.metadata.metering = metering;
+        self
+    }
 }
 
 /// A [`WorkloadComponent`] is a component that is part of a workload.
@@ -274,6 +533,12 @@ pub struct WorkloadComponent {
     pool_size: usize,
     /// The maximum number of concurrent invocations allowed for this component
     max_invocations: usize,
+    /// The export to run once before the workload serves, if this is an init component
+    init_export: Option<Arc<str>>,
+    /// How to run this component to completion, if it is a job component
+    job: Option<Job>,
+    /// Whether this component handles HTTP requests before the serving component
+    middleware: bool,
 }
 
 impl WorkloadComponent {
@@ -285,9 +550,10 @@ impl WorkloadComponent {
         workload_namespace: impl Into<Arc<str>>,
         component: Component,
         linker: Linker<Ctx>,
-        volume_mounts: Vec<(PathBuf, VolumeMount)>,
+        volume_mounts: Vec<(VolumeRoot, VolumeMount)>,
         local_resources: LocalResources,
     ) -> Self {
+        let mount_quotas = mount_quotas(&volume_mounts);
         Self {
             metadata: WorkloadMetadata {
                 id: uuid::Uuid::new_v4().to_string().into(),
@@ -297,12 +563,27 @@ impl WorkloadComponent {
                 component,
                 linker,
                 volume_mounts,
+                mount_quotas,
                 local_resources,
                 plugins: None,
+                epoch_yield_ticks: None,
+                invocation_timeout: None,
+                cpu_throttle: None,
+                max_memory_bytes: None,
+                outgoing_http: OutgoingHttpLimits::default(),
+                outbound_throttle: None,
+                execution_pool: None,
+                instance_pool: None,
+                pre_init: None,
+                provenance: Arc::default(),
+                metering: None,
             },
             // TODO: Implement pooling and instance limits
             pool_size: 0,
             max_invocations: 0,
+            init_export: None,
+            job: None,
+            middleware: false,
         }
     }
 
@@ -315,6 +596,125 @@ impl WorkloadComponent {
     pub fn metadata(&self) -> &WorkloadMetadata {
         &self.metadata
     }
+
+    /// Makes stores of this component yield to the async executor every `ticks`
+    /// epoch ticks. Must be set when the engine uses epoch interruption.
+    pub fn with_epoch_yield_ticks(mut self, ticks: Option<u64>) -> Self {
+        self.metadata.epoch_yield_ticks = ticks;
+        self
+    }
+
+    /// Interrupts invocations of this component still running after
+    /// `timeout`. Only enforced when the engine uses epoch interruption.
+    pub fn with_invocation_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.metadata.invocation_timeout = timeout;
+        self
+    }
+
+    /// Limits the guests of this component to its `cpu_limit` with
+    /// `throttle`, see [`crate::engine::cpu`]. Only enforced when the engine
+    /// uses epoch interruption.
+    pub fn with_cpu_throttle(mut self, throttle: Option<Arc<CpuThrottle>>) -> Self {
+        self.metadata.cpu_throttle = throttle;
+        self
+    }
+
+    /// Caps the linear memories of this component at `bytes` in total, on top of the
+    /// `memory_limit_mb` of its local resources.
+    pub fn with_max_memory_bytes(mut self, bytes: Option<usize>) -> Self {
+        self.metadata.max_memory_bytes = bytes;
+        self
+    }
+
+    /// Limits the outgoing HTTP requests of this component.
+    pub fn with_outgoing_http_limits(mut self, limits: OutgoingHttpLimits) -> Self {
+        self.metadata.outgoing_http = limits;
+        self
+    }
+
+    /// Holds the outbound connections of this component to the limits of its
+    /// workload.
+    pub fn with_outbound_throttle(mut self, throttle: Option<Arc<OutboundThrottle>>) -> Self {
+        self.metadata.outbound_throttle = throttle;
+        self
+    }
+
+    /// Runs the guests of this component on the threads of `pool` instead of
+    /// the host's.
+    pub fn with_execution_pool(mut self, pool: Option<Arc<ExecutionPool>>) -> Self {
+        self.metadata.execution_pool = pool;
+        self
+    }
+
+    /// Scales the instances of this component with `pool`, see
+    /// [`crate::engine::autoscale`].
+    pub fn with_instance_pool(mut self, pool: Option<Arc<InstancePool>>) -> Self {
+        self.metadata.instance_pool = pool;
+        self
+    }
+
+    /// Keeps instances of this component initialized ahead of its
+    /// invocations in `pool`, see [`crate::engine::preinit`].
+    pub fn with_pre_init(mut self, pool: Option<Arc<PreInitPool>>) -> Self {
+        self.metadata.pre_init = pool;
+        self
+    }
+
+    /// Records where this component comes from, see
+    /// [`crate::engine::provenance`].
+    pub fn with_provenance(mut self, provenance: ComponentProvenance) -> Self {
+        self.metadata.set_provenance(provenance);
+        self
+    }
+
+    /// Meters the invocations of this component, see
+    /// [`crate::host::metering`].
+    pub fn with_metering(mut self, metering: Option<Metering>) -> Self {
+        self.metadata.metering = metering;
+        self
+    }
+
+    /// Makes this an init component that runs `export` once, before any
+    /// serving component of the workload is routed to.
+    pub fn with_init_export(mut self, export: Option<String>) -> Self {
+        self.init_export = export.map(Into::into);
+        self
+    }
+
+    /// Returns the export this component runs on startup, if it is an init component.
+    pub fn init_export(&self) -> Option<&str> {
+        self.init_export.as_deref()
+    }
+
+    /// Makes this a job component that runs to completion as described by `job`
+    /// once the workload has started, instead of serving invocations.
+    pub fn with_job(mut self, job: Option<Job>) -> Self {
+        self.job = job;
+        self
+    }
+
+    /// Returns how this component runs to completion, if it is a job component.
+    pub fn job(&self) -> Option<&Job> {
+        self.job.as_ref()
+    }
+
+    /// Makes this the middleware of the workload, invoked before the serving
+    /// component for every HTTP request, see [`crate::host::http::middleware`].
+    pub fn with_middleware(mut self, middleware: bool) -> Self {
+        self.middleware = middleware;
+        self
+    }
+
+    /// Whether this component is the middleware of the workload.
+    pub fn is_middleware(&self) -> bool {
+        self.middleware
+    }
+
+    /// Whether invocations are routed to this component, which is not the case
+    /// for init, job and middleware components.
+    pub fn is_serving(&self) -> bool {
+        self.init_export.is_none() && self.job.is_none() && !self.middleware
+    }
 }
 
 impl std::fmt::Debug for WorkloadComponent {
@@ -325,6 +725,8 @@ impl std::fmt::Debug for WorkloadComponent {
             .field("volume_mounts", &self.metadata.volume_mounts)
             .field("pool_size", &self.pool_size)
             .field("max_invocations", &self.max_invocations)
+            .field("init_export", &self.init_export)
+            .field("job", &self.job)
             .finish()
     }
 }
@@ -395,62 +797,745 @@ pub struct ResolvedWorkload {
     service: Option<WorkloadService>,
     /// The requested host [`WitInterface`]s to resolve this workload
     host_interfaces: Vec<WitInterface>,
+    /// Where the service and components come from, see [`Self::provenance`]
+    provenance: Arc<[ComponentProvenance]>,
+    /// The plugins the components were bound to, see [`Self::activity`]
+    interfaces: Arc<[InterfaceBinding]>,
+    /// When the workload was resolved
+    started_at: Instant,
+    /// Counts the invocations of [`Self::run_invocation`]
+    invocations: Arc<InvocationStats>,
+    /// Records the invocations of the components, see [`crate::host::metrics`]
+    metrics: Option<Arc<WorkloadMetrics>>,
+    /// Receives the events of the instances and invocations of the components
+    events: LifecycleEvents,
+    /// Captures what the components log, see [`crate::host::logs`]
+    logs: Option<WorkloadLogs>,
+    /// Counts the consecutive failures of the components, see
+    /// [`crate::engine::crash_loop`]
+    crash_loops: Option<Arc<CrashLoopTracker>>,
+    /// Asks the host to stop the workload once a component keeps failing
+    crash_loop_stops: Option<tokio::sync::mpsc::UnboundedSender<CrashLoopStop>>,
+    /// The live instances of the components, see [`crate::engine::debug`]
+    #[cfg(feature = "instance-debug")]
+    instances: Arc<crate::engine::debug::InstanceRegistry>,
 }
 
-impl ResolvedWorkload {
-    /// Executes the service, if present, and returns whether it was run.
-    pub(crate) async fn execute_service(&mut self) -> anyhow::Result<bool> {
-        let service = self
-            .service
-            .as_mut()
-            .map(|s| (s.pre_instantiate(), s.max_restarts));
-
-        if let Some((Ok(pre), mut max_restarts)) = service {
-            // This will always be present since we just checked above, but we need this structure
-            // to only borrow the service metadata
-            let mut store = if let Some(service) = self.service.as_ref() {
-                self.new_store_from_metadata(&service.metadata).await?
-            } else {
-                bail!("service unexpectedly missing during execution");
-            };
-            let instance = pre.instantiate_async(&mut store).await?;
-            let handle = tokio::spawn(async move {
-                loop {
-                    if let Err(e) = instance.wasi_cli_run().call_run(&mut store).await {
-                        warn!(err = %e, retries = max_restarts, "service execution failed");
-                        if max_restarts == 0 {
-                            info!("max restarts reached, service will not be restarted");
-                            break;
-                        }
-                    } else {
-                        info!("service executed successfully");
-                        break;
+/// Invocations of the components of a workload, see
+/// [`ResolvedWorkload::activity`].
+#[derive(Debug, Default)]
+struct InvocationStats {
+    total: AtomicU64,
+    failed: AtomicU64,
+    in_flight: AtomicU64,
+    /// Time the finished invocations took, in microseconds
+    time_us: AtomicU64,
+    /// Fuel the finished invocations consumed, when the engine consumes fuel
+    fuel: Arc<AtomicU64>,
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl ResolvedWorkload {
+    /// Executes the service, if present, and returns whether it was run. Each
+    /// time the service exits with an error, a
+    /// [`LifecycleEventType::ServiceCrashed`] event is emitted to `events`.
+    ///
+    /// The service is restarted on a fresh instance up to its maximum number
+    /// of restarts. With a [`CrashLoopPolicy`], a service that keeps crashing
+    /// or failing to instantiate waits for its backoff before each restart.
+    /// With a [`MemoryLeakPolicy`], an instance whose memory keeps growing is
+    /// flagged and, if the policy says so, replaced with a fresh instance
+    /// without counting as a restart.
+    pub(crate) async fn execute_service(
+        &mut self,
+        events: LifecycleEvents,
+    ) -> anyhow::Result<bool> {
+        let Some(service) = self.service.as_mut() else {
+            return Ok(false);
+        };
+        let Ok(pre) = service.pre_instantiate() else {
+            return Ok(false);
+        };
+        let metadata = service.metadata.clone();
+        let mut max_restarts = service.max_restarts;
+        let policy = service.memory_leak_policy;
+        let memory_warning = service.memory_warning.clone();
+        let recycle = service.recycle.clone();
+
+        // The first instance is created here so that failing to instantiate fails the workload
+        let mut store = self.new_store_from_metadata(&metadata).await?;
+        let mut instance = pre.instantiate_async(&mut store).await?;
+
+        let workload = self.clone();
+        let execution_pool = metadata.execution_pool().cloned();
+        let task = async move {
+            let event = |event_type, message: String| {
+                LifecycleEvent::new(event_type, workload.id(), message)
+                    .with_workload_name(workload.name())
+                    .with_component_id(metadata.id())
+            };
+            loop {
+                let usage = store.data().limits.usage();
+                // `Err` with the reason when the instance is recycled
+                let exit = {
+                    store.data().set_invocation("wasi:cli/run#run");
+                    let run = instance.wasi_cli_run().call_run(&mut store);
+                    tokio::pin!(run);
+                    let leak = watch_service_memory(usage, policy);
+                    tokio::pin!(leak);
+                    tokio::select! {
+                        result = &mut run => Ok(result),
+                        () = recycle.notified() => Err("its configuration changed"),
+                        growth = &mut leak => {
+                            let message = format!(
+                                "suspected memory leak: service memory grew by {growth} bytes since it started"
+                            );
+                            warn!(workload_id = workload.id(), growth, "service memory keeps growing");
+                            *memory_warning
+                                .lock()
+                                .unwrap_or_else(std::sync::PoisonError::into_inner) =
+                                Some(message.clone());
+                            events.emit(event(LifecycleEventType::MemoryLeakSuspected, message));
+                            if policy.is_some_and(|policy| policy.recycle) {
+                                Err("it is suspected of leaking memory")
+                            } else {
+                                Ok(run.await)
+                            }
+                        }
+                    }
+                };
+                metering::record_fuel(&mut store);
+                match exit {
+                    Err(reason) => {
+                        info!(
+                            workload_id = workload.id(),
+                            reason, "recycling service instance"
+                        );
+                        events.emit(event(
+                            LifecycleEventType::InstanceRecycled,
+                            format!("replacing service instance as {reason}"),
+                        ));
+                    }
+                    Ok(Err(e)) => {
+                        if memory::is_memory_limit_exceeded(&e) {
+                            workload.memory_limit_exceeded(metadata.id(), &e);
+                        }
+                        warn!(err = %e, retries = max_restarts, "service execution failed");
+                        events.emit(event(
+                            LifecycleEventType::ServiceCrashed,
+                            format!("service failed, {max_restarts} restart(s) left: {e:#}"),
+                        ));
+                        workload.record_crash_loop(metadata.id(), Some(&e));
+                        if max_restarts == 0 {
+                            info!("max restarts reached, service will not be restarted");
+                            break;
+                        }
+                        max_restarts = max_restarts.saturating_sub(1);
+                        workload.crash_loop_backoff(metadata.id()).await;
+                    }
+                    Ok(Ok(_)) => {
+                        info!("service executed successfully");
+                        workload.record_crash_loop(metadata.id(), None);
+                        break;
+                    }
+                }
+
+                // Restarts and recycles run on a fresh instance
+                *memory_warning
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+                let fresh = loop {
+                    let fresh = async {
+                        let mut store = workload.new_store_from_metadata(&metadata).await?;
+                        let instance = pre.instantiate_async(&mut store).await?;
+                        anyhow::Ok((store, instance))
+                    }
+                    .await;
+                    match fresh {
+                        Ok(fresh) => break Some(fresh),
+                        // Instantiating again may succeed once whatever it depends on recovers
+                        Err(e) if workload.crash_loops.is_some() && max_restarts > 0 => {
+                            warn!(err = %e, retries = max_restarts, "failed to instantiate service");
+                            events.emit(event(
+                                LifecycleEventType::ServiceCrashed,
+                                format!(
+                                    "service failed to instantiate, {max_restarts} restart(s) left: {e:#}"
+                                ),
+                            ));
+                            workload.record_crash_loop(metadata.id(), Some(&e));
+                            max_restarts = max_restarts.saturating_sub(1);
+                            workload.crash_loop_backoff(metadata.id()).await;
+                        }
+                        Err(e) => {
+                            warn!(err = %e, "failed to instantiate service, it will not be restarted");
+                            break None;
+                        }
+                    }
+                };
+                match fresh {
+                    Some(fresh) => (store, instance) = fresh,
+                    None => break,
+                }
+            }
+        };
+        let handle = match execution_pool {
+            Some(pool) => pool.spawn(task),
+            None => tokio::spawn(task),
+        };
+
+        // Store the handle to ensure the service can be cleaned up during workload shutdown
+        if let Some(s) = self.service.as_mut() {
+            s.handle = Some(Arc::new(handle));
+        }
+        Ok(true)
+    }
+
+    /// Returns why the service instance is suspected of leaking memory, if it is.
+    pub fn memory_warning(&self) -> Option<String> {
+        self.service.as_ref().and_then(|service| {
+            service
+                .memory_warning
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .clone()
+        })
+    }
+
+    /// Aborts the running service [`JoinHandle`] if it exists.
+    pub(crate) fn stop_service(&self) {
+        if let Some(service) = &self.service
+            && let Some(handle) = &service.handle
+        {
+            handle.abort();
+            debug!(
+                workload_id = self.id.as_ref(),
+                "service for workload aborted"
+            );
+        }
+    }
+
+    /// Instantiates an init component and calls its init export once.
+    async fn run_init_component(&self, component_id: &str) -> anyhow::Result<()> {
+        let export = self
+            .components
+            .read()
+            .await
+            .get(component_id)
+            .and_then(|c| c.init_export())
+            .context("component is not an init component")?
+            .to_string();
+        info!(component_id, export, "running init component");
+        self.call_export(component_id, &export).await?;
+        info!(component_id, export, "init component completed");
+        Ok(())
+    }
+
+    /// Returns the job components of this workload with how they should run.
+    pub async fn jobs(&self) -> Vec<(String, Job)> {
+        self.components
+            .read()
+            .await
+            .iter()
+            .filter_map(|(id, c)| Some((id.to_string(), c.job()?.clone())))
+            .collect()
+    }
+
+    /// Runs a job component to completion: its export is called until it has
+    /// succeeded `job.completions` times, with up to `job.parallelism` calls in
+    /// flight, retrying each failed call up to `job.max_retries` times.
+    ///
+    /// # Errors
+    /// Returns the error of the first call that ran out of retries.
+    pub async fn run_job(&self, component_id: &str, job: &Job) -> anyhow::Result<()> {
+        let export = job.export.as_deref().unwrap_or(DEFAULT_INIT_EXPORT);
+        info!(
+            component_id,
+            export,
+            completions = job.completions,
+            "running job"
+        );
+        futures::stream::iter(0..job.completions)
+            .map(|run| self.run_job_once(component_id, export, job, run))
+            .buffer_unordered(job.parallelism.max(1) as usize)
+            .try_collect::<Vec<_>>()
+            .await?;
+        info!(component_id, export, "job completed");
+        Ok(())
+    }
+
+    async fn run_job_once(
+        &self,
+        component_id: &str,
+        export: &str,
+        job: &Job,
+        run: u32,
+    ) -> anyhow::Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.call_export(component_id, export).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < job.max_retries => {
+                    let backoff = job_backoff(job.backoff_ms, attempt);
+                    warn!(
+                        component_id,
+                        run,
+                        attempt,
+                        ?backoff,
+                        "job run failed, retrying: {e:#}"
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "job run {run} failed after {} attempt(s)",
+                        attempt + 1
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Instantiates a component in a new store and calls `export`, given as a
+    /// function name or `interface#function`, once.
+    ///
+    /// # Errors
+    /// Returns an error if the export doesn't exist, takes parameters, traps, or
+    /// returns the error case of a `result`.
+    async fn call_export(&self, component_id: &str, export: &str) -> anyhow::Result<()> {
+        let results = self.invoke(component_id, export, &[]).await?;
+        if let [Val::Result(Err(e))] = results.as_slice() {
+            bail!("export '{export}' returned an error: {e:?}");
+        }
+        Ok(())
+    }
+
+    /// Runs `future`, an invocation of `component_id`, on the component's
+    /// execution pool, or in place if it has none. An invocation of an
+    /// autoscaling component first waits for an instance of its
+    /// [`InstancePool`].
+    ///
+    /// # Errors
+    /// Returns the error of `future`, or an error if it panicked on the pool.
+    /// Returns a [`CrashLoopBackoff`](crate::engine::crash_loop::CrashLoopBackoff)
+    /// without running `future` while the component backs off, see
+    /// [`crate::engine::crash_loop`].
+    pub async fn run_invocation<F, T>(&self, component_id: &str, future: F) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        /// Counts an invocation as in flight until it finishes or is dropped
+        struct InFlight<'a>(&'a AtomicU64);
+        impl Drop for InFlight<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        let (pool, instance_pool) = self
+            .components
+            .read()
+            .await
+            .get(component_id)
+            .map(|component| {
+                (
+                    component.metadata.execution_pool.clone(),
+                    component.metadata.instance_pool.clone(),
+                )
+            })
+            .unwrap_or_default();
+        if let Some(crash_loops) = &self.crash_loops {
+            crash_loops.check(component_id)?;
+        }
+        let stats = &self.invocations;
+        stats.total.fetch_add(1, Ordering::Relaxed);
+        stats.in_flight.fetch_add(1, Ordering::Relaxed);
+        let in_flight = InFlight(&stats.in_flight);
+        let started = Instant::now();
+        // Waiting for an instance counts towards the duration of the invocation
+        let instance = match &instance_pool {
+            Some(instance_pool) => instance_pool.acquire().await.map(Some),
+            None => Ok(None),
+        };
+        if let Some(metrics) = &self.metrics {
+            let attributes = WorkloadMetrics::attributes(&self.namespace, &self.name, component_id);
+            match &instance {
+                Ok(Some(instance)) => metrics.record_queue_time(&attributes, instance.queue_time()),
+                Ok(None) => {}
+                Err(reason) => metrics.record_refused(&attributes, *reason),
+            }
+        }
+        // An invocation refused an instance says nothing about the component
+        let refused = instance.is_err();
+        let result = match instance {
+            Ok(instance) => {
+                let result = match pool {
+                    Some(pool) => pool.run(future).await,
+                    None => future.await,
+                };
+                drop(instance);
+                result
+            }
+            Err(reason) => Err(reason.into()),
+        };
+        let elapsed = started.elapsed();
+        stats
+            .time_us
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        drop(in_flight);
+        if let Some(metrics) = &self.metrics {
+            let attributes = WorkloadMetrics::attributes(&self.namespace, &self.name, component_id);
+            metrics.record_invocation(&attributes, elapsed, result.is_err());
+        }
+        // The store of the instance is gone with its invocation, so the next
+        // invocation runs on a fresh one
+        if let Err(e) = &result
+            && memory::is_memory_limit_exceeded(e)
+        {
+            self.memory_limit_exceeded(component_id, e);
+        }
+        if !refused {
+            self.record_crash_loop(component_id, result.as_ref().err());
+        }
+        if let Err(e) = &result {
+            stats.failed.fetch_add(1, Ordering::Relaxed);
+            *stats
+                .last_error
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(format!("{e:#}"));
+            self.events.emit(
+                LifecycleEvent::new(
+                    LifecycleEventType::InvocationFailed,
+                    self.id.as_ref(),
+                    format!("{e:#}"),
+                )
+                .with_workload_name(self.name.as_ref())
+                .with_component_id(component_id),
+            );
+        }
+        result
+    }
+
+    /// Counts a run of `component_id`, failed with `error` if any, towards
+    /// its crash loop and reports the changes of the crash loop, see
+    /// [`crate::engine::crash_loop`].
+    fn record_crash_loop(&self, component_id: &str, error: Option<&anyhow::Error>) {
+        let Some(crash_loops) = &self.crash_loops else {
+            return;
+        };
+        let transition = match error {
+            Some(_) => crash_loops.failed(component_id),
+            None => crash_loops.succeeded(component_id),
+        };
+        let reason = || error.map(|e| format!("{e:#}")).unwrap_or_default();
+        let event = |event_type, message: String| {
+            LifecycleEvent::new(event_type, self.id.as_ref(), message)
+                .with_workload_name(self.name.as_ref())
+                .with_component_id(component_id)
+        };
+        match transition {
+            Some(Transition::Detected { failures, backoff }) => {
+                warn!(
+                    workload_id = self.id.as_ref(),
+                    component_id,
+                    failures,
+                    ?backoff,
+                    "component is crash looping"
+                );
+                self.events.emit(event(
+                    LifecycleEventType::CrashLoopDetected,
+                    format!(
+                        "component failed {failures} times in a row, backing off for {}s: {}",
+                        backoff.as_secs_f64(),
+                        reason()
+                    ),
+                ));
+            }
+            Some(Transition::Recovered { failures }) => {
+                info!(
+                    workload_id = self.id.as_ref(),
+                    component_id, failures, "component recovered from crash loop"
+                );
+                self.events.emit(event(
+                    LifecycleEventType::CrashLoopRecovered,
+                    format!("component recovered after failing {failures} times in a row"),
+                ));
+            }
+            Some(Transition::Stop { failures }) => {
+                warn!(
+                    workload_id = self.id.as_ref(),
+                    component_id, failures, "stopping crash looping workload"
+                );
+                if let Some(stops) = &self.crash_loop_stops {
+                    // Fails only once the host is gone
+                    _ = stops.send(CrashLoopStop {
+                        workload_id: self.id.to_string(),
+                        reason: format!(
+                            "component '{component_id}' failed {failures} times in a row: {}",
+                            reason()
+                        ),
+                    });
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Waits for `component_id` to be attempted again, if it is crash
+    /// looping.
+    async fn crash_loop_backoff(&self, component_id: &str) {
+        let Some(crash_loops) = &self.crash_loops else {
+            return;
+        };
+        let backoff = crash_loops.retry_after(component_id);
+        if !backoff.is_zero() {
+            info!(
+                workload_id = self.id.as_ref(),
+                component_id,
+                ?backoff,
+                "backing off before restarting crash looping component"
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Returns the components of the workload that are crash looping, see
+    /// [`crate::engine::crash_loop`].
+    pub fn crash_loops(&self) -> Vec<CrashLoopStatus> {
+        self.crash_loops
+            .as_ref()
+            .map(|crash_loops| crash_loops.status())
+            .unwrap_or_default()
+    }
+
+    /// Reports an instance of `component_id` trapped with `err` for exceeding
+    /// its memory limit, see [`crate::engine::memory`].
+    fn memory_limit_exceeded(&self, component_id: &str, err: &anyhow::Error) {
+        warn!(
+            workload_id = self.id.as_ref(),
+            component_id, "instance exceeded its memory limit: {err:#}"
+        );
+        if let Some(metrics) = &self.metrics {
+            let attributes = WorkloadMetrics::attributes(&self.namespace, &self.name, component_id);
+            metrics.record_memory_limit_exceeded(&attributes);
+        }
+        self.events.emit(
+            LifecycleEvent::new(
+                LifecycleEventType::MemoryLimitExceeded,
+                self.id.as_ref(),
+                format!("{err:#}"),
+            )
+            .with_workload_name(self.name.as_ref())
+            .with_component_id(component_id),
+        );
+    }
+
+    /// Instantiates a component in a new store and calls `export`, given as a
+    /// function name or `interface#function`, with `params`, on the
+    /// component's execution pool. Returns the values the export returned.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the component does not exist,
+    /// [`HostError::InvalidWorkload`] if the export does not exist or takes a
+    /// different number of parameters, and any other error if the parameters
+    /// have the wrong types or the call traps.
+    pub async fn invoke(
+        &self,
+        component_id: &str,
+        export: &str,
+        params: &[Val],
+    ) -> anyhow::Result<Vec<Val>> {
+        let workload = self.clone();
+        let (id, export, params) = (
+            component_id.to_string(),
+            export.to_string(),
+            params.to_vec(),
+        );
+        self.run_invocation(component_id, async move {
+            workload.invoke_in_place(&id, &export, &params).await
+        })
+        .await
+    }
+
+    async fn invoke_in_place(
+        &self,
+        component_id: &str,
+        export: &str,
+        params: &[Val],
+    ) -> anyhow::Result<Vec<Val>> {
+        let (mut store, instance) = match self.take_pre_initialized(component_id).await {
+            Some(PreInitialized { store, instance }) => (store, instance),
+            None => {
+                let pre = self
+                    .components
+                    .write()
+                    .await
+                    .get_mut(component_id)
+                    .ok_or_else(|| HostError::NotFound(format!("component {component_id}")))?
+                    .pre_instantiate()?;
+
+                let mut store = self.new_store(component_id).await?;
+                let instance = pre
+                    .instantiate_async(&mut store)
+                    .await
+                    .context("failed to instantiate component")?;
+                (store, instance)
+            }
+        };
+
+        let func = export_func(&instance, &mut store, export)?;
+        let expected = func.params(&store).len();
+        if expected != params.len() {
+            return Err(HostError::InvalidWorkload(format!(
+                "export '{export}' takes {expected} parameter(s), {} given",
+                params.len()
+            ))
+            .into());
+        }
+
+        let mut results = vec![Val::Bool(false); func.results(&store).len()];
+        store.data().set_invocation(export);
+        let called = func.call_async(&mut store, params, &mut results).await;
+        metering::record_fuel(&mut store);
+        #[cfg(feature = "instance-debug")]
+        crate::engine::debug::finish_dump(&mut store, &called);
+        called
+            .map_err(crate::engine::explain_trap)
+            .with_context(|| format!("export '{export}' failed"))?;
+        func.post_return_async(&mut store).await?;
+        Ok(results)
+    }
+
+    /// Takes a pre-initialized instance of `component_id` if one is ready,
+    /// and prepares the instances its pool lacks in the background, see
+    /// [`crate::engine::preinit`]. Returns `None` for components without
+    /// pre-initialization.
+    pub(crate) async fn take_pre_initialized(&self, component_id: &str) -> Option<PreInitialized> {
+        let pool = self
+            .components
+            .read()
+            .await
+            .get(component_id)?
+            .metadata
+            .pre_init
+            .clone()?;
+        let taken = pool.take();
+        let missing = pool.reserve();
+        if missing > 0 {
+            let workload = self.clone();
+            let component_id = component_id.to_string();
+            tokio::spawn(async move {
+                if let Err(e) = workload.fill_pre_init(&component_id, &pool, missing).await {
+                    warn!(
+                        workload_id = workload.id(),
+                        component_id,
+                        error = ?e,
+                        "failed to pre-initialize instance"
+                    );
+                }
+            });
+        }
+        if taken.is_none() {
+            debug!(
+                workload_id = self.id(),
+                component_id, "no pre-initialized instance ready, instantiating"
+            );
+        }
+        taken.map(PreInitialized::start)
+    }
+
+    /// Prepares the pre-initialized instances of the components before the
+    /// workload serves, see [`crate::engine::preinit`].
+    ///
+    /// # Errors
+    /// Returns the error of the first instance that failed to pre-initialize.
+    async fn start_pre_init(&self) -> anyhow::Result<()> {
+        let pools: Vec<(String, Arc<PreInitPool>)> = self
+            .components
+            .read()
+            .await
+            .iter()
+            .filter_map(|(id, component)| {
+                Some((id.to_string(), component.metadata.pre_init.clone()?))
+            })
+            .collect();
+        for (component_id, pool) in pools {
+            let count = pool.reserve();
+            info!(component_id, count, "pre-initializing instances");
+            self.fill_pre_init(&component_id, &pool, count)
+                .await
+                .with_context(|| format!("failed to pre-initialize component {component_id}"))?;
+        }
+        Ok(())
+    }
+
+    /// Drops the pre-initialized instances of the components once the
+    /// workload stops.
+    pub(crate) async fn stop_pre_init(&self) {
+        for component in self.components.read().await.values() {
+            if let Some(pool) = &component.metadata.pre_init {
+                pool.close();
+            }
+        }
+    }
+
+    /// Prepares `count` instances of `component_id` reserved in `pool`.
+    async fn fill_pre_init(
+        &self,
+        component_id: &str,
+        pool: &PreInitPool,
+        count: usize,
+    ) -> anyhow::Result<()> {
+        let mut remaining = count;
+        while remaining > 0 {
+            remaining -= 1;
+            match self.pre_initialize(component_id, pool.export()).await {
+                Ok(instance) => pool.put(instance),
+                Err(e) => {
+                    for _ in 0..=remaining {
+                        pool.abandon();
                     }
-                    max_restarts = max_restarts.saturating_sub(1);
+                    return Err(e);
                 }
-            });
-
-            // Store the handle to ensure the service can be cleaned up during workload shutdown
-            if let Some(s) = self.service.as_mut() {
-                s.handle = Some(Arc::new(handle));
             }
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        Ok(())
     }
 
-    /// Aborts the running service [`JoinHandle`] if it exists.
-    pub(crate) fn stop_service(&self) {
-        if let Some(service) = &self.service
-            && let Some(handle) = &service.handle
-        {
-            handle.abort();
-            debug!(
-                workload_id = self.id.as_ref(),
-                "service for workload aborted"
+    /// Instantiates `component_id` in a new store and calls its
+    /// pre-initialization `export`, if any.
+    async fn pre_initialize(
+        &self,
+        component_id: &str,
+        export: Option<&str>,
+    ) -> anyhow::Result<PreInitialized> {
+        let pre = self.instantiate_pre(component_id).await?;
+        let mut store = self.new_store(component_id).await?;
+        let instance = pre
+            .instantiate_async(&mut store)
+            .await
+            .context("failed to instantiate component")?;
+        if let Some(export) = export {
+            let func = export_func(&instance, &mut store, export)?;
+            ensure!(
+                func.params(&store).is_empty(),
+                "pre-initialization export '{export}' must take no parameters"
             );
+            let mut results = vec![Val::Bool(false); func.results(&store).len()];
+            store.data().set_invocation(export);
+            func.call_async(&mut store, &[], &mut results)
+                .await
+                .map_err(crate::engine::explain_trap)
+                .with_context(|| format!("pre-initialization export '{export}' failed"))?;
+            func.post_return_async(&mut store).await?;
+            if let [Val::Result(Err(e))] = results.as_slice() {
+                bail!("pre-initialization export '{export}' returned an error: {e:?}");
+            }
         }
+        // The invocation that takes the instance isn't charged for its initialization
+        metering::fuel_store(&mut store);
+        Ok(PreInitialized { store, instance })
     }
 
     pub fn components(&self) -> Arc<RwLock<HashMap<Arc<str>, WorkloadComponent>>> {
@@ -461,8 +1546,17 @@ impl ResolvedWorkload {
         &self.host_interfaces
     }
 
+    /// Links the components of the workload to each other: an interface one
+    /// component imports and another exports is satisfied in-process by an
+    /// instance of the exporting component, created in the store of the
+    /// importing one. Interfaces are matched by their full name, including
+    /// the version, and each may only be exported by one component.
+    ///
+    /// Components are linked in [`link_order`], exporters first, so chains
+    /// like a handler calling business logic calling a storage adapter
+    /// resolve. Components importing each other's interfaces fail to link.
     async fn link_components(&mut self) -> anyhow::Result<()> {
-        // A map from component ID to its exported interfaces
+        // A map from exported interface to the ID of the component exporting it
         let mut interface_map: HashMap<String, Arc<str>> = HashMap::new();
 
         // Determine available component exports to link to the rest of the workload
@@ -485,9 +1579,10 @@ impl ResolvedWorkload {
                 }
                 if let ComponentItem::ComponentInstance(_) = item {
                     // Register the interface name to the component key
-                    if interface_map.contains_key(&name) {
+                    if let Some(other) = interface_map.get(&name) {
                         anyhow::bail!(
-                            "another component already implements the interface '{name}'"
+                            "components '{other}' and '{}' both export the interface '{name}'",
+                            c.id()
                         );
                     }
                     trace!(name, "registering component export for linking");
@@ -509,8 +1604,21 @@ impl ResolvedWorkload {
         &mut self,
         interface_map: &HashMap<String, Arc<str>>,
     ) -> anyhow::Result<()> {
-        let component_ids: Vec<Arc<str>> = self.components.read().await.keys().cloned().collect();
-        for component_id in component_ids {
+        let imports = self
+            .components
+            .read()
+            .await
+            .iter()
+            .map(|(id, component)| {
+                let names = component
+                    .component_imports()?
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect();
+                Ok((id.clone(), names))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        for component_id in link_order(&imports, interface_map)? {
             // In order to have mutable access to both the workload component and components that need
             // to be instantiated as "plugins" during linking, we remove and re-add the component to the list.
             let mut workload_component = {
@@ -524,7 +1632,7 @@ impl ResolvedWorkload {
             let component = workload_component.metadata.component.clone();
             let linker = &mut workload_component.metadata.linker;
             let res = self
-                .resolve_component_imports(&component, linker, interface_map)
+                .resolve_component_imports(&component_id, &component, linker, interface_map)
                 .await;
             self.components
                 .write()
@@ -536,10 +1644,11 @@ impl ResolvedWorkload {
 
         if let Some(mut service) = self.service.take() {
             let component = service.metadata.component.clone();
+            let component_id = service.metadata.id.clone();
             let linker = &mut service.metadata.linker;
 
             let res = self
-                .resolve_component_imports(&component, linker, interface_map)
+                .resolve_component_imports(&component_id, &component, linker, interface_map)
                 .await;
 
             self.service = Some(service);
@@ -553,6 +1662,7 @@ impl ResolvedWorkload {
 
     async fn resolve_component_imports(
         &self,
+        component_id: &str,
         component: &wasmtime::component::Component,
         linker: &mut Linker<Ctx>,
         interface_map: &HashMap<String, Arc<str>>,
@@ -560,9 +1670,10 @@ impl ResolvedWorkload {
         let ty = component.component_type();
         let imports: Vec<_> = ty.imports(component.engine()).collect();
 
-        // TODO: some kind of shared import_name -> component registry. need to remove when new store
-        // store id, instance, import_name. That will keep the instance properly unique
-        let instance: Arc<RwLock<Option<(String, Instance)>>> = Arc::default();
+        // The instance of each exporting component in the current store, shared by all the
+        // interfaces the component imports from it
+        let mut instances: HashMap<Arc<str>, Arc<RwLock<Option<(String, Instance)>>>> =
+            HashMap::new();
         for (import_name, import_item) in imports.into_iter() {
             match import_item {
                 ComponentItem::ComponentInstance(import_instance_ty) => {
@@ -597,6 +1708,16 @@ impl ResolvedWorkload {
                         (plugin_component, idx)
                     };
                     trace!(name = import_name, index = ?instance_idx, "found import at index");
+                    debug!(
+                        component_id,
+                        name = import_name,
+                        exporter = %plugin_component.metadata.id,
+                        "linking import to component export"
+                    );
+                    let instance = instances
+                        .entry(plugin_component.metadata.id.clone())
+                        .or_default()
+                        .clone();
 
                     // Preinstantiate the plugin instance so we can use it later
                     let pre = plugin_component
@@ -837,12 +1958,100 @@ impl ResolvedWorkload {
         &self.namespace
     }
 
+    /// Returns where the service and components of this workload come from,
+    /// the service first and the components by id.
+    pub fn provenance(&self) -> &[ComponentProvenance] {
+        &self.provenance
+    }
+
     /// Returns the number of components in this workload.
     /// Does not include the service component if one is defined.
     pub async fn component_count(&self) -> usize {
         self.components.read().await.len()
     }
 
+    /// Returns the live instances of the components, see
+    /// [`crate::engine::debug`].
+    #[cfg(feature = "instance-debug")]
+    pub fn instances(&self) -> &Arc<crate::engine::debug::InstanceRegistry> {
+        &self.instances
+    }
+
+    /// Returns the number of invocations of the components running now, see
+    /// [`Self::run_invocation`].
+    pub fn in_flight_invocations(&self) -> u64 {
+        self.invocations.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Returns the [`InstancePool`] of the component serving HTTP requests, if
+    /// it autoscales.
+    pub async fn http_instance_pool(&self) -> Option<Arc<InstancePool>> {
+        self.components
+            .read()
+            .await
+            .values()
+            .find(|component| component.is_serving() && component.exports_wasi_http())
+            .and_then(|component| component.metadata.instance_pool.clone())
+    }
+
+    /// Returns what the workload is doing: its uptime, the invocations of its
+    /// components through [`Self::run_invocation`] and the execution pools
+    /// they run on, and the versions of the host interfaces their plugins
+    /// provide. The routes of the workload are left to the caller, which
+    /// knows the HTTP router.
+    pub async fn activity(&self) -> WorkloadActivity {
+        let components = self.components.read().await;
+        let mut pools: Vec<PoolUtilization> = Vec::new();
+        for pool in components
+            .values()
+            .filter_map(|component| component.metadata.execution_pool.as_ref())
+        {
+            if pools.iter().all(|p| p.name != pool.name()) {
+                pools.push(PoolUtilization {
+                    name: pool.name().to_string(),
+                    threads: pool.threads() as u64,
+                    busy: pool.busy() as u64,
+                });
+            }
+        }
+        pools.sort_by(|a, b| a.name.cmp(&b.name));
+        let stats = &self.invocations;
+        WorkloadActivity {
+            component_count: components.len() as u64,
+            uptime: self.started_at.elapsed(),
+            invocations: stats.total.load(Ordering::Relaxed),
+            failed_invocations: stats.failed.load(Ordering::Relaxed),
+            in_flight_invocations: self.in_flight_invocations(),
+            invocation_time: Duration::from_micros(stats.time_us.load(Ordering::Relaxed)),
+            fuel: stats.fuel.load(Ordering::Relaxed),
+            cpu_throttled: components
+                .values()
+                .filter_map(|component| component.metadata.cpu_throttle.as_ref())
+                .map(|throttle| throttle.throttled())
+                .sum(),
+            last_error: stats
+                .last_error
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .clone(),
+            pools,
+            routes: Vec::new(),
+            interfaces: self.interfaces.to_vec(),
+            crash_loops: self.crash_loops(),
+        }
+    }
+
+    /// Returns the ID of the middleware component of the workload, if it has
+    /// one.
+    pub async fn middleware_component(&self) -> Option<String> {
+        self.components
+            .read()
+            .await
+            .values()
+            .find(|component| component.is_middleware())
+            .map(|component| component.id().to_string())
+    }
+
     /// Helper to create a new wasmtime Store for a given component in the workload.
     pub async fn new_store(&self, component_id: &str) -> anyhow::Result<wasmtime::Store<Ctx>> {
         let components = self.components.read().await;
@@ -859,7 +2068,7 @@ impl ResolvedWorkload {
     ) -> anyhow::Result<wasmtime::Store<Ctx>> {
         let components = self.components.read().await;
 
-        // TODO: Consider stderr/stdout buffering + logging
+        let log = LogContext::default();
         let mut wasi_ctx_builder = WasiCtxBuilder::new();
         wasi_ctx_builder
             .envs(
@@ -871,34 +2080,165 @@ impl ResolvedWorkload {
                     .collect::<Vec<_>>()
                     .as_slice(),
             )
-            .inherit_stdout()
-            .inherit_stderr();
+            .stdout(GuestOutput::stdout(
+                metadata.workload_id(),
+                metadata.id(),
+                log.clone(),
+            ))
+            .stderr(GuestOutput::stderr(
+                metadata.workload_id(),
+                metadata.id(),
+                log.clone(),
+            ));
 
         // Mount all possible volume mounts in the workload since components share a WasiCtx
-        for (host_path, mount) in &components
+        for (root, mount) in &components
             .iter()
             .flat_map(|(_id, workload_component)| workload_component.metadata.volume_mounts.clone())
             .collect::<Vec<_>>()
         {
-            let dir = tokio::fs::canonicalize(host_path).await?;
+            // Preopen only the canonical root, which is read-only once full
+            let full = root.open().await?;
+            let dir = root.path();
             debug!(host_path = %dir.display(), container_path = %mount.mount_path, "preopening volume mount");
-            let (dir_perms, file_perms) = match mount.read_only {
+            let (dir_perms, file_perms) = match mount.read_only || full {
                 true => (DirPerms::READ, FilePerms::READ),
                 false => (DirPerms::all(), FilePerms::all()),
             };
-            wasi_ctx_builder.preopened_dir(&dir, &mount.mount_path, dir_perms, file_perms)?;
+            wasi_ctx_builder.preopened_dir(dir, &mount.mount_path, dir_perms, file_perms)?;
         }
 
+        let filesystem_quotas = FilesystemQuotas::new(
+            metadata.workload_id(),
+            metadata.workload_name(),
+            metadata.id(),
+            components
+                .values()
+                .flat_map(|component| component.metadata.mount_quotas.iter())
+                .chain(&metadata.mount_quotas)
+                .cloned(),
+            self.events.clone(),
+        );
+
         let mut ctx_builder = Ctx::builder(metadata.workload_id(), metadata.id())
             .with_http_handler(self.http_handler.clone())
-            .with_wasi_ctx(wasi_ctx_builder.build());
+            .with_wasi_ctx(wasi_ctx_builder.build())
+            .with_filesystem_quotas(filesystem_quotas)
+            .with_log_context(log)
+            .with_egress_policy(
+                EgressPolicy::new(&metadata.local_resources.allowed_hosts)
+                    .with_default(self.http_handler.egress_default())
+                    .with_limits(metadata.outgoing_http.clone())
+                    .with_throttle(metadata.outbound_throttle.clone())
+                    .with_signers(
+                        self.http_handler
+                            .request_signers(metadata.workload_namespace()),
+                    ),
+            )
+            .with_memory_limit(memory_limit(
+                metadata.local_resources.memory_limit_mb,
+                metadata.max_memory_bytes,
+            ))
+            .with_usage_meter(metadata.metering.as_ref().map(|metering| {
+                metering.start(
+                    metadata.workload_namespace(),
+                    metadata.workload_name(),
+                    metadata.workload_id(),
+                    metadata.id(),
+                )
+            }))
+            .with_instance_metrics(
+                self.metrics.clone(),
+                WorkloadMetrics::attributes(
+                    metadata.workload_namespace(),
+                    metadata.workload_name(),
+                    metadata.id(),
+                ),
+            )
+            .with_fuel_total(self.invocations.fuel.clone());
 
         if let Some(plugins) = &metadata.plugins {
             ctx_builder = ctx_builder.with_plugins(plugins.clone());
         }
+        if let Some(logs) = &self.logs {
+            ctx_builder = ctx_builder.with_logger(logs.component(
+                metadata.workload_id(),
+                metadata.workload_name(),
+                metadata.workload_namespace(),
+                metadata.id(),
+            ));
+        }
 
-        let store = wasmtime::Store::new(metadata.engine(), ctx_builder.build());
+        let mut store = wasmtime::Store::new(metadata.engine(), ctx_builder.build());
+        store.limiter(|ctx| &mut ctx.limits);
+        metering::fuel_store(&mut store);
+        #[cfg(feature = "instance-debug")]
+        let debug_instance = {
+            let guard = self
+                .instances
+                .register(metadata.id.clone(), store.data().limits.usage());
+            let instance = guard.instance().clone();
+            store.data_mut().set_debug_instance(guard);
+            instance
+        };
+        // Without a deadline, the first epoch check of an interruptible engine would trap
+        match metadata.epoch_yield_ticks {
+            Some(ticks)
+                if metadata.invocation_timeout.is_some()
+                    || metadata.cpu_throttle.is_some()
+                    || cfg!(feature = "instance-debug") =>
+            {
+                // Stores live for a single invocation, whose time starts with
+                // the store or when a pre-initialized instance takes it
+                let timeout = metadata.invocation_timeout;
+                let throttle = metadata.cpu_throttle.clone();
+                let mut fuel = store.get_fuel().unwrap_or_default();
+                store.set_epoch_deadline(ticks);
+                store.epoch_deadline_callback(move |store| {
+                    #[cfg(feature = "instance-debug")]
+                    if debug_instance.dump_requested() {
+                        return Err(crate::engine::debug::DumpRequested.into());
+                    }
+                    let now = Instant::now();
+                    let deadline = timeout.map(|timeout| (store.data().started + timeout, timeout));
+                    if let Some((deadline, timeout)) = deadline
+                        && now >= deadline
+                    {
+                        return Err(InvocationTimeout(timeout).into());
+                    }
+                    if let Some(throttle) = &throttle
+                        && let Ok(remaining) = store.get_fuel()
+                    {
+                        let mut pause = throttle.charge(fuel.saturating_sub(remaining));
+                        fuel = remaining;
+                        if let Some((deadline, _)) = deadline {
+                            pause = pause.min(deadline - now);
+                        }
+                        if !pause.is_zero() {
+                            return Ok(wasmtime::UpdateDeadline::YieldCustom(
+                                ticks,
+                                Box::pin(tokio::time::sleep(pause)),
+                            ));
+                        }
+                    }
+                    Ok(wasmtime::UpdateDeadline::Yield(ticks))
+                });
+            }
+            Some(ticks) => store.epoch_deadline_async_yield_and_update(ticks),
+            None => {}
+        }
 
+        if self.events.is_observed() {
+            self.events.emit(
+                LifecycleEvent::new(
+                    LifecycleEventType::InstanceCreated,
+                    metadata.workload_id(),
+                    format!("created an instance of component {}", metadata.id()),
+                )
+                .with_workload_name(metadata.workload_name())
+                .with_component_id(metadata.id()),
+            );
+        }
         Ok(store)
     }
 
@@ -917,6 +2257,67 @@ impl ResolvedWorkload {
         Ok(pre)
     }
 
+    /// Calls [`HostPlugin::on_workload_stop`] on the plugins bound to this
+    /// workload, in [`teardown_order`](crate::plugin::teardown_order). Errors
+    /// are logged.
+    pub async fn notify_plugins_of_stop(&self) {
+        let mut bound: HashMap<&'static str, Arc<dyn HostPlugin>> = HashMap::new();
+        for component in self.components.read().await.values() {
+            for (id, plugin) in component.plugins().iter().flatten() {
+                bound.insert(*id, plugin.clone() as Arc<dyn HostPlugin>);
+            }
+        }
+        for plugin in crate::plugin::teardown_order(bound.values()) {
+            if let Err(e) = plugin.on_workload_stop(self.id()).await {
+                warn!(
+                    plugin_id = plugin.id(),
+                    workload_id = self.id.as_ref(),
+                    error = ?e,
+                    "plugin failed to handle workload stop, continuing"
+                );
+            }
+        }
+    }
+
+    /// Applies `update` to the configuration of `component_id`, or of every
+    /// component and the service if `None`, through the plugins bound to
+    /// them, see [`HostPlugin::on_config_update`]. The service instance is
+    /// replaced when its configuration changed, unless its
+    /// [`ConfigChangePolicy`] is [`ConfigChangePolicy::Live`].
+    ///
+    /// Returns the components whose configuration changed and whether the
+    /// service instance was replaced.
+    ///
+    /// # Errors
+    /// Returns an error if a plugin fails to apply the update.
+    pub async fn update_config(
+        &self,
+        component_id: Option<&str>,
+        update: &crate::types::ConfigUpdate,
+    ) -> anyhow::Result<(Vec<String>, bool)> {
+        let mut updated = Vec::new();
+        for component in self.components.read().await.values() {
+            if component_id.is_none_or(|id| id == component.id())
+                && update_plugins(component.plugins(), component.id(), update).await?
+            {
+                updated.push(component.id().to_string());
+            }
+        }
+        let mut recycled = false;
+        if let Some(service) = &self.service
+            && component_id.is_none_or(|id| id == service.metadata.id())
+            && update_plugins(&service.metadata.plugins, service.metadata.id(), update).await?
+        {
+            updated.push(service.metadata.id().to_string());
+            if service.config_change == ConfigChangePolicy::Recycle && service.is_running() {
+                service.recycle.notify_one();
+                recycled = true;
+            }
+        }
+        updated.sort_unstable();
+        Ok((updated, recycled))
+    }
+
     /// Unbind all plugins from all components in this workload.
     ///
     /// This should be called when stopping a workload to ensure proper cleanup
@@ -1009,6 +2410,22 @@ pub struct UnresolvedWorkload {
     service: Option<WorkloadService>,
     /// All [`WorkloadComponent`]s in the workload
     components: HashMap<Arc<str>, WorkloadComponent>,
+    /// IDs of the init components, in the order they were declared
+    init_components: Vec<Arc<str>>,
+    /// Links the interfaces no plugin provides to peers providing them
+    forwarding: Option<Forwarding>,
+    /// Records the invocations of the components
+    metrics: Option<Arc<WorkloadMetrics>>,
+    /// Receives the events of the instances and invocations of the components
+    events: LifecycleEvents,
+    /// Captures what the components log
+    logs: Option<WorkloadLogs>,
+    /// The host interfaces bound to plugins by [`Self::bind_plugins`]
+    interfaces: Vec<InterfaceBinding>,
+    /// Counts the consecutive failures of the components, if set
+    crash_loop_policy: Option<CrashLoopPolicy>,
+    /// Receives the workloads to stop for crash looping
+    crash_loop_stops: Option<tokio::sync::mpsc::UnboundedSender<CrashLoopStop>>,
 }
 
 impl UnresolvedWorkload {
@@ -1033,19 +2450,206 @@ impl UnresolvedWorkload {
         components: impl IntoIterator<Item = WorkloadComponent>,
         host_interfaces: Vec<WitInterface>,
     ) -> Self {
+        let mut init_components = Vec::new();
+        let components = components
+            .into_iter()
+            .map(|c| {
+                let id = Arc::from(c.id());
+                if c.init_export().is_some() {
+                    init_components.push(Arc::clone(&id));
+                }
+                (id, c)
+            })
+            .collect();
         Self {
             id: id.into(),
             name: name.into(),
             namespace: namespace.into(),
             service,
-            components: components
-                .into_iter()
-                .map(|c| {
-                    let id = Arc::from(c.id());
-                    (id, c)
-                })
-                .collect(),
+            components,
+            init_components,
             host_interfaces,
+            forwarding: None,
+            metrics: None,
+            events: LifecycleEvents::default(),
+            logs: None,
+            interfaces: Vec::new(),
+            crash_loop_policy: None,
+            crash_loop_stops: None,
+        }
+    }
+
+    /// Forwards calls to the interfaces no plugin provides to peers, if they
+    /// provide them, see [`crate::plugin::forward`].
+    pub(crate) fn with_forwarding(mut self, forwarding: Option<Forwarding>) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    /// Records the invocations of the components and the memory of their
+    /// instances in `metrics`, see [`crate::host::metrics`].
+    pub(crate) fn with_metrics(mut self, metrics: Option<Arc<WorkloadMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Emits the events of the instances and invocations of the components,
+    /// e.g. of instances exceeding their memory limit, to `events`.
+    pub(crate) fn with_events(mut self, events: LifecycleEvents) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Captures what the components log through `wasi:logging` in `logs`,
+    /// see [`crate::host::logs`].
+    pub(crate) fn with_logs(mut self, logs: WorkloadLogs) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+
+    /// Counts the consecutive failures of the components according to
+    /// `policy`, see [`crate::engine::crash_loop`].
+    pub(crate) fn with_crash_loop_policy(mut self, policy: Option<CrashLoopPolicy>) -> Self {
+        self.crash_loop_policy = policy;
+        self
+    }
+
+    /// Sends the workload to `stops` once a component failed as many times in
+    /// a row as the crash loop policy allows.
+    pub(crate) fn with_crash_loop_stops(
+        mut self,
+        stops: tokio::sync::mpsc::UnboundedSender<CrashLoopStop>,
+    ) -> Self {
+        self.crash_loop_stops = Some(stops);
+        self
+    }
+
+    /// Checks the WIT worlds of the components against what they will be
+    /// linked to before anything is bound, so a workload that could never be
+    /// instantiated fails to start with every [`WorldMismatch`] instead of at
+    /// its first instance.
+    ///
+    /// An import is satisfied by the interfaces the engine links, see
+    /// [`crate::engine::builtin_interfaces`], by another component of the
+    /// workload exporting it, see [`ResolvedWorkload::link_components`], or
+    /// by declaring it in the host interfaces of the workload. Whether plugins
+    /// provide the declared imports is checked by [`Self::bind_plugins`].
+    /// Declared interfaces the host calls into, `wasi:http/incoming-handler`
+    /// and the exports of plugins, must be exported by a component.
+    ///
+    /// # Errors
+    /// Returns [`HostError::IncompatibleWorld`] listing every mismatch.
+    pub fn validate_world(
+        &self,
+        plugins: &HashMap<&'static str, Arc<dyn HostPlugin + 'static>>,
+    ) -> Result<(), HostError> {
+        let builtin = WitWorld {
+            imports: HashSet::new(),
+            exports: crate::engine::builtin_interfaces(),
+        };
+        let plugin_worlds: Vec<WitWorld> = plugins.values().map(|p| p.world()).collect();
+        let provided = WitWorld {
+            imports: HashSet::new(),
+            exports: plugin_worlds
+                .iter()
+                .flat_map(|world| world.imports.iter().chain(&world.exports))
+                .cloned()
+                .collect(),
+        };
+
+        let mut components: Vec<&WorkloadMetadata> =
+            self.components.values().map(|c| &c.metadata).collect();
+        components.sort_by(|a, b| a.id.cmp(&b.id));
+        components.extend(self.service.as_ref().map(|service| &service.metadata));
+
+        let mut exporters: HashMap<String, &str> = HashMap::new();
+        for component in &components {
+            for (name, _) in component.component_exports()? {
+                exporters.insert(name, component.id());
+            }
+        }
+
+        let mut mismatches = Vec::new();
+        for component in &components {
+            for (name, _) in component.component_imports()? {
+                let interface = WitInterface::from(name.as_str());
+                if builtin.includes_bidirectional(&interface)
+                    || crate::engine::is_wasip3_interface(&name)
+                    || exporters
+                        .get(&name)
+                        .is_some_and(|exporter| *exporter != component.id())
+                    || declares(&self.host_interfaces, &interface)
+                {
+                    continue;
+                }
+                let component_id = component.id().to_string();
+                let report = provided.compatibility(&WitWorld {
+                    imports: HashSet::from([interface.clone()]),
+                    exports: HashSet::new(),
+                });
+                let forwarded = self
+                    .forwarding
+                    .as_ref()
+                    .is_some_and(|forwarding| forwarding.provides(&interface));
+                mismatches.push(match report.version_conflicts.into_iter().next() {
+                    Some(conflict) if !forwarded => WorldMismatch::VersionConflict {
+                        component_id,
+                        conflict,
+                    },
+                    _ if report.satisfied.is_empty() && !forwarded => {
+                        WorldMismatch::MissingImport {
+                            component_id,
+                            interface,
+                        }
+                    }
+                    _ => WorldMismatch::UndeclaredImport {
+                        component_id,
+                        interface,
+                    },
+                });
+            }
+        }
+
+        let worlds: Vec<WitWorld> = components.iter().map(|c| c.world()).collect();
+        let incoming_handler = WitInterface::from("wasi:http/incoming-handler");
+        // Components exporting a wasi:http@0.3 handler are served with it instead
+        let serves_http_p3 = exporters.keys().any(|name| {
+            name.starts_with("wasi:http/handler@") && crate::engine::is_wasip3_interface(name)
+        });
+        for declared in &self.host_interfaces {
+            let mut names: Vec<&String> = declared.interfaces.iter().collect();
+            names.sort();
+            for name in names {
+                let interface = WitInterface {
+                    interfaces: HashSet::from([name.clone()]),
+                    config: HashMap::new(),
+                    ..declared.clone()
+                };
+                let host_calls = incoming_handler.contains(&interface)
+                    || plugin_worlds.iter().any(|world| {
+                        world.exports.iter().any(|export| {
+                            export.namespace == interface.namespace
+                                && export.package == interface.package
+                                && export.interfaces.contains(name)
+                                && export.version_compatible(&interface)
+                        })
+                    });
+                // Plugins may also list interfaces components import as exports
+                if host_calls
+                    && !(serves_http_p3 && incoming_handler.contains(&interface))
+                    && !worlds
+                        .iter()
+                        .any(|world| world.includes_bidirectional(&interface))
+                {
+                    mismatches.push(WorldMismatch::MissingExport { interface });
+                }
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(HostError::IncompatibleWorld { mismatches })
         }
     }
 
@@ -1104,9 +2708,32 @@ impl UnresolvedWorkload {
 
         trace!(?unmatched_interfaces, "resolving unmatched interfaces");
 
+        // When several plugins serve a compatible version of an interface, bind
+        // the one serving the exact requested version, otherwise the newest one.
+        let plugin_worlds: HashMap<&'static str, WitWorld> =
+            plugins.iter().map(|(id, p)| (*id, p.world())).collect();
+        let preferred: HashMap<WitInterface, &'static str> = unmatched_interfaces
+            .values()
+            .flatten()
+            .filter_map(|wit_interface| {
+                plugin_worlds
+                    .iter()
+                    .filter(|(_, world)| world.includes_bidirectional(wit_interface))
+                    .max_by_key(|&(id, world)| {
+                        let version = world.package_version(wit_interface);
+                        (
+                            version.is_some() && version == wit_interface.version.as_ref(),
+                            version,
+                            std::cmp::Reverse(*id),
+                        )
+                    })
+                    .map(|(id, _)| (wit_interface.clone(), *id))
+            })
+            .collect();
+
         // Iterate through each plugin first, then check every component for matching worlds
         for (plugin_id, p) in plugins.iter() {
-            let plugin_interfaces = p.world();
+            let plugin_interfaces = &plugin_worlds[plugin_id];
             trace!(plugin_id = plugin_id, plugin_interfaces = ?plugin_interfaces, "checking plugin interfaces");
 
             // Collect bindings for this plugin across all components
@@ -1117,8 +2744,8 @@ impl UnresolvedWorkload {
                 // Find interfaces that this plugin can satisfy for this component
                 let mut matching_interfaces = HashSet::new();
                 for wit_interface in required_interfaces.iter() {
-                    // Check if plugin supports this interface
-                    if plugin_interfaces.includes_bidirectional(wit_interface) {
+                    // Check if this is the plugin preferred for this interface
+                    if preferred.get(wit_interface) == Some(plugin_id) {
                         matching_interfaces.insert(wit_interface.clone());
                     }
                 }
@@ -1230,6 +2857,27 @@ impl UnresolvedWorkload {
                         workload_component.add_plugin(plugin_id, p.clone());
                         plugin_component_ids.push(workload_component.id().to_string());
 
+                        // Record the version the plugin provides, which may be
+                        // a compatible one other than requested
+                        for interface in &matching_interfaces {
+                            let version = plugin_interfaces.package_version(interface).cloned();
+                            if version.is_some() && version != interface.version {
+                                debug!(
+                                    plugin_id = plugin_id,
+                                    component_id = workload_component.id(),
+                                    requested = %interface,
+                                    version = ?version,
+                                    "bound interface to a compatible version"
+                                );
+                            }
+                            self.interfaces.push(InterfaceBinding {
+                                component_id: workload_component.id().to_string(),
+                                plugin_id: plugin_id.to_string(),
+                                requested: interface.to_string(),
+                                version,
+                            });
+                        }
+
                         // Remove matched interfaces from unmatched set
                         if let Some(unmatched) = unmatched_interfaces.get_mut(&component_id) {
                             for interface in &matching_interfaces {
@@ -1249,7 +2897,43 @@ impl UnresolvedWorkload {
             }
         }
 
-        // Check if all required interfaces were matched
+        // Interfaces no plugin provides are forwarded when a peer provides them
+        if let Some(forwarding) = &self.forwarding {
+            for (component_id, unmatched) in unmatched_interfaces.iter_mut() {
+                let forwarded: HashSet<WitInterface> = unmatched
+                    .iter()
+                    .filter(|interface| forwarding.provides(interface))
+                    .cloned()
+                    .collect();
+                if forwarded.is_empty() {
+                    continue;
+                }
+                let metadata = match (self.components.get_mut(component_id), &mut self.service) {
+                    (Some(component), _) => &mut component.metadata,
+                    (None, Some(service)) if service.id() == component_id.as_ref() => {
+                        &mut service.metadata
+                    }
+                    (None, _) => continue,
+                };
+                debug!(
+                    component_id = component_id.as_ref(),
+                    interfaces = ?forwarded,
+                    "forwarding interfaces no plugin provides"
+                );
+                forwarding.bind(metadata, &forwarded)?;
+                unmatched.retain(|interface| !forwarded.contains(interface));
+            }
+        }
+
+        // Check if all required interfaces were matched, telling interfaces no
+        // plugin provides apart from ones provided at other versions
+        let provided = WitWorld {
+            imports: HashSet::new(),
+            exports: plugin_worlds
+                .into_values()
+                .flat_map(|world| world.imports.into_iter().chain(world.exports))
+                .collect(),
+        };
         for (component_id, unmatched) in unmatched_interfaces.iter() {
             if !unmatched.is_empty() {
                 tracing::error!(
@@ -1257,9 +2941,24 @@ impl UnresolvedWorkload {
                     interfaces = ?unmatched,
                     "no plugins found for requested interfaces"
                 );
-                bail!(
-                    "workload component {component_id} requested interfaces that are not available on this host: {unmatched:?}",
-                )
+                let report = provided.compatibility(&WitWorld {
+                    imports: unmatched.clone(),
+                    exports: HashSet::new(),
+                });
+                if !report.version_conflicts.is_empty() {
+                    return Err(HostError::VersionConflict {
+                        component_id: component_id.to_string(),
+                        conflicts: report.version_conflicts,
+                    }
+                    .into());
+                }
+                let mut interfaces: Vec<WitInterface> = unmatched.iter().cloned().collect();
+                interfaces.sort_by_key(|i| i.to_string());
+                return Err(HostError::UnsatisfiedImport {
+                    component_id: component_id.to_string(),
+                    interfaces,
+                }
+                .into());
             }
         }
 
@@ -1285,7 +2984,12 @@ impl UnresolvedWorkload {
     ///
     /// # Errors
     /// Returns an error if:
-    /// - Required interfaces cannot be satisfied by available plugins
+    /// - The workload has several middleware components, or one that does not
+    ///   export `wasi:http/incoming-handler`
+    /// - The worlds of the components don't match the interfaces available to
+    ///   them, see [`Self::validate_world`]
+    /// - Required interfaces cannot be satisfied by available plugins, nor
+    ///   forwarded to peers
     /// - Plugin binding fails
     /// - Component linking fails
     /// - Plugin notification fails
@@ -1294,8 +2998,21 @@ impl UnresolvedWorkload {
         plugins: Option<&HashMap<&'static str, Arc<dyn HostPlugin + 'static>>>,
         http_handler: Arc<dyn crate::host::http::HostHandler>,
     ) -> anyhow::Result<ResolvedWorkload> {
+        let mut middleware = self.components.values().filter(|c| c.is_middleware());
+        if let Some(component) = middleware.next() {
+            ensure!(
+                middleware.next().is_none(),
+                "a workload may have at most one middleware component"
+            );
+            ensure!(
+                component.exports_wasi_http(),
+                "middleware component must export wasi:http/incoming-handler"
+            );
+        }
+
         // Bind to plugins
         let bound_plugins = if let Some(plugins) = plugins {
+            self.validate_world(plugins)?;
             trace!("binding plugins to workload");
             self.bind_plugins(plugins).await?
         } else {
@@ -1314,11 +3031,37 @@ impl UnresolvedWorkload {
                 true => self
                     .components
                     .values()
-                    .find(|component| component.exports_wasi_http())
+                    .find(|component| component.is_serving() && component.exports_wasi_http())
                     .map(|c| c.id().to_string()),
             }
         };
 
+        // Init and job components are never routed to
+        let unrouted: HashSet<Arc<str>> = self
+            .components
+            .iter()
+            .filter(|(_, component)| !component.is_serving())
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut components: Vec<&WorkloadComponent> = self.components.values().collect();
+        components.sort_by(|a, b| a.id().cmp(b.id()));
+        let provenance = self
+            .service
+            .iter()
+            .map(|service| &service.metadata)
+            .chain(components.into_iter().map(WorkloadComponent::metadata))
+            .map(|metadata| metadata.provenance().clone())
+            .collect();
+        let mut interfaces = self.interfaces;
+        interfaces.sort_by(|a, b| {
+            (&a.component_id, &a.requested, &a.plugin_id).cmp(&(
+                &b.component_id,
+                &b.requested,
+                &b.plugin_id,
+            ))
+        });
+
         // Resolve the workload
         let mut resolved_workload = ResolvedWorkload {
             id: self.id.clone(),
@@ -1328,6 +3071,19 @@ impl UnresolvedWorkload {
             service: self.service,
             host_interfaces: self.host_interfaces,
             http_handler: http_handler.clone(),
+            provenance,
+            interfaces: interfaces.into(),
+            started_at: Instant::now(),
+            invocations: Arc::default(),
+            metrics: self.metrics,
+            events: self.events,
+            logs: self.logs,
+            crash_loops: self
+                .crash_loop_policy
+                .map(|policy| Arc::new(CrashLoopTracker::new(policy))),
+            crash_loop_stops: self.crash_loop_stops,
+            #[cfg(feature = "instance-debug")]
+            instances: Arc::default(),
         };
 
         // Link components before plugin resolution
@@ -1341,6 +3097,19 @@ impl UnresolvedWorkload {
             bail!(e);
         }
 
+        // Run init components to completion before anything is routed to the workload
+        for component_id in &self.init_components {
+            if let Err(e) = resolved_workload.run_init_component(component_id).await {
+                warn!(
+                    component_id = component_id.as_ref(),
+                    error = ?e,
+                    "init component failed, unbinding all plugins"
+                );
+                let _ = resolved_workload.unbind_all_plugins().await;
+                bail!(e);
+            }
+        }
+
         // Notify plugins of the resolved workload
         for (plugin, component_ids) in bound_plugins.iter() {
             trace!(
@@ -1349,7 +3118,10 @@ impl UnresolvedWorkload {
                 "notifying plugin of resolved workload"
             );
             // Call on_workload_resolved for each component this plugin is bound to
-            for component_id in component_ids {
+            for component_id in component_ids
+                .iter()
+                .filter(|id| !unrouted.contains(id.as_str()))
+            {
                 if let Err(e) = plugin
                     .on_workload_resolved(&resolved_workload, component_id.as_str())
                     .await
@@ -1367,20 +3139,50 @@ impl UnresolvedWorkload {
             }
         }
 
-        if let Some(component_id) = incoming_http_component
-            && let Err(e) = http_handler
-                .on_workload_resolved(&resolved_workload, &component_id)
-                .await
-        {
+        // Instances are pre-initialized once plugins know the workload, before it serves
+        if let Err(e) = resolved_workload.start_pre_init().await {
             warn!(
-                component_id = component_id,
                 error = ?e,
-                "failed to notify HTTP handler of resolved workload, unbinding all plugins"
+                "failed to pre-initialize instances, unbinding all plugins"
             );
             let _ = resolved_workload.unbind_all_plugins().await;
             bail!(e);
         }
 
+        if let Some(component_id) = incoming_http_component {
+            if let Err(e) = http_handler
+                .on_workload_resolved(&resolved_workload, &component_id)
+                .await
+            {
+                warn!(
+                    component_id = component_id,
+                    error = ?e,
+                    "failed to notify HTTP handler of resolved workload, unbinding all plugins"
+                );
+                let _ = resolved_workload.unbind_all_plugins().await;
+                bail!(e);
+            }
+            let http_iface = WitInterface::from("wasi:http/incoming-handler");
+            let message = resolved_workload
+                .host_interfaces()
+                .iter()
+                .find(|iface| iface.contains(&http_iface))
+                .and_then(|iface| crate::host::http::HttpRouteConfig::try_from(iface).ok())
+                .map_or_else(
+                    || "registered HTTP route".to_string(),
+                    |route| format!("registered HTTP route {route}"),
+                );
+            resolved_workload.events.emit(
+                LifecycleEvent::new(
+                    LifecycleEventType::RouteRegistered,
+                    resolved_workload.id(),
+                    message,
+                )
+                .with_workload_name(resolved_workload.name())
+                .with_component_id(component_id),
+            );
+        }
+
         Ok(resolved_workload)
     }
 
@@ -1408,6 +3210,60 @@ impl UnresolvedWorkload {
     }
 }
 
+/// Returns how long to wait before retrying a failed job run for the
+/// `attempt + 1`th time.
+fn job_backoff(backoff_ms: u64, attempt: u32) -> Duration {
+    Duration::from_millis(backoff_ms.saturating_mul(1 << attempt.min(16)))
+}
+
+/// Samples the memory of a service instance until it is suspected of leaking
+/// and returns its growth since the first sample. Never returns without a
+/// policy.
+/// Applies `update` to the configuration of `component_id` through each of
+/// `plugins`, returning whether one of them holds its configuration.
+async fn update_plugins(
+    plugins: &Option<HashMap<&'static str, Arc<dyn HostPlugin + Send + Sync>>>,
+    component_id: &str,
+    update: &crate::types::ConfigUpdate,
+) -> anyhow::Result<bool> {
+    let mut updated = false;
+    for (id, plugin) in plugins.iter().flatten() {
+        updated |= plugin
+            .on_config_update(component_id, update)
+            .await
+            .with_context(|| format!("plugin '{id}' failed to update the configuration"))?;
+    }
+    Ok(updated)
+}
+
+async fn watch_service_memory(usage: Arc<AtomicUsize>, policy: Option<MemoryLeakPolicy>) -> usize {
+    let Some(policy) = policy else {
+        return std::future::pending().await;
+    };
+    let mut tracker = GrowthTracker::new(policy);
+    let mut interval = tokio::time::interval(policy.sample_interval);
+    loop {
+        interval.tick().await;
+        if let Some(growth) = tracker.observe(usage.load(Ordering::Relaxed)) {
+            return growth;
+        }
+    }
+}
+
+/// Returns the limit for each linear memory of a component: the smaller of its
+/// `memory_limit_mb`, where a non-positive value means unlimited, and the host's
+/// maximum.
+pub(crate) fn memory_limit(memory_limit_mb: i32, max_memory_bytes: Option<usize>) -> Option<usize> {
+    let component = usize::try_from(memory_limit_mb)
+        .ok()
+        .filter(|mb| *mb > 0)
+        .map(|mb| mb.saturating_mul(1024 * 1024));
+    match (component, max_memory_bytes) {
+        (Some(component), Some(host)) => Some(component.min(host)),
+        (component, host) => component.or(host),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1615,6 +3471,17 @@ mod tests {
         assert_eq!(records[0].method, "on_workload_bind");
         assert_eq!(records[1].method, "on_component_bind");
         assert_eq!(records[1].component_id.as_ref().unwrap(), &component_ids[0]);
+
+        // The binding is reported with the version the plugin provides
+        assert_eq!(
+            workload.interfaces,
+            vec![InterfaceBinding {
+                component_id: component_ids[0].clone(),
+                plugin_id: ID.to_string(),
+                requested: http_interface.to_string(),
+                version: http_interface.version.clone(),
+            }]
+        );
     }
 
     /// Tests complex binding scenarios with multiple plugins and components.
@@ -1838,4 +3705,129 @@ mod tests {
         // Show the difference between includes and includes_bidirectional
         assert!(!world.includes(&interface3));
     }
+
+    /// Tests that imports nothing links and declared interfaces no component
+    /// exports are reported before binding.
+    #[tokio::test]
+    async fn test_validate_world() {
+        let blobstore = WitInterface::from("wasi:blobstore/blobstore,container,types@0.2.0-draft");
+        let timers = WitInterface::from("wasmcloud:timers/handler@0.1.0");
+        let plugin = Arc::new(MockPlugin::new(
+            "blobstore-plugin",
+            vec![blobstore.clone()],
+            vec![timers.clone()],
+        ));
+        let plugins = HashMap::from([(plugin.id(), plugin as Arc<dyn HostPlugin>)]);
+        let component = create_test_component("component1");
+        let component_id = component.id().to_string();
+
+        let workload = UnresolvedWorkload::new(
+            "test-workload-id".to_string(),
+            "test-workload".to_string(),
+            "test-namespace".to_string(),
+            None,
+            vec![component],
+            vec![timers.clone()],
+        );
+        let Err(HostError::IncompatibleWorld { mismatches }) = workload.validate_world(&plugins)
+        else {
+            panic!("expected the world to be incompatible");
+        };
+        assert!(mismatches.contains(&WorldMismatch::UndeclaredImport {
+            component_id: component_id.clone(),
+            interface: WitInterface::from("wasi:blobstore/container@0.2.0-draft"),
+        }));
+        assert!(mismatches.contains(&WorldMismatch::MissingImport {
+            component_id,
+            interface: WitInterface::from("wasi:config/store@0.2.0-rc.1"),
+        }));
+        assert!(mismatches.contains(&WorldMismatch::MissingExport { interface: timers }));
+        // wasi@0.2 interfaces are linked by the engine
+        assert!(!mismatches.iter().any(|m| m.to_string().contains("wasi:io")));
+
+        // Declared imports are left to binding
+        let workload = UnresolvedWorkload::new(
+            "test-workload-id".to_string(),
+            "test-workload".to_string(),
+            "test-namespace".to_string(),
+            None,
+            vec![create_test_component("component1")],
+            vec![
+                blobstore,
+                WitInterface::from("wasi:config/store@0.2.0-rc.1"),
+            ],
+        );
+        let mismatches = match workload.validate_world(&plugins) {
+            Ok(()) => Vec::new(),
+            Err(HostError::IncompatibleWorld { mismatches }) => mismatches,
+            Err(e) => panic!("{e}"),
+        };
+        assert!(
+            !mismatches.iter().any(|m| {
+                let m = m.to_string();
+                m.contains("wasi:blobstore") || m.contains("wasi:config")
+            }),
+            "{mismatches:?}"
+        );
+    }
+
+    #[test]
+    fn test_link_order() {
+        let ids = |order: Vec<Arc<str>>| order.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let mut imports: HashMap<Arc<str>, Vec<String>> = HashMap::from([
+            (
+                Arc::from("handler"),
+                vec!["acme:logic/api@0.1.0".to_string()],
+            ),
+            (
+                Arc::from("logic"),
+                vec![
+                    "acme:storage/store@0.1.0".to_string(),
+                    "wasi:logging/logging@0.1.0-draft".to_string(),
+                ],
+            ),
+            // Importing its own export doesn't link a component to itself
+            (
+                Arc::from("storage"),
+                vec!["acme:storage/store@0.1.0".to_string()],
+            ),
+        ]);
+        let interface_map: HashMap<String, Arc<str>> = HashMap::from([
+            ("acme:logic/api@0.1.0".to_string(), Arc::from("logic")),
+            ("acme:storage/store@0.1.0".to_string(), Arc::from("storage")),
+        ]);
+        assert_eq!(
+            ids(link_order(&imports, &interface_map).unwrap()),
+            ["storage", "logic", "handler"]
+        );
+
+        imports.insert(
+            Arc::from("storage"),
+            vec!["acme:logic/api@0.1.0".to_string()],
+        );
+        let err = link_order(&imports, &interface_map).unwrap_err();
+        assert!(
+            err.to_string().ends_with("logic -> storage -> logic"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        const MIB: usize = 1024 * 1024;
+        assert_eq!(memory_limit(-1, None), None);
+        assert_eq!(memory_limit(0, Some(64 * MIB)), Some(64 * MIB));
+        assert_eq!(memory_limit(256, None), Some(256 * MIB));
+        assert_eq!(memory_limit(256, Some(64 * MIB)), Some(64 * MIB));
+        // Components may ask for more than 4 GiB when the host allows it
+        assert_eq!(memory_limit(8192, Some(16 * 1024 * MIB)), Some(8192 * MIB));
+    }
+
+    #[test]
+    fn test_job_backoff() {
+        assert_eq!(job_backoff(1_000, 0), Duration::from_secs(1));
+        assert_eq!(job_backoff(1_000, 3), Duration::from_secs(8));
+        assert_eq!(job_backoff(0, 5), Duration::ZERO);
+        assert_eq!(job_backoff(u64::MAX, 40), Duration::from_millis(u64::MAX));
+    }
 }
@@ -0,0 +1,295 @@
+//! Host identity and attestation reports.
+//!
+//! Every host has an Ed25519 [`HostIdentity`]. A host given a key file with
+//! [`HostBuilder::with_identity_file`](super::HostBuilder::with_identity_file)
+//! keeps its identity across restarts, other hosts get a new one each time
+//! they are built.
+//!
+//! [`HostApi::attestation`](super::HostApi::attestation) returns an
+//! [`AttestationReport`] signed with the identity key. It lists the host
+//! version, its plugins, labels and policy configuration, and optionally
+//! evidence from a trusted execution environment (TEE), so a control plane can
+//! verify what it is scheduling onto. The report carries a nonce chosen by the
+//! caller to prove it is fresh. TEE evidence is bound to the identity key and
+//! the nonce through [`report_data`], which ties the key to the measured
+//! environment.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use ring::rand::SystemRandom;
+use ring::signature::{ED25519, Ed25519KeyPair, KeyPair as _, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha512};
+
+/// The cryptographic identity of a host.
+pub struct HostIdentity {
+    keypair: Ed25519KeyPair,
+}
+
+impl HostIdentity {
+    /// Generates a new identity.
+    pub fn generate() -> anyhow::Result<Self> {
+        Self::from_pkcs8(&generate_pkcs8()?)
+    }
+
+    /// Loads an identity from a PKCS#8 encoded Ed25519 key.
+    pub fn from_pkcs8(pkcs8: &[u8]) -> anyhow::Result<Self> {
+        let keypair = Ed25519KeyPair::from_pkcs8(pkcs8)
+            .map_err(|e| anyhow::anyhow!("invalid host identity key: {e}"))?;
+        Ok(Self { keypair })
+    }
+
+    /// Loads the identity kept in `path`, generating and saving a new one if
+    /// the file does not exist. The file is only readable by its owner.
+    pub fn load_or_generate(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(pkcs8) => Self::from_pkcs8(&pkcs8)
+                .with_context(|| format!("failed to load host identity {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let pkcs8 = generate_pkcs8()?;
+                write_private(path, &pkcs8)
+                    .with_context(|| format!("failed to save host identity {}", path.display()))?;
+                Self::from_pkcs8(&pkcs8)
+            }
+            Err(e) => {
+                Err(e).with_context(|| format!("failed to read host identity {}", path.display()))
+            }
+        }
+    }
+
+    /// Returns the Ed25519 public key of the host.
+    pub fn public_key(&self) -> &[u8] {
+        self.keypair.public_key().as_ref()
+    }
+
+    /// Signs `message` with the identity key.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.keypair.sign(message).as_ref().to_vec()
+    }
+}
+
+impl std::fmt::Debug for HostIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostIdentity")
+            .field("public_key", &hex(self.public_key()))
+            .finish()
+    }
+}
+
+fn generate_pkcs8() -> anyhow::Result<Vec<u8>> {
+    Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map(|pkcs8| pkcs8.as_ref().to_vec())
+        .map_err(|_| anyhow::anyhow!("failed to generate host identity key"))
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write as _;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Returns the data TEE evidence is bound to, the SHA-512 of the host public
+/// key followed by the nonce of the request.
+pub fn report_data(public_key: &[u8], nonce: &str) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(public_key);
+    hasher.update(nonce.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Evidence from a trusted execution environment, such as a TDX or SEV-SNP
+/// attestation report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeeEvidence {
+    /// The kind of evidence, e.g. `tdx_guest`
+    pub kind: String,
+    #[serde(with = "crate::types::base64_bytes")]
+    pub data: Bytes,
+}
+
+/// Collects evidence from the trusted execution environment a host runs in.
+#[async_trait::async_trait]
+pub trait TeeEvidenceProvider: Send + Sync {
+    /// Returns evidence binding `report_data` to the environment.
+    async fn evidence(&self, report_data: &[u8; 64]) -> anyhow::Result<TeeEvidence>;
+}
+
+/// Collects TEE evidence through the Linux configfs-tsm interface, which
+/// covers TDX, SEV-SNP and other confidential computing guests.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub struct ConfigfsTsm {
+    root: std::path::PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl Default for ConfigfsTsm {
+    fn default() -> Self {
+        Self {
+            root: std::path::PathBuf::from("/sys/kernel/config/tsm/report"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[async_trait::async_trait]
+impl TeeEvidenceProvider for ConfigfsTsm {
+    async fn evidence(&self, report_data: &[u8; 64]) -> anyhow::Result<TeeEvidence> {
+        let dir = self.root.join(format!("wash-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir(&dir)
+            .await
+            .with_context(|| format!("failed to create TSM report {}", dir.display()))?;
+        let evidence = async {
+            tokio::fs::write(dir.join("inblob"), report_data)
+                .await
+                .context("failed to write TSM report data")?;
+            let data = tokio::fs::read(dir.join("outblob"))
+                .await
+                .context("failed to read TSM report")?;
+            let kind = tokio::fs::read_to_string(dir.join("provider"))
+                .await
+                .context("failed to read TSM provider")?;
+            anyhow::Ok(TeeEvidence {
+                kind: kind.trim().to_string(),
+                data: data.into(),
+            })
+        }
+        .await;
+        // Reports are kernel objects that must be removed explicitly
+        let _ = tokio::fs::remove_dir(&dir).await;
+        evidence
+    }
+}
+
+/// What a host reports about itself in an attestation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttestationReport {
+    pub host_id: String,
+    #[serde(with = "crate::types::base64_bytes")]
+    pub public_key: Bytes,
+    pub version: String,
+    /// The nonce of the request
+    pub nonce: String,
+    pub issued_at: chrono::DateTime<chrono::Utc>,
+    /// IDs of the plugins of the host
+    pub plugins: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    /// Security-relevant configuration, see
+    /// [`HostBuilder::with_policy`](super::HostBuilder::with_policy)
+    pub policies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tee_evidence: Option<TeeEvidence>,
+}
+
+/// Request for a signed [`AttestationReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttestationRequest {
+    /// Chosen by the caller, e.g. random bytes, to prove the report is fresh
+    pub nonce: String,
+}
+
+/// An [`AttestationReport`] as signed by a host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedAttestationReport {
+    /// The JSON encoded report, exactly as signed
+    pub report: Vec<u8>,
+    /// The Ed25519 signature of `report`
+    pub signature: Vec<u8>,
+    /// The public key of the host
+    pub public_key: Vec<u8>,
+}
+
+impl SignedAttestationReport {
+    /// Checks the signature and returns the report.
+    ///
+    /// This only proves the report was signed by the holder of `public_key`.
+    /// Callers must still check that the key is the one they expect for the
+    /// host, that the nonce is the one they sent and, for TEE evidence, that
+    /// it is valid and carries the [`report_data`] of the report.
+    pub fn verify(&self) -> anyhow::Result<AttestationReport> {
+        UnparsedPublicKey::new(&ED25519, &self.public_key)
+            .verify(&self.report, &self.signature)
+            .map_err(|_| anyhow::anyhow!("invalid attestation report signature"))?;
+        let report: AttestationReport =
+            serde_json::from_slice(&self.report).context("malformed attestation report")?;
+        anyhow::ensure!(
+            report.public_key.as_ref() == self.public_key.as_slice(),
+            "attestation report was signed by a different key than it names"
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(identity: &HostIdentity) -> SignedAttestationReport {
+        let report = AttestationReport {
+            host_id: "host".to_string(),
+            public_key: Bytes::copy_from_slice(identity.public_key()),
+            version: "1.0.0".to_string(),
+            nonce: "nonce".to_string(),
+            issued_at: chrono::Utc::now(),
+            plugins: vec!["wasi-config".to_string()],
+            labels: BTreeMap::new(),
+            policies: BTreeMap::from([("api_tokens".to_string(), "required".to_string())]),
+            tee_evidence: None,
+        };
+        let report = serde_json::to_vec(&report).unwrap();
+        SignedAttestationReport {
+            signature: identity.sign(&report),
+            report,
+            public_key: identity.public_key().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let identity = HostIdentity::generate().unwrap();
+        let signed = report(&identity);
+        assert_eq!(signed.verify().unwrap().nonce, "nonce");
+
+        let mut tampered = signed.clone();
+        tampered.report = String::from_utf8(tampered.report)
+            .unwrap()
+            .replace("required", "disabled")
+            .into_bytes();
+        assert!(tampered.verify().is_err());
+
+        // Signed by another key than the report names
+        let other = HostIdentity::generate().unwrap();
+        let mut resigned = signed.clone();
+        resigned.signature = other.sign(&resigned.report);
+        resigned.public_key = other.public_key().to_vec();
+        assert!(resigned.verify().is_err());
+    }
+
+    #[test]
+    fn test_identity_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys/host.pk8");
+        let first = HostIdentity::load_or_generate(&path).unwrap();
+        let second = HostIdentity::load_or_generate(&path).unwrap();
+        assert_eq!(first.public_key(), second.public_key());
+        assert_ne!(
+            HostIdentity::generate().unwrap().public_key(),
+            first.public_key()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Devices a host gives workloads exclusive access to.
+//!
+//! A host advertises the devices it has, e.g. a GPU backing a `wasi:nn` or
+//! `wasi:webgpu` plugin, or the serial ports of an edge device, see
+//! [`HostBuilder::with_device`](super::HostBuilder::with_device). A component
+//! requests devices by class in its
+//! [`LocalResources::devices`](crate::types::LocalResources::devices):
+//!
+//! ```json
+//! "devices": [{ "class": "gpu", "count": 1 }]
+//! ```
+//!
+//! A workload starts only if the host has a free device of the class for
+//! every device its components request, otherwise it fails with
+//! [`HostError::ResourceExhausted`], so a scheduler places it on another
+//! host. Devices are held by the workload until it stops. The names of the
+//! devices given to a component are set in its `devices` config key, comma
+//! separated, so the plugins it uses know which devices to open.
+//!
+//! Every heartbeat lists the devices of the host and the workload holding
+//! each of them, so schedulers can place workloads on hosts with free
+//! devices in the first place.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use anyhow::{Context as _, bail};
+use serde::{Deserialize, Serialize};
+
+use super::{HostError, HostResult};
+use crate::types::{DeviceClass, LocalResources, Workload};
+
+/// Config key of a component listing the names of the devices given to it
+pub const DEVICES_CONFIG_KEY: &str = "devices";
+
+/// A device of a host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Device {
+    /// Unique name of the device on the host. Devices without a name are
+    /// named after the file name of their path.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub class: DeviceClass,
+    /// Path of the device, e.g. `/dev/dri/renderD128` or `/dev/ttyUSB0`
+    pub path: PathBuf,
+}
+
+impl Device {
+    /// Creates a device named after the file name of its `path`.
+    pub fn new(class: DeviceClass, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            name: file_name(&path),
+            class,
+            path,
+        }
+    }
+
+    /// Names the device `name` instead.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+/// Parses `CLASS=PATH` or `CLASS:NAME=PATH`, e.g. `gpu=/dev/dri/renderD128`
+/// or `serial:gps=/dev/ttyUSB0`.
+impl FromStr for Device {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, path) = s
+            .split_once('=')
+            .with_context(|| format!("invalid device '{s}', expected CLASS=PATH"))?;
+        let (class, name) = match class.split_once(':') {
+            Some((class, name)) => (class, Some(name)),
+            None => (class, None),
+        };
+        if path.is_empty() || name.is_some_and(str::is_empty) {
+            bail!("invalid device '{s}', expected CLASS=PATH or CLASS:NAME=PATH");
+        }
+        let device = Device::new(class.parse()?, path);
+        Ok(match name {
+            Some(name) => device.with_name(name),
+            None => device,
+        })
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// A device of a host and the workload holding it, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceStatus {
+    pub device: Device,
+    pub workload_id: Option<String>,
+}
+
+/// Gives the devices of a host to workloads.
+#[derive(Debug, Default)]
+pub struct DeviceManager {
+    devices: Vec<Device>,
+    /// The workload holding each device, by device name
+    holders: Mutex<HashMap<String, String>>,
+}
+
+impl DeviceManager {
+    /// Manages `devices`.
+    ///
+    /// # Errors
+    /// Returns an error if two devices have the same name.
+    pub fn new(mut devices: Vec<Device>) -> anyhow::Result<Self> {
+        for device in devices.iter_mut().filter(|device| device.name.is_empty()) {
+            device.name = file_name(&device.path);
+        }
+        for (i, device) in devices.iter().enumerate() {
+            if devices[..i].iter().any(|other| other.name == device.name) {
+                bail!("device name '{}' is used twice", device.name);
+            }
+        }
+        Ok(Self {
+            devices,
+            holders: Mutex::default(),
+        })
+    }
+
+    /// Returns every device and the workload holding it.
+    pub fn status(&self) -> Vec<DeviceStatus> {
+        let holders = self.holders();
+        self.devices
+            .iter()
+            .map(|device| DeviceStatus {
+                device: device.clone(),
+                workload_id: holders.get(&device.name).cloned(),
+            })
+            .collect()
+    }
+
+    /// Gives the devices its components request to a workload, setting the
+    /// [`DEVICES_CONFIG_KEY`] of each component.
+    ///
+    /// # Errors
+    /// Returns [`HostError::ResourceExhausted`] if the host has too few free
+    /// devices of a class. The workload then holds no device.
+    pub fn acquire(&self, workload_id: &str, workload: &mut Workload) -> HostResult<()> {
+        let mut holders = self.holders();
+        let mut taken = Vec::new();
+        let resources = workload
+            .service
+            .iter_mut()
+            .map(|service| &mut service.local_resources)
+            .chain(
+                workload
+                    .components
+                    .iter_mut()
+                    .map(|component| &mut component.local_resources),
+            );
+        for resources in resources {
+            let names = self.take(&holders, &taken, resources)?;
+            taken.extend(names);
+        }
+        for name in taken {
+            holders.insert(name, workload_id.to_string());
+        }
+        Ok(())
+    }
+
+    /// Picks free devices for `resources`, not among `taken`, and lists them
+    /// in its config. Returns their names.
+    fn take(
+        &self,
+        holders: &HashMap<String, String>,
+        taken: &[String],
+        resources: &mut LocalResources,
+    ) -> HostResult<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        for request in &resources.devices {
+            let free = self
+                .devices
+                .iter()
+                .filter(|device| device.class == request.class)
+                .filter(|device| {
+                    !holders.contains_key(&device.name)
+                        && !taken.contains(&device.name)
+                        && !names.contains(&device.name)
+                })
+                .map(|device| device.name.clone())
+                .take(request.count as usize)
+                .collect::<Vec<_>>();
+            if free.len() < request.count as usize {
+                return Err(HostError::ResourceExhausted(format!(
+                    "the workload requests {} {} devices, the host has {} free",
+                    request.count,
+                    request.class,
+                    free.len()
+                )));
+            }
+            names.extend(free);
+        }
+        if !names.is_empty() {
+            resources
+                .config
+                .insert(DEVICES_CONFIG_KEY.to_string(), names.join(","));
+        }
+        Ok(names)
+    }
+
+    /// Takes back the devices held by a workload.
+    pub fn release(&self, workload_id: &str) {
+        self.holders().retain(|_, holder| holder != workload_id);
+    }
+
+    fn holders(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.holders.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Component, DeviceRequest};
+
+    fn workload(requests: &[(DeviceClass, u32)]) -> Workload {
+        let mut component = Component::default();
+        component.local_resources.devices = requests
+            .iter()
+            .map(|(class, count)| DeviceRequest {
+                class: *class,
+                count: *count,
+            })
+            .collect();
+        Workload::builder("default", "devices")
+            .with_component(component)
+            .build()
+    }
+
+    #[test]
+    fn test_parse_device() {
+        assert_eq!(
+            "gpu=/dev/dri/renderD128".parse::<Device>().unwrap(),
+            Device::new(DeviceClass::Gpu, "/dev/dri/renderD128")
+        );
+        let serial = "serial:gps=/dev/ttyUSB0".parse::<Device>().unwrap();
+        assert_eq!(serial.name, "gps");
+        assert_eq!(serial.class, DeviceClass::Serial);
+        assert!("gpu".parse::<Device>().is_err());
+        assert!("gpu=".parse::<Device>().is_err());
+        assert!("tpu=/dev/tpu0".parse::<Device>().is_err());
+        assert!(
+            DeviceManager::new(vec![
+                Device::new(DeviceClass::Gpu, "/dev/dri/renderD128"),
+                Device::new(DeviceClass::Serial, "/dev/renderD128"),
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_acquire_devices() {
+        let devices = DeviceManager::new(vec![
+            Device::new(DeviceClass::Gpu, "/dev/dri/renderD128"),
+            Device::new(DeviceClass::Gpu, "/dev/dri/renderD129"),
+            Device::new(DeviceClass::Serial, "/dev/ttyUSB0"),
+        ])
+        .unwrap();
+
+        let mut first = workload(&[(DeviceClass::Gpu, 1), (DeviceClass::Serial, 1)]);
+        devices.acquire("w1", &mut first).unwrap();
+        assert_eq!(
+            first.components[0].local_resources.config[DEVICES_CONFIG_KEY],
+            "renderD128,ttyUSB0"
+        );
+
+        // Not enough devices left, and nothing is held on failure
+        let mut second = workload(&[(DeviceClass::Gpu, 1), (DeviceClass::Serial, 1)]);
+        assert!(matches!(
+            devices.acquire("w2", &mut second),
+            Err(HostError::ResourceExhausted(_))
+        ));
+        assert_eq!(
+            devices
+                .status()
+                .iter()
+                .filter(|status| status.workload_id.is_some())
+                .count(),
+            2
+        );
+
+        let mut gpu = workload(&[(DeviceClass::Gpu, 1)]);
+        devices.acquire("w3", &mut gpu).unwrap();
+        assert_eq!(
+            gpu.components[0].local_resources.config[DEVICES_CONFIG_KEY],
+            "renderD129"
+        );
+
+        devices.release("w1");
+        devices.acquire("w2", &mut second).unwrap();
+        let status = devices.status();
+        assert_eq!(status[0].workload_id.as_deref(), Some("w2"));
+        assert_eq!(status[1].workload_id.as_deref(), Some("w3"));
+    }
+}
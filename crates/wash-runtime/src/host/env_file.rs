@@ -0,0 +1,250 @@
+//! Dotenv files in the environment of components.
+//!
+//! A service or component may list [`EnvFile`]s in its
+//! [`LocalResources::env_files`], read from the host or from a secret of the
+//! host's [`SecretStore`] when the workload starts. Their variables are added
+//! to its environment in order, so a later file overrides an earlier one, and
+//! variables set in [`LocalResources::environment`] override them all:
+//!
+//! ```text
+//! # Comments and blank lines are skipped
+//! export DATABASE_URL=postgres://db.internal:5432/app
+//! GREETING="Hello,\nworld"   # escapes in double quotes
+//! PATTERN='${not} $interpolated'
+//! ```
+//!
+//! Values of env files are taken literally: `${env:..}` and `${secret:..}`
+//! references are only resolved in the workload definition, see
+//! [`super::interpolate`]. A file that can't be read or parsed fails the
+//! workload start with [`HostError::InvalidWorkload`].
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, ensure};
+
+use crate::host::HostError;
+use crate::host::interpolate::{SecretStore, lookup_secret};
+use crate::types::{EnvFile, LocalResources, Workload};
+
+/// Adds the variables of the env files of every service and component in
+/// `workload` to their environment, in place.
+///
+/// # Errors
+/// Returns [`HostError::InvalidWorkload`] naming the env file if it can't be
+/// read or parsed.
+pub async fn load_env_files(
+    workload: &mut Workload,
+    secrets: Option<&dyn SecretStore>,
+) -> Result<(), HostError> {
+    let mut targets: Vec<(String, &mut LocalResources)> = Vec::new();
+    if let Some(service) = workload.service.as_mut() {
+        targets.push(("service".to_string(), &mut service.local_resources));
+    }
+    for (i, component) in workload.components.iter_mut().enumerate() {
+        targets.push((format!("component {i}"), &mut component.local_resources));
+    }
+
+    for (owner, resources) in targets {
+        let mut vars = Vec::new();
+        for env_file in &resources.env_files {
+            let content = match env_file {
+                EnvFile::HostPath(path) => tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("failed to read {path}")),
+                EnvFile::Secret(name) => lookup_secret(secrets, name).await,
+            };
+            let parsed = content.and_then(|content| parse(&content)).map_err(|e| {
+                HostError::InvalidWorkload(format!("{owner} env file {env_file}: {e:#}"))
+            })?;
+            vars.extend(parsed);
+        }
+        // Later files override earlier ones, the environment overrides them all
+        let from_files: HashMap<String, String> = vars.into_iter().collect();
+        for (key, value) in from_files {
+            resources.environment.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
+}
+
+/// Parses the `KEY=VALUE` lines of a dotenv file, in order.
+///
+/// # Errors
+/// Returns an error naming the line of a malformed variable or an
+/// unterminated quoted value.
+pub fn parse(content: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("line {}: expected KEY=VALUE", i + 1))?;
+        let key = key.trim_end();
+        ensure!(
+            !key.is_empty()
+                && !key.starts_with(|c: char| c.is_ascii_digit())
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+            "line {}: invalid variable name '{key}'",
+            i + 1
+        );
+
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // Quoted values may span several lines
+                let mut raw = value[1..].to_string();
+                let end = loop {
+                    if let Some(end) = closing_quote(&raw, quote) {
+                        break end;
+                    }
+                    let (_, next) = lines
+                        .next()
+                        .with_context(|| format!("line {}: unterminated quoted value", i + 1))?;
+                    raw.push('\n');
+                    raw.push_str(next);
+                };
+                let rest = raw[end + 1..].trim_start();
+                ensure!(
+                    rest.is_empty() || rest.starts_with('#'),
+                    "line {}: unexpected '{rest}' after quoted value",
+                    i + 1
+                );
+                raw.truncate(end);
+                if quote == '"' { unescape(&raw) } else { raw }
+            }
+            _ => value
+                .split(" #")
+                .next()
+                .unwrap_or_default()
+                .trim_end()
+                .to_string(),
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// Returns the position of the quote closing a value, skipping quotes escaped
+/// in double-quoted values.
+fn closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('"' | '\\')) => out.push(c),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Component;
+
+    #[test]
+    fn test_parse() {
+        let vars = parse(
+            r#"
+# Database
+export DATABASE_URL=postgres://db:5432/app
+EMPTY=
+PORT = 8080 # inline comment
+GREETING="Hello,\n\"world\"" # comment
+LITERAL='${not} \n $interpolated'
+KEY="-----BEGIN KEY-----
+abc
+-----END KEY-----"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            [
+                ("DATABASE_URL", "postgres://db:5432/app"),
+                ("EMPTY", ""),
+                ("PORT", "8080"),
+                ("GREETING", "Hello,\n\"world\""),
+                ("LITERAL", "${not} \\n $interpolated"),
+                ("KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        for (content, error) in [
+            ("NO_VALUE", "line 1: expected KEY=VALUE"),
+            ("\n1KEY=x", "line 2: invalid variable name '1KEY'"),
+            ("KEY=\"open", "line 1: unterminated quoted value"),
+            ("KEY='a' b", "line 1: unexpected 'b' after quoted value"),
+        ] {
+            let err = parse(content).unwrap_err();
+            assert_eq!(err.to_string(), error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_env_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.env");
+        std::fs::write(&path, "A=from-file\nB=from-file\nC=from-file\n").unwrap();
+
+        let mut workload = Workload::builder("default", "app")
+            .with_component(
+                Component::new(&b""[..])
+                    .with_env("A", "from-environment")
+                    .with_env_file(EnvFile::HostPath(path.display().to_string()))
+                    .with_env_file(EnvFile::Secret("app-env".to_string())),
+            )
+            .build();
+        let secrets = HashMap::from([("app-env".to_string(), "B=from-secret".to_string())]);
+        load_env_files(&mut workload, Some(&secrets)).await.unwrap();
+        let environment = &workload.components[0].local_resources.environment;
+        assert_eq!(environment["A"], "from-environment");
+        assert_eq!(environment["B"], "from-secret");
+        assert_eq!(environment["C"], "from-file");
+
+        let mut workload = Workload::builder("default", "app")
+            .with_component(
+                Component::new(&b""[..])
+                    .with_env_file(EnvFile::HostPath("/does/not/exist.env".to_string())),
+            )
+            .build();
+        let err = load_env_files(&mut workload, None).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("component 0 env file /does/not/exist.env: failed to read"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Structured errors returned by the [`crate::host::HostApi`].
+//!
+//! Every [`HostError`] variant carries a stable machine-readable code (see
+//! [`HostError::code`]) and maps onto a gRPC status code and an HTTP status
+//! code, so API frontends can surface failures without inspecting messages.
+//!
+//! Errors raised deep inside workload resolution are transported as
+//! [`anyhow::Error`] and recovered with [`HostError::from`], which downcasts
+//! to a [`HostError`] when one was raised and otherwise falls back to
+//! [`HostError::Internal`].
+
+use crate::wit::{VersionConflict, WitInterface, WorldMismatch};
+
+/// Result type for [`crate::host::HostApi`] operations.
+pub type HostResult<T> = Result<T, HostError>;
+
+/// An error returned by a [`crate::host::HostApi`] operation.
+#[derive(Debug)]
+pub enum HostError {
+    /// The workload definition is invalid, e.g. a component failed to compile
+    /// or a volume does not exist.
+    InvalidWorkload(String),
+    /// A component requested host interfaces that no plugin on this host provides.
+    UnsatisfiedImport {
+        /// The component that requested the interfaces
+        component_id: String,
+        /// The interfaces that could not be satisfied
+        interfaces: Vec<WitInterface>,
+    },
+    /// A component requested host interfaces that plugins on this host only
+    /// provide at incompatible versions.
+    VersionConflict {
+        /// The component that requested the interfaces
+        component_id: String,
+        /// The requested interfaces and the versions available instead
+        conflicts: Vec<VersionConflict>,
+    },
+    /// The components of the workload import interfaces nothing provides or
+    /// the workload doesn't declare, or don't export interfaces it declares.
+    IncompatibleWorld {
+        /// Every mismatch, in the order of the components
+        mismatches: Vec<WorldMismatch>,
+    },
+    /// The workload's routes conflict with a workload that is already running.
+    RouteConflict(String),
+    /// The host does not have capacity to run the workload.
+    ResourceExhausted(String),
+    /// The workload exceeds a quota of the host or of its namespace, see
+    /// [`crate::host::limits`].
+    QuotaExceeded(String),
+    /// The requested resource, usually a workload, does not exist.
+    NotFound(String),
+    /// The request itself is malformed, e.g. a volume name is invalid.
+    InvalidRequest(String),
+    /// A resource with the requested name, such as a volume, already exists.
+    AlreadyExists(String),
+    /// The resource, such as a volume, is used by a running workload.
+    InUse(String),
+    /// Any other failure inside the host.
+    Internal(anyhow::Error),
+}
+
+impl HostError {
+    /// Returns a stable, machine-readable code for the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            HostError::InvalidWorkload(_) => "INVALID_WORKLOAD",
+            HostError::UnsatisfiedImport { .. } => "UNSATISFIED_IMPORT",
+            HostError::VersionConflict { .. } => "VERSION_CONFLICT",
+            HostError::IncompatibleWorld { .. } => "INCOMPATIBLE_WORLD",
+            HostError::RouteConflict(_) => "ROUTE_CONFLICT",
+            HostError::ResourceExhausted(_) => "RESOURCE_EXHAUSTED",
+            HostError::QuotaExceeded(_) => "QUOTA_EXCEEDED",
+            HostError::NotFound(_) => "NOT_FOUND",
+            HostError::InvalidRequest(_) => "INVALID_REQUEST",
+            HostError::AlreadyExists(_) => "ALREADY_EXISTS",
+            HostError::InUse(_) => "IN_USE",
+            HostError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// Returns the gRPC status code that corresponds to the error.
+    pub fn grpc_code(&self) -> tonic::Code {
+        match self {
+            HostError::InvalidWorkload(_) => tonic::Code::InvalidArgument,
+            HostError::UnsatisfiedImport { .. } => tonic::Code::FailedPrecondition,
+            HostError::VersionConflict { .. } => tonic::Code::FailedPrecondition,
+            HostError::IncompatibleWorld { .. } => tonic::Code::FailedPrecondition,
+            HostError::RouteConflict(_) => tonic::Code::AlreadyExists,
+            HostError::ResourceExhausted(_) => tonic::Code::ResourceExhausted,
+            HostError::QuotaExceeded(_) => tonic::Code::ResourceExhausted,
+            HostError::NotFound(_) => tonic::Code::NotFound,
+            HostError::InvalidRequest(_) => tonic::Code::InvalidArgument,
+            HostError::AlreadyExists(_) => tonic::Code::AlreadyExists,
+            HostError::InUse(_) => tonic::Code::FailedPrecondition,
+            HostError::Internal(_) => tonic::Code::Internal,
+        }
+    }
+
+    /// Returns the HTTP status code that corresponds to the error.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            HostError::InvalidWorkload(_) => 400,
+            HostError::UnsatisfiedImport { .. } => 422,
+            HostError::VersionConflict { .. } => 422,
+            HostError::IncompatibleWorld { .. } => 422,
+            HostError::RouteConflict(_) => 409,
+            HostError::ResourceExhausted(_) => 429,
+            HostError::QuotaExceeded(_) => 429,
+            HostError::NotFound(_) => 404,
+            HostError::InvalidRequest(_) => 400,
+            HostError::AlreadyExists(_) => 409,
+            HostError::InUse(_) => 409,
+            HostError::Internal(_) => 500,
+        }
+    }
+
+    /// Converts an [`anyhow::Error`] into a [`HostError`], using `fallback` to
+    /// wrap the message when the error did not originate as a [`HostError`].
+    pub fn from_anyhow_or(e: anyhow::Error, fallback: impl FnOnce(String) -> HostError) -> Self {
+        match e.downcast::<HostError>() {
+            Ok(host_error) => host_error,
+            Err(e) => fallback(format!("{e:#}")),
+        }
+    }
+}
+
+impl std::fmt::Display for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostError::InvalidWorkload(msg) => write!(f, "invalid workload: {msg}"),
+            HostError::UnsatisfiedImport {
+                component_id,
+                interfaces,
+            } => {
+                let interfaces: Vec<String> = interfaces.iter().map(|i| i.to_string()).collect();
+                write!(
+                    f,
+                    "workload component {component_id} requested interfaces that are not available on this host: {}",
+                    interfaces.join(", ")
+                )
+            }
+            HostError::VersionConflict {
+                component_id,
+                conflicts,
+            } => {
+                let conflicts: Vec<String> = conflicts
+                    .iter()
+                    .map(|c| {
+                        let available: Vec<String> =
+                            c.available.iter().map(|v| v.to_string()).collect();
+                        format!("{} (available: {})", c.required, available.join(", "))
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "workload component {component_id} requested interface versions that are not available on this host: {}",
+                    conflicts.join(", ")
+                )
+            }
+            HostError::IncompatibleWorld { mismatches } => {
+                let mismatches: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+                write!(
+                    f,
+                    "workload does not match the interfaces available on this host: {}",
+                    mismatches.join("; ")
+                )
+            }
+            HostError::RouteConflict(msg) => write!(f, "route conflict: {msg}"),
+            HostError::ResourceExhausted(msg) => write!(f, "resource exhausted: {msg}"),
+            HostError::QuotaExceeded(msg) => write!(f, "quota exceeded: {msg}"),
+            HostError::NotFound(msg) => write!(f, "not found: {msg}"),
+            HostError::InvalidRequest(msg) => write!(f, "invalid request: {msg}"),
+            HostError::AlreadyExists(msg) => write!(f, "already exists: {msg}"),
+            HostError::InUse(msg) => write!(f, "in use: {msg}"),
+            HostError::Internal(e) => write!(f, "{e:#}"),
+        }
+    }
+}
+
+impl std::error::Error for HostError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HostError::Internal(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for HostError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<HostError>() {
+            Ok(host_error) => host_error,
+            Err(e) => HostError::Internal(e),
+        }
+    }
+}
+
+impl From<HostError> for tonic::Status {
+    fn from(e: HostError) -> Self {
+        tonic::Status::new(e.grpc_code(), e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_error_downcast_from_anyhow() {
+        let err = anyhow::Error::new(HostError::NotFound("workload abc".to_string()))
+            .context("failed to query workload");
+        let host_error = HostError::from(err);
+        assert!(matches!(host_error, HostError::NotFound(_)));
+        assert_eq!(host_error.code(), "NOT_FOUND");
+        assert_eq!(host_error.http_status(), 404);
+        assert_eq!(host_error.grpc_code(), tonic::Code::NotFound);
+
+        let host_error = HostError::from(anyhow::anyhow!("boom"));
+        assert!(matches!(host_error, HostError::Internal(_)));
+        assert_eq!(host_error.to_string(), "boom");
+        let source = std::error::Error::source(&host_error).expect("internal error has a source");
+        assert_eq!(source.to_string(), "boom");
+
+        let host_error =
+            HostError::from_anyhow_or(anyhow::anyhow!("bad bytes"), HostError::InvalidWorkload);
+        assert_eq!(host_error.to_string(), "invalid workload: bad bytes");
+    }
+
+    #[test]
+    fn test_unsatisfied_import_message() {
+        let host_error = HostError::UnsatisfiedImport {
+            component_id: "component-1".to_string(),
+            interfaces: vec![WitInterface::from("wasi:blobstore/container@0.2.0-draft")],
+        };
+        assert_eq!(
+            host_error.to_string(),
+            "workload component component-1 requested interfaces that are not available on this host: wasi:blobstore/container@0.2.0-draft"
+        );
+        let status = tonic::Status::from(host_error);
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn test_version_conflict_message() {
+        let host_error = HostError::VersionConflict {
+            component_id: "component-1".to_string(),
+            conflicts: vec![VersionConflict {
+                required: WitInterface::from("wasi:http/outgoing-handler@0.3.0"),
+                available: vec![semver::Version::new(0, 2, 0)],
+            }],
+        };
+        assert_eq!(host_error.code(), "VERSION_CONFLICT");
+        assert_eq!(
+            host_error.to_string(),
+            "workload component component-1 requested interface versions that are not available on this host: wasi:http/outgoing-handler@0.3.0 (available: 0.2.0)"
+        );
+    }
+
+    #[test]
+    fn test_incompatible_world_message() {
+        let host_error = HostError::IncompatibleWorld {
+            mismatches: vec![
+                WorldMismatch::UndeclaredImport {
+                    component_id: "component-1".to_string(),
+                    interface: WitInterface::from("wasi:keyvalue/store@0.2.0-draft"),
+                },
+                WorldMismatch::MissingExport {
+                    interface: WitInterface::from("wasmcloud:timers/handler@0.1.0"),
+                },
+            ],
+        };
+        assert_eq!(host_error.code(), "INCOMPATIBLE_WORLD");
+        assert_eq!(
+            host_error.to_string(),
+            "workload does not match the interfaces available on this host: component component-1 imports wasi:keyvalue/store@0.2.0-draft, which the workload does not declare in its host interfaces; the workload declares wasmcloud:timers/handler@0.1.0, which no component exports"
+        );
+        assert_eq!(host_error.http_status(), 422);
+    }
+}
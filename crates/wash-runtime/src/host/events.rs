@@ -0,0 +1,346 @@
+//! Workload lifecycle events.
+//!
+//! The host emits a [`LifecycleEvent`] when a workload starts, fails to start,
+//! stops, or when its jobs or service finish or crash, for each step of a
+//! [rollout](super::rollout), when a workload registers its HTTP route, when
+//! an instance of a component is created or recycled or an invocation fails,
+//! and when a component starts or stops crash looping. Events are handed to
+//! the [`LifecycleEventSink`]s registered with
+//! [`HostBuilder::with_event_sink`](super::HostBuilder::with_event_sink) in the
+//! background, so a slow or unreachable sink never delays a workload.
+//!
+//! Events are also published on a bus inside the host, which code embedding
+//! the host reads with [`Host::subscribe_events`](super::Host::subscribe_events)
+//! and remote clients with the `WorkloadEvents` RPC of the
+//! [gRPC API](crate::washlet::grpc). A subscriber falling behind by more than
+//! [`EVENT_BUFFER`] events misses the oldest ones.
+//!
+//! Instance creations and failed invocations can happen on every invocation
+//! of a busy workload, see [`LifecycleEventType::is_frequent`], so webhooks
+//! only receive them when they ask for them.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Events a subscriber of the bus may fall behind by before it misses some
+pub const EVENT_BUFFER: usize = 1024;
+
+/// What happened to a workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEventType {
+    WorkloadStarted,
+    /// The workload could not be started
+    WorkloadStartFailed,
+    WorkloadStopped,
+    /// A new version of the workload replaced the old one, see
+    /// [`crate::host::HostApi::workload_update`]
+    WorkloadUpdated,
+    /// The configuration of the workload changed while it runs, see
+    /// [`crate::host::HostApi::workload_config_update`]
+    WorkloadConfigUpdated,
+    /// Every job component of the workload finished successfully
+    WorkloadCompleted,
+    /// A job component of the workload failed
+    WorkloadFailed,
+    /// The service of the workload exited with an error
+    ServiceCrashed,
+    /// The memory of the workload's service kept growing, see
+    /// [`crate::engine::memory`]
+    MemoryLeakSuspected,
+    /// An instance of a component trapped for exceeding its memory limit, see
+    /// [`crate::engine::memory`]
+    MemoryLimitExceeded,
+    /// A write through a volume mount of the workload exceeded a quota of the
+    /// mount, see [`crate::engine::volume`]
+    VolumeQuotaExceeded,
+    /// A rollout started replacing the workload, see [`crate::host::rollout`]
+    RolloutStarted,
+    /// The new version of a rollout baked without exceeding a threshold
+    RolloutSucceeded,
+    /// The new version of a rollout exceeded a threshold and was replaced by
+    /// the previous one
+    RolloutRolledBack,
+    /// A version failed to start during a rollout
+    RolloutFailed,
+    /// The workload registered its route on the HTTP server
+    RouteRegistered,
+    /// An instance of a component was created to serve an invocation or run
+    /// a service
+    InstanceCreated,
+    /// An instance of a service was replaced with a fresh one, see
+    /// [`crate::engine::memory`] and
+    /// [`ConfigChangePolicy`](crate::engine::workload::ConfigChangePolicy)
+    InstanceRecycled,
+    /// An invocation of a component returned an error or trapped
+    InvocationFailed,
+    /// A component failed too many times in a row and backs off, see
+    /// [`crate::engine::crash_loop`]
+    CrashLoopDetected,
+    /// A crash looping component succeeded again
+    CrashLoopRecovered,
+}
+
+impl LifecycleEventType {
+    /// Returns the name of the event type, e.g. `workload_started`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleEventType::WorkloadStarted => "workload_started",
+            LifecycleEventType::WorkloadStartFailed => "workload_start_failed",
+            LifecycleEventType::WorkloadStopped => "workload_stopped",
+            LifecycleEventType::WorkloadUpdated => "workload_updated",
+            LifecycleEventType::WorkloadConfigUpdated => "workload_config_updated",
+            LifecycleEventType::WorkloadCompleted => "workload_completed",
+            LifecycleEventType::WorkloadFailed => "workload_failed",
+            LifecycleEventType::ServiceCrashed => "service_crashed",
+            LifecycleEventType::MemoryLeakSuspected => "memory_leak_suspected",
+            LifecycleEventType::MemoryLimitExceeded => "memory_limit_exceeded",
+            LifecycleEventType::VolumeQuotaExceeded => "volume_quota_exceeded",
+            LifecycleEventType::RolloutStarted => "rollout_started",
+            LifecycleEventType::RolloutSucceeded => "rollout_succeeded",
+            LifecycleEventType::RolloutRolledBack => "rollout_rolled_back",
+            LifecycleEventType::RolloutFailed => "rollout_failed",
+            LifecycleEventType::RouteRegistered => "route_registered",
+            LifecycleEventType::InstanceCreated => "instance_created",
+            LifecycleEventType::InstanceRecycled => "instance_recycled",
+            LifecycleEventType::InvocationFailed => "invocation_failed",
+            LifecycleEventType::CrashLoopDetected => "crash_loop_detected",
+            LifecycleEventType::CrashLoopRecovered => "crash_loop_recovered",
+        }
+    }
+
+    /// Returns whether events of the type are emitted as often as workloads
+    /// are invoked, rather than when their state changes.
+    pub fn is_frequent(&self) -> bool {
+        matches!(
+            self,
+            LifecycleEventType::InstanceCreated | LifecycleEventType::InvocationFailed
+        )
+    }
+}
+
+impl std::str::FromStr for LifecycleEventType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow::anyhow!("unknown lifecycle event type '{s}'"))
+    }
+}
+
+/// A change in the state of a workload on a host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: LifecycleEventType,
+    pub host_id: String,
+    pub workload_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload_name: Option<String>,
+    /// The component that crashed, for failures of a single component
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_id: Option<String>,
+    /// The rollout, for events of [`crate::host::rollout`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollout_id: Option<String>,
+    pub message: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl LifecycleEvent {
+    /// Creates an event that happened now. The host id is filled in when the
+    /// event is emitted.
+    pub fn new(
+        event_type: LifecycleEventType,
+        workload_id: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            event_type,
+            host_id: String::new(),
+            workload_id: workload_id.into(),
+            workload_name: None,
+            component_id: None,
+            rollout_id: None,
+            message: message.into(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    pub fn with_workload_name(mut self, workload_name: impl Into<String>) -> Self {
+        self.workload_name = Some(workload_name.into());
+        self
+    }
+
+    pub fn with_component_id(mut self, component_id: impl Into<String>) -> Self {
+        self.component_id = Some(component_id.into());
+        self
+    }
+
+    pub fn with_rollout_id(mut self, rollout_id: impl Into<String>) -> Self {
+        self.rollout_id = Some(rollout_id.into());
+        self
+    }
+}
+
+/// Receives the lifecycle events of a host, such as a webhook.
+#[async_trait::async_trait]
+pub trait LifecycleEventSink: Send + Sync {
+    /// Delivers an event. Errors are logged and the event is dropped.
+    async fn send(&self, event: &LifecycleEvent) -> anyhow::Result<()>;
+}
+
+/// Hands events to the sinks and subscribers of a host.
+#[derive(Clone)]
+pub struct LifecycleEvents {
+    host_id: String,
+    sinks: Arc<[Arc<dyn LifecycleEventSink>]>,
+    bus: broadcast::Sender<Arc<LifecycleEvent>>,
+}
+
+impl Default for LifecycleEvents {
+    fn default() -> Self {
+        Self::new(String::new(), Vec::new())
+    }
+}
+
+impl LifecycleEvents {
+    pub(crate) fn new(host_id: impl Into<String>, sinks: Vec<Arc<dyn LifecycleEventSink>>) -> Self {
+        Self {
+            host_id: host_id.into(),
+            sinks: sinks.into(),
+            bus: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+
+    /// Returns a receiver of the events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LifecycleEvent>> {
+        self.bus.subscribe()
+    }
+
+    /// Returns whether any sink or subscriber receives events, so frequent
+    /// events aren't built for nobody.
+    pub fn is_observed(&self) -> bool {
+        !self.sinks.is_empty() || self.bus.receiver_count() > 0
+    }
+
+    /// Publishes `event` to the subscribers and sends it to every sink in the
+    /// background.
+    pub fn emit(&self, mut event: LifecycleEvent) {
+        if !self.is_observed() {
+            return;
+        }
+        event.host_id = self.host_id.clone();
+        let event = Arc::new(event);
+        // Fails only without subscribers
+        _ = self.bus.send(event.clone());
+        for sink in self.sinks.iter() {
+            let sink = sink.clone();
+            let event = event.clone();
+            tokio::spawn(async move {
+                if let Err(e) = sink.send(&event).await {
+                    warn!(
+                        event = event.event_type.as_str(),
+                        workload_id = event.workload_id,
+                        "failed to send lifecycle event: {e:#}"
+                    );
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serde() {
+        let event = LifecycleEvent::new(
+            LifecycleEventType::ServiceCrashed,
+            "workload",
+            "service exited",
+        )
+        .with_component_id("service");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "service_crashed");
+        assert!(json.get("workload_name").is_none());
+        assert_eq!(
+            serde_json::from_value::<LifecycleEvent>(json).unwrap(),
+            event
+        );
+
+        for event_type in [
+            LifecycleEventType::WorkloadStarted,
+            LifecycleEventType::WorkloadStartFailed,
+            LifecycleEventType::WorkloadStopped,
+            LifecycleEventType::WorkloadUpdated,
+            LifecycleEventType::WorkloadConfigUpdated,
+            LifecycleEventType::WorkloadCompleted,
+            LifecycleEventType::WorkloadFailed,
+            LifecycleEventType::ServiceCrashed,
+            LifecycleEventType::MemoryLeakSuspected,
+            LifecycleEventType::MemoryLimitExceeded,
+            LifecycleEventType::VolumeQuotaExceeded,
+            LifecycleEventType::RolloutStarted,
+            LifecycleEventType::RolloutSucceeded,
+            LifecycleEventType::RolloutRolledBack,
+            LifecycleEventType::RolloutFailed,
+            LifecycleEventType::RouteRegistered,
+            LifecycleEventType::InstanceCreated,
+            LifecycleEventType::InstanceRecycled,
+            LifecycleEventType::InvocationFailed,
+        ] {
+            assert_eq!(
+                event_type.as_str().parse::<LifecycleEventType>().unwrap(),
+                event_type
+            );
+        }
+        assert!("workload_exploded".parse::<LifecycleEventType>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_emit() {
+        struct Channel(tokio::sync::mpsc::UnboundedSender<LifecycleEvent>);
+
+        #[async_trait::async_trait]
+        impl LifecycleEventSink for Channel {
+            async fn send(&self, event: &LifecycleEvent) -> anyhow::Result<()> {
+                self.0.send(event.clone())?;
+                Ok(())
+            }
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let events = LifecycleEvents::new("host", vec![Arc::new(Channel(tx))]);
+        events.emit(LifecycleEvent::new(
+            LifecycleEventType::WorkloadStarted,
+            "workload",
+            "started",
+        ));
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.host_id, "host");
+        assert_eq!(event.event_type, LifecycleEventType::WorkloadStarted);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let events = LifecycleEvents::new("host", Vec::new());
+        assert!(!events.is_observed());
+        let mut subscriber = events.subscribe();
+        assert!(events.is_observed());
+        events.emit(
+            LifecycleEvent::new(LifecycleEventType::InvocationFailed, "workload", "trapped")
+                .with_component_id("component"),
+        );
+        let event = subscriber.recv().await.unwrap();
+        assert_eq!(event.host_id, "host");
+        assert_eq!(event.event_type, LifecycleEventType::InvocationFailed);
+        assert_eq!(event.component_id.as_deref(), Some("component"));
+    }
+}
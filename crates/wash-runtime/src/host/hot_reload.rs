@@ -0,0 +1,258 @@
+//! Reloading components from local files when they change.
+//!
+//! For the development loop, a [`Component`](crate::types::Component) may
+//! point at a `.wasm` file on the host with its `local_path` instead of
+//! carrying its bytes. The file is read when the workload starts, and a host
+//! built with
+//! [`HostBuilder::with_hot_reload`](super::HostBuilder::with_hot_reload)
+//! watches it: once the file changed and stayed unchanged for one interval,
+//! so a half-written file is never loaded, the workload is replaced by a new
+//! version with [`HostApi::workload_update`]. The routes of the workload are
+//! taken over by the new version, and the old one drains its invocations
+//! before it stops unless [`HotReload::with_drain`] turns that off.
+//!
+//! A reload that fails, e.g. because the new component doesn't compile,
+//! leaves the old version running and is logged. It is retried when the file
+//! changes again.
+
+use std::collections::HashMap;
+use std::sync::Weak;
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::host::{Host, HostApi, HostError};
+use crate::types::{Workload, WorkloadStartRequest, WorkloadUpdateRequest};
+
+/// How often the files of components are checked by default
+pub const DEFAULT_HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How a host reloads components from local files, see the
+/// [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotReload {
+    interval: Duration,
+    drain: bool,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_HOT_RELOAD_INTERVAL,
+            drain: true,
+        }
+    }
+}
+
+impl HotReload {
+    /// Checks the files of components every `interval`.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Whether the old version of a workload finishes its invocations before
+    /// it is stopped, the default, or is stopped right away.
+    pub fn with_drain(mut self, drain: bool) -> Self {
+        self.drain = drain;
+        self
+    }
+
+    /// Watches the components with a local path of the workloads of `host`
+    /// until it is dropped or drains.
+    pub(crate) fn spawn(self, host: Weak<Host>) {
+        tokio::spawn(async move {
+            let mut watched: HashMap<String, Watched> = HashMap::new();
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(host) = host.upgrade() else {
+                    break;
+                };
+                if host.is_draining() {
+                    break;
+                }
+                self.check(&host, &mut watched).await;
+            }
+        });
+    }
+
+    async fn check(&self, host: &Host, watched: &mut HashMap<String, Watched>) {
+        let definitions = host.definitions().clone();
+        watched.retain(|id, _| definitions.iter().any(|d| &d.workload_id == id));
+
+        for definition in definitions {
+            let mut stamps = Vec::new();
+            for path in local_paths(&definition.workload) {
+                stamps.push(Stamp::of(path).await);
+            }
+            if stamps.is_empty() {
+                continue;
+            }
+            let Some(state) = watched.get_mut(&definition.workload_id) else {
+                watched.insert(definition.workload_id, Watched::new(stamps));
+                continue;
+            };
+            if !state.observe(stamps) {
+                continue;
+            }
+
+            let workload = &definition.workload;
+            let replacement = WorkloadStartRequest::new(workload.clone());
+            let new_id = replacement.workload_id.clone();
+            let request = WorkloadUpdateRequest {
+                workload_id: definition.workload_id.clone(),
+                replacement,
+                drain_timeout: (!self.drain).then_some(Duration::ZERO),
+                rolling_update: None,
+            };
+            match host.workload_update(request).await {
+                Ok(_) => {
+                    info!(
+                        namespace = %workload.namespace,
+                        name = %workload.name,
+                        workload_id = %new_id,
+                        "reloaded workload after its component files changed"
+                    );
+                    if let Some(state) = watched.remove(&definition.workload_id) {
+                        watched.insert(new_id, state);
+                    }
+                }
+                Err(e) => warn!(
+                    namespace = %workload.namespace,
+                    name = %workload.name,
+                    workload_id = %definition.workload_id,
+                    "failed to reload workload after its component files changed: {e}"
+                ),
+            }
+        }
+    }
+}
+
+/// Reads the components of `workload` that have no bytes from their local
+/// path, in place.
+///
+/// # Errors
+/// Returns [`HostError::InvalidWorkload`] naming the file if it can't be read.
+pub(crate) async fn load_local_components(workload: &mut Workload) -> Result<(), HostError> {
+    for (i, component) in workload.components.iter_mut().enumerate() {
+        let Some(path) = component
+            .local_path
+            .as_deref()
+            .filter(|_| component.bytes.is_empty())
+        else {
+            continue;
+        };
+        component.bytes = tokio::fs::read(path).await.map(Into::into).map_err(|e| {
+            HostError::InvalidWorkload(format!("failed to read component {i} from {path}: {e}"))
+        })?;
+    }
+    Ok(())
+}
+
+/// The local paths the components of `workload` are read from.
+fn local_paths(workload: &Workload) -> impl Iterator<Item = &str> {
+    workload
+        .components
+        .iter()
+        .filter(|component| component.bytes.is_empty())
+        .filter_map(|component| component.local_path.as_deref())
+}
+
+/// When a file was last modified and its length, `None` while it is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp(Option<(SystemTime, u64)>);
+
+impl Stamp {
+    async fn of(path: &str) -> Self {
+        let metadata = tokio::fs::metadata(path).await.ok();
+        Self(metadata.and_then(|m| Some((m.modified().ok()?, m.len()))))
+    }
+}
+
+/// The files of the components of a workload as last seen
+#[derive(Debug)]
+struct Watched {
+    stamps: Vec<Stamp>,
+    /// Set when a file changed since the workload was last loaded
+    changed: bool,
+}
+
+impl Watched {
+    fn new(stamps: Vec<Stamp>) -> Self {
+        Self {
+            stamps,
+            changed: false,
+        }
+    }
+
+    /// Records the files as seen now and returns whether the workload should
+    /// be reloaded: once they changed, then stayed the same for one check and
+    /// all exist.
+    fn observe(&mut self, stamps: Vec<Stamp>) -> bool {
+        if stamps != self.stamps {
+            self.stamps = stamps;
+            self.changed = true;
+            return false;
+        }
+        if !self.changed || self.stamps.iter().any(|stamp| stamp.0.is_none()) {
+            return false;
+        }
+        self.changed = false;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Component;
+
+    #[test]
+    fn test_observe() {
+        let at = |secs| {
+            Stamp(Some((
+                SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                8,
+            )))
+        };
+        let mut watched = Watched::new(vec![at(1)]);
+        assert!(!watched.observe(vec![at(1)]));
+
+        // Reloaded once the change is seen twice
+        assert!(!watched.observe(vec![at(2)]));
+        assert!(watched.observe(vec![at(2)]));
+        assert!(!watched.observe(vec![at(2)]));
+
+        // Not while the file is being written or is missing
+        assert!(!watched.observe(vec![at(3)]));
+        assert!(!watched.observe(vec![at(4)]));
+        assert!(!watched.observe(vec![Stamp(None)]));
+        assert!(!watched.observe(vec![Stamp(None)]));
+        assert!(!watched.observe(vec![at(5)]));
+        assert!(watched.observe(vec![at(5)]));
+    }
+
+    #[tokio::test]
+    async fn test_load_local_components() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("component.wasm");
+        std::fs::write(&path, b"\0asm").unwrap();
+
+        let path = path.to_str().unwrap();
+        let mut workload = Workload::builder("default", "dev")
+            .with_component(Component::default().with_local_path(path))
+            .with_component(Component::new(&b"bytes"[..]).with_local_path(path))
+            .build();
+        load_local_components(&mut workload).await.unwrap();
+        assert_eq!(&workload.components[0].bytes[..], b"\0asm");
+        assert_eq!(&workload.components[1].bytes[..], b"bytes");
+
+        workload.components[0] = Component::default().with_local_path("missing.wasm");
+        assert!(matches!(
+            load_local_components(&mut workload).await,
+            Err(HostError::InvalidWorkload(_))
+        ));
+    }
+}
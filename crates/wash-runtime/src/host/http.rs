@@ -5,7 +5,11 @@
 //! server implementation with support for:
 //!
 //! - Virtual hosting based on Host headers
+//! - Several listen addresses, with dual-stack IPv6
 //! - TLS/HTTPS connections
+//! - HTTP/1.1 and HTTP/2, negotiated with ALPN or with prior knowledge, e.g. for gRPC
+//! - Client addresses from the PROXY protocol and trusted forwarding headers
+//! - Request and response trailers, e.g. for gRPC-web and other streaming protocols
 //! - Component isolation per request
 //! - Graceful shutdown capabilities
 //!
@@ -16,31 +20,316 @@
 //! 2. Routing requests to components based on the Host header
 //! 3. Creating isolated component instances for each request
 //! 4. Managing the request/response lifecycle through WASI-HTTP
+//!
+//! # Listen addresses
+//!
+//! An [`HttpServer`] listens on every address it is given, see
+//! [`HttpServer::with_addr`]. The unspecified IPv6 address, e.g. `[::]:8000`,
+//! accepts IPv4 connections too, regardless of the `IPV6_V6ONLY` default of the
+//! system, unless the server also listens on an IPv4 address with the same
+//! port. Clients connecting over IPv4 to a dual-stack listener are reported
+//! by their IPv4 address, not the IPv4-mapped IPv6 address, in logs and
+//! forwarding headers.
+//!
+//! # Online reconfiguration
+//!
+//! A running [`HttpServer`] can listen on further addresses and stop
+//! listening on others, see [`HostHandler::add_listener`], and read its TLS
+//! certificate and key again, e.g. after they were renewed, or switch to
+//! other files, see [`HostHandler::reload_tls`]. Connections that are already
+//! open are not affected: they keep their TLS session and are served until
+//! they close, even if their listener was removed.
+//!
+//! # TLS
+//!
+//! An [`HttpServer`] serves HTTPS with certificates read from files, see
+//! [`HttpServer::with_tls_files`], or given in memory, see
+//! [`HttpServer::with_tls`]. It may require client certificates and serve
+//! each host name it routes its own certificate, see the [`tls`] module.
+//!
+//! # Virtual hosts
+//!
+//! A route may serve several hosts, given as a comma-separated list in its
+//! `host` config, e.g. `api.example.com,api.internal`. A host may be a
+//! wildcard matching a single label, e.g. `*.example.com` matches
+//! `www.example.com` but neither `example.com` nor `a.b.example.com`. The
+//! [`DynamicRouter`] matches requests against the routes of their exact
+//! `Host` header first, and against those of the wildcard only if none of
+//! these matches.
+//!
+//! # Method and header routing
+//!
+//! A route may accept only some methods, in its `methods` config, and only
+//! requests with some headers, in its `headers` config, e.g.
+//! `x-api-version:2`, so requests for the same path can go to different
+//! workloads, e.g. while writes move to a new component. The more specific
+//! route takes precedence, by host, path, methods and headers in that order,
+//! see [`DynamicRouter`].
+//!
+//! # Traffic splitting
+//!
+//! Workloads may serve the same route with weights, e.g. 90 and 10, sharing
+//! its requests in proportion so a new version of a component can be
+//! canaried. Operators shift the weights at runtime with the `route.weight`
+//! API command, see [`split`].
+//!
+//! # Route metadata
+//!
+//! A route path may capture segments as parameters, e.g. `/users/{id}`
+//! matches `/users/42` and anything below it, capturing `id=42`. The
+//! [`DynamicRouter`] tells the component about the route a request matched in
+//! request headers, which the host sets and clients can't, see [`RouteMatch`]:
+//!
+//! ```text
+//! x-wasmcloud-workload: default/users
+//! x-wasmcloud-route: /users/{id}
+//! x-wasmcloud-route-prefix: /users/42
+//! x-wasmcloud-route-params: id=42
 //! ```
+//!
+//! # Deadlines
+//!
+//! Components serving a route with a timeout are told the time they have
+//! left in a request header, and their outgoing requests are cancelled once
+//! it passed, see [`deadline`]. Invocations running longer than the
+//! invocation timeout of their component are interrupted and answered with a
+//! `504 Gateway Timeout`, see
+//! [`EngineBuilder::with_invocation_timeout`](crate::engine::EngineBuilder::with_invocation_timeout).
+//!
+//! # Middleware
+//!
+//! A workload may have a middleware component that handles its requests
+//! before the serving component, and passes them on to it, e.g. to add
+//! authentication or rewrite requests and responses, see [`middleware`].
+//!
+//! # Hooks
+//!
+//! Host operators can run hooks written in Rust on every request before it
+//! is routed and on its response, e.g. to validate tokens, add CORS headers
+//! or generate request IDs for all workloads, see [`hooks`].
+//!
+//! # Access logs
+//!
+//! The server can log every request, with the route and workload serving it,
+//! the status, latency and size of the response and its trace, to stdout, a
+//! rotated file or `tracing`, see [`HttpServer::with_access_log`] and
+//! [`access_log`].
+//!
+//! # Recording and replay
+//!
+//! The requests of a workload can be recorded to a file, with their bodies
+//! up to a size limit and the responses of its component, and replayed later
+//! against another workload, e.g. a new build of the component, to reproduce
+//! bugs only production traffic triggers, see [`recording`].
+//!
+//! # Large uploads
+//!
+//! Routes accepting large uploads that their components read slowly can have
+//! the host read request bodies first, spilling them to temporary files, so
+//! slow guests don't hold connection buffers and memory, see [`spool`].
+//!
+//! # Error pages
+//!
+//! Requests the host answers itself, e.g. because no route matches them or
+//! their component failed, get a bare status code by default, or a JSON or
+//! HTML body configured with [`HttpServer::with_error_pages`], see
+//! [`error_pages`].
+//!
+//! # gRPC-web and Connect
+//!
+//! Browsers can call gRPC services of components over gRPC-web and the
+//! Connect protocol, which the host translates to and from gRPC, see
+//! [`grpc_web`].
+//!
+//! # Content negotiation
+//!
+//! Routes may declare the media types they produce, so the same path can be
+//! served by different workloads depending on the `Accept` header of a
+//! request, e.g. JSON from an API and HTML from a frontend, see [`negotiate`].
+//!
+//! # Server-Sent Events
+//!
+//! Event streams are flushed to the client after every chunk the component
+//! writes, and may be kept alive with comments while the component is quiet,
+//! see [`sse`].
+//!
+//! # WebSockets
+//!
+//! Routes may accept WebSocket connections over HTTP/1.1. The host completes
+//! the handshake once the component accepts it and exchanges the messages of
+//! the connection with the component through the request and response
+//! bodies, see [`websocket`].
+//!
+//! # Introspection
+//!
+//! The server tracks the requests it is handling, whether they wait for or
+//! run in a component instance and for how long, which operators list by
+//! route with the `http.introspect` API command to find slow routes, see
+//! [`inflight`].
+//!
+//! # Tracing
+//!
+//! Requests are traced from the listener through routing, instance
+//! acquisition and the guest to the outgoing requests of the component,
+//! continuing the trace of their `traceparent` header, see [`trace`].
+//!
+//! # Request signing
+//!
+//! The server can sign the outgoing requests of components to some hosts
+//! with credentials only the host holds, using AWS Signature Version 4 or an
+//! HMAC, so components call cloud APIs without long-lived keys of their own,
+//! see [`HttpServer::with_request_signing`] and [`signing`].
+//!
+//! # Outbound resilience
+//!
+//! The server can retry the failed outgoing requests of components to some
+//! hosts with a backoff, and fail requests fast through a circuit breaker
+//! while a host keeps failing, see [`HttpServer::with_outbound_resilience`]
+//! and [`resilience`].
+//!
+//! # Route pins
+//!
+//! An operator may pin a host and path prefix to a workload by its
+//! `namespace/name` with the `route.pin` API command, see [`RoutePin`]. While
+//! the pinned workload serves a route, requests matching the pin go to it,
+//! whatever the routes of other workloads. Pins are kept in the host
+//! snapshot, see [`crate::host::snapshot`], and restored before the listener
+//! accepts requests.
+//!
+//! # Route holds
+//!
+//! An operator may also hold a host and path prefix with the `route.hold` API
+//! command, see [`RouteHold`], so the host answers its requests itself while
+//! the workloads serving it keep running: with `503 Service Unavailable`, as
+//! an error page and with a `Retry-After` header if configured, while it is
+//! in maintenance, or with `404 Not Found` while it is disabled. Holds take
+//! precedence over pins and routes, the most specific hold first, until they
+//! are released with the `route.release` API command. Like pins, they are
+//! kept in the host snapshot.
+//!
+//! # Health checks
+//!
+//! A route may declare a health check, an export of the component or a path
+//! it serves, which the host runs every interval. Requests are only routed to
+//! workloads passing their check, and orchestrators can probe the aggregate
+//! health of the host on a path of the server, see [`health`].
+//!
+//! # Rate limits
+//!
+//! A route may limit the requests per second of each client, told apart by
+//! IP address or a header. Requests over the limit are answered with `429 Too
+//! Many Requests` before an instance is acquired, see [`rate_limit`].
+//!
+//! # Response caching
+//!
+//! A route may let the host cache the responses its component marks as
+//! cacheable with `Cache-Control`, answering identical requests without
+//! invoking the component and revalidating stale responses by their `ETag`.
+//! Operators drop cached responses with the `route.invalidate` API command,
+//! see [`cache`].
+//!
+//! # Request limits
+//!
+//! The host limits the size of request bodies and headers, and how long
+//! clients may take to send them, with defaults for all routes that a route
+//! may override, answering requests over the limits with `413`, `431` or
+//! `408` before they reach a component instance, see [`request_limits`].
 
-use std::{collections::HashMap, net::SocketAddr, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::engine::ctx::Ctx;
 use crate::engine::workload::ResolvedWorkload;
+use crate::host::HostError;
+use crate::host::idempotency::{self, IdempotencyStore, Invocation};
+use crate::host::limits::{ConnectionGuard, ConnectionTracker, HostLimits};
+use crate::host::metering;
 use crate::wit::WitInterface;
 use anyhow::{Context, ensure};
-use hyper::server::conn::http1;
+use bytes::Bytes;
+use http_body_util::BodyExt as _;
+use metrics::HttpMetrics;
+use opentelemetry::trace::TraceContextExt as _;
+use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
 use tracing::{debug, error, info, warn};
-use wasmtime::component::InstancePre;
+use wasmtime::component::{Instance, InstancePre};
 use wasmtime::{AsContextMut, StoreContextMut};
 use wasmtime_wasi_http::{
     WasiHttpView,
-    bindings::{ProxyPre, http::types::Scheme},
+    bindings::{Proxy, ProxyPre, http::types::Scheme},
     body::HyperOutgoingBody,
     io::TokioIo,
 };
 
-use rustls::{ServerConfig, pki_types::CertificateDer};
-use rustls_pemfile::{certs, private_key};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, watch};
 use tokio_rustls::TlsAcceptor;
 
+pub mod access_log;
+pub mod cache;
+pub mod connection_pool;
+pub mod deadline;
+pub mod egress;
+pub mod error_pages;
+pub mod forwarded;
+pub mod grpc_web;
+pub mod h2;
+pub mod health;
+pub mod hooks;
+pub mod inflight;
+pub mod metrics;
+pub mod middleware;
+pub mod rate_limit;
+pub mod recording;
+pub mod request_limits;
+pub mod resilience;
+pub mod signing;
+pub mod split;
+pub mod spool;
+pub mod sse;
+mod stream;
+pub mod tls;
+pub mod trace;
+pub mod websocket;
+pub use crate::types::route::{
+    HEADERS_CONFIG_KEY, HOST_CONFIG_KEY, HttpRouteConfig, MAX_BODY_BYTES_CONFIG_KEY,
+    METHODS_CONFIG_KEY, PATH_CONFIG_KEY, RouteHeaderMatch, SHADOW_CONFIG_KEY,
+    SPOOL_MAX_BYTES_CONFIG_KEY, SSE_KEEPALIVE_MS_CONFIG_KEY, STREAM_IDLE_TIMEOUT_MS_CONFIG_KEY,
+    STREAM_MAX_DURATION_MS_CONFIG_KEY, TIMEOUT_MS_CONFIG_KEY, WEBSOCKET_CONFIG_KEY, auth,
+    experiment, negotiate,
+};
+pub use access_log::{AccessLog, AccessLogConfig, AccessLogEntry, AccessLogSink};
+pub use auth::RouteAuth;
+pub use cache::{ResponseCache, RouteCache};
+pub use connection_pool::{ConnectionPool, ConnectionPoolConfig, ConnectionPoolStats};
+pub use egress::{EgressDefault, EgressPolicy, OutboundThrottle, Resolver, ResolverConfig};
+use error_pages::host_response;
+pub use error_pages::{ErrorFormat, ErrorPages};
+pub use experiment::RouteExperiment;
+pub use forwarded::{ForwardingConfig, IpCidr};
+use grpc_web::GrpcWebCall;
+pub use health::{HealthProbe, RouteHealthCheck, WorkloadNotReady};
+pub use hooks::{HookRequest, HttpHook, HttpHooks, RequestIdHook};
+use inflight::{InFlight, InFlightRequests};
+pub use inflight::{InFlightRequest, RequestPhase, RouteActivity};
+use middleware::{Middleware, Next};
+use rate_limit::RateLimiter;
+pub use rate_limit::{RateLimitKey, RouteRateLimit};
+use recording::Recorder;
+pub use recording::{RecordedInvocation, Recording, RecordingStatus, ReplayReport};
+pub use request_limits::RequestLimits;
+pub use resilience::{CircuitState, CircuitStatus, OutboundResilience, ResilienceRule};
+pub use signing::{SigningMethod, SigningRule};
+pub use split::{RolloutSplit, RouteWeight};
+use spool::SpoolError;
+pub use stream::StreamLimits;
+pub use tls::{SniFiles, TlsCertificates, TlsFiles};
+
 /// Trait defining the routing behavior for HTTP requests
 /// Allows for custom routing logic based on workload IDs and requests
 /// Use this trait to implement custom routing strategies with the default HTTP Extension
@@ -65,623 +354,4348 @@ pub trait Router: Send + Sync + 'static {
         config: &wasmtime_wasi_http::types::OutgoingRequestConfig,
     ) -> anyhow::Result<()>;
 
-    /// Pick a workload ID based on the incoming request
+    /// Pick a workload ID based on the incoming request, along with the
+    /// policy of the route it matched
     fn route_incoming_request(
         &self,
         req: &hyper::Request<hyper::body::Incoming>,
-    ) -> anyhow::Result<String>;
-}
+    ) -> anyhow::Result<(String, RoutePolicy)>;
 
-/// Router that routes requests by 'Host' header, configured via WitInterface config
-#[derive(Default)]
-pub struct DynamicRouter {
-    host_to_workload: tokio::sync::RwLock<HashMap<String, String>>,
-}
+    /// The maximum time the given workload may take to respond to a request.
+    /// Returns `None` (no limit) by default.
+    fn request_timeout(&self, _workload_id: &str) -> Option<Duration> {
+        None
+    }
 
-/// Implementation of Router that maps Host headers to workload IDs
-/// based on the 'host' config in the wasi:http/incoming-handler interface
-#[async_trait::async_trait]
-impl Router for DynamicRouter {
-    async fn on_workload_resolved(
+    /// The `namespace/name` of the given workload if it shares its route with
+    /// others by weight, recorded in request metrics, see [`split`]. Returns
+    /// `None` by default.
+    fn split_target(&self, _workload_id: &str) -> Option<String> {
+        None
+    }
+
+    /// Counts a request with `headers` against the rate limit of the given
+    /// workload, see [`rate_limit`], returning how long its client should
+    /// wait if it is over the limit. Requests are not limited by default.
+    fn check_rate_limit(
         &self,
-        resolved_handle: &ResolvedWorkload,
-        _component_id: &str,
-    ) -> anyhow::Result<()> {
-        let incoming_handler_interface = WitInterface::from("wasi:http/incoming-handler");
-        let Some(http_iface) = resolved_handle
-            .host_interfaces()
-            .iter()
-            .find(|iface| iface.contains(&incoming_handler_interface))
-        else {
-            anyhow::bail!("workload did not request wasi:http/incoming-handler interface");
-        };
+        _workload_id: &str,
+        _headers: &hyper::HeaderMap,
+    ) -> Result<(), Duration> {
+        Ok(())
+    }
 
-        let host_header = http_iface
-            .config
-            .get("host")
-            .cloned()
-            .context("No host header found")?;
+    /// The routes requests are matched against. Returns none by default.
+    async fn routes(&self) -> Vec<RouteEntry> {
+        Vec::new()
+    }
 
-        let mut lock = self.host_to_workload.write().await;
-        lock.insert(host_header, resolved_handle.id().to_string());
+    /// The pins overriding routes, see [`RoutePin`]. Returns none by default.
+    async fn route_pins(&self) -> Vec<RoutePin> {
+        Vec::new()
+    }
 
-        Ok(())
+    /// Adds a pin, replacing the pin of the same host and path prefix.
+    /// Unsupported by default.
+    async fn pin_route(&self, _pin: RoutePin) -> anyhow::Result<()> {
+        anyhow::bail!("the router does not support route pins")
     }
 
-    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
-        let mut lock = self.host_to_workload.write().await;
-        lock.retain(|_host, wid| wid != workload_id);
-        Ok(())
+    /// Removes the pin of a host and path prefix, returning whether it
+    /// existed. Returns `false` by default.
+    async fn unpin_route(&self, _host: &str, _path: Option<&str>) -> bool {
+        false
     }
 
-    fn allow_outgoing_request(
-        &self,
-        _workload_id: &str,
-        _request: &hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        _config: &wasmtime_wasi_http::types::OutgoingRequestConfig,
-    ) -> anyhow::Result<()> {
-        Ok(())
+    /// The holds answering requests instead of routes, see [`RouteHold`].
+    /// Returns none by default.
+    async fn route_holds(&self) -> Vec<RouteHold> {
+        Vec::new()
     }
 
-    /// Pick a workload ID based on the incoming request
-    fn route_incoming_request(
-        &self,
-        req: &hyper::Request<hyper::body::Incoming>,
-    ) -> anyhow::Result<String> {
-        tokio::task::block_in_place(move || {
-            let lock = self.host_to_workload.try_read()?;
-            let workload_host = req
-                .headers()
-                .get(hyper::header::HOST)
-                .and_then(|h| h.to_str().ok())
-                .context("no Host header in request")?;
-            let Some(workload_id) = lock.get(workload_host) else {
-                anyhow::bail!("no workload bound to host header: {}", workload_host);
-            };
-            Ok(workload_id.clone())
-        })
+    /// Adds a hold, replacing the hold of the same host and path prefix.
+    /// Unsupported by default.
+    async fn hold_route(&self, _hold: RouteHold) -> anyhow::Result<()> {
+        anyhow::bail!("the router does not support route holds")
     }
-}
 
-/// Development router that routes all requests to the last resolved workload
-#[derive(Default)]
-pub struct DevRouter {
-    last_workload_id: tokio::sync::Mutex<Option<String>>,
-}
+    /// Removes the hold of a host and path prefix, returning whether it
+    /// existed. Returns `false` by default.
+    async fn release_route(&self, _host: &str, _path: Option<&str>) -> bool {
+        false
+    }
 
-#[async_trait::async_trait]
-impl Router for DevRouter {
-    async fn on_workload_resolved(
-        &self,
-        resolved_handle: &ResolvedWorkload,
-        _component_id: &str,
-    ) -> anyhow::Result<()> {
-        let mut lock = self.last_workload_id.lock().await;
-        lock.replace(resolved_handle.id().to_string());
-        Ok(())
+    /// The weights shifted at runtime, see [`split`]. Returns none by default.
+    async fn route_weights(&self) -> Vec<RouteWeight> {
+        Vec::new()
     }
 
-    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
-        let mut lock = self.last_workload_id.lock().await;
-        if let Some(current_id) = &*lock
-            && current_id == workload_id
-        {
-            let _ = lock.take();
+    /// Shifts the weight of a workload among those splitting its route.
+    /// Unsupported by default.
+    async fn set_route_weight(&self, _weight: RouteWeight) -> anyhow::Result<()> {
+        anyhow::bail!("the router does not support route weights")
+    }
+
+    /// Splits the requests for the routes of a workload between it and the
+    /// new version replacing it, or stops splitting them if `None`, see
+    /// [`split`]. Ignored by default.
+    fn set_rollout(&self, _workload_id: &str, _split: Option<RolloutSplit>) {}
+
+    /// The health check of the given workload, see [`health`]. Returns
+    /// `None` (always ready) by default.
+    fn health_check(&self, _workload_id: &str) -> Option<RouteHealthCheck> {
+        None
+    }
+
+    /// Records whether the given workload passes its health check. Ignored
+    /// by default.
+    fn set_ready(&self, _workload_id: &str, _ready: bool) {}
+
+    /// The workloads that don't pass their health check. Returns none by
+    /// default.
+    fn unready_workloads(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl HttpRouteConfig {
+    /// Returns the limits applied to streamed response bodies of this route.
+    pub fn stream_limits(&self) -> StreamLimits {
+        StreamLimits {
+            idle_timeout: self.stream_idle_timeout_ms.map(Duration::from_millis),
+            max_duration: self.stream_max_duration_ms.map(Duration::from_millis),
         }
-        Ok(())
     }
+}
 
-    fn allow_outgoing_request(
-        &self,
-        _workload_id: &str,
-        _request: &hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        _config: &wasmtime_wasi_http::types::OutgoingRequestConfig,
-    ) -> anyhow::Result<()> {
-        Ok(())
+/// Returns the routes of `host`, then those of the wildcard matching it.
+fn host_routes<'a>(
+    routes: &'a HashMap<String, Vec<RouteEntry>>,
+    host: &str,
+) -> impl Iterator<Item = &'a Vec<RouteEntry>> {
+    let wildcard = host
+        .split_once('.')
+        .and_then(|(_, parent)| routes.get(&format!("*.{parent}")));
+    routes.get(host).into_iter().chain(wildcard)
+}
+
+/// Returns whether `host` is, or matches the wildcard, `pattern`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(parent) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == parent),
+        None => pattern == host,
     }
+}
 
-    /// Pick a workload ID based on the incoming request
-    fn route_incoming_request(
-        &self,
-        _req: &hyper::Request<hyper::body::Incoming>,
-    ) -> anyhow::Result<String> {
-        let lock = self.last_workload_id.try_lock()?;
-        match &*lock {
-            Some(id) => {
-                tracing::info!(workload_id = %id, "incoming request");
-                Ok(id.clone())
+/// Request header carrying the `namespace/name` of the workload serving the request
+pub const ROUTE_WORKLOAD_HEADER: &str = "x-wasmcloud-workload";
+/// Request header carrying the path prefix of the route the request matched
+pub const ROUTE_HEADER: &str = "x-wasmcloud-route";
+/// Request header carrying the part of the request path the route matched
+pub const ROUTE_PREFIX_HEADER: &str = "x-wasmcloud-route-prefix";
+/// Request header carrying the path parameters the route captured
+pub const ROUTE_PARAMS_HEADER: &str = "x-wasmcloud-route-params";
+
+/// The route a request matched, passed to the component in request headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMatch {
+    /// The `namespace/name` of the workload, in the [`ROUTE_WORKLOAD_HEADER`]
+    pub workload_name: String,
+    /// The path prefix of the route without a trailing `/`, e.g.
+    /// `/users/{id}`, in the [`ROUTE_HEADER`]. Empty if the route matches all
+    /// paths.
+    pub route: String,
+    /// The part of the request path the route matched, where the component is
+    /// mounted, e.g. `/users/42`, in the [`ROUTE_PREFIX_HEADER`]. Links
+    /// relative to the component start with it.
+    pub prefix: String,
+    /// The captured parameters, in the [`ROUTE_PARAMS_HEADER`] as
+    /// `name=value` pairs joined by `&`, with `&` and `=` in values
+    /// percent-encoded. Values are percent-encoded as in the request path.
+    pub params: Vec<(String, String)>,
+}
+
+impl RouteMatch {
+    /// Sets the route headers of a request, after removing any the client
+    /// sent.
+    pub(crate) fn apply(this: Option<&Self>, headers: &mut hyper::HeaderMap) {
+        for header in [
+            ROUTE_WORKLOAD_HEADER,
+            ROUTE_HEADER,
+            ROUTE_PREFIX_HEADER,
+            ROUTE_PARAMS_HEADER,
+        ] {
+            headers.remove(header);
+        }
+        let Some(this) = this else {
+            return;
+        };
+        let params = this
+            .params
+            .iter()
+            .map(|(name, value)| {
+                format!("{name}={}", value.replace('&', "%26").replace('=', "%3D"))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        for (header, value) in [
+            (ROUTE_WORKLOAD_HEADER, this.workload_name.as_str()),
+            (ROUTE_HEADER, this.route.as_str()),
+            (ROUTE_PREFIX_HEADER, this.prefix.as_str()),
+            (ROUTE_PARAMS_HEADER, params.as_str()),
+        ] {
+            if let Ok(value) = hyper::header::HeaderValue::from_str(value) {
+                headers.insert(header, value);
             }
-            None => anyhow::bail!("no workload available to route request"),
         }
     }
 }
 
-/// Trait defining the behavior of a Host HTTP Extension
-/// Allows for custom handling of incoming and outgoing HTTP requests
-/// Use this trait to implement custom HTTP server transport
-#[async_trait::async_trait]
-pub trait HostHandler: Send + Sync + 'static {
-    async fn start(&self) -> anyhow::Result<()>;
-    async fn stop(&self) -> anyhow::Result<()>;
+/// What the route a request matched asks of the host, returned with the
+/// workload the request is routed to by [`Router::route_incoming_request`].
+/// Defaults to no policy at all.
+#[derive(Debug, Clone, Default)]
+pub struct RoutePolicy {
+    /// The route, recorded as the `http.route` of request metrics
+    pub name: Option<String>,
+    /// The authentication requests must pass
+    pub auth: Option<RouteAuth>,
+    /// The request limits of the route, overriding those of the host, see
+    /// [`request_limits`]
+    pub limits: RequestLimits,
+    /// The largest request body the route accepts, in bytes
+    pub max_body_bytes: Option<u64>,
+    /// The response caching of the route, see [`cache`]
+    pub cache: Option<RouteCache>,
+    /// The largest request body the host reads before invoking the
+    /// workload, see [`spool`]
+    pub spool_max_bytes: Option<u64>,
+    /// Whether the route accepts WebSocket connections, see [`websocket`]
+    pub websocket: bool,
+    /// The maximum time the workload may take to respond, before the
+    /// deadline a client asks for, see [`deadline`]
+    pub timeout: Option<Duration>,
+    /// Limits applied to streaming response bodies
+    pub stream_limits: StreamLimits,
+    /// How long an event stream may stay quiet before the host sends a
+    /// keepalive comment, see [`sse`]
+    pub sse_keepalive: Option<Duration>,
+    /// Whether the route was chosen by the `Accept` header, see [`negotiate`]
+    pub negotiated: bool,
+    /// The workload ID that receives a copy of every request, with its
+    /// response discarded
+    pub shadow: Option<String>,
+    /// What the request learns about its route
+    pub matched: Option<RouteMatch>,
+    /// The experiment the route takes part in, exposed to the guest in the
+    /// [`experiment::EXPERIMENT_HEADER`]
+    pub experiment: Option<RouteExperiment>,
+}
 
-    async fn on_workload_resolved(
-        &self,
-        resolved_handle: &ResolvedWorkload,
-        component_id: &str,
-    ) -> anyhow::Result<()>;
-    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()>;
+/// A single entry in the effective routing table of a [`DynamicRouter`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteEntry {
+    /// The route as configured by the workload
+    pub route: HttpRouteConfig,
+    /// The workload requests matching the route are sent to
+    pub workload_id: String,
+    /// The `namespace/name` of the workload
+    pub workload_name: String,
+}
 
-    fn outgoing_request(
+impl RouteEntry {
+    /// Returns the namespace of the workload.
+    pub fn namespace(&self) -> &str {
+        namespace_of(&self.workload_name)
+    }
+
+    /// Returns why `route` of the workload `workload_id`, named
+    /// `workload_name`, can't be registered next to this entry, if it can't.
+    /// The hosts of the route overlap the host of this entry if they are the
+    /// same, or one is a wildcard matching the other. A new version of the
+    /// workload of this entry takes it over.
+    fn conflict(
         &self,
         workload_id: &str,
-        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        config: wasmtime_wasi_http::types::OutgoingRequestConfig,
-    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse>;
+        workload_name: &str,
+        route: &HttpRouteConfig,
+    ) -> Option<String> {
+        if self.workload_id == workload_id
+            || self.workload_name == workload_name
+            || !route.hosts().any(|host| {
+                host_matches(&self.route.host, host) || host_matches(host, &self.route.host)
+            })
+        {
+            return None;
+        }
+        if self.namespace() != namespace_of(workload_name) {
+            Some(format!(
+                "host {} of workload {workload_id} is served by namespace {}",
+                self.route.host,
+                self.namespace()
+            ))
+        } else if self.route.conflicts_with(route) {
+            Some(format!(
+                "route {route} of workload {workload_id} overlaps route {} of workload {}",
+                self.route, self.workload_id
+            ))
+        } else {
+            None
+        }
+    }
 }
 
-impl std::fmt::Debug for dyn HostHandler {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("HostHandler").finish()
-    }
+/// Returns the namespace of a `namespace/name` workload name.
+fn namespace_of(workload_name: &str) -> &str {
+    workload_name
+        .split_once('/')
+        .map_or("", |(namespace, _)| namespace)
 }
 
-#[derive(Default)]
-pub struct NullServer {}
+/// A manual override sending the requests for a host and path prefix to a
+/// workload, see the [module docs](self#route-pins).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutePin {
+    /// The `Host` header the pin applies to
+    pub host: String,
+    /// The path prefix the pin applies to, which may capture parameters like
+    /// a route. Applies to all paths if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The `namespace/name` of the workload requests are sent to
+    pub workload_name: String,
+}
 
-#[async_trait::async_trait]
-impl HostHandler for NullServer {
-    async fn start(&self) -> anyhow::Result<()> {
-        Ok(())
+impl RoutePin {
+    pub fn new(host: impl Into<String>, workload_name: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            path: None,
+            workload_name: workload_name.into(),
+        }
     }
 
-    async fn stop(&self) -> anyhow::Result<()> {
-        Ok(())
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
     }
 
-    async fn on_workload_resolved(
-        &self,
-        _resolved_handle: &ResolvedWorkload,
-        _component_id: &str,
-    ) -> anyhow::Result<()> {
+    /// Validates the pin.
+    ///
+    /// # Errors
+    /// Returns an error if the host or path is invalid as in a route, the pin
+    /// names several hosts, or the workload is not of the form
+    /// `namespace/name`.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.as_route().validate()?;
+        ensure!(
+            !self.host.contains(','),
+            "pinned host '{}' must be a single host",
+            self.host
+        );
+        ensure!(
+            self.workload_name
+                .split_once('/')
+                .is_some_and(|(ns, name)| !ns.is_empty() && !name.is_empty()),
+            "pinned workload '{}' must have the form namespace/name",
+            self.workload_name
+        );
         Ok(())
     }
 
-    async fn on_workload_unbind(&self, _workload_id: &str) -> anyhow::Result<()> {
-        Ok(())
+    /// Returns whether the pin applies to `host` and the path prefix `path`,
+    /// up to the names of parameters.
+    fn same_target(&self, host: &str, path: Option<&str>) -> bool {
+        let other = HttpRouteConfig {
+            host: host.to_string(),
+            path: path.map(str::to_string),
+            ..Default::default()
+        };
+        self.host == host && self.as_route().path_shape() == other.path_shape()
     }
 
-    fn outgoing_request(
-        &self,
-        _workload_id: &str,
-        _request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        _config: wasmtime_wasi_http::types::OutgoingRequestConfig,
-    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse> {
-        Err(wasmtime_wasi_http::HttpError::trap(anyhow::anyhow!(
-            "http client not available"
-        )))
+    /// The route matching the requests the pin applies to.
+    fn as_route(&self) -> HttpRouteConfig {
+        HttpRouteConfig {
+            host: self.host.clone(),
+            path: self.path.clone(),
+            ..Default::default()
+        }
     }
 }
 
-/// A map from host header to resolved workload handles and their associated component id
-pub type WorkloadHandles =
-    Arc<RwLock<HashMap<String, (ResolvedWorkload, InstancePre<Ctx>, String)>>>;
+impl std::fmt::Display for RoutePin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}/ -> {}",
+            self.host,
+            self.as_route().normalized_path(),
+            self.workload_name
+        )
+    }
+}
 
-/// HTTP server plugin that handles incoming HTTP requests for WebAssembly components.
-///
-/// This plugin implements the `wasi:http/incoming-handler` interface and routes
-/// HTTP requests to appropriate WebAssembly components based on virtual hosting.
-/// It supports both HTTP and HTTPS connections with optional mutual TLS.
-pub struct HttpServer<T: Router> {
-    router: Arc<T>,
-    addr: SocketAddr,
-    workload_handles: WorkloadHandles,
-    shutdown_tx: Arc<RwLock<Option<mpsc::Sender<()>>>>,
-    tls_acceptor: Option<TlsAcceptor>,
+/// How the host answers the requests a [`RouteHold`] applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteHoldMode {
+    /// With `503 Service Unavailable`
+    #[default]
+    Maintenance,
+    /// With `404 Not Found`, as if no workload served the route
+    Disabled,
 }
 
-impl<T: Router> std::fmt::Debug for HttpServer<T> {
+impl std::fmt::Display for RouteHoldMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("HttpServer")
-            .field("addr", &self.addr)
-            .finish()
+        f.write_str(match self {
+            RouteHoldMode::Maintenance => "maintenance",
+            RouteHoldMode::Disabled => "disabled",
+        })
     }
 }
 
-impl<T: Router> HttpServer<T> {
-    /// Creates a new HTTP server listening on the specified address.
-    ///
-    /// # Arguments
-    /// * `router` - The router implementation for handling requests
-    /// * `addr` - The socket address to bind to
-    ///
-    /// # Returns
-    /// A new `HttpServer` instance configured for HTTP connections.
-    pub fn new(router: T, addr: SocketAddr) -> Self {
+/// A manual override answering the requests for a host and path prefix from
+/// the host, without stopping the workloads serving them, see the
+/// [module docs](self#route-holds).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteHold {
+    /// The `Host` header the hold applies to
+    pub host: String,
+    /// The path prefix the hold applies to, which may capture parameters like
+    /// a route. Applies to all paths if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub mode: RouteHoldMode,
+    /// Seconds clients are asked to wait before retrying, in the
+    /// `Retry-After` header of maintenance responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl RouteHold {
+    /// Puts the requests for `host` into maintenance.
+    pub fn new(host: impl Into<String>) -> Self {
         Self {
-            router: Arc::new(router),
-            addr,
-            workload_handles: Arc::default(),
-            shutdown_tx: Arc::new(RwLock::new(None)),
-            tls_acceptor: None,
+            host: host.into(),
+            path: None,
+            mode: RouteHoldMode::Maintenance,
+            retry_after_secs: None,
         }
     }
 
-    /// Creates a new HTTPS server with TLS support.
-    ///
-    /// # Arguments
-    /// * `router` - The router implementation for handling requests
-    /// * `addr` - The socket address to bind to
-    /// * `cert_path` - Path to the TLS certificate file
-    /// * `key_path` - Path to the private key file
-    /// * `ca_path` - Optional path to CA certificate for mutual TLS
-    ///
-    /// # Returns
-    /// A new `HttpServer` instance configured for HTTPS connections.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_mode(mut self, mode: RouteHoldMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_retry_after_secs(mut self, secs: u64) -> Self {
+        self.retry_after_secs = Some(secs);
+        self
+    }
+
+    /// Validates the hold.
     ///
     /// # Errors
-    /// Returns an error if the TLS configuration cannot be loaded.
-    pub async fn new_with_tls(
-        router: T,
-        addr: SocketAddr,
-        cert_path: &Path,
-        key_path: &Path,
-        ca_path: Option<&Path>,
-    ) -> anyhow::Result<Self> {
-        let tls_config = load_tls_config(cert_path, key_path, ca_path).await?;
-        let tls_acceptor = TlsAcceptor::from(Arc::new(tls_config));
+    /// Returns an error if the host or path is invalid as in a route or the
+    /// hold names several hosts.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.as_route().validate()?;
+        ensure!(
+            !self.host.contains(','),
+            "held host '{}' must be a single host",
+            self.host
+        );
+        Ok(())
+    }
 
-        Ok(Self {
-            router: Arc::new(router),
-            addr,
-            workload_handles: Arc::default(),
-            shutdown_tx: Arc::new(RwLock::new(None)),
-            tls_acceptor: Some(tls_acceptor),
-        })
+    /// Returns whether the hold applies to `host` and the path prefix `path`,
+    /// up to the names of parameters.
+    fn same_target(&self, host: &str, path: Option<&str>) -> bool {
+        let other = HttpRouteConfig {
+            host: host.to_string(),
+            path: path.map(str::to_string),
+            ..Default::default()
+        };
+        self.host == host && self.as_route().path_shape() == other.path_shape()
+    }
+
+    /// The route matching the requests the hold applies to.
+    fn as_route(&self) -> HttpRouteConfig {
+        HttpRouteConfig {
+            host: self.host.clone(),
+            path: self.path.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The response the host answers the requests the hold applies to with.
+    fn response(&self) -> hyper::Response<HyperOutgoingBody> {
+        match self.mode {
+            RouteHoldMode::Maintenance => {
+                let mut response = host_response(503);
+                if let Some(secs) = self.retry_after_secs {
+                    response
+                        .headers_mut()
+                        .insert(hyper::header::RETRY_AFTER, secs.into());
+                }
+                response
+            }
+            RouteHoldMode::Disabled => host_response(404),
+        }
+    }
+}
+
+impl std::fmt::Display for RouteHold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}/ ({})",
+            self.host,
+            self.as_route().normalized_path(),
+            self.mode
+        )
+    }
+}
+
+/// The error [`Router::route_incoming_request`] returns for a request a
+/// [`RouteHold`] applies to.
+#[derive(Debug)]
+pub struct RouteHeld(pub RouteHold);
+
+impl std::fmt::Display for RouteHeld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "route {} is held", self.0)
+    }
+}
+
+impl std::error::Error for RouteHeld {}
+
+/// Router that routes requests by 'Host' header, configured via WitInterface config
+///
+/// # Precedence
+///
+/// Within a host, the route with the longest matching path prefix wins, so a
+/// workload serving `/api/users` receives those requests even if another workload
+/// serves `/api`. Between prefixes of the same length in segments, the one with
+/// more literal segments wins, so `/users/me` takes precedence over
+/// `/users/{id}`. On the same path prefix, a route accepting some methods
+/// takes precedence over one accepting all, and one accepting fewer methods
+/// over one accepting more, so `GET` and `POST` requests can be sent to
+/// different workloads while a catch-all route serves the rest. Then a route
+/// with more header predicates takes precedence, e.g. `x-api-version:2` over
+/// none. Two equally specific routes may share a path prefix only if their
+/// methods do not overlap or they require different values of a header. Any
+/// other overlap is a conflict: the workload that registers second fails to
+/// start with [`HostError::RouteConflict`].
+///
+/// A host belongs to the namespace whose workloads serve it. Workloads of
+/// other namespaces can't register routes on it, whatever their paths, nor on
+/// the hosts a wildcard of it matches or a wildcard matching it, so one tenant
+/// of a shared host can't take requests meant for another.
+///
+/// A workload with the namespace and name of a workload already serving
+/// overlapping routes is a new version of it and takes them over: requests are
+/// routed to it as soon as it starts, while those the old version received
+/// finish there, see [`HostApi::workload_update`](crate::host::HostApi::workload_update).
+///
+/// A route serving several hosts is registered, and listed in the routing
+/// table, once per host. The routes of the exact `Host` header of a request
+/// take precedence over those of a wildcard, see the
+/// [module docs](self#virtual-hosts).
+///
+/// [`RoutePin`]s take precedence over routes, the most specific pin first,
+/// and [`RouteHold`]s over both.
+///
+/// Workloads with a health check only get requests while they pass it, see
+/// [`health`].
+///
+/// Workloads serving equally specific routes with a weight split their
+/// requests, see [`split`], as do the versions of a workload during a
+/// rolling update.
+#[derive(Default)]
+pub struct DynamicRouter {
+    /// Routes keyed by host or wildcard, ordered from most to least specific
+    /// path. Requests wait for changes in progress rather than miss the
+    /// policies of their route.
+    host_to_workload: std::sync::RwLock<HashMap<String, Vec<RouteEntry>>>,
+    /// Pins ordered from most to least specific path
+    pins: std::sync::RwLock<Vec<RoutePin>>,
+    /// Holds ordered from most to least specific path
+    holds: std::sync::RwLock<Vec<RouteHold>>,
+    /// Whether the workloads with a health check pass it
+    ready: std::sync::RwLock<HashMap<String, bool>>,
+    /// Rate limiters of the workloads whose route has a rate limit
+    rate_limiters: std::sync::RwLock<HashMap<String, Arc<RateLimiter>>>,
+    /// Weights shifted at runtime, by workload name, see [`split`]
+    weights: std::sync::RwLock<HashMap<String, u32>>,
+    /// Workloads being replaced by a rolling update, see [`split`]
+    rollouts: std::sync::RwLock<HashMap<String, RolloutSplit>>,
+}
+
+impl DynamicRouter {
+    /// Returns the effective routing table, sorted by host and then in the order
+    /// routes are matched.
+    pub async fn routing_table(&self) -> Vec<RouteEntry> {
+        let lock = self
+            .host_to_workload
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut hosts: Vec<_> = lock.iter().collect();
+        hosts.sort_by(|(a, _), (b, _)| a.cmp(b));
+        hosts
+            .into_iter()
+            .flat_map(|(_host, routes)| routes.iter().cloned())
+            .map(|mut entry| {
+                entry.route.weight = self.weight(&entry);
+                entry
+            })
+            .collect()
+    }
+
+    /// Returns the weights shifted at runtime, sorted by workload name.
+    pub fn weights(&self) -> Vec<RouteWeight> {
+        let weights = self
+            .weights
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut weights: Vec<RouteWeight> = weights
+            .iter()
+            .map(|(name, weight)| RouteWeight::new(name, *weight))
+            .collect();
+        weights.sort_by(|a, b| a.workload_name.cmp(&b.workload_name));
+        weights
+    }
+
+    /// Returns the weight of a route, shifted at runtime or as configured,
+    /// if it has one.
+    fn weight(&self, entry: &RouteEntry) -> Option<u32> {
+        let configured = entry.route.weight?;
+        let weights = self
+            .weights
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Some(
+            weights
+                .get(&entry.workload_name)
+                .copied()
+                .unwrap_or(configured),
+        )
+    }
+
+    /// Returns the pins overriding routes, in the order they are matched.
+    pub async fn pins(&self) -> Vec<RoutePin> {
+        self.pins
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the holds answering requests instead of routes, in the order
+    /// they are matched.
+    pub async fn holds(&self) -> Vec<RouteHold> {
+        self.holds
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the hold applying to a request for `host` and `path`, if any.
+    fn held_route(&self, host: &str, path: &str) -> Option<RouteHold> {
+        let holds = self
+            .holds
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        holds
+            .iter()
+            .find(|hold| {
+                host_matches(&hold.host, host) && hold.as_route().match_path(path).is_some()
+            })
+            .cloned()
+    }
+
+    /// Returns the route of the workload a pin sends a request for `host` and
+    /// `path` to, if it serves one.
+    fn pinned_route<'a>(
+        &self,
+        routes: &'a HashMap<String, Vec<RouteEntry>>,
+        host: &str,
+        path: &str,
+    ) -> Option<&'a RouteEntry> {
+        let pins = self
+            .pins
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let pin = pins.iter().find(|pin| {
+            host_matches(&pin.host, host) && pin.as_route().match_path(path).is_some()
+        })?;
+        // Prefer a route of the pinned workload on the same host
+        let mut entries = routes
+            .get(host)
+            .into_iter()
+            .chain(routes.values())
+            .flatten()
+            .filter(|entry| entry.workload_name == pin.workload_name);
+        let Some(entry) = entries.next() else {
+            debug!(%pin, "pinned workload serves no route, routing normally");
+            return None;
+        };
+        Some(entry)
+    }
+
+    /// Returns whether the given workload passes its health check, if it has
+    /// one.
+    fn is_ready(&self, workload_id: &str) -> bool {
+        let ready = self
+            .ready
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        ready.get(workload_id).copied().unwrap_or(true)
+    }
+
+    /// Finds the route registered by the given workload.
+    fn find_route<T>(
+        &self,
+        workload_id: &str,
+        f: impl FnOnce(&RouteEntry) -> Option<T>,
+    ) -> Option<T> {
+        let lock = self
+            .host_to_workload
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        lock.values()
+            .flatten()
+            .find(|entry| entry.workload_id == workload_id)
+            .and_then(f)
+    }
+
+    /// Returns the version of the workload of `entry` a request goes to while
+    /// a rolling update replaces it, in proportion to their instances, or
+    /// `entry` if none does, see [`split`].
+    fn rollout_target<'a>(
+        &self,
+        candidates: &[&'a RouteEntry],
+        entry: &'a RouteEntry,
+    ) -> &'a RouteEntry {
+        let rollouts = self
+            .rollouts
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if rollouts.is_empty() {
+            return entry;
+        }
+        let mut versions = candidates.iter().copied().filter(|candidate| {
+            candidate.workload_name == entry.workload_name
+                && candidate.route.produces == entry.route.produces
+        });
+        let Some((old, rollout)) = versions
+            .clone()
+            .find_map(|candidate| Some((candidate, rollouts.get(&candidate.workload_id)?)))
+        else {
+            return entry;
+        };
+        let Some(new) = versions.find(|candidate| candidate.workload_id != old.workload_id) else {
+            return old;
+        };
+        match split::pick(&[rollout.replacement_instances, rollout.instances]) {
+            0 => new,
+            _ => old,
+        }
+    }
+
+    /// Picks the route of `routes` a request is sent by.
+    fn pick_route<'a>(
+        &self,
+        routes: &'a HashMap<String, Vec<RouteEntry>>,
+        req: &hyper::Request<hyper::body::Incoming>,
+    ) -> anyhow::Result<&'a RouteEntry> {
+        let workload_host = req
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .context("no Host header in request")?;
+        if let Some(hold) = self.held_route(workload_host, req.uri().path()) {
+            return Err(RouteHeld(hold).into());
+        }
+        if let Some(entry) = self.pinned_route(routes, workload_host, req.uri().path()) {
+            ensure!(
+                self.is_ready(&entry.workload_id),
+                WorkloadNotReady(entry.workload_id.clone())
+            );
+            return Ok(entry);
+        }
+        let mut hosts = host_routes(routes, workload_host).peekable();
+        if hosts.peek().is_none() {
+            anyhow::bail!("no workload bound to host header: {}", workload_host);
+        }
+        // The routes of the most specific host with a route matching the request
+        let Some((routes, entry)) = hosts.find_map(|routes| {
+            routes
+                .iter()
+                .find(|entry| entry.route.matches_request(req))
+                .map(|entry| (routes, entry))
+        }) else {
+            anyhow::bail!(
+                "no route on host {} matches {} {}",
+                workload_host,
+                req.method(),
+                req.uri().path()
+            );
+        };
+        // Workloads failing their health check are passed over
+        let candidates: Vec<&RouteEntry> = routes
+            .iter()
+            .filter(|other| {
+                other.route.normalized_path() == entry.route.normalized_path()
+                    && other.route.specificity() == entry.route.specificity()
+                    && other.route.matches_request(req)
+                    && self.is_ready(&other.workload_id)
+            })
+            .collect();
+        let Some(&first) = candidates.first() else {
+            return Err(WorkloadNotReady(entry.workload_id.clone()).into());
+        };
+        // Pick by the Accept header among routes producing different media types
+        let entry = if candidates.iter().any(|c| !c.route.produces.is_empty()) {
+            let produces: Vec<&[String]> = candidates
+                .iter()
+                .map(|c| c.route.produces.as_slice())
+                .collect();
+            candidates[negotiate::choose(&produces, req.headers())]
+        } else {
+            first
+        };
+        let entry = self.rollout_target(&candidates, entry);
+        if entry.route.weight.is_some() {
+            // Split by weight among the workloads serving the route,
+            // counting an updated workload once
+            let mut targets: Vec<&RouteEntry> = Vec::new();
+            for &candidate in &candidates {
+                if candidate.route.weight.is_some()
+                    && candidate.route.produces == entry.route.produces
+                    && !targets
+                        .iter()
+                        .any(|t| t.workload_name == candidate.workload_name)
+                {
+                    targets.push(candidate);
+                }
+            }
+            let weights: Vec<u32> = targets
+                .iter()
+                .map(|target| self.weight(target).unwrap_or_default())
+                .collect();
+            let target = targets[split::pick(&weights)];
+            // The version of the workload a rolling update picked
+            if target.workload_name == entry.workload_name {
+                return Ok(entry);
+            }
+            return Ok(target);
+        }
+        let Some(experiment) = &entry.route.experiment else {
+            return Ok(entry);
+        };
+
+        // Pick among all variants of the experiment on the same path
+        let variants: Vec<&RouteEntry> = candidates
+            .iter()
+            .copied()
+            .filter(|other| {
+                other.route.produces == entry.route.produces
+                    && other
+                        .route
+                        .experiment
+                        .as_ref()
+                        .is_some_and(|e| e.name == experiment.name)
+            })
+            .collect();
+        let experiments: Vec<&RouteExperiment> = variants
+            .iter()
+            .filter_map(|entry| entry.route.experiment.as_ref())
+            .collect();
+        let chosen = experiment::assign(&experiments, req.headers());
+        Ok(variants[chosen])
+    }
+}
+
+/// Returns the policy of `entry`, one of `routes`, for a request to `path`.
+fn route_policy(
+    routes: &HashMap<String, Vec<RouteEntry>>,
+    entry: &RouteEntry,
+    path: &str,
+) -> RoutePolicy {
+    let route = &entry.route;
+    let shadow = route.shadow.as_deref().and_then(|shadow| {
+        routes
+            .values()
+            .flatten()
+            .find(|other| other.workload_name == shadow && other.workload_id != entry.workload_id)
+            .map(|other| other.workload_id.clone())
+    });
+    let matched = route.match_path(path).map(|(matched, params)| RouteMatch {
+        workload_name: entry.workload_name.clone(),
+        route: route.normalized_path().to_string(),
+        prefix: path[..matched].to_string(),
+        params,
+    });
+    RoutePolicy {
+        name: Some(format!("{}{}/", route.host, route.normalized_path())),
+        auth: route.auth.clone(),
+        limits: route.request_limits(),
+        max_body_bytes: route.max_body_bytes,
+        cache: route.cache.clone(),
+        spool_max_bytes: route.spool_max_bytes,
+        websocket: route.websocket,
+        timeout: route.timeout(),
+        stream_limits: route.stream_limits(),
+        sse_keepalive: route.sse_keepalive_ms.map(Duration::from_millis),
+        negotiated: !route.produces.is_empty(),
+        shadow,
+        matched,
+        experiment: route.experiment.clone(),
     }
 }
 
+/// Implementation of Router that maps Host headers to workload IDs
+/// based on the [`HttpRouteConfig`] in the wasi:http/incoming-handler interface
 #[async_trait::async_trait]
-impl<T: Router> HostHandler for HttpServer<T> {
-    async fn start(&self) -> anyhow::Result<()> {
-        let addr = self.addr;
-        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
-        let shutdown_tx_clone = self.shutdown_tx.clone();
-        let workload_handles = self.workload_handles.clone();
-        let tls_acceptor = self.tls_acceptor.clone();
-
-        // Store the shutdown sender
-        *shutdown_tx_clone.write().await = Some(shutdown_tx);
-
-        let listener = TcpListener::bind(addr).await?;
-        debug!(addr = ?addr, "HTTP server listening");
-        // Start the HTTP server, any incoming requests call Host::handle and then it's routed
-        // to the workload based on host header.
-        let handler = self.router.clone();
-        tokio::spawn(async move {
-            if let Err(e) = run_http_server(
-                listener,
-                handler,
-                workload_handles,
-                &mut shutdown_rx,
-                tls_acceptor,
-            )
-            .await
+impl Router for DynamicRouter {
+    async fn on_workload_resolved(
+        &self,
+        resolved_handle: &ResolvedWorkload,
+        _component_id: &str,
+    ) -> anyhow::Result<()> {
+        let incoming_handler_interface = WitInterface::from("wasi:http/incoming-handler");
+        let Some(http_iface) = resolved_handle
+            .host_interfaces()
+            .iter()
+            .find(|iface| iface.contains(&incoming_handler_interface))
+        else {
+            anyhow::bail!("workload did not request wasi:http/incoming-handler interface");
+        };
+
+        let route = HttpRouteConfig::try_from(http_iface).map_err(|e| {
+            HostError::InvalidWorkload(format!(
+                "invalid wasi:http/incoming-handler route configuration: {e:#}"
+            ))
+        })?;
+
+        let workload_id = resolved_handle.id();
+        let workload_name = format!("{}/{}", resolved_handle.namespace(), resolved_handle.name());
+        let mut hosts: Vec<&str> = route.hosts().collect();
+        hosts.sort_unstable();
+        hosts.dedup();
+        let mut lock = self
+            .host_to_workload
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // Registered on all hosts or none
+        if let Some(conflict) = lock
+            .values()
+            .flatten()
+            .find_map(|entry| entry.conflict(workload_id, &workload_name, &route))
+        {
+            return Err(HostError::RouteConflict(conflict).into());
+        }
+        for host in hosts {
+            let routes = lock.entry(host.to_string()).or_default();
+            let entry = RouteEntry {
+                route: HttpRouteConfig {
+                    host: host.to_string(),
+                    ..route.clone()
+                },
+                workload_id: workload_id.to_string(),
+                workload_name: workload_name.clone(),
+            };
+            // A new version is matched before the old one it replaces
+            if routes
+                .iter()
+                .any(|entry| entry.workload_name == workload_name)
             {
-                error!(err = ?e, addr = ?addr, "HTTP server error");
+                routes.insert(0, entry);
+            } else {
+                routes.push(entry);
             }
-        });
+            // Most specific routes are matched first
+            routes.sort_by_key(|entry| std::cmp::Reverse(entry.route.specificity()));
+        }
+        // Not ready until its health check passes
+        if route.health_check.is_some() {
+            self.ready
+                .write()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(workload_id.to_string(), false);
+        }
+        if let Some(limit) = route.rate_limit {
+            self.rate_limiters
+                .write()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(workload_id.to_string(), Arc::new(RateLimiter::new(limit)));
+        }
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
+        let mut lock = self
+            .host_to_workload
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for routes in lock.values_mut() {
+            routes.retain(|entry| entry.workload_id != workload_id);
+        }
+        lock.retain(|_host, routes| !routes.is_empty());
+        self.ready
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(workload_id);
+        self.rate_limiters
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(workload_id);
+        self.rollouts
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(workload_id);
+        Ok(())
+    }
+
+    fn allow_outgoing_request(
+        &self,
+        _workload_id: &str,
+        _request: &hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        _config: &wasmtime_wasi_http::types::OutgoingRequestConfig,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Pick a workload ID based on the incoming request, along with the
+    /// policy of the route it matched
+    fn route_incoming_request(
+        &self,
+        req: &hyper::Request<hyper::body::Incoming>,
+    ) -> anyhow::Result<(String, RoutePolicy)> {
+        tokio::task::block_in_place(move || {
+            let lock = self
+                .host_to_workload
+                .read()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            // The policy is read under the same lock the route was picked by
+            let entry = self.pick_route(&lock, req)?;
+            let policy = route_policy(&lock, entry, req.uri().path());
+            Ok((entry.workload_id.clone(), policy))
+        })
+    }
+
+    fn request_timeout(&self, workload_id: &str) -> Option<Duration> {
+        self.find_route(workload_id, |entry| entry.route.timeout())
+    }
+
+    fn split_target(&self, workload_id: &str) -> Option<String> {
+        self.find_route(workload_id, |entry| {
+            entry.route.weight.map(|_| entry.workload_name.clone())
+        })
+    }
+
+    fn check_rate_limit(
+        &self,
+        workload_id: &str,
+        headers: &hyper::HeaderMap,
+    ) -> Result<(), Duration> {
+        let limiter = self
+            .rate_limiters
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(workload_id)
+            .cloned();
+        limiter.map_or(Ok(()), |limiter| limiter.check(headers))
+    }
+
+    async fn routes(&self) -> Vec<RouteEntry> {
+        self.routing_table().await
+    }
+
+    async fn route_pins(&self) -> Vec<RoutePin> {
+        self.pins().await
+    }
+
+    async fn pin_route(&self, pin: RoutePin) -> anyhow::Result<()> {
+        pin.validate()?;
+        let mut pins = self
+            .pins
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        pins.retain(|existing| !existing.same_target(&pin.host, pin.path.as_deref()));
+        info!(%pin, "pinned route");
+        pins.push(pin);
+        // Most specific path prefixes are matched first
+        pins.sort_by_key(|pin| std::cmp::Reverse(pin.as_route().specificity()));
+        Ok(())
+    }
+
+    async fn unpin_route(&self, host: &str, path: Option<&str>) -> bool {
+        let mut pins = self
+            .pins
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let before = pins.len();
+        pins.retain(|pin| !pin.same_target(host, path));
+        before != pins.len()
+    }
+
+    async fn route_holds(&self) -> Vec<RouteHold> {
+        self.holds().await
+    }
+
+    async fn hold_route(&self, hold: RouteHold) -> anyhow::Result<()> {
+        hold.validate()?;
+        let mut holds = self
+            .holds
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        holds.retain(|existing| !existing.same_target(&hold.host, hold.path.as_deref()));
+        info!(%hold, "held route");
+        holds.push(hold);
+        // Most specific path prefixes are matched first
+        holds.sort_by_key(|hold| std::cmp::Reverse(hold.as_route().specificity()));
+        Ok(())
+    }
+
+    async fn release_route(&self, host: &str, path: Option<&str>) -> bool {
+        let mut holds = self
+            .holds
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let before = holds.len();
+        holds.retain(|hold| !hold.same_target(host, path));
+        before != holds.len()
+    }
+
+    async fn route_weights(&self) -> Vec<RouteWeight> {
+        self.weights()
+    }
+
+    async fn set_route_weight(&self, weight: RouteWeight) -> anyhow::Result<()> {
+        weight.validate()?;
+        info!(%weight, "shifted route weight");
+        self.weights
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(weight.workload_name, weight.weight);
+        Ok(())
+    }
+
+    fn set_rollout(&self, workload_id: &str, split: Option<RolloutSplit>) {
+        let mut rollouts = self
+            .rollouts
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match split {
+            Some(split) => rollouts.insert(workload_id.to_string(), split),
+            None => rollouts.remove(workload_id),
+        };
+    }
+
+    fn health_check(&self, workload_id: &str) -> Option<RouteHealthCheck> {
+        self.find_route(workload_id, |entry| entry.route.health_check.clone())
+    }
+
+    fn set_ready(&self, workload_id: &str, ready: bool) {
+        let mut lock = self
+            .ready
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // Workloads that stopped are not tracked again
+        if let Some(current) = lock.get_mut(workload_id) {
+            *current = ready;
+        }
+    }
+
+    fn unready_workloads(&self) -> Vec<String> {
+        let ready = self
+            .ready
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut unready: Vec<String> = ready
+            .iter()
+            .filter(|(_, ready)| !**ready)
+            .map(|(id, _)| id.clone())
+            .collect();
+        unready.sort_unstable();
+        unready
+    }
+}
+
+/// Development router that routes all requests to the last resolved workload
+#[derive(Default)]
+pub struct DevRouter {
+    last_workload_id: tokio::sync::Mutex<Option<String>>,
+}
+
+#[async_trait::async_trait]
+impl Router for DevRouter {
+    async fn on_workload_resolved(
+        &self,
+        resolved_handle: &ResolvedWorkload,
+        _component_id: &str,
+    ) -> anyhow::Result<()> {
+        let mut lock = self.last_workload_id.lock().await;
+        lock.replace(resolved_handle.id().to_string());
+        Ok(())
+    }
+
+    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
+        let mut lock = self.last_workload_id.lock().await;
+        if let Some(current_id) = &*lock
+            && current_id == workload_id
+        {
+            let _ = lock.take();
+        }
+        Ok(())
+    }
+
+    fn allow_outgoing_request(
+        &self,
+        _workload_id: &str,
+        _request: &hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        _config: &wasmtime_wasi_http::types::OutgoingRequestConfig,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Pick a workload ID based on the incoming request
+    fn route_incoming_request(
+        &self,
+        _req: &hyper::Request<hyper::body::Incoming>,
+    ) -> anyhow::Result<(String, RoutePolicy)> {
+        let lock = self.last_workload_id.try_lock()?;
+        match &*lock {
+            Some(id) => {
+                tracing::info!(workload_id = %id, "incoming request");
+                Ok((id.clone(), RoutePolicy::default()))
+            }
+            None => anyhow::bail!("no workload available to route request"),
+        }
+    }
+}
+
+/// Trait defining the behavior of a Host HTTP Extension
+/// Allows for custom handling of incoming and outgoing HTTP requests
+/// Use this trait to implement custom HTTP server transport
+#[async_trait::async_trait]
+pub trait HostHandler: Send + Sync + 'static {
+    async fn start(&self) -> anyhow::Result<()>;
+    /// Stops accepting connections. Open connections finish the requests in
+    /// flight, then close.
+    async fn stop(&self) -> anyhow::Result<()>;
+
+    async fn on_workload_resolved(
+        &self,
+        resolved_handle: &ResolvedWorkload,
+        component_id: &str,
+    ) -> anyhow::Result<()>;
+    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()>;
+
+    /// Sends an outgoing request of a component, restricted to the hosts
+    /// `egress` allows, see [`egress`].
+    fn outgoing_request(
+        &self,
+        workload_id: &str,
+        egress: Arc<EgressPolicy>,
+        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        config: wasmtime_wasi_http::types::OutgoingRequestConfig,
+    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse>;
+
+    /// Applies the connection limits of the host. Ignored by default.
+    fn set_limits(&self, _limits: &HostLimits) {}
+
+    /// Returns the rules signing the outgoing requests of components of
+    /// `namespace`, see [`signing`]. Returns none by default.
+    fn request_signers(&self, _namespace: &str) -> Vec<Arc<SigningRule>> {
+        Vec::new()
+    }
+
+    /// Returns whether components whose `allowed_hosts` allows no host may
+    /// send requests to every host, see [`EgressPolicy`]. Allows them by
+    /// default.
+    fn egress_default(&self) -> EgressDefault {
+        EgressDefault::Allow
+    }
+
+    /// Returns the pooled connections of outgoing requests, see
+    /// [`connection_pool`]. Returns none by default.
+    fn outgoing_connections(&self) -> ConnectionPoolStats {
+        ConnectionPoolStats::default()
+    }
+
+    /// Returns the circuit breakers of the hosts outgoing requests were sent
+    /// to, see [`resilience`]. Returns none by default.
+    fn outgoing_circuits(&self) -> Vec<CircuitStatus> {
+        Vec::new()
+    }
+
+    /// Drops the cached responses to requests for `host`, or every host,
+    /// whose path starts with `path`, or any path, see [`cache`]. Returns how
+    /// many were dropped, none by default.
+    fn invalidate_cache(&self, _host: Option<&str>, _path: Option<&str>) -> usize {
+        0
+    }
+
+    /// Returns the open connections in total, and the requests being handled
+    /// by namespace. Returns none by default.
+    fn open_connections(&self) -> (usize, HashMap<String, usize>) {
+        Default::default()
+    }
+
+    /// Returns the requests being handled, the oldest first, see
+    /// [`inflight`]. Returns none by default.
+    fn in_flight_requests(&self) -> Vec<InFlightRequest> {
+        Vec::new()
+    }
+
+    /// Returns the routes requests are matched against. Returns none by
+    /// default.
+    async fn routes(&self) -> Vec<RouteEntry> {
+        Vec::new()
+    }
+
+    /// Returns the pins overriding routes, see [`RoutePin`]. Returns none by
+    /// default.
+    async fn route_pins(&self) -> Vec<RoutePin> {
+        Vec::new()
+    }
+
+    /// Adds a pin, replacing the pin of the same host and path prefix.
+    /// Unsupported by default.
+    async fn pin_route(&self, _pin: RoutePin) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP router supporting route pins")
+    }
+
+    /// Removes the pin of a host and path prefix, returning whether it
+    /// existed. Returns `false` by default.
+    async fn unpin_route(&self, _host: &str, _path: Option<&str>) -> bool {
+        false
+    }
+
+    /// Returns the holds answering requests instead of routes, see
+    /// [`RouteHold`]. Returns none by default.
+    async fn route_holds(&self) -> Vec<RouteHold> {
+        Vec::new()
+    }
+
+    /// Adds a hold, replacing the hold of the same host and path prefix.
+    /// Unsupported by default.
+    async fn hold_route(&self, _hold: RouteHold) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP router supporting route holds")
+    }
+
+    /// Removes the hold of a host and path prefix, returning whether it
+    /// existed. Returns `false` by default.
+    async fn release_route(&self, _host: &str, _path: Option<&str>) -> bool {
+        false
+    }
+
+    /// Returns the weights shifted at runtime, see [`split`]. Returns none
+    /// by default.
+    async fn route_weights(&self) -> Vec<RouteWeight> {
+        Vec::new()
+    }
+
+    /// Shifts the weight of a workload among those splitting its route.
+    /// Unsupported by default.
+    async fn set_route_weight(&self, _weight: RouteWeight) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP router supporting route weights")
+    }
+
+    /// Splits the requests for the routes of a workload between it and the
+    /// new version replacing it, or stops splitting them if `None`, see
+    /// [`split`]. Ignored by default.
+    fn set_rollout(&self, _workload_id: &str, _split: Option<RolloutSplit>) {}
+
+    /// Returns the addresses the handler listens on. Returns none by default.
+    async fn listeners(&self) -> Vec<SocketAddr> {
+        Vec::new()
+    }
+
+    /// Returns the certificate files of the TLS listeners, if any. Returns
+    /// `None` by default.
+    async fn tls_files(&self) -> Option<TlsFiles> {
+        None
+    }
+
+    /// Starts listening on `addr` while running. Unsupported by default.
+    async fn add_listener(&self, _addr: SocketAddr) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP server to add listeners to")
+    }
+
+    /// Stops listening on `addr`, keeping the connections accepted on it open,
+    /// and returns whether it listened on it. Returns `false` by default.
+    async fn remove_listener(&self, _addr: SocketAddr) -> bool {
+        false
+    }
+
+    /// Reads the TLS certificate and key of the listeners again, from `files`
+    /// if given, for the connections accepted from now on. Unsupported by
+    /// default.
+    async fn reload_tls(&self, _files: Option<TlsFiles>) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP server with TLS")
+    }
+
+    /// Starts recording the requests of a workload, replacing its recording
+    /// if any, see [`recording`]. Unsupported by default.
+    fn start_recording(&self, _recording: Recording) -> anyhow::Result<()> {
+        anyhow::bail!("the host has no HTTP server to record requests of")
+    }
+
+    /// Stops recording the requests of a workload, returning the recording
+    /// if it was recorded. Returns `None` by default.
+    fn stop_recording(&self, _workload_id: &str) -> Option<RecordingStatus> {
+        None
+    }
+
+    /// Returns the workloads being recorded. Returns none by default.
+    fn recordings(&self) -> Vec<RecordingStatus> {
+        Vec::new()
+    }
+
+    /// Sends recorded requests to a workload one after the other, see
+    /// [`recording`]. Unsupported by default.
+    async fn replay(
+        &self,
+        _workload_id: &str,
+        _invocations: Vec<RecordedInvocation>,
+        _pace: bool,
+    ) -> anyhow::Result<ReplayReport> {
+        anyhow::bail!("the host has no HTTP server to replay requests to")
+    }
+}
+
+impl std::fmt::Debug for dyn HostHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostHandler").finish()
+    }
+}
+
+#[derive(Default)]
+pub struct NullServer {}
+
+#[async_trait::async_trait]
+impl HostHandler for NullServer {
+    async fn start(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn on_workload_resolved(
+        &self,
+        _resolved_handle: &ResolvedWorkload,
+        _component_id: &str,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn on_workload_unbind(&self, _workload_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn outgoing_request(
+        &self,
+        _workload_id: &str,
+        _egress: Arc<EgressPolicy>,
+        _request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        _config: wasmtime_wasi_http::types::OutgoingRequestConfig,
+    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse> {
+        Err(wasmtime_wasi_http::HttpError::trap(anyhow::anyhow!(
+            "http client not available"
+        )))
+    }
+}
+
+/// A resolved workload handle, its associated component id and its middleware, if any
+pub type WorkloadHandle = (
+    ResolvedWorkload,
+    InstancePre<Ctx>,
+    String,
+    Option<Middleware>,
+);
+
+/// A map from host header to resolved workload handles
+pub type WorkloadHandles = Arc<RwLock<HashMap<String, WorkloadHandle>>>;
+
+/// HTTP server plugin that handles incoming HTTP requests for WebAssembly components.
+///
+/// This plugin implements the `wasi:http/incoming-handler` interface and routes
+/// HTTP requests to appropriate WebAssembly components based on virtual hosting.
+/// It supports both HTTP and HTTPS connections with optional mutual TLS.
+///
+/// Trailers flow through in both directions: request trailers are exposed to the
+/// component as `wasi:http` incoming-body trailers, and trailers set on the
+/// outgoing body are written after a chunked response. Over HTTP/1.1, response
+/// trailers are only sent to clients that announce `TE: trailers`, over HTTP/2
+/// they are always sent, see [`h2`].
+pub struct HttpServer<T: Router> {
+    router: Arc<T>,
+    addrs: Vec<SocketAddr>,
+    workload_handles: WorkloadHandles,
+    /// The running listeners, each of which stops accepting connections once
+    /// its sender is dropped
+    listeners: RwLock<Vec<(SocketAddr, mpsc::Sender<()>)>>,
+    /// What the listeners share, set when the server starts
+    listening: RwLock<Option<Arc<Listening<T>>>>,
+    tls_files: RwLock<Option<TlsFiles>>,
+    tls_acceptor: Arc<RwLock<Option<TlsAcceptor>>>,
+    forwarding: ForwardingConfig,
+    idempotency: Option<Arc<dyn IdempotencyStore>>,
+    slow_request_threshold: Duration,
+    connections: Arc<ConnectionTracker>,
+    resolver: Arc<Resolver>,
+    egress_default: EgressDefault,
+    connection_pool: Arc<ConnectionPool>,
+    resilience: Arc<OutboundResilience>,
+    response_cache: Arc<ResponseCache>,
+    request_limits: Arc<RequestLimits>,
+    error_pages: Arc<ErrorPages>,
+    hooks: Arc<HttpHooks>,
+    access_log: Arc<AccessLog>,
+    /// The workloads whose requests are recorded, see [`recording`]
+    recorder: Arc<Recorder>,
+    grpc_web: bool,
+    in_flight: InFlightRequests,
+    /// Where request bodies are spilled to, see [`spool`]
+    spool_dir: Arc<PathBuf>,
+    signing: Vec<Arc<SigningRule>>,
+    /// The path the server answers probes of the host on, see [`health`]
+    health_path: Option<Arc<str>>,
+    /// The tasks running the health checks of workloads
+    health_checks: std::sync::Mutex<HashMap<String, tokio::task::AbortHandle>>,
+}
+
+impl<T: Router> std::fmt::Debug for HttpServer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpServer")
+            .field("addrs", &self.addrs)
+            .field("forwarding", &self.forwarding)
+            .finish()
+    }
+}
+
+impl<T: Router> HttpServer<T> {
+    /// Creates a new HTTP server listening on the specified address.
+    ///
+    /// # Arguments
+    /// * `router` - The router implementation for handling requests
+    /// * `addr` - The socket address to bind to
+    ///
+    /// # Returns
+    /// A new `HttpServer` instance configured for HTTP connections.
+    pub fn new(router: T, addr: SocketAddr) -> Self {
+        Self {
+            router: Arc::new(router),
+            addrs: vec![addr],
+            workload_handles: Arc::default(),
+            listeners: RwLock::default(),
+            listening: RwLock::default(),
+            tls_files: RwLock::default(),
+            tls_acceptor: Arc::default(),
+            forwarding: ForwardingConfig::default(),
+            idempotency: None,
+            slow_request_threshold: metrics::DEFAULT_SLOW_REQUEST_THRESHOLD,
+            connections: Arc::default(),
+            resolver: Arc::default(),
+            egress_default: EgressDefault::default(),
+            connection_pool: Arc::default(),
+            resilience: Arc::default(),
+            response_cache: Arc::default(),
+            request_limits: Arc::default(),
+            error_pages: Arc::default(),
+            hooks: Arc::default(),
+            access_log: Arc::default(),
+            recorder: Arc::default(),
+            grpc_web: false,
+            in_flight: InFlightRequests::default(),
+            spool_dir: Arc::new(std::env::temp_dir()),
+            signing: Vec::new(),
+            health_path: None,
+            health_checks: std::sync::Mutex::default(),
+        }
+    }
+
+    /// Also listens on `addr`, e.g. to serve both `0.0.0.0:8000` and
+    /// `[::1]:8000`. See the [module docs](self#listen-addresses) for
+    /// dual-stack addresses.
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        if !self.addrs.contains(&addr) {
+            self.addrs.push(addr);
+        }
+        self
+    }
+
+    /// Returns the addresses the server was created to listen on. See
+    /// [`HostHandler::listeners`] for the addresses it listens on now.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Expects every connection to start with a PROXY protocol v1 or v2 header,
+    /// as sent by L4 load balancers, and uses its source as the client address.
+    ///
+    /// Connections without a valid header are closed, so only enable this when
+    /// every client connects through such a load balancer.
+    pub fn with_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.forwarding.proxy_protocol = enabled;
+        self
+    }
+
+    /// Trusts `X-Forwarded-For` and `Forwarded` headers sent by peers in the
+    /// given networks.
+    pub fn with_trusted_proxies(mut self, proxies: impl IntoIterator<Item = IpCidr>) -> Self {
+        self.forwarding.trusted_proxies.extend(proxies);
+        self
+    }
+
+    /// Deduplicates requests that carry an `Idempotency-Key` header.
+    ///
+    /// A repeated key for the same component is answered with the status of
+    /// the first response and an empty body, or `409 Conflict` while the first
+    /// request is still being handled. Keys of failed requests are released.
+    pub fn with_idempotency(mut self, store: Arc<dyn IdempotencyStore>) -> Self {
+        self.idempotency = Some(store);
+        self
+    }
+
+    /// Links requests that take at least `threshold` to their trace in the
+    /// request metrics, in addition to requests that fail with a server error.
+    /// See [`metrics`] for details.
+    pub fn with_slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = threshold;
+        self
+    }
+
+    /// Resolves the hosts of outgoing requests of components with `resolver`,
+    /// see [`egress`].
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Whether components whose `allowed_hosts` allows no host may send
+    /// requests to every host, the default, or to none, see [`EgressPolicy`].
+    pub fn with_egress_default(mut self, default: EgressDefault) -> Self {
+        self.egress_default = default;
+        self
+    }
+
+    /// Pools the connections of outgoing requests of components as `config`
+    /// says, see [`connection_pool`].
+    pub fn with_connection_pool(mut self, config: ConnectionPoolConfig) -> Self {
+        self.connection_pool = Arc::new(ConnectionPool::new(config));
+        self
+    }
+
+    /// Formats the responses the host answers requests with itself as
+    /// `pages`, see [`error_pages`].
+    pub fn with_error_pages(mut self, pages: ErrorPages) -> Self {
+        self.error_pages = Arc::new(pages);
+        self
+    }
+
+    /// Runs `hook` on every request and its response, after the hooks added
+    /// before it, see [`hooks`].
+    pub fn with_hook(mut self, hook: impl HttpHook) -> Self {
+        Arc::make_mut(&mut self.hooks).push(Arc::new(hook));
+        self
+    }
+
+    /// Writes an entry for every request to the sinks of `log`, see
+    /// [`access_log`].
+    pub fn with_access_log(mut self, log: AccessLog) -> Self {
+        self.access_log = Arc::new(log);
+        self
+    }
+
+    /// Translates gRPC-web and Connect requests to gRPC for components, and
+    /// their responses back, see [`grpc_web`].
+    pub fn with_grpc_web(mut self, enabled: bool) -> Self {
+        self.grpc_web = enabled;
+        self
+    }
+
+    /// Spills request bodies of routes with body spooling to temporary files
+    /// in `dir` instead of the temporary directory of the system, see
+    /// [`spool`].
+    pub fn with_spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool_dir = Arc::new(dir.into());
+        self
+    }
+
+    /// Signs the outgoing requests of components matching `rules` with their
+    /// credentials, see [`signing`].
+    pub fn with_request_signing(mut self, rules: impl IntoIterator<Item = SigningRule>) -> Self {
+        self.signing = rules.into_iter().map(Arc::new).collect();
+        self
+    }
+
+    /// Retries and fails fast the outgoing requests of components to the
+    /// hosts of `rules`, see [`resilience`].
+    pub fn with_outbound_resilience(
+        mut self,
+        rules: impl IntoIterator<Item = ResilienceRule>,
+    ) -> Self {
+        self.resilience = Arc::new(OutboundResilience::new(rules));
+        self
+    }
+
+    /// Caches up to `max_bytes` of responses of routes with response caching,
+    /// instead of [`cache::DEFAULT_CACHE_BYTES`], see [`cache`].
+    pub fn with_response_cache_size(mut self, max_bytes: u64) -> Self {
+        self.response_cache = Arc::new(ResponseCache::new(max_bytes));
+        self
+    }
+
+    /// Limits the requests of all routes, unless a route sets its own
+    /// limits, see [`request_limits`].
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.request_limits = Arc::new(limits);
+        self
+    }
+
+    /// Answers requests for `path`, e.g. `/healthz`, on any host with the
+    /// health of the host instead of routing them, see [`health`].
+    pub fn with_health_path(mut self, path: impl AsRef<str>) -> Self {
+        self.health_path = Some(path.as_ref().into());
+        self
+    }
+
+    /// Returns the router used to route incoming requests.
+    pub fn router(&self) -> &Arc<T> {
+        &self.router
+    }
+
+    /// Creates a new HTTPS server with TLS support.
+    ///
+    /// # Arguments
+    /// * `router` - The router implementation for handling requests
+    /// * `addr` - The socket address to bind to
+    /// * `cert_path` - Path to the TLS certificate file
+    /// * `key_path` - Path to the private key file
+    /// * `ca_path` - Optional path to CA certificate for mutual TLS
+    ///
+    /// # Returns
+    /// A new `HttpServer` instance configured for HTTPS connections.
+    ///
+    /// # Errors
+    /// Returns an error if the TLS configuration cannot be loaded.
+    pub async fn new_with_tls(
+        router: T,
+        addr: SocketAddr,
+        cert_path: &Path,
+        key_path: &Path,
+        ca_path: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        let mut files = TlsFiles::new(cert_path, key_path);
+        if let Some(ca_path) = ca_path {
+            files = files.with_ca(ca_path);
+        }
+        Self::new(router, addr).with_tls_files(files).await
+    }
+
+    /// Serves HTTPS with the certificates read from `files`, which
+    /// [`HostHandler::reload_tls`] reads again.
+    ///
+    /// # Errors
+    /// Returns an error if the files can't be read or their certificates are
+    /// invalid.
+    pub async fn with_tls_files(self, files: TlsFiles) -> anyhow::Result<Self> {
+        let tls_acceptor = files.load().await?;
+        *self.tls_files.write().await = Some(files);
+        *self.tls_acceptor.write().await = Some(tls_acceptor);
+        Ok(self)
+    }
+
+    /// Serves HTTPS with `certificates` given in memory. Without files there
+    /// is nothing for [`HostHandler::reload_tls`] to read again, it needs new
+    /// files instead.
+    ///
+    /// # Errors
+    /// Returns an error if the certificates are invalid.
+    pub fn with_tls(mut self, certificates: TlsCertificates) -> anyhow::Result<Self> {
+        self.tls_acceptor = Arc::new(RwLock::new(Some(certificates.acceptor()?)));
+        self.tls_files = RwLock::default();
+        Ok(self)
+    }
+
+    /// Binds a listener on `addr` and accepts its connections until its
+    /// sender in `listeners` is dropped.
+    fn spawn_listener(
+        listening: &Arc<Listening<T>>,
+        listeners: &mut Vec<(SocketAddr, mpsc::Sender<()>)>,
+        addr: SocketAddr,
+        addrs: &[SocketAddr],
+    ) -> anyhow::Result<()> {
+        let listener =
+            bind_listener(addr, addrs).with_context(|| format!("failed to listen on {addr}"))?;
+        debug!(addr = ?listener.local_addr()?, "HTTP server listening");
+        let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
+        let listening = listening.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_http_server(listener, listening, shutdown_rx).await {
+                error!(err = ?e, addr = ?addr, "HTTP server error");
+            }
+        });
+        listeners.push((addr, shutdown_tx));
+        Ok(())
+    }
+}
+
+/// What the listeners of a running [`HttpServer`] share.
+struct Listening<T: Router> {
+    handler: Arc<T>,
+    workload_handles: WorkloadHandles,
+    /// Read for every connection, so reloaded certificates apply to the next
+    /// handshake
+    tls_acceptor: Arc<RwLock<Option<TlsAcceptor>>>,
+    forwarding: Arc<ForwardingConfig>,
+    idempotency: Option<Arc<dyn IdempotencyStore>>,
+    metrics: Arc<HttpMetrics>,
+    connections: Arc<ConnectionTracker>,
+    error_pages: Arc<ErrorPages>,
+    hooks: Arc<HttpHooks>,
+    access_log: Arc<AccessLog>,
+    recorder: Arc<Recorder>,
+    grpc_web: bool,
+    in_flight: InFlightRequests,
+    spool_dir: Arc<PathBuf>,
+    health_path: Option<Arc<str>>,
+    response_cache: Arc<ResponseCache>,
+    request_limits: Arc<RequestLimits>,
+    /// Set when the server stops, closing the connections once their requests
+    /// in flight finish
+    closing: watch::Sender<bool>,
+}
+
+#[async_trait::async_trait]
+impl<T: Router> HostHandler for HttpServer<T> {
+    async fn start(&self) -> anyhow::Result<()> {
+        // Any incoming requests call Host::handle and then it's routed to the
+        // workload based on host header.
+        let listening = Arc::new(Listening {
+            handler: self.router.clone(),
+            workload_handles: self.workload_handles.clone(),
+            tls_acceptor: self.tls_acceptor.clone(),
+            forwarding: Arc::new(self.forwarding.clone()),
+            idempotency: self.idempotency.clone(),
+            metrics: Arc::new(HttpMetrics::new(
+                &opentelemetry::global::meter("wash-http"),
+                self.slow_request_threshold,
+            )),
+            connections: self.connections.clone(),
+            error_pages: self.error_pages.clone(),
+            hooks: self.hooks.clone(),
+            access_log: self.access_log.clone(),
+            recorder: self.recorder.clone(),
+            grpc_web: self.grpc_web,
+            in_flight: self.in_flight.clone(),
+            spool_dir: self.spool_dir.clone(),
+            health_path: self.health_path.clone(),
+            response_cache: self.response_cache.clone(),
+            request_limits: self.request_limits.clone(),
+            closing: watch::channel(false).0,
+        });
+
+        let mut listeners = Vec::with_capacity(self.addrs.len());
+        for addr in &self.addrs {
+            // Listeners bound so far stop when dropped on error
+            Self::spawn_listener(&listening, &mut listeners, *addr, &self.addrs)?;
+        }
+        *self.listeners.write().await = listeners;
+        *self.listening.write().await = Some(listening);
+
+        let protocol = if self.tls_acceptor.read().await.is_some() {
+            "HTTPS"
+        } else {
+            "HTTP"
+        };
+        debug!(addrs = ?self.addrs, protocol = protocol, "HTTP server starting");
+        Ok(())
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        let listeners = std::mem::take(&mut *self.listeners.write().await);
+        info!(
+            addrs = ?listeners.iter().map(|(addr, _)| addr).collect::<Vec<_>>(),
+            "HTTP server stopping"
+        );
+        if let Some(listening) = self.listening.write().await.take() {
+            listening.closing.send_replace(true);
+        }
+        Ok(())
+    }
+
+    async fn on_workload_resolved(
+        &self,
+        resolved_handle: &ResolvedWorkload,
+        component_id: &str,
+    ) -> anyhow::Result<()> {
+        self.router
+            .on_workload_resolved(resolved_handle, component_id)
+            .await?;
+        let instance_pre = resolved_handle.instantiate_pre(component_id).await?;
+        let middleware = match resolved_handle.middleware_component().await {
+            Some(id) => Some(Middleware::new(
+                resolved_handle.instantiate_pre(&id).await?,
+                id,
+            )),
+            None => None,
+        };
+
+        let handle = (
+            resolved_handle.clone(),
+            instance_pre,
+            component_id.to_string(),
+            middleware,
+        );
+        self.workload_handles
+            .write()
+            .await
+            .insert(resolved_handle.id().to_string(), handle.clone());
+
+        if let Some(check) = self.router.health_check(resolved_handle.id()) {
+            // Path checks are sent to a host of the route
+            let host = self
+                .router
+                .routes()
+                .await
+                .into_iter()
+                .find(|entry| {
+                    entry.workload_id == resolved_handle.id() && !entry.route.host.starts_with("*.")
+                })
+                .map_or_else(|| "localhost".to_string(), |entry| entry.route.host);
+            let task = tokio::spawn(health::run(
+                self.router.clone(),
+                resolved_handle.id().to_string(),
+                check,
+                host,
+                handle,
+            ));
+            if let Some(previous) = self
+                .health_checks
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(resolved_handle.id().to_string(), task.abort_handle())
+            {
+                previous.abort();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
+        if let Some(task) = self
+            .health_checks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(workload_id)
+        {
+            task.abort();
+        }
+        self.router.on_workload_unbind(workload_id).await?;
+        self.response_cache.remove_workload(workload_id);
+        self.recorder.stop(workload_id);
+
+        self.workload_handles.write().await.remove(workload_id);
+
+        Ok(())
+    }
+
+    fn outgoing_request(
+        &self,
+        workload_id: &str,
+        egress: Arc<EgressPolicy>,
+        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
+        config: wasmtime_wasi_http::types::OutgoingRequestConfig,
+    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse> {
+        self.router
+            .allow_outgoing_request(workload_id, &request, &config)
+            .map_err(|e| {
+                wasmtime_wasi_http::HttpError::trap(anyhow::anyhow!("request not allowed: {}", e))
+            })?;
+
+        Ok(egress::send_request(
+            self.resolver.clone(),
+            self.connection_pool.clone(),
+            self.resilience.clone(),
+            egress,
+            request,
+            config,
+        ))
+    }
+
+    fn set_limits(&self, limits: &HostLimits) {
+        self.connections.set_limits(limits);
+    }
+
+    fn open_connections(&self) -> (usize, HashMap<String, usize>) {
+        self.connections.open()
+    }
+
+    fn request_signers(&self, namespace: &str) -> Vec<Arc<SigningRule>> {
+        self.signing
+            .iter()
+            .filter(|rule| rule.applies_to(namespace))
+            .cloned()
+            .collect()
+    }
+
+    fn egress_default(&self) -> EgressDefault {
+        self.egress_default
+    }
+
+    fn outgoing_connections(&self) -> ConnectionPoolStats {
+        self.connection_pool.stats()
+    }
+
+    fn outgoing_circuits(&self) -> Vec<CircuitStatus> {
+        self.resilience.circuits()
+    }
+
+    fn invalidate_cache(&self, host: Option<&str>, path: Option<&str>) -> usize {
+        self.response_cache.invalidate(host, path)
+    }
+
+    fn in_flight_requests(&self) -> Vec<InFlightRequest> {
+        self.in_flight.list()
+    }
+
+    async fn routes(&self) -> Vec<RouteEntry> {
+        self.router.routes().await
+    }
+
+    async fn route_pins(&self) -> Vec<RoutePin> {
+        self.router.route_pins().await
+    }
+
+    async fn pin_route(&self, pin: RoutePin) -> anyhow::Result<()> {
+        self.router.pin_route(pin).await
+    }
+
+    async fn unpin_route(&self, host: &str, path: Option<&str>) -> bool {
+        self.router.unpin_route(host, path).await
+    }
+
+    async fn route_holds(&self) -> Vec<RouteHold> {
+        self.router.route_holds().await
+    }
+
+    async fn hold_route(&self, hold: RouteHold) -> anyhow::Result<()> {
+        self.router.hold_route(hold).await
+    }
+
+    async fn release_route(&self, host: &str, path: Option<&str>) -> bool {
+        self.router.release_route(host, path).await
+    }
+
+    async fn route_weights(&self) -> Vec<RouteWeight> {
+        self.router.route_weights().await
+    }
+
+    async fn set_route_weight(&self, weight: RouteWeight) -> anyhow::Result<()> {
+        self.router.set_route_weight(weight).await
+    }
+
+    fn set_rollout(&self, workload_id: &str, split: Option<RolloutSplit>) {
+        self.router.set_rollout(workload_id, split);
+    }
+
+    async fn listeners(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .read()
+            .await
+            .iter()
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    async fn tls_files(&self) -> Option<TlsFiles> {
+        self.tls_files.read().await.clone()
+    }
+
+    async fn add_listener(&self, addr: SocketAddr) -> anyhow::Result<()> {
+        let Some(listening) = self.listening.read().await.clone() else {
+            anyhow::bail!("the HTTP server is not running");
+        };
+        let mut listeners = self.listeners.write().await;
+        ensure!(
+            !listeners.iter().any(|(existing, _)| *existing == addr),
+            "the HTTP server already listens on {addr}"
+        );
+        let mut addrs: Vec<SocketAddr> = listeners.iter().map(|(addr, _)| *addr).collect();
+        addrs.push(addr);
+        Self::spawn_listener(&listening, &mut listeners, addr, &addrs)?;
+        info!(addr = ?addr, "HTTP server listening on new address");
+        Ok(())
+    }
+
+    async fn remove_listener(&self, addr: SocketAddr) -> bool {
+        let mut listeners = self.listeners.write().await;
+        let before = listeners.len();
+        // Dropping the sender stops the listener, open connections are kept
+        listeners.retain(|(existing, _)| *existing != addr);
+        let removed = before != listeners.len();
+        if removed {
+            info!(addr = ?addr, "HTTP server stopped listening");
+        }
+        removed
+    }
+
+    async fn reload_tls(&self, files: Option<TlsFiles>) -> anyhow::Result<()> {
+        let mut tls_files = self.tls_files.write().await;
+        let Some(files) = files.or_else(|| tls_files.clone()) else {
+            anyhow::bail!("the HTTP server has no TLS certificate to reload");
+        };
+        // The current certificate stays in use if the new one is invalid
+        let tls_acceptor = files.load().await?;
+        *self.tls_acceptor.write().await = Some(tls_acceptor);
+        info!(cert = %files.cert.display(), "reloaded HTTP server TLS certificate");
+        *tls_files = Some(files);
+        Ok(())
+    }
+
+    fn start_recording(&self, recording: Recording) -> anyhow::Result<()> {
+        self.recorder.start(recording)
+    }
+
+    fn stop_recording(&self, workload_id: &str) -> Option<RecordingStatus> {
+        self.recorder.stop(workload_id)
+    }
+
+    fn recordings(&self) -> Vec<RecordingStatus> {
+        self.recorder.list()
+    }
+
+    async fn replay(
+        &self,
+        workload_id: &str,
+        invocations: Vec<RecordedInvocation>,
+        pace: bool,
+    ) -> anyhow::Result<ReplayReport> {
+        let Some(handle) = self.workload_handles.read().await.get(workload_id).cloned() else {
+            anyhow::bail!("workload {workload_id} serves no HTTP requests");
+        };
+        let timeout = self.router.request_timeout(workload_id);
+        recording::replay(workload_id, handle, &invocations, pace, timeout).await
+    }
+}
+
+/// Binds a listener on `addr`, one of the addresses in `addrs`.
+///
+/// The unspecified IPv6 address is dual-stack unless `addrs` has an IPv4
+/// address with the same port, which it would conflict with.
+fn bind_listener(addr: SocketAddr, addrs: &[SocketAddr]) -> anyhow::Result<TcpListener> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    if let SocketAddr::V6(v6) = addr {
+        let dual_stack = v6.ip().is_unspecified()
+            && !addrs
+                .iter()
+                .any(|other| other.is_ipv4() && other.port() == addr.port());
+        socket.set_only_v6(!dual_stack)?;
+    }
+    // Matches `TcpListener::bind`, so a restarted host can listen again
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+/// HTTP server implementation that routes to workload components, accepting
+/// connections on `listener` until `shutdown_rx` is closed
+async fn run_http_server<T: Router>(
+    listener: TcpListener,
+    listening: Arc<Listening<T>>,
+    mut shutdown_rx: mpsc::Receiver<()>,
+) -> anyhow::Result<()> {
+    loop {
+        tokio::select! {
+            // Handle shutdown signal
+            _ = shutdown_rx.recv() => {
+                debug!(addr = ?listener.local_addr().ok(), "HTTP listener received shutdown signal");
+                break;
+            }
+            // Accept new connections
+            result = listener.accept() => {
+                match result {
+                    Ok((mut client, client_addr)) => {
+                        // IPv4 clients of dual-stack listeners arrive as IPv4-mapped addresses
+                        let client_addr = forwarded::canonical_addr(client_addr);
+                        debug!(addr = ?client_addr, "new HTTP client connection");
+                        let Some(connection) = listening.connections.open_connection() else {
+                            warn!(addr = ?client_addr, "refused HTTP connection, the host has as many open connections as it allows");
+                            continue;
+                        };
+
+                        // Reloaded certificates apply from the next connection on
+                        let tls_acceptor = listening.tls_acceptor.read().await.clone();
+                        let listening = listening.clone();
+                        let closing = listening.closing.subscribe();
+                        let stopping = closing.clone();
+                        let no_delay = sse::NoDelay::default();
+                        tokio::spawn(async move {
+                            // Counted until the connection closes
+                            let _connection = connection;
+                            // The PROXY header precedes the TLS handshake
+                            let mut peer_ip = client_addr.ip();
+                            if listening.forwarding.proxy_protocol {
+                                let header = tokio::time::timeout(
+                                    PROXY_HEADER_TIMEOUT,
+                                    forwarded::read_proxy_header(&mut client),
+                                )
+                                .await;
+                                match header {
+                                    Ok(Ok(Some(source))) => peer_ip = source.ip(),
+                                    // LOCAL and UNKNOWN connections are attributed to the peer
+                                    Ok(Ok(None)) => {}
+                                    Ok(Err(e)) => {
+                                        warn!(addr = ?client_addr, err = ?e, "rejected connection with invalid PROXY protocol header");
+                                        return;
+                                    }
+                                    Err(_) => {
+                                        warn!(addr = ?client_addr, "timed out waiting for PROXY protocol header");
+                                        return;
+                                    }
+                                }
+                            }
+
+                            let socket = no_delay.clone();
+                            let serving = listening.clone();
+                            let service = hyper::service::service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+                                let listening = serving.clone();
+                                // Probes of the host are answered before routing
+                                let health = listening
+                                    .health_path
+                                    .as_deref()
+                                    .filter(|path| req.uri().path() == *path)
+                                    .map(|_| health::host_health(listening.handler.unready_workloads(), *stopping.borrow()));
+                                listening.forwarding.apply(peer_ip, req.headers_mut());
+                                h2::set_host(&mut req);
+                                let no_delay = no_delay.clone();
+                                async move {
+                                    if let Some(response) = health {
+                                        return Ok(response);
+                                    }
+                                    let tracked = listening.in_flight.track(&req);
+                                    let response = handle_http_request(&listening, req, &tracked).await?;
+                                    // Events are sent as soon as they are written
+                                    if sse::is_event_stream(response.headers()) {
+                                        no_delay.request();
+                                    }
+                                    Ok(response)
+                                }
+                            });
+
+                            let result = if let Some(acceptor) = tls_acceptor {
+                                // Handle HTTPS connection
+                                match acceptor.accept(sse::NoDelayIo::new(client, socket)).await {
+                                    Ok(tls_stream) => {
+                                        let h2 = tls_stream.get_ref().1.alpn_protocol() == Some(h2::ALPN_H2);
+                                        h2::serve_connection(TokioIo::new(tls_stream), h2, service, &listening.request_limits, closing).await
+                                    }
+                                    Err(e) => {
+                                        error!(addr = ?client_addr, err = ?e, "TLS handshake failed");
+                                        return;
+                                    }
+                                }
+                            } else {
+                                // Handle HTTP connection
+                                let h2 = h2::is_h2c(&client).await;
+                                let client = sse::NoDelayIo::new(client, socket);
+                                h2::serve_connection(TokioIo::new(client), h2, service, &listening.request_limits, closing).await
+                            };
+
+                            if let Err(e) = result {
+                                error!(addr = ?client_addr, err = ?e, "error serving HTTP client");
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!(err = ?e, "failed to accept HTTP connection");
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How long a connection may take to send its PROXY protocol header
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handle individual HTTP requests by looking up workload and invoking component
+async fn handle_http_request<T: Router>(
+    listening: &Listening<T>,
+    req: hyper::Request<hyper::body::Incoming>,
+    in_flight: &InFlight,
+) -> Result<hyper::Response<HyperOutgoingBody>, hyper::Error> {
+    let Listening {
+        handler,
+        metrics,
+        error_pages,
+        hooks,
+        access_log,
+        ..
+    } = listening;
+    let started = Instant::now();
+    let method = req.method().clone();
+    let trace = metrics::parse_traceparent(req.headers());
+    let server_span = trace::server_span(&method, req.uri().path(), trace.clone());
+    let access = access_log.begin(&req, started);
+    let (req, hooked) = hooks.on_request(req, started).await;
+    let mut route = None;
+    let mut workload_id = None;
+    let mut response = match req {
+        Ok(req) => {
+            let request_id = request_id(req.headers()).map(str::to_string);
+            let serve = serve_http_request(listening, req, in_flight, &mut route, &mut workload_id);
+            let mut response =
+                opentelemetry::trace::FutureExt::with_context(serve, server_span.clone()).await?;
+            error_pages.apply(&mut response, request_id.as_deref());
+            response
+        }
+        Err(response) => {
+            debug!(method = %method, "HTTP request answered by a hook");
+            response
+        }
+    };
+    hooks.on_response(hooked, &mut response).await;
+    trace::end_server_span(&server_span, &method, route.as_deref(), response.status());
+    let split_target = workload_id
+        .as_deref()
+        .and_then(|workload_id| handler.split_target(workload_id));
+    if let Some(access) = access {
+        let span = server_span.span();
+        let context = span.span_context();
+        let trace_id = context
+            .is_valid()
+            .then(|| context.trace_id())
+            .or_else(|| trace.as_ref().map(|parent| parent.trace_id()))
+            .map(|id| id.to_string());
+        response = access.finish(response, route.clone(), workload_id, trace_id);
+    }
+    metrics.record(
+        route.as_deref(),
+        split_target.as_deref(),
+        &method,
+        response.status(),
+        started.elapsed(),
+        trace,
+    );
+    Ok(response)
+}
+
+/// Routes a request to its workload and invokes it, setting `route` to the
+/// route the request matched and `routed` to the workload it was routed to.
+/// gRPC-web and Connect requests are translated if the server has it enabled.
+async fn serve_http_request<T: Router>(
+    listening: &Listening<T>,
+    req: hyper::Request<hyper::body::Incoming>,
+    in_flight: &InFlight,
+    route: &mut Option<String>,
+    routed: &mut Option<String>,
+) -> Result<hyper::Response<HyperOutgoingBody>, hyper::Error> {
+    let handler = &listening.handler;
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+
+    let routing = trace::child_span(&opentelemetry::Context::current(), "route");
+    let (workload_id, policy) = match handler.route_incoming_request(&req) {
+        Ok(routed) => routed,
+        Err(e) => {
+            if let Some(WorkloadNotReady(workload_id)) = e.downcast_ref() {
+                warn!(host = %workload_id, uri = %uri, "refused HTTP request, no workload serving the route is ready");
+                return Ok(host_response(503));
+            }
+            return Ok(match e.downcast_ref::<RouteHeld>() {
+                Some(RouteHeld(hold)) => {
+                    debug!(%hold, uri = %uri, "answering request to held route");
+                    hold.response()
+                }
+                None => host_response(400),
+            });
+        }
+    };
+    *route = policy.name.clone();
+    *routed = Some(workload_id.clone());
+    routing.span().end();
+    in_flight.routed(&workload_id, route.as_deref());
+
+    debug!(
+        method = %method,
+        uri = %uri,
+        host = %workload_id,
+        "HTTP request received"
+    );
+
+    // Password hashes are slow to check on purpose
+    if let Some(auth) = &policy.auth
+        && let Err(rejection) = tokio::task::block_in_place(|| auth.authorize(req.headers()))
+    {
+        warn!(host = %workload_id, reason = %rejection, "rejected unauthorized HTTP request");
+        let mut response = host_response(401);
+        if let Some(challenge) = auth.challenge() {
+            response.headers_mut().insert(
+                hyper::header::WWW_AUTHENTICATE,
+                hyper::header::HeaderValue::from_static(challenge),
+            );
+        }
+        return Ok(response);
+    }
+
+    if let Err(wait) = handler.check_rate_limit(&workload_id, req.headers()) {
+        debug!(host = %workload_id, uri = %uri, "refused HTTP request over the rate limit of its route");
+        let mut response = host_response(429);
+        response.headers_mut().insert(
+            hyper::header::RETRY_AFTER,
+            hyper::header::HeaderValue::from(rate_limit::retry_after(wait)),
+        );
+        return Ok(response);
+    }
+
+    let limits = policy.limits.clone().or(&listening.request_limits);
+    if let Some(status) = limits.check_head(req.headers()) {
+        warn!(host = %workload_id, uri = %uri, %status, "rejected HTTP request over the request limits of its route");
+        return Ok(host_response(status.as_u16()));
+    }
+    if let Some(response) = check_request_head(&req, policy.max_body_bytes) {
+        return Ok(response);
+    }
+    // Bodies are counted and timed as they are read, see `request_limits`
+    let (req, mut violations) = {
+        let (parts, body) = req.into_parts();
+        let (body, violations) = limits.guard(body);
+        (hyper::Request::from_parts(parts, body), violations)
+    };
+
+    // Cached responses are answered without invoking the component
+    let caching = policy.cache.clone().and_then(|route| {
+        let key = cache::CacheKey::of(&workload_id, &req)?;
+        Some((route, key, req.headers().clone()))
+    });
+    let mut req = req;
+    let mut stale = None;
+    if let Some((_, key, headers)) = &caching {
+        match listening.response_cache.lookup(key, headers) {
+            cache::Lookup::Fresh(response) => {
+                debug!(host = %workload_id, uri = %uri, "answering HTTP request from the cache");
+                return Ok(response);
+            }
+            cache::Lookup::Stale(entry, etag) => {
+                req.headers_mut().insert(hyper::header::IF_NONE_MATCH, etag);
+                stale = Some(entry);
+            }
+            cache::Lookup::Miss => {}
+        }
+    }
+    // The credentials the host checked are not passed on to the component
+    if let Some(auth) = &policy.auth {
+        auth.strip(req.headers_mut());
+    }
+
+    // NOTE(lxf): Separate HTTP / GRPC handling
+
+    // Look up workload handle for this host, with wildcard fallback
+    let (workload_handle, shadow_handle) = {
+        let handles = listening.workload_handles.read().await;
+        debug!(host = %workload_id, "looking up workload handle for host header");
+        (
+            handles.get(&workload_id).cloned(),
+            policy
+                .shadow
+                .as_ref()
+                .and_then(|id| handles.get(id).cloned().map(|handle| (id.clone(), handle))),
+        )
+    };
+
+    // Count the request against the connection quota of the namespace of its workload
+    let request_guard = match &workload_handle {
+        Some((handle, ..)) => match listening.connections.open_request(handle.namespace()) {
+            Some(request) => Some(request),
+            None => {
+                warn!(
+                    host = %workload_id,
+                    namespace = handle.namespace(),
+                    "refused HTTP request, the namespace handles as many requests as it allows"
+                );
+                return Ok(host_response(503));
+            }
+        },
+        None => None,
+    };
+
+    // Large uploads are read before the component is invoked
+    let req = match (policy.spool_max_bytes, &workload_handle) {
+        (Some(max), Some(_)) => {
+            let (mut parts, body) = req.into_parts();
+            let spooled = match violations
+                .or_violation(spool::spool(body, max, &listening.spool_dir))
+                .await
+            {
+                Ok(spooled) => spooled,
+                Err(violation) => return Ok(limit_response(violation, &workload_id)),
+            };
+            match spooled {
+                Ok(spooled) => {
+                    debug!(
+                        host = %workload_id,
+                        bytes = spooled.len(),
+                        on_disk = spooled.on_disk(),
+                        "spooled request body"
+                    );
+                    parts.headers.remove(hyper::header::TRANSFER_ENCODING);
+                    parts.headers.insert(
+                        hyper::header::CONTENT_LENGTH,
+                        hyper::header::HeaderValue::from(spooled.len()),
+                    );
+                    hyper::Request::from_parts(parts, spooled.into_body())
+                }
+                Err(SpoolError::TooLarge) => return Ok(host_response(413)),
+                Err(SpoolError::Body(e)) => {
+                    warn!(host = %workload_id, err = ?e, "failed to read request body");
+                    return Ok(host_response(400));
+                }
+                Err(SpoolError::Io(e)) => {
+                    error!(host = %workload_id, err = ?e, "failed to spool request body");
+                    return Ok(host_response(500));
+                }
+            }
+        }
+        _ => req.map(|body| body.boxed_unsync()),
+    };
+
+    // A WebSocket handshake hands the component the messages of the client as its body
+    let (req, websocket) = if workload_handle.is_some()
+        && policy.websocket
+        && websocket::is_upgrade(req.headers())
+    {
+        let mut req = req;
+        match websocket::Handshake::start(&mut req) {
+            Ok((handshake, messages)) => (req.map(|_| messages.boxed_unsync()), Some(handshake)),
+            Err(reason) => {
+                warn!(host = %workload_id, %reason, "rejected invalid WebSocket handshake");
+                return Ok(host_response(400));
+            }
+        }
+    } else {
+        (req, None)
+    };
+
+    // Only requests that reach a component are deduplicated
+    let idempotency_claim = match (&listening.idempotency, &workload_handle) {
+        (Some(store), Some((handle, _, component_id, _))) => req
+            .headers()
+            .get(idempotency::IDEMPOTENCY_KEY_HEADER)
+            .and_then(|value| idempotency::idempotency_key(value.as_bytes()))
+            .map(|key| {
+                (
+                    store.clone(),
+                    format!("{}/{component_id}", handle.id()),
+                    key.to_string(),
+                )
+            }),
+        _ => None,
+    };
+    if let Some((store, scope, key)) = &idempotency_claim {
+        match store.claim(scope, key).await {
+            Ok(None) => {}
+            Ok(Some(previous)) => {
+                debug!(host = %workload_id, key, ?previous, "answering repeated idempotency key");
+                return Ok(replayed_response(previous));
+            }
+            // Deduplication is best effort, so the request is still served
+            Err(e) => warn!(host = %workload_id, err = ?e, "failed to claim idempotency key"),
+        }
+    }
+
+    let request_timeout = deadline::request_timeout(policy.timeout, req.headers());
+
+    let (mut parts, body) = req.into_parts();
+    // Never let clients choose their own experiment assignment or route
+    parts.headers.remove(experiment::EXPERIMENT_HEADER);
+    parts.headers.remove(deadline::DEADLINE_HEADER);
+    if let Some(timeout) = request_timeout {
+        deadline::set_remaining(&mut parts.headers, timeout);
+    }
+    RouteMatch::apply(policy.matched.as_ref(), &mut parts.headers);
+    if let Some(assignment) = policy
+        .experiment
+        .as_ref()
+        .and_then(|e| hyper::header::HeaderValue::from_str(&e.assignment()).ok())
+    {
+        parts
+            .headers
+            .insert(experiment::EXPERIMENT_HEADER, assignment);
+    }
+
+    let grpc_web = listening
+        .grpc_web
+        .then(|| GrpcWebCall::detect(&parts))
+        .flatten();
+    let body = match grpc_web {
+        Some(call) => match violations
+            .or_violation(call.grpc_request(&mut parts, body.boxed_unsync()))
+            .await
+        {
+            Ok(Ok(body)) => body,
+            Ok(Err(e)) => {
+                warn!(host = %workload_id, err = ?e, "failed to read request body");
+                finish_idempotency_claim(idempotency_claim, None).await;
+                return Ok(host_response(400));
+            }
+            Err(violation) => {
+                finish_idempotency_claim(idempotency_claim, None).await;
+                return Ok(limit_response(violation, &workload_id));
+            }
+        },
+        None => body.boxed_unsync(),
+    };
+
+    // Mirroring and recording need the whole body, so it is only buffered when a
+    // shadow is running or the workload is recorded
+    let recording = listening.recorder.get(&workload_id);
+    let mut recorded = None;
+    let req = if (shadow_handle.is_some() || recording.is_some())
+        && workload_handle.is_some()
+        && websocket.is_none()
+    {
+        let body = match violations.or_violation(body.collect()).await {
+            Ok(Ok(body)) => BufferedBody {
+                trailers: body.trailers().cloned(),
+                data: body.to_bytes(),
+            },
+            Ok(Err(e)) => {
+                warn!(host = %workload_id, err = ?e, "failed to read request body");
+                finish_idempotency_claim(idempotency_claim, None).await;
+                return Ok(host_response(400));
+            }
+            Err(violation) => {
+                finish_idempotency_claim(idempotency_claim, None).await;
+                return Ok(limit_response(violation, &workload_id));
+            }
+        };
+        if let Some((shadow_id, (handle, instance_pre, component_id, middleware))) = shadow_handle {
+            let mirrored = copy_request(&parts, body.clone());
+            let shadow_timeout = handler.request_timeout(&shadow_id);
+            tokio::spawn(async move {
+                mirror_request(
+                    handle,
+                    instance_pre,
+                    component_id,
+                    middleware,
+                    mirrored,
+                    shadow_id,
+                    shadow_timeout,
+                )
+                .await
+            });
+        }
+        if let (Some(recording), Some((_, _, component_id, _))) = (recording, &workload_handle) {
+            recorded = recording.begin(component_id, &parts, &body.data);
+        }
+        hyper::Request::from_parts(parts, body.into_body())
+    } else {
+        hyper::Request::from_parts(parts, body)
+    };
+
+    let response = match (workload_handle, websocket) {
+        (Some((handle, instance_pre, component_id, middleware)), Some(handshake)) => {
+            if middleware.is_some() {
+                warn!(host = %workload_id, "refused WebSocket handshake, connections can't pass through middleware");
+                host_response(501)
+            } else {
+                accept_websocket(
+                    handshake,
+                    handle,
+                    instance_pre,
+                    component_id,
+                    req,
+                    request_timeout,
+                    in_flight.clone(),
+                    &workload_id,
+                    request_guard,
+                )
+                .await
+            }
+        }
+        (Some((handle, instance_pre, component_id, middleware)), None) => {
+            let deadline = request_timeout.map(|limit| tokio::time::Instant::now() + limit);
+            let invocation = invoke_component_handler(
+                handle,
+                instance_pre,
+                &component_id,
+                middleware,
+                req,
+                deadline,
+                Some(in_flight.clone()),
+            );
+            let invocation = async {
+                match request_timeout {
+                    Some(limit) => tokio::time::timeout(limit, invocation)
+                        .await
+                        .map_err(|_| limit),
+                    None => Ok(invocation.await),
+                }
+            };
+            // The component is cancelled if its request breaks the request limits
+            let result = match violations.or_violation(invocation).await {
+                Ok(Ok(result)) => result,
+                Ok(Err(limit)) => {
+                    warn!(host = %workload_id, timeout = ?limit, "component request timed out");
+                    if let Some(recorded) = recorded {
+                        recorded.finish(
+                            hyper::StatusCode::GATEWAY_TIMEOUT,
+                            Some(format!("timed out after {limit:?}")),
+                        );
+                    }
+                    finish_idempotency_claim(idempotency_claim, None).await;
+                    return Ok(host_response(504));
+                }
+                Err(violation) => {
+                    finish_idempotency_claim(idempotency_claim, None).await;
+                    return Ok(limit_response(violation, &workload_id));
+                }
+            };
+            let mut error = None;
+            let response = match result {
+                Ok(mut resp) => {
+                    // The response depends on the Accept header the route was chosen by
+                    if policy.negotiated {
+                        resp.headers_mut().append(
+                            hyper::header::VARY,
+                            hyper::header::HeaderValue::from_static("accept"),
+                        );
+                    }
+                    let resp = match caching {
+                        Some((route, key, headers)) => listening
+                            .response_cache
+                            .store(&route, key, &headers, stale, resp),
+                        None => resp,
+                    };
+                    let resp = resp.map(|body| policy.stream_limits.apply(&workload_id, body));
+                    let resp = match grpc_web {
+                        Some(call) => call.response(resp).await,
+                        None => resp,
+                    };
+                    sse::apply(resp, policy.sse_keepalive)
+                }
+                Err(e) => {
+                    error = recorded.is_some().then(|| format!("{e:#}"));
+                    invocation_error_response(e, &workload_id)
+                }
+            };
+            if let Some(recorded) = recorded {
+                recorded.finish(response.status(), error);
+            }
+            response
+        }
+        (None, _) => {
+            warn!(host = %workload_id, "No workload bound to host header or wildcard '*'");
+            host_response(404)
+        }
+    };
+
+    finish_idempotency_claim(idempotency_claim, Some(response.status())).await;
+
+    Ok(response)
+}
+
+/// Answers a request whose body broke its request limits, see
+/// [`request_limits`].
+fn limit_response(
+    violation: request_limits::Violation,
+    workload_id: &str,
+) -> hyper::Response<HyperOutgoingBody> {
+    warn!(host = %workload_id, ?violation, "rejected HTTP request whose body broke the request limits of its route");
+    let mut response = host_response(violation.status().as_u16());
+    // The rest of the body is not read
+    response.headers_mut().insert(
+        hyper::header::CONNECTION,
+        hyper::header::HeaderValue::from_static("close"),
+    );
+    response
+}
+
+/// Answers a request whose component could not be invoked, or failed.
+fn invocation_error_response(
+    e: anyhow::Error,
+    workload_id: &str,
+) -> hyper::Response<HyperOutgoingBody> {
+    if crate::engine::is_invocation_timeout(&e) {
+        warn!(err = ?e, host = %workload_id, "component invocation timed out");
+        host_response(504)
+    } else if crate::engine::autoscale::is_overloaded(&e) {
+        warn!(err = %e, host = %workload_id, "component is overloaded");
+        host_response(429)
+    } else if let Some(backoff) = crate::engine::crash_loop::crash_loop_backoff(&e) {
+        debug!(err = %e, host = %workload_id, "component is crash looping");
+        let mut response = host_response(503);
+        response.headers_mut().insert(
+            hyper::header::RETRY_AFTER,
+            hyper::header::HeaderValue::from(backoff.retry_after.as_secs_f64().ceil() as u64),
+        );
+        response
+    } else {
+        error!(err = ?e, host = %workload_id, "failed to invoke component");
+        host_response(500)
+    }
+}
+
+/// Invokes the component handling a WebSocket handshake, see [`websocket`].
+/// The component keeps running for as long as the connection is open,
+/// holding `request_guard` of the connection quota of its namespace, so only
+/// the handshake is held to `timeout`.
+#[allow(clippy::too_many_arguments)]
+async fn accept_websocket(
+    handshake: websocket::Handshake,
+    workload_handle: ResolvedWorkload,
+    instance_pre: InstancePre<Ctx>,
+    component_id: String,
+    req: hyper::Request<RequestBody>,
+    timeout: Option<Duration>,
+    in_flight: InFlight,
+    workload_id: &str,
+    request_guard: Option<ConnectionGuard>,
+) -> hyper::Response<HyperOutgoingBody> {
+    let (respond, responded) = tokio::sync::oneshot::channel();
+    let workload = workload_handle.clone();
+    let id = component_id.clone();
+    let session = tokio::spawn(async move {
+        let _request = request_guard;
+        workload_handle
+            .run_invocation(&component_id, async move {
+                let mut store = workload.new_store(&id).await?;
+                if let Some(request_id) = request_id(req.headers()) {
+                    store.data().log_context().set_request_id(request_id);
+                }
+                in_flight.running();
+                store
+                    .data()
+                    .set_invocation("wasi:http/incoming-handler#handle");
+                let result =
+                    stream_component_request(store.as_context_mut(), instance_pre, req, respond)
+                        .await;
+                metering::record_fuel(&mut store);
+                result
+            })
+            .await
+    });
+    let abort = session.abort_handle();
+    let handshake_response = async {
+        match responded.await {
+            Ok(result) => result,
+            // The component failed before it responded
+            Err(_) => match session.await {
+                Ok(Err(e)) => Err(e),
+                Ok(Ok(())) => Err(anyhow::anyhow!("component returned no response")),
+                Err(e) => Err(e.into()),
+            },
+        }
+    };
+    let result = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, handshake_response).await {
+            Ok(result) => result,
+            Err(_) => {
+                abort.abort();
+                warn!(host = %workload_id, timeout = ?limit, "component WebSocket handshake timed out");
+                return host_response(504);
+            }
+        },
+        None => handshake_response.await,
+    };
+    match result {
+        Ok(response) => handshake.respond(response, workload_id),
+        Err(e) => invocation_error_response(e, workload_id),
+    }
+}
+
+/// Records the outcome of a request that claimed an idempotency key. Keys of
+/// requests that timed out or failed with a server error are released so the
+/// client can retry them.
+async fn finish_idempotency_claim(
+    claim: Option<(Arc<dyn IdempotencyStore>, String, String)>,
+    status: Option<hyper::StatusCode>,
+) {
+    let Some((store, scope, key)) = claim else {
+        return;
+    };
+    let result = match status {
+        Some(status) if !status.is_server_error() => {
+            store.complete(&scope, &key, Some(status.as_u16())).await
+        }
+        _ => store.release(&scope, &key).await,
+    };
+    if let Err(e) = result {
+        warn!(scope, err = ?e, "failed to record idempotency key");
+    }
+}
+
+/// Builds the response to a request whose idempotency key was already used.
+fn replayed_response(previous: Invocation) -> hyper::Response<HyperOutgoingBody> {
+    let status = match previous {
+        Invocation::InProgress => 409,
+        Invocation::Completed { status } => status.unwrap_or(200),
+    };
+    hyper::Response::builder()
+        .status(status)
+        .header(idempotency::IDEMPOTENT_REPLAYED_HEADER, "true")
+        .body(HyperOutgoingBody::default())
+        .expect("failed to build replayed response")
+}
+
+/// Applies `Expect` and body size policy before the request body is read.
+///
+/// hyper only answers `Expect: 100-continue` with `100 Continue` once the body is
+/// first polled, so a request rejected here never has its body uploaded. Accepted
+/// requests receive `100 Continue` when the component starts reading the body.
+fn check_request_head<B: hyper::body::Body>(
+    req: &hyper::Request<B>,
+    max_body_bytes: Option<u64>,
+) -> Option<hyper::Response<HyperOutgoingBody>> {
+    let reject = |status: u16| Some(host_response(status));
+
+    if let Some(expect) = req.headers().get(hyper::header::EXPECT)
+        && !expect.as_bytes().eq_ignore_ascii_case(b"100-continue")
+    {
+        return reject(417);
+    }
+
+    let Some(max) = max_body_bytes else {
+        return None;
+    };
+    let content_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    match content_length {
+        Some(length) if length > max => reject(413),
+        Some(_) => None,
+        None if req.body().is_end_stream() => None,
+        // The size of a chunked body is unknown until it has been read
+        None => reject(411),
+    }
+}
+
+/// Body of a request handed to a component, either streamed or buffered for mirroring
+type RequestBody = http_body_util::combinators::UnsyncBoxBody<Bytes, hyper::Error>;
+
+/// A fully read request body, keeping its trailers so they reach the component
+#[derive(Clone, Debug, Default)]
+struct BufferedBody {
+    data: Bytes,
+    trailers: Option<hyper::HeaderMap>,
+}
+
+impl BufferedBody {
+    /// Replays the data followed by the trailers, if any
+    fn into_body(self) -> RequestBody {
+        let trailers = self.trailers;
+        http_body_util::Full::new(self.data)
+            .map_err(|never| match never {})
+            .with_trailers(async move { trailers.map(Ok) })
+            .boxed_unsync()
+    }
+}
+
+/// Builds a copy of a request with the given buffered body
+fn copy_request(
+    parts: &hyper::http::request::Parts,
+    body: BufferedBody,
+) -> hyper::Request<RequestBody> {
+    let mut req = hyper::Request::new(body.into_body());
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    req
+}
+
+/// Sends a mirrored request to a shadow workload and discards the response
+async fn mirror_request(
+    workload_handle: ResolvedWorkload,
+    instance_pre: InstancePre<Ctx>,
+    component_id: String,
+    middleware: Option<Middleware>,
+    req: hyper::Request<RequestBody>,
+    shadow_id: String,
+    timeout: Option<Duration>,
+) {
+    let deadline = timeout.map(|limit| tokio::time::Instant::now() + limit);
+    let invocation = async {
+        let response = invoke_component_handler(
+            workload_handle,
+            instance_pre,
+            &component_id,
+            middleware,
+            req,
+            deadline,
+            None,
+        )
+        .await?;
+        let status = response.status();
+        // Drain the body so the guest runs to completion
+        response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to read shadow response body: {e:?}"))?;
+        anyhow::Ok(status)
+    };
+    let result = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, invocation).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(shadow = %shadow_id, timeout = ?limit, "mirrored request timed out");
+                return;
+            }
+        },
+        None => invocation.await,
+    };
+    match result {
+        Ok(status) => debug!(shadow = %shadow_id, %status, "mirrored request completed"),
+        Err(e) => warn!(shadow = %shadow_id, err = ?e, "mirrored request failed"),
+    }
+}
+
+/// Invoke the component handler for the given workload, through its
+/// `middleware` if any, holding its outgoing requests to `deadline`. The
+/// request is marked as running in `in_flight` once it has an instance.
+async fn invoke_component_handler<B>(
+    workload_handle: ResolvedWorkload,
+    instance_pre: InstancePre<Ctx>,
+    component_id: &str,
+    middleware: Option<Middleware>,
+    req: hyper::Request<B>,
+    deadline: Option<tokio::time::Instant>,
+    in_flight: Option<InFlight>,
+) -> anyhow::Result<hyper::Response<HyperOutgoingBody>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    // The middleware is invoked instead, and passes the request on to the component
+    let (instance_pre, component_id, next) = match middleware {
+        Some(middleware) => {
+            let next = Next::new(
+                workload_handle.clone(),
+                instance_pre,
+                component_id,
+                req.headers(),
+                deadline,
+            );
+            (middleware.instance_pre, middleware.component_id, Some(next))
+        }
+        None => (instance_pre, component_id.to_string(), None),
+    };
+    let workload = workload_handle.clone();
+    let id = component_id.clone();
+    // Only requests traced by the server get spans, not e.g. mirrored requests
+    let parent = opentelemetry::Context::current();
+    let acquiring = parent
+        .has_active_span()
+        .then(|| trace::child_span(&parent, "acquire instance"));
+    workload_handle
+        .run_invocation(&component_id, async move {
+            // Create a new store for this request with plugin contexts, unless
+            // an instance was pre-initialized for it
+            let (mut store, instance) = match workload.take_pre_initialized(&id).await {
+                Some(ready) => (ready.store, Some(ready.instance)),
+                None => (workload.new_store(&id).await?, None),
+            };
+            let executing = acquiring.map(|acquiring| {
+                acquiring.span().end();
+                trace::child_span(&parent, "execute")
+            });
+            if let Some(executing) = &executing {
+                store
+                    .data_mut()
+                    .set_trace(executing.span().span_context().clone());
+            }
+            if let Some(request_id) = request_id(req.headers()) {
+                store.data().log_context().set_request_id(request_id);
+            }
+            if let Some(deadline) = deadline {
+                store.data_mut().set_deadline(deadline);
+            }
+            if let Some(next) = next {
+                store.data_mut().set_next(next);
+            }
+            if let Some(in_flight) = in_flight {
+                in_flight.running();
+            }
+
+            store
+                .data()
+                .set_invocation("wasi:http/incoming-handler#handle");
+            let response = match instance {
+                Some(instance) => {
+                    handle_instance_request(store.as_context_mut(), &instance, req).await
+                }
+                None => handle_component_request(store.as_context_mut(), instance_pre, req).await,
+            };
+            metering::record_fuel(&mut store);
+            #[cfg(feature = "instance-debug")]
+            crate::engine::debug::finish_dump(&mut store, &response);
+            if let Some(executing) = executing {
+                if let Err(e) = &response {
+                    executing
+                        .span()
+                        .set_status(opentelemetry::trace::Status::error(format!("{e:#}")));
+                }
+                executing.span().end();
+            }
+            response
+        })
+        .await
+}
+
+/// Longest `X-Request-Id` header attributed to the output of a component
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Returns the id of a request from its `X-Request-Id` header, if it is
+/// printable and not too long.
+fn request_id(headers: &hyper::HeaderMap) -> Option<&str> {
+    headers
+        .get("x-request-id")?
+        .to_str()
+        .ok()
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+}
+
+/// Like [`handle_component_request`], but sends the response on `respond` as
+/// soon as the component sets it, then waits for the component to return, for
+/// responses that stream for as long as the component runs.
+async fn stream_component_request<B>(
+    mut store: StoreContextMut<'_, Ctx>,
+    pre: InstancePre<Ctx>,
+    req: hyper::Request<B>,
+    respond: tokio::sync::oneshot::Sender<anyhow::Result<hyper::Response<HyperOutgoingBody>>>,
+) -> anyhow::Result<()>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    let (sender, mut receiver) = tokio::sync::oneshot::channel();
+    let req = store
+        .data_mut()
+        .new_incoming_request(request_scheme(req.uri()), req)?;
+    let out = store.data_mut().new_response_outparam(sender)?;
+    let pre = ProxyPre::new(pre).context("failed to instantiate proxy pre")?;
+    let proxy = pre.instantiate_async(&mut store).await?;
+
+    let call = proxy
+        .wasi_http_incoming_handler()
+        .call_handle(&mut store, req, out);
+    tokio::pin!(call);
+    let mut respond = Some(respond);
+    let result = loop {
+        tokio::select! {
+            result = &mut call => break result.map_err(crate::engine::explain_trap),
+            response = &mut receiver, if respond.is_some() => {
+                let response = match response {
+                    Ok(response) => response.map_err(Into::into),
+                    Err(_) => Err(anyhow::anyhow!(
+                        "oneshot channel closed but no response was sent"
+                    )),
+                };
+                if let Some(respond) = respond.take() {
+                    _ = respond.send(response);
+                }
+            }
+        }
+    };
+    // The response may be set just as the component returns
+    if let Some(respond) = respond
+        && result.is_ok()
+        && let Ok(response) = receiver.await
+    {
+        _ = respond.send(response.map_err(Into::into));
+    }
+    result
+}
+
+/// Returns the scheme of a request handed to a component, `http` if it has none.
+fn request_scheme(uri: &hyper::Uri) -> Scheme {
+    match uri.scheme() {
+        Some(scheme) if scheme == &hyper::http::uri::Scheme::HTTP => Scheme::Http,
+        Some(scheme) if scheme == &hyper::http::uri::Scheme::HTTPS => Scheme::Https,
+        Some(scheme) => Scheme::Other(scheme.as_str().to_string()),
+        // Fallback to HTTP if no scheme is present
+        None => Scheme::Http,
+    }
+}
+
+/// Handle a component request using WASI HTTP (copied from wash/crates/src/cli/dev.rs)
+pub async fn handle_component_request<'a, B>(
+    mut store: StoreContextMut<'a, Ctx>,
+    pre: InstancePre<Ctx>,
+    req: hyper::Request<B>,
+) -> anyhow::Result<hyper::Response<HyperOutgoingBody>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    #[cfg(feature = "wasip3")]
+    if crate::engine::exports_wasi_http_p3(pre.component()) {
+        let pre = wasmtime_wasi_http::p3::bindings::ServicePre::new(pre)
+            .context("failed to instantiate service pre")?;
+        let service = pre.instantiate_async(&mut store).await?;
+        return call_handler_p3(store, service, req).await;
+    }
+
+    let pre = ProxyPre::new(pre).context("failed to instantiate proxy pre")?;
+
+    // Run the http request itself by instantiating and calling the component
+    let proxy = pre.instantiate_async(&mut store).await?;
+    call_incoming_handler(store, proxy, req).await
+}
+
+/// Handles `req` with `instance`, an instance of a component created ahead
+/// of the request, see [`crate::engine::preinit`].
+async fn handle_instance_request<'a, B>(
+    mut store: StoreContextMut<'a, Ctx>,
+    instance: &Instance,
+    req: hyper::Request<B>,
+) -> anyhow::Result<hyper::Response<HyperOutgoingBody>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    #[cfg(feature = "wasip3")]
+    if let Ok(service) = wasmtime_wasi_http::p3::bindings::Service::new(&mut store, instance) {
+        return call_handler_p3(store, service, req).await;
+    }
+
+    let proxy = Proxy::new(&mut store, instance)
+        .context("component does not export wasi:http/incoming-handler")?;
+    call_incoming_handler(store, proxy, req).await
+}
+
+/// Calls the `wasi:http@0.3` handler of `service` with `req`. The component
+/// may write the response body after returning the response, so it is read
+/// while the instance still runs.
+#[cfg(feature = "wasip3")]
+async fn call_handler_p3<'a, B>(
+    store: StoreContextMut<'a, Ctx>,
+    service: wasmtime_wasi_http::p3::bindings::Service,
+    req: hyper::Request<B>,
+) -> anyhow::Result<hyper::Response<HyperOutgoingBody>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    use wasmtime_wasi_http::p3::{Request, bindings::http::types::ErrorCode};
+
+    let req = req.map(|body| body.map_err(|e| ErrorCode::InternalError(Some(e.to_string()))));
+    let (request, request_io) = Request::from_http(req);
+    let (parts, body) = store
+        .run_concurrent(async move |store| {
+            let response = service
+                .handle(store, request)
+                .await?
+                .map_err(|e| anyhow::anyhow!("component failed to handle the request: {e:?}"))?;
+            let response = store.with(|store| response.into_http(store, request_io))?;
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await.map_err(|e| {
+                anyhow::anyhow!("component failed to write the response body: {e:?}")
+            })?;
+            anyhow::Ok((parts, body))
+        })
+        .await
+        .and_then(|response| response)
+        .map_err(crate::engine::explain_trap)?;
+
+    Ok(hyper::Response::from_parts(
+        parts,
+        body.map_err(|never| match never {}).boxed(),
+    ))
+}
+
+/// Calls the `wasi:http/incoming-handler` export of `proxy` with `req`.
+async fn call_incoming_handler<'a, B>(
+    mut store: StoreContextMut<'a, Ctx>,
+    proxy: Proxy,
+    req: hyper::Request<B>,
+) -> anyhow::Result<hyper::Response<HyperOutgoingBody>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + 'static,
+{
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    let scheme = request_scheme(req.uri());
+    let req = store.data_mut().new_incoming_request(scheme, req)?;
+    let out = store.data_mut().new_response_outparam(sender)?;
+
+    proxy
+        .wasi_http_incoming_handler()
+        .call_handle(&mut store, req, out)
+        .await
+        .map_err(crate::engine::explain_trap)?;
+
+    match receiver.await {
+        // If the client calls `response-outparam::set` then one of these
+        // methods will be called.
+        Ok(Ok(resp)) => Ok(resp),
+        Ok(Err(e)) => Err(e.into()),
+
+        // Otherwise the `sender` will get dropped along with the `Store`
+        // meaning that the oneshot will get disconnected
+        Err(e) => {
+            error!(err = ?e, "error receiving http response");
+            Err(anyhow::anyhow!(
+                "oneshot channel closed but no response was sent"
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_config_from_string_map() {
+        let config = HashMap::from([
+            ("host".to_string(), "example.com".to_string()),
+            ("path".to_string(), "/api".to_string()),
+            ("methods".to_string(), "get, post".to_string()),
+            ("timeout_ms".to_string(), "1500".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("example.com")
+                .with_path("/api")
+                .with_method("GET")
+                .with_method("post")
+                .with_timeout(Duration::from_millis(1500))
+        );
+        assert_eq!(route.to_config(), config_with_normalized_methods(config));
+    }
+
+    fn config_with_normalized_methods(
+        mut config: HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        config.insert("methods".to_string(), "GET,POST".to_string());
+        config
+    }
+
+    #[test]
+    fn test_route_config_websocket() {
+        let route = HttpRouteConfig::new("chat.example.com").with_websocket();
+        assert_eq!(
+            route
+                .to_config()
+                .get(WEBSOCKET_CONFIG_KEY)
+                .map(String::as_str),
+            Some("true")
+        );
+        assert_eq!(
+            HttpRouteConfig::try_from(&route.to_config()).unwrap(),
+            route
+        );
+
+        let config = HashMap::from([
+            ("host".to_string(), "chat.example.com".to_string()),
+            ("websocket".to_string(), "yes".to_string()),
+        ]);
+        assert!(HttpRouteConfig::try_from(&config).is_err());
+    }
+
+    #[test]
+    fn test_route_config_rate_limit() {
+        let config = HashMap::from([
+            ("host".to_string(), "api.example.com".to_string()),
+            ("rate_limit_rps".to_string(), "20".to_string()),
+            ("rate_limit_burst".to_string(), "40".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route.rate_limit,
+            Some(RouteRateLimit::new(20).with_burst(40))
+        );
+        assert_eq!(
+            HttpRouteConfig::try_from(&route.to_config()).unwrap(),
+            route
+        );
+        assert!(
+            HttpRouteConfig::new("api.example.com")
+                .with_rate_limit(RouteRateLimit::new(10).with_header_key("bad header"))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_route_config_validation() {
+        assert!(HttpRouteConfig::try_from(&HashMap::new()).is_err());
+        assert!(HttpRouteConfig::new("").validate().is_err());
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_path("api")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_method("NOT A METHOD")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_timeout(Duration::ZERO)
+                .validate()
+                .is_err()
+        );
+        let bad_timeout = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("timeout_ms".to_string(), "soon".to_string()),
+        ]);
+        assert!(HttpRouteConfig::try_from(&bad_timeout).is_err());
+        assert!(HttpRouteConfig::new("foo").validate().is_ok());
+    }
+
+    #[test]
+    fn test_route_config_hosts() {
+        let config = HashMap::from([(
+            "host".to_string(),
+            "api.example.com, *.example.com".to_string(),
+        )]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("api.example.com").with_host("*.example.com")
+        );
+        assert_eq!(
+            route.hosts().collect::<Vec<_>>(),
+            ["api.example.com", "*.example.com"]
+        );
+
+        for host in [
+            "api.example.com,",
+            "*",
+            "*.",
+            "a.*.example.com",
+            "*example.com",
+        ] {
+            assert!(HttpRouteConfig::new(host).validate().is_err(), "{host}");
+        }
+
+        // Routes sharing one of their hosts conflict
+        let api = HttpRouteConfig::new("api.example.com,api.internal");
+        assert!(api.conflicts_with(&HttpRouteConfig::new("api.internal")));
+        assert!(!api.conflicts_with(&HttpRouteConfig::new("*.example.com")));
+        assert!(
+            RoutePin::new("a.example.com,b.example.com", "default/api")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_host_routes() {
+        let entry = |host: &str, workload_id: &str| RouteEntry {
+            route: HttpRouteConfig::new(host),
+            workload_id: workload_id.to_string(),
+            workload_name: format!("default/{workload_id}"),
+        };
+        let routes = HashMap::from([
+            (
+                "api.example.com".to_string(),
+                vec![entry("api.example.com", "api")],
+            ),
+            (
+                "*.example.com".to_string(),
+                vec![entry("*.example.com", "sites")],
+            ),
+        ]);
+        let workloads = |host| {
+            host_routes(&routes, host)
+                .flatten()
+                .map(|entry| entry.workload_id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(workloads("api.example.com"), ["api", "sites"]);
+        assert_eq!(workloads("www.example.com"), ["sites"]);
+        // Wildcards match a single label
+        assert!(workloads("a.b.example.com").is_empty());
+        assert!(workloads("example.com").is_empty());
+
+        assert!(host_matches("*.example.com", "www.example.com"));
+        assert!(!host_matches("*.example.com", ".example.com"));
+        assert!(host_matches("example.com", "example.com"));
+    }
+
+    #[test]
+    fn test_route_config_matches() {
+        let route = HttpRouteConfig::new("foo")
+            .with_path("/api/")
+            .with_method("GET");
+        assert!(route.matches(&hyper::Method::GET, "/api"));
+        assert!(route.matches(&hyper::Method::GET, "/api/users"));
+        assert!(!route.matches(&hyper::Method::GET, "/apiary"));
+        assert!(!route.matches(&hyper::Method::POST, "/api"));
+
+        let any = HttpRouteConfig::new("foo");
+        assert!(any.matches(&hyper::Method::DELETE, "/anything"));
+    }
+
+    #[test]
+    fn test_route_config_params() {
+        let route = HttpRouteConfig::new("foo").with_path("/users/{id}/posts/{post-id}");
+        assert!(route.validate().is_ok());
+        assert_eq!(
+            route.match_path("/users/42/posts/7/comments"),
+            Some((
+                17,
+                vec![
+                    ("id".to_string(), "42".to_string()),
+                    ("post-id".to_string(), "7".to_string())
+                ]
+            ))
+        );
+        assert_eq!(route.match_path("/users//posts/7"), None);
+        assert_eq!(route.match_path("/users/42/comments/7"), None);
+        assert_eq!(route.match_path("/users/42"), None);
+
+        for path in [
+            "/users/{}",
+            "/users/{id}/{id}",
+            "/users/x{id}",
+            "/users/{a b}",
+        ] {
+            assert!(
+                HttpRouteConfig::new("foo")
+                    .with_path(path)
+                    .validate()
+                    .is_err(),
+                "{path}"
+            );
+        }
+
+        // Parameter names don't tell routes apart, literals take precedence
+        let by_id = HttpRouteConfig::new("foo").with_path("/users/{id}");
+        assert!(by_id.conflicts_with(&HttpRouteConfig::new("foo").with_path("/users/{name}")));
+        let me = HttpRouteConfig::new("foo").with_path("/users/me");
+        assert!(!by_id.conflicts_with(&me));
+        assert!(me.specificity() > by_id.specificity());
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(ROUTE_PARAMS_HEADER, "id=spoofed".parse().unwrap());
+        let route_match = RouteMatch {
+            workload_name: "default/users".to_string(),
+            route: "/users/{id}".to_string(),
+            prefix: "/users/a&b".to_string(),
+            params: vec![("id".to_string(), "a&b".to_string())],
+        };
+        RouteMatch::apply(Some(&route_match), &mut headers);
+        assert_eq!(headers[ROUTE_WORKLOAD_HEADER], "default/users");
+        assert_eq!(headers[ROUTE_HEADER], "/users/{id}");
+        assert_eq!(headers[ROUTE_PREFIX_HEADER], "/users/a&b");
+        assert_eq!(headers[ROUTE_PARAMS_HEADER], "id=a%26b");
+        RouteMatch::apply(None, &mut headers);
+        assert!(headers.is_empty());
+    }
 
-        let protocol = if self.tls_acceptor.is_some() {
-            "HTTPS"
-        } else {
-            "HTTP"
+    #[test]
+    fn test_route_entry_namespace_conflicts() {
+        let entry = RouteEntry {
+            route: HttpRouteConfig::new("foo").with_path("/api"),
+            workload_id: "a".to_string(),
+            workload_name: "team-a/api".to_string(),
         };
-        debug!(addr = ?addr, protocol = protocol, "HTTP server starting");
-        Ok(())
+        assert_eq!(entry.namespace(), "team-a");
+
+        let users = HttpRouteConfig::new("foo").with_path("/users");
+        assert!(entry.conflict("b", "team-a/users", &users).is_none());
+        assert!(
+            entry
+                .conflict(
+                    "b",
+                    "team-a/api2",
+                    &HttpRouteConfig::new("foo").with_path("/api")
+                )
+                .is_some()
+        );
+        // Another namespace can't serve any path of the host
+        let conflict = entry.conflict("c", "team-b/users", &users).unwrap();
+        assert!(conflict.contains("namespace team-a"), "{conflict}");
+        // A new version takes the route over
+        assert!(entry.conflict("d", "team-a/api", &entry.route).is_none());
     }
 
-    async fn stop(&self) -> anyhow::Result<()> {
-        info!(addr = ?self.addr, "HTTP server stopping");
-        let mut shutdown_guard = self.shutdown_tx.write().await;
-        if let Some(tx) = shutdown_guard.take() {
-            let _ = tx.send(()).await;
+    #[test]
+    fn test_route_entry_wildcard_namespace_conflicts() {
+        let wildcard = RouteEntry {
+            route: HttpRouteConfig::new("*.example.com"),
+            workload_id: "a".to_string(),
+            workload_name: "team-a/web".to_string(),
+        };
+        // Exact hosts win over the wildcard, so another namespace can't take
+        // the requests of one of its hosts
+        let api = HttpRouteConfig::new("api.example.com").with_path("/api");
+        let conflict = wildcard.conflict("b", "team-b/api", &api).unwrap();
+        assert!(conflict.contains("namespace team-a"), "{conflict}");
+        assert!(
+            wildcard
+                .conflict("b", "team-b/api", &api.clone().with_host("other.com"))
+                .is_some()
+        );
+        assert!(wildcard.conflict("b", "team-a/api", &api).is_none());
+        for host in ["example.com", "a.b.example.com", "*.other.com"] {
+            let route = HttpRouteConfig::new(host);
+            assert!(
+                wildcard.conflict("b", "team-b/api", &route).is_none(),
+                "{host}"
+            );
         }
-        Ok(())
+
+        // Nor register a wildcard over the hosts of another namespace
+        let exact = RouteEntry {
+            route: api,
+            workload_id: "c".to_string(),
+            workload_name: "team-b/api".to_string(),
+        };
+        let conflict = exact.conflict("d", "team-a/web", &wildcard.route).unwrap();
+        assert!(conflict.contains("namespace team-b"), "{conflict}");
+        assert!(exact.conflict("d", "team-b/web", &wildcard.route).is_none());
     }
 
-    async fn on_workload_resolved(
-        &self,
-        resolved_handle: &ResolvedWorkload,
-        component_id: &str,
-    ) -> anyhow::Result<()> {
-        self.router
-            .on_workload_resolved(resolved_handle, component_id)
-            .await?;
-        let instance_pre = resolved_handle.instantiate_pre(component_id).await?;
+    #[test]
+    fn test_route_config_conflicts() {
+        let api = HttpRouteConfig::new("foo").with_path("/api");
+        assert!(api.conflicts_with(&HttpRouteConfig::new("foo").with_path("/api/")));
+        // A route accepting some methods takes precedence over the catch-all
+        let get_api = HttpRouteConfig::new("foo")
+            .with_path("/api")
+            .with_method("GET");
+        assert!(!api.conflicts_with(&get_api));
+        assert!(get_api.specificity() > api.specificity());
+        assert!(!api.conflicts_with(&HttpRouteConfig::new("bar").with_path("/api")));
+        assert!(!api.conflicts_with(&HttpRouteConfig::new("foo").with_path("/api/users")));
+        assert!(!api.conflicts_with(&HttpRouteConfig::new("foo")));
+        assert!(
+            HttpRouteConfig::new("foo").conflicts_with(&HttpRouteConfig::new("foo").with_path("/"))
+        );
+
+        let get = HttpRouteConfig::new("foo").with_method("GET");
+        assert!(!get.conflicts_with(&HttpRouteConfig::new("foo").with_method("POST")));
+        assert!(get.conflicts_with(&HttpRouteConfig::new("foo").with_method("get")));
+        assert_eq!(get.to_string(), "GET foo/");
+        assert_eq!(api.to_string(), "* foo/api/");
+    }
 
-        self.workload_handles.write().await.insert(
-            resolved_handle.id().to_string(),
+    #[test]
+    fn test_route_config_headers() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("path".to_string(), "/api".to_string()),
             (
-                resolved_handle.clone(),
-                instance_pre,
-                component_id.to_string(),
+                "headers".to_string(),
+                "X-Api-Version: 2, x-beta".to_string(),
             ),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("foo")
+                .with_path("/api")
+                .with_header("x-api-version", "2")
+                .with_header_present("x-beta")
         );
+        assert_eq!(route.to_config()["headers"], "x-api-version:2,x-beta");
+        assert_eq!(route.to_string(), "* foo/api/ [x-api-version:2] [x-beta]");
 
-        Ok(())
+        let request = |headers: &[(&str, &str)]| {
+            let mut req = hyper::Request::get("/api/users");
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            req.body(()).unwrap()
+        };
+        assert!(route.matches_request(&request(&[("x-api-version", "2"), ("x-beta", "")])));
+        assert!(!route.matches_request(&request(&[("x-api-version", "1"), ("x-beta", "1")])));
+        assert!(!route.matches_request(&request(&[("x-api-version", "2")])));
+
+        for headers in ["x-a:1,x-a:2", "bad header:1", "x-a:", "x-a:\n"] {
+            let config = HashMap::from([
+                ("host".to_string(), "foo".to_string()),
+                ("headers".to_string(), headers.to_string()),
+            ]);
+            assert!(HttpRouteConfig::try_from(&config).is_err(), "{headers}");
+        }
     }
 
-    async fn on_workload_unbind(&self, workload_id: &str) -> anyhow::Result<()> {
-        self.router.on_workload_unbind(workload_id).await?;
+    #[test]
+    fn test_route_config_precedence() {
+        let api = HttpRouteConfig::new("foo").with_path("/api");
+        let get = api.clone().with_method("GET");
+        let post = api.clone().with_method("POST");
+        let get_head = api.clone().with_method("GET").with_method("HEAD");
+        let v2 = get.clone().with_header("x-api-version", "2");
+        let v3 = get.clone().with_header("x-api-version", "3");
+        let beta = get.clone().with_header_present("x-api-version");
 
-        self.workload_handles.write().await.remove(workload_id);
+        // Longer paths first, then fewer methods, then more header predicates
+        let users = HttpRouteConfig::new("foo").with_path("/api/users");
+        let mut routes = vec![&api, &get_head, &v2, &users, &get];
+        routes.sort_by_key(|route| std::cmp::Reverse(route.specificity()));
+        let order: Vec<String> = routes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            order,
+            [
+                "* foo/api/users/",
+                "GET foo/api/ [x-api-version:2]",
+                "GET foo/api/",
+                "GET,HEAD foo/api/",
+                "* foo/api/",
+            ]
+        );
 
-        Ok(())
+        // GETs and POSTs of the same path may go to different workloads
+        assert!(!get.conflicts_with(&post));
+        assert!(!get.conflicts_with(&get_head));
+        assert!(!v2.conflicts_with(&v3));
+        assert!(v2.conflicts_with(&beta));
+        assert!(get.conflicts_with(&api.clone().with_method("get")));
     }
 
-    fn outgoing_request(
-        &self,
-        workload_id: &str,
-        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        config: wasmtime_wasi_http::types::OutgoingRequestConfig,
-    ) -> wasmtime_wasi_http::HttpResult<wasmtime_wasi_http::types::HostFutureIncomingResponse> {
-        self.router
-            .allow_outgoing_request(workload_id, &request, &config)
-            .map_err(|e| {
-                wasmtime_wasi_http::HttpError::trap(anyhow::anyhow!("request not allowed: {}", e))
-            })?;
+    #[test]
+    fn test_route_config_produces() {
+        let api = HttpRouteConfig::new("foo")
+            .with_path("/users")
+            .with_produces("application/json");
+        let frontend = HttpRouteConfig::new("foo")
+            .with_path("/users")
+            .with_produces("Text/HTML");
+        assert_eq!(frontend.produces, vec!["text/html"]);
+        assert!(!api.conflicts_with(&frontend));
+        assert!(!api.conflicts_with(&HttpRouteConfig::new("foo").with_path("/users")));
+        assert!(api.conflicts_with(&api.clone().with_produces("text/html")));
+        assert!(api.with_produces("text/*").validate().is_err());
 
-        // NOTE(lxf): Bring wasi-http code if needed
-        // Separate HTTP / GRPC handling
-        Ok(wasmtime_wasi_http::types::default_send_request(
-            request, config,
-        ))
+        let mut config = frontend.to_config();
+        assert_eq!(config["produces"], "text/html");
+        config.insert(
+            "produces".to_string(),
+            "text/html; charset=utf-8, application/xhtml+xml".to_string(),
+        );
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(route.produces, vec!["text/html", "application/xhtml+xml"]);
     }
-}
 
-/// HTTP server implementation that routes to workload components
-async fn run_http_server<T: Router>(
-    listener: TcpListener,
-    handler: Arc<T>,
-    workload_handles: WorkloadHandles,
-    shutdown_rx: &mut mpsc::Receiver<()>,
-    tls_acceptor: Option<TlsAcceptor>,
-) -> anyhow::Result<()> {
-    loop {
-        tokio::select! {
-            // Handle shutdown signal
-            _ = shutdown_rx.recv() => {
-                info!("HTTP server received shutdown signal");
-                break;
-            }
-            // Accept new connections
-            result = listener.accept() => {
-                match result {
-                    Ok((client, client_addr)) => {
-                        debug!(addr = ?client_addr, "new HTTP client connection");
+    #[test]
+    fn test_route_config_auth() {
+        let route = HttpRouteConfig::new("foo").with_auth(RouteAuth::api_key(["s3cr3t"]));
+        let config = route.to_config();
+        assert!(!config.values().any(|v| v.contains("s3cr3t")));
+        assert_eq!(HttpRouteConfig::try_from(&config).unwrap(), route);
+    }
 
-                        let handles_clone = workload_handles.clone();
-                        let tls_acceptor_clone = tls_acceptor.clone();
-                        let handler_clone = handler.clone();
-                        tokio::spawn(async move {
-                            let service = hyper::service::service_fn(move |req| {
-                                let handles = handles_clone.clone();
-                                let handler = handler_clone.clone();
-                                async move {
-                                    handle_http_request(handler, req, handles).await
-                                }
-                            });
+    #[test]
+    fn test_route_config_shadow() {
+        let route = HttpRouteConfig::new("foo").with_shadow("default", "api-canary");
+        assert_eq!(route.shadow.as_deref(), Some("default/api-canary"));
+        assert_eq!(
+            HttpRouteConfig::try_from(&route.to_config()).unwrap(),
+            route
+        );
 
-                            let result = if let Some(acceptor) = tls_acceptor_clone {
-                                // Handle HTTPS connection
-                                match acceptor.accept(client).await {
-                                    Ok(tls_stream) => {
-                                        http1::Builder::new()
-                                            .keep_alive(true)
-                                            .serve_connection(TokioIo::new(tls_stream), service)
-                                            .await
-                                    }
-                                    Err(e) => {
-                                        error!(addr = ?client_addr, err = ?e, "TLS handshake failed");
-                                        return;
-                                    }
-                                }
-                            } else {
-                                // Handle HTTP connection
-                                http1::Builder::new()
-                                    .keep_alive(true)
-                                    .serve_connection(TokioIo::new(client), service)
-                                    .await
-                            };
+        let mut config = route.to_config();
+        config.insert(SHADOW_CONFIG_KEY.to_string(), "api-canary".to_string());
+        assert!(HttpRouteConfig::try_from(&config).is_err());
+    }
 
-                            if let Err(e) = result {
-                                error!(addr = ?client_addr, err = ?e, "error serving HTTP client");
-                            }
-                        });
-                    }
-                    Err(e) => {
-                        error!(err = ?e, "failed to accept HTTP connection");
-                    }
-                }
-            }
-        }
+    #[test]
+    fn test_route_config_experiment_conflicts() {
+        let control = HttpRouteConfig::new("foo")
+            .with_path("/checkout")
+            .with_experiment(RouteExperiment::new("checkout", "control"));
+        let beta = HttpRouteConfig::new("foo")
+            .with_path("/checkout")
+            .with_experiment(RouteExperiment::new("checkout", "beta").with_weight(10));
+        assert!(!control.conflicts_with(&beta));
+        assert!(control.conflicts_with(&control.clone()));
+        assert!(control.conflicts_with(&HttpRouteConfig::new("foo").with_path("/checkout")));
+        assert_eq!(HttpRouteConfig::try_from(&beta.to_config()).unwrap(), beta);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_route_config_weight_conflicts() {
+        let stable = HttpRouteConfig::new("foo")
+            .with_path("/checkout")
+            .with_weight(90);
+        let canary = HttpRouteConfig::new("foo")
+            .with_path("/checkout")
+            .with_weight(10);
+        assert!(!stable.conflicts_with(&canary));
+        assert!(stable.conflicts_with(&HttpRouteConfig::new("foo").with_path("/checkout")));
+        assert_eq!(
+            HttpRouteConfig::try_from(&canary.to_config()).unwrap(),
+            canary
+        );
+        assert_eq!(canary.to_config()["weight"], "10");
+        assert!(
+            canary
+                .with_experiment(RouteExperiment::new("checkout", "beta"))
+                .validate()
+                .is_err()
+        );
+    }
 
-/// Handle individual HTTP requests by looking up workload and invoking component
-async fn handle_http_request<T: Router>(
-    handler: Arc<T>,
-    req: hyper::Request<hyper::body::Incoming>,
-    workload_handles: WorkloadHandles,
-) -> Result<hyper::Response<HyperOutgoingBody>, hyper::Error> {
-    let method = req.method().clone();
-    let uri = req.uri().clone();
+    #[tokio::test]
+    async fn test_route_weights() {
+        let router = DynamicRouter::default();
+        let entry = |workload_name: &str, weight: u32| RouteEntry {
+            route: HttpRouteConfig::new("foo")
+                .with_path("/checkout")
+                .with_weight(weight),
+            workload_id: workload_name.replace('/', "-"),
+            workload_name: workload_name.to_string(),
+        };
+        router.host_to_workload.write().unwrap().insert(
+            "foo".to_string(),
+            vec![entry("default/api", 90), entry("default/api-canary", 10)],
+        );
+        assert_eq!(
+            router.split_target("default-api-canary").as_deref(),
+            Some("default/api-canary")
+        );
 
-    let Ok(workload_id) = handler.route_incoming_request(&req) else {
-        return Ok(hyper::Response::builder()
-            .status(400)
-            .body(HyperOutgoingBody::default())
-            .expect("failed to build 400 response"));
-    };
+        router
+            .set_route_weight(RouteWeight::new("default/api-canary", 50))
+            .await
+            .unwrap();
+        assert!(
+            router
+                .set_route_weight(RouteWeight::new("api", 50))
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            router.route_weights().await,
+            vec![RouteWeight::new("default/api-canary", 50)]
+        );
+        let weights: Vec<Option<u32>> = router
+            .routing_table()
+            .await
+            .iter()
+            .map(|entry| entry.route.weight)
+            .collect();
+        assert_eq!(weights, [Some(90), Some(50)]);
+    }
 
-    debug!(
-        method = %method,
-        uri = %uri,
-        host = %workload_id,
-        "HTTP request received"
-    );
+    #[test]
+    fn test_rollout_split() {
+        let router = DynamicRouter::default();
+        let entry = |workload_id: &str| RouteEntry {
+            route: HttpRouteConfig::new("foo"),
+            workload_id: workload_id.to_string(),
+            workload_name: "default/api".to_string(),
+        };
+        // The new version is matched first
+        let (new, old) = (entry("api-v2"), entry("api-v1"));
+        let candidates = [&new, &old];
+        let target = |candidates: &[&RouteEntry]| {
+            router
+                .rollout_target(candidates, candidates[0])
+                .workload_id
+                .clone()
+        };
+        let split = |instances, replacement_instances| {
+            Some(RolloutSplit {
+                instances,
+                replacement_instances,
+            })
+        };
 
-    // NOTE(lxf): Separate HTTP / GRPC handling
+        // Without a rolling update, the new version takes over right away
+        assert_eq!(target(&candidates), "api-v2");
 
-    // Look up workload handle for this host, with wildcard fallback
-    let workload_handle = {
-        let handles = workload_handles.read().await;
-        debug!(host = %workload_id, "looking up workload handle for host header");
-        handles.get(&workload_id).cloned()
-    };
+        router.set_rollout("api-v1", split(4, 0));
+        assert_eq!(target(&candidates), "api-v1");
+        router.set_rollout("api-v1", split(3, 1));
+        let picks = (0..1000)
+            .filter(|_| target(&candidates) == "api-v2")
+            .count();
+        assert!((100..400).contains(&picks), "{picks}");
+        router.set_rollout("api-v1", split(0, 4));
+        assert_eq!(target(&candidates), "api-v2");
+        // The old version serves alone while the new one isn't ready
+        assert_eq!(target(&[&old]), "api-v1");
 
-    let response = match workload_handle {
-        Some((handle, instance_pre, component_id)) => {
-            match invoke_component_handler(handle, instance_pre, &component_id, req).await {
-                Ok(resp) => resp,
-                Err(e) => {
-                    error!(err = ?e, host = %workload_id, "failed to invoke component");
-                    hyper::Response::builder()
-                        .status(500)
-                        .body(HyperOutgoingBody::default())
-                        // TODO: Add in the actual error message in the response body
-                        // .body(HyperOutgoingBody::new(e.to_string()))
-                        .expect("failed to build 500 response")
-                }
-            }
-        }
-        None => {
-            warn!(host = %workload_id, "No workload bound to host header or wildcard '*'");
-            hyper::Response::builder()
-                .status(404)
-                .body(HyperOutgoingBody::default())
-                .expect("failed to build 404 response")
-        }
-    };
+        router.set_rollout("api-v1", split(4, 0));
+        router.set_rollout("api-v1", None);
+        assert_eq!(target(&candidates), "api-v2");
+    }
 
-    Ok(response)
-}
+    #[test]
+    fn test_route_policies_wait_for_route_changes() {
+        let router = Arc::new(DynamicRouter::default());
+        let mut routes = router.host_to_workload.write().unwrap();
+        let reader = std::thread::spawn({
+            let router = router.clone();
+            move || router.request_timeout("default-api")
+        });
+        // The policies are read once the change is done, not skipped while
+        // it is in progress
+        std::thread::sleep(Duration::from_millis(50));
+        routes.insert(
+            "foo".to_string(),
+            vec![RouteEntry {
+                route: HttpRouteConfig::new("foo").with_timeout(Duration::from_secs(5)),
+                workload_id: "default-api".to_string(),
+                workload_name: "default/api".to_string(),
+            }],
+        );
+        drop(routes);
+        assert_eq!(reader.join().unwrap(), Some(Duration::from_secs(5)));
+    }
 
-/// Invoke the component handler for the given workload
-async fn invoke_component_handler(
-    workload_handle: ResolvedWorkload,
-    instance_pre: InstancePre<Ctx>,
-    component_id: &str,
-    req: hyper::Request<hyper::body::Incoming>,
-) -> anyhow::Result<hyper::Response<HyperOutgoingBody>> {
-    // Create a new store for this request with plugin contexts
-    let mut store = workload_handle.new_store(component_id).await?;
+    #[test]
+    fn test_route_policy() {
+        let entry = |workload_name: &str, route: HttpRouteConfig| RouteEntry {
+            route,
+            workload_id: workload_name.replace('/', "-"),
+            workload_name: workload_name.to_string(),
+        };
+        let api = entry(
+            "default/api",
+            HttpRouteConfig::new("foo")
+                .with_path("/users/{id}")
+                .with_auth(RouteAuth::api_key(["s3cr3t"]))
+                .with_timeout(Duration::from_secs(5))
+                .with_max_body_bytes(1024)
+                .with_shadow("default", "api-next"),
+        );
+        let routes = HashMap::from([(
+            "foo".to_string(),
+            vec![
+                api.clone(),
+                entry("default/api-next", HttpRouteConfig::new("foo")),
+            ],
+        )]);
 
-    handle_component_request(store.as_context_mut(), instance_pre, req).await
-}
+        let policy = route_policy(&routes, &api, "/users/42/posts");
+        assert_eq!(policy.name.as_deref(), Some("foo/users/{id}/"));
+        assert_eq!(policy.auth, Some(RouteAuth::api_key(["s3cr3t"])));
+        assert_eq!(policy.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(policy.max_body_bytes, Some(1024));
+        assert_eq!(policy.shadow.as_deref(), Some("default-api-next"));
+        assert_eq!(
+            policy.matched,
+            Some(RouteMatch {
+                workload_name: "default/api".to_string(),
+                route: "/users/{id}".to_string(),
+                prefix: "/users/42".to_string(),
+                params: vec![("id".to_string(), "42".to_string())],
+            })
+        );
+        assert!(!policy.negotiated);
+    }
 
-/// Handle a component request using WASI HTTP (copied from wash/crates/src/cli/dev.rs)
-pub async fn handle_component_request<'a>(
-    mut store: StoreContextMut<'a, Ctx>,
-    pre: InstancePre<Ctx>,
-    req: hyper::Request<hyper::body::Incoming>,
-) -> anyhow::Result<hyper::Response<HyperOutgoingBody>> {
-    let (sender, receiver) = tokio::sync::oneshot::channel();
-    let scheme = match req.uri().scheme() {
-        Some(scheme) if scheme == &hyper::http::uri::Scheme::HTTP => Scheme::Http,
-        Some(scheme) if scheme == &hyper::http::uri::Scheme::HTTPS => Scheme::Https,
-        Some(scheme) => Scheme::Other(scheme.as_str().to_string()),
-        // Fallback to HTTP if no scheme is present
-        None => Scheme::Http,
-    };
-    let req = store.data_mut().new_incoming_request(scheme, req)?;
-    let out = store.data_mut().new_response_outparam(sender)?;
-    let pre = ProxyPre::new(pre).context("failed to instantiate proxy pre")?;
+    #[test]
+    fn test_route_config_stream_limits() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("stream_idle_timeout_ms".to_string(), "500".to_string()),
+            ("stream_max_duration_ms".to_string(), "60000".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route.stream_limits(),
+            StreamLimits {
+                idle_timeout: Some(Duration::from_millis(500)),
+                max_duration: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(route.to_config(), config);
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_stream_limits(Some(Duration::ZERO), None)
+                .validate()
+                .is_err()
+        );
+    }
 
-    // Run the http request itself by instantiating and calling the component
-    let proxy = pre.instantiate_async(&mut store).await?;
+    #[test]
+    fn test_route_config_spooling() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("spool_max_bytes".to_string(), "104857600".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("foo").with_body_spooling(100 << 20)
+        );
+        assert_eq!(route.to_config(), config);
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_body_spooling(0)
+                .validate()
+                .is_err()
+        );
+    }
 
-    proxy
-        .wasi_http_incoming_handler()
-        .call_handle(&mut store, req, out)
-        .await?;
+    #[test]
+    fn test_route_config_request_limits() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("max_body_bytes".to_string(), "1024".to_string()),
+            ("max_headers".to_string(), "20".to_string()),
+            ("max_header_bytes".to_string(), "4096".to_string()),
+            ("body_read_timeout_ms".to_string(), "5000".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("foo")
+                .with_max_body_bytes(1024)
+                .with_header_limits(20, 4096)
+                .with_body_read_timeout(Duration::from_secs(5))
+        );
+        assert_eq!(route.to_config(), config);
+        assert_eq!(
+            route.request_limits(),
+            RequestLimits::default()
+                .with_max_body_bytes(1024)
+                .with_header_limits(20, 4096)
+                .with_body_read_timeout(Duration::from_secs(5))
+        );
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_header_limits(0, 4096)
+                .validate()
+                .is_err()
+        );
+    }
 
-    match receiver.await {
-        // If the client calls `response-outparam::set` then one of these
-        // methods will be called.
-        Ok(Ok(resp)) => Ok(resp),
-        Ok(Err(e)) => Err(e.into()),
+    #[test]
+    fn test_route_config_sse_keepalive() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("sse_keepalive_ms".to_string(), "15000".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        assert_eq!(
+            route,
+            HttpRouteConfig::new("foo").with_sse_keepalive(Duration::from_secs(15))
+        );
+        assert_eq!(route.to_config(), config);
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_sse_keepalive(Duration::ZERO)
+                .validate()
+                .is_err()
+        );
+    }
 
-        // Otherwise the `sender` will get dropped along with the `Store`
-        // meaning that the oneshot will get disconnected
-        Err(e) => {
-            error!(err = ?e, "error receiving http response");
-            Err(anyhow::anyhow!(
-                "oneshot channel closed but no response was sent"
-            ))
+    #[tokio::test]
+    async fn test_buffered_body_keeps_trailers() {
+        let mut trailers = hyper::HeaderMap::new();
+        trailers.insert("grpc-status", hyper::header::HeaderValue::from_static("0"));
+        let body = BufferedBody {
+            data: Bytes::from_static(b"payload"),
+            trailers: Some(trailers.clone()),
+        };
+
+        let parts = hyper::Request::builder()
+            .method("POST")
+            .uri("/rpc")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let mirrored = copy_request(&parts, body.clone()).into_body();
+        for body in [body.into_body(), mirrored] {
+            let collected = body.collect().await.unwrap();
+            assert_eq!(collected.trailers(), Some(&trailers));
+            assert_eq!(collected.to_bytes(), Bytes::from_static(b"payload"));
         }
+
+        let collected = BufferedBody::default().into_body().collect().await.unwrap();
+        assert!(collected.trailers().is_none());
     }
-}
 
-/// Load TLS configuration from certificate and key files
-/// Extracted from wash dev command for reuse in HTTP server plugin
-async fn load_tls_config(
-    cert_path: &Path,
-    key_path: &Path,
-    ca_path: Option<&Path>,
-) -> anyhow::Result<ServerConfig> {
-    // Load certificate chain
-    let cert_data = tokio::fs::read(cert_path)
-        .await
-        .with_context(|| format!("Failed to read certificate file: {}", cert_path.display()))?;
-    let mut cert_reader = std::io::Cursor::new(cert_data);
-    let cert_chain: Vec<CertificateDer<'static>> = certs(&mut cert_reader)
-        .collect::<Result<Vec<_>, _>>()
-        .with_context(|| format!("Failed to parse certificate file: {}", cert_path.display()))?;
-
-    ensure!(
-        !cert_chain.is_empty(),
-        "No certificates found in file: {}",
-        cert_path.display()
-    );
+    #[test]
+    fn test_check_request_head() {
+        let request = |headers: &[(&str, &str)], body: &'static [u8]| {
+            let mut builder = hyper::Request::builder().uri("/upload");
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            builder
+                .body(http_body_util::Full::new(Bytes::from_static(body)))
+                .unwrap()
+        };
+        let status = |response: Option<hyper::Response<HyperOutgoingBody>>| {
+            response.map(|r| r.status().as_u16())
+        };
 
-    // Load private key
-    let key_data = tokio::fs::read(key_path)
-        .await
-        .with_context(|| format!("Failed to read private key file: {}", key_path.display()))?;
-    let mut key_reader = std::io::Cursor::new(key_data);
-    let key = private_key(&mut key_reader)
-        .with_context(|| format!("Failed to parse private key file: {}", key_path.display()))?
-        .ok_or_else(|| anyhow::anyhow!("No private key found in file: {}", key_path.display()))?;
-
-    // Create rustls server config
-    let config = ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(cert_chain, key)
-        .with_context(|| "Failed to create TLS configuration")?;
-
-    // If CA is provided, configure client certificate verification
-    if let Some(ca_path) = ca_path {
-        let ca_data = tokio::fs::read(ca_path)
+        let continue_req = request(
+            &[("expect", "100-continue"), ("content-length", "4")],
+            b"data",
+        );
+        assert_eq!(status(check_request_head(&continue_req, None)), None);
+        assert_eq!(status(check_request_head(&continue_req, Some(4))), None);
+        assert_eq!(
+            status(check_request_head(&continue_req, Some(3))),
+            Some(413)
+        );
+
+        let bad_expect = request(&[("expect", "something-else")], b"");
+        assert_eq!(status(check_request_head(&bad_expect, None)), Some(417));
+
+        let chunked = request(&[("expect", "100-continue")], b"data");
+        assert_eq!(status(check_request_head(&chunked, Some(1024))), Some(411));
+        assert_eq!(
+            status(check_request_head(&request(&[], b""), Some(1024))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_request_id() {
+        let headers = |id: &str| {
+            let mut headers = hyper::HeaderMap::new();
+            headers.insert("x-request-id", id.parse().unwrap());
+            headers
+        };
+        assert_eq!(request_id(&headers("abc-123")), Some("abc-123"));
+        assert_eq!(request_id(&headers("")), None);
+        assert_eq!(request_id(&headers(&"a".repeat(129))), None);
+        assert_eq!(request_id(&hyper::HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_route_pins() {
+        let router = DynamicRouter::default();
+        router
+            .pin_route(RoutePin::new("foo", "default/api"))
+            .await
+            .unwrap();
+        router
+            .pin_route(RoutePin::new("foo", "default/users").with_path("/users/{id}"))
             .await
-            .with_context(|| format!("Failed to read CA file: {}", ca_path.display()))?;
-        let mut ca_reader = std::io::Cursor::new(ca_data);
-        let ca_certs: Vec<CertificateDer<'static>> = certs(&mut ca_reader)
-            .collect::<Result<Vec<_>, _>>()
-            .with_context(|| format!("Failed to parse CA file: {}", ca_path.display()))?;
+            .unwrap();
+        // Replaces the pin of the same path, up to the names of parameters
+        router
+            .pin_route(RoutePin::new("foo", "default/users-v2").with_path("/users/{user}/"))
+            .await
+            .unwrap();
+        assert!(router.pin_route(RoutePin::new("foo", "api")).await.is_err());
+        assert!(
+            router
+                .pin_route(RoutePin::new("foo", "default/api").with_path("users"))
+                .await
+                .is_err()
+        );
 
-        ensure!(
-            !ca_certs.is_empty(),
-            "No CA certificates found in file: {}",
-            ca_path.display()
+        let pins = router.pins().await;
+        assert_eq!(
+            pins,
+            vec![
+                RoutePin::new("foo", "default/users-v2").with_path("/users/{user}/"),
+                RoutePin::new("foo", "default/api"),
+            ]
+        );
+        let json = serde_json::to_value(&pins[1]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"host": "foo", "workload_name": "default/api"})
+        );
+
+        assert!(router.unpin_route("foo", Some("/users/{id}")).await);
+        assert!(!router.unpin_route("foo", Some("/users/{id}")).await);
+        assert!(!router.unpin_route("bar", None).await);
+        assert_eq!(
+            router.pins().await,
+            vec![RoutePin::new("foo", "default/api")]
         );
+    }
+
+    #[tokio::test]
+    async fn test_route_holds() {
+        let router = DynamicRouter::default();
+        router
+            .hold_route(RouteHold::new("foo").with_retry_after_secs(120))
+            .await
+            .unwrap();
+        router
+            .hold_route(
+                RouteHold::new("foo")
+                    .with_path("/users/{id}")
+                    .with_mode(RouteHoldMode::Disabled),
+            )
+            .await
+            .unwrap();
+        assert!(router.hold_route(RouteHold::new("a,b")).await.is_err());
+
+        // The most specific hold applies
+        let held = router.held_route("foo", "/users/1/posts").unwrap();
+        assert_eq!(held.mode, RouteHoldMode::Disabled);
+        assert_eq!(held.response().status(), 404);
+        let held = router.held_route("foo", "/orders").unwrap();
+        let response = held.response();
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()[hyper::header::RETRY_AFTER], "120");
+        assert!(router.held_route("bar", "/orders").is_none());
+
+        let json = serde_json::to_value(&router.holds().await[1]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"host": "foo", "mode": "maintenance", "retry_after_secs": 120})
+        );
+
+        assert!(router.release_route("foo", None).await);
+        assert!(!router.release_route("foo", None).await);
+        assert!(router.held_route("foo", "/orders").is_none());
+        assert_eq!(router.holds().await.len(), 1);
+    }
+
+    #[test]
+    fn test_invocation_error_response() {
+        let backoff = anyhow::Error::new(crate::engine::crash_loop::CrashLoopBackoff {
+            component_id: "api".to_string(),
+            failures: 5,
+            retry_after: Duration::from_millis(1500),
+        });
+        let response = invocation_error_response(backoff, "foo");
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()[hyper::header::RETRY_AFTER], "2");
+        let response = invocation_error_response(anyhow::anyhow!("guest trapped"), "foo");
+        assert_eq!(response.status(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_listeners() {
+        let free_addr = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        };
+        let (first, second) = (free_addr(), free_addr());
+        let server = HttpServer::new(DynamicRouter::default(), first);
+        assert!(server.add_listener(second).await.is_err());
+
+        server.start().await.unwrap();
+        server.add_listener(second).await.unwrap();
+        assert!(server.add_listener(second).await.is_err());
+        assert_eq!(server.listeners().await, vec![first, second]);
+        tokio::net::TcpStream::connect(second).await.unwrap();
 
-        // Note: Client certificate verification configuration would go here
-        // For now, we'll keep it simple without client cert verification
-        debug!("CA certificate loaded, but client certificate verification not yet implemented");
+        assert!(server.remove_listener(second).await);
+        assert!(!server.remove_listener(second).await);
+        assert_eq!(server.listeners().await, vec![first]);
+        let mut refused = false;
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(second).await.is_err() {
+                refused = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(refused, "removed listener still accepts connections");
+        tokio::net::TcpStream::connect(first).await.unwrap();
+
+        // Without TLS there is no certificate to reload
+        assert!(server.reload_tls(None).await.is_err());
+        assert!(
+            server
+                .reload_tls(Some(TlsFiles::new("missing.pem", "missing.key")))
+                .await
+                .is_err()
+        );
+        assert_eq!(server.tls_files().await, None);
+
+        // Open connections are closed once they have no request in flight
+        let mut idle = tokio::net::TcpStream::connect(first).await.unwrap();
+        server.stop().await.unwrap();
+        assert!(server.listeners().await.is_empty());
+        let closed = tokio::time::timeout(
+            Duration::from_secs(5),
+            tokio::io::AsyncReadExt::read(&mut idle, &mut [0; 1]),
+        )
+        .await;
+        assert!(
+            matches!(closed, Ok(Ok(0) | Err(_))),
+            "idle connection stayed open"
+        );
+    }
+
+    #[test]
+    fn test_route_config_health_check() {
+        let config = HashMap::from([
+            ("host".to_string(), "foo".to_string()),
+            ("health_export".to_string(), "health".to_string()),
+            ("health_interval_ms".to_string(), "500".to_string()),
+        ]);
+        let route = HttpRouteConfig::try_from(&config).unwrap();
+        let check = RouteHealthCheck::export("health").with_interval(Duration::from_millis(500));
+        assert_eq!(route.health_check, Some(check.clone()));
+        let route = HttpRouteConfig::new("foo").with_health_check(check);
+        assert_eq!(
+            HttpRouteConfig::try_from(&route.to_config()).unwrap(),
+            route
+        );
+        assert!(
+            HttpRouteConfig::new("foo")
+                .with_health_check(RouteHealthCheck::path("/ready").with_threshold(0))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_path() {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = HttpServer::new(DynamicRouter::default(), addr).with_health_path("/healthz");
+        server.start().await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\nHost: anything\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with(r#"{"status":"ready"}"#), "{response}");
+        server.stop().await.unwrap();
     }
 
-    Ok(config)
+    #[test]
+    fn test_route_config_serde() {
+        let route = HttpRouteConfig::new("foo").with_path("/api");
+        let json = serde_json::to_value(&route).unwrap();
+        assert_eq!(json, serde_json::json!({"host": "foo", "path": "/api"}));
+        let parsed: HttpRouteConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, route);
+    }
 }
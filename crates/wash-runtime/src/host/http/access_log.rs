@@ -0,0 +1,519 @@
+//! Access logs of the HTTP server.
+//!
+//! Components only log what they choose to through `wasi:logging`, so the
+//! server can log every request it answers itself, whatever component
+//! served it. An [`AccessLogEntry`] is written to the sinks of the
+//! [`AccessLog`] of the server once the response has been sent, or the
+//! client went away before, and records
+//!
+//! - the method and path the client requested and its IP address,
+//! - the route the request matched and the workload serving it,
+//! - the status of the response and the bytes of its body sent,
+//! - the time from receiving the request to sending the last byte, and
+//! - the trace the request is part of.
+//!
+//! The host has sinks writing entries as JSON lines to stdout or to a file
+//! rotated by size, and one emitting them as `tracing` events with the
+//! [`ACCESS_LOG_TARGET`] target. Embedders can add their own by implementing
+//! [`AccessLogSink`].
+//!
+//! In the configuration file of `wash host`:
+//!
+//! ```toml
+//! [http.access_log]
+//! stdout = true
+//! file = "/var/log/wash/access.log"
+//! file_max_bytes = 104857600     # optional, defaults to 100 MiB
+//! file_max_files = 5             # optional, defaults to 5
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use super::forwarded::REAL_IP_HEADER;
+
+/// The `tracing` target of the entries [`TracingSink`] emits
+pub const ACCESS_LOG_TARGET: &str = "wash::access_log";
+
+/// Size a log file is rotated at if not configured
+pub const DEFAULT_FILE_MAX_BYTES: u64 = 100 * 1024 * 1024;
+/// Rotated log files kept if not configured
+pub const DEFAULT_FILE_MAX_FILES: u32 = 5;
+
+/// The access log entry of a request, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccessLogEntry {
+    /// When the request was received, in RFC 3339
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    /// The IP address of the client, as resolved from trusted forwarding
+    /// headers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// The route the request matched, unset if it matched none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// The workload the request was routed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload_id: Option<String>,
+    pub status: u16,
+    /// Time from receiving the request to sending the end of the response
+    pub latency_ms: f64,
+    /// Bytes of the response body sent
+    pub bytes_sent: u64,
+    /// Whether the whole response was sent, `false` if the client went away
+    /// or the body failed before its end
+    pub completed: bool,
+    /// The trace the request is part of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+/// Somewhere access log entries are written to.
+pub trait AccessLogSink: Send + Sync + 'static {
+    /// Writes `entry`. Called once the response of a request has been sent,
+    /// so it should not block for long.
+    fn write(&self, entry: &AccessLogEntry);
+}
+
+/// Writes entries to stdout as JSON lines.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl AccessLogSink for StdoutSink {
+    fn write(&self, entry: &AccessLogEntry) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        // Logging is best effort, a closed stdout doesn't fail requests
+        _ = writeln!(std::io::stdout().lock(), "{line}");
+    }
+}
+
+/// Emits entries as `tracing` events with the [`ACCESS_LOG_TARGET`] target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+impl AccessLogSink for TracingSink {
+    fn write(&self, entry: &AccessLogEntry) {
+        info!(
+            target: ACCESS_LOG_TARGET,
+            method = %entry.method,
+            path = %entry.path,
+            client_ip = entry.client_ip.as_deref(),
+            route = entry.route.as_deref(),
+            workload_id = entry.workload_id.as_deref(),
+            status = entry.status,
+            latency_ms = entry.latency_ms,
+            bytes_sent = entry.bytes_sent,
+            completed = entry.completed,
+            trace_id = entry.trace_id.as_deref(),
+            "HTTP request"
+        );
+    }
+}
+
+/// Appends entries to a file as JSON lines. Once the file reaches its size
+/// limit it is renamed to `<file>.1`, earlier rotated files are shifted to
+/// `<file>.2` and on, the oldest beyond the limit is removed, and a new file
+/// is started.
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: Mutex<(File, u64)>,
+}
+
+impl FileSink {
+    /// Appends entries to `path`, rotating it at `max_bytes` and keeping
+    /// `max_files` rotated files.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be opened.
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, max_files: u32) -> anyhow::Result<Self> {
+        let path = path.into();
+        let file = open(&path)?;
+        let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file: Mutex::new((file, len)),
+        })
+    }
+
+    /// Shifts the rotated files and starts a new file.
+    fn rotate(&self) -> anyhow::Result<File> {
+        let rotated = |n: u32| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path).ok();
+        } else {
+            std::fs::remove_file(rotated(self.max_files)).ok();
+            for n in (1..self.max_files).rev() {
+                std::fs::rename(rotated(n), rotated(n + 1)).ok();
+            }
+            std::fs::rename(&self.path, rotated(1))
+                .with_context(|| format!("failed to rotate {}", self.path.display()))?;
+        }
+        open(&self.path)
+    }
+}
+
+fn open(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open access log {}", path.display()))
+}
+
+impl AccessLogSink for FileSink {
+    fn write(&self, entry: &AccessLogEntry) {
+        let Ok(mut line) = serde_json::to_vec(entry) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if file.1 > 0 && file.1 + line.len() as u64 > self.max_bytes {
+            match self.rotate() {
+                Ok(new) => *file = (new, 0),
+                Err(e) => warn!(err = ?e, "failed to rotate access log"),
+            }
+        }
+        match file.0.write_all(&line) {
+            Ok(()) => file.1 += line.len() as u64,
+            Err(e) => warn!(path = %self.path.display(), err = ?e, "failed to write access log"),
+        }
+    }
+}
+
+/// The sinks the host writes access logs to, in the configuration file of
+/// `wash host`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    /// Write entries to stdout as JSON lines
+    pub stdout: bool,
+    /// Emit entries as `tracing` events with the `wash::access_log` target
+    pub tracing: bool,
+    /// File to append entries to as JSON lines
+    pub file: Option<PathBuf>,
+    /// Size in bytes at which `file` is rotated
+    pub file_max_bytes: u64,
+    /// Rotated files of `file` kept
+    pub file_max_files: u32,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            stdout: false,
+            tracing: false,
+            file: None,
+            file_max_bytes: DEFAULT_FILE_MAX_BYTES,
+            file_max_files: DEFAULT_FILE_MAX_FILES,
+        }
+    }
+}
+
+/// The sinks access log entries are written to, see the
+/// [module docs](self). Requests aren't logged without sinks.
+#[derive(Clone, Default)]
+pub struct AccessLog(Vec<Arc<dyn AccessLogSink>>);
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AccessLog").field(&self.0.len()).finish()
+    }
+}
+
+impl AccessLog {
+    /// Creates the access log `config` describes.
+    ///
+    /// # Errors
+    /// Returns an error if the log file can't be opened.
+    pub fn from_config(config: &AccessLogConfig) -> anyhow::Result<Self> {
+        let mut log = Self::default();
+        if config.stdout {
+            log = log.with_sink(StdoutSink);
+        }
+        if config.tracing {
+            log = log.with_sink(TracingSink);
+        }
+        if let Some(path) = &config.file {
+            log = log.with_sink(FileSink::new(
+                path,
+                config.file_max_bytes,
+                config.file_max_files,
+            )?);
+        }
+        Ok(log)
+    }
+
+    /// Also writes entries to `sink`.
+    pub fn with_sink(mut self, sink: impl AccessLogSink) -> Self {
+        self.0.push(Arc::new(sink));
+        self
+    }
+
+    /// Returns whether there are no sinks.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Starts the entry of a request received at `received`, unless there
+    /// are no sinks.
+    pub(super) fn begin<B>(
+        self: &Arc<Self>,
+        req: &hyper::Request<B>,
+        received: Instant,
+    ) -> Option<PendingEntry> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(PendingEntry {
+            log: self.clone(),
+            received,
+            entry: AccessLogEntry {
+                timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                method: req.method().to_string(),
+                path: req.uri().path().to_string(),
+                client_ip: req
+                    .headers()
+                    .get(REAL_IP_HEADER)
+                    .and_then(|ip| ip.to_str().ok())
+                    .map(str::to_string),
+                route: None,
+                workload_id: None,
+                status: 0,
+                latency_ms: 0.0,
+                bytes_sent: 0,
+                completed: false,
+                trace_id: None,
+            },
+        })
+    }
+
+    fn write(&self, entry: &AccessLogEntry) {
+        for sink in &self.0 {
+            sink.write(entry);
+        }
+    }
+}
+
+/// The entry of a request whose response hasn't been sent yet.
+pub(super) struct PendingEntry {
+    log: Arc<AccessLog>,
+    received: Instant,
+    entry: AccessLogEntry,
+}
+
+impl PendingEntry {
+    /// Records how the request was served, and has the entry written once
+    /// the body of `response` has been sent or dropped.
+    pub(super) fn finish(
+        mut self,
+        response: hyper::Response<HyperOutgoingBody>,
+        route: Option<String>,
+        workload_id: Option<String>,
+        trace_id: Option<String>,
+    ) -> hyper::Response<HyperOutgoingBody> {
+        self.entry.route = route;
+        self.entry.workload_id = workload_id;
+        self.entry.trace_id = trace_id;
+        self.entry.status = response.status().as_u16();
+        response.map(|inner| {
+            http_body_util::BodyExt::boxed(LoggedBody {
+                inner,
+                pending: Some(self),
+            })
+        })
+    }
+}
+
+/// A response body writing the access log entry of its request once it
+/// ended or was dropped.
+struct LoggedBody {
+    inner: HyperOutgoingBody,
+    pending: Option<PendingEntry>,
+}
+
+impl LoggedBody {
+    fn write(&mut self, completed: bool) {
+        if let Some(PendingEntry {
+            log,
+            received,
+            mut entry,
+        }) = self.pending.take()
+        {
+            entry.completed = completed;
+            entry.latency_ms = received.elapsed().as_secs_f64() * 1000.0;
+            log.write(&entry);
+        }
+    }
+}
+
+impl Body for LoggedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = Pin::new(&mut self.inner).poll_frame(cx);
+        match &frame {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let (Some(data), Some(pending)) = (frame.data_ref(), self.pending.as_mut()) {
+                    pending.entry.bytes_sent += data.len() as u64;
+                }
+            }
+            Poll::Ready(Some(Err(_))) => self.write(false),
+            Poll::Ready(None) => self.write(true),
+            Poll::Pending => {}
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        // Empty bodies may be dropped without being polled
+        let completed = self.inner.is_end_stream();
+        self.write(completed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt as _;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<AccessLogEntry>>>);
+
+    impl AccessLogSink for Collect {
+        fn write(&self, entry: &AccessLogEntry) {
+            self.0.lock().unwrap().push(entry.clone());
+        }
+    }
+
+    fn response(body: &'static str) -> hyper::Response<HyperOutgoingBody> {
+        hyper::Response::new(
+            http_body_util::Full::new(Bytes::from_static(body.as_bytes()))
+                .map_err(|never| match never {})
+                .boxed(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_entry_written_after_body() {
+        let sink = Collect::default();
+        let log = Arc::new(AccessLog::default().with_sink(sink.clone()));
+        let req = hyper::Request::post("/orders?id=1")
+            .header(REAL_IP_HEADER, "203.0.113.9")
+            .body(())
+            .unwrap();
+        let pending = log.begin(&req, Instant::now()).unwrap();
+        let response = pending.finish(
+            response("hello"),
+            Some("api.example.com/orders/".to_string()),
+            Some("w1".to_string()),
+            None,
+        );
+        assert!(sink.0.lock().unwrap().is_empty());
+
+        response.into_body().collect().await.unwrap();
+        let entries = sink.0.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.method, "POST");
+        assert_eq!(entry.path, "/orders");
+        assert_eq!(entry.client_ip.as_deref(), Some("203.0.113.9"));
+        assert_eq!(entry.workload_id.as_deref(), Some("w1"));
+        assert_eq!(entry.status, 200);
+        assert_eq!(entry.bytes_sent, 5);
+        assert!(entry.completed);
+    }
+
+    #[test]
+    fn test_dropped_body_is_logged_incomplete() {
+        let sink = Collect::default();
+        let log = Arc::new(AccessLog::default().with_sink(sink.clone()));
+        let req = hyper::Request::get("/").body(()).unwrap();
+        let response =
+            log.begin(&req, Instant::now())
+                .unwrap()
+                .finish(response("hello"), None, None, None);
+        drop(response);
+        assert!(!sink.0.lock().unwrap()[0].completed);
+
+        // Nothing to log to
+        assert!(
+            Arc::new(AccessLog::default())
+                .begin(&req, Instant::now())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_file_sink_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let sink = FileSink::new(&path, 300, 1).unwrap();
+        let entry = AccessLogEntry {
+            timestamp: "2025-01-01T00:00:00.000Z".to_string(),
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            client_ip: None,
+            route: None,
+            workload_id: None,
+            status: 200,
+            latency_ms: 1.5,
+            bytes_sent: 0,
+            completed: true,
+            trace_id: None,
+        };
+        for _ in 0..5 {
+            sink.write(&entry);
+        }
+        let current = std::fs::read_to_string(&path).unwrap();
+        let rotated = std::fs::read_to_string(dir.path().join("access.log.1")).unwrap();
+        assert!(current.len() <= 300 && rotated.len() <= 300);
+        assert!(!dir.path().join("access.log.2").exists());
+        let line: serde_json::Value =
+            serde_json::from_str(current.lines().next().unwrap()).unwrap();
+        assert_eq!(line["status"], 200);
+    }
+}
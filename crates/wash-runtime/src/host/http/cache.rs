@@ -0,0 +1,773 @@
+//! Response caching of routes.
+//!
+//! A route may let the host cache the responses its component marks as
+//! cacheable, so identical requests are answered without invoking the
+//! component. In the string config map of `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! cache: true
+//! cache_max_entry_bytes: 1048576   # optional, defaults to 1 MiB
+//! cache_max_ttl_secs: 300          # optional, caps how long responses are fresh
+//! ```
+//!
+//! Only `GET` requests without an `Authorization` or `Upgrade` header, and without
+//! `no-cache` or `no-store` in their `Cache-Control`, are answered from and
+//! stored in the cache. A response is stored if its status is cacheable by
+//! default, e.g. `200 OK` or `404 Not Found`, its `Cache-Control` has a
+//! `s-maxage` or `max-age` and neither `no-store`, `no-cache` nor `private`,
+//! it sets no cookie and doesn't `Vary: *`, and its body has no trailers and
+//! fits in the `cache_max_entry_bytes` of the route. It is fresh for its
+//! `s-maxage`, or else `max-age`, up to the `cache_max_ttl_secs` of the
+//! route. The request headers its `Vary` names are stored with it, and only
+//! requests with the same values are answered with it.
+//!
+//! Fresh responses are answered with an `Age` header, or with `304 Not
+//! Modified` to requests whose `If-None-Match` matches their `ETag`. A stale
+//! response with an `ETag` is revalidated: the request is sent to the
+//! component with an `If-None-Match` header, and if it answers `304 Not
+//! Modified`, the stored response is fresh again and answers the request.
+//!
+//! The responses cached by a host take up to
+//! [`DEFAULT_CACHE_BYTES`], see
+//! [`HttpServer::with_response_cache_size`](super::HttpServer::with_response_cache_size),
+//! evicting the least recently used. The responses of a workload are dropped
+//! when it stops, and operators drop the responses of a host and path prefix
+//! with the `route.invalidate` API command, see [`ResponseCache::invalidate`].
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, ready};
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use http_body_util::{BodyExt as _, Full};
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::{
+    AGE, AUTHORIZATION, CACHE_CONTROL, CONNECTION, CONTENT_LENGTH, ETAG, HeaderMap, HeaderName,
+    HeaderValue, IF_NONE_MATCH, PRAGMA, SET_COOKIE, TRANSFER_ENCODING, UPGRADE, VARY,
+};
+use hyper::{Method, StatusCode};
+use tracing::debug;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+pub use crate::types::route::cache::{
+    CACHE_CONFIG_KEY, CACHE_MAX_ENTRY_BYTES_CONFIG_KEY, CACHE_MAX_TTL_SECS_CONFIG_KEY,
+    DEFAULT_MAX_ENTRY_BYTES, RouteCache,
+};
+
+/// Size of the responses cached by a host by default
+pub const DEFAULT_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+impl RouteCache {
+    /// Returns how long a response with `headers` is fresh, if it may be
+    /// stored at all.
+    fn freshness(&self, headers: &HeaderMap) -> Option<Duration> {
+        let ttl = max_age(headers)?;
+        Some(match self.max_ttl_secs {
+            Some(max) => ttl.min(Duration::from_secs(max)),
+            None => ttl,
+        })
+    }
+}
+
+/// Identifies the cached response of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    workload_id: String,
+    host: String,
+    /// The path and query of the request
+    path: String,
+}
+
+impl CacheKey {
+    /// Returns the key of `req` to the given workload, if it may be answered
+    /// from the cache.
+    pub(crate) fn of<B>(workload_id: &str, req: &hyper::Request<B>) -> Option<Self> {
+        if req.method() != Method::GET
+            || req.headers().contains_key(AUTHORIZATION)
+            || req.headers().contains_key(UPGRADE)
+            || has_directive(req.headers(), CACHE_CONTROL, &["no-cache", "no-store"])
+            || has_directive(req.headers(), PRAGMA, &["no-cache"])
+        {
+            return None;
+        }
+        let host = match req.uri().host() {
+            Some(host) => host.to_string(),
+            None => {
+                let authority = req.headers().get(hyper::header::HOST)?.to_str().ok()?;
+                authority
+                    .parse::<hyper::http::uri::Authority>()
+                    .ok()?
+                    .host()
+                    .to_string()
+            }
+        };
+        Some(Self {
+            workload_id: workload_id.to_string(),
+            host: host.to_ascii_lowercase(),
+            path: req.uri().path_and_query()?.to_string(),
+        })
+    }
+}
+
+/// A cached response.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The request headers named by `Vary`, with the values they were stored
+    /// for
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    stored_at: Instant,
+    ttl: Duration,
+}
+
+impl Entry {
+    fn size(&self) -> u64 {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        (self.body.len() + headers) as u64
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.stored_at.elapsed() < self.ttl
+    }
+
+    fn varies_from(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .any(|(name, value)| headers.get(name) != value.as_ref())
+    }
+
+    /// Returns the entry, fresh again for `ttl` and with the headers a
+    /// revalidation updated.
+    fn refreshed(&self, updated: &HeaderMap, ttl: Option<Duration>) -> Self {
+        let mut headers = self.headers.clone();
+        for name in [CACHE_CONTROL, ETAG, hyper::header::EXPIRES] {
+            if let Some(value) = updated.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        Self {
+            status: self.status,
+            headers,
+            body: self.body.clone(),
+            vary: self.vary.clone(),
+            stored_at: Instant::now(),
+            ttl: ttl.unwrap_or(self.ttl),
+        }
+    }
+
+    /// Answers a request with `request_headers` with the entry.
+    fn response(&self, request_headers: &HeaderMap) -> hyper::Response<HyperOutgoingBody> {
+        let not_modified = match (self.headers.get(ETAG), request_headers.get(IF_NONE_MATCH)) {
+            (Some(etag), Some(tags)) => etag_matches(etag, tags),
+            _ => false,
+        };
+        let age = HeaderValue::from(self.stored_at.elapsed().as_secs());
+        let mut response = if not_modified {
+            let mut response = hyper::Response::new(HyperOutgoingBody::default());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            for name in [CACHE_CONTROL, ETAG, VARY, hyper::header::EXPIRES] {
+                for value in self.headers.get_all(&name) {
+                    response.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+            response
+        } else {
+            let body = Full::new(self.body.clone())
+                .map_err(|never| match never {})
+                .boxed_unsync();
+            let mut response = hyper::Response::new(body);
+            *response.status_mut() = self.status;
+            *response.headers_mut() = self.headers.clone();
+            response
+                .headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(self.body.len()));
+            response
+        };
+        response.headers_mut().insert(AGE, age);
+        response
+    }
+}
+
+/// What the cache holds for a request.
+pub(crate) enum Lookup {
+    /// A fresh response answering the request
+    Fresh(hyper::Response<HyperOutgoingBody>),
+    /// A stale response to revalidate with the component, sending the
+    /// request with `If-None-Match` set to its `ETag`
+    Stale(Arc<Entry>, HeaderValue),
+    /// Nothing to answer the request with
+    Miss,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Entries with the tick they were last used at
+    entries: HashMap<CacheKey, (Arc<Entry>, u64)>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((entry, _)) = self.entries.remove(key) {
+            self.bytes -= entry.size();
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&CacheKey) -> bool) -> usize {
+        let before = self.entries.len();
+        let mut freed = 0;
+        self.entries.retain(|key, (entry, _)| {
+            let kept = keep(key);
+            if !kept {
+                freed += entry.size();
+            }
+            kept
+        });
+        self.bytes -= freed;
+        before - self.entries.len()
+    }
+}
+
+/// The responses cached by the HTTP server, see the [module docs](self).
+#[derive(Debug)]
+pub struct ResponseCache {
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BYTES)
+    }
+}
+
+impl ResponseCache {
+    /// Creates a cache holding responses of up to `max_bytes` in total.
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the responses in the cache.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns whether the cache holds no response.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the size of the responses in the cache.
+    pub fn size_bytes(&self) -> u64 {
+        self.lock().bytes
+    }
+
+    /// Drops the responses to requests for `host`, or every host if `None`,
+    /// whose path starts with `path`, or any path if `None`. Returns how many
+    /// were dropped.
+    pub fn invalidate(&self, host: Option<&str>, path: Option<&str>) -> usize {
+        let prefix = path.map(|path| path.trim_end_matches('/'));
+        self.lock().retain(|key| {
+            let host_matches = host.is_none_or(|host| key.host.eq_ignore_ascii_case(host));
+            let path_matches = prefix.is_none_or(|prefix| {
+                key.path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+            });
+            !(host_matches && path_matches)
+        })
+    }
+
+    /// Drops the responses of a workload.
+    pub(crate) fn remove_workload(&self, workload_id: &str) -> usize {
+        self.lock().retain(|key| key.workload_id != workload_id)
+    }
+
+    /// Looks up the response to a request with `key` and `headers`.
+    pub(crate) fn lookup(&self, key: &CacheKey, headers: &HeaderMap) -> Lookup {
+        let mut state = self.lock();
+        state.tick += 1;
+        let tick = state.tick;
+        let Some((entry, used)) = state.entries.get_mut(key) else {
+            return Lookup::Miss;
+        };
+        if entry.varies_from(headers) {
+            return Lookup::Miss;
+        }
+        *used = tick;
+        if entry.is_fresh() {
+            return Lookup::Fresh(entry.response(headers));
+        }
+        // Requests with their own validators are left to the component
+        match entry.headers.get(ETAG) {
+            Some(etag) if !headers.contains_key(IF_NONE_MATCH) => {
+                Lookup::Stale(entry.clone(), etag.clone())
+            }
+            _ => Lookup::Miss,
+        }
+    }
+
+    /// Caches `response` to a request with `key` and `headers` to a route
+    /// caching as `route` says, if it may be stored, returning the response
+    /// to answer the request with. `stale` is the entry the request was sent
+    /// to revalidate, if any.
+    pub(crate) fn store(
+        self: &Arc<Self>,
+        route: &RouteCache,
+        key: CacheKey,
+        headers: &HeaderMap,
+        stale: Option<Arc<Entry>>,
+        response: hyper::Response<HyperOutgoingBody>,
+    ) -> hyper::Response<HyperOutgoingBody> {
+        if let Some(stale) = stale {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                debug!(
+                    host = key.host,
+                    path = key.path,
+                    "revalidated cached response"
+                );
+                let entry =
+                    stale.refreshed(response.headers(), route.freshness(response.headers()));
+                let response = entry.response(headers);
+                self.insert(key, entry);
+                return response;
+            }
+            self.lock().remove(&key);
+        }
+
+        if !is_cacheable_status(response.status()) {
+            return response;
+        }
+        let Some(ttl) = route.freshness(response.headers()) else {
+            return response;
+        };
+        let Some(vary) = vary(response.headers(), headers) else {
+            return response;
+        };
+        let max = route.max_entry_bytes().min(self.max_bytes);
+        if response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+            .is_some_and(|len| len > max)
+        {
+            return response;
+        }
+
+        let (parts, body) = response.into_parts();
+        let mut stored_headers = parts.headers.clone();
+        for name in [CONNECTION, TRANSFER_ENCODING, CONTENT_LENGTH] {
+            stored_headers.remove(name);
+        }
+        let pending = Pending {
+            cache: self.clone(),
+            key,
+            status: parts.status,
+            headers: stored_headers,
+            vary,
+            ttl,
+            data: BytesMut::new(),
+            max,
+        };
+        let mut body = CachingBody {
+            inner: body,
+            pending: Some(pending),
+        };
+        // Bodies that are already done are never polled
+        if body.inner.is_end_stream()
+            && let Some(pending) = body.pending.take()
+        {
+            pending.finish();
+        }
+        hyper::Response::from_parts(parts, body.boxed_unsync())
+    }
+
+    fn insert(&self, key: CacheKey, entry: Entry) {
+        let size = entry.size();
+        if size > self.max_bytes {
+            return;
+        }
+        let mut state = self.lock();
+        state.remove(&key);
+        while state.bytes + size > self.max_bytes {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            state.remove(&oldest);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.bytes += size;
+        state.entries.insert(key, (Arc::new(entry), tick));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A response being sent while its body is collected to be cached.
+struct Pending {
+    cache: Arc<ResponseCache>,
+    key: CacheKey,
+    status: StatusCode,
+    headers: HeaderMap,
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    ttl: Duration,
+    data: BytesMut,
+    max: u64,
+}
+
+impl Pending {
+    fn finish(self) {
+        debug!(
+            host = self.key.host,
+            path = self.key.path,
+            bytes = self.data.len(),
+            ttl = ?self.ttl,
+            "cached response"
+        );
+        self.cache.insert(
+            self.key,
+            Entry {
+                status: self.status,
+                headers: self.headers,
+                body: self.data.freeze(),
+                vary: self.vary,
+                stored_at: Instant::now(),
+                ttl: self.ttl,
+            },
+        );
+    }
+}
+
+/// Passes the body of a response on, caching it once it is complete.
+struct CachingBody {
+    inner: HyperOutgoingBody,
+    pending: Option<Pending>,
+}
+
+impl Body for CachingBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        let this = self.get_mut();
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => match (frame.data_ref(), &mut this.pending) {
+                (Some(data), Some(pending)) => {
+                    if (pending.data.len() + data.len()) as u64 > pending.max {
+                        this.pending = None;
+                    } else {
+                        pending.data.extend_from_slice(data);
+                    }
+                }
+                // Responses with trailers are not cached
+                _ => this.pending = None,
+            },
+            Some(Err(_)) => this.pending = None,
+            None => {}
+        }
+        if (frame.is_none() || this.inner.is_end_stream())
+            && let Some(pending) = this.pending.take()
+        {
+            pending.finish();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Returns whether responses with `status` may be cached by default.
+fn is_cacheable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// Returns how long a response with `headers` is fresh for a shared cache,
+/// or `None` if it may not be stored.
+fn max_age(headers: &HeaderMap) -> Option<Duration> {
+    if headers.contains_key(SET_COOKIE) {
+        return None;
+    }
+    let mut max_age = None;
+    let mut s_maxage = None;
+    for (name, value) in directives(headers, CACHE_CONTROL) {
+        let seconds = || value.and_then(|v| v.parse::<u64>().ok());
+        match name.as_str() {
+            "no-store" | "no-cache" | "private" => return None,
+            "max-age" => max_age = seconds(),
+            "s-maxage" => s_maxage = seconds(),
+            _ => {}
+        }
+    }
+    s_maxage
+        .or(max_age)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Returns the request headers named by the `Vary` of a response with
+/// `headers`, with their values in `request`, or `None` if it varies on
+/// everything.
+fn vary(
+    headers: &HeaderMap,
+    request: &HeaderMap,
+) -> Option<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let mut vary = Vec::new();
+    for (name, _) in directives(headers, VARY) {
+        if name == "*" {
+            return None;
+        }
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        let value = request.get(&name).cloned();
+        vary.push((name, value));
+    }
+    Some(vary)
+}
+
+/// Returns whether any of the directives of header `name` is one of `any`.
+fn has_directive(headers: &HeaderMap, name: HeaderName, any: &[&str]) -> bool {
+    directives(headers, name).any(|(directive, _)| any.contains(&directive.as_str()))
+}
+
+/// Returns the comma-separated directives of header `name`, lowercase, with
+/// their values.
+fn directives(
+    headers: &HeaderMap,
+    name: HeaderName,
+) -> impl Iterator<Item = (String, Option<&str>)> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            (name.trim().to_ascii_lowercase(), value)
+        })
+        .filter(|(name, _)| !name.is_empty())
+}
+
+/// Returns whether `If-None-Match: tags` matches `etag`, weakly.
+fn etag_matches(etag: &HeaderValue, tags: &HeaderValue) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let (Ok(etag), Ok(tags)) = (etag.to_str(), tags.to_str()) else {
+        return false;
+    };
+    tags.trim() == "*" || tags.split(',').any(|tag| weak(tag) == weak(etag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> hyper::Request<()> {
+        hyper::Request::get(format!("http://example.com{path}"))
+            .body(())
+            .unwrap()
+    }
+
+    fn response(cache_control: &str, body: &'static str) -> hyper::Response<HyperOutgoingBody> {
+        hyper::Response::builder()
+            .header(CACHE_CONTROL, cache_control)
+            .header(ETAG, "\"v1\"")
+            .header(VARY, "accept-language")
+            .body(
+                Full::new(Bytes::from_static(body.as_bytes()))
+                    .map_err(|never| match never {})
+                    .boxed_unsync(),
+            )
+            .unwrap()
+    }
+
+    async fn body(response: hyper::Response<HyperOutgoingBody>) -> Bytes {
+        response.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    #[test]
+    fn test_max_age() {
+        let headers = |value: &'static str| {
+            HeaderMap::from_iter([(CACHE_CONTROL, HeaderValue::from_static(value))])
+        };
+        assert_eq!(
+            max_age(&headers("public, max-age=60")),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            max_age(&headers("max-age=60, s-maxage=10")),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(max_age(&headers("max-age=60, private")), None);
+        assert_eq!(max_age(&headers("no-store")), None);
+        assert_eq!(max_age(&headers("max-age=0")), None);
+        assert_eq!(max_age(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_cacheable_requests() {
+        assert!(CacheKey::of("w", &request("/a?b=c")).is_some());
+        let mut req = request("/a");
+        req.headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        assert!(CacheKey::of("w", &req).is_none());
+        let mut req = request("/a");
+        req.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        assert!(CacheKey::of("w", &req).is_none());
+        let mut req = request("/a");
+        *req.method_mut() = Method::POST;
+        assert!(CacheKey::of("w", &req).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_store_and_lookup() {
+        let cache = Arc::new(ResponseCache::default());
+        let route = RouteCache::default();
+        let key = CacheKey::of("w", &request("/a")).unwrap();
+        let english = HeaderMap::from_iter([(
+            HeaderName::from_static("accept-language"),
+            HeaderValue::from_static("en"),
+        )]);
+
+        let stored = cache.store(
+            &route,
+            key.clone(),
+            &english,
+            None,
+            response("max-age=60", "hello"),
+        );
+        assert_eq!(body(stored).await, "hello");
+        assert_eq!(cache.len(), 1);
+
+        let Lookup::Fresh(hit) = cache.lookup(&key, &english) else {
+            panic!("expected a fresh response");
+        };
+        assert_eq!(hit.headers()[AGE], "0");
+        assert_eq!(body(hit).await, "hello");
+        assert!(matches!(
+            cache.lookup(&key, &HeaderMap::new()),
+            Lookup::Miss
+        ));
+
+        let mut conditional = english.clone();
+        conditional.insert(IF_NONE_MATCH, HeaderValue::from_static("W/\"v1\""));
+        let Lookup::Fresh(hit) = cache.lookup(&key, &conditional) else {
+            panic!("expected a fresh response");
+        };
+        assert_eq!(hit.status(), StatusCode::NOT_MODIFIED);
+
+        // Neither private responses nor those larger than the route allows
+        let other = CacheKey::of("w", &request("/b")).unwrap();
+        let _ = cache.store(
+            &route,
+            other.clone(),
+            &english,
+            None,
+            response("private, max-age=60", "x"),
+        );
+        let small = RouteCache::default().with_max_entry_bytes(2);
+        let _ = body(cache.store(
+            &small,
+            other.clone(),
+            &english,
+            None,
+            response("max-age=60", "large"),
+        ))
+        .await;
+        assert!(matches!(cache.lookup(&other, &english), Lookup::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_revalidation() {
+        let cache = Arc::new(ResponseCache::default());
+        let route = RouteCache::default();
+        let key = CacheKey::of("w", &request("/a")).unwrap();
+        let headers = HeaderMap::new();
+        cache.insert(
+            key.clone(),
+            Entry {
+                status: StatusCode::OK,
+                headers: HeaderMap::from_iter([(ETAG, HeaderValue::from_static("\"v1\""))]),
+                body: Bytes::from_static(b"hello"),
+                vary: Vec::new(),
+                stored_at: Instant::now(),
+                ttl: Duration::ZERO,
+            },
+        );
+        let Lookup::Stale(stale, etag) = cache.lookup(&key, &headers) else {
+            panic!("expected a stale response");
+        };
+        assert_eq!(etag, "\"v1\"");
+
+        let not_modified = hyper::Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(CACHE_CONTROL, "max-age=60")
+            .body(HyperOutgoingBody::default())
+            .unwrap();
+        let resp = cache.store(&route, key.clone(), &headers, Some(stale), not_modified);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body(resp).await, "hello");
+        assert!(matches!(cache.lookup(&key, &headers), Lookup::Fresh(_)));
+    }
+
+    #[test]
+    fn test_invalidate_and_evict() {
+        let cache = ResponseCache::new(20);
+        let entry = || Entry {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"0123456789"),
+            vary: Vec::new(),
+            stored_at: Instant::now(),
+            ttl: Duration::from_secs(60),
+        };
+        for path in ["/api/a", "/api/b", "/apix"] {
+            cache.insert(CacheKey::of("w", &request(path)).unwrap(), entry());
+        }
+        // Only two entries fit, the least recently used was evicted
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size_bytes(), 20);
+
+        assert_eq!(cache.invalidate(Some("example.com"), Some("/api")), 1);
+        assert_eq!(cache.invalidate(Some("other.com"), None), 0);
+        assert_eq!(cache.remove_workload("w"), 1);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+//! Pooled connections of outgoing `wasi:http` requests.
+//!
+//! The HTTP server keeps the connections of outgoing requests open once their
+//! response is read, and sends the next request to the same scheme, host and
+//! port over one of them instead of connecting again, see
+//! [`ConnectionPool`]. Connections are shared by all components, but one is
+//! only reused for a request if the [`EgressPolicy`](super::EgressPolicy) of
+//! its component allows the address it is connected to. Connections of
+//! workloads with [`OutboundLimits`](crate::types::OutboundLimits) are not
+//! pooled, as they count against the limits of the workload while open.
+//!
+//! A request sent over a pooled connection the server closed in the meantime
+//! is sent again over a new connection, unless any of it was sent already.
+//!
+//! The connections in use and idle are recorded on every heartbeat as the
+//! `wasmcloud.host.http.outgoing.connections` gauge, with their state in
+//! [`CONNECTION_STATE_ATTRIBUTE`](crate::host::metrics::CONNECTION_STATE_ATTRIBUTE).
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::client::conn::http1::SendRequest;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use wasmtime_wasi::runtime::AbortOnDropJoinHandle;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::OutgoingRequestConfig;
+
+/// How the HTTP server pools the connections of outgoing requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ConnectionPoolConfig {
+    /// Idle connections kept open per scheme, host and port. Zero disables
+    /// pooling.
+    pub max_idle_per_host: usize,
+    /// Seconds an idle connection is kept open
+    pub idle_timeout_secs: u64,
+    /// Connections open at once per scheme, host and port, unlimited if
+    /// `None`. Requests beyond it wait for a connection until their connect
+    /// timeout, then fail with `connection-limit-reached`.
+    pub max_connections_per_host: Option<usize>,
+    /// Longest time, in milliseconds, connecting may take, whatever the
+    /// component asks for
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest time, in milliseconds, to wait for the first byte of a
+    /// response and between its bytes, whatever the component asks for
+    pub read_timeout_ms: Option<u64>,
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 32,
+            idle_timeout_secs: 90,
+            max_connections_per_host: None,
+            connect_timeout_ms: None,
+            read_timeout_ms: None,
+        }
+    }
+}
+
+impl ConnectionPoolConfig {
+    /// Keeps up to `max` idle connections per host. Defaults to 32.
+    pub fn with_max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Closes idle connections after `secs` seconds. Defaults to 90.
+    pub fn with_idle_timeout_secs(mut self, secs: u64) -> Self {
+        self.idle_timeout_secs = secs;
+        self
+    }
+
+    /// Opens at most `max` connections at once per host.
+    pub fn with_max_connections_per_host(mut self, max: usize) -> Self {
+        self.max_connections_per_host = Some(max);
+        self
+    }
+
+    /// Caps the connect timeout of outgoing requests.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Caps the first byte and between bytes timeouts of outgoing requests.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Applies the timeouts of the pool to the `config` of a request.
+    pub(super) fn cap_timeouts(&self, config: &mut OutgoingRequestConfig) {
+        if let Some(ms) = self.connect_timeout_ms {
+            config.connect_timeout = config.connect_timeout.min(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.read_timeout_ms {
+            let timeout = Duration::from_millis(ms);
+            config.first_byte_timeout = config.first_byte_timeout.min(timeout);
+            config.between_bytes_timeout = config.between_bytes_timeout.min(timeout);
+        }
+    }
+}
+
+/// The connections of the outgoing requests of a pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionPoolStats {
+    /// Connections a request is sent or a response is read over
+    pub active: usize,
+    /// Connections kept open for the next request
+    pub idle: usize,
+    /// Connections opened since the pool was created
+    pub opened: u64,
+    /// Requests sent over a pooled connection since the pool was created
+    pub reused: u64,
+}
+
+/// Where the connections of a pool go to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct PoolKey {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+/// An open connection of an outgoing request
+pub(super) struct Connection {
+    pub sender: SendRequest<HyperOutgoingBody>,
+    /// Runs the connection, which closes once it's dropped
+    pub _worker: AbortOnDropJoinHandle<()>,
+    /// The address connected to
+    pub ip: IpAddr,
+}
+
+struct Idle {
+    connection: Connection,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct HostConnections {
+    idle: Vec<Idle>,
+    /// Limits the connections open at once, if the pool does
+    limit: Option<Arc<Semaphore>>,
+}
+
+/// The connections of outgoing requests, see the [module docs](self).
+pub struct ConnectionPool {
+    config: ConnectionPoolConfig,
+    hosts: Mutex<HashMap<PoolKey, HostConnections>>,
+    active: AtomicUsize,
+    opened: AtomicU64,
+    reused: AtomicU64,
+}
+
+impl std::fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Default for ConnectionPool {
+    fn default() -> Self {
+        Self::new(ConnectionPoolConfig::default())
+    }
+}
+
+impl ConnectionPool {
+    pub fn new(config: ConnectionPoolConfig) -> Self {
+        Self {
+            config,
+            hosts: Mutex::default(),
+            active: AtomicUsize::new(0),
+            opened: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &ConnectionPoolConfig {
+        &self.config
+    }
+
+    /// Returns the connections of the pool.
+    pub fn stats(&self) -> ConnectionPoolStats {
+        let idle = self.hosts().values().map(|host| host.idle.len()).sum();
+        ConnectionPoolStats {
+            active: self.active.load(Ordering::Relaxed),
+            idle,
+            opened: self.opened.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+        }
+    }
+
+    fn hosts(&self) -> std::sync::MutexGuard<'_, HashMap<PoolKey, HostConnections>> {
+        self.hosts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Waits until a connection to `key` may be used, at most `timeout`.
+    pub(super) async fn checkout(
+        self: &Arc<Self>,
+        key: PoolKey,
+        timeout: Duration,
+    ) -> Result<Checkout, ErrorCode> {
+        let limit = self.config.max_connections_per_host.map(|max| {
+            self.hosts()
+                .entry(key.clone())
+                .or_default()
+                .limit
+                .get_or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone()
+        });
+        let permit = match limit {
+            Some(limit) => Some(
+                tokio::time::timeout(timeout, limit.acquire_owned())
+                    .await
+                    .map_err(|_| ErrorCode::ConnectionLimitReached)?
+                    .map_err(|_| ErrorCode::ConnectionLimitReached)?,
+            ),
+            None => None,
+        };
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Ok(Checkout {
+            pool: self.clone(),
+            key,
+            _permit: permit,
+        })
+    }
+
+    /// Takes the most recently used idle connection to `key` whose address
+    /// is `allowed`, closing those that expired.
+    pub(super) fn take_idle(
+        &self,
+        key: &PoolKey,
+        allowed: impl Fn(IpAddr) -> bool,
+    ) -> Option<Connection> {
+        let max_idle = self.idle_timeout();
+        let mut hosts = self.hosts();
+        let host = hosts.get_mut(key)?;
+        host.idle
+            .retain(|idle| idle.since.elapsed() < max_idle && !idle.connection.sender.is_closed());
+        let i = host
+            .idle
+            .iter()
+            .rposition(|idle| allowed(idle.connection.ip))?;
+        let connection = host.idle.remove(i).connection;
+        self.reused.fetch_add(1, Ordering::Relaxed);
+        Some(connection)
+    }
+
+    /// Counts a connection opened for a request.
+    pub(super) fn opened(&self) {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.idle_timeout_secs)
+    }
+
+    /// Keeps `connection` open for the next request to `key`, unless the pool
+    /// holds as many as it may.
+    fn put(&self, key: &PoolKey, connection: Connection) {
+        if self.config.max_idle_per_host == 0 || connection.sender.is_closed() {
+            return;
+        }
+        let max_idle = self.idle_timeout();
+        let mut hosts = self.hosts();
+        let host = hosts.entry(key.clone()).or_default();
+        host.idle.retain(|idle| idle.since.elapsed() < max_idle);
+        if host.idle.len() >= self.config.max_idle_per_host {
+            host.idle.remove(0);
+        }
+        host.idle.push(Idle {
+            connection,
+            since: Instant::now(),
+        });
+        // Forget hosts without connections
+        hosts.retain(|_, host| {
+            !host.idle.is_empty()
+                || host
+                    .limit
+                    .as_ref()
+                    .is_some_and(|limit| Arc::strong_count(limit) > 1)
+        });
+    }
+}
+
+/// A connection to a host in use, counted against the limit of the host
+/// until it's dropped.
+pub(super) struct Checkout {
+    pool: Arc<ConnectionPool>,
+    key: PoolKey,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Checkout {
+    /// Returns `connection` to the pool once its response was read.
+    pub(super) fn release(self, connection: Connection) {
+        self.pool.put(&self.key, connection);
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        self.pool.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connection_limit() {
+        let pool = Arc::new(ConnectionPool::new(
+            ConnectionPoolConfig::default().with_max_connections_per_host(1),
+        ));
+        let key = |host: &str| PoolKey {
+            tls: true,
+            host: host.to_string(),
+            port: 443,
+        };
+        let timeout = Duration::from_millis(20);
+
+        let first = pool.checkout(key("a.example"), timeout).await.unwrap();
+        assert!(matches!(
+            pool.checkout(key("a.example"), timeout).await,
+            Err(ErrorCode::ConnectionLimitReached)
+        ));
+        // Other hosts have their own limit
+        let other = pool.checkout(key("b.example"), timeout).await.unwrap();
+        assert_eq!(pool.stats().active, 2);
+
+        drop(first);
+        drop(other);
+        assert!(pool.checkout(key("a.example"), timeout).await.is_ok());
+        assert_eq!(pool.stats().active, 0);
+        assert!(pool.take_idle(&key("a.example"), |_| true).is_none());
+    }
+
+    #[test]
+    fn test_cap_timeouts() {
+        let mut config = OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(600),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(600),
+        };
+        ConnectionPoolConfig::default()
+            .with_connect_timeout(Duration::from_secs(5))
+            .with_read_timeout(Duration::from_secs(30))
+            .cap_timeouts(&mut config);
+        assert_eq!(config.connect_timeout, Duration::from_secs(5));
+        assert_eq!(config.first_byte_timeout, Duration::from_secs(1));
+        assert_eq!(config.between_bytes_timeout, Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,90 @@
+//! Propagating the deadline of a request to the calls a component makes.
+//!
+//! A request to a route with a timeout must be answered before its deadline,
+//! after which the client is sent a `504 Gateway Timeout`. The component
+//! learns how much time it has left from the [`DEADLINE_HEADER`] of the
+//! request, in milliseconds:
+//!
+//! ```text
+//! x-wasmcloud-deadline-ms: 1500
+//! ```
+//!
+//! The outgoing HTTP requests of the component are held to the same
+//! deadline: they fail with `HTTP-response-timeout` once it passed, and carry
+//! the time left in the same header, so a component served by another host
+//! is held to it too. A request arriving with the header is held to the
+//! shorter of its own deadline and the timeout of its route.
+//!
+//! A request whose client disconnects is cancelled together with the
+//! outgoing requests of its component, whether it had a deadline or not.
+
+use std::time::Duration;
+
+use hyper::HeaderMap;
+use hyper::header::HeaderValue;
+
+/// Header telling a component, or the host serving an outgoing request, how
+/// many milliseconds are left until the deadline of the request
+pub const DEADLINE_HEADER: &str = "x-wasmcloud-deadline-ms";
+
+/// Returns the time a request may take: the shorter of the `timeout` of its
+/// route and the time left in its [`DEADLINE_HEADER`], if any.
+pub fn request_timeout(timeout: Option<Duration>, headers: &HeaderMap) -> Option<Duration> {
+    let remaining = headers
+        .get(DEADLINE_HEADER)
+        .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_millis);
+    match (timeout, remaining) {
+        (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+        (timeout, remaining) => timeout.or(remaining),
+    }
+}
+
+/// Sets the [`DEADLINE_HEADER`] of a request to `remaining`, unless it
+/// already has an earlier deadline.
+pub fn set_remaining(headers: &mut HeaderMap, remaining: Duration) {
+    let remaining = request_timeout(Some(remaining), headers).unwrap_or(remaining);
+    headers.insert(
+        DEADLINE_HEADER,
+        HeaderValue::from(u64::try_from(remaining.as_millis()).unwrap_or(u64::MAX)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(DEADLINE_HEADER, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let second = Some(Duration::from_secs(1));
+        assert_eq!(request_timeout(None, &HeaderMap::new()), None);
+        assert_eq!(request_timeout(second, &HeaderMap::new()), second);
+        assert_eq!(
+            request_timeout(second, &headers("250")),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(request_timeout(second, &headers("5000")), second);
+        assert_eq!(
+            request_timeout(None, &headers("250")),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(request_timeout(second, &headers("soon")), second);
+    }
+
+    #[test]
+    fn test_set_remaining() {
+        let mut empty = HeaderMap::new();
+        set_remaining(&mut empty, Duration::from_millis(1500));
+        assert_eq!(empty.get(DEADLINE_HEADER).unwrap(), "1500");
+
+        let mut earlier = headers("100");
+        set_remaining(&mut earlier, Duration::from_millis(1500));
+        assert_eq!(earlier.get(DEADLINE_HEADER).unwrap(), "100");
+    }
+}
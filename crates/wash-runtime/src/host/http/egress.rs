@@ -0,0 +1,2119 @@
+//! Outgoing `wasi:http` requests of components.
+//!
+//! A component may only send requests to the hosts and URLs in its
+//! `allowed_hosts`, see [`EgressPolicy`]. The HTTP server resolves hosts with its [`Resolver`]: the
+//! system resolver, DNS servers queried directly, or DNS over HTTPS, with
+//! answers cached for their TTL.
+//!
+//! The connection of a request is made to exactly the addresses the policy was
+//! checked against, the host is never resolved again on the way. An answer
+//! that changes between the check and the connection (DNS rebinding) therefore
+//! can't send a request elsewhere. Names resolving into the
+//! [`ResolverConfig::denied_networks`], e.g. private networks or cloud
+//! metadata endpoints, are refused, so a component can only reach those
+//! addresses if its `allowed_hosts` lists them.
+//!
+//! Requests the policy denies fail with `HTTP-request-denied`, those whose
+//! host only resolves to denied addresses with `destination-IP-prohibited`,
+//! and both are logged with the host and port they were sent to.
+//!
+//! Requests are held to the [`OutgoingHttpLimits`] of their component: how
+//! long they may take, how large their response body may be and how many
+//! redirects the host follows for them. A followed redirect is sent like a new
+//! request, so its location has to be allowed as well, and credentials are
+//! only sent again to the same origin.
+//!
+//! The outbound connections of a workload are held to its
+//! [`OutboundLimits`](crate::types::OutboundLimits), shared by all of its
+//! components through an [`OutboundThrottle`]: connections beyond
+//! `max_connections` are refused with `connection-limit-reached`, and the bytes
+//! sent and received over them, TLS included, are paced to
+//! `max_bytes_per_sec`. `wasi:sockets` can't open connections, components are
+//! not given access to the network of the host, so outgoing requests are the
+//! only outbound connections of a workload.
+//!
+//! Requests matching a [`SigningRule`] of the host are signed with its
+//! credentials before they are sent, see [`super::signing`], and those
+//! matching a [`ResilienceRule`](super::ResilienceRule) are retried or failed
+//! fast when their host keeps failing, see [`super::resilience`].
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Poll, ready};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, bail, ensure};
+use bytes::Bytes;
+use http_body_util::{BodyExt as _, Full};
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::client::conn::http1::SendRequest;
+use hyper::header::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST, HeaderMap, HeaderValue, LOCATION,
+    PROXY_AUTHORIZATION, TRANSFER_ENCODING,
+};
+use hyper::http::uri::Scheme;
+use hyper::{Method, Uri};
+use rustls::pki_types::ServerName;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+use tracing::{debug, warn};
+use wasmtime_wasi::runtime::AbortOnDropJoinHandle;
+use wasmtime_wasi_http::bindings::http::types::{DnsErrorPayload, ErrorCode};
+use wasmtime_wasi_http::body::{HyperIncomingBody, HyperOutgoingBody};
+use wasmtime_wasi_http::hyper_request_error;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::types::{
+    HostFutureIncomingResponse, IncomingResponse, OutgoingRequestConfig,
+};
+
+use super::IpCidr;
+use super::connection_pool::{Checkout, Connection, ConnectionPool, PoolKey};
+use super::resilience::OutboundResilience;
+use super::signing::SigningRule;
+use crate::types::{OutboundLimits, OutgoingHttpLimits};
+
+/// How long a DNS server may take to answer
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// The hosts and URLs a component may send requests to, from its
+/// `allowed_hosts`.
+///
+/// Entries are host names, e.g. `api.example.com`, wildcards matching any
+/// subdomain, e.g. `*.example.com`, or IP networks, e.g. `10.0.0.0/8` or
+/// `192.0.2.1`. `*` allows every host. A port after an entry, e.g.
+/// `api.example.com:443`, `*.example.com:8080` or `[2001:db8::/32]:443`,
+/// restricts it to requests to that port.
+///
+/// Entries starting with `!`, e.g. `!internal.example.com` or `!10.0.0.0/8`,
+/// deny the hosts they match, whatever else the list allows. Denied networks
+/// also apply to the addresses names resolve to.
+///
+/// A list without entries allowing hosts allows every host unless the host
+/// denies requests by default, see [`EgressDefault`].
+///
+/// Entries can also be URL patterns, allowing only some requests to a host,
+/// e.g. `https://generativelanguage.googleapis.com/v1beta/*`. The scheme, host
+/// and port of a request must match, and its path must match the path of the
+/// pattern, where `*` matches any characters, `/` included. The query is not
+/// matched. Methods before the pattern, e.g. `GET,HEAD https://...`, restrict
+/// it to these methods. Requests whose path has `.` or `..` segments, or an
+/// encoded `.`, `/` or `\`, never match a pattern, as the server could read
+/// them as a path outside of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressPolicy {
+    allowed: Vec<HostRule>,
+    denied: Vec<HostRule>,
+    patterns: Vec<UrlPattern>,
+    default: EgressDefault,
+    limits: OutgoingHttpLimits,
+    /// The deadline of the invocation sending the requests
+    deadline: Option<tokio::time::Instant>,
+    /// The outbound limits of the workload of the component
+    throttle: Option<Arc<OutboundThrottle>>,
+    /// The rules signing requests of the component
+    signers: Vec<Arc<SigningRule>>,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl EgressPolicy {
+    /// Creates the policy of a component from its `allowed_hosts`.
+    pub fn new(allowed_hosts: &[String]) -> Self {
+        let mut policy = Self {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+            patterns: Vec::new(),
+            default: EgressDefault::default(),
+            limits: OutgoingHttpLimits::default(),
+            deadline: None,
+            throttle: None,
+            signers: Vec::new(),
+        };
+        for entry in allowed_hosts {
+            // Paths of URL patterns are case sensitive
+            if entry.contains("://") {
+                match UrlPattern::parse(entry.trim()) {
+                    Ok(pattern) => policy.patterns.push(pattern),
+                    Err(e) => warn!(
+                        entry,
+                        "ignoring invalid URL pattern in allowed_hosts: {e:#}"
+                    ),
+                }
+                continue;
+            }
+            let entry = entry.trim().to_ascii_lowercase();
+            let (rules, rule) = match entry.strip_prefix('!') {
+                Some(rule) => (&mut policy.denied, rule),
+                None => (&mut policy.allowed, entry.as_str()),
+            };
+            match HostRule::parse(rule) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => warn!(entry, "ignoring invalid entry in allowed_hosts: {e:#}"),
+            }
+        }
+        policy
+    }
+
+    /// Whether requests are allowed when `allowed_hosts` has no entries
+    /// allowing hosts. Defaults to [`EgressDefault::Allow`].
+    pub fn with_default(mut self, default: EgressDefault) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Holds requests to the `limits` of the component.
+    pub fn with_limits(mut self, limits: OutgoingHttpLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &OutgoingHttpLimits {
+        &self.limits
+    }
+
+    /// Holds the connections of requests to the outbound limits of the
+    /// workload, if it has any.
+    pub fn with_throttle(mut self, throttle: Option<Arc<OutboundThrottle>>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Fails requests still running at `deadline`, the deadline of the
+    /// invocation sending them, and tells their server about it, see
+    /// [`super::deadline`].
+    pub fn with_deadline(mut self, deadline: tokio::time::Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn deadline(&self) -> Option<tokio::time::Instant> {
+        self.deadline
+    }
+
+    /// Signs requests with the first of `signers` matching their host, see
+    /// [`super::signing`].
+    pub fn with_signers(mut self, signers: Vec<Arc<SigningRule>>) -> Self {
+        self.signers = signers;
+        self
+    }
+
+    /// Returns the rule signing requests to `host`, if any.
+    pub fn signer(&self, host: &str) -> Option<&SigningRule> {
+        self.signers
+            .iter()
+            .map(Arc::as_ref)
+            .find(|rule| rule.matches(host.trim_matches(['[', ']'])))
+    }
+
+    /// Returns whether every request to `port` of `host`, a name or an IP
+    /// address, is allowed, regardless of the URL patterns of the policy.
+    pub fn allows_host(&self, host: &str, port: u16) -> bool {
+        let host = host.trim_matches(['[', ']']);
+        if self.denies_host(host, port) {
+            return false;
+        }
+        (self.allowed.is_empty() && self.patterns.is_empty() && self.default.allows())
+            || self.allowed.iter().any(|rule| rule.matches(host, port))
+    }
+
+    /// Returns whether an entry of the policy denies requests to `port` of
+    /// `host`.
+    pub fn denies_host(&self, host: &str, port: u16) -> bool {
+        let host = host.trim_matches(['[', ']']);
+        self.denied.iter().any(|rule| rule.matches(host, port))
+    }
+
+    /// Returns whether a request with `method` to `uri`, which has a scheme
+    /// and an authority, is allowed.
+    pub fn allows_request(&self, method: &Method, uri: &Uri) -> bool {
+        let Some(authority) = uri.authority() else {
+            return false;
+        };
+        let port = authority
+            .port_u16()
+            .or_else(|| uri.scheme().map(default_port))
+            .unwrap_or(80);
+        !self.denies_host(authority.host(), port)
+            && (self.allows_host(authority.host(), port)
+                || self
+                    .patterns
+                    .iter()
+                    .any(|pattern| pattern.matches(method, uri)))
+    }
+
+    /// Returns whether a request to an allowed host may connect to `port` of
+    /// `ip`: it is in none of the networks the policy denies, and outside the
+    /// `denied` networks or in one of the networks the policy allows.
+    pub fn allows_ip(&self, ip: IpAddr, port: u16, denied: &[IpCidr]) -> bool {
+        !self.denied.iter().any(|rule| rule.contains(ip, port))
+            && (self.allowed.iter().any(|rule| rule.contains(ip, port))
+                || !denied.iter().any(|network| network.contains(ip)))
+    }
+}
+
+/// Whether a host allows the outgoing requests of components that don't
+/// list the hosts they may send requests to, see [`EgressPolicy`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EgressDefault {
+    /// Requests to every host are allowed
+    #[default]
+    Allow,
+    /// No request is allowed
+    Deny,
+}
+
+impl EgressDefault {
+    fn allows(self) -> bool {
+        self == Self::Allow
+    }
+}
+
+impl FromStr for EgressDefault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "deny" => Ok(Self::Deny),
+            _ => bail!("unknown egress default '{s}', expected allow or deny"),
+        }
+    }
+}
+
+/// A host entry of an [`EgressPolicy`], matching any port if `port` is `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostRule {
+    host: HostMatch,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostMatch {
+    Any,
+    /// A name or a wildcard such as `*.example.com`
+    Name(String),
+    Network(IpCidr),
+}
+
+impl HostRule {
+    /// Parses a lowercase entry without `!`.
+    fn parse(entry: &str) -> anyhow::Result<Self> {
+        // IPv6 addresses only take a port in brackets
+        let (host, port) = match entry.strip_prefix('[') {
+            Some(rest) => {
+                let (host, rest) = rest.split_once(']').context("missing ']'")?;
+                match rest {
+                    "" => (host, None),
+                    _ => (host, Some(rest.strip_prefix(':').context("invalid port")?)),
+                }
+            }
+            None => match entry.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, Some(port)),
+                _ => (entry, None),
+            },
+        };
+        let port = port
+            .map(|port| {
+                port.parse::<u16>()
+                    .with_context(|| format!("invalid port '{port}'"))
+            })
+            .transpose()?;
+        let host = if host == "*" {
+            HostMatch::Any
+        } else if let Ok(network) = host.parse::<IpCidr>() {
+            HostMatch::Network(network)
+        } else {
+            let name = host.trim_end_matches('.');
+            ensure!(!name.is_empty(), "missing host");
+            HostMatch::Name(name.to_string())
+        };
+        Ok(Self { host, port })
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        match (&self.host, host.parse::<IpAddr>()) {
+            (HostMatch::Any, _) => true,
+            (HostMatch::Network(network), Ok(ip)) => network.contains(ip),
+            (HostMatch::Name(name), Err(_)) => host_matches(name, host),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the rule names a network containing `ip`.
+    fn contains(&self, ip: IpAddr, port: u16) -> bool {
+        matches!(&self.host, HostMatch::Network(network) if network.contains(ip))
+            && self.port.is_none_or(|p| p == port)
+    }
+}
+
+/// How the HTTP server resolves the hosts of outgoing requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ResolverConfig {
+    /// DNS servers to query over UDP, in order, instead of the system resolver
+    pub nameservers: Vec<SocketAddr>,
+    /// URL of a DNS over HTTPS (RFC 8484) server to query instead, e.g.
+    /// `https://1.1.1.1/dns-query`. A name in the URL is resolved by the
+    /// system resolver.
+    pub doh_url: Option<String>,
+    /// Longest time, in seconds, an answer is cached, whatever its TTL. Answers
+    /// of the system resolver, which have no TTL, are cached this long.
+    pub max_cache_secs: u64,
+    /// Networks that names may not resolve to, see the [module docs](self)
+    pub denied_networks: Vec<IpCidr>,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            doh_url: None,
+            max_cache_secs: 300,
+            denied_networks: Vec::new(),
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Queries the given DNS servers over UDP, in order.
+    pub fn with_nameservers(mut self, nameservers: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.nameservers.extend(nameservers);
+        self
+    }
+
+    /// Queries the DNS over HTTPS server at `url` instead.
+    pub fn with_doh_url(mut self, url: impl Into<String>) -> Self {
+        self.doh_url = Some(url.into());
+        self
+    }
+
+    /// Caches answers for at most `secs` seconds. Defaults to 300.
+    pub fn with_max_cache_secs(mut self, secs: u64) -> Self {
+        self.max_cache_secs = secs;
+        self
+    }
+
+    /// Rejects names resolving to the given networks.
+    pub fn with_denied_networks(mut self, networks: impl IntoIterator<Item = IpCidr>) -> Self {
+        self.denied_networks.extend(networks);
+        self
+    }
+}
+
+/// A URL pattern of an [`EgressPolicy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UrlPattern {
+    /// Any method if empty
+    methods: Vec<Method>,
+    scheme: Scheme,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl UrlPattern {
+    fn parse(entry: &str) -> anyhow::Result<Self> {
+        let (methods, url) = match entry.split_once(char::is_whitespace) {
+            Some((methods, url)) => {
+                let methods: Vec<Method> = methods
+                    .split(',')
+                    .map(|method| {
+                        Method::from_bytes(method.trim().to_ascii_uppercase().as_bytes())
+                            .with_context(|| format!("invalid method '{method}'"))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                (methods, url.trim())
+            }
+            None => (Vec::new(), entry),
+        };
+        // A wildcard host is not a valid URI authority, so the URL is split by hand
+        let (scheme, rest) = url.split_once("://").context("missing scheme")?;
+        let scheme = match scheme.to_ascii_lowercase().as_str() {
+            "http" => Scheme::HTTP,
+            "https" => Scheme::HTTPS,
+            other => bail!("unsupported scheme '{other}'"),
+        };
+        let (authority, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+        ensure!(
+            !path.contains(['?', '#']),
+            "URL patterns match paths, without query or fragment"
+        );
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => (
+                host,
+                port.parse()
+                    .with_context(|| format!("invalid port '{port}'"))?,
+            ),
+            _ => (authority, default_port(&scheme)),
+        };
+        let host = host
+            .trim_matches(['[', ']'])
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        ensure!(!host.is_empty(), "missing host");
+        Ok(Self {
+            methods,
+            scheme,
+            host,
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn matches(&self, method: &Method, uri: &Uri) -> bool {
+        let (Some(scheme), Some(authority)) = (uri.scheme(), uri.authority()) else {
+            return false;
+        };
+        (self.methods.is_empty() || self.methods.contains(method))
+            && *scheme == self.scheme
+            && authority.port_u16().unwrap_or_else(|| default_port(scheme)) == self.port
+            && host_matches(&self.host, authority.host().trim_matches(['[', ']']))
+            && is_normalized(uri.path())
+            && glob_matches(&self.path, uri.path())
+    }
+}
+
+fn default_port(scheme: &Scheme) -> u16 {
+    if *scheme == Scheme::HTTPS { 443 } else { 80 }
+}
+
+/// Returns whether `host` is `name`, or a subdomain of it if `name` is a
+/// wildcard such as `*.example.com`.
+fn host_matches(name: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match name.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.')),
+        None => name == host,
+    }
+}
+
+/// Returns whether `path` has no `.` or `..` segment, and no encoded `.`, `/`
+/// or `\` that could become one.
+fn is_normalized(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    !path
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+        && !["%2e", "%2f", "%5c"]
+            .iter()
+            .any(|encoded| lower.contains(encoded))
+}
+
+/// Returns whether `path` matches `pattern`, where `*` matches any characters.
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Resolves and caches the hosts of outgoing requests, see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct Resolver {
+    config: ResolverConfig,
+    doh_url: Option<Uri>,
+    /// Addresses by host, until they expire
+    cache: Mutex<HashMap<String, (Vec<IpAddr>, Instant)>>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(ResolverConfig::default()).expect("the default resolver is valid")
+    }
+}
+
+impl Resolver {
+    /// Creates a resolver.
+    ///
+    /// # Errors
+    /// Returns an error if the DNS over HTTPS URL is not an `https` URL.
+    pub fn new(config: ResolverConfig) -> anyhow::Result<Self> {
+        let doh_url = match &config.doh_url {
+            Some(url) => {
+                let uri: Uri = url
+                    .parse()
+                    .with_context(|| format!("invalid DNS over HTTPS URL '{url}'"))?;
+                ensure!(
+                    uri.scheme_str() == Some("https") && uri.host().is_some(),
+                    "DNS over HTTPS URL '{url}' must be an https URL"
+                );
+                Some(uri)
+            }
+            None => None,
+        };
+        Ok(Self {
+            config,
+            doh_url,
+            cache: Mutex::default(),
+        })
+    }
+
+    pub fn config(&self) -> &ResolverConfig {
+        &self.config
+    }
+
+    /// Returns the addresses of `host`, from the cache while they haven't
+    /// expired. IP addresses are returned as they are.
+    pub async fn resolve(&self, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if let Some((addrs, expires)) = self.cache().get(&host)
+            && *expires > Instant::now()
+        {
+            return Ok(addrs.clone());
+        }
+
+        let max_ttl = self.config.max_cache_secs;
+        let (addrs, ttl) = if let Some(url) = &self.doh_url {
+            query_doh(url, &host).await?
+        } else if !self.config.nameservers.is_empty() {
+            self.query_nameservers(&host).await?
+        } else {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .with_context(|| format!("failed to resolve {host}"))?
+                .map(|addr| addr.ip())
+                .collect();
+            (addrs, max_ttl)
+        };
+        ensure!(!addrs.is_empty(), "{host} has no addresses");
+        debug!(host, ?addrs, ttl, "resolved host of outgoing request");
+
+        let now = Instant::now();
+        let expires = now + Duration::from_secs(ttl.min(max_ttl));
+        let mut cache = self.cache();
+        cache.retain(|_, (_, expires)| *expires > now);
+        cache.insert(host, (addrs.clone(), expires));
+        Ok(addrs)
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Vec<IpAddr>, Instant)>> {
+        self.cache
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Queries the nameservers in order until one answers.
+    async fn query_nameservers(&self, host: &str) -> anyhow::Result<(Vec<IpAddr>, u64)> {
+        let mut last_error = None;
+        for server in &self.config.nameservers {
+            let mut addrs = Vec::new();
+            let mut ttl = u32::MAX;
+            let mut result = Ok(());
+            for qtype in [TYPE_A, TYPE_AAAA] {
+                match query_udp(*server, host, qtype).await {
+                    Ok(answer) => {
+                        addrs.extend(answer.addrs);
+                        ttl = ttl.min(answer.ttl);
+                    }
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            match result {
+                Ok(()) => return Ok((addrs, ttl.into())),
+                Err(e) => {
+                    debug!(%server, "DNS server failed to answer: {e:#}");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.context("no DNS server configured")?)
+            .with_context(|| format!("failed to resolve {host}"))
+    }
+}
+
+/// Holds the outbound connections of a workload to its [`OutboundLimits`].
+/// One throttle is shared by the egress policies of all components of the
+/// workload.
+pub struct OutboundThrottle {
+    limits: OutboundLimits,
+    connections: Option<Arc<Semaphore>>,
+    bandwidth: Option<Mutex<TokenBucket>>,
+}
+
+impl std::fmt::Debug for OutboundThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutboundThrottle")
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for OutboundThrottle {
+    fn eq(&self, other: &Self) -> bool {
+        self.limits == other.limits
+    }
+}
+
+impl Eq for OutboundThrottle {}
+
+impl OutboundThrottle {
+    /// Creates the throttle of a workload, or returns `None` if `limits` are
+    /// unlimited.
+    pub fn new(limits: &OutboundLimits) -> Option<Arc<Self>> {
+        if limits.is_unlimited() {
+            return None;
+        }
+        Some(Arc::new(Self {
+            limits: limits.clone(),
+            connections: limits
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max as usize))),
+            bandwidth: limits
+                .max_bytes_per_sec
+                .map(|rate| Mutex::new(TokenBucket::new(rate, Instant::now()))),
+        }))
+    }
+
+    pub fn limits(&self) -> &OutboundLimits {
+        &self.limits
+    }
+
+    /// Opens an outbound connection, which stays open until the returned
+    /// permit is dropped. Fails if the workload has as many connections open
+    /// as it may.
+    fn open_connection(&self) -> Result<Option<OwnedSemaphorePermit>, ErrorCode> {
+        let Some(connections) = &self.connections else {
+            return Ok(None);
+        };
+        connections
+            .clone()
+            .try_acquire_owned()
+            .map(Some)
+            .map_err(|_| ErrorCode::ConnectionLimitReached)
+    }
+
+    /// Takes up to `wanted` bytes from the bandwidth of the workload, or
+    /// returns how long to wait before any is left.
+    fn take(&self, wanted: usize) -> Result<usize, Duration> {
+        match &self.bandwidth {
+            Some(bucket) => bucket
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .take(wanted, Instant::now()),
+            None => Ok(wanted),
+        }
+    }
+
+    /// Returns bytes taken but not transferred.
+    fn give_back(&self, bytes: usize) {
+        if bytes > 0
+            && let Some(bucket) = &self.bandwidth
+        {
+            bucket
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .give_back(bytes);
+        }
+    }
+}
+
+/// Bytes that may be transferred, refilled at `rate` bytes per second up to a
+/// second's worth.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    tokens: u64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        let rate = rate.max(1);
+        Self {
+            rate,
+            tokens: rate,
+            refilled: now,
+        }
+    }
+
+    fn take(&mut self, wanted: usize, now: Instant) -> Result<usize, Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled).as_nanos();
+        let refill = elapsed * u128::from(self.rate) / 1_000_000_000;
+        if refill > 0 {
+            let tokens = u128::from(self.tokens) + refill;
+            if tokens >= u128::from(self.rate) {
+                self.tokens = self.rate;
+                self.refilled = now;
+            } else {
+                self.tokens = tokens as u64;
+                // Keep the fraction of a byte not refilled yet
+                self.refilled +=
+                    Duration::from_nanos((refill * 1_000_000_000 / u128::from(self.rate)) as u64);
+            }
+        }
+        if self.tokens == 0 {
+            let wait = Duration::from_nanos(1_000_000_000u64.div_ceil(self.rate));
+            return Err(wait.max(Duration::from_millis(1)));
+        }
+        let taken = self.tokens.min(wanted as u64);
+        self.tokens -= taken;
+        Ok(taken as usize)
+    }
+
+    fn give_back(&mut self, bytes: usize) {
+        self.tokens = self.rate.min(self.tokens + bytes as u64);
+    }
+}
+
+/// A connection held to the [`OutboundThrottle`] of a workload.
+struct ThrottledStream<S> {
+    inner: S,
+    throttle: Option<Arc<OutboundThrottle>>,
+    read_wait: Option<Pin<Box<Sleep>>>,
+    write_wait: Option<Pin<Box<Sleep>>>,
+    /// Counts the connection against the workload while it is open
+    _connection: Option<OwnedSemaphorePermit>,
+}
+
+impl<S> ThrottledStream<S> {
+    fn new(
+        inner: S,
+        throttle: Option<Arc<OutboundThrottle>>,
+        connection: Option<OwnedSemaphorePermit>,
+    ) -> Self {
+        Self {
+            inner,
+            throttle,
+            read_wait: None,
+            write_wait: None,
+            _connection: connection,
+        }
+    }
+}
+
+/// Takes up to `wanted` bytes from `throttle`, waiting with `wait` until any
+/// are left.
+fn poll_take(
+    throttle: &OutboundThrottle,
+    wait: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut std::task::Context<'_>,
+    wanted: usize,
+) -> Poll<usize> {
+    loop {
+        if let Some(sleep) = wait {
+            ready!(sleep.as_mut().poll(cx));
+            *wait = None;
+        }
+        match throttle.take(wanted) {
+            Ok(taken) => return Poll::Ready(taken),
+            Err(delay) => *wait = Some(Box::pin(tokio::time::sleep(delay))),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ThrottledStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let Some(throttle) = &this.throttle else {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        };
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let taken = ready!(poll_take(
+            throttle,
+            &mut this.read_wait,
+            cx,
+            buf.remaining()
+        ));
+        let mut limited = ReadBuf::new(buf.initialize_unfilled_to(taken));
+        let result = Pin::new(&mut this.inner).poll_read(cx, &mut limited);
+        let read = limited.filled().len();
+        buf.advance(read);
+        throttle.give_back(taken - read);
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ThrottledStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(throttle) = &this.throttle else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+        if buf.is_empty() {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        let taken = ready!(poll_take(throttle, &mut this.write_wait, cx, buf.len()));
+        let result = Pin::new(&mut this.inner).poll_write(cx, &buf[..taken]);
+        let written = match &result {
+            Poll::Ready(Ok(written)) => *written,
+            _ => 0,
+        };
+        throttle.give_back(taken - written);
+        result
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Sends an outgoing request of a component, if `policy` allows its host,
+/// over a connection of `pool` or to the addresses `resolver` resolved the
+/// host to, within the limits of the policy, retrying as `resilience` says.
+pub(crate) fn send_request(
+    resolver: Arc<Resolver>,
+    pool: Arc<ConnectionPool>,
+    resilience: Arc<OutboundResilience>,
+    policy: Arc<EgressPolicy>,
+    request: hyper::Request<HyperOutgoingBody>,
+    mut config: OutgoingRequestConfig,
+) -> HostFutureIncomingResponse {
+    pool.config().cap_timeouts(&mut config);
+    let handle = wasmtime_wasi::runtime::spawn(async move {
+        Ok(send(&resolver, &pool, &resilience, &policy, request, config).await)
+    });
+    HostFutureIncomingResponse::pending(handle)
+}
+
+async fn send(
+    resolver: &Resolver,
+    pool: &Arc<ConnectionPool>,
+    resilience: &OutboundResilience,
+    policy: &EgressPolicy,
+    mut request: hyper::Request<HyperOutgoingBody>,
+    config: OutgoingRequestConfig,
+) -> Result<IncomingResponse, ErrorCode> {
+    let limits = &policy.limits;
+    let now = tokio::time::Instant::now();
+    if let Some(deadline) = policy.deadline {
+        let remaining = deadline.saturating_duration_since(now);
+        if remaining.is_zero() {
+            return Err(ErrorCode::HttpResponseTimeout);
+        }
+        super::deadline::set_remaining(request.headers_mut(), remaining);
+    }
+    let deadline = limits
+        .timeout_ms
+        .map(|ms| now + Duration::from_millis(ms))
+        .into_iter()
+        .chain(policy.deadline)
+        .min();
+    let between_bytes_timeout = config.between_bytes_timeout;
+    let exchange = follow_redirects(resolver, pool, resilience, policy, request, config);
+    let resp = match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, exchange)
+            .await
+            .map_err(|_| ErrorCode::HttpResponseTimeout)??,
+        None => exchange.await?,
+    };
+
+    if let Some(max) = limits.max_response_bytes
+        && let Some(len) = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+        && len > max
+    {
+        return Err(ErrorCode::HttpResponseBodySize(Some(len)));
+    }
+    let resp = if deadline.is_some() || limits.max_response_bytes.is_some() {
+        resp.map(|body| {
+            LimitedResponseBody {
+                inner: body,
+                read: 0,
+                max_bytes: limits.max_response_bytes,
+                deadline: deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline))),
+                failed: false,
+            }
+            .boxed_unsync()
+        })
+    } else {
+        resp
+    };
+    Ok(IncomingResponse {
+        resp,
+        // The connection is held by the body, see `PooledBody`
+        worker: None,
+        between_bytes_timeout,
+    })
+}
+
+/// Sends `request`, then the requests of up to `max_redirects` redirects it
+/// answers with. Every redirect is checked against the policy, signed and
+/// retried like the first request.
+async fn follow_redirects(
+    resolver: &Resolver,
+    pool: &Arc<ConnectionPool>,
+    resilience: &OutboundResilience,
+    policy: &EgressPolicy,
+    mut request: hyper::Request<HyperOutgoingBody>,
+    OutgoingRequestConfig {
+        mut use_tls,
+        connect_timeout,
+        first_byte_timeout,
+        ..
+    }: OutgoingRequestConfig,
+) -> Result<hyper::Response<HyperIncomingBody>, ErrorCode> {
+    let mut redirects = 0;
+    loop {
+        if let Some(rule) = request.uri().host().and_then(|host| policy.signer(host))
+            && policy.allows_request(request.method(), request.uri())
+        {
+            request = rule.sign(request).await?;
+        }
+        let sent = (redirects < policy.limits.max_redirects).then(|| SentRequest {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            headers: request.headers().clone(),
+            bodyless: request.body().is_end_stream()
+                || request.body().size_hint().exact() == Some(0),
+        });
+        let resp = resilience
+            .send(request, |request| {
+                send_once(
+                    resolver,
+                    pool,
+                    policy,
+                    request,
+                    use_tls,
+                    connect_timeout,
+                    first_byte_timeout,
+                )
+            })
+            .await?;
+        let Some(next) = sent.and_then(|sent| sent.redirect(&resp)) else {
+            return Ok(resp);
+        };
+        redirects += 1;
+        debug!(
+            status = resp.status().as_u16(),
+            location = %next.uri(),
+            redirects,
+            "following redirect of outgoing request"
+        );
+        use_tls = next.uri().scheme() == Some(&Scheme::HTTPS);
+        request = next;
+    }
+}
+
+/// A request that was sent, to follow the redirect it is answered with.
+struct SentRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    /// The request had no body, so it can be sent again as it was
+    bodyless: bool,
+}
+
+impl SentRequest {
+    /// Returns the request following `resp`, if it is a redirect that can be
+    /// followed: its location is an `http` or `https` URL, which is not
+    /// `http` after `https`, and the request can be sent again without its
+    /// body.
+    fn redirect<B>(self, resp: &hyper::Response<B>) -> Option<hyper::Request<HyperOutgoingBody>> {
+        let Self {
+            method,
+            uri,
+            mut headers,
+            bodyless,
+        } = self;
+        let next_method = match resp.status().as_u16() {
+            301 | 302 if method == Method::POST => Method::GET,
+            303 if method != Method::HEAD => Method::GET,
+            301 | 302 | 303 | 307 | 308 => method.clone(),
+            _ => return None,
+        };
+        let body_dropped = next_method != method;
+        if !body_dropped && !bodyless && method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+        let location = resp.headers().get(LOCATION)?.to_str().ok()?;
+        let next = resolve_location(&uri, location)?;
+        if uri.scheme() == Some(&Scheme::HTTPS) && next.scheme() != Some(&Scheme::HTTPS) {
+            return None;
+        }
+
+        // Credentials only go to the origin they were meant for
+        if (next.scheme(), next.authority()) != (uri.scheme(), uri.authority()) {
+            headers.remove(AUTHORIZATION);
+            headers.remove(PROXY_AUTHORIZATION);
+            headers.remove(COOKIE);
+        }
+        if body_dropped {
+            headers.remove(CONTENT_LENGTH);
+            headers.remove(CONTENT_TYPE);
+            headers.remove(TRANSFER_ENCODING);
+        }
+        if let Ok(host) = HeaderValue::from_str(next.authority()?.as_str()) {
+            headers.insert(HOST, host);
+        }
+        let mut request = hyper::Request::new(HyperOutgoingBody::default());
+        *request.method_mut() = next_method;
+        *request.uri_mut() = next;
+        *request.headers_mut() = headers;
+        Some(request)
+    }
+}
+
+/// Resolves the `location` of a redirect against the URL of the request.
+fn resolve_location(base: &Uri, location: &str) -> Option<Uri> {
+    let scheme = base.scheme()?;
+    let absolute = if location.contains("://") {
+        location.to_string()
+    } else if let Some(rest) = location.strip_prefix("//") {
+        format!("{scheme}://{rest}")
+    } else if location.starts_with('/') {
+        format!("{scheme}://{}{location}", base.authority()?)
+    } else {
+        let dir = base.path().rsplit_once('/').map_or("", |(dir, _)| dir);
+        format!("{scheme}://{}{dir}/{location}", base.authority()?)
+    };
+    let uri: Uri = absolute.parse().ok()?;
+    match uri.scheme() {
+        Some(scheme) if *scheme == Scheme::HTTP || *scheme == Scheme::HTTPS => Some(uri),
+        _ => None,
+    }
+}
+
+/// Sends a single request, without following redirects.
+async fn send_once(
+    resolver: &Resolver,
+    pool: &Arc<ConnectionPool>,
+    policy: &EgressPolicy,
+    mut request: hyper::Request<HyperOutgoingBody>,
+    use_tls: bool,
+    connect_timeout: Duration,
+    first_byte_timeout: Duration,
+) -> Result<hyper::Response<HyperIncomingBody>, ErrorCode> {
+    let authority = request
+        .uri()
+        .authority()
+        .ok_or(ErrorCode::HttpRequestUriInvalid)?;
+    let host = authority.host().trim_matches(['[', ']']).to_string();
+    let port = authority
+        .port_u16()
+        .unwrap_or(if use_tls { 443 } else { 80 });
+    if !policy.allows_request(request.method(), request.uri()) {
+        let reason = if policy.denies_host(&host, port) {
+            "denied by allowed_hosts"
+        } else {
+            "not in allowed_hosts"
+        };
+        warn!(
+            method = %request.method(),
+            uri = %request.uri(),
+            host,
+            port,
+            "denied outgoing request to a host or URL {reason}"
+        );
+        return Err(ErrorCode::HttpRequestDenied);
+    }
+    let method = request.method().clone();
+
+    // The scheme and authority only belong in requests to proxies
+    *request.uri_mut() = Uri::builder()
+        .path_and_query(
+            request
+                .uri()
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or("/"),
+        )
+        .build()
+        .expect("comes from a valid request");
+
+    let key = PoolKey {
+        tls: use_tls,
+        host: host.clone(),
+        port,
+    };
+    let checkout = pool
+        .checkout(key.clone(), connect_timeout)
+        .await
+        .inspect_err(|_| {
+            debug!(
+                host,
+                port, "refused outgoing request beyond the connections to its host"
+            );
+        })?;
+    // Connections counted against the outbound limits of a workload are its own
+    let pooled = policy.throttle.is_none();
+    let denied = &resolver.config.denied_networks;
+    while pooled
+        && let Some(mut connection) = pool.take_idle(&key, |ip| policy.allows_ip(ip, port, denied))
+    {
+        if !matches!(
+            tokio::time::timeout(connect_timeout, connection.sender.ready()).await,
+            Ok(Ok(()))
+        ) {
+            continue;
+        }
+        let sent = tokio::time::timeout(
+            first_byte_timeout,
+            connection.sender.try_send_request(request),
+        )
+        .await
+        .map_err(|_| ErrorCode::ConnectionReadTimeout)?;
+        match sent {
+            Ok(resp) => return Ok(PooledBody::response(resp, connection, checkout, true)),
+            // The server closed the connection before the request was sent
+            Err(mut e) => match e.take_message() {
+                Some(unsent) => request = unsent,
+                None => return Err(hyper_request_error(e.into_error())),
+            },
+        }
+    }
+
+    let addrs = tokio::time::timeout(connect_timeout, resolver.resolve(&host))
+        .await
+        .map_err(|_| ErrorCode::DnsTimeout)?
+        .map_err(|e| {
+            ErrorCode::DnsError(DnsErrorPayload {
+                rcode: Some(format!("{e:#}")),
+                info_code: Some(0),
+            })
+        })?;
+    // The checked addresses are the ones connected to
+    let addrs: Vec<IpAddr> = addrs
+        .into_iter()
+        .filter(|ip| policy.allows_ip(*ip, port, denied))
+        .collect();
+    if addrs.is_empty() {
+        warn!(
+            %method,
+            host,
+            port,
+            "denied outgoing request to a denied network"
+        );
+        return Err(ErrorCode::DestinationIpProhibited);
+    }
+    let limit = match &policy.throttle {
+        Some(throttle) => throttle.open_connection().inspect_err(|_| {
+            debug!(
+                host,
+                "refused outgoing request beyond the connections of the workload"
+            );
+        })?,
+        None => None,
+    };
+    let stream = tokio::time::timeout(connect_timeout, connect(&addrs, port))
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(|_| ErrorCode::ConnectionRefused)?;
+    let ip = stream
+        .peer_addr()
+        .map_err(|_| ErrorCode::ConnectionRefused)?
+        .ip();
+    let stream = ThrottledStream::new(stream, policy.throttle.clone(), limit);
+
+    let (sender, worker) = if use_tls {
+        let stream = tls_connect(&host, stream).await?;
+        handshake(stream, connect_timeout).await?
+    } else {
+        handshake(stream, connect_timeout).await?
+    };
+    pool.opened();
+    let mut connection = Connection {
+        sender,
+        _worker: worker,
+        ip,
+    };
+
+    let resp = tokio::time::timeout(first_byte_timeout, connection.sender.send_request(request))
+        .await
+        .map_err(|_| ErrorCode::ConnectionReadTimeout)?
+        .map_err(hyper_request_error)?;
+    Ok(PooledBody::response(resp, connection, checkout, pooled))
+}
+
+/// A response body holding the connection it is read from, which goes back to
+/// the [`ConnectionPool`] once the body is read, if it may be reused, and is
+/// closed otherwise.
+struct PooledBody {
+    inner: hyper::body::Incoming,
+    connection: Option<(Connection, Checkout)>,
+    reusable: bool,
+}
+
+impl PooledBody {
+    fn response(
+        resp: hyper::Response<hyper::body::Incoming>,
+        connection: Connection,
+        checkout: Checkout,
+        reusable: bool,
+    ) -> hyper::Response<HyperIncomingBody> {
+        resp.map(|inner| {
+            let mut body = Self {
+                inner,
+                connection: Some((connection, checkout)),
+                reusable,
+            };
+            if body.inner.is_end_stream() {
+                body.release();
+            }
+            body.boxed_unsync()
+        })
+    }
+
+    fn release(&mut self) {
+        if let Some((connection, checkout)) = self.connection.take()
+            && self.reusable
+        {
+            checkout.release(connection);
+        }
+    }
+}
+
+impl Body for PooledBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        let this = self.get_mut();
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        match &frame {
+            None => this.release(),
+            Some(Ok(_)) if this.inner.is_end_stream() => this.release(),
+            // The connection is closed with the body
+            Some(Err(_)) => this.reusable = false,
+            Some(Ok(_)) => {}
+        }
+        Poll::Ready(frame.map(|frame| frame.map_err(hyper_request_error)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// A response body held to the [`OutgoingHttpLimits`] of a component.
+struct LimitedResponseBody {
+    inner: HyperIncomingBody,
+    /// Bytes of the body read so far
+    read: u64,
+    max_bytes: Option<u64>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    failed: bool,
+}
+
+impl Body for LimitedResponseBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+        if let Some(deadline) = this.deadline.as_mut()
+            && deadline.as_mut().poll(cx).is_ready()
+        {
+            this.failed = true;
+            return Poll::Ready(Some(Err(ErrorCode::HttpResponseTimeout)));
+        }
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            this.read += data.len() as u64;
+            if this.max_bytes.is_some_and(|max| this.read > max) {
+                this.failed = true;
+                return Poll::Ready(Some(Err(ErrorCode::HttpResponseBodySize(Some(this.read)))));
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.failed || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Connects to the first of `addrs` that accepts the connection.
+async fn connect(addrs: &[IpAddr], port: u16) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for ip in addrs {
+        match TcpStream::connect((*ip, port)).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::Error::other("no addresses to connect to")))
+}
+
+/// Trusts the web PKI, with the process default crypto provider, see
+/// [`crate::host::tls`]
+static TLS_CONNECTOR: LazyLock<tokio_rustls::TlsConnector> = LazyLock::new(|| {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+    };
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tokio_rustls::TlsConnector::from(Arc::new(config))
+});
+
+async fn tls_connect<S>(
+    host: &str,
+    stream: S,
+) -> Result<tokio_rustls::client::TlsStream<S>, ErrorCode>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let name =
+        ServerName::try_from(host.to_string()).map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+    TLS_CONNECTOR.connect(name, stream).await.map_err(|e| {
+        debug!(host, err = %e, "TLS handshake of outgoing request failed");
+        ErrorCode::TlsProtocolError
+    })
+}
+
+async fn handshake<S>(
+    stream: S,
+    connect_timeout: Duration,
+) -> Result<(SendRequest<HyperOutgoingBody>, AbortOnDropJoinHandle<()>), ErrorCode>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, conn) = tokio::time::timeout(
+        connect_timeout,
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)),
+    )
+    .await
+    .map_err(|_| ErrorCode::ConnectionTimeout)?
+    .map_err(hyper_request_error)?;
+    let worker = wasmtime_wasi::runtime::spawn(async move {
+        if let Err(e) = conn.await {
+            debug!(err = %e, "outgoing HTTP connection failed");
+        }
+    });
+    Ok((sender, worker))
+}
+
+/// The addresses in a DNS response
+#[derive(Debug, PartialEq, Eq)]
+struct Answer {
+    addrs: Vec<IpAddr>,
+    /// Lowest TTL of the addresses, in seconds
+    ttl: u32,
+    /// The server truncated the response to fit a UDP datagram
+    truncated: bool,
+}
+
+async fn query_udp(server: SocketAddr, host: &str, qtype: u16) -> anyhow::Result<Answer> {
+    let id = query_id();
+    let query = encode_query(id, host, qtype)?;
+    let local: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(&query).await?;
+
+    let mut buf = vec![0u8; 4096];
+    let answer = tokio::time::timeout(DNS_TIMEOUT, async {
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            // Datagrams answering another query are ignored
+            if let Some(answer) = decode_response(id, &buf[..len])? {
+                return anyhow::Ok(answer);
+            }
+        }
+    })
+    .await
+    .with_context(|| format!("timed out waiting for DNS server {server}"))??;
+    if !answer.truncated {
+        return Ok(answer);
+    }
+
+    // Truncated answers are queried again over TCP
+    let mut stream = tokio::time::timeout(DNS_TIMEOUT, TcpStream::connect(server))
+        .await
+        .with_context(|| format!("timed out connecting to DNS server {server}"))??;
+    let response = tokio::time::timeout(DNS_TIMEOUT, async {
+        stream.write_u16(query.len() as u16).await?;
+        stream.write_all(&query).await?;
+        let len = stream.read_u16().await?;
+        let mut response = vec![0u8; len as usize];
+        stream.read_exact(&mut response).await?;
+        anyhow::Ok(response)
+    })
+    .await
+    .with_context(|| format!("timed out waiting for DNS server {server}"))??;
+    decode_response(id, &response)?.context("DNS server answered another query")
+}
+
+/// Queries a DNS over HTTPS server for the IPv4 and IPv6 addresses of `host`.
+async fn query_doh(url: &Uri, host: &str) -> anyhow::Result<(Vec<IpAddr>, u64)> {
+    let server = url.host().context("DNS over HTTPS URL has no host")?;
+    let server = server.trim_matches(['[', ']']);
+    let port = url.port_u16().unwrap_or(443);
+    let addrs: Vec<IpAddr> = tokio::net::lookup_host((server, port))
+        .await
+        .with_context(|| format!("failed to resolve DNS over HTTPS server {server}"))?
+        .map(|addr| addr.ip())
+        .collect();
+    let stream = tokio::time::timeout(DNS_TIMEOUT, connect(&addrs, port))
+        .await
+        .context("timed out connecting to DNS over HTTPS server")??;
+    let stream = tls_connect(server, stream)
+        .await
+        .map_err(|e| anyhow::anyhow!("TLS handshake with DNS over HTTPS server failed: {e:?}"))?;
+    let (mut sender, conn) =
+        hyper::client::conn::http1::handshake::<_, Full<Bytes>>(TokioIo::new(stream)).await?;
+    let _worker = wasmtime_wasi::runtime::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        // RFC 8484 recommends an ID of 0, so responses can be cached
+        let query = encode_query(0, host, qtype)?;
+        let request = hyper::Request::post(url.path_and_query().map_or("/", |p| p.as_str()))
+            .header(
+                hyper::header::HOST,
+                url.authority().map_or(server, |a| a.as_str()),
+            )
+            .header(hyper::header::CONTENT_TYPE, "application/dns-message")
+            .header(hyper::header::ACCEPT, "application/dns-message")
+            .body(Full::new(Bytes::from(query)))?;
+        sender.ready().await?;
+        let response = tokio::time::timeout(DNS_TIMEOUT, sender.send_request(request))
+            .await
+            .context("timed out waiting for DNS over HTTPS server")??;
+        let status = response.status();
+        let body = tokio::time::timeout(DNS_TIMEOUT, response.into_body().collect())
+            .await
+            .context("timed out waiting for DNS over HTTPS server")??
+            .to_bytes();
+        if !status.is_success() {
+            bail!("DNS over HTTPS server answered with status {status}");
+        }
+        let answer = decode_response(0, &body)?.context("DNS over HTTPS server sent no answer")?;
+        addrs.extend(answer.addrs);
+        ttl = ttl.min(answer.ttl);
+    }
+    Ok((addrs, ttl.into()))
+}
+
+fn query_id() -> u16 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// Encodes a recursive query for the `qtype` records of `name`.
+fn encode_query(id: u16, name: &str, qtype: u16) -> anyhow::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        ensure!(
+            !label.is_empty() && label.len() <= 63,
+            "invalid host name '{name}'"
+        );
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    // Class IN
+    query.extend_from_slice(&[0, 1]);
+    Ok(query)
+}
+
+/// Decodes the A and AAAA records of a response to the query `id`. Returns
+/// `None` for a response to another query.
+fn decode_response(id: u16, response: &[u8]) -> anyhow::Result<Option<Answer>> {
+    ensure!(response.len() >= 12, "truncated DNS response");
+    let be16 = |pos: usize| u16::from_be_bytes([response[pos], response[pos + 1]]);
+    let is_response = response[2] & 0x80 != 0;
+    if be16(0) != id || !is_response {
+        return Ok(None);
+    }
+    let truncated = response[2] & 0x02 != 0;
+    // NXDOMAIN is an answer without addresses
+    let rcode = response[3] & 0x0f;
+    ensure!(
+        rcode == 0 || rcode == 3,
+        "DNS server answered with response code {rcode}"
+    );
+
+    let mut pos = 12;
+    for _ in 0..be16(4) {
+        pos = skip_name(response, pos)? + 4;
+    }
+    let mut answer = Answer {
+        addrs: Vec::new(),
+        ttl: u32::MAX,
+        truncated,
+    };
+    for _ in 0..be16(6) {
+        pos = skip_name(response, pos)?;
+        let record = response
+            .get(pos..pos + 10)
+            .context("truncated DNS response")?;
+        let rtype = u16::from_be_bytes([record[0], record[1]]);
+        let ttl = u32::from_be_bytes([record[4], record[5], record[6], record[7]]);
+        let len = u16::from_be_bytes([record[8], record[9]]) as usize;
+        let data = response
+            .get(pos + 10..pos + 10 + len)
+            .context("truncated DNS response")?;
+        pos += 10 + len;
+        let addr = match (rtype, <[u8; 4]>::try_from(data), <[u8; 16]>::try_from(data)) {
+            (TYPE_A, Ok(v4), _) => IpAddr::from(v4),
+            (TYPE_AAAA, _, Ok(v6)) => IpAddr::from(v6),
+            // CNAMEs and other records
+            _ => continue,
+        };
+        answer.addrs.push(addr);
+        answer.ttl = answer.ttl.min(ttl);
+    }
+    Ok(Some(answer))
+}
+
+/// Returns the position after the name at `pos`.
+fn skip_name(response: &[u8], mut pos: usize) -> anyhow::Result<usize> {
+    loop {
+        match *response.get(pos).context("truncated DNS response")? {
+            0 => return Ok(pos + 1),
+            // A pointer to a previous name ends the name
+            len if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_egress_policy() {
+        let policy = EgressPolicy::new(&[
+            "api.example.com".to_string(),
+            "*.wasmcloud.dev".to_string(),
+            "10.1.0.0/16".to_string(),
+        ]);
+        assert!(policy.allows_host("api.example.com", 443));
+        assert!(policy.allows_host("API.example.com.", 80));
+        assert!(!policy.allows_host("example.com", 443));
+        assert!(policy.allows_host("cdn.wasmcloud.dev", 443));
+        assert!(!policy.allows_host("wasmcloud.dev", 443));
+        assert!(!policy.allows_host("evilwasmcloud.dev", 443));
+        assert!(policy.allows_host("10.1.2.3", 443));
+        assert!(!policy.allows_host("10.2.0.1", 443));
+
+        let denied = [
+            "10.0.0.0/8".parse().unwrap(),
+            "169.254.169.254".parse().unwrap(),
+        ];
+        assert!(policy.allows_ip(ip("93.184.216.34"), 443, &denied));
+        assert!(!policy.allows_ip(ip("169.254.169.254"), 80, &denied));
+        // Listed networks are allowed even inside denied ones
+        assert!(policy.allows_ip(ip("10.1.2.3"), 443, &denied));
+        assert!(!policy.allows_ip(ip("10.2.0.1"), 443, &denied));
+
+        assert!(EgressPolicy::default().allows_host("anything.example", 443));
+        assert!(EgressPolicy::new(&["*".to_string()]).allows_host("192.0.2.1", 443));
+    }
+
+    #[test]
+    fn test_egress_ports_and_denials() {
+        let policy = EgressPolicy::new(&[
+            "api.example.com:443".to_string(),
+            "*.example.com".to_string(),
+            "[2001:db8::/32]:8443".to_string(),
+            "2001:db9::1".to_string(),
+            "!internal.example.com".to_string(),
+            "!*.corp.example.com:22".to_string(),
+            "!192.0.2.0/24".to_string(),
+        ]);
+        assert!(policy.allows_host("api.example.com", 443));
+        assert!(!policy.allows_host("api.example.com", 8080));
+        assert!(policy.allows_host("cdn.example.com", 8080));
+        assert!(!policy.allows_host("internal.example.com", 443));
+        assert!(policy.denies_host("internal.example.com", 443));
+        assert!(policy.allows_host("git.corp.example.com", 443));
+        assert!(!policy.allows_host("git.corp.example.com", 22));
+        assert!(policy.allows_host("[2001:db8::1]", 8443));
+        assert!(!policy.allows_host("2001:db8::1", 443));
+        assert!(policy.allows_host("2001:db9::1", 443));
+
+        // Denied networks apply to resolved addresses
+        assert!(policy.allows_ip(ip("93.184.216.34"), 443, &[]));
+        assert!(!policy.allows_ip(ip("192.0.2.10"), 443, &[]));
+        // Denials win over URL patterns
+        let policy = EgressPolicy::new(&[
+            "https://*.example.com/*".to_string(),
+            "!admin.example.com".to_string(),
+        ]);
+        let allows = |uri: &str| policy.allows_request(&Method::GET, &uri.parse().unwrap());
+        assert!(allows("https://api.example.com/v1"));
+        assert!(!allows("https://admin.example.com/v1"));
+
+        // Only denials allow every other host, unless the host denies by default
+        let policy = EgressPolicy::new(&["!internal.example.com".to_string()]);
+        assert!(policy.allows_host("example.org", 443));
+        assert!(!policy.allows_host("internal.example.com", 443));
+        let policy = policy.with_default(EgressDefault::Deny);
+        assert!(!policy.allows_host("example.org", 443));
+        assert!(
+            !EgressPolicy::default()
+                .with_default(EgressDefault::Deny)
+                .allows_host("example.org", 443)
+        );
+        assert!(
+            EgressPolicy::new(&["example.org".to_string()])
+                .with_default(EgressDefault::Deny)
+                .allows_host("example.org", 443)
+        );
+
+        let policy = EgressPolicy::new(&["example.org:http".to_string(), "[::1".to_string()]);
+        assert!(policy.allowed.is_empty());
+    }
+
+    #[test]
+    fn test_url_patterns() {
+        let policy = EgressPolicy::new(&[
+            "https://generativelanguage.googleapis.com/v1beta/*".to_string(),
+            "GET,head http://*.example.com:8080/status".to_string(),
+            "ftp://example.com/*".to_string(),
+        ]);
+        assert_eq!(policy.patterns.len(), 2);
+        let allows =
+            |method: Method, uri: &str| policy.allows_request(&method, &uri.parse().unwrap());
+
+        assert!(allows(
+            Method::POST,
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini:generateContent?key=k"
+        ));
+        assert!(allows(
+            Method::GET,
+            "https://GenerativeLanguage.googleapis.com:443/v1beta/"
+        ));
+        assert!(!allows(
+            Method::GET,
+            "https://generativelanguage.googleapis.com/v1/models"
+        ));
+        assert!(!allows(
+            Method::GET,
+            "http://generativelanguage.googleapis.com/v1beta/x"
+        ));
+        assert!(!allows(
+            Method::GET,
+            "https://generativelanguage.googleapis.com:8443/v1beta/x"
+        ));
+        // Paths that could leave the pattern on the server
+        assert!(!allows(
+            Method::GET,
+            "https://generativelanguage.googleapis.com/v1beta/../admin"
+        ));
+        assert!(!allows(
+            Method::GET,
+            "https://generativelanguage.googleapis.com/v1beta/%2E%2E/admin"
+        ));
+
+        assert!(allows(Method::HEAD, "http://api.example.com:8080/status"));
+        assert!(!allows(Method::POST, "http://api.example.com:8080/status"));
+        assert!(!allows(
+            Method::GET,
+            "http://api.example.com:8080/status/more"
+        ));
+        assert!(!allows(Method::GET, "http://example.com:8080/status"));
+        // Patterns don't allow their hosts as a whole
+        assert!(!policy.allows_host("generativelanguage.googleapis.com", 443));
+
+        assert!(glob_matches("/a/*/c/*", "/a/b/c/d/e"));
+        assert!(!glob_matches("/a/*/c", "/a/b/d"));
+        assert!(glob_matches("/*.json", "/x/y.json"));
+    }
+
+    /// Answers A queries for every name with `addr`
+    async fn dns_server(addr: [u8; 4]) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let query = &buf[..len];
+                let is_a = query[len - 4..len - 2] == TYPE_A.to_be_bytes();
+                let mut response = query[..2].to_vec();
+                response.extend_from_slice(&[0x81, 0x80, 0, 1, 0, is_a as u8, 0, 0, 0, 0]);
+                response.extend_from_slice(&query[12..]);
+                if is_a {
+                    // Pointer to the question name, A, IN, TTL 60
+                    response.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                    response.extend_from_slice(&addr);
+                }
+                let _ = socket.send_to(&response, peer).await;
+            }
+        });
+        server
+    }
+
+    #[tokio::test]
+    async fn test_resolver() {
+        let resolver = Resolver::new(ResolverConfig {
+            nameservers: vec![dns_server([192, 0, 2, 7]).await],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            resolver.resolve("api.example.com").await.unwrap(),
+            vec![ip("192.0.2.7")]
+        );
+        assert!(resolver.cache().contains_key("api.example.com"));
+        assert_eq!(
+            resolver.resolve("[2001:db8::1]").await.unwrap(),
+            vec![ip("2001:db8::1")]
+        );
+
+        assert!(
+            Resolver::new(ResolverConfig {
+                doh_url: Some("http://1.1.1.1/dns-query".to_string()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_deadline() {
+        let resolver = Resolver::new(ResolverConfig::default()).unwrap();
+        let policy = EgressPolicy::default().with_deadline(tokio::time::Instant::now());
+        let request = hyper::Request::get("http://api.example.com/")
+            .body(HyperOutgoingBody::default())
+            .unwrap();
+        let config = OutgoingRequestConfig {
+            use_tls: false,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+        assert!(matches!(
+            send(
+                &resolver,
+                &Arc::default(),
+                &OutboundResilience::default(),
+                &policy,
+                request,
+                config
+            )
+            .await,
+            Err(ErrorCode::HttpResponseTimeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pooled_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        tokio::spawn({
+            let accepted = accepted.clone();
+            async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    accepted.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let service = hyper::service::service_fn(|_| async {
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(Full::new(
+                            Bytes::from_static(b"ok"),
+                        )))
+                    });
+                    tokio::spawn(
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service),
+                    );
+                }
+            }
+        });
+
+        let resolver = Resolver::default();
+        let pool = Arc::new(ConnectionPool::default());
+        let policy = EgressPolicy::new(&["127.0.0.1".to_string()]);
+        let config = || OutgoingRequestConfig {
+            use_tls: false,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+        for _ in 0..3 {
+            let request = hyper::Request::get(format!("http://{addr}/"))
+                .body(HyperOutgoingBody::default())
+                .unwrap();
+            let resp = send(
+                &resolver,
+                &pool,
+                &OutboundResilience::default(),
+                &policy,
+                request,
+                config(),
+            )
+            .await
+            .unwrap();
+            let body = resp.resp.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], b"ok");
+        }
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::Relaxed), 1);
+        let stats = pool.stats();
+        assert_eq!((stats.opened, stats.reused), (1, 2));
+        assert_eq!((stats.active, stats.idle), (0, 1));
+    }
+
+    fn sent(method: Method, uri: &str, bodyless: bool) -> SentRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        SentRequest {
+            method,
+            uri: uri.parse().unwrap(),
+            headers,
+            bodyless,
+        }
+    }
+
+    fn redirect(status: u16, location: &str) -> hyper::Response<()> {
+        hyper::Response::builder()
+            .status(status)
+            .header(LOCATION, location)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_redirect() {
+        let next = sent(Method::POST, "https://api.example.com/a/b", false)
+            .redirect(&redirect(302, "c?d=e"))
+            .unwrap();
+        assert_eq!(next.method(), Method::GET);
+        assert_eq!(next.uri(), "https://api.example.com/a/c?d=e");
+        assert!(next.headers().contains_key(AUTHORIZATION));
+        assert!(!next.headers().contains_key(CONTENT_TYPE));
+
+        // Credentials don't follow a redirect to another origin
+        let next = sent(Method::GET, "https://api.example.com/", true)
+            .redirect(&redirect(301, "https://cdn.example.com/x"))
+            .unwrap();
+        assert!(!next.headers().contains_key(AUTHORIZATION));
+        assert_eq!(next.headers()[HOST], "cdn.example.com");
+
+        let next = sent(Method::PUT, "http://api.example.com/a", true)
+            .redirect(&redirect(308, "//api.example.com/b"))
+            .unwrap();
+        assert_eq!(next.method(), Method::PUT);
+        assert_eq!(next.uri(), "http://api.example.com/b");
+
+        // The body of the request is gone
+        assert!(
+            sent(Method::PUT, "http://api.example.com/a", false)
+                .redirect(&redirect(307, "/b"))
+                .is_none()
+        );
+        // Downgrade to plain HTTP
+        assert!(
+            sent(Method::GET, "https://api.example.com/", true)
+                .redirect(&redirect(302, "http://api.example.com/"))
+                .is_none()
+        );
+        assert!(
+            sent(Method::GET, "https://api.example.com/", true)
+                .redirect(&redirect(302, "file:///etc/passwd"))
+                .is_none()
+        );
+        assert!(
+            sent(Method::GET, "https://api.example.com/", true)
+                .redirect(&redirect(304, "/b"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_outbound_throttle() {
+        assert!(OutboundThrottle::new(&OutboundLimits::default()).is_none());
+
+        let throttle = OutboundThrottle::new(&OutboundLimits {
+            max_connections: Some(1),
+            max_bytes_per_sec: None,
+        })
+        .unwrap();
+        let first = throttle.open_connection().unwrap();
+        assert!(first.is_some());
+        assert!(matches!(
+            throttle.open_connection(),
+            Err(ErrorCode::ConnectionLimitReached)
+        ));
+        drop(first);
+        assert!(throttle.open_connection().unwrap().is_some());
+        assert_eq!(throttle.take(1 << 20), Ok(1 << 20));
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, start);
+        assert_eq!(bucket.take(5000, start), Ok(1000));
+        assert_eq!(bucket.take(1, start), Err(Duration::from_millis(1)));
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(bucket.take(5000, later), Ok(250));
+        bucket.give_back(100);
+        assert_eq!(bucket.take(5000, later), Ok(100));
+
+        // Refills up to a second's worth
+        assert_eq!(bucket.take(5000, later + Duration::from_secs(10)), Ok(1000));
+    }
+
+    #[tokio::test]
+    async fn test_throttled_stream() {
+        let throttle = OutboundThrottle::new(&OutboundLimits {
+            max_connections: None,
+            max_bytes_per_sec: Some(64),
+        });
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut stream = ThrottledStream::new(client, throttle, None);
+
+        // A write sends no more than the bandwidth left
+        let written = stream.write(&[0; 100]).await.unwrap();
+        assert_eq!(written, 64);
+        let mut buf = [0; 100];
+        assert_eq!(server.read(&mut buf).await.unwrap(), 64);
+
+        // Reading waits for the bandwidth to refill
+        server.write_all(&[1; 10]).await.unwrap();
+        let started = Instant::now();
+        stream.read_exact(&mut buf[..10]).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(&buf[..10], &[1; 10]);
+    }
+
+    #[tokio::test]
+    async fn test_limited_response_body() {
+        let body = |max_bytes, deadline| LimitedResponseBody {
+            inner: Full::new(Bytes::from_static(b"0123456789"))
+                .map_err(|never| match never {})
+                .boxed_unsync(),
+            read: 0,
+            max_bytes,
+            deadline,
+            failed: false,
+        };
+        assert_eq!(
+            body(Some(10), None).collect().await.unwrap().to_bytes(),
+            "0123456789"
+        );
+        assert!(matches!(
+            body(Some(5), None).collect().await,
+            Err(ErrorCode::HttpResponseBodySize(Some(10)))
+        ));
+
+        let elapsed = Box::pin(tokio::time::sleep(Duration::ZERO));
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert!(matches!(
+            body(None, Some(elapsed)).collect().await,
+            Err(ErrorCode::HttpResponseTimeout)
+        ));
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let query = encode_query(7, "example.com", TYPE_AAAA).unwrap();
+        assert_eq!(&query[12..], b"\x07example\x03com\x00\x00\x1c\x00\x01");
+
+        let mut response = query.clone();
+        response[2] = 0x81;
+        response[7] = 2;
+        // A CNAME, then the address of its target
+        response.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 1, 0, 0, 2, 0xc0, 12]);
+        response.extend_from_slice(&[0xc0, 12, 0, 28, 0, 1, 0, 0, 0, 30, 0, 16]);
+        response.extend_from_slice(
+            &"2001:db8::1"
+                .parse::<std::net::Ipv6Addr>()
+                .unwrap()
+                .octets(),
+        );
+        assert_eq!(
+            decode_response(7, &response).unwrap(),
+            Some(Answer {
+                addrs: vec![ip("2001:db8::1")],
+                ttl: 30,
+                truncated: false,
+            })
+        );
+        assert_eq!(decode_response(8, &response).unwrap(), None);
+        assert!(decode_response(7, &response[..response.len() - 4]).is_err());
+        assert!(encode_query(7, "bad..name", TYPE_A).is_err());
+    }
+}
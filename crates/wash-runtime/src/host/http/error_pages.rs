@@ -0,0 +1,286 @@
+//! Error responses of the HTTP server.
+//!
+//! The host answers a request itself when no route matches it, when it is
+//! rejected before reaching a component, e.g. for failing authentication or a
+//! too large body, and when the component fails or times out. By default these
+//! answers are bare status codes. [`ErrorPages`] give them a body instead:
+//!
+//! - [`ErrorFormat::Json`]: an RFC 9457 `application/problem+json` document
+//!
+//!   ```json
+//!   { "type": "about:blank", "title": "Gateway Timeout", "status": 504, "request_id": "..." }
+//!   ```
+//!
+//! - [`ErrorFormat::Html`]: a page rendered from an HTML template, in which
+//!   `{{status}}`, `{{title}}` and `{{request_id}}` are replaced by the status
+//!   code, its reason phrase and the request id, HTML-escaped
+//!
+//! Error pages may include the id of the request, taken from its
+//! `X-Request-Id` header or generated by the host, which is also returned in
+//! the `X-Request-Id` header of the response so a client can report it.
+//!
+//! Responses of components are never replaced, whatever their status.
+
+use std::str::FromStr;
+
+use bytes::Bytes;
+use http_body_util::BodyExt as _;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderValue};
+use hyper::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+/// Response header carrying the id of the request an error page refers to
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The HTML template of error pages without one of their own
+pub const DEFAULT_HTML_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>{{status}} {{title}}</title></head>
+<body>
+<h1>{{status}} {{title}}</h1>
+<p>{{request_id}}</p>
+</body>
+</html>
+";
+
+/// The format of the body of error responses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorFormat {
+    /// No body, only the status code
+    #[default]
+    Empty,
+    /// An RFC 9457 `application/problem+json` document
+    Json,
+    /// An HTML page rendered from a template
+    Html,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty" => Ok(Self::Empty),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            _ => anyhow::bail!("unknown error format '{s}', expected empty, json or html"),
+        }
+    }
+}
+
+/// How the host formats the error responses it answers requests with, see
+/// the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ErrorPages {
+    pub format: ErrorFormat,
+    /// Whether error responses carry the id of their request
+    pub include_request_id: bool,
+    /// The template of [`ErrorFormat::Html`] pages, [`DEFAULT_HTML_TEMPLATE`]
+    /// if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html_template: Option<String>,
+}
+
+impl ErrorPages {
+    /// Formats error responses as `format`.
+    pub fn new(format: ErrorFormat) -> Self {
+        Self {
+            format,
+            ..Default::default()
+        }
+    }
+
+    /// Includes the id of the request in error responses.
+    pub fn with_request_id(mut self, include: bool) -> Self {
+        self.include_request_id = include;
+        self
+    }
+
+    /// Renders HTML error pages from `template` instead of
+    /// [`DEFAULT_HTML_TEMPLATE`].
+    pub fn with_html_template(mut self, template: impl Into<String>) -> Self {
+        self.html_template = Some(template.into());
+        self
+    }
+
+    /// Gives a response the host answered a request with, see
+    /// [`host_response`], the body of its error page. The id of the request
+    /// is generated if it has none. Responses of components are left as they
+    /// are.
+    pub(super) fn apply(
+        &self,
+        response: &mut Response<HyperOutgoingBody>,
+        request_id: Option<&str>,
+    ) {
+        if response.extensions_mut().remove::<HostResponse>().is_none() {
+            return;
+        }
+        let generated;
+        let request_id = match (self.include_request_id, request_id) {
+            (false, _) => None,
+            (true, Some(id)) => Some(id),
+            (true, None) => {
+                generated = uuid::Uuid::new_v4().to_string();
+                Some(generated.as_str())
+            }
+        };
+        if let Some(value) = request_id.and_then(|id| HeaderValue::from_str(id).ok()) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+
+        let status = response.status();
+        let (content_type, body) = match self.format {
+            ErrorFormat::Empty => return,
+            ErrorFormat::Json => ("application/problem+json", problem(status, request_id)),
+            ErrorFormat::Html => (
+                "text/html; charset=utf-8",
+                render(
+                    self.html_template
+                        .as_deref()
+                        .unwrap_or(DEFAULT_HTML_TEMPLATE),
+                    status,
+                    request_id,
+                ),
+            ),
+        };
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        *response.body_mut() = http_body_util::Full::new(Bytes::from(body))
+            .map_err(|never| match never {})
+            .boxed();
+    }
+}
+
+/// Marks a response the host answered a request with itself
+#[derive(Debug, Clone, Copy)]
+struct HostResponse;
+
+/// Builds a response with `status` and no body, which [`ErrorPages`] apply to.
+pub(super) fn host_response(status: u16) -> Response<HyperOutgoingBody> {
+    let mut response = Response::builder()
+        .status(status)
+        .body(HyperOutgoingBody::default())
+        .expect("failed to build error response");
+    response.extensions_mut().insert(HostResponse);
+    response
+}
+
+/// Returns the reason phrase of `status`, or the code itself if it has none.
+fn title(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .map_or_else(|| status.as_str().to_string(), str::to_string)
+}
+
+/// Returns the problem details document of an error.
+fn problem(status: StatusCode, request_id: Option<&str>) -> String {
+    let mut problem = serde_json::json!({
+        "type": "about:blank",
+        "title": title(status),
+        "status": status.as_u16(),
+    });
+    if let Some(id) = request_id {
+        problem["request_id"] = id.into();
+    }
+    problem.to_string()
+}
+
+/// Renders the HTML error page of an error from `template`.
+fn render(template: &str, status: StatusCode, request_id: Option<&str>) -> String {
+    template
+        .replace("{{status}}", status.as_str())
+        .replace("{{title}}", &escape_html(&title(status)))
+        .replace(
+            "{{request_id}}",
+            &escape_html(request_id.unwrap_or_default()),
+        )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(response: Response<HyperOutgoingBody>) -> String {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_error_pages() {
+        let pages = ErrorPages::new(ErrorFormat::Json).with_request_id(true);
+        let mut response = host_response(504);
+        pages.apply(&mut response, Some("req-1"));
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-1");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let problem: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+        assert_eq!(
+            problem,
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Gateway Timeout",
+                "status": 504,
+                "request_id": "req-1",
+            })
+        );
+
+        // Requests without an id are given one
+        let mut response = host_response(404);
+        pages.apply(&mut response, None);
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn test_html_error_pages() {
+        let pages = ErrorPages::new(ErrorFormat::Html)
+            .with_request_id(true)
+            .with_html_template("<p>{{status}} {{title}} {{request_id}}</p>");
+        let mut response = host_response(404);
+        pages.apply(&mut response, Some("<script>"));
+        assert_eq!(body(response).await, "<p>404 Not Found &lt;script&gt;</p>");
+
+        // Request ids are left out unless included
+        let mut response = host_response(500);
+        ErrorPages::new(ErrorFormat::Html).apply(&mut response, Some("req-1"));
+        assert!(!response.headers().contains_key(REQUEST_ID_HEADER));
+        let page = body(response).await;
+        assert!(page.contains("<h1>500 Internal Server Error</h1>"));
+        assert!(!page.contains("req-1"));
+    }
+
+    #[tokio::test]
+    async fn test_component_responses_unchanged() {
+        let pages = ErrorPages::new(ErrorFormat::Json).with_request_id(true);
+        let mut response = Response::builder()
+            .status(404)
+            .body(HyperOutgoingBody::default())
+            .unwrap();
+        pages.apply(&mut response, Some("req-1"));
+        assert!(response.headers().is_empty());
+        assert_eq!(body(response).await, "");
+
+        // Bare status codes by default
+        let mut response = host_response(404);
+        ErrorPages::default().apply(&mut response, Some("req-1"));
+        assert!(response.headers().is_empty());
+    }
+}
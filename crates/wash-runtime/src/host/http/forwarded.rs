@@ -0,0 +1,541 @@
+//! Client address resolution behind proxies and load balancers.
+//!
+//! When the host sits behind an L4 load balancer, the TCP peer of every
+//! connection is the balancer itself. The balancer can announce the real client
+//! with the HAProxy [PROXY protocol] (v1 or v2), which the listener reads before
+//! any TLS or HTTP bytes when [`ForwardingConfig::proxy_protocol`] is enabled.
+//!
+//! L7 proxies instead report the client in `X-Forwarded-For` or `Forwarded`
+//! (RFC 7239) headers. These are only honoured when the immediate peer is one of
+//! the [`ForwardingConfig::trusted_proxies`]; otherwise they are replaced so a
+//! client cannot spoof its address. The peer is then appended to
+//! `X-Forwarded-For`, and to `Forwarded` unless a trusted proxy only sent
+//! `X-Forwarded-For`. IPv6 peers appear as `for="[2001:db8::1]"` in `Forwarded`.
+//! The resolved address is passed to components in the `x-real-ip` header.
+//!
+//! [PROXY protocol]: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use anyhow::{Context as _, bail, ensure};
+use hyper::HeaderMap;
+use hyper::header::{FORWARDED, HeaderName, HeaderValue};
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+
+/// Header carrying the resolved client address to components
+pub const REAL_IP_HEADER: &str = "x-real-ip";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest possible v1 header, including the trailing CRLF
+const V1_MAX_LEN: usize = 107;
+
+/// An IP network in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+///
+/// A bare address is parsed as a network containing only that address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Returns whether `ip` is inside this network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, canonical(ip)) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (
+                addr,
+                Some(
+                    prefix_len
+                        .parse::<u8>()
+                        .with_context(|| format!("invalid prefix length in '{s}'"))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        let network = canonical(
+            addr.parse::<IpAddr>()
+                .with_context(|| format!("invalid address in '{s}'"))?,
+        );
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max);
+        ensure!(
+            prefix_len <= max,
+            "prefix length in '{s}' must be at most {max}"
+        );
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl std::fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+impl serde::Serialize for IpCidr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for IpCidr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// How the HTTP server determines the address of the client behind a request.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct ForwardingConfig {
+    /// Expect every connection to start with a PROXY protocol v1 or v2 header
+    pub proxy_protocol: bool,
+    /// Peers whose `X-Forwarded-For` and `Forwarded` headers are trusted
+    pub trusted_proxies: Vec<IpCidr>,
+}
+
+impl ForwardingConfig {
+    /// Expects every connection to start with a PROXY protocol header.
+    pub fn with_proxy_protocol(mut self, proxy_protocol: bool) -> Self {
+        self.proxy_protocol = proxy_protocol;
+        self
+    }
+
+    /// Trusts the forwarding headers of peers in `networks`.
+    pub fn with_trusted_proxies(mut self, networks: impl IntoIterator<Item = IpCidr>) -> Self {
+        self.trusted_proxies.extend(networks);
+        self
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Returns the client address for a request received from `peer`.
+    ///
+    /// Forwarded hops are walked from the nearest to the furthest, and the first
+    /// address that is not a trusted proxy is the client.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = canonical(peer);
+        if !self.is_trusted(client) {
+            return client;
+        }
+        for hop in forwarded_hops(headers).into_iter().rev() {
+            let Some(hop) = hop else {
+                // Obfuscated or malformed hops end the chain of trust
+                break;
+            };
+            client = hop;
+            if !self.is_trusted(hop) {
+                break;
+            }
+        }
+        client
+    }
+
+    /// Resolves the client address and rewrites the forwarding headers of a
+    /// request received from `peer`.
+    ///
+    /// Headers from untrusted peers are replaced, headers from trusted peers are
+    /// extended with the peer address, and `x-real-ip` is set to the client.
+    pub(crate) fn apply(&self, peer: IpAddr, headers: &mut HeaderMap) -> IpAddr {
+        let client = self.client_ip(peer, headers);
+        let peer = canonical(peer);
+        let trusted = self.is_trusted(peer);
+        append_hop(
+            headers,
+            HeaderName::from_static(X_FORWARDED_FOR),
+            peer.to_string(),
+            trusted,
+        );
+        // A trusted proxy that only sends X-Forwarded-For keeps the hops before
+        // it there, a Forwarded header with just the peer would hide them
+        if !trusted || headers.contains_key(FORWARDED) {
+            append_hop(headers, FORWARDED, forwarded_node(peer), trusted);
+        }
+        headers.insert(
+            REAL_IP_HEADER,
+            HeaderValue::from_str(&client.to_string()).expect("IP addresses are valid headers"),
+        );
+        client
+    }
+}
+
+/// Returns the `for` addresses of all forwarded hops, furthest first. `Forwarded`
+/// is preferred over `X-Forwarded-For` when both are present. Hops that are not
+/// IP addresses are `None`.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let forwarded = values(FORWARDED.as_str());
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node.trim().trim_matches('"')))
+            })
+            .collect();
+    }
+    values(X_FORWARDED_FOR)
+        .into_iter()
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses an address with an optional port, e.g. `192.0.2.1`, `192.0.2.1:80`,
+/// `2001:db8::1` or `[2001:db8::1]:80`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok().map(canonical);
+    }
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .map(canonical)
+}
+
+/// Appends `hop` to the `name` header, replacing it unless `trusted`.
+fn append_hop(headers: &mut HeaderMap, name: HeaderName, hop: String, trusted: bool) {
+    let existing = headers
+        .get_all(&name)
+        .iter()
+        .map(HeaderValue::to_str)
+        .collect::<Result<Vec<_>, _>>();
+    let value = match existing {
+        Ok(existing) if trusted && !existing.is_empty() => {
+            format!("{}, {hop}", existing.join(", "))
+        }
+        _ => hop,
+    };
+    headers.remove(&name);
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(name, value);
+    }
+}
+
+/// Returns the `for` element of `ip` in a `Forwarded` header. IPv6 addresses
+/// are bracketed and quoted, as RFC 7239 requires.
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("for={v4}"),
+        IpAddr::V6(v6) => format!("for=\"[{v6}]\""),
+    }
+}
+
+/// Unwraps the IPv4-mapped address of an IPv4 client of a dual-stack listener
+pub(crate) fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(canonical(addr.ip()), addr.port())
+}
+
+/// Unwraps IPv4-mapped IPv6 addresses so they compare equal to IPv4 networks
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// Reads a PROXY protocol v1 or v2 header from the start of a connection.
+///
+/// Returns the source address announced by the proxy, or `None` for `LOCAL`
+/// (v2) and `UNKNOWN` (v1) connections, such as health checks, which should be
+/// attributed to the TCP peer. Exactly the header is consumed, so the stream is
+/// left positioned at the first byte of TLS or HTTP.
+///
+/// # Errors
+/// Returns an error if the connection does not start with a valid header.
+pub(crate) async fn read_proxy_header<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> anyhow::Result<Option<SocketAddr>> {
+    let mut prefix = [0u8; 12];
+    stream
+        .read_exact(&mut prefix)
+        .await
+        .context("failed to read PROXY protocol header")?;
+
+    if &prefix == V2_SIGNATURE {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await?;
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await?;
+        return parse_v2(header[0], header[1], &payload);
+    }
+
+    ensure!(
+        prefix.starts_with(b"PROXY "),
+        "connection did not start with a PROXY protocol header"
+    );
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        ensure!(
+            line.len() < V1_MAX_LEN,
+            "PROXY protocol v1 header is too long"
+        );
+        line.push(stream.read_u8().await?);
+    }
+    parse_v1(&line)
+}
+
+fn parse_v1(line: &[u8]) -> anyhow::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line)
+        .context("PROXY protocol v1 header is not ASCII")?
+        .trim_end_matches("\r\n");
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        [
+            "PROXY",
+            protocol @ ("TCP4" | "TCP6"),
+            source,
+            _,
+            source_port,
+            _,
+        ] => {
+            let ip: IpAddr = source
+                .parse()
+                .with_context(|| format!("invalid source address '{source}'"))?;
+            ensure!(
+                ip.is_ipv4() == (*protocol == "TCP4"),
+                "source address '{source}' does not match protocol {protocol}"
+            );
+            let port = source_port
+                .parse()
+                .with_context(|| format!("invalid source port '{source_port}'"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => bail!("malformed PROXY protocol v1 header '{line}'"),
+    }
+}
+
+fn parse_v2(version_command: u8, family: u8, payload: &[u8]) -> anyhow::Result<Option<SocketAddr>> {
+    ensure!(
+        version_command >> 4 == 2,
+        "unsupported PROXY protocol version {}",
+        version_command >> 4
+    );
+    match version_command & 0x0f {
+        // LOCAL: the proxy's own connection, e.g. a health check
+        0x0 => return Ok(None),
+        0x1 => {}
+        command => bail!("unsupported PROXY protocol v2 command {command}"),
+    }
+    match family >> 4 {
+        0x1 => {
+            ensure!(
+                payload.len() >= 12,
+                "truncated PROXY protocol v2 IPv4 address"
+            );
+            let ip = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        0x2 => {
+            ensure!(
+                payload.len() >= 36,
+                "truncated PROXY protocol v2 IPv6 address"
+            );
+            let octets: [u8; 16] = payload[..16].try_into().expect("length checked above");
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        // UNSPEC or unix sockets carry no usable client address
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(trusted: &[&str]) -> ForwardingConfig {
+        ForwardingConfig {
+            proxy_protocol: false,
+            trusted_proxies: trusted.iter().map(|c| c.parse().unwrap()).collect(),
+        }
+    }
+
+    fn header_map(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| {
+                (
+                    hyper::header::HeaderName::from_static(k),
+                    HeaderValue::from_static(v),
+                )
+            })
+            .collect()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ip_cidr() {
+        let cidr: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains(ip("10.1.2.3")));
+        assert!(cidr.contains(ip("::ffff:10.1.2.3")));
+        assert!(!cidr.contains(ip("11.0.0.1")));
+        assert!(!cidr.contains(ip("fd00::1")));
+
+        let cidr: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(cidr.contains(ip("fd12::1")));
+        assert!(!cidr.contains(ip("fe80::1")));
+
+        let single: IpCidr = "192.0.2.1".parse().unwrap();
+        assert_eq!(single.to_string(), "192.0.2.1/32");
+        assert!(!single.contains(ip("192.0.2.2")));
+        assert!(
+            "0.0.0.0/0"
+                .parse::<IpCidr>()
+                .unwrap()
+                .contains(ip("8.8.8.8"))
+        );
+
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("not-an-ip/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn test_client_ip_ignores_untrusted_headers() {
+        let config = config(&["10.0.0.0/8"]);
+        let mut headers =
+            header_map(&[("x-forwarded-for", "1.2.3.4"), ("forwarded", "for=1.2.3.4")]);
+        let client = config.apply(ip("203.0.113.9"), &mut headers);
+        assert_eq!(client, ip("203.0.113.9"));
+        assert_eq!(headers["x-forwarded-for"], "203.0.113.9");
+        assert_eq!(headers[REAL_IP_HEADER], "203.0.113.9");
+        assert_eq!(headers[FORWARDED], "for=203.0.113.9");
+    }
+
+    #[test]
+    fn test_apply_ipv6_peers() {
+        let config = config(&["fd00::/8"]);
+        let mut headers = header_map(&[("forwarded", "for=192.0.2.60")]);
+        let client = config.apply(ip("fd00::2"), &mut headers);
+        assert_eq!(client, ip("192.0.2.60"));
+        assert_eq!(headers[FORWARDED], "for=192.0.2.60, for=\"[fd00::2]\"");
+        assert_eq!(headers["x-forwarded-for"], "fd00::2");
+
+        // IPv4 clients of dual-stack listeners are reported as IPv4
+        let mut headers = HeaderMap::new();
+        let client = config.apply(ip("::ffff:203.0.113.9"), &mut headers);
+        assert_eq!(client, ip("203.0.113.9"));
+        assert_eq!(headers[FORWARDED], "for=203.0.113.9");
+        assert_eq!(
+            canonical_addr("[::ffff:203.0.113.9]:4711".parse().unwrap()),
+            "203.0.113.9:4711".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_client_ip_walks_trusted_hops() {
+        let config = config(&["10.0.0.0/8"]);
+        let mut headers = header_map(&[("x-forwarded-for", "1.2.3.4, 198.51.100.7, 10.0.0.2")]);
+        let client = config.apply(ip("10.0.0.1"), &mut headers);
+        assert_eq!(client, ip("198.51.100.7"));
+        assert_eq!(
+            headers["x-forwarded-for"],
+            "1.2.3.4, 198.51.100.7, 10.0.0.2, 10.0.0.1"
+        );
+        assert_eq!(headers[REAL_IP_HEADER], "198.51.100.7");
+
+        let forwarded = header_map(&[(
+            "forwarded",
+            "for=192.0.2.60;proto=https, for=\"[2001:db8::1]:4711\"",
+        )]);
+        assert_eq!(
+            config.client_ip(ip("10.0.0.1"), &forwarded),
+            ip("2001:db8::1")
+        );
+        let hidden = header_map(&[("forwarded", "for=192.0.2.60, for=_hidden")]);
+        assert_eq!(config.client_ip(ip("10.0.0.1"), &hidden), ip("10.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn test_read_proxy_header_v1() {
+        let mut stream: &[u8] =
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n";
+        let addr = read_proxy_header(&mut stream).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(stream, b"GET / HTTP/1.1\r\n");
+
+        let mut stream: &[u8] = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_proxy_header(&mut stream).await.unwrap(), None);
+
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\nHost: example\r\n";
+        assert!(read_proxy_header(&mut stream).await.is_err());
+        let mut stream: &[u8] = b"PROXY TCP4 2001:db8::1 198.51.100.1 1 2\r\n";
+        assert!(read_proxy_header(&mut stream).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_proxy_header_v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]);
+        header.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 1]);
+        header.extend_from_slice(&56324u16.to_be_bytes());
+        header.extend_from_slice(&443u16.to_be_bytes());
+        header.extend_from_slice(b"\x16\x03\x01");
+        let mut stream = header.as_slice();
+        let addr = read_proxy_header(&mut stream).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(stream, b"\x16\x03\x01");
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            read_proxy_header(&mut local.as_slice()).await.unwrap(),
+            None
+        );
+    }
+}
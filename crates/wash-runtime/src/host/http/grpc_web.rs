@@ -0,0 +1,649 @@
+//! gRPC-web and Connect for browser clients.
+//!
+//! Browsers can't read or send HTTP trailers, so they can't call gRPC
+//! services directly. With [`HttpServer::with_grpc_web`](super::HttpServer::with_grpc_web),
+//! the host translates requests of the protocols browsers call gRPC services
+//! with into gRPC before they reach the component, and the gRPC response of
+//! the component back:
+//!
+//! - gRPC-web, `application/grpc-web[+proto|+json]`, frames messages like
+//!   gRPC and receives the trailers as a last frame flagged `0x80`.
+//!   `application/grpc-web-text` does the same with base64 bodies.
+//! - Connect streaming calls, `application/connect+proto|json`, frame
+//!   messages like gRPC and receive the trailers as a last frame flagged
+//!   `0x02`, a JSON document with the error and metadata of the call.
+//! - Connect unary calls, `POST`s of `application/proto|json` with a
+//!   `Connect-Protocol-Version` header, send and receive a single unframed
+//!   message. Errors are a JSON document with the HTTP status of their gRPC
+//!   code, and trailers are sent as `Trailer-` prefixed headers.
+//!
+//! The component serves plain gRPC: it receives an `application/grpc+proto`
+//! or `application/grpc+json` request with `TE: trailers`, and sets
+//! `grpc-status` in the trailers of its response, or in its headers for a
+//! response without messages. Responses the host answers itself, e.g. when no
+//! route matches, are not translated.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use bytes::{BufMut as _, Bytes, BytesMut};
+use futures::StreamExt as _;
+use http_body_util::BodyExt as _;
+use hyper::body::Frame;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Response, StatusCode};
+use tracing::warn;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use super::RequestBody;
+
+/// Header of Connect requests telling them from other JSON and protobuf requests
+pub const CONNECT_PROTOCOL_VERSION_HEADER: &str = "connect-protocol-version";
+
+/// Flag of a compressed message frame
+const COMPRESSED_FLAG: u8 = 0x01;
+/// Flag of the frame carrying the trailers of a gRPC-web response
+const GRPC_WEB_TRAILERS_FLAG: u8 = 0x80;
+/// Flag of the frame ending a Connect streaming response
+const CONNECT_END_STREAM_FLAG: u8 = 0x02;
+
+/// A protocol browsers call gRPC services with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Protocol {
+    /// gRPC-web, with base64 bodies if `text`
+    GrpcWeb {
+        text: bool,
+    },
+    ConnectUnary,
+    ConnectStream,
+}
+
+/// A call translated to gRPC, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct GrpcWebCall {
+    protocol: Protocol,
+    /// `proto` or `json`
+    codec: &'static str,
+}
+
+impl GrpcWebCall {
+    /// Returns the call a request makes, if it uses one of the protocols.
+    pub(super) fn detect(parts: &hyper::http::request::Parts) -> Option<Self> {
+        let content_type = parts.headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let (protocol, codec) = match essence.as_str() {
+            "application/grpc-web" | "application/grpc-web+proto" => {
+                (Protocol::GrpcWeb { text: false }, "proto")
+            }
+            "application/grpc-web+json" => (Protocol::GrpcWeb { text: false }, "json"),
+            "application/grpc-web-text" | "application/grpc-web-text+proto" => {
+                (Protocol::GrpcWeb { text: true }, "proto")
+            }
+            "application/connect+proto" => (Protocol::ConnectStream, "proto"),
+            "application/connect+json" => (Protocol::ConnectStream, "json"),
+            "application/proto" | "application/json"
+                if parts.method == hyper::Method::POST
+                    && parts.headers.contains_key(CONNECT_PROTOCOL_VERSION_HEADER) =>
+            {
+                let codec = if essence.ends_with("json") {
+                    "json"
+                } else {
+                    "proto"
+                };
+                (Protocol::ConnectUnary, codec)
+            }
+            _ => return None,
+        };
+        Some(Self { protocol, codec })
+    }
+
+    /// Turns the request into a gRPC request.
+    ///
+    /// # Errors
+    /// Returns an error if the body of a unary Connect call can't be read.
+    pub(super) async fn grpc_request(
+        &self,
+        parts: &mut hyper::http::request::Parts,
+        body: RequestBody,
+    ) -> Result<RequestBody, hyper::Error> {
+        let headers = &mut parts.headers;
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(match self.codec {
+                "json" => "application/grpc+json",
+                _ => "application/grpc+proto",
+            }),
+        );
+        headers.insert(hyper::header::TE, HeaderValue::from_static("trailers"));
+        // Framing and decoding change the length of the body
+        headers.remove(CONTENT_LENGTH);
+        if let Some(timeout) = headers
+            .remove("connect-timeout-ms")
+            .and_then(|ms| ms.to_str().ok()?.parse::<u64>().ok())
+            && let Ok(timeout) = HeaderValue::from_str(&format!("{timeout}m"))
+        {
+            headers.insert("grpc-timeout", timeout);
+        }
+        match self.protocol {
+            Protocol::GrpcWeb { text: false } => Ok(body),
+            Protocol::GrpcWeb { text: true } => {
+                let mut pending = Vec::new();
+                let data = body.into_data_stream().scan((), move |_, chunk| {
+                    let decoded = match chunk {
+                        Ok(chunk) => decode_text(&mut pending, &chunk).map(Ok),
+                        Err(e) => Some(Err(e)),
+                    };
+                    if decoded.is_none() {
+                        // The body type can't carry the error, the component
+                        // sees a truncated message
+                        warn!("invalid base64 in gRPC-web-text request body");
+                    }
+                    futures::future::ready(decoded.map(|data| data.map(Frame::data)))
+                });
+                Ok(http_body_util::StreamBody::new(data).boxed_unsync())
+            }
+            Protocol::ConnectStream => {
+                rename_header(headers, "connect-content-encoding", "grpc-encoding");
+                rename_header(headers, "connect-accept-encoding", "grpc-accept-encoding");
+                Ok(body)
+            }
+            Protocol::ConnectUnary => {
+                let compressed = rename_header(headers, "content-encoding", "grpc-encoding");
+                rename_header(headers, "accept-encoding", "grpc-accept-encoding");
+                let message = body.collect().await?.to_bytes();
+                let flags = if compressed { COMPRESSED_FLAG } else { 0 };
+                Ok(super::BufferedBody {
+                    data: frame(flags, &message),
+                    trailers: None,
+                }
+                .into_body())
+            }
+        }
+    }
+
+    /// Turns the gRPC response of the component into a response of the
+    /// protocol of the call.
+    pub(super) async fn response(
+        &self,
+        response: Response<HyperOutgoingBody>,
+    ) -> Response<HyperOutgoingBody> {
+        match self.protocol {
+            Protocol::GrpcWeb { text } => self.grpc_web_response(response, text),
+            Protocol::ConnectStream => self.connect_stream_response(response),
+            Protocol::ConnectUnary => self.connect_unary_response(response).await,
+        }
+    }
+
+    fn grpc_web_response(
+        &self,
+        response: Response<HyperOutgoingBody>,
+        text: bool,
+    ) -> Response<HyperOutgoingBody> {
+        let (mut parts, body) = response.into_parts();
+        let content_type = match (text, self.codec) {
+            (true, _) => "application/grpc-web-text+proto",
+            (false, "json") => "application/grpc-web+json",
+            (false, _) => "application/grpc-web+proto",
+        };
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        parts.headers.remove(CONTENT_LENGTH);
+        let body = body
+            .map_frame(move |frame| {
+                let data = match frame.into_data() {
+                    Ok(data) => data,
+                    Err(frame) => match frame.into_trailers() {
+                        Ok(trailers) => frame_trailers(&trailers),
+                        Err(_) => Bytes::new(),
+                    },
+                };
+                if text {
+                    Frame::data(Bytes::from(STANDARD.encode(data)))
+                } else {
+                    Frame::data(data)
+                }
+            })
+            .boxed();
+        Response::from_parts(parts, body)
+    }
+
+    fn connect_stream_response(
+        &self,
+        response: Response<HyperOutgoingBody>,
+    ) -> Response<HyperOutgoingBody> {
+        let (mut parts, body) = response.into_parts();
+        let content_type = match self.codec {
+            "json" => "application/connect+json",
+            _ => "application/connect+proto",
+        };
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        parts.headers.remove(CONTENT_LENGTH);
+        rename_header(
+            &mut parts.headers,
+            "grpc-encoding",
+            "connect-content-encoding",
+        );
+        // A response without messages carries its status in its headers
+        if parts.headers.contains_key("grpc-status") {
+            let end = end_stream(&parts.headers);
+            strip_grpc_headers(&mut parts.headers);
+            let body = http_body_util::Full::new(end)
+                .map_err(|never| match never {})
+                .boxed();
+            return Response::from_parts(parts, body);
+        }
+        let body = body
+            .map_frame(|frame| match frame.into_data() {
+                Ok(data) => Frame::data(data),
+                Err(frame) => match frame.into_trailers() {
+                    Ok(trailers) => Frame::data(end_stream(&trailers)),
+                    Err(_) => Frame::data(Bytes::new()),
+                },
+            })
+            .boxed();
+        Response::from_parts(parts, body)
+    }
+
+    async fn connect_unary_response(
+        &self,
+        response: Response<HyperOutgoingBody>,
+    ) -> Response<HyperOutgoingBody> {
+        let (mut parts, body) = response.into_parts();
+        let body = match body.collect().await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(err = ?e, "failed to read gRPC response of component");
+                return connect_error(StatusCode::BAD_GATEWAY, 13, "");
+            }
+        };
+        let mut trailers = body.trailers().cloned().unwrap_or_default();
+        let data = body.to_bytes();
+        // A response without messages carries its status in its headers
+        for (name, value) in parts.headers.iter() {
+            if name.as_str().starts_with("grpc-") && !trailers.contains_key(name) {
+                trailers.insert(name.clone(), value.clone());
+            }
+        }
+        let (code, message) = grpc_status(&trailers);
+        if code != 0 {
+            return connect_error(connect_status(code), code, &message);
+        }
+        let compressed = data.first() == Some(&COMPRESSED_FLAG);
+        let message = data
+            .get(5..)
+            .map(Bytes::copy_from_slice)
+            .unwrap_or_default();
+        if compressed {
+            rename_header(&mut parts.headers, "grpc-encoding", "content-encoding");
+        }
+        strip_grpc_headers(&mut parts.headers);
+        let content_type = match self.codec {
+            "json" => "application/json",
+            _ => "application/proto",
+        };
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        parts.headers.remove(CONTENT_LENGTH);
+        strip_grpc_headers(&mut trailers);
+        for (name, value) in trailers.iter() {
+            if let Ok(name) = HeaderName::from_bytes(format!("trailer-{name}").as_bytes()) {
+                parts.headers.append(name, value.clone());
+            }
+        }
+        let body = http_body_util::Full::new(message)
+            .map_err(|never| match never {})
+            .boxed();
+        Response::from_parts(parts, body)
+    }
+}
+
+/// Frames a message with a 5 byte prefix: its flags and its length.
+fn frame(flags: u8, message: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(5 + message.len());
+    frame.put_u8(flags);
+    frame.put_u32(message.len() as u32);
+    frame.put_slice(message);
+    frame.freeze()
+}
+
+/// Frames trailers as the last frame of a gRPC-web response, one
+/// `name: value` line each.
+fn frame_trailers(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.extend_from_slice(b": ");
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+    frame(GRPC_WEB_TRAILERS_FLAG, &block)
+}
+
+/// Builds the frame ending a Connect streaming response from the gRPC
+/// trailers.
+fn end_stream(trailers: &HeaderMap) -> Bytes {
+    let (code, message) = grpc_status(trailers);
+    let mut metadata = serde_json::Map::new();
+    let mut trailers = trailers.clone();
+    strip_grpc_headers(&mut trailers);
+    for name in trailers.keys() {
+        let values = trailers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(serde_json::Value::from)
+            .collect();
+        metadata.insert(name.to_string(), serde_json::Value::Array(values));
+    }
+    let mut end = serde_json::json!({ "metadata": metadata });
+    if code != 0 {
+        end["error"] = error_json(code, &message);
+    }
+    frame(CONNECT_END_STREAM_FLAG, end.to_string().as_bytes())
+}
+
+/// A Connect error response for a gRPC status.
+fn connect_error(status: StatusCode, code: u32, message: &str) -> Response<HyperOutgoingBody> {
+    let body = error_json(code, message).to_string();
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(
+            http_body_util::Full::new(Bytes::from(body))
+                .map_err(|never| match never {})
+                .boxed(),
+        )
+        .expect("failed to build Connect error response")
+}
+
+fn error_json(code: u32, message: &str) -> serde_json::Value {
+    let mut error = serde_json::json!({ "code": connect_code(code) });
+    if !message.is_empty() {
+        error["message"] = message.into();
+    }
+    error
+}
+
+/// Returns the gRPC status code and message in `headers`. A missing status
+/// is an unknown error.
+fn grpc_status(headers: &HeaderMap) -> (u32, String) {
+    let code = headers
+        .get("grpc-status")
+        .and_then(|code| code.to_str().ok()?.parse().ok())
+        .unwrap_or(2);
+    let message = headers
+        .get("grpc-message")
+        .map(|message| percent_decode(message.as_bytes()))
+        .unwrap_or_default();
+    (code, message)
+}
+
+/// The Connect name of a gRPC status code
+fn connect_code(code: u32) -> &'static str {
+    match code {
+        1 => "canceled",
+        3 => "invalid_argument",
+        4 => "deadline_exceeded",
+        5 => "not_found",
+        6 => "already_exists",
+        7 => "permission_denied",
+        8 => "resource_exhausted",
+        9 => "failed_precondition",
+        10 => "aborted",
+        11 => "out_of_range",
+        12 => "unimplemented",
+        13 => "internal",
+        14 => "unavailable",
+        15 => "data_loss",
+        16 => "unauthenticated",
+        _ => "unknown",
+    }
+}
+
+/// The HTTP status of a unary Connect error with a gRPC status code
+fn connect_status(code: u32) -> StatusCode {
+    let status = match code {
+        1 => 499,
+        3 | 9 | 11 => 400,
+        4 => 504,
+        5 => 404,
+        6 | 10 => 409,
+        7 => 403,
+        8 => 429,
+        12 => 501,
+        14 => 503,
+        16 => 401,
+        _ => 500,
+    };
+    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Removes the gRPC status and encoding headers the client doesn't receive as
+/// such.
+fn strip_grpc_headers(headers: &mut HeaderMap) {
+    for name in [
+        "grpc-status",
+        "grpc-message",
+        "grpc-status-details-bin",
+        "grpc-encoding",
+        "grpc-accept-encoding",
+    ] {
+        headers.remove(name);
+    }
+}
+
+/// Moves header `from` to `to`, returning whether there was one.
+fn rename_header(headers: &mut HeaderMap, from: &'static str, to: &'static str) -> bool {
+    let Some(value) = headers.remove(from) else {
+        return false;
+    };
+    headers.insert(to, value);
+    true
+}
+
+/// Decodes the complete base64 groups of `pending` and `chunk`, keeping the
+/// rest for the next chunk. Clients may pad every message, so groups are
+/// decoded one by one. Returns `None` if the body isn't base64.
+fn decode_text(pending: &mut Vec<u8>, chunk: &[u8]) -> Option<Bytes> {
+    pending.extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+    let complete = pending.len() / 4 * 4;
+    let mut decoded = Vec::with_capacity(complete / 4 * 3);
+    for group in pending[..complete].chunks_exact(4) {
+        decoded.extend(STANDARD.decode(group).ok()?);
+    }
+    pending.drain(..complete);
+    Some(Bytes::from(decoded))
+}
+
+/// Decodes percent-encoding, e.g. of a `grpc-message`.
+pub(crate) fn percent_decode(value: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        let escaped = (value[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(value[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, content_type: &str) -> hyper::http::request::Parts {
+        hyper::Request::builder()
+            .method(method)
+            .header(CONTENT_TYPE, content_type)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    fn grpc_response(trailers: HeaderMap) -> Response<HyperOutgoingBody> {
+        let body = http_body_util::Full::new(frame(0, b"reply"))
+            .map_err(|never| match never {})
+            .with_trailers(async move { Some(Ok(trailers)) })
+            .boxed();
+        Response::builder()
+            .header(CONTENT_TYPE, "application/grpc+proto")
+            .body(body)
+            .unwrap()
+    }
+
+    fn trailers(status: &str, message: Option<&str>) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_str(status).unwrap());
+        if let Some(message) = message {
+            trailers.insert("grpc-message", HeaderValue::from_str(message).unwrap());
+        }
+        trailers.insert("x-served-by", HeaderValue::from_static("users"));
+        trailers
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |method, content_type| GrpcWebCall::detect(&request(method, content_type));
+        assert_eq!(
+            detect("POST", "application/grpc-web-text"),
+            Some(GrpcWebCall {
+                protocol: Protocol::GrpcWeb { text: true },
+                codec: "proto"
+            })
+        );
+        assert_eq!(
+            detect("POST", "application/connect+json; charset=utf-8").map(|call| call.codec),
+            Some("json")
+        );
+        // Unary Connect calls must say so
+        assert_eq!(detect("POST", "application/json"), None);
+        let mut connect = request("POST", "application/proto");
+        connect.headers.insert(
+            CONNECT_PROTOCOL_VERSION_HEADER,
+            HeaderValue::from_static("1"),
+        );
+        assert_eq!(
+            GrpcWebCall::detect(&connect).map(|call| call.protocol),
+            Some(Protocol::ConnectUnary)
+        );
+        assert_eq!(detect("POST", "application/grpc"), None);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_text() {
+        let call = GrpcWebCall {
+            protocol: Protocol::GrpcWeb { text: true },
+            codec: "proto",
+        };
+        let mut parts = request("POST", "application/grpc-web-text");
+        // Two messages encoded separately, split across chunks
+        let encoded = format!(
+            "{}{}",
+            STANDARD.encode(frame(0, b"hi")),
+            STANDARD.encode(frame(0, b"there"))
+        );
+        let (first, second) = encoded.split_at(5);
+        let chunks = [first.to_string(), second.to_string()]
+            .map(|chunk| Ok::<_, hyper::Error>(Frame::data(Bytes::from(chunk))));
+        let body = http_body_util::StreamBody::new(futures::stream::iter(chunks)).boxed_unsync();
+        let body = call.grpc_request(&mut parts, body).await.unwrap();
+        assert_eq!(parts.headers[CONTENT_TYPE], "application/grpc+proto");
+        assert_eq!(parts.headers[hyper::header::TE], "trailers");
+        let mut expected = frame(0, b"hi").to_vec();
+        expected.extend_from_slice(&frame(0, b"there"));
+        assert_eq!(body.collect().await.unwrap().to_bytes(), expected);
+
+        let response = call.response(grpc_response(trailers("0", None))).await;
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "application/grpc-web-text+proto"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = std::str::from_utf8(&body).unwrap();
+        let message = STANDARD.encode(frame(0, b"reply"));
+        assert_eq!(&body[..message.len()], message);
+        let trailers = STANDARD.decode(&body[message.len()..]).unwrap();
+        assert_eq!(trailers[0], GRPC_WEB_TRAILERS_FLAG);
+        assert_eq!(&trailers[5..], b"grpc-status: 0\r\nx-served-by: users\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_connect_unary() {
+        let call = GrpcWebCall {
+            protocol: Protocol::ConnectUnary,
+            codec: "proto",
+        };
+        let mut parts = request("POST", "application/proto");
+        parts
+            .headers
+            .insert("connect-timeout-ms", HeaderValue::from_static("1500"));
+        let body = super::super::BufferedBody {
+            data: Bytes::from_static(b"ask"),
+            trailers: None,
+        }
+        .into_body();
+        let body = call.grpc_request(&mut parts, body).await.unwrap();
+        assert_eq!(parts.headers["grpc-timeout"], "1500m");
+        assert_eq!(body.collect().await.unwrap().to_bytes(), frame(0, b"ask"));
+
+        let response = call.response(grpc_response(trailers("0", None))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/proto");
+        assert_eq!(response.headers()["trailer-x-served-by"], "users");
+        assert!(!response.headers().contains_key("grpc-status"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "reply");
+
+        let response = call
+            .response(grpc_response(trailers("5", Some("no%20such user"))))
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({ "code": "not_found", "message": "no such user" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_stream() {
+        let call = GrpcWebCall {
+            protocol: Protocol::ConnectStream,
+            codec: "json",
+        };
+        let response = call.response(grpc_response(trailers("14", None))).await;
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/connect+json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..10], &frame(0, b"reply")[..]);
+        assert_eq!(body[10], CONNECT_END_STREAM_FLAG);
+        let end: serde_json::Value = serde_json::from_slice(&body[15..]).unwrap();
+        assert_eq!(
+            end,
+            serde_json::json!({
+                "error": { "code": "unavailable" },
+                "metadata": { "x-served-by": ["users"] },
+            })
+        );
+    }
+}
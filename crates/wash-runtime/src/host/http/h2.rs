@@ -0,0 +1,236 @@
+//! Serving HTTP/2 next to HTTP/1.1.
+//!
+//! Every listener of an [`HttpServer`](super::HttpServer) speaks both
+//! protocols, so components can serve gRPC and clients can multiplex
+//! requests over one connection:
+//!
+//! - Over TLS, the protocol is negotiated with ALPN, preferring `h2` over
+//!   `http/1.1`.
+//! - Over cleartext, a connection starting with the HTTP/2 connection preface
+//!   is served as HTTP/2 with prior knowledge (h2c), any other as HTTP/1.1.
+//!   Upgrading an HTTP/1.1 connection to h2c is not supported.
+//!
+//! Request and response bodies stream through to components in either
+//! protocol, and response trailers, e.g. `grpc-status`, are always sent over
+//! HTTP/2. HTTP/2 requests carry their host in the `:authority`
+//! pseudo-header, which the host copies to the `Host` header, so they are
+//! routed like HTTP/1.1 requests.
+//!
+//! The header limits and header read timeout of the host's
+//! [`RequestLimits`] are enforced by the connection, see
+//! [`request_limits`](super::request_limits).
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper::header::{HOST, HeaderValue};
+use hyper::server::conn::{http1, http2};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use super::RequestLimits;
+
+/// ALPN protocol id of HTTP/2
+pub const ALPN_H2: &[u8] = b"h2";
+/// ALPN protocol id of HTTP/1.1
+pub const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+/// The bytes an HTTP/2 client starts a connection with
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+/// How long a client may take to send the start of its first request
+const PREFACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a cleartext connection starts with the HTTP/2 connection preface,
+/// without consuming any of its bytes.
+pub(super) async fn is_h2c(stream: &TcpStream) -> bool {
+    tokio::time::timeout(PREFACE_TIMEOUT, async {
+        let mut buf = [0; PREFACE.len()];
+        loop {
+            let n = match stream.peek(&mut buf).await {
+                Ok(0) | Err(_) => return false,
+                Ok(n) => n,
+            };
+            match preface_match(&buf[..n]) {
+                Some(matches) => return matches,
+                // Only the start of the preface arrived so far
+                None => tokio::time::sleep(Duration::from_millis(5)).await,
+            }
+        }
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// Whether `start`, the first bytes of a connection, are the HTTP/2
+/// preface. Returns `None` if they are too short to tell.
+fn preface_match(start: &[u8]) -> Option<bool> {
+    if !PREFACE.starts_with(start) {
+        Some(false)
+    } else if start.len() == PREFACE.len() {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Sets the `Host` header of a request without one from its authority, as
+/// HTTP/2 clients send it in the `:authority` pseudo-header instead.
+pub(super) fn set_host<B>(req: &mut hyper::Request<B>) {
+    if req.headers().contains_key(HOST) {
+        return;
+    }
+    if let Some(host) = req
+        .uri()
+        .authority()
+        .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+    {
+        req.headers_mut().insert(HOST, host);
+    }
+}
+
+/// Serves a connection as HTTP/2 if `h2` is set, otherwise as HTTP/1.1,
+/// enforcing the header `limits` of the host.
+pub(super) async fn serve_connection<I, S>(
+    io: I,
+    h2: bool,
+    service: S,
+    limits: &RequestLimits,
+    closing: watch::Receiver<bool>,
+) -> Result<(), hyper::Error>
+where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+    S: hyper::service::HttpService<hyper::body::Incoming, ResBody = HyperOutgoingBody>,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    if h2 {
+        let mut builder = http2::Builder::new(TokioExecutor);
+        builder.timer(TokioTimer);
+        if let Some(max) = limits.max_header_bytes {
+            builder.max_header_list_size(u32::try_from(max).unwrap_or(u32::MAX));
+        }
+        let connection = builder.serve_connection(io, service);
+        until_closing(connection, closing, |connection| {
+            connection.graceful_shutdown()
+        })
+        .await
+    } else {
+        let mut builder = http1::Builder::new();
+        builder.keep_alive(true).timer(TokioTimer);
+        if let Some(timeout) = limits.header_read_timeout() {
+            builder.header_read_timeout(timeout);
+        }
+        if let Some(max) = limits.max_headers {
+            builder.max_headers(usize::try_from(max).unwrap_or(usize::MAX));
+        }
+        if let Some(size) = limits.http1_buf_size() {
+            builder.max_buf_size(size);
+        }
+        let connection = builder
+            .serve_connection(io, service)
+            // For WebSocket connections, see `super::websocket`
+            .with_upgrades();
+        until_closing(connection, closing, |connection| {
+            connection.graceful_shutdown()
+        })
+        .await
+    }
+}
+
+/// Serves `connection` until the server is closing, then lets it finish the
+/// requests in flight and closes it: an HTTP/1.1 connection closes once its
+/// current response is sent, an HTTP/2 connection sends `GOAWAY`.
+async fn until_closing<C>(
+    connection: C,
+    mut closing: watch::Receiver<bool>,
+    graceful_shutdown: impl FnOnce(Pin<&mut C>),
+) -> Result<(), hyper::Error>
+where
+    C: Future<Output = Result<(), hyper::Error>>,
+{
+    tokio::pin!(connection);
+    tokio::select! {
+        result = connection.as_mut() => return result,
+        // Also when the server is gone
+        _ = closing.wait_for(|closing| *closing) => graceful_shutdown(connection.as_mut()),
+    }
+    connection.await
+}
+
+/// Runs the streams of HTTP/2 connections as tokio tasks
+#[derive(Debug, Clone, Copy)]
+struct TokioExecutor;
+
+impl<F> hyper::rt::Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, future: F) {
+        tokio::spawn(future);
+    }
+}
+
+/// Times connections with tokio, e.g. their header read timeout
+#[derive(Debug, Clone, Copy)]
+struct TokioTimer;
+
+impl hyper::rt::Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn hyper::rt::Sleep>> {
+        Box::pin(TokioSleep(Box::pin(tokio::time::sleep(duration))))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn hyper::rt::Sleep>> {
+        Box::pin(TokioSleep(Box::pin(tokio::time::sleep_until(
+            deadline.into(),
+        ))))
+    }
+}
+
+/// A tokio sleep hyper can use
+struct TokioSleep(Pin<Box<tokio::time::Sleep>>);
+
+impl Future for TokioSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+impl hyper::rt::Sleep for TokioSleep {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preface_match() {
+        assert_eq!(preface_match(PREFACE), Some(true));
+        assert_eq!(preface_match(b"PRI * HTTP"), None);
+        assert_eq!(preface_match(b"GET / HTTP/1.1\r\n"), Some(false));
+        assert_eq!(preface_match(b"P"), None);
+        assert_eq!(preface_match(b"POST"), Some(false));
+    }
+
+    #[test]
+    fn test_set_host() {
+        let mut req = hyper::Request::builder()
+            .uri("https://api.example.com:8443/users")
+            .body(())
+            .unwrap();
+        set_host(&mut req);
+        assert_eq!(req.headers()[HOST], "api.example.com:8443");
+
+        // HTTP/1.1 requests keep their Host header
+        let mut req = hyper::Request::builder()
+            .uri("http://other/")
+            .header(HOST, "example.com")
+            .body(())
+            .unwrap();
+        set_host(&mut req);
+        assert_eq!(req.headers()[HOST], "example.com");
+    }
+}
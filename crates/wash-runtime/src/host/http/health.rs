@@ -0,0 +1,246 @@
+//! Health checks of the workloads serving routes, and of the host itself.
+//!
+//! A route may declare a health check, which the host runs against its
+//! workload from the moment it starts and then every interval: a `GET`
+//! request to a path, passing with a `2xx` or `3xx` status, or a call to an
+//! export of the component taking no arguments, passing unless it fails,
+//! returns `false` or returns an `err`. Checks reach the component directly,
+//! without its middleware or the authentication of the route, and carry the
+//! [`HEALTH_CHECK_HEADER`]. A check not done within the interval fails.
+//!
+//! A workload with a health check is not ready until a check passes, and
+//! stops being ready once `threshold` checks in a row failed, until one
+//! passes again. The [`DynamicRouter`](super::DynamicRouter) only routes
+//! requests to ready workloads: among workloads serving the same route, e.g.
+//! the old and new versions of a workload being updated, the first ready one
+//! gets the request, and `503 Service Unavailable` is answered if none is.
+//!
+//! In the string config map of `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! health_path: /healthz                              # or
+//! health_export: wasmcloud:example/health#check
+//! health_interval_ms: 10000                          # optional, defaults to 10000
+//! health_threshold: 3                                # optional, defaults to 3
+//! ```
+//!
+//! Orchestrators such as Kubernetes or Nomad probe the host itself on a path
+//! the server answers for any `Host`, see
+//! [`HttpServer::with_health_path`](super::HttpServer::with_health_path):
+//! with `200 OK` while every workload with a health check is ready, and with
+//! `503 Service Unavailable`, listing the workloads that are not, otherwise
+//! or once the server is stopping. The body is JSON:
+//!
+//! ```text
+//! {"status": "not_ready", "unready": ["01J9..."]}
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::ensure;
+use bytes::Bytes;
+use http_body_util::BodyExt as _;
+use tracing::{debug, info, warn};
+use wasmtime::component::Val;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use super::{BufferedBody, Router, WorkloadHandle, invoke_component_handler};
+pub use crate::types::route::health::{
+    DEFAULT_INTERVAL_MS, DEFAULT_THRESHOLD, HEALTH_EXPORT_CONFIG_KEY,
+    HEALTH_INTERVAL_MS_CONFIG_KEY, HEALTH_PATH_CONFIG_KEY, HEALTH_THRESHOLD_CONFIG_KEY,
+    HealthProbe, RouteHealthCheck,
+};
+
+/// Request header marking the requests of health checks
+pub const HEALTH_CHECK_HEADER: &str = "x-wasmcloud-health-check";
+
+/// The error a request to a workload that is not ready fails to be routed
+/// with, answered with `503 Service Unavailable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkloadNotReady(pub String);
+
+impl std::fmt::Display for WorkloadNotReady {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "workload {} is not ready", self.0)
+    }
+}
+
+impl std::error::Error for WorkloadNotReady {}
+
+/// Whether a workload is ready, from the outcomes of its checks.
+#[derive(Debug, Default)]
+struct Readiness {
+    ready: bool,
+    failures: u32,
+}
+
+impl Readiness {
+    /// Records the outcome of a check, returning the new readiness if it
+    /// changed.
+    fn record(&mut self, passed: bool, threshold: u32) -> Option<bool> {
+        if passed {
+            self.failures = 0;
+            return (!std::mem::replace(&mut self.ready, true)).then_some(true);
+        }
+        self.failures = self.failures.saturating_add(1);
+        if self.ready && self.failures >= threshold {
+            self.ready = false;
+            return Some(false);
+        }
+        None
+    }
+}
+
+/// Checks the health of a workload until the task is aborted, telling
+/// `router` whenever it becomes ready or stops being ready. Requests of path
+/// checks are sent with `host` as their `Host` header.
+pub(super) async fn run<T: Router>(
+    router: Arc<T>,
+    workload_id: String,
+    check: RouteHealthCheck,
+    host: String,
+    handle: WorkloadHandle,
+) {
+    let mut readiness = Readiness::default();
+    let mut ticks = tokio::time::interval(check.interval());
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let outcome =
+            match tokio::time::timeout(check.interval(), probe(&check.probe, &host, &handle)).await
+            {
+                Ok(outcome) => outcome,
+                Err(_) => Err(anyhow::anyhow!("timed out after {:?}", check.interval())),
+            };
+        if let Err(e) = &outcome {
+            debug!(workload_id, probe = %check.probe, err = %e, "health check failed");
+        }
+        match readiness.record(outcome.is_ok(), check.threshold) {
+            Some(true) => info!(workload_id, probe = %check.probe, "workload is ready"),
+            Some(false) => warn!(
+                workload_id,
+                probe = %check.probe,
+                threshold = check.threshold,
+                "workload is not ready, its health checks failed"
+            ),
+            None => continue,
+        }
+        router.set_ready(&workload_id, readiness.ready);
+    }
+}
+
+/// Runs a single check.
+async fn probe(probe: &HealthProbe, host: &str, handle: &WorkloadHandle) -> anyhow::Result<()> {
+    let (workload, instance_pre, component_id, _middleware) = handle.clone();
+    match probe {
+        HealthProbe::Path(path) => {
+            let req = hyper::Request::get(path.as_str())
+                .header(hyper::header::HOST, host)
+                .header(HEALTH_CHECK_HEADER, "1")
+                .body(BufferedBody::default().into_body())?;
+            let response = invoke_component_handler(
+                workload,
+                instance_pre,
+                &component_id,
+                None,
+                req,
+                None,
+                None,
+            )
+            .await?;
+            let status = response.status();
+            // Drain the body so the guest runs to completion
+            response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to read health check response: {e:?}"))?;
+            ensure!(
+                status.is_success() || status.is_redirection(),
+                "answered {status}"
+            );
+        }
+        HealthProbe::Export(export) => {
+            let results = workload.invoke(&component_id, export, &[]).await?;
+            ensure!(passed(&results), "reported the workload unhealthy");
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the results of a health check export are a pass.
+fn passed(results: &[Val]) -> bool {
+    match results {
+        [Val::Bool(healthy)] => *healthy,
+        [Val::Result(result)] => result.is_ok(),
+        _ => true,
+    }
+}
+
+/// The answer to a probe of the host, see the [module docs](self).
+pub(super) fn host_health(
+    unready: Vec<String>,
+    stopping: bool,
+) -> hyper::Response<HyperOutgoingBody> {
+    let (status, body) = if stopping {
+        (503, serde_json::json!({ "status": "stopping" }))
+    } else if unready.is_empty() {
+        (200, serde_json::json!({ "status": "ready" }))
+    } else {
+        (
+            503,
+            serde_json::json!({ "status": "not_ready", "unready": unready }),
+        )
+    };
+    let body = body.to_string();
+    hyper::Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .header(hyper::header::CACHE_CONTROL, "no-store")
+        .body(
+            http_body_util::Full::new(Bytes::from(body))
+                .map_err(|never| match never {})
+                .boxed(),
+        )
+        .expect("failed to build health response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness() {
+        let mut readiness = Readiness::default();
+        // Not ready until a check passes
+        assert_eq!(readiness.record(false, 2), None);
+        assert_eq!(readiness.record(true, 2), Some(true));
+        assert_eq!(readiness.record(true, 2), None);
+        // Ready until `threshold` checks in a row failed
+        assert_eq!(readiness.record(false, 2), None);
+        assert_eq!(readiness.record(true, 2), None);
+        assert_eq!(readiness.record(false, 2), None);
+        assert_eq!(readiness.record(false, 2), Some(false));
+        assert_eq!(readiness.record(false, 2), None);
+        assert_eq!(readiness.record(true, 2), Some(true));
+
+        assert!(passed(&[]));
+        assert!(!passed(&[Val::Bool(false)]));
+        assert!(passed(&[Val::Result(Ok(None))]));
+        assert!(!passed(&[Val::Result(Err(None))]));
+    }
+
+    #[tokio::test]
+    async fn test_host_health() {
+        let response = host_health(Vec::new(), false);
+        assert_eq!(response.status(), 200);
+        let response = host_health(vec!["a".to_string()], false);
+        assert_eq!(response.status(), 503);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({"status": "not_ready", "unready": ["a"]})
+        );
+        assert_eq!(host_health(Vec::new(), true).status(), 503);
+    }
+}
@@ -0,0 +1,287 @@
+//! Hooks operators run on every request of an HTTP server.
+//!
+//! Cross-cutting concerns like validating tokens, answering CORS preflights
+//! or generating request IDs don't have to be compiled into every
+//! component: an [`HttpHook`] registered with
+//! [`HttpServer::with_hook`](super::HttpServer::with_hook) sees every request
+//! the server receives, except probes of the host health, before it is
+//! routed. A hook may
+//!
+//! - rewrite the head of the request, e.g. add headers or change its path,
+//!   which routing then sees,
+//! - answer the request itself, e.g. with `401 Unauthorized`, in which case
+//!   neither later hooks nor a component see it, and
+//! - rewrite or observe the response, e.g. add headers or record how long it
+//!   took.
+//!
+//! Hooks see requests in the order they were registered and responses in the
+//! reverse order, like layers around the component: a hook only sees the
+//! response of a request if it saw the request, including the response it
+//! answered the request with itself. Middleware components a workload
+//! declares run after the hooks, see [`middleware`](super::middleware).
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Method, Request, Response, Uri};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+/// The header [`RequestIdHook`] sets
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// A hook run on every request of an HTTP server, see the
+/// [module docs](self).
+#[async_trait::async_trait]
+pub trait HttpHook: Send + Sync + 'static {
+    /// Runs before the request is routed, and may rewrite its head. Returning
+    /// a response answers the request with it instead of routing it. Lets
+    /// the request through by default.
+    async fn on_request(
+        &self,
+        _request: &mut hyper::http::request::Parts,
+    ) -> Option<Response<HyperOutgoingBody>> {
+        None
+    }
+
+    /// Runs before the response is sent, and may rewrite it. `request` is
+    /// the request as the hooks left it. Does nothing by default.
+    async fn on_response(
+        &self,
+        _request: &HookRequest,
+        _response: &mut Response<HyperOutgoingBody>,
+    ) {
+    }
+}
+
+/// A request as [`HttpHook::on_response`] sees it.
+#[derive(Debug, Clone)]
+pub struct HookRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    /// When the server received the request
+    pub received: Instant,
+}
+
+impl HookRequest {
+    /// Returns how long ago the server received the request.
+    pub fn elapsed(&self) -> Duration {
+        self.received.elapsed()
+    }
+}
+
+/// The hooks of an HTTP server, in the order they see requests.
+#[derive(Clone, Default)]
+pub struct HttpHooks(Vec<Arc<dyn HttpHook>>);
+
+impl std::fmt::Debug for HttpHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HttpHooks").field(&self.0.len()).finish()
+    }
+}
+
+impl HttpHooks {
+    /// Adds a hook seeing requests after the hooks added before it.
+    pub fn push(&mut self, hook: Arc<dyn HttpHook>) {
+        self.0.push(hook);
+    }
+
+    /// Returns whether there are no hooks.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Runs the hooks on `req`, received at `received`, until one answers it.
+    /// Returns the request as the hooks left it, or the response answering
+    /// it, and what [`HttpHooks::on_response`] needs.
+    pub(super) async fn on_request<B>(
+        &self,
+        req: Request<B>,
+        received: Instant,
+    ) -> (Result<Request<B>, Response<HyperOutgoingBody>>, Hooked) {
+        if self.is_empty() {
+            return (Ok(req), Hooked::default());
+        }
+        let (mut parts, body) = req.into_parts();
+        let mut entered = 0;
+        let mut answer = None;
+        for hook in &self.0 {
+            entered += 1;
+            answer = hook.on_request(&mut parts).await;
+            if answer.is_some() {
+                break;
+            }
+        }
+        let hooked = Hooked {
+            entered,
+            request: Some(HookRequest {
+                method: parts.method.clone(),
+                uri: parts.uri.clone(),
+                headers: parts.headers.clone(),
+                received,
+            }),
+        };
+        match answer {
+            Some(response) => (Err(response), hooked),
+            None => (Ok(Request::from_parts(parts, body)), hooked),
+        }
+    }
+
+    /// Runs the hooks that saw a request on its `response`, in reverse order.
+    pub(super) async fn on_response(
+        &self,
+        hooked: Hooked,
+        response: &mut Response<HyperOutgoingBody>,
+    ) {
+        let Some(request) = hooked.request else {
+            return;
+        };
+        for hook in self.0[..hooked.entered].iter().rev() {
+            hook.on_response(&request, response).await;
+        }
+    }
+}
+
+/// The hooks that saw a request, see [`HttpHooks::on_request`].
+#[derive(Debug, Default)]
+pub(super) struct Hooked {
+    entered: usize,
+    request: Option<HookRequest>,
+}
+
+/// Gives requests without an `X-Request-Id` header a generated one, and
+/// responses the ID of their request, so logs of the host and the component
+/// and the client can be matched up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestIdHook;
+
+#[async_trait::async_trait]
+impl HttpHook for RequestIdHook {
+    async fn on_request(
+        &self,
+        request: &mut hyper::http::request::Parts,
+    ) -> Option<Response<HyperOutgoingBody>> {
+        if !request.headers.contains_key(REQUEST_ID_HEADER) {
+            let id = uuid::Uuid::new_v4().to_string();
+            request.headers.insert(
+                REQUEST_ID_HEADER,
+                HeaderValue::from_str(&id).expect("UUIDs are valid header values"),
+            );
+        }
+        None
+    }
+
+    async fn on_response(&self, request: &HookRequest, response: &mut Response<HyperOutgoingBody>) {
+        if let Some(id) = request.headers.get(REQUEST_ID_HEADER) {
+            response
+                .headers_mut()
+                .entry(REQUEST_ID_HEADER)
+                .or_insert_with(|| id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Records the order it is called in, and answers requests to `/deny`
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl HttpHook for Recorder {
+        async fn on_request(
+            &self,
+            request: &mut hyper::http::request::Parts,
+        ) -> Option<Response<HyperOutgoingBody>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("request {}", self.name));
+            request
+                .headers
+                .append("x-hooks", HeaderValue::from_static(self.name));
+            (request.uri.path() == "/deny" && self.name == "a").then(|| {
+                let mut response = Response::new(HyperOutgoingBody::default());
+                *response.status_mut() = hyper::StatusCode::FORBIDDEN;
+                response
+            })
+        }
+
+        async fn on_response(
+            &self,
+            request: &HookRequest,
+            _response: &mut Response<HyperOutgoingBody>,
+        ) {
+            let seen = request.headers.get_all("x-hooks").iter().count();
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("response {} {seen}", self.name));
+        }
+    }
+
+    fn hooks(calls: &Arc<Mutex<Vec<String>>>) -> HttpHooks {
+        let mut hooks = HttpHooks::default();
+        for name in ["a", "b"] {
+            hooks.push(Arc::new(Recorder {
+                name,
+                calls: calls.clone(),
+            }));
+        }
+        hooks
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_in_order() {
+        let calls = Arc::default();
+        let hooks = hooks(&calls);
+        let req = Request::get("/allow").body(()).unwrap();
+        let (req, hooked) = hooks.on_request(req, Instant::now()).await;
+        assert_eq!(req.unwrap().headers().get_all("x-hooks").iter().count(), 2);
+        let mut response = Response::new(HyperOutgoingBody::default());
+        hooks.on_response(hooked, &mut response).await;
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["request a", "request b", "response b 2", "response a 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_answers_request() {
+        let calls = Arc::default();
+        let hooks = hooks(&calls);
+        let req = Request::get("/deny").body(()).unwrap();
+        let (answer, hooked) = hooks.on_request(req, Instant::now()).await;
+        let mut response = answer.unwrap_err();
+        assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+        hooks.on_response(hooked, &mut response).await;
+        assert_eq!(*calls.lock().unwrap(), ["request a", "response a 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_hook() {
+        let mut hooks = HttpHooks::default();
+        hooks.push(Arc::new(RequestIdHook));
+
+        let req = Request::get("/").body(()).unwrap();
+        let (req, hooked) = hooks.on_request(req, Instant::now()).await;
+        let id = req.unwrap().headers()[REQUEST_ID_HEADER].clone();
+        let mut response = Response::new(HyperOutgoingBody::default());
+        hooks.on_response(hooked, &mut response).await;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], id);
+
+        let req = Request::get("/")
+            .header(REQUEST_ID_HEADER, "abc")
+            .body(())
+            .unwrap();
+        let (req, _) = hooks.on_request(req, Instant::now()).await;
+        assert_eq!(req.unwrap().headers()[REQUEST_ID_HEADER], "abc");
+    }
+}
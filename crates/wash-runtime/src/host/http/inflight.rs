@@ -0,0 +1,286 @@
+//! The requests an HTTP server is handling.
+//!
+//! Every request is tracked from the moment the server receives it until the
+//! head of its response is sent, so an operator can see which routes are
+//! slow while it happens, with the `http.introspect` API command, see
+//! [`crate::host::HostApi::http_introspect`]. A request is
+//!
+//! - [`RequestPhase::Queued`] while the host routes it, checks it and waits
+//!   for its component to be instantiated, e.g. on a busy execution pool, and
+//! - [`RequestPhase::Running`] once a component instance handles it.
+//!
+//! [`route_activity`] sums the requests up by route: how many are queued,
+//! how many instances are busy with them and how long the oldest has waited.
+//! Every request gets its own instance, so busy instances are requests
+//! running. Requests mirrored to a shadow workload are not tracked.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Whether a request waits for or runs in a component instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPhase {
+    /// Received, but not handled by a component instance yet
+    Queued,
+    /// Handled by a component instance
+    Running,
+}
+
+/// A request an HTTP server is handling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InFlightRequest {
+    pub method: String,
+    /// The `Host` header of the request
+    pub host: String,
+    pub path: String,
+    /// The id of the request from its `X-Request-Id` header
+    pub request_id: Option<String>,
+    /// The workload the request was routed to, `None` until it is routed
+    pub workload_id: Option<String>,
+    /// The route the request matched, see [`super::RoutePolicy::name`]
+    pub route: Option<String>,
+    pub phase: RequestPhase,
+    pub received_at: chrono::DateTime<chrono::Utc>,
+    /// How long ago the request was received
+    pub age: Duration,
+}
+
+/// The requests in flight on a route of a workload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteActivity {
+    pub workload_id: String,
+    pub route: Option<String>,
+    /// Requests waiting for a component instance
+    pub queued: usize,
+    /// Component instances handling requests
+    pub busy: usize,
+    /// The age of the oldest request
+    pub oldest: Duration,
+}
+
+/// Sums up in-flight requests by workload and route. Requests that weren't
+/// routed yet are left out.
+pub fn route_activity(requests: &[InFlightRequest]) -> Vec<RouteActivity> {
+    let mut activity: BTreeMap<(&str, Option<&str>), RouteActivity> = BTreeMap::new();
+    for request in requests {
+        let Some(workload_id) = request.workload_id.as_deref() else {
+            continue;
+        };
+        let route = activity
+            .entry((workload_id, request.route.as_deref()))
+            .or_insert_with(|| RouteActivity {
+                workload_id: workload_id.to_string(),
+                route: request.route.clone(),
+                queued: 0,
+                busy: 0,
+                oldest: Duration::ZERO,
+            });
+        match request.phase {
+            RequestPhase::Queued => route.queued += 1,
+            RequestPhase::Running => route.busy += 1,
+        }
+        route.oldest = route.oldest.max(request.age);
+    }
+    activity.into_values().collect()
+}
+
+/// A request as tracked, see [`InFlightRequests::list`]
+#[derive(Debug)]
+struct Tracked {
+    request: InFlightRequest,
+    received: Instant,
+}
+
+/// Tracks the requests of an HTTP server, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InFlightRequests {
+    next_id: Arc<AtomicU64>,
+    requests: Arc<std::sync::Mutex<HashMap<u64, Tracked>>>,
+}
+
+impl InFlightRequests {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Tracked>> {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Starts tracking a request, until the returned guard is dropped.
+    pub(crate) fn track<B>(&self, req: &hyper::Request<B>) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let host = req
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or_default();
+        let request = InFlightRequest {
+            method: req.method().to_string(),
+            host: host.to_string(),
+            path: req.uri().path().to_string(),
+            request_id: super::request_id(req.headers()).map(str::to_string),
+            workload_id: None,
+            route: None,
+            phase: RequestPhase::Queued,
+            received_at: chrono::Utc::now(),
+            age: Duration::ZERO,
+        };
+        self.lock().insert(
+            id,
+            Tracked {
+                request,
+                received: Instant::now(),
+            },
+        );
+        InFlightGuard(InFlight {
+            requests: self.clone(),
+            id,
+        })
+    }
+
+    /// Returns the requests in flight, the oldest first.
+    pub(crate) fn list(&self) -> Vec<InFlightRequest> {
+        let mut requests: Vec<InFlightRequest> = self
+            .lock()
+            .values()
+            .map(|tracked| InFlightRequest {
+                age: tracked.received.elapsed(),
+                ..tracked.request.clone()
+            })
+            .collect();
+        requests.sort_by(|a, b| b.age.cmp(&a.age));
+        requests
+    }
+}
+
+/// A tracked request, which may be updated after its guard was dropped
+/// without effect.
+#[derive(Debug, Clone)]
+pub(crate) struct InFlight {
+    requests: InFlightRequests,
+    id: u64,
+}
+
+impl InFlight {
+    fn update(&self, update: impl FnOnce(&mut InFlightRequest)) {
+        if let Some(tracked) = self.requests.lock().get_mut(&self.id) {
+            update(&mut tracked.request);
+        }
+    }
+
+    /// Records the workload and route the request was routed to.
+    pub(crate) fn routed(&self, workload_id: &str, route: Option<&str>) {
+        self.update(|request| {
+            request.workload_id = Some(workload_id.to_string());
+            request.route = route.map(str::to_string);
+        });
+    }
+
+    /// Records that a component instance handles the request.
+    pub(crate) fn running(&self) {
+        self.update(|request| request.phase = RequestPhase::Running);
+    }
+}
+
+/// Stops tracking a request when dropped.
+#[derive(Debug)]
+pub(crate) struct InFlightGuard(InFlight);
+
+impl std::ops::Deref for InFlightGuard {
+    type Target = InFlight;
+
+    fn deref(&self) -> &InFlight {
+        &self.0
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.requests.lock().remove(&self.0.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> hyper::Request<()> {
+        hyper::Request::builder()
+            .uri(path)
+            .header(hyper::header::HOST, "example.com")
+            .header("x-request-id", "req-1")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_track_requests() {
+        let requests = InFlightRequests::default();
+        let first = requests.track(&request("/users/1"));
+        let second = requests.track(&request("/health"));
+        first.routed("default/users", Some("example.com/users/"));
+        first.running();
+
+        let listed = requests.list();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].path, "/users/1");
+        assert_eq!(listed[0].host, "example.com");
+        assert_eq!(listed[0].request_id.as_deref(), Some("req-1"));
+        assert_eq!(listed[0].phase, RequestPhase::Running);
+        assert_eq!(listed[1].workload_id, None);
+        assert_eq!(listed[1].phase, RequestPhase::Queued);
+
+        // Updates after a request finished are ignored
+        let handle = first.clone();
+        drop(first);
+        handle.running();
+        assert_eq!(requests.list().len(), 1);
+        drop(second);
+        assert!(requests.list().is_empty());
+    }
+
+    #[test]
+    fn test_route_activity() {
+        let request = |workload_id: Option<&str>, phase, age| InFlightRequest {
+            method: "GET".to_string(),
+            host: "example.com".to_string(),
+            path: "/".to_string(),
+            request_id: None,
+            workload_id: workload_id.map(str::to_string),
+            route: workload_id.map(|_| "example.com/".to_string()),
+            phase,
+            received_at: chrono::Utc::now(),
+            age: Duration::from_millis(age),
+        };
+        let activity = route_activity(&[
+            request(Some("a"), RequestPhase::Running, 300),
+            request(Some("a"), RequestPhase::Queued, 50),
+            request(Some("a"), RequestPhase::Running, 100),
+            request(Some("b"), RequestPhase::Queued, 10),
+            request(None, RequestPhase::Queued, 900),
+        ]);
+        assert_eq!(
+            activity,
+            vec![
+                RouteActivity {
+                    workload_id: "a".to_string(),
+                    route: Some("example.com/".to_string()),
+                    queued: 1,
+                    busy: 2,
+                    oldest: Duration::from_millis(300),
+                },
+                RouteActivity {
+                    workload_id: "b".to_string(),
+                    route: Some("example.com/".to_string()),
+                    queued: 1,
+                    busy: 0,
+                    oldest: Duration::from_millis(10),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Request metrics for the HTTP server.
+//!
+//! Every request is recorded in the `http.server.request.duration` histogram,
+//! labelled with its method, status code and the route it matched. The rate,
+//! errors and duration (RED) of each route follow from the histogram's count,
+//! the count of measurements with an `error.type`, and its buckets. Requests
+//! to a route split between workloads by weight are also labelled with the
+//! `wasmcloud.workload.name` serving them, see [`super::split`]. Durations
+//! are measured until the response head is sent, not until a streamed body
+//! finishes.
+//!
+//! Requests that are slow or fail with a server error are recorded in the
+//! context of the trace named by their W3C `traceparent` header. Meter
+//! providers that sample exemplars attach that trace to the measurement, so a
+//! dashboard can jump from a latency spike or error burst straight to a
+//! representative trace. Other requests are recorded without a trace so the
+//! exemplars point at the requests worth looking at.
+
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Histogram, Meter};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt as _, TraceFlags, TraceId};
+use opentelemetry_semantic_conventions::{attribute, metric};
+
+/// Requests that take at least this long are linked to their trace by default
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(1);
+/// W3C trace context header naming the trace a request belongs to
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Bucket boundaries in seconds recommended for `http.server.request.duration`
+const DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// Instruments recording the requests handled by an HTTP server.
+pub(crate) struct HttpMetrics {
+    request_duration: Histogram<f64>,
+    slow_request_threshold: Duration,
+}
+
+impl HttpMetrics {
+    pub(crate) fn new(meter: &Meter, slow_request_threshold: Duration) -> Self {
+        let request_duration = meter
+            .f64_histogram(metric::HTTP_SERVER_REQUEST_DURATION)
+            .with_unit("s")
+            .with_description("Duration of HTTP requests handled by components")
+            .with_boundaries(DURATION_BUCKETS.to_vec())
+            .build();
+        Self {
+            request_duration,
+            slow_request_threshold,
+        }
+    }
+
+    /// Records a handled request. `route` is `None` for requests that matched
+    /// no route, and `split_target` the workload serving a split route.
+    pub(crate) fn record(
+        &self,
+        route: Option<&str>,
+        split_target: Option<&str>,
+        method: &hyper::Method,
+        status: hyper::StatusCode,
+        duration: Duration,
+        trace: Option<SpanContext>,
+    ) {
+        let mut attributes = vec![
+            KeyValue::new(attribute::HTTP_REQUEST_METHOD, method.as_str().to_string()),
+            KeyValue::new(
+                attribute::HTTP_RESPONSE_STATUS_CODE,
+                i64::from(status.as_u16()),
+            ),
+        ];
+        if let Some(route) = route {
+            attributes.push(KeyValue::new(attribute::HTTP_ROUTE, route.to_string()));
+        }
+        if let Some(workload_name) = split_target {
+            attributes.push(KeyValue::new(
+                crate::host::metrics::WORKLOAD_NAME_ATTRIBUTE,
+                workload_name.to_string(),
+            ));
+        }
+        if status.is_server_error() {
+            attributes.push(KeyValue::new(
+                attribute::ERROR_TYPE,
+                status.as_u16().to_string(),
+            ));
+        }
+
+        let seconds = duration.as_secs_f64();
+        match trace.filter(|_| self.links_trace(status, duration)) {
+            Some(trace) => {
+                let _guard = opentelemetry::Context::current()
+                    .with_remote_span_context(trace)
+                    .attach();
+                self.request_duration.record(seconds, &attributes);
+            }
+            None => self.request_duration.record(seconds, &attributes),
+        }
+    }
+
+    /// Returns whether a request is recorded in the context of its trace.
+    fn links_trace(&self, status: hyper::StatusCode, duration: Duration) -> bool {
+        status.is_server_error() || duration >= self.slow_request_threshold
+    }
+}
+
+/// Parses a W3C `traceparent` header, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+pub(crate) fn parse_traceparent(headers: &hyper::HeaderMap) -> Option<SpanContext> {
+    let value = headers.get(TRACEPARENT_HEADER)?.to_str().ok()?.trim();
+    let mut parts = value.split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = parts.next()?;
+    // Version 00 has exactly four fields, later versions may append more
+    if version.len() != 2
+        || version.eq_ignore_ascii_case("ff")
+        || (version == "00" && parts.next().is_some())
+        || trace_id.len() != 32
+        || span_id.len() != 16
+        || flags.len() != 2
+    {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(trace_id).ok()?;
+    let span_id = SpanId::from_hex(span_id).ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    let context = SpanContext::new(
+        trace_id,
+        span_id,
+        TraceFlags::new(flags),
+        true,
+        Default::default(),
+    );
+    context.is_valid().then_some(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(traceparent: &str) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(TRACEPARENT_HEADER, traceparent.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_parse_traceparent() {
+        let context = parse_traceparent(&headers(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ))
+        .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(
+            context.span_id(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+        assert!(context.is_sampled());
+        assert!(context.is_remote());
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-xyz92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(parse_traceparent(&headers(invalid)).is_none(), "{invalid}");
+        }
+        assert!(parse_traceparent(&hyper::HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_links_trace() {
+        let meter = opentelemetry::global::meter("test");
+        let metrics = HttpMetrics::new(&meter, Duration::from_millis(500));
+        assert!(!metrics.links_trace(hyper::StatusCode::OK, Duration::from_millis(10)));
+        assert!(!metrics.links_trace(hyper::StatusCode::NOT_FOUND, Duration::from_millis(10)));
+        assert!(metrics.links_trace(hyper::StatusCode::OK, Duration::from_millis(500)));
+        assert!(metrics.links_trace(hyper::StatusCode::BAD_GATEWAY, Duration::from_millis(10)));
+    }
+}
@@ -0,0 +1,209 @@
+//! Middleware components handling the requests of a workload first.
+//!
+//! A workload may declare one of its components as middleware, see
+//! [`ComponentKind::Middleware`](crate::types::ComponentKind::Middleware).
+//! The middleware exports `wasi:http/incoming-handler` like the serving
+//! component, and every request routed to the workload is handed to it
+//! instead, once the route accepted it. It may answer the request itself,
+//! e.g. to reject a request failing its own authentication, or pass it on to
+//! the serving component by sending it to [`NEXT_AUTHORITY`] with
+//! `wasi:http/outgoing-handler`:
+//!
+//! ```text
+//! GET http://next/users/42
+//! ```
+//!
+//! The middleware may change the method, path, headers and body of the
+//! request it passes on, and the response it gets back before answering with
+//! it, so authentication, rewriting or enrichment is added to a workload
+//! without recompiling the component serving it.
+//!
+//! Requests passed on are not subject to the `allowed_hosts` of the
+//! middleware. The host sets the `Host` header and the route headers of the
+//! original request on them again, see [`RouteMatch`](super::RouteMatch),
+//! holds them to the deadline of the original request, see [`deadline`], and
+//! reads their body completely before invoking the serving component.
+
+use http_body_util::BodyExt as _;
+use hyper::header::HeaderMap;
+use hyper::{Request, Uri};
+use wasmtime::component::InstancePre;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::{
+    HostFutureIncomingResponse, IncomingResponse, OutgoingRequestConfig,
+};
+
+use super::{
+    BufferedBody, ROUTE_HEADER, ROUTE_PARAMS_HEADER, ROUTE_PREFIX_HEADER, ROUTE_WORKLOAD_HEADER,
+    deadline, experiment, invoke_component_handler,
+};
+use crate::engine::ctx::Ctx;
+use crate::engine::workload::ResolvedWorkload;
+
+/// Authority of the requests a middleware passes on to the serving component
+pub const NEXT_AUTHORITY: &str = "next";
+
+/// Headers of the original request set again on the requests passed on
+const HOST_HEADERS: [&str; 6] = [
+    "host",
+    ROUTE_WORKLOAD_HEADER,
+    ROUTE_HEADER,
+    ROUTE_PREFIX_HEADER,
+    ROUTE_PARAMS_HEADER,
+    experiment::EXPERIMENT_HEADER,
+];
+
+/// The middleware component of a workload
+#[derive(Clone)]
+pub struct Middleware {
+    pub(super) instance_pre: InstancePre<Ctx>,
+    pub(super) component_id: String,
+}
+
+impl Middleware {
+    pub(super) fn new(instance_pre: InstancePre<Ctx>, component_id: impl Into<String>) -> Self {
+        Self {
+            instance_pre,
+            component_id: component_id.into(),
+        }
+    }
+}
+
+/// The serving component a middleware passes a request on to.
+#[derive(Clone)]
+pub struct Next {
+    workload: ResolvedWorkload,
+    instance_pre: InstancePre<Ctx>,
+    component_id: String,
+    /// The [`HOST_HEADERS`] of the original request
+    headers: HeaderMap,
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl Next {
+    /// Passes requests on to the component `component_id` of `workload`,
+    /// with the headers the host set on `original` and held to `deadline`.
+    pub(super) fn new(
+        workload: ResolvedWorkload,
+        instance_pre: InstancePre<Ctx>,
+        component_id: impl Into<String>,
+        original: &HeaderMap,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Self {
+        let mut headers = HeaderMap::new();
+        for name in HOST_HEADERS {
+            if let Some(value) = original.get(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        Self {
+            workload,
+            instance_pre,
+            component_id: component_id.into(),
+            headers,
+            deadline,
+        }
+    }
+
+    /// Whether an outgoing request of the middleware is addressed to the
+    /// serving component.
+    pub fn matches(request: &Request<HyperOutgoingBody>) -> bool {
+        request
+            .uri()
+            .host()
+            .is_some_and(|host| host.eq_ignore_ascii_case(NEXT_AUTHORITY))
+    }
+
+    /// Invokes the serving component with `request`.
+    pub fn send(
+        &self,
+        request: Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HostFutureIncomingResponse {
+        let next = self.clone();
+        let handle =
+            wasmtime_wasi::runtime::spawn(async move { Ok(next.invoke(request, config).await) });
+        HostFutureIncomingResponse::pending(handle)
+    }
+
+    async fn invoke(
+        self,
+        request: Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let (mut parts, body) = request.into_parts();
+        parts.uri = forwarded_uri(&parts.uri);
+        for (name, value) in &self.headers {
+            parts.headers.insert(name, value.clone());
+        }
+        if let Some(deadline) = self.deadline {
+            let now = tokio::time::Instant::now();
+            if deadline <= now {
+                return Err(ErrorCode::HttpResponseTimeout);
+            }
+            deadline::set_remaining(&mut parts.headers, deadline - now);
+        }
+        let body = body.collect().await?;
+        let body = BufferedBody {
+            trailers: body.trailers().cloned(),
+            data: body.to_bytes(),
+        };
+
+        let resp = invoke_component_handler(
+            self.workload,
+            self.instance_pre,
+            &self.component_id,
+            None,
+            Request::from_parts(parts, body.into_body()),
+            self.deadline,
+            None,
+        )
+        .await
+        .map_err(|e| ErrorCode::InternalError(Some(format!("{e:#}"))))?;
+        Ok(IncomingResponse {
+            resp,
+            worker: None,
+            between_bytes_timeout: config.between_bytes_timeout,
+        })
+    }
+}
+
+/// Returns the path and query of the URI of a request passed on, as the
+/// serving component would receive it from a client.
+fn forwarded_uri(uri: &Uri) -> Uri {
+    let path = match uri.query() {
+        Some(query) => format!("{}?{query}", uri.path()),
+        None => uri.path().to_string(),
+    };
+    path.parse().unwrap_or_else(|_| Uri::from_static("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str) -> Request<HyperOutgoingBody> {
+        Request::builder()
+            .uri(uri)
+            .body(HyperOutgoingBody::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_matches_next() {
+        assert!(Next::matches(&request("http://next/users/42")));
+        assert!(Next::matches(&request("https://NEXT:8080/")));
+        assert!(!Next::matches(&request("http://next.example.com/")));
+        assert!(!Next::matches(&request("/users/42")));
+    }
+
+    #[test]
+    fn test_forwarded_uri() {
+        assert_eq!(
+            forwarded_uri(&"http://next/users/42?full=true".parse().unwrap()),
+            "/users/42?full=true"
+        );
+        assert_eq!(forwarded_uri(&"http://next".parse().unwrap()), "/");
+    }
+}
@@ -0,0 +1,145 @@
+//! Rate limits of routes.
+//!
+//! A route may limit how many requests a client sends it: every client gets
+//! a token bucket holding up to `burst` requests, refilled with
+//! `requests_per_second`. A request that finds the bucket of its client empty
+//! is answered with `429 Too Many Requests` and a `Retry-After` header before
+//! an instance of the component is acquired, so floods don't drain the
+//! instance pool of the workload.
+//!
+//! Clients are told apart by their IP address, as resolved from the
+//! forwarding headers the server trusts, see [`forwarded`](super::forwarded),
+//! or by the value of a request header such as an API key. Requests without
+//! that header are told apart by their IP address.
+//!
+//! In the string config map of `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! rate_limit_rps: 50
+//! rate_limit_burst: 100              # optional, defaults to rate_limit_rps
+//! rate_limit_key: header:x-api-key   # optional, defaults to ip
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hyper::header::HeaderMap;
+
+use super::forwarded::REAL_IP_HEADER;
+pub use crate::types::route::rate_limit::{
+    RATE_LIMIT_BURST_CONFIG_KEY, RATE_LIMIT_KEY_CONFIG_KEY, RATE_LIMIT_RPS_CONFIG_KEY,
+    RateLimitKey, RouteRateLimit,
+};
+
+/// Clients tracked before the buckets of idle clients are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// The tokens left to a client.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Enforces a [`RouteRateLimit`] with a token bucket per client.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+    limit: RouteRateLimit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub(super) fn new(limit: RouteRateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of the client sending a request with
+    /// `headers`, returning how long the client should wait if it is empty.
+    pub(super) fn check(&self, headers: &HeaderMap) -> Result<(), Duration> {
+        let key = match &self.limit.key {
+            RateLimitKey::Header(name) => headers.get(name.as_str()),
+            RateLimitKey::ClientIp => None,
+        }
+        .or_else(|| headers.get(REAL_IP_HEADER))
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default();
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: String, now: Instant) -> Result<(), Duration> {
+        let rate = f64::from(self.limit.requests_per_second);
+        let capacity = f64::from(self.limit.burst());
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&key) {
+            // Clients whose bucket refilled are as good as new
+            buckets.retain(|_, bucket| {
+                bucket.tokens
+                    + now
+                        .saturating_duration_since(bucket.refilled_at)
+                        .as_secs_f64()
+                        * rate
+                    < capacity
+            });
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(capacity);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+}
+
+/// Returns the `Retry-After` value for waiting `wait`, in whole seconds.
+pub(super) fn retry_after(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills() {
+        let limiter = RateLimiter::new(RouteRateLimit::new(2).with_burst(3));
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at("a".into(), start).is_ok());
+        }
+        let wait = limiter.check_at("a".into(), start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        assert_eq!(retry_after(wait), 1);
+        // Other clients have their own bucket
+        assert!(limiter.check_at("b".into(), start).is_ok());
+
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check_at("a".into(), later).is_ok());
+        assert!(limiter.check_at("a".into(), later).is_err());
+    }
+
+    #[test]
+    fn test_key_falls_back_to_client_ip() {
+        let limiter = RateLimiter::new(RouteRateLimit::new(1).with_header_key("X-Api-Key"));
+        let mut headers = HeaderMap::new();
+        headers.insert(REAL_IP_HEADER, HeaderValue::from_static("203.0.113.9"));
+        assert!(limiter.check(&headers).is_ok());
+        assert!(limiter.check(&headers).is_err());
+        headers.insert("x-api-key", HeaderValue::from_static("secret"));
+        assert!(limiter.check(&headers).is_ok());
+    }
+}
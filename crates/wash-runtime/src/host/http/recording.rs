@@ -0,0 +1,617 @@
+//! Recording the HTTP requests of a workload to replay them later.
+//!
+//! Bugs that only show with production traffic are hard to reproduce by
+//! hand. While a workload is recorded, see
+//! [`HostApi::http_recording_start`](crate::host::HostApi::http_recording_start),
+//! every request routed to it is appended to a file as a
+//! [`RecordedInvocation`], one JSON object per line, once its response
+//! starts:
+//!
+//! - the method, URI and headers the component received, including those
+//!   the host adds, e.g. the experiment assignment,
+//! - the body, up to [`Recording::with_max_body_bytes`] bytes, and its size,
+//! - when it was received, relative to the start of the recording, and
+//! - the status of the response, the time to it and the error if the
+//!   component failed.
+//!
+//! The `Authorization`, `Proxy-Authorization`, `Cookie` and `X-Api-Key`
+//! headers are recorded as [`REDACTED`] unless
+//! [`Recording::with_sensitive_headers`] is set, and left out of replayed
+//! requests. Trailers and WebSocket handshakes aren't recorded. Requests
+//! answered by the host before reaching the component, e.g. from the
+//! response cache or for breaking the request limits, aren't recorded
+//! either. A recording stops when its workload stops.
+//!
+//! [`HostApi::replay`](crate::host::HostApi::replay) sends the requests of a
+//! recording to a workload, typically one running a new build of the
+//! component, one after the other, optionally paced like they were
+//! received. Replayed requests go straight to the component of the
+//! workload, bypassing routing, hooks and caching, and its responses are
+//! only compared by status, see [`ReplayReport::diverged`].
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use http_body_util::BodyExt as _;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{BufferedBody, RequestBody, WorkloadHandle};
+
+/// Bytes of a request body recorded if not configured
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// The value recorded in place of sensitive headers
+pub const REDACTED: &str = "<redacted>";
+
+/// Headers carrying credentials, redacted unless configured otherwise
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+];
+
+/// The recording of the requests of a workload to a file, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    workload_id: String,
+    path: PathBuf,
+    max_body_bytes: usize,
+    max_invocations: Option<u64>,
+    sensitive_headers: bool,
+}
+
+impl Recording {
+    /// Records the requests of `workload_id`, appending them to `path`.
+    pub fn new(workload_id: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            workload_id: workload_id.into(),
+            path: path.into(),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_invocations: None,
+            sensitive_headers: false,
+        }
+    }
+
+    /// Records up to `max` bytes of each request body, the rest is dropped.
+    pub fn with_max_body_bytes(mut self, max: usize) -> Self {
+        self.max_body_bytes = max;
+        self
+    }
+
+    /// Stops recording requests once `max` were recorded.
+    pub fn with_max_invocations(mut self, max: u64) -> Self {
+        self.max_invocations = Some(max);
+        self
+    }
+
+    /// Records the headers carrying credentials as they were sent, rather
+    /// than [`REDACTED`].
+    pub fn with_sensitive_headers(mut self, record: bool) -> Self {
+        self.sensitive_headers = record;
+        self
+    }
+
+    pub fn workload_id(&self) -> &str {
+        &self.workload_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// A workload being recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingStatus {
+    pub workload_id: String,
+    pub path: PathBuf,
+    /// The requests recorded so far, including those awaiting their response
+    pub recorded: u64,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A request recorded with the response of the component, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInvocation {
+    /// When the request was received, in RFC 3339
+    pub recorded_at: String,
+    /// Time from the start of the recording to the request
+    pub offset_ms: u64,
+    /// The component that served the request
+    pub component_id: String,
+    pub method: String,
+    pub uri: String,
+    /// The headers in the order they were received, sensitive ones redacted
+    pub headers: Vec<(String, String)>,
+    /// The start of the body, see [`RecordedInvocation::is_truncated`]
+    #[serde(with = "crate::types::base64_bytes")]
+    pub body: Bytes,
+    /// Size of the whole body
+    pub body_size: u64,
+    pub status: u16,
+    /// Time from receiving the request to the start of the response
+    pub duration_ms: f64,
+    /// The error of the component, if it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RecordedInvocation {
+    /// Returns whether the body was cut at the size limit of the recording
+    pub fn is_truncated(&self) -> bool {
+        (self.body.len() as u64) < self.body_size
+    }
+
+    /// Builds the request to replay, leaving out redacted headers. A
+    /// truncated body is sent without its `Content-Length`.
+    fn request(&self) -> anyhow::Result<hyper::Request<RequestBody>> {
+        let mut req = hyper::Request::new(
+            BufferedBody {
+                data: self.body.clone(),
+                trailers: None,
+            }
+            .into_body(),
+        );
+        *req.method_mut() = self
+            .method
+            .parse()
+            .with_context(|| format!("invalid method {}", self.method))?;
+        *req.uri_mut() = self
+            .uri
+            .parse()
+            .with_context(|| format!("invalid URI {}", self.uri))?;
+        for (name, value) in &self.headers {
+            let name: hyper::header::HeaderName = name
+                .parse()
+                .with_context(|| format!("invalid header name {name}"))?;
+            if value == REDACTED || (self.is_truncated() && name == hyper::header::CONTENT_LENGTH) {
+                continue;
+            }
+            let value = hyper::header::HeaderValue::from_str(value)
+                .with_context(|| format!("invalid value of header {name}"))?;
+            req.headers_mut().append(name, value);
+        }
+        Ok(req)
+    }
+}
+
+/// Reads the requests of a recording, in the order they were recorded.
+pub async fn read_recording(path: &Path) -> anyhow::Result<Vec<RecordedInvocation>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read recording {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "invalid recorded request on line {} of {}",
+                    index + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// The workloads being recorded by an HTTP server.
+#[derive(Debug, Default)]
+pub(super) struct Recorder {
+    active: RwLock<HashMap<String, Arc<ActiveRecording>>>,
+}
+
+impl Recorder {
+    /// Starts a recording, replacing the recording of the same workload.
+    pub(super) fn start(&self, recording: Recording) -> anyhow::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&recording.path)
+            .with_context(|| format!("failed to open recording {}", recording.path.display()))?;
+        info!(
+            workload_id = %recording.workload_id,
+            path = %recording.path.display(),
+            "recording HTTP requests"
+        );
+        let active = ActiveRecording {
+            started: Instant::now(),
+            started_at: chrono::Utc::now(),
+            recorded: AtomicU64::new(0),
+            file: Mutex::new(file),
+            recording,
+        };
+        self.active
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(active.recording.workload_id.clone(), Arc::new(active));
+        Ok(())
+    }
+
+    /// Stops recording a workload. Requests awaiting their response are still
+    /// written.
+    pub(super) fn stop(&self, workload_id: &str) -> Option<RecordingStatus> {
+        let active = self
+            .active
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(workload_id)?;
+        let status = active.status();
+        info!(
+            workload_id,
+            path = %status.path.display(),
+            recorded = status.recorded,
+            "stopped recording HTTP requests"
+        );
+        Some(status)
+    }
+
+    pub(super) fn list(&self) -> Vec<RecordingStatus> {
+        let mut recordings: Vec<_> = self
+            .active
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .values()
+            .map(|active| active.status())
+            .collect();
+        recordings.sort_by(|a, b| a.workload_id.cmp(&b.workload_id));
+        recordings
+    }
+
+    /// Returns the recording of a workload, if it is being recorded.
+    pub(super) fn get(&self, workload_id: &str) -> Option<Arc<ActiveRecording>> {
+        self.active
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(workload_id)
+            .cloned()
+    }
+}
+
+/// A recording in progress
+#[derive(Debug)]
+pub(super) struct ActiveRecording {
+    recording: Recording,
+    started: Instant,
+    started_at: chrono::DateTime<chrono::Utc>,
+    recorded: AtomicU64,
+    file: Mutex<File>,
+}
+
+impl ActiveRecording {
+    fn status(&self) -> RecordingStatus {
+        RecordingStatus {
+            workload_id: self.recording.workload_id.clone(),
+            path: self.recording.path.clone(),
+            recorded: self.recorded.load(Ordering::Relaxed),
+            started_at: self.started_at,
+        }
+    }
+
+    /// Records a request with its buffered body, unless the recording has as
+    /// many requests as it allows. The request is written once
+    /// [`PendingInvocation::finish`] is called with its response.
+    pub(super) fn begin(
+        self: &Arc<Self>,
+        component_id: &str,
+        parts: &hyper::http::request::Parts,
+        body: &Bytes,
+    ) -> Option<PendingInvocation> {
+        let max = self.recording.max_invocations.unwrap_or(u64::MAX);
+        self.recorded
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |recorded| {
+                (recorded < max).then_some(recorded + 1)
+            })
+            .ok()?;
+        let headers = parts
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if !self.recording.sensitive_headers
+                    && SENSITIVE_HEADERS.contains(&name.as_str())
+                {
+                    REDACTED.to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        Some(PendingInvocation {
+            recording: self.clone(),
+            received: Instant::now(),
+            invocation: RecordedInvocation {
+                recorded_at: chrono::Utc::now()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                offset_ms: self.started.elapsed().as_millis() as u64,
+                component_id: component_id.to_string(),
+                method: parts.method.to_string(),
+                uri: parts.uri.to_string(),
+                headers,
+                body: body.slice(..body.len().min(self.recording.max_body_bytes)),
+                body_size: body.len() as u64,
+                status: 0,
+                duration_ms: 0.0,
+                error: None,
+            },
+        })
+    }
+
+    fn write(&self, invocation: &RecordedInvocation) {
+        let Ok(mut line) = serde_json::to_vec(invocation) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Err(e) = file.write_all(&line) {
+            warn!(
+                path = %self.recording.path.display(),
+                err = ?e,
+                "failed to write recorded request"
+            );
+        }
+    }
+}
+
+/// A recorded request awaiting its response
+pub(super) struct PendingInvocation {
+    recording: Arc<ActiveRecording>,
+    received: Instant,
+    invocation: RecordedInvocation,
+}
+
+impl PendingInvocation {
+    /// Writes the request with the status of its response, and the error of
+    /// the component if it failed.
+    pub(super) fn finish(mut self, status: hyper::StatusCode, error: Option<String>) {
+        self.invocation.status = status.as_u16();
+        self.invocation.duration_ms = self.received.elapsed().as_secs_f64() * 1000.0;
+        self.invocation.error = error;
+        self.recording.write(&self.invocation);
+    }
+}
+
+/// A replayed request and how the response compares to the recorded one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayedInvocation {
+    /// The position of the request in the recording
+    pub index: usize,
+    pub method: String,
+    pub uri: String,
+    pub recorded_status: u16,
+    pub status: u16,
+    /// Time from sending the request to the end of the response
+    pub duration_ms: f64,
+    /// The error of the component, if it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReplayedInvocation {
+    /// Returns whether the response has another status than the recorded one
+    pub fn diverged(&self) -> bool {
+        self.status != self.recorded_status
+    }
+}
+
+/// The outcome of replaying a recording, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReplayReport {
+    pub workload_id: String,
+    /// The replayed requests, in the order they were sent
+    pub invocations: Vec<ReplayedInvocation>,
+}
+
+impl ReplayReport {
+    /// Returns the replayed requests whose responses have another status
+    /// than the recorded ones.
+    pub fn diverged(&self) -> impl Iterator<Item = &ReplayedInvocation> {
+        self.invocations
+            .iter()
+            .filter(|invocation| invocation.diverged())
+    }
+}
+
+/// Sends recorded requests to a workload one after the other, spaced like
+/// they were recorded if `pace` is set, each limited to `timeout`.
+pub(super) async fn replay(
+    workload_id: &str,
+    handle: WorkloadHandle,
+    invocations: &[RecordedInvocation],
+    pace: bool,
+    timeout: Option<Duration>,
+) -> anyhow::Result<ReplayReport> {
+    // Every request is checked before any is sent
+    let requests = invocations
+        .iter()
+        .enumerate()
+        .map(|(index, invocation)| {
+            invocation
+                .request()
+                .with_context(|| format!("invalid recorded request {index}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (workload, instance_pre, component_id, middleware) = handle;
+    let started = tokio::time::Instant::now();
+    let first_offset = invocations
+        .first()
+        .map_or(0, |invocation| invocation.offset_ms);
+    let mut report = ReplayReport {
+        workload_id: workload_id.to_string(),
+        invocations: Vec::with_capacity(invocations.len()),
+    };
+    for (index, (invocation, req)) in invocations.iter().zip(requests).enumerate() {
+        if pace {
+            let offset = invocation.offset_ms.saturating_sub(first_offset);
+            tokio::time::sleep_until(started + Duration::from_millis(offset)).await;
+        }
+        let sent = Instant::now();
+        let deadline = timeout.map(|limit| tokio::time::Instant::now() + limit);
+        let replayed = async {
+            let response = super::invoke_component_handler(
+                workload.clone(),
+                instance_pre.clone(),
+                &component_id,
+                middleware.clone(),
+                req,
+                deadline,
+                None,
+            )
+            .await?;
+            let status = response.status();
+            // Drain the body so the guest runs to completion
+            response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to read response body: {e:?}"))?;
+            anyhow::Ok(status)
+        };
+        let result = match timeout {
+            Some(limit) => tokio::time::timeout(limit, replayed)
+                .await
+                .map_err(|_| limit),
+            None => Ok(replayed.await),
+        };
+        let (status, error) = match result {
+            Ok(Ok(status)) => (status, None),
+            Ok(Err(e)) => {
+                let error = format!("{e:#}");
+                (
+                    super::invocation_error_response(e, workload_id).status(),
+                    Some(error),
+                )
+            }
+            Err(limit) => (
+                hyper::StatusCode::GATEWAY_TIMEOUT,
+                Some(format!("timed out after {limit:?}")),
+            ),
+        };
+        report.invocations.push(ReplayedInvocation {
+            index,
+            method: invocation.method.clone(),
+            uri: invocation.uri.clone(),
+            recorded_status: invocation.status,
+            status: status.as_u16(),
+            duration_ms: sent.elapsed().as_secs_f64() * 1000.0,
+            error,
+        });
+    }
+    let diverged = report.diverged().count();
+    info!(
+        workload_id,
+        replayed = report.invocations.len(),
+        diverged,
+        "replayed recorded HTTP requests"
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(headers: &[(&str, &str)]) -> hyper::http::request::Parts {
+        let mut req = hyper::Request::builder().method("POST").uri("/orders?id=7");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_recorded_request_is_redacted_and_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.jsonl");
+        let recorder = Recorder::default();
+        recorder
+            .start(
+                Recording::new("orders", &path)
+                    .with_max_body_bytes(4)
+                    .with_max_invocations(1),
+            )
+            .unwrap();
+        let active = recorder.get("orders").unwrap();
+        let parts = parts(&[
+            ("authorization", "Bearer secret"),
+            ("content-length", "11"),
+            ("x-tenant", "acme"),
+        ]);
+        let pending = active
+            .begin("orders-api", &parts, &Bytes::from_static(b"hello world"))
+            .unwrap();
+        // Only one request is allowed
+        assert!(active.begin("orders-api", &parts, &Bytes::new()).is_none());
+        pending.finish(hyper::StatusCode::CREATED, None);
+        assert_eq!(recorder.stop("orders").unwrap().recorded, 1);
+        assert!(recorder.get("orders").is_none());
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let invocation: RecordedInvocation = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(invocation.component_id, "orders-api");
+        assert_eq!(invocation.uri, "/orders?id=7");
+        assert_eq!(invocation.status, 201);
+        assert_eq!(invocation.body, Bytes::from_static(b"hell"));
+        assert!(invocation.is_truncated());
+        assert_eq!(
+            invocation.headers[0],
+            ("authorization".to_string(), REDACTED.to_string())
+        );
+
+        // The redacted header and the stale length aren't replayed
+        let req = invocation.request().unwrap();
+        assert_eq!(req.method(), hyper::Method::POST);
+        assert!(req.headers().get("authorization").is_none());
+        assert!(req.headers().get("content-length").is_none());
+        assert_eq!(req.headers()["x-tenant"], "acme");
+    }
+
+    #[tokio::test]
+    async fn test_read_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.jsonl");
+        let recorder = Recorder::default();
+        recorder
+            .start(Recording::new("orders", &path).with_sensitive_headers(true))
+            .unwrap();
+        let active = recorder.get("orders").unwrap();
+        let parts = parts(&[("authorization", "Bearer secret")]);
+        for status in [
+            hyper::StatusCode::OK,
+            hyper::StatusCode::INTERNAL_SERVER_ERROR,
+        ] {
+            active
+                .begin("orders-api", &parts, &Bytes::from_static(b"{}"))
+                .unwrap()
+                .finish(
+                    status,
+                    status.is_server_error().then(|| "trapped".to_string()),
+                );
+        }
+
+        let invocations = read_recording(&path).await.unwrap();
+        assert_eq!(invocations.len(), 2);
+        assert_eq!(invocations[0].headers[0].1, "Bearer secret");
+        assert!(!invocations[0].is_truncated());
+        assert_eq!(invocations[1].status, 500);
+        assert_eq!(invocations[1].error.as_deref(), Some("trapped"));
+
+        std::fs::write(&path, "{}\n").unwrap();
+        assert!(read_recording(&path).await.is_err());
+    }
+}
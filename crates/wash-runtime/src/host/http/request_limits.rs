@@ -0,0 +1,328 @@
+//! Limits of the requests the HTTP server reads.
+//!
+//! A host sets default limits for the requests of all routes, see
+//! [`HttpServer::with_request_limits`](super::HttpServer::with_request_limits),
+//! which a route may override in the string config map of
+//! `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! max_body_bytes: 10485760        # optional, requests must then declare a Content-Length
+//! max_headers: 50                 # optional
+//! max_header_bytes: 8192          # optional
+//! body_read_timeout_ms: 10000     # optional
+//! ```
+//!
+//! Requests are rejected before they reach a component instance:
+//!
+//! - A request declaring a `Content-Length` larger than its limit is answered
+//!   with `413 Content Too Large` before its body is read. A body without a
+//!   `Content-Length` is counted as it arrives, and once it exceeds the limit
+//!   the request is answered with `413` and the component handling it is
+//!   cancelled, so it never reads more than the limit.
+//! - A request with more headers, or larger headers, than its limit is
+//!   answered with `431 Request Header Fields Too Large`. The host limits are
+//!   enforced by the connection while the head is read, so an HTTP/1.1 head
+//!   can't grow larger than the host limit before it is routed.
+//! - A client that doesn't send the head of an HTTP/1.1 request within the
+//!   `header_read_timeout_ms` of the host, 30 seconds by default, has its
+//!   connection closed.
+//! - A client that sends nothing for `body_read_timeout_ms` while the body of
+//!   its request is read is answered with `408 Request Timeout`, cancelling
+//!   the component handling it.
+//!
+//! A route's `max_body_bytes` keeps requiring requests to declare their
+//! `Content-Length`, answering others with `411 Length Required`, while the
+//! host default also accepts bodies of unknown length.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::StatusCode;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::{CONTENT_LENGTH, HeaderMap};
+use tokio::sync::watch;
+use tokio::time::Sleep;
+
+pub use crate::types::route::request_limits::{
+    BODY_READ_TIMEOUT_MS_CONFIG_KEY, MAX_HEADER_BYTES_CONFIG_KEY, MAX_HEADERS_CONFIG_KEY,
+    RequestLimits,
+};
+
+/// The smallest read buffer of HTTP/1.1 connections hyper accepts
+const MIN_HTTP1_BUF_SIZE: usize = 8192;
+
+impl RequestLimits {
+    /// The read buffer size of HTTP/1.1 connections, bounding the size of
+    /// request heads, if the header size is limited.
+    pub(super) fn http1_buf_size(&self) -> Option<usize> {
+        self.max_header_bytes.map(|max| {
+            usize::try_from(max)
+                .unwrap_or(usize::MAX)
+                .max(MIN_HTTP1_BUF_SIZE)
+        })
+    }
+
+    /// Checks the head of a request against these limits, returning the
+    /// status to reject it with.
+    pub(super) fn check_head(&self, headers: &HeaderMap) -> Option<StatusCode> {
+        if self
+            .max_headers
+            .is_some_and(|max| headers.len() as u64 > max)
+            || self
+                .max_header_bytes
+                .is_some_and(|max| header_bytes(headers) > max)
+        {
+            return Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        }
+        let content_length = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        match (self.max_body_bytes, content_length) {
+            (Some(max), Some(length)) if length > max => Some(StatusCode::PAYLOAD_TOO_LARGE),
+            _ => None,
+        }
+    }
+
+    /// Wraps a request body to enforce the body limits, returning it with the
+    /// [`Violations`] it reports.
+    pub(super) fn guard<B>(&self, body: B) -> (GuardedBody<B>, Violations) {
+        let (violated, violations) = watch::channel(None);
+        let body = GuardedBody {
+            inner: body,
+            remaining: self.max_body_bytes,
+            idle_timeout: self.body_read_timeout(),
+            idle: None,
+            violated,
+        };
+        (body, Violations(violations))
+    }
+}
+
+/// The size of `headers` as sent over HTTP/1.1, without line endings.
+fn header_bytes(headers: &HeaderMap) -> u64 {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().len() + 2 + value.len()) as u64)
+        .sum()
+}
+
+/// How a request body broke its limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Violation {
+    /// The body is larger than allowed
+    TooLarge,
+    /// The client sent nothing for longer than allowed
+    Timeout,
+}
+
+impl Violation {
+    /// The status the request is answered with
+    pub(super) fn status(self) -> StatusCode {
+        match self {
+            Violation::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Violation::Timeout => StatusCode::REQUEST_TIMEOUT,
+        }
+    }
+}
+
+/// Reports when the [`GuardedBody`] of a request breaks its limits
+pub(super) struct Violations(watch::Receiver<Option<Violation>>);
+
+impl Violations {
+    /// Runs `future` until it completes, or the body breaks its limits.
+    pub(super) async fn or_violation<F: Future>(
+        &mut self,
+        future: F,
+    ) -> Result<F::Output, Violation> {
+        tokio::select! {
+            output = future => Ok(output),
+            violation = self.wait() => Err(violation),
+        }
+    }
+
+    /// Waits for the body to break its limits, which it can't once dropped.
+    async fn wait(&mut self) -> Violation {
+        match self.0.wait_for(Option::is_some).await {
+            Ok(violation) => (*violation).expect("violation is set"),
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+/// A request body that stops once it is larger than its limit, or its client
+/// sends nothing for longer than its idle timeout, reporting the violation.
+///
+/// The body type can't carry the error, so a body that broke its limits
+/// never yields another frame instead of ending early, which its reader
+/// would take for the whole body. The reader is cancelled when the
+/// violation is reported, see [`Violations::or_violation`].
+pub(super) struct GuardedBody<B> {
+    inner: B,
+    /// The bytes the body may still yield
+    remaining: Option<u64>,
+    idle_timeout: Option<Duration>,
+    /// Armed while the reader waits for the client
+    idle: Option<Pin<Box<Sleep>>>,
+    violated: watch::Sender<Option<Violation>>,
+}
+
+impl<B> GuardedBody<B> {
+    fn violate(&mut self, violation: Violation) -> Poll<Option<Result<Frame<Bytes>, B::Error>>>
+    where
+        B: Body,
+    {
+        self.idle = None;
+        self.violated.send_replace(Some(violation));
+        Poll::Pending
+    }
+}
+
+impl<B> Body for GuardedBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.violated.borrow().is_some() {
+            return Poll::Pending;
+        }
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                this.idle = None;
+                if let (Some(remaining), Some(data)) = (&mut this.remaining, frame.data_ref()) {
+                    match remaining.checked_sub(data.len() as u64) {
+                        Some(left) => *remaining = left,
+                        None => return this.violate(Violation::TooLarge),
+                    }
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Pending => {
+                let Some(timeout) = this.idle_timeout else {
+                    return Poll::Pending;
+                };
+                let idle = this
+                    .idle
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+                match idle.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.violate(Violation::Timeout),
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+            other => {
+                this.idle = None;
+                other
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt as _;
+    use http_body_util::{BodyExt as _, Full, StreamBody};
+
+    #[test]
+    fn test_check_head() {
+        let limits = RequestLimits::default()
+            .with_max_body_bytes(10)
+            .with_header_limits(2, 48);
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "example.com".parse().unwrap());
+        assert_eq!(limits.check_head(&headers), None);
+
+        headers.insert(CONTENT_LENGTH, "11".parse().unwrap());
+        assert_eq!(
+            limits.check_head(&headers),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+        headers.insert(CONTENT_LENGTH, "10".parse().unwrap());
+        assert_eq!(limits.check_head(&headers), None);
+
+        headers.insert("x-extra", "1".parse().unwrap());
+        assert_eq!(
+            limits.check_head(&headers),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+        headers.remove("x-extra");
+        headers.insert("host", "a-much-longer-host.example.com".parse().unwrap());
+        assert_eq!(
+            limits.check_head(&headers),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+
+        assert_eq!(RequestLimits::default().check_head(&headers), None);
+    }
+
+    #[test]
+    fn test_validate_and_buf_size() {
+        assert!(RequestLimits::default().validate().is_ok());
+        assert!(
+            RequestLimits::default()
+                .with_max_body_bytes(0)
+                .validate()
+                .is_err()
+        );
+        assert_eq!(RequestLimits::default().http1_buf_size(), None);
+        let limits = RequestLimits::default().with_header_limits(10, 1024);
+        assert_eq!(limits.http1_buf_size(), Some(MIN_HTTP1_BUF_SIZE));
+        let limits = RequestLimits::default().with_header_limits(10, 65536);
+        assert_eq!(limits.http1_buf_size(), Some(65536));
+    }
+
+    #[tokio::test]
+    async fn test_guard_passes_bodies_within_limits() {
+        let limits = RequestLimits::default()
+            .with_max_body_bytes(5)
+            .with_body_read_timeout(Duration::from_secs(5));
+        let (body, mut violations) = limits.guard(Full::new(Bytes::from_static(b"hello")));
+        let body = violations
+            .or_violation(body.collect())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(body.to_bytes(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_guard_stops_large_bodies() {
+        let chunks = [b"abc".as_slice(), b"def".as_slice()]
+            .map(|chunk| Ok::<_, std::convert::Infallible>(Frame::data(Bytes::from(chunk))));
+        let limits = RequestLimits::default().with_max_body_bytes(5);
+        let (body, mut violations) = limits.guard(StreamBody::new(futures::stream::iter(chunks)));
+        let result = violations.or_violation(body.collect()).await;
+        assert_eq!(result.err(), Some(Violation::TooLarge));
+        assert_eq!(Violation::TooLarge.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_guard_times_out_idle_clients() {
+        let stream = futures::stream::iter([Ok::<_, std::convert::Infallible>(Frame::data(
+            Bytes::from_static(b"abc"),
+        ))])
+        .chain(futures::stream::pending());
+        let limits = RequestLimits::default().with_body_read_timeout(Duration::from_secs(1));
+        let (body, mut violations) = limits.guard(StreamBody::new(stream));
+        let result = violations.or_violation(body.collect()).await;
+        assert_eq!(result.err(), Some(Violation::Timeout));
+        assert_eq!(Violation::Timeout.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+}
@@ -0,0 +1,569 @@
+//! Retries and circuit breakers for the outgoing requests of components.
+//!
+//! Components calling flaky upstreams, e.g. proxies in front of an LLM API,
+//! would otherwise each retry on their own. Instead, the HTTP server retries
+//! and fails fast for them as the first [`ResilienceRule`] matching the host
+//! of a request says, see
+//! [`HttpServer::with_outbound_resilience`](super::HttpServer::with_outbound_resilience).
+//!
+//! A request fails when it can't connect, its connection breaks or times out
+//! before the response head arrives, or it is answered with one of the
+//! `retry_statuses` of the rule. A failed request is sent again, up to
+//! `max_attempts` times in total, after a backoff starting at `backoff_ms`
+//! and doubling up to `max_backoff_ms`, or the `Retry-After` of the response
+//! if longer. Only requests with an idempotent method and without a body are
+//! retried: the body is streamed from the component, so it can't be sent a
+//! second time. Retries count towards the timeout of the request, see
+//! [`super::egress`], and the last response or error goes to the component.
+//!
+//! With a `failure_threshold`, requests to each host of the rule go through a
+//! circuit breaker. Once that many requests in a row failed, the breaker
+//! opens and requests fail with `destination-unavailable` without being sent.
+//! After `open_ms`, one request is let through: if it succeeds the breaker
+//! closes again, otherwise it stays open for another `open_ms`. The state of
+//! every breaker is recorded by the host metrics, see
+//! [`crate::host::metrics`].
+//!
+//! Redirects are separate requests, each held to the rule of its own host.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyper::Method;
+use hyper::header::RETRY_AFTER;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+/// Retries and fails fast the outgoing requests of components to some hosts,
+/// see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ResilienceRule {
+    /// Hosts the rule applies to, e.g. `api.example.com`, or wildcards
+    /// matching any subdomain, e.g. `*.example.com`
+    pub hosts: Vec<String>,
+    /// How many times a request is sent at most, the first time included
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled for every further retry
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Longest backoff between two attempts
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Response statuses counting as failures
+    #[serde(default = "default_retry_statuses")]
+    pub retry_statuses: Vec<u16>,
+    /// Failures in a row opening the circuit breaker of a host, no breaker if
+    /// unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_threshold: Option<u32>,
+    /// How long an open breaker fails requests before letting one through
+    #[serde(default = "default_open_ms")]
+    pub open_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    1
+}
+
+fn default_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    5_000
+}
+
+fn default_retry_statuses() -> Vec<u16> {
+    vec![502, 503, 504]
+}
+
+fn default_open_ms() -> u64 {
+    30_000
+}
+
+impl ResilienceRule {
+    /// Creates a rule for `hosts` that neither retries nor breaks circuits.
+    pub fn new(hosts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            hosts: hosts.into_iter().map(Into::into).collect(),
+            max_attempts: default_max_attempts(),
+            backoff_ms: default_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            retry_statuses: default_retry_statuses(),
+            failure_threshold: None,
+            open_ms: default_open_ms(),
+        }
+    }
+
+    /// Sends requests up to `max_attempts` times, backing off from
+    /// `backoff` between attempts.
+    pub fn with_retries(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        self.max_attempts = max_attempts;
+        self.backoff_ms = backoff.as_millis() as u64;
+        self
+    }
+
+    /// Opens the circuit of a host for `open` after `failure_threshold`
+    /// failures in a row.
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, open: Duration) -> Self {
+        self.failure_threshold = Some(failure_threshold);
+        self.open_ms = open.as_millis() as u64;
+        self
+    }
+
+    /// Returns whether the rule applies to requests to `host`.
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.');
+        self.hosts
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+                None => host.eq_ignore_ascii_case(pattern),
+            })
+    }
+
+    /// Returns the backoff before attempt `attempt`, the second being 1.
+    fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let max = Duration::from_millis(self.max_backoff_ms);
+        let exponential = Duration::from_millis(
+            self.backoff_ms
+                .saturating_mul(1 << attempt.saturating_sub(1).min(16)),
+        );
+        exponential.max(retry_after.unwrap_or_default()).min(max)
+    }
+}
+
+/// State of the circuit breaker of a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent
+    Closed,
+    /// A trial request is let through to find out whether the host recovered
+    HalfOpen,
+    /// Requests fail without being sent
+    Open,
+}
+
+impl CircuitState {
+    /// Returns the name of the state, e.g. `half_open`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::HalfOpen => "half_open",
+            CircuitState::Open => "open",
+        }
+    }
+}
+
+/// The circuit breaker of a host, as recorded in metrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitStatus {
+    pub host: String,
+    pub state: CircuitState,
+}
+
+#[derive(Debug)]
+enum Breaker {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_started: Instant },
+}
+
+impl Breaker {
+    /// Returns whether a request may be sent at `now`.
+    fn acquire(&mut self, open: Duration, now: Instant) -> bool {
+        match *self {
+            Breaker::Closed { .. } => true,
+            Breaker::Open { until } if now >= until => {
+                *self = Breaker::HalfOpen { trial_started: now };
+                true
+            }
+            Breaker::Open { .. } => false,
+            // A trial that never finished, e.g. because the component
+            // dropped it, is replaced by another after `open`
+            Breaker::HalfOpen { trial_started } if now >= trial_started + open => {
+                *self = Breaker::HalfOpen { trial_started: now };
+                true
+            }
+            Breaker::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Records the outcome of a request, returning whether the breaker
+    /// opened.
+    fn record(&mut self, failed: bool, threshold: u32, open: Duration, now: Instant) -> bool {
+        match (&mut *self, failed) {
+            (Breaker::Closed { failures }, false) => *failures = 0,
+            (Breaker::Closed { failures }, true) => {
+                *failures += 1;
+                if *failures >= threshold {
+                    *self = Breaker::Open { until: now + open };
+                    return true;
+                }
+            }
+            (Breaker::HalfOpen { .. }, false) => *self = Breaker::Closed { failures: 0 },
+            (Breaker::HalfOpen { .. }, true) => {
+                *self = Breaker::Open { until: now + open };
+                return true;
+            }
+            // Requests sent before the breaker opened don't change it
+            (Breaker::Open { .. }, _) => {}
+        }
+        false
+    }
+
+    fn state(&self) -> CircuitState {
+        match self {
+            Breaker::Closed { .. } => CircuitState::Closed,
+            Breaker::Open { .. } => CircuitState::Open,
+            Breaker::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+}
+
+/// The resilience rules of an HTTP server and the circuit breakers of the
+/// hosts they apply to.
+#[derive(Debug, Default)]
+pub struct OutboundResilience {
+    rules: Vec<ResilienceRule>,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl OutboundResilience {
+    /// Applies `rules`, the first matching the host of a request.
+    pub fn new(rules: impl IntoIterator<Item = ResilienceRule>) -> Self {
+        Self {
+            rules: rules.into_iter().collect(),
+            breakers: Mutex::default(),
+        }
+    }
+
+    /// Returns the circuit breakers of the hosts requests were sent to.
+    pub fn circuits(&self) -> Vec<CircuitStatus> {
+        let breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut circuits: Vec<_> = breakers
+            .iter()
+            .map(|(host, breaker)| CircuitStatus {
+                host: host.clone(),
+                state: breaker.state(),
+            })
+            .collect();
+        circuits.sort_by(|a, b| a.host.cmp(&b.host));
+        circuits
+    }
+
+    fn rule(&self, host: &str) -> Option<&ResilienceRule> {
+        self.rules.iter().find(|rule| rule.matches(host))
+    }
+
+    fn acquire(&self, rule: &ResilienceRule, host: &str) -> bool {
+        if rule.failure_threshold.is_none() {
+            return true;
+        }
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        breakers
+            .entry(host.to_string())
+            .or_insert(Breaker::Closed { failures: 0 })
+            .acquire(Duration::from_millis(rule.open_ms), Instant::now())
+    }
+
+    fn record(&self, rule: &ResilienceRule, host: &str, failed: bool) {
+        let Some(threshold) = rule.failure_threshold else {
+            return;
+        };
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(breaker) = breakers.get_mut(host) else {
+            return;
+        };
+        let open = Duration::from_millis(rule.open_ms);
+        if breaker.record(failed, threshold.max(1), open, Instant::now()) {
+            warn!(
+                host,
+                open_ms = rule.open_ms,
+                "opened circuit breaker of outgoing requests"
+            );
+        }
+    }
+
+    /// Sends `request` with `send`, retrying and failing fast as the rule of
+    /// its host says.
+    pub(crate) async fn send<B, R, F, Fut>(
+        &self,
+        request: hyper::Request<B>,
+        mut send: F,
+    ) -> Result<hyper::Response<R>, ErrorCode>
+    where
+        B: hyper::body::Body + Default,
+        F: FnMut(hyper::Request<B>) -> Fut,
+        Fut: Future<Output = Result<hyper::Response<R>, ErrorCode>>,
+    {
+        let host = request
+            .uri()
+            .host()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let Some(rule) = self.rule(&host) else {
+            return send(request).await;
+        };
+
+        // Only requests that can be sent again as they were are retried
+        let replay = (rule.max_attempts > 1
+            && is_idempotent(request.method())
+            && (request.body().is_end_stream() || request.body().size_hint().exact() == Some(0)))
+        .then(|| {
+            (
+                request.method().clone(),
+                request.uri().clone(),
+                request.headers().clone(),
+            )
+        });
+        let mut request = Some(request);
+        let mut attempt = 1;
+        loop {
+            let Some(current) = request.take() else {
+                unreachable!("a request is sent once per attempt");
+            };
+            if !self.acquire(rule, &host) {
+                debug!(host, "circuit breaker of outgoing requests is open");
+                return Err(ErrorCode::DestinationUnavailable);
+            }
+            let result = send(current).await;
+            let failed = match &result {
+                Ok(resp) => rule.retry_statuses.contains(&resp.status().as_u16()),
+                Err(code) => is_transient(code),
+            };
+            self.record(rule, &host, failed);
+            let Some((method, uri, headers)) = replay.as_ref() else {
+                return result;
+            };
+            if !failed || attempt >= rule.max_attempts {
+                return result;
+            }
+
+            let retry_after = result.as_ref().ok().and_then(|resp| {
+                resp.headers()
+                    .get(RETRY_AFTER)?
+                    .to_str()
+                    .ok()?
+                    .parse()
+                    .ok()
+                    .map(Duration::from_secs)
+            });
+            let backoff = rule.backoff(attempt, retry_after);
+            debug!(
+                host,
+                attempt,
+                status = result.as_ref().ok().map(|resp| resp.status().as_u16()),
+                backoff_ms = backoff.as_millis() as u64,
+                "retrying outgoing request"
+            );
+            drop(result);
+            tokio::time::sleep(backoff).await;
+
+            let mut next = hyper::Request::new(B::default());
+            *next.method_mut() = method.clone();
+            *next.uri_mut() = uri.clone();
+            *next.headers_mut() = headers.clone();
+            request = Some(next);
+            attempt += 1;
+        }
+    }
+}
+
+/// Returns whether requests with `method` may be sent more than once.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+/// Returns whether a request failing with `code` may succeed when sent
+/// again, as opposed to failing for what it is or the policy of the host.
+fn is_transient(code: &ErrorCode) -> bool {
+    matches!(
+        code,
+        ErrorCode::DnsTimeout
+            | ErrorCode::DestinationUnavailable
+            | ErrorCode::ConnectionRefused
+            | ErrorCode::ConnectionTerminated
+            | ErrorCode::ConnectionTimeout
+            | ErrorCode::ConnectionReadTimeout
+            | ErrorCode::ConnectionWriteTimeout
+            | ErrorCode::HttpResponseIncomplete
+            | ErrorCode::HttpProtocolError
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use http_body_util::Empty;
+    use hyper::body::Bytes;
+
+    use super::*;
+
+    fn request(method: Method) -> hyper::Request<Empty<Bytes>> {
+        hyper::Request::builder()
+            .method(method)
+            .uri("http://api.example.com/")
+            .body(Empty::new())
+            .unwrap()
+    }
+
+    fn status(status: u16) -> Result<hyper::Response<()>, ErrorCode> {
+        Ok(hyper::Response::builder().status(status).body(()).unwrap())
+    }
+
+    #[test]
+    fn test_matches() {
+        let rule = ResilienceRule::new(["api.example.com", "*.example.org"]);
+        assert!(rule.matches("API.example.com"));
+        assert!(rule.matches("eu.example.org."));
+        assert!(!rule.matches("example.org"));
+        assert!(!rule.matches("other.example.com"));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let rule = ResilienceRule {
+            max_backoff_ms: 1_000,
+            ..ResilienceRule::new(["api.example.com"])
+        };
+        assert_eq!(rule.backoff(1, None), Duration::from_millis(100));
+        assert_eq!(rule.backoff(3, None), Duration::from_millis(400));
+        assert_eq!(rule.backoff(10, None), Duration::from_secs(1));
+        assert_eq!(
+            rule.backoff(1, Some(Duration::from_millis(500))),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_breaker() {
+        let open = Duration::from_secs(10);
+        let now = Instant::now();
+        let mut breaker = Breaker::Closed { failures: 0 };
+        assert!(!breaker.record(true, 2, open, now));
+        assert!(!breaker.record(false, 2, open, now));
+        assert!(!breaker.record(true, 2, open, now));
+        assert!(breaker.record(true, 2, open, now));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.acquire(open, now));
+
+        // One trial once the breaker was open long enough
+        let later = now + open;
+        assert!(breaker.acquire(open, later));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.acquire(open, later));
+        assert!(breaker.record(true, 2, open, later));
+        assert!(!breaker.acquire(open, later));
+
+        let later = later + open;
+        assert!(breaker.acquire(open, later));
+        breaker.record(false, 2, open, later);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.acquire(open, later));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries() {
+        let resilience = OutboundResilience::new([
+            ResilienceRule::new(["api.example.com"]).with_retries(3, Duration::from_millis(10))
+        ]);
+        let attempts = AtomicU32::new(0);
+        let resp = resilience
+            .send(request(Method::GET), |_| {
+                let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if attempt < 2 {
+                        status(503)
+                    } else {
+                        status(200)
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+
+        // The last failure goes to the component
+        attempts.store(0, Ordering::Relaxed);
+        let result = resilience
+            .send(request(Method::GET), |_| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { Err::<hyper::Response<()>, _>(ErrorCode::ConnectionRefused) }
+            })
+            .await;
+        assert!(matches!(result, Err(ErrorCode::ConnectionRefused)));
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+
+        // Neither non-idempotent requests nor permanent errors are retried
+        attempts.store(0, Ordering::Relaxed);
+        let resp = resilience
+            .send(request(Method::POST), |_| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { status(503) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 503);
+        attempts.store(0, Ordering::Relaxed);
+        let _ = resilience
+            .send(request(Method::GET), |_| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { Err::<hyper::Response<()>, _>(ErrorCode::HttpRequestDenied) }
+            })
+            .await;
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        let resilience = OutboundResilience::new([ResilienceRule::new(["api.example.com"])
+            .with_circuit_breaker(2, Duration::from_secs(30))]);
+        let attempts = AtomicU32::new(0);
+        for _ in 0..2 {
+            let _ = resilience
+                .send(request(Method::POST), |_| {
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    async { status(502) }
+                })
+                .await;
+        }
+        assert_eq!(
+            resilience.circuits(),
+            [CircuitStatus {
+                host: "api.example.com".to_string(),
+                state: CircuitState::Open,
+            }]
+        );
+
+        // Fails fast until the breaker lets a trial through
+        let result = resilience
+            .send(request(Method::POST), |_| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { status(200) }
+            })
+            .await;
+        assert!(matches!(result, Err(ErrorCode::DestinationUnavailable)));
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let resp = resilience
+            .send(request(Method::POST), |_| async { status(200) })
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resilience.circuits()[0].state, CircuitState::Closed);
+    }
+}
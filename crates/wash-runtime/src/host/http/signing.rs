@@ -0,0 +1,419 @@
+//! Signing the outgoing requests of components with credentials of the host.
+//!
+//! Components calling cloud APIs would otherwise need long-lived keys in
+//! their config. Instead, the HTTP server can hold the keys and sign the
+//! outgoing requests of components after they leave the component, see
+//! [`HttpServer::with_request_signing`](super::HttpServer::with_request_signing).
+//! A [`SigningRule`] signs the requests to its `hosts` sent by components of
+//! its `namespaces`, every namespace if it lists none, with either:
+//!
+//! - AWS Signature Version 4, for AWS APIs and services accepting it, e.g.
+//!   Google Cloud Storage with HMAC keys. The `host`, `content-type` and
+//!   `x-amz-*` headers are signed, together with the body.
+//! - An HMAC-SHA256 keyed with a shared secret. `x-wasmcloud-timestamp` is
+//!   set to the time the request was signed, in seconds since the Unix epoch,
+//!   and the signature header, `x-wasmcloud-signature` by default, to
+//!   `sha256=` followed by the hex HMAC-SHA256 of
+//!   `<timestamp>\n<method>\n<path and query>\n<hex SHA-256 of the body>`.
+//!
+//! The first rule matching a request signs it, replacing the `Authorization`
+//! or signature header the component set. Redirects followed for a request are
+//! signed again if a rule matches their location. Signed requests have their
+//! body read before they are sent, up to [`MAX_SIGNED_BODY_BYTES`], so it can
+//! be hashed.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt as _, Full, Limited};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HOST, HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use crate::plugin::blobstore::s3::{encode, hex, hmac_sha256, signing_key};
+
+/// Largest body of a signed request, which is read to be hashed
+pub const MAX_SIGNED_BODY_BYTES: usize = 16 * 1024 * 1024;
+/// Header carrying the time an HMAC signed request was signed
+pub const TIMESTAMP_HEADER: &str = "x-wasmcloud-timestamp";
+/// Header carrying the HMAC signature of a request by default
+pub const SIGNATURE_HEADER: &str = "x-wasmcloud-signature";
+
+/// Signs the outgoing requests of components to some hosts, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SigningRule {
+    /// Hosts whose requests are signed, e.g. `s3.eu-west-1.amazonaws.com`, or
+    /// wildcards matching any subdomain, e.g. `*.amazonaws.com`
+    pub hosts: Vec<String>,
+    /// Namespaces whose components have their requests signed, all if empty
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    #[serde(flatten)]
+    pub method: SigningMethod,
+}
+
+/// How requests are signed.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SigningMethod {
+    /// AWS Signature Version 4
+    Sigv4 {
+        access_key_id: String,
+        secret_access_key: String,
+        /// Token of temporary credentials, e.g. from an assumed role
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_token: Option<String>,
+        region: String,
+        /// The signing name of the service, e.g. `s3` or `execute-api`
+        service: String,
+    },
+    /// HMAC-SHA256 of the request keyed with a shared secret
+    Hmac {
+        secret: String,
+        /// Header carrying the signature
+        #[serde(default = "default_signature_header")]
+        header: String,
+    },
+}
+
+fn default_signature_header() -> String {
+    SIGNATURE_HEADER.to_string()
+}
+
+impl std::fmt::Debug for SigningMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sigv4 {
+                access_key_id,
+                region,
+                service,
+                ..
+            } => f
+                .debug_struct("Sigv4")
+                .field("access_key_id", access_key_id)
+                .field("region", region)
+                .field("service", service)
+                .finish_non_exhaustive(),
+            Self::Hmac { header, .. } => f
+                .debug_struct("Hmac")
+                .field("header", header)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl SigningRule {
+    /// Returns whether the rule signs the requests of components of
+    /// `namespace`.
+    pub fn applies_to(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty() || self.namespaces.iter().any(|ns| ns == namespace)
+    }
+
+    /// Returns whether the rule signs requests to `host`.
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.');
+        self.hosts
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+                None => host.eq_ignore_ascii_case(pattern),
+            })
+    }
+
+    /// Signs `request`, reading its body to hash it.
+    pub(crate) async fn sign(
+        &self,
+        request: hyper::Request<HyperOutgoingBody>,
+    ) -> Result<hyper::Request<HyperOutgoingBody>, ErrorCode> {
+        let (mut parts, body) = request.into_parts();
+        let body = Limited::new(body, MAX_SIGNED_BODY_BYTES)
+            .collect()
+            .await
+            .map_err(|e| match e.downcast::<ErrorCode>() {
+                Ok(e) => *e,
+                Err(_) => ErrorCode::HttpRequestBodySize(Some(MAX_SIGNED_BODY_BYTES as u64)),
+            })?
+            .to_bytes();
+        let payload_hash = hex(&Sha256::digest(&body));
+        self.sign_parts(&mut parts, &payload_hash, Utc::now())?;
+        let body = Full::new(body).map_err(|never| match never {}).boxed();
+        Ok(hyper::Request::from_parts(parts, body))
+    }
+
+    /// Signs the head of a request whose body hashes to `payload_hash`.
+    pub(crate) fn sign_parts(
+        &self,
+        parts: &mut hyper::http::request::Parts,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), ErrorCode> {
+        let invalid = |_| ErrorCode::HttpRequestHeaderSize(None);
+        match &self.method {
+            SigningMethod::Sigv4 {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                region,
+                service,
+            } => {
+                let authority = parts
+                    .uri
+                    .authority()
+                    .ok_or(ErrorCode::HttpRequestUriInvalid)?
+                    .clone();
+                let headers = &mut parts.headers;
+                if !headers.contains_key(HOST) {
+                    headers.insert(
+                        HOST,
+                        HeaderValue::from_str(authority.as_str()).map_err(invalid)?,
+                    );
+                }
+                let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+                headers.insert(
+                    "x-amz-date",
+                    HeaderValue::from_str(&timestamp).map_err(invalid)?,
+                );
+                headers.insert(
+                    "x-amz-content-sha256",
+                    HeaderValue::from_str(payload_hash).map_err(invalid)?,
+                );
+                match session_token {
+                    Some(token) => {
+                        headers.insert(
+                            "x-amz-security-token",
+                            HeaderValue::from_str(token).map_err(invalid)?,
+                        );
+                    }
+                    None => {
+                        headers.remove("x-amz-security-token");
+                    }
+                }
+
+                let signed: BTreeMap<String, String> = headers
+                    .iter()
+                    .filter(|(name, _)| {
+                        *name == HOST
+                            || *name == CONTENT_TYPE
+                            || name.as_str().starts_with("x-amz-")
+                    })
+                    .filter_map(|(name, value)| {
+                        Some((
+                            name.as_str().to_string(),
+                            value.to_str().ok()?.trim().to_string(),
+                        ))
+                    })
+                    .collect();
+                let canonical_headers: String = signed
+                    .iter()
+                    .map(|(name, value)| format!("{name}:{value}\n"))
+                    .collect();
+                let signed_headers = signed
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(";");
+                // Paths are encoded twice for every service but S3
+                let path = match service.as_str() {
+                    "s3" => parts.uri.path().to_string(),
+                    _ => parts
+                        .uri
+                        .path()
+                        .split('/')
+                        .map(encode)
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                };
+                let canonical_request = format!(
+                    "{}\n{path}\n{}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+                    parts.method,
+                    canonical_query(parts.uri.query().unwrap_or_default()),
+                );
+
+                let date = now.format("%Y%m%d").to_string();
+                let scope = format!("{date}/{region}/{service}/aws4_request");
+                let string_to_sign = format!(
+                    "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+                    hex(&Sha256::digest(canonical_request.as_bytes()))
+                );
+                let key = signing_key(secret_access_key, &date, region, service);
+                let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+                let authorization = format!(
+                    "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope},SignedHeaders={signed_headers},Signature={signature}"
+                );
+                headers.insert(
+                    AUTHORIZATION,
+                    HeaderValue::from_str(&authorization).map_err(invalid)?,
+                );
+            }
+            SigningMethod::Hmac { secret, header } => {
+                let timestamp = u64::try_from(now.timestamp()).unwrap_or_default();
+                let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+                let string_to_sign =
+                    format!("{timestamp}\n{}\n{path}\n{payload_hash}", parts.method);
+                let signature = format!(
+                    "sha256={}",
+                    hex(&hmac_sha256(secret.as_bytes(), string_to_sign.as_bytes()))
+                );
+                let header = HeaderName::try_from(header.as_str()).map_err(|_| {
+                    ErrorCode::InternalError(Some(format!("invalid signature header '{header}'")))
+                })?;
+                parts
+                    .headers
+                    .insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+                parts
+                    .headers
+                    .insert(header, HeaderValue::from_str(&signature).map_err(invalid)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the SigV4 canonical form of a query: its parameters encoded and
+/// sorted by name, then value.
+fn canonical_query(query: &str) -> String {
+    let mut params: Vec<(String, String)> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            (
+                encode(&super::grpc_web::percent_decode(name.as_bytes())),
+                encode(&super::grpc_web::percent_decode(value.as_bytes())),
+            )
+        })
+        .collect();
+    params.sort();
+    params
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sigv4(service: &str) -> SigningRule {
+        SigningRule {
+            hosts: vec!["*.amazonaws.com".to_string()],
+            namespaces: Vec::new(),
+            method: SigningMethod::Sigv4 {
+                access_key_id: "AKIDEXAMPLE".to_string(),
+                secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+                session_token: None,
+                region: "us-east-1".to_string(),
+                service: service.to_string(),
+            },
+        }
+    }
+
+    fn parts(method: &str, uri: &str) -> hyper::http::request::Parts {
+        hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut rule = sigv4("s3");
+        rule.hosts.push("storage.googleapis.com".to_string());
+        assert!(rule.matches("s3.us-east-1.amazonaws.com"));
+        assert!(rule.matches("iam.amazonaws.com."));
+        assert!(rule.matches("Storage.GoogleAPIs.com"));
+        assert!(!rule.matches("amazonaws.com"));
+        assert!(!rule.matches("evilamazonaws.com"));
+        assert!(!rule.matches("amazonaws.com.example.com"));
+
+        assert!(rule.applies_to("default"));
+        rule.namespaces = vec!["billing".to_string()];
+        assert!(rule.applies_to("billing"));
+        assert!(!rule.applies_to("default"));
+    }
+
+    #[test]
+    fn test_sigv4() {
+        // The signing key of the example in the AWS documentation
+        assert_eq!(
+            hex(&signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam"
+            )),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+
+        let now = DateTime::parse_from_rfc3339("2015-08-30T12:36:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut request = parts(
+            "GET",
+            "https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers",
+        );
+        request
+            .headers
+            .insert(AUTHORIZATION, HeaderValue::from_static("Bearer component"));
+        let payload_hash = hex(&Sha256::digest(b""));
+        sigv4("iam")
+            .sign_parts(&mut request, &payload_hash, now)
+            .unwrap();
+        assert_eq!(request.headers[HOST], "iam.amazonaws.com");
+        assert_eq!(request.headers["x-amz-date"], "20150830T123600Z");
+        assert_eq!(request.headers["x-amz-content-sha256"], payload_hash);
+        let authorization = request.headers[AUTHORIZATION].to_str().unwrap();
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request,SignedHeaders=host;x-amz-content-sha256;x-amz-date,Signature="
+        ));
+
+        // The same request is signed the same, whatever the order of its query
+        let mut again = parts(
+            "GET",
+            "https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08",
+        );
+        sigv4("iam")
+            .sign_parts(&mut again, &payload_hash, now)
+            .unwrap();
+        assert_eq!(again.headers[AUTHORIZATION], authorization);
+        assert_eq!(canonical_query("b=2&a=%2F&a=1&c"), "a=%2F&a=1&b=2&c=");
+    }
+
+    #[test]
+    fn test_hmac() {
+        let rule = SigningRule {
+            hosts: vec!["api.example.com".to_string()],
+            namespaces: Vec::new(),
+            method: SigningMethod::Hmac {
+                secret: "secret".to_string(),
+                header: default_signature_header(),
+            },
+        };
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let payload_hash = hex(&Sha256::digest(b"{}"));
+        let mut request = parts("POST", "https://api.example.com/orders?id=1");
+        rule.sign_parts(&mut request, &payload_hash, now).unwrap();
+        assert_eq!(request.headers[TIMESTAMP_HEADER], "1700000000");
+        let expected = hex(&hmac_sha256(
+            b"secret",
+            format!("1700000000\nPOST\n/orders?id=1\n{payload_hash}").as_bytes(),
+        ));
+        assert_eq!(
+            request.headers[SIGNATURE_HEADER],
+            format!("sha256={expected}").as_str()
+        );
+
+        // Secrets are not logged
+        assert!(!format!("{rule:?}").contains("secret\""));
+    }
+}
@@ -0,0 +1,152 @@
+//! Weighted traffic splitting between workloads that serve the same route.
+//!
+//! Workloads declaring a `weight` on the same host, path, methods and headers
+//! share the requests of the route instead of conflicting with each other,
+//! e.g. the current version of a component with a weight of 90 and a canary of
+//! its next version with a weight of 10:
+//!
+//! ```text
+//! host: api.example.com
+//! path: /checkout
+//! weight: 10
+//! ```
+//!
+//! Every request is sent to one of them at random, in proportion to the
+//! weights, among the workloads passing their health check. A workload with a
+//! weight of 0 gets no requests while another weight is greater than zero,
+//! so a canary can be deployed dark and drained again. If all weights are 0,
+//! the requests go to the workload that started first.
+//!
+//! Unlike an experiment, see [`super::experiment`], a split doesn't keep a
+//! client on the same workload, and the component isn't told which one
+//! serves it. Operators shift the weights at runtime, without redeploying,
+//! with the `route.weight` API command, see [`RouteWeight`]. Shifted weights
+//! are kept in the host snapshot.
+//!
+//! The requests of a split route are recorded in the request metrics of the
+//! server with the `wasmcloud.workload.name` of the workload serving them, so
+//! the error rate and latency of a canary can be compared with the current
+//! version before the weights are shifted further, see [`super::metrics`].
+//!
+//! # Rolling updates
+//!
+//! While a rolling update replaces a workload, see
+//! [`RollingUpdate`](crate::types::RollingUpdate), the old version and the
+//! new one split the requests of its routes in proportion to their
+//! instances, as given by the host at every step with a [`RolloutSplit`].
+//! Once the update is over, the new version serves the routes alone.
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+
+pub use crate::types::route::WEIGHT_CONFIG_KEY;
+
+/// The weight of a workload among those splitting a route, shifted at
+/// runtime, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteWeight {
+    /// The `namespace/name` of the workload
+    pub workload_name: String,
+    /// The share of requests of the workload, relative to the weights of
+    /// the other workloads splitting its route
+    pub weight: u32,
+}
+
+impl RouteWeight {
+    pub fn new(workload_name: impl Into<String>, weight: u32) -> Self {
+        Self {
+            workload_name: workload_name.into(),
+            weight,
+        }
+    }
+
+    /// Validates the weight.
+    ///
+    /// # Errors
+    /// Returns an error if the workload name doesn't have the form
+    /// `namespace/name`.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.workload_name
+                .split_once('/')
+                .is_some_and(|(ns, name)| !ns.is_empty() && !name.is_empty()),
+            "route weight workload '{}' must have the form namespace/name",
+            self.workload_name
+        );
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for RouteWeight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.workload_name, self.weight)
+    }
+}
+
+/// The instances of a workload and of the new version replacing it during a
+/// rolling update, which split the requests of its routes in proportion to
+/// them, see the [module docs](self#rolling-updates).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutSplit {
+    /// Instances of the workload being replaced
+    pub instances: u32,
+    /// Instances of the new version
+    pub replacement_instances: u32,
+}
+
+/// Picks a target by `weights` at random, returning its index. `weights`
+/// must not be empty and is expected in start order, see the
+/// [module docs](self).
+pub fn pick(weights: &[u32]) -> usize {
+    pick_at(weights, uuid::Uuid::new_v4().as_u128() as u64)
+}
+
+/// Picks the target `random` falls on.
+fn pick_at(weights: &[u32], random: u64) -> usize {
+    let total: u64 = weights.iter().copied().map(u64::from).sum();
+    if total == 0 {
+        return 0;
+    }
+    let point = random % total;
+    let mut upper = 0u64;
+    for (i, weight) in weights.iter().enumerate() {
+        upper += u64::from(*weight);
+        if point < upper {
+            return i;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_by_weight() {
+        let weights = [90, 10];
+        assert_eq!(pick_at(&weights, 0), 0);
+        assert_eq!(pick_at(&weights, 89), 0);
+        assert_eq!(pick_at(&weights, 90), 1);
+        assert_eq!(pick_at(&weights, 99), 1);
+        assert_eq!(pick_at(&weights, 100), 0);
+
+        // Drained targets get no requests
+        assert_eq!(pick_at(&[0, 5], 3), 1);
+        assert_eq!(pick_at(&[0, 0], 7), 0);
+
+        let picks = (0..1000).filter(|_| pick(&weights) == 1).count();
+        assert!((30..200).contains(&picks), "{picks}");
+    }
+
+    #[test]
+    fn test_route_weight_validate() {
+        assert!(RouteWeight::new("default/api", 10).validate().is_ok());
+        assert!(RouteWeight::new("api", 10).validate().is_err());
+        assert!(RouteWeight::new("default/", 10).validate().is_err());
+        assert_eq!(
+            RouteWeight::new("default/api", 10).to_string(),
+            "default/api=10"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Spooling request bodies to disk.
+//!
+//! A component reads its request body as fast as it likes. While it reads
+//! slowly, the rest of the body stays in the buffers of the connection, which
+//! holds memory and, over HTTP/2, the flow control window of the client.
+//! Routes accepting large uploads can instead have the host read the whole
+//! body before invoking the component, see
+//! [`HttpRouteConfig::with_body_spooling`](super::HttpRouteConfig::with_body_spooling):
+//!
+//! - Bodies up to 64 KiB are kept in memory, larger ones are written to an
+//!   anonymous temporary file in the spool directory of the server, see
+//!   [`HttpServer::with_spool_dir`](super::HttpServer::with_spool_dir), which
+//!   is removed once the component is done with it.
+//! - Bodies larger than the cap of the route are rejected with
+//!   `413 Content Too Large` as soon as they exceed it, whether they declare
+//!   a `Content-Length` or are chunked.
+//! - The component receives the body with its `Content-Length` and
+//!   trailers, and is only invoked once the body has been read, so the
+//!   request timeout of the route doesn't include the upload.
+
+use std::path::Path;
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt as _;
+use http_body_util::BodyExt as _;
+use hyper::body::{Body, Frame};
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt as _};
+use tracing::warn;
+
+use super::{BufferedBody, RequestBody};
+
+/// Bodies up to this size are kept in memory
+const MEMORY_BYTES: usize = 64 * 1024;
+/// Size of the chunks a spooled body is read back in
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Why a body couldn't be spooled
+#[derive(Debug)]
+pub(super) enum SpoolError {
+    /// The body exceeds the cap of the route
+    TooLarge,
+    /// The client failed to send the body
+    Body(hyper::Error),
+    /// The body couldn't be written to the spool directory
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for SpoolError {
+    fn from(e: std::io::Error) -> Self {
+        SpoolError::Io(e)
+    }
+}
+
+/// A request body read by the host, see the [module docs](self).
+#[derive(Debug)]
+pub(super) struct SpooledBody {
+    /// The body, unless it was written to `file`
+    memory: Bytes,
+    file: Option<tokio::fs::File>,
+    len: u64,
+    trailers: Option<hyper::HeaderMap>,
+}
+
+impl SpooledBody {
+    /// Returns the size of the body in bytes.
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether the body was written to a file.
+    pub(super) fn on_disk(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Replays the body followed by its trailers, if any.
+    pub(super) fn into_body(self) -> RequestBody {
+        let trailers = self.trailers;
+        let Some(file) = self.file else {
+            return BufferedBody {
+                data: self.memory,
+                trailers,
+            }
+            .into_body();
+        };
+        let data = futures::stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut chunk = BytesMut::with_capacity(CHUNK_BYTES);
+            match file.read_buf(&mut chunk).await {
+                Ok(0) => None,
+                Ok(_) => Some((
+                    Ok::<_, hyper::Error>(Frame::data(chunk.freeze())),
+                    Some(file),
+                )),
+                // The body type can't carry the error, the component sees
+                // fewer bytes than the Content-Length
+                Err(e) => {
+                    warn!(err = ?e, "failed to read spooled request body");
+                    None
+                }
+            }
+        });
+        let trailers =
+            futures::stream::iter(trailers.map(|trailers| Ok(Frame::trailers(trailers))));
+        http_body_util::StreamBody::new(data.chain(trailers)).boxed_unsync()
+    }
+}
+
+/// Reads a whole request body, spilling it to a temporary file in `dir` once
+/// it outgrows memory.
+///
+/// # Errors
+/// Returns [`SpoolError::TooLarge`] as soon as the body exceeds `max` bytes.
+pub(super) async fn spool<B>(mut body: B, max: u64, dir: &Path) -> Result<SpooledBody, SpoolError>
+where
+    B: Body<Data = Bytes, Error = hyper::Error> + Unpin,
+{
+    let mut memory = BytesMut::new();
+    let mut file: Option<tokio::fs::File> = None;
+    let mut len = 0u64;
+    let mut trailers = None;
+    while let Some(frame) = body.frame().await {
+        let data = match frame.map_err(SpoolError::Body)?.into_data() {
+            Ok(data) => data,
+            Err(frame) => {
+                if let Ok(frame_trailers) = frame.into_trailers() {
+                    trailers = Some(frame_trailers);
+                }
+                continue;
+            }
+        };
+        len += data.len() as u64;
+        if len > max {
+            return Err(SpoolError::TooLarge);
+        }
+        match &mut file {
+            Some(file) => file.write_all(&data).await?,
+            None if memory.len() + data.len() <= MEMORY_BYTES => memory.extend_from_slice(&data),
+            None => {
+                // Removed by the OS once closed
+                let spilled = tempfile::tempfile_in(dir)?;
+                let mut spilled = tokio::fs::File::from_std(spilled);
+                spilled.write_all(&memory).await?;
+                spilled.write_all(&data).await?;
+                memory.clear();
+                file = Some(spilled);
+            }
+        }
+    }
+    if let Some(file) = &mut file {
+        file.flush().await?;
+        file.rewind().await?;
+    }
+    Ok(SpooledBody {
+        memory: memory.freeze(),
+        file,
+        len,
+        trailers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(chunks: usize, chunk_len: usize) -> RequestBody {
+        let mut trailers = hyper::HeaderMap::new();
+        trailers.insert("x-checksum", hyper::header::HeaderValue::from_static("abc"));
+        let frames = (0..chunks)
+            .map(|i| Ok(Frame::data(Bytes::from(vec![i as u8; chunk_len]))))
+            .chain([Ok(Frame::trailers(trailers))]);
+        http_body_util::StreamBody::new(futures::stream::iter(frames)).boxed_unsync()
+    }
+
+    #[tokio::test]
+    async fn test_spool_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let spooled = spool(body(10, 32 * 1024), 1 << 20, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(spooled.len(), 320 * 1024);
+        assert!(spooled.on_disk());
+        // The file is anonymous
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let collected = spooled.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["x-checksum"], "abc");
+        let data = collected.to_bytes();
+        assert_eq!(data.len(), 320 * 1024);
+        assert!(data[..32 * 1024].iter().all(|b| *b == 0));
+        assert!(data[9 * 32 * 1024..].iter().all(|b| *b == 9));
+    }
+
+    #[tokio::test]
+    async fn test_spool_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let spooled = spool(body(2, 1024), 1 << 20, dir.path()).await.unwrap();
+        assert!(!spooled.on_disk());
+        let collected = spooled.into_body().collect().await.unwrap();
+        assert!(collected.trailers().is_some());
+        assert_eq!(collected.to_bytes().len(), 2048);
+    }
+
+    #[tokio::test]
+    async fn test_spool_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            spool(body(10, 32 * 1024), 100 * 1024, dir.path()).await,
+            Err(SpoolError::TooLarge)
+        ));
+    }
+}
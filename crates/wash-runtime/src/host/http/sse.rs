@@ -0,0 +1,305 @@
+//! Streaming of Server-Sent Events.
+//!
+//! Responses with the `text/event-stream` content type are sent as the
+//! component writes them, without the host coalescing events:
+//!
+//! - every chunk the component writes to the body, e.g. with
+//!   `blocking-write-and-flush`, is flushed to the client before the next one
+//!   is read, so a component controls when events leave the host,
+//! - Nagle's algorithm is turned off on the connection, so small events
+//!   aren't held back waiting for the acknowledgement of earlier ones, and
+//! - `Cache-Control: no-cache` and `X-Accel-Buffering: no` are set unless the
+//!   component set them, which keeps common reverse proxies from buffering.
+//!
+//! Proxies and load balancers often close connections that stay quiet for a
+//! while. A route with an
+//! [`sse_keepalive_ms`](super::HttpRouteConfig::sse_keepalive_ms) has the
+//! host send a comment line, which clients ignore, whenever the component
+//! hasn't written anything for that long. Comments are only sent between
+//! events, never in the middle of one the component is still writing.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE, HeaderMap, HeaderValue};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, Sleep};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+/// The media type of Server-Sent Events
+pub const EVENT_STREAM: &str = "text/event-stream";
+/// The comment sent on quiet event streams
+const KEEPALIVE_COMMENT: &[u8] = b": keepalive\n\n";
+
+/// Returns whether the headers of a response declare an event stream.
+pub(crate) fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(EVENT_STREAM))
+}
+
+/// Streams the body of an event stream `response` as the [module docs](self)
+/// describe, sending a keepalive comment after `keepalive` without events.
+/// Other responses are returned as they are.
+pub(crate) fn apply(
+    response: hyper::Response<HyperOutgoingBody>,
+    keepalive: Option<Duration>,
+) -> hyper::Response<HyperOutgoingBody> {
+    if !is_event_stream(response.headers()) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .entry(CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-cache"));
+    parts
+        .headers
+        .entry("x-accel-buffering")
+        .or_insert(HeaderValue::from_static("no"));
+    let body = http_body_util::BodyExt::boxed(EventStreamBody {
+        inner: body,
+        keepalive: keepalive.map(|interval| {
+            (
+                interval,
+                Box::pin(tokio::time::sleep_until(Instant::now() + interval)),
+            )
+        }),
+        flushed: true,
+        tail: Vec::new(),
+    });
+    hyper::Response::from_parts(parts, body)
+}
+
+/// An event stream body that is flushed after every chunk and kept alive
+/// with comments.
+struct EventStreamBody {
+    inner: HyperOutgoingBody,
+    keepalive: Option<(Duration, Pin<Box<Sleep>>)>,
+    /// Whether the connection was given a chance to flush the last chunk
+    flushed: bool,
+    /// The last bytes sent, to tell whether the stream is between events
+    tail: Vec<u8>,
+}
+
+impl EventStreamBody {
+    /// Returns whether the bytes sent so far end with a whole event.
+    fn between_events(&self) -> bool {
+        self.tail.is_empty()
+            || self.tail.ends_with(b"\n\n")
+            || self.tail.ends_with(b"\r\r")
+            || self.tail.ends_with(b"\n\r\n")
+    }
+
+    fn sent(&mut self, data: &[u8]) {
+        self.tail
+            .extend_from_slice(&data[data.len().saturating_sub(3)..]);
+        let keep = self.tail.len().saturating_sub(3);
+        self.tail.drain(..keep);
+        self.flushed = false;
+        if let Some((interval, sleep)) = self.keepalive.as_mut() {
+            sleep.as_mut().reset(Instant::now() + *interval);
+        }
+    }
+}
+
+impl Body for EventStreamBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        // The connection flushes what it has written whenever the body is pending
+        if !this.flushed {
+            this.flushed = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref().filter(|data| !data.is_empty()) {
+                    this.sent(data);
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(other) => Poll::Ready(other),
+            Poll::Pending => {
+                let between_events = this.between_events();
+                if let Some((_, sleep)) = this.keepalive.as_mut()
+                    && between_events
+                    && sleep.as_mut().poll(cx).is_ready()
+                {
+                    this.sent(KEEPALIVE_COMMENT);
+                    return Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(
+                        KEEPALIVE_COMMENT,
+                    )))));
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Turns off Nagle's algorithm on a connection once it serves an event
+/// stream, see [`NoDelayIo`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NoDelay(Arc<AtomicBool>);
+
+impl NoDelay {
+    /// Asks for Nagle's algorithm to be turned off before the next write.
+    pub(crate) fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A TCP connection whose Nagle's algorithm is turned off once its
+/// [`NoDelay`] is requested. TLS is layered on top of it.
+pub(crate) struct NoDelayIo {
+    inner: TcpStream,
+    no_delay: NoDelay,
+}
+
+impl NoDelayIo {
+    pub(crate) fn new(inner: TcpStream, no_delay: NoDelay) -> Self {
+        Self { inner, no_delay }
+    }
+
+    fn apply_no_delay(&self) {
+        if self.no_delay.0.swap(false, Ordering::Relaxed) {
+            // Only a latency optimization, so failing to set it is ignored
+            _ = self.inner.set_nodelay(true);
+        }
+    }
+}
+
+impl AsyncRead for NoDelayIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for NoDelayIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.apply_no_delay();
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        self.apply_no_delay();
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt as _;
+    use http_body_util::BodyExt as _;
+
+    use super::*;
+
+    fn event_stream(chunks: &[&'static [u8]]) -> hyper::Response<HyperOutgoingBody> {
+        let frames = chunks
+            .iter()
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk))))
+            .collect::<Vec<_>>();
+        let body = http_body_util::StreamBody::new(
+            futures::stream::iter(frames).chain(futures::stream::pending()),
+        )
+        .boxed();
+        hyper::Response::builder()
+            .header(CONTENT_TYPE, "text/event-stream; charset=utf-8")
+            .body(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_event_stream() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_event_stream(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("Text/Event-Stream"));
+        assert!(is_event_stream(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(!is_event_stream(&headers));
+    }
+
+    #[test]
+    fn test_flushes_after_each_chunk() {
+        let response = apply(event_stream(&[b"data: a\n\n", b"data: b\n\n"]), None);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        assert_eq!(response.headers()["x-accel-buffering"], "no");
+
+        let mut body = response.into_body();
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut poll = || Pin::new(&mut body).poll_frame(&mut cx);
+        assert!(matches!(poll(), Poll::Ready(Some(Ok(_)))));
+        assert!(poll().is_pending());
+        assert!(matches!(poll(), Poll::Ready(Some(Ok(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_between_events() {
+        let keepalive = Some(Duration::from_millis(20));
+        let mut body = apply(event_stream(&[b"data: a\n\n"]), keepalive).into_body();
+        let event = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(&event[..], b"data: a\n\n");
+        let comment = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(&comment[..], KEEPALIVE_COMMENT);
+
+        // Not in the middle of an event
+        let mut body = apply(event_stream(&[b"data: a\n"]), keepalive).into_body();
+        body.frame().await.unwrap().unwrap();
+        let frame = tokio::time::timeout(Duration::from_millis(100), body.frame()).await;
+        assert!(frame.is_err());
+    }
+
+    #[test]
+    fn test_other_responses_pass_through() {
+        let response = hyper::Response::new(HyperOutgoingBody::default());
+        let response = apply(response, Some(Duration::from_secs(1)));
+        assert!(response.headers().get(CACHE_CONTROL).is_none());
+    }
+}
@@ -0,0 +1,171 @@
+//! Flow control for streaming responses.
+//!
+//! A component may keep writing a response body long after it returned the
+//! response head. [`StreamLimits`] bound how long the host waits between two
+//! chunks and how long the whole body may take. When a limit is hit, the stream
+//! is aborted and the component's body is dropped, which releases its instance
+//! instead of leaking it on a hung upstream.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::time::{Instant, Sleep};
+use tracing::warn;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+/// Limits applied to the body of a streaming response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamLimits {
+    /// Maximum time between two chunks of the body
+    pub idle_timeout: Option<Duration>,
+    /// Maximum time to stream the whole body, starting when the head is sent
+    pub max_duration: Option<Duration>,
+}
+
+impl StreamLimits {
+    /// Aborts bodies that stall for longer than `timeout` between chunks.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Aborts bodies that take longer than `duration` to stream.
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Returns whether no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.idle_timeout.is_none() && self.max_duration.is_none()
+    }
+
+    /// Wraps a response body so it is aborted when a limit is exceeded.
+    pub(crate) fn apply(&self, workload_id: &str, body: HyperOutgoingBody) -> HyperOutgoingBody {
+        if self.is_unlimited() {
+            return body;
+        }
+        let now = Instant::now();
+        http_body_util::BodyExt::boxed(LimitedBody {
+            inner: body,
+            workload_id: workload_id.to_string(),
+            idle_timeout: self.idle_timeout,
+            idle: self
+                .idle_timeout
+                .map(|timeout| Box::pin(tokio::time::sleep_until(now + timeout))),
+            deadline: self
+                .max_duration
+                .map(|duration| Box::pin(tokio::time::sleep_until(now + duration))),
+            aborted: false,
+        })
+    }
+}
+
+/// A response body that enforces [`StreamLimits`] on an inner body.
+struct LimitedBody {
+    inner: HyperOutgoingBody,
+    workload_id: String,
+    idle_timeout: Option<Duration>,
+    idle: Option<Pin<Box<Sleep>>>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    aborted: bool,
+}
+
+impl LimitedBody {
+    fn abort(&mut self, reason: &str) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        warn!(
+            workload_id = %self.workload_id,
+            reason,
+            "aborting stalled response stream"
+        );
+        self.aborted = true;
+        // Dropping the component's body releases the instance producing it
+        self.inner = HyperOutgoingBody::default();
+        Poll::Ready(Some(Err(ErrorCode::HttpResponseTimeout)))
+    }
+}
+
+impl Body for LimitedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.aborted {
+            return Poll::Ready(None);
+        }
+        if let Some(deadline) = this.deadline.as_mut()
+            && deadline.as_mut().poll(cx).is_ready()
+        {
+            return this.abort("stream exceeded its maximum duration");
+        }
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(frame) => {
+                if let (Some(idle), Some(timeout)) = (this.idle.as_mut(), this.idle_timeout) {
+                    idle.as_mut().reset(Instant::now() + timeout);
+                }
+                Poll::Ready(frame)
+            }
+            Poll::Pending => {
+                if let Some(idle) = this.idle.as_mut()
+                    && idle.as_mut().poll(cx).is_ready()
+                {
+                    return this.abort("no chunk received within the idle timeout");
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.aborted || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt as _;
+
+    use super::*;
+
+    fn stalled_body() -> HyperOutgoingBody {
+        http_body_util::StreamBody::new(
+            futures::stream::pending::<Result<Frame<Bytes>, ErrorCode>>(),
+        )
+        .boxed()
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_aborts_stream() {
+        let limits = StreamLimits {
+            idle_timeout: Some(Duration::from_millis(50)),
+            max_duration: None,
+        };
+        let mut body = limits.apply("workload", stalled_body());
+        let frame = body.frame().await.expect("should yield an error");
+        assert!(matches!(frame, Err(ErrorCode::HttpResponseTimeout)));
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_passes_body_through() {
+        let body = http_body_util::Full::new(Bytes::from_static(b"hello"))
+            .map_err(|never| match never {})
+            .boxed();
+        let body = StreamLimits::default().apply("workload", body);
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert_eq!(collected, Bytes::from_static(b"hello"));
+    }
+}
@@ -0,0 +1,360 @@
+//! TLS termination of the HTTP server.
+//!
+//! An [`HttpServer`](super::HttpServer) serves HTTPS once it has a
+//! certificate, either read from PEM files, see [`TlsFiles`], which the server
+//! can read again while it runs, or given as PEM in memory, see
+//! [`TlsCertificates`], e.g. when they come from a secret store.
+//!
+//! # Client certificates
+//!
+//! Given the CA certificates of clients, the server requires every client to
+//! present a certificate issued by one of them (mutual TLS). The handshake of
+//! a client without one fails.
+//!
+//! # Certificates per host name
+//!
+//! The [`DynamicRouter`](super::DynamicRouter) routes requests by their `Host`
+//! header, so one server serves the routes of many host names. Each of them
+//! may have a certificate of its own, picked by the server name (SNI) the
+//! client sends in its handshake:
+//!
+//! ```toml
+//! [[http.tls_sni]]
+//! host = "api.example.com"
+//! cert = "/etc/wash/tls/api.crt"
+//! key = "/etc/wash/tls/api.key"
+//! ```
+//!
+//! A host name may be a wildcard matching a single label, e.g.
+//! `*.example.com`, which an exact host name takes precedence over. Clients
+//! sending no server name, or one without a certificate of its own, are
+//! served the default certificate.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::CertificateDer;
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig};
+use rustls_pemfile::{certs, private_key};
+use serde::{Deserialize, Serialize};
+use tokio_rustls::TlsAcceptor;
+
+use super::h2::{ALPN_H2, ALPN_HTTP1};
+
+/// The certificate files of the TLS listeners of an
+/// [`HttpServer`](super::HttpServer).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TlsFiles {
+    /// PEM file of the certificate chain
+    pub cert: PathBuf,
+    /// PEM file of the private key
+    pub key: PathBuf,
+    /// PEM file of the CA certificates of clients, for mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca: Option<PathBuf>,
+    /// Certificates of host names served another certificate than `cert`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sni: Vec<SniFiles>,
+}
+
+impl TlsFiles {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            ca: None,
+            sni: Vec::new(),
+        }
+    }
+
+    pub fn with_ca(mut self, ca: impl Into<PathBuf>) -> Self {
+        self.ca = Some(ca.into());
+        self
+    }
+
+    /// Serves clients asking for a host name the certificate of `files`.
+    pub fn with_sni(mut self, files: SniFiles) -> Self {
+        self.sni.push(files);
+        self
+    }
+
+    /// Reads the certificates from the files.
+    pub async fn read(&self) -> anyhow::Result<TlsCertificates> {
+        let mut certificates =
+            TlsCertificates::new(read_pem(&self.cert).await?, read_pem(&self.key).await?);
+        if let Some(ca) = &self.ca {
+            certificates = certificates.with_client_ca(read_pem(ca).await?);
+        }
+        for sni in &self.sni {
+            certificates = certificates.with_sni(
+                &sni.host,
+                read_pem(&sni.cert).await?,
+                read_pem(&sni.key).await?,
+            );
+        }
+        Ok(certificates)
+    }
+
+    /// Reads the files and builds the acceptor of TLS connections.
+    pub(super) async fn load(&self) -> anyhow::Result<TlsAcceptor> {
+        self.read()
+            .await?
+            .acceptor()
+            .with_context(|| format!("invalid TLS certificates in {}", self.cert.display()))
+    }
+}
+
+/// The certificate files of a host name, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SniFiles {
+    /// Host name, or wildcard like `*.example.com`, clients ask for
+    pub host: String,
+    /// PEM file of the certificate chain
+    pub cert: PathBuf,
+    /// PEM file of the private key
+    pub key: PathBuf,
+}
+
+impl SniFiles {
+    pub fn new(host: impl Into<String>, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            host: host.into(),
+            cert: cert.into(),
+            key: key.into(),
+        }
+    }
+}
+
+/// Parses `HOST=CERT,KEY`, e.g. `api.example.com=api.crt,api.key`.
+impl FromStr for SniFiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((host, (cert, key))) = s
+            .split_once('=')
+            .and_then(|(host, files)| Some((host, files.split_once(',')?)))
+        else {
+            bail!("invalid SNI certificate '{s}', expected HOST=CERT,KEY");
+        };
+        if host.is_empty() || cert.is_empty() || key.is_empty() {
+            bail!("invalid SNI certificate '{s}', expected HOST=CERT,KEY");
+        }
+        Ok(Self::new(host, cert, key))
+    }
+}
+
+async fn read_pem(path: &Path) -> anyhow::Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// PEM encoded certificates of the TLS listeners of an
+/// [`HttpServer`](super::HttpServer), see the [module docs](self).
+#[derive(Clone)]
+pub struct TlsCertificates {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    client_ca: Option<Vec<u8>>,
+    /// Host name, certificate chain and private key of each host name
+    sni: Vec<(String, Vec<u8>, Vec<u8>)>,
+}
+
+impl std::fmt::Debug for TlsCertificates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsCertificates")
+            .field("client_ca", &self.client_ca.is_some())
+            .field(
+                "sni",
+                &self.sni.iter().map(|(host, ..)| host).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl TlsCertificates {
+    /// Serves the certificate chain `cert` with the private key `key`.
+    pub fn new(cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+            sni: Vec::new(),
+        }
+    }
+
+    /// Requires clients to present a certificate issued by one of the CA
+    /// certificates in `ca`.
+    pub fn with_client_ca(mut self, ca: impl Into<Vec<u8>>) -> Self {
+        self.client_ca = Some(ca.into());
+        self
+    }
+
+    /// Serves clients asking for `host` the certificate chain `cert` with the
+    /// private key `key` instead.
+    pub fn with_sni(
+        mut self,
+        host: impl Into<String>,
+        cert: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.sni.push((host.into(), cert.into(), key.into()));
+        self
+    }
+
+    /// Builds the acceptor of TLS connections.
+    ///
+    /// # Errors
+    /// Returns an error if a certificate or key is invalid, a key does not
+    /// belong to its certificate, or a host name has two certificates.
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let builder = ServerConfig::builder();
+        let provider = builder.crypto_provider().clone();
+
+        let mut resolver = SniResolver {
+            default: certified_key(&provider, &self.cert, &self.key)
+                .context("invalid default certificate")?,
+            hosts: HashMap::new(),
+        };
+        for (host, cert, key) in &self.sni {
+            let certified = certified_key(&provider, cert, key)
+                .with_context(|| format!("invalid certificate of {host}"))?;
+            ensure!(
+                resolver
+                    .hosts
+                    .insert(host.to_ascii_lowercase(), certified)
+                    .is_none(),
+                "{host} has two certificates"
+            );
+        }
+
+        let builder = match &self.client_ca {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in parse_certs(ca).context("invalid client CA certificates")? {
+                    roots.add(cert).context("invalid client CA certificate")?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()
+                        .context("failed to build client certificate verifier")?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(Arc::new(resolver));
+        config.alpn_protocols = vec![ALPN_H2.to_vec(), ALPN_HTTP1.to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn parse_certs(pem: &[u8]) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = certs(&mut std::io::Cursor::new(pem))
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to parse PEM certificates")?;
+    ensure!(!certs.is_empty(), "no certificate found");
+    Ok(certs)
+}
+
+/// Parses a certificate chain and its private key.
+fn certified_key(
+    provider: &CryptoProvider,
+    cert: &[u8],
+    key: &[u8],
+) -> anyhow::Result<Arc<CertifiedKey>> {
+    let cert_chain = parse_certs(cert)?;
+    let key = private_key(&mut std::io::Cursor::new(key))
+        .context("failed to parse PEM private key")?
+        .context("no private key found")?;
+    let key = provider
+        .key_provider
+        .load_private_key(key)
+        .context("unsupported private key")?;
+    let certified = CertifiedKey::new(cert_chain, key);
+    certified
+        .keys_match()
+        .context("the private key does not belong to the certificate")?;
+    Ok(Arc::new(certified))
+}
+
+/// Picks the certificate of the server name a client asks for.
+struct SniResolver {
+    default: Arc<CertifiedKey>,
+    /// Certificates by lowercase host name
+    hosts: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl std::fmt::Debug for SniResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SniResolver")
+            .field("hosts", &self.hosts.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certified = client_hello
+            .server_name()
+            .and_then(|name| lookup(&self.hosts, name))
+            .unwrap_or(&self.default);
+        Some(certified.clone())
+    }
+}
+
+/// Returns the entry of `name` in `hosts`, or else of the wildcard matching
+/// it.
+fn lookup<'a, V>(hosts: &'a HashMap<String, V>, name: &str) -> Option<&'a V> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    hosts.get(&name).or_else(|| {
+        let (_, parent) = name.split_once('.')?;
+        hosts.get(&format!("*.{parent}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_sni() {
+        let hosts = HashMap::from([
+            ("api.example.com".to_string(), "api"),
+            ("*.example.com".to_string(), "wildcard"),
+        ]);
+        assert_eq!(lookup(&hosts, "api.example.com"), Some(&"api"));
+        assert_eq!(lookup(&hosts, "API.Example.com."), Some(&"api"));
+        assert_eq!(lookup(&hosts, "www.example.com"), Some(&"wildcard"));
+        // Wildcards match a single label
+        assert_eq!(lookup(&hosts, "a.b.example.com"), None);
+        assert_eq!(lookup(&hosts, "example.com"), None);
+    }
+
+    #[test]
+    fn test_parse_sni_files() {
+        assert_eq!(
+            "api.example.com=api.crt,api.key"
+                .parse::<SniFiles>()
+                .unwrap(),
+            SniFiles::new("api.example.com", "api.crt", "api.key")
+        );
+        assert!("api.example.com=api.crt".parse::<SniFiles>().is_err());
+        assert!("=api.crt,api.key".parse::<SniFiles>().is_err());
+        assert!("api.example.com".parse::<SniFiles>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_certificates() {
+        let err = TlsCertificates::new("not a certificate", "not a key")
+            .acceptor()
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("invalid default certificate"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Tracing requests from the listener through the component to the requests
+//! it sends.
+//!
+//! Every request is traced with a server span, continuing the trace named by
+//! its W3C `traceparent` header if it has one. Matching the request to a
+//! route, acquiring an instance of the component and running the guest are
+//! traced with child spans. The outgoing HTTP requests of the component carry
+//! the context of the span running the guest in their `traceparent` header,
+//! unless the component set the header itself, so a component served by
+//! another host continues the same trace.
+//!
+//! Spans are recorded with the global tracer provider, which drops them
+//! unless an exporter is installed, see
+//! [`HostBuilder::with_tracing`](crate::host::HostBuilder).
+
+use std::borrow::Cow;
+
+use hyper::HeaderMap;
+use hyper::header::HeaderValue;
+use opentelemetry::trace::{SpanContext, SpanKind, Status, TraceContextExt as _, Tracer as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_semantic_conventions::attribute;
+
+use super::metrics::TRACEPARENT_HEADER;
+
+/// Name of the tracer recording the spans of the HTTP server
+const TRACER_NAME: &str = "wash-http";
+
+/// Starts the server span of a request, continuing the trace of `parent`,
+/// and returns the context it is the active span of.
+pub(crate) fn server_span(
+    method: &hyper::Method,
+    path: &str,
+    parent: Option<SpanContext>,
+) -> Context {
+    let parent = match parent {
+        Some(parent) => Context::new().with_remote_span_context(parent),
+        None => Context::new(),
+    };
+    let tracer = opentelemetry::global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(method.as_str().to_string())
+        .with_kind(SpanKind::Server)
+        .with_attributes([
+            KeyValue::new(attribute::HTTP_REQUEST_METHOD, method.as_str().to_string()),
+            KeyValue::new(attribute::URL_PATH, path.to_string()),
+        ])
+        .start_with_context(&tracer, &parent);
+    parent.with_span(span)
+}
+
+/// Ends the server span of a request answered with `status`. The span is
+/// renamed after the route the request matched, if any.
+pub(crate) fn end_server_span(
+    cx: &Context,
+    method: &hyper::Method,
+    route: Option<&str>,
+    status: hyper::StatusCode,
+) {
+    let span = cx.span();
+    if let Some(route) = route {
+        span.update_name(format!("{method} {route}"));
+        span.set_attribute(KeyValue::new(attribute::HTTP_ROUTE, route.to_string()));
+    }
+    span.set_attribute(KeyValue::new(
+        attribute::HTTP_RESPONSE_STATUS_CODE,
+        i64::from(status.as_u16()),
+    ));
+    if status.is_server_error() {
+        span.set_status(Status::error(status.as_u16().to_string()));
+    }
+    span.end();
+}
+
+/// Starts a span named `name` as a child of `parent`.
+pub(crate) fn child_span(parent: &Context, name: impl Into<Cow<'static, str>>) -> Context {
+    let tracer = opentelemetry::global::tracer(TRACER_NAME);
+    let span = tracer.start_with_context(name, parent);
+    parent.with_span(span)
+}
+
+/// Formats a span context as a W3C `traceparent` header, the inverse of
+/// [`super::metrics::parse_traceparent`].
+pub(crate) fn format_traceparent(context: &SpanContext) -> String {
+    format!(
+        "00-{}-{}-{:02x}",
+        context.trace_id(),
+        context.span_id(),
+        context.trace_flags().to_u8()
+    )
+}
+
+/// Sets the `traceparent` header of an outgoing request to `context`, unless
+/// the component set one.
+pub(crate) fn inject_traceparent(headers: &mut HeaderMap, context: &SpanContext) {
+    if !context.is_valid() || headers.contains_key(TRACEPARENT_HEADER) {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&format_traceparent(context)) {
+        headers.insert(TRACEPARENT_HEADER, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{SpanId, TraceFlags, TraceId};
+
+    use super::*;
+
+    #[test]
+    fn test_inject_traceparent() {
+        let context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            Default::default(),
+        );
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert_eq!(format_traceparent(&context), traceparent);
+
+        let mut headers = HeaderMap::new();
+        inject_traceparent(&mut headers, &context);
+        assert_eq!(headers[TRACEPARENT_HEADER], traceparent);
+        let parsed = super::super::metrics::parse_traceparent(&headers).unwrap();
+        assert_eq!(parsed.trace_id(), context.trace_id());
+        assert_eq!(parsed.span_id(), context.span_id());
+
+        // The header a component set is kept
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT_HEADER,
+            HeaderValue::from_static("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00"),
+        );
+        inject_traceparent(&mut headers, &context);
+        assert_ne!(headers[TRACEPARENT_HEADER], traceparent);
+
+        let mut headers = HeaderMap::new();
+        inject_traceparent(&mut headers, &SpanContext::empty_context());
+        assert!(headers.is_empty());
+    }
+}
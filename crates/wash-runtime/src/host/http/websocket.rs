@@ -0,0 +1,680 @@
+//! WebSocket connections to components.
+//!
+//! A route with [`HttpRouteConfig::websocket`](super::HttpRouteConfig::websocket)
+//! set accepts WebSocket handshakes, i.e. `GET` requests over HTTP/1.1 with
+//! `Connection: Upgrade` and `Upgrade: websocket`. The host checks the
+//! handshake and invokes the component with the request as usual, its
+//! WebSocket headers included. The component accepts the connection by
+//! responding with `101 Switching Protocols` or any `2xx` status, e.g. with
+//! the `Sec-WebSocket-Protocol` it chose, and refuses it with any other
+//! response, which is sent to the client as it is.
+//!
+//! `wasi:http` has no notion of upgrades, so once a connection is accepted the
+//! messages are exchanged through the bodies of the request and response: the
+//! request body carries the messages of the client, the response body those
+//! sent to the client. On both, each message is
+//!
+//! - a byte with its type, `1` for text and `2` for binary, see [`MessageKind`],
+//! - its length as a 32-bit big-endian integer, and
+//! - its payload,
+//!
+//! see [`encode_message`]. The host answers pings and reassembles fragmented
+//! messages, so components only see whole messages. The request body ends when
+//! the client closes the connection, and the connection is closed once the
+//! component ends its response body.
+//!
+//! The component instance handles the connection for as long as it is open,
+//! so the request timeout of the route only applies to the handshake.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::Engine as _;
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use http_body_util::BodyExt as _;
+use hyper::header::{
+    CONNECTION, CONTENT_LENGTH, HeaderMap, HeaderName, HeaderValue, SEC_WEBSOCKET_ACCEPT,
+    SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, TRANSFER_ENCODING, UPGRADE,
+};
+use hyper::upgrade::OnUpgrade;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use wasmtime_wasi_http::{body::HyperOutgoingBody, io::TokioIo};
+
+/// Largest message exchanged in either direction, in bytes
+pub const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Appended to the key of a handshake to compute the accept key, see RFC 6455
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Messages of the client waiting for the component to read them
+const INBOUND_BUFFER: usize = 16;
+/// Pongs and closes waiting to be sent
+const CONTROL_BUFFER: usize = 4;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_LARGE: u16 = 1009;
+const CLOSE_INTERNAL_ERROR: u16 = 1011;
+
+/// The type of a WebSocket message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Text,
+    Binary,
+}
+
+impl MessageKind {
+    fn opcode(self) -> u8 {
+        match self {
+            MessageKind::Text => OP_TEXT,
+            MessageKind::Binary => OP_BINARY,
+        }
+    }
+
+    fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            OP_TEXT => Some(MessageKind::Text),
+            OP_BINARY => Some(MessageKind::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a message as it is carried on request and response bodies, see
+/// the [module docs](self).
+pub fn encode_message(kind: MessageKind, payload: &[u8]) -> Bytes {
+    let mut message = BytesMut::with_capacity(5 + payload.len());
+    message.put_u8(kind.opcode());
+    message.put_u32(payload.len() as u32);
+    message.put_slice(payload);
+    message.freeze()
+}
+
+/// Returns whether the headers of a request ask to upgrade it to a WebSocket.
+pub(crate) fn is_upgrade(headers: &HeaderMap) -> bool {
+    has_token(headers, CONNECTION, "upgrade") && has_token(headers, UPGRADE, "websocket")
+}
+
+fn has_token(headers: &HeaderMap, name: HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Computes the `Sec-WebSocket-Accept` answering the `Sec-WebSocket-Key` of a
+/// handshake.
+fn accept_key(key: &[u8]) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(key);
+    context.update(ACCEPT_GUID);
+    base64::engine::general_purpose::STANDARD.encode(context.finish())
+}
+
+/// A WebSocket handshake waiting for the response of the component.
+pub(crate) struct Handshake {
+    accept: HeaderValue,
+    upgrade: OnUpgrade,
+    inbound: mpsc::Sender<Bytes>,
+}
+
+impl Handshake {
+    /// Checks the handshake `req` asks for and takes over its connection.
+    /// Returns the body the component receives the messages of the client on.
+    ///
+    /// # Errors
+    /// Returns why the handshake is invalid.
+    pub(crate) fn start<B>(req: &mut hyper::Request<B>) -> Result<(Self, MessageBody), String> {
+        if req.method() != hyper::Method::GET {
+            return Err(format!("handshake must be a GET, not {}", req.method()));
+        }
+        if req.version() != hyper::Version::HTTP_11 {
+            return Err(format!(
+                "handshake must use HTTP/1.1, not {:?}",
+                req.version()
+            ));
+        }
+        let version = req.headers().get(SEC_WEBSOCKET_VERSION);
+        if version.is_none_or(|version| version != "13") {
+            return Err(format!("unsupported WebSocket version {version:?}"));
+        }
+        let key = req
+            .headers()
+            .get(SEC_WEBSOCKET_KEY)
+            .map(|key| key.as_bytes().trim_ascii())
+            .filter(|key| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(key)
+                    .is_ok_and(|nonce| nonce.len() == 16)
+            })
+            .ok_or("handshake has no valid Sec-WebSocket-Key")?;
+        let accept = HeaderValue::from_str(&accept_key(key))
+            .map_err(|e| format!("invalid accept key: {e}"))?;
+
+        let upgrade = hyper::upgrade::on(req);
+        let (inbound, messages) = mpsc::channel(INBOUND_BUFFER);
+        Ok((
+            Self {
+                accept,
+                upgrade,
+                inbound,
+            },
+            MessageBody(messages),
+        ))
+    }
+
+    /// Completes the handshake with the response of the component. An
+    /// accepting response is replaced with `101 Switching Protocols` and its
+    /// body becomes the messages sent to the client, other responses refuse
+    /// the upgrade and are returned as they are.
+    pub(crate) fn respond(
+        self,
+        response: hyper::Response<HyperOutgoingBody>,
+        workload_id: &str,
+    ) -> hyper::Response<HyperOutgoingBody> {
+        let status = response.status();
+        if status != hyper::StatusCode::SWITCHING_PROTOCOLS && !status.is_success() {
+            debug!(host = %workload_id, %status, "component refused WebSocket upgrade");
+            return response;
+        }
+        let (parts, outbound) = response.into_parts();
+
+        let mut response = hyper::Response::new(HyperOutgoingBody::default());
+        *response.status_mut() = hyper::StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        for (name, value) in &parts.headers {
+            if name != CONTENT_LENGTH && name != TRANSFER_ENCODING {
+                headers.append(name, value.clone());
+            }
+        }
+        headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(SEC_WEBSOCKET_ACCEPT, self.accept);
+
+        let workload_id = workload_id.to_string();
+        let (upgrade, inbound) = (self.upgrade, self.inbound);
+        tokio::spawn(async move {
+            let upgraded = match upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(e) => {
+                    warn!(host = %workload_id, err = ?e, "failed to upgrade connection to WebSocket");
+                    return;
+                }
+            };
+            debug!(host = %workload_id, "WebSocket connection opened");
+            match relay(TokioIo::new(upgraded), inbound, outbound).await {
+                Ok(()) => debug!(host = %workload_id, "WebSocket connection closed"),
+                Err(e) => debug!(host = %workload_id, err = ?e, "WebSocket connection failed"),
+            }
+        });
+        response
+    }
+}
+
+/// The body carrying the messages of the client to the component.
+pub(crate) struct MessageBody(mpsc::Receiver<Bytes>);
+
+impl hyper::body::Body for MessageBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<hyper::body::Frame<Bytes>, hyper::Error>>> {
+        self.0
+            .poll_recv(cx)
+            .map(|message| message.map(|message| Ok(hyper::body::Frame::data(message))))
+    }
+}
+
+/// Exchanges messages between a client on `stream` and a component, until
+/// either closes the connection.
+async fn relay<S>(
+    stream: S,
+    inbound: mpsc::Sender<Bytes>,
+    outbound: HyperOutgoingBody,
+) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (control, controls) = mpsc::channel(CONTROL_BUFFER);
+    let receiving = async move {
+        if let Some(code) = receive(&mut reader, &inbound, &control).await {
+            _ = control.send(Frame::close(code)).await;
+        }
+    };
+    let sending = send(&mut writer, outbound, controls);
+    tokio::pin!(sending);
+    tokio::select! {
+        result = &mut sending => result?,
+        // The client is gone or closed the connection, which is answered
+        () = receiving => sending.await?,
+    }
+    writer.shutdown().await
+}
+
+/// Passes the messages of the client to the component until the client
+/// closes the connection. Returns the code of the close frame to answer with,
+/// if any.
+async fn receive<R>(
+    reader: &mut R,
+    inbound: &mpsc::Sender<Bytes>,
+    control: &mpsc::Sender<Frame>,
+) -> Option<u16>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut message: Option<(MessageKind, BytesMut)> = None;
+    loop {
+        let frame = match read_frame(reader).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return None,
+            Err(e) => return e.close_code(),
+        };
+        match frame.opcode {
+            OP_PING => {
+                _ = control.send(Frame::new(OP_PONG, frame.payload)).await;
+                continue;
+            }
+            OP_PONG => continue,
+            OP_CLOSE => {
+                return Some(match frame.payload.len() {
+                    0 => CLOSE_NORMAL,
+                    1 => CLOSE_PROTOCOL_ERROR,
+                    _ => u16::from_be_bytes([frame.payload[0], frame.payload[1]]),
+                });
+            }
+            OP_CONTINUATION => match &mut message {
+                Some((_, data)) if data.len() + frame.payload.len() > MAX_MESSAGE_BYTES => {
+                    return Some(CLOSE_TOO_LARGE);
+                }
+                Some((_, data)) => data.extend_from_slice(&frame.payload),
+                None => return Some(CLOSE_PROTOCOL_ERROR),
+            },
+            opcode => match MessageKind::from_opcode(opcode) {
+                // A new message may only start once the previous one is whole
+                Some(kind) if message.is_none() => {
+                    message = Some((kind, BytesMut::from(&frame.payload[..])));
+                }
+                _ => return Some(CLOSE_PROTOCOL_ERROR),
+            },
+        }
+        if !frame.fin {
+            continue;
+        }
+        let Some((kind, data)) = message.take() else {
+            continue;
+        };
+        if kind == MessageKind::Text && std::str::from_utf8(&data).is_err() {
+            return Some(CLOSE_INVALID_DATA);
+        }
+        // The component may stop reading before the connection closes
+        _ = inbound.send(encode_message(kind, &data)).await;
+    }
+}
+
+/// Sends the messages of the component, and the control frames answering
+/// the client, until either closes the connection.
+async fn send<W>(
+    writer: &mut W,
+    mut outbound: HyperOutgoingBody,
+    mut controls: mpsc::Receiver<Frame>,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut decoder = MessageDecoder::default();
+    loop {
+        tokio::select! {
+            control = controls.recv() => {
+                let Some(frame) = control else {
+                    return Ok(());
+                };
+                write_frame(writer, &frame).await?;
+                if frame.opcode == OP_CLOSE {
+                    return Ok(());
+                }
+            }
+            chunk = outbound.frame() => {
+                let data = match chunk {
+                    Some(Ok(frame)) => match frame.into_data() {
+                        Ok(data) => data,
+                        Err(_) => continue,
+                    },
+                    None if decoder.is_empty() => {
+                        return write_frame(writer, &Frame::close(CLOSE_NORMAL)).await;
+                    }
+                    None => {
+                        warn!("component ended its WebSocket messages in the middle of one");
+                        return write_frame(writer, &Frame::close(CLOSE_INTERNAL_ERROR)).await;
+                    }
+                    Some(Err(e)) => {
+                        warn!(err = ?e, "failed to read WebSocket messages of component");
+                        return write_frame(writer, &Frame::close(CLOSE_INTERNAL_ERROR)).await;
+                    }
+                };
+                decoder.push(&data);
+                loop {
+                    match decoder.next() {
+                        Ok(Some((kind, payload))) => {
+                            write_frame(writer, &Frame::new(kind.opcode(), payload)).await?;
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            warn!("invalid WebSocket message from component: {e}");
+                            return write_frame(writer, &Frame::close(CLOSE_INTERNAL_ERROR)).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits the response body of a component into messages.
+#[derive(Debug, Default)]
+struct MessageDecoder {
+    buffer: BytesMut,
+}
+
+impl MessageDecoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the next whole message, if any.
+    fn next(&mut self) -> Result<Option<(MessageKind, Bytes)>, String> {
+        let Some(&[kind, a, b, c, d]) = self.buffer.get(..5) else {
+            return Ok(None);
+        };
+        let kind = MessageKind::from_opcode(kind).ok_or(format!("unknown message type {kind}"))?;
+        let len = u32::from_be_bytes([a, b, c, d]) as usize;
+        if len > MAX_MESSAGE_BYTES {
+            return Err(format!("message of {len} bytes is too large"));
+        }
+        if self.buffer.len() < 5 + len {
+            return Ok(None);
+        }
+        self.buffer.advance(5);
+        Ok(Some((kind, self.buffer.split_to(len).freeze())))
+    }
+}
+
+/// A frame of the WebSocket protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Bytes,
+}
+
+impl Frame {
+    fn new(opcode: u8, payload: Bytes) -> Self {
+        Self {
+            fin: true,
+            opcode,
+            payload,
+        }
+    }
+
+    fn close(code: u16) -> Self {
+        Self::new(OP_CLOSE, Bytes::copy_from_slice(&code.to_be_bytes()))
+    }
+}
+
+/// Why a frame of the client could not be read.
+#[derive(Debug)]
+enum FrameError {
+    Io(std::io::Error),
+    Protocol,
+    TooLarge,
+}
+
+impl FrameError {
+    /// The code of the close frame answering the error, if the connection
+    /// still works.
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            FrameError::Io(_) => None,
+            FrameError::Protocol => Some(CLOSE_PROTOCOL_ERROR),
+            FrameError::TooLarge => Some(CLOSE_TOO_LARGE),
+        }
+    }
+}
+
+impl From<std::io::Error> for FrameError {
+    fn from(e: std::io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+/// Reads a frame sent by a client, which are always masked. Returns `None`
+/// once the client closed the connection.
+async fn read_frame<R>(reader: &mut R) -> Result<Option<Frame>, FrameError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut head = [0u8; 2];
+    match reader.read_exact(&mut head).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+    // No extensions are negotiated, so the reserved bits are never set
+    if head[0] & 0x70 != 0 || head[1] & 0x80 == 0 {
+        return Err(FrameError::Protocol);
+    }
+    let len = match head[1] & 0x7f {
+        126 => u64::from(reader.read_u16().await?),
+        127 => reader.read_u64().await?,
+        len => u64::from(len),
+    };
+    if opcode >= OP_CLOSE && (!fin || len > 125) {
+        return Err(FrameError::Protocol);
+    }
+    if len > MAX_MESSAGE_BYTES as u64 {
+        return Err(FrameError::TooLarge);
+    }
+    let mut mask = [0u8; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload: payload.into(),
+    }))
+}
+
+/// Writes a frame to a client, unmasked as servers do.
+async fn write_frame<W>(writer: &mut W, frame: &Frame) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut head = Vec::with_capacity(10);
+    head.push((u8::from(frame.fin) << 7) | frame.opcode);
+    match frame.payload.len() {
+        len @ 0..=125 => head.push(len as u8),
+        len @ 126..=0xffff => {
+            head.push(126);
+            head.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            head.push(127);
+            head.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    writer.write_all(&head).await?;
+    writer.write_all(&frame.payload).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+    /// Encodes a frame as a client sends it, masked.
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![(u8::from(fin) << 7) | opcode];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    async fn read(client: &mut tokio::io::DuplexStream, len: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; len];
+        client.read_exact(&mut frame).await.unwrap();
+        frame
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example of RFC 6455
+        assert_eq!(
+            accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiX3fyKpHTBwnPvtB6LE4="
+        );
+    }
+
+    #[test]
+    fn test_handshake() {
+        let request = |version: &str, key: &str| {
+            hyper::Request::get("/chat")
+                .header(CONNECTION, "keep-alive, Upgrade")
+                .header(UPGRADE, "websocket")
+                .header(SEC_WEBSOCKET_VERSION, version)
+                .header(SEC_WEBSOCKET_KEY, key)
+                .body(())
+                .unwrap()
+        };
+        let mut req = request("13", "dGhlIHNhbXBsZSBub25jZQ==");
+        assert!(is_upgrade(req.headers()));
+        let (handshake, _) = Handshake::start(&mut req).unwrap();
+        assert_eq!(handshake.accept, "s3pPLMBiX3fyKpHTBwnPvtB6LE4=");
+
+        assert!(Handshake::start(&mut request("8", "dGhlIHNhbXBsZSBub25jZQ==")).is_err());
+        assert!(Handshake::start(&mut request("13", "c2hvcnQ=")).is_err());
+        let mut post = request("13", "dGhlIHNhbXBsZSBub25jZQ==");
+        *post.method_mut() = hyper::Method::POST;
+        assert!(Handshake::start(&mut post).is_err());
+
+        let plain = hyper::Request::get("/").body(()).unwrap();
+        assert!(!is_upgrade(plain.headers()));
+    }
+
+    #[test]
+    fn test_message_decoder() {
+        let mut decoder = MessageDecoder::default();
+        let messages = [
+            encode_message(MessageKind::Text, b"hello"),
+            encode_message(MessageKind::Binary, &[0, 1, 2]),
+        ]
+        .concat();
+        decoder.push(&messages[..7]);
+        assert_eq!(decoder.next(), Ok(None));
+        decoder.push(&messages[7..]);
+        assert_eq!(
+            decoder.next(),
+            Ok(Some((MessageKind::Text, Bytes::from_static(b"hello"))))
+        );
+        assert_eq!(
+            decoder.next(),
+            Ok(Some((MessageKind::Binary, Bytes::from_static(&[0, 1, 2]))))
+        );
+        assert_eq!(decoder.next(), Ok(None));
+        assert!(decoder.is_empty());
+
+        decoder.push(&[7, 0, 0, 0, 0]);
+        assert!(decoder.next().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let (mut client, server) = tokio::io::duplex(1 << 16);
+        let (inbound, messages) = mpsc::channel(INBOUND_BUFFER);
+        let mut messages = MessageBody(messages);
+        let (outgoing, outbound) = mpsc::channel(INBOUND_BUFFER);
+        let outbound = MessageBody(outbound)
+            .map_err(|_| ErrorCode::InternalError(None))
+            .boxed();
+        let relay = tokio::spawn(relay(server, inbound, outbound));
+
+        // Fragments are reassembled and pings answered
+        let mut frames = client_frame(false, OP_TEXT, b"he");
+        frames.extend(client_frame(true, OP_PING, b"p"));
+        frames.extend(client_frame(true, OP_CONTINUATION, b"llo"));
+        client.write_all(&frames).await.unwrap();
+        assert_eq!(read(&mut client, 3).await, [0x8a, 1, b'p']);
+        let message = messages
+            .frame()
+            .await
+            .unwrap()
+            .unwrap()
+            .into_data()
+            .unwrap();
+        assert_eq!(message, encode_message(MessageKind::Text, b"hello"));
+
+        outgoing
+            .send(encode_message(MessageKind::Binary, &[1, 2]))
+            .await
+            .unwrap();
+        assert_eq!(read(&mut client, 4).await, [0x82, 2, 1, 2]);
+
+        // A close is answered and ends the messages of the client
+        client
+            .write_all(&client_frame(true, OP_CLOSE, &1000u16.to_be_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(read(&mut client, 4).await, [0x88, 2, 0x03, 0xe8]);
+        relay.await.unwrap().unwrap();
+        assert!(messages.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_relay_rejects_unmasked_frames() {
+        let (mut client, server) = tokio::io::duplex(1 << 16);
+        let (inbound, _messages) = mpsc::channel(INBOUND_BUFFER);
+        let (_outgoing, outbound) = mpsc::channel(INBOUND_BUFFER);
+        let outbound = MessageBody(outbound)
+            .map_err(|_| ErrorCode::InternalError(None))
+            .boxed();
+        let relay = tokio::spawn(relay(server, inbound, outbound));
+
+        client.write_all(&[0x81, 2, b'h', b'i']).await.unwrap();
+        let mut close = [0u8; 4];
+        client.read_exact(&mut close).await.unwrap();
+        assert_eq!(close, [0x88, 2, 0x03, 0xea]);
+        relay.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Deduplication of redelivered invocations.
+//!
+//! Clients and brokers retry deliveries they could not confirm, which runs a
+//! non-idempotent component twice. When an invocation carries an idempotency
+//! key, such as the `Idempotency-Key` header of an HTTP request or the
+//! `Nats-Msg-Id` header of a message, the host claims the key in an
+//! [`IdempotencyStore`] before calling the component. A second invocation with
+//! the same key for the same component within the store's time-to-live is then
+//! answered without calling the component again.
+//!
+//! Keys are released when an invocation fails, so a retry after a failure is
+//! delivered normally.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use async_nats::jetstream::kv;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::sync::Mutex;
+
+/// Header carrying the idempotency key of an HTTP request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Header set on responses answered from a previous invocation
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+/// Longest idempotency key accepted. Longer keys are ignored.
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+/// How long keys are remembered by default
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The state of an invocation that claimed an idempotency key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Invocation {
+    /// The component is still handling the invocation
+    InProgress,
+    /// The component handled the invocation, with the HTTP status of its
+    /// response if it was an HTTP request
+    Completed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<u16>,
+    },
+}
+
+/// Remembers which idempotency keys have been seen recently.
+///
+/// Keys are scoped, usually to a workload and component, so the same key sent
+/// to different components is not deduplicated.
+#[async_trait::async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims `key` for a new invocation. Returns `None` if the key was free,
+    /// or the state of the invocation that claimed it before.
+    async fn claim(&self, scope: &str, key: &str) -> anyhow::Result<Option<Invocation>>;
+
+    /// Marks the invocation that claimed `key` as completed.
+    async fn complete(&self, scope: &str, key: &str, status: Option<u16>) -> anyhow::Result<()>;
+
+    /// Releases `key` after a failed invocation so it can be retried.
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()>;
+}
+
+/// Returns the key to deduplicate on from an header value, ignoring empty and
+/// overly long keys.
+pub fn idempotency_key(value: &[u8]) -> Option<&str> {
+    std::str::from_utf8(value)
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+}
+
+/// Hashes a scoped key into a fixed-length key that is valid in any store.
+fn storage_key(scope: &str, key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_bytes());
+    hasher.update([0]);
+    hasher.update(key.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// An [`IdempotencyStore`] kept in host memory. Keys are not shared between
+/// hosts and are lost on restart.
+pub struct MemoryIdempotencyStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, Invocation)>>,
+}
+
+impl MemoryIdempotencyStore {
+    /// Creates a store that remembers keys for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+}
+
+impl Default for MemoryIdempotencyStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_TTL)
+    }
+}
+
+#[async_trait::async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn claim(&self, scope: &str, key: &str) -> anyhow::Result<Option<Invocation>> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().await;
+        entries.retain(|_, (updated, _)| now.duration_since(*updated) < self.ttl);
+        match entries.entry(storage_key(scope, key)) {
+            std::collections::hash_map::Entry::Occupied(entry) => Ok(Some(entry.get().1)),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert((now, Invocation::InProgress));
+                Ok(None)
+            }
+        }
+    }
+
+    async fn complete(&self, scope: &str, key: &str, status: Option<u16>) -> anyhow::Result<()> {
+        self.entries.lock().await.insert(
+            storage_key(scope, key),
+            (Instant::now(), Invocation::Completed { status }),
+        );
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+        self.entries.lock().await.remove(&storage_key(scope, key));
+        Ok(())
+    }
+}
+
+/// An [`IdempotencyStore`] backed by a NATS JetStream key-value bucket, so
+/// keys are deduplicated across all hosts sharing the bucket. Keys expire with
+/// the bucket's maximum age.
+pub struct KeyvalueIdempotencyStore {
+    kv: kv::Store,
+}
+
+impl KeyvalueIdempotencyStore {
+    /// Opens `bucket`, creating it with a maximum age of `ttl` if needed.
+    pub async fn new(
+        client: Arc<async_nats::Client>,
+        bucket: impl Into<String>,
+        ttl: Duration,
+    ) -> anyhow::Result<Self> {
+        let bucket = bucket.into();
+        let kv = async_nats::jetstream::new((*client).clone())
+            .create_key_value(kv::Config {
+                bucket: bucket.clone(),
+                max_age: ttl,
+                history: 1,
+                ..Default::default()
+            })
+            .await
+            .with_context(|| format!("failed to open idempotency bucket '{bucket}'"))?;
+        Ok(Self { kv })
+    }
+}
+
+#[async_trait::async_trait]
+impl IdempotencyStore for KeyvalueIdempotencyStore {
+    async fn claim(&self, scope: &str, key: &str) -> anyhow::Result<Option<Invocation>> {
+        let key = storage_key(scope, key);
+        let claimed = serde_json::to_vec(&Invocation::InProgress)?;
+        // The key may expire between a failed create and the read, so try twice
+        for _ in 0..2 {
+            match self.kv.create(&key, claimed.clone().into()).await {
+                Ok(_) => return Ok(None),
+                Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e).context("failed to claim idempotency key"),
+            }
+            if let Some(value) = self
+                .kv
+                .get(&key)
+                .await
+                .context("failed to read idempotency key")?
+            {
+                return serde_json::from_slice(&value)
+                    .map(Some)
+                    .context("malformed idempotency record");
+            }
+        }
+        // Claimed and released concurrently; treat it as still in progress
+        Ok(Some(Invocation::InProgress))
+    }
+
+    async fn complete(&self, scope: &str, key: &str, status: Option<u16>) -> anyhow::Result<()> {
+        let value = serde_json::to_vec(&Invocation::Completed { status })?;
+        self.kv
+            .put(storage_key(scope, key), value.into())
+            .await
+            .context("failed to complete idempotency key")?;
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+        self.kv
+            .delete(storage_key(scope, key))
+            .await
+            .context("failed to release idempotency key")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryIdempotencyStore::default();
+        assert_eq!(store.claim("a", "key").await.unwrap(), None);
+        assert_eq!(
+            store.claim("a", "key").await.unwrap(),
+            Some(Invocation::InProgress)
+        );
+        // Keys are scoped
+        assert_eq!(store.claim("b", "key").await.unwrap(), None);
+
+        store.complete("a", "key", Some(201)).await.unwrap();
+        assert_eq!(
+            store.claim("a", "key").await.unwrap(),
+            Some(Invocation::Completed { status: Some(201) })
+        );
+
+        store.release("b", "key").await.unwrap();
+        assert_eq!(store.claim("b", "key").await.unwrap(), None);
+
+        let expiring = MemoryIdempotencyStore::new(Duration::ZERO);
+        assert_eq!(expiring.claim("a", "key").await.unwrap(), None);
+        assert_eq!(expiring.claim("a", "key").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        assert_eq!(idempotency_key(b" abc "), Some("abc"));
+        assert_eq!(idempotency_key(b""), None);
+        assert_eq!(idempotency_key(&[0xff]), None);
+        assert_eq!(idempotency_key(&[b'a'; MAX_IDEMPOTENCY_KEY_LEN + 1]), None);
+        assert_ne!(storage_key("a", "bc"), storage_key("ab", "c"));
+    }
+}
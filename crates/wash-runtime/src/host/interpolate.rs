@@ -0,0 +1,496 @@
+//! Interpolation of `${env:NAME}` and `${secret:NAME}` references in workload
+//! configuration.
+//!
+//! Values in [`LocalResources::config`] and [`LocalResources::environment`] may
+//! reference environment variables of the host process or secrets from the
+//! host's [`SecretStore`]. References are resolved once, when the workload is
+//! started, so a single manifest can be parameterized per environment:
+//!
+//! ```text
+//! config:
+//!   database_url: postgres://${env:DB_HOST}:5432/app
+//!   api_token: ${secret:api-token}
+//! ```
+//!
+//! Only the environment variables allowed by the host's [`EnvAllowlist`] can be
+//! referenced, so a workload can't read credentials the host process was
+//! started with. The allowlist is empty unless the host configures one.
+//!
+//! A literal `${` can be written as `$${`. Any reference that cannot be
+//! resolved fails the workload start with [`HostError::InvalidWorkload`].
+//!
+//! References in the config of an interface are left in place and resolved by
+//! the plugin binding it with [`interpolate_config`], which keeps secrets out
+//! of the workload.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Context as _, bail};
+
+use crate::host::HostError;
+use crate::types::{LocalResources, Workload};
+
+/// A source of secret values that can be referenced from workload configuration
+/// with `${secret:NAME}`.
+#[async_trait::async_trait]
+pub trait SecretStore: Send + Sync + 'static {
+    /// Returns the value of the secret `name`, or `None` if it does not exist.
+    async fn get(&self, name: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// A static, in-memory secret store, mostly useful for development and tests.
+#[async_trait::async_trait]
+impl SecretStore for HashMap<String, String> {
+    async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        Ok(HashMap::get(self, name).cloned())
+    }
+}
+
+/// The environment variables of the host process that workloads may reference
+/// with `${env:NAME}`.
+///
+/// A pattern is either the exact name of a variable or a prefix ending in `*`,
+/// e.g. `APP_*`. An empty allowlist, the default, rejects every reference.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnvAllowlist {
+    names: BTreeSet<String>,
+    prefixes: BTreeSet<String>,
+}
+
+impl EnvAllowlist {
+    /// Returns an allowlist of `patterns`.
+    pub fn new(patterns: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        patterns
+            .into_iter()
+            .fold(Self::default(), |allowlist, pattern| {
+                allowlist.with_pattern(pattern.as_ref())
+            })
+    }
+
+    /// Allows the variables matching `pattern`, see [`EnvAllowlist`].
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        match pattern.strip_suffix('*') {
+            Some(prefix) => self.prefixes.insert(prefix.to_string()),
+            None => self.names.insert(pattern.to_string()),
+        };
+        self
+    }
+
+    /// Returns whether `name` may be referenced.
+    pub fn allows(&self, name: &str) -> bool {
+        self.names.contains(name)
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+
+    /// Returns the value of the environment variable `name` of the host
+    /// process.
+    ///
+    /// # Errors
+    /// Returns an error if `name` isn't allowed or isn't set.
+    pub fn var(&self, name: &str) -> anyhow::Result<String> {
+        if !self.allows(name) {
+            bail!(
+                "environment variable '{name}' is not in the host's allowlist of variables \
+                 workloads may reference"
+            );
+        }
+        std::env::var(name).with_context(|| format!("environment variable '{name}' is not set"))
+    }
+}
+
+/// Where the value of a [`Reference`] comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReferenceSource {
+    /// An environment variable of the host process, `${env:NAME}`
+    Env,
+    /// A secret from the host's [`SecretStore`], `${secret:NAME}`
+    Secret,
+}
+
+/// A single `${source:name}` reference inside a configuration value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Reference {
+    pub source: ReferenceSource,
+    pub name: String,
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            ReferenceSource::Env => write!(f, "${{env:{}}}", self.name),
+            ReferenceSource::Secret => write!(f, "${{secret:{}}}", self.name),
+        }
+    }
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Reference(Reference),
+}
+
+fn parse(value: &str) -> anyhow::Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        let after = &rest[start + 1..];
+        if after.starts_with("${") {
+            // `$${` is an escaped, literal `${`
+            segments.push(Segment::Literal(&rest[..start + 1]));
+            segments.push(Segment::Literal("{"));
+            rest = &after[2..];
+        } else if let Some(body) = after.strip_prefix('{') {
+            segments.push(Segment::Literal(&rest[..start]));
+            let end = body
+                .find('}')
+                .with_context(|| format!("unterminated reference '{}'", &rest[start..]))?;
+            let inner = &body[..end];
+            let (source, name) = inner.split_once(':').with_context(|| {
+                format!(
+                    "reference '${{{inner}}}' must have the form ${{env:NAME}} or ${{secret:NAME}}"
+                )
+            })?;
+            let source = match source {
+                "env" => ReferenceSource::Env,
+                "secret" => ReferenceSource::Secret,
+                other => bail!(
+                    "unsupported reference source '{other}' in '${{{inner}}}', expected 'env' or 'secret'"
+                ),
+            };
+            if name.is_empty() {
+                bail!("reference '${{{inner}}}' is missing a name");
+            }
+            segments.push(Segment::Reference(Reference {
+                source,
+                name: name.to_string(),
+            }));
+            rest = &body[end + 1..];
+        } else {
+            segments.push(Segment::Literal(&rest[..start + 1]));
+            rest = after;
+        }
+    }
+    segments.push(Segment::Literal(rest));
+    Ok(segments)
+}
+
+/// Returns all references contained in `value`.
+///
+/// # Errors
+/// Returns an error if `value` contains a malformed reference.
+pub fn references(value: &str) -> anyhow::Result<Vec<Reference>> {
+    Ok(parse(value)?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Reference(reference) => Some(reference),
+            Segment::Literal(_) => None,
+        })
+        .collect())
+}
+
+/// Replaces every reference in `value` with the value returned by `resolve`.
+///
+/// # Errors
+/// Returns an error if `value` contains a malformed reference or if `resolve`
+/// fails for any reference.
+pub fn interpolate(
+    value: &str,
+    mut resolve: impl FnMut(&Reference) -> anyhow::Result<String>,
+) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(value.len());
+    for segment in parse(value)? {
+        match segment {
+            Segment::Literal(literal) => out.push_str(literal),
+            Segment::Reference(reference) => out.push_str(&resolve(&reference)?),
+        }
+    }
+    Ok(out)
+}
+
+/// Resolves all references in the config and environment of every service and
+/// component in `workload`, in place.
+///
+/// Environment references are read from the host process environment, if `env`
+/// allows them, and secret references from `secrets`.
+///
+/// # Errors
+/// Returns [`HostError::InvalidWorkload`] naming the offending key if a value is
+/// malformed or a reference cannot be resolved.
+pub async fn interpolate_workload(
+    workload: &mut Workload,
+    env: &EnvAllowlist,
+    secrets: Option<&dyn SecretStore>,
+) -> Result<(), HostError> {
+    let mut targets: Vec<(String, &mut LocalResources)> = Vec::new();
+    if let Some(service) = workload.service.as_mut() {
+        targets.push(("service".to_string(), &mut service.local_resources));
+    }
+    for (i, component) in workload.components.iter_mut().enumerate() {
+        targets.push((format!("component {i}"), &mut component.local_resources));
+    }
+
+    // Secrets are fetched up front so the substitution itself stays synchronous
+    let mut secret_values: HashMap<String, String> = HashMap::new();
+    for (owner, resources) in &targets {
+        for (kind, key, value) in entries(resources) {
+            let references = references(value).map_err(|e| invalid(owner, kind, key, e))?;
+            for reference in references {
+                if reference.source != ReferenceSource::Secret
+                    || secret_values.contains_key(&reference.name)
+                {
+                    continue;
+                }
+                let secret = lookup_secret(secrets, &reference.name)
+                    .await
+                    .map_err(|e| invalid(owner, kind, key, e))?;
+                secret_values.insert(reference.name, secret);
+            }
+        }
+    }
+
+    for (owner, resources) in targets {
+        for (kind, map) in [
+            ("config", &mut resources.config),
+            ("environment", &mut resources.environment),
+        ] {
+            for (key, value) in map.iter_mut() {
+                *value = interpolate(value, |reference| match reference.source {
+                    ReferenceSource::Env => env.var(&reference.name),
+                    ReferenceSource::Secret => secret_values
+                        .get(&reference.name)
+                        .cloned()
+                        .with_context(|| format!("secret '{}' was not resolved", reference.name)),
+                })
+                .map_err(|e| invalid(&owner, kind, key, e))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves all references in the values of `config`, e.g. the config of an
+/// interface, returning the resolved copy so `config` itself keeps the
+/// references.
+///
+/// # Errors
+/// Returns an error naming the offending key if a value is malformed or a
+/// reference cannot be resolved.
+pub async fn interpolate_config(
+    config: &HashMap<String, String>,
+    env: &EnvAllowlist,
+    secrets: Option<&dyn SecretStore>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut secret_values: HashMap<String, String> = HashMap::new();
+    for (key, value) in config {
+        for reference in references(value).with_context(|| format!("config key '{key}'"))? {
+            if reference.source == ReferenceSource::Secret
+                && !secret_values.contains_key(&reference.name)
+            {
+                let secret = lookup_secret(secrets, &reference.name)
+                    .await
+                    .with_context(|| format!("config key '{key}'"))?;
+                secret_values.insert(reference.name, secret);
+            }
+        }
+    }
+    config
+        .iter()
+        .map(|(key, value)| {
+            let value = interpolate(value, |reference| match reference.source {
+                ReferenceSource::Env => env.var(&reference.name),
+                ReferenceSource::Secret => Ok(secret_values[&reference.name].clone()),
+            })
+            .with_context(|| format!("config key '{key}'"))?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+fn entries(resources: &LocalResources) -> impl Iterator<Item = (&'static str, &String, &String)> {
+    resources
+        .config
+        .iter()
+        .map(|(k, v)| ("config", k, v))
+        .chain(
+            resources
+                .environment
+                .iter()
+                .map(|(k, v)| ("environment", k, v)),
+        )
+}
+
+pub(crate) async fn lookup_secret(
+    secrets: Option<&dyn SecretStore>,
+    name: &str,
+) -> anyhow::Result<String> {
+    let Some(secrets) = secrets else {
+        bail!("secret '{name}' is referenced but no secret store is configured on this host");
+    };
+    secrets
+        .get(name)
+        .await
+        .with_context(|| format!("failed to fetch secret '{name}'"))?
+        .with_context(|| format!("secret '{name}' does not exist"))
+}
+
+fn invalid(owner: &str, kind: &str, key: &str, e: anyhow::Error) -> HostError {
+    HostError::InvalidWorkload(format!("{owner} {kind} key '{key}': {e:#}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Component, Service};
+
+    fn resolve(reference: &Reference) -> anyhow::Result<String> {
+        match (reference.source, reference.name.as_str()) {
+            (ReferenceSource::Env, "HOST") => Ok("db.internal".to_string()),
+            (ReferenceSource::Secret, "token") => Ok("s3cr3t".to_string()),
+            _ => bail!("{reference} not found"),
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate("postgres://${env:HOST}:5432", resolve).unwrap(),
+            "postgres://db.internal:5432"
+        );
+        assert_eq!(
+            interpolate("${secret:token}${env:HOST}", resolve).unwrap(),
+            "s3cr3tdb.internal"
+        );
+        assert_eq!(
+            interpolate("plain $value", resolve).unwrap(),
+            "plain $value"
+        );
+        assert_eq!(interpolate("$${env:HOST}", resolve).unwrap(), "${env:HOST}");
+        assert_eq!(interpolate("cost: 5$", resolve).unwrap(), "cost: 5$");
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        let err = interpolate("${env:HOST", resolve).unwrap_err();
+        assert!(err.to_string().contains("unterminated"), "{err}");
+        let err = interpolate("${vault:HOST}", resolve).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unsupported reference source 'vault'")
+        );
+        let err = interpolate("${HOST}", resolve).unwrap_err();
+        assert!(err.to_string().contains("must have the form"));
+        let err = interpolate("${env:}", resolve).unwrap_err();
+        assert!(err.to_string().contains("missing a name"));
+        let err = interpolate("${env:MISSING}", resolve).unwrap_err();
+        assert!(err.to_string().contains("${env:MISSING} not found"));
+    }
+
+    #[tokio::test]
+    async fn test_interpolate_workload_secrets() {
+        let mut workload = Workload::builder("default", "app")
+            .with_service(Service::new(&b""[..]).with_local_resources(LocalResources {
+                environment: HashMap::from([(
+                    "TOKEN".to_string(),
+                    "Bearer ${secret:token}".to_string(),
+                )]),
+                ..Default::default()
+            }))
+            .with_component(Component::new(&b""[..]).with_config("token", "${secret:token}"))
+            .build();
+        let secrets = HashMap::from([("token".to_string(), "s3cr3t".to_string())]);
+
+        interpolate_workload(&mut workload, &EnvAllowlist::default(), Some(&secrets))
+            .await
+            .expect("should interpolate");
+        assert_eq!(
+            workload.service.unwrap().local_resources.environment["TOKEN"],
+            "Bearer s3cr3t"
+        );
+        assert_eq!(
+            workload.components[0].local_resources.config["token"],
+            "s3cr3t"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interpolate_config() {
+        let config = HashMap::from([
+            ("password".to_string(), "${secret:db}".to_string()),
+            ("user".to_string(), "app".to_string()),
+        ]);
+        let secrets = HashMap::from([("db".to_string(), "hunter2".to_string())]);
+
+        let resolved = interpolate_config(&config, &EnvAllowlist::default(), Some(&secrets))
+            .await
+            .unwrap();
+        assert_eq!(resolved["password"], "hunter2");
+        assert_eq!(resolved["user"], "app");
+        assert_eq!(config["password"], "${secret:db}");
+
+        let err = interpolate_config(&config, &EnvAllowlist::default(), None)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("config key 'password': secret 'db' is referenced"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interpolate_workload_errors() {
+        let mut workload = Workload::builder("default", "app")
+            .with_component(Component::new(&b""[..]).with_config("token", "${secret:token}"))
+            .build();
+        let err = interpolate_workload(&mut workload, &EnvAllowlist::default(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HostError::InvalidWorkload(_)));
+        assert!(
+            err.to_string()
+                .contains("component 0 config key 'token': secret 'token' is referenced but no secret store is configured"),
+            "{err}"
+        );
+
+        let secrets: HashMap<String, String> = HashMap::new();
+        let err = interpolate_workload(&mut workload, &EnvAllowlist::default(), Some(&secrets))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("secret 'token' does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_interpolate_env_allowlist() {
+        let env = EnvAllowlist::new(["PATH", "CARGO_PKG_*"]);
+        assert!(env.allows("PATH"));
+        assert!(env.allows("CARGO_PKG_NAME"));
+        assert!(!env.allows("PATHEXT"));
+        assert!(!env.allows("HOME"));
+
+        let config = HashMap::from([("path".to_string(), "${env:PATH}".to_string())]);
+        let resolved = interpolate_config(&config, &env, None).await.unwrap();
+        assert_eq!(resolved["path"], std::env::var("PATH").unwrap());
+
+        let config = HashMap::from([("home".to_string(), "${env:HOME}".to_string())]);
+        let err = interpolate_config(&config, &env, None).await.unwrap_err();
+        assert!(
+            format!("{err:#}")
+                .contains("environment variable 'HOME' is not in the host's allowlist"),
+            "{err:#}"
+        );
+
+        let mut workload = Workload::builder("default", "app")
+            .with_component(Component::new(&b""[..]).with_config("home", "${env:HOME}"))
+            .build();
+        let err = interpolate_workload(&mut workload, &EnvAllowlist::default(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HostError::InvalidWorkload(_)));
+        assert!(
+            err.to_string().contains("not in the host's allowlist"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,543 @@
+//! Limits on what a host accepts.
+//!
+//! Limits are set with [`HostBuilder::with_limits`](super::HostBuilder::with_limits)
+//! and can be changed while the host runs with
+//! [`Host::set_limits`](super::Host::set_limits), e.g. when its configuration
+//! is reloaded. Lowering a limit doesn't stop workloads that already run, it
+//! only rejects new ones until the host is below the limit again.
+//!
+//! A [`Quota`] caps workloads, component instances, guest memory and open
+//! connections, either for the whole host or for a single namespace. A
+//! workload reserves one instance per warm instance of its pool, at least one
+//! per component and service, plus its pre-initialized instances, see
+//! [`crate::engine::preinit`], and the memory limit of each instance. Under a
+//! memory quota, workloads with components without a memory limit are
+//! rejected. Workloads that exceed a quota fail to start with
+//! [`HostError::QuotaExceeded`].
+//!
+//! Connections are the connections open to the HTTP server of the host. For a
+//! namespace, they are the requests its workloads handle at once. Connections
+//! and requests beyond the limit are refused.
+//!
+//! The remaining headroom of every limited quota is recorded as
+//! `wasmcloud.host.headroom.*` gauges on every heartbeat, see [`super::metrics`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use super::{HostError, HostResult};
+use crate::types::Workload;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Caps on the workloads of a host or a namespace. Every cap is unlimited if
+/// `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    /// Most workloads, in any state
+    max_workloads: Option<usize>,
+    /// Most component instances reserved by workloads
+    max_instances: Option<usize>,
+    /// Most guest memory reserved by workloads, in MiB
+    max_memory_mb: Option<u64>,
+    /// Most open connections
+    max_connections: Option<usize>,
+}
+
+impl Quota {
+    pub fn with_max_workloads(mut self, max_workloads: usize) -> Self {
+        self.max_workloads = Some(max_workloads);
+        self
+    }
+
+    pub fn with_max_instances(mut self, max_instances: usize) -> Self {
+        self.max_instances = Some(max_instances);
+        self
+    }
+
+    pub fn with_max_memory_mb(mut self, max_memory_mb: u64) -> Self {
+        self.max_memory_mb = Some(max_memory_mb);
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    pub fn max_workloads(&self) -> Option<usize> {
+        self.max_workloads
+    }
+
+    pub fn max_instances(&self) -> Option<usize> {
+        self.max_instances
+    }
+
+    pub fn max_memory_mb(&self) -> Option<u64> {
+        self.max_memory_mb
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// Returns whether any cap is set.
+    pub fn is_limited(&self) -> bool {
+        *self != Quota::default()
+    }
+
+    /// Checks that a workload reserving `requested` fits next to `used`.
+    fn check(&self, scope: &str, used: &Usage, requested: &Usage) -> HostResult<()> {
+        let exceeded = |what: &str, used: u64, requested: u64, max: u64| {
+            Err(HostError::QuotaExceeded(format!(
+                "{scope} allows {max} {what}, {used} are in use and the workload needs {requested}"
+            )))
+        };
+        if let Some(max) = self.max_workloads
+            && used.workloads + requested.workloads > max
+        {
+            return exceeded(
+                "workloads",
+                used.workloads as u64,
+                requested.workloads as u64,
+                max as u64,
+            );
+        }
+        if let Some(max) = self.max_instances
+            && used.instances + requested.instances > max
+        {
+            return exceeded(
+                "instances",
+                used.instances as u64,
+                requested.instances as u64,
+                max as u64,
+            );
+        }
+        if let Some(max) = self.max_memory_mb {
+            if requested.unbounded_instances > 0 {
+                return Err(HostError::QuotaExceeded(format!(
+                    "{scope} limits guest memory, set memory_limit_mb on every component of the workload"
+                )));
+            }
+            if used.unbounded_instances > 0
+                || used.memory_bytes + requested.memory_bytes > max * MIB
+            {
+                return exceeded(
+                    "MiB of guest memory",
+                    used.memory_bytes.div_ceil(MIB),
+                    requested.memory_bytes.div_ceil(MIB),
+                    max,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns what is left of this quota after `used`.
+    pub fn headroom(&self, used: &Usage) -> Headroom {
+        Headroom {
+            workloads: self
+                .max_workloads
+                .map(|max| max.saturating_sub(used.workloads)),
+            instances: self
+                .max_instances
+                .map(|max| max.saturating_sub(used.instances)),
+            memory_bytes: self.max_memory_mb.map(|max| {
+                if used.unbounded_instances > 0 {
+                    0
+                } else {
+                    (max * MIB).saturating_sub(used.memory_bytes)
+                }
+            }),
+            connections: self
+                .max_connections
+                .map(|max| max.saturating_sub(used.connections)),
+        }
+    }
+}
+
+/// Caps on the workloads a host runs, host-wide and by namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostLimits {
+    #[serde(flatten)]
+    host: Quota,
+    /// Quotas of namespaces, on top of the host quota
+    namespaces: BTreeMap<String, Quota>,
+}
+
+impl HostLimits {
+    pub fn with_max_workloads(mut self, max_workloads: usize) -> Self {
+        self.host = self.host.with_max_workloads(max_workloads);
+        self
+    }
+
+    pub fn with_max_instances(mut self, max_instances: usize) -> Self {
+        self.host = self.host.with_max_instances(max_instances);
+        self
+    }
+
+    pub fn with_max_memory_mb(mut self, max_memory_mb: u64) -> Self {
+        self.host = self.host.with_max_memory_mb(max_memory_mb);
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.host = self.host.with_max_connections(max_connections);
+        self
+    }
+
+    /// Sets the quota of the workloads in `namespace`.
+    pub fn with_namespace_quota(mut self, namespace: impl Into<String>, quota: Quota) -> Self {
+        self.namespaces.insert(namespace.into(), quota);
+        self
+    }
+
+    pub fn max_workloads(&self) -> Option<usize> {
+        self.host.max_workloads
+    }
+
+    /// Returns the host-wide quota.
+    pub fn host(&self) -> &Quota {
+        &self.host
+    }
+
+    /// Returns the quota of `namespace`, if it has one.
+    pub fn namespace(&self, namespace: &str) -> Option<&Quota> {
+        self.namespaces.get(namespace)
+    }
+
+    /// Returns the quotas of namespaces.
+    pub fn namespaces(&self) -> impl Iterator<Item = (&str, &Quota)> {
+        self.namespaces
+            .iter()
+            .map(|(ns, quota)| (ns.as_str(), quota))
+    }
+
+    /// Checks that a workload in `namespace` reserving `requested` fits next
+    /// to the workloads the host already runs.
+    pub(crate) fn check(
+        &self,
+        namespace: &str,
+        requested: &Usage,
+        reservations: &Reservations,
+    ) -> HostResult<()> {
+        self.host
+            .check("host", &reservations.usage(None), requested)?;
+        if let Some(quota) = self.namespaces.get(namespace) {
+            quota.check(
+                &format!("namespace {namespace}"),
+                &reservations.usage(Some(namespace)),
+                requested,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Resources reserved by workloads, or open connections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub workloads: usize,
+    pub instances: usize,
+    /// Guest memory reserved by instances with a memory limit
+    pub memory_bytes: u64,
+    /// Instances without a memory limit
+    pub unbounded_instances: usize,
+    pub connections: usize,
+}
+
+impl Usage {
+    /// Returns what `workload` reserves when every linear memory is capped to
+    /// `max_memory_bytes` by the engine.
+    pub(crate) fn of_workload(workload: &Workload, max_memory_bytes: Option<usize>) -> Self {
+        let mut usage = Usage {
+            workloads: 1,
+            ..Default::default()
+        };
+        let mut reserve = |instances: usize, memory_limit_mb: i32| {
+            usage.instances += instances;
+            match crate::engine::workload::memory_limit(memory_limit_mb, max_memory_bytes) {
+                Some(bytes) => usage.memory_bytes += bytes as u64 * instances as u64,
+                None => usage.unbounded_instances += instances,
+            }
+        };
+        if let Some(service) = &workload.service {
+            reserve(1, service.local_resources.memory_limit_mb);
+        }
+        for component in &workload.components {
+            let instances = match &component.autoscaling {
+                Some(autoscaling) => autoscaling.max_instances as usize,
+                None => usize::try_from(component.pool_size).unwrap_or(0),
+            }
+            .max(1)
+                // Pre-initialized instances hold their memory while they wait
+                + component
+                    .pre_init
+                    .as_ref()
+                    .map_or(0, |pre_init| pre_init.ready as usize);
+            reserve(instances, component.local_resources.memory_limit_mb);
+        }
+        usage
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.workloads += other.workloads;
+        self.instances += other.instances;
+        self.memory_bytes += other.memory_bytes;
+        self.unbounded_instances += other.unbounded_instances;
+        self.connections += other.connections;
+    }
+}
+
+/// What remains of a [`Quota`]. Unlimited caps are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headroom {
+    pub workloads: Option<usize>,
+    pub instances: Option<usize>,
+    pub memory_bytes: Option<u64>,
+    pub connections: Option<usize>,
+}
+
+/// The resources reserved by the workloads of a host, by workload ID.
+#[derive(Debug, Default)]
+pub(crate) struct Reservations(HashMap<String, (String, Usage)>);
+
+impl Reservations {
+    pub(crate) fn reserve(&mut self, workload_id: &str, namespace: &str, usage: Usage) {
+        self.0
+            .insert(workload_id.to_string(), (namespace.to_string(), usage));
+    }
+
+    pub(crate) fn release(&mut self, workload_id: &str) {
+        self.0.remove(workload_id);
+    }
+
+    /// Returns the resources reserved by the workloads of `namespace`, or of
+    /// all workloads if `None`.
+    pub(crate) fn usage(&self, namespace: Option<&str>) -> Usage {
+        let mut total = Usage::default();
+        for (ns, usage) in self.0.values() {
+            if namespace.is_none_or(|namespace| namespace == ns) {
+                total.add(usage);
+            }
+        }
+        total
+    }
+}
+
+/// Counts the open connections of an HTTP server and refuses connections
+/// beyond the limits of the host.
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    limits: std::sync::RwLock<HostLimits>,
+    open: Arc<AtomicUsize>,
+    by_namespace: Arc<std::sync::Mutex<HashMap<String, usize>>>,
+}
+
+impl ConnectionTracker {
+    /// Replaces the limits connections are checked against.
+    pub fn set_limits(&self, limits: &HostLimits) {
+        *self
+            .limits
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = limits.clone();
+    }
+
+    /// Returns the open connections in total and by namespace.
+    pub fn open(&self) -> (usize, HashMap<String, usize>) {
+        (
+            self.open.load(Ordering::Relaxed),
+            self.by_namespace
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .clone(),
+        )
+    }
+
+    /// Opens a connection to the host, or returns `None` if the host has as
+    /// many open connections as it allows. The connection closes when the
+    /// guard is dropped.
+    pub fn open_connection(&self) -> Option<ConnectionGuard> {
+        let max = self
+            .limits
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .host
+            .max_connections
+            .unwrap_or(usize::MAX);
+        self.open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                (open < max).then_some(open + 1)
+            })
+            .ok()?;
+        Some(ConnectionGuard::Host(self.open.clone()))
+    }
+
+    /// Opens a request to a workload of `namespace`, or returns `None` if the
+    /// namespace handles as many requests as its quota allows.
+    pub fn open_request(&self, namespace: &str) -> Option<ConnectionGuard> {
+        let max = self
+            .limits
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .namespace(namespace)
+            .and_then(Quota::max_connections)
+            .unwrap_or(usize::MAX);
+        let mut by_namespace = self
+            .by_namespace
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let open = by_namespace.entry(namespace.to_string()).or_default();
+        if *open >= max {
+            return None;
+        }
+        *open += 1;
+        Some(ConnectionGuard::Namespace(
+            self.by_namespace.clone(),
+            namespace.to_string(),
+        ))
+    }
+}
+
+/// An open connection or request counted by a [`ConnectionTracker`].
+pub enum ConnectionGuard {
+    Host(Arc<AtomicUsize>),
+    Namespace(Arc<std::sync::Mutex<HashMap<String, usize>>>, String),
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        match self {
+            ConnectionGuard::Host(open) => {
+                open.fetch_sub(1, Ordering::AcqRel);
+            }
+            ConnectionGuard::Namespace(by_namespace, namespace) => {
+                let mut by_namespace = by_namespace
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                if let Some(open) = by_namespace.get_mut(namespace.as_str()) {
+                    *open -= 1;
+                    if *open == 0 {
+                        by_namespace.remove(namespace.as_str());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Component, LocalResources};
+
+    fn workload(namespace: &str, pool_size: i32, memory_limit_mb: i32) -> Workload {
+        Workload::builder(namespace, "app")
+            .with_component(
+                Component::new(&b"\0asm"[..])
+                    .with_pool_size(pool_size)
+                    .with_local_resources(LocalResources {
+                        memory_limit_mb,
+                        ..Default::default()
+                    }),
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_check() {
+        let mut reservations = Reservations::default();
+        assert!(
+            HostLimits::default()
+                .check(
+                    "a",
+                    &Usage::of_workload(&workload("a", 1, -1), None),
+                    &reservations
+                )
+                .is_ok()
+        );
+
+        let limits = HostLimits::default()
+            .with_max_workloads(2)
+            .with_max_memory_mb(512)
+            .with_namespace_quota("a", Quota::default().with_max_instances(4));
+
+        let usage = Usage::of_workload(&workload("a", 3, 64), None);
+        assert_eq!(usage.instances, 3);
+        assert_eq!(usage.memory_bytes, 192 * MIB);
+        // Autoscaling components reserve their maximum
+        let mut autoscaled = workload("a", 1, 64);
+        autoscaled.components[0].autoscaling = Some(crate::types::Autoscaling {
+            min_instances: 1,
+            max_instances: 5,
+            ..Default::default()
+        });
+        assert_eq!(Usage::of_workload(&autoscaled, None).instances, 5);
+        assert!(limits.check("a", &usage, &reservations).is_ok());
+        reservations.reserve("w1", "a", usage);
+
+        // Only one instance is left in namespace a
+        let usage = Usage::of_workload(&workload("a", 2, 64), None);
+        assert!(matches!(
+            limits.check("a", &usage, &reservations),
+            Err(HostError::QuotaExceeded(_))
+        ));
+        // Components need a memory limit under a memory quota, unless the
+        // engine caps memory
+        let usage = Usage::of_workload(&workload("b", 1, -1), None);
+        assert!(limits.check("b", &usage, &reservations).is_err());
+        let usage = Usage::of_workload(&workload("b", 1, -1), Some(128 * MIB as usize));
+        assert!(limits.check("b", &usage, &reservations).is_ok());
+        reservations.reserve("w2", "b", usage);
+
+        // The host runs as many workloads as it allows
+        let usage = Usage::of_workload(&workload("c", 1, 1), None);
+        assert!(limits.check("c", &usage, &reservations).is_err());
+        reservations.release("w2");
+        assert!(limits.check("c", &usage, &reservations).is_ok());
+
+        let used = reservations.usage(Some("a"));
+        assert_eq!(
+            limits.namespace("a").unwrap().headroom(&used),
+            Headroom {
+                instances: Some(1),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            limits
+                .host()
+                .headroom(&reservations.usage(None))
+                .memory_bytes,
+            Some(320 * MIB)
+        );
+    }
+
+    #[test]
+    fn test_connection_tracker() {
+        let tracker = ConnectionTracker::default();
+        tracker.set_limits(
+            &HostLimits::default()
+                .with_max_connections(1)
+                .with_namespace_quota("a", Quota::default().with_max_connections(1)),
+        );
+
+        let connection = tracker.open_connection().unwrap();
+        assert!(tracker.open_connection().is_none());
+        drop(connection);
+        assert!(tracker.open_connection().is_some());
+
+        let request = tracker.open_request("a").unwrap();
+        assert!(tracker.open_request("a").is_none());
+        let other = tracker.open_request("b").unwrap();
+        assert_eq!(tracker.open().1["a"], 1);
+        drop(request);
+        drop(other);
+        assert!(tracker.open().1.is_empty());
+    }
+}
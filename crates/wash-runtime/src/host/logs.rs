@@ -0,0 +1,471 @@
+//! Capturing what components log through `wasi:logging`.
+//!
+//! Every line a component logs is tagged with the ID, name and namespace of
+//! its workload and the ID of the component, and then
+//!
+//! - dropped if it is below the log level of its workload,
+//! - emitted as a `tracing` event with those tags as fields,
+//! - kept in a ring buffer of the host holding the most recent lines of all
+//!   workloads, and
+//! - sent to the clients following the logs of its workload.
+//!
+//! The log level of a workload is set in the config of its
+//! `wasi:logging/logging` host interface, and defaults to `trace`, so every
+//! line is captured:
+//!
+//! ```yaml
+//! hostInterfaces:
+//!   - namespace: wasi
+//!     package: logging
+//!     interfaces: [logging]
+//!     config:
+//!       level: info
+//! ```
+//!
+//! Tooling reads the buffered lines of a workload and follows new ones with
+//! [`HostApi::workload_logs`](super::HostApi::workload_logs), served as the
+//! `workload.logs` API command. Lines of stopped workloads stay in the buffer
+//! until newer lines push them out, so the last words of a crashed workload
+//! can still be read.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::SystemTime;
+
+use tokio::sync::broadcast;
+
+/// Lines the host keeps if not configured
+pub const DEFAULT_CAPACITY: usize = 10_000;
+/// Config key of the `wasi:logging/logging` interface for the log level of
+/// a workload
+pub const LEVEL_CONFIG_KEY: &str = "level";
+
+/// Lines a slow follower may fall behind by before it misses some
+const FOLLOW_BUFFER: usize = 1024;
+
+/// The level of a log line, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum LogLevel {
+    #[default]
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+        }
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "critical" => Ok(LogLevel::Critical),
+            _ => anyhow::bail!(
+                "unknown log level '{s}', expected trace, debug, info, warn, error or critical"
+            ),
+        }
+    }
+}
+
+/// A line a component logged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub timestamp: SystemTime,
+    pub workload_id: Arc<str>,
+    pub workload_name: Arc<str>,
+    pub workload_namespace: Arc<str>,
+    pub component_id: Arc<str>,
+    pub level: LogLevel,
+    /// The context the component logged the line in, e.g. a module name
+    pub context: String,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Emits the line as a `tracing` event.
+    pub(crate) fn trace(&self) {
+        macro_rules! emit {
+            ($macro:ident) => {
+                tracing::$macro!(
+                    workload.id = %self.workload_id,
+                    workload.name = %self.workload_name,
+                    workload.namespace = %self.workload_namespace,
+                    workload.component_id = %self.component_id,
+                    context = self.context,
+                    "{}",
+                    self.message
+                )
+            };
+        }
+        match self.level {
+            LogLevel::Trace => emit!(trace),
+            LogLevel::Debug => emit!(debug),
+            LogLevel::Info => emit!(info),
+            LogLevel::Warn => emit!(warn),
+            LogLevel::Error | LogLevel::Critical => emit!(error),
+        }
+    }
+}
+
+/// Which lines of a workload to read, see
+/// [`HostApi::workload_logs`](super::HostApi::workload_logs).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LogQuery {
+    pub workload_id: String,
+    /// Only lines of this component, lines of all components if `None`
+    pub component_id: Option<String>,
+    /// Only lines at or above this level
+    pub min_level: LogLevel,
+    /// The most recent buffered lines to read, all buffered lines if `None`
+    pub tail: Option<usize>,
+    /// Whether to follow the lines logged from now on
+    pub follow: bool,
+}
+
+impl LogQuery {
+    fn matches(&self, record: &LogRecord) -> bool {
+        *record.workload_id == *self.workload_id
+            && record.level >= self.min_level
+            && self
+                .component_id
+                .as_deref()
+                .is_none_or(|component_id| *record.component_id == *component_id)
+    }
+}
+
+/// The buffered lines a [`LogQuery`] matched, and the lines logged since if
+/// it follows them.
+#[derive(Debug)]
+pub struct LogTail {
+    /// The oldest first
+    pub lines: Vec<Arc<LogRecord>>,
+    pub follower: Option<LogFollower>,
+}
+
+/// Receives the lines a [`LogQuery`] matches as they are logged.
+#[derive(Debug)]
+pub struct LogFollower {
+    query: LogQuery,
+    receiver: broadcast::Receiver<Arc<LogRecord>>,
+    /// Lines missed because the follower fell behind
+    missed: u64,
+}
+
+impl LogFollower {
+    /// Waits for the next matching line. Lines are skipped if the follower
+    /// falls too far behind, see [`LogFollower::missed`].
+    pub async fn next(&mut self) -> Option<Arc<LogRecord>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(record) if self.query.matches(&record) => return Some(record),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => self.missed += missed,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Returns the lines of all workloads skipped because the follower fell
+    /// behind.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+/// The log pipeline of a host, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct WorkloadLogs {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    buffer: Mutex<VecDeque<Arc<LogRecord>>>,
+    /// Log levels of the workloads that set one
+    levels: RwLock<HashMap<String, LogLevel>>,
+    followers: broadcast::Sender<Arc<LogRecord>>,
+}
+
+impl Default for WorkloadLogs {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl WorkloadLogs {
+    /// Creates a pipeline keeping the `capacity` most recent lines.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                capacity,
+                buffer: Mutex::new(VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY))),
+                levels: RwLock::default(),
+                followers: broadcast::channel(FOLLOW_BUFFER).0,
+            }),
+        }
+    }
+
+    /// Sets the level below which lines of a workload are dropped, or resets
+    /// it to `trace`.
+    pub fn set_level(&self, workload_id: &str, level: Option<LogLevel>) {
+        let mut levels = self
+            .inner
+            .levels
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        match level {
+            Some(level) => levels.insert(workload_id.to_string(), level),
+            None => levels.remove(workload_id),
+        };
+    }
+
+    /// Returns whether lines of a workload at `level` are captured.
+    pub fn enabled(&self, workload_id: &str, level: LogLevel) -> bool {
+        let levels = self
+            .inner
+            .levels
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        levels
+            .get(workload_id)
+            .is_none_or(|threshold| level >= *threshold)
+    }
+
+    /// Returns the logger of a component.
+    pub fn component(
+        &self,
+        workload_id: impl Into<Arc<str>>,
+        workload_name: impl Into<Arc<str>>,
+        workload_namespace: impl Into<Arc<str>>,
+        component_id: impl Into<Arc<str>>,
+    ) -> ComponentLogger {
+        ComponentLogger {
+            logs: self.clone(),
+            workload_id: workload_id.into(),
+            workload_name: workload_name.into(),
+            workload_namespace: workload_namespace.into(),
+            component_id: component_id.into(),
+        }
+    }
+
+    /// Captures a line, unless it is below the level of its workload.
+    pub fn record(&self, record: LogRecord) {
+        if !self.enabled(&record.workload_id, record.level) {
+            return;
+        }
+        record.trace();
+        if self.inner.capacity == 0 {
+            return;
+        }
+        let record = Arc::new(record);
+        let mut buffer = self
+            .inner
+            .buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if buffer.len() == self.inner.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(record.clone());
+        // Sent while buffered, so queries see every line once
+        _ = self.inner.followers.send(record);
+    }
+
+    /// Returns the buffered lines `query` matches, and follows the lines
+    /// logged from now on if it asks to.
+    pub fn query(&self, query: LogQuery) -> LogTail {
+        let buffer = self
+            .inner
+            .buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut lines: Vec<_> = buffer
+            .iter()
+            .rev()
+            .filter(|record| query.matches(record))
+            .take(query.tail.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        lines.reverse();
+        let follower = query.follow.then(|| LogFollower {
+            receiver: self.inner.followers.subscribe(),
+            query,
+            missed: 0,
+        });
+        LogTail { lines, follower }
+    }
+
+    /// Forgets the level of a stopped workload. Its buffered lines are kept.
+    pub(crate) fn forget(&self, workload_id: &str) {
+        self.set_level(workload_id, None);
+    }
+}
+
+/// Captures the lines of one component, see [`WorkloadLogs::component`].
+#[derive(Debug, Clone)]
+pub struct ComponentLogger {
+    logs: WorkloadLogs,
+    workload_id: Arc<str>,
+    workload_name: Arc<str>,
+    workload_namespace: Arc<str>,
+    component_id: Arc<str>,
+}
+
+impl ComponentLogger {
+    /// Captures a line the component logged.
+    pub fn log(&self, level: LogLevel, context: String, message: String) {
+        self.logs.record(LogRecord {
+            timestamp: SystemTime::now(),
+            workload_id: self.workload_id.clone(),
+            workload_name: self.workload_name.clone(),
+            workload_namespace: self.workload_namespace.clone(),
+            component_id: self.component_id.clone(),
+            level,
+            context,
+            message,
+        });
+    }
+}
+
+/// Returns the log level set in the config of the `wasi:logging/logging`
+/// host interface of a workload, if any.
+///
+/// # Errors
+/// Returns an error if the level is unknown.
+pub fn configured_level(
+    interfaces: &[crate::wit::WitInterface],
+) -> anyhow::Result<Option<LogLevel>> {
+    interfaces
+        .iter()
+        .filter(|interface| interface.namespace == "wasi" && interface.package == "logging")
+        .find_map(|interface| interface.config.get(LEVEL_CONFIG_KEY))
+        .map(|level| level.parse())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logger(logs: &WorkloadLogs, workload_id: &str, component_id: &str) -> ComponentLogger {
+        logs.component(workload_id, "app", "default", component_id)
+    }
+
+    fn messages(lines: &[Arc<LogRecord>]) -> Vec<&str> {
+        lines.iter().map(|line| line.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_ring_buffer_and_filters() {
+        let logs = WorkloadLogs::new(3);
+        let a = logger(&logs, "w1", "a");
+        let b = logger(&logs, "w1", "b");
+        a.log(LogLevel::Info, String::new(), "one".into());
+        b.log(LogLevel::Debug, String::new(), "two".into());
+        logger(&logs, "w2", "a").log(LogLevel::Info, String::new(), "other".into());
+        a.log(LogLevel::Error, String::new(), "three".into());
+
+        let query = LogQuery {
+            workload_id: "w1".into(),
+            ..Default::default()
+        };
+        // The oldest line was pushed out
+        let tail = logs.query(query.clone());
+        assert_eq!(messages(&tail.lines), ["two", "three"]);
+        assert!(tail.follower.is_none());
+
+        let tail = logs.query(LogQuery {
+            min_level: LogLevel::Info,
+            ..query.clone()
+        });
+        assert_eq!(messages(&tail.lines), ["three"]);
+        let tail = logs.query(LogQuery {
+            component_id: Some("b".into()),
+            ..query.clone()
+        });
+        assert_eq!(messages(&tail.lines), ["two"]);
+        let tail = logs.query(LogQuery {
+            tail: Some(1),
+            ..query
+        });
+        assert_eq!(messages(&tail.lines), ["three"]);
+    }
+
+    #[test]
+    fn test_workload_level() {
+        let logs = WorkloadLogs::default();
+        logs.set_level("w1", Some(LogLevel::Warn));
+        let a = logger(&logs, "w1", "a");
+        a.log(LogLevel::Info, String::new(), "dropped".into());
+        a.log(LogLevel::Critical, String::new(), "kept".into());
+        let query = LogQuery {
+            workload_id: "w1".into(),
+            ..Default::default()
+        };
+        assert_eq!(messages(&logs.query(query.clone()).lines), ["kept"]);
+
+        logs.forget("w1");
+        a.log(LogLevel::Info, String::new(), "captured".into());
+        assert_eq!(messages(&logs.query(query).lines), ["kept", "captured"]);
+    }
+
+    #[tokio::test]
+    async fn test_follow() {
+        let logs = WorkloadLogs::default();
+        let a = logger(&logs, "w1", "a");
+        a.log(LogLevel::Info, String::new(), "before".into());
+        let mut tail = logs.query(LogQuery {
+            workload_id: "w1".into(),
+            follow: true,
+            ..Default::default()
+        });
+        assert_eq!(messages(&tail.lines), ["before"]);
+        logger(&logs, "w2", "a").log(LogLevel::Info, String::new(), "other".into());
+        a.log(LogLevel::Info, String::new(), "after".into());
+        let follower = tail.follower.as_mut().unwrap();
+        assert_eq!(follower.next().await.unwrap().message, "after");
+    }
+
+    #[test]
+    fn test_configured_level() {
+        let mut interface = crate::wit::WitInterface::from("wasi:logging/logging");
+        assert_eq!(configured_level(&[interface.clone()]).unwrap(), None);
+        interface
+            .config
+            .insert(LEVEL_CONFIG_KEY.to_string(), "Warning".to_string());
+        assert_eq!(
+            configured_level(&[interface.clone()]).unwrap(),
+            Some(LogLevel::Warn)
+        );
+        interface
+            .config
+            .insert(LEVEL_CONFIG_KEY.to_string(), "loud".to_string());
+        assert!(configured_level(&[interface]).is_err());
+    }
+}
@@ -0,0 +1,599 @@
+//! Usage metering of invocations, for chargeback on multi-tenant hosts.
+//!
+//! With [`crate::engine::EngineBuilder::with_metering`], every store a
+//! component is instantiated in is metered from its creation until it is
+//! dropped, which is one invocation: an HTTP request, a message, a timer, a
+//! call through the API, a job run or the lifetime of a service. For each
+//! invocation the host records
+//!
+//! - how long it took,
+//! - the fuel the guest consumed, a deterministic measure of the instructions
+//!   it ran, with [`Metering::with_fuel`],
+//! - the high-water mark of its linear memories, and
+//! - the bytes of the bodies of the outgoing HTTP requests it sent.
+//!
+//! Invocations are added up per namespace, both since the host started, see
+//! [`Metering::totals`], and per export window, which every
+//! [`UsageSink`] receives as a [`UsageReport`] when the window closes:
+//! [`CsvUsageSink`] appends it to a CSV file and
+//! [`Webhook`](super::webhook::Webhook) POSTs it as JSON. Each invocation is
+//! also recorded in `wasmcloud.usage.*` metrics, exported by the global meter
+//! provider such as the OTLP exporter in [`super::otlp`].
+//!
+//! Fuel metering makes guests run slower, as the engine counts the
+//! instructions they run, and is therefore off by default.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt as _;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use super::metrics::NAMESPACE_ATTRIBUTE;
+use crate::engine::ctx::Ctx;
+
+/// How often usage is exported by default
+pub const DEFAULT_EXPORT_INTERVAL: Duration = Duration::from_secs(60);
+/// Fuel every metered store starts with
+const FUEL: u64 = u64::MAX;
+/// Attribute identifying the workload an invocation belongs to
+const WORKLOAD_ATTRIBUTE: &str = "wasmcloud.workload.name";
+
+/// The usage of a single invocation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InvocationUsage {
+    pub namespace: String,
+    pub workload_name: String,
+    pub workload_id: String,
+    pub component_id: String,
+    pub duration: Duration,
+    /// `None` without fuel metering
+    pub fuel: Option<u64>,
+    /// The largest the linear memories of the invocation grew to, in bytes
+    pub memory_peak_bytes: u64,
+    /// Bytes of the bodies of the outgoing HTTP requests of the invocation
+    pub egress_bytes: u64,
+}
+
+/// The usage of the invocations in a namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    pub invocations: u64,
+    /// Total duration of the invocations, in microseconds
+    pub duration_us: u64,
+    /// Total fuel consumed, 0 without fuel metering
+    pub fuel: u64,
+    /// The largest memory high-water mark of an invocation, in bytes
+    pub memory_peak_bytes: u64,
+    pub egress_bytes: u64,
+}
+
+impl NamespaceUsage {
+    fn add(&mut self, usage: &InvocationUsage) {
+        self.invocations += 1;
+        self.duration_us = self
+            .duration_us
+            .saturating_add(usage.duration.as_micros() as u64);
+        self.fuel = self.fuel.saturating_add(usage.fuel.unwrap_or(0));
+        self.memory_peak_bytes = self.memory_peak_bytes.max(usage.memory_peak_bytes);
+        self.egress_bytes = self.egress_bytes.saturating_add(usage.egress_bytes);
+    }
+}
+
+/// The usage of every namespace during an export window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsageReport {
+    pub window_start: chrono::DateTime<chrono::Utc>,
+    pub window_end: chrono::DateTime<chrono::Utc>,
+    /// Namespaces with at least one invocation in the window, by name
+    pub namespaces: Vec<NamespaceUsage>,
+}
+
+/// Receives the usage of every export window, see the [module docs](self).
+#[async_trait::async_trait]
+pub trait UsageSink: Send + Sync {
+    async fn export(&self, report: &UsageReport) -> anyhow::Result<()>;
+}
+
+/// Appends usage reports to a CSV file, one row per namespace and window.
+/// The header is written when the file is empty.
+#[derive(Debug, Clone)]
+pub struct CsvUsageSink {
+    path: PathBuf,
+}
+
+impl CsvUsageSink {
+    /// The columns of every row
+    pub const HEADER: &str = "window_start,window_end,namespace,invocations,duration_us,fuel,memory_peak_bytes,egress_bytes";
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the rows of `report`, without the header.
+    pub fn rows(report: &UsageReport) -> String {
+        let mut rows = String::new();
+        for usage in &report.namespaces {
+            rows.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                report.window_start.to_rfc3339(),
+                report.window_end.to_rfc3339(),
+                csv_field(&usage.namespace),
+                usage.invocations,
+                usage.duration_us,
+                usage.fuel,
+                usage.memory_peak_bytes,
+                usage.egress_bytes
+            ));
+        }
+        rows
+    }
+}
+
+/// Quotes a CSV field if it has to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl UsageSink for CsvUsageSink {
+    async fn export(&self, report: &UsageReport) -> anyhow::Result<()> {
+        if report.namespaces.is_empty() {
+            return Ok(());
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        let mut rows = String::new();
+        if file.metadata().await?.len() == 0 {
+            rows.push_str(Self::HEADER);
+            rows.push('\n');
+        }
+        rows.push_str(&Self::rows(report));
+        file.write_all(rows.as_bytes())
+            .await
+            .with_context(|| format!("failed to write to {}", self.path.display()))?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Instruments every invocation is recorded in
+struct UsageMetrics {
+    invocations: Counter<u64>,
+    duration: Counter<f64>,
+    fuel: Counter<u64>,
+    egress: Counter<u64>,
+    memory_peak: Histogram<u64>,
+}
+
+impl UsageMetrics {
+    fn new(meter: &Meter) -> Self {
+        Self {
+            invocations: meter
+                .u64_counter("wasmcloud.usage.invocations")
+                .with_unit("{invocation}")
+                .with_description("Invocations of components")
+                .build(),
+            duration: meter
+                .f64_counter("wasmcloud.usage.duration")
+                .with_unit("s")
+                .with_description("Time spent in invocations of components")
+                .build(),
+            fuel: meter
+                .u64_counter("wasmcloud.usage.fuel")
+                .with_unit("{fuel}")
+                .with_description("Fuel consumed by invocations of components")
+                .build(),
+            egress: meter
+                .u64_counter("wasmcloud.usage.egress")
+                .with_unit("By")
+                .with_description("Bytes sent in outgoing HTTP requests of components")
+                .build(),
+            memory_peak: meter
+                .u64_histogram("wasmcloud.usage.memory.peak")
+                .with_unit("By")
+                .with_description("Memory high-water mark of invocations of components")
+                .build(),
+        }
+    }
+
+    fn record(&self, usage: &InvocationUsage) {
+        let attributes = [
+            KeyValue::new(NAMESPACE_ATTRIBUTE, usage.namespace.clone()),
+            KeyValue::new(WORKLOAD_ATTRIBUTE, usage.workload_name.clone()),
+        ];
+        self.invocations.add(1, &attributes);
+        self.duration.add(usage.duration.as_secs_f64(), &attributes);
+        if let Some(fuel) = usage.fuel {
+            self.fuel.add(fuel, &attributes);
+        }
+        self.egress.add(usage.egress_bytes, &attributes);
+        self.memory_peak
+            .record(usage.memory_peak_bytes, &attributes);
+    }
+}
+
+/// The usage of the current export window
+struct Window {
+    start: chrono::DateTime<chrono::Utc>,
+    namespaces: BTreeMap<String, NamespaceUsage>,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            start: chrono::Utc::now(),
+            namespaces: BTreeMap::new(),
+        }
+    }
+}
+
+/// Meters invocations and exports their usage, see the [module docs](self).
+#[derive(Clone)]
+pub struct Metering {
+    fuel: bool,
+    interval: Duration,
+    sinks: Vec<Arc<dyn UsageSink>>,
+    metrics: Arc<UsageMetrics>,
+    totals: Arc<std::sync::Mutex<BTreeMap<String, NamespaceUsage>>>,
+    window: Arc<std::sync::Mutex<Window>>,
+    stopped: CancellationToken,
+}
+
+impl Default for Metering {
+    fn default() -> Self {
+        Self {
+            fuel: false,
+            interval: DEFAULT_EXPORT_INTERVAL,
+            sinks: Vec::new(),
+            metrics: Arc::new(UsageMetrics::new(&opentelemetry::global::meter(
+                "wash-usage",
+            ))),
+            totals: Arc::default(),
+            window: Arc::new(std::sync::Mutex::new(Window::new())),
+            stopped: CancellationToken::new(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Metering {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metering")
+            .field("fuel", &self.fuel)
+            .field("interval", &self.interval)
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
+
+impl Metering {
+    /// Meters duration, memory and egress of invocations, without exporting
+    /// them anywhere but to metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Meters the fuel guests consume as well, which slows them down.
+    pub fn with_fuel(mut self, fuel: bool) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Exports the usage of every window to `sink`.
+    pub fn with_sink(mut self, sink: Arc<dyn UsageSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Sets how long export windows are. Defaults to
+    /// [`DEFAULT_EXPORT_INTERVAL`].
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns whether guests are metered in fuel.
+    pub fn meters_fuel(&self) -> bool {
+        self.fuel
+    }
+
+    /// Returns the usage of every namespace since the host started, by name.
+    pub fn totals(&self) -> Vec<NamespaceUsage> {
+        lock(&self.totals).values().cloned().collect()
+    }
+
+    /// Starts metering an invocation of a component.
+    pub(crate) fn start(
+        &self,
+        namespace: &str,
+        workload_name: &str,
+        workload_id: &str,
+        component_id: &str,
+    ) -> UsageMeter {
+        UsageMeter {
+            metering: self.clone(),
+            usage: InvocationUsage {
+                namespace: namespace.to_string(),
+                workload_name: workload_name.to_string(),
+                workload_id: workload_id.to_string(),
+                component_id: component_id.to_string(),
+                duration: Duration::ZERO,
+                fuel: None,
+                memory_peak_bytes: 0,
+                egress_bytes: 0,
+            },
+            started: Instant::now(),
+            memory: Arc::default(),
+            egress: Arc::default(),
+        }
+    }
+
+    fn record(&self, usage: InvocationUsage) {
+        self.metrics.record(&usage);
+        lock(&self.totals)
+            .entry(usage.namespace.clone())
+            .or_insert_with(|| NamespaceUsage {
+                namespace: usage.namespace.clone(),
+                ..Default::default()
+            })
+            .add(&usage);
+        lock(&self.window)
+            .namespaces
+            .entry(usage.namespace.clone())
+            .or_insert_with(|| NamespaceUsage {
+                namespace: usage.namespace.clone(),
+                ..Default::default()
+            })
+            .add(&usage);
+    }
+
+    /// Closes the current window and exports it to every sink. Failed exports
+    /// are logged, the window is not exported again.
+    pub async fn flush(&self) {
+        let report = {
+            let mut window = lock(&self.window);
+            let closed = std::mem::replace(&mut *window, Window::new());
+            UsageReport {
+                window_start: closed.start,
+                window_end: window.start,
+                namespaces: closed.namespaces.into_values().collect(),
+            }
+        };
+        for sink in &self.sinks {
+            if let Err(e) = sink.export(&report).await {
+                warn!("failed to export usage: {e:#}");
+            }
+        }
+    }
+
+    /// Exports usage every interval until [`Metering::stop`].
+    pub(crate) fn spawn_export(&self) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        let metering = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(metering.interval);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => metering.flush().await,
+                    _ = metering.stopped.cancelled() => break,
+                }
+            }
+        });
+    }
+
+    /// Stops exporting usage every interval and exports the last window.
+    pub(crate) async fn stop(&self) {
+        self.stopped.cancel();
+        if !self.sinks.is_empty() {
+            self.flush().await;
+        }
+    }
+}
+
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Meters an invocation and records it when dropped with its store.
+pub(crate) struct UsageMeter {
+    metering: Metering,
+    usage: InvocationUsage,
+    started: Instant,
+    /// Total size of the linear memories of the store, see
+    /// [`crate::engine::memory::MemoryLimiter::usage`]. Memories never
+    /// shrink, so their size at the end is their high-water mark.
+    memory: Arc<AtomicUsize>,
+    egress: Arc<AtomicU64>,
+}
+
+impl UsageMeter {
+    /// Reads the memory size of the store from `memory`.
+    pub(crate) fn with_memory(mut self, memory: Arc<AtomicUsize>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Meters the duration of the invocation from now on.
+    pub(crate) fn restart(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Counts the bytes of the body of an outgoing request.
+    pub(crate) fn count_egress<B>(&self, body: B) -> EgressBody<B> {
+        EgressBody {
+            inner: body,
+            sent: self.egress.clone(),
+        }
+    }
+}
+
+impl Drop for UsageMeter {
+    fn drop(&mut self) {
+        let mut usage = self.usage.clone();
+        usage.duration = self.started.elapsed();
+        usage.memory_peak_bytes = self.memory.load(Ordering::Relaxed) as u64;
+        usage.egress_bytes = self.egress.load(Ordering::Relaxed);
+        self.metering.record(usage);
+    }
+}
+
+/// Gives a store its fuel. Stores of engines that don't consume fuel are left
+/// alone.
+pub(crate) fn fuel_store(store: &mut wasmtime::Store<Ctx>) {
+    // Fails when the engine doesn't consume fuel
+    let _ = store.set_fuel(FUEL);
+}
+
+/// Records the fuel a store consumed, in its usage if metered, the activity
+/// of its workload and metrics. Called once the invocation returned, before
+/// the store is dropped.
+pub fn record_fuel(store: &mut wasmtime::Store<Ctx>) {
+    let Ok(remaining) = store.get_fuel() else {
+        return;
+    };
+    store.data_mut().record_fuel(FUEL - remaining);
+}
+
+/// A request body counting the bytes sent, see [`UsageMeter::count_egress`]
+pub(crate) struct EgressBody<B> {
+    inner: B,
+    sent: Arc<AtomicU64>,
+}
+
+impl<B> hyper::body::Body for EgressBody<B>
+where
+    B: hyper::body::Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Bytes>, B::Error>>> {
+        let this = self.get_mut();
+        let frame = std::task::ready!(std::pin::Pin::new(&mut this.inner).poll_frame(cx));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            this.sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        std::task::Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt as _;
+
+    use super::*;
+
+    /// Collects the reports it receives
+    #[derive(Default)]
+    struct Reports(std::sync::Mutex<Vec<UsageReport>>);
+
+    #[async_trait::async_trait]
+    impl UsageSink for Reports {
+        async fn export(&self, report: &UsageReport) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(report.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metering() {
+        let reports = Arc::new(Reports::default());
+        let metering = Metering::new().with_sink(reports.clone());
+
+        for (namespace, memory, egress) in [("team-a", 65536, 10), ("team-a", 131072, 5)] {
+            let meter = metering
+                .start(namespace, "api", "workload-1", "component-1")
+                .with_memory(Arc::new(AtomicUsize::new(memory)));
+            let body = meter.count_egress(http_body_util::Full::new(Bytes::from(vec![0; egress])));
+            body.collect().await.unwrap();
+        }
+        drop(metering.start("team-b", "worker", "workload-2", "component-2"));
+
+        let totals = metering.totals();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].namespace, "team-a");
+        assert_eq!(totals[0].invocations, 2);
+        assert_eq!(totals[0].memory_peak_bytes, 131072);
+        assert_eq!(totals[0].egress_bytes, 15);
+        assert_eq!(totals[1].invocations, 1);
+
+        metering.flush().await;
+        drop(metering.start("team-b", "worker", "workload-2", "component-2"));
+        metering.stop().await;
+        let reports = reports.0.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].namespaces.len(), 2);
+        assert_eq!(reports[0].window_end, reports[1].window_start);
+        // Windows only hold the invocations made during them
+        assert_eq!(reports[1].namespaces.len(), 1);
+        assert_eq!(reports[1].namespaces[0].namespace, "team-b");
+        assert_eq!(metering.totals()[1].invocations, 2);
+    }
+
+    #[tokio::test]
+    async fn test_csv_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.csv");
+        let sink = CsvUsageSink::new(&path);
+        let report = UsageReport {
+            window_start: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            window_end: chrono::DateTime::from_timestamp(1_700_000_060, 0).unwrap(),
+            namespaces: vec![NamespaceUsage {
+                namespace: "team,a".to_string(),
+                invocations: 3,
+                duration_us: 1500,
+                fuel: 0,
+                memory_peak_bytes: 65536,
+                egress_bytes: 42,
+            }],
+        };
+        sink.export(&report).await.unwrap();
+        sink.export(&report).await.unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CsvUsageSink::HEADER);
+        assert_eq!(
+            lines[1],
+            "2023-11-14T22:13:20+00:00,2023-11-14T22:14:20+00:00,\"team,a\",3,1500,0,65536,42"
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Host metrics.
+//!
+//! Every heartbeat records the resource usage of the host and how many
+//! workloads and components it runs as gauges. They are exported by the global
+//! meter provider, such as the OTLP push exporter in [`super::otlp`], alongside
+//! the per-workload metrics recorded by the HTTP server and plugins.
+//!
+//! The headroom left by every limited quota of the host, see [`super::limits`],
+//! is recorded as `wasmcloud.host.headroom.*` gauges, with the namespace of
+//! namespace quotas in [`NAMESPACE_ATTRIBUTE`]. The threads and busy threads
+//! of every execution pool, see [`crate::engine::pool`], are recorded as
+//! `wasmcloud.host.pool.*` gauges. The pooled connections of outgoing HTTP
+//! requests, see [`super::http::connection_pool`], are recorded as the
+//! `wasmcloud.host.http.outgoing.connections` gauge, in use or idle as given
+//! by [`CONNECTION_STATE_ATTRIBUTE`], with the connections opened and reused
+//! since the host started in `wasmcloud.host.http.outgoing.opened` and
+//! `wasmcloud.host.http.outgoing.reused`. The circuit breaker of every host
+//! outgoing requests were sent to, see [`super::http::resilience`], is
+//! recorded in the `wasmcloud.host.http.outgoing.circuit` gauge, 1 for the
+//! state given by [`CIRCUIT_STATE_ATTRIBUTE`] and 0 for the others, with the
+//! host in `server.address`.
+//!
+//! Every invocation of a component is counted in
+//! `wasmcloud.workload.invocations`, and failed ones in
+//! `wasmcloud.workload.invocation.errors`, with the time it took in the
+//! `wasmcloud.workload.invocation.duration` histogram. The linear memory of
+//! each instance is recorded in the `wasmcloud.component.memory` gauge when
+//! the instance is dropped, and the fuel it consumed, when the engine consumes
+//! fuel, in the `wasmcloud.workload.invocation.fuel` histogram. Instances
+//! trapped for exceeding their memory limit, see [`crate::engine::memory`],
+//! are counted in `wasmcloud.workload.memory_limit_exceeded`. The time
+//! invocations of autoscaling components waited for an instance, see
+//! [`crate::engine::autoscale`], is recorded in the
+//! `wasmcloud.workload.invocation.queue_time` histogram, and those refused an
+//! instance are counted in `wasmcloud.workload.invocation.refused`, with the
+//! reason in [`REFUSAL_REASON_ATTRIBUTE`]. These carry the namespace and name
+//! of the workload and the ID of the component.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter};
+use opentelemetry_semantic_conventions::attribute::SERVER_ADDRESS;
+
+use super::http::{CircuitState, CircuitStatus, ConnectionPoolStats};
+use super::limits::Headroom;
+use crate::engine::autoscale::Overloaded;
+use crate::engine::pool::ExecutionPool;
+use crate::types::HostHeartbeat;
+
+/// Attribute identifying the host a measurement was taken on
+pub const HOST_ID_ATTRIBUTE: &str = "wasmcloud.host.id";
+/// Attribute identifying the namespace a quota applies to
+pub const NAMESPACE_ATTRIBUTE: &str = "wasmcloud.namespace";
+/// Attribute identifying the workload a measurement belongs to, by name
+pub const WORKLOAD_NAME_ATTRIBUTE: &str = "wasmcloud.workload.name";
+/// Attribute identifying the component a measurement belongs to
+pub const COMPONENT_ID_ATTRIBUTE: &str = "wasmcloud.component.id";
+/// Attribute identifying an execution pool
+const POOL_ATTRIBUTE: &str = "wasmcloud.pool.name";
+/// Attribute giving whether pooled connections are `active` or `idle`
+pub const CONNECTION_STATE_ATTRIBUTE: &str = "wasmcloud.connection.state";
+/// Attribute giving whether a circuit breaker is `closed`, `half_open` or
+/// `open`
+pub const CIRCUIT_STATE_ATTRIBUTE: &str = "wasmcloud.circuit.state";
+/// Attribute giving why an invocation was refused an instance, see [`Overloaded`]
+pub const REFUSAL_REASON_ATTRIBUTE: &str = "wasmcloud.refusal.reason";
+/// Bucket boundaries in seconds of `wasmcloud.workload.invocation.duration`
+const DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// Gauges describing the state of a host.
+pub(crate) struct HostMetrics {
+    cpu_utilization: Gauge<f64>,
+    memory_total: Gauge<u64>,
+    memory_free: Gauge<u64>,
+    workloads: Gauge<u64>,
+    components: Gauge<u64>,
+    headroom_workloads: Gauge<u64>,
+    headroom_instances: Gauge<u64>,
+    headroom_memory: Gauge<u64>,
+    headroom_connections: Gauge<u64>,
+    pool_threads: Gauge<u64>,
+    pool_busy: Gauge<u64>,
+    outgoing_connections: Gauge<u64>,
+    outgoing_opened: Gauge<u64>,
+    outgoing_reused: Gauge<u64>,
+    outgoing_circuit: Gauge<u64>,
+}
+
+impl HostMetrics {
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            cpu_utilization: meter
+                .f64_gauge("wasmcloud.host.cpu.utilization")
+                .with_unit("1")
+                .with_description("CPU utilization of the system running the host")
+                .build(),
+            memory_total: meter
+                .u64_gauge("wasmcloud.host.memory.total")
+                .with_unit("By")
+                .with_description("Total memory of the system running the host")
+                .build(),
+            memory_free: meter
+                .u64_gauge("wasmcloud.host.memory.free")
+                .with_unit("By")
+                .with_description("Free memory of the system running the host")
+                .build(),
+            workloads: meter
+                .u64_gauge("wasmcloud.host.workloads")
+                .with_unit("{workload}")
+                .with_description("Workloads on the host")
+                .build(),
+            components: meter
+                .u64_gauge("wasmcloud.host.components")
+                .with_unit("{component}")
+                .with_description("Components of the workloads on the host")
+                .build(),
+            headroom_workloads: meter
+                .u64_gauge("wasmcloud.host.headroom.workloads")
+                .with_unit("{workload}")
+                .with_description("Workloads that can still start within a quota")
+                .build(),
+            headroom_instances: meter
+                .u64_gauge("wasmcloud.host.headroom.instances")
+                .with_unit("{instance}")
+                .with_description("Component instances that can still be reserved within a quota")
+                .build(),
+            headroom_memory: meter
+                .u64_gauge("wasmcloud.host.headroom.memory")
+                .with_unit("By")
+                .with_description("Guest memory that can still be reserved within a quota")
+                .build(),
+            headroom_connections: meter
+                .u64_gauge("wasmcloud.host.headroom.connections")
+                .with_unit("{connection}")
+                .with_description("Connections that can still be opened within a quota")
+                .build(),
+            pool_threads: meter
+                .u64_gauge("wasmcloud.host.pool.threads")
+                .with_unit("{thread}")
+                .with_description("Worker threads of an execution pool")
+                .build(),
+            pool_busy: meter
+                .u64_gauge("wasmcloud.host.pool.busy")
+                .with_unit("{thread}")
+                .with_description("Worker threads of an execution pool running an invocation")
+                .build(),
+            outgoing_connections: meter
+                .u64_gauge("wasmcloud.host.http.outgoing.connections")
+                .with_unit("{connection}")
+                .with_description("Pooled connections of outgoing HTTP requests")
+                .build(),
+            outgoing_opened: meter
+                .u64_gauge("wasmcloud.host.http.outgoing.opened")
+                .with_unit("{connection}")
+                .with_description("Connections opened for outgoing HTTP requests")
+                .build(),
+            outgoing_reused: meter
+                .u64_gauge("wasmcloud.host.http.outgoing.reused")
+                .with_unit("{request}")
+                .with_description("Outgoing HTTP requests sent over a pooled connection")
+                .build(),
+            outgoing_circuit: meter
+                .u64_gauge("wasmcloud.host.http.outgoing.circuit")
+                .with_unit("1")
+                .with_description("Whether the circuit breaker of a host is in a state")
+                .build(),
+        }
+    }
+
+    /// Records the state of the host reported by a heartbeat.
+    pub(crate) fn record(&self, heartbeat: &HostHeartbeat) {
+        let attributes = [KeyValue::new(HOST_ID_ATTRIBUTE, heartbeat.id.clone())];
+        self.cpu_utilization
+            .record(f64::from(heartbeat.system_cpu_usage) / 100.0, &attributes);
+        self.memory_total
+            .record(heartbeat.system_memory_total, &attributes);
+        self.memory_free
+            .record(heartbeat.system_memory_free, &attributes);
+        self.workloads.record(heartbeat.workload_count, &attributes);
+        self.components
+            .record(heartbeat.component_count, &attributes);
+    }
+
+    /// Records the headroom of the host quota, or of the quota of `namespace`.
+    /// Unlimited caps are not recorded.
+    pub(crate) fn record_headroom(
+        &self,
+        host_id: &str,
+        namespace: Option<&str>,
+        headroom: &Headroom,
+    ) {
+        let mut attributes = vec![KeyValue::new(HOST_ID_ATTRIBUTE, host_id.to_string())];
+        if let Some(namespace) = namespace {
+            attributes.push(KeyValue::new(NAMESPACE_ATTRIBUTE, namespace.to_string()));
+        }
+        if let Some(workloads) = headroom.workloads {
+            self.headroom_workloads
+                .record(workloads as u64, &attributes);
+        }
+        if let Some(instances) = headroom.instances {
+            self.headroom_instances
+                .record(instances as u64, &attributes);
+        }
+        if let Some(memory) = headroom.memory_bytes {
+            self.headroom_memory.record(memory, &attributes);
+        }
+        if let Some(connections) = headroom.connections {
+            self.headroom_connections
+                .record(connections as u64, &attributes);
+        }
+    }
+
+    /// Records how busy the execution pools of the host are.
+    pub(crate) fn record_pools<'a>(
+        &self,
+        host_id: &str,
+        pools: impl IntoIterator<Item = &'a Arc<ExecutionPool>>,
+    ) {
+        for pool in pools {
+            let attributes = [
+                KeyValue::new(HOST_ID_ATTRIBUTE, host_id.to_string()),
+                KeyValue::new(POOL_ATTRIBUTE, pool.name().to_string()),
+            ];
+            self.pool_threads.record(pool.threads() as u64, &attributes);
+            self.pool_busy.record(pool.busy() as u64, &attributes);
+        }
+    }
+
+    /// Records the pooled connections of outgoing HTTP requests.
+    pub(crate) fn record_outgoing_connections(&self, host_id: &str, stats: &ConnectionPoolStats) {
+        let host = KeyValue::new(HOST_ID_ATTRIBUTE, host_id.to_string());
+        for (state, connections) in [("active", stats.active), ("idle", stats.idle)] {
+            self.outgoing_connections.record(
+                connections as u64,
+                &[
+                    host.clone(),
+                    KeyValue::new(CONNECTION_STATE_ATTRIBUTE, state),
+                ],
+            );
+        }
+        self.outgoing_opened
+            .record(stats.opened, std::slice::from_ref(&host));
+        self.outgoing_reused
+            .record(stats.reused, std::slice::from_ref(&host));
+    }
+
+    /// Records the state of the circuit breakers of outgoing HTTP requests.
+    pub(crate) fn record_outgoing_circuits(&self, host_id: &str, circuits: &[CircuitStatus]) {
+        for circuit in circuits {
+            for state in [
+                CircuitState::Closed,
+                CircuitState::HalfOpen,
+                CircuitState::Open,
+            ] {
+                self.outgoing_circuit.record(
+                    u64::from(circuit.state == state),
+                    &[
+                        KeyValue::new(HOST_ID_ATTRIBUTE, host_id.to_string()),
+                        KeyValue::new(SERVER_ADDRESS, circuit.host.clone()),
+                        KeyValue::new(CIRCUIT_STATE_ATTRIBUTE, state.as_str()),
+                    ],
+                );
+            }
+        }
+    }
+}
+
+/// Instruments recording the invocations of the components of workloads.
+#[derive(Debug)]
+pub(crate) struct WorkloadMetrics {
+    invocations: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+    memory: Gauge<u64>,
+    fuel: Histogram<u64>,
+    memory_limit_exceeded: Counter<u64>,
+    queue_time: Histogram<f64>,
+    refused: Counter<u64>,
+}
+
+impl WorkloadMetrics {
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            invocations: meter
+                .u64_counter("wasmcloud.workload.invocations")
+                .with_unit("{invocation}")
+                .with_description("Invocations of components")
+                .build(),
+            errors: meter
+                .u64_counter("wasmcloud.workload.invocation.errors")
+                .with_unit("{invocation}")
+                .with_description("Invocations of components that returned an error or trapped")
+                .build(),
+            duration: meter
+                .f64_histogram("wasmcloud.workload.invocation.duration")
+                .with_unit("s")
+                .with_description("Duration of invocations of components")
+                .with_boundaries(DURATION_BUCKETS.to_vec())
+                .build(),
+            memory: meter
+                .u64_gauge("wasmcloud.component.memory")
+                .with_unit("By")
+                .with_description("Linear memory of the last instance of a component to finish")
+                .build(),
+            fuel: meter
+                .u64_histogram("wasmcloud.workload.invocation.fuel")
+                .with_unit("{fuel}")
+                .with_description("Fuel consumed by invocations of components")
+                .build(),
+            memory_limit_exceeded: meter
+                .u64_counter("wasmcloud.workload.memory_limit_exceeded")
+                .with_unit("{instance}")
+                .with_description(
+                    "Instances of components trapped for exceeding their memory limit",
+                )
+                .build(),
+            queue_time: meter
+                .f64_histogram("wasmcloud.workload.invocation.queue_time")
+                .with_unit("s")
+                .with_description("Time invocations of components waited for an instance")
+                .with_boundaries(DURATION_BUCKETS.to_vec())
+                .build(),
+            refused: meter
+                .u64_counter("wasmcloud.workload.invocation.refused")
+                .with_unit("{invocation}")
+                .with_description("Invocations of components refused an instance")
+                .build(),
+        }
+    }
+
+    /// Returns the attributes of the measurements of a component.
+    pub(crate) fn attributes(
+        namespace: &str,
+        workload_name: &str,
+        component_id: &str,
+    ) -> Vec<KeyValue> {
+        vec![
+            KeyValue::new(NAMESPACE_ATTRIBUTE, namespace.to_string()),
+            KeyValue::new(WORKLOAD_NAME_ATTRIBUTE, workload_name.to_string()),
+            KeyValue::new(COMPONENT_ID_ATTRIBUTE, component_id.to_string()),
+        ]
+    }
+
+    /// Records a finished invocation.
+    pub(crate) fn record_invocation(
+        &self,
+        attributes: &[KeyValue],
+        elapsed: Duration,
+        failed: bool,
+    ) {
+        self.invocations.add(1, attributes);
+        if failed {
+            self.errors.add(1, attributes);
+        }
+        self.duration.record(elapsed.as_secs_f64(), attributes);
+    }
+
+    /// Records an instance trapped for exceeding its memory limit.
+    pub(crate) fn record_memory_limit_exceeded(&self, attributes: &[KeyValue]) {
+        self.memory_limit_exceeded.add(1, attributes);
+    }
+
+    /// Records how long an invocation waited for an instance.
+    pub(crate) fn record_queue_time(&self, attributes: &[KeyValue], queue_time: Duration) {
+        self.queue_time.record(queue_time.as_secs_f64(), attributes);
+    }
+
+    /// Records an invocation refused an instance.
+    pub(crate) fn record_refused(&self, attributes: &[KeyValue], reason: Overloaded) {
+        let mut attributes = attributes.to_vec();
+        attributes.push(KeyValue::new(REFUSAL_REASON_ATTRIBUTE, reason.as_str()));
+        self.refused.add(1, &attributes);
+    }
+}
+
+/// Records the linear memory of an instance in `wasmcloud.component.memory`,
+/// and the fuel it consumed in `wasmcloud.workload.invocation.fuel`, when
+/// dropped with its store.
+pub(crate) struct InstanceMetrics {
+    metrics: Arc<WorkloadMetrics>,
+    attributes: Vec<KeyValue>,
+    usage: Arc<AtomicUsize>,
+    /// Set once the invocation returned, see [`crate::host::metering::record_fuel`]
+    pub(crate) fuel: Option<u64>,
+}
+
+impl InstanceMetrics {
+    /// `usage` is the size of the linear memories of the store, see
+    /// [`crate::engine::memory`].
+    pub(crate) fn new(
+        metrics: Arc<WorkloadMetrics>,
+        attributes: Vec<KeyValue>,
+        usage: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            metrics,
+            attributes,
+            usage,
+            fuel: None,
+        }
+    }
+}
+
+impl Drop for InstanceMetrics {
+    fn drop(&mut self) {
+        self.metrics
+            .memory
+            .record(self.usage.load(Ordering::Relaxed) as u64, &self.attributes);
+        if let Some(fuel) = self.fuel {
+            self.metrics.fuel.record(fuel, &self.attributes);
+        }
+    }
+}
@@ -40,25 +40,72 @@
 //! # }
 //! ```
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use anyhow::{Context, bail};
+use anyhow::{Context, bail, ensure};
 use names::{Generator, Name};
 use tokio::sync::RwLock;
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
+use wasmtime::component::Val;
 
 use crate::engine::Engine;
+use crate::engine::autoscale::InstancePool;
+use crate::engine::cgroup::Cgroups;
+use crate::engine::crash_loop::CrashLoopStop;
 use crate::engine::workload::ResolvedWorkload;
 use crate::plugin::HostPlugin;
+use crate::plugin::forward::{CallForwarder, ForwardedCall, Forwarding, ForwardingStats};
 use crate::types::*;
-use crate::wit::WitWorld;
+use crate::wit::{CompatibilityReport, WitInterface, WitWorld};
 
 mod sysinfo;
 use sysinfo::SystemMonitor;
 
+pub mod attestation;
+pub mod devices;
+pub mod env_file;
+pub mod error;
+pub mod events;
+pub mod hot_reload;
 pub mod http;
+pub mod idempotency;
+pub mod interpolate;
+pub mod limits;
+pub mod logs;
+pub mod metering;
+pub mod metrics;
+#[cfg(feature = "otlp-metrics")]
+pub mod otlp;
+#[cfg(feature = "otlp-tracing")]
+pub mod otlp_tracing;
+#[cfg(feature = "prometheus-metrics")]
+pub mod prometheus;
+pub mod rollout;
+pub mod secrets;
+pub mod snapshot;
+pub mod state;
+pub mod tls;
+pub mod volumes;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+
+use attestation::{
+    AttestationReport, AttestationRequest, HostIdentity, SignedAttestationReport,
+    TeeEvidenceProvider,
+};
+use devices::{Device, DeviceManager};
+pub use error::{HostError, HostResult};
+use events::{LifecycleEvent, LifecycleEventSink, LifecycleEventType, LifecycleEvents};
+use interpolate::{EnvAllowlist, SecretStore};
+use limits::{HostLimits, Reservations, Usage};
+use metrics::{HostMetrics, WorkloadMetrics};
+use snapshot::{Artifact, HostSnapshot, RestoreReport, WorkloadSnapshot};
+use volumes::VolumeManager;
 
 /// The API for interacting with a wasmcloud host.
 ///
@@ -73,7 +120,7 @@ pub trait HostApi {
     ///
     /// # Errors
     /// Returns an error if system information cannot be retrieved.
-    fn heartbeat(&self) -> impl Future<Output = anyhow::Result<HostHeartbeat>>;
+    fn heartbeat(&self) -> impl Future<Output = HostResult<HostHeartbeat>>;
     /// Start a new workload on this host.
     ///
     /// # Arguments
@@ -82,26 +129,102 @@ pub trait HostApi {
     /// # Returns
     /// A `WorkloadStartResponse` with the status of the started workload.
     ///
+    /// `${env:NAME}` and `${secret:NAME}` references in component and service
+    /// configuration are resolved before the workload starts, see [`interpolate`],
+    /// and their env files are loaded, see [`env_file`].
+    ///
     /// # Errors
-    /// Returns an error if the workload fails to start or validate.
+    /// Returns [`HostError::InvalidWorkload`] if the workload fails to validate,
+    /// a configuration reference cannot be resolved or an env file cannot be read,
+    /// [`HostError::IncompatibleWorld`] if components import interfaces that
+    /// nothing provides or the workload doesn't declare, or don't export
+    /// declared interfaces the host calls, see
+    /// [`UnresolvedWorkload::validate_world`](crate::engine::workload::UnresolvedWorkload::validate_world),
+    /// [`HostError::UnsatisfiedImport`] if a component requests interfaces this
+    /// host does not provide and can't forward to a peer, see
+    /// [`HostBuilder::with_call_forwarder`], [`HostError::VersionConflict`] if plugins only
+    /// provide them at incompatible versions, [`HostError::QuotaExceeded`] if
+    /// the workload exceeds a quota of the host or its namespace, or another
+    /// [`HostError`] if the workload fails to start.
     fn workload_start(
         &self,
         request: WorkloadStartRequest,
-    ) -> impl Future<Output = anyhow::Result<WorkloadStartResponse>>;
+    ) -> impl Future<Output = HostResult<WorkloadStartResponse>>;
     /// Query the status of a running workload.
     ///
     /// # Arguments
-    /// * `request` - Contains the workload ID to query
+    /// * `request` - Contains the workload ID to query and, optionally, the
+    ///   namespace it must be in
     ///
     /// # Returns
-    /// A `WorkloadStatusResponse` with the current state of the workload.
+    /// A `WorkloadStatusResponse` with the current state of the workload and,
+    /// once it started, its [`WorkloadActivity`]: its components, uptime,
+    /// invocations, last error, execution pools and HTTP routes.
     ///
     /// # Errors
-    /// Returns an error if the workload is not found.
+    /// Returns [`HostError::NotFound`] if the workload is not found, or is not
+    /// in the requested namespace, so callers scoped to a namespace can't tell
+    /// the workloads of others apart from missing ones.
     fn workload_status(
         &self,
         request: WorkloadStatusRequest,
-    ) -> impl Future<Output = anyhow::Result<WorkloadStatusResponse>>;
+    ) -> impl Future<Output = HostResult<WorkloadStatusResponse>>;
+    /// List the workloads on this host with their status, optionally only
+    /// those in one namespace.
+    ///
+    /// Workloads that are still starting or already stopping have no
+    /// namespace or name yet and are only listed without a namespace filter.
+    fn workload_list(
+        &self,
+        request: WorkloadListRequest,
+    ) -> impl Future<Output = HostResult<Vec<WorkloadSummary>>>;
+    /// Replace a running workload with a new version without dropping
+    /// requests.
+    ///
+    /// The new version is started under its own workload ID and takes over the
+    /// HTTP routes of the old one as soon as it runs, see
+    /// [`http::DynamicRouter`]. The old version then drains: it is stopped once
+    /// the invocations it already received finished, or after the drain
+    /// timeout. Quotas must fit both versions while they overlap.
+    ///
+    /// With a [`RollingUpdate`], the instances of the autoscaling component
+    /// serving HTTP are replaced step by step instead: the old version keeps
+    /// its share of the requests of the routes, in proportion to its
+    /// instances, until its last instance is stopped, see [`http::split`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload is not found,
+    /// [`HostError::InvalidRequest`] if it is not running, the replacement
+    /// has another namespace, name or the same workload ID, or a rolling
+    /// update is asked for a workload without an autoscaling component
+    /// serving HTTP, and the errors of
+    /// [`HostApi::workload_start`] if the new version fails to start, in which
+    /// case the old version keeps running.
+    fn workload_update(
+        &self,
+        request: WorkloadUpdateRequest,
+    ) -> impl Future<Output = HostResult<WorkloadUpdateResponse>>;
+    /// Change the configuration components of a running workload read
+    /// through `wasi:config/store`, without restarting it.
+    ///
+    /// The plugins serving the configuration apply the update, see
+    /// [`HostPlugin::on_config_update`], so the next invocations read the new
+    /// values. The instance of the service is replaced unless its
+    /// `config_change` setting is `live`, see
+    /// [`ConfigChangePolicy`](crate::engine::workload::ConfigChangePolicy).
+    /// Updates are not written into the workload, so it starts with its own
+    /// configuration again when it is restarted or restored.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload or component is not
+    /// found, [`HostError::InvalidRequest`] if the workload is not running,
+    /// the update is empty or no component reads configuration, and
+    /// [`HostError::InvalidWorkload`] if a plugin fails to apply the update,
+    /// in which case components already updated keep the new values.
+    fn workload_config_update(
+        &self,
+        request: WorkloadConfigUpdateRequest,
+    ) -> impl Future<Output = HostResult<WorkloadConfigUpdateResponse>>;
     /// Stop a running workload on this host.
     ///
     /// # Arguments
@@ -115,32 +238,394 @@ pub trait HostApi {
     fn workload_stop(
         &self,
         request: WorkloadStopRequest,
-    ) -> impl Future<Output = anyhow::Result<WorkloadStopResponse>>;
+    ) -> impl Future<Output = HostResult<WorkloadStopResponse>>;
+    /// Call an export of a component in a running workload.
+    ///
+    /// # Arguments
+    /// * `request` - Contains the workload, component, export and parameters
+    ///
+    /// # Returns
+    /// A `WorkloadInvokeResponse` with the values returned by the export.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload or component is not
+    /// found, [`HostError::InvalidWorkload`] if the component must be named or
+    /// the export does not exist or takes other parameters, and
+    /// [`HostError::Internal`] if the call traps.
+    fn workload_invoke(
+        &self,
+        request: WorkloadInvokeRequest,
+    ) -> impl Future<Output = HostResult<WorkloadInvokeResponse>>;
+    /// Serve a call a peer forwarded to an interface it lacks, with the plugin
+    /// of this host providing the interface, see [`crate::plugin::forward`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if no plugin provides the interface,
+    /// [`HostError::InvalidRequest`] if the plugin doesn't serve forwarded
+    /// calls, and [`HostError::Internal`] if the call fails.
+    fn plugin_invoke(&self, call: ForwardedCall) -> impl Future<Output = HostResult<Vec<Val>>>;
+    /// Read the lines the components of a workload logged through
+    /// `wasi:logging`, and follow the lines they log from now on if `query`
+    /// asks to, see [`logs`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload is not running and
+    /// the host kept none of its lines `query` matches.
+    fn workload_logs(
+        &self,
+        query: logs::LogQuery,
+    ) -> impl Future<Output = HostResult<logs::LogTail>>;
+    /// List the live component instances of a running workload, see
+    /// [`crate::engine::debug`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload does not exist and
+    /// [`HostError::InvalidWorkload`] if it is not running.
+    #[cfg(feature = "instance-debug")]
+    fn workload_instances(
+        &self,
+        workload_id: &str,
+    ) -> impl Future<Output = HostResult<Vec<crate::engine::debug::InstanceInfo>>>;
+    /// Interrupt a live component instance at its next epoch tick, failing
+    /// its invocation, and return its wasm core dump, see
+    /// [`crate::engine::debug`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload or instance does not
+    /// exist, [`HostError::InvalidRequest`] if a dump of the instance is
+    /// already pending, and [`HostError::Internal`] if the instance finished
+    /// or did not reach an epoch tick in time.
+    #[cfg(feature = "instance-debug")]
+    fn workload_instance_dump(
+        &self,
+        request: WorkloadInstanceDumpRequest,
+    ) -> impl Future<Output = HostResult<Vec<u8>>>;
+    /// Create a named volume that workloads can mount.
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the name is invalid and
+    /// [`HostError::AlreadyExists`] if a volume with the name exists.
+    fn volume_create(
+        &self,
+        request: VolumeCreateRequest,
+    ) -> impl Future<Output = HostResult<VolumeInfo>>;
+    /// List the named volumes of this host.
+    fn volume_list(&self) -> impl Future<Output = HostResult<Vec<VolumeInfo>>>;
+    /// Return a named volume with its size and the workloads mounting it.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the volume does not exist.
+    fn volume_inspect(
+        &self,
+        request: VolumeInspectRequest,
+    ) -> impl Future<Output = HostResult<VolumeInfo>>;
+    /// Delete a named volume and its data.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the volume does not exist and
+    /// [`HostError::InUse`] if a workload mounts it.
+    fn volume_delete(&self, request: VolumeDeleteRequest) -> impl Future<Output = HostResult<()>>;
+    /// List the HTTP routes of this host and the pins overriding them.
+    fn route_list(&self) -> impl Future<Output = HostResult<RoutingTable>>;
+    /// Return the live state of the HTTP server: its routing table, the
+    /// requests in flight with their age and whether they wait for or run in
+    /// a component instance, and these summed up by route, so slow routes
+    /// can be diagnosed while they are slow, see [`http::inflight`].
+    fn http_introspect(&self) -> impl Future<Output = HostResult<HttpIntrospection>>;
+    /// Pin a host and path prefix to a workload, replacing the pin of the
+    /// same host and path prefix, see [`http::RoutePin`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the pin is invalid or the HTTP
+    /// handler does not support pins.
+    fn route_pin(&self, pin: http::RoutePin) -> impl Future<Output = HostResult<()>>;
+    /// Remove the pin of a host and path prefix.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if there is no such pin.
+    fn route_unpin(&self, request: RouteUnpinRequest) -> impl Future<Output = HostResult<()>>;
+    /// Hold a host and path prefix, so the host answers its requests while
+    /// the workloads serving it keep running, replacing the hold of the same
+    /// host and path prefix, see [`http::RouteHold`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the hold is invalid or the
+    /// HTTP handler does not support holds.
+    fn route_hold(&self, hold: http::RouteHold) -> impl Future<Output = HostResult<()>>;
+    /// Release the hold of a host and path prefix.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if there is no such hold.
+    fn route_release(&self, request: RouteReleaseRequest) -> impl Future<Output = HostResult<()>>;
+    /// Drop the cached responses to requests for a host and path prefix,
+    /// see [`http::cache`], returning how many were dropped.
+    fn route_invalidate(
+        &self,
+        request: RouteInvalidateRequest,
+    ) -> impl Future<Output = HostResult<u64>>;
+    /// Shift the weight of a workload among the workloads splitting its
+    /// route, see [`http::split`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if no route of the workload has a
+    /// weight, and [`HostError::InvalidRequest`] if the HTTP handler does
+    /// not support weights.
+    fn route_weight(&self, weight: http::RouteWeight) -> impl Future<Output = HostResult<()>>;
+    /// List the addresses the HTTP server listens on and its TLS certificate
+    /// files.
+    fn http_listener_list(&self) -> impl Future<Output = HostResult<HttpListeners>>;
+    /// Start listening on another address without restarting the HTTP
+    /// server, see [`http`](http#online-reconfiguration).
+    ///
+    /// # Errors
+    /// Returns [`HostError::AlreadyExists`] if the server listens on `addr`
+    /// and [`HostError::InvalidRequest`] if the host has no HTTP server or
+    /// can't listen on `addr`.
+    fn http_listener_add(&self, addr: SocketAddr) -> impl Future<Output = HostResult<()>>;
+    /// Stop listening on an address. Connections accepted on it stay open.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the server does not listen on
+    /// `addr`.
+    fn http_listener_remove(&self, addr: SocketAddr) -> impl Future<Output = HostResult<()>>;
+    /// Read the TLS certificate and key of the HTTP server again, or the
+    /// given files instead, e.g. after they were renewed. Open connections
+    /// keep their TLS session.
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the files can't be loaded or
+    /// the server has no TLS listeners and no files are given. The current
+    /// certificate stays in use.
+    fn http_tls_reload(
+        &self,
+        files: Option<http::TlsFiles>,
+    ) -> impl Future<Output = HostResult<()>>;
+    /// Start recording the HTTP requests of a workload to a file, replacing
+    /// its recording if any, see [`http::recording`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload does not exist and
+    /// [`HostError::InvalidRequest`] if the file can't be opened or the host
+    /// has no HTTP server.
+    fn http_recording_start(
+        &self,
+        recording: http::Recording,
+    ) -> impl Future<Output = HostResult<()>>;
+    /// Stop recording the HTTP requests of a workload, returning how many
+    /// were recorded.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload is not being recorded.
+    fn http_recording_stop(
+        &self,
+        workload_id: String,
+    ) -> impl Future<Output = HostResult<http::RecordingStatus>>;
+    /// List the workloads whose HTTP requests are being recorded.
+    fn http_recording_list(&self) -> impl Future<Output = HostResult<Vec<http::RecordingStatus>>>;
+    /// Replay the HTTP requests of a recording against a workload, e.g. one
+    /// running a new build of the component that served them, comparing the
+    /// statuses of the responses to the recorded ones, see
+    /// [`http::recording`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload does not exist and
+    /// [`HostError::InvalidRequest`] if the recording can't be read or the
+    /// workload serves no HTTP requests.
+    fn replay(
+        &self,
+        request: ReplayRequest,
+    ) -> impl Future<Output = HostResult<http::ReplayReport>>;
+    /// Return a report of the host's version, plugins and policy
+    /// configuration signed with its identity key, see [`attestation`].
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the nonce is empty and
+    /// [`HostError::Internal`] if TEE evidence cannot be collected.
+    fn attestation(
+        &self,
+        request: AttestationRequest,
+    ) -> impl Future<Output = HostResult<SignedAttestationReport>>;
+    /// Take a snapshot of the running workloads, their routes and compiled
+    /// components, which a restarted host restores with [`Host::restore`].
+    /// See [`snapshot`].
+    fn host_snapshot(&self) -> impl Future<Output = HostResult<HostSnapshot>>;
+    /// Pre-stage a component: compile it ahead of time without starting it, so
+    /// workloads started later with the same bytes, e.g. when scaling out for
+    /// a traffic spike, don't wait for compilation. The component is kept
+    /// under its reference while the host runs, until it is unstaged or
+    /// staged again.
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the reference is empty and
+    /// [`HostError::InvalidWorkload`] if the bytes are not a component the
+    /// host can run.
+    fn component_stage(
+        &self,
+        request: ComponentStageRequest,
+    ) -> impl Future<Output = HostResult<StagedComponent>>;
+    /// Return the component staged under a reference, if any.
+    fn component_staged(
+        &self,
+        reference: String,
+    ) -> impl Future<Output = HostResult<Option<StagedComponent>>>;
+    /// List the staged components by reference.
+    fn component_staged_list(&self) -> impl Future<Output = HostResult<Vec<StagedComponent>>>;
+    /// Drop a staged component. Workloads using it keep running.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if no component is staged under the
+    /// reference.
+    fn component_unstage(&self, reference: String) -> impl Future<Output = HostResult<()>>;
 }
 
 // Helper trait impl that helps with Arc-ing the Host
 impl<T: HostApi> HostApi for Arc<T> {
-    async fn heartbeat(&self) -> anyhow::Result<HostHeartbeat> {
+    async fn heartbeat(&self) -> HostResult<HostHeartbeat> {
         self.as_ref().heartbeat().await
     }
     async fn workload_start(
         &self,
         request: WorkloadStartRequest,
-    ) -> anyhow::Result<WorkloadStartResponse> {
+    ) -> HostResult<WorkloadStartResponse> {
         self.as_ref().workload_start(request).await
     }
+    async fn workload_update(
+        &self,
+        request: WorkloadUpdateRequest,
+    ) -> HostResult<WorkloadUpdateResponse> {
+        self.as_ref().workload_update(request).await
+    }
+    async fn workload_config_update(
+        &self,
+        request: WorkloadConfigUpdateRequest,
+    ) -> HostResult<WorkloadConfigUpdateResponse> {
+        self.as_ref().workload_config_update(request).await
+    }
     async fn workload_stop(
         &self,
         request: WorkloadStopRequest,
-    ) -> anyhow::Result<WorkloadStopResponse> {
+    ) -> HostResult<WorkloadStopResponse> {
         self.as_ref().workload_stop(request).await
     }
     async fn workload_status(
         &self,
         request: WorkloadStatusRequest,
-    ) -> anyhow::Result<WorkloadStatusResponse> {
+    ) -> HostResult<WorkloadStatusResponse> {
         self.as_ref().workload_status(request).await
     }
+    async fn workload_list(
+        &self,
+        request: WorkloadListRequest,
+    ) -> HostResult<Vec<WorkloadSummary>> {
+        self.as_ref().workload_list(request).await
+    }
+    async fn workload_invoke(
+        &self,
+        request: WorkloadInvokeRequest,
+    ) -> HostResult<WorkloadInvokeResponse> {
+        self.as_ref().workload_invoke(request).await
+    }
+    async fn plugin_invoke(&self, call: ForwardedCall) -> HostResult<Vec<Val>> {
+        self.as_ref().plugin_invoke(call).await
+    }
+    async fn workload_logs(&self, query: logs::LogQuery) -> HostResult<logs::LogTail> {
+        self.as_ref().workload_logs(query).await
+    }
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instances(
+        &self,
+        workload_id: &str,
+    ) -> HostResult<Vec<crate::engine::debug::InstanceInfo>> {
+        self.as_ref().workload_instances(workload_id).await
+    }
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instance_dump(
+        &self,
+        request: WorkloadInstanceDumpRequest,
+    ) -> HostResult<Vec<u8>> {
+        self.as_ref().workload_instance_dump(request).await
+    }
+    async fn volume_create(&self, request: VolumeCreateRequest) -> HostResult<VolumeInfo> {
+        self.as_ref().volume_create(request).await
+    }
+    async fn volume_list(&self) -> HostResult<Vec<VolumeInfo>> {
+        self.as_ref().volume_list().await
+    }
+    async fn volume_inspect(&self, request: VolumeInspectRequest) -> HostResult<VolumeInfo> {
+        self.as_ref().volume_inspect(request).await
+    }
+    async fn volume_delete(&self, request: VolumeDeleteRequest) -> HostResult<()> {
+        self.as_ref().volume_delete(request).await
+    }
+    async fn route_list(&self) -> HostResult<RoutingTable> {
+        self.as_ref().route_list().await
+    }
+    async fn http_introspect(&self) -> HostResult<HttpIntrospection> {
+        self.as_ref().http_introspect().await
+    }
+    async fn route_pin(&self, pin: http::RoutePin) -> HostResult<()> {
+        self.as_ref().route_pin(pin).await
+    }
+    async fn route_unpin(&self, request: RouteUnpinRequest) -> HostResult<()> {
+        self.as_ref().route_unpin(request).await
+    }
+    async fn route_hold(&self, hold: http::RouteHold) -> HostResult<()> {
+        self.as_ref().route_hold(hold).await
+    }
+    async fn route_release(&self, request: RouteReleaseRequest) -> HostResult<()> {
+        self.as_ref().route_release(request).await
+    }
+    async fn route_invalidate(&self, request: RouteInvalidateRequest) -> HostResult<u64> {
+        self.as_ref().route_invalidate(request).await
+    }
+    async fn route_weight(&self, weight: http::RouteWeight) -> HostResult<()> {
+        self.as_ref().route_weight(weight).await
+    }
+    async fn http_listener_list(&self) -> HostResult<HttpListeners> {
+        self.as_ref().http_listener_list().await
+    }
+    async fn http_listener_add(&self, addr: SocketAddr) -> HostResult<()> {
+        self.as_ref().http_listener_add(addr).await
+    }
+    async fn http_listener_remove(&self, addr: SocketAddr) -> HostResult<()> {
+        self.as_ref().http_listener_remove(addr).await
+    }
+    async fn http_tls_reload(&self, files: Option<http::TlsFiles>) -> HostResult<()> {
+        self.as_ref().http_tls_reload(files).await
+    }
+    async fn http_recording_start(&self, recording: http::Recording) -> HostResult<()> {
+        self.as_ref().http_recording_start(recording).await
+    }
+    async fn http_recording_stop(&self, workload_id: String) -> HostResult<http::RecordingStatus> {
+        self.as_ref().http_recording_stop(workload_id).await
+    }
+    async fn http_recording_list(&self) -> HostResult<Vec<http::RecordingStatus>> {
+        self.as_ref().http_recording_list().await
+    }
+    async fn replay(&self, request: ReplayRequest) -> HostResult<http::ReplayReport> {
+        self.as_ref().replay(request).await
+    }
+    async fn attestation(
+        &self,
+        request: AttestationRequest,
+    ) -> HostResult<SignedAttestationReport> {
+        self.as_ref().attestation(request).await
+    }
+    async fn host_snapshot(&self) -> HostResult<HostSnapshot> {
+        self.as_ref().host_snapshot().await
+    }
+    async fn component_stage(&self, request: ComponentStageRequest) -> HostResult<StagedComponent> {
+        self.as_ref().component_stage(request).await
+    }
+    async fn component_staged(&self, reference: String) -> HostResult<Option<StagedComponent>> {
+        self.as_ref().component_staged(reference).await
+    }
+    async fn component_staged_list(&self) -> HostResult<Vec<StagedComponent>> {
+        self.as_ref().component_staged_list().await
+    }
+    async fn component_unstage(&self, reference: String) -> HostResult<()> {
+        self.as_ref().component_unstage(reference).await
+    }
 }
 
 /// Internal representation of a workload's state within the host.
@@ -152,17 +637,102 @@ pub enum HostWorkload {
     Starting,
     // Boxed to reduce size of the enum
     Running(Box<ResolvedWorkload>),
+    /// Every job component finished successfully, with a status message
+    Completed(Box<ResolvedWorkload>, String),
+    /// A job component failed, with the error
+    Failed(Box<ResolvedWorkload>, String),
     Stopping,
     Error,
 }
 
+impl HostWorkload {
+    /// Returns the namespace of the workload, or `None` while it is starting
+    /// or stopping.
+    fn namespace(&self) -> Option<&str> {
+        match self {
+            HostWorkload::Running(workload)
+            | HostWorkload::Completed(workload, _)
+            | HostWorkload::Failed(workload, _) => Some(workload.namespace()),
+            _ => None,
+        }
+    }
+
+    /// Returns the status of the workload with ID `workload_id`, with its
+    /// usage if the host has `cgroups` and its activity, with those of
+    /// `routes` sending requests to it, once it started.
+    async fn status(
+        &self,
+        workload_id: String,
+        cgroups: Option<&Cgroups>,
+        routes: &[http::RouteEntry],
+    ) -> WorkloadStatus {
+        let workload_state: WorkloadState = self.into();
+        let message = match self {
+            HostWorkload::Completed(_, message) | HostWorkload::Failed(_, message) => {
+                message.clone()
+            }
+            HostWorkload::Running(resolved) => {
+                let warnings: Vec<String> = resolved
+                    .memory_warning()
+                    .into_iter()
+                    .chain(resolved.crash_loops().iter().map(|crash_loop| {
+                        format!(
+                            "component '{}' is crash looping after {} consecutive failures",
+                            crash_loop.component_id, crash_loop.consecutive_failures
+                        )
+                    }))
+                    .collect();
+                if warnings.is_empty() {
+                    format!("Workload is {workload_state:?}")
+                } else {
+                    format!("Workload is {workload_state:?}, {}", warnings.join(", "))
+                }
+            }
+            _ => format!("Workload is {workload_state:?}"),
+        };
+        let (components, activity) = match self {
+            HostWorkload::Running(resolved)
+            | HostWorkload::Completed(resolved, _)
+            | HostWorkload::Failed(resolved, _) => {
+                let mut activity = resolved.activity().await;
+                activity.routes = routes
+                    .iter()
+                    .filter(|route| route.workload_id == workload_id)
+                    .cloned()
+                    .collect();
+                (resolved.provenance().to_vec(), Some(activity))
+            }
+            _ => (Vec::new(), None),
+        };
+        WorkloadStatus {
+            usage: cgroups.and_then(|cgroups| cgroups.workload_usage(&workload_id)),
+            workload_id,
+            workload_state,
+            message,
+            components,
+            activity,
+        }
+    }
+
+    /// Returns the resolved workload, if it was started.
+    fn into_resolved(self) -> Option<ResolvedWorkload> {
+        match self {
+            HostWorkload::Running(rw)
+            | HostWorkload::Completed(rw, _)
+            | HostWorkload::Failed(rw, _) => Some(*rw),
+            _ => None,
+        }
+    }
+}
+
 impl From<&HostWorkload> for WorkloadState {
     fn from(hw: &HostWorkload) -> Self {
         match hw {
             HostWorkload::Starting => WorkloadState::Starting,
             HostWorkload::Running(_) => WorkloadState::Running,
+            HostWorkload::Completed(..) => WorkloadState::Completed,
             HostWorkload::Stopping => WorkloadState::Stopping,
-            HostWorkload::Error => WorkloadState::Error,
+            HostWorkload::Error | HostWorkload::Failed(..) => WorkloadState::Error,
         }
     }
 }
@@ -191,6 +761,63 @@ pub struct Host {
     system_monitor: Arc<RwLock<SystemMonitor>>,
     // endpoints: HashMap<String, EndpointConfiguration>
     pub(crate) http_handler: std::sync::Arc<dyn crate::host::http::HostHandler>,
+    /// Store used to resolve `${secret:NAME}` references in workload configuration
+    secret_store: Option<Arc<dyn SecretStore>>,
+    /// Environment variables `${env:NAME}` references in workload configuration may read
+    env_allowlist: EnvAllowlist,
+    /// Named volumes that workloads can mount
+    volumes: Arc<VolumeManager>,
+    /// Devices given to workloads
+    devices: DeviceManager,
+    /// Sinks that receive workload lifecycle events
+    events: LifecycleEvents,
+    /// Captures what the components of workloads log
+    logs: logs::WorkloadLogs,
+    /// Gauges recorded on every heartbeat
+    metrics: HostMetrics,
+    /// Records the invocations of the components of workloads
+    workload_metrics: Arc<WorkloadMetrics>,
+    /// Serves the metrics of the host to Prometheus
+    #[cfg(feature = "prometheus-metrics")]
+    metrics_endpoint: Option<prometheus::MetricsEndpoint>,
+    /// Exports the spans of the requests the host serves
+    #[cfg(feature = "otlp-tracing")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    /// The key attestation reports are signed with
+    identity: HostIdentity,
+    /// Security-relevant configuration listed in attestation reports
+    policies: BTreeMap<String, String>,
+    tee_evidence: Option<Arc<dyn TeeEvidenceProvider>>,
+    /// Changed while the host runs when its configuration is reloaded
+    limits: std::sync::RwLock<HostLimits>,
+    /// Resources reserved by each workload, checked against the limits
+    reservations: std::sync::Mutex<Reservations>,
+    /// Workloads as requested, before configuration references are resolved,
+    /// in the order they started
+    definitions: std::sync::Mutex<Vec<WorkloadSnapshot>>,
+    /// Components compiled ahead of the workloads using them, by reference
+    staged: std::sync::Mutex<BTreeMap<String, StagedComponent>>,
+    /// Set once the host stops accepting workloads before shutting down
+    draining: AtomicBool,
+    /// Rollouts started since the host started, by ID
+    rollouts: std::sync::Mutex<BTreeMap<String, rollout::RolloutStatus>>,
+    /// Used to pull the components of workloads given as OCI images
+    #[cfg(feature = "oci")]
+    oci_config: crate::oci::OciConfig,
+    /// Forwards calls to interfaces no plugin provides to peers
+    forwarding: Option<Forwarding>,
+    /// Reloads components from local files when they change
+    hot_reload: Option<hot_reload::HotReload>,
+    /// Receives the workloads to stop for crash looping, see
+    /// [`crate::engine::crash_loop`]
+    crash_loop_stops: tokio::sync::mpsc::UnboundedSender<CrashLoopStop>,
+    /// Taken by the task stopping them once the host starts
+    crash_loop_stopped:
+        std::sync::Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<CrashLoopStop>>>,
+    /// Where the state of the host is persisted, see [`state`]
+    state_file: Option<state::StateFile>,
+    /// Notified when the workloads or routes of the host change
+    state_changed: Arc<tokio::sync::Notify>,
 }
 
 impl Host {
@@ -203,6 +830,8 @@ impl Host {
     ///
     /// This method must be called before the host can accept workloads.
     /// It starts all registered plugins and prepares the host for operation.
+    /// A host with a state file restores the state it finds there with
+    /// [`Host::start_restored`], see [`state`].
     ///
     /// # Returns
     /// An `Arc` wrapped host ready to accept workloads.
@@ -210,27 +839,163 @@ impl Host {
     /// # Errors
     /// Returns an error if any plugin fails to start.
     pub async fn start(self) -> anyhow::Result<Arc<Self>> {
+        let persisted = match &self.state_file {
+            Some(state_file) => state_file.read().await,
+            None => None,
+        };
+        if let Some(snapshot) = persisted {
+            let (host, report) = self.start_restored(snapshot).await?;
+            info!(
+                restored = report.restored.len(),
+                failed = report.failed.len(),
+                "restored host state"
+            );
+            return Ok(host);
+        }
+        self.start_metrics().await?;
         self.http_handler
             .start()
             .await
             .context("failed to start HTTP handler")?;
+        self.start_plugins().await?;
+        if let Some(metering) = self.engine.metering() {
+            metering.spawn_export();
+        }
+        let host = Arc::new(self);
+        if let Some(hot_reload) = host.hot_reload {
+            hot_reload.spawn(Arc::downgrade(&host));
+        }
+        host.spawn_crash_loop_stops();
+        host.spawn_state_file();
+        Ok(host)
+    }
+
+    /// Starts the host like [`Host::start`] after restoring `snapshot`, see
+    /// [`Host::restore`]. The HTTP handler only starts accepting requests
+    /// once the routes and pins of the snapshot are registered, so no request
+    /// is refused while the workloads start. The state file of the host, if
+    /// it has one, is written from then on, see [`state`].
+    ///
+    /// # Errors
+    /// Returns an error if a plugin or the HTTP handler fails to start.
+    pub async fn start_restored(
+        self,
+        snapshot: HostSnapshot,
+    ) -> anyhow::Result<(Arc<Self>, RestoreReport)> {
+        self.start_metrics().await?;
+        self.start_plugins().await?;
+        if let Some(metering) = self.engine.metering() {
+            metering.spawn_export();
+        }
+        let host = Arc::new(self);
+        if let Some(hot_reload) = host.hot_reload {
+            hot_reload.spawn(Arc::downgrade(&host));
+        }
+        host.spawn_crash_loop_stops();
+        let report = host.restore(snapshot).await?;
+        host.spawn_state_file();
+        host.http_handler
+            .start()
+            .await
+            .context("failed to start HTTP handler")?;
+        Ok((host, report))
+    }
+
+    /// Keeps the state file of the host up to date, if it has one, see
+    /// [`state`].
+    fn spawn_state_file(self: &Arc<Self>) {
+        if let Some(state_file) = self.state_file.clone() {
+            state_file.spawn(Arc::downgrade(self), self.state_changed.clone());
+            // Written once as it starts, without the workloads that failed to restore
+            self.state_changed();
+        }
+    }
+
+    /// Has the state file of the host written again, see [`state`].
+    fn state_changed(&self) {
+        self.state_changed.notify_one();
+    }
+
+    /// Stops the workloads whose components fail as many times in a row as
+    /// the crash loop policy of the engine allows, in the background, see
+    /// [`crate::engine::crash_loop`].
+    fn spawn_crash_loop_stops(self: &Arc<Self>) {
+        let Some(mut stops) = self
+            .crash_loop_stopped
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        let host = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(stop) = stops.recv().await {
+                let Some(host) = host.upgrade() else {
+                    break;
+                };
+                host.stop_crash_loop(stop).await;
+            }
+        });
+    }
+
+    /// Stops a running workload crash looping, emitting a
+    /// [`LifecycleEventType::WorkloadFailed`] event with the reason.
+    async fn stop_crash_loop(&self, stop: CrashLoopStop) {
+        let CrashLoopStop {
+            workload_id,
+            reason,
+        } = stop;
+        let name = match self.workloads.read().await.get(&workload_id) {
+            Some(HostWorkload::Running(workload)) => workload.name().to_string(),
+            _ => return,
+        };
+        warn!(workload_id, reason, "stopping crash looping workload");
+        self.events.emit(
+            LifecycleEvent::new(
+                LifecycleEventType::WorkloadFailed,
+                &workload_id,
+                format!("workload stopped for crash looping, {reason}"),
+            )
+            .with_workload_name(name),
+        );
+        if let Err(e) = self
+            .workload_stop(WorkloadStopRequest { workload_id })
+            .await
+        {
+            warn!(err = %e, "failed to stop crash looping workload");
+        }
+    }
 
-        // Start all plugins, any errors means the host fails to start.
+    /// Starts serving metrics if the host was built with
+    /// `HostBuilder::with_metrics`.
+    async fn start_metrics(&self) -> anyhow::Result<()> {
+        #[cfg(feature = "prometheus-metrics")]
+        if let Some(endpoint) = &self.metrics_endpoint {
+            endpoint.start().await?;
+        }
+        Ok(())
+    }
+
+    /// Starts all plugins, any error means the host fails to start.
+    async fn start_plugins(&self) -> anyhow::Result<()> {
         for (id, plugin) in &self.plugins {
+            plugin.set_meter(opentelemetry::global::meter(plugin.id()));
             if let Err(e) = plugin.start().await {
                 tracing::error!(id = id, err = ?e, "failed to start plugin");
                 bail!(e)
             }
         }
-
-        Ok(Arc::new(self))
+        Ok(())
     }
 
-    /// Stop the host and shut down all plugins.
+    /// Stop the host, its workloads and all plugins.
     ///
-    /// Attempts to gracefully stop all plugins with a 3-second timeout
-    /// for each. Errors are logged but don't prevent other plugins from
-    /// being stopped.
+    /// Plugins are torn down in [`teardown_order`](crate::plugin::teardown_order),
+    /// see [the plugin docs](crate::plugin#teardown): every plugin is told the
+    /// host shuts down, running workloads are stopped, then every plugin is
+    /// stopped. Each plugin hook has a 3-second timeout. Errors are logged but
+    /// don't prevent other plugins from being stopped.
     ///
     /// # Returns
     /// Ok if the shutdown process completes (even with plugin errors).
@@ -240,23 +1005,73 @@ impl Host {
             .await
             .context("failed to stop HTTP handler")?;
 
-        // Stop all plugins, log errors but continue stopping others
-        for (id, plugin) in &self.plugins {
-            let stop_fut = plugin.stop();
-            match tokio::time::timeout(std::time::Duration::from_secs(3), stop_fut).await {
-                Ok(Err(e)) => {
-                    tracing::error!(id = id, err = ?e, "failed to stop plugin");
-                }
-                Err(_) => {
-                    tracing::error!(id = id, "plugin stop timed out after 3 seconds");
-                }
-                _ => {}
+        let plugins = crate::plugin::teardown_order(self.plugins.values());
+        for plugin in &plugins {
+            plugin_hook(plugin.id(), "on_host_shutdown", plugin.on_host_shutdown()).await;
+        }
+
+        let mut workload_ids: Vec<String> = self.workloads.read().await.keys().cloned().collect();
+        workload_ids.sort();
+        for workload_id in workload_ids {
+            if let Err(e) = self
+                .workload_stop(WorkloadStopRequest {
+                    workload_id: workload_id.clone(),
+                })
+                .await
+            {
+                warn!(
+                    workload_id,
+                    "failed to stop workload during host shutdown: {e}"
+                );
             }
         }
 
+        // Stop all plugins, log errors but continue stopping others
+        for plugin in &plugins {
+            plugin_hook(plugin.id(), "stop", plugin.stop()).await;
+        }
+
+        // Exports the usage of the workloads stopped above
+        if let Some(metering) = self.engine.metering() {
+            metering.stop().await;
+        }
+
+        #[cfg(feature = "prometheus-metrics")]
+        if let Some(endpoint) = &self.metrics_endpoint {
+            endpoint.stop();
+        }
+
+        // Exports the spans of the requests drained above
+        #[cfg(feature = "otlp-tracing")]
+        if let Some(provider) = &self.tracer_provider
+            && let Err(e) = provider.shutdown()
+        {
+            warn!(err = ?e, "failed to export remaining spans");
+        }
+
         Ok(())
     }
 
+    /// Shuts the host down gracefully, for rolling restarts of the host itself.
+    ///
+    /// The host [drains](Host::drain) and stops accepting HTTP connections,
+    /// while open connections finish the requests in flight and close. Once
+    /// no request or invocation is in flight, or after `deadline` at the
+    /// latest, the host [stops](Host::stop): its workloads are stopped,
+    /// aborting the invocations still in flight, and only then its plugins.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP handler fails to stop.
+    pub async fn shutdown(self: Arc<Self>, deadline: Duration) -> anyhow::Result<()> {
+        self.drain();
+        self.http_handler
+            .stop()
+            .await
+            .context("failed to stop HTTP handler")?;
+        self.wait_idle(deadline).await;
+        self.stop().await
+    }
+
     /// Get a label value by key.
     ///
     /// # Arguments
@@ -268,6 +1083,77 @@ impl Host {
         self.labels.get(label.as_ref())
     }
 
+    /// Returns the current limits of the host.
+    pub fn limits(&self) -> HostLimits {
+        self.limits
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the limits of the host. Workloads that already run are not
+    /// affected, see [`limits`].
+    pub fn set_limits(&self, limits: HostLimits) {
+        self.http_handler.set_limits(&limits);
+        *self
+            .limits
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = limits;
+    }
+
+    /// Puts the host in drain mode before it stops: new workloads are
+    /// rejected and heartbeats report the host as draining, so schedulers
+    /// place workloads elsewhere. Running workloads keep serving.
+    pub fn drain(&self) {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            info!(host_id = %self.id, "host is draining, new workloads are rejected");
+        }
+    }
+
+    /// Returns whether the host is in drain mode, see [`Host::drain`].
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the HTTP handler has no requests in flight and the
+    /// workloads no invocations, e.g. of messages, for at most `timeout`.
+    /// Returns whether it became idle in time.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let requests = || {
+            let (_, by_namespace) = self.http_handler.open_connections();
+            by_namespace.values().sum::<usize>()
+        };
+        let invocations = || async {
+            self.workloads
+                .read()
+                .await
+                .values()
+                .map(|workload| match workload {
+                    HostWorkload::Running(resolved)
+                    | HostWorkload::Completed(resolved, _)
+                    | HostWorkload::Failed(resolved, _) => resolved.in_flight_invocations(),
+                    _ => 0,
+                })
+                .sum::<u64>()
+        };
+        let idle = async {
+            while requests() > 0 || invocations().await > 0 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        match tokio::time::timeout(timeout, idle).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!(
+                    requests = requests(),
+                    invocations = invocations().await,
+                    "requests still in flight after draining for {timeout:?}"
+                );
+                false
+            }
+        }
+    }
+
     /// Get the unique identifier for this host.
     ///
     /// # Returns
@@ -284,6 +1170,137 @@ impl Host {
         &self.friendly_name
     }
 
+    /// Returns the Ed25519 public key attestation reports are signed with.
+    pub fn public_key(&self) -> &[u8] {
+        self.identity.public_key()
+    }
+
+    /// Returns the namespace of a workload, or `None` if the workload is not
+    /// found or still starting.
+    pub async fn workload_namespace(&self, workload_id: &str) -> Option<String> {
+        self.workloads
+            .read()
+            .await
+            .get(workload_id)?
+            .namespace()
+            .map(str::to_string)
+    }
+
+    /// Returns a workload whose components can be invoked.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload does not exist and
+    /// [`HostError::InvalidWorkload`] if it is starting or stopping.
+    async fn resolved_workload(&self, workload_id: &str) -> HostResult<ResolvedWorkload> {
+        match self.workloads.read().await.get(workload_id) {
+            Some(
+                HostWorkload::Running(rw)
+                | HostWorkload::Completed(rw, _)
+                | HostWorkload::Failed(rw, _),
+            ) => Ok(rw.as_ref().clone()),
+            Some(_) => Err(HostError::InvalidWorkload(format!(
+                "workload {workload_id} is not running"
+            ))),
+            None => Err(HostError::NotFound(format!("workload {workload_id}"))),
+        }
+    }
+
+    /// Replaces the instances of `pool`, of the component serving HTTP in the
+    /// workload `workload_id`, with those of `replacement_id` in the steps of
+    /// `strategy`. Every step shifts the requests of the routes to the
+    /// replacement in proportion to its instances, then waits up to
+    /// `drain_timeout` for the old instances it stopped to finish their
+    /// invocations. The replacement serves the routes alone afterwards.
+    async fn roll_instances(
+        &self,
+        workload_id: &str,
+        replacement_id: &str,
+        strategy: RollingUpdate,
+        pool: &Arc<InstancePool>,
+        drain_timeout: Duration,
+    ) {
+        let replacement_pool = match self.resolved_workload(replacement_id).await {
+            Ok(replacement) => replacement.http_instance_pool().await,
+            Err(_) => None,
+        };
+        let pool_size = pool.max_instances();
+        let steps = strategy.plan(pool_size);
+        let (mut instances, mut replacement_instances) = (pool_size, 0);
+        for (step, RolloutStep { start, stop }) in steps.iter().copied().enumerate() {
+            replacement_instances += start;
+            if let Some(replacement_pool) = &replacement_pool {
+                replacement_pool.limit_instances(Some(replacement_instances));
+            }
+            self.http_handler.set_rollout(
+                workload_id,
+                Some(http::RolloutSplit {
+                    instances,
+                    replacement_instances,
+                }),
+            );
+            instances -= stop;
+            pool.limit_instances(Some(instances));
+            self.http_handler.set_rollout(
+                workload_id,
+                Some(http::RolloutSplit {
+                    instances,
+                    replacement_instances,
+                }),
+            );
+
+            let drained = tokio::time::timeout(drain_timeout, async {
+                while pool.stats().instances > instances {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await;
+            if drained.is_err() {
+                warn!(
+                    workload_id,
+                    instances, "old instances did not drain in time, continuing the rolling update"
+                );
+            }
+            info!(
+                workload_id,
+                replacement_id,
+                instances,
+                replacement_instances,
+                "rolling update step {} of {} done",
+                step + 1,
+                steps.len()
+            );
+        }
+        if let Some(replacement_pool) = &replacement_pool {
+            replacement_pool.limit_instances(None);
+        }
+        self.http_handler.set_rollout(workload_id, None);
+    }
+
+    /// Returns the address metrics are served on, see
+    /// [`HostBuilder::with_metrics`], or `None` if they are not or the host
+    /// didn't start yet.
+    #[cfg(feature = "prometheus-metrics")]
+    pub fn metrics_addr(&self) -> Option<std::net::SocketAddr> {
+        self.metrics_endpoint
+            .as_ref()
+            .and_then(prometheus::MetricsEndpoint::local_addr)
+    }
+
+    /// Returns the calls forwarded to peers since the host started, by the
+    /// instance they called, see [`HostBuilder::with_call_forwarder`].
+    pub fn forwarded_calls(&self) -> BTreeMap<String, ForwardingStats> {
+        self.forwarding
+            .as_ref()
+            .map(Forwarding::stats)
+            .unwrap_or_default()
+    }
+
+    /// Returns a receiver of the lifecycle, instance, invocation and route
+    /// events the host emits from now on, see [`events`].
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<Arc<LifecycleEvent>> {
+        self.events.subscribe()
+    }
+
     /// Returns the WIT (imports, exports) that this host can provide to any component.
     ///
     /// Put another way, this represents a simplified version of the host world. For
@@ -315,19 +1332,8 @@ impl Host {
     /// and other imports that are unsatisfied will be rejected.
     pub fn wit_world(&self) -> WitWorld {
         let mut imports = HashSet::new();
-        // The host provides wasi@0.2 interfaces other than wasi:http
-        // <https://docs.rs/wasmtime-wasi/36.0.2/wasmtime_wasi/p2/index.html#wasip2-interfaces>
-        let mut exports = HashSet::from([
-            "wasi:http/types,incoming-handler,outgoing-handler@0.2.0".into(),
-            "wasi:io/poll,error,streams@0.2.0".into(),
-            "wasi:clocks/monotonic-clock,wall-time@0.2.0".into(),
-            "wasi:random/random@0.2.0".into(),
-            "wasi:cli/environment,exit,stderr,stdin,stdout,terminal-input,terminal-output,terminal-stderr,terminal-stdin,terminal-stdout@0.2.0".into(),
-            "wasi:clocks/monotonic-clock,wall-clock@0.2.0".into(),
-            "wasi:filesystem/preopens,types@0.2.0".into(),
-            "wasi:random/insecure-seed,insecure,random@0.2.0".into(),
-            "wasi:sockets/instance-network,ip-name-lookup,network,tcp-create-socket,tcp,udp-create-socket,udp@0.2.0".into(),
-        ]);
+        // The host provides wasi@0.2 interfaces, including wasi:http
+        let mut exports = crate::engine::builtin_interfaces();
 
         // Include imports and exports that plugins specify
         imports.extend(
@@ -344,6 +1350,28 @@ impl Host {
         WitWorld { imports, exports }
     }
 
+    /// Checks whether a component can run on this host without starting it.
+    ///
+    /// The component's imports are compared against the interfaces built into the
+    /// host and those provided by its registered plugins (see [`Host::wit_world`]).
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes of the component to check
+    ///
+    /// # Returns
+    /// A [`CompatibilityReport`] listing satisfied imports, missing interfaces and
+    /// version conflicts.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid component.
+    pub fn check_compatibility(
+        &self,
+        bytes: impl AsRef<[u8]>,
+    ) -> anyhow::Result<CompatibilityReport> {
+        let guest = self.engine.component_world(bytes)?;
+        Ok(self.wit_world().compatibility(&guest))
+    }
+
     /// Returns a three-tuple of (OS architecture, OS name, OS kernel)
     async fn get_system_info(&self) -> (String, String, String) {
         // Get OS information
@@ -365,10 +1393,534 @@ impl Host {
         let monitor = self.system_monitor.read().await;
         Ok(monitor.cpu_usage().global_usage)
     }
+
+    fn reservations(&self) -> std::sync::MutexGuard<'_, Reservations> {
+        self.reservations
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn definitions(&self) -> std::sync::MutexGuard<'_, Vec<WorkloadSnapshot>> {
+        self.definitions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn staged(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, StagedComponent>> {
+        self.staged
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Releases what a stopped, or failed, workload held: its reservation, its
+    /// definition and the compiled components no other workload uses.
+    fn forget_workload(&self, workload_id: &str) {
+        self.reservations().release(workload_id);
+        self.devices.release(workload_id);
+        self.definitions()
+            .retain(|definition| definition.workload_id != workload_id);
+        self.prune_compiled();
+        self.logs.forget(workload_id);
+        if let Some(cgroups) = self.engine.cgroups() {
+            cgroups.remove_workload(workload_id);
+            self.limit_cgroup_memory(cgroups);
+        }
+        self.state_changed();
+    }
+
+    /// Caps the memory of the host at what its workloads reserve, while it
+    /// runs workloads that all have a memory limit.
+    fn limit_cgroup_memory(&self, cgroups: &Cgroups) {
+        let reserved = self.reservations().usage(None);
+        let bounded = reserved.workloads > 0 && reserved.unbounded_instances == 0;
+        cgroups.limit_memory(bounded.then_some(reserved.memory_bytes));
+    }
+
+    /// Drops the compiled components no workload uses and that aren't staged.
+    fn prune_compiled(&self) {
+        let mut used: HashSet<String> = self
+            .definitions()
+            .iter()
+            .flat_map(|definition| component_digests(&definition.workload))
+            .collect();
+        used.extend(self.staged().values().map(|staged| staged.digest.clone()));
+        self.engine.retain_compiled(&used);
+    }
+
+    /// Starts the workloads of a snapshot taken with [`HostApi::host_snapshot`],
+    /// with the same IDs and in the same order, after loading its compiled
+    /// components. Workloads that fail to start are listed in the report, the
+    /// others keep running.
+    ///
+    /// # Errors
+    /// Returns an error if the host already runs workloads.
+    pub async fn restore(&self, snapshot: HostSnapshot) -> anyhow::Result<RestoreReport> {
+        ensure!(
+            self.workloads.read().await.is_empty(),
+            "a host can only be restored before it runs workloads"
+        );
+        let mut report = RestoreReport::default();
+        for (digest, artifact) in &snapshot.artifacts {
+            // SAFETY: snapshots are written by the host itself, see the snapshot module
+            match unsafe { self.engine.load_compiled_artifact(digest, &artifact.0) } {
+                Ok(()) => report.artifacts_loaded += 1,
+                // Compiled by another wasmtime version or engine configuration
+                Err(e) => debug!(digest, "compiling component again: {e:#}"),
+            }
+        }
+        // Pins apply as soon as their workload registers its route
+        for pin in snapshot.pins {
+            if let Err(e) = self.http_handler.pin_route(pin.clone()).await {
+                warn!(%pin, "failed to restore route pin: {e:#}");
+            }
+        }
+        for hold in snapshot.holds {
+            if let Err(e) = self.http_handler.hold_route(hold.clone()).await {
+                warn!(%hold, "failed to restore route hold: {e:#}");
+            }
+        }
+        for weight in snapshot.weights {
+            if let Err(e) = self.http_handler.set_route_weight(weight.clone()).await {
+                warn!(%weight, "failed to restore route weight: {e:#}");
+            }
+        }
+        for WorkloadSnapshot {
+            workload_id,
+            workload,
+        } in snapshot.workloads
+        {
+            match self
+                .workload_start(WorkloadStartRequest {
+                    workload_id: workload_id.clone(),
+                    workload,
+                })
+                .await
+            {
+                Ok(_) => report.restored.push(workload_id),
+                Err(e) => {
+                    warn!(workload_id, "failed to restore workload: {e}");
+                    report.failed.push((workload_id, e.to_string()));
+                }
+            }
+        }
+        // Drop compiled components no restored workload uses
+        self.prune_compiled();
+        info!(
+            restored = report.restored.len(),
+            failed = report.failed.len(),
+            artifacts = report.artifacts_loaded,
+            "restored host snapshot"
+        );
+        Ok(report)
+    }
+
+    /// Takes a snapshot of the host, see [`HostApi::host_snapshot`], with the
+    /// compiled components of its workloads if `with_artifacts` is set.
+    async fn snapshot(&self, with_artifacts: bool) -> HostResult<HostSnapshot> {
+        // Only running workloads are restored, completed jobs don't run again
+        let running: HashSet<String> = self
+            .workloads
+            .read()
+            .await
+            .iter()
+            .filter(|(_, workload)| matches!(workload, HostWorkload::Running(_)))
+            .map(|(workload_id, _)| workload_id.clone())
+            .collect();
+        let workloads: Vec<WorkloadSnapshot> = self
+            .definitions()
+            .iter()
+            .filter(|definition| running.contains(&definition.workload_id))
+            .cloned()
+            .collect();
+
+        let mut artifacts = BTreeMap::new();
+        for digest in workloads
+            .iter()
+            .filter(|_| with_artifacts)
+            .flat_map(|definition| component_digests(&definition.workload))
+        {
+            if let Some(artifact) = self.engine.compiled_artifact(&digest)? {
+                artifacts.insert(digest, Artifact(artifact.into()));
+            }
+        }
+
+        Ok(HostSnapshot {
+            format: snapshot::SNAPSHOT_FORMAT,
+            host_id: self.id.clone(),
+            host_version: self.version.clone(),
+            taken_at: chrono::Utc::now(),
+            routes: HostSnapshot::routes_of(&workloads),
+            pins: self.http_handler.route_pins().await,
+            holds: self.http_handler.route_holds().await,
+            weights: self.http_handler.route_weights().await,
+            workloads,
+            artifacts,
+        })
+    }
+
+    /// Brings the workloads of the host in line with `manifest`, see
+    /// [`crate::manifest`]: starts the workloads that don't run, replaces
+    /// those whose definition changed and stops those applied from a
+    /// manifest before, in its namespaces, that it no longer lists. Workloads
+    /// that fail are listed in the report, the others are applied.
+    ///
+    /// # Errors
+    /// Returns an error if a component file of the manifest can't be read,
+    /// before any workload is changed.
+    pub async fn apply_manifest(
+        &self,
+        manifest: &crate::manifest::Manifest,
+    ) -> anyhow::Result<crate::manifest::ApplyReport> {
+        let workloads = manifest.workloads().await?;
+        let definitions = self.definitions().clone();
+        let mut report = crate::manifest::ApplyReport::default();
+        for workload in workloads {
+            let name = format!("{}/{}", workload.namespace, workload.name);
+            let running = definitions.iter().find(|definition| {
+                definition.workload.namespace == workload.namespace
+                    && definition.workload.name == workload.name
+            });
+            let result = match running {
+                None => self
+                    .workload_start(WorkloadStartRequest::new(workload))
+                    .await
+                    .map(|_| &mut report.started),
+                Some(definition) if definition.workload == workload => Ok(&mut report.unchanged),
+                Some(definition) => self
+                    .workload_update(WorkloadUpdateRequest {
+                        workload_id: definition.workload_id.clone(),
+                        replacement: WorkloadStartRequest::new(workload),
+                        drain_timeout: None,
+                        rolling_update: None,
+                    })
+                    .await
+                    .map(|_| &mut report.updated),
+            };
+            match result {
+                Ok(outcome) => outcome.push(name),
+                Err(e) => {
+                    warn!(workload = name, "failed to apply workload: {e}");
+                    report.failed.push((name, e.to_string()));
+                }
+            }
+        }
+
+        let namespaces = manifest.namespaces();
+        for definition in &definitions {
+            let workload = &definition.workload;
+            if !workload
+                .annotations
+                .contains_key(crate::manifest::MANIFEST_ANNOTATION)
+                || !namespaces.contains(workload.namespace.as_str())
+                || manifest
+                    .workloads
+                    .iter()
+                    .any(|spec| spec.namespace == workload.namespace && spec.name == workload.name)
+            {
+                continue;
+            }
+            let name = format!("{}/{}", workload.namespace, workload.name);
+            match self
+                .workload_stop(WorkloadStopRequest {
+                    workload_id: definition.workload_id.clone(),
+                })
+                .await
+            {
+                Ok(_) => report.stopped.push(name),
+                Err(e) => {
+                    warn!(workload = name, "failed to stop workload: {e}");
+                    report.failed.push((name, e.to_string()));
+                }
+            }
+        }
+        info!(
+            started = report.started.len(),
+            updated = report.updated.len(),
+            stopped = report.stopped.len(),
+            failed = report.failed.len(),
+            "applied workload manifest"
+        );
+        Ok(report)
+    }
+
+    /// Returns the resources reserved by the workloads of `namespace`, or of
+    /// all workloads if `None`, and the connections they have open.
+    pub fn usage(&self, namespace: Option<&str>) -> Usage {
+        let mut usage = self.reservations().usage(namespace);
+        let (open, by_namespace) = self.http_handler.open_connections();
+        usage.connections = match namespace {
+            Some(namespace) => by_namespace.get(namespace).copied().unwrap_or_default(),
+            None => open,
+        };
+        usage
+    }
+
+    /// Records the headroom of every limited quota of the host.
+    fn record_headroom(&self) {
+        let limits = self.limits();
+        if limits.host().is_limited() {
+            self.metrics.record_headroom(
+                &self.id,
+                None,
+                &limits.host().headroom(&self.usage(None)),
+            );
+        }
+        for (namespace, quota) in limits.namespaces() {
+            self.metrics.record_headroom(
+                &self.id,
+                Some(namespace),
+                &quota.headroom(&self.usage(Some(namespace))),
+            );
+        }
+    }
+
+    /// Starts a workload for [`HostApi::workload_start`].
+    /// Pulls the components of a workload that have no bytes but an image,
+    /// taking the bytes of a component staged under the image reference
+    /// instead when the image is not pinned to a digest.
+    async fn pull_images(&self, workload: &mut Workload) -> HostResult<()> {
+        for component in &mut workload.components {
+            let Some(image) = component
+                .image
+                .as_ref()
+                .filter(|_| component.bytes.is_empty())
+            else {
+                continue;
+            };
+            if image.digest.is_none()
+                && let Some(staged) = self.staged().get(&image.reference)
+            {
+                debug!(reference = %image.reference, "using staged component");
+                component.bytes = staged.bytes.clone();
+                continue;
+            }
+            component.bytes = self.pull_image(image).await.map_err(|e| {
+                HostError::InvalidWorkload(format!(
+                    "failed to pull component image {}: {e:#}",
+                    image.reference
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "oci")]
+    async fn pull_image(&self, image: &ComponentImage) -> anyhow::Result<bytes::Bytes> {
+        let mut config = self.oci_config.clone();
+        match &image.credentials {
+            Some(RegistryCredentials::Basic { username, password }) => {
+                config.credentials = Some((username.clone(), password.clone()));
+                config.token = None;
+            }
+            Some(RegistryCredentials::Token { token }) => {
+                config.credentials = None;
+                config.token = Some(token.clone());
+            }
+            None => {}
+        }
+        let (bytes, digest) =
+            crate::oci::pull_verified_component(&image.reference, image.digest.as_deref(), config)
+                .await?;
+        debug!(reference = %image.reference, %digest, "pulled component image");
+        Ok(bytes.into())
+    }
+
+    #[cfg(not(feature = "oci"))]
+    async fn pull_image(&self, _image: &ComponentImage) -> anyhow::Result<bytes::Bytes> {
+        bail!("this host is built without the oci feature")
+    }
+
+    async fn start_workload(
+        &self,
+        request: WorkloadStartRequest,
+    ) -> HostResult<WorkloadStartResponse> {
+        if self.is_draining() {
+            return Err(HostError::ResourceExhausted(
+                "the host is draining and accepts no new workloads".to_string(),
+            ));
+        }
+
+        // Resolve `${env:..}` and `${secret:..}` references and env files before
+        // anything is stored
+        let definition = request.workload.clone();
+        let mut workload = request.workload;
+        interpolate::interpolate_workload(
+            &mut workload,
+            &self.env_allowlist,
+            self.secret_store.as_deref(),
+        )
+        .await?;
+        env_file::load_env_files(&mut workload, self.secret_store.as_deref()).await?;
+        self.pull_images(&mut workload).await?;
+        hot_reload::load_local_components(&mut workload).await?;
+        self.volumes
+            .acquire(&request.workload_id, &mut workload)
+            .await?;
+
+        // Store the workload with initial state, unless it exceeds a quota
+        let usage = Usage::of_workload(&workload, self.engine.max_memory_bytes());
+        {
+            let mut workloads = self.workloads.write().await;
+            let reserved = {
+                let mut reservations = self.reservations();
+                self.limits()
+                    .check(&workload.namespace, &usage, &reservations)
+                    .and_then(|()| self.devices.acquire(&request.workload_id, &mut workload))
+                    .map(|()| {
+                        reservations.reserve(&request.workload_id, &workload.namespace, usage)
+                    })
+            };
+            if reserved.is_ok() {
+                self.definitions().push(WorkloadSnapshot {
+                    workload_id: request.workload_id.clone(),
+                    workload: definition,
+                });
+            }
+            if let Err(e) = reserved {
+                drop(workloads);
+                self.volumes.release(&request.workload_id).await;
+                return Err(e);
+            }
+            workloads.insert(request.workload_id.clone(), HostWorkload::Starting);
+        }
+        if let Some(cgroups) = self.engine.cgroups() {
+            self.limit_cgroup_memory(cgroups);
+        }
+
+        let service_present = workload.service.is_some();
+
+        // Initialize the workload using the engine, receiving the unresolved workload
+        let resolved_workload = async {
+            let level = logs::configured_level(&workload.host_interfaces)
+                .map_err(|e| HostError::InvalidWorkload(format!("{e:#}")))?;
+            self.logs.set_level(&request.workload_id, level);
+            let unresolved_workload = self
+                .engine
+                .initialize_workload(&request.workload_id, workload)
+                .map_err(|e| HostError::from_anyhow_or(e, HostError::InvalidWorkload))?;
+
+            unresolved_workload
+                .with_forwarding(self.forwarding.clone())
+                .with_metrics(Some(self.workload_metrics.clone()))
+                .with_events(self.events.clone())
+                .with_logs(self.logs.clone())
+                .with_crash_loop_stops(self.crash_loop_stops.clone())
+                .resolve(Some(&self.plugins), self.http_handler.clone())
+                .await
+                .map_err(HostError::from)
+        }
+        .await;
+        let mut resolved_workload = match resolved_workload {
+            Ok(resolved_workload) => resolved_workload,
+            Err(e) => {
+                // The workload never ran, so it doesn't keep its volumes or reservation
+                self.volumes.release(&request.workload_id).await;
+                self.forget_workload(&request.workload_id);
+                return Err(e);
+            }
+        };
+
+        // If the service didn't run and we had one, warn
+        let service_executed = match resolved_workload.execute_service(self.events.clone()).await {
+            Ok(executed) => executed,
+            Err(e) => {
+                self.forget_workload(&request.workload_id);
+                return Err(e.into());
+            }
+        };
+        if service_executed != service_present {
+            warn!(
+                workload_id = request.workload_id,
+                "service did not properly execute"
+            );
+        }
+
+        let jobs = resolved_workload.jobs().await;
+        if !jobs.is_empty() {
+            self.run_jobs(request.workload_id.clone(), resolved_workload.clone(), jobs);
+        }
+
+        let components = resolved_workload.provenance().to_vec();
+
+        // Update the workload state to `Running`
+        self.workloads
+            .write()
+            .await
+            .entry(request.workload_id.clone())
+            .and_modify(|workload| {
+                *workload = HostWorkload::Running(Box::new(resolved_workload));
+            });
+        self.state_changed();
+
+        Ok(WorkloadStartResponse {
+            workload_status: WorkloadStatus {
+                workload_id: request.workload_id,
+                workload_state: WorkloadState::Running,
+                message: "Workload started successfully".to_string(),
+                usage: None,
+                components,
+                activity: None,
+            },
+        })
+    }
+
+    /// Runs the job components of a started workload in the background and
+    /// marks the workload completed or failed once they have all finished.
+    fn run_jobs(&self, workload_id: String, workload: ResolvedWorkload, jobs: Vec<(String, Job)>) {
+        let workloads = self.workloads.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let results = futures::future::join_all(
+                jobs.iter()
+                    .map(|(component_id, job)| workload.run_job(component_id, job)),
+            )
+            .await;
+            let errors: Vec<String> = results
+                .into_iter()
+                .filter_map(Result::err)
+                .map(|e| format!("{e:#}"))
+                .collect();
+
+            let mut workloads = workloads.write().await;
+            // The workload may have been stopped while its jobs were running
+            match workloads.remove(&workload_id) {
+                Some(HostWorkload::Running(resolved)) if errors.is_empty() => {
+                    info!(workload_id, jobs = jobs.len(), "workload jobs completed");
+                    let message = format!("{} job(s) completed successfully", jobs.len());
+                    events.emit(
+                        LifecycleEvent::new(
+                            LifecycleEventType::WorkloadCompleted,
+                            &workload_id,
+                            &message,
+                        )
+                        .with_workload_name(resolved.name()),
+                    );
+                    workloads.insert(workload_id, HostWorkload::Completed(resolved, message));
+                }
+                Some(HostWorkload::Running(resolved)) => {
+                    warn!(workload_id, ?errors, "workload jobs failed");
+                    let message = format!("job failed: {}", errors.join("; "));
+                    events.emit(
+                        LifecycleEvent::new(
+                            LifecycleEventType::WorkloadFailed,
+                            &workload_id,
+                            &message,
+                        )
+                        .with_workload_name(resolved.name()),
+                    );
+                    workloads.insert(workload_id, HostWorkload::Failed(resolved, message));
+                }
+                Some(other) => {
+                    workloads.insert(workload_id, other);
+                }
+                None => {}
+            }
+        });
+    }
 }
 
 impl HostApi for Host {
-    async fn heartbeat(&self) -> anyhow::Result<HostHeartbeat> {
+    async fn heartbeat(&self) -> HostResult<HostHeartbeat> {
         // Refresh system info before reporting
         {
             let mut monitor = self.system_monitor.write().await;
@@ -392,7 +1944,10 @@ impl HostApi for Host {
             let workload_count: u64 = workloads.len() as u64;
             let mut component_count: u64 = 0;
             for workload in workloads.values() {
-                if let HostWorkload::Running(workload) = workload {
+                if let HostWorkload::Running(workload)
+                | HostWorkload::Completed(workload, _)
+                | HostWorkload::Failed(workload, _) = workload
+                {
                     component_count += workload.component_count().await as u64;
                 }
             }
@@ -409,7 +1964,7 @@ impl HostApi for Host {
             exports.extend(world.exports.into_iter());
         }
 
-        Ok(HostHeartbeat {
+        let heartbeat = HostHeartbeat {
             id: self.id.clone(),
             hostname: self.hostname.clone(),
             friendly_name: self.friendly_name.clone(),
@@ -426,79 +1981,317 @@ impl HostApi for Host {
             workload_count,
             imports,
             exports,
-        })
+            draining: self.is_draining(),
+            devices: self.devices.status(),
+        };
+        self.metrics.record(&heartbeat);
+        self.metrics
+            .record_pools(&self.id, self.engine.execution_pools());
+        self.metrics
+            .record_outgoing_connections(&self.id, &self.http_handler.outgoing_connections());
+        self.metrics
+            .record_outgoing_circuits(&self.id, &self.http_handler.outgoing_circuits());
+        self.record_headroom();
+        Ok(heartbeat)
     }
 
     /// Start a workload
     async fn workload_start(
         &self,
         request: WorkloadStartRequest,
-    ) -> anyhow::Result<WorkloadStartResponse> {
-        // Store the workload with initial state
-        self.workloads
-            .write()
+    ) -> HostResult<WorkloadStartResponse> {
+        let workload_id = request.workload_id.clone();
+        let workload_name = request.workload.name.clone();
+        let result = self.start_workload(request).await;
+        let event = match &result {
+            Ok(_) => LifecycleEvent::new(
+                LifecycleEventType::WorkloadStarted,
+                workload_id,
+                "workload started",
+            ),
+            Err(e) => LifecycleEvent::new(
+                LifecycleEventType::WorkloadStartFailed,
+                workload_id,
+                e.to_string(),
+            ),
+        };
+        self.events.emit(event.with_workload_name(workload_name));
+        result
+    }
+
+    async fn workload_status(
+        &self,
+        request: WorkloadStatusRequest,
+    ) -> HostResult<WorkloadStatusResponse> {
+        let routes = self.http_handler.routes().await;
+        if let Some(workload) = self
+            .workloads
+            .read()
             .await
-            .insert(request.workload_id.clone(), HostWorkload::Starting);
+            .get(&request.workload_id)
+            .filter(|workload| {
+                request
+                    .namespace
+                    .as_deref()
+                    .is_none_or(|namespace| workload.namespace() == Some(namespace))
+            })
+        {
+            Ok(WorkloadStatusResponse {
+                workload_status: workload
+                    .status(request.workload_id, self.engine.cgroups(), &routes)
+                    .await,
+            })
+        } else {
+            Err(HostError::NotFound(format!(
+                "workload {}",
+                request.workload_id
+            )))
+        }
+    }
 
-        let service_present = request.workload.service.is_some();
+    async fn workload_list(
+        &self,
+        request: WorkloadListRequest,
+    ) -> HostResult<Vec<WorkloadSummary>> {
+        let routes = self.http_handler.routes().await;
+        let workloads = self.workloads.read().await;
+        let mut summaries = Vec::with_capacity(workloads.len());
+        for (workload_id, workload) in workloads.iter() {
+            let (namespace, name) = match workload {
+                HostWorkload::Running(resolved)
+                | HostWorkload::Completed(resolved, _)
+                | HostWorkload::Failed(resolved, _) => (
+                    resolved.namespace().to_string(),
+                    resolved.name().to_string(),
+                ),
+                _ => Default::default(),
+            };
+            if request
+                .namespace
+                .as_ref()
+                .is_some_and(|filter| namespace != *filter)
+            {
+                continue;
+            }
+            summaries.push(WorkloadSummary {
+                namespace,
+                name,
+                workload_status: workload
+                    .status(workload_id.clone(), self.engine.cgroups(), &routes)
+                    .await,
+            });
+        }
+        summaries.sort_by(|a, b| {
+            (&a.namespace, &a.name, &a.workload_status.workload_id).cmp(&(
+                &b.namespace,
+                &b.name,
+                &b.workload_status.workload_id,
+            ))
+        });
+        Ok(summaries)
+    }
 
-        // Initialize the workload using the engine, receiving the unresolved workload
-        let unresolved_workload = self
-            .engine
-            .initialize_workload(&request.workload_id, request.workload)?;
+    async fn workload_update(
+        &self,
+        request: WorkloadUpdateRequest,
+    ) -> HostResult<WorkloadUpdateResponse> {
+        let old = match self.workloads.read().await.get(&request.workload_id) {
+            Some(HostWorkload::Running(resolved)) => (**resolved).clone(),
+            Some(_) => {
+                return Err(HostError::InvalidRequest(format!(
+                    "workload {} is not running",
+                    request.workload_id
+                )));
+            }
+            None => {
+                return Err(HostError::NotFound(format!(
+                    "workload {}",
+                    request.workload_id
+                )));
+            }
+        };
+        let replacement = &request.replacement;
+        if (
+            replacement.workload.namespace.as_str(),
+            replacement.workload.name.as_str(),
+        ) != (old.namespace(), old.name())
+        {
+            return Err(HostError::InvalidRequest(format!(
+                "workload {} is {}/{}, the replacement is {}/{}",
+                request.workload_id,
+                old.namespace(),
+                old.name(),
+                replacement.workload.namespace,
+                replacement.workload.name
+            )));
+        }
+        if replacement.workload_id == request.workload_id {
+            return Err(HostError::InvalidRequest(
+                "the replacement needs a workload ID of its own".to_string(),
+            ));
+        }
+
+        // The old version keeps the requests of its routes until the new one
+        // has instances
+        let rolling = match request.rolling_update {
+            Some(strategy) => {
+                let Some(pool) = old.http_instance_pool().await else {
+                    return Err(HostError::InvalidRequest(format!(
+                        "a rolling update replaces the instances of an autoscaling component \
+                         serving HTTP, workload {} has none",
+                        request.workload_id
+                    )));
+                };
+                self.http_handler.set_rollout(
+                    &request.workload_id,
+                    Some(http::RolloutSplit {
+                        instances: pool.max_instances(),
+                        replacement_instances: 0,
+                    }),
+                );
+                Some((strategy, pool))
+            }
+            None => None,
+        };
 
-        let mut resolved_workload = unresolved_workload
-            .resolve(Some(&self.plugins), self.http_handler.clone())
-            .await?;
+        let workload_id = replacement.workload_id.clone();
+        let started = match self.workload_start(request.replacement).await {
+            Ok(started) => started,
+            Err(e) => {
+                self.http_handler.set_rollout(&request.workload_id, None);
+                return Err(e);
+            }
+        };
+        let drain_timeout = request
+            .drain_timeout
+            .unwrap_or(DEFAULT_UPDATE_DRAIN_TIMEOUT);
+        if let Some((strategy, pool)) = rolling {
+            info!(
+                workload_id,
+                replaced = request.workload_id,
+                "new workload version started, replacing the instances of the old one"
+            );
+            self.roll_instances(
+                &request.workload_id,
+                &workload_id,
+                strategy,
+                &pool,
+                drain_timeout,
+            )
+            .await;
+        } else {
+            info!(
+                workload_id,
+                replaced = request.workload_id,
+                "new workload version started, draining the old one"
+            );
+        }
 
-        // If the service didn't run and we had one, warn
-        if resolved_workload.execute_service().await? != service_present {
+        // Requests the old version received finish there, new ones already
+        // reach the new version
+        let drained = tokio::time::timeout(drain_timeout, async {
+            while old.in_flight_invocations() > 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        let abandoned_invocations = old.in_flight_invocations();
+        if drained.is_err() {
             warn!(
                 workload_id = request.workload_id,
-                "service did not properly execute"
+                abandoned_invocations, "old workload version did not drain in time, stopping it"
             );
         }
+        self.workload_stop(WorkloadStopRequest {
+            workload_id: request.workload_id.clone(),
+        })
+        .await?;
 
-        // Update the workload state to `Running`
-        self.workloads
-            .write()
-            .await
-            .entry(request.workload_id.clone())
-            .and_modify(|workload| {
-                *workload = HostWorkload::Running(Box::new(resolved_workload));
-            });
-
-        Ok(WorkloadStartResponse {
-            workload_status: WorkloadStatus {
-                workload_id: request.workload_id,
-                workload_state: WorkloadState::Running,
-                message: "Workload started successfully".to_string(),
-            },
+        self.events.emit(
+            LifecycleEvent::new(
+                LifecycleEventType::WorkloadUpdated,
+                &workload_id,
+                format!("workload replaced {}", request.workload_id),
+            )
+            .with_workload_name(old.name()),
+        );
+        Ok(WorkloadUpdateResponse {
+            workload_status: started.workload_status,
+            abandoned_invocations,
         })
     }
 
-    async fn workload_status(
+    async fn workload_config_update(
         &self,
-        request: WorkloadStatusRequest,
-    ) -> anyhow::Result<WorkloadStatusResponse> {
-        if let Some(workload) = self.workloads.read().await.get(&request.workload_id) {
-            let workload_state = workload.into();
-            Ok(WorkloadStatusResponse {
-                workload_status: WorkloadStatus {
-                    workload_id: request.workload_id,
-                    message: format!("Workload is {workload_state:?}"),
-                    workload_state,
-                },
-            })
-        } else {
-            anyhow::bail!("Workload not found: {}", request.workload_id)
+        request: WorkloadConfigUpdateRequest,
+    ) -> HostResult<WorkloadConfigUpdateResponse> {
+        if request.update.is_empty() {
+            return Err(HostError::InvalidRequest(
+                "the configuration update changes nothing".to_string(),
+            ));
+        }
+        let workload = match self.workloads.read().await.get(&request.workload_id) {
+            Some(HostWorkload::Running(resolved)) => (**resolved).clone(),
+            Some(_) => {
+                return Err(HostError::InvalidRequest(format!(
+                    "workload {} is not running",
+                    request.workload_id
+                )));
+            }
+            None => {
+                return Err(HostError::NotFound(format!(
+                    "workload {}",
+                    request.workload_id
+                )));
+            }
+        };
+
+        let (component_ids, service_recycled) = workload
+            .update_config(request.component_id.as_deref(), &request.update)
+            .await
+            .map_err(|e| HostError::InvalidWorkload(format!("{e:#}")))?;
+        if component_ids.is_empty() {
+            return Err(match request.component_id {
+                Some(component_id) => HostError::NotFound(format!(
+                    "component {component_id} of workload {} reading configuration",
+                    request.workload_id
+                )),
+                None => HostError::InvalidRequest(format!(
+                    "no component of workload {} reads configuration",
+                    request.workload_id
+                )),
+            });
         }
+
+        info!(
+            workload_id = request.workload_id,
+            components = component_ids.len(),
+            service_recycled,
+            "workload configuration updated"
+        );
+        self.events.emit(
+            LifecycleEvent::new(
+                LifecycleEventType::WorkloadConfigUpdated,
+                &request.workload_id,
+                format!(
+                    "configuration of {} component(s) updated, {} key(s) set, {} removed",
+                    component_ids.len(),
+                    request.update.set.len(),
+                    request.update.remove.len()
+                ),
+            )
+            .with_workload_name(workload.name()),
+        );
+        Ok(WorkloadConfigUpdateResponse {
+            component_ids,
+            service_recycled,
+        })
     }
 
     async fn workload_stop(
         &self,
         request: WorkloadStopRequest,
-    ) -> anyhow::Result<WorkloadStopResponse> {
+    ) -> HostResult<WorkloadStopResponse> {
         let has_workload = self
             .workloads
             .read()
@@ -516,14 +2309,11 @@ impl HostApi for Host {
                 // Insert Stopping state, extract the running workload if it was running
                 workloads
                     .insert(request.workload_id.clone(), HostWorkload::Stopping)
-                    .and_then(|hw| match hw {
-                        HostWorkload::Running(rw) => Some(*rw),
-                        _ => None,
-                    })
+                    .and_then(HostWorkload::into_resolved)
             };
 
             // Stop the workload:
-            // 1. Unbind from all plugins
+            // 1. Notify and unbind plugins
             // 2. Clean up resources (drop will handle wasmtime cleanup)
             // 3. Remove from active workloads
             if let Some(resolved_workload) = resolved_workload {
@@ -533,8 +2323,12 @@ impl HostApi for Host {
                     "stopping workload"
                 );
 
+                // Let plugins stop using the workload while it still runs
+                resolved_workload.notify_plugins_of_stop().await;
+
                 // Stop the service if running
                 resolved_workload.stop_service();
+                resolved_workload.stop_pre_init().await;
 
                 // Unbind all plugins from the workload
                 if let Err(e) = resolved_workload.unbind_all_plugins().await {
@@ -549,6 +2343,13 @@ impl HostApi for Host {
             // Remove the workload from the active workloads map
             // This will drop the workload and clean up wasmtime resources
             self.workloads.write().await.remove(&request.workload_id);
+            self.volumes.release(&request.workload_id).await;
+            self.forget_workload(&request.workload_id);
+            self.events.emit(LifecycleEvent::new(
+                LifecycleEventType::WorkloadStopped,
+                &request.workload_id,
+                "workload stopped",
+            ));
 
             debug!(
                 workload_id = request.workload_id,
@@ -568,9 +2369,428 @@ impl HostApi for Host {
                 workload_id: request.workload_id,
                 workload_state,
                 message,
+                usage: None,
+                components: Vec::new(),
+                activity: None,
             },
         })
     }
+
+    async fn workload_invoke(
+        &self,
+        request: WorkloadInvokeRequest,
+    ) -> HostResult<WorkloadInvokeResponse> {
+        let workload = self.resolved_workload(&request.workload_id).await?;
+
+        let component_id = match request.component_id {
+            Some(component_id) => component_id,
+            None => {
+                let components = workload.components();
+                let components = components.read().await;
+                let mut ids = components.keys();
+                match (ids.next(), ids.next()) {
+                    (Some(id), None) => id.to_string(),
+                    _ => {
+                        return Err(HostError::InvalidWorkload(format!(
+                            "workload {} has {} components, the component to invoke must be given",
+                            request.workload_id,
+                            components.len()
+                        )));
+                    }
+                }
+            }
+        };
+
+        debug!(
+            workload_id = request.workload_id,
+            component_id,
+            export = request.export,
+            "invoking component export"
+        );
+        let results = workload
+            .invoke(&component_id, &request.export, &request.params)
+            .await
+            .map_err(|e| {
+                if crate::engine::memory::is_memory_limit_exceeded(&e)
+                    || crate::engine::autoscale::is_overloaded(&e)
+                {
+                    HostError::ResourceExhausted(format!("{e:#}"))
+                } else {
+                    HostError::from(e)
+                }
+            })?;
+        Ok(WorkloadInvokeResponse { results })
+    }
+
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instances(
+        &self,
+        workload_id: &str,
+    ) -> HostResult<Vec<crate::engine::debug::InstanceInfo>> {
+        Ok(self
+            .resolved_workload(workload_id)
+            .await?
+            .instances()
+            .list())
+    }
+
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instance_dump(
+        &self,
+        request: WorkloadInstanceDumpRequest,
+    ) -> HostResult<Vec<u8>> {
+        /// How long an instance has to reach an epoch tick
+        const DUMP_TIMEOUT: Duration = Duration::from_secs(10);
+
+        let workload = self.resolved_workload(&request.workload_id).await?;
+        let dump = workload
+            .instances()
+            .request_dump(request.instance_id)
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))?
+            .ok_or_else(|| {
+                HostError::NotFound(format!(
+                    "instance {} of workload {}",
+                    request.instance_id, request.workload_id
+                ))
+            })?;
+        info!(
+            workload_id = request.workload_id,
+            instance_id = request.instance_id,
+            "interrupting instance to dump its core"
+        );
+        match tokio::time::timeout(DUMP_TIMEOUT, dump).await {
+            Ok(Ok(result)) => result.map_err(HostError::Internal),
+            Ok(Err(_)) => Err(HostError::Internal(anyhow::anyhow!(
+                "instance was dropped before it could be dumped"
+            ))),
+            Err(_) => Err(HostError::Internal(anyhow::anyhow!(
+                "instance did not reach an epoch tick within {DUMP_TIMEOUT:?}, it may be waiting on the host"
+            ))),
+        }
+    }
+
+    async fn plugin_invoke(&self, call: ForwardedCall) -> HostResult<Vec<Val>> {
+        let interface = WitInterface::from(call.instance.as_str());
+        let plugin = self
+            .plugins
+            .values()
+            .find(|plugin| plugin.world().includes_bidirectional(&interface))
+            .ok_or_else(|| HostError::NotFound(format!("plugin providing {}", call.instance)))?;
+        debug!(
+            plugin_id = plugin.id(),
+            workload_id = call.workload_id,
+            instance = call.instance,
+            function = call.function,
+            "serving forwarded call"
+        );
+        Ok(plugin.invoke(call).await?)
+    }
+
+    async fn workload_logs(&self, query: logs::LogQuery) -> HostResult<logs::LogTail> {
+        let running = self.workloads.read().await.contains_key(&query.workload_id);
+        let workload_id = query.workload_id.clone();
+        let tail = self.logs.query(query);
+        if !running && tail.lines.is_empty() {
+            return Err(HostError::NotFound(format!("workload {workload_id}")));
+        }
+        Ok(tail)
+    }
+
+    async fn volume_create(&self, request: VolumeCreateRequest) -> HostResult<VolumeInfo> {
+        self.volumes.create(request).await
+    }
+
+    async fn volume_list(&self) -> HostResult<Vec<VolumeInfo>> {
+        self.volumes.list().await
+    }
+
+    async fn volume_inspect(&self, request: VolumeInspectRequest) -> HostResult<VolumeInfo> {
+        self.volumes.inspect(&request.name).await
+    }
+
+    async fn volume_delete(&self, request: VolumeDeleteRequest) -> HostResult<()> {
+        self.volumes.delete(&request.name).await
+    }
+
+    async fn route_list(&self) -> HostResult<RoutingTable> {
+        Ok(RoutingTable {
+            routes: self.http_handler.routes().await,
+            pins: self.http_handler.route_pins().await,
+            holds: self.http_handler.route_holds().await,
+        })
+    }
+
+    async fn http_introspect(&self) -> HostResult<HttpIntrospection> {
+        let requests = self.http_handler.in_flight_requests();
+        Ok(HttpIntrospection {
+            routing: self.route_list().await?,
+            activity: http::inflight::route_activity(&requests),
+            requests,
+            open_connections: self.http_handler.open_connections().0,
+        })
+    }
+
+    async fn route_pin(&self, pin: http::RoutePin) -> HostResult<()> {
+        self.http_handler
+            .pin_route(pin)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))?;
+        self.state_changed();
+        Ok(())
+    }
+
+    async fn route_unpin(&self, request: RouteUnpinRequest) -> HostResult<()> {
+        if self
+            .http_handler
+            .unpin_route(&request.host, request.path.as_deref())
+            .await
+        {
+            self.state_changed();
+            Ok(())
+        } else {
+            Err(HostError::NotFound(format!(
+                "no pin for host {} and path {}",
+                request.host,
+                request.path.as_deref().unwrap_or("/")
+            )))
+        }
+    }
+
+    async fn route_hold(&self, hold: http::RouteHold) -> HostResult<()> {
+        self.http_handler
+            .hold_route(hold)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))?;
+        self.state_changed();
+        Ok(())
+    }
+
+    async fn route_release(&self, request: RouteReleaseRequest) -> HostResult<()> {
+        if self
+            .http_handler
+            .release_route(&request.host, request.path.as_deref())
+            .await
+        {
+            self.state_changed();
+            Ok(())
+        } else {
+            Err(HostError::NotFound(format!(
+                "no hold for host {} and path {}",
+                request.host,
+                request.path.as_deref().unwrap_or("/")
+            )))
+        }
+    }
+
+    async fn route_invalidate(&self, request: RouteInvalidateRequest) -> HostResult<u64> {
+        let entries = self
+            .http_handler
+            .invalidate_cache(request.host.as_deref(), request.path.as_deref());
+        info!(
+            host = request.host.as_deref().unwrap_or("*"),
+            path = request.path.as_deref().unwrap_or("/"),
+            entries,
+            "invalidated cached HTTP responses"
+        );
+        Ok(entries as u64)
+    }
+
+    async fn route_weight(&self, weight: http::RouteWeight) -> HostResult<()> {
+        if !self.http_handler.routes().await.iter().any(|entry| {
+            entry.workload_name == weight.workload_name && entry.route.weight.is_some()
+        }) {
+            return Err(HostError::NotFound(format!(
+                "no route of workload {} has a weight",
+                weight.workload_name
+            )));
+        }
+        self.http_handler
+            .set_route_weight(weight)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))?;
+        self.state_changed();
+        Ok(())
+    }
+
+    async fn http_listener_list(&self) -> HostResult<HttpListeners> {
+        Ok(HttpListeners {
+            addrs: self.http_handler.listeners().await,
+            tls: self.http_handler.tls_files().await,
+        })
+    }
+
+    async fn http_listener_add(&self, addr: SocketAddr) -> HostResult<()> {
+        if self.http_handler.listeners().await.contains(&addr) {
+            return Err(HostError::AlreadyExists(format!(
+                "the HTTP server already listens on {addr}"
+            )));
+        }
+        self.http_handler
+            .add_listener(addr)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))
+    }
+
+    async fn http_listener_remove(&self, addr: SocketAddr) -> HostResult<()> {
+        if self.http_handler.remove_listener(addr).await {
+            Ok(())
+        } else {
+            Err(HostError::NotFound(format!(
+                "the HTTP server does not listen on {addr}"
+            )))
+        }
+    }
+
+    async fn http_tls_reload(&self, files: Option<http::TlsFiles>) -> HostResult<()> {
+        self.http_handler
+            .reload_tls(files)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))
+    }
+
+    async fn http_recording_start(&self, recording: http::Recording) -> HostResult<()> {
+        if !self
+            .workloads
+            .read()
+            .await
+            .contains_key(recording.workload_id())
+        {
+            return Err(HostError::NotFound(format!(
+                "workload {}",
+                recording.workload_id()
+            )));
+        }
+        self.http_handler
+            .start_recording(recording)
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))
+    }
+
+    async fn http_recording_stop(&self, workload_id: String) -> HostResult<http::RecordingStatus> {
+        self.http_handler
+            .stop_recording(&workload_id)
+            .ok_or_else(|| HostError::NotFound(format!("recording of workload {workload_id}")))
+    }
+
+    async fn http_recording_list(&self) -> HostResult<Vec<http::RecordingStatus>> {
+        Ok(self.http_handler.recordings())
+    }
+
+    async fn replay(&self, request: ReplayRequest) -> HostResult<http::ReplayReport> {
+        if !self
+            .workloads
+            .read()
+            .await
+            .contains_key(&request.workload_id)
+        {
+            return Err(HostError::NotFound(format!(
+                "workload {}",
+                request.workload_id
+            )));
+        }
+        let invocations = http::recording::read_recording(&request.path)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))?;
+        self.http_handler
+            .replay(&request.workload_id, invocations, request.pace)
+            .await
+            .map_err(|e| HostError::InvalidRequest(format!("{e:#}")))
+    }
+
+    async fn host_snapshot(&self) -> HostResult<HostSnapshot> {
+        self.snapshot(true).await
+    }
+
+    async fn component_stage(&self, request: ComponentStageRequest) -> HostResult<StagedComponent> {
+        let ComponentStageRequest { reference, bytes } = request;
+        if reference.is_empty() {
+            return Err(HostError::InvalidRequest(
+                "a staged component needs a reference".to_string(),
+            ));
+        }
+        let engine = self.engine.clone();
+        let compiled = bytes.clone();
+        let digest = tokio::task::spawn_blocking(move || engine.precompile(&compiled))
+            .await
+            .context("failed to compile component")?
+            .map_err(|e| HostError::InvalidWorkload(format!("{e:#}")))?;
+        let staged = StagedComponent {
+            reference: reference.clone(),
+            digest,
+            bytes,
+            staged_at: chrono::Utc::now(),
+        };
+        let replaced = self.staged().insert(reference, staged.clone());
+        // A component staged before under the reference may no longer be used
+        if replaced.is_some() {
+            self.prune_compiled();
+        }
+        info!(
+            reference = staged.reference,
+            digest = staged.digest,
+            "staged component"
+        );
+        Ok(staged)
+    }
+
+    async fn component_staged(&self, reference: String) -> HostResult<Option<StagedComponent>> {
+        Ok(self.staged().get(&reference).cloned())
+    }
+
+    async fn component_staged_list(&self) -> HostResult<Vec<StagedComponent>> {
+        Ok(self.staged().values().cloned().collect())
+    }
+
+    async fn component_unstage(&self, reference: String) -> HostResult<()> {
+        if self.staged().remove(&reference).is_none() {
+            return Err(HostError::NotFound(format!(
+                "no component is staged as {reference}"
+            )));
+        }
+        self.prune_compiled();
+        Ok(())
+    }
+
+    async fn attestation(
+        &self,
+        request: AttestationRequest,
+    ) -> HostResult<SignedAttestationReport> {
+        if request.nonce.is_empty() {
+            return Err(HostError::InvalidRequest(
+                "attestation nonce must not be empty".to_string(),
+            ));
+        }
+        let public_key = self.identity.public_key().to_vec();
+        let tee_evidence = match &self.tee_evidence {
+            Some(provider) => Some(
+                provider
+                    .evidence(&attestation::report_data(&public_key, &request.nonce))
+                    .await
+                    .context("failed to collect TEE evidence")?,
+            ),
+            None => None,
+        };
+        let mut plugins: Vec<String> = self.plugins.keys().map(|id| id.to_string()).collect();
+        plugins.sort();
+
+        let report = AttestationReport {
+            host_id: self.id.clone(),
+            public_key: public_key.clone().into(),
+            version: self.version.clone(),
+            nonce: request.nonce,
+            issued_at: chrono::Utc::now(),
+            plugins,
+            labels: self.labels.clone().into_iter().collect(),
+            policies: self.policies.clone(),
+            tee_evidence,
+        };
+        let report =
+            serde_json::to_vec(&report).context("failed to serialize attestation report")?;
+        Ok(SignedAttestationReport {
+            signature: self.identity.sign(&report),
+            report,
+            public_key,
+        })
+    }
 }
 
 impl std::fmt::Debug for Host {
@@ -596,6 +2816,27 @@ pub struct HostBuilder {
     friendly_name: Option<String>,
     labels: HashMap<String, String>,
     http_handler: Option<Arc<dyn crate::host::http::HostHandler>>,
+    secret_store: Option<Arc<dyn SecretStore>>,
+    env_allowlist: EnvAllowlist,
+    volume_root: Option<std::path::PathBuf>,
+    volume_providers: Vec<(&'static str, Arc<dyn volumes::VolumeProvider>)>,
+    devices: Vec<Device>,
+    event_sinks: Vec<Arc<dyn LifecycleEventSink>>,
+    log_capacity: Option<usize>,
+    identity: Option<HostIdentity>,
+    identity_file: Option<std::path::PathBuf>,
+    policies: BTreeMap<String, String>,
+    tee_evidence: Option<Arc<dyn TeeEvidenceProvider>>,
+    limits: HostLimits,
+    #[cfg(feature = "oci")]
+    oci_config: Option<crate::oci::OciConfig>,
+    call_forwarder: Option<Arc<dyn CallForwarder>>,
+    hot_reload: Option<hot_reload::HotReload>,
+    state_file: Option<state::StateFile>,
+    #[cfg(feature = "prometheus-metrics")]
+    metrics_addr: Option<std::net::SocketAddr>,
+    #[cfg(feature = "otlp-tracing")]
+    tracing: Option<otlp_tracing::OtlpTracing>,
 }
 
 impl Default for HostBuilder {
@@ -608,6 +2849,27 @@ impl Default for HostBuilder {
             friendly_name: Default::default(),
             labels: Default::default(),
             http_handler: Default::default(),
+            secret_store: Default::default(),
+            env_allowlist: Default::default(),
+            volume_root: Default::default(),
+            volume_providers: Default::default(),
+            devices: Default::default(),
+            event_sinks: Default::default(),
+            log_capacity: Default::default(),
+            identity: Default::default(),
+            identity_file: Default::default(),
+            policies: Default::default(),
+            tee_evidence: Default::default(),
+            limits: Default::default(),
+            #[cfg(feature = "oci")]
+            oci_config: Default::default(),
+            call_forwarder: Default::default(),
+            hot_reload: Default::default(),
+            state_file: Default::default(),
+            #[cfg(feature = "prometheus-metrics")]
+            metrics_addr: None,
+            #[cfg(feature = "otlp-tracing")]
+            tracing: None,
         }
     }
 }
@@ -632,6 +2894,58 @@ impl HostBuilder {
         self
     }
 
+    /// Sets the store used to resolve `${secret:NAME}` references in workload
+    /// configuration and environment variables.
+    ///
+    /// Without a secret store, workloads that reference secrets fail to start.
+    pub fn with_secret_store(mut self, secret_store: Arc<dyn SecretStore>) -> Self {
+        self.secret_store = Some(secret_store);
+        self
+    }
+
+    /// Sets the environment variables of the host process that `${env:NAME}`
+    /// references in workload configuration and environment variables may read.
+    ///
+    /// Without an allowlist, workloads that reference environment variables
+    /// fail to start.
+    pub fn with_env_allowlist(mut self, env_allowlist: EnvAllowlist) -> Self {
+        self.env_allowlist = env_allowlist;
+        self
+    }
+
+    /// Sets the directory named volumes are kept in. Defaults to
+    /// `wash-volumes` in the system temporary directory, which may not survive
+    /// a reboot.
+    pub fn with_volume_root(mut self, root: impl Into<std::path::PathBuf>) -> Self {
+        self.volume_root = Some(root.into());
+        self
+    }
+
+    /// Prepares volumes of `kind`, one of `oci`, `nfs` and `s3`, with
+    /// `provider` instead of the default one, see [`volumes`].
+    pub fn with_volume_provider(
+        mut self,
+        kind: &'static str,
+        provider: Arc<dyn volumes::VolumeProvider>,
+    ) -> Self {
+        self.volume_providers.push((kind, provider));
+        self
+    }
+
+    /// Adds a sink, such as a webhook, that receives the
+    /// lifecycle events of the host's workloads.
+    pub fn with_event_sink(mut self, sink: Arc<dyn LifecycleEventSink>) -> Self {
+        self.event_sinks.push(sink);
+        self
+    }
+
+    /// Sets how many of the lines components log the host keeps for
+    /// [`HostApi::workload_logs`]. Defaults to [`logs::DEFAULT_CAPACITY`].
+    pub fn with_log_capacity(mut self, capacity: usize) -> Self {
+        self.log_capacity = Some(capacity);
+        self
+    }
+
     pub fn with_plugin<T: HostPlugin>(mut self, plugin: Arc<T>) -> anyhow::Result<Self> {
         let plugin_id = plugin.id();
 
@@ -685,6 +2999,97 @@ impl HostBuilder {
         self
     }
 
+    /// Sets the identity attestation reports are signed with. Without an
+    /// identity or identity file, a new identity is generated on build.
+    pub fn with_identity(mut self, identity: HostIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Keeps the identity of the host in `path` so it is stable across
+    /// restarts. The key is generated on the first build.
+    pub fn with_identity_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.identity_file = Some(path.into());
+        self
+    }
+
+    /// Lists a setting in attestation reports, e.g. `api_tokens=required`, so
+    /// control planes can check how the host is configured.
+    pub fn with_policy(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.policies.insert(key.into(), value.into());
+        self
+    }
+
+    /// Includes evidence from a trusted execution environment in attestation
+    /// reports.
+    pub fn with_tee_evidence(mut self, provider: Arc<dyn TeeEvidenceProvider>) -> Self {
+        self.tee_evidence = Some(provider);
+        self
+    }
+
+    /// Gives `device` to the workloads requesting it, see [`devices`].
+    pub fn with_device(mut self, device: Device) -> Self {
+        self.devices.push(device);
+        self
+    }
+
+    /// Sets the limits of the host, see [`limits`].
+    pub fn with_limits(mut self, limits: HostLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how components given as OCI images are pulled, see
+    /// [`ComponentImage`]. Credentials of an image take precedence over those
+    /// of the config. By default, artifacts are cached in a directory below
+    /// the temporary directory of the system.
+    #[cfg(feature = "oci")]
+    pub fn with_oci_config(mut self, config: crate::oci::OciConfig) -> Self {
+        self.oci_config = Some(config);
+        self
+    }
+
+    /// Forwards calls to the interfaces no plugin of the host provides to
+    /// peers providing them, instead of refusing the workloads importing
+    /// them, see [`crate::plugin::forward`].
+    pub fn with_call_forwarder(mut self, forwarder: Arc<dyn CallForwarder>) -> Self {
+        self.call_forwarder = Some(forwarder);
+        self
+    }
+
+    /// Reloads the components of workloads read from a local path when their
+    /// file changes, see [`hot_reload`].
+    pub fn with_hot_reload(mut self, hot_reload: hot_reload::HotReload) -> Self {
+        self.hot_reload = Some(hot_reload);
+        self
+    }
+
+    /// Persists the state of the host in `state_file` and restores it when
+    /// the host starts, see [`state`].
+    pub fn with_state_file(mut self, state_file: state::StateFile) -> Self {
+        self.state_file = Some(state_file);
+        self
+    }
+
+    /// Serves the metrics of the host, its workloads and its plugins on
+    /// `GET /metrics` at `addr`, in the Prometheus text format, see
+    /// [`prometheus`]. Building the host installs the global meter provider
+    /// serving them.
+    #[cfg(feature = "prometheus-metrics")]
+    pub fn with_metrics(mut self, addr: std::net::SocketAddr) -> Self {
+        self.metrics_addr = Some(addr);
+        self
+    }
+
+    /// Traces the requests the host serves and exports their spans with
+    /// `tracing`, see [`http::trace`]. Building the host installs the global
+    /// tracer provider, so it must be built from within a Tokio runtime.
+    #[cfg(feature = "otlp-tracing")]
+    pub fn with_tracing(mut self, tracing: otlp_tracing::OtlpTracing) -> Self {
+        self.tracing = Some(tracing);
+        self
+    }
+
     /// Builds and returns a configured [`Host`].
     ///
     /// This method finalizes the configuration and creates the host.
@@ -696,7 +3101,8 @@ impl HostBuilder {
     /// A new `Host` instance ready to be started.
     ///
     /// # Errors
-    /// Returns an error if the default engine cannot be created (when no engine is provided).
+    /// Returns an error if the default engine cannot be created (when no engine is provided),
+    /// or if two devices have the same name.
     pub fn build(self) -> anyhow::Result<Host> {
         let engine = if let Some(engine) = self.engine {
             engine
@@ -725,6 +3131,53 @@ impl HostBuilder {
             Some(handler) => handler,
             None => Arc::new(crate::host::http::NullServer::default()),
         };
+        http_handler.set_limits(&self.limits);
+
+        let events = LifecycleEvents::new(&self.id, self.event_sinks);
+        let (crash_loop_stops, crash_loop_stopped) = tokio::sync::mpsc::unbounded_channel();
+
+        // Installed before the instruments of the host are created
+        #[cfg(feature = "prometheus-metrics")]
+        let metrics_endpoint = self.metrics_addr.map(prometheus::MetricsEndpoint::install);
+        let meter = opentelemetry::global::meter("wash-host");
+        #[cfg(feature = "otlp-tracing")]
+        let tracer_provider = self
+            .tracing
+            .map(|tracing| {
+                tracing.install(
+                    opentelemetry_sdk::Resource::builder()
+                        .with_attribute(opentelemetry::KeyValue::new(
+                            metrics::HOST_ID_ATTRIBUTE,
+                            self.id.clone(),
+                        ))
+                        .build(),
+                )
+            })
+            .transpose()?;
+
+        let identity = match (self.identity, self.identity_file) {
+            (Some(identity), _) => identity,
+            (None, Some(path)) => HostIdentity::load_or_generate(path)?,
+            (None, None) => HostIdentity::generate()?,
+        };
+
+        #[cfg(feature = "oci")]
+        let oci_config = self.oci_config.unwrap_or_else(|| {
+            crate::oci::OciConfig::new_with_cache(std::env::temp_dir().join("wash-oci-cache"))
+        });
+        let mut volume_manager = self.volume_root.map(VolumeManager::new).unwrap_or_default();
+        // Artifacts are pulled with the credentials components are
+        #[cfg(feature = "oci")]
+        {
+            let cache_dir = volume_manager.root().join(volumes::OCI_DIR);
+            volume_manager = volume_manager.with_provider(
+                "oci",
+                Arc::new(volumes::OciVolumes::new(oci_config.clone(), cache_dir)),
+            );
+        }
+        for (kind, provider) in self.volume_providers {
+            volume_manager = volume_manager.with_provider(kind, provider);
+        }
 
         Ok(Host {
             engine,
@@ -738,6 +3191,58 @@ impl HostBuilder {
             started_at: chrono::Utc::now(),
             system_monitor: Arc::new(RwLock::new(SystemMonitor::new())),
             http_handler,
+            secret_store: self.secret_store,
+            env_allowlist: self.env_allowlist,
+            volumes: Arc::new(volume_manager),
+            devices: DeviceManager::new(self.devices)?,
+            events,
+            logs: logs::WorkloadLogs::new(self.log_capacity.unwrap_or(logs::DEFAULT_CAPACITY)),
+            metrics: HostMetrics::new(&meter),
+            workload_metrics: Arc::new(WorkloadMetrics::new(&meter)),
+            #[cfg(feature = "prometheus-metrics")]
+            metrics_endpoint,
+            #[cfg(feature = "otlp-tracing")]
+            tracer_provider,
+            identity,
+            policies: self.policies,
+            tee_evidence: self.tee_evidence,
+            limits: std::sync::RwLock::new(self.limits),
+            reservations: Default::default(),
+            definitions: Default::default(),
+            staged: Default::default(),
+            draining: AtomicBool::new(false),
+            rollouts: Default::default(),
+            #[cfg(feature = "oci")]
+            oci_config,
+            forwarding: self.call_forwarder.map(Forwarding::new),
+            hot_reload: self.hot_reload,
+            crash_loop_stops,
+            crash_loop_stopped: std::sync::Mutex::new(Some(crash_loop_stopped)),
+            state_file: self.state_file,
+            state_changed: Arc::default(),
         })
     }
 }
+
+/// Returns the digests of the compiled components of a workload.
+fn component_digests(workload: &Workload) -> impl Iterator<Item = String> + '_ {
+    workload
+        .service
+        .iter()
+        .map(|service| &service.bytes)
+        .chain(workload.components.iter().map(|component| &component.bytes))
+        .map(|bytes| crate::engine::component_digest(bytes))
+}
+
+/// Runs a teardown hook of a plugin with a 3-second timeout, logging failures.
+async fn plugin_hook(id: &str, hook: &str, fut: impl Future<Output = anyhow::Result<()>>) {
+    match tokio::time::timeout(std::time::Duration::from_secs(3), fut).await {
+        Ok(Err(e)) => {
+            tracing::error!(id, hook, err = ?e, "plugin teardown hook failed");
+        }
+        Err(_) => {
+            tracing::error!(id, hook, "plugin teardown hook timed out after 3 seconds");
+        }
+        Ok(Ok(())) => {}
+    }
+}
@@ -0,0 +1,88 @@
+//! Pushing metrics to an OpenTelemetry collector.
+//!
+//! Hosts without a scraper, such as edge devices, can push their metrics over
+//! OTLP/gRPC instead. [`OtlpMetrics::install`] sets a global meter provider
+//! that exports every metric recorded by the host, its HTTP server and its
+//! plugins to a collector on a fixed interval.
+//!
+//! Instruments are bound to the meter provider that is global when they are
+//! created, so the exporter must be installed before the host is built.
+
+use std::time::Duration;
+
+use anyhow::Context as _;
+use opentelemetry_otlp::WithExportConfig as _;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+
+/// Collector endpoint used when none is given
+pub const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4317";
+/// How often metrics are pushed by default
+pub const DEFAULT_OTLP_METRICS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long an export may take before it is abandoned
+pub const DEFAULT_OTLP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Periodically pushes metrics to an OTLP/gRPC collector.
+#[derive(Debug, Clone)]
+pub struct OtlpMetrics {
+    endpoint: String,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Default for OtlpMetrics {
+    fn default() -> Self {
+        Self::new(DEFAULT_OTLP_ENDPOINT)
+    }
+}
+
+impl OtlpMetrics {
+    /// Pushes metrics to the collector at `endpoint`, e.g. `http://collector:4317`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            interval: DEFAULT_OTLP_METRICS_INTERVAL,
+            timeout: DEFAULT_OTLP_TIMEOUT,
+        }
+    }
+
+    /// Sets how often metrics are pushed.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long an export may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Installs a global meter provider pushing metrics described by
+    /// `resource` to the collector. Must be called from within a multi-threaded
+    /// Tokio runtime.
+    ///
+    /// The returned provider should be shut down when the host exits, which
+    /// pushes the metrics recorded since the last export.
+    pub fn install(&self, resource: Resource) -> anyhow::Result<SdkMeterProvider> {
+        anyhow::ensure!(
+            !self.interval.is_zero(),
+            "OTLP metrics interval must not be zero"
+        );
+        let exporter = opentelemetry_otlp::MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(&self.endpoint)
+            .with_timeout(self.timeout)
+            .build()
+            .with_context(|| format!("failed to create OTLP exporter for {}", self.endpoint))?;
+        let reader = PeriodicReader::builder(exporter)
+            .with_interval(self.interval)
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(resource)
+            .build();
+        opentelemetry::global::set_meter_provider(provider.clone());
+        Ok(provider)
+    }
+}
@@ -0,0 +1,104 @@
+//! Exporting traces to an OpenTelemetry collector.
+//!
+//! A host built with [`HostBuilder::with_tracing`](super::HostBuilder::with_tracing)
+//! installs a global tracer provider that exports the spans of the requests
+//! it serves, see [`crate::host::http::trace`], to a collector over OTLP/gRPC
+//! in batches.
+//!
+//! Requests continuing a trace are sampled if the trace is. Requests starting
+//! a trace are sampled at the configured ratio, so a busy host can export a
+//! fraction of its traces.
+
+use std::time::Duration;
+
+use anyhow::Context as _;
+use opentelemetry_otlp::WithExportConfig as _;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+
+/// How long an export may take before it is abandoned
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exports spans of requests to an OTLP/gRPC collector.
+#[derive(Debug, Clone)]
+pub struct OtlpTracing {
+    endpoint: String,
+    sampling_ratio: f64,
+    timeout: Duration,
+}
+
+impl OtlpTracing {
+    /// Exports spans to the collector at `endpoint`, e.g. `http://collector:4317`,
+    /// sampling every trace.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            sampling_ratio: 1.0,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the fraction of the traces starting at the host that are
+    /// sampled, between 0 and 1.
+    pub fn with_sampling_ratio(mut self, ratio: f64) -> Self {
+        self.sampling_ratio = ratio;
+        self
+    }
+
+    /// Sets how long an export may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Installs a global tracer provider exporting spans described by
+    /// `resource` to the collector. Must be called from within a Tokio
+    /// runtime.
+    ///
+    /// The returned provider should be shut down when the host exits, which
+    /// exports the spans that are still buffered.
+    pub fn install(&self, resource: Resource) -> anyhow::Result<SdkTracerProvider> {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&self.sampling_ratio),
+            "OTLP tracing sampling ratio must be between 0 and 1, got {}",
+            self.sampling_ratio
+        );
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&self.endpoint)
+            .with_timeout(self.timeout)
+            .build()
+            .with_context(|| format!("failed to create OTLP exporter for {}", self.endpoint))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                self.sampling_ratio,
+            ))))
+            .with_resource(resource)
+            .build();
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        Ok(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_ratio() {
+        let resource = Resource::builder_empty().build();
+        assert!(
+            OtlpTracing::new("http://localhost:4317")
+                .with_sampling_ratio(1.5)
+                .install(resource.clone())
+                .is_err()
+        );
+        assert!(
+            OtlpTracing::new("http://localhost:4317")
+                .with_sampling_ratio(-0.1)
+                .install(resource)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,531 @@
+//! Serving metrics to a Prometheus scraper.
+//!
+//! A host built with [`HostBuilder::with_metrics`](super::HostBuilder::with_metrics)
+//! installs a global meter provider read by a [`PrometheusReader`], and serves
+//! everything recorded through it on `GET /metrics` in the Prometheus text
+//! format: the gauges of the host, see [`super::metrics`], the invocations of
+//! workloads, the requests of the HTTP server per route and the series of
+//! plugins, which get a meter with
+//! [`HostPlugin::set_meter`](crate::plugin::HostPlugin::set_meter). The
+//! endpoint has a listener of its own, so scrapes never reach workloads and
+//! the port can be kept off the ingress.
+//!
+//! Names are translated the way Prometheus expects them: dots become
+//! underscores, the unit is appended as in `_seconds` or `_bytes`, and
+//! counters end in `_total`. `wasmcloud.workload.invocation.duration` in
+//! seconds is therefore scraped as
+//! `wasmcloud_workload_invocation_duration_seconds`.
+//!
+//! Instruments are bound to the meter provider that is global when they are
+//! created, so those created before the host is built are not served. The
+//! provider replaces any global provider installed before, such as the OTLP
+//! exporter in `super::otlp`.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::metrics::data::{self, ResourceMetrics};
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use opentelemetry_sdk::metrics::{
+    InstrumentKind, ManualReader, MetricResult, Pipeline, SdkMeterProvider, Temporality,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use wasmtime_wasi_http::io::TokioIo;
+
+/// Content type of the Prometheus text format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// A [`MetricReader`] collecting metrics when they are scraped, see the
+/// [module docs](self).
+#[derive(Debug, Clone)]
+pub struct PrometheusReader {
+    reader: Arc<ManualReader>,
+}
+
+impl Default for PrometheusReader {
+    fn default() -> Self {
+        Self {
+            reader: Arc::new(ManualReader::builder().build()),
+        }
+    }
+}
+
+impl PrometheusReader {
+    /// Collects the metrics of the meter provider the reader is registered
+    /// with, in the Prometheus text format.
+    ///
+    /// # Errors
+    /// Returns an error if the reader is not registered with a provider, or
+    /// the provider was shut down.
+    pub fn render(&self) -> anyhow::Result<String> {
+        let mut metrics = ResourceMetrics {
+            resource: Resource::builder_empty().build(),
+            scope_metrics: Vec::new(),
+        };
+        self.reader
+            .collect(&mut metrics)
+            .context("failed to collect metrics")?;
+        Ok(encode(&metrics))
+    }
+}
+
+impl MetricReader for PrometheusReader {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.reader.register_pipeline(pipeline)
+    }
+
+    fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+        self.reader.collect(rm)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.reader.force_flush()
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.reader.shutdown()
+    }
+
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.reader.temporality(kind)
+    }
+}
+
+/// The `/metrics` listener of a host and the meter provider it serves.
+pub(crate) struct MetricsEndpoint {
+    addr: SocketAddr,
+    reader: PrometheusReader,
+    provider: SdkMeterProvider,
+    /// The address the listener is bound to, once it started
+    local_addr: std::sync::OnceLock<SocketAddr>,
+    stopped: CancellationToken,
+}
+
+impl MetricsEndpoint {
+    /// Installs a global meter provider whose metrics are served on `addr`
+    /// once [`Self::start`] is called.
+    pub(crate) fn install(addr: SocketAddr) -> Self {
+        let reader = PrometheusReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .with_resource(Resource::builder().build())
+            .build();
+        opentelemetry::global::set_meter_provider(provider.clone());
+        Self {
+            addr,
+            reader,
+            provider,
+            local_addr: std::sync::OnceLock::new(),
+            stopped: CancellationToken::new(),
+        }
+    }
+
+    /// Returns the address the listener is bound to, or `None` before it
+    /// started.
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr.get().copied()
+    }
+
+    /// Binds the listener and serves scrapes until [`Self::stop`] is called.
+    ///
+    /// # Errors
+    /// Returns an error if the address can't be bound.
+    pub(crate) async fn start(&self) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind(self.addr)
+            .await
+            .with_context(|| format!("failed to bind metrics endpoint to {}", self.addr))?;
+        let local_addr = listener.local_addr()?;
+        let _ = self.local_addr.set(local_addr);
+        info!(addr = %local_addr, "serving metrics on /metrics");
+
+        let reader = self.reader.clone();
+        let stopped = self.stopped.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = tokio::select! {
+                    () = stopped.cancelled() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("failed to accept metrics connection: {e}");
+                            continue;
+                        }
+                    },
+                };
+                let reader = reader.clone();
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(move |req| {
+                        let reader = reader.clone();
+                        async move { Ok::<_, Infallible>(scrape(&reader, &req)) }
+                    });
+                    if let Err(e) = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        debug!(%peer, "error serving metrics connection: {e}");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Closes the listener and shuts the meter provider down.
+    pub(crate) fn stop(&self) {
+        self.stopped.cancel();
+        if let Err(e) = self.provider.shutdown() {
+            warn!("failed to shut down meter provider: {e}");
+        }
+    }
+}
+
+/// Answers a request to the metrics endpoint.
+fn scrape<B>(
+    reader: &PrometheusReader,
+    req: &hyper::Request<B>,
+) -> hyper::Response<http_body_util::Full<Bytes>> {
+    let response = |status: hyper::StatusCode, body: String| {
+        let mut response = hyper::Response::new(http_body_util::Full::new(Bytes::from(body)));
+        *response.status_mut() = status;
+        response
+    };
+    if req.uri().path() != "/metrics" {
+        return response(hyper::StatusCode::NOT_FOUND, "not found\n".to_string());
+    }
+    if req.method() != hyper::Method::GET {
+        return response(
+            hyper::StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed\n".to_string(),
+        );
+    }
+    match reader.render() {
+        Ok(body) => {
+            let mut response = response(hyper::StatusCode::OK, body);
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static(CONTENT_TYPE),
+            );
+            response
+        }
+        Err(e) => response(hyper::StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}\n")),
+    }
+}
+
+/// A number of a data point.
+trait Number: Copy {
+    fn format(self) -> String;
+}
+
+impl Number for u64 {
+    fn format(self) -> String {
+        self.to_string()
+    }
+}
+
+impl Number for i64 {
+    fn format(self) -> String {
+        self.to_string()
+    }
+}
+
+impl Number for f64 {
+    fn format(self) -> String {
+        if self.is_nan() {
+            "NaN".to_string()
+        } else if self.is_infinite() {
+            if self > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+/// The samples of a metric, by Prometheus name.
+struct Family {
+    kind: &'static str,
+    help: String,
+    samples: Vec<String>,
+}
+
+/// Encodes metrics in the Prometheus text format.
+fn encode(metrics: &ResourceMetrics) -> String {
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+    for metric in metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope| scope.metrics.iter())
+    {
+        let data = metric.data.as_any();
+        let mut add = |kind: &'static str, name: String, samples: Vec<String>| {
+            families
+                .entry(name)
+                .or_insert_with(|| Family {
+                    kind,
+                    help: metric.description.to_string(),
+                    samples: Vec::new(),
+                })
+                .samples
+                .extend(samples);
+        };
+        if let Some(sum) = data.downcast_ref::<data::Sum<u64>>() {
+            encode_sum(metric, sum, &mut add);
+        } else if let Some(sum) = data.downcast_ref::<data::Sum<i64>>() {
+            encode_sum(metric, sum, &mut add);
+        } else if let Some(sum) = data.downcast_ref::<data::Sum<f64>>() {
+            encode_sum(metric, sum, &mut add);
+        } else if let Some(gauge) = data.downcast_ref::<data::Gauge<u64>>() {
+            encode_gauge(metric, gauge, &mut add);
+        } else if let Some(gauge) = data.downcast_ref::<data::Gauge<i64>>() {
+            encode_gauge(metric, gauge, &mut add);
+        } else if let Some(gauge) = data.downcast_ref::<data::Gauge<f64>>() {
+            encode_gauge(metric, gauge, &mut add);
+        } else if let Some(histogram) = data.downcast_ref::<data::Histogram<u64>>() {
+            encode_histogram(metric, histogram, &mut add);
+        } else if let Some(histogram) = data.downcast_ref::<data::Histogram<f64>>() {
+            encode_histogram(metric, histogram, &mut add);
+        } else {
+            debug!(name = %metric.name, "skipping metric of unsupported type");
+        }
+    }
+
+    let mut out = String::new();
+    for (name, family) in families {
+        if !family.help.is_empty() {
+            let help = family.help.replace('\\', "\\\\").replace('\n', "\\n");
+            let _ = writeln!(out, "# HELP {name} {help}");
+        }
+        let _ = writeln!(out, "# TYPE {name} {}", family.kind);
+        for sample in family.samples {
+            out.push_str(&sample);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn encode_sum<T: Number>(
+    metric: &data::Metric,
+    sum: &data::Sum<T>,
+    add: &mut impl FnMut(&'static str, String, Vec<String>),
+) {
+    let (kind, name) = if sum.is_monotonic {
+        ("counter", format!("{}_total", metric_name(metric)))
+    } else {
+        ("gauge", metric_name(metric))
+    };
+    let samples = sum
+        .data_points
+        .iter()
+        .map(|point| sample(&name, &point.attributes, None, point.value.format()))
+        .collect();
+    add(kind, name, samples);
+}
+
+fn encode_gauge<T: Number>(
+    metric: &data::Metric,
+    gauge: &data::Gauge<T>,
+    add: &mut impl FnMut(&'static str, String, Vec<String>),
+) {
+    let name = metric_name(metric);
+    let samples = gauge
+        .data_points
+        .iter()
+        .map(|point| sample(&name, &point.attributes, None, point.value.format()))
+        .collect();
+    add("gauge", name, samples);
+}
+
+fn encode_histogram<T: Number>(
+    metric: &data::Metric,
+    histogram: &data::Histogram<T>,
+    add: &mut impl FnMut(&'static str, String, Vec<String>),
+) {
+    let name = metric_name(metric);
+    let mut samples = Vec::new();
+    for point in &histogram.data_points {
+        let bucket = format!("{name}_bucket");
+        let mut cumulative = 0;
+        for (bound, count) in point.bounds.iter().zip(&point.bucket_counts) {
+            cumulative += count;
+            samples.push(sample(
+                &bucket,
+                &point.attributes,
+                Some(bound.format()),
+                cumulative.to_string(),
+            ));
+        }
+        samples.push(sample(
+            &bucket,
+            &point.attributes,
+            Some("+Inf".to_string()),
+            point.count.to_string(),
+        ));
+        samples.push(sample(
+            &format!("{name}_sum"),
+            &point.attributes,
+            None,
+            point.sum.format(),
+        ));
+        samples.push(sample(
+            &format!("{name}_count"),
+            &point.attributes,
+            None,
+            point.count.to_string(),
+        ));
+    }
+    add("histogram", name, samples);
+}
+
+/// Returns the Prometheus name of a metric, with its unit appended.
+fn metric_name(metric: &data::Metric) -> String {
+    let mut name = sanitize(&metric.name);
+    let unit = match metric.unit.as_ref() {
+        "s" => "seconds",
+        "ms" => "milliseconds",
+        "By" => "bytes",
+        "1" => "ratio",
+        // Annotations such as `{invocation}` are not units
+        _ => "",
+    };
+    if !unit.is_empty() && !name.ends_with(unit) {
+        name.push('_');
+        name.push_str(unit);
+    }
+    name
+}
+
+/// Replaces the characters Prometheus doesn't allow in names with `_`.
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Formats a sample line, with `le` as the last label if given.
+fn sample(name: &str, attributes: &[KeyValue], le: Option<String>, value: String) -> String {
+    let mut labels: Vec<(String, String)> = attributes
+        .iter()
+        .map(|kv| (sanitize(kv.key.as_str()), kv.value.to_string()))
+        .collect();
+    labels.sort();
+    labels.extend(le.map(|le| ("le".to_string(), le)));
+    if labels.is_empty() {
+        return format!("{name} {value}");
+    }
+    let labels: Vec<String> = labels
+        .into_iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{key}=\"{value}\"")
+        })
+        .collect();
+    format!("{name}{{{}}} {value}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider as _;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let reader = PrometheusReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let meter = provider.meter("test");
+
+        let attributes = [KeyValue::new("wasmcloud.workload.name", "shop \"eu\"")];
+        let invocations = meter
+            .u64_counter("wasmcloud.workload.invocations")
+            .with_unit("{invocation}")
+            .with_description("Invocations of components")
+            .build();
+        invocations.add(3, &attributes);
+        let memory = meter
+            .u64_gauge("wasmcloud.component.memory")
+            .with_unit("By")
+            .build();
+        memory.record(65536, &[]);
+        let duration = meter
+            .f64_histogram("wasmcloud.workload.invocation.duration")
+            .with_unit("s")
+            .with_boundaries(vec![0.1, 1.0])
+            .build();
+        duration.record(0.0625, &attributes);
+        duration.record(0.5, &attributes);
+        duration.record(2.0, &attributes);
+
+        let text = reader.render().unwrap();
+        let label = r#"wasmcloud_workload_name="shop \"eu\"""#;
+        for line in [
+            "# HELP wasmcloud_workload_invocations_total Invocations of components".to_string(),
+            "# TYPE wasmcloud_workload_invocations_total counter".to_string(),
+            format!("wasmcloud_workload_invocations_total{{{label}}} 3"),
+            "# TYPE wasmcloud_component_memory_bytes gauge".to_string(),
+            "wasmcloud_component_memory_bytes 65536".to_string(),
+            "# TYPE wasmcloud_workload_invocation_duration_seconds histogram".to_string(),
+            format!(
+                "wasmcloud_workload_invocation_duration_seconds_bucket{{{label},le=\"0.1\"}} 1"
+            ),
+            format!("wasmcloud_workload_invocation_duration_seconds_bucket{{{label},le=\"1\"}} 2"),
+            format!(
+                "wasmcloud_workload_invocation_duration_seconds_bucket{{{label},le=\"+Inf\"}} 3"
+            ),
+            format!("wasmcloud_workload_invocation_duration_seconds_sum{{{label}}} 2.5625"),
+            format!("wasmcloud_workload_invocation_duration_seconds_count{{{label}}} 3"),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+
+        provider.shutdown().unwrap();
+        assert!(reader.render().is_err());
+    }
+
+    #[test]
+    fn test_scrape() {
+        let reader = PrometheusReader::default();
+        let _provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let request = |method: hyper::Method, path: &str| {
+            hyper::Request::builder()
+                .method(method)
+                .uri(path)
+                .body(())
+                .unwrap()
+        };
+        let response = scrape(&reader, &request(hyper::Method::GET, "/metrics"));
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(
+            response.headers()[hyper::header::CONTENT_TYPE],
+            CONTENT_TYPE
+        );
+        assert_eq!(
+            scrape(&reader, &request(hyper::Method::GET, "/")).status(),
+            hyper::StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            scrape(&reader, &request(hyper::Method::POST, "/metrics")).status(),
+            hyper::StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+}
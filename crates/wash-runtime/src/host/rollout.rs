@@ -0,0 +1,362 @@
+//! Rollouts of new workload versions that roll back on their own.
+//!
+//! [`Host::rollout`] replaces a running workload with a new version like
+//! [`HostApi::workload_update`], right away or at a scheduled time, and then
+//! bakes it: for the bake period of its [`RolloutPolicy`], the error rate and
+//! mean latency of the invocations of the new version are checked against the
+//! thresholds of the policy. When one is exceeded, the previous version is
+//! started again and replaces the new one the same way.
+//!
+//! Every step of a rollout is emitted as a lifecycle event carrying the
+//! rollout ID, see [`super::events`]: `rollout_started` when the new version
+//! is started, then `rollout_succeeded` after the bake period,
+//! `rollout_rolled_back` when a threshold was exceeded, or `rollout_failed`
+//! when the new version, or the previous one while rolling back, failed to
+//! start. The state of a rollout can also be queried with
+//! [`Host::rollout_status`].
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::events::{LifecycleEvent, LifecycleEventType};
+use super::{Host, HostApi, HostError, HostResult};
+use crate::types::{
+    WorkloadActivity, WorkloadStartRequest, WorkloadStatusRequest, WorkloadUpdateRequest,
+};
+
+/// When a new version is rolled back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolloutPolicy {
+    /// How long the new version is watched after it started
+    pub bake_period: Duration,
+    /// How often its invocations are checked while it bakes
+    pub check_interval: Duration,
+    /// Largest share of failed invocations, from 0 to 1
+    pub max_error_rate: Option<f64>,
+    /// Largest mean time an invocation takes
+    pub max_mean_latency: Option<Duration>,
+    /// Invocations needed before the thresholds are checked, so a single
+    /// early failure doesn't roll back a version
+    pub min_invocations: u64,
+}
+
+impl Default for RolloutPolicy {
+    fn default() -> Self {
+        Self {
+            bake_period: Duration::from_secs(5 * 60),
+            check_interval: Duration::from_secs(10),
+            max_error_rate: Some(0.05),
+            max_mean_latency: None,
+            min_invocations: 10,
+        }
+    }
+}
+
+impl RolloutPolicy {
+    /// Returns why the activity of a new version exceeds a threshold, or
+    /// `None` if it doesn't.
+    pub fn breach(&self, activity: &WorkloadActivity) -> Option<String> {
+        let finished = activity
+            .invocations
+            .saturating_sub(activity.in_flight_invocations);
+        if finished == 0 || finished < self.min_invocations {
+            return None;
+        }
+        let error_rate = activity.failed_invocations as f64 / finished as f64;
+        if let Some(max) = self.max_error_rate
+            && error_rate > max
+        {
+            return Some(format!(
+                "{} of {finished} invocations failed, more than {:.1}%",
+                activity.failed_invocations,
+                max * 100.0
+            ));
+        }
+        let mean_latency = activity.invocation_time.div_f64(finished as f64);
+        if let Some(max) = self.max_mean_latency
+            && mean_latency > max
+        {
+            return Some(format!(
+                "invocations took {mean_latency:?} on average, more than {max:?}"
+            ));
+        }
+        None
+    }
+}
+
+/// Request to roll out a new version of a workload, see [`Host::rollout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolloutRequest {
+    /// The update applied when the rollout starts
+    pub update: WorkloadUpdateRequest,
+    pub policy: RolloutPolicy,
+    /// When to apply the update, right away if `None` or in the past
+    pub start_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Where a rollout is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RolloutState {
+    /// Waiting for its start time
+    Scheduled,
+    /// The new version is starting
+    Updating,
+    /// The new version runs and is watched
+    Baking,
+    /// The new version ran for the bake period without exceeding a threshold
+    Succeeded,
+    /// The new version exceeded a threshold and was replaced by the previous
+    /// one
+    RolledBack,
+    /// The new version, or the previous one while rolling back, failed to start
+    Failed,
+}
+
+impl RolloutState {
+    /// Returns whether the rollout ended.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            RolloutState::Succeeded | RolloutState::RolledBack | RolloutState::Failed
+        )
+    }
+}
+
+/// The state of a rollout, see [`Host::rollout_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolloutStatus {
+    pub rollout_id: String,
+    pub state: RolloutState,
+    /// The workload replaced by the rollout
+    pub previous_workload_id: String,
+    /// The new version, which is the previous version again after a rollback
+    pub workload_id: String,
+    pub message: String,
+}
+
+impl Host {
+    /// Starts a rollout in the background and returns its ID, see the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the workload to update is not found
+    /// and [`HostError::InvalidRequest`] if a rollout of it is already
+    /// running. Errors of the update itself end the rollout as
+    /// [`RolloutState::Failed`].
+    pub fn rollout(self: &Arc<Self>, request: RolloutRequest) -> HostResult<String> {
+        let previous_workload_id = request.update.workload_id.clone();
+        let previous = self
+            .definitions()
+            .iter()
+            .find(|definition| definition.workload_id == previous_workload_id)
+            .map(|definition| definition.workload.clone())
+            .ok_or_else(|| HostError::NotFound(format!("workload {previous_workload_id}")))?;
+
+        let rollout_id = uuid::Uuid::new_v4().to_string();
+        {
+            let mut rollouts = self.rollouts();
+            if rollouts.values().any(|rollout| {
+                !rollout.state.is_finished() && rollout.previous_workload_id == previous_workload_id
+            }) {
+                return Err(HostError::InvalidRequest(format!(
+                    "workload {previous_workload_id} is already being rolled out"
+                )));
+            }
+            rollouts.insert(
+                rollout_id.clone(),
+                RolloutStatus {
+                    rollout_id: rollout_id.clone(),
+                    state: RolloutState::Scheduled,
+                    previous_workload_id,
+                    workload_id: request.update.replacement.workload_id.clone(),
+                    message: String::new(),
+                },
+            );
+        }
+
+        let host = self.clone();
+        let id = rollout_id.clone();
+        tokio::spawn(async move {
+            let (state, message) = host.run_rollout(&id, request, previous).await;
+            host.set_rollout_state(&id, state, message);
+        });
+        Ok(rollout_id)
+    }
+
+    /// Returns the state of a rollout, or `None` if there is none with the ID.
+    pub fn rollout_status(&self, rollout_id: &str) -> Option<RolloutStatus> {
+        self.rollouts().get(rollout_id).cloned()
+    }
+
+    /// Returns every rollout since the host started.
+    pub fn rollout_list(&self) -> Vec<RolloutStatus> {
+        self.rollouts().values().cloned().collect()
+    }
+
+    fn rollouts(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, RolloutStatus>> {
+        self.rollouts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Records the state of a rollout and emits the event of the state, if it
+    /// has one.
+    fn set_rollout_state(&self, rollout_id: &str, state: RolloutState, message: String) {
+        let event_type = match state {
+            RolloutState::Updating => Some(LifecycleEventType::RolloutStarted),
+            RolloutState::Succeeded => Some(LifecycleEventType::RolloutSucceeded),
+            RolloutState::RolledBack => Some(LifecycleEventType::RolloutRolledBack),
+            RolloutState::Failed => Some(LifecycleEventType::RolloutFailed),
+            RolloutState::Scheduled | RolloutState::Baking => None,
+        };
+        let workload_id = {
+            let mut rollouts = self.rollouts();
+            let Some(rollout) = rollouts.get_mut(rollout_id) else {
+                return;
+            };
+            rollout.state = state;
+            rollout.message = message.clone();
+            rollout.workload_id.clone()
+        };
+        if let Some(event_type) = event_type {
+            self.events.emit(
+                LifecycleEvent::new(event_type, workload_id, message).with_rollout_id(rollout_id),
+            );
+        }
+    }
+
+    /// Runs a rollout to its end and returns its final state.
+    async fn run_rollout(
+        &self,
+        rollout_id: &str,
+        request: RolloutRequest,
+        previous: crate::types::Workload,
+    ) -> (RolloutState, String) {
+        if let Some(start_at) = request.start_at
+            && let Ok(delay) = (start_at - chrono::Utc::now()).to_std()
+        {
+            info!(rollout_id, ?delay, "rollout scheduled");
+            tokio::time::sleep(delay).await;
+        }
+
+        let previous_workload_id = request.update.workload_id.clone();
+        let workload_id = request.update.replacement.workload_id.clone();
+        let drain_timeout = request.update.drain_timeout;
+        self.set_rollout_state(
+            rollout_id,
+            RolloutState::Updating,
+            format!("replacing workload {previous_workload_id}"),
+        );
+        if let Err(e) = self.workload_update(request.update).await {
+            return (
+                RolloutState::Failed,
+                format!("the new version failed to start: {e}"),
+            );
+        }
+
+        self.set_rollout_state(
+            rollout_id,
+            RolloutState::Baking,
+            format!("baking for {:?}", request.policy.bake_period),
+        );
+        let breach = tokio::time::timeout(request.policy.bake_period, async {
+            let mut interval = tokio::time::interval(request.policy.check_interval);
+            loop {
+                interval.tick().await;
+                let status = self
+                    .workload_status(WorkloadStatusRequest {
+                        workload_id: workload_id.clone(),
+                        ..Default::default()
+                    })
+                    .await;
+                let activity = match status {
+                    Ok(response) => response.workload_status.activity,
+                    Err(e) => return format!("the new version is gone: {e}"),
+                };
+                if let Some(breach) = activity
+                    .as_ref()
+                    .and_then(|activity| request.policy.breach(activity))
+                {
+                    return breach;
+                }
+            }
+        })
+        .await;
+        let Ok(breach) = breach else {
+            return (
+                RolloutState::Succeeded,
+                format!("workload {workload_id} replaced workload {previous_workload_id}"),
+            );
+        };
+
+        warn!(rollout_id, workload_id, breach, "rolling back");
+        let rollback = WorkloadUpdateRequest {
+            workload_id: workload_id.clone(),
+            replacement: WorkloadStartRequest {
+                workload_id: uuid::Uuid::new_v4().to_string(),
+                workload: previous,
+            },
+            drain_timeout,
+            // The failing version is replaced at once
+            rolling_update: None,
+        };
+        let restored_id = rollback.replacement.workload_id.clone();
+        match self.workload_update(rollback).await {
+            Ok(_) => {
+                if let Some(rollout) = self.rollouts().get_mut(rollout_id) {
+                    rollout.workload_id = restored_id;
+                }
+                (RolloutState::RolledBack, breach)
+            }
+            Err(e) => (
+                RolloutState::Failed,
+                format!("{breach}, and the previous version failed to start: {e}"),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_breach() {
+        let policy = RolloutPolicy {
+            max_mean_latency: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let activity = |invocations, failed, millis| WorkloadActivity {
+            invocations,
+            failed_invocations: failed,
+            invocation_time: Duration::from_millis(millis),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.breach(&activity(0, 0, 0)), None);
+        // Too few invocations to judge
+        assert_eq!(policy.breach(&activity(5, 5, 0)), None);
+        assert_eq!(policy.breach(&activity(100, 5, 1_000)), None);
+        assert!(
+            policy
+                .breach(&activity(100, 6, 1_000))
+                .unwrap()
+                .contains("6 of 100 invocations failed")
+        );
+        assert!(
+            policy
+                .breach(&activity(100, 0, 20_000))
+                .unwrap()
+                .contains("200ms")
+        );
+
+        let lenient = RolloutPolicy {
+            max_error_rate: None,
+            ..Default::default()
+        };
+        assert_eq!(lenient.breach(&activity(100, 100, 20_000)), None);
+    }
+}
@@ -0,0 +1,439 @@
+//! Secret providers backing the host's [`SecretStore`].
+//!
+//! A host built with [`HostBuilder::with_secret_store`](super::HostBuilder::with_secret_store)
+//! resolves `${secret:NAME}` references, see [`super::interpolate`]. This
+//! module provides stores reading secrets from:
+//!
+//! - [`EnvSecrets`]: environment variables of the host process
+//! - [`FileSecrets`]: one file per secret in a directory, e.g. mounted by
+//!   Kubernetes or systemd credentials
+//! - `VaultSecrets`: the KV version 2 engine of HashiCorp Vault
+//! - `AwsSecretsManager`: AWS Secrets Manager
+//!
+//! Vault and AWS need the `remote-secrets` feature. [`SecretProviders`]
+//! combines them, so a reference names its provider before the secret, e.g.
+//! `${secret:vault:db/creds#password}`.
+//!
+//! Secrets referenced in the config of the `wasi:config/store` interface of a
+//! workload are resolved by the config plugin when the workload starts and
+//! only kept in its memory, so unlike references in
+//! [`LocalResources`](crate::types::LocalResources), their values are never
+//! written into the workload or its snapshots. See
+//! [`WasiConfig::with_secrets`](crate::plugin::wasi_config::WasiConfig::with_secrets).
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+
+use crate::host::interpolate::SecretStore;
+
+/// Reads secrets from the environment of the host process, the secret `NAME`
+/// from the variable `<prefix>NAME`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvSecrets {
+    prefix: String,
+}
+
+impl EnvSecrets {
+    /// Reads the secret `NAME` from the variable `<prefix>NAME`, so secrets
+    /// can't name arbitrary variables of the host.
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretStore for EnvSecrets {
+    async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        match std::env::var(format!("{}{name}", self.prefix)) {
+            Ok(value) => Ok(Some(value)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("invalid secret '{name}'")),
+        }
+    }
+}
+
+/// Reads the secret `NAME` from the file `NAME` in a directory. A single
+/// trailing newline is removed from the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSecrets {
+    dir: PathBuf,
+}
+
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretStore for FileSecrets {
+    async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        ensure!(
+            !name.is_empty()
+                && Path::new(name)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_))),
+            "secret name '{name}' must be a relative path inside the secrets directory"
+        );
+        let path = self.dir.join(name);
+        let mut value = match tokio::fs::read_to_string(&path).await {
+            Ok(value) => value,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        if value.ends_with('\n') {
+            value.pop();
+            if value.ends_with('\r') {
+                value.pop();
+            }
+        }
+        Ok(Some(value))
+    }
+}
+
+/// Routes references of the form `provider:name` to the store registered as
+/// `provider`. Names without a provider are looked up in the default one.
+#[derive(Clone, Default)]
+pub struct SecretProviders {
+    providers: HashMap<String, Arc<dyn SecretStore>>,
+    default: Option<String>,
+}
+
+impl SecretProviders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the references of the form `name:...` from `store`.
+    pub fn with_provider(mut self, name: impl Into<String>, store: Arc<dyn SecretStore>) -> Self {
+        self.providers.insert(name.into(), store);
+        self
+    }
+
+    /// Resolves the references without a provider from the provider `name`.
+    pub fn with_default(mut self, name: impl Into<String>) -> Self {
+        self.default = Some(name.into());
+        self
+    }
+
+    /// Returns whether no provider is registered.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    fn provider(&self, name: &str) -> anyhow::Result<(&dyn SecretStore, String)> {
+        let (provider, secret) = match name.split_once(':') {
+            Some((provider, secret)) if self.providers.contains_key(provider) => (provider, secret),
+            _ => match &self.default {
+                Some(default) => (default.as_str(), name),
+                None => bail!(
+                    "secret '{name}' must name its provider, one of {}",
+                    self.names()
+                ),
+            },
+        };
+        let store = self
+            .providers
+            .get(provider)
+            .with_context(|| format!("unknown secret provider '{provider}'"))?;
+        Ok((store.as_ref(), secret.to_string()))
+    }
+
+    fn names(&self) -> String {
+        let mut names: Vec<_> = self.providers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names.join(", ")
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretStore for SecretProviders {
+    async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let (store, secret) = self.provider(name)?;
+        store.get(&secret).await
+    }
+}
+
+/// Splits `path#field` into the path of a secret and the field of it to read.
+#[cfg(feature = "remote-secrets")]
+fn split_field(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('#') {
+        Some((path, field)) => (path, Some(field)),
+        None => (name, None),
+    }
+}
+
+#[cfg(feature = "remote-secrets")]
+pub use remote::{AwsSecretsManager, VaultSecrets};
+
+#[cfg(feature = "remote-secrets")]
+mod remote {
+    use std::time::Duration;
+
+    use anyhow::{Context as _, bail};
+    use sha2::{Digest as _, Sha256};
+
+    use super::split_field;
+    use crate::host::http::signing::{SigningMethod, SigningRule};
+    use crate::host::interpolate::SecretStore;
+    use crate::plugin::blobstore::s3::{S3Credentials, hex};
+
+    /// How long to wait for a provider to respond
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    }
+
+    /// Reads secrets from the KV version 2 engine of HashiCorp Vault. The
+    /// secret `path#field` is the field `field` of the secret at `path`, the
+    /// field `value` without one.
+    #[derive(Clone)]
+    pub struct VaultSecrets {
+        addr: String,
+        token: String,
+        mount: String,
+        namespace: Option<String>,
+        client: reqwest::Client,
+    }
+
+    impl VaultSecrets {
+        /// Reads secrets from the engine mounted at `secret` of the Vault at
+        /// `addr`, e.g. `https://vault.internal:8200`, with `token`.
+        pub fn new(addr: impl Into<String>, token: impl Into<String>) -> Self {
+            Self {
+                addr: addr.into().trim_end_matches('/').to_string(),
+                token: token.into(),
+                mount: "secret".to_string(),
+                namespace: None,
+                client: client(),
+            }
+        }
+
+        /// Reads secrets from the engine mounted at `mount`.
+        pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+            self.mount = mount.into().trim_matches('/').to_string();
+            self
+        }
+
+        /// Reads secrets from the Vault Enterprise namespace `namespace`.
+        pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+            self.namespace = Some(namespace.into());
+            self
+        }
+    }
+
+    impl std::fmt::Debug for VaultSecrets {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("VaultSecrets")
+                .field("addr", &self.addr)
+                .field("mount", &self.mount)
+                .field("namespace", &self.namespace)
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SecretStore for VaultSecrets {
+        async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+            let (path, field) = split_field(name);
+            let url = format!(
+                "{}/v1/{}/data/{}",
+                self.addr,
+                self.mount,
+                path.trim_matches('/')
+            );
+            let mut request = self.client.get(&url).header("x-vault-token", &self.token);
+            if let Some(namespace) = &self.namespace {
+                request = request.header("x-vault-namespace", namespace);
+            }
+            let response = request
+                .send()
+                .await
+                .with_context(|| format!("failed to reach Vault at {}", self.addr))?;
+            let status = response.status();
+            if status == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !status.is_success() {
+                bail!("Vault responded with {status} for secret '{path}'");
+            }
+            let body: serde_json::Value = serde_json::from_slice(
+                &response
+                    .bytes()
+                    .await
+                    .context("failed to read Vault response")?,
+            )
+            .context("invalid Vault response")?;
+            let field = field.unwrap_or("value");
+            match body.pointer("/data/data").and_then(|data| data.get(field)) {
+                Some(serde_json::Value::String(value)) => Ok(Some(value.clone())),
+                Some(value) => Ok(Some(value.to_string())),
+                None => bail!("Vault secret '{path}' has no field '{field}'"),
+            }
+        }
+    }
+
+    /// Reads secrets from AWS Secrets Manager. The secret `id#key` is the key
+    /// `key` of the JSON object stored in the secret `id`, the whole string
+    /// of the secret without one.
+    #[derive(Clone)]
+    pub struct AwsSecretsManager {
+        signer: SigningRule,
+        endpoint: String,
+        client: reqwest::Client,
+    }
+
+    impl AwsSecretsManager {
+        /// Reads secrets of the Secrets Manager of `region`.
+        pub fn new(credentials: S3Credentials, region: impl Into<String>) -> Self {
+            let region = region.into();
+            let endpoint = format!("https://secretsmanager.{region}.amazonaws.com/");
+            Self {
+                signer: SigningRule {
+                    hosts: Vec::new(),
+                    namespaces: Vec::new(),
+                    method: SigningMethod::Sigv4 {
+                        access_key_id: credentials.access_key_id,
+                        secret_access_key: credentials.secret_access_key,
+                        session_token: credentials.session_token,
+                        region,
+                        service: "secretsmanager".to_string(),
+                    },
+                },
+                endpoint,
+                client: client(),
+            }
+        }
+
+        /// Sends requests to a compatible endpoint, e.g. LocalStack.
+        pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+            self.endpoint = endpoint.into();
+            self
+        }
+    }
+
+    impl std::fmt::Debug for AwsSecretsManager {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("AwsSecretsManager")
+                .field("endpoint", &self.endpoint)
+                .field("signer", &self.signer.method)
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SecretStore for AwsSecretsManager {
+        async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+            let (id, key) = split_field(name);
+            let body = serde_json::to_vec(&serde_json::json!({ "SecretId": id }))?;
+            let (mut parts, ()) = hyper::Request::post(&self.endpoint)
+                .header("content-type", "application/x-amz-json-1.1")
+                .header("x-amz-target", "secretsmanager.GetSecretValue")
+                .body(())
+                .with_context(|| format!("invalid endpoint '{}'", self.endpoint))?
+                .into_parts();
+            self.signer
+                .sign_parts(&mut parts, &hex(&Sha256::digest(&body)), chrono::Utc::now())
+                .map_err(|e| anyhow::anyhow!("failed to sign request: {e:?}"))?;
+            let mut request = self.client.post(&self.endpoint).body(body);
+            for (name, value) in &parts.headers {
+                request = request.header(name.as_str(), value.as_bytes());
+            }
+            let response = request
+                .send()
+                .await
+                .with_context(|| format!("failed to reach {}", self.endpoint))?;
+            let status = response.status();
+            let body: serde_json::Value = serde_json::from_slice(
+                &response
+                    .bytes()
+                    .await
+                    .context("failed to read Secrets Manager response")?,
+            )
+            .unwrap_or_default();
+            if !status.is_success() {
+                let error = body
+                    .get("__type")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default();
+                if error.ends_with("ResourceNotFoundException") {
+                    return Ok(None);
+                }
+                bail!("Secrets Manager responded with {status} for secret '{id}': {error}");
+            }
+            let secret = body
+                .get("SecretString")
+                .and_then(serde_json::Value::as_str)
+                .with_context(|| format!("secret '{id}' has no string value"))?;
+            let Some(key) = key else {
+                return Ok(Some(secret.to_string()));
+            };
+            let fields: serde_json::Value = serde_json::from_str(secret)
+                .with_context(|| format!("secret '{id}' is not a JSON object"))?;
+            match fields.get(key) {
+                Some(serde_json::Value::String(value)) => Ok(Some(value.clone())),
+                Some(value) => Ok(Some(value.to_string())),
+                None => bail!("secret '{id}' has no key '{key}'"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("token"), "s3cr3t\n").unwrap();
+        std::fs::create_dir(dir.path().join("db")).unwrap();
+        std::fs::write(dir.path().join("db/password"), "hunter2").unwrap();
+        let secrets = FileSecrets::new(dir.path());
+
+        assert_eq!(secrets.get("token").await.unwrap().unwrap(), "s3cr3t");
+        assert_eq!(
+            secrets.get("db/password").await.unwrap().unwrap(),
+            "hunter2"
+        );
+        assert!(secrets.get("missing").await.unwrap().is_none());
+        assert!(secrets.get("../token").await.is_err());
+        assert!(secrets.get("/etc/passwd").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_providers() {
+        let file = HashMap::from([("token".to_string(), "from-file".to_string())]);
+        let vault = HashMap::from([("db#password".to_string(), "from-vault".to_string())]);
+        let providers = SecretProviders::new()
+            .with_provider("file", Arc::new(file))
+            .with_provider("vault", Arc::new(vault));
+
+        assert_eq!(
+            providers.get("file:token").await.unwrap().unwrap(),
+            "from-file"
+        );
+        assert_eq!(
+            providers.get("vault:db#password").await.unwrap().unwrap(),
+            "from-vault"
+        );
+        let err = providers.get("token").await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("must name its provider, one of file, vault")
+        );
+
+        let providers = providers.with_default("file");
+        assert_eq!(providers.get("token").await.unwrap().unwrap(), "from-file");
+        assert!(providers.get("aws:token").await.unwrap().is_none());
+    }
+}
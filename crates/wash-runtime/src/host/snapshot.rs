@@ -0,0 +1,208 @@
+//! Snapshots of the state of a host.
+//!
+//! A [`HostSnapshot`] holds the definitions of the workloads a host runs, in
+//! the order they started, the routes they serve, the pins and holds
+//! overriding those routes, the weights shifted between workloads splitting
+//! a route and the compiled form of their components. A host restarted, or
+//! upgraded, with [`Host::restore`](super::Host::restore) starts the same
+//! workloads with the same IDs, registering their routes in the same order
+//! after its pins, without compiling components again when the compiled form
+//! matches its engine. With [`Host::start_restored`](super::Host::start_restored)
+//! the host only accepts HTTP requests once all of them are registered.
+//!
+//! Workload definitions are saved as they were requested, so `${secret:NAME}`
+//! references are resolved again on restore and secrets never reach the
+//! snapshot. Compiled components are native code: only restore snapshots the
+//! host wrote itself, from a location only it can write to.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context as _, ensure};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use super::http::{HttpRouteConfig, RouteEntry, RouteHold, RoutePin, RouteWeight};
+use crate::types::Workload;
+use crate::wit::WitInterface;
+
+/// Version of the snapshot format, bumped on incompatible changes
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// The state of a host, see the [module docs](self).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostSnapshot {
+    pub format: u32,
+    /// The host the snapshot was taken on
+    pub host_id: String,
+    /// The version of the runtime that took the snapshot
+    pub host_version: String,
+    pub taken_at: chrono::DateTime<chrono::Utc>,
+    /// Running workloads, in the order they started
+    pub workloads: Vec<WorkloadSnapshot>,
+    /// The HTTP routes of the workloads
+    pub routes: Vec<RouteEntry>,
+    /// Pins overriding the routes, see [`RoutePin`]
+    #[serde(default)]
+    pub pins: Vec<RoutePin>,
+    /// Holds answering requests instead of the routes, see [`RouteHold`]
+    #[serde(default)]
+    pub holds: Vec<RouteHold>,
+    /// Weights shifted between workloads splitting a route, see
+    /// [`RouteWeight`]
+    #[serde(default)]
+    pub weights: Vec<RouteWeight>,
+    /// Compiled components, by the digest of their bytes, see
+    /// [`crate::engine::component_digest`]
+    pub artifacts: BTreeMap<String, Artifact>,
+}
+
+/// A running workload in a [`HostSnapshot`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadSnapshot {
+    pub workload_id: String,
+    pub workload: Workload,
+}
+
+/// A compiled component in a [`HostSnapshot`]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Artifact(#[serde(with = "crate::types::base64_bytes")] pub Bytes);
+
+impl std::fmt::Debug for Artifact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Artifact({} bytes)", self.0.len())
+    }
+}
+
+/// What [`Host::restore`](super::Host::restore) did with a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Workloads running again
+    pub restored: Vec<String>,
+    /// Workloads that failed to start, with the error
+    pub failed: Vec<(String, String)>,
+    /// Compiled components that were loaded instead of compiled again
+    pub artifacts_loaded: usize,
+}
+
+impl HostSnapshot {
+    /// Returns the HTTP routes the given workloads serve.
+    pub(crate) fn routes_of(workloads: &[WorkloadSnapshot]) -> Vec<RouteEntry> {
+        let incoming_handler = WitInterface::from("wasi:http/incoming-handler");
+        workloads
+            .iter()
+            .filter_map(|snapshot| {
+                let workload = &snapshot.workload;
+                let iface = workload
+                    .host_interfaces
+                    .iter()
+                    .find(|iface| iface.contains(&incoming_handler))?;
+                Some(RouteEntry {
+                    route: HttpRouteConfig::try_from(iface).ok()?,
+                    workload_id: snapshot.workload_id.clone(),
+                    workload_name: format!("{}/{}", workload.namespace, workload.name),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the snapshot to `path`, replacing it atomically.
+    pub async fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(self).context("failed to serialize host snapshot")?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, json)
+            .await
+            .with_context(|| format!("failed to write host snapshot {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .with_context(|| format!("failed to write host snapshot {}", path.display()))
+    }
+
+    /// Reads a snapshot written with [`HostSnapshot::write`].
+    pub async fn read(path: &Path) -> anyhow::Result<Self> {
+        let json = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("failed to read host snapshot {}", path.display()))?;
+        let snapshot: HostSnapshot = serde_json::from_slice(&json)
+            .with_context(|| format!("failed to parse host snapshot {}", path.display()))?;
+        ensure!(
+            snapshot.format == SNAPSHOT_FORMAT,
+            "host snapshot {} has format {}, this host reads format {SNAPSHOT_FORMAT}",
+            path.display(),
+            snapshot.format
+        );
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Component;
+
+    #[tokio::test]
+    async fn test_write_read() {
+        let workloads = vec![
+            WorkloadSnapshot {
+                workload_id: "a".to_string(),
+                workload: Workload::builder("default", "api")
+                    .with_component(Component::new(&b"\0asm"[..]))
+                    .with_http_route("localhost", "/api")
+                    .build(),
+            },
+            WorkloadSnapshot {
+                workload_id: "b".to_string(),
+                workload: Workload::builder("default", "worker")
+                    .with_component(Component::new(&b"\0asm"[..]))
+                    .build(),
+            },
+        ];
+        let routes = HostSnapshot::routes_of(&workloads);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].workload_id, "a");
+        assert_eq!(routes[0].workload_name, "default/api");
+
+        let snapshot = HostSnapshot {
+            format: SNAPSHOT_FORMAT,
+            host_id: "host".to_string(),
+            host_version: "0.0.0".to_string(),
+            taken_at: chrono::Utc::now(),
+            workloads,
+            routes,
+            pins: vec![RoutePin::new("localhost", "default/api").with_path("/api")],
+            holds: vec![RouteHold::new("localhost").with_retry_after_secs(60)],
+            weights: vec![RouteWeight::new("default/api", 10)],
+            artifacts: BTreeMap::from([(
+                "digest".to_string(),
+                Artifact(Bytes::from_static(b"compiled")),
+            )]),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        snapshot.write(&path).await.unwrap();
+        let read = HostSnapshot::read(&path).await.unwrap();
+        assert_eq!(read.workloads.len(), 2);
+        assert_eq!(read.workloads[0].workload, snapshot.workloads[0].workload);
+        assert_eq!(read.routes, snapshot.routes);
+        assert_eq!(read.pins, snapshot.pins);
+        assert_eq!(read.holds, snapshot.holds);
+        assert_eq!(read.weights, snapshot.weights);
+        assert_eq!(&read.artifacts["digest"].0[..], b"compiled");
+
+        let mut json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        // Snapshots taken before pins, holds and weights existed have none
+        json.as_object_mut().unwrap().remove("pins");
+        json.as_object_mut().unwrap().remove("holds");
+        json.as_object_mut().unwrap().remove("weights");
+        std::fs::write(&path, json.to_string()).unwrap();
+        let read = HostSnapshot::read(&path).await.unwrap();
+        assert!(read.pins.is_empty());
+        assert!(read.holds.is_empty());
+        assert!(read.weights.is_empty());
+
+        json["format"] = (SNAPSHOT_FORMAT + 1).into();
+        std::fs::write(&path, json.to_string()).unwrap();
+        assert!(HostSnapshot::read(&path).await.is_err());
+    }
+}
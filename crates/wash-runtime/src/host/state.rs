@@ -0,0 +1,133 @@
+//! Persisting the state of a host across restarts.
+//!
+//! A host built with
+//! [`HostBuilder::with_state_file`](super::HostBuilder::with_state_file)
+//! keeps a [`HostSnapshot`] of itself in a file while it runs: the workloads
+//! it runs, with their definitions and the digests of their components, the
+//! routes they serve and the pins, holds and weights overriding them. The
+//! file is written again shortly after any of them change, replaced
+//! atomically, so a host that crashes or is killed loses at most the changes
+//! of the last [`StateFile::with_delay`].
+//!
+//! When [`Host::start`](super::Host::start) finds the file, it restores it
+//! like [`Host::start_restored`](super::Host::start_restored): the workloads
+//! start again with the same IDs, and HTTP requests are only accepted once
+//! their routes are registered. Workloads that fail to start are logged and
+//! left out of the state. A file that can't be read is moved aside with a
+//! `.corrupt` extension and the host starts empty.
+//!
+//! The state doesn't hold compiled components, which would make every write
+//! as large as the components, so restored components are compiled again
+//! unless the engine has a component cache, see [`crate::engine::cache`].
+//! Once the host drains, the file is no longer written, so a host that shuts
+//! down restores the workloads it ran when it started draining.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+use crate::host::Host;
+use crate::host::snapshot::HostSnapshot;
+
+/// How long after a change the state is written by default
+pub const DEFAULT_STATE_WRITE_DELAY: Duration = Duration::from_millis(200);
+
+/// Where a host persists its state, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateFile {
+    path: PathBuf,
+    delay: Duration,
+}
+
+impl StateFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            delay: DEFAULT_STATE_WRITE_DELAY,
+        }
+    }
+
+    /// Writes the state `delay` after it changed, so a burst of changes is
+    /// written once.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the state, if the file exists. A file that can't be read is
+    /// moved aside.
+    pub(crate) async fn read(&self) -> Option<HostSnapshot> {
+        if !tokio::fs::try_exists(&self.path).await.unwrap_or(false) {
+            return None;
+        }
+        match HostSnapshot::read(&self.path).await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                let aside = self.path.with_extension("corrupt");
+                warn!(
+                    path = %self.path.display(),
+                    aside = %aside.display(),
+                    "failed to read host state, starting empty: {e:#}"
+                );
+                if let Err(e) = tokio::fs::rename(&self.path, &aside).await {
+                    warn!("failed to move host state aside: {e}");
+                }
+                None
+            }
+        }
+    }
+
+    /// Writes the state of `host` every time `changed` is notified, until the
+    /// host drains or is dropped.
+    pub(crate) fn spawn(self, host: Weak<Host>, changed: Arc<Notify>) {
+        tokio::spawn(async move {
+            loop {
+                changed.notified().await;
+                tokio::time::sleep(self.delay).await;
+                let Some(host) = host.upgrade() else {
+                    break;
+                };
+                if host.is_draining() {
+                    break;
+                }
+                let result = match host.snapshot(false).await {
+                    Ok(snapshot) => snapshot.write(&self.path).await,
+                    Err(e) => Err(e.into()),
+                };
+                match result {
+                    Ok(()) => debug!(path = %self.path.display(), "wrote host state"),
+                    Err(e) => warn!("failed to write host state: {e:#}"),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_missing_or_corrupt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let state = StateFile::new(&path);
+        assert!(state.read().await.is_none());
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(state.read().await.is_none());
+        // The corrupt file is kept aside rather than overwritten
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("state.corrupt")).unwrap(),
+            "{"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Selection of the TLS crypto provider and minimum protocol version.
+//!
+//! Compliance-constrained deployments can require a validated crypto module,
+//! such as AWS-LC in FIPS mode, or forbid TLS 1.2. A [`TlsPolicy`] picks the
+//! rustls crypto provider and drops the cipher suites of protocol versions
+//! below the minimum, so a connection can't negotiate them whatever versions
+//! its configuration enables.
+//!
+//! [`TlsPolicy::install`] makes the policy the process default. It covers the
+//! TLS listeners of the HTTP server and the outgoing `wasi:http` requests of
+//! components, which build their configuration from the default provider. It
+//! must be installed before any TLS configuration is built. Clients that bring
+//! their own provider, such as the NATS and OCI registry clients, are not
+//! covered.
+//!
+//! The `ring` provider needs the `tls-ring` feature and FIPS mode needs the
+//! `tls-fips` feature, which builds AWS-LC from source.
+
+use rustls::SupportedCipherSuite;
+use rustls::crypto::CryptoProvider;
+use serde::{Deserialize, Serialize};
+
+/// A rustls crypto provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsCryptoProvider {
+    /// AWS-LC
+    #[default]
+    AwsLcRs,
+    /// AWS-LC in FIPS mode
+    Fips,
+    /// ring
+    Ring,
+}
+
+impl TlsCryptoProvider {
+    /// Returns the name of the provider, e.g. `aws-lc-rs`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TlsCryptoProvider::AwsLcRs => "aws-lc-rs",
+            TlsCryptoProvider::Fips => "fips",
+            TlsCryptoProvider::Ring => "ring",
+        }
+    }
+}
+
+impl std::fmt::Display for TlsCryptoProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TlsCryptoProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aws-lc-rs" => Ok(TlsCryptoProvider::AwsLcRs),
+            "fips" => Ok(TlsCryptoProvider::Fips),
+            "ring" => Ok(TlsCryptoProvider::Ring),
+            _ => {
+                anyhow::bail!("unknown TLS crypto provider '{s}', expected aws-lc-rs, fips or ring")
+            }
+        }
+    }
+}
+
+/// A TLS protocol version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Returns the version number, e.g. `1.3`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TlsVersion::Tls12 => "1.2",
+            TlsVersion::Tls13 => "1.3",
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches("TLS").trim_start_matches("tls") {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => anyhow::bail!("unsupported TLS version '{s}', expected 1.2 or 1.3"),
+        }
+    }
+}
+
+/// The crypto provider and minimum protocol version used for TLS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TlsPolicy {
+    provider: TlsCryptoProvider,
+    min_version: TlsVersion,
+}
+
+impl TlsPolicy {
+    pub fn with_provider(mut self, provider: TlsCryptoProvider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    pub fn with_min_version(mut self, min_version: TlsVersion) -> Self {
+        self.min_version = min_version;
+        self
+    }
+
+    pub fn provider(&self) -> TlsCryptoProvider {
+        self.provider
+    }
+
+    pub fn min_version(&self) -> TlsVersion {
+        self.min_version
+    }
+
+    /// Returns the crypto provider, without the cipher suites of protocol
+    /// versions below the minimum.
+    ///
+    /// # Errors
+    /// Returns an error if the provider was not compiled in.
+    pub fn crypto_provider(&self) -> anyhow::Result<CryptoProvider> {
+        let mut provider = match self.provider {
+            TlsCryptoProvider::AwsLcRs => rustls::crypto::aws_lc_rs::default_provider(),
+            #[cfg(feature = "tls-fips")]
+            TlsCryptoProvider::Fips => rustls::crypto::default_fips_provider(),
+            #[cfg(not(feature = "tls-fips"))]
+            TlsCryptoProvider::Fips => {
+                anyhow::bail!("FIPS mode requires wash-runtime to be built with `tls-fips`")
+            }
+            #[cfg(feature = "tls-ring")]
+            TlsCryptoProvider::Ring => rustls::crypto::ring::default_provider(),
+            #[cfg(not(feature = "tls-ring"))]
+            TlsCryptoProvider::Ring => {
+                anyhow::bail!("the ring provider requires wash-runtime to be built with `tls-ring`")
+            }
+        };
+        if self.min_version == TlsVersion::Tls13 {
+            provider
+                .cipher_suites
+                .retain(|suite| matches!(suite, SupportedCipherSuite::Tls13(_)));
+        }
+        Ok(provider)
+    }
+
+    /// Makes this policy the process default.
+    ///
+    /// # Errors
+    /// Returns an error if the provider was not compiled in or another
+    /// provider was installed already, e.g. because a TLS configuration was
+    /// built before.
+    pub fn install(&self) -> anyhow::Result<()> {
+        let provider = self.crypto_provider()?;
+        if self.provider == TlsCryptoProvider::Fips {
+            anyhow::ensure!(provider.fips(), "TLS crypto provider is not in FIPS mode");
+        }
+        CryptoProvider::install_default(provider).map_err(|_| {
+            anyhow::anyhow!("a TLS crypto provider was installed before the TLS policy")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_version_drops_cipher_suites() {
+        let provider = TlsPolicy::default().crypto_provider().unwrap();
+        assert!(
+            provider
+                .cipher_suites
+                .iter()
+                .any(|suite| matches!(suite, SupportedCipherSuite::Tls12(_)))
+        );
+
+        let provider = TlsPolicy::default()
+            .with_min_version(TlsVersion::Tls13)
+            .crypto_provider()
+            .unwrap();
+        assert!(!provider.cipher_suites.is_empty());
+        assert!(
+            provider
+                .cipher_suites
+                .iter()
+                .all(|suite| matches!(suite, SupportedCipherSuite::Tls13(_)))
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "ring".parse::<TlsCryptoProvider>().unwrap(),
+            TlsCryptoProvider::Ring
+        );
+        assert!("openssl".parse::<TlsCryptoProvider>().is_err());
+        assert_eq!("1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        assert_eq!("TLS1.2".parse::<TlsVersion>().unwrap(), TlsVersion::Tls12);
+        assert!("1.1".parse::<TlsVersion>().is_err());
+    }
+}
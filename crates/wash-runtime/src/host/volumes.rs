@@ -0,0 +1,995 @@
+//! Named volumes managed by the host.
+//!
+//! `empty_dir` and `host_path` volumes are declared by a workload and live as
+//! long as it does. Named volumes are instead created, listed, inspected and
+//! deleted through the [`HostApi`](super::HostApi), and workloads mount them by
+//! name with [`VolumeType::Named`]. Their data outlives the workloads that
+//! write it, so it can be handed from one version of a workload to the next.
+//!
+//! Each volume is a directory under the host's volume root that holds the
+//! volume data in `data/` and its metadata in `volume.json`. A volume cannot be
+//! deleted while a workload mounts it.
+//!
+//! # Providers
+//!
+//! Volumes backed by something other than a host directory are prepared by a
+//! [`VolumeProvider`] when the workload starts and handed to the engine as a
+//! host path. The host registers these providers by default:
+//!
+//! - `oci`: [`OciVolumes`] unpacks the tar layers of an [`OciVolume`](crate::types::OciVolume)
+//!   artifact once per digest and mounts them read-only, whatever the mounts ask for
+//! - `nfs`: [`NfsVolumes`] mounts an [`NfsVolume`](crate::types::NfsVolume)
+//!   export with `mount -t nfs` and unmounts it when the workload stops
+//! - `s3`: [`S3Volumes`] downloads the objects of an [`S3Volume`](crate::types::S3Volume)
+//!   and uploads the files back when the workload stops, unless every mount
+//!   was read-only
+//!
+//! Providers get a scratch directory per workload and volume under
+//! `.mounts/` of the volume root, which is removed once the workload released
+//! the volume. Object keys and archive entries that would land outside of the
+//! volume are rejected.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, bail, ensure};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::host::{HostError, HostResult};
+use crate::plugin::blobstore::BlobBody;
+use crate::plugin::blobstore::s3::{S3Credentials, S3Store};
+use crate::types::{
+    HostPathVolume, S3Volume, Volume, VolumeCreateRequest, VolumeInfo, VolumeMount, VolumeType,
+    Workload,
+};
+
+/// Directory under the system temporary directory used as the volume root when
+/// none is configured
+pub const DEFAULT_VOLUME_ROOT_DIR: &str = "wash-volumes";
+/// Longest name a volume may have
+pub const MAX_VOLUME_NAME_LEN: usize = 63;
+
+const DATA_DIR: &str = "data";
+const METADATA_FILE: &str = "volume.json";
+/// Directory of the volume root holding the scratch directories of providers
+const MOUNTS_DIR: &str = ".mounts";
+/// Directory of the volume root holding unpacked OCI artifacts
+#[cfg(feature = "oci")]
+pub(crate) const OCI_DIR: &str = ".oci";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VolumeMetadata {
+    name: String,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    created_at_ms: u64,
+}
+
+/// Prepares the host directory of a volume that is not one already, see the
+/// [module docs](self).
+#[async_trait::async_trait]
+pub trait VolumeProvider: Send + Sync {
+    /// Makes `volume` available on the host and returns where. `scratch` is an
+    /// empty directory the provider may use, removed once the workload released
+    /// the volume.
+    async fn mount(&self, volume: &VolumeType, scratch: &Path) -> anyhow::Result<MountedVolume>;
+
+    /// Releases a volume once its workload stopped. `written` is whether any
+    /// mount of the volume was writable.
+    async fn unmount(
+        &self,
+        _volume: &VolumeType,
+        _mounted: &MountedVolume,
+        _written: bool,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A volume made available by a [`VolumeProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountedVolume {
+    /// The host directory to mount
+    pub path: PathBuf,
+    /// Whether all mounts of the volume are read-only
+    pub read_only: bool,
+}
+
+/// A volume a provider mounted for a workload
+struct ProvidedVolume {
+    provider: Arc<dyn VolumeProvider>,
+    volume_type: VolumeType,
+    mounted: MountedVolume,
+    written: bool,
+    scratch: PathBuf,
+}
+
+/// Creates, tracks and deletes the named volumes of a host, and mounts the
+/// volumes of its [`VolumeProvider`]s.
+pub struct VolumeManager {
+    root: PathBuf,
+    /// Workloads mounting each volume, by volume name
+    users: RwLock<HashMap<String, HashSet<String>>>,
+    /// Providers by the kind of volume they prepare
+    providers: HashMap<&'static str, Arc<dyn VolumeProvider>>,
+    /// Volumes mounted by providers, by workload
+    provided: tokio::sync::Mutex<HashMap<String, Vec<ProvidedVolume>>>,
+}
+
+impl std::fmt::Debug for VolumeManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut providers: Vec<_> = self.providers.keys().collect();
+        providers.sort();
+        f.debug_struct("VolumeManager")
+            .field("root", &self.root)
+            .field("providers", &providers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for VolumeManager {
+    fn default() -> Self {
+        Self::new(std::env::temp_dir().join(DEFAULT_VOLUME_ROOT_DIR))
+    }
+}
+
+impl VolumeManager {
+    /// Creates a manager that keeps volumes under `root`, which is created
+    /// when the first volume is, with the default providers.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let manager = Self {
+            root: root.into(),
+            users: RwLock::default(),
+            providers: HashMap::new(),
+            provided: tokio::sync::Mutex::default(),
+        }
+        .with_provider("nfs", Arc::new(NfsVolumes))
+        .with_provider("s3", Arc::new(S3Volumes));
+        #[cfg(feature = "oci")]
+        let manager = {
+            let oci = OciVolumes::new(crate::oci::OciConfig::default(), manager.root.join(OCI_DIR));
+            manager.with_provider("oci", Arc::new(oci))
+        };
+        manager
+    }
+
+    /// Uses `provider` for volumes of `kind`, one of `oci`, `nfs` and `s3`,
+    /// replacing the default provider.
+    pub fn with_provider(mut self, kind: &'static str, provider: Arc<dyn VolumeProvider>) -> Self {
+        self.providers.insert(kind, provider);
+        self
+    }
+
+    /// Returns the directory volumes are kept in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Creates an empty volume.
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidRequest`] if the name is invalid and
+    /// [`HostError::AlreadyExists`] if a volume with the name exists.
+    pub async fn create(&self, request: VolumeCreateRequest) -> HostResult<VolumeInfo> {
+        validate_name(&request.name)?;
+        tokio::fs::create_dir_all(&self.root)
+            .await
+            .with_context(|| format!("failed to create volume root {}", self.root.display()))?;
+
+        // Creating the directory is what claims the name, so concurrent creates can't both win
+        let dir = self.root.join(&request.name);
+        match tokio::fs::create_dir(&dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(HostError::AlreadyExists(format!("volume {}", request.name)));
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("failed to create volume {}", request.name))
+                    .into());
+            }
+        }
+
+        let metadata = VolumeMetadata {
+            name: request.name.clone(),
+            labels: request.labels,
+            created_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        };
+        let created = async {
+            tokio::fs::create_dir(dir.join(DATA_DIR)).await?;
+            tokio::fs::write(dir.join(METADATA_FILE), serde_json::to_vec(&metadata)?).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(e) = created {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(e
+                .context(format!("failed to create volume {}", request.name))
+                .into());
+        }
+
+        debug!(volume = request.name, path = ?dir, "created volume");
+        self.info(metadata).await
+    }
+
+    /// Lists all volumes, ordered by name.
+    pub async fn list(&self) -> HostResult<Vec<VolumeInfo>> {
+        let mut entries = match tokio::fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("failed to list volumes")
+                    .into());
+            }
+        };
+
+        let mut volumes = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .context("failed to list volumes")?
+        {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            // Skip directories that are not (or not yet) volumes
+            let Ok(metadata) = self.metadata(&name).await else {
+                continue;
+            };
+            volumes.push(self.info(metadata).await?);
+        }
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    /// Returns a volume with its size and the workloads mounting it.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the volume does not exist.
+    pub async fn inspect(&self, name: &str) -> HostResult<VolumeInfo> {
+        let metadata = self.metadata(name).await?;
+        self.info(metadata).await
+    }
+
+    /// Deletes a volume and all of its data.
+    ///
+    /// # Errors
+    /// Returns [`HostError::NotFound`] if the volume does not exist and
+    /// [`HostError::InUse`] if a workload mounts it.
+    pub async fn delete(&self, name: &str) -> HostResult<()> {
+        // Holding the lock keeps workloads from mounting the volume while it is removed
+        let users = self.users.write().await;
+        self.metadata(name).await?;
+        if let Some(workloads) = users.get(name).filter(|w| !w.is_empty()) {
+            let mut workloads: Vec<_> = workloads.iter().cloned().collect();
+            workloads.sort();
+            return Err(HostError::InUse(format!(
+                "volume {name} is mounted by workload(s) {}",
+                workloads.join(", ")
+            )));
+        }
+        tokio::fs::remove_dir_all(self.root.join(name))
+            .await
+            .with_context(|| format!("failed to delete volume {name}"))?;
+        debug!(volume = name, "deleted volume");
+        Ok(())
+    }
+
+    /// Replaces the named and provided volumes of `workload` with the host
+    /// paths of their data and records `workload_id` as mounting them. Mounts
+    /// of volumes a provider made read-only are made read-only as well.
+    ///
+    /// # Errors
+    /// Returns [`HostError::InvalidWorkload`] if a named volume does not exist
+    /// or a provider failed to mount a volume.
+    pub(crate) async fn acquire(
+        &self,
+        workload_id: &str,
+        workload: &mut Workload,
+    ) -> HostResult<()> {
+        // Providers may take a while, e.g. to pull an artifact, so they mount
+        // before the lock keeping named volumes from being deleted is taken
+        let mut provided = Vec::new();
+        for index in 0..workload.volumes.len() {
+            let volume = &workload.volumes[index];
+            let Some(kind) = provider_kind(&volume.volume_type) else {
+                continue;
+            };
+            let name = volume.name.clone();
+            let mut volume = match self.mount(workload_id, kind, volume).await {
+                Ok(volume) => volume,
+                Err(e) => {
+                    self.unmount(workload_id, provided).await;
+                    return Err(e);
+                }
+            };
+            let mut mounts = volume_mounts(workload, &name);
+            if volume.mounted.read_only {
+                mounts.iter_mut().for_each(|mount| mount.read_only = true);
+            }
+            volume.written = mounts.iter().any(|mount| !mount.read_only);
+            workload.volumes[index].volume_type = VolumeType::HostPath(HostPathVolume {
+                local_path: volume.mounted.path.to_string_lossy().to_string(),
+            });
+            provided.push(volume);
+        }
+
+        let mut users = self.users.write().await;
+        let mut acquired = Vec::new();
+        for volume in workload.volumes.iter() {
+            let VolumeType::Named(named) = &volume.volume_type else {
+                continue;
+            };
+            if self.metadata(&named.name).await.is_err() {
+                drop(users);
+                self.unmount(workload_id, provided).await;
+                return Err(HostError::InvalidWorkload(format!(
+                    "volume '{}' mounts named volume '{}', which does not exist",
+                    volume.name, named.name
+                )));
+            }
+            acquired.push(named.name.clone());
+        }
+
+        for volume in workload.volumes.iter_mut() {
+            if let VolumeType::Named(named) = &volume.volume_type {
+                volume.volume_type = VolumeType::HostPath(HostPathVolume {
+                    local_path: self.data_dir(&named.name).to_string_lossy().to_string(),
+                });
+            }
+        }
+        for name in acquired {
+            users
+                .entry(name)
+                .or_default()
+                .insert(workload_id.to_string());
+        }
+        if !provided.is_empty() {
+            self.provided
+                .lock()
+                .await
+                .insert(workload_id.to_string(), provided);
+        }
+        Ok(())
+    }
+
+    /// Records that `workload_id` no longer mounts any volume and releases the
+    /// volumes providers mounted for it.
+    pub(crate) async fn release(&self, workload_id: &str) {
+        {
+            let mut users = self.users.write().await;
+            users.retain(|_, workloads| {
+                workloads.remove(workload_id);
+                !workloads.is_empty()
+            });
+        }
+        let provided = self.provided.lock().await.remove(workload_id);
+        if let Some(provided) = provided {
+            self.unmount(workload_id, provided).await;
+        }
+    }
+
+    /// Unmounts the volumes providers mounted for `workload_id` and removes
+    /// their scratch directories.
+    async fn unmount(&self, workload_id: &str, provided: Vec<ProvidedVolume>) {
+        for volume in provided {
+            // A scratch directory is kept if its volume failed to unmount, as it
+            // may still be mounted or hold the only copy of the data
+            match volume
+                .provider
+                .unmount(&volume.volume_type, &volume.mounted, volume.written)
+                .await
+            {
+                Ok(()) => {
+                    let _ = tokio::fs::remove_dir_all(&volume.scratch).await;
+                }
+                Err(e) => {
+                    warn!(path = ?volume.scratch, error = ?e, "failed to unmount volume, keeping its directory");
+                }
+            }
+        }
+        let _ = tokio::fs::remove_dir(self.root.join(MOUNTS_DIR).join(workload_id)).await;
+    }
+
+    /// Mounts `volume` with the provider of `kind` in a fresh scratch directory.
+    async fn mount(
+        &self,
+        workload_id: &str,
+        kind: &str,
+        volume: &Volume,
+    ) -> HostResult<ProvidedVolume> {
+        let Some(provider) = self.providers.get(kind) else {
+            return Err(HostError::InvalidWorkload(format!(
+                "volume '{}' is a {kind} volume, which this host does not support",
+                volume.name
+            )));
+        };
+        let scratch = self
+            .root
+            .join(MOUNTS_DIR)
+            .join(workload_id)
+            .join(&volume.name);
+        tokio::fs::create_dir_all(&scratch)
+            .await
+            .with_context(|| format!("failed to create {}", scratch.display()))?;
+        // A leftover directory may still be mounted, so it is never cleared here
+        let leftover = tokio::fs::read_dir(&scratch)
+            .await
+            .context("failed to read scratch directory")?
+            .next_entry()
+            .await
+            .context("failed to read scratch directory")?;
+        if leftover.is_some() {
+            return Err(HostError::InvalidWorkload(format!(
+                "volume '{}' has a leftover scratch directory {}, which may still be mounted",
+                volume.name,
+                scratch.display()
+            )));
+        }
+        let mounted = match provider.mount(&volume.volume_type, &scratch).await {
+            Ok(mounted) => mounted,
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&scratch).await;
+                return Err(HostError::InvalidWorkload(format!(
+                    "failed to mount {kind} volume '{}': {e:#}",
+                    volume.name
+                )));
+            }
+        };
+        debug!(volume = volume.name, kind, path = ?mounted.path, "mounted volume");
+        Ok(ProvidedVolume {
+            provider: provider.clone(),
+            volume_type: volume.volume_type.clone(),
+            mounted,
+            written: false,
+            scratch,
+        })
+    }
+
+    fn data_dir(&self, name: &str) -> PathBuf {
+        self.root.join(name).join(DATA_DIR)
+    }
+
+    async fn metadata(&self, name: &str) -> HostResult<VolumeMetadata> {
+        validate_name(name)?;
+        let path = self.root.join(name).join(METADATA_FILE);
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(HostError::NotFound(format!("volume {name}")));
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("failed to read volume {name}"))
+                    .into());
+            }
+        };
+        Ok(serde_json::from_slice(&bytes)
+            .with_context(|| format!("malformed metadata for volume {name}"))?)
+    }
+
+    async fn info(&self, metadata: VolumeMetadata) -> HostResult<VolumeInfo> {
+        let path = self.data_dir(&metadata.name);
+        let size_path = path.clone();
+        let size_bytes = tokio::task::spawn_blocking(move || dir_size(&size_path))
+            .await
+            .context("failed to measure volume")?;
+        let mut workloads: Vec<String> = self
+            .users
+            .read()
+            .await
+            .get(&metadata.name)
+            .map(|w| w.iter().cloned().collect())
+            .unwrap_or_default();
+        workloads.sort();
+        Ok(VolumeInfo {
+            created_at: chrono::DateTime::from_timestamp_millis(metadata.created_at_ms as i64)
+                .unwrap_or_default(),
+            name: metadata.name,
+            labels: metadata.labels,
+            path,
+            size_bytes,
+            workloads,
+        })
+    }
+}
+
+/// Returns the kind of provider a volume needs, if any.
+fn provider_kind(volume_type: &VolumeType) -> Option<&'static str> {
+    match volume_type {
+        VolumeType::Oci(_) => Some("oci"),
+        VolumeType::Nfs(_) => Some("nfs"),
+        VolumeType::S3(_) => Some("s3"),
+        VolumeType::HostPath(_) | VolumeType::EmptyDir(_) | VolumeType::Named(_) => None,
+    }
+}
+
+/// Returns the mounts of the volume `name` in all components of a workload.
+fn volume_mounts<'a>(workload: &'a mut Workload, name: &str) -> Vec<&'a mut VolumeMount> {
+    workload
+        .components
+        .iter_mut()
+        .map(|component| &mut component.local_resources)
+        .chain(
+            workload
+                .service
+                .iter_mut()
+                .map(|service| &mut service.local_resources),
+        )
+        .flat_map(|resources| resources.volume_mounts.iter_mut())
+        .filter(|mount| mount.name == name)
+        .collect()
+}
+
+/// Returns where the file at `relative`, a `/` separated path, is kept in
+/// `root`, refusing paths that would leave it.
+fn contained_path(root: &Path, relative: &str) -> anyhow::Result<PathBuf> {
+    let path = Path::new(relative);
+    ensure!(
+        !relative.is_empty()
+            && path
+                .components()
+                .all(|c| matches!(c, PathComponent::Normal(_))),
+        "'{relative}' is outside of the volume"
+    );
+    Ok(root.join(path))
+}
+
+/// Returns the `/` separated paths of the regular files under `root`, without
+/// following symbolic links.
+fn list_files(root: &Path) -> std::io::Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                walk(root, &entry.path(), files)?;
+            } else if file_type.is_file()
+                && let Ok(relative) = entry.path().strip_prefix(root)
+            {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    walk(root, root, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Mounts [`OciVolume`](crate::types::OciVolume)s, see the [module docs](self).
+///
+/// Artifacts are unpacked into a directory per digest, shared by all workloads
+/// mounting them and kept for later workloads.
+#[cfg(feature = "oci")]
+#[derive(Debug, Clone)]
+pub struct OciVolumes {
+    config: crate::oci::OciConfig,
+    cache_dir: PathBuf,
+}
+
+#[cfg(feature = "oci")]
+impl OciVolumes {
+    /// Creates a provider pulling artifacts with `config` and unpacking them
+    /// into `cache_dir`.
+    pub fn new(config: crate::oci::OciConfig, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config,
+            cache_dir: cache_dir.into(),
+        }
+    }
+}
+
+#[cfg(feature = "oci")]
+#[async_trait::async_trait]
+impl VolumeProvider for OciVolumes {
+    async fn mount(&self, volume: &VolumeType, _scratch: &Path) -> anyhow::Result<MountedVolume> {
+        let VolumeType::Oci(oci) = volume else {
+            bail!("not an OCI volume");
+        };
+        let (layers, digest) = crate::oci::pull_layers(&oci.reference, self.config.clone()).await?;
+        let dir = self.cache_dir.join(digest.replace(':', "-"));
+        if !tokio::fs::try_exists(&dir).await.unwrap_or_default() {
+            // Unpack next to the final directory so concurrent pulls never see a partial one
+            let staging = self.cache_dir.join(format!(".{}", uuid::Uuid::new_v4()));
+            let target = staging.clone();
+            let unpacked = tokio::task::spawn_blocking(move || {
+                std::fs::create_dir_all(&target)?;
+                for layer in layers {
+                    // Entries that would be written outside of the target are refused
+                    tar::Archive::new(layer.as_slice()).unpack(&target)?;
+                }
+                std::io::Result::Ok(())
+            })
+            .await
+            .context("failed to unpack artifact")?;
+            if let Err(e) = unpacked {
+                let _ = tokio::fs::remove_dir_all(&staging).await;
+                return Err(anyhow::Error::new(e).context("failed to unpack artifact"));
+            }
+            if tokio::fs::rename(&staging, &dir).await.is_err() {
+                // Another workload unpacked the same digest first
+                let _ = tokio::fs::remove_dir_all(&staging).await;
+            }
+        }
+        Ok(MountedVolume {
+            path: dir,
+            read_only: true,
+        })
+    }
+}
+
+/// Mounts [`NfsVolume`](crate::types::NfsVolume)s, see the [module docs](self).
+///
+/// Mounting runs `mount` and `umount`, so the host needs the privileges to
+/// mount filesystems.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NfsVolumes;
+
+#[async_trait::async_trait]
+impl VolumeProvider for NfsVolumes {
+    async fn mount(&self, volume: &VolumeType, scratch: &Path) -> anyhow::Result<MountedVolume> {
+        let VolumeType::Nfs(nfs) = volume else {
+            bail!("not an NFS volume");
+        };
+        let mut command = tokio::process::Command::new("mount");
+        command.args(["-t", "nfs"]);
+        if let Some(options) = &nfs.options {
+            command.args(["-o", options]);
+        }
+        command
+            .arg(format!("{}:{}", nfs.server, nfs.path))
+            .arg(scratch);
+        run(command).await?;
+        Ok(MountedVolume {
+            path: scratch.to_path_buf(),
+            read_only: false,
+        })
+    }
+
+    async fn unmount(
+        &self,
+        _volume: &VolumeType,
+        mounted: &MountedVolume,
+        _written: bool,
+    ) -> anyhow::Result<()> {
+        let mut command = tokio::process::Command::new("umount");
+        command.arg(&mounted.path);
+        run(command).await
+    }
+}
+
+/// Runs `command`, failing with its error output unless it succeeds.
+async fn run(mut command: tokio::process::Command) -> anyhow::Result<()> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .await
+        .with_context(|| format!("failed to run {program}"))?;
+    ensure!(
+        output.status.success(),
+        "{program} failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(())
+}
+
+/// Mounts [`S3Volume`]s, see the [module docs](self).
+///
+/// Objects are downloaded into the scratch directory of the volume. When the
+/// workload stops, every file is uploaded and objects whose files were removed
+/// are deleted. Keys ending in `/` are taken for directory markers and skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct S3Volumes;
+
+impl S3Volumes {
+    fn store(volume: &S3Volume) -> anyhow::Result<S3Store> {
+        let store = S3Store::new(
+            S3Credentials::from_env()?,
+            volume.bucket.clone(),
+            volume.region.clone(),
+        );
+        match &volume.endpoint {
+            Some(endpoint) => store.with_endpoint(endpoint, true),
+            None => Ok(store),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl VolumeProvider for S3Volumes {
+    async fn mount(&self, volume: &VolumeType, scratch: &Path) -> anyhow::Result<MountedVolume> {
+        let VolumeType::S3(s3) = volume else {
+            bail!("not an S3 volume");
+        };
+        let store = Self::store(s3)?;
+        for key in store.list_keys(&s3.prefix).await? {
+            let Some(relative) = key
+                .strip_prefix(&s3.prefix)
+                .filter(|relative| !relative.is_empty() && !relative.ends_with('/'))
+            else {
+                continue;
+            };
+            let path = contained_path(scratch, relative)
+                .with_context(|| format!("refusing object '{key}'"))?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut body = store.get_key(&key).await?;
+            let mut file = tokio::fs::File::create(&path)
+                .await
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            tokio::io::copy(&mut body.reader, &mut file)
+                .await
+                .with_context(|| format!("failed to download object '{key}'"))?;
+        }
+        Ok(MountedVolume {
+            path: scratch.to_path_buf(),
+            read_only: false,
+        })
+    }
+
+    async fn unmount(
+        &self,
+        volume: &VolumeType,
+        mounted: &MountedVolume,
+        written: bool,
+    ) -> anyhow::Result<()> {
+        let VolumeType::S3(s3) = volume else {
+            bail!("not an S3 volume");
+        };
+        if !written {
+            return Ok(());
+        }
+        let store = Self::store(s3)?;
+        let root = mounted.path.clone();
+        let files = tokio::task::spawn_blocking(move || list_files(&root))
+            .await
+            .context("failed to list volume files")??;
+        for relative in &files {
+            let path = mounted.path.join(relative);
+            let file = tokio::fs::File::open(&path)
+                .await
+                .with_context(|| format!("failed to open {}", path.display()))?;
+            let size = file.metadata().await?.len();
+            store
+                .put_key(
+                    &format!("{}{relative}", s3.prefix),
+                    BlobBody {
+                        reader: Box::new(file),
+                        size,
+                    },
+                )
+                .await
+                .with_context(|| format!("failed to upload {relative}"))?;
+        }
+
+        let files: HashSet<&str> = files.iter().map(String::as_str).collect();
+        for key in store.list_keys(&s3.prefix).await? {
+            if let Some(relative) = key.strip_prefix(&s3.prefix)
+                && !relative.is_empty()
+                && !relative.ends_with('/')
+                && !files.contains(relative)
+            {
+                store.delete_key(&key).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a volume name is safe to use as a directory name.
+fn validate_name(name: &str) -> HostResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_VOLUME_NAME_LEN
+        && !name.starts_with(['.', '-'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(HostError::InvalidRequest(format!(
+            "volume name '{name}' must be 1 to {MAX_VOLUME_NAME_LEN} lowercase letters, digits, '-', '_' or '.' and not start with '.' or '-'"
+        )))
+    }
+}
+
+/// Returns the total size of the files under `path`, without following
+/// symbolic links. Entries that cannot be read are skipped.
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?)))
+        .map(|(path, file_type)| {
+            if file_type.is_dir() {
+                dir_size(&path)
+            } else if file_type.is_file() {
+                std::fs::metadata(&path)
+                    .map(|m| m.len())
+                    .unwrap_or_default()
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Component, NamedVolume, OciVolume};
+
+    /// Provides a read-only copy of a fixed directory
+    struct FixedVolumes(PathBuf);
+
+    #[async_trait::async_trait]
+    impl VolumeProvider for FixedVolumes {
+        async fn mount(
+            &self,
+            _volume: &VolumeType,
+            _scratch: &Path,
+        ) -> anyhow::Result<MountedVolume> {
+            Ok(MountedVolume {
+                path: self.0.clone(),
+                read_only: true,
+            })
+        }
+    }
+
+    fn workload_with(volume_type: VolumeType) -> Workload {
+        Workload::builder("test", "volumes")
+            .with_component(
+                Component::new(bytes::Bytes::from_static(b"\0asm")).with_volume_mount(
+                    VolumeMount {
+                        name: "cache".to_string(),
+                        mount_path: "/cache".to_string(),
+                        ..Default::default()
+                    },
+                ),
+            )
+            .with_volume(Volume {
+                name: "cache".to_string(),
+                volume_type,
+            })
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_volume_lifecycle() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = VolumeManager::new(root.path());
+
+        let created = manager
+            .create(VolumeCreateRequest {
+                name: "data".to_string(),
+                labels: HashMap::from([("team".to_string(), "a".to_string())]),
+            })
+            .await
+            .unwrap();
+        assert_eq!(created.size_bytes, 0);
+        assert!(matches!(
+            manager
+                .create(VolumeCreateRequest {
+                    name: "data".to_string(),
+                    labels: HashMap::new(),
+                })
+                .await,
+            Err(HostError::AlreadyExists(_))
+        ));
+
+        let mut workload = workload_with(VolumeType::Named(NamedVolume {
+            name: "data".to_string(),
+        }));
+        manager.acquire("workload-1", &mut workload).await.unwrap();
+        let VolumeType::HostPath(HostPathVolume { local_path }) = &workload.volumes[0].volume_type
+        else {
+            panic!("named volume was not resolved");
+        };
+        std::fs::write(Path::new(local_path).join("file"), b"hello").unwrap();
+
+        let inspected = manager.inspect("data").await.unwrap();
+        assert_eq!(inspected.size_bytes, 5);
+        assert_eq!(inspected.workloads, vec!["workload-1".to_string()]);
+        assert_eq!(inspected.labels["team"], "a");
+        assert!(matches!(
+            manager.delete("data").await,
+            Err(HostError::InUse(_))
+        ));
+
+        manager.release("workload-1").await;
+        manager.delete("data").await.unwrap();
+        assert!(manager.list().await.unwrap().is_empty());
+        assert!(matches!(
+            manager.inspect("data").await,
+            Err(HostError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_named_volume() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = VolumeManager::new(root.path());
+        let mut workload = workload_with(VolumeType::Named(NamedVolume {
+            name: "missing".to_string(),
+        }));
+        assert!(matches!(
+            manager.acquire("workload-1", &mut workload).await,
+            Err(HostError::InvalidWorkload(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_provided_volume() {
+        let root = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let manager = VolumeManager::new(root.path())
+            .with_provider("oci", Arc::new(FixedVolumes(data.path().to_path_buf())));
+
+        let mut workload = workload_with(VolumeType::Oci(OciVolume {
+            reference: "ghcr.io/org/data:v1".to_string(),
+        }));
+        manager.acquire("workload-1", &mut workload).await.unwrap();
+        assert_eq!(
+            workload.volumes[0].volume_type,
+            VolumeType::HostPath(HostPathVolume {
+                local_path: data.path().to_string_lossy().to_string(),
+            })
+        );
+        // The provider made the volume read-only, whatever the mount asked for
+        assert!(workload.components[0].local_resources.volume_mounts[0].read_only);
+        assert!(root.path().join(MOUNTS_DIR).join("workload-1").is_dir());
+
+        manager.release("workload-1").await;
+        assert!(!root.path().join(MOUNTS_DIR).join("workload-1").exists());
+        assert!(data.path().is_dir());
+    }
+
+    #[test]
+    fn test_contained_path() {
+        let root = Path::new("/volume");
+        assert_eq!(
+            contained_path(root, "a/b.txt").unwrap(),
+            Path::new("/volume/a/b.txt")
+        );
+        for outside in ["", "../etc/passwd", "a/../../b", "/etc/passwd", "./a"] {
+            assert!(contained_path(root, outside).is_err(), "{outside}");
+        }
+    }
+
+    #[test]
+    fn test_list_files() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("a/b")).unwrap();
+        std::fs::write(root.path().join("top"), b"1").unwrap();
+        std::fs::write(root.path().join("a/b/nested"), b"2").unwrap();
+        assert_eq!(
+            list_files(root.path()).unwrap(),
+            vec!["a/b/nested".to_string(), "top".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("data-1.v2").is_ok());
+        for invalid in ["", "..", "-data", "Data", "a/b", &"a".repeat(64)] {
+            assert!(validate_name(invalid).is_err(), "{invalid}");
+        }
+    }
+}
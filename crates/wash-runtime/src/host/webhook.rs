@@ -0,0 +1,197 @@
+//! Outbound webhooks for workload lifecycle events.
+//!
+//! A [`Webhook`] POSTs each [`LifecycleEvent`] as JSON to a URL, which lets
+//! simple integrations such as chat alerts or CI gates follow a host without
+//! watching its API. When a secret is set, each request is signed so the
+//! receiver can check it came from the host:
+//!
+//! - `x-wasmcloud-timestamp` is the time the request was signed, in seconds
+//!   since the Unix epoch
+//! - `x-wasmcloud-signature` is `sha256=` followed by the hex HMAC-SHA256 of
+//!   `<timestamp>.<body>` keyed with the secret
+//!
+//! Receivers should reject requests with an old timestamp to prevent replays.
+//!
+//! A webhook is also a [`UsageSink`]: usage reports are sent as
+//! `usage_report` events, see [`crate::host::metering`].
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+use tracing::debug;
+
+use crate::host::events::{LifecycleEvent, LifecycleEventSink, LifecycleEventType};
+use crate::host::metering::{UsageReport, UsageSink};
+
+/// Event type of usage reports
+pub const USAGE_REPORT_EVENT: &str = "usage_report";
+/// Header carrying the type of the event, e.g. `workload_started`
+pub const EVENT_HEADER: &str = "x-wasmcloud-event";
+/// Header carrying the id of the event, which is the same on every retry
+pub const DELIVERY_HEADER: &str = "x-wasmcloud-delivery";
+/// Header carrying the time the request was signed
+pub const TIMESTAMP_HEADER: &str = "x-wasmcloud-timestamp";
+/// Header carrying the signature of the request
+pub const SIGNATURE_HEADER: &str = "x-wasmcloud-signature";
+/// How long to wait for the receiver to respond
+pub const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many times a failed delivery is retried
+pub const DEFAULT_WEBHOOK_RETRIES: u32 = 3;
+
+/// Sends lifecycle events to an HTTP endpoint.
+#[derive(Clone)]
+pub struct Webhook {
+    url: String,
+    secret: Option<Vec<u8>>,
+    /// Event types to send, or all of them if `None`
+    events: Option<HashSet<LifecycleEventType>>,
+    max_retries: u32,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    /// Creates a webhook that POSTs every event to `url` unsigned.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            events: None,
+            max_retries: DEFAULT_WEBHOOK_RETRIES,
+            client: reqwest::Client::builder()
+                .timeout(DEFAULT_WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Signs requests with `secret`.
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Only sends events of the given types. Without them, the webhook
+    /// receives every event but the [frequent](LifecycleEventType::is_frequent)
+    /// ones.
+    pub fn with_events(mut self, events: impl IntoIterator<Item = LifecycleEventType>) -> Self {
+        self.events = Some(events.into_iter().collect());
+        self
+    }
+
+    /// Sets how many times a failed delivery is retried.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// POSTs `body`, retrying failed deliveries.
+    async fn deliver(&self, event_type: &str, id: &str, body: &[u8]) -> anyhow::Result<()> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.post(event_type, id, body).await {
+                Ok(()) => {
+                    debug!(url = self.url, id, "sent webhook");
+                    return Ok(());
+                }
+                Err(e) if attempts <= self.max_retries => {
+                    debug!(url = self.url, attempts, "webhook failed, retrying: {e:#}");
+                    tokio::time::sleep(Duration::from_millis(500 << attempts.min(5))).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn post(&self, event_type: &str, id: &str, body: &[u8]) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event_type)
+            .header(DELIVERY_HEADER, id);
+        if let Some(secret) = &self.secret {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, sign(secret, timestamp, body));
+        }
+        let response = request
+            .body(body.to_vec())
+            .send()
+            .await
+            .with_context(|| format!("failed to send webhook to {}", self.url))?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("webhook {} responded with {status}", self.url);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl LifecycleEventSink for Webhook {
+    async fn send(&self, event: &LifecycleEvent) -> anyhow::Result<()> {
+        // Frequent events are only sent to webhooks asking for them
+        let wanted = match &self.events {
+            Some(events) => events.contains(&event.event_type),
+            None => !event.event_type.is_frequent(),
+        };
+        if !wanted {
+            return Ok(());
+        }
+        let body = serde_json::to_vec(event).context("failed to serialize lifecycle event")?;
+        self.deliver(event.event_type.as_str(), &event.id, &body)
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl UsageSink for Webhook {
+    async fn export(&self, report: &UsageReport) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(report).context("failed to serialize usage report")?;
+        let id = uuid::Uuid::new_v4().to_string();
+        self.deliver(USAGE_REPORT_EVENT, &id, &body).await
+    }
+}
+
+/// Returns the signature of a webhook body, `sha256=<hex HMAC-SHA256>` over
+/// `<timestamp>.<body>`.
+pub fn sign(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let expected = "sha256=9d713ed406bb7076d4123f0dc2c39d2df5c654ed4b0cd56b52c8b4c940bd63ae";
+        assert_eq!(sign(b"key", 1_700_000_000, b"{}"), expected);
+        assert_ne!(sign(b"other", 1_700_000_000, b"{}"), expected);
+        assert_ne!(sign(b"key", 1_700_000_001, b"{}"), expected);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_event_is_not_sent() {
+        // Nothing listens on port 9, so a send that isn't filtered out fails
+        let webhook = Webhook::new("http://127.0.0.1:9/hook")
+            .with_events([LifecycleEventType::ServiceCrashed])
+            .with_max_retries(0);
+        let started = LifecycleEvent::new(LifecycleEventType::WorkloadStarted, "workload", "");
+        assert!(webhook.send(&started).await.is_ok());
+        let crashed = LifecycleEvent::new(LifecycleEventType::ServiceCrashed, "workload", "");
+        assert!(webhook.send(&crashed).await.is_err());
+    }
+}
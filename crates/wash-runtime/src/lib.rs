@@ -2,7 +2,9 @@
 
 pub mod engine;
 pub mod host;
+pub mod manifest;
 pub mod plugin;
+pub mod prelude;
 pub mod types;
 pub mod wit;
 
@@ -17,22 +19,14 @@ pub use wasmtime;
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
     use std::sync::Arc;
 
-    use crate::host::http::HttpServer;
-    use crate::plugin::wasi_config::WasiConfig;
-    use crate::{
-        host::HostApi,
-        types::{Workload, WorkloadStartRequest},
-    };
-
-    use super::{engine::Engine, host::HostBuilder};
+    use crate::prelude::*;
 
     #[tokio::test]
     async fn can_run_engine() -> anyhow::Result<()> {
         let engine = Engine::builder().build()?;
-        let http_handler = crate::host::http::DevRouter::default();
+        let http_handler = DevRouter::default();
         let http_plugin = HttpServer::new(http_handler, "127.0.0.1:8080".parse()?);
         let wasi_config_plugin = WasiConfig::default();
 
@@ -44,18 +38,7 @@ mod test {
 
         let host = host.start().await?;
 
-        let req = WorkloadStartRequest {
-            workload_id: uuid::Uuid::new_v4().to_string(),
-            workload: Workload {
-                namespace: "test".to_string(),
-                name: "test-workload".to_string(),
-                annotations: HashMap::new(),
-                service: None,
-                components: vec![],
-                host_interfaces: vec![],
-                volumes: vec![],
-            },
-        };
+        let req = WorkloadStartRequest::new(Workload::builder("test", "test-workload").build());
         let _res = host.workload_start(req).await?;
 
         Ok(())
@@ -0,0 +1,358 @@
+//! Declarative workload manifests.
+//!
+//! A manifest describes workloads in YAML, or JSON, instead of building
+//! [`Workload`]s in code. Components are given by an OCI image reference or
+//! a file, read relative to the manifest, and interfaces by their name, with
+//! an optional config map. Everything else has the format of the
+//! [`types`](crate::types):
+//!
+//! ```yaml
+//! workloads:
+//!   - namespace: default
+//!     name: api
+//!     components:
+//!       - image: ghcr.io/example/api:1.2.0
+//!         local_resources:
+//!           memory_limit_mb: 128
+//!           environment:
+//!             LOG_LEVEL: info
+//!       - file: ./auth.wasm
+//!         kind: middleware
+//!     host_interfaces:
+//!       - wasi:keyvalue/store,atomics@0.2.0-draft
+//!       - interface: wasi:http/incoming-handler
+//!         config:
+//!           host: api.example.com
+//!           path: /v1
+//!     volumes:
+//!       - name: cache
+//!         volume_type:
+//!           empty_dir: {}
+//! ```
+//!
+//! [`Host::apply_manifest`](crate::host::Host::apply_manifest) brings a host
+//! in line with a manifest: it starts the workloads that don't run yet,
+//! replaces those whose definition changed, see
+//! [`HostApi::workload_update`](crate::host::HostApi::workload_update), and
+//! stops the workloads it applied before that the manifest no longer lists
+//! in its namespaces. Workloads started otherwise are never stopped.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    Component, ComponentImage, LocalResources, OutboundLimits, Service, Volume, Workload,
+};
+use crate::wit::WitInterface;
+
+/// Annotation marking the workloads applied from a manifest
+pub const MANIFEST_ANNOTATION: &str = "wasmcloud.dev/manifest";
+
+/// Workloads described declaratively, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    #[serde(default)]
+    pub workloads: Vec<WorkloadSpec>,
+    /// The directory component files are read relative to
+    #[serde(skip)]
+    base_dir: Option<PathBuf>,
+}
+
+/// A workload in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkloadSpec {
+    pub namespace: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<ServiceSpec>,
+    #[serde(default)]
+    pub components: Vec<ComponentSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_interfaces: Vec<InterfaceSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<Volume>,
+    #[serde(default, skip_serializing_if = "OutboundLimits::is_unlimited")]
+    pub outbound: OutboundLimits,
+}
+
+/// Where the bytes of a component come from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ImageSpec {
+    /// An image reference, e.g. `ghcr.io/example/api:1.2.0`
+    Reference(String),
+    /// An image with a digest or credentials
+    Image(ComponentImage),
+}
+
+/// A component in a [`WorkloadSpec`], pulled from `image` or read from
+/// `file`. The other fields are those of [`Component`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ComponentSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageSpec>,
+    /// A component file, relative to the manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    #[serde(flatten)]
+    pub component: Component,
+}
+
+/// The service of a [`WorkloadSpec`], read from `file`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ServiceSpec {
+    /// A component file, relative to the manifest
+    pub file: PathBuf,
+    #[serde(default)]
+    pub local_resources: LocalResources,
+    #[serde(default)]
+    pub max_restarts: u64,
+}
+
+/// A host interface of a [`WorkloadSpec`], e.g.
+/// `wasi:http/incoming-handler`, with its config if any.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum InterfaceSpec {
+    Name(String),
+    Configured {
+        interface: String,
+        #[serde(default)]
+        config: HashMap<String, String>,
+    },
+}
+
+impl InterfaceSpec {
+    fn to_interface(&self) -> anyhow::Result<WitInterface> {
+        let (name, config) = match self {
+            InterfaceSpec::Name(name) => (name, None),
+            InterfaceSpec::Configured { interface, config } => (interface, Some(config)),
+        };
+        let mut interface: WitInterface = name.parse()?;
+        if let Some(config) = config {
+            interface.config = config.clone();
+        }
+        Ok(interface)
+    }
+}
+
+impl Manifest {
+    /// Parses a manifest in YAML or JSON. Component files are read relative
+    /// to the working directory.
+    ///
+    /// # Errors
+    /// Returns an error if the manifest is malformed.
+    pub fn parse(manifest: &str) -> anyhow::Result<Self> {
+        let manifest: Self =
+            serde_yaml::from_str(manifest).context("failed to parse workload manifest")?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Reads the manifest at `path`. Component files are read relative to
+    /// the directory of the manifest.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or the manifest is
+    /// malformed.
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read workload manifest {}", path.display()))?;
+        let mut manifest =
+            Self::parse(&contents).with_context(|| format!("in {}", path.display()))?;
+        manifest.base_dir = path.parent().map(Path::to_path_buf);
+        Ok(manifest)
+    }
+
+    /// Validates the manifest.
+    ///
+    /// # Errors
+    /// Returns an error if a workload is listed twice, a component has both
+    /// or neither of an image and a file, or an interface name is invalid.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut names = BTreeSet::new();
+        for spec in &self.workloads {
+            ensure!(
+                !spec.namespace.is_empty() && !spec.name.is_empty(),
+                "workloads in a manifest need a namespace and a name"
+            );
+            ensure!(
+                names.insert((spec.namespace.as_str(), spec.name.as_str())),
+                "workload {}/{} is listed twice",
+                spec.namespace,
+                spec.name
+            );
+            for (i, component) in spec.components.iter().enumerate() {
+                ensure!(
+                    component.image.is_some() != component.file.is_some(),
+                    "component {i} of workload {}/{} needs either an image or a file",
+                    spec.namespace,
+                    spec.name
+                );
+            }
+            for interface in &spec.host_interfaces {
+                interface.to_interface().with_context(|| {
+                    format!(
+                        "invalid interface of workload {}/{}",
+                        spec.namespace, spec.name
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The namespaces of the workloads of the manifest.
+    pub fn namespaces(&self) -> BTreeSet<&str> {
+        self.workloads
+            .iter()
+            .map(|spec| spec.namespace.as_str())
+            .collect()
+    }
+
+    /// Builds the workloads of the manifest, reading their component files
+    /// and marking them with the [`MANIFEST_ANNOTATION`]. Images are pulled
+    /// by the host when the workloads start.
+    ///
+    /// # Errors
+    /// Returns an error if a component file can't be read.
+    pub async fn workloads(&self) -> anyhow::Result<Vec<Workload>> {
+        let mut workloads = Vec::with_capacity(self.workloads.len());
+        for spec in &self.workloads {
+            let service = match &spec.service {
+                Some(service) => Some(
+                    Service::new(self.read(&service.file).await?)
+                        .with_local_resources(service.local_resources.clone())
+                        .with_max_restarts(service.max_restarts),
+                ),
+                None => None,
+            };
+            let mut components = Vec::with_capacity(spec.components.len());
+            for component in &spec.components {
+                let mut built = component.component.clone();
+                if let Some(file) = &component.file {
+                    built.bytes = self.read(file).await?;
+                }
+                built.image = component.image.as_ref().map(|image| match image {
+                    ImageSpec::Reference(reference) => ComponentImage::new(reference),
+                    ImageSpec::Image(image) => image.clone(),
+                });
+                components.push(built);
+            }
+            let mut annotations = spec.annotations.clone();
+            annotations.insert(MANIFEST_ANNOTATION.to_string(), "true".to_string());
+            workloads.push(Workload {
+                namespace: spec.namespace.clone(),
+                name: spec.name.clone(),
+                annotations,
+                service,
+                components,
+                host_interfaces: spec
+                    .host_interfaces
+                    .iter()
+                    .map(InterfaceSpec::to_interface)
+                    .collect::<anyhow::Result<_>>()?,
+                volumes: spec.volumes.clone(),
+                outbound: spec.outbound.clone(),
+            });
+        }
+        Ok(workloads)
+    }
+
+    async fn read(&self, file: &Path) -> anyhow::Result<bytes::Bytes> {
+        let path = match &self.base_dir {
+            Some(dir) => dir.join(file),
+            None => file.to_path_buf(),
+        };
+        let bytes = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read component file {}", path.display()))?;
+        Ok(bytes.into())
+    }
+}
+
+/// What [`Host::apply_manifest`](crate::host::Host::apply_manifest) did, by
+/// `namespace/name` of the workloads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    pub started: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub stopped: Vec<String>,
+    /// Workloads that failed to start, update or stop, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ComponentKind;
+
+    #[tokio::test]
+    async fn test_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("auth.wasm"), b"\0asm").unwrap();
+        let path = dir.path().join("manifest.yaml");
+        std::fs::write(
+            &path,
+            r#"
+workloads:
+  - namespace: default
+    name: api
+    components:
+      - image: ghcr.io/example/api:1.2.0
+        local_resources:
+          memory_limit_mb: 128
+      - file: auth.wasm
+        kind: middleware
+    host_interfaces:
+      - wasi:keyvalue/store@0.2.0-draft
+      - interface: wasi:http/incoming-handler
+        config:
+          host: api.example.com
+"#,
+        )
+        .unwrap();
+        let manifest = Manifest::load(&path).await.unwrap();
+        assert_eq!(manifest.namespaces(), BTreeSet::from(["default"]));
+        let workloads = manifest.workloads().await.unwrap();
+        let workload = &workloads[0];
+        assert_eq!(workload.annotations[MANIFEST_ANNOTATION], "true");
+        let [api, auth] = workload.components.as_slice() else {
+            panic!("expected two components");
+        };
+        assert_eq!(
+            api.image,
+            Some(ComponentImage::new("ghcr.io/example/api:1.2.0"))
+        );
+        assert!(api.bytes.is_empty());
+        assert_eq!(api.local_resources.memory_limit_mb, 128);
+        assert_eq!(auth.kind, ComponentKind::Middleware);
+        assert_eq!(&auth.bytes[..], b"\0asm");
+        assert_eq!(
+            workload.host_interfaces[1].config["host"],
+            "api.example.com"
+        );
+
+        // JSON is accepted as well
+        let json = r#"{"workloads": [{"namespace": "a", "name": "b", "components": [{"file": "x.wasm"}]}]}"#;
+        assert_eq!(Manifest::parse(json).unwrap().workloads.len(), 1);
+
+        for invalid in [
+            "workloads: [{namespace: a, name: b, components: [{}]}]",
+            "workloads: [{namespace: a, name: b, components: [{file: x.wasm, image: y}]}]",
+            "workloads: [{namespace: a, name: b}, {namespace: a, name: b}]",
+            "workloads: [{namespace: a, name: b, host_interfaces: [http]}]",
+        ] {
+            assert!(Manifest::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -48,9 +48,19 @@ use tracing::{debug, info, instrument, warn};
 #[deprecated = "old media type used before Wasm WG standardization"]
 const WASMCLOUD_MEDIA_TYPE: &str = "application/vnd.module.wasm.content.layer.v1+wasm";
 
+/// Media types of the layers [`pull_layers`] accepts
+const TAR_LAYER_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.oci.image.layer.v1.tar",
+    "application/vnd.oci.image.layer.v1.tar+gzip",
+    "application/vnd.docker.image.rootfs.diff.tar.gzip",
+];
+
+/// Media types of the layers [`pull_blob`] accepts
+const BLOB_LAYER_MEDIA_TYPES: &[&str] = &["application/octet-stream", "application/vnd.onnx.model"];
+
 /// Configuration for OCI operations
 /// ️ **Credential Precedence**:
-/// 1. Explicit credentials (if provided in this config)
+/// 1. Explicit credentials or token (if provided in this config)
 /// 2. Docker credential helper (system default)
 /// 3. Anonymous (if no credentials found)
 ///
@@ -63,6 +73,8 @@ const WASMCLOUD_MEDIA_TYPE: &str = "application/vnd.module.wasm.content.layer.v1
 pub struct OciConfig {
     /// Optional explicit credentials (username, password)
     pub credentials: Option<(String, String)>,
+    /// Optional explicit bearer token, used when no credentials are set
+    pub token: Option<String>,
     /// Whether to allow insecure registries (HTTP instead of HTTPS)
     pub insecure: bool,
     /// Cache directory override
@@ -89,6 +101,14 @@ impl OciConfig {
         }
     }
 
+    /// Create a new OciConfig with an explicit bearer token
+    pub fn new_with_token(token: impl Into<String>) -> Self {
+        Self {
+            token: Some(token.into()),
+            ..Default::default()
+        }
+    }
+
     /// Create a new OciConfig for insecure registries (HTTP)
     pub fn new_insecure() -> Self {
         Self {
@@ -110,6 +130,17 @@ impl OciConfig {
         self.timeout = Some(timeout);
         self
     }
+
+    /// Set the cache directory for this config
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+}
+
+/// Strips the optional `oci://` scheme from a reference
+fn strip_scheme(reference: &str) -> &str {
+    reference.strip_prefix("oci://").unwrap_or(reference)
 }
 
 /// Cache manager for OCI artifacts
@@ -230,12 +261,14 @@ impl CacheManager {
 /// Credential resolver that implements the precedence: explicit → docker creds → anonymous
 struct CredentialResolver {
     explicit_credentials: Option<(String, String)>,
+    explicit_token: Option<String>,
 }
 
 impl CredentialResolver {
-    fn new(explicit_credentials: Option<(String, String)>) -> Self {
+    fn new(explicit_credentials: Option<(String, String)>, explicit_token: Option<String>) -> Self {
         Self {
             explicit_credentials,
+            explicit_token,
         }
     }
 
@@ -247,6 +280,10 @@ impl CredentialResolver {
             debug!("using explicit credentials");
             return RegistryAuth::Basic(username.clone(), password.clone());
         }
+        if let Some(token) = &self.explicit_token {
+            debug!("using explicit token");
+            return RegistryAuth::Bearer(token.clone());
+        }
 
         // Next, try docker credential helper
         match self.get_docker_credentials(registry).await {
@@ -293,7 +330,8 @@ impl CredentialResolver {
 /// validates it, and optionally caches it for future use.
 ///
 /// # Arguments
-/// * `reference` - OCI reference (e.g., "registry.io/my/component:v1.0.0")
+/// * `reference` - OCI reference (e.g., "registry.io/my/component:v1.0.0"),
+///   optionally with an `oci://` scheme
 /// * `config` - Configuration for the pull operation
 ///
 /// # Returns
@@ -322,6 +360,7 @@ impl CredentialResolver {
 #[instrument(skip(config), fields(reference = %reference))]
 pub async fn pull_component(reference: &str, config: OciConfig) -> Result<(Vec<u8>, String)> {
     info!(reference = %reference, "Pulling component");
+    let reference = strip_scheme(reference);
 
     // Parse OCI reference
     let reference_parsed = Reference::try_from(reference)
@@ -341,51 +380,16 @@ pub async fn pull_component(reference: &str, config: OciConfig) -> Result<(Vec<u
         }
     }
 
-    // Setup credential resolver
-    let credential_resolver = CredentialResolver::new(config.credentials);
-    let auth = credential_resolver
-        .resolve_credentials(reference_parsed.registry())
-        .await;
-
-    // Configure OCI client
-    let client_config = ClientConfig {
-        protocol: if config.insecure {
-            ClientProtocol::Http
-        } else {
-            ClientProtocol::Https
-        },
-        ..Default::default()
-    };
-
-    let client = Client::new(client_config);
-
-    // Pull the component using oci-client
-    let pull_future = client.pull(
+    let image_data = pull_image(
         &reference_parsed,
-        &auth,
+        &config,
         vec![
             WASM_LAYER_MEDIA_TYPE,
             #[allow(deprecated)]
             WASMCLOUD_MEDIA_TYPE,
         ],
-    );
-
-    // Apply timeout if configured, otherwise just await the pull
-    let image_data = if let Some(timeout) = config.timeout {
-        tokio::time::timeout(timeout, pull_future)
-            .await
-            .with_context(|| {
-                format!(
-                    "timeout pulling component from {reference} after {:?}",
-                    timeout
-                )
-            })?
-            .with_context(|| format!("failed to pull component from {reference}"))?
-    } else {
-        pull_future
-            .await
-            .with_context(|| format!("failed to pull component from {reference}"))?
-    };
+    )
+    .await?;
 
     // Extract the component bytes from the first layer
     let component_data = image_data
@@ -415,6 +419,166 @@ pub async fn pull_component(reference: &str, config: OciConfig) -> Result<(Vec<u
     Ok((component_data, digest))
 }
 
+/// Pull the tar layers of an OCI artifact, e.g. files to prepopulate a volume with
+///
+/// Layers are returned in order, with gzipped layers decompressed. Unlike
+/// components, archives are not cached.
+///
+/// # Errors
+/// Returns an error if the reference is invalid, the registry is unreachable,
+/// authentication fails or the artifact has no tar layers.
+#[instrument(skip(config), fields(reference = %reference))]
+pub async fn pull_layers(reference: &str, config: OciConfig) -> Result<(Vec<Vec<u8>>, String)> {
+    let reference = strip_scheme(reference);
+    let reference_parsed = Reference::try_from(reference)
+        .with_context(|| format!("invalid OCI reference: {reference}"))?;
+    let image_data = pull_image(&reference_parsed, &config, TAR_LAYER_MEDIA_TYPES.to_vec()).await?;
+    let digest = image_data
+        .digest
+        .ok_or_else(|| anyhow!("no digest found in pulled artifact"))?;
+    if image_data.layers.is_empty() {
+        bail!("artifact {reference} has no tar layers");
+    }
+
+    let layers = image_data
+        .layers
+        .into_iter()
+        .map(|layer| {
+            if layer.media_type.ends_with("gzip") {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(
+                    &mut flate2::read::GzDecoder::new(layer.data.as_ref()),
+                    &mut data,
+                )
+                .context("failed to decompress layer")?;
+                Ok(data)
+            } else {
+                Ok(layer.data)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    info!(layers = layers.len(), digest = %digest, "Successfully pulled layers");
+    Ok((layers, digest))
+}
+
+/// Pull the file an OCI artifact holds as its single layer, e.g. a machine
+/// learning model
+///
+/// Unlike components, files are not cached.
+///
+/// # Errors
+/// Returns an error if the reference is invalid, the registry is unreachable,
+/// authentication fails or the artifact has more or less than one layer.
+#[instrument(skip(config), fields(reference = %reference))]
+pub async fn pull_blob(reference: &str, config: OciConfig) -> Result<(Vec<u8>, String)> {
+    let reference = strip_scheme(reference);
+    let reference_parsed = Reference::try_from(reference)
+        .with_context(|| format!("invalid OCI reference: {reference}"))?;
+    let image_data =
+        pull_image(&reference_parsed, &config, BLOB_LAYER_MEDIA_TYPES.to_vec()).await?;
+    let digest = image_data
+        .digest
+        .ok_or_else(|| anyhow!("no digest found in pulled artifact"))?;
+    let mut layers = image_data.layers.into_iter();
+    let (Some(layer), None) = (layers.next(), layers.next()) else {
+        bail!("artifact {reference} must have exactly one layer");
+    };
+    info!(size = layer.data.len(), digest = %digest, "Successfully pulled blob");
+    Ok((layer.data, digest))
+}
+
+/// Pulls the layers of `reference` with the given media types, resolving
+/// credentials and applying the timeout of `config`.
+async fn pull_image(
+    reference: &Reference,
+    config: &OciConfig,
+    media_types: Vec<&str>,
+) -> Result<oci_client::client::ImageData> {
+    // Setup credential resolver
+    let credential_resolver =
+        CredentialResolver::new(config.credentials.clone(), config.token.clone());
+    let auth = credential_resolver
+        .resolve_credentials(reference.registry())
+        .await;
+
+    // Configure OCI client
+    let client_config = ClientConfig {
+        protocol: if config.insecure {
+            ClientProtocol::Http
+        } else {
+            ClientProtocol::Https
+        },
+        ..Default::default()
+    };
+
+    let client = Client::new(client_config);
+    let pull_future = client.pull(reference, &auth, media_types);
+
+    // Apply timeout if configured, otherwise just await the pull
+    if let Some(timeout) = config.timeout {
+        tokio::time::timeout(timeout, pull_future)
+            .await
+            .with_context(|| format!("timeout pulling {reference} after {timeout:?}"))?
+            .with_context(|| format!("failed to pull {reference}"))
+    } else {
+        pull_future
+            .await
+            .with_context(|| format!("failed to pull {reference}"))
+    }
+}
+
+/// Pull a WebAssembly component and verify its digest
+///
+/// Like [`pull_component`], but fails unless the manifest digest of the pulled
+/// artifact is `digest`, or the digest the reference is pinned to
+/// (`registry.io/my/component@sha256:...`) if `digest` is `None`. A cached
+/// artifact with another digest, e.g. of a tag that was pushed again, is
+/// pulled again and replaces the cached one.
+///
+/// # Errors
+/// Returns the errors of [`pull_component`], or an error if the pulled
+/// artifact has another digest.
+#[instrument(skip(config), fields(reference = %reference))]
+pub async fn pull_verified_component(
+    reference: &str,
+    digest: Option<&str>,
+    config: OciConfig,
+) -> Result<(Vec<u8>, String)> {
+    let reference = strip_scheme(reference);
+    let pinned = Reference::try_from(reference)
+        .with_context(|| format!("invalid OCI reference: {reference}"))?
+        .digest()
+        .map(str::to_string);
+    let Some(expected) = digest.map(str::to_string).or(pinned) else {
+        return pull_component(reference, config).await;
+    };
+
+    let (data, pulled) = pull_component(reference, config.clone()).await?;
+    if pulled == expected {
+        return Ok((data, pulled));
+    }
+    let Some(cache_dir) = config.cache_dir.clone() else {
+        bail!("component {reference} has digest {pulled}, expected {expected}");
+    };
+    debug!(cached = %pulled, expected = %expected, "cached artifact has another digest, pulling again");
+    let (data, pulled) = pull_component(
+        reference,
+        OciConfig {
+            cache_dir: None,
+            ..config
+        },
+    )
+    .await?;
+    if pulled != expected {
+        bail!("component {reference} has digest {pulled}, expected {expected}");
+    }
+    CacheManager::new(cache_dir)
+        .write_to_cache(reference, &data, &pulled)
+        .await
+        .context("failed to cache component")?;
+    Ok((data, pulled))
+}
+
 /// Push a WebAssembly component to an OCI registry
 ///
 /// This function validates a WebAssembly component and pushes it to an OCI-compliant registry.
@@ -479,7 +643,7 @@ pub async fn push_component(
         .with_context(|| "component data is not a valid WebAssembly component")?;
 
     // Setup credential resolver
-    let credential_resolver = CredentialResolver::new(config.credentials);
+    let credential_resolver = CredentialResolver::new(config.credentials, config.token);
     let auth = credential_resolver
         .resolve_credentials(reference_parsed.registry())
         .await;
@@ -637,6 +801,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_oci_config_with_token() {
+        let config = OciConfig::new_with_token("ghp_token").with_cache_dir("/var/cache/oci");
+        assert_eq!(config.token.as_deref(), Some("ghp_token"));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/var/cache/oci")));
+        assert_eq!(
+            strip_scheme("oci://ghcr.io/org/component:v1"),
+            "ghcr.io/org/component:v1"
+        );
+        assert_eq!(
+            strip_scheme("ghcr.io/org/component:v1"),
+            "ghcr.io/org/component:v1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_credential_resolver_precedence() {
+        let resolver = CredentialResolver::new(
+            Some(("user".to_string(), "pass".to_string())),
+            Some("token".to_string()),
+        );
+        assert!(matches!(
+            resolver.resolve_credentials("ghcr.io").await,
+            RegistryAuth::Basic(user, pass) if user == "user" && pass == "pass"
+        ));
+        let resolver = CredentialResolver::new(None, Some("token".to_string()));
+        assert!(matches!(
+            resolver.resolve_credentials("ghcr.io").await,
+            RegistryAuth::Bearer(token) if token == "token"
+        ));
+    }
+
     #[test]
     fn test_oci_config_insecure() {
         let config = OciConfig::new_insecure();
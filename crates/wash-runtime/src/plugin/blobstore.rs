@@ -0,0 +1,332 @@
+//! Types shared by the `wasi:blobstore` backends.
+//!
+//! # Stores
+//!
+//! The built-in [`WasiBlobstore`](super::wasi_blobstore::WasiBlobstore) plugin
+//! keeps containers in a [`BlobStore`], picked per workload by the config of
+//! its `wasi:blobstore` interface, see [`BlobstoreBackend::from_config`]:
+//!
+//! ```json
+//! { "namespace": "wasi", "package": "blobstore", "interfaces": ["blobstore", "container", "types"],
+//!   "config": { "backend": "s3", "bucket": "objects", "region": "eu-west-1" } }
+//! ```
+//!
+//! - `memory`, the default: containers live in the memory of the host, see
+//!   [`memory::MemoryStore`]
+//! - `filesystem`: containers are directories below `path`, see
+//!   [`fs::FilesystemStore`]
+//! - `s3`: containers are key prefixes in `bucket` of an S3 compatible object
+//!   store, optionally at `endpoint` and addressed `path_style`, see
+//!   [`s3::S3Store`]
+//!
+//! Objects are streamed between the component and the filesystem and S3
+//! stores, so reading or writing them does not hold them in the memory of the
+//! host.
+//!
+//! Containers belong to the namespace of the workload opening them, see
+//! [`container_name`]: workloads of one namespace share a container of the
+//! same name, and can't read the containers of other namespaces.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{Context as _, bail, ensure};
+use tokio::io::AsyncRead;
+
+pub mod fs;
+pub mod memory;
+pub mod s3;
+
+/// Interface config key of the backend of the containers of a workload
+pub const BACKEND_CONFIG_KEY: &str = "backend";
+/// Interface config key of the directory of the `filesystem` backend
+pub const PATH_CONFIG_KEY: &str = "path";
+/// Interface config key of the bucket of the `s3` backend
+pub const BUCKET_CONFIG_KEY: &str = "bucket";
+/// Interface config key of the region of the `s3` backend, `us-east-1` if unset
+pub const REGION_CONFIG_KEY: &str = "region";
+/// Interface config key of a custom endpoint of the `s3` backend, e.g. MinIO
+pub const ENDPOINT_CONFIG_KEY: &str = "endpoint";
+/// Interface config key enabling path-style URLs for the `s3` backend
+pub const PATH_STYLE_CONFIG_KEY: &str = "path_style";
+
+/// The backend of the containers of a workload, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlobstoreBackend {
+    /// Containers in the memory of the host
+    #[default]
+    Memory,
+    /// Containers in directories below `root`
+    Filesystem { root: PathBuf },
+    /// Containers in `bucket` of an S3 compatible object store
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+        path_style: bool,
+    },
+}
+
+impl BlobstoreBackend {
+    /// Reads the backend from the config of a `wasi:blobstore` interface.
+    ///
+    /// # Errors
+    /// Returns an error if the backend is unknown, `filesystem` without a
+    /// path or `s3` without a bucket.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Self> {
+        match config.get(BACKEND_CONFIG_KEY).map(String::as_str) {
+            None | Some("memory") => Ok(Self::Memory),
+            Some("filesystem") => {
+                let root = config
+                    .get(PATH_CONFIG_KEY)
+                    .context("the filesystem blobstore backend needs a path")?;
+                Ok(Self::Filesystem {
+                    root: PathBuf::from(root),
+                })
+            }
+            Some("s3") => {
+                let bucket = config
+                    .get(BUCKET_CONFIG_KEY)
+                    .context("the s3 blobstore backend needs a bucket")?;
+                let path_style = match config.get(PATH_STYLE_CONFIG_KEY).map(String::as_str) {
+                    None | Some("false") => false,
+                    Some("true") => true,
+                    Some(other) => bail!("invalid path_style '{other}', expected true or false"),
+                };
+                Ok(Self::S3 {
+                    bucket: bucket.clone(),
+                    region: config
+                        .get(REGION_CONFIG_KEY)
+                        .cloned()
+                        .unwrap_or_else(|| "us-east-1".to_string()),
+                    endpoint: config.get(ENDPOINT_CONFIG_KEY).cloned(),
+                    path_style,
+                })
+            }
+            Some(backend) => {
+                bail!("unknown blobstore backend '{backend}', expected memory, filesystem or s3")
+            }
+        }
+    }
+}
+
+/// Returns the name a store keeps `container` of the workloads of
+/// `namespace` under.
+pub fn container_name(namespace: &str, container: &str) -> String {
+    format!("{namespace}/{container}")
+}
+
+/// Checks a container name given by a component. Names must not be empty or
+/// contain `/`, so [`container_name`] stays unambiguous.
+pub fn validate_container_name(name: &str) -> anyhow::Result<()> {
+    ensure!(
+        !name.is_empty() && name != "." && name != "..",
+        "invalid container name '{name}'"
+    );
+    ensure!(
+        !name.contains(['/', '\\', '\0']),
+        "container name '{name}' must not contain '/', '\\' or NUL"
+    );
+    Ok(())
+}
+
+/// Checks an object name given by a component. Names may contain `/`, but no
+/// empty, `.` or `..` segments, so they can't escape their container.
+pub fn validate_object_name(name: &str) -> anyhow::Result<()> {
+    ensure!(
+        !name.contains(['\\', '\0']),
+        "object name '{name}' must not contain '\\' or NUL"
+    );
+    ensure!(
+        name.split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != ".."),
+        "invalid object name '{name}'"
+    );
+    Ok(())
+}
+
+/// The content of an object, streamed to or from a store.
+pub struct BlobBody {
+    pub reader: Box<dyn AsyncRead + Send + Unpin>,
+    /// Length of the content in bytes
+    pub size: u64,
+}
+
+impl BlobBody {
+    /// Returns a body holding `data`.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            size: data.len() as u64,
+            reader: Box::new(std::io::Cursor::new(data)),
+        }
+    }
+}
+
+impl std::fmt::Debug for BlobBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlobBody")
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Metadata of a stored object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// Length of the object in bytes
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+/// Storage of `wasi:blobstore` containers.
+///
+/// Containers are named by [`container_name`] and must be created before
+/// objects are written to them. Names are checked by the plugin with
+/// [`validate_container_name`] and [`validate_object_name`] before they reach
+/// a store.
+#[async_trait::async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Returns the size of the largest object the store accepts.
+    fn max_object_size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Creates an empty container.
+    ///
+    /// # Errors
+    /// Returns an error if the container already exists.
+    async fn create_container(&self, container: &str) -> anyhow::Result<()>;
+
+    /// Returns when the container was created, in seconds since the Unix
+    /// epoch, or `None` if it does not exist.
+    async fn container_info(&self, container: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Deletes a container and all its objects. Deleting a container that
+    /// does not exist succeeds.
+    async fn delete_container(&self, container: &str) -> anyhow::Result<()>;
+
+    /// Returns the names of the objects in a container.
+    async fn list_objects(&self, container: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Returns the metadata of an object, or `None` if it does not exist.
+    async fn object_info(
+        &self,
+        container: &str,
+        object: &str,
+    ) -> anyhow::Result<Option<ObjectInfo>>;
+
+    /// Returns the bytes of an object from `start` up to, not including,
+    /// `end`. Both are clamped to the size of the object.
+    async fn read(
+        &self,
+        container: &str,
+        object: &str,
+        start: u64,
+        end: u64,
+    ) -> anyhow::Result<BlobBody>;
+
+    /// Stores `body` as an object, replacing any object of the same name.
+    async fn write(&self, container: &str, object: &str, body: BlobBody) -> anyhow::Result<()>;
+
+    /// Deletes objects of a container. Objects that do not exist are skipped.
+    async fn delete_objects(&self, container: &str, objects: &[String]) -> anyhow::Result<()>;
+
+    /// Copies an object, replacing any object of the same name at the
+    /// destination.
+    async fn copy_object(
+        &self,
+        src_container: &str,
+        src_object: &str,
+        dest_container: &str,
+        dest_object: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Deletes all objects of a container, keeping the container.
+    async fn clear(&self, container: &str) -> anyhow::Result<()> {
+        let objects = self.list_objects(container).await?;
+        self.delete_objects(container, &objects).await
+    }
+}
+
+/// Clamps the range `start..end` to an object of `size` bytes.
+pub(crate) fn clamp_range(start: u64, end: u64, size: u64) -> (u64, u64) {
+    let start = start.min(size);
+    (start, end.clamp(start, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_config() {
+        let config = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(
+            BlobstoreBackend::from_config(&config(&[])).unwrap(),
+            BlobstoreBackend::Memory
+        );
+        assert_eq!(
+            BlobstoreBackend::from_config(&config(&[
+                ("backend", "filesystem"),
+                ("path", "/var/lib/blobs")
+            ]))
+            .unwrap(),
+            BlobstoreBackend::Filesystem {
+                root: PathBuf::from("/var/lib/blobs")
+            }
+        );
+        assert_eq!(
+            BlobstoreBackend::from_config(&config(&[
+                ("backend", "s3"),
+                ("bucket", "objects"),
+                ("endpoint", "http://localhost:9000"),
+                ("path_style", "true")
+            ]))
+            .unwrap(),
+            BlobstoreBackend::S3 {
+                bucket: "objects".to_string(),
+                region: "us-east-1".to_string(),
+                endpoint: Some("http://localhost:9000".to_string()),
+                path_style: true,
+            }
+        );
+        assert!(BlobstoreBackend::from_config(&config(&[("backend", "filesystem")])).is_err());
+        assert!(BlobstoreBackend::from_config(&config(&[("backend", "s3")])).is_err());
+        assert!(
+            BlobstoreBackend::from_config(&config(&[
+                ("backend", "s3"),
+                ("bucket", "objects"),
+                ("path_style", "yes")
+            ]))
+            .is_err()
+        );
+        assert!(BlobstoreBackend::from_config(&config(&[("backend", "gcs")])).is_err());
+        assert_eq!(container_name("team-a", "uploads"), "team-a/uploads");
+    }
+
+    #[test]
+    fn test_validate_names() {
+        assert!(validate_container_name("uploads").is_ok());
+        for name in ["", ".", "..", "a/b", "a\\b"] {
+            assert!(validate_container_name(name).is_err(), "{name}");
+        }
+        assert!(validate_object_name("photo.png").is_ok());
+        assert!(validate_object_name("users/1/photo.png").is_ok());
+        for name in ["", "/etc/passwd", "a//b", "a/", "../a", "a/./b", "a\\b"] {
+            assert!(validate_object_name(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_clamp_range() {
+        assert_eq!(clamp_range(0, u64::MAX, 10), (0, 10));
+        assert_eq!(clamp_range(2, 5, 10), (2, 5));
+        assert_eq!(clamp_range(12, 20, 10), (10, 10));
+        assert_eq!(clamp_range(5, 2, 10), (5, 5));
+    }
+}
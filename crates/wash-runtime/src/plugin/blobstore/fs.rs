@@ -0,0 +1,295 @@
+//! A `wasi:blobstore` store in a directory of the host.
+//!
+//! The container `namespace/container` is the directory
+//! `root/namespace/container`, and its objects are the files below it. Object
+//! names containing `/` are stored in subdirectories.
+//!
+//! Objects are written to a temporary file next to their destination, which
+//! is renamed over it once complete, so readers never see a partial object.
+//! Temporary files start with `.wash-upload-` and are not listed.
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context as _, bail};
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt as _};
+
+use super::{BlobBody, BlobStore, ObjectInfo, clamp_range};
+
+/// Prefix of the files objects are written to before they are complete
+const UPLOAD_PREFIX: &str = ".wash-upload-";
+
+/// Containers in directories below a root directory, see the
+/// [module docs](self).
+#[derive(Clone, Debug)]
+pub struct FilesystemStore {
+    root: PathBuf,
+}
+
+impl FilesystemStore {
+    /// Creates a store keeping containers below `root`, which is created if
+    /// it does not exist.
+    pub fn new(root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)
+            .with_context(|| format!("failed to create blobstore directory {}", root.display()))?;
+        Ok(Self { root })
+    }
+
+    fn container_dir(&self, container: &str) -> PathBuf {
+        self.root.join(container)
+    }
+
+    /// Returns the directory of a container, failing if it does not exist.
+    async fn existing_container_dir(&self, container: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.container_dir(container);
+        match tokio::fs::metadata(&dir).await {
+            Ok(metadata) if metadata.is_dir() => Ok(dir),
+            Ok(_) => bail!("container '{container}' does not exist"),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                bail!("container '{container}' does not exist")
+            }
+            Err(e) => Err(e).with_context(|| format!("failed to open container '{container}'")),
+        }
+    }
+
+    /// Writes `body` to `path` through a temporary file in the same directory.
+    async fn write_file(path: &Path, mut body: BlobBody) -> anyhow::Result<()> {
+        let dir = path.parent().context("object has no parent directory")?;
+        tokio::fs::create_dir_all(dir).await?;
+        let partial = dir.join(format!("{UPLOAD_PREFIX}{}", uuid::Uuid::new_v4()));
+        let result = async {
+            let mut file = tokio::fs::File::create(&partial).await?;
+            tokio::io::copy(&mut body.reader, &mut file).await?;
+            file.flush().await?;
+            file.sync_all().await?;
+            tokio::fs::rename(&partial, path).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&partial).await;
+        }
+        result
+    }
+}
+
+fn seconds_since_epoch(time: std::io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[async_trait::async_trait]
+impl BlobStore for FilesystemStore {
+    async fn create_container(&self, container: &str) -> anyhow::Result<()> {
+        let dir = self.container_dir(container);
+        if let Some(parent) = dir.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        match tokio::fs::create_dir(&dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                bail!("container '{container}' already exists")
+            }
+            Err(e) => Err(e).with_context(|| format!("failed to create container '{container}'")),
+        }
+    }
+
+    async fn container_info(&self, container: &str) -> anyhow::Result<Option<u64>> {
+        match tokio::fs::metadata(self.container_dir(container)).await {
+            Ok(metadata) if metadata.is_dir() => Ok(Some(seconds_since_epoch(
+                metadata.created().or_else(|_| metadata.modified()),
+            ))),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to open container '{container}'")),
+        }
+    }
+
+    async fn delete_container(&self, container: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_dir_all(self.container_dir(container)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("failed to delete container '{container}'"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn list_objects(&self, container: &str) -> anyhow::Result<Vec<String>> {
+        let root = self.existing_container_dir(container).await?;
+        let mut objects = Vec::new();
+        let mut dirs = vec![(root, String::new())];
+        while let Some((dir, prefix)) = dirs.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    dirs.push((entry.path(), format!("{prefix}{name}/")));
+                } else if file_type.is_file() && !name.starts_with(UPLOAD_PREFIX) {
+                    objects.push(format!("{prefix}{name}"));
+                }
+            }
+        }
+        objects.sort();
+        Ok(objects)
+    }
+
+    async fn object_info(
+        &self,
+        container: &str,
+        object: &str,
+    ) -> anyhow::Result<Option<ObjectInfo>> {
+        let dir = self.existing_container_dir(container).await?;
+        match tokio::fs::metadata(dir.join(object)).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ObjectInfo {
+                size: metadata.len(),
+                created_at: seconds_since_epoch(metadata.modified()),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to open object '{object}'")),
+        }
+    }
+
+    async fn read(
+        &self,
+        container: &str,
+        object: &str,
+        start: u64,
+        end: u64,
+    ) -> anyhow::Result<BlobBody> {
+        let dir = self.existing_container_dir(container).await?;
+        let mut file = match tokio::fs::File::open(dir.join(object)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                bail!("object '{object}' does not exist")
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to open object '{object}'")),
+        };
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            bail!("object '{object}' does not exist");
+        }
+        let (start, end) = clamp_range(start, end, metadata.len());
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(BlobBody {
+            reader: Box::new(file.take(end - start)),
+            size: end - start,
+        })
+    }
+
+    async fn write(&self, container: &str, object: &str, body: BlobBody) -> anyhow::Result<()> {
+        let dir = self.existing_container_dir(container).await?;
+        Self::write_file(&dir.join(object), body)
+            .await
+            .with_context(|| format!("failed to write object '{object}'"))
+    }
+
+    async fn delete_objects(&self, container: &str, objects: &[String]) -> anyhow::Result<()> {
+        let dir = self.existing_container_dir(container).await?;
+        for object in objects {
+            match tokio::fs::remove_file(dir.join(object)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("failed to delete object '{object}'"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn copy_object(
+        &self,
+        src_container: &str,
+        src_object: &str,
+        dest_container: &str,
+        dest_object: &str,
+    ) -> anyhow::Result<()> {
+        let body = self
+            .read(src_container, src_object, 0, u64::MAX)
+            .await
+            .context("failed to read source object")?;
+        self.write(dest_container, dest_object, body)
+            .await
+            .context("failed to write destination object")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all(body: BlobBody) -> Vec<u8> {
+        let mut reader = body.reader;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FilesystemStore::new(dir.path()).unwrap();
+        store.create_container("ns/c").await.unwrap();
+        assert!(store.create_container("ns/c").await.is_err());
+        assert!(store.container_info("ns/c").await.unwrap().is_some());
+        assert!(store.container_info("other/c").await.unwrap().is_none());
+
+        store
+            .write(
+                "ns/c",
+                "users/1/photo.png",
+                BlobBody::from_bytes(b"hello world".to_vec()),
+            )
+            .await
+            .unwrap();
+        assert!(dir.path().join("ns/c/users/1/photo.png").is_file());
+        assert!(
+            store
+                .write("ns/missing", "a", BlobBody::from_bytes(vec![]))
+                .await
+                .is_err()
+        );
+
+        let body = store
+            .read("ns/c", "users/1/photo.png", 6, u64::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.size, 5);
+        assert_eq!(read_all(body).await, b"world");
+        assert!(store.read("ns/c", "missing", 0, 1).await.is_err());
+        assert_eq!(
+            store
+                .object_info("ns/c", "users/1/photo.png")
+                .await
+                .unwrap()
+                .unwrap()
+                .size,
+            11
+        );
+
+        store
+            .copy_object("ns/c", "users/1/photo.png", "ns/c", "copy")
+            .await
+            .unwrap();
+        std::fs::write(dir.path().join("ns/c/.wash-upload-partial"), b"x").unwrap();
+        assert_eq!(
+            store.list_objects("ns/c").await.unwrap(),
+            ["copy", "users/1/photo.png"]
+        );
+
+        store.clear("ns/c").await.unwrap();
+        assert!(store.list_objects("ns/c").await.unwrap().is_empty());
+        store.delete_container("ns/c").await.unwrap();
+        store.delete_container("ns/c").await.unwrap();
+        assert!(store.container_info("ns/c").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,297 @@
+//! A `wasi:blobstore` store in the memory of the host.
+//!
+//! Containers are kept as long as the host runs, across invocations and
+//! restarts of the workloads using them, and are lost when it stops. Objects
+//! are held in memory whole, so their size is limited, see
+//! [`MemoryStore::new`].
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, bail};
+use tokio::{io::AsyncReadExt as _, sync::RwLock};
+
+use super::{BlobBody, BlobStore, ObjectInfo, clamp_range};
+
+/// Largest object stored without a limit configured
+const DEFAULT_MAX_OBJECT_SIZE: u64 = 1_000_000;
+
+/// Metadata for an object stored in memory
+#[derive(Clone, Debug)]
+pub struct ObjectData {
+    pub name: String,
+    pub container: String,
+    pub data: Vec<u8>,
+    pub created_at: u64,
+}
+
+/// In-memory container representation
+#[derive(Clone, Debug)]
+pub struct ContainerData {
+    pub name: String,
+    pub created_at: u64,
+    pub objects: HashMap<String, ObjectData>,
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Containers in the memory of the host, see the [module docs](self).
+#[derive(Debug)]
+pub struct MemoryStore {
+    /// Containers by name
+    containers: RwLock<HashMap<String, ContainerData>>,
+    max_object_size: u64,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl MemoryStore {
+    /// Creates a store accepting objects of up to `max_object_size` bytes,
+    /// 1 MB by default.
+    pub fn new(max_object_size: Option<u64>) -> Self {
+        Self {
+            containers: RwLock::default(),
+            max_object_size: max_object_size.unwrap_or(DEFAULT_MAX_OBJECT_SIZE),
+        }
+    }
+
+    /// Returns a copy of a container, if it exists.
+    pub async fn container(&self, container: &str) -> Option<ContainerData> {
+        self.containers.read().await.get(container).cloned()
+    }
+}
+
+fn missing_container(container: &str) -> anyhow::Error {
+    anyhow::anyhow!("container '{container}' does not exist")
+}
+
+#[async_trait::async_trait]
+impl BlobStore for MemoryStore {
+    fn max_object_size(&self) -> Option<u64> {
+        Some(self.max_object_size)
+    }
+
+    async fn create_container(&self, container: &str) -> anyhow::Result<()> {
+        let mut containers = self.containers.write().await;
+        if containers.contains_key(container) {
+            bail!("container '{container}' already exists");
+        }
+        containers.insert(
+            container.to_string(),
+            ContainerData {
+                name: container.to_string(),
+                created_at: now(),
+                objects: HashMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn container_info(&self, container: &str) -> anyhow::Result<Option<u64>> {
+        let containers = self.containers.read().await;
+        Ok(containers.get(container).map(|c| c.created_at))
+    }
+
+    async fn delete_container(&self, container: &str) -> anyhow::Result<()> {
+        self.containers.write().await.remove(container);
+        Ok(())
+    }
+
+    async fn list_objects(&self, container: &str) -> anyhow::Result<Vec<String>> {
+        let containers = self.containers.read().await;
+        let container = containers
+            .get(container)
+            .ok_or_else(|| missing_container(container))?;
+        let mut objects: Vec<String> = container.objects.keys().cloned().collect();
+        objects.sort();
+        Ok(objects)
+    }
+
+    async fn object_info(
+        &self,
+        container: &str,
+        object: &str,
+    ) -> anyhow::Result<Option<ObjectInfo>> {
+        let containers = self.containers.read().await;
+        let container = containers
+            .get(container)
+            .ok_or_else(|| missing_container(container))?;
+        Ok(container.objects.get(object).map(|o| ObjectInfo {
+            size: o.data.len() as u64,
+            created_at: o.created_at,
+        }))
+    }
+
+    async fn read(
+        &self,
+        container: &str,
+        object: &str,
+        start: u64,
+        end: u64,
+    ) -> anyhow::Result<BlobBody> {
+        let containers = self.containers.read().await;
+        let data = &containers
+            .get(container)
+            .ok_or_else(|| missing_container(container))?
+            .objects
+            .get(object)
+            .with_context(|| format!("object '{object}' does not exist"))?
+            .data;
+        let (start, end) = clamp_range(start, end, data.len() as u64);
+        Ok(BlobBody::from_bytes(
+            data[start as usize..end as usize].to_vec(),
+        ))
+    }
+
+    async fn write(&self, container: &str, object: &str, body: BlobBody) -> anyhow::Result<()> {
+        if body.size > self.max_object_size {
+            bail!(
+                "object of {} bytes exceeds the maximum size of {} bytes",
+                body.size,
+                self.max_object_size
+            );
+        }
+        let mut data = Vec::with_capacity(body.size as usize);
+        body.reader
+            .take(self.max_object_size)
+            .read_to_end(&mut data)
+            .await
+            .context("failed to read object")?;
+
+        let mut containers = self.containers.write().await;
+        let container_data = containers
+            .get_mut(container)
+            .ok_or_else(|| missing_container(container))?;
+        container_data.objects.insert(
+            object.to_string(),
+            ObjectData {
+                name: object.to_string(),
+                container: container.to_string(),
+                data,
+                created_at: now(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn delete_objects(&self, container: &str, objects: &[String]) -> anyhow::Result<()> {
+        let mut containers = self.containers.write().await;
+        let container = containers
+            .get_mut(container)
+            .ok_or_else(|| missing_container(container))?;
+        for object in objects {
+            container.objects.remove(object);
+        }
+        Ok(())
+    }
+
+    async fn copy_object(
+        &self,
+        src_container: &str,
+        src_object: &str,
+        dest_container: &str,
+        dest_object: &str,
+    ) -> anyhow::Result<()> {
+        let mut containers = self.containers.write().await;
+        let data = containers
+            .get(src_container)
+            .with_context(|| format!("source container '{src_container}' does not exist"))?
+            .objects
+            .get(src_object)
+            .with_context(|| format!("source object '{src_object}' does not exist"))?
+            .data
+            .clone();
+        let dest = containers
+            .get_mut(dest_container)
+            .with_context(|| format!("destination container '{dest_container}' does not exist"))?;
+        dest.objects.insert(
+            dest_object.to_string(),
+            ObjectData {
+                name: dest_object.to_string(),
+                container: dest_container.to_string(),
+                data,
+                created_at: now(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn clear(&self, container: &str) -> anyhow::Result<()> {
+        let mut containers = self.containers.write().await;
+        containers
+            .get_mut(container)
+            .ok_or_else(|| missing_container(container))?
+            .objects
+            .clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all(body: BlobBody) -> Vec<u8> {
+        let mut data = Vec::new();
+        body.reader
+            .take(u64::MAX)
+            .read_to_end(&mut data)
+            .await
+            .unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new(Some(16));
+        store.create_container("ns/c").await.unwrap();
+        assert!(store.create_container("ns/c").await.is_err());
+        assert!(store.container_info("ns/c").await.unwrap().is_some());
+        assert!(store.container_info("ns/other").await.unwrap().is_none());
+
+        store
+            .write("ns/c", "a", BlobBody::from_bytes(b"hello world".to_vec()))
+            .await
+            .unwrap();
+        assert!(
+            store
+                .write("ns/c", "big", BlobBody::from_bytes(vec![0; 17]))
+                .await
+                .is_err()
+        );
+        assert!(
+            store
+                .write("ns/missing", "a", BlobBody::from_bytes(vec![]))
+                .await
+                .is_err()
+        );
+
+        let body = store.read("ns/c", "a", 6, u64::MAX).await.unwrap();
+        assert_eq!(body.size, 5);
+        assert_eq!(read_all(body).await, b"world");
+        assert_eq!(
+            store.object_info("ns/c", "a").await.unwrap().unwrap().size,
+            11
+        );
+
+        store.copy_object("ns/c", "a", "ns/c", "b").await.unwrap();
+        assert_eq!(store.list_objects("ns/c").await.unwrap(), ["a", "b"]);
+        store
+            .delete_objects("ns/c", &["a".to_string(), "missing".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(store.list_objects("ns/c").await.unwrap(), ["b"]);
+        store.clear("ns/c").await.unwrap();
+        assert!(store.list_objects("ns/c").await.unwrap().is_empty());
+        store.delete_container("ns/c").await.unwrap();
+        assert!(store.container("ns/c").await.is_none());
+    }
+}
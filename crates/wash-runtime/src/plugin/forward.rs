@@ -0,0 +1,213 @@
+//! Forwarding calls to interfaces a host lacks to peers providing them.
+//!
+//! A host built with a [`CallForwarder`], see
+//! [`HostBuilder::with_call_forwarder`](crate::host::HostBuilder::with_call_forwarder),
+//! starts workloads importing interfaces none of its plugins provide, as long
+//! as the forwarder knows a peer providing them. Every function of these
+//! interfaces is linked to a stub handing the call to the forwarder, which
+//! relays it to the peer, e.g. over NATS in a cluster. The peer serves it with
+//! [`HostApi::plugin_invoke`](crate::host::HostApi::plugin_invoke), which calls
+//! [`HostPlugin::invoke`](super::HostPlugin::invoke) on the plugin providing
+//! the interface.
+//!
+//! As with [process plugins](super::process), only plain values cross hosts:
+//! calls passing resources, futures or streams fail. Every forwarded call is
+//! timed, see [`ForwardingStats`], since it takes at least a round trip to the
+//! peer.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, ensure};
+use tracing::debug;
+use wasmtime::component::Val;
+use wasmtime::component::types::ComponentItem;
+
+use crate::engine::workload::WorkloadMetadata;
+use crate::wit::WitInterface;
+
+/// A call of a component to a function of an imported interface.
+#[derive(Debug, Clone)]
+pub struct ForwardedCall {
+    pub workload_id: String,
+    pub component_id: String,
+    /// The imported instance, e.g. `wasi:keyvalue/store@0.2.0-draft`
+    pub instance: String,
+    pub function: String,
+    pub params: Vec<Val>,
+}
+
+/// Relays calls to interfaces this host lacks to peers providing them, see
+/// the [module docs](self).
+#[async_trait::async_trait]
+pub trait CallForwarder: Send + Sync + 'static {
+    /// Returns whether a peer provides `interface`. Workloads importing it
+    /// only start if this is true when they start.
+    fn provides(&self, interface: &WitInterface) -> bool;
+
+    /// Calls the function on a peer and returns the values it returned.
+    async fn forward(&self, call: ForwardedCall) -> anyhow::Result<Vec<Val>>;
+}
+
+/// The calls forwarded to the functions of an interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardingStats {
+    pub calls: u64,
+    pub failed_calls: u64,
+    /// Time the calls took in total, round trips included
+    pub total_time: Duration,
+    /// Time the slowest call took
+    pub max_time: Duration,
+}
+
+impl ForwardingStats {
+    /// Returns the mean time of a call, or `None` if there was none.
+    pub fn mean_time(&self) -> Option<Duration> {
+        (self.calls > 0).then(|| self.total_time / self.calls as u32)
+    }
+
+    fn record(&mut self, elapsed: Duration, succeeded: bool) {
+        self.calls += 1;
+        if !succeeded {
+            self.failed_calls += 1;
+        }
+        self.total_time += elapsed;
+        self.max_time = self.max_time.max(elapsed);
+    }
+}
+
+/// A [`CallForwarder`] with the stats of the calls it forwarded, by instance.
+#[derive(Clone)]
+pub(crate) struct Forwarding {
+    forwarder: Arc<dyn CallForwarder>,
+    stats: Arc<std::sync::Mutex<BTreeMap<String, ForwardingStats>>>,
+}
+
+impl Forwarding {
+    pub(crate) fn new(forwarder: Arc<dyn CallForwarder>) -> Self {
+        Self {
+            forwarder,
+            stats: Arc::default(),
+        }
+    }
+
+    pub(crate) fn provides(&self, interface: &WitInterface) -> bool {
+        self.forwarder.provides(interface)
+    }
+
+    pub(crate) fn stats(&self) -> BTreeMap<String, ForwardingStats> {
+        self.stats
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    fn record(&self, instance: &str, elapsed: Duration, succeeded: bool) {
+        self.stats
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(instance.to_string())
+            .or_default()
+            .record(elapsed, succeeded);
+    }
+
+    /// Links every function of the `interfaces` a component imports to a
+    /// stub forwarding its calls.
+    pub(crate) fn bind(
+        &self,
+        metadata: &mut WorkloadMetadata,
+        interfaces: &HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let engine = metadata.engine().clone();
+        for (name, item) in metadata.component_imports()? {
+            let ComponentItem::ComponentInstance(instance_ty) = item else {
+                continue;
+            };
+            let import = WitInterface::from(name.as_str());
+            if !interfaces.iter().any(|i| i.contains(&import)) {
+                continue;
+            }
+            debug!(instance = name, "forwarding calls to peers");
+
+            let mut linker_instance = metadata.linker().instance(&name)?;
+            for (function, item) in instance_ty.exports(&engine) {
+                if !matches!(item, ComponentItem::ComponentFunc(_)) {
+                    continue;
+                }
+                let forwarding = self.clone();
+                let instance: Arc<str> = name.as_str().into();
+                let function_name: Arc<str> = function.into();
+                linker_instance.func_new_async(function, move |store, params, results| {
+                    let forwarding = forwarding.clone();
+                    let instance = instance.clone();
+                    let function = function_name.clone();
+                    Box::new(async move {
+                        let call = ForwardedCall {
+                            workload_id: store.data().workload_id.to_string(),
+                            component_id: store.data().component_id.to_string(),
+                            instance: instance.to_string(),
+                            function: function.to_string(),
+                            params: params.to_vec(),
+                        };
+                        let started = Instant::now();
+                        let returned = forwarding.forwarder.forward(call).await;
+                        forwarding.record(&instance, started.elapsed(), returned.is_ok());
+                        let returned = returned.with_context(|| {
+                            format!("forwarded call to {instance}#{function} failed")
+                        })?;
+                        ensure!(
+                            returned.len() == results.len(),
+                            "peer returned {} results for {instance}#{function}, expected {}",
+                            returned.len(),
+                            results.len()
+                        );
+                        for (slot, value) in results.iter_mut().zip(returned) {
+                            *slot = value;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Peers;
+
+    #[async_trait::async_trait]
+    impl CallForwarder for Peers {
+        fn provides(&self, interface: &WitInterface) -> bool {
+            WitInterface::from("wasi:keyvalue/store").contains(interface)
+        }
+
+        async fn forward(&self, call: ForwardedCall) -> anyhow::Result<Vec<Val>> {
+            Ok(call.params)
+        }
+    }
+
+    #[test]
+    fn test_forwarding_stats() {
+        let forwarding = Forwarding::new(Arc::new(Peers));
+        assert!(forwarding.provides(&WitInterface::from("wasi:keyvalue/store")));
+        assert!(!forwarding.provides(&WitInterface::from("wasi:blobstore/blobstore")));
+        assert!(forwarding.stats().is_empty());
+
+        let instance = "wasi:keyvalue/store@0.2.0-draft";
+        forwarding.record(instance, Duration::from_millis(4), true);
+        forwarding.record(instance, Duration::from_millis(10), false);
+        forwarding.record(instance, Duration::from_millis(1), true);
+        let stats = &forwarding.stats()[instance];
+        assert_eq!(stats.calls, 3);
+        assert_eq!(stats.failed_calls, 1);
+        assert_eq!(stats.total_time, Duration::from_millis(15));
+        assert_eq!(stats.max_time, Duration::from_millis(10));
+        assert_eq!(stats.mean_time(), Some(Duration::from_millis(5)));
+        assert_eq!(ForwardingStats::default().mean_time(), None);
+    }
+}
@@ -0,0 +1,220 @@
+//! Types shared by the `wasi:keyvalue` backends.
+//!
+//! Backends differ in which optional operations they can guarantee. Each one
+//! reports its [`KeyvalueCapabilities`], which decide the interfaces it offers to
+//! components, so a component that needs atomics is only placed on a host whose
+//! backend actually provides them.
+//!
+//! # Stores
+//!
+//! The built-in [`WasiKeyvalue`](super::wasi_keyvalue::WasiKeyvalue) plugin
+//! keeps buckets in a [`KeyvalueStore`], picked per workload by the config of
+//! its `wasi:keyvalue` interface, see [`KeyvalueBackend::from_config`]:
+//!
+//! ```json
+//! { "namespace": "wasi", "package": "keyvalue", "interfaces": ["store", "atomics"],
+//!   "config": { "backend": "redis", "url": "redis://cache:6379/0" } }
+//! ```
+//!
+//! - `memory`, the default: buckets live in the memory of the host, see
+//!   [`memory::MemoryStore`]
+//! - `redis`: buckets live in a Redis server, see [`redis::RedisStore`]
+//!
+//! Buckets belong to the namespace of the workload opening them, see
+//! [`bucket_name`]: workloads of one namespace share a bucket of the same
+//! name, and can't read the buckets of other namespaces.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, bail};
+
+use crate::wit::WitInterface;
+
+pub mod memory;
+pub mod redis;
+
+/// Interface config key of the backend of the buckets of a workload
+pub const BACKEND_CONFIG_KEY: &str = "backend";
+/// Interface config key of the URL of the backend, e.g. `redis://cache:6379`
+pub const URL_CONFIG_KEY: &str = "url";
+
+/// Optional `wasi:keyvalue` features supported by a backend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyvalueCapabilities {
+    /// `wasi:keyvalue/atomics` is provided and `increment` is atomic
+    pub atomics: bool,
+    /// The backend offers a host-side compare-and-swap
+    pub compare_and_swap: bool,
+    /// `wasi:keyvalue/batch` is provided
+    pub batch: bool,
+    /// Batch writes are applied all-or-nothing rather than key by key
+    pub atomic_batch: bool,
+}
+
+impl KeyvalueCapabilities {
+    /// Returns the `wasi:keyvalue` interfaces a backend with these capabilities
+    /// provides. `store` is always included.
+    pub fn interface(&self, version: Option<&str>) -> WitInterface {
+        let mut interfaces = vec!["store"];
+        if self.atomics {
+            interfaces.push("atomics");
+        }
+        if self.batch {
+            interfaces.push("batch");
+        }
+        let mut interface = format!("wasi:keyvalue/{}", interfaces.join(","));
+        if let Some(version) = version {
+            interface.push('@');
+            interface.push_str(version);
+        }
+        WitInterface::from(interface)
+    }
+}
+
+/// The backend of the buckets of a workload, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyvalueBackend {
+    /// Buckets in the memory of the host
+    #[default]
+    Memory,
+    /// Buckets in the Redis server at `url`
+    Redis { url: String },
+}
+
+impl KeyvalueBackend {
+    /// Reads the backend from the config of a `wasi:keyvalue` interface.
+    ///
+    /// # Errors
+    /// Returns an error if the backend is unknown, or `redis` without a URL.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Self> {
+        match config.get(BACKEND_CONFIG_KEY).map(String::as_str) {
+            None | Some("memory") => Ok(Self::Memory),
+            Some("redis") => {
+                let url = config
+                    .get(URL_CONFIG_KEY)
+                    .context("the redis keyvalue backend needs a url")?;
+                Ok(Self::Redis { url: url.clone() })
+            }
+            Some(backend) => {
+                bail!("unknown keyvalue backend '{backend}', expected memory or redis")
+            }
+        }
+    }
+}
+
+/// Returns the name a store keeps `bucket` of the workloads of `namespace`
+/// under.
+pub fn bucket_name(namespace: &str, bucket: &str) -> String {
+    format!("{namespace}/{bucket}")
+}
+
+/// Storage of `wasi:keyvalue` buckets.
+///
+/// Buckets are named by [`bucket_name`] and exist as soon as a key is set in
+/// them. Reading a bucket without keys returns nothing rather than failing.
+#[async_trait::async_trait]
+pub trait KeyvalueStore: Send + Sync {
+    /// Returns the optional keyvalue features the store supports.
+    fn capabilities(&self) -> KeyvalueCapabilities;
+
+    async fn get(&self, bucket: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn set(&self, bucket: &str, key: &str, value: Vec<u8>) -> anyhow::Result<()>;
+
+    async fn delete(&self, bucket: &str, key: &str) -> anyhow::Result<()>;
+
+    async fn exists(&self, bucket: &str, key: &str) -> anyhow::Result<bool>;
+
+    /// Returns a page of the keys of `bucket` from `cursor` on, and the cursor
+    /// of the next page if there is one.
+    async fn list_keys(
+        &self,
+        bucket: &str,
+        cursor: Option<u64>,
+    ) -> anyhow::Result<(Vec<String>, Option<u64>)>;
+
+    /// Adds `delta` to the number stored at `key`, which is 0 if it does not
+    /// exist, and returns the result.
+    async fn increment(&self, bucket: &str, key: &str, delta: u64) -> anyhow::Result<u64>;
+
+    async fn get_many(
+        &self,
+        bucket: &str,
+        keys: Vec<String>,
+    ) -> anyhow::Result<Vec<Option<(String, Vec<u8>)>>>;
+
+    async fn set_many(
+        &self,
+        bucket: &str,
+        key_values: Vec<(String, Vec<u8>)>,
+    ) -> anyhow::Result<()>;
+
+    async fn delete_many(&self, bucket: &str, keys: Vec<String>) -> anyhow::Result<()>;
+
+    /// Atomically replaces the value of `key` with `value` if its current
+    /// value is `expected`, where `None` means the key does not exist.
+    /// Returns whether the value was swapped.
+    ///
+    /// # Errors
+    /// Returns an error if the store does not support compare-and-swap, see
+    /// [`KeyvalueCapabilities::compare_and_swap`].
+    async fn compare_and_swap(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _value: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        bail!("the keyvalue store does not support compare-and-swap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_interface() {
+        let all = KeyvalueCapabilities {
+            atomics: true,
+            compare_and_swap: true,
+            batch: true,
+            atomic_batch: false,
+        };
+        assert_eq!(
+            all.interface(Some("0.2.0-draft")),
+            WitInterface::from("wasi:keyvalue/store,atomics,batch@0.2.0-draft")
+        );
+        assert_eq!(
+            KeyvalueCapabilities::default().interface(None),
+            WitInterface::from("wasi:keyvalue/store")
+        );
+    }
+
+    #[test]
+    fn test_backend_from_config() {
+        let config = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(
+            KeyvalueBackend::from_config(&config(&[])).unwrap(),
+            KeyvalueBackend::Memory
+        );
+        assert_eq!(
+            KeyvalueBackend::from_config(&config(&[
+                ("backend", "redis"),
+                ("url", "redis://cache:6379")
+            ]))
+            .unwrap(),
+            KeyvalueBackend::Redis {
+                url: "redis://cache:6379".to_string()
+            }
+        );
+        assert!(KeyvalueBackend::from_config(&config(&[("backend", "redis")])).is_err());
+        assert!(KeyvalueBackend::from_config(&config(&[("backend", "etcd")])).is_err());
+        assert_eq!(bucket_name("team-a", "sessions"), "team-a/sessions");
+    }
+}
@@ -0,0 +1,234 @@
+//! A `wasi:keyvalue` store in the memory of the host.
+//!
+//! Buckets are kept as long as the host runs, across invocations and restarts
+//! of the workloads using them, and are lost when it stops. All operations,
+//! including batches, run under a single lock and are therefore atomic.
+
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use super::{KeyvalueCapabilities, KeyvalueStore};
+
+/// Keys returned per page of [`KeyvalueStore::list_keys`]
+const PAGE_SIZE: usize = 100;
+
+/// In-memory bucket representation
+#[derive(Clone, Debug)]
+pub struct BucketData {
+    pub name: String,
+    pub data: HashMap<String, Vec<u8>>,
+    pub created_at: u64,
+}
+
+impl BucketData {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            data: HashMap::new(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// Buckets in the memory of the host, see the [module docs](self).
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    /// Buckets by name
+    buckets: RwLock<HashMap<String, BucketData>>,
+}
+
+impl MemoryStore {
+    /// Returns a copy of a bucket, if a key was ever set in it.
+    pub async fn bucket(&self, bucket: &str) -> Option<BucketData> {
+        self.buckets.read().await.get(bucket).cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyvalueStore for MemoryStore {
+    fn capabilities(&self) -> KeyvalueCapabilities {
+        KeyvalueCapabilities {
+            atomics: true,
+            compare_and_swap: true,
+            batch: true,
+            atomic_batch: true,
+        }
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let buckets = self.buckets.read().await;
+        Ok(buckets.get(bucket).and_then(|b| b.data.get(key)).cloned())
+    }
+
+    async fn set(&self, bucket: &str, key: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        let mut buckets = self.buckets.write().await;
+        buckets
+            .entry(bucket.to_string())
+            .or_insert_with(|| BucketData::new(bucket))
+            .data
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> anyhow::Result<()> {
+        if let Some(bucket) = self.buckets.write().await.get_mut(bucket) {
+            bucket.data.remove(key);
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> anyhow::Result<bool> {
+        let buckets = self.buckets.read().await;
+        Ok(buckets
+            .get(bucket)
+            .is_some_and(|b| b.data.contains_key(key)))
+    }
+
+    async fn list_keys(
+        &self,
+        bucket: &str,
+        cursor: Option<u64>,
+    ) -> anyhow::Result<(Vec<String>, Option<u64>)> {
+        let buckets = self.buckets.read().await;
+        let mut keys: Vec<&String> = buckets
+            .get(bucket)
+            .map(|b| b.data.keys().collect())
+            .unwrap_or_default();
+        // The cursor is the index of the first key of the page in sorted order
+        keys.sort();
+        let start = (cursor.unwrap_or(0) as usize).min(keys.len());
+        let end = (start + PAGE_SIZE).min(keys.len());
+        let next = (end < keys.len()).then_some(end as u64);
+        Ok((
+            keys[start..end].iter().map(|k| k.to_string()).collect(),
+            next,
+        ))
+    }
+
+    async fn increment(&self, bucket: &str, key: &str, delta: u64) -> anyhow::Result<u64> {
+        let mut buckets = self.buckets.write().await;
+        let data = &mut buckets
+            .entry(bucket.to_string())
+            .or_insert_with(|| BucketData::new(bucket))
+            .data;
+        // Numbers are stored as 8 little-endian bytes, or parsed from a decimal string
+        let current = match data.get(key) {
+            Some(bytes) => match <[u8; 8]>::try_from(bytes.as_slice()) {
+                Ok(bytes) => u64::from_le_bytes(bytes),
+                Err(_) => String::from_utf8_lossy(bytes).parse().unwrap_or(0),
+            },
+            None => 0,
+        };
+        let value = current.saturating_add(delta);
+        data.insert(key.to_string(), value.to_le_bytes().to_vec());
+        Ok(value)
+    }
+
+    async fn get_many(
+        &self,
+        bucket: &str,
+        keys: Vec<String>,
+    ) -> anyhow::Result<Vec<Option<(String, Vec<u8>)>>> {
+        let buckets = self.buckets.read().await;
+        let data = buckets.get(bucket).map(|b| &b.data);
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = data?.get(&key)?.clone();
+                Some((key, value))
+            })
+            .collect())
+    }
+
+    async fn set_many(
+        &self,
+        bucket: &str,
+        key_values: Vec<(String, Vec<u8>)>,
+    ) -> anyhow::Result<()> {
+        let mut buckets = self.buckets.write().await;
+        buckets
+            .entry(bucket.to_string())
+            .or_insert_with(|| BucketData::new(bucket))
+            .data
+            .extend(key_values);
+        Ok(())
+    }
+
+    async fn delete_many(&self, bucket: &str, keys: Vec<String>) -> anyhow::Result<()> {
+        if let Some(bucket) = self.buckets.write().await.get_mut(bucket) {
+            for key in keys {
+                bucket.data.remove(&key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn compare_and_swap(
+        &self,
+        bucket: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        let mut buckets = self.buckets.write().await;
+        let data = &mut buckets
+            .entry(bucket.to_string())
+            .or_insert_with(|| BucketData::new(bucket))
+            .data;
+        if data.get(key).map(Vec::as_slice) != expected {
+            return Ok(false);
+        }
+        data.insert(key.to_string(), value);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::default();
+        assert_eq!(store.get("ns/bucket", "key").await.unwrap(), None);
+        store
+            .set("ns/bucket", "key", b"value".to_vec())
+            .await
+            .unwrap();
+        assert!(store.exists("ns/bucket", "key").await.unwrap());
+        assert!(!store.exists("other/bucket", "key").await.unwrap());
+        assert_eq!(store.bucket("ns/bucket").await.unwrap().name, "ns/bucket");
+
+        assert_eq!(store.increment("ns/bucket", "count", 2).await.unwrap(), 2);
+        assert_eq!(store.increment("ns/bucket", "count", 3).await.unwrap(), 5);
+
+        store
+            .set_many(
+                "ns/bucket",
+                (0..150).map(|i| (format!("k{i:03}"), vec![])).collect(),
+            )
+            .await
+            .unwrap();
+        let (page, cursor) = store.list_keys("ns/bucket", None).await.unwrap();
+        assert_eq!(page.len(), PAGE_SIZE);
+        let (rest, cursor) = store.list_keys("ns/bucket", cursor).await.unwrap();
+        assert_eq!(page.len() + rest.len(), 152);
+        assert_eq!(cursor, None);
+
+        store
+            .delete_many("ns/bucket", vec!["key".to_string(), "k000".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .get_many("ns/bucket", vec!["key".to_string(), "k001".to_string()])
+                .await
+                .unwrap(),
+            vec![None, Some(("k001".to_string(), vec![]))]
+        );
+    }
+}
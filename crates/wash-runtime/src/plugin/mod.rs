@@ -8,27 +8,150 @@
 //!
 //! Plugins are Rust types that implement the [`HostPlugin`] trait. They:
 //! - Declare which WIT interfaces they provide via [`HostPlugin::world`]
-//! - Bind to components that need their capabilities via [`HostPlugin::bind_component`]
+//! - Bind to components that need their capabilities via [`HostPlugin::on_component_bind`]
 //! - Can participate in workload lifecycle events
 //! - Are automatically linked into the wasmtime runtime
 //!
+//! # Writing a plugin
+//!
+//! [`HostPlugin`] is the extension point for interfaces the crate doesn't
+//! implement, e.g. `wasi:nn`, and doesn't require changes to the host: register
+//! the plugin with [`HostBuilder::with_plugin`](crate::host::HostBuilder::with_plugin).
+//! A workload that declares one of the interfaces of its [world](HostPlugin::world)
+//! in its host interfaces is bound to the plugin when it starts:
+//! 1. [`HostPlugin::on_workload_bind`] once for the workload
+//! 2. [`HostPlugin::on_component_bind`] for each component importing the
+//!    interfaces, which adds their functions to the [linker](WorkloadComponent::linker)
+//! 3. [`HostPlugin::on_workload_resolved`] once the workload is linked
+//!
+//! and [`HostPlugin::on_workload_stop`] and [`HostPlugin::on_workload_unbind`]
+//! are called when it stops. The functions run with the
+//! [`Ctx`](crate::engine::ctx::Ctx) of the instance, which hands out the plugin
+//! with [`get_plugin`](crate::engine::ctx::Ctx::get_plugin).
+//! Configuration arrives with the interfaces, as declared by the workload, and
+//! per component through [`component_config`].
+//!
+//! ```no_run
+//! use std::collections::HashSet;
+//! use std::sync::Arc;
+//!
+//! use wash_runtime::engine::ctx::Ctx;
+//! use wash_runtime::engine::workload::WorkloadComponent;
+//! use wash_runtime::plugin::{HostPlugin, component_config};
+//! use wash_runtime::wasmtime::StoreContextMut;
+//! use wash_runtime::wit::{WitInterface, WitWorld};
+//!
+//! const GREETER_ID: &str = "greeter";
+//!
+//! struct Greeter;
+//!
+//! #[async_trait::async_trait]
+//! impl HostPlugin for Greeter {
+//!     fn id(&self) -> &'static str {
+//!         GREETER_ID
+//!     }
+//!
+//!     fn world(&self) -> WitWorld {
+//!         WitWorld {
+//!             imports: HashSet::from([WitInterface::from("example:greeter/greet@0.1.0")]),
+//!             ..Default::default()
+//!         }
+//!     }
+//!
+//!     async fn on_component_bind(
+//!         &self,
+//!         component: &mut WorkloadComponent,
+//!         interfaces: HashSet<WitInterface>,
+//!     ) -> anyhow::Result<()> {
+//!         let interface = interfaces.iter().next().expect("bound for greet");
+//!         let greeting = component_config(interface, component.local_resources())
+//!             .remove("greeting")
+//!             .unwrap_or_else(|| "Hello".to_string());
+//!         component
+//!             .linker()
+//!             .instance("example:greeter/greet@0.1.0")?
+//!             .func_wrap(
+//!                 "greet",
+//!                 move |store: StoreContextMut<Ctx>, (name,): (String,)| {
+//!                     let _plugin = store.data().get_plugin::<Greeter>(GREETER_ID);
+//!                     Ok((format!("{greeting}, {name}!"),))
+//!                 },
+//!             )?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let _host = wash_runtime::host::HostBuilder::new()
+//!     .with_plugin(Arc::new(Greeter))?
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The trait is stable within a major release: hooks added later come with a
+//! default implementation, so existing plugins keep compiling. Plugins should
+//! build the bindings of their interfaces against the [`wasmtime`](crate::wasmtime)
+//! the crate re-exports, so they link into the same engine.
+//!
 //! # Built-in Plugins
 //!
 //! The crate provides several built-in plugins for common WASI interfaces:
 //! - [`wasi_http`] - HTTP server capabilities (`wasi:http/incoming-handler`)
 //! - [`wasi_config`] - Runtime configuration (`wasi:config/store`)
 //! - [`wasi_blobstore`] - Object storage (`wasi:blobstore`)
+//! - [`blobstore_presign`] - Presigned S3 URLs (`wasmcloud:blobstore/presign`)
 //! - [`wasi_keyvalue`] - Key-value storage (`wasi:keyvalue`)
 //! - [`wasi_logging`] - Structured logging (`wasi:logging`)
+//! - [`wasi_messaging`] - Publishing and subscribing over NATS (`wasi:messaging`)
+//! - [`wash_vector`] - Embedding storage and similarity search (`wash:vector`)
+//! - [`wash_llm`] - Chat completions with host-managed API keys and quotas (`wash:llm`)
+//! - [`wash_metrics`] - Counters, gauges and histograms exported with host metrics (`wash:metrics`)
+//! - [`wash_sockets`] - Serving TCP connections and UDP datagrams on ports of the host (`wash:sockets`)
+//! - [`wasi_nn`] - Inference on ONNX models loaded from volumes or OCI artifacts (`wasi:nn`)
+//!
+//! Plugins can also run as external processes with [`process::ProcessPlugin`],
+//! which forwards the interfaces they provide over gRPC. They are the only
+//! plugins a deployed host can add without being rebuilt, see
+//! [`process::discover`]. Interfaces no plugin of a host provides can be
+//! forwarded to peers, see [`forward`].
+//!
+//! # Teardown
+//!
+//! Plugins are torn down in [`teardown_order`]: [triggers](PluginRole::Trigger),
+//! which invoke components, before [providers](PluginRole::Provider), which
+//! serve the interfaces components import. Components therefore stop being
+//! invoked while the connections and pools they call into are still open.
+//!
+//! When a workload stops, [`HostPlugin::on_workload_stop`] is called on the
+//! plugins bound to it in that order, before its service is stopped and its
+//! plugins are unbound. When the host stops:
+//! 1. the HTTP handler stops accepting requests
+//! 2. [`HostPlugin::on_host_shutdown`] is called on every plugin
+//! 3. running workloads are stopped
+//! 4. [`HostPlugin::stop`] is called on every plugin
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wasmtime::component::Val;
 
 use crate::{
     engine::workload::{ResolvedWorkload, UnresolvedWorkload, WorkloadComponent},
-    wit::WitWorld,
+    types::LocalResources,
+    wit::{WitInterface, WitWorld},
 };
 
+pub mod blobstore;
+pub mod forward;
+pub mod keyvalue;
+
 #[cfg(feature = "wasi-config")]
 pub mod wasi_config;
 
+#[cfg(feature = "blobstore-presign")]
+pub mod blobstore_presign;
+
 #[cfg(feature = "wasi-blobstore")]
 pub mod wasi_blobstore;
 
@@ -38,18 +161,78 @@ pub mod wasi_keyvalue;
 #[cfg(feature = "wasi-logging")]
 pub mod wasi_logging;
 
+#[cfg(feature = "wasi-messaging")]
+pub mod wasi_messaging;
+
+#[cfg(feature = "wash-llm")]
+pub mod wash_llm;
+
+#[cfg(feature = "wash-vector")]
+pub mod wash_vector;
+
+#[cfg(feature = "wash-metrics")]
+pub mod wash_metrics;
+
+#[cfg(feature = "wash-sockets")]
+pub mod wash_sockets;
+
+#[cfg(feature = "process-plugin")]
+pub mod process;
+
 #[cfg(feature = "wasi-webgpu")]
 pub mod wasi_webgpu;
 
+#[cfg(feature = "wasi-nn")]
+pub mod wasi_nn;
+
+/// The part a plugin plays for components, which orders its teardown, see
+/// the [module docs](self#teardown).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PluginRole {
+    /// Invokes components, e.g. on messages or timers
+    Trigger,
+    /// Provides interfaces components import, e.g. key-value or blobstore
+    #[default]
+    Provider,
+}
+
+/// Returns `plugins` in the order they are torn down: triggers first, then
+/// providers, each by ID.
+pub fn teardown_order<'a>(
+    plugins: impl IntoIterator<Item = &'a Arc<dyn HostPlugin>>,
+) -> Vec<&'a Arc<dyn HostPlugin>> {
+    let mut plugins: Vec<_> = plugins.into_iter().collect();
+    plugins.sort_by_key(|plugin| (plugin.role(), plugin.id()));
+    plugins
+}
+
+/// Returns the configuration of a component for a plugin bound to it for
+/// `interface`: the config the workload declares for the interface, overridden
+/// by the entries of the component's own [`LocalResources::config`] prefixed
+/// with the package of the interface, e.g. `wasi:nn.backend` for `backend`.
+pub fn component_config(
+    interface: &WitInterface,
+    resources: &LocalResources,
+) -> HashMap<String, String> {
+    let prefix = format!("{}:{}.", interface.namespace, interface.package);
+    let mut config = interface.config.clone();
+    config.extend(resources.config.iter().filter_map(|(key, value)| {
+        key.strip_prefix(&prefix)
+            .map(|key| (key.to_string(), value.clone()))
+    }));
+    config
+}
+
 /// The [`HostPlugin`] trait provides an interface for implementing built-in plugins for the host.
 /// A plugin is primarily responsible for implementing a specific [`WitWorld`] as a collection of
 /// imports and exports that will be directly linked to the workload's [`wasmtime::component::Linker`].
 ///
 /// For example, the runtime doesn't implement `wasi:keyvalue`, but it's a key capability for many component
 /// applications. This crate provides a [`wasi_keyvalue::WasiKeyvalue`] built-in that persists key-value data
-/// in-memory and implements the component imports of `wasi:keyvalue` atomics, batch and store.
+/// in-memory or in Redis and implements the component imports of `wasi:keyvalue` atomics, batch and store.
 ///
-/// You can supply your own [`HostPlugin`] implementations to the [`crate::host::HostBuilder::with_plugin`] function.
+/// You can supply your own [`HostPlugin`] implementations to the [`crate::host::HostBuilder::with_plugin`] function,
+/// see [writing a plugin](self#writing-a-plugin).
 #[async_trait::async_trait]
 pub trait HostPlugin: std::any::Any + Send + Sync + 'static {
     /// Returns the unique identifier for this plugin.
@@ -71,6 +254,18 @@ pub trait HostPlugin: std::any::Any + Send + Sync + 'static {
     /// A `WitWorld` containing the plugin's imports and exports.
     fn world(&self) -> WitWorld;
 
+    /// Returns the part this plugin plays for components, which orders its
+    /// teardown. Plugins that invoke components should return
+    /// [`PluginRole::Trigger`]. Defaults to [`PluginRole::Provider`].
+    fn role(&self) -> PluginRole {
+        PluginRole::Provider
+    }
+
+    /// Called before [`HostPlugin::start`] with the meter the plugin should
+    /// record its own series with, named after its ID and served alongside
+    /// the metrics of the host. The default implementation does nothing.
+    fn set_meter(&self, _meter: opentelemetry::metrics::Meter) {}
+
     /// Called when the plugin is started during host initialization.
     ///
     /// This method allows plugins to perform any necessary setup before
@@ -180,10 +375,82 @@ pub trait HostPlugin: std::any::Any + Send + Sync + 'static {
         Ok(())
     }
 
+    /// Called when a running workload bound to this plugin is stopped.
+    ///
+    /// This method is called before the workload's service is stopped and
+    /// before [`HostPlugin::on_workload_unbind`], while providers still serve
+    /// the workload. Triggers should stop invoking the workload here. The
+    /// default implementation does nothing.
+    ///
+    /// # Arguments
+    /// * `workload_id` - The ID of the workload being stopped
+    ///
+    /// # Errors
+    /// Returns an error if the plugin fails to stop using the workload (errors
+    /// are logged but don't prevent the workload from stopping).
+    async fn on_workload_stop(&self, _workload_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the configuration of a running component bound to this
+    /// plugin changes, see [`crate::host::HostApi::workload_config_update`].
+    ///
+    /// Plugins serving configuration to components apply `update` to the
+    /// configuration of `component_id`, so its next reads return the new
+    /// values. The default implementation ignores the update.
+    ///
+    /// # Returns
+    /// Whether the plugin holds configuration of the component.
+    ///
+    /// # Errors
+    /// Returns an error if the update can't be applied, e.g. a secret it
+    /// references can't be resolved. The configuration is left unchanged.
+    async fn on_config_update(
+        &self,
+        _component_id: &str,
+        _update: &crate::types::ConfigUpdate,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    /// Called on every plugin when the host begins to shut down.
+    ///
+    /// This method is called before running workloads are stopped and before
+    /// any plugin is [stopped](HostPlugin::stop). Triggers should stop invoking
+    /// components, and providers can finish in-flight work, e.g. flush pending
+    /// writes. The default implementation does nothing.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin fails to prepare for shutdown (errors are
+    /// logged but don't prevent shutdown).
+    async fn on_host_shutdown(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called with a call a peer lacking this plugin's interfaces forwarded,
+    /// see [`forward`]. Only plugins that handle plain values can serve
+    /// forwarded calls, the default implementation refuses them.
+    ///
+    /// # Returns
+    /// The values the function returned.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin doesn't serve forwarded calls or the
+    /// call fails.
+    async fn invoke(&self, call: forward::ForwardedCall) -> anyhow::Result<Vec<Val>> {
+        Err(crate::host::HostError::InvalidRequest(format!(
+            "plugin '{}' does not serve forwarded calls to {}",
+            self.id(),
+            call.instance
+        ))
+        .into())
+    }
+
     /// Called when the plugin is being stopped during host shutdown.
     ///
-    /// This method allows plugins to perform cleanup before the host stops.
-    /// The default implementation does nothing.
+    /// This method is called last, after running workloads were stopped, so
+    /// plugins can close the connections and pools they hold. The default
+    /// implementation does nothing.
     ///
     /// # Returns
     /// Ok if the plugin stopped successfully.
@@ -194,3 +461,64 @@ pub trait HostPlugin: std::any::Any + Send + Sync + 'static {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str, PluginRole);
+
+    #[async_trait::async_trait]
+    impl HostPlugin for Named {
+        fn id(&self) -> &'static str {
+            self.0
+        }
+
+        fn world(&self) -> WitWorld {
+            WitWorld::default()
+        }
+
+        fn role(&self) -> PluginRole {
+            self.1
+        }
+    }
+
+    #[test]
+    fn test_teardown_order() {
+        let plugins: Vec<Arc<dyn HostPlugin>> = vec![
+            Arc::new(Named("keyvalue", PluginRole::Provider)),
+            Arc::new(Named("timers", PluginRole::Trigger)),
+            Arc::new(Named("blobstore", PluginRole::Provider)),
+            Arc::new(Named("messaging", PluginRole::Trigger)),
+        ];
+        let order: Vec<_> = teardown_order(&plugins)
+            .into_iter()
+            .map(|plugin| plugin.id())
+            .collect();
+        assert_eq!(order, ["messaging", "timers", "blobstore", "keyvalue"]);
+    }
+
+    #[test]
+    fn test_component_config() {
+        let mut interface = WitInterface::from("wasi:nn/graph@0.2.0-rc-2024-10-28");
+        interface.config = HashMap::from([
+            ("backend".to_string(), "onnx".to_string()),
+            ("device".to_string(), "cpu".to_string()),
+        ]);
+        let resources = LocalResources {
+            config: HashMap::from([
+                ("wasi:nn.device".to_string(), "gpu".to_string()),
+                ("wasi:keyvalue.bucket".to_string(), "cache".to_string()),
+                ("device".to_string(), "tpu".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            component_config(&interface, &resources),
+            HashMap::from([
+                ("backend".to_string(), "onnx".to_string()),
+                ("device".to_string(), "gpu".to_string()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! Host plugins implemented by external processes.
+//!
+//! A [`ProcessPlugin`] runs a plugin as a separate process that speaks the
+//! `wasmcloud.runtime.v2.PluginService` gRPC protocol, so capabilities can be
+//! written in any language or pull in native dependencies without rebuilding the
+//! host. The host spawns the process, waits for it to print a handshake line of
+//! the form `wash-plugin|1|<address>` on stdout and then connects to it. Every
+//! function a component imports from the plugin's interfaces is forwarded to the
+//! `Invoke` RPC.
+//!
+//! The process is supervised: if it exits it is restarted with a backoff, up to
+//! [`ProcessPluginCommand::with_max_restarts`] times, and the workloads bound to
+//! it are bound again. Only plain values can cross the process boundary, so
+//! interfaces that use resources, futures or streams are not supported. For the
+//! same reason, process plugins can serve calls forwarded by peers lacking
+//! their interfaces, see [`crate::plugin::forward`].
+//!
+//! Plugins can be installed on a deployed host by dropping a manifest into a
+//! plugin directory, see [`discover`]. A manifest is a JSON file naming the
+//! program to run, relative to the manifest, and optionally its arguments,
+//! environment and restart limit:
+//!
+//! ```json
+//! { "program": "./redis-keyvalue", "args": ["--url", "redis://localhost"] }
+//! ```
+//!
+//! Process plugins are how a deployed host gains capabilities without being
+//! rebuilt. Loading plugins from shared libraries is not supported, as Rust has
+//! no stable ABI for the [`HostPlugin`] trait and a crashing library would take
+//! the host down with it. Plugins compiled to Wasm components aren't supported
+//! either; a capability written as a component can be composed into the
+//! workloads using it instead.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, bail, ensure};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt as _, BufReader, Lines};
+use tokio::process::{Child, ChildStdout};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tonic::transport::Channel;
+use tracing::{debug, error, info, warn};
+use wasmtime::component::Val;
+use wasmtime::component::types::ComponentItem;
+
+use crate::engine::workload::{UnresolvedWorkload, WorkloadComponent};
+use crate::plugin::HostPlugin;
+use crate::plugin::forward::ForwardedCall;
+use crate::washlet::convert::{value_from_api, value_to_api};
+use crate::washlet::types::v2;
+use crate::washlet::types::v2::plugin_service_client::PluginServiceClient;
+use crate::wit::{WitInterface, WitWorld};
+
+/// Prefix of the handshake line a plugin process prints once it is serving
+pub const HANDSHAKE_PREFIX: &str = "wash-plugin|";
+/// Version of the plugin protocol implemented by the host
+pub const PROTOCOL_VERSION: u32 = 1;
+/// How long a plugin process has to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest delay between restarts of a plugin process
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How to launch a plugin process.
+#[derive(Debug, Clone)]
+pub struct ProcessPluginCommand {
+    program: PathBuf,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    max_restarts: u32,
+}
+
+impl ProcessPluginCommand {
+    /// Creates a command that runs `program` and restarts it up to 5 times.
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            max_restarts: 5,
+        }
+    }
+
+    /// Adds an argument to pass to the plugin process.
+    pub fn with_arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Sets an environment variable for the plugin process.
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets how many times the process is restarted after exiting before the
+    /// host gives up on it.
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Spawns the process and connects to it once it completes the handshake.
+    async fn launch(&self) -> anyhow::Result<(Child, PluginServiceClient<Channel>)> {
+        let program = self.program.display().to_string();
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to spawn plugin process '{program}'"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("plugin process stdout is not captured")?;
+
+        let mut lines = BufReader::new(stdout).lines();
+        let addr = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_handshake(&mut lines, &program))
+            .await
+            .context("timed out waiting for the plugin handshake")??;
+
+        // Keep draining stdout so the process never blocks on a full pipe
+        let log_program = program.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!(program = log_program, "{line}");
+            }
+        });
+
+        let client = PluginServiceClient::connect(format!("http://{addr}"))
+            .await
+            .with_context(|| {
+                format!("failed to connect to plugin process '{program}' at {addr}")
+            })?;
+        Ok((child, client))
+    }
+}
+
+/// A plugin manifest found by [`discover`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProcessPluginManifest {
+    program: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    max_restarts: Option<u32>,
+}
+
+impl ProcessPluginCommand {
+    /// Reads the plugin manifest at `path`.
+    pub fn from_manifest(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let manifest = std::fs::read(path)
+            .with_context(|| format!("failed to read plugin manifest {}", path.display()))?;
+        let manifest: ProcessPluginManifest = serde_json::from_slice(&manifest)
+            .with_context(|| format!("invalid plugin manifest {}", path.display()))?;
+        let program = match path.parent() {
+            Some(dir) if manifest.program.is_relative() => dir.join(&manifest.program),
+            _ => manifest.program,
+        };
+        let mut command = Self::new(program);
+        command.args = manifest.args;
+        command.env = manifest.env;
+        if let Some(max_restarts) = manifest.max_restarts {
+            command.max_restarts = max_restarts;
+        }
+        Ok(command)
+    }
+}
+
+/// Returns the commands of the plugin manifests, `*.json` files, in `dir`,
+/// ordered by file name. A missing directory has no plugins.
+pub fn discover(dir: impl AsRef<Path>) -> anyhow::Result<Vec<ProcessPluginCommand>> {
+    let dir = dir.as_ref();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to read plugin directory {}", dir.display()));
+        }
+    };
+    let mut manifests = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read plugin directory {}", dir.display()))?
+            .path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            manifests.push(path);
+        }
+    }
+    manifests.sort();
+    manifests
+        .iter()
+        .map(ProcessPluginCommand::from_manifest)
+        .collect()
+}
+
+/// Reads the plugin's output until the handshake line and returns the address
+/// it serves on.
+async fn read_handshake(
+    lines: &mut Lines<BufReader<ChildStdout>>,
+    program: &str,
+) -> anyhow::Result<SocketAddr> {
+    while let Some(line) = lines.next_line().await? {
+        if let Some(addr) = parse_handshake(&line)? {
+            return Ok(addr);
+        }
+        debug!(program, "{line}");
+    }
+    bail!("plugin process exited before completing the handshake")
+}
+
+/// Parses a handshake line, returning `None` for any other output.
+fn parse_handshake(line: &str) -> anyhow::Result<Option<SocketAddr>> {
+    let Some(rest) = line.trim().strip_prefix(HANDSHAKE_PREFIX) else {
+        return Ok(None);
+    };
+    let (version, addr) = rest.split_once('|').context("malformed plugin handshake")?;
+    ensure!(
+        version == PROTOCOL_VERSION.to_string(),
+        "plugin speaks protocol version {version}, the host supports {PROTOCOL_VERSION}"
+    );
+    addr.parse()
+        .map(Some)
+        .with_context(|| format!("invalid address '{addr}' in plugin handshake"))
+}
+
+/// A [`HostPlugin`] backed by an external plugin process.
+pub struct ProcessPlugin {
+    id: &'static str,
+    imports: HashSet<WitInterface>,
+    client: Arc<RwLock<PluginServiceClient<Channel>>>,
+    /// Interfaces bound by each workload, replayed when the process restarts
+    bound: Arc<RwLock<HashMap<String, Vec<v2::WitInterface>>>>,
+    shutdown: CancellationToken,
+}
+
+impl ProcessPlugin {
+    /// Spawns the plugin process, asks it which interfaces it provides and
+    /// starts supervising it.
+    pub async fn spawn(command: ProcessPluginCommand) -> anyhow::Result<Self> {
+        let (child, mut client) = command.launch().await?;
+        let description = client
+            .describe(v2::DescribeRequest {})
+            .await
+            .context("failed to describe plugin process")?
+            .into_inner();
+        ensure!(!description.id.is_empty(), "plugin process has no id");
+        info!(
+            id = description.id,
+            program = %command.program.display(),
+            "started plugin process"
+        );
+
+        let plugin = Self {
+            // Plugins live for as long as the host, so the id is never freed
+            id: Box::leak(description.id.into_boxed_str()),
+            imports: description.imports.into_iter().map(Into::into).collect(),
+            client: Arc::new(RwLock::new(client)),
+            bound: Arc::default(),
+            shutdown: CancellationToken::new(),
+        };
+        tokio::spawn(supervise(
+            child,
+            command,
+            plugin.client.clone(),
+            plugin.bound.clone(),
+            plugin.shutdown.clone(),
+        ));
+        Ok(plugin)
+    }
+
+    async fn client(&self) -> PluginServiceClient<Channel> {
+        self.client.read().await.clone()
+    }
+}
+
+/// Calls a function of the plugin process with the `Invoke` RPC.
+async fn call_process(
+    client: &RwLock<PluginServiceClient<Channel>>,
+    call: ForwardedCall,
+) -> anyhow::Result<Vec<Val>> {
+    let request = v2::InvokeRequest {
+        workload_id: call.workload_id,
+        component_id: call.component_id,
+        instance: call.instance.clone(),
+        function: call.function.clone(),
+        params: call
+            .params
+            .iter()
+            .map(value_to_api)
+            .collect::<anyhow::Result<_>>()?,
+    };
+    let mut client = client.read().await.clone();
+    let response = client
+        .invoke(request)
+        .await
+        .map_err(|status| {
+            anyhow::anyhow!(
+                "plugin call to {}#{} failed: {}",
+                call.instance,
+                call.function,
+                status.message()
+            )
+        })?
+        .into_inner();
+    response.results.into_iter().map(value_from_api).collect()
+}
+
+/// Restarts the plugin process whenever it exits until `shutdown` is cancelled
+/// or it ran out of restarts.
+async fn supervise(
+    mut child: Child,
+    command: ProcessPluginCommand,
+    client: Arc<RwLock<PluginServiceClient<Channel>>>,
+    bound: Arc<RwLock<HashMap<String, Vec<v2::WitInterface>>>>,
+    shutdown: CancellationToken,
+) {
+    let program = command.program.display().to_string();
+    let mut restarts = 0;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                if let Err(e) = child.kill().await {
+                    warn!(program, "failed to kill plugin process: {e}");
+                }
+                return;
+            }
+            status = child.wait() => warn!(program, ?status, "plugin process exited"),
+        }
+
+        child = loop {
+            if restarts >= command.max_restarts {
+                error!(
+                    program,
+                    restarts, "plugin process will not be restarted again"
+                );
+                return;
+            }
+            restarts += 1;
+            let backoff = Duration::from_secs(1u64 << restarts.min(5)).min(MAX_RESTART_BACKOFF);
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            match command.launch().await {
+                Ok((child, mut new_client)) => {
+                    for (workload_id, interfaces) in bound.read().await.iter() {
+                        let request = v2::BindWorkloadRequest {
+                            workload_id: workload_id.clone(),
+                            interfaces: interfaces.clone(),
+                        };
+                        if let Err(e) = new_client.bind_workload(request).await {
+                            warn!(program, workload_id, "failed to rebind workload: {e}");
+                        }
+                    }
+                    *client.write().await = new_client;
+                    info!(program, restarts, "restarted plugin process");
+                    break child;
+                }
+                Err(e) => warn!(program, restarts, "failed to restart plugin process: {e:#}"),
+            }
+        };
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for ProcessPlugin {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: self.imports.clone(),
+            ..Default::default()
+        }
+    }
+
+    async fn on_workload_bind(
+        &self,
+        workload: &UnresolvedWorkload,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let interfaces: Vec<v2::WitInterface> = interfaces.into_iter().map(Into::into).collect();
+        self.client()
+            .await
+            .bind_workload(v2::BindWorkloadRequest {
+                workload_id: workload.id().to_string(),
+                interfaces: interfaces.clone(),
+            })
+            .await
+            .with_context(|| format!("plugin '{}' refused to bind workload", self.id))?;
+        self.bound
+            .write()
+            .await
+            .insert(workload.id().to_string(), interfaces);
+        Ok(())
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let engine = component.engine().clone();
+        for (name, item) in component.component_imports()? {
+            let ComponentItem::ComponentInstance(instance_ty) = item else {
+                continue;
+            };
+            let import = WitInterface::from(name.as_str());
+            if !interfaces.iter().any(|i| i.contains(&import)) {
+                continue;
+            }
+
+            let mut linker_instance = component.linker().instance(&name)?;
+            for (function, item) in instance_ty.exports(&engine) {
+                if !matches!(item, ComponentItem::ComponentFunc(_)) {
+                    continue;
+                }
+                let client = self.client.clone();
+                let instance: Arc<str> = name.as_str().into();
+                let function_name: Arc<str> = function.into();
+                linker_instance.func_new_async(function, move |store, params, results| {
+                    let client = client.clone();
+                    let instance = instance.clone();
+                    let function = function_name.clone();
+                    Box::new(async move {
+                        let call = ForwardedCall {
+                            workload_id: store.data().workload_id.to_string(),
+                            component_id: store.data().component_id.to_string(),
+                            instance: instance.to_string(),
+                            function: function.to_string(),
+                            params: params.to_vec(),
+                        };
+                        let returned = call_process(&client, call).await?;
+                        ensure!(
+                            returned.len() == results.len(),
+                            "plugin returned {} results for {instance}#{function}, expected {}",
+                            returned.len(),
+                            results.len()
+                        );
+                        for (slot, value) in results.iter_mut().zip(returned) {
+                            *slot = value;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn invoke(&self, call: ForwardedCall) -> anyhow::Result<Vec<Val>> {
+        call_process(&self.client, call).await
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        if self.bound.write().await.remove(workload_id).is_none() {
+            return Ok(());
+        }
+        self.client()
+            .await
+            .unbind_workload(v2::UnbindWorkloadRequest {
+                workload_id: workload_id.to_string(),
+            })
+            .await
+            .with_context(|| format!("plugin '{}' failed to unbind workload", self.id))?;
+        Ok(())
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        self.shutdown.cancel();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_handshake() {
+        assert_eq!(
+            parse_handshake("wash-plugin|1|127.0.0.1:4000\n").unwrap(),
+            Some("127.0.0.1:4000".parse().unwrap())
+        );
+        assert_eq!(parse_handshake("starting up").unwrap(), None);
+        assert!(parse_handshake("wash-plugin|2|127.0.0.1:4000").is_err());
+        assert!(parse_handshake("wash-plugin|1|localhost").is_err());
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(discover(dir.path().join("missing")).unwrap().is_empty());
+
+        std::fs::write(
+            dir.path().join("b.json"),
+            r#"{"program": "/usr/bin/kv", "max_restarts": 0}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("a.json"),
+            r#"{"program": "bin/blob", "args": ["--verbose"], "env": {"RUST_LOG": "debug"}}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a manifest").unwrap();
+
+        let commands = discover(dir.path()).unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].program, dir.path().join("bin/blob"));
+        assert_eq!(commands[0].args, vec!["--verbose"]);
+        assert_eq!(commands[0].env["RUST_LOG"], "debug");
+        assert_eq!(commands[0].max_restarts, 5);
+        assert_eq!(commands[1].program, PathBuf::from("/usr/bin/kv"));
+        assert_eq!(commands[1].max_restarts, 0);
+
+        std::fs::write(dir.path().join("c.json"), r#"{"command": "kv"}"#).unwrap();
+        assert!(discover(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,769 @@
+//! # Managed LLM Plugin
+//!
+//! This plugin implements the `wash:llm/chat@0.1.0` interface, a
+//! provider-agnostic chat completion API backed by OpenAI, Anthropic or Google
+//! Gemini. The API key is configured on the host, typically as a secret in the
+//! host's [`SecretStore`], and attached to requests by the plugin, so components
+//! neither see the key nor need outgoing HTTP access to the provider.
+//!
+//! Every workload is subject to an [`LlmQuota`] limiting the requests it may make
+//! and the tokens it may consume within a time window. A workload can tighten,
+//! but never raise, the host's quota through the config of its interface:
+//!
+//! ```text
+//! wash:llm/chat@0.1.0 { max-requests: "100", max-tokens: "50000" }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, bail};
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+use tracing::warn;
+use wasmtime::component::HasSelf;
+
+use crate::{
+    engine::{ctx::Ctx, workload::WorkloadComponent},
+    host::interpolate::SecretStore,
+    plugin::HostPlugin,
+    wit::{WitInterface, WitWorld},
+};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "llm",
+        imports: { default: async | trappable },
+    });
+}
+
+use bindings::wash::llm::chat;
+
+const WASH_LLM_ID: &str = "wash-llm";
+/// Tokens requested from providers that require an explicit limit
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// A supported model provider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LlmProvider {
+    OpenAi,
+    Anthropic,
+    Gemini,
+}
+
+impl LlmProvider {
+    fn default_base_url(&self) -> &'static str {
+        match self {
+            LlmProvider::OpenAi => "https://api.openai.com",
+            LlmProvider::Anthropic => "https://api.anthropic.com",
+            LlmProvider::Gemini => "https://generativelanguage.googleapis.com",
+        }
+    }
+}
+
+impl std::str::FromStr for LlmProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "openai" => Ok(LlmProvider::OpenAi),
+            "anthropic" => Ok(LlmProvider::Anthropic),
+            "gemini" => Ok(LlmProvider::Gemini),
+            _ => bail!("unknown LLM provider '{s}', expected openai, anthropic or gemini"),
+        }
+    }
+}
+
+/// The author of a chat message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// A chat completion request, independent of the provider.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChatRequest {
+    pub model: Option<String>,
+    pub messages: Vec<(ChatRole, String)>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+}
+
+/// A chat completion, independent of the provider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatResponse {
+    pub content: String,
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+/// Limits on a workload's use of the provider within each `window`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LlmQuota {
+    pub max_requests: Option<u64>,
+    /// Input and output tokens combined
+    pub max_tokens: Option<u64>,
+    pub window: Duration,
+}
+
+impl Default for LlmQuota {
+    /// Unlimited usage, accounted per hour
+    fn default() -> Self {
+        Self {
+            max_requests: None,
+            max_tokens: None,
+            window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl LlmQuota {
+    /// Returns the stricter of this quota and the limits in a workload's interface
+    /// config.
+    fn restrict(&self, config: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let limit = |key: &str, current: Option<u64>| -> anyhow::Result<Option<u64>> {
+            let Some(value) = config.get(key) else {
+                return Ok(current);
+            };
+            let value: u64 = value
+                .parse()
+                .with_context(|| format!("invalid {key} '{value}'"))?;
+            Ok(Some(current.map_or(value, |current| current.min(value))))
+        };
+        Ok(Self {
+            max_requests: limit("max-requests", self.max_requests)?,
+            max_tokens: limit("max-tokens", self.max_tokens)?,
+            window: self.window,
+        })
+    }
+}
+
+/// Usage of a workload in its current quota window.
+struct QuotaUsage {
+    quota: LlmQuota,
+    window_start: Instant,
+    requests: u64,
+    tokens: u64,
+}
+
+impl QuotaUsage {
+    fn new(quota: LlmQuota) -> Self {
+        Self {
+            quota,
+            window_start: Instant::now(),
+            requests: 0,
+            tokens: 0,
+        }
+    }
+
+    /// Counts a request against the quota and returns the tokens still
+    /// available, or an error if the quota is used up.
+    fn reserve(&mut self, now: Instant) -> Result<Option<u64>, String> {
+        if now.duration_since(self.window_start) >= self.quota.window {
+            self.window_start = now;
+            self.requests = 0;
+            self.tokens = 0;
+        }
+        if let Some(max) = self.quota.max_requests.filter(|max| self.requests >= *max) {
+            return Err(format!(
+                "request quota of {max} per {}s exceeded",
+                self.quota.window.as_secs()
+            ));
+        }
+        let remaining = self
+            .quota
+            .max_tokens
+            .map(|max| max.saturating_sub(self.tokens));
+        if let (Some(0), Some(max)) = (remaining, self.quota.max_tokens) {
+            return Err(format!(
+                "token quota of {max} per {}s exceeded",
+                self.quota.window.as_secs()
+            ));
+        }
+        self.requests += 1;
+        Ok(remaining)
+    }
+}
+
+/// Where the provider API key comes from.
+#[derive(Clone)]
+enum ApiKey {
+    Static(String),
+    /// Looked up for every request, so rotating the secret takes effect immediately
+    Secret {
+        store: Arc<dyn SecretStore>,
+        name: String,
+    },
+}
+
+/// A provider account the plugin sends requests to.
+#[derive(Clone)]
+pub struct LlmBackend {
+    provider: LlmProvider,
+    api_key: ApiKey,
+    model: String,
+    base_url: String,
+}
+
+impl std::fmt::Debug for LlmBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmBackend")
+            .field("provider", &self.provider)
+            .field("model", &self.model)
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LlmBackend {
+    /// Creates a backend for `provider` using `model` unless a request names
+    /// another one.
+    pub fn new(
+        provider: LlmProvider,
+        api_key: impl Into<String>,
+        model: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider,
+            api_key: ApiKey::Static(api_key.into()),
+            model: model.into(),
+            base_url: provider.default_base_url().to_string(),
+        }
+    }
+
+    /// Creates a backend whose API key is the secret `secret_name` in
+    /// `secret_store`.
+    pub fn from_secret(
+        provider: LlmProvider,
+        secret_store: Arc<dyn SecretStore>,
+        secret_name: impl Into<String>,
+        model: impl Into<String>,
+    ) -> Self {
+        Self {
+            api_key: ApiKey::Secret {
+                store: secret_store,
+                name: secret_name.into(),
+            },
+            ..Self::new(provider, String::new(), model)
+        }
+    }
+
+    async fn api_key(&self) -> anyhow::Result<String> {
+        match &self.api_key {
+            ApiKey::Static(key) => Ok(key.clone()),
+            ApiKey::Secret { store, name } => store
+                .get(name)
+                .await
+                .with_context(|| format!("failed to read secret '{name}'"))?
+                .with_context(|| format!("secret '{name}' does not exist")),
+        }
+    }
+
+    /// Sends requests to a different base URL, e.g. an OpenAI compatible server
+    /// or a proxy.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Returns the URL, headers and body of the provider request for `request`.
+    fn build_request(
+        &self,
+        request: &ChatRequest,
+        api_key: &str,
+    ) -> (String, Vec<(&'static str, String)>, Value) {
+        let model = request.model.as_deref().unwrap_or(&self.model);
+        let system: Vec<&str> = request
+            .messages
+            .iter()
+            .filter(|(role, _)| *role == ChatRole::System)
+            .map(|(_, content)| content.as_str())
+            .collect();
+        let conversation = request
+            .messages
+            .iter()
+            .filter(|(role, _)| *role != ChatRole::System);
+
+        match self.provider {
+            LlmProvider::OpenAi => {
+                let messages: Vec<_> = request
+                    .messages
+                    .iter()
+                    .map(|(role, content)| {
+                        let role = match role {
+                            ChatRole::System => "system",
+                            ChatRole::User => "user",
+                            ChatRole::Assistant => "assistant",
+                        };
+                        json!({ "role": role, "content": content })
+                    })
+                    .collect();
+                let mut body = json!({ "model": model, "messages": messages });
+                if let Some(max_tokens) = request.max_tokens {
+                    body["max_tokens"] = max_tokens.into();
+                }
+                if let Some(temperature) = request.temperature {
+                    body["temperature"] = temperature.into();
+                }
+                (
+                    format!("{}/v1/chat/completions", self.base_url),
+                    vec![("authorization", format!("Bearer {api_key}"))],
+                    body,
+                )
+            }
+            LlmProvider::Anthropic => {
+                let messages: Vec<_> = conversation
+                    .map(|(role, content)| {
+                        let role = if *role == ChatRole::Assistant {
+                            "assistant"
+                        } else {
+                            "user"
+                        };
+                        json!({ "role": role, "content": content })
+                    })
+                    .collect();
+                let mut body = json!({
+                    "model": model,
+                    "messages": messages,
+                    "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                });
+                if !system.is_empty() {
+                    body["system"] = system.join("\n\n").into();
+                }
+                if let Some(temperature) = request.temperature {
+                    body["temperature"] = temperature.into();
+                }
+                (
+                    format!("{}/v1/messages", self.base_url),
+                    vec![
+                        ("x-api-key", api_key.to_string()),
+                        ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+                    ],
+                    body,
+                )
+            }
+            LlmProvider::Gemini => {
+                let contents: Vec<_> = conversation
+                    .map(|(role, content)| {
+                        let role = if *role == ChatRole::Assistant {
+                            "model"
+                        } else {
+                            "user"
+                        };
+                        json!({ "role": role, "parts": [{ "text": content }] })
+                    })
+                    .collect();
+                let mut body = json!({ "contents": contents });
+                if !system.is_empty() {
+                    body["systemInstruction"] =
+                        json!({ "parts": [{ "text": system.join("\n\n") }] });
+                }
+                let mut generation_config = serde_json::Map::new();
+                if let Some(max_tokens) = request.max_tokens {
+                    generation_config.insert("maxOutputTokens".to_string(), max_tokens.into());
+                }
+                if let Some(temperature) = request.temperature {
+                    generation_config.insert("temperature".to_string(), temperature.into());
+                }
+                if !generation_config.is_empty() {
+                    body["generationConfig"] = generation_config.into();
+                }
+                (
+                    format!("{}/v1beta/models/{model}:generateContent", self.base_url),
+                    vec![("x-goog-api-key", api_key.to_string())],
+                    body,
+                )
+            }
+        }
+    }
+
+    /// Extracts the completion from a successful provider response.
+    fn parse_response(&self, model: &str, body: &Value) -> anyhow::Result<ChatResponse> {
+        let tokens = |value: &Value| value.as_u64().unwrap_or_default() as u32;
+        let response = match self.provider {
+            LlmProvider::OpenAi => ChatResponse {
+                content: body["choices"][0]["message"]["content"]
+                    .as_str()
+                    .context("response has no message content")?
+                    .to_string(),
+                model: body["model"].as_str().unwrap_or(model).to_string(),
+                input_tokens: tokens(&body["usage"]["prompt_tokens"]),
+                output_tokens: tokens(&body["usage"]["completion_tokens"]),
+            },
+            LlmProvider::Anthropic => ChatResponse {
+                content: text_parts(&body["content"], "text")
+                    .context("response has no text content")?,
+                model: body["model"].as_str().unwrap_or(model).to_string(),
+                input_tokens: tokens(&body["usage"]["input_tokens"]),
+                output_tokens: tokens(&body["usage"]["output_tokens"]),
+            },
+            LlmProvider::Gemini => ChatResponse {
+                content: text_parts(&body["candidates"][0]["content"]["parts"], "")
+                    .context("response has no candidates")?,
+                model: body["modelVersion"].as_str().unwrap_or(model).to_string(),
+                input_tokens: tokens(&body["usageMetadata"]["promptTokenCount"]),
+                output_tokens: tokens(&body["usageMetadata"]["candidatesTokenCount"]),
+            },
+        };
+        Ok(response)
+    }
+}
+
+/// Concatenates the `text` of every part in `parts`. When `kind` is not empty,
+/// only parts whose `type` equals it are included.
+fn text_parts(parts: &Value, kind: &str) -> Option<String> {
+    let texts: Vec<&str> = parts
+        .as_array()?
+        .iter()
+        .filter(|part| kind.is_empty() || part["type"] == kind)
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+    (!texts.is_empty()).then(|| texts.concat())
+}
+
+/// Returns the error message of a failed provider response.
+fn provider_error(body: &Value) -> &str {
+    body["error"]["message"]
+        .as_str()
+        .or(body["error"].as_str())
+        .unwrap_or("unknown error")
+}
+
+/// Plugin providing `wash:llm/chat` through an [`LlmBackend`].
+#[derive(Clone)]
+pub struct WashLlm {
+    client: reqwest::Client,
+    backend: Arc<LlmBackend>,
+    quota: LlmQuota,
+    /// Quota usage of each workload, keyed by workload ID
+    usage: Arc<Mutex<HashMap<Arc<str>, QuotaUsage>>>,
+}
+
+impl WashLlm {
+    /// Creates a plugin sending completions to `backend` without quotas.
+    pub fn new(backend: LlmBackend) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            backend: Arc::new(backend),
+            quota: LlmQuota::default(),
+            usage: Arc::default(),
+        }
+    }
+
+    /// Sets the quota applied to each workload.
+    pub fn with_quota(mut self, quota: LlmQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    async fn reserve(&self, workload_id: &str) -> Result<Option<u64>, String> {
+        match self.usage.lock().await.get_mut(workload_id) {
+            Some(usage) => usage.reserve(Instant::now()),
+            None => Err("workload is not bound to wash:llm".to_string()),
+        }
+    }
+
+    async fn record_tokens(&self, workload_id: &str, tokens: u64) {
+        if let Some(usage) = self.usage.lock().await.get_mut(workload_id) {
+            usage.tokens += tokens;
+        }
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse, chat::Error> {
+        // Never include the key itself in errors returned to the component
+        let api_key = self.backend.api_key().await.map_err(|e| {
+            warn!("failed to resolve LLM provider API key: {e:#}");
+            chat::Error::Provider("provider credentials are unavailable".to_string())
+        })?;
+        let (url, headers, body) = self.backend.build_request(request, &api_key);
+        let mut http_request = self.client.post(url).json(&body);
+        for (name, value) in headers {
+            http_request = http_request.header(name, value);
+        }
+        let response = http_request
+            .send()
+            .await
+            .map_err(|e| chat::Error::Provider(format!("failed to reach provider: {e}")))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| chat::Error::Provider(format!("failed to decode response: {e}")))?;
+        if !status.is_success() {
+            return Err(chat::Error::Provider(format!(
+                "provider returned {status}: {}",
+                provider_error(&body)
+            )));
+        }
+        let model = request.model.as_deref().unwrap_or(&self.backend.model);
+        self.backend
+            .parse_response(model, &body)
+            .map_err(|e| chat::Error::Provider(format!("{e:#}")))
+    }
+}
+
+impl chat::Host for Ctx {
+    async fn complete(
+        &mut self,
+        request: chat::Request,
+    ) -> anyhow::Result<Result<chat::Response, chat::Error>> {
+        let Some(plugin) = self.get_plugin::<WashLlm>(WASH_LLM_ID) else {
+            return Ok(Err(chat::Error::Provider(
+                "llm plugin not available".to_string(),
+            )));
+        };
+        if request.messages.is_empty() {
+            return Ok(Err(chat::Error::InvalidRequest(
+                "at least one message is required".to_string(),
+            )));
+        }
+
+        let remaining = match plugin.reserve(&self.workload_id).await {
+            Ok(remaining) => remaining,
+            Err(e) => return Ok(Err(chat::Error::QuotaExceeded(e))),
+        };
+        // Don't let a single completion generate more tokens than the quota has left
+        let max_tokens = match (request.max_tokens, remaining) {
+            (Some(max), Some(remaining)) => Some(max.min(remaining.try_into().unwrap_or(u32::MAX))),
+            (None, Some(remaining)) => Some(remaining.try_into().unwrap_or(u32::MAX)),
+            (max, None) => max,
+        };
+
+        let request = ChatRequest {
+            model: request.model,
+            messages: request
+                .messages
+                .into_iter()
+                .map(|m| {
+                    let role = match m.role {
+                        chat::Role::System => ChatRole::System,
+                        chat::Role::User => ChatRole::User,
+                        chat::Role::Assistant => ChatRole::Assistant,
+                    };
+                    (role, m.content)
+                })
+                .collect(),
+            max_tokens,
+            temperature: request.temperature,
+        };
+
+        let response = match plugin.complete(&request).await {
+            Ok(response) => response,
+            Err(e) => return Ok(Err(e)),
+        };
+        plugin
+            .record_tokens(
+                &self.workload_id,
+                u64::from(response.input_tokens) + u64::from(response.output_tokens),
+            )
+            .await;
+
+        Ok(Ok(chat::Response {
+            content: response.content,
+            model: response.model,
+            usage: chat::Usage {
+                input_tokens: response.input_tokens,
+                output_tokens: response.output_tokens,
+            },
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WashLlm {
+    fn id(&self) -> &'static str {
+        WASH_LLM_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from("wash:llm/chat@0.1.0")]),
+            ..Default::default()
+        }
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wash" && i.package == "llm" && i.interfaces.contains("chat"))
+        else {
+            warn!(
+                "WashLlm plugin requested for unsupported interface(s): {:?}",
+                interfaces
+            );
+            return Ok(());
+        };
+
+        let quota = self.quota.restrict(&interface.config)?;
+        chat::add_to_linker::<_, HasSelf<Ctx>>(component.linker(), |ctx| ctx)?;
+
+        // Components of a workload share its quota
+        self.usage
+            .lock()
+            .await
+            .entry(Arc::from(component.workload_id()))
+            .or_insert_with(|| QuotaUsage::new(quota));
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        self.usage.lock().await.remove(workload_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: None,
+            messages: vec![
+                (ChatRole::System, "Be brief.".to_string()),
+                (ChatRole::User, "Hi".to_string()),
+            ],
+            max_tokens: Some(64),
+            temperature: None,
+        }
+    }
+
+    #[test]
+    fn test_build_request_per_provider() {
+        let (url, headers, body) = LlmBackend::new(LlmProvider::OpenAi, "key", "gpt-4o-mini")
+            .build_request(&request(), "key");
+        assert_eq!(url, "https://api.openai.com/v1/chat/completions");
+        assert_eq!(headers, vec![("authorization", "Bearer key".to_string())]);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["max_tokens"], 64);
+
+        let (url, _, body) = LlmBackend::new(LlmProvider::Anthropic, "key", "claude")
+            .with_base_url("http://localhost:8080/")
+            .build_request(&request(), "key");
+        assert_eq!(url, "http://localhost:8080/v1/messages");
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+
+        let (url, headers, body) = LlmBackend::new(LlmProvider::Gemini, "key", "gemini-2.0-flash")
+            .build_request(&request(), "key");
+        assert_eq!(
+            url,
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent"
+        );
+        assert_eq!(headers[0].0, "x-goog-api-key");
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 64);
+    }
+
+    #[test]
+    fn test_parse_response_per_provider() {
+        let openai = LlmBackend::new(LlmProvider::OpenAi, "key", "gpt");
+        let response = openai
+            .parse_response(
+                "gpt",
+                &json!({
+                    "model": "gpt-4o-mini",
+                    "choices": [{ "message": { "content": "Hello" } }],
+                    "usage": { "prompt_tokens": 5, "completion_tokens": 2 },
+                }),
+            )
+            .unwrap();
+        assert_eq!(response.content, "Hello");
+        assert_eq!((response.input_tokens, response.output_tokens), (5, 2));
+
+        let anthropic = LlmBackend::new(LlmProvider::Anthropic, "key", "claude");
+        let response = anthropic
+            .parse_response(
+                "claude",
+                &json!({
+                    "content": [{ "type": "text", "text": "Hel" }, { "type": "text", "text": "lo" }],
+                    "usage": { "input_tokens": 5, "output_tokens": 2 },
+                }),
+            )
+            .unwrap();
+        assert_eq!(response.content, "Hello");
+        assert_eq!(response.model, "claude");
+
+        let gemini = LlmBackend::new(LlmProvider::Gemini, "key", "gemini");
+        let response = gemini
+            .parse_response(
+                "gemini",
+                &json!({
+                    "candidates": [{ "content": { "parts": [{ "text": "Hello" }] } }],
+                    "usageMetadata": { "promptTokenCount": 5, "candidatesTokenCount": 2 },
+                }),
+            )
+            .unwrap();
+        assert_eq!(response.content, "Hello");
+        assert!(gemini.parse_response("gemini", &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_quota_window() {
+        let quota = LlmQuota {
+            max_requests: Some(2),
+            max_tokens: Some(100),
+            window: Duration::from_secs(60),
+        };
+        let mut usage = QuotaUsage::new(quota);
+        let now = Instant::now();
+        assert_eq!(usage.reserve(now), Ok(Some(100)));
+        usage.tokens += 40;
+        assert_eq!(usage.reserve(now), Ok(Some(60)));
+        assert!(usage.reserve(now).is_err());
+
+        // A new window resets usage
+        let later = now + Duration::from_secs(61);
+        assert_eq!(usage.reserve(later), Ok(Some(100)));
+        usage.tokens += 100;
+        assert!(usage.reserve(later).is_err());
+    }
+
+    #[test]
+    fn test_quota_config_only_tightens() {
+        let quota = LlmQuota {
+            max_requests: Some(10),
+            ..Default::default()
+        };
+        let config = HashMap::from([
+            ("max-requests".to_string(), "50".to_string()),
+            ("max-tokens".to_string(), "1000".to_string()),
+        ]);
+        let restricted = quota.restrict(&config).unwrap();
+        assert_eq!(restricted.max_requests, Some(10));
+        assert_eq!(restricted.max_tokens, Some(1000));
+
+        let config = HashMap::from([("max-tokens".to_string(), "lots".to_string())]);
+        assert!(quota.restrict(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_from_secret() {
+        let secrets: Arc<dyn SecretStore> = Arc::new(HashMap::from([(
+            "openai-key".to_string(),
+            "sk-test".to_string(),
+        )]));
+        let backend =
+            LlmBackend::from_secret(LlmProvider::OpenAi, secrets.clone(), "openai-key", "gpt");
+        assert_eq!(backend.api_key().await.unwrap(), "sk-test");
+
+        let missing = LlmBackend::from_secret(LlmProvider::OpenAi, secrets, "missing", "gpt");
+        assert!(missing.api_key().await.is_err());
+    }
+}
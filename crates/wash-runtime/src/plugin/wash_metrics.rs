@@ -0,0 +1,489 @@
+//! # Metrics Plugin
+//!
+//! This plugin implements the `wash:metrics/recorder@0.1.0` interface, letting
+//! components record counters, gauges and histograms by name. Measurements go
+//! through the meter the host hands the plugin, so they are exported with the
+//! metrics of the host, e.g. by the OTLP exporter or on its metrics listener.
+//!
+//! A metric named `requests` by a component is recorded as
+//! `wasmcloud.guest.requests`, labelled with the namespace, name and ID of its
+//! workload and the ID of the component. Workloads recording the same name
+//! therefore share an instrument but not their series, and must agree on its
+//! kind. Components can't set attributes starting with `wasmcloud.`, so they
+//! can't record into the series of another workload.
+//!
+//! To bound the series a workload adds, it may only record as many metric
+//! names as the `max-metrics` config of its interface allows:
+//!
+//! ```text
+//! wash:metrics/recorder@0.1.0 { max-metrics: "20" }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter};
+use wasmtime::component::HasSelf;
+
+use crate::{
+    engine::{ctx::Ctx, workload::WorkloadComponent},
+    host::metrics::NAMESPACE_ATTRIBUTE,
+    plugin::HostPlugin,
+    wit::{WitInterface, WitWorld},
+};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "metrics",
+        imports: { default: async | trappable },
+    });
+}
+
+use bindings::wash::metrics::recorder;
+
+const WASH_METRICS_ID: &str = "wash-metrics";
+/// Prefix of the instruments recording guest metrics
+const METRIC_PREFIX: &str = "wasmcloud.guest.";
+/// Prefix of the attributes set by the host
+const RESERVED_ATTRIBUTE_PREFIX: &str = "wasmcloud.";
+const WORKLOAD_NAME_ATTRIBUTE: &str = "wasmcloud.workload.name";
+const WORKLOAD_ID_ATTRIBUTE: &str = "wasmcloud.workload.id";
+const COMPONENT_ID_ATTRIBUTE: &str = "wasmcloud.component.id";
+/// Metric names a workload may record unless its config says otherwise
+pub const DEFAULT_MAX_METRICS: usize = 100;
+/// Most attributes a component may set on a measurement
+const MAX_ATTRIBUTES: usize = 16;
+/// Longest metric name or attribute key
+const MAX_NAME_LEN: usize = 128;
+
+/// The kinds of guest metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl std::fmt::Display for MetricKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricKind::Counter => write!(f, "counter"),
+            MetricKind::Gauge => write!(f, "gauge"),
+            MetricKind::Histogram => write!(f, "histogram"),
+        }
+    }
+}
+
+/// An instrument recording a guest metric.
+#[derive(Clone)]
+enum Instrument {
+    Counter(Counter<u64>),
+    Gauge(Gauge<f64>),
+    Histogram(Histogram<f64>),
+}
+
+impl Instrument {
+    fn kind(&self) -> MetricKind {
+        match self {
+            Instrument::Counter(_) => MetricKind::Counter,
+            Instrument::Gauge(_) => MetricKind::Gauge,
+            Instrument::Histogram(_) => MetricKind::Histogram,
+        }
+    }
+}
+
+/// A measurement of a guest metric.
+#[derive(Debug, Clone, Copy)]
+enum Measurement {
+    Counter(u64),
+    Gauge(f64),
+    Histogram(f64),
+}
+
+impl Measurement {
+    fn kind(&self) -> MetricKind {
+        match self {
+            Measurement::Counter(_) => MetricKind::Counter,
+            Measurement::Gauge(_) => MetricKind::Gauge,
+            Measurement::Histogram(_) => MetricKind::Histogram,
+        }
+    }
+}
+
+/// What the plugin knows about a workload recording metrics.
+#[derive(Debug, Clone)]
+struct WorkloadMetrics {
+    namespace: Arc<str>,
+    name: Arc<str>,
+    max_metrics: usize,
+    /// The metric names it recorded
+    names: HashSet<String>,
+}
+
+/// Plugin providing `wash:metrics`, see the [module docs](self).
+#[derive(Clone)]
+pub struct WashMetrics {
+    /// Replaced by the meter the host hands the plugin when it starts
+    meter: Arc<std::sync::Mutex<Meter>>,
+    /// Instruments by metric name, shared by every workload
+    instruments: Arc<std::sync::Mutex<HashMap<String, Instrument>>>,
+    /// Workloads keyed by workload ID
+    workloads: Arc<std::sync::Mutex<HashMap<Arc<str>, WorkloadMetrics>>>,
+}
+
+impl Default for WashMetrics {
+    fn default() -> Self {
+        Self::new(&opentelemetry::global::meter(WASH_METRICS_ID))
+    }
+}
+
+impl WashMetrics {
+    /// Creates a plugin recording guest metrics with `meter`.
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            meter: Arc::new(std::sync::Mutex::new(meter.clone())),
+            instruments: Arc::default(),
+            workloads: Arc::default(),
+        }
+    }
+
+    fn instruments(&self) -> std::sync::MutexGuard<'_, HashMap<String, Instrument>> {
+        self.instruments
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn workloads(&self) -> std::sync::MutexGuard<'_, HashMap<Arc<str>, WorkloadMetrics>> {
+        self.workloads
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns the instrument of `name`, created on first use.
+    fn instrument(&self, name: &str, kind: MetricKind) -> Result<Instrument, String> {
+        let mut instruments = self.instruments();
+        if let Some(instrument) = instruments.get(name) {
+            if instrument.kind() != kind {
+                return Err(format!(
+                    "metric '{name}' is already recorded as a {}",
+                    instrument.kind()
+                ));
+            }
+            return Ok(instrument.clone());
+        }
+        let full_name = format!("{METRIC_PREFIX}{name}");
+        let meter = self
+            .meter
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let instrument = match kind {
+            MetricKind::Counter => Instrument::Counter(meter.u64_counter(full_name).build()),
+            MetricKind::Gauge => Instrument::Gauge(meter.f64_gauge(full_name).build()),
+            MetricKind::Histogram => Instrument::Histogram(meter.f64_histogram(full_name).build()),
+        };
+        instruments.insert(name.to_string(), instrument.clone());
+        Ok(instrument)
+    }
+
+    /// Records a measurement of a component, returning why it was refused.
+    fn record(
+        &self,
+        workload_id: &str,
+        component_id: &str,
+        name: &str,
+        measurement: Measurement,
+        attributes: Vec<(String, String)>,
+    ) -> Result<(), String> {
+        validate_name(name).map_err(|e| format!("invalid metric name '{name}': {e}"))?;
+        if let Measurement::Gauge(value) | Measurement::Histogram(value) = measurement
+            && !value.is_finite()
+        {
+            return Err(format!("value of metric '{name}' must be finite"));
+        }
+        if attributes.len() > MAX_ATTRIBUTES {
+            return Err(format!("at most {MAX_ATTRIBUTES} attributes are allowed"));
+        }
+        for (key, _) in &attributes {
+            validate_name(key).map_err(|e| format!("invalid attribute key '{key}': {e}"))?;
+            if key.starts_with(RESERVED_ATTRIBUTE_PREFIX) {
+                return Err(format!(
+                    "attribute keys starting with '{RESERVED_ATTRIBUTE_PREFIX}' are reserved"
+                ));
+            }
+        }
+
+        let (namespace, workload_name) = {
+            let mut workloads = self.workloads();
+            let Some(workload) = workloads.get_mut(workload_id) else {
+                return Err("metrics plugin not bound to this workload".to_string());
+            };
+            if !workload.names.contains(name) {
+                if workload.names.len() >= workload.max_metrics {
+                    return Err(format!(
+                        "workload may record at most {} metrics",
+                        workload.max_metrics
+                    ));
+                }
+                workload.names.insert(name.to_string());
+            }
+            (workload.namespace.clone(), workload.name.clone())
+        };
+
+        let instrument = self.instrument(name, measurement.kind())?;
+        let mut attributes: Vec<KeyValue> = attributes
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect();
+        attributes.extend([
+            KeyValue::new(NAMESPACE_ATTRIBUTE, namespace.to_string()),
+            KeyValue::new(WORKLOAD_NAME_ATTRIBUTE, workload_name.to_string()),
+            KeyValue::new(WORKLOAD_ID_ATTRIBUTE, workload_id.to_string()),
+            KeyValue::new(COMPONENT_ID_ATTRIBUTE, component_id.to_string()),
+        ]);
+        match (instrument, measurement) {
+            (Instrument::Counter(counter), Measurement::Counter(value)) => {
+                counter.add(value, &attributes)
+            }
+            (Instrument::Gauge(gauge), Measurement::Gauge(value)) => {
+                gauge.record(value, &attributes)
+            }
+            (Instrument::Histogram(histogram), Measurement::Histogram(value)) => {
+                histogram.record(value, &attributes)
+            }
+            // `instrument` returns an instrument of the kind of the measurement
+            _ => unreachable!("instrument kind mismatch"),
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a metric name or attribute key is made of ASCII letters,
+/// digits, `_`, `.` and `-`, and starts with a letter.
+fn validate_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err("must be 1 to 128 characters");
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err("must start with a letter");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+    {
+        return Err("may only contain ASCII letters, digits, '_', '.' and '-'");
+    }
+    Ok(())
+}
+
+impl recorder::Host for Ctx {
+    async fn add_counter(
+        &mut self,
+        name: String,
+        value: u64,
+        attributes: Vec<(String, String)>,
+    ) -> anyhow::Result<Result<(), String>> {
+        let Some(plugin) = self.get_plugin::<WashMetrics>(WASH_METRICS_ID) else {
+            return Ok(Err("metrics plugin not available".to_string()));
+        };
+        Ok(plugin.record(
+            &self.workload_id,
+            &self.component_id,
+            &name,
+            Measurement::Counter(value),
+            attributes,
+        ))
+    }
+
+    async fn set_gauge(
+        &mut self,
+        name: String,
+        value: f64,
+        attributes: Vec<(String, String)>,
+    ) -> anyhow::Result<Result<(), String>> {
+        let Some(plugin) = self.get_plugin::<WashMetrics>(WASH_METRICS_ID) else {
+            return Ok(Err("metrics plugin not available".to_string()));
+        };
+        Ok(plugin.record(
+            &self.workload_id,
+            &self.component_id,
+            &name,
+            Measurement::Gauge(value),
+            attributes,
+        ))
+    }
+
+    async fn record_histogram(
+        &mut self,
+        name: String,
+        value: f64,
+        attributes: Vec<(String, String)>,
+    ) -> anyhow::Result<Result<(), String>> {
+        let Some(plugin) = self.get_plugin::<WashMetrics>(WASH_METRICS_ID) else {
+            return Ok(Err("metrics plugin not available".to_string()));
+        };
+        Ok(plugin.record(
+            &self.workload_id,
+            &self.component_id,
+            &name,
+            Measurement::Histogram(value),
+            attributes,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WashMetrics {
+    fn id(&self) -> &'static str {
+        WASH_METRICS_ID
+    }
+
+    fn set_meter(&self, meter: Meter) {
+        // Instruments of the previous meter are recreated on their next use
+        let mut instruments = self.instruments();
+        *self
+            .meter
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = meter;
+        instruments.clear();
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from("wash:metrics/recorder@0.1.0")]),
+            ..Default::default()
+        }
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wash" && i.package == "metrics")
+        else {
+            tracing::warn!(
+                "WashMetrics plugin requested for unsupported interface(s): {:?}",
+                interfaces
+            );
+            return Ok(());
+        };
+
+        let max_metrics = match interface.config.get("max-metrics") {
+            Some(max) => max
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid max-metrics for wash:metrics: '{max}'"))?,
+            None => DEFAULT_MAX_METRICS,
+        };
+
+        recorder::add_to_linker::<_, HasSelf<Ctx>>(component.linker(), |ctx| ctx)?;
+
+        self.workloads()
+            .entry(Arc::from(component.workload_id()))
+            .or_insert_with(|| WorkloadMetrics {
+                namespace: component.workload_namespace().into(),
+                name: component.workload_name().into(),
+                max_metrics,
+                names: HashSet::new(),
+            });
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        self.workloads().remove(workload_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(max_metrics: usize) -> WashMetrics {
+        let plugin = WashMetrics::default();
+        plugin.workloads().insert(
+            "workload-1".into(),
+            WorkloadMetrics {
+                namespace: "default".into(),
+                name: "shop".into(),
+                max_metrics,
+                names: HashSet::new(),
+            },
+        );
+        plugin
+    }
+
+    fn attributes(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("orders_total").is_ok());
+        assert!(validate_name("cart.size-bytes").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("1st").is_err());
+        assert!(validate_name("orders total").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let plugin = plugin(2);
+        let record = |name: &str, measurement, attrs: &[(&str, &str)]| {
+            plugin.record("workload-1", "api", name, measurement, attributes(attrs))
+        };
+
+        record("orders", Measurement::Counter(1), &[("region", "eu")]).unwrap();
+        record("orders", Measurement::Counter(2), &[]).unwrap();
+        record("cart_size", Measurement::Histogram(3.0), &[]).unwrap();
+
+        // A name keeps its kind
+        assert!(
+            record("orders", Measurement::Gauge(1.0), &[])
+                .unwrap_err()
+                .contains("already recorded as a counter")
+        );
+        // The workload recorded its 2 metric names
+        assert!(
+            record("queue_depth", Measurement::Gauge(1.0), &[])
+                .unwrap_err()
+                .contains("at most 2 metrics")
+        );
+        assert!(
+            record(
+                "orders",
+                Measurement::Counter(1),
+                &[("wasmcloud.namespace", "other")]
+            )
+            .unwrap_err()
+            .contains("reserved")
+        );
+        assert!(record("cart_size", Measurement::Histogram(f64::NAN), &[]).is_err());
+        assert!(
+            plugin
+                .record(
+                    "workload-2",
+                    "api",
+                    "orders",
+                    Measurement::Counter(1),
+                    Vec::new()
+                )
+                .unwrap_err()
+                .contains("not bound")
+        );
+    }
+}
@@ -0,0 +1,706 @@
+//! # Socket ingress plugin
+//!
+//! This module lets components serve raw TCP connections and UDP datagrams,
+//! e.g. MQTT bridges, DNS servers or custom binary protocols, through the
+//! `wash:sockets` interfaces. The host binds the port of the workload and
+//! hands what arrives to its component:
+//!
+//! - `tcp-handler`: every accepted connection is passed to
+//!   `handle-connection` as a pair of `wasi:io` streams, the same streams
+//!   `wasi:sockets` hands out for its connections. The connection is closed
+//!   when the call returns.
+//! - `udp-handler`: every received datagram is passed to `handle-datagram`,
+//!   and the datagrams it returns are sent back to the peer.
+//!
+//! The port is assigned per workload by the config of its `wash:sockets`
+//! interface:
+//!
+//! ```json
+//! { "namespace": "wash", "package": "sockets", "interfaces": ["tcp-handler"],
+//!   "config": { "tcp_port": "1883", "max_connections": "100" } }
+//! ```
+//!
+//! - `tcp_port`, `udp_port`: the port connections and datagrams are accepted
+//!   on, required with the matching handler. A port is served by a single
+//!   workload of the host and must be within the ports of the plugin, see
+//!   [`WashSockets::with_ports`].
+//! - `address`: the address to bind, instead of the one of the plugin, see
+//!   [`WashSockets::with_address`]
+//! - `max_connections`: most connections, or datagrams, the workload handles
+//!   at once. Connections beyond are closed right away, datagrams dropped.
+//!
+//! Connections and datagrams invoke the component like HTTP requests do:
+//! each on a fresh instance, or a pre-initialized one, within the invocation
+//! limits, instance pool and invocation timeout of the component, on its
+//! execution pool. A connection is a single invocation, so the invocation
+//! timeout of the component caps how long a connection stays open.
+//! Connections also count towards the connection quotas of the host and of
+//! the namespace of the workload, see [`crate::host::limits`], alongside
+//! those of the HTTP server; datagrams only count towards the namespace
+//! quota, while they are handled.
+//!
+//! Ports are bound once the workload is resolved and released when it stops.
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use wasmtime_wasi::p2::{
+    InputStream, OutputStream,
+    pipe::{AsyncReadStream, AsyncWriteStream},
+};
+
+use crate::engine::ctx::Ctx;
+use crate::engine::workload::{ResolvedWorkload, WorkloadComponent};
+use crate::host::limits::{ConnectionTracker, HostLimits};
+use crate::host::metering;
+use crate::plugin::{HostPlugin, PluginRole};
+use crate::wit::{WitInterface, WitWorld};
+
+const WASH_SOCKETS_ID: &str = "wash-sockets";
+/// Config key of the port TCP connections are accepted on
+pub const TCP_PORT_CONFIG_KEY: &str = "tcp_port";
+/// Config key of the port UDP datagrams are received on
+pub const UDP_PORT_CONFIG_KEY: &str = "udp_port";
+/// The ports workloads may bind unless the plugin says otherwise, those
+/// that don't need privileges
+pub const DEFAULT_PORTS: RangeInclusive<u16> = 1024..=u16::MAX;
+/// Bytes written to a connection that may be buffered before a write blocks
+const WRITE_BUDGET: usize = 64 * 1024;
+/// Largest datagram received, the largest a UDP datagram can be
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+mod tcp_bindings {
+    wasmtime::component::bindgen!({
+        world: "tcp-server",
+        exports: { default: async },
+        with: {
+            "wasi:io": ::wasmtime_wasi::p2::bindings::io,
+        },
+    });
+}
+
+mod udp_bindings {
+    wasmtime::component::bindgen!({
+        world: "udp-server",
+        exports: { default: async },
+    });
+}
+
+/// The config of the `wash:sockets` interface of a workload
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SocketsConfig {
+    address: Option<IpAddr>,
+    tcp_port: Option<u16>,
+    udp_port: Option<u16>,
+    max_connections: Option<usize>,
+}
+
+impl SocketsConfig {
+    fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Self> {
+        fn parse<T: std::str::FromStr>(
+            config: &HashMap<String, String>,
+            key: &str,
+        ) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            config
+                .get(key)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    value
+                        .parse()
+                        .with_context(|| format!("invalid {key} '{value}'"))
+                })
+                .transpose()
+        }
+        let parsed = Self {
+            address: parse(config, "address")?,
+            tcp_port: parse(config, TCP_PORT_CONFIG_KEY)?,
+            udp_port: parse(config, UDP_PORT_CONFIG_KEY)?,
+            max_connections: parse(config, "max_connections")?,
+        };
+        ensure!(
+            parsed.max_connections != Some(0),
+            "max_connections must be greater than zero"
+        );
+        Ok(parsed)
+    }
+}
+
+/// The ports a bound component serves
+struct ComponentData {
+    workload_id: String,
+    tcp: Option<SocketAddr>,
+    udp: Option<SocketAddr>,
+    /// Connections and datagrams handled at once, if limited
+    permits: Option<Arc<Semaphore>>,
+    cancel_token: CancellationToken,
+}
+
+/// Serves TCP connections and UDP datagrams to components, see the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct WashSockets {
+    address: IpAddr,
+    ports: RangeInclusive<u16>,
+    connections: Arc<ConnectionTracker>,
+    /// The components with a handler, by component ID
+    components: Arc<RwLock<HashMap<String, ComponentData>>>,
+}
+
+impl Default for WashSockets {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            ports: DEFAULT_PORTS,
+            connections: Arc::default(),
+            components: Arc::default(),
+        }
+    }
+}
+
+impl WashSockets {
+    /// Binds the ports of workloads on `address` unless they set their own.
+    /// Defaults to all IPv4 addresses.
+    pub fn with_address(mut self, address: IpAddr) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Only lets workloads bind `ports`. Defaults to [`DEFAULT_PORTS`].
+    pub fn with_ports(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.ports = ports;
+        self
+    }
+
+    /// Holds connections to the connection quotas of `limits`. The limits
+    /// are those the plugin is created with, they don't follow the limits
+    /// of the host when they change.
+    pub fn with_limits(self, limits: &HostLimits) -> Self {
+        self.connections.set_limits(limits);
+        self
+    }
+
+    /// Returns the address a component binds `port` on, unless it is outside
+    /// of the ports of the plugin or another component serves it.
+    async fn claim(
+        &self,
+        address: Option<IpAddr>,
+        port: u16,
+        claimed: impl Fn(&ComponentData) -> Option<SocketAddr>,
+    ) -> anyhow::Result<SocketAddr> {
+        ensure!(
+            self.ports.contains(&port),
+            "port {port} is outside of the ports workloads may bind, {}-{}",
+            self.ports.start(),
+            self.ports.end()
+        );
+        if let Some(other) = self
+            .components
+            .read()
+            .await
+            .values()
+            .find(|data| claimed(data).is_some_and(|addr| addr.port() == port))
+        {
+            bail!(
+                "port {port} is already served by workload {}",
+                other.workload_id
+            );
+        }
+        Ok(SocketAddr::new(address.unwrap_or(self.address), port))
+    }
+
+    /// Stops accepting connections and datagrams for the components of
+    /// `workload_id`, or of every workload if `None`.
+    async fn cancel(&self, workload_id: Option<&str>) {
+        for data in self.components.read().await.values() {
+            if workload_id.is_none_or(|id| id == data.workload_id) {
+                data.cancel_token.cancel();
+            }
+        }
+    }
+
+    /// Accepts connections on `listener` until `cancel_token` is cancelled,
+    /// handling each concurrently.
+    fn serve_tcp(
+        &self,
+        workload: ResolvedWorkload,
+        pre: tcp_bindings::TcpServerPre<Ctx>,
+        component_id: String,
+        listener: TcpListener,
+        permits: Option<Arc<Semaphore>>,
+        cancel_token: CancellationToken,
+    ) {
+        let connections = self.connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            debug!(component_id, "failed to accept connection: {e}");
+                            continue;
+                        }
+                    },
+                    _ = cancel_token.cancelled() => break,
+                };
+                // Refused connections are closed by dropping them
+                let permit = match &permits {
+                    Some(permits) => match permits.clone().try_acquire_owned() {
+                        Ok(permit) => Some(permit),
+                        Err(_) => {
+                            debug!(
+                                component_id,
+                                %addr,
+                                "workload handles as many connections as it may, refusing connection"
+                            );
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let Some(host) = connections.open_connection() else {
+                    debug!(
+                        component_id,
+                        %addr,
+                        "host has as many connections as it allows, refusing connection"
+                    );
+                    continue;
+                };
+                let Some(namespace) = connections.open_request(workload.namespace()) else {
+                    debug!(
+                        component_id,
+                        %addr,
+                        "namespace has as many connections as it allows, refusing connection"
+                    );
+                    continue;
+                };
+                let (workload, pre, component_id) =
+                    (workload.clone(), pre.clone(), component_id.clone());
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        handle_connection(&workload, &pre, &component_id, stream, addr).await
+                    {
+                        warn!(component_id, %addr, "failed to handle connection: {e:#}");
+                    }
+                    drop((permit, host, namespace));
+                });
+            }
+        });
+    }
+
+    /// Receives datagrams on `socket` until `cancel_token` is cancelled,
+    /// handling each concurrently.
+    fn serve_udp(
+        &self,
+        workload: ResolvedWorkload,
+        pre: udp_bindings::UdpServerPre<Ctx>,
+        component_id: String,
+        socket: UdpSocket,
+        permits: Option<Arc<Semaphore>>,
+        cancel_token: CancellationToken,
+    ) {
+        let connections = self.connections.clone();
+        let socket = Arc::new(socket);
+        tokio::spawn(async move {
+            let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+            loop {
+                let (len, addr) = tokio::select! {
+                    received = socket.recv_from(&mut buf) => match received {
+                        Ok(received) => received,
+                        Err(e) => {
+                            debug!(component_id, "failed to receive datagram: {e}");
+                            continue;
+                        }
+                    },
+                    _ = cancel_token.cancelled() => break,
+                };
+                let permit = match &permits {
+                    Some(permits) => match permits.clone().try_acquire_owned() {
+                        Ok(permit) => Some(permit),
+                        Err(_) => {
+                            debug!(
+                                component_id,
+                                %addr,
+                                "workload handles as many datagrams as it may, dropping datagram"
+                            );
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let Some(namespace) = connections.open_request(workload.namespace()) else {
+                    debug!(
+                        component_id,
+                        %addr,
+                        "namespace handles as many requests as it allows, dropping datagram"
+                    );
+                    continue;
+                };
+                let data = buf[..len].to_vec();
+                let (workload, pre, component_id, socket) = (
+                    workload.clone(),
+                    pre.clone(),
+                    component_id.clone(),
+                    socket.clone(),
+                );
+                tokio::spawn(async move {
+                    match handle_datagram(&workload, &pre, &component_id, data, addr).await {
+                        Ok(replies) => {
+                            for reply in replies {
+                                if let Err(e) = socket.send_to(&reply, addr).await {
+                                    debug!(component_id, %addr, "failed to send datagram: {e}");
+                                    break;
+                                }
+                            }
+                        }
+                        Err(e) => warn!(component_id, %addr, "failed to handle datagram: {e:#}"),
+                    }
+                    drop((permit, namespace));
+                });
+            }
+        });
+    }
+}
+
+/// Returns the peer as the component sees it.
+fn peer(addr: SocketAddr) -> (String, u16) {
+    (addr.ip().to_canonical().to_string(), addr.port())
+}
+
+/// Hands a connection to a new instance of a component, on its execution pool.
+async fn handle_connection(
+    workload: &ResolvedWorkload,
+    pre: &tcp_bindings::TcpServerPre<Ctx>,
+    component_id: &str,
+    stream: TcpStream,
+    addr: SocketAddr,
+) -> anyhow::Result<()> {
+    let (handle, pre, id) = (workload.clone(), pre.clone(), component_id.to_string());
+    workload
+        .run_invocation(component_id, async move {
+            let (mut store, instance) = match handle.take_pre_initialized(&id).await {
+                Some(ready) => (ready.store, Some(ready.instance)),
+                None => (handle.new_store(&id).await?, None),
+            };
+            store
+                .data()
+                .set_invocation("wash:sockets/tcp-handler#handle-connection");
+            let (reader, writer) = stream.into_split();
+            let input: Box<dyn InputStream> = Box::new(AsyncReadStream::new(reader));
+            let output: Box<dyn OutputStream> =
+                Box::new(AsyncWriteStream::new(WRITE_BUDGET, writer));
+            let input = store.data_mut().table.push(input)?;
+            let output = store.data_mut().table.push(output)?;
+            let server = match instance {
+                Some(instance) => tcp_bindings::TcpServer::new(&mut store, &instance)?,
+                None => pre
+                    .instantiate_async(&mut store)
+                    .await
+                    .context("failed to instantiate component")?,
+            };
+            let (address, port) = peer(addr);
+            let handled = server
+                .wash_sockets_tcp_handler()
+                .call_handle_connection(
+                    &mut store,
+                    &tcp_bindings::wash::sockets::types::Peer { address, port },
+                    input,
+                    output,
+                )
+                .await;
+            metering::record_fuel(&mut store);
+            handled
+                .map_err(crate::engine::explain_trap)?
+                .map_err(|e| anyhow::anyhow!("handler returned an error: {e}"))
+        })
+        .await
+}
+
+/// Hands a datagram to a new instance of a component, on its execution pool,
+/// returning the datagrams to send back.
+async fn handle_datagram(
+    workload: &ResolvedWorkload,
+    pre: &udp_bindings::UdpServerPre<Ctx>,
+    component_id: &str,
+    data: Vec<u8>,
+    addr: SocketAddr,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let (handle, pre, id) = (workload.clone(), pre.clone(), component_id.to_string());
+    workload
+        .run_invocation(component_id, async move {
+            let (mut store, instance) = match handle.take_pre_initialized(&id).await {
+                Some(ready) => (ready.store, Some(ready.instance)),
+                None => (handle.new_store(&id).await?, None),
+            };
+            store
+                .data()
+                .set_invocation("wash:sockets/udp-handler#handle-datagram");
+            let server = match instance {
+                Some(instance) => udp_bindings::UdpServer::new(&mut store, &instance)?,
+                None => pre
+                    .instantiate_async(&mut store)
+                    .await
+                    .context("failed to instantiate component")?,
+            };
+            let (address, port) = peer(addr);
+            let handled = server
+                .wash_sockets_udp_handler()
+                .call_handle_datagram(
+                    &mut store,
+                    &udp_bindings::wash::sockets::types::Peer { address, port },
+                    &data,
+                )
+                .await;
+            metering::record_fuel(&mut store);
+            handled
+                .map_err(crate::engine::explain_trap)?
+                .map_err(|e| anyhow::anyhow!("handler returned an error: {e}"))
+        })
+        .await
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WashSockets {
+    fn id(&self) -> &'static str {
+        WASH_SOCKETS_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            exports: HashSet::from([WitInterface::from(
+                "wash:sockets/tcp-handler,udp-handler@0.1.0",
+            )]),
+            ..Default::default()
+        }
+    }
+
+    fn role(&self) -> PluginRole {
+        PluginRole::Trigger
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wash" && i.package == "sockets")
+        else {
+            return Ok(());
+        };
+        let config = SocketsConfig::from_config(&interface.config)?;
+        let workload_id = component.workload_id();
+
+        let tcp = match (
+            interface.interfaces.contains("tcp-handler"),
+            config.tcp_port,
+        ) {
+            (true, Some(port)) => Some(self.claim(config.address, port, |data| data.tcp).await?),
+            (true, None) => bail!("wash:sockets/tcp-handler needs a {TCP_PORT_CONFIG_KEY}"),
+            (false, Some(_)) => bail!(
+                "{TCP_PORT_CONFIG_KEY} needs the component to export wash:sockets/tcp-handler"
+            ),
+            (false, None) => None,
+        };
+        let udp = match (
+            interface.interfaces.contains("udp-handler"),
+            config.udp_port,
+        ) {
+            (true, Some(port)) => Some(self.claim(config.address, port, |data| data.udp).await?),
+            (true, None) => bail!("wash:sockets/udp-handler needs a {UDP_PORT_CONFIG_KEY}"),
+            (false, Some(_)) => bail!(
+                "{UDP_PORT_CONFIG_KEY} needs the component to export wash:sockets/udp-handler"
+            ),
+            (false, None) => None,
+        };
+
+        self.components.write().await.insert(
+            component.id().to_string(),
+            ComponentData {
+                workload_id: workload_id.to_string(),
+                tcp,
+                udp,
+                permits: config
+                    .max_connections
+                    .map(|max| Arc::new(Semaphore::new(max))),
+                cancel_token: CancellationToken::new(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn on_workload_resolved(
+        &self,
+        workload: &ResolvedWorkload,
+        component_id: &str,
+    ) -> anyhow::Result<()> {
+        let (tcp, udp, permits, cancel_token) = {
+            let components = self.components.read().await;
+            let Some(data) = components.get(component_id) else {
+                return Ok(());
+            };
+            (
+                data.tcp,
+                data.udp,
+                data.permits.clone(),
+                data.cancel_token.clone(),
+            )
+        };
+
+        if let Some(addr) = tcp {
+            let instance_pre = workload.instantiate_pre(component_id).await?;
+            let pre = tcp_bindings::TcpServerPre::new(instance_pre)
+                .context("failed to instantiate tcp-handler pre")?;
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("failed to bind TCP port {addr}"))?;
+            debug!(component_id, %addr, "accepting TCP connections");
+            self.serve_tcp(
+                workload.clone(),
+                pre,
+                component_id.to_string(),
+                listener,
+                permits.clone(),
+                cancel_token.clone(),
+            );
+        }
+        if let Some(addr) = udp {
+            let instance_pre = workload.instantiate_pre(component_id).await?;
+            let pre = udp_bindings::UdpServerPre::new(instance_pre)
+                .context("failed to instantiate udp-handler pre")?;
+            let socket = match UdpSocket::bind(addr).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    // The TCP port may already be served
+                    cancel_token.cancel();
+                    return Err(e).with_context(|| format!("failed to bind UDP port {addr}"));
+                }
+            };
+            debug!(component_id, %addr, "receiving UDP datagrams");
+            self.serve_udp(
+                workload.clone(),
+                pre,
+                component_id.to_string(),
+                socket,
+                permits,
+                cancel_token,
+            );
+        }
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        self.cancel(Some(workload_id)).await;
+        self.components
+            .write()
+            .await
+            .retain(|_, data| data.workload_id != workload_id);
+        Ok(())
+    }
+
+    async fn on_workload_stop(&self, workload_id: &str) -> anyhow::Result<()> {
+        self.cancel(Some(workload_id)).await;
+        Ok(())
+    }
+
+    async fn on_host_shutdown(&self) -> anyhow::Result<()> {
+        self.cancel(None).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sockets_config() {
+        assert_eq!(
+            SocketsConfig::from_config(&HashMap::new()).unwrap(),
+            SocketsConfig::default()
+        );
+        assert_eq!(
+            SocketsConfig::from_config(&config(&[
+                ("tcp_port", "1883"),
+                ("udp_port", " 5353 "),
+                ("address", "127.0.0.1"),
+                ("max_connections", "100"),
+            ]))
+            .unwrap(),
+            SocketsConfig {
+                address: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                tcp_port: Some(1883),
+                udp_port: Some(5353),
+                max_connections: Some(100),
+            }
+        );
+        assert!(SocketsConfig::from_config(&config(&[("tcp_port", "70000")])).is_err());
+        assert!(SocketsConfig::from_config(&config(&[("address", "localhost")])).is_err());
+        assert!(SocketsConfig::from_config(&config(&[("max_connections", "0")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim() {
+        let sockets = WashSockets::default().with_ports(10_000..=10_999);
+        assert_eq!(
+            sockets.claim(None, 10_000, |data| data.tcp).await.unwrap(),
+            "0.0.0.0:10000".parse().unwrap()
+        );
+        assert!(sockets.claim(None, 80, |data| data.tcp).await.is_err());
+
+        sockets.components.write().await.insert(
+            "component".to_string(),
+            ComponentData {
+                workload_id: "a".to_string(),
+                tcp: Some("0.0.0.0:10000".parse().unwrap()),
+                udp: None,
+                permits: None,
+                cancel_token: CancellationToken::new(),
+            },
+        );
+        // TCP and UDP ports are claimed separately
+        assert!(sockets.claim(None, 10_000, |data| data.tcp).await.is_err());
+        assert_eq!(
+            sockets
+                .claim(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)), 10_000, |data| data
+                    .udp)
+                .await
+                .unwrap(),
+            "127.0.0.1:10000".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_peer() {
+        assert_eq!(
+            peer("[::ffff:192.0.2.1]:4000".parse().unwrap()),
+            ("192.0.2.1".to_string(), 4000)
+        );
+        assert_eq!(
+            peer("[2001:db8::1]:53".parse().unwrap()),
+            ("2001:db8::1".to_string(), 53)
+        );
+    }
+}
@@ -0,0 +1,642 @@
+//! # Vector Store Plugin
+//!
+//! This plugin implements the `wash:vector@0.1.0` interfaces, giving components
+//! storage and cosine similarity search of embeddings without raw network access
+//! to a vector database. This is the retrieval half of RAG-style components,
+//! which embed documents, store them, and later look up the closest matches to a
+//! prompt.
+//!
+//! Storage is delegated to a [`VectorBackend`]. [`InMemoryVectorBackend`] is
+//! meant for development and tests, [`QdrantBackend`] stores vectors in a
+//! [Qdrant](https://qdrant.tech) instance over its REST API and, with the
+//! `pgvector` feature, `PgvectorBackend` stores them in Postgres with the
+//! [pgvector](https://github.com/pgvector/pgvector) extension. Other databases
+//! can be supported by implementing [`VectorBackend`].
+//!
+//! Each workload may only use the collections listed in the `collections` config
+//! of its interface:
+//!
+//! ```text
+//! wash:vector/store@0.1.0 { collections: "docs,faq" }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::{Context as _, bail, ensure};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use wasmtime::component::HasSelf;
+
+use crate::{
+    engine::{ctx::Ctx, workload::WorkloadComponent},
+    plugin::HostPlugin,
+    wit::{WitInterface, WitWorld},
+};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "vector",
+        imports: { default: async | trappable },
+    });
+}
+
+use bindings::wash::vector::types;
+
+#[cfg(feature = "pgvector")]
+mod pgvector;
+#[cfg(feature = "pgvector")]
+pub use pgvector::PgvectorBackend;
+
+const WASH_VECTOR_ID: &str = "wash-vector";
+/// Most points a single query may return
+const MAX_QUERY_LIMIT: u32 = 1000;
+
+/// A vector stored in a collection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorPoint {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub metadata: Vec<(String, String)>,
+}
+
+/// A point matching a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoredPoint {
+    pub id: String,
+    /// Cosine similarity to the query vector
+    pub score: f32,
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Storage for the vector store plugin.
+#[async_trait::async_trait]
+pub trait VectorBackend: Send + Sync + 'static {
+    /// Inserts or replaces points, creating the collection if needed.
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> anyhow::Result<()>;
+
+    /// Returns up to `limit` points closest to `vector`, best match first, among
+    /// the points whose metadata contains every entry of `filter`.
+    async fn query(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u32,
+        filter: Vec<(String, String)>,
+    ) -> anyhow::Result<Vec<ScoredPoint>>;
+
+    /// Removes points by id, ignoring unknown ids.
+    async fn delete(&self, collection: &str, ids: Vec<String>) -> anyhow::Result<()>;
+}
+
+/// A [`VectorBackend`] that keeps vectors in memory and searches them
+/// exhaustively. Data is lost when the host stops.
+#[derive(Default)]
+pub struct InMemoryVectorBackend {
+    collections: RwLock<HashMap<String, HashMap<String, VectorPoint>>>,
+}
+
+#[async_trait::async_trait]
+impl VectorBackend for InMemoryVectorBackend {
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> anyhow::Result<()> {
+        let mut collections = self.collections.write().await;
+        let stored = collections.entry(collection.to_string()).or_default();
+        let dimension = stored
+            .values()
+            .next()
+            .or(points.first())
+            .map(|p| p.vector.len());
+        ensure!(
+            points.iter().all(|p| Some(p.vector.len()) == dimension),
+            "all vectors in collection '{collection}' must have dimension {}",
+            dimension.unwrap_or_default()
+        );
+        stored.extend(points.into_iter().map(|p| (p.id.clone(), p)));
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u32,
+        filter: Vec<(String, String)>,
+    ) -> anyhow::Result<Vec<ScoredPoint>> {
+        let collections = self.collections.read().await;
+        let Some(stored) = collections.get(collection) else {
+            return Ok(Vec::new());
+        };
+        let mut matches: Vec<ScoredPoint> = stored
+            .values()
+            .filter(|p| filter.iter().all(|entry| p.metadata.contains(entry)))
+            .map(|p| ScoredPoint {
+                id: p.id.clone(),
+                score: cosine_similarity(&vector, &p.vector),
+                metadata: p.metadata.clone(),
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit as usize);
+        Ok(matches)
+    }
+
+    async fn delete(&self, collection: &str, ids: Vec<String>) -> anyhow::Result<()> {
+        if let Some(stored) = self.collections.write().await.get_mut(collection) {
+            for id in ids {
+                stored.remove(&id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 { 0.0 } else { dot / norm }
+}
+
+/// Payload key holding the component's point id, since Qdrant only accepts
+/// integer and UUID ids
+const QDRANT_ID_KEY: &str = "_wash_id";
+
+/// A [`VectorBackend`] backed by a Qdrant instance.
+pub struct QdrantBackend {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    /// Collections known to exist
+    collections: RwLock<HashSet<String>>,
+}
+
+impl QdrantBackend {
+    /// Creates a backend for the Qdrant REST API at `url`, e.g. `http://localhost:6333`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            collections: RwLock::default(),
+        }
+    }
+
+    /// Authenticates requests with an API key.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let mut request = self.client.request(method, format!("{}{path}", self.url));
+        if let Some(api_key) = &self.api_key {
+            request = request.header("api-key", api_key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        request.send().await.context("failed to reach qdrant")
+    }
+
+    async fn call(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let response = self.send(method, path, Some(body)).await?;
+        let status = response.status();
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .context("failed to decode qdrant response")?;
+        if !status.is_success() {
+            bail!(
+                "qdrant returned {status}: {}",
+                body["status"]["error"].as_str().unwrap_or("unknown error")
+            );
+        }
+        Ok(body["result"].clone())
+    }
+
+    async fn ensure_collection(&self, collection: &str, dimension: usize) -> anyhow::Result<()> {
+        if self.collections.read().await.contains(collection) {
+            return Ok(());
+        }
+        let path = format!("/collections/{collection}");
+        let response = self.send(reqwest::Method::GET, &path, None).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            self.call(
+                reqwest::Method::PUT,
+                &path,
+                json!({ "vectors": { "size": dimension, "distance": "Cosine" } }),
+            )
+            .await
+            .with_context(|| format!("failed to create collection '{collection}'"))?;
+        } else {
+            response
+                .error_for_status()
+                .context("qdrant request failed")?;
+        }
+        self.collections
+            .write()
+            .await
+            .insert(collection.to_string());
+        Ok(())
+    }
+}
+
+/// Derives a stable Qdrant UUID from a component's point id.
+fn qdrant_point_id(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Uuid::from_bytes(bytes).to_string()
+}
+
+fn qdrant_filter(filter: &[(String, String)]) -> serde_json::Value {
+    let must: Vec<_> = filter
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "match": { "value": value } }))
+        .collect();
+    json!({ "must": must })
+}
+
+fn qdrant_scored_point(point: &serde_json::Value) -> Option<ScoredPoint> {
+    let payload = point["payload"].as_object()?;
+    Some(ScoredPoint {
+        id: payload.get(QDRANT_ID_KEY)?.as_str()?.to_string(),
+        score: point["score"].as_f64()? as f32,
+        metadata: payload
+            .iter()
+            .filter(|(key, _)| *key != QDRANT_ID_KEY)
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect(),
+    })
+}
+
+#[async_trait::async_trait]
+impl VectorBackend for QdrantBackend {
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> anyhow::Result<()> {
+        let Some(first) = points.first() else {
+            return Ok(());
+        };
+        self.ensure_collection(collection, first.vector.len())
+            .await?;
+        let points: Vec<_> = points
+            .into_iter()
+            .map(|p| {
+                let mut payload: serde_json::Map<_, _> = p
+                    .metadata
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect();
+                payload.insert(QDRANT_ID_KEY.to_string(), p.id.clone().into());
+                json!({ "id": qdrant_point_id(&p.id), "vector": p.vector, "payload": payload })
+            })
+            .collect();
+        self.call(
+            reqwest::Method::PUT,
+            &format!("/collections/{collection}/points?wait=true"),
+            json!({ "points": points }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u32,
+        filter: Vec<(String, String)>,
+    ) -> anyhow::Result<Vec<ScoredPoint>> {
+        let mut body = json!({ "vector": vector, "limit": limit, "with_payload": true });
+        if !filter.is_empty() {
+            body["filter"] = qdrant_filter(&filter);
+        }
+        let result = self
+            .call(
+                reqwest::Method::POST,
+                &format!("/collections/{collection}/points/search"),
+                body,
+            )
+            .await?;
+        Ok(result
+            .as_array()
+            .map(|points| points.iter().filter_map(qdrant_scored_point).collect())
+            .unwrap_or_default())
+    }
+
+    async fn delete(&self, collection: &str, ids: Vec<String>) -> anyhow::Result<()> {
+        let points: Vec<_> = ids.iter().map(|id| qdrant_point_id(id)).collect();
+        self.call(
+            reqwest::Method::POST,
+            &format!("/collections/{collection}/points/delete?wait=true"),
+            json!({ "points": points }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Plugin providing `wash:vector` on top of a [`VectorBackend`].
+#[derive(Clone)]
+pub struct WashVector {
+    backend: Arc<dyn VectorBackend>,
+    /// Collections each workload may use, keyed by workload ID
+    collections: Arc<RwLock<HashMap<Arc<str>, HashSet<String>>>>,
+}
+
+impl Default for WashVector {
+    fn default() -> Self {
+        Self::new(InMemoryVectorBackend::default())
+    }
+}
+
+impl WashVector {
+    /// Creates a plugin storing vectors in `backend`.
+    pub fn new(backend: impl VectorBackend) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            collections: Arc::default(),
+        }
+    }
+
+    async fn is_allowed(&self, workload_id: &str, collection: &str) -> bool {
+        self.collections
+            .read()
+            .await
+            .get(workload_id)
+            .is_some_and(|collections| collections.contains(collection))
+    }
+}
+
+impl types::Host for Ctx {}
+
+impl bindings::wash::vector::store::Host for Ctx {
+    async fn upsert(
+        &mut self,
+        collection: String,
+        points: Vec<types::Point>,
+    ) -> anyhow::Result<Result<(), String>> {
+        let Some(plugin) = self.get_plugin::<WashVector>(WASH_VECTOR_ID) else {
+            return Ok(Err("vector plugin not available".to_string()));
+        };
+        if !plugin.is_allowed(&self.workload_id, &collection).await {
+            return Ok(Err("unauthorized".to_string()));
+        }
+        if points.iter().any(|p| p.vector.is_empty()) {
+            return Ok(Err("vectors must not be empty".to_string()));
+        }
+        let points = points
+            .into_iter()
+            .map(|p| VectorPoint {
+                id: p.id,
+                vector: p.vector,
+                metadata: p.metadata,
+            })
+            .collect();
+        Ok(plugin
+            .backend
+            .upsert(&collection, points)
+            .await
+            .map_err(|e| format!("{e:#}")))
+    }
+
+    async fn query(
+        &mut self,
+        collection: String,
+        vector: Vec<f32>,
+        limit: u32,
+        filter: Vec<(String, String)>,
+    ) -> anyhow::Result<Result<Vec<types::ScoredPoint>, String>> {
+        let Some(plugin) = self.get_plugin::<WashVector>(WASH_VECTOR_ID) else {
+            return Ok(Err("vector plugin not available".to_string()));
+        };
+        if !plugin.is_allowed(&self.workload_id, &collection).await {
+            return Ok(Err("unauthorized".to_string()));
+        }
+        let matches = plugin
+            .backend
+            .query(&collection, vector, limit.min(MAX_QUERY_LIMIT), filter)
+            .await;
+        Ok(matches
+            .map(|matches| {
+                matches
+                    .into_iter()
+                    .map(|m| types::ScoredPoint {
+                        id: m.id,
+                        score: m.score,
+                        metadata: m.metadata,
+                    })
+                    .collect()
+            })
+            .map_err(|e| format!("{e:#}")))
+    }
+
+    async fn delete(
+        &mut self,
+        collection: String,
+        ids: Vec<String>,
+    ) -> anyhow::Result<Result<(), String>> {
+        let Some(plugin) = self.get_plugin::<WashVector>(WASH_VECTOR_ID) else {
+            return Ok(Err("vector plugin not available".to_string()));
+        };
+        if !plugin.is_allowed(&self.workload_id, &collection).await {
+            return Ok(Err("unauthorized".to_string()));
+        }
+        Ok(plugin
+            .backend
+            .delete(&collection, ids)
+            .await
+            .map_err(|e| format!("{e:#}")))
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WashVector {
+    fn id(&self) -> &'static str {
+        WASH_VECTOR_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from("wash:vector/types,store@0.1.0")]),
+            ..Default::default()
+        }
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wash" && i.package == "vector")
+        else {
+            tracing::warn!(
+                "WashVector plugin requested for unsupported interface(s): {:?}",
+                interfaces
+            );
+            return Ok(());
+        };
+
+        let collections: HashSet<String> = interface
+            .config
+            .get("collections")
+            .map(|collections| {
+                collections
+                    .split(',')
+                    .map(|c| c.trim().to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if collections.is_empty() {
+            tracing::warn!(
+                workload_id = component.workload_id(),
+                "no collections configured for wash:vector, all requests will be rejected"
+            );
+        }
+
+        let linker = component.linker();
+        types::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wash::vector::store::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+
+        self.collections
+            .write()
+            .await
+            .entry(Arc::from(component.workload_id()))
+            .or_default()
+            .extend(collections);
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        self.collections.write().await.remove(workload_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(id: &str, vector: &[f32], topic: &str) -> VectorPoint {
+        VectorPoint {
+            id: id.to_string(),
+            vector: vector.to_vec(),
+            metadata: vec![("topic".to_string(), topic.to_string())],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_query_ranks_by_similarity() {
+        let backend = InMemoryVectorBackend::default();
+        backend
+            .upsert(
+                "docs",
+                vec![
+                    point("a", &[1.0, 0.0], "rust"),
+                    point("b", &[0.7, 0.7], "rust"),
+                    point("c", &[0.0, 1.0], "go"),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let matches = backend
+            .query("docs", vec![1.0, 0.1], 2, Vec::new())
+            .await
+            .unwrap();
+        let ids: Vec<_> = matches.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+
+        let filter = vec![("topic".to_string(), "go".to_string())];
+        let matches = backend
+            .query("docs", vec![1.0, 0.1], 10, filter)
+            .await
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, "c");
+
+        // Upserting an existing id replaces it, deleting removes it
+        backend
+            .upsert("docs", vec![point("a", &[0.0, 1.0], "go")])
+            .await
+            .unwrap();
+        backend
+            .delete("docs", vec!["c".to_string(), "missing".to_string()])
+            .await
+            .unwrap();
+        let matches = backend
+            .query("docs", vec![0.0, 1.0], 10, Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(matches[0].id, "a");
+        assert_eq!(matches.len(), 2);
+
+        assert!(
+            backend
+                .query("empty", vec![1.0], 10, Vec::new())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_rejects_mismatched_dimensions() {
+        let backend = InMemoryVectorBackend::default();
+        backend
+            .upsert("docs", vec![point("a", &[1.0, 0.0], "rust")])
+            .await
+            .unwrap();
+        assert!(
+            backend
+                .upsert("docs", vec![point("b", &[1.0, 0.0, 0.0], "rust")])
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_qdrant_point_id_is_stable_uuid() {
+        let id = qdrant_point_id("doc-1");
+        assert_eq!(id, qdrant_point_id("doc-1"));
+        assert_ne!(id, qdrant_point_id("doc-2"));
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+
+        let point = json!({
+            "id": id,
+            "score": 0.5,
+            "payload": { QDRANT_ID_KEY: "doc-1", "topic": "rust" },
+        });
+        assert_eq!(
+            qdrant_scored_point(&point),
+            Some(ScoredPoint {
+                id: "doc-1".to_string(),
+                score: 0.5,
+                metadata: vec![("topic".to_string(), "rust".to_string())],
+            })
+        );
+    }
+}
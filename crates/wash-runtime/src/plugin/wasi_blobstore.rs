@@ -1,36 +1,63 @@
-//! # WASI Blobstore Memory Plugin
+//! # WASI Blobstore Plugin
 //!
-//! This module implements an in-memory blobstore plugin for the wasmCloud runtime,
-//! providing the `wasi:blobstore@0.2.0-draft` interface for development and testing scenarios.
+//! This module implements the `wasi:blobstore@0.2.0-draft` interfaces for the
+//! wasmCloud runtime. Containers are kept in memory by default, or in a
+//! directory of the host or an S3 compatible object store, picked per
+//! workload by the config of its `wasi:blobstore` interface, and belong to
+//! the namespace of the workload, see [`blobstore`](crate::plugin::blobstore).
+//!
+//! Object bodies are streamed: `incoming-value-consume-async` reads from the
+//! store as the component reads the stream, and the output stream of an
+//! `outgoing-value` is spooled to a temporary file, which is streamed to the
+//! store on `finish`, so large objects are not held in the memory of the
+//! host.
 
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
-    time::SystemTime,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll, ready},
 };
 
 const WASI_BLOBSTORE_ID: &str = "wasi-blobstore";
-use tokio::sync::RwLock;
+/// Bytes the component may write to an output stream before it is flushed
+const WRITE_BUDGET: usize = 64 * 1024;
+use tempfile::NamedTempFile;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWrite},
+    sync::RwLock,
+};
 use wasmtime::component::{HasSelf, Resource};
 use wasmtime_wasi::p2::{
     InputStream, OutputStream,
-    pipe::{MemoryInputPipe, MemoryOutputPipe},
+    pipe::{AsyncReadStream, AsyncWriteStream},
 };
 
 use crate::{
     engine::ctx::Ctx,
     engine::workload::WorkloadComponent,
-    plugin::HostPlugin,
+    plugin::{
+        HostPlugin,
+        blobstore::{
+            BlobBody, BlobStore, BlobstoreBackend, container_name,
+            fs::FilesystemStore,
+            memory::MemoryStore,
+            s3::{S3Credentials, S3Store},
+            validate_container_name, validate_object_name,
+        },
+    },
     wit::{WitInterface, WitWorld},
 };
 
+pub use crate::plugin::blobstore::memory::{ContainerData, ObjectData};
+
 mod bindings {
     wasmtime::component::bindgen!({
         world: "blobstore",
         imports: { default: async | trappable },
         with: {
             "wasi:io": ::wasmtime_wasi::p2::bindings::io,
-            "wasi:blobstore/container/container": String,
+            "wasi:blobstore/container/container": crate::plugin::wasi_blobstore::ContainerHandle,
             "wasi:blobstore/container/stream-object-names": crate::plugin::wasi_blobstore::StreamObjectNamesHandle,
             "wasi:blobstore/types/incoming-value": crate::plugin::wasi_blobstore::IncomingValueHandle,
             "wasi:blobstore/types/outgoing-value": crate::plugin::wasi_blobstore::OutgoingValueHandle,
@@ -46,29 +73,30 @@ use bindings::wasi::blobstore::{
     },
 };
 
-/// Metadata for an object stored in memory
-#[derive(Clone, Debug)]
-pub struct ObjectData {
-    pub name: String,
-    pub container: String,
-    pub data: Vec<u8>,
-    pub created_at: u64,
-}
-
-/// In-memory container representation
-#[derive(Clone, Debug)]
-pub struct ContainerData {
-    pub name: String,
-    pub created_at: u64,
-    pub objects: HashMap<String, ObjectData>,
+/// Resource representation for a container
+pub struct ContainerHandle {
+    store: Arc<dyn BlobStore>,
+    /// The name of the container given by the component
+    name: String,
+    /// The name of the container in the store, see [`container_name`]
+    scoped: String,
 }
 
 /// Resource representation for an incoming value (data being read)
-pub type IncomingValueHandle = Vec<u8>;
+pub struct IncomingValueHandle {
+    body: BlobBody,
+}
 
 /// Resource representation for an outgoing value (data being written)
 pub struct OutgoingValueHandle {
-    pub pipe: MemoryOutputPipe,
+    /// The file the output stream writes to, until the value is finished
+    spool: NamedTempFile,
+    /// Largest object the store of the workload accepts
+    max_size: Option<u64>,
+    /// Whether the output stream was already handed out
+    body_taken: bool,
+    /// The container the value is written to, set by `write-data`
+    target: Option<(Arc<dyn BlobStore>, String)>,
     pub container_name: Option<String>,
     pub object_name: Option<String>,
 }
@@ -82,28 +110,134 @@ pub struct StreamObjectNamesHandle {
     pub position: usize,
 }
 
-/// Memory-based blobstore plugin
+/// Writes the output stream of an outgoing value to its spool file, failing
+/// once the object grows larger than the store accepts.
+struct SpoolWriter {
+    file: tokio::fs::File,
+    remaining: Option<u64>,
+}
+
+impl AsyncWrite for SpoolWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        if let Some(remaining) = self.remaining
+            && buf.len() as u64 > remaining
+        {
+            return Poll::Ready(Err(std::io::Error::other(
+                "object exceeds the maximum size of the blobstore",
+            )));
+        }
+        let written = ready!(Pin::new(&mut self.file).poll_write(cx, buf))?;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= written as u64;
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+/// The store of the containers of a workload and its namespace
+#[derive(Clone)]
+struct WorkloadStore {
+    store: Arc<dyn BlobStore>,
+    namespace: String,
+}
+
+/// Blobstore plugin storing containers in memory, on disk or in S3
 #[derive(Clone, Default)]
 pub struct WasiBlobstore {
-    /// Storage for all containers, keyed by store context ID
-    storage: Arc<RwLock<HashMap<String, HashMap<String, ContainerData>>>>,
-    /// The maximum size for objects stored in the blobstore
-    max_object_size: usize,
+    memory: Arc<MemoryStore>,
+    /// Filesystem and S3 stores by backend, shared by the workloads using
+    /// the same one
+    stores: Arc<Mutex<HashMap<BlobstoreBackend, Arc<dyn BlobStore>>>>,
+    /// The store of each bound workload, by workload ID
+    workloads: Arc<RwLock<HashMap<String, WorkloadStore>>>,
 }
 
 impl WasiBlobstore {
+    /// Creates the plugin. `max_object_size` limits the objects kept in
+    /// memory, 1 MB by default, and does not apply to the other backends.
     pub fn new(max_object_size: Option<usize>) -> Self {
         Self {
-            storage: Arc::new(RwLock::new(HashMap::new())),
-            max_object_size: max_object_size.unwrap_or(1_000_000), // 1mb limit by default
+            memory: Arc::new(MemoryStore::new(max_object_size.map(|size| size as u64))),
+            ..Default::default()
         }
     }
 
-    fn get_timestamp() -> u64 {
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+    /// Returns the in-memory store, which keeps the containers of workloads
+    /// without a backend configured.
+    pub fn memory(&self) -> &Arc<MemoryStore> {
+        &self.memory
+    }
+
+    /// Returns the store of `backend`, creating filesystem and S3 stores once
+    /// per backend.
+    fn store(&self, backend: BlobstoreBackend) -> anyhow::Result<Arc<dyn BlobStore>> {
+        if backend == BlobstoreBackend::Memory {
+            return Ok(self.memory.clone());
+        }
+        let mut stores = self.stores.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(store) = stores.get(&backend) {
+            return Ok(store.clone());
+        }
+        let store: Arc<dyn BlobStore> = match &backend {
+            BlobstoreBackend::Memory => unreachable!("memory store is returned above"),
+            BlobstoreBackend::Filesystem { root } => Arc::new(FilesystemStore::new(root)?),
+            BlobstoreBackend::S3 {
+                bucket,
+                region,
+                endpoint,
+                path_style,
+            } => {
+                let mut store = S3Store::new(S3Credentials::from_env()?, bucket, region);
+                if let Some(endpoint) = endpoint {
+                    store = store.with_endpoint(endpoint, *path_style)?;
+                }
+                Arc::new(store)
+            }
+        };
+        stores.insert(backend, store.clone());
+        Ok(store)
+    }
+}
+
+fn blob_error(e: anyhow::Error) -> String {
+    format!("{e:#}")
+}
+
+impl Ctx {
+    /// Returns the store of the workload and the namespace its containers
+    /// belong to.
+    async fn blobstore(&self) -> Result<WorkloadStore, String> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
+            return Err("blobstore plugin not available".to_string());
+        };
+        plugin
+            .workloads
+            .read()
+            .await
+            .get(self.workload_id.as_ref())
+            .cloned()
+            .ok_or_else(|| "workload has no blobstore".to_string())
+    }
+
+    /// Returns the store and the name in the store of a container resource.
+    fn blobstore_container(
+        &self,
+        container: &Resource<ContainerHandle>,
+    ) -> anyhow::Result<(Arc<dyn BlobStore>, String)> {
+        let container = self.table.get(container)?;
+        Ok((container.store.clone(), container.scoped.clone()))
     }
 }
 
@@ -112,46 +246,48 @@ impl bindings::wasi::blobstore::blobstore::Host for Ctx {
     async fn create_container(
         &mut self,
         name: ContainerName,
-    ) -> anyhow::Result<Result<Resource<String>, BlobstoreError>> {
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
+    ) -> anyhow::Result<Result<Resource<ContainerHandle>, BlobstoreError>> {
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
         };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        if workload_storage.contains_key(&name) {
-            return Ok(Err(format!("container '{name}' already exists")));
+        if let Err(e) = validate_container_name(&name) {
+            return Ok(Err(blob_error(e)));
         }
-
-        let container_data = ContainerData {
-            name: name.clone(),
-            created_at: WasiBlobstore::get_timestamp(),
-            objects: HashMap::new(),
-        };
-
-        workload_storage.insert(name.clone(), container_data);
-        let resource = self.table.push(name)?;
+        let scoped = container_name(&workload.namespace, &name);
+        if let Err(e) = workload.store.create_container(&scoped).await {
+            return Ok(Err(blob_error(e)));
+        }
+        let resource = self.table.push(ContainerHandle {
+            store: workload.store,
+            name,
+            scoped,
+        })?;
         Ok(Ok(resource))
     }
 
     async fn get_container(
         &mut self,
         name: ContainerName,
-    ) -> anyhow::Result<Result<Resource<String>, BlobstoreError>> {
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
+    ) -> anyhow::Result<Result<Resource<ContainerHandle>, BlobstoreError>> {
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
         };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        if !workload_storage.contains_key(&name) {
-            return Ok(Err(format!("container '{name}' does not exist")));
+        if let Err(e) = validate_container_name(&name) {
+            return Ok(Err(blob_error(e)));
         }
-
-        let resource = self.table.push(name)?;
+        let scoped = container_name(&workload.namespace, &name);
+        match workload.store.container_info(&scoped).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Ok(Err(format!("container '{name}' does not exist"))),
+            Err(e) => return Ok(Err(blob_error(e))),
+        }
+        let resource = self.table.push(ContainerHandle {
+            store: workload.store,
+            name,
+            scoped,
+        })?;
         Ok(Ok(resource))
     }
 
@@ -159,30 +295,37 @@ impl bindings::wasi::blobstore::blobstore::Host for Ctx {
         &mut self,
         name: ContainerName,
     ) -> anyhow::Result<Result<(), BlobstoreError>> {
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
         };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        workload_storage.remove(&name);
-        Ok(Ok(()))
+        if let Err(e) = validate_container_name(&name) {
+            return Ok(Err(blob_error(e)));
+        }
+        Ok(workload
+            .store
+            .delete_container(&container_name(&workload.namespace, &name))
+            .await
+            .map_err(blob_error))
     }
 
     async fn container_exists(
         &mut self,
         name: ContainerName,
     ) -> anyhow::Result<Result<bool, BlobstoreError>> {
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
         };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        Ok(Ok(workload_storage.contains_key(&name)))
+        if validate_container_name(&name).is_err() {
+            return Ok(Ok(false));
+        }
+        Ok(workload
+            .store
+            .container_info(&container_name(&workload.namespace, &name))
+            .await
+            .map(|created_at| created_at.is_some())
+            .map_err(blob_error))
     }
 
     async fn copy_object(
@@ -190,54 +333,30 @@ impl bindings::wasi::blobstore::blobstore::Host for Ctx {
         src: ObjectId,
         dest: ObjectId,
     ) -> anyhow::Result<Result<(), BlobstoreError>> {
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        // Get source object data (clone to avoid borrow conflicts)
-        let src_object_data = {
-            let src_container = match workload_storage.get(&src.container) {
-                Some(container) => container,
-                None => {
-                    return Ok(Err(format!(
-                        "source container '{}' does not exist",
-                        src.container
-                    )));
-                }
-            };
-
-            match src_container.objects.get(&src.object) {
-                Some(object) => object.clone(),
-                None => {
-                    return Ok(Err(format!(
-                        "source object '{}' does not exist",
-                        src.object
-                    )));
-                }
-            }
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
         };
-
-        // Ensure destination container exists and copy object
-        let dest_container = match workload_storage.get_mut(&dest.container) {
-            Some(container) => container,
-            None => {
-                return Ok(Err(format!(
-                    "destination container '{}' does not exist",
-                    dest.container
-                )));
+        for (container, object) in [
+            (&src.container, &src.object),
+            (&dest.container, &dest.object),
+        ] {
+            if let Err(e) =
+                validate_container_name(container).and_then(|()| validate_object_name(object))
+            {
+                return Ok(Err(blob_error(e)));
             }
-        };
-
-        let mut copied_object = src_object_data;
-        copied_object.name = dest.object.clone();
-        copied_object.container = dest.container.clone();
-        copied_object.created_at = WasiBlobstore::get_timestamp();
-
-        dest_container.objects.insert(dest.object, copied_object);
-        Ok(Ok(()))
+        }
+        Ok(workload
+            .store
+            .copy_object(
+                &container_name(&workload.namespace, &src.container),
+                &src.object,
+                &container_name(&workload.namespace, &dest.container),
+                &dest.object,
+            )
+            .await
+            .map_err(blob_error))
     }
 
     async fn move_object(
@@ -246,21 +365,23 @@ impl bindings::wasi::blobstore::blobstore::Host for Ctx {
         dest: ObjectId,
     ) -> anyhow::Result<Result<(), BlobstoreError>> {
         // First copy the object
-        let _ = self.copy_object(src.clone(), dest).await?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        // Then delete the source
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        if let Some(src_container) = workload_storage.get_mut(&src.container) {
-            src_container.objects.remove(&src.object);
+        if let Err(e) = self.copy_object(src.clone(), dest).await? {
+            return Ok(Err(e));
         }
 
-        Ok(Ok(()))
+        // Then delete the source
+        let workload = match self.blobstore().await {
+            Ok(workload) => workload,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(workload
+            .store
+            .delete_objects(
+                &container_name(&workload.namespace, &src.container),
+                &[src.object],
+            )
+            .await
+            .map_err(blob_error))
     }
 }
 
@@ -268,46 +389,36 @@ impl bindings::wasi::blobstore::blobstore::Host for Ctx {
 impl bindings::wasi::blobstore::container::HostContainer for Ctx {
     async fn name(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
     ) -> anyhow::Result<Result<String, ContainerError>> {
-        let container_name = self.table.get(&container)?;
-        Ok(Ok(container_name.clone()))
+        let container = self.table.get(&container)?;
+        Ok(Ok(container.name.clone()))
     }
 
     async fn info(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
     ) -> anyhow::Result<Result<ContainerMetadata, ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(container_name) {
-            Some(container_data) => Ok(Ok(ContainerMetadata {
-                name: container_data.name.clone(),
-                created_at: container_data.created_at,
-            })),
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
-        }
+        let (store, scoped) = self.blobstore_container(&container)?;
+        let name = self.table.get(&container)?.name.clone();
+        Ok(match store.container_info(&scoped).await {
+            Ok(Some(created_at)) => Ok(ContainerMetadata { name, created_at }),
+            Ok(None) => Err(format!("container '{name}' does not exist")),
+            Err(e) => Err(blob_error(e)),
+        })
     }
 
     async fn get_data(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         name: ObjectName,
         start: u64,
         end: u64,
     ) -> anyhow::Result<Result<Resource<IncomingValueHandle>, ContainerError>> {
-        let container_name = self.table.get(&container)?;
+        let (store, scoped) = self.blobstore_container(&container)?;
 
         tracing::debug!(
-            container = container_name,
+            container = scoped,
             object = name,
             start = start,
             end = end,
@@ -315,123 +426,83 @@ impl bindings::wasi::blobstore::container::HostContainer for Ctx {
             "Getting object data from container"
         );
 
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            tracing::error!("blobstore plugin not available for get_data");
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(container_name) {
-            Some(container_data) => match container_data.objects.get(&name) {
-                Some(object_data) => {
-                    let start_idx = start.min(object_data.data.len() as u64) as usize;
-                    let end_idx = end.min(object_data.data.len() as u64) as usize;
-                    let data_slice = object_data.data[start_idx..end_idx].to_vec();
-
-                    tracing::debug!(
-                        container = container_name,
-                        object = name,
-                        original_size = object_data.data.len(),
-                        slice_size = data_slice.len(),
-                        start_idx = start_idx,
-                        end_idx = end_idx,
-                        "Retrieved object data slice"
-                    );
-
-                    let resource = self.table.push(data_slice)?;
-                    Ok(Ok(resource))
-                }
-                None => {
-                    tracing::warn!(
-                        container = container_name,
-                        object = name,
-                        "Object does not exist in container"
-                    );
-                    Ok(Err(format!("object '{name}' does not exist")))
-                }
-            },
-            None => {
+        if let Err(e) = validate_object_name(&name) {
+            return Ok(Err(blob_error(e)));
+        }
+        match store.read(&scoped, &name, start, end).await {
+            Ok(body) => {
+                tracing::debug!(
+                    container = scoped,
+                    object = name,
+                    size = body.size,
+                    "Opened object data"
+                );
+                let resource = self.table.push(IncomingValueHandle { body })?;
+                Ok(Ok(resource))
+            }
+            Err(e) => {
                 tracing::warn!(
-                    container = container_name,
-                    workload_id = self.id,
-                    "Container does not exist for workload"
+                    container = scoped,
+                    object = name,
+                    error = %e,
+                    "Failed to read object"
                 );
-                Ok(Err(format!("container '{container_name}' does not exist")))
+                Ok(Err(blob_error(e)))
             }
         }
     }
 
     async fn write_data(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         name: ObjectName,
         data: Resource<OutgoingValueHandle>,
     ) -> anyhow::Result<Result<(), ContainerError>> {
-        let container_name = self.table.get(&container)?.clone();
+        let (store, scoped) = self.blobstore_container(&container)?;
 
         tracing::debug!(
-            container = container_name,
+            container = scoped,
             object = name,
             workload_id = self.id,
             "Initiating write_data for object"
         );
 
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            tracing::error!("blobstore plugin not available for write_data");
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
+        if let Err(e) = validate_object_name(&name) {
+            return Ok(Err(blob_error(e)));
+        }
         // Verify the container exists
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        if !workload_storage.contains_key(&container_name) {
-            tracing::warn!(
-                container = container_name,
-                workload_id = self.id,
-                "Container does not exist for write_data"
-            );
-            return Ok(Err(format!("container '{container_name}' does not exist")));
+        match store.container_info(&scoped).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                tracing::warn!(
+                    container = scoped,
+                    workload_id = self.id,
+                    "Container does not exist for write_data"
+                );
+                return Ok(Err(format!("container '{scoped}' does not exist")));
+            }
+            Err(e) => return Ok(Err(blob_error(e))),
         }
-        drop(storage);
 
-        // Store the container and object names - actual writing happens in finish()
+        // Store the target of the value - actual writing happens in finish()
         let outgoing_handle = self.table.get_mut(&data)?;
-        outgoing_handle.container_name = Some(container_name.clone());
-        outgoing_handle.object_name = Some(name.clone());
-
-        tracing::debug!(
-            container = container_name,
-            object = name,
-            "write_data setup complete, actual write will happen in finish()"
-        );
+        outgoing_handle.target = Some((store, scoped.clone()));
+        outgoing_handle.container_name = Some(scoped);
+        outgoing_handle.object_name = Some(name);
 
         Ok(Ok(()))
     }
 
     async fn list_objects(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
     ) -> anyhow::Result<Result<Resource<StreamObjectNamesHandle>, ContainerError>> {
-        let container_name = self.table.get(&container)?;
+        let (store, scoped) = self.blobstore_container(&container)?;
 
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(container_name) {
-            Some(container_data) => {
-                let objects: Vec<String> = container_data.objects.keys().cloned().collect();
+        match store.list_objects(&scoped).await {
+            Ok(objects) => {
                 let handle = StreamObjectNamesHandle {
-                    container_name: container_name.clone(),
+                    container_name: scoped,
                     workload_id: self.id.clone(),
                     objects,
                     position: 0,
@@ -439,131 +510,83 @@ impl bindings::wasi::blobstore::container::HostContainer for Ctx {
                 let resource = self.table.push(handle)?;
                 Ok(Ok(resource))
             }
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
+            Err(e) => Ok(Err(blob_error(e))),
         }
     }
 
     async fn delete_object(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         name: ObjectName,
     ) -> anyhow::Result<Result<(), ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(container_name) {
-            Some(container_data) => {
-                container_data.objects.remove(&name);
-                Ok(Ok(()))
-            }
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
-        }
+        self.delete_objects(container, vec![name]).await
     }
 
     async fn delete_objects(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         names: Vec<ObjectName>,
     ) -> anyhow::Result<Result<(), ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(container_name) {
-            Some(container_data) => {
-                for name in names {
-                    container_data.objects.remove(&name);
-                }
-                Ok(Ok(()))
-            }
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
+        let (store, scoped) = self.blobstore_container(&container)?;
+        if let Some(e) = names
+            .iter()
+            .find_map(|name| validate_object_name(name).err())
+        {
+            return Ok(Err(blob_error(e)));
         }
+        Ok(store
+            .delete_objects(&scoped, &names)
+            .await
+            .map_err(blob_error))
     }
 
     async fn has_object(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         name: ObjectName,
     ) -> anyhow::Result<Result<bool, ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(container_name) {
-            Some(container_data) => Ok(Ok(container_data.objects.contains_key(&name))),
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
+        let (store, scoped) = self.blobstore_container(&container)?;
+        if validate_object_name(&name).is_err() {
+            return Ok(Ok(false));
         }
+        Ok(store
+            .object_info(&scoped, &name)
+            .await
+            .map(|info| info.is_some())
+            .map_err(blob_error))
     }
 
     async fn object_info(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
         name: ObjectName,
     ) -> anyhow::Result<Result<ObjectMetadata, ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(container_name) {
-            Some(container_data) => match container_data.objects.get(&name) {
-                Some(object_data) => Ok(Ok(ObjectMetadata {
-                    name: object_data.name.clone(),
-                    container: object_data.container.clone(),
-                    created_at: object_data.created_at,
-                    size: object_data.data.len() as u64,
-                })),
-                None => Ok(Err(format!("object '{name}' does not exist"))),
-            },
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
+        let (store, scoped) = self.blobstore_container(&container)?;
+        let container_name = self.table.get(&container)?.name.clone();
+        if let Err(e) = validate_object_name(&name) {
+            return Ok(Err(blob_error(e)));
         }
+        Ok(match store.object_info(&scoped, &name).await {
+            Ok(Some(info)) => Ok(ObjectMetadata {
+                name,
+                container: container_name,
+                created_at: info.created_at,
+                size: info.size,
+            }),
+            Ok(None) => Err(format!("object '{name}' does not exist")),
+            Err(e) => Err(blob_error(e)),
+        })
     }
 
     async fn clear(
         &mut self,
-        container: Resource<String>,
+        container: Resource<ContainerHandle>,
     ) -> anyhow::Result<Result<(), ContainerError>> {
-        let container_name = self.table.get(&container)?;
-
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            return Ok(Err("blobstore plugin not available".to_string()));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(container_name) {
-            Some(container_data) => {
-                container_data.objects.clear();
-                Ok(Ok(()))
-            }
-            None => Ok(Err(format!("container '{container_name}' does not exist"))),
-        }
+        let (store, scoped) = self.blobstore_container(&container)?;
+        Ok(store.clear(&scoped).await.map_err(blob_error))
     }
 
-    async fn drop(&mut self, rep: Resource<String>) -> anyhow::Result<()> {
+    async fn drop(&mut self, rep: Resource<ContainerHandle>) -> anyhow::Result<()> {
         // Container resource cleanup - resource table handles this automatically
         tracing::debug!(
             workload_id = self.id,
@@ -637,99 +660,35 @@ impl bindings::wasi::blobstore::types::HostOutgoingValue for Ctx {
     async fn new_outgoing_value(&mut self) -> anyhow::Result<Resource<OutgoingValueHandle>> {
         tracing::debug!(workload_id = self.id, "Creating new OutgoingValue");
 
-        let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-            tracing::error!("blobstore plugin not available in new_outgoing_value");
-            return Err(anyhow::anyhow!("blobstore plugin not available"));
-        };
-
+        let workload = self.blobstore().await.map_err(anyhow::Error::msg)?;
         let handle = OutgoingValueHandle {
-            pipe: MemoryOutputPipe::new(plugin.max_object_size),
+            spool: NamedTempFile::new()?,
+            max_size: workload.store.max_object_size(),
+            body_taken: false,
+            target: None,
             container_name: None,
             object_name: None,
         };
-
-        tracing::debug!(
-            workload_id = self.id,
-            pipe_capacity = plugin.max_object_size,
-            "Created OutgoingValueHandle with MemoryOutputPipe"
-        );
-
-        match self.table.push(handle) {
-            Ok(resource) => {
-                tracing::debug!(
-                    workload_id = self.id,
-                    resource_id = ?resource,
-                    "Successfully pushed OutgoingValueHandle to resource table"
-                );
-                Ok(resource)
-            }
-            Err(e) => {
-                tracing::error!(
-                    workload_id = self.id,
-                    error = ?e,
-                    "Failed to push OutgoingValueHandle to resource table in new_outgoing_value"
-                );
-                Err(e.into())
-            }
-        }
+        Ok(self.table.push(handle)?)
     }
 
     async fn outgoing_value_write_body(
         &mut self,
         outgoing_value: Resource<OutgoingValueHandle>,
     ) -> anyhow::Result<Result<Resource<bindings::wasi::io0_2_1::streams::OutputStream>, ()>> {
-        tracing::debug!(workload_id = self.id, "outgoing_value_write_body called");
+        let handle = self.table.get_mut(&outgoing_value)?;
+        if handle.body_taken {
+            return Ok(Err(()));
+        }
+        handle.body_taken = true;
 
-        let handle = match self.table.get_mut(&outgoing_value) {
-            Ok(h) => {
-                tracing::debug!(
-                    workload_id = self.id,
-                    "Successfully retrieved OutgoingValueHandle from table"
-                );
-                h
-            }
-            Err(e) => {
-                tracing::error!(
-                    workload_id = self.id,
-                    error = ?e,
-                    "Failed to get OutgoingValueHandle from table"
-                );
-                return Err(e.into());
-            }
+        // The stream writes to a handle of its own, read again in finish()
+        let writer = SpoolWriter {
+            file: tokio::fs::File::from_std(handle.spool.reopen()?),
+            remaining: handle.max_size,
         };
-
-        tracing::debug!(
-            workload_id = self.id,
-            "Creating boxed OutputStream from pipe"
-        );
-
-        // Return the pipe as the output stream - this is the same pipe that will be read in finish()
-        let boxed: Box<dyn OutputStream> = Box::new(handle.pipe.clone());
-
-        tracing::debug!(
-            workload_id = self.id,
-            "Attempting to push OutputStream to resource table"
-        );
-
-        match self.table.push(boxed) {
-            Ok(stream) => {
-                tracing::debug!(
-                    workload_id = self.id,
-                    stream_resource_id = ?stream,
-                    "Successfully pushed OutputStream to resource table"
-                );
-                Ok(Ok(stream))
-            }
-            Err(e) => {
-                tracing::error!(
-                    workload_id = self.id,
-                    error = ?e,
-                    error_type = std::any::type_name::<anyhow::Error>(),
-                    "Failed to push OutputStream to resource table - this is likely the TryFromIntError source"
-                );
-                Err(e.into())
-            }
-        }
+        let stream: Box<dyn OutputStream> = Box::new(AsyncWriteStream::new(WRITE_BUDGET, writer));
+        Ok(Ok(self.table.push(stream)?))
     }
 
     async fn finish(
@@ -740,71 +699,42 @@ impl bindings::wasi::blobstore::types::HostOutgoingValue for Ctx {
 
         let handle = self.table.delete(outgoing_value)?;
 
-        tracing::debug!(
-            container_name = ?handle.container_name,
-            object_name = ?handle.object_name,
-            "Retrieved OutgoingValueHandle in finish()"
-        );
-
-        // If we have container and object names, perform the actual write
-        if let (Some(container_name), Some(object_name)) =
-            (&handle.container_name, &handle.object_name)
-        {
-            let Some(plugin) = self.get_plugin::<WasiBlobstore>(WASI_BLOBSTORE_ID) else {
-                tracing::error!("blobstore plugin not available in finish()");
-                return Ok(Err("blobstore plugin not available".to_string()));
-            };
-
-            // Get the data from the pipe
-            let data_bytes = handle.pipe.contents();
-
-            tracing::debug!(
-                container = container_name,
-                object = object_name,
-                pipe_data_size = data_bytes.len(),
-                workload_id = self.id,
-                "Retrieved data from pipe in finish()"
-            );
-
-            let mut storage = plugin.storage.write().await;
-            let workload_storage = storage.entry(self.id.clone()).or_default();
-
-            match workload_storage.get_mut(container_name) {
-                Some(container_data) => {
-                    let object_data = ObjectData {
-                        name: object_name.clone(),
-                        container: container_name.clone(),
-                        data: data_bytes.to_vec(),
-                        created_at: WasiBlobstore::get_timestamp(),
-                    };
-                    container_data
-                        .objects
-                        .insert(object_name.clone(), object_data);
-
-                    tracing::debug!(
-                        container = container_name,
-                        object = object_name,
-                        size = data_bytes.len(),
-                        "Stored object data to container"
-                    );
-                }
-                None => {
-                    tracing::error!(
-                        container = container_name,
-                        workload_id = self.id,
-                        "Container does not exist in finish()"
-                    );
-                    return Ok(Err(format!("container '{container_name}' does not exist")));
-                }
-            }
-        } else {
+        // If we have a target, perform the actual write
+        let (Some((store, container)), Some(object_name)) = (handle.target, handle.object_name)
+        else {
             tracing::warn!(
                 workload_id = self.id,
                 "finish() called without container/object names set"
             );
+            return Ok(Ok(()));
+        };
+
+        let file = tokio::fs::File::from_std(handle.spool.reopen()?);
+        let size = file.metadata().await?.len();
+        if let Some(max_size) = handle.max_size
+            && size > max_size
+        {
+            return Ok(Err(format!(
+                "object of {size} bytes exceeds the maximum size of {max_size} bytes"
+            )));
         }
 
-        Ok(Ok(()))
+        tracing::debug!(
+            container = container,
+            object = object_name,
+            size = size,
+            workload_id = self.id,
+            "Writing object data to container"
+        );
+
+        let body = BlobBody {
+            reader: Box::new(file),
+            size,
+        };
+        Ok(store
+            .write(&container, &object_name, body)
+            .await
+            .map_err(blob_error))
     }
 
     async fn drop(&mut self, rep: Resource<OutgoingValueHandle>) -> anyhow::Result<()> {
@@ -823,7 +753,13 @@ impl bindings::wasi::blobstore::types::HostIncomingValue for Ctx {
         &mut self,
         incoming_value: Resource<IncomingValueHandle>,
     ) -> anyhow::Result<Result<Vec<u8>, BlobstoreError>> {
-        let data = self.table.delete(incoming_value)?;
+        let IncomingValueHandle { body } = self.table.delete(incoming_value)?;
+
+        let mut data = Vec::with_capacity(body.size as usize);
+        let mut reader = body.reader;
+        if let Err(e) = reader.read_to_end(&mut data).await {
+            return Ok(Err(format!("failed to read object: {e}")));
+        }
 
         tracing::debug!(
             workload_id = self.id,
@@ -840,28 +776,22 @@ impl bindings::wasi::blobstore::types::HostIncomingValue for Ctx {
     ) -> anyhow::Result<
         Result<Resource<bindings::wasi::blobstore::types::IncomingValueAsyncBody>, BlobstoreError>,
     > {
-        let data = self.table.get(&incoming_value)?;
+        let IncomingValueHandle { body } = self.table.delete(incoming_value)?;
 
         tracing::debug!(
             workload_id = self.id,
-            data_size = data.len(),
-            "incoming_value_consume_async creating MemoryInputPipe with data"
+            data_size = body.size,
+            "incoming_value_consume_async streaming object data"
         );
 
-        let stream: Box<dyn InputStream> = Box::new(MemoryInputPipe::new(data.clone()));
+        let stream: Box<dyn InputStream> = Box::new(AsyncReadStream::new(body.reader));
         let stream = self.table.push(stream)?;
 
-        tracing::debug!(
-            workload_id = self.id,
-            "incoming_value_consume_async created stream resource"
-        );
-
         Ok(Ok(stream))
     }
 
     async fn size(&mut self, incoming_value: Resource<IncomingValueHandle>) -> anyhow::Result<u64> {
-        let data = self.table.get(&incoming_value)?;
-        Ok(data.len() as u64)
+        Ok(self.table.get(&incoming_value)?.body.size)
     }
 
     async fn drop(&mut self, rep: Resource<IncomingValueHandle>) -> anyhow::Result<()> {
@@ -905,17 +835,18 @@ impl HostPlugin for WasiBlobstore {
         interfaces: std::collections::HashSet<crate::wit::WitInterface>,
     ) -> anyhow::Result<()> {
         // Check if any of the interfaces are wasi:blobstore related
-        let has_blobstore = interfaces
+        let Some(interface) = interfaces
             .iter()
-            .any(|i| i.namespace == "wasi" && i.package == "blobstore");
-
-        if !has_blobstore {
+            .find(|i| i.namespace == "wasi" && i.package == "blobstore")
+        else {
             tracing::warn!(
                 "WasiBlobstore plugin requested for non-wasi:blobstore interface(s): {:?}",
                 interfaces
             );
             return Ok(());
-        }
+        };
+        let backend = BlobstoreBackend::from_config(&interface.config)?;
+        let store = self.store(backend)?;
 
         // Add blobstore interfaces to the workload's linker
         // Note: wasi:io interfaces are already added by wasmtime_wasi::add_to_linker_async()
@@ -937,11 +868,16 @@ impl HostPlugin for WasiBlobstore {
             "Successfully added blobstore interfaces to linker for workload"
         );
 
-        // Initialize storage for this component (note: actual storage is per-store-context, this is just a placeholder)
-        let mut storage = self.storage.write().await;
-        storage.insert(id.to_string(), HashMap::new());
+        // Components of a workload share its store
+        self.workloads.write().await.insert(
+            workload_handle.workload_id().to_string(),
+            WorkloadStore {
+                store,
+                namespace: workload_handle.workload_namespace().to_string(),
+            },
+        );
 
-        tracing::debug!("WasiBlobstore plugin bound to workload '{id}'");
+        tracing::debug!("WasiBlobstore plugin bound to component '{id}'");
 
         Ok(())
     }
@@ -951,9 +887,8 @@ impl HostPlugin for WasiBlobstore {
         workload_id: &str,
         _interfaces: std::collections::HashSet<crate::wit::WitInterface>,
     ) -> anyhow::Result<()> {
-        // Clean up storage for this workload
-        let mut storage = self.storage.write().await;
-        storage.remove(workload_id);
+        // The containers stay, they belong to the namespace of the workload
+        self.workloads.write().await.remove(workload_id);
 
         tracing::debug!("WasiBlobstore plugin unbound from workload '{workload_id}'");
 
@@ -963,62 +898,85 @@ impl HostPlugin for WasiBlobstore {
 
 #[cfg(test)]
 mod tests {
+    use tokio::io::AsyncWriteExt as _;
+
     use super::*;
 
     #[test]
     fn test_wasi_blobstore_creation() {
         let blobstore = WasiBlobstore::new(None);
-        assert!(blobstore.storage.try_read().is_ok());
-    }
-
-    #[test]
-    fn test_get_timestamp() {
-        let timestamp = WasiBlobstore::get_timestamp();
-        assert!(timestamp > 0);
-    }
-
-    #[test]
-    fn test_object_data_creation() {
-        let data = ObjectData {
-            name: "test.txt".to_string(),
-            container: "test-container".to_string(),
-            data: b"hello world".to_vec(),
-            created_at: WasiBlobstore::get_timestamp(),
-        };
-
-        assert_eq!(data.name, "test.txt");
-        assert_eq!(data.container, "test-container");
-        assert_eq!(data.data, b"hello world");
-        assert!(data.created_at > 0);
+        assert!(blobstore.workloads.try_read().is_ok());
+        assert_eq!(blobstore.memory().max_object_size(), Some(1_000_000));
+        assert_eq!(
+            WasiBlobstore::new(Some(1024)).memory().max_object_size(),
+            Some(1024)
+        );
     }
 
     #[test]
-    fn test_container_data_creation() {
-        let container = ContainerData {
-            name: "test-container".to_string(),
-            created_at: WasiBlobstore::get_timestamp(),
-            objects: HashMap::new(),
+    fn test_stores_shared_per_backend() {
+        let blobstore = WasiBlobstore::new(None);
+        let dir = tempfile::tempdir().unwrap();
+        let filesystem = || {
+            blobstore.store(BlobstoreBackend::Filesystem {
+                root: dir.path().to_path_buf(),
+            })
         };
-
-        assert_eq!(container.name, "test-container");
-        assert!(container.created_at > 0);
-        assert!(container.objects.is_empty());
+        let first = filesystem().unwrap();
+        let second = filesystem().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(
+            &blobstore.store(BlobstoreBackend::Memory).unwrap(),
+            &(blobstore.memory().clone() as Arc<dyn BlobStore>)
+        ));
     }
 
     #[tokio::test]
-    async fn test_storage_operations() {
+    async fn test_containers_namespaced() {
         let blobstore = WasiBlobstore::new(None);
-
-        // Test write access
-        {
-            let mut storage = blobstore.storage.write().await;
-            storage.insert("workload1".to_string(), HashMap::new());
+        let memory = blobstore.memory();
+        for namespace in ["team-a", "team-b"] {
+            memory
+                .create_container(&container_name(namespace, "uploads"))
+                .await
+                .unwrap();
         }
+        memory
+            .write(
+                &container_name("team-a", "uploads"),
+                "a",
+                BlobBody::from_bytes(b"a".to_vec()),
+            )
+            .await
+            .unwrap();
+        assert!(
+            memory
+                .container("team-b/uploads")
+                .await
+                .unwrap()
+                .objects
+                .is_empty()
+        );
+        assert!(
+            memory
+                .container("team-a/uploads")
+                .await
+                .unwrap()
+                .objects
+                .contains_key("a")
+        );
+    }
 
-        // Test read access
-        {
-            let storage = blobstore.storage.read().await;
-            assert!(storage.contains_key("workload1"));
-        }
+    #[tokio::test]
+    async fn test_spool_writer_limit() {
+        let spool = NamedTempFile::new().unwrap();
+        let mut writer = SpoolWriter {
+            file: tokio::fs::File::from_std(spool.reopen().unwrap()),
+            remaining: Some(8),
+        };
+        writer.write_all(b"12345").await.unwrap();
+        assert!(writer.write_all(b"6789").await.is_err());
+        writer.flush().await.unwrap();
+        assert_eq!(std::fs::read(spool.path()).unwrap(), b"12345");
     }
 }
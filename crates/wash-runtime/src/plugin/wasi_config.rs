@@ -16,17 +16,41 @@
 //!
 //! Components can use this plugin through the standard WASI config interface
 //! to retrieve configuration values that are set by the host environment.
+//!
+//! # Secrets
+//!
+//! Values of the `wasi:config/store` interface config may reference
+//! environment variables and secrets, e.g. `${secret:vault:db#password}`,
+//! resolved from the store given to [`WasiConfig::with_secrets`] when the
+//! component is bound, see [`crate::host::secrets`]. Resolved values are only
+//! kept by the plugin, so they never appear in the workload.
+//!
+//! # Overrides and updates
+//!
+//! Values given to [`WasiConfig::with_overrides`] are set for every component
+//! and take precedence over its own configuration, e.g. to point every
+//! component of a host at a regional endpoint.
+//!
+//! The configuration of a running component is changed with
+//! [`HostApi::workload_config_update`](crate::host::HostApi::workload_config_update).
+//! Every invocation runs on a fresh instance, so it reads the new values right
+//! away. The instance of a service is replaced unless its `config_change`
+//! setting is `live`, see [`ConfigChangePolicy`](crate::engine::workload::ConfigChangePolicy).
 
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
 };
+
+use anyhow::Context as _;
 use tokio::sync::RwLock;
 use wasmtime::component::HasSelf;
 
 use crate::{
     engine::{ctx::Ctx, workload::WorkloadComponent},
+    host::interpolate::{EnvAllowlist, SecretStore, interpolate_config},
     plugin::HostPlugin,
+    types::ConfigUpdate,
     wit::{WitInterface, WitWorld},
 };
 
@@ -52,6 +76,35 @@ type ConfigMap = HashMap<Arc<str>, HashMap<String, String>>;
 pub struct WasiConfig {
     /// A map of configuration from component id to key-value pairs
     config: Arc<RwLock<ConfigMap>>,
+    /// Resolves the secrets referenced in the configuration
+    secrets: Option<Arc<dyn SecretStore>>,
+    /// Environment variables the configuration may reference
+    env: Arc<EnvAllowlist>,
+    /// Host-level values set for every component over its own configuration
+    overrides: Arc<HashMap<String, String>>,
+}
+
+impl WasiConfig {
+    /// Resolves `${secret:NAME}` references in the configuration from
+    /// `secrets`, see the [module docs](self).
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretStore>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Resolves `${env:NAME}` references in the configuration to the environment
+    /// variables allowed by `env`.
+    pub fn with_env_allowlist(mut self, env: EnvAllowlist) -> Self {
+        self.env = Arc::new(env);
+        self
+    }
+
+    /// Sets `overrides` for every component, over the configuration of its
+    /// interface and its updates, see the [module docs](self#overrides-and-updates).
+    pub fn with_overrides(mut self, overrides: HashMap<String, String>) -> Self {
+        self.overrides = Arc::new(overrides);
+        self
+    }
 }
 
 impl Host for Ctx {
@@ -119,12 +172,74 @@ impl HostPlugin for WasiConfig {
             |ctx| ctx,
         )?;
 
-        // Store the configuration for lookups later
+        // Store the configuration for lookups later, with its references resolved
+        let mut config = interpolate_config(&interface.config, &self.env, self.secrets.as_deref())
+            .await
+            .context("invalid wasi:config/store configuration")?;
+        config.extend(self.overrides.as_ref().clone());
         self.config
             .write()
             .await
-            .insert(Arc::from(component_handle.id()), interface.config.clone());
+            .insert(Arc::from(component_handle.id()), config);
 
         Ok(())
     }
+
+    async fn on_config_update(
+        &self,
+        component_id: &str,
+        update: &ConfigUpdate,
+    ) -> anyhow::Result<bool> {
+        let set = interpolate_config(&update.set, &self.env, self.secrets.as_deref())
+            .await
+            .context("invalid wasi:config/store configuration")?;
+        let mut config = self.config.write().await;
+        let Some(values) = config.get_mut(component_id) else {
+            return Ok(false);
+        };
+        for key in &update.remove {
+            values.remove(key);
+        }
+        values.extend(set);
+        values.extend(self.overrides.as_ref().clone());
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_config_update() {
+        let plugin = WasiConfig::default()
+            .with_overrides(HashMap::from([("region".to_string(), "eu".to_string())]));
+        plugin.config.write().await.insert(
+            Arc::from("component"),
+            HashMap::from([
+                ("region".to_string(), "eu".to_string()),
+                ("level".to_string(), "info".to_string()),
+                ("legacy".to_string(), "true".to_string()),
+            ]),
+        );
+
+        let update = ConfigUpdate {
+            set: HashMap::from([
+                ("level".to_string(), "debug".to_string()),
+                ("region".to_string(), "us".to_string()),
+            ]),
+            remove: vec!["legacy".to_string()],
+        };
+        assert!(plugin.on_config_update("component", &update).await.unwrap());
+        assert!(!plugin.on_config_update("other", &update).await.unwrap());
+
+        let config = plugin.config.read().await;
+        assert_eq!(
+            config["component"],
+            HashMap::from([
+                ("region".to_string(), "eu".to_string()),
+                ("level".to_string(), "debug".to_string()),
+            ])
+        );
+    }
 }
@@ -1,11 +1,14 @@
-//! # WASI KeyValue Memory Plugin
+//! # WASI KeyValue Plugin
 //!
-//! This module implements an in-memory keyvalue plugin for the wasmCloud runtime,
-//! providing the `wasi:keyvalue@0.2.0-draft` interfaces for development and testing scenarios.
+//! This module implements the `wasi:keyvalue@0.2.0-draft` interfaces for the
+//! wasmCloud runtime. Buckets are kept in memory by default, or in a Redis
+//! server, picked per workload by the config of its `wasi:keyvalue`
+//! interface, and belong to the namespace of the workload, see
+//! [`keyvalue`](crate::plugin::keyvalue).
 
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    sync::{Arc, Mutex, PoisonError},
 };
 
 const WASI_KEYVALUE_ID: &str = "wasi-keyvalue";
@@ -14,10 +17,18 @@ use wasmtime::component::{HasSelf, Resource};
 
 use crate::{
     engine::{ctx::Ctx, workload::WorkloadComponent},
-    plugin::HostPlugin,
-    wit::{WitInterface, WitWorld},
+    plugin::{
+        HostPlugin,
+        keyvalue::{
+            KeyvalueBackend, KeyvalueCapabilities, KeyvalueStore, bucket_name, memory::MemoryStore,
+            redis::RedisStore,
+        },
+    },
+    wit::WitWorld,
 };
 
+pub use crate::plugin::keyvalue::memory::BucketData;
+
 mod bindings {
     wasmtime::component::bindgen!({
         world: "keyvalue",
@@ -30,36 +41,113 @@ mod bindings {
 
 use bindings::wasi::keyvalue::store::{Error as StoreError, KeyResponse};
 
-/// In-memory bucket representation
-#[derive(Clone, Debug)]
-pub struct BucketData {
-    pub name: String,
-    pub data: HashMap<String, Vec<u8>>,
-    pub created_at: u64,
+/// Resource representation for a bucket (key-value store)
+pub struct BucketHandle {
+    store: Arc<dyn KeyvalueStore>,
+    /// The name of the bucket in the store, see [`bucket_name`]
+    name: String,
 }
 
-/// Resource representation for a bucket (key-value store)
-pub type BucketHandle = String;
+/// The store of the buckets of a workload and its namespace
+#[derive(Clone)]
+struct WorkloadStore {
+    store: Arc<dyn KeyvalueStore>,
+    namespace: String,
+}
 
-/// Memory-based keyvalue plugin
+/// Keyvalue plugin storing buckets in memory or in Redis
 #[derive(Clone, Default)]
 pub struct WasiKeyvalue {
-    /// Storage for all buckets, keyed by workload ID, then bucket name
-    storage: Arc<RwLock<HashMap<String, HashMap<String, BucketData>>>>,
+    memory: Arc<MemoryStore>,
+    /// Redis stores by URL, shared by the workloads using the same server
+    redis: Arc<Mutex<HashMap<String, Arc<RedisStore>>>>,
+    /// The store of each bound workload, by workload ID
+    workloads: Arc<RwLock<HashMap<String, WorkloadStore>>>,
 }
 
 impl WasiKeyvalue {
     pub fn new() -> Self {
-        Self {
-            storage: Arc::new(RwLock::new(HashMap::new())),
+        Self::default()
+    }
+
+    /// Returns the optional keyvalue features supported by every backend
+    /// workloads may pick.
+    pub fn capabilities(&self) -> KeyvalueCapabilities {
+        KeyvalueCapabilities {
+            compare_and_swap: false,
+            ..self.memory.capabilities()
+        }
+    }
+
+    /// Returns the in-memory store, which keeps the buckets of workloads
+    /// without a backend configured.
+    pub fn memory(&self) -> &Arc<MemoryStore> {
+        &self.memory
+    }
+
+    /// Atomically replaces the value of `key` with `value` if its current value
+    /// is `expected`, where `None` means the key does not exist.
+    ///
+    /// Returns whether the value was swapped.
+    ///
+    /// # Errors
+    /// Returns an error if the workload is not bound to the plugin, or its
+    /// backend does not support compare-and-swap.
+    pub async fn compare_and_swap(
+        &self,
+        workload_id: &str,
+        bucket: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        let workload = self
+            .workloads
+            .read()
+            .await
+            .get(workload_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("workload '{workload_id}' has no keyvalue store"))?;
+        workload
+            .store
+            .compare_and_swap(
+                &bucket_name(&workload.namespace, bucket),
+                key,
+                expected,
+                value,
+            )
+            .await
+    }
+
+    /// Returns the store of `backend`, connecting to Redis servers once per URL.
+    fn store(&self, backend: KeyvalueBackend) -> anyhow::Result<Arc<dyn KeyvalueStore>> {
+        match backend {
+            KeyvalueBackend::Memory => Ok(self.memory.clone()),
+            KeyvalueBackend::Redis { url } => {
+                let mut redis = self.redis.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(store) = redis.get(&url) {
+                    return Ok(store.clone());
+                }
+                let store = Arc::new(RedisStore::new(&url)?);
+                redis.insert(url, store.clone());
+                Ok(store)
+            }
         }
     }
+}
 
-    fn get_timestamp() -> u64 {
-        std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+fn store_error(e: anyhow::Error) -> StoreError {
+    StoreError::Other(format!("{e:#}"))
+}
+
+impl Ctx {
+    /// Returns the store and name of a bucket resource.
+    fn keyvalue_bucket(
+        &self,
+        bucket: &Resource<BucketHandle>,
+    ) -> anyhow::Result<(Arc<dyn KeyvalueStore>, String)> {
+        let bucket = self.table.get(bucket)?;
+        Ok((bucket.store.clone(), bucket.name.clone()))
     }
 }
 
@@ -74,21 +162,22 @@ impl bindings::wasi::keyvalue::store::Host for Ctx {
                 "keyvalue plugin not available".to_string(),
             )));
         };
+        let Some(workload) = plugin
+            .workloads
+            .read()
+            .await
+            .get(self.workload_id.as_ref())
+            .cloned()
+        else {
+            return Ok(Err(StoreError::Other(
+                "workload has no keyvalue store".to_string(),
+            )));
+        };
 
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        // Create bucket if it doesn't exist
-        if !workload_storage.contains_key(&identifier) {
-            let bucket_data = BucketData {
-                name: identifier.clone(),
-                data: HashMap::new(),
-                created_at: WasiKeyvalue::get_timestamp(),
-            };
-            workload_storage.insert(identifier.clone(), bucket_data);
-        }
-
-        let resource = self.table.push(identifier)?;
+        let resource = self.table.push(BucketHandle {
+            store: workload.store,
+            name: bucket_name(&workload.namespace, &identifier),
+        })?;
         Ok(Ok(resource))
     }
 }
@@ -100,27 +189,8 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
         bucket: Resource<BucketHandle>,
         key: String,
     ) -> anyhow::Result<Result<Option<Vec<u8>>, StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(bucket_name) {
-            Some(bucket_data) => {
-                let value = bucket_data.data.get(&key).cloned();
-                Ok(Ok(value))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.get(&name, &key).await.map_err(store_error))
     }
 
     async fn set(
@@ -129,26 +199,8 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
         key: String,
         value: Vec<u8>,
     ) -> anyhow::Result<Result<(), StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(bucket_name) {
-            Some(bucket_data) => {
-                bucket_data.data.insert(key, value);
-                Ok(Ok(()))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.set(&name, &key, value).await.map_err(store_error))
     }
 
     async fn delete(
@@ -156,26 +208,8 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
         bucket: Resource<BucketHandle>,
         key: String,
     ) -> anyhow::Result<Result<(), StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(bucket_name) {
-            Some(bucket_data) => {
-                bucket_data.data.remove(&key);
-                Ok(Ok(()))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.delete(&name, &key).await.map_err(store_error))
     }
 
     async fn exists(
@@ -183,24 +217,8 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
         bucket: Resource<BucketHandle>,
         key: String,
     ) -> anyhow::Result<Result<bool, StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(bucket_name) {
-            Some(bucket_data) => Ok(Ok(bucket_data.data.contains_key(&key))),
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.exists(&name, &key).await.map_err(store_error))
     }
 
     async fn list_keys(
@@ -208,47 +226,12 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
         bucket: Resource<BucketHandle>,
         cursor: Option<u64>,
     ) -> anyhow::Result<Result<KeyResponse, StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(bucket_name) {
-            Some(bucket_data) => {
-                let mut keys: Vec<String> = bucket_data.data.keys().cloned().collect();
-                keys.sort(); // Ensure consistent ordering
-
-                // Simple cursor-based pagination - cursor is the index from previous page
-                let start_index = cursor.unwrap_or(0) as usize;
-
-                // Return up to 100 keys per page
-                const PAGE_SIZE: usize = 100;
-                let end_index = std::cmp::min(start_index + PAGE_SIZE, keys.len());
-                let page_keys = keys[start_index..end_index].to_vec();
-
-                // Set next cursor if there are more keys
-                let next_cursor = if end_index < keys.len() {
-                    Some(end_index as u64)
-                } else {
-                    None
-                };
-
-                Ok(Ok(KeyResponse {
-                    keys: page_keys,
-                    cursor: next_cursor,
-                }))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store
+            .list_keys(&name, cursor)
+            .await
+            .map(|(keys, cursor)| KeyResponse { keys, cursor })
+            .map_err(store_error))
     }
 
     async fn drop(&mut self, rep: Resource<BucketHandle>) -> anyhow::Result<()> {
@@ -270,46 +253,11 @@ impl bindings::wasi::keyvalue::atomics::Host for Ctx {
         key: String,
         delta: u64,
     ) -> anyhow::Result<Result<u64, StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(bucket_name) {
-            Some(bucket_data) => {
-                // Get current value, treating missing key as 0
-                let current_bytes = bucket_data.data.get(&key);
-                let current_value = if let Some(bytes) = current_bytes {
-                    // Try to parse as u64 from 8-byte array
-                    if bytes.len() == 8 {
-                        u64::from_le_bytes(bytes.clone().try_into().unwrap_or([0; 8]))
-                    } else {
-                        // Try to parse as string representation
-                        String::from_utf8_lossy(bytes).parse::<u64>().unwrap_or(0)
-                    }
-                } else {
-                    0
-                };
-
-                let new_value = current_value.saturating_add(delta);
-
-                // Store as 8-byte little-endian representation
-                bucket_data
-                    .data
-                    .insert(key, new_value.to_le_bytes().to_vec());
-
-                Ok(Ok(new_value))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store
+            .increment(&name, &key, delta)
+            .await
+            .map_err(store_error))
     }
 }
 
@@ -320,36 +268,8 @@ impl bindings::wasi::keyvalue::batch::Host for Ctx {
         bucket: Resource<BucketHandle>,
         keys: Vec<String>,
     ) -> anyhow::Result<Result<Vec<Option<(String, Vec<u8>)>>, StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let storage = plugin.storage.read().await;
-        let empty_map = HashMap::new();
-        let workload_storage = storage.get(&self.id).unwrap_or(&empty_map);
-
-        match workload_storage.get(bucket_name) {
-            Some(bucket_data) => {
-                let results: Vec<Option<(String, Vec<u8>)>> = keys
-                    .into_iter()
-                    .map(|key| {
-                        bucket_data
-                            .data
-                            .get(&key)
-                            .cloned()
-                            .map(|value| (key, value))
-                    })
-                    .collect();
-                Ok(Ok(results))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.get_many(&name, keys).await.map_err(store_error))
     }
 
     async fn set_many(
@@ -357,28 +277,8 @@ impl bindings::wasi::keyvalue::batch::Host for Ctx {
         bucket: Resource<BucketHandle>,
         key_values: Vec<(String, Vec<u8>)>,
     ) -> anyhow::Result<Result<(), StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(bucket_name) {
-            Some(bucket_data) => {
-                for (key, value) in key_values {
-                    bucket_data.data.insert(key, value);
-                }
-                Ok(Ok(()))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.set_many(&name, key_values).await.map_err(store_error))
     }
 
     async fn delete_many(
@@ -386,28 +286,8 @@ impl bindings::wasi::keyvalue::batch::Host for Ctx {
         bucket: Resource<BucketHandle>,
         keys: Vec<String>,
     ) -> anyhow::Result<Result<(), StoreError>> {
-        let bucket_name = self.table.get(&bucket)?;
-
-        let Some(plugin) = self.get_plugin::<WasiKeyvalue>(WASI_KEYVALUE_ID) else {
-            return Ok(Err(StoreError::Other(
-                "keyvalue plugin not available".to_string(),
-            )));
-        };
-
-        let mut storage = plugin.storage.write().await;
-        let workload_storage = storage.entry(self.id.clone()).or_default();
-
-        match workload_storage.get_mut(bucket_name) {
-            Some(bucket_data) => {
-                for key in keys {
-                    bucket_data.data.remove(&key);
-                }
-                Ok(Ok(()))
-            }
-            None => Ok(Err(StoreError::Other(format!(
-                "bucket '{bucket_name}' does not exist"
-            )))),
-        }
+        let (store, name) = self.keyvalue_bucket(&bucket)?;
+        Ok(store.delete_many(&name, keys).await.map_err(store_error))
     }
 }
 
@@ -419,9 +299,7 @@ impl HostPlugin for WasiKeyvalue {
 
     fn world(&self) -> WitWorld {
         WitWorld {
-            imports: HashSet::from([WitInterface::from(
-                "wasi:keyvalue/store,atomics,batch@0.2.0-draft",
-            )]),
+            imports: HashSet::from([self.capabilities().interface(Some("0.2.0-draft"))]),
             ..Default::default()
         }
     }
@@ -432,27 +310,33 @@ impl HostPlugin for WasiKeyvalue {
         interfaces: std::collections::HashSet<crate::wit::WitInterface>,
     ) -> anyhow::Result<()> {
         // Check if any of the interfaces are wasi:keyvalue related
-        let has_keyvalue = interfaces
+        let Some(interface) = interfaces
             .iter()
-            .any(|i| i.namespace == "wasi" && i.package == "keyvalue");
-
-        if !has_keyvalue {
+            .find(|i| i.namespace == "wasi" && i.package == "keyvalue")
+        else {
             tracing::warn!(
                 "WasiKeyvalue plugin requested for non-wasi:keyvalue interface(s): {:?}",
                 interfaces
             );
             return Ok(());
-        }
+        };
+        let backend = KeyvalueBackend::from_config(&interface.config)?;
+        let store = self.store(backend)?;
 
         tracing::debug!(
             workload_id = component.id(),
             "Adding keyvalue interfaces to linker for workload"
         );
+        let capabilities = store.capabilities();
         let linker = component.linker();
 
         bindings::wasi::keyvalue::store::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
-        bindings::wasi::keyvalue::atomics::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
-        bindings::wasi::keyvalue::batch::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        if capabilities.atomics {
+            bindings::wasi::keyvalue::atomics::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        }
+        if capabilities.batch {
+            bindings::wasi::keyvalue::batch::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        }
 
         let id = component.id();
         tracing::debug!(
@@ -460,11 +344,16 @@ impl HostPlugin for WasiKeyvalue {
             "Successfully added keyvalue interfaces to linker for workload"
         );
 
-        // Initialize storage for this workload
-        let mut storage = self.storage.write().await;
-        storage.insert(id.to_string(), HashMap::new());
+        // Components of a workload share its store
+        self.workloads.write().await.insert(
+            component.workload_id().to_string(),
+            WorkloadStore {
+                store,
+                namespace: component.workload_namespace().to_string(),
+            },
+        );
 
-        tracing::debug!("WasiKeyvalue plugin bound to workload '{id}'");
+        tracing::debug!("WasiKeyvalue plugin bound to component '{id}'");
 
         Ok(())
     }
@@ -474,9 +363,8 @@ impl HostPlugin for WasiKeyvalue {
         workload_id: &str,
         _interfaces: std::collections::HashSet<crate::wit::WitInterface>,
     ) -> anyhow::Result<()> {
-        // Clean up storage for this workload
-        let mut storage = self.storage.write().await;
-        storage.remove(workload_id);
+        // The buckets stay, they belong to the namespace of the workload
+        self.workloads.write().await.remove(workload_id);
 
         tracing::debug!("WasiKeyvalue plugin unbound from workload '{workload_id}'");
 
@@ -491,63 +379,77 @@ mod tests {
     #[test]
     fn test_wasi_keyvalue_creation() {
         let keyvalue = WasiKeyvalue::new();
-        assert!(keyvalue.storage.try_read().is_ok());
+        assert!(keyvalue.workloads.try_read().is_ok());
+        assert!(keyvalue.capabilities().atomics);
+        assert!(keyvalue.capabilities().batch);
     }
 
     #[test]
-    fn test_get_timestamp() {
-        let timestamp = WasiKeyvalue::get_timestamp();
-        assert!(timestamp > 0);
-    }
-
-    #[test]
-    fn test_bucket_data_creation() {
-        let bucket = BucketData {
-            name: "test-bucket".to_string(),
-            data: HashMap::new(),
-            created_at: WasiKeyvalue::get_timestamp(),
+    fn test_redis_stores_shared_per_url() {
+        let keyvalue = WasiKeyvalue::new();
+        let redis = |url: &str| {
+            keyvalue.store(KeyvalueBackend::Redis {
+                url: url.to_string(),
+            })
         };
-
-        assert_eq!(bucket.name, "test-bucket");
-        assert!(bucket.data.is_empty());
-        assert!(bucket.created_at > 0);
+        let first = redis("redis://cache:6379").unwrap();
+        let second = redis("redis://cache:6379").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(
+            !redis("redis://cache:6379")
+                .unwrap()
+                .capabilities()
+                .compare_and_swap
+        );
+        assert!(redis("memcached://cache").is_err());
     }
 
     #[tokio::test]
-    async fn test_storage_operations() {
+    async fn test_buckets_namespaced() {
         let keyvalue = WasiKeyvalue::new();
+        let bind = |workload_id: &str, namespace: &str| {
+            let keyvalue = keyvalue.clone();
+            let (workload_id, namespace) = (workload_id.to_string(), namespace.to_string());
+            async move {
+                keyvalue.workloads.write().await.insert(
+                    workload_id,
+                    WorkloadStore {
+                        store: keyvalue.memory.clone(),
+                        namespace,
+                    },
+                );
+            }
+        };
+        bind("w1", "team-a").await;
+        bind("w2", "team-b").await;
 
-        // Test write access
-        {
-            let mut storage = keyvalue.storage.write().await;
-            storage.insert("workload1".to_string(), HashMap::new());
-        }
-
-        // Test read access
-        {
-            let storage = keyvalue.storage.read().await;
-            assert!(storage.contains_key("workload1"));
-        }
-    }
+        let cas =
+            |workload_id: &'static str, expected: Option<&'static [u8]>, value: &'static [u8]| {
+                keyvalue.compare_and_swap(workload_id, "bucket", "key", expected, value.to_vec())
+            };
+        assert!(cas("w1", None, b"v1").await.unwrap());
+        assert!(!cas("w1", None, b"v2").await.unwrap());
+        assert!(!cas("w1", Some(b"other"), b"v2").await.unwrap());
+        assert!(cas("w1", Some(b"v1"), b"v2").await.unwrap());
+
+        // Another namespace has a bucket of the same name of its own
+        assert!(cas("w2", None, b"b1").await.unwrap());
+        let memory = keyvalue.memory();
+        assert_eq!(
+            memory.bucket("team-a/bucket").await.unwrap().data["key"],
+            b"v2"
+        );
+        assert_eq!(
+            memory.bucket("team-b/bucket").await.unwrap().data["key"],
+            b"b1"
+        );
 
-    #[test]
-    fn test_batch_operations_data_structures() {
-        // Test that we can create the data structures for batch operations
-        let key_values = [
-            ("key1".to_string(), b"value1".to_vec()),
-            ("key2".to_string(), b"value2".to_vec()),
-        ];
-        assert_eq!(key_values.len(), 2);
-
-        let keys = ["key1".to_string(), "key2".to_string()];
-        assert_eq!(keys.len(), 2);
-
-        let results: Vec<Option<(String, Vec<u8>)>> = vec![
-            Some(("key1".to_string(), b"value1".to_vec())),
-            None, // key not found
-        ];
-        assert_eq!(results.len(), 2);
-        assert!(results[0].is_some());
-        assert!(results[1].is_none());
+        // Unbound workloads have no store
+        assert!(
+            keyvalue
+                .compare_and_swap("w3", "bucket", "key", None, vec![])
+                .await
+                .is_err()
+        );
     }
 }
@@ -2,7 +2,8 @@
 //!
 //! This plugin implements the `wasi:logging/logging@0.1.0-draft` interface,
 //! providing components with structured logging capabilities. It bridges
-//! component log messages to the host's tracing infrastructure.
+//! component log messages to the host's log pipeline, see
+//! [`crate::host::logs`].
 //!
 //! # Features
 //!
@@ -34,6 +35,7 @@ const WASI_LOGGING_ID: &str = "wasi-logging";
 
 use crate::{
     engine::{ctx::Ctx, workload::WorkloadComponent},
+    host::logs::LogLevel,
     plugin::{HostPlugin, wasi_logging::bindings::wasi::logging::logging::Level},
     wit::{WitInterface, WitWorld},
 };
@@ -54,14 +56,15 @@ pub struct WasiLogging;
 
 impl bindings::wasi::logging::logging::Host for Ctx {
     async fn log(&mut self, level: Level, context: String, message: String) -> anyhow::Result<()> {
-        match level {
-            Level::Critical => tracing::error!(id = &self.id, context, "{message}"),
-            Level::Error => tracing::error!(id = &self.id, context, "{message}"),
-            Level::Warn => tracing::warn!(id = &self.id, context, "{message}"),
-            Level::Info => tracing::info!(id = &self.id, context, "{message}"),
-            Level::Debug => tracing::debug!(id = &self.id, context, "{message}"),
-            Level::Trace => tracing::trace!(id = &self.id, context, "{message}"),
-        }
+        let level = match level {
+            Level::Trace => LogLevel::Trace,
+            Level::Debug => LogLevel::Debug,
+            Level::Info => LogLevel::Info,
+            Level::Warn => LogLevel::Warn,
+            Level::Error => LogLevel::Error,
+            Level::Critical => LogLevel::Critical,
+        };
+        self.guest_log(level, context, message);
         Ok(())
     }
 }
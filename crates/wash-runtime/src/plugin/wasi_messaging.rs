@@ -0,0 +1,840 @@
+//! # WASI Messaging Plugin
+//!
+//! This module implements the `wasi:messaging@0.2.0-draft` interfaces for the
+//! wasmCloud runtime, backed by NATS. Components publish messages with
+//! `producer`, send requests and reply to messages with `request-reply`, and
+//! receive the messages of their subscriptions through their
+//! `incoming-handler` export.
+//!
+//! The plugin is configured per workload by the config of its
+//! `wasi:messaging` interface:
+//!
+//! - `subscriptions`: comma-separated subjects delivered to the handler of the
+//!   component, wildcards included
+//! - `queue_group`: subscribes in a queue group, so each message is handled by
+//!   one of the subscribed components instead of all of them
+//! - `url`: the NATS server of the workload, instead of the one the plugin was
+//!   created with
+//! - `credentials` (the contents of a `.creds` file), `credentials_file`,
+//!   `token`, `user` and `password`, or `nkey`: how to authenticate to `url`
+//!
+//! Workloads with the same server and credentials share a connection.
+//! Incoming messages invoke the component like HTTP requests do, within the
+//! invocation limits and on the execution pool of the component.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, bail};
+use futures::stream::StreamExt;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+use wasmtime::component::{HasSelf, Resource};
+
+use crate::engine::ctx::Ctx;
+use crate::engine::workload::{ResolvedWorkload, WorkloadComponent};
+use crate::plugin::{HostPlugin, PluginRole};
+use crate::wit::{WitInterface, WitWorld};
+
+const WASI_MESSAGING_ID: &str = "wasi-messaging";
+/// How long a request waits for replies unless its options say otherwise
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "wasi-messaging",
+        imports: { default: async | trappable },
+        exports: { default: async },
+        with: {
+            "wasi:messaging/types/client": crate::plugin::wasi_messaging::Client,
+            "wasi:messaging/types/message": crate::plugin::wasi_messaging::Message,
+            "wasi:messaging/request-reply/request-options": crate::plugin::wasi_messaging::RequestOptions,
+        },
+    });
+}
+
+use bindings::wasi::messaging::types::Error;
+
+/// Resource representation of a client, the NATS connection of the workload
+pub struct Client {
+    nats: Arc<async_nats::Client>,
+}
+
+/// Resource representation of a message sent or received by a component
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    topic: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+    metadata: Vec<(String, String)>,
+    /// The subject replies to a received message are published to
+    reply_to: Option<String>,
+}
+
+impl Message {
+    /// Returns the NATS headers of the message: its metadata and content type.
+    fn headers(&self) -> async_nats::HeaderMap {
+        let mut headers = async_nats::HeaderMap::new();
+        for (key, value) in &self.metadata {
+            headers.append(key.as_str(), value.as_str());
+        }
+        if let Some(content_type) = &self.content_type {
+            headers.insert(CONTENT_TYPE_HEADER, content_type.as_str());
+        }
+        headers
+    }
+}
+
+impl From<async_nats::Message> for Message {
+    fn from(msg: async_nats::Message) -> Self {
+        let mut message = Message {
+            topic: Some(msg.subject.to_string()),
+            data: msg.payload.to_vec(),
+            reply_to: msg.reply.map(|reply| reply.to_string()),
+            ..Default::default()
+        };
+        for (name, values) in msg.headers.iter().flat_map(|headers| headers.iter()) {
+            let name = name.to_string();
+            for value in values {
+                if name.eq_ignore_ascii_case(CONTENT_TYPE_HEADER) {
+                    message.content_type = Some(value.as_str().to_string());
+                } else {
+                    message
+                        .metadata
+                        .push((name.clone(), value.as_str().to_string()));
+                }
+            }
+        }
+        message
+    }
+}
+
+/// Resource representation of the options of a request
+pub struct RequestOptions {
+    timeout: Duration,
+    expected_replies: u32,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            expected_replies: 1,
+        }
+    }
+}
+
+/// The NATS server of a workload and how to authenticate to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct NatsConnection {
+    url: String,
+    credentials: Option<String>,
+    credentials_file: Option<String>,
+    token: Option<String>,
+    user_and_password: Option<(String, String)>,
+    nkey: Option<String>,
+}
+
+impl NatsConnection {
+    async fn connect(&self) -> anyhow::Result<async_nats::Client> {
+        let mut options = async_nats::ConnectOptions::new();
+        if let Some(credentials) = &self.credentials {
+            options = options
+                .credentials(credentials)
+                .context("invalid NATS credentials")?;
+        }
+        if let Some(path) = &self.credentials_file {
+            options = options
+                .credentials_file(path)
+                .await
+                .with_context(|| format!("failed to read NATS credentials from {path}"))?;
+        }
+        if let Some(token) = &self.token {
+            options = options.token(token.clone());
+        }
+        if let Some((user, password)) = &self.user_and_password {
+            options = options.user_and_password(user.clone(), password.clone());
+        }
+        if let Some(nkey) = &self.nkey {
+            options = options.nkey(nkey.clone());
+        }
+        options
+            .connect(self.url.as_str())
+            .await
+            .with_context(|| format!("failed to connect to NATS at {}", self.url))
+    }
+}
+
+/// The config of the `wasi:messaging` interface of a workload
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MessagingConfig {
+    /// The server of the workload, or `None` for the one of the plugin
+    connection: Option<NatsConnection>,
+    subscriptions: Vec<String>,
+    queue_group: Option<String>,
+}
+
+impl MessagingConfig {
+    fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let get = |key: &str| {
+            config
+                .get(key)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let user_and_password = match (get("user"), get("password")) {
+            (Some(user), Some(password)) => Some((user, password)),
+            (None, None) => None,
+            _ => bail!("NATS user and password must be set together"),
+        };
+        let mut connection = NatsConnection {
+            url: String::new(),
+            credentials: get("credentials"),
+            credentials_file: get("credentials_file"),
+            token: get("token"),
+            user_and_password,
+            nkey: get("nkey"),
+        };
+        let connection = match get("url") {
+            Some(url) => {
+                connection.url = url;
+                Some(connection)
+            }
+            None if connection != NatsConnection::default() => {
+                bail!("NATS credentials are only used with the url of a server")
+            }
+            None => None,
+        };
+        Ok(Self {
+            connection,
+            subscriptions: get("subscriptions")
+                .map(|subjects| {
+                    subjects
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|subject| !subject.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            queue_group: get("queue_group"),
+        })
+    }
+}
+
+/// What a bound component subscribed to
+struct ComponentData {
+    workload_id: String,
+    nats: Arc<async_nats::Client>,
+    subscriptions: Vec<String>,
+    queue_group: Option<String>,
+    cancel_token: tokio_util::sync::CancellationToken,
+}
+
+/// Messaging plugin backed by NATS
+#[derive(Clone)]
+pub struct WasiMessaging {
+    /// The connection of workloads without a server of their own
+    client: Arc<async_nats::Client>,
+    /// Connections to the servers of workloads, shared by the workloads using
+    /// the same server and credentials
+    connections: Arc<RwLock<HashMap<NatsConnection, Arc<async_nats::Client>>>>,
+    /// The connection of each bound workload, by workload ID
+    workloads: Arc<RwLock<HashMap<String, Arc<async_nats::Client>>>>,
+    /// The components with an incoming handler, by component ID
+    components: Arc<RwLock<HashMap<String, ComponentData>>>,
+}
+
+impl WasiMessaging {
+    /// Creates the plugin, connecting workloads without a `url` of their own
+    /// through `client`.
+    pub fn new(client: Arc<async_nats::Client>) -> Self {
+        Self {
+            client,
+            connections: Arc::default(),
+            workloads: Arc::default(),
+            components: Arc::default(),
+        }
+    }
+
+    /// Returns the connection to `connection`, connecting once per server and
+    /// credentials.
+    async fn connection(
+        &self,
+        connection: Option<NatsConnection>,
+    ) -> anyhow::Result<Arc<async_nats::Client>> {
+        let Some(connection) = connection else {
+            return Ok(self.client.clone());
+        };
+        if let Some(client) = self.connections.read().await.get(&connection) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(connection.connect().await?);
+        Ok(self
+            .connections
+            .write()
+            .await
+            .entry(connection)
+            .or_insert(client)
+            .clone())
+    }
+
+    /// Stops delivering messages to the components of `workload_id`, or of
+    /// every workload if `None`.
+    async fn cancel_subscriptions(&self, workload_id: Option<&str>) {
+        for data in self.components.read().await.values() {
+            if workload_id.is_none_or(|id| id == data.workload_id) {
+                data.cancel_token.cancel();
+            }
+        }
+    }
+}
+
+fn connection_error(e: impl std::fmt::Display) -> Error {
+    Error::Connection(e.to_string())
+}
+
+/// Sends `message` to `topic` and collects up to `expected_replies` replies
+/// until `timeout`.
+async fn request(
+    nats: &async_nats::Client,
+    topic: String,
+    message: &Message,
+    options: &RequestOptions,
+) -> Result<Vec<Message>, Error> {
+    let inbox = nats.new_inbox();
+    let mut replies = nats
+        .subscribe(inbox.clone())
+        .await
+        .map_err(connection_error)?;
+    nats.publish_with_reply_and_headers(
+        topic,
+        inbox,
+        message.headers(),
+        message.data.clone().into(),
+    )
+    .await
+    .map_err(connection_error)?;
+
+    let mut received = Vec::new();
+    let expected = options.expected_replies.max(1) as usize;
+    let _ = tokio::time::timeout(options.timeout, async {
+        while received.len() < expected {
+            match replies.next().await {
+                Some(reply) => received.push(Message::from(reply)),
+                None => break,
+            }
+        }
+    })
+    .await;
+    if received.is_empty() {
+        return Err(Error::Timeout);
+    }
+    Ok(received)
+}
+
+/// Delivers a message to a new instance of a component, on its execution pool.
+async fn handle_message(
+    workload: &ResolvedWorkload,
+    pre: &bindings::WasiMessagingPre<Ctx>,
+    component_id: &str,
+    message: Message,
+) -> anyhow::Result<()> {
+    let (handle, pre, id) = (workload.clone(), pre.clone(), component_id.to_string());
+    workload
+        .run_invocation(component_id, async move {
+            let mut store = handle
+                .new_store(&id)
+                .await
+                .context("failed to create store")?;
+            let message = store.data_mut().table.push(message)?;
+            let proxy = pre
+                .instantiate_async(&mut store)
+                .await
+                .context("failed to instantiate component")?;
+            proxy
+                .wasi_messaging_incoming_handler()
+                .call_handle(&mut store, message)
+                .await
+                .map_err(crate::engine::explain_trap)?
+                .map_err(|e| anyhow::anyhow!("handler returned an error: {e:?}"))
+        })
+        .await
+}
+
+impl Ctx {
+    /// Returns the connection of a client resource.
+    fn messaging_client(
+        &self,
+        client: &Resource<Client>,
+    ) -> anyhow::Result<Arc<async_nats::Client>> {
+        Ok(self.table.get(client)?.nats.clone())
+    }
+}
+
+impl bindings::wasi::messaging::types::Host for Ctx {}
+
+impl bindings::wasi::messaging::types::HostClient for Ctx {
+    /// Connects to the server of the workload. All clients of a workload
+    /// share its connection, whatever their `name`.
+    async fn connect(&mut self, name: String) -> anyhow::Result<Result<Resource<Client>, Error>> {
+        let Some(plugin) = self.get_plugin::<WasiMessaging>(WASI_MESSAGING_ID) else {
+            return Ok(Err(Error::Other(
+                "messaging plugin not available".to_string(),
+            )));
+        };
+        let Some(nats) = plugin
+            .workloads
+            .read()
+            .await
+            .get(self.workload_id.as_ref())
+            .cloned()
+        else {
+            return Ok(Err(Error::Connection(
+                "workload has no messaging connection".to_string(),
+            )));
+        };
+        debug!(workload_id = %self.workload_id, name, "opening messaging client");
+        Ok(Ok(self.table.push(Client { nats })?))
+    }
+
+    async fn disconnect(&mut self, _client: Resource<Client>) -> anyhow::Result<Result<(), Error>> {
+        // The connection is shared by the workload and closed by the plugin
+        Ok(Ok(()))
+    }
+
+    async fn drop(&mut self, rep: Resource<Client>) -> anyhow::Result<()> {
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
+
+impl bindings::wasi::messaging::types::HostMessage for Ctx {
+    async fn new(&mut self, data: Vec<u8>) -> anyhow::Result<Resource<Message>> {
+        Ok(self.table.push(Message {
+            data,
+            ..Default::default()
+        })?)
+    }
+
+    async fn topic(&mut self, message: Resource<Message>) -> anyhow::Result<Option<String>> {
+        Ok(self.table.get(&message)?.topic.clone())
+    }
+
+    async fn content_type(&mut self, message: Resource<Message>) -> anyhow::Result<Option<String>> {
+        Ok(self.table.get(&message)?.content_type.clone())
+    }
+
+    async fn set_content_type(
+        &mut self,
+        message: Resource<Message>,
+        content_type: String,
+    ) -> anyhow::Result<()> {
+        self.table.get_mut(&message)?.content_type = Some(content_type);
+        Ok(())
+    }
+
+    async fn data(&mut self, message: Resource<Message>) -> anyhow::Result<Vec<u8>> {
+        Ok(self.table.get(&message)?.data.clone())
+    }
+
+    async fn set_data(&mut self, message: Resource<Message>, buf: Vec<u8>) -> anyhow::Result<()> {
+        self.table.get_mut(&message)?.data = buf;
+        Ok(())
+    }
+
+    async fn metadata(
+        &mut self,
+        message: Resource<Message>,
+    ) -> anyhow::Result<Option<Vec<(String, String)>>> {
+        let metadata = &self.table.get(&message)?.metadata;
+        Ok((!metadata.is_empty()).then(|| metadata.clone()))
+    }
+
+    async fn add_metadata(
+        &mut self,
+        message: Resource<Message>,
+        key: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let metadata = &mut self.table.get_mut(&message)?.metadata;
+        metadata.retain(|(k, _)| *k != key);
+        metadata.push((key, value));
+        Ok(())
+    }
+
+    async fn set_metadata(
+        &mut self,
+        message: Resource<Message>,
+        meta: Vec<(String, String)>,
+    ) -> anyhow::Result<()> {
+        self.table.get_mut(&message)?.metadata = meta;
+        Ok(())
+    }
+
+    async fn remove_metadata(
+        &mut self,
+        message: Resource<Message>,
+        key: String,
+    ) -> anyhow::Result<()> {
+        self.table
+            .get_mut(&message)?
+            .metadata
+            .retain(|(k, _)| *k != key);
+        Ok(())
+    }
+
+    async fn drop(&mut self, rep: Resource<Message>) -> anyhow::Result<()> {
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
+
+impl bindings::wasi::messaging::producer::Host for Ctx {
+    async fn send(
+        &mut self,
+        client: Resource<Client>,
+        topic: String,
+        message: Resource<Message>,
+    ) -> anyhow::Result<Result<(), Error>> {
+        let nats = self.messaging_client(&client)?;
+        let message = self.table.delete(message)?;
+        Ok(nats
+            .publish_with_headers(topic, message.headers(), message.data.into())
+            .await
+            .map_err(connection_error))
+    }
+}
+
+impl bindings::wasi::messaging::request_reply::Host for Ctx {
+    async fn request(
+        &mut self,
+        client: Resource<Client>,
+        topic: String,
+        message: Resource<Message>,
+        options: Option<Resource<RequestOptions>>,
+    ) -> anyhow::Result<Result<Vec<Resource<Message>>, Error>> {
+        let nats = self.messaging_client(&client)?;
+        let message = self.table.get(&message)?.clone();
+        let options = match options {
+            Some(options) => self.table.delete(options)?,
+            None => RequestOptions::default(),
+        };
+        let replies = match request(&nats, topic, &message, &options).await {
+            Ok(replies) => replies,
+            Err(e) => return Ok(Err(e)),
+        };
+        let replies = replies
+            .into_iter()
+            .map(|reply| self.table.push(reply))
+            .collect::<Result<_, _>>()?;
+        Ok(Ok(replies))
+    }
+
+    async fn reply(
+        &mut self,
+        reply_to: Resource<Message>,
+        message: Resource<Message>,
+    ) -> anyhow::Result<Result<(), Error>> {
+        let Some(subject) = self.table.get(&reply_to)?.reply_to.clone() else {
+            return Ok(Err(Error::Other(
+                "message has no subject to reply to".to_string(),
+            )));
+        };
+        let Some(plugin) = self.get_plugin::<WasiMessaging>(WASI_MESSAGING_ID) else {
+            return Ok(Err(Error::Other(
+                "messaging plugin not available".to_string(),
+            )));
+        };
+        let Some(nats) = plugin
+            .workloads
+            .read()
+            .await
+            .get(self.workload_id.as_ref())
+            .cloned()
+        else {
+            return Ok(Err(Error::Connection(
+                "workload has no messaging connection".to_string(),
+            )));
+        };
+        let message = self.table.delete(message)?;
+        Ok(nats
+            .publish_with_headers(subject, message.headers(), message.data.into())
+            .await
+            .map_err(connection_error))
+    }
+}
+
+impl bindings::wasi::messaging::request_reply::HostRequestOptions for Ctx {
+    async fn new(&mut self) -> anyhow::Result<Resource<RequestOptions>> {
+        Ok(self.table.push(RequestOptions::default())?)
+    }
+
+    async fn set_timeout_ms(
+        &mut self,
+        options: Resource<RequestOptions>,
+        timeout_ms: u32,
+    ) -> anyhow::Result<()> {
+        self.table.get_mut(&options)?.timeout = Duration::from_millis(timeout_ms.into());
+        Ok(())
+    }
+
+    async fn set_expected_replies(
+        &mut self,
+        options: Resource<RequestOptions>,
+        expected_replies: u32,
+    ) -> anyhow::Result<()> {
+        self.table.get_mut(&options)?.expected_replies = expected_replies;
+        Ok(())
+    }
+
+    async fn drop(&mut self, rep: Resource<RequestOptions>) -> anyhow::Result<()> {
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WasiMessaging {
+    fn id(&self) -> &'static str {
+        WASI_MESSAGING_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from(
+                "wasi:messaging/types,producer,request-reply@0.2.0-draft",
+            )]),
+            exports: HashSet::from([WitInterface::from(
+                "wasi:messaging/incoming-handler@0.2.0-draft",
+            )]),
+        }
+    }
+
+    fn role(&self) -> PluginRole {
+        PluginRole::Trigger
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wasi" && i.package == "messaging")
+        else {
+            return Ok(());
+        };
+        let config = MessagingConfig::from_config(&interface.config)?;
+        let nats = self.connection(config.connection).await?;
+
+        let linker = component.linker();
+        bindings::wasi::messaging::types::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wasi::messaging::producer::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wasi::messaging::request_reply::add_to_linker::<_, HasSelf<Ctx>>(
+            linker,
+            |ctx| ctx,
+        )?;
+
+        // Components of a workload share its connection
+        self.workloads
+            .write()
+            .await
+            .insert(component.workload_id().to_string(), nats.clone());
+
+        if interface.interfaces.contains("incoming-handler") && !config.subscriptions.is_empty() {
+            self.components.write().await.insert(
+                component.id().to_string(),
+                ComponentData {
+                    workload_id: component.workload_id().to_string(),
+                    nats,
+                    subscriptions: config.subscriptions,
+                    queue_group: config.queue_group,
+                    cancel_token: tokio_util::sync::CancellationToken::new(),
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn on_workload_resolved(
+        &self,
+        workload: &ResolvedWorkload,
+        component_id: &str,
+    ) -> anyhow::Result<()> {
+        let (nats, subjects, queue_group, cancel_token) = {
+            let components = self.components.read().await;
+            let Some(data) = components.get(component_id) else {
+                return Ok(());
+            };
+            (
+                data.nats.clone(),
+                data.subscriptions.clone(),
+                data.queue_group.clone(),
+                data.cancel_token.clone(),
+            )
+        };
+
+        let instance_pre = workload.instantiate_pre(component_id).await?;
+        let pre = bindings::WasiMessagingPre::new(instance_pre)
+            .context("failed to instantiate messaging pre")?;
+
+        let mut subscriptions = Vec::with_capacity(subjects.len());
+        for subject in subjects {
+            let subscribed = match &queue_group {
+                Some(group) => nats.queue_subscribe(subject.clone(), group.clone()).await,
+                None => nats.subscribe(subject.clone()).await,
+            };
+            subscriptions.push(
+                subscribed
+                    .map_err(anyhow::Error::new)
+                    .with_context(|| format!("failed to subscribe to {subject}"))?,
+            );
+        }
+        let mut messages = futures::stream::select_all(subscriptions);
+
+        let workload = workload.clone();
+        let component_id = component_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                let msg = tokio::select! {
+                    msg = messages.next() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = cancel_token.cancelled() => break,
+                };
+                let (workload, pre, component_id) =
+                    (workload.clone(), pre.clone(), component_id.clone());
+                // Messages are handled concurrently, up to the invocation
+                // limits of the component
+                tokio::spawn(async move {
+                    let subject = msg.subject.to_string();
+                    if let Err(e) = handle_message(&workload, &pre, &component_id, msg.into()).await
+                    {
+                        warn!(subject, "failed to handle message: {e:#}");
+                    }
+                });
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        self.cancel_subscriptions(Some(workload_id)).await;
+        self.components
+            .write()
+            .await
+            .retain(|_, data| data.workload_id != workload_id);
+        self.workloads.write().await.remove(workload_id);
+        Ok(())
+    }
+
+    async fn on_workload_stop(&self, workload_id: &str) -> anyhow::Result<()> {
+        self.cancel_subscriptions(Some(workload_id)).await;
+        Ok(())
+    }
+
+    async fn on_host_shutdown(&self) -> anyhow::Result<()> {
+        self.cancel_subscriptions(None).await;
+        Ok(())
+    }
+
+    /// Flushes messages components published before the host stops
+    async fn stop(&self) -> anyhow::Result<()> {
+        let connections = self.connections.read().await;
+        for client in std::iter::once(&self.client).chain(connections.values()) {
+            client.flush().await.context("failed to flush messages")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_messaging_config() {
+        assert_eq!(
+            MessagingConfig::from_config(&HashMap::new()).unwrap(),
+            MessagingConfig::default()
+        );
+
+        let parsed = MessagingConfig::from_config(&config(&[
+            ("subscriptions", "orders.*, invoices.>,"),
+            ("queue_group", "billing"),
+            ("url", "nats://nats.internal:4222"),
+            ("user", "billing"),
+            ("password", "secret"),
+        ]))
+        .unwrap();
+        assert_eq!(parsed.subscriptions, ["orders.*", "invoices.>"]);
+        assert_eq!(parsed.queue_group.as_deref(), Some("billing"));
+        assert_eq!(
+            parsed.connection,
+            Some(NatsConnection {
+                url: "nats://nats.internal:4222".to_string(),
+                user_and_password: Some(("billing".to_string(), "secret".to_string())),
+                ..Default::default()
+            })
+        );
+
+        // Credentials need a server and come in pairs
+        assert!(MessagingConfig::from_config(&config(&[("token", "t")])).is_err());
+        assert!(
+            MessagingConfig::from_config(&config(&[("url", "nats://nats:4222"), ("user", "u")]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_message_headers() {
+        let message = Message {
+            content_type: Some("application/json".to_string()),
+            data: b"{}".to_vec(),
+            metadata: vec![
+                ("trace".to_string(), "1".to_string()),
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+            ],
+            ..Default::default()
+        };
+        let headers = message.headers();
+        assert_eq!(
+            headers.get(CONTENT_TYPE_HEADER).map(|v| v.as_str()),
+            Some("application/json")
+        );
+
+        let received = Message::from(async_nats::Message {
+            subject: "orders.created".into(),
+            reply: Some("_INBOX.1".into()),
+            payload: message.data.clone().into(),
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        });
+        assert_eq!(received.topic.as_deref(), Some("orders.created"));
+        assert_eq!(received.reply_to.as_deref(), Some("_INBOX.1"));
+        assert_eq!(received.content_type.as_deref(), Some("application/json"));
+        assert_eq!(received.metadata.len(), 3);
+    }
+}
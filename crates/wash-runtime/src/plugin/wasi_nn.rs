@@ -0,0 +1,1054 @@
+//! # Neural Network Inference Plugin
+//!
+//! This plugin implements `wasi:nn@0.2.0-rc-2024-10-28`, letting components run
+//! inference on machine learning models on the host instead of calling a remote
+//! API. Models are run by an [`NnBackend`]: [`OnnxBackend`] runs ONNX models on
+//! the CPU with [ONNX Runtime](https://onnxruntime.ai). Other runtimes, such as
+//! candle, can be supported by implementing [`NnBackend`] and [`NnModel`].
+//!
+//! Components load the models named in the `models` config of their interface
+//! with `load-by-name`. A model is either a file in a volume mounted into the
+//! component, given by its path in the guest, or an OCI artifact holding the
+//! model as its single layer:
+//!
+//! ```text
+//! wasi:nn/graph,inference,tensor,errors@0.2.0-rc-2024-10-28 {
+//!   models: "classifier=/models/resnet50.onnx,sentiment=oci://ghcr.io/acme/sentiment:1.0",
+//!   max-model-memory-mb: "512"
+//! }
+//! ```
+//!
+//! A component can name further models with the `wasi:nn.models` entry of its
+//! own config, see [`component_config`]. Components can also pass the bytes of
+//! a model to `load`.
+//!
+//! The models a workload loaded count towards its `max-model-memory-mb` with
+//! their size, loading one beyond it fails with `too-large`. Models loaded by
+//! name are loaded once per workload and shared by its instances until the
+//! workload stops, models passed to `load` are released with their graph.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::{Context as _, bail, ensure};
+use tokio::sync::{Mutex, RwLock};
+use wasmtime::component::{HasSelf, Resource, ResourceTable};
+
+use crate::{
+    engine::{
+        ctx::Ctx,
+        volume::{VolumeRoot, validate_mount_path},
+        workload::WorkloadComponent,
+    },
+    plugin::{HostPlugin, component_config},
+    types::VolumeMount,
+    wit::{WitInterface, WitWorld},
+};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "nn",
+        imports: { default: async | trappable },
+        with: {
+            "wasi:nn/tensor/tensor": crate::plugin::wasi_nn::Tensor,
+            "wasi:nn/graph/graph": crate::plugin::wasi_nn::Graph,
+            "wasi:nn/inference/graph-execution-context": crate::plugin::wasi_nn::ExecutionContext,
+            "wasi:nn/errors/error": crate::plugin::wasi_nn::NnError,
+        },
+    });
+}
+
+use bindings::wasi::nn::{
+    errors::ErrorCode,
+    graph::{ExecutionTarget, GraphEncoding},
+};
+
+const WASI_NN_ID: &str = "wasi-nn";
+
+/// The type of the elements of a [`Tensor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TensorType {
+    Fp16,
+    Fp32,
+    Fp64,
+    Bf16,
+    U8,
+    I32,
+    I64,
+}
+
+impl TensorType {
+    /// Returns the size of an element in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            TensorType::U8 => 1,
+            TensorType::Fp16 | TensorType::Bf16 => 2,
+            TensorType::Fp32 | TensorType::I32 => 4,
+            TensorType::Fp64 | TensorType::I64 => 8,
+        }
+    }
+}
+
+impl From<bindings::wasi::nn::tensor::TensorType> for TensorType {
+    fn from(ty: bindings::wasi::nn::tensor::TensorType) -> Self {
+        use bindings::wasi::nn::tensor::TensorType as Wit;
+        match ty {
+            Wit::Fp16 => TensorType::Fp16,
+            Wit::Fp32 => TensorType::Fp32,
+            Wit::Fp64 => TensorType::Fp64,
+            Wit::Bf16 => TensorType::Bf16,
+            Wit::U8 => TensorType::U8,
+            Wit::I32 => TensorType::I32,
+            Wit::I64 => TensorType::I64,
+        }
+    }
+}
+
+impl From<TensorType> for bindings::wasi::nn::tensor::TensorType {
+    fn from(ty: TensorType) -> Self {
+        match ty {
+            TensorType::Fp16 => Self::Fp16,
+            TensorType::Fp32 => Self::Fp32,
+            TensorType::Fp64 => Self::Fp64,
+            TensorType::Bf16 => Self::Bf16,
+            TensorType::U8 => Self::U8,
+            TensorType::I32 => Self::I32,
+            TensorType::I64 => Self::I64,
+        }
+    }
+}
+
+/// An input or output of an inference, the `tensor` resource of `wasi:nn`.
+/// The elements are stored in row-major order as little-endian bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    pub dimensions: Vec<u32>,
+    pub ty: TensorType,
+    pub data: Vec<u8>,
+}
+
+impl Tensor {
+    /// Checks that the data holds exactly the elements of the dimensions.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let bytes = self
+            .dimensions
+            .iter()
+            .try_fold(self.ty.size(), |bytes, dimension| {
+                bytes.checked_mul(*dimension as usize)
+            })
+            .context("tensor is too large")?;
+        ensure!(
+            bytes == self.data.len(),
+            "tensor of {:?} {:?} elements must have {bytes} bytes of data, got {}",
+            self.dimensions,
+            self.ty,
+            self.data.len()
+        );
+        Ok(())
+    }
+}
+
+/// Runs the models of the `wasi:nn` plugin.
+pub trait NnBackend: Send + Sync + 'static {
+    /// Returns the `wasi:nn` graph encoding of the models the backend runs,
+    /// e.g. `onnx`.
+    fn encoding(&self) -> &'static str;
+
+    /// Loads a model from its bytes. Called on a blocking thread.
+    fn load(&self, model: &[u8]) -> anyhow::Result<Arc<dyn NnModel>>;
+}
+
+/// A model loaded by an [`NnBackend`].
+pub trait NnModel: Send + Sync + 'static {
+    /// Runs an inference on the named inputs and returns the named outputs.
+    /// Called on a blocking thread.
+    fn compute(&self, inputs: Vec<(String, Tensor)>) -> anyhow::Result<Vec<(String, Tensor)>>;
+}
+
+/// An [`NnBackend`] running ONNX models on the CPU with ONNX Runtime.
+#[derive(Clone, Debug, Default)]
+pub struct OnnxBackend {
+    intra_threads: Option<usize>,
+}
+
+impl OnnxBackend {
+    /// Limits the threads an inference uses within an operator, which ONNX
+    /// Runtime otherwise sets to the number of cores.
+    pub fn with_intra_threads(mut self, threads: usize) -> Self {
+        self.intra_threads = Some(threads);
+        self
+    }
+}
+
+impl NnBackend for OnnxBackend {
+    fn encoding(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn load(&self, model: &[u8]) -> anyhow::Result<Arc<dyn NnModel>> {
+        let mut builder = ort::session::Session::builder()?;
+        if let Some(threads) = self.intra_threads {
+            builder = builder.with_intra_threads(threads)?;
+        }
+        let session = builder
+            .commit_from_memory(model)
+            .context("failed to load ONNX model")?;
+        Ok(Arc::new(OnnxModel {
+            session: std::sync::Mutex::new(session),
+        }))
+    }
+}
+
+struct OnnxModel {
+    session: std::sync::Mutex<ort::session::Session>,
+}
+
+impl NnModel for OnnxModel {
+    fn compute(&self, inputs: Vec<(String, Tensor)>) -> anyhow::Result<Vec<(String, Tensor)>> {
+        let inputs = inputs
+            .into_iter()
+            .map(|(name, tensor)| {
+                let value =
+                    onnx_value(tensor).with_context(|| format!("invalid input '{name}'"))?;
+                Ok((name, value))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut session = self
+            .session
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let outputs = session.run(inputs).context("inference failed")?;
+        outputs
+            .iter()
+            .map(|(name, value)| {
+                let tensor =
+                    onnx_tensor(&value).with_context(|| format!("unsupported output '{name}'"))?;
+                Ok((name.to_string(), tensor))
+            })
+            .collect()
+    }
+}
+
+/// Converts a tensor to an ONNX Runtime value.
+fn onnx_value(tensor: Tensor) -> anyhow::Result<ort::value::DynValue> {
+    tensor.validate()?;
+    let shape: Vec<i64> = tensor.dimensions.iter().map(|d| i64::from(*d)).collect();
+    match tensor.ty {
+        TensorType::Fp32 => onnx_array(shape, &tensor.data, f32::from_le_bytes),
+        TensorType::Fp64 => onnx_array(shape, &tensor.data, f64::from_le_bytes),
+        TensorType::U8 => onnx_array(shape, &tensor.data, u8::from_le_bytes),
+        TensorType::I32 => onnx_array(shape, &tensor.data, i32::from_le_bytes),
+        TensorType::I64 => onnx_array(shape, &tensor.data, i64::from_le_bytes),
+        TensorType::Fp16 | TensorType::Bf16 => bail!("{:?} tensors are not supported", tensor.ty),
+    }
+}
+
+fn onnx_array<T, const N: usize>(
+    shape: Vec<i64>,
+    data: &[u8],
+    from_le_bytes: fn([u8; N]) -> T,
+) -> anyhow::Result<ort::value::DynValue>
+where
+    T: ort::tensor::PrimitiveTensorElementType + std::fmt::Debug + Clone + 'static,
+{
+    let elements: Vec<T> = data
+        .chunks_exact(N)
+        .map(|chunk| from_le_bytes(chunk.try_into().expect("chunks have N bytes")))
+        .collect();
+    Ok(ort::value::Tensor::from_array((shape, elements))?.into_dyn())
+}
+
+/// Converts an ONNX Runtime value to a tensor.
+fn onnx_tensor(value: &ort::value::DynValue) -> anyhow::Result<Tensor> {
+    use ort::tensor::TensorElementType;
+
+    let ort::value::ValueType::Tensor { ty, .. } = value.dtype() else {
+        bail!("only tensors are supported");
+    };
+    match *ty {
+        TensorElementType::Float32 => tensor_from(value, TensorType::Fp32, f32::to_le_bytes),
+        TensorElementType::Float64 => tensor_from(value, TensorType::Fp64, f64::to_le_bytes),
+        TensorElementType::Uint8 => tensor_from(value, TensorType::U8, u8::to_le_bytes),
+        TensorElementType::Int32 => tensor_from(value, TensorType::I32, i32::to_le_bytes),
+        TensorElementType::Int64 => tensor_from(value, TensorType::I64, i64::to_le_bytes),
+        other => bail!("{other:?} tensors are not supported"),
+    }
+}
+
+fn tensor_from<T, const N: usize>(
+    value: &ort::value::DynValue,
+    ty: TensorType,
+    to_le_bytes: fn(T) -> [u8; N],
+) -> anyhow::Result<Tensor>
+where
+    T: ort::tensor::PrimitiveTensorElementType + Copy + 'static,
+{
+    let (shape, data) = value.try_extract_tensor::<T>()?;
+    let dimensions = shape
+        .iter()
+        .map(|d| u32::try_from(*d).context("dimension out of range"))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Tensor {
+        dimensions,
+        ty,
+        data: data.iter().flat_map(|x| to_le_bytes(*x)).collect(),
+    })
+}
+
+/// A model loaded by a component, the `graph` resource of `wasi:nn`.
+pub struct Graph {
+    model: Arc<dyn NnModel>,
+    /// Memory of a model passed to `load`, released with the graph
+    _reservation: Option<Reservation>,
+}
+
+/// The `graph-execution-context` resource of `wasi:nn`.
+pub struct ExecutionContext {
+    model: Arc<dyn NnModel>,
+}
+
+/// The `error` resource of `wasi:nn`.
+#[derive(Debug)]
+pub struct NnError {
+    code: ErrorCode,
+    data: String,
+}
+
+impl NnError {
+    fn new(code: ErrorCode, data: impl Into<String>) -> Self {
+        Self {
+            code,
+            data: data.into(),
+        }
+    }
+}
+
+/// Where a model named in the `models` config is loaded from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ModelSource {
+    /// A file on the host, in a volume mounted into the component
+    File(PathBuf),
+    /// An OCI artifact
+    Oci(String),
+}
+
+/// Parses the `models` config, `name=path` or `name=oci://reference` entries
+/// separated by commas, resolving the paths through `mounts`.
+fn parse_models(
+    models: &str,
+    mounts: &[(VolumeRoot, VolumeMount)],
+) -> anyhow::Result<HashMap<String, ModelSource>> {
+    models
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, location) = entry.split_once('=').with_context(|| {
+                format!("model '{entry}' must be given as name=path or name=oci://reference")
+            })?;
+            let location = location.trim();
+            let source = if location.starts_with("oci://") {
+                ModelSource::Oci(location.to_string())
+            } else {
+                ModelSource::File(resolve_model_path(location, mounts)?)
+            };
+            Ok((name.trim().to_string(), source))
+        })
+        .collect()
+}
+
+/// Resolves the guest `path` of a model to the file backing it on the host,
+/// in the volume mounted at the longest prefix of the path.
+fn resolve_model_path(path: &str, mounts: &[(VolumeRoot, VolumeMount)]) -> anyhow::Result<PathBuf> {
+    validate_mount_path(path).with_context(|| format!("invalid model path '{path}'"))?;
+    let (root, relative) = mounts
+        .iter()
+        .filter_map(|(root, mount)| {
+            Some((root, Path::new(path).strip_prefix(&mount.mount_path).ok()?))
+        })
+        .min_by_key(|(_, relative)| relative.components().count())
+        .with_context(|| format!("model '{path}' is not in a volume mounted into the component"))?;
+    let file = root
+        .path()
+        .join(relative)
+        .canonicalize()
+        .with_context(|| format!("model '{path}' does not exist"))?;
+    ensure!(
+        file.starts_with(root.path()) && file.is_file(),
+        "model '{path}' is not a file in its volume"
+    );
+    Ok(file)
+}
+
+/// The memory of the models a workload loaded, against its limit.
+#[derive(Debug, Default)]
+struct ModelMemory {
+    limit: Option<u64>,
+    used: AtomicU64,
+}
+
+impl ModelMemory {
+    /// Reserves `bytes` for a model, unless that would exceed the limit.
+    fn reserve(self: &Arc<Self>, bytes: u64) -> Option<Reservation> {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes)
+                    .filter(|total| self.limit.is_none_or(|limit| *total <= limit))
+            })
+            .ok()?;
+        Some(Reservation {
+            memory: Arc::clone(self),
+            bytes,
+        })
+    }
+}
+
+/// Memory reserved for a model, released when dropped.
+struct Reservation {
+    memory: Arc<ModelMemory>,
+    bytes: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.memory.used.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// The models of a workload.
+#[derive(Default)]
+struct WorkloadModels {
+    memory: Arc<ModelMemory>,
+    /// Models each component may load by name, keyed by component ID
+    named: HashMap<Arc<str>, HashMap<String, ModelSource>>,
+    /// Models loaded by name, shared by the instances of the workload
+    loaded: HashMap<ModelSource, (Arc<dyn NnModel>, Reservation)>,
+}
+
+/// Plugin providing `wasi:nn` on top of an [`NnBackend`].
+#[derive(Clone)]
+pub struct WasiNn {
+    backend: Arc<dyn NnBackend>,
+    /// Models of each workload, keyed by workload ID
+    workloads: Arc<RwLock<HashMap<Arc<str>, Arc<Mutex<WorkloadModels>>>>>,
+    #[cfg(feature = "oci")]
+    oci: crate::oci::OciConfig,
+}
+
+impl Default for WasiNn {
+    fn default() -> Self {
+        Self::new(OnnxBackend::default())
+    }
+}
+
+impl WasiNn {
+    /// Creates a plugin running models with `backend`.
+    pub fn new(backend: impl NnBackend) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            workloads: Arc::default(),
+            #[cfg(feature = "oci")]
+            oci: crate::oci::OciConfig::default(),
+        }
+    }
+
+    /// Pulls the models given as OCI artifacts with `config`.
+    #[cfg(feature = "oci")]
+    pub fn with_oci_config(mut self, config: crate::oci::OciConfig) -> Self {
+        self.oci = config;
+        self
+    }
+
+    /// Lets `component_id` load `models` by name, limiting the models of its
+    /// workload to `limit` bytes.
+    async fn bind_models(
+        &self,
+        workload_id: &str,
+        component_id: &str,
+        models: HashMap<String, ModelSource>,
+        limit: Option<u64>,
+    ) {
+        let workload = Arc::clone(
+            self.workloads
+                .write()
+                .await
+                .entry(Arc::from(workload_id))
+                .or_insert_with(|| {
+                    Arc::new(Mutex::new(WorkloadModels {
+                        memory: Arc::new(ModelMemory {
+                            limit,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }))
+                }),
+        );
+        workload
+            .lock()
+            .await
+            .named
+            .insert(Arc::from(component_id), models);
+    }
+
+    async fn workload(&self, workload_id: &str) -> Result<Arc<Mutex<WorkloadModels>>, NnError> {
+        self.workloads
+            .read()
+            .await
+            .get(workload_id)
+            .cloned()
+            .ok_or_else(|| NnError::new(ErrorCode::RuntimeError, "workload not bound to wasi:nn"))
+    }
+
+    async fn load(
+        &self,
+        workload_id: &str,
+        builders: Vec<Vec<u8>>,
+        encoding: GraphEncoding,
+        target: ExecutionTarget,
+    ) -> Result<Graph, NnError> {
+        let encoding = encoding_name(encoding);
+        if encoding != "autodetect" && encoding != self.backend.encoding() {
+            return Err(NnError::new(
+                ErrorCode::InvalidEncoding,
+                format!("only {} models are supported", self.backend.encoding()),
+            ));
+        }
+        if target != ExecutionTarget::Cpu {
+            return Err(NnError::new(
+                ErrorCode::UnsupportedOperation,
+                "only the cpu execution target is supported",
+            ));
+        }
+        let Ok([model]) = <[Vec<u8>; 1]>::try_from(builders) else {
+            return Err(NnError::new(
+                ErrorCode::InvalidArgument,
+                "expected the model as a single graph builder",
+            ));
+        };
+        let memory = Arc::clone(&self.workload(workload_id).await?.lock().await.memory);
+        let reservation = reserve(&memory, &model)?;
+        Ok(Graph {
+            model: self.load_model(model).await?,
+            _reservation: Some(reservation),
+        })
+    }
+
+    async fn load_by_name(
+        &self,
+        workload_id: &str,
+        component_id: &str,
+        name: &str,
+    ) -> Result<Graph, NnError> {
+        let workload = self.workload(workload_id).await?;
+        let mut workload = workload.lock().await;
+        let Some(source) = workload
+            .named
+            .get(component_id)
+            .and_then(|models| models.get(name))
+            .cloned()
+        else {
+            return Err(NnError::new(
+                ErrorCode::NotFound,
+                format!("no model named '{name}'"),
+            ));
+        };
+        if let Some((model, _)) = workload.loaded.get(&source) {
+            return Ok(Graph {
+                model: Arc::clone(model),
+                _reservation: None,
+            });
+        }
+
+        let bytes = self.read_model(&source).await.map_err(|e| {
+            NnError::new(
+                ErrorCode::RuntimeError,
+                format!("failed to read model '{name}': {e:#}"),
+            )
+        })?;
+        let reservation = reserve(&workload.memory, &bytes)?;
+        let model = self.load_model(bytes).await?;
+        tracing::debug!(workload_id, model = name, "loaded wasi:nn model");
+        workload
+            .loaded
+            .insert(source, (Arc::clone(&model), reservation));
+        Ok(Graph {
+            model,
+            _reservation: None,
+        })
+    }
+
+    async fn read_model(&self, source: &ModelSource) -> anyhow::Result<Vec<u8>> {
+        match source {
+            ModelSource::File(path) => tokio::fs::read(path).await.map_err(Into::into),
+            #[cfg(feature = "oci")]
+            ModelSource::Oci(reference) => {
+                let (bytes, _digest) = crate::oci::pull_blob(reference, self.oci.clone()).await?;
+                Ok(bytes)
+            }
+            #[cfg(not(feature = "oci"))]
+            ModelSource::Oci(reference) => {
+                bail!("cannot pull '{reference}', the host is built without OCI support")
+            }
+        }
+    }
+
+    async fn load_model(&self, bytes: Vec<u8>) -> Result<Arc<dyn NnModel>, NnError> {
+        let backend = Arc::clone(&self.backend);
+        tokio::task::spawn_blocking(move || backend.load(&bytes))
+            .await
+            .map_err(|e| NnError::new(ErrorCode::RuntimeError, e.to_string()))?
+            .map_err(|e| NnError::new(ErrorCode::InvalidArgument, format!("{e:#}")))
+    }
+}
+
+fn reserve(memory: &Arc<ModelMemory>, model: &[u8]) -> Result<Reservation, NnError> {
+    memory.reserve(model.len() as u64).ok_or_else(|| {
+        NnError::new(
+            ErrorCode::TooLarge,
+            "model exceeds the model memory of the workload",
+        )
+    })
+}
+
+async fn compute(
+    model: Arc<dyn NnModel>,
+    inputs: Vec<(String, Tensor)>,
+) -> Result<Vec<(String, Tensor)>, NnError> {
+    for (name, tensor) in &inputs {
+        tensor.validate().map_err(|e| {
+            NnError::new(
+                ErrorCode::InvalidArgument,
+                format!("invalid input '{name}': {e:#}"),
+            )
+        })?;
+    }
+    tokio::task::spawn_blocking(move || model.compute(inputs))
+        .await
+        .map_err(|e| NnError::new(ErrorCode::RuntimeError, e.to_string()))?
+        .map_err(|e| NnError::new(ErrorCode::RuntimeError, format!("{e:#}")))
+}
+
+fn encoding_name(encoding: GraphEncoding) -> &'static str {
+    match encoding {
+        GraphEncoding::Openvino => "openvino",
+        GraphEncoding::Onnx => "onnx",
+        GraphEncoding::Tensorflow => "tensorflow",
+        GraphEncoding::Pytorch => "pytorch",
+        GraphEncoding::Tensorflowlite => "tensorflowlite",
+        GraphEncoding::Ggml => "ggml",
+        GraphEncoding::Autodetect => "autodetect",
+    }
+}
+
+/// Pushes the value or error of a `wasi:nn` call to the resource table.
+fn push_result<T: Send + 'static>(
+    table: &mut ResourceTable,
+    result: Result<T, NnError>,
+) -> anyhow::Result<Result<Resource<T>, Resource<NnError>>> {
+    Ok(match result {
+        Ok(value) => Ok(table.push(value)?),
+        Err(error) => Err(table.push(error)?),
+    })
+}
+
+impl bindings::wasi::nn::tensor::Host for Ctx {}
+
+impl bindings::wasi::nn::tensor::HostTensor for Ctx {
+    async fn new(
+        &mut self,
+        dimensions: Vec<u32>,
+        ty: bindings::wasi::nn::tensor::TensorType,
+        data: Vec<u8>,
+    ) -> anyhow::Result<Resource<Tensor>> {
+        Ok(self.table.push(Tensor {
+            dimensions,
+            ty: ty.into(),
+            data,
+        })?)
+    }
+
+    async fn dimensions(&mut self, tensor: Resource<Tensor>) -> anyhow::Result<Vec<u32>> {
+        Ok(self.table.get(&tensor)?.dimensions.clone())
+    }
+
+    async fn ty(
+        &mut self,
+        tensor: Resource<Tensor>,
+    ) -> anyhow::Result<bindings::wasi::nn::tensor::TensorType> {
+        Ok(self.table.get(&tensor)?.ty.into())
+    }
+
+    async fn data(&mut self, tensor: Resource<Tensor>) -> anyhow::Result<Vec<u8>> {
+        Ok(self.table.get(&tensor)?.data.clone())
+    }
+
+    async fn drop(&mut self, tensor: Resource<Tensor>) -> anyhow::Result<()> {
+        self.table.delete(tensor)?;
+        Ok(())
+    }
+}
+
+impl bindings::wasi::nn::errors::Host for Ctx {}
+
+impl bindings::wasi::nn::errors::HostError for Ctx {
+    async fn code(&mut self, error: Resource<NnError>) -> anyhow::Result<ErrorCode> {
+        Ok(self.table.get(&error)?.code)
+    }
+
+    async fn data(&mut self, error: Resource<NnError>) -> anyhow::Result<String> {
+        Ok(self.table.get(&error)?.data.clone())
+    }
+
+    async fn drop(&mut self, error: Resource<NnError>) -> anyhow::Result<()> {
+        self.table.delete(error)?;
+        Ok(())
+    }
+}
+
+impl bindings::wasi::nn::graph::Host for Ctx {
+    async fn load(
+        &mut self,
+        builder: Vec<Vec<u8>>,
+        encoding: GraphEncoding,
+        target: ExecutionTarget,
+    ) -> anyhow::Result<Result<Resource<Graph>, Resource<NnError>>> {
+        let graph = match self.get_plugin::<WasiNn>(WASI_NN_ID) {
+            Some(plugin) => {
+                plugin
+                    .load(&self.workload_id, builder, encoding, target)
+                    .await
+            }
+            None => Err(NnError::new(
+                ErrorCode::RuntimeError,
+                "wasi:nn plugin not available",
+            )),
+        };
+        push_result(&mut self.table, graph)
+    }
+
+    async fn load_by_name(
+        &mut self,
+        name: String,
+    ) -> anyhow::Result<Result<Resource<Graph>, Resource<NnError>>> {
+        let graph = match self.get_plugin::<WasiNn>(WASI_NN_ID) {
+            Some(plugin) => {
+                plugin
+                    .load_by_name(&self.workload_id, &self.component_id, &name)
+                    .await
+            }
+            None => Err(NnError::new(
+                ErrorCode::RuntimeError,
+                "wasi:nn plugin not available",
+            )),
+        };
+        push_result(&mut self.table, graph)
+    }
+}
+
+impl bindings::wasi::nn::graph::HostGraph for Ctx {
+    async fn init_execution_context(
+        &mut self,
+        graph: Resource<Graph>,
+    ) -> anyhow::Result<Result<Resource<ExecutionContext>, Resource<NnError>>> {
+        let model = Arc::clone(&self.table.get(&graph)?.model);
+        push_result(&mut self.table, Ok(ExecutionContext { model }))
+    }
+
+    async fn drop(&mut self, graph: Resource<Graph>) -> anyhow::Result<()> {
+        self.table.delete(graph)?;
+        Ok(())
+    }
+}
+
+impl bindings::wasi::nn::inference::Host for Ctx {}
+
+impl bindings::wasi::nn::inference::HostGraphExecutionContext for Ctx {
+    async fn compute(
+        &mut self,
+        context: Resource<ExecutionContext>,
+        inputs: Vec<(String, Resource<Tensor>)>,
+    ) -> anyhow::Result<Result<Vec<(String, Resource<Tensor>)>, Resource<NnError>>> {
+        let model = Arc::clone(&self.table.get(&context)?.model);
+        // The inputs are owned handles, passed to the host for good
+        let inputs = inputs
+            .into_iter()
+            .map(|(name, tensor)| Ok((name, self.table.delete(tensor)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let outputs = match compute(model, inputs).await {
+            Ok(outputs) => outputs,
+            Err(error) => return Ok(Err(self.table.push(error)?)),
+        };
+        let outputs = outputs
+            .into_iter()
+            .map(|(name, tensor)| Ok((name, self.table.push(tensor)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Ok(outputs))
+    }
+
+    async fn drop(&mut self, context: Resource<ExecutionContext>) -> anyhow::Result<()> {
+        self.table.delete(context)?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl HostPlugin for WasiNn {
+    fn id(&self) -> &'static str {
+        WASI_NN_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from(
+                "wasi:nn/tensor,graph,inference,errors@0.2.0-rc-2024-10-28",
+            )]),
+            ..Default::default()
+        }
+    }
+
+    async fn on_component_bind(
+        &self,
+        component: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wasi" && i.package == "nn")
+        else {
+            tracing::warn!(
+                "WasiNn plugin requested for unsupported interface(s): {:?}",
+                interfaces
+            );
+            return Ok(());
+        };
+
+        let config = component_config(interface, component.local_resources());
+        let models = match config.get("models") {
+            Some(models) => {
+                parse_models(models, component.volume_mounts()).context("invalid wasi:nn models")?
+            }
+            None => HashMap::new(),
+        };
+        // The limit applies to the whole workload, components can't raise it
+        let limit = interface
+            .config
+            .get("max-model-memory-mb")
+            .map(|mb| {
+                mb.parse::<u64>()
+                    .context("max-model-memory-mb must be a number of MiB")
+            })
+            .transpose()?
+            .map(|mb| mb.saturating_mul(1024 * 1024));
+
+        let linker = component.linker();
+        bindings::wasi::nn::tensor::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wasi::nn::errors::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wasi::nn::inference::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        bindings::wasi::nn::graph::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+
+        self.bind_models(component.workload_id(), component.id(), models, limit)
+            .await;
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        // Dropping the models of the workload releases them
+        self.workloads.write().await.remove(workload_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend whose models return their inputs.
+    struct Echo;
+
+    impl NnBackend for Echo {
+        fn encoding(&self) -> &'static str {
+            "onnx"
+        }
+
+        fn load(&self, _model: &[u8]) -> anyhow::Result<Arc<dyn NnModel>> {
+            Ok(Arc::new(Echo))
+        }
+    }
+
+    impl NnModel for Echo {
+        fn compute(&self, inputs: Vec<(String, Tensor)>) -> anyhow::Result<Vec<(String, Tensor)>> {
+            Ok(inputs)
+        }
+    }
+
+    #[test]
+    fn test_tensor_validate() {
+        let mut tensor = Tensor {
+            dimensions: vec![2, 2],
+            ty: TensorType::Fp32,
+            data: vec![0; 16],
+        };
+        assert!(tensor.validate().is_ok());
+        tensor.data.pop();
+        assert!(tensor.validate().is_err());
+        tensor.dimensions = vec![u32::MAX, u32::MAX, u32::MAX];
+        assert!(tensor.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_models() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("vision")).unwrap();
+        std::fs::write(dir.path().join("vision/resnet.onnx"), b"model").unwrap();
+        let mounts = vec![(
+            VolumeRoot::new(dir.path()).unwrap(),
+            VolumeMount {
+                name: "models".to_string(),
+                mount_path: "/models".to_string(),
+                ..Default::default()
+            },
+        )];
+
+        let models = parse_models(
+            "resnet=/models/vision/resnet.onnx, sentiment=oci://ghcr.io/acme/sentiment:1.0",
+            &mounts,
+        )
+        .unwrap();
+        assert_eq!(
+            models["resnet"],
+            ModelSource::File(
+                dir.path()
+                    .join("vision/resnet.onnx")
+                    .canonicalize()
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            models["sentiment"],
+            ModelSource::Oci("oci://ghcr.io/acme/sentiment:1.0".to_string())
+        );
+
+        for invalid in [
+            "resnet",
+            "resnet=/data/resnet.onnx",
+            "resnet=/models/missing.onnx",
+            "resnet=/models/vision",
+            "resnet=/models/../etc/passwd",
+        ] {
+            assert!(parse_models(invalid, &mounts).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_by_name_shares_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small.onnx");
+        let large = dir.path().join("large.onnx");
+        std::fs::write(&small, [0; 8]).unwrap();
+        std::fs::write(&large, [0; 32]).unwrap();
+        let plugin = WasiNn::new(Echo);
+        plugin
+            .bind_models(
+                "workload",
+                "component",
+                HashMap::from([
+                    ("small".to_string(), ModelSource::File(small)),
+                    ("large".to_string(), ModelSource::File(large)),
+                ]),
+                Some(16),
+            )
+            .await;
+
+        // Loading a model again reuses it and its memory
+        plugin
+            .load_by_name("workload", "component", "small")
+            .await
+            .unwrap();
+        plugin
+            .load_by_name("workload", "component", "small")
+            .await
+            .unwrap();
+        let memory = Arc::clone(
+            &plugin
+                .workload("workload")
+                .await
+                .unwrap()
+                .lock()
+                .await
+                .memory,
+        );
+        assert_eq!(memory.used.load(Ordering::Relaxed), 8);
+
+        let error = plugin
+            .load_by_name("workload", "component", "large")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::TooLarge);
+        let error = plugin
+            .load_by_name("workload", "other", "small")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::NotFound);
+
+        // Models passed to load are released with their graph
+        let graph = plugin
+            .load(
+                "workload",
+                vec![vec![0; 8]],
+                GraphEncoding::Onnx,
+                ExecutionTarget::Cpu,
+            )
+            .await
+            .unwrap();
+        assert_eq!(memory.used.load(Ordering::Relaxed), 16);
+        drop(graph);
+        assert_eq!(memory.used.load(Ordering::Relaxed), 8);
+
+        let error = plugin
+            .load(
+                "workload",
+                vec![vec![0; 8]],
+                GraphEncoding::Tensorflow,
+                ExecutionTarget::Cpu,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidEncoding);
+    }
+
+    #[tokio::test]
+    async fn test_compute_validates_inputs() {
+        let input = Tensor {
+            dimensions: vec![1, 2],
+            ty: TensorType::I32,
+            data: vec![1, 0, 0, 0, 2, 0, 0, 0],
+        };
+        let outputs = compute(Arc::new(Echo), vec![("x".to_string(), input.clone())])
+            .await
+            .unwrap();
+        assert_eq!(outputs, vec![("x".to_string(), input)]);
+
+        let invalid = Tensor {
+            dimensions: vec![3],
+            ty: TensorType::I32,
+            data: vec![0; 4],
+        };
+        let error = compute(Arc::new(Echo), vec![("x".to_string(), invalid)])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+    }
+}
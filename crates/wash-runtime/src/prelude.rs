@@ -0,0 +1,39 @@
+//! The types needed to embed a host in another Rust service.
+//!
+//! ```
+//! use wash_runtime::prelude::*;
+//! ```
+//!
+//! brings the [`Engine`], the [`HostBuilder`] with its [`HostApi`], the HTTP
+//! server and routers, workload definitions and the WASI plugins enabled by
+//! cargo features into scope, see the [crate documentation](crate#embedding)
+//! for an example. Everything is re-exported from the module documenting it.
+//!
+//! Items are only added to the prelude within a minor release. Configuration
+//! structs are `#[non_exhaustive]`, so new settings are not breaking changes:
+//! build them from their `Default` or constructor and `with_*` methods
+//! instead of struct literals.
+
+pub use crate::engine::{Engine, EngineBuilder};
+pub use crate::host::http::{
+    DevRouter, DynamicRouter, HostHandler, HttpRouteConfig, HttpServer, Router,
+};
+pub use crate::host::limits::HostLimits;
+pub use crate::host::{Host, HostApi, HostBuilder, HostError, HostResult};
+pub use crate::plugin::HostPlugin;
+#[cfg(feature = "wasi-blobstore")]
+pub use crate::plugin::wasi_blobstore::WasiBlobstore;
+#[cfg(feature = "wasi-config")]
+pub use crate::plugin::wasi_config::WasiConfig;
+#[cfg(feature = "wasi-keyvalue")]
+pub use crate::plugin::wasi_keyvalue::WasiKeyvalue;
+#[cfg(feature = "wasi-logging")]
+pub use crate::plugin::wasi_logging::WasiLogging;
+#[cfg(feature = "wasi-messaging")]
+pub use crate::plugin::wasi_messaging::WasiMessaging;
+pub use crate::types::{
+    Component, LocalResources, Service, Workload, WorkloadBuilder, WorkloadStartRequest,
+    WorkloadStartResponse, WorkloadState, WorkloadStatusRequest, WorkloadStatusResponse,
+    WorkloadStopRequest, WorkloadStopResponse,
+};
+pub use crate::wit::WitInterface;
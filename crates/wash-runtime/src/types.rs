@@ -5,32 +5,179 @@
 //! ## Public API Types (used in [`crate::host::HostApi`])
 //! - Request/Response types: [`WorkloadStartRequest`], [`WorkloadStartResponse`],
 //!   [`WorkloadStatusRequest`], [`WorkloadStatusResponse`],
-//!   [`WorkloadStopRequest`], [`WorkloadStopResponse`]
+//!   [`WorkloadStopRequest`], [`WorkloadStopResponse`],
+//!   [`WorkloadInvokeRequest`], [`WorkloadInvokeResponse`],
+//!   [`VolumeCreateRequest`], [`VolumeInspectRequest`], [`VolumeDeleteRequest`], [`VolumeInfo`],
+//!   [`RouteUnpinRequest`], [`RouteReleaseRequest`], [`RouteInvalidateRequest`],
+//!   [`RoutingTable`], [`HttpListeners`], [`ReplayRequest`]
 //! - Host information: [`HostHeartbeat`]
 //!
 //! ## Core Workload Types (used internally)
-//! - Workload definition: [`Workload`], [`WorkloadState`], [`WorkloadStatus`]
-//! - Component configuration: [`Component`], [`Service`], [`LocalResources`]
+//! - Workload definition: [`Workload`], [`WorkloadBuilder`], [`WorkloadState`], [`WorkloadStatus`],
+//!   [`OutboundLimits`]
+//! - Component configuration: [`Component`], [`ComponentKind`], [`Job`],
+//!   [`OutgoingHttpLimits`], [`Service`], [`LocalResources`], [`EnvFile`],
+//!   [`DeviceRequest`], [`DeviceClass`]
 //! - Volume management: [`Volume`], [`VolumeType`], [`VolumeMount`],
-//!   [`EmptyDirVolume`], [`HostPathVolume`]
+//!   [`EmptyDirVolume`], [`HostPathVolume`], [`NamedVolume`]
+//! - Update strategy: [`RollingUpdate`], [`RolloutLimit`], [`RolloutStep`]
+//! - HTTP routes: [`route::HttpRouteConfig`] and the route policies in [`route`]
+//!
+//! ## Wire Format
+//!
+//! The workload definition types implement [`serde::Serialize`] and [`serde::Deserialize`]
+//! and derive [`schemars::JsonSchema`], so manifests and external tools can share a
+//! single canonical format. Component bytes are encoded as base64 strings. See
+//! [`workload_json_schema`] for the generated schema.
 
-use bytes::Bytes;
 use std::collections::HashMap;
 
+use bytes::Bytes;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
 use crate::wit::WitInterface;
 
+pub mod route;
+use route::HttpRouteConfig;
+
 /// Represents a deployable workload containing one or more WebAssembly components.
 /// A workload defines the complete runtime configuration including components,
 /// services, interfaces, and volumes.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Workload {
     pub namespace: String,
     pub name: String,
+    #[serde(default)]
     pub annotations: HashMap<String, String>,
+    #[serde(default)]
     pub service: Option<Service>,
+    #[serde(default)]
     pub components: Vec<Component>,
+    #[serde(default)]
     pub host_interfaces: Vec<WitInterface>,
+    #[serde(default)]
     pub volumes: Vec<Volume>,
+    /// Limits on the outbound connections of all components of the workload
+    #[serde(default, skip_serializing_if = "OutboundLimits::is_unlimited")]
+    pub outbound: OutboundLimits,
+}
+
+impl Workload {
+    /// Creates a new [`WorkloadBuilder`] for a workload with the given namespace and name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wash_runtime::types::{Component, Workload};
+    ///
+    /// let workload = Workload::builder("default", "my-workload")
+    ///     .with_component(Component::new(&b"\0asm"[..]))
+    ///     .with_http_route("localhost", "/api")
+    ///     .build();
+    /// assert_eq!(workload.components.len(), 1);
+    /// ```
+    pub fn builder(namespace: impl Into<String>, name: impl Into<String>) -> WorkloadBuilder {
+        WorkloadBuilder::new(namespace, name)
+    }
+}
+
+/// Builder for a [`Workload`].
+///
+/// All collections start empty, so only the parts of the workload that matter
+/// for a particular deployment need to be specified.
+#[derive(Debug, Clone)]
+pub struct WorkloadBuilder {
+    workload: Workload,
+}
+
+impl WorkloadBuilder {
+    /// Creates a new builder for a workload with the given namespace and name.
+    pub fn new(namespace: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            workload: Workload {
+                namespace: namespace.into(),
+                name: name.into(),
+                annotations: HashMap::new(),
+                service: None,
+                components: Vec::new(),
+                host_interfaces: Vec::new(),
+                volumes: Vec::new(),
+                outbound: OutboundLimits::default(),
+            },
+        }
+    }
+
+    /// Adds an annotation to the workload.
+    pub fn with_annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.workload.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the service for the workload.
+    pub fn with_service(mut self, service: Service) -> Self {
+        self.workload.service = Some(service);
+        self
+    }
+
+    /// Adds a component to the workload.
+    pub fn with_component(mut self, component: Component) -> Self {
+        self.workload.components.push(component);
+        self
+    }
+
+    /// Adds a requested host interface to the workload.
+    ///
+    /// If an interface with the same [`WitInterface::instance`] was already requested,
+    /// the two are merged instead of being listed twice.
+    pub fn with_host_interface(mut self, interface: impl Into<WitInterface>) -> Self {
+        let interface = interface.into();
+        if !self
+            .workload
+            .host_interfaces
+            .iter_mut()
+            .any(|existing| existing.merge(&interface))
+        {
+            self.workload.host_interfaces.push(interface);
+        }
+        self
+    }
+
+    /// Requests `wasi:http/incoming-handler` and routes requests for `host` and `path`
+    /// to this workload.
+    pub fn with_http_route(self, host: impl Into<String>, path: impl Into<String>) -> Self {
+        self.with_http_route_config(HttpRouteConfig::new(host).with_path(path))
+    }
+
+    /// Requests `wasi:http/incoming-handler` and routes requests matching `route`
+    /// to this workload.
+    pub fn with_http_route_config(self, route: HttpRouteConfig) -> Self {
+        let mut interface = WitInterface::from("wasi:http/incoming-handler");
+        interface.config = route.to_config();
+        self.with_host_interface(interface)
+    }
+
+    /// Adds a volume to the workload.
+    pub fn with_volume(mut self, volume: Volume) -> Self {
+        self.workload.volumes.push(volume);
+        self
+    }
+
+    /// Limits the outbound connections of the workload.
+    pub fn with_outbound_limits(mut self, limits: OutboundLimits) -> Self {
+        self.workload.outbound = limits;
+        self
+    }
+
+    /// Returns the configured [`Workload`].
+    pub fn build(self) -> Workload {
+        self.workload
+    }
+
+    /// Returns a [`WorkloadStartRequest`] for the configured workload with a new random ID.
+    pub fn start_request(self) -> WorkloadStartRequest {
+        WorkloadStartRequest::new(self.build())
+    }
 }
 
 /// The current state of a workload in its lifecycle.
@@ -46,28 +193,437 @@ pub enum WorkloadState {
 
 /// Configuration for a long-running service component that handles requests.
 /// Services can be restarted if they fail and have resource limits.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Service {
+    #[serde(with = "base64_bytes")]
+    #[schemars(with = "String")]
     pub bytes: Bytes,
+    #[serde(default)]
     pub local_resources: LocalResources,
+    #[serde(default)]
     pub max_restarts: u64,
 }
 
+impl Service {
+    /// Creates a new service from component bytes with default resources and no restarts.
+    pub fn new(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            local_resources: LocalResources::default(),
+            max_restarts: 0,
+        }
+    }
+
+    /// Sets the local resources for the service.
+    pub fn with_local_resources(mut self, local_resources: LocalResources) -> Self {
+        self.local_resources = local_resources;
+        self
+    }
+
+    /// Sets the maximum number of times the service is restarted after failing.
+    pub fn with_max_restarts(mut self, max_restarts: u64) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+}
+
 /// A WebAssembly component that can be executed as part of a workload.
 /// Components can be pooled for concurrent execution and have invocation limits.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Component {
+    /// May be left empty when the component is pulled from `image`
+    #[serde(default, with = "base64_bytes")]
+    #[schemars(with = "String")]
     pub bytes: Bytes,
+    /// The OCI artifact the host pulls the component from when `bytes` is
+    /// empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ComponentImage>,
+    /// A `.wasm` file on the host the component is read from when `bytes` is
+    /// empty, reloaded when it changes if the host watches it, see
+    /// [`crate::host::hot_reload`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_path: Option<String>,
+    #[serde(default)]
     pub local_resources: LocalResources,
+    #[serde(default)]
     pub pool_size: i32,
+    #[serde(default)]
     pub max_invocations: i32,
+    /// Scales the instances of the component with its load instead of
+    /// `pool_size`, see [`crate::engine::autoscale`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoscaling: Option<Autoscaling>,
+    /// Keeps instances of the component initialized ahead of its
+    /// invocations, see [`crate::engine::preinit`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_init: Option<PreInit>,
+    #[serde(default)]
+    pub kind: ComponentKind,
+    /// The export an init component runs, either a function name or
+    /// `interface#function`. Defaults to [`DEFAULT_INIT_EXPORT`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_export: Option<String>,
+    /// How a job component is run. Defaults to [`Job::default`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<Job>,
+    /// Limits on the outgoing HTTP requests of the component
+    #[serde(default, skip_serializing_if = "OutgoingHttpLimits::is_unlimited")]
+    pub outgoing_http: OutgoingHttpLimits,
+}
+
+/// The export run by init components that don't specify one
+pub const DEFAULT_INIT_EXPORT: &str = "wasi:cli/run@0.2.0#run";
+
+/// A component published to an OCI registry.
+///
+/// The host pulls the artifact when the workload starts, unless a component
+/// is staged under the same reference, and keeps it in its OCI cache, see
+/// [`crate::host::HostBuilder::with_oci_config`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ComponentImage {
+    /// e.g. `oci://ghcr.io/org/component:tag`, the `oci://` scheme is
+    /// optional
+    pub reference: String,
+    /// The manifest digest the artifact must have, e.g. `sha256:...`. A
+    /// reference pinned to a digest is checked against it as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Credentials for the registry. Without them, those of the docker config
+    /// of the host are used, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<RegistryCredentials>,
+}
+
+impl ComponentImage {
+    /// Creates an image reference without digest or credentials.
+    pub fn new(reference: impl Into<String>) -> Self {
+        Self {
+            reference: reference.into(),
+            digest: None,
+            credentials: None,
+        }
+    }
+
+    /// Sets the digest the artifact must have.
+    pub fn with_digest(mut self, digest: impl Into<String>) -> Self {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    /// Sets the credentials for the registry.
+    pub fn with_credentials(mut self, credentials: RegistryCredentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+}
+
+/// Credentials for an OCI registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryCredentials {
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// A bearer token, e.g. a personal access token
+    Token {
+        token: String,
+    },
+}
+
+/// How a [`Component`] takes part in its workload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentKind {
+    /// Serves invocations for as long as the workload runs
+    #[default]
+    Serving,
+    /// Runs its init export once, e.g. to run migrations or seed caches. The
+    /// workload only starts serving once every init component has succeeded.
+    Init,
+    /// Runs its job export to completion, see [`Job`]. The workload completes
+    /// once every job component has finished.
+    Job,
+    /// Handles every HTTP request routed to the workload before its serving
+    /// component, which it passes requests on to, see
+    /// [`crate::host::http::middleware`]. A workload has at most one.
+    Middleware,
+}
+
+/// How a job component runs its export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Job {
+    /// The export to run, either a function name or `interface#function`.
+    /// Defaults to [`DEFAULT_INIT_EXPORT`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<String>,
+    /// How many successful runs complete the job
+    pub completions: u32,
+    /// How many runs may be in flight at once
+    pub parallelism: u32,
+    /// How many times a failed run is retried before the job fails
+    pub max_retries: u32,
+    /// Delay before the first retry of a run, doubled on every further retry
+    pub backoff_ms: u64,
+}
+
+impl Default for Job {
+    fn default() -> Self {
+        Self {
+            export: None,
+            completions: 1,
+            parallelism: 1,
+            max_retries: 0,
+            backoff_ms: 1_000,
+        }
+    }
+}
+
+/// Bounds the instances of a component scaled with its load, see
+/// [`crate::engine::autoscale`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Autoscaling {
+    /// Instances kept even when the component is idle
+    pub min_instances: u32,
+    /// Most instances running at once, further invocations are queued
+    pub max_instances: u32,
+    /// How long, in seconds, an instance may stay idle before it is reclaimed
+    pub idle_ttl_secs: u64,
+    /// Most invocations waiting for an instance, unbounded if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<u32>,
+    /// What happens to invocations once every instance is busy
+    pub queue_policy: QueuePolicy,
+}
+
+impl Default for Autoscaling {
+    fn default() -> Self {
+        Self {
+            min_instances: 0,
+            max_instances: 10,
+            idle_ttl_secs: 60,
+            queue_depth: None,
+            queue_policy: QueuePolicy::default(),
+        }
+    }
+}
+
+/// Pre-initialization of the instances of a component, see
+/// [`crate::engine::preinit`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PreInit {
+    /// The export run on each instance before it serves an invocation, either
+    /// a function name or `interface#function`, e.g. a warm-up call. Instances
+    /// are only instantiated if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<String>,
+    /// Instances kept initialized ahead of invocations
+    pub ready: u32,
+}
+
+impl Default for PreInit {
+    fn default() -> Self {
+        Self {
+            export: None,
+            ready: 1,
+        }
+    }
+}
+
+/// What happens to the invocations of an autoscaling component once every
+/// instance is busy, see [`crate::engine::autoscale`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// Invocations wait for an instance, and are refused once the queue is full
+    #[default]
+    Queue,
+    /// Invocations are refused right away
+    Reject,
+    /// Invocations arriving to a full queue take the place of the one waiting
+    /// the longest, which is refused instead
+    ShedOldest,
+}
+
+/// Limits on the outgoing `wasi:http` requests of a component, so a slow or
+/// misbehaving upstream can't hold its instances or exhaust their memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct OutgoingHttpLimits {
+    /// Longest time, in milliseconds, a request may take, from sending it to
+    /// reading the end of its response body, redirects included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Largest response body, in bytes, a request may return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<u64>,
+    /// How many redirects the host follows before returning the response to
+    /// the component. Redirects are returned to the component if 0.
+    pub max_redirects: u32,
+}
+
+impl OutgoingHttpLimits {
+    /// Returns whether no limit is set and no redirect is followed.
+    pub fn is_unlimited(&self) -> bool {
+        self.timeout_ms.is_none() && self.max_response_bytes.is_none() && self.max_redirects == 0
+    }
+}
+
+/// Limits on the outbound connections a workload opens, shared by all of its
+/// components, so a single workload, e.g. a proxy, can't exhaust the
+/// connections or the bandwidth of the host.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct OutboundLimits {
+    /// Most outbound connections open at once. Connections beyond are refused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u32>,
+    /// Most bytes per second sent and received over outbound connections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes_per_sec: Option<u64>,
+}
+
+impl OutboundLimits {
+    /// Returns whether no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_connections.is_none() && self.max_bytes_per_sec.is_none()
+    }
+}
+
+impl Component {
+    /// Creates a new component from its bytes with default resources and limits.
+    pub fn new(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new component pulled from an OCI registry when its workload
+    /// starts, with default resources and limits.
+    pub fn from_image(image: ComponentImage) -> Self {
+        Self {
+            image: Some(image),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the component from a file on the host when its workload starts,
+    /// see [`crate::host::hot_reload`].
+    pub fn with_local_path(mut self, path: impl Into<String>) -> Self {
+        self.local_path = Some(path.into());
+        self
+    }
+
+    /// Sets the local resources for the component.
+    pub fn with_local_resources(mut self, local_resources: LocalResources) -> Self {
+        self.local_resources = local_resources;
+        self
+    }
+
+    /// Adds a configuration value to the component's local resources.
+    pub fn with_config(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.local_resources.config.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds an environment variable to the component's local resources.
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.local_resources
+            .environment
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds the variables of a dotenv file to the component's environment.
+    pub fn with_env_file(mut self, env_file: EnvFile) -> Self {
+        self.local_resources.env_files.push(env_file);
+        self
+    }
+
+    /// Mounts a workload volume into the component.
+    pub fn with_volume_mount(mut self, volume_mount: VolumeMount) -> Self {
+        self.local_resources.volume_mounts.push(volume_mount);
+        self
+    }
+
+    /// Allows the component to make outgoing requests to the given host.
+    pub fn with_allowed_host(mut self, host: impl Into<String>) -> Self {
+        self.local_resources.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Limits the outgoing HTTP requests of the component.
+    pub fn with_outgoing_http_limits(mut self, limits: OutgoingHttpLimits) -> Self {
+        self.outgoing_http = limits;
+        self
+    }
+
+    /// Sets the number of warm instances to keep for the component.
+    pub fn with_pool_size(mut self, pool_size: i32) -> Self {
+        self.pool_size = pool_size;
+        self
+    }
+
+    /// Sets the maximum number of invocations for the component.
+    pub fn with_max_invocations(mut self, max_invocations: i32) -> Self {
+        self.max_invocations = max_invocations;
+        self
+    }
+
+    /// Scales the instances of the component with its load instead of its
+    /// `pool_size`.
+    pub fn with_autoscaling(mut self, autoscaling: Autoscaling) -> Self {
+        self.autoscaling = Some(autoscaling);
+        self
+    }
+
+    /// Keeps instances of the component initialized ahead of its
+    /// invocations.
+    pub fn with_pre_init(mut self, pre_init: PreInit) -> Self {
+        self.pre_init = Some(pre_init);
+        self
+    }
+
+    /// Makes this an init component that runs `export` once before the rest of
+    /// the workload serves, or [`DEFAULT_INIT_EXPORT`] if `None`.
+    pub fn with_init(mut self, export: Option<String>) -> Self {
+        self.kind = ComponentKind::Init;
+        self.init_export = export;
+        self
+    }
+
+    /// Makes this a job component that runs to completion as described by `job`.
+    pub fn with_job(mut self, job: Job) -> Self {
+        self.kind = ComponentKind::Job;
+        self.job = Some(job);
+        self
+    }
+
+    /// Makes this the middleware of its workload, handling HTTP requests
+    /// before the serving component.
+    pub fn with_middleware(mut self) -> Self {
+        self.kind = ComponentKind::Middleware;
+        self
+    }
 }
 
 /// Resource limits and configuration for a component or service.
 /// Defines memory, CPU limits, configuration values, and volume mounts.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct LocalResources {
+    /// Linear memory an instance may grow to in total, in MiB, where a
+    /// non-positive value means unlimited. An instance growing beyond it
+    /// traps, see [`crate::engine::memory`].
     pub memory_limit_mb: i32,
+    /// CPU in millicores, 1000 being one CPU. Only enforced by hosts with
+    /// [`cgroups`](crate::engine::cgroup), or with fuel, see
+    /// [`crate::engine::cpu`].
     pub cpu_limit: i32,
     /// Opaque key-value configuration shared between operator + runtime + plugins.
     /// Allows passing arbitrary configuration values to influence implementation behavior for all component interfaces.
@@ -75,8 +631,21 @@ pub struct LocalResources {
     pub config: HashMap<String, String>,
     // wasi:cli/env variables, copied to WasiCtxBuilder
     pub environment: HashMap<String, String>,
+    /// Dotenv files whose variables are added to `environment` when the
+    /// workload starts, see [`crate::host::env_file`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env_files: Vec<EnvFile>,
     pub volume_mounts: Vec<VolumeMount>,
+    /// Hosts, with optional ports, or URL patterns with optional methods,
+    /// outgoing HTTP requests may be sent to, and hosts prefixed with `!` they
+    /// may not be sent to. All hosts if none is allowed, unless the host
+    /// denies requests by default, see
+    /// [`EgressPolicy`](crate::host::http::EgressPolicy)
     pub allowed_hosts: Vec<String>,
+    /// Devices of the host the component needs exclusive access to, see
+    /// [`crate::host::devices`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceRequest>,
 }
 
 impl Default for LocalResources {
@@ -86,44 +655,194 @@ impl Default for LocalResources {
             cpu_limit: -1,
             config: HashMap::new(),
             environment: HashMap::new(),
+            env_files: Vec::new(),
             volume_mounts: Vec::new(),
             allowed_hosts: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+}
+
+/// Devices of a class a component needs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceRequest {
+    pub class: DeviceClass,
+    /// Number of devices, one by default
+    #[serde(default = "default_device_count")]
+    pub count: u32,
+}
+
+fn default_device_count() -> u32 {
+    1
+}
+
+/// A class of devices a host can give workloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceClass {
+    /// A GPU, e.g. for inference with `wasi:nn` or for `wasi:webgpu`
+    Gpu,
+    /// A serial port, e.g. of a sensor attached to an edge device
+    Serial,
+}
+
+impl std::fmt::Display for DeviceClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceClass::Gpu => write!(f, "gpu"),
+            DeviceClass::Serial => write!(f, "serial"),
+        }
+    }
+}
+
+impl std::str::FromStr for DeviceClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gpu" => Ok(DeviceClass::Gpu),
+            "serial" => Ok(DeviceClass::Serial),
+            _ => anyhow::bail!("unknown device class '{s}', expected gpu or serial"),
         }
     }
 }
 
 /// A named volume that can be mounted into components.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Volume {
     pub name: String,
     pub volume_type: VolumeType,
 }
 
-/// The type of volume - either host path or empty directory.
-#[derive(Debug, Clone, PartialEq)]
+/// Where a dotenv file of a component comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvFile {
+    /// A file on the host
+    HostPath(String),
+    /// A secret of the host's
+    /// [`SecretStore`](crate::host::interpolate::SecretStore) holding the file
+    Secret(String),
+}
+
+impl std::fmt::Display for EnvFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvFile::HostPath(path) => write!(f, "{path}"),
+            EnvFile::Secret(name) => write!(f, "secret '{name}'"),
+        }
+    }
+}
+
+/// The type of volume - a host path, an empty directory, a named volume
+/// managed by the host, or a volume the host prepares from an OCI artifact, an
+/// NFS export or an S3 bucket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum VolumeType {
     HostPath(HostPathVolume),
     EmptyDir(EmptyDirVolume),
+    Named(NamedVolume),
+    Oci(OciVolume),
+    Nfs(NfsVolume),
+    S3(S3Volume),
 }
 
 /// Describes how a volume should be mounted into a component.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// The limits apply to what all instances of the workload write through the
+/// mount, see [`crate::engine::volume`]. Writes exceeding them fail with the
+/// `quota` error code of `wasi:filesystem`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct VolumeMount {
     pub name: String,
     pub mount_path: String,
+    /// Mounts the volume without write access
+    #[serde(default)]
     pub read_only: bool,
+    /// Bytes the workload may write to files through the mount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes_written: Option<u64>,
+    /// Files and directories there may be under the mount, counting those
+    /// already in the volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u64>,
 }
 
 /// An ephemeral empty directory volume that exists for the lifetime of the workload.
-#[derive(Debug, Clone, PartialEq)]
-pub struct EmptyDirVolume {}
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EmptyDirVolume {
+    /// Where the directory is kept
+    #[serde(default)]
+    pub medium: StorageMedium,
+    /// Size the directory may grow to. Once reached, new instances mount the
+    /// volume read-only until files are removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_limit_bytes: Option<u64>,
+}
+
+/// Where an [`EmptyDirVolume`] is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageMedium {
+    /// The host's temporary directory
+    #[default]
+    Disk,
+    /// A memory-backed filesystem (`/dev/shm` on Linux), falling back to the
+    /// temporary directory where there is none
+    Memory,
+}
 
 /// A volume that mounts a directory from the host filesystem.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct HostPathVolume {
     pub local_path: String,
 }
 
+/// A volume that mounts a named volume created with
+/// [`crate::host::HostApi::volume_create`]. Its data outlives the workload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NamedVolume {
+    pub name: String,
+}
+
+/// A read-only volume prepopulated with the layers of an OCI artifact, e.g.
+/// `ghcr.io/org/models:v1`. Layers must be tar archives, optionally gzipped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OciVolume {
+    pub reference: String,
+}
+
+/// A volume backed by an NFS export, mounted on the host while the workload
+/// runs. Mounting requires the host to run with the privileges to do so.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NfsVolume {
+    pub server: String,
+    /// The exported path, e.g. `/exports/data`
+    pub path: String,
+    /// Mount options passed to `mount -o`, e.g. `nfsvers=4.1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+}
+
+/// A volume holding the objects under a prefix of an S3 bucket.
+///
+/// The objects are downloaded when the workload starts and, unless every mount
+/// of the volume is read-only, the files are uploaded back when it stops.
+/// Credentials are read from the host's `AWS_*` environment variables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct S3Volume {
+    pub bucket: String,
+    pub region: String,
+    /// Key prefix of the objects, e.g. `models/`
+    #[serde(default)]
+    pub prefix: String,
+    /// S3 compatible endpoint such as `http://localhost:9000`, using path-style
+    /// URLs. AWS is used when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
 /// Information about the host's current state and capabilities.
 /// Returned by [`crate::host::HostApi::heartbeat`].
 #[derive(Debug, Clone, PartialEq)]
@@ -147,6 +866,10 @@ pub struct HostHeartbeat {
     pub workload_count: u64,
     pub imports: Vec<WitInterface>,
     pub exports: Vec<WitInterface>,
+    /// Whether the host rejects new workloads because it is shutting down
+    pub draining: bool,
+    /// The devices of the host and the workloads holding them
+    pub devices: Vec<crate::host::devices::DeviceStatus>,
 }
 
 /// Status information about a workload including its ID, state, and any messages.
@@ -155,15 +878,154 @@ pub struct WorkloadStatus {
     pub workload_id: String,
     pub workload_state: WorkloadState,
     pub message: String,
+    /// What the workload used, when the host accounts for it with cgroups
+    pub usage: Option<WorkloadUsage>,
+    /// Where the components of the workload come from, its service first.
+    /// Empty until the workload started.
+    pub components: Vec<ComponentProvenance>,
+    /// What the workload is doing, `None` until it started
+    pub activity: Option<WorkloadActivity>,
+}
+
+/// What a started workload is doing, see
+/// [`ResolvedWorkload::activity`](crate::engine::workload::ResolvedWorkload::activity).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkloadActivity {
+    /// Number of components, not counting the service
+    pub component_count: u64,
+    /// Time since the workload started
+    pub uptime: std::time::Duration,
+    /// Invocations of the components since the workload started
+    pub invocations: u64,
+    /// Invocations that returned an error or trapped
+    pub failed_invocations: u64,
+    /// Invocations running now
+    pub in_flight_invocations: u64,
+    /// Time the finished invocations took in total, the mean latency is this
+    /// divided by `invocations - in_flight_invocations`
+    pub invocation_time: std::time::Duration,
+    /// Fuel the finished invocations consumed in total, 0 unless the engine
+    /// consumes fuel
+    pub fuel: u64,
+    /// Time the guests of the components slept to keep to their `cpu_limit`,
+    /// see [`crate::engine::cpu`]
+    pub cpu_throttled: std::time::Duration,
+    /// Error of the last failed invocation
+    pub last_error: Option<String>,
+    /// Execution pools the components run on
+    pub pools: Vec<PoolUtilization>,
+    /// HTTP routes sending requests to the workload
+    pub routes: Vec<crate::host::http::RouteEntry>,
+    /// Host interfaces the components were bound to plugins for
+    pub interfaces: Vec<InterfaceBinding>,
+    /// Components failing over and over, see [`crate::engine::crash_loop`]
+    pub crash_loops: Vec<CrashLoopStatus>,
+}
+
+/// How busy an execution pool is, see [`crate::engine::pool`]. Pools are
+/// shared by the workloads assigned to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolUtilization {
+    pub name: String,
+    /// Number of worker threads
+    pub threads: u64,
+    /// Invocations running on the pool, of all its workloads
+    pub busy: u64,
+}
+
+/// A component crash looping, see [`crate::engine::crash_loop`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashLoopStatus {
+    pub component_id: String,
+    /// Times the component failed in a row
+    pub consecutive_failures: u32,
+    /// Time until the component is attempted again, zero while it is
+    pub retry_after: std::time::Duration,
+}
+
+/// A host interface a component was bound to a plugin for. The plugin may
+/// provide a different version than the component requested, as long as the
+/// two are compatible, see [`WitInterface::version`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceBinding {
+    pub component_id: String,
+    pub plugin_id: String,
+    /// The interface as requested by the workload, e.g. `wasi:keyvalue/store@0.2.0-draft`
+    pub requested: String,
+    /// The version of the interface the plugin provides, if versioned
+    pub version: Option<semver::Version>,
+}
+
+/// CPU time of a workload as accounted by the kernel, see
+/// [`crate::engine::cgroup`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkloadUsage {
+    /// CPU time used by the components with a CPU limit, in microseconds
+    pub cpu_usage_us: u64,
+    /// Time the components were throttled for exceeding their CPU limit, in
+    /// microseconds
+    pub cpu_throttled_us: u64,
+    /// Number of periods the components were throttled in
+    pub cpu_throttled_periods: u64,
+}
+
+/// Where a component of a workload comes from, read from the component when
+/// the workload starts, see [`crate::engine::provenance`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentProvenance {
+    /// The id of the component in its workload
+    pub component_id: String,
+    /// Hex SHA-256 digest of the component bytes, see
+    /// [`crate::engine::component_digest`]
+    pub digest: String,
+    pub size_bytes: u64,
+    /// The version the component was released as, e.g. `1.4.2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The revision the component was built from, e.g. a commit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Where the source of the component is, e.g. the URL of its repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authors: Option<String>,
+    /// SPDX license expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub licenses: Option<String>,
+    /// The languages, tools and SDKs that produced the component
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub producers: Vec<ComponentProducer>,
+}
+
+/// A language, tool or SDK that produced a component, from its `producers`
+/// section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentProducer {
+    /// `language`, `processed-by` or `sdk`
+    pub field: String,
+    pub name: String,
+    /// Empty when the producer didn't record its version
+    pub version: String,
 }
 
 /// Request to start a new workload on the host.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct WorkloadStartRequest {
     pub workload_id: String,
     pub workload: Workload,
 }
 
+impl WorkloadStartRequest {
+    /// Creates a request to start the given workload under a new random workload ID.
+    pub fn new(workload: Workload) -> Self {
+        Self {
+            workload_id: uuid::Uuid::new_v4().to_string(),
+            workload,
+        }
+    }
+}
+
 /// Response after attempting to start a workload.
 #[derive(Debug, Clone, PartialEq)]
 pub struct WorkloadStartResponse {
@@ -171,9 +1033,11 @@ pub struct WorkloadStartResponse {
 }
 
 /// Request to get the status of a specific workload.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct WorkloadStatusRequest {
     pub workload_id: String,
+    /// Only find the workload in this namespace
+    pub namespace: Option<String>,
 }
 
 /// Response containing the status of a requested workload.
@@ -182,6 +1046,23 @@ pub struct WorkloadStatusResponse {
     pub workload_status: WorkloadStatus,
 }
 
+/// Request to list the workloads of a host.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkloadListRequest {
+    /// Only list workloads in this namespace
+    pub namespace: Option<String>,
+}
+
+/// A workload as listed by a host, see [`WorkloadListRequest`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadSummary {
+    /// Empty while the workload is starting or stopping
+    pub namespace: String,
+    /// Empty while the workload is starting or stopping
+    pub name: String,
+    pub workload_status: WorkloadStatus,
+}
+
 /// Request to stop a running workload.
 #[derive(Debug, Clone, PartialEq)]
 pub struct WorkloadStopRequest {
@@ -193,3 +1074,630 @@ pub struct WorkloadStopRequest {
 pub struct WorkloadStopResponse {
     pub workload_status: WorkloadStatus,
 }
+
+/// Request to replace a running workload with a new version, see
+/// [`crate::host::HostApi::workload_update`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadUpdateRequest {
+    /// The running workload to replace
+    pub workload_id: String,
+    /// The new version, started under its own workload ID. It must have the
+    /// namespace and name of the workload it replaces.
+    pub replacement: WorkloadStartRequest,
+    /// How long the invocations of the old version may take to finish before
+    /// it is stopped anyway, [`DEFAULT_UPDATE_DRAIN_TIMEOUT`] if `None`. A
+    /// rolling update waits as long for each of its steps.
+    pub drain_timeout: Option<std::time::Duration>,
+    /// Replaces the instances of the component serving HTTP step by step
+    /// instead of all at once, see [`RollingUpdate`]
+    pub rolling_update: Option<RollingUpdate>,
+}
+
+/// How long a workload replaced by [`WorkloadUpdateRequest`] drains by default
+pub const DEFAULT_UPDATE_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Response after replacing a workload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadUpdateResponse {
+    /// The status of the new version
+    pub workload_status: WorkloadStatus,
+    /// Invocations of the old version still running when the drain timeout
+    /// passed and it was stopped
+    pub abandoned_invocations: u64,
+}
+
+/// Request to call an export of a component in a running workload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadInvokeRequest {
+    pub workload_id: String,
+    /// The component to call. May be omitted when the workload has a single
+    /// component.
+    pub component_id: Option<String>,
+    /// The function to call, given as a function name or `interface#function`,
+    /// e.g. `wasi:cli/run@0.2.0#run`
+    pub export: String,
+    pub params: Vec<wasmtime::component::Val>,
+}
+
+/// The values returned by an invoked export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadInvokeResponse {
+    pub results: Vec<wasmtime::component::Val>,
+}
+
+/// Changes to the configuration a component reads through
+/// `wasi:config/store`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigUpdate {
+    /// Keys to set, replacing their values. Values may reference environment
+    /// variables and secrets like the config of the interface, see
+    /// [`crate::host::interpolate`].
+    pub set: HashMap<String, String>,
+    /// Keys to remove, applied before `set`
+    pub remove: Vec<String>,
+}
+
+impl ConfigUpdate {
+    /// Returns whether the update changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+}
+
+/// Request to change the configuration of a running workload without
+/// restarting it, see [`crate::host::HostApi::workload_config_update`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadConfigUpdateRequest {
+    pub workload_id: String,
+    /// The component to update, every component of the workload reading
+    /// configuration if `None`
+    pub component_id: Option<String>,
+    pub update: ConfigUpdate,
+}
+
+/// Response after changing the configuration of a workload.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkloadConfigUpdateResponse {
+    /// The components whose configuration changed
+    pub component_ids: Vec<String>,
+    /// Whether the service instance was replaced to read the new
+    /// configuration, see [`crate::engine::CONFIG_CHANGE_CONFIG_KEY`]
+    pub service_recycled: bool,
+}
+
+/// Request to compile a component ahead of the workloads using it, see
+/// [`crate::host::HostApi::component_stage`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentStageRequest {
+    /// What the component is staged under, usually its OCI image reference
+    pub reference: String,
+    pub bytes: Bytes,
+}
+
+/// A component compiled ahead of the workloads using it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedComponent {
+    pub reference: String,
+    /// The digest of the compiled component, see
+    /// [`crate::engine::component_digest`]
+    pub digest: String,
+    pub bytes: Bytes,
+    pub staged_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Request to create a named volume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeCreateRequest {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+}
+
+/// Request to inspect a named volume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeInspectRequest {
+    pub name: String,
+}
+
+/// Request to delete a named volume and its data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeDeleteRequest {
+    pub name: String,
+}
+
+/// A named volume managed by the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeInfo {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Where the volume data is kept on the host
+    pub path: std::path::PathBuf,
+    /// Total size of the files in the volume
+    pub size_bytes: u64,
+    /// The workloads that mount the volume
+    pub workloads: Vec<String>,
+}
+
+/// Request to interrupt a live component instance and dump its core, see
+/// [`crate::host::HostApi::workload_instance_dump`].
+#[cfg(feature = "instance-debug")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadInstanceDumpRequest {
+    pub workload_id: String,
+    /// The instance, as listed by [`crate::host::HostApi::workload_instances`]
+    pub instance_id: u64,
+}
+
+/// Request to remove the pin of a host and path prefix, see
+/// [`crate::host::http::RoutePin`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteUnpinRequest {
+    pub host: String,
+    /// The path prefix of the pin, `None` for the pin of all paths
+    pub path: Option<String>,
+}
+
+/// Request to release the hold of a host and path prefix, see
+/// [`crate::host::http::RouteHold`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteReleaseRequest {
+    pub host: String,
+    /// The path prefix of the hold, `None` for the hold of all paths
+    pub path: Option<String>,
+}
+
+/// Request to drop the cached responses of a host and path prefix, see
+/// [`crate::host::http::cache`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteInvalidateRequest {
+    /// The host of the requests, `None` for every host
+    pub host: Option<String>,
+    /// The path prefix of the requests, `None` for all paths
+    pub path: Option<String>,
+}
+
+/// Request to replay the HTTP requests recorded in a file against a
+/// workload, see [`crate::host::http::recording`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayRequest {
+    pub workload_id: String,
+    /// The recording, as written by
+    /// [`crate::host::HostApi::http_recording_start`]
+    pub path: std::path::PathBuf,
+    /// Whether requests are spaced like they were recorded, rather than sent
+    /// as soon as the previous one completed
+    pub pace: bool,
+}
+
+/// The live state of the HTTP server of a host, see
+/// [`crate::host::HostApi::http_introspect`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpIntrospection {
+    /// The routes, pins and holds requests are matched against
+    pub routing: RoutingTable,
+    /// The requests in flight summed up by route, see
+    /// [`crate::host::http::inflight::route_activity`]
+    pub activity: Vec<crate::host::http::RouteActivity>,
+    /// The requests in flight, the oldest first
+    pub requests: Vec<crate::host::http::InFlightRequest>,
+    pub open_connections: usize,
+}
+
+/// The HTTP routes of a host and the pins and holds overriding them, in the
+/// order requests are matched against them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoutingTable {
+    pub routes: Vec<crate::host::http::RouteEntry>,
+    pub pins: Vec<crate::host::http::RoutePin>,
+    pub holds: Vec<crate::host::http::RouteHold>,
+}
+
+/// The addresses the HTTP server of a host listens on and the certificate
+/// files of its TLS listeners.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpListeners {
+    pub addrs: Vec<std::net::SocketAddr>,
+    /// `None` if the listeners don't use TLS
+    pub tls: Option<crate::host::http::TlsFiles>,
+}
+
+/// A bound on instances during a rolling update, either an absolute number or a
+/// percentage of the pool size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutLimit {
+    Count(u32),
+    Percent(u8),
+}
+
+impl RolloutLimit {
+    /// Resolves the limit for a pool of `pool_size` instances. Percentages are
+    /// rounded up when `round_up` is set and down otherwise.
+    fn resolve(&self, pool_size: u32, round_up: bool) -> u32 {
+        match *self {
+            RolloutLimit::Count(count) => count,
+            RolloutLimit::Percent(percent) => {
+                let scaled = u64::from(pool_size) * u64::from(percent.min(100));
+                let resolved = if round_up {
+                    scaled.div_ceil(100)
+                } else {
+                    scaled / 100
+                };
+                resolved as u32
+            }
+        }
+    }
+}
+
+/// Replaces the instances of a workload gradually so it keeps serving traffic
+/// while it is updated, see [`crate::host::HostApi::workload_update`].
+///
+/// At most `max_surge` instances beyond the pool size run at any time, and at
+/// most `max_unavailable` of the pool may be down. Both default to 25%, with the
+/// surge rounded up and the unavailability rounded down.
+///
+/// The pool is the [`Autoscaling`] of the component serving HTTP in the
+/// version being replaced, of `max_instances` instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RollingUpdate {
+    pub max_surge: RolloutLimit,
+    pub max_unavailable: RolloutLimit,
+}
+
+impl Default for RollingUpdate {
+    fn default() -> Self {
+        Self {
+            max_surge: RolloutLimit::Percent(25),
+            max_unavailable: RolloutLimit::Percent(25),
+        }
+    }
+}
+
+/// One step of a rolling update: start `start` instances of the new version,
+/// wait for them to become ready, then stop `stop` instances of the old one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutStep {
+    pub start: u32,
+    pub stop: u32,
+}
+
+impl RollingUpdate {
+    /// Returns the steps that replace a pool of `pool_size` instances.
+    ///
+    /// If both limits resolve to zero, one instance is allowed to be unavailable
+    /// so the update can make progress.
+    pub fn plan(&self, pool_size: u32) -> Vec<RolloutStep> {
+        let surge = self.max_surge.resolve(pool_size, true);
+        let unavailable = match self.max_unavailable.resolve(pool_size, false) {
+            0 if surge == 0 => 1,
+            unavailable => unavailable,
+        }
+        .min(pool_size);
+        let max_running = pool_size + surge;
+        let min_available = pool_size - unavailable;
+
+        let (mut old, mut new) = (pool_size, 0);
+        let mut steps = Vec::new();
+        while new < pool_size || old > 0 {
+            let start = (pool_size - new).min(max_running - (old + new));
+            new += start;
+            let stop = old.min((old + new).saturating_sub(min_available));
+            old -= stop;
+            steps.push(RolloutStep { start, stop });
+        }
+        steps
+    }
+}
+
+/// Generates the JSON Schema for the [`Workload`] wire format.
+pub fn workload_json_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(Workload)
+}
+
+/// Serializes component bytes as base64 strings so workloads can be embedded in
+/// JSON or YAML manifests.
+pub(crate) mod base64_bytes {
+    use base64::Engine as _;
+    use base64::engine::general_purpose::STANDARD;
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workload_builder_defaults() {
+        let workload = Workload::builder("test", "empty").build();
+        assert_eq!(workload.namespace, "test");
+        assert_eq!(workload.name, "empty");
+        assert!(workload.annotations.is_empty());
+        assert!(workload.service.is_none());
+        assert!(workload.components.is_empty());
+        assert!(workload.host_interfaces.is_empty());
+        assert!(workload.volumes.is_empty());
+    }
+
+    #[test]
+    fn test_workload_builder_http_route() {
+        let workload = Workload::builder("test", "http")
+            .with_component(Component::new(Bytes::from_static(b"component")).with_pool_size(2))
+            .with_http_route("localhost", "/api")
+            .with_host_interface("wasi:keyvalue/store@0.2.0-draft")
+            .build();
+
+        assert_eq!(workload.components.len(), 1);
+        assert_eq!(workload.components[0].pool_size, 2);
+        assert_eq!(workload.host_interfaces.len(), 2);
+
+        let http = workload
+            .host_interfaces
+            .iter()
+            .find(|i| i.package == "http")
+            .expect("http interface should be requested");
+        assert!(http.interfaces.contains("incoming-handler"));
+        assert_eq!(
+            http.config.get("host").map(String::as_str),
+            Some("localhost")
+        );
+        assert_eq!(http.config.get("path").map(String::as_str), Some("/api"));
+    }
+
+    #[test]
+    fn test_workload_builder_merges_interfaces() {
+        let workload = Workload::builder("test", "merge")
+            .with_host_interface("wasi:keyvalue/store@0.2.0-draft")
+            .with_host_interface("wasi:keyvalue/atomics@0.2.0-draft")
+            .build();
+
+        assert_eq!(workload.host_interfaces.len(), 1);
+        let keyvalue = &workload.host_interfaces[0];
+        assert!(keyvalue.interfaces.contains("store"));
+        assert!(keyvalue.interfaces.contains("atomics"));
+    }
+
+    #[test]
+    fn test_component_builder() {
+        let component = Component::new(Bytes::from_static(b"component"))
+            .with_config("key", "value")
+            .with_env("RUST_LOG", "debug")
+            .with_allowed_host("example.com")
+            .with_max_invocations(10);
+
+        assert_eq!(component.local_resources.memory_limit_mb, -1);
+        assert_eq!(
+            component
+                .local_resources
+                .config
+                .get("key")
+                .map(String::as_str),
+            Some("value")
+        );
+        assert_eq!(
+            component
+                .local_resources
+                .environment
+                .get("RUST_LOG")
+                .map(String::as_str),
+            Some("debug")
+        );
+        assert_eq!(component.local_resources.allowed_hosts, vec!["example.com"]);
+        assert_eq!(component.max_invocations, 10);
+    }
+
+    #[test]
+    fn test_workload_serde_round_trip() {
+        let workload = Workload::builder("test", "serde")
+            .with_component(
+                Component::new(Bytes::from_static(b"\0asm")).with_volume_mount(VolumeMount {
+                    name: "data".to_string(),
+                    mount_path: "/data".to_string(),
+                    read_only: true,
+                    max_bytes_written: Some(1 << 20),
+                    max_files: Some(100),
+                }),
+            )
+            .with_volume(Volume {
+                name: "data".to_string(),
+                volume_type: VolumeType::EmptyDir(EmptyDirVolume {
+                    medium: StorageMedium::Memory,
+                    size_limit_bytes: Some(1 << 20),
+                }),
+            })
+            .with_http_route("localhost", "/api")
+            .build();
+
+        let json = serde_json::to_value(&workload).unwrap();
+        assert_eq!(json["components"][0]["bytes"], "AGFzbQ==");
+        assert!(json["volumes"][0]["volume_type"]["empty_dir"].is_object());
+
+        let parsed: Workload = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, workload);
+    }
+
+    #[test]
+    fn test_workload_deserialize_defaults() {
+        let workload: Workload = serde_json::from_str(
+            r#"{
+                "namespace": "test",
+                "name": "minimal",
+                "components": [{ "bytes": "AGFzbQ==" }],
+                "host_interfaces": [{ "namespace": "wasi", "package": "http", "interfaces": ["incoming-handler"], "version": "0.2.2" }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(workload.components[0].bytes, Bytes::from_static(b"\0asm"));
+        assert_eq!(
+            workload.components[0].local_resources,
+            LocalResources::default()
+        );
+        assert_eq!(
+            workload.host_interfaces[0].version,
+            Some(semver::Version::new(0, 2, 2))
+        );
+        assert!(workload.host_interfaces[0].config.is_empty());
+        assert_eq!(workload.components[0].kind, ComponentKind::Serving);
+    }
+
+    #[test]
+    fn test_init_component() {
+        let component: Component = serde_json::from_str(
+            r#"{ "bytes": "AGFzbQ==", "kind": "init", "init_export": "migrate" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            component,
+            Component::new(Bytes::from_static(b"\0asm")).with_init(Some("migrate".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_image_component() {
+        let component: Component = serde_json::from_str(
+            r#"{ "image": { "reference": "oci://ghcr.io/org/component:v1", "digest": "sha256:abcd",
+                 "credentials": { "token": { "token": "ghp_token" } } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            component,
+            Component::from_image(
+                ComponentImage::new("oci://ghcr.io/org/component:v1")
+                    .with_digest("sha256:abcd")
+                    .with_credentials(RegistryCredentials::Token {
+                        token: "ghp_token".to_string()
+                    })
+            )
+        );
+        assert!(component.bytes.is_empty());
+    }
+
+    #[test]
+    fn test_middleware_component() {
+        let component: Component =
+            serde_json::from_str(r#"{ "bytes": "AGFzbQ==", "kind": "middleware" }"#).unwrap();
+        assert_eq!(
+            component,
+            Component::new(Bytes::from_static(b"\0asm")).with_middleware()
+        );
+    }
+
+    #[test]
+    fn test_outgoing_http_limits() {
+        let component: Component = serde_json::from_str(
+            r#"{ "bytes": "AGFzbQ==", "outgoing_http": { "timeout_ms": 5000, "max_redirects": 3 } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            component.outgoing_http,
+            OutgoingHttpLimits {
+                timeout_ms: Some(5000),
+                max_response_bytes: None,
+                max_redirects: 3,
+            }
+        );
+
+        // Unlimited components serialize as before
+        let json = serde_json::to_value(Component::new(Bytes::from_static(b"\0asm"))).unwrap();
+        assert!(json.get("outgoing_http").is_none());
+    }
+
+    #[test]
+    fn test_outbound_limits() {
+        let workload: Workload = serde_json::from_str(
+            r#"{ "namespace": "default", "name": "proxy", "outbound": { "max_connections": 16 } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            workload,
+            Workload::builder("default", "proxy")
+                .with_outbound_limits(OutboundLimits {
+                    max_connections: Some(16),
+                    max_bytes_per_sec: None,
+                })
+                .build()
+        );
+
+        let json = serde_json::to_value(Workload::builder("default", "proxy").build()).unwrap();
+        assert!(json.get("outbound").is_none());
+    }
+
+    #[test]
+    fn test_job_component() {
+        let component: Component = serde_json::from_str(
+            r#"{ "bytes": "AGFzbQ==", "kind": "job", "job": { "completions": 3, "max_retries": 2 } }"#,
+        )
+        .unwrap();
+        assert_eq!(component.kind, ComponentKind::Job);
+        assert_eq!(
+            component.job,
+            Some(Job {
+                completions: 3,
+                max_retries: 2,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_workload_json_schema() {
+        let schema = serde_json::to_value(workload_json_schema()).unwrap();
+        let properties = &schema["properties"];
+        for field in [
+            "namespace",
+            "name",
+            "components",
+            "host_interfaces",
+            "volumes",
+        ] {
+            assert!(properties.get(field).is_some(), "missing {field} in schema");
+        }
+        assert_eq!(schema["required"], serde_json::json!(["name", "namespace"]));
+        assert_eq!(
+            schema["definitions"]["Component"]["properties"]["bytes"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_rolling_update_plan() {
+        let step = |start, stop| RolloutStep { start, stop };
+
+        // 25% of 10: surge rounds up to 3, unavailability down to 2
+        assert_eq!(
+            RollingUpdate::default().plan(10),
+            vec![step(3, 5), step(5, 5), step(2, 0)]
+        );
+
+        // Without surge, old instances are stopped before new ones start
+        let no_surge = RollingUpdate {
+            max_surge: RolloutLimit::Count(0),
+            max_unavailable: RolloutLimit::Count(1),
+        };
+        assert_eq!(no_surge.plan(2), vec![step(0, 1), step(1, 1), step(1, 0)]);
+
+        // Zero for both limits still makes progress
+        let stuck = RollingUpdate {
+            max_surge: RolloutLimit::Percent(0),
+            max_unavailable: RolloutLimit::Percent(0),
+        };
+        let steps = stuck.plan(3);
+        assert_eq!(steps.iter().map(|s| s.start).sum::<u32>(), 3);
+        assert_eq!(steps.iter().map(|s| s.stop).sum::<u32>(), 3);
+
+        assert!(RollingUpdate::default().plan(0).is_empty());
+    }
+}
@@ -0,0 +1,840 @@
+//! Routes of the workloads serving `wasi:http/incoming-handler`: the requests
+//! a route matches and the policies the HTTP server applies to them, see
+//! [`crate::host::http`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as _, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::wit::WitInterface;
+
+pub mod auth;
+pub mod cache;
+pub mod experiment;
+pub mod health;
+pub mod negotiate;
+pub mod rate_limit;
+pub mod request_limits;
+pub use auth::RouteAuth;
+pub use cache::RouteCache;
+pub use experiment::RouteExperiment;
+pub use health::{HealthProbe, RouteHealthCheck};
+pub use rate_limit::{RateLimitKey, RouteRateLimit};
+pub use request_limits::RequestLimits;
+
+/// Config key for the `Host` header a workload is routed by
+pub const HOST_CONFIG_KEY: &str = "host";
+/// Config key for the path prefix a workload is routed by
+pub const PATH_CONFIG_KEY: &str = "path";
+/// Config key for the comma-separated list of HTTP methods a workload accepts
+pub const METHODS_CONFIG_KEY: &str = "methods";
+/// Config key for the comma-separated `name:value` or `name` header
+/// predicates requests must match, see [`RouteHeaderMatch`]
+pub const HEADERS_CONFIG_KEY: &str = "headers";
+/// Config key for the request timeout of a workload, in milliseconds
+pub const TIMEOUT_MS_CONFIG_KEY: &str = "timeout_ms";
+/// Config key for the maximum time between two chunks of a streamed response, in milliseconds
+pub const STREAM_IDLE_TIMEOUT_MS_CONFIG_KEY: &str = "stream_idle_timeout_ms";
+/// Config key for the maximum duration of a streamed response, in milliseconds
+pub const STREAM_MAX_DURATION_MS_CONFIG_KEY: &str = "stream_max_duration_ms";
+/// Config key for the time after which a quiet event stream is sent a keepalive comment, in milliseconds
+pub const SSE_KEEPALIVE_MS_CONFIG_KEY: &str = "sse_keepalive_ms";
+/// Config key for the largest request body a workload accepts, in bytes
+pub const MAX_BODY_BYTES_CONFIG_KEY: &str = "max_body_bytes";
+/// Config key for the largest request body the host reads before invoking the
+/// component, in bytes, see [`spool`](crate::host::http::spool)
+pub const SPOOL_MAX_BYTES_CONFIG_KEY: &str = "spool_max_bytes";
+/// Config key for the `namespace/name` of a workload that receives a copy of each request
+pub const SHADOW_CONFIG_KEY: &str = "shadow";
+/// Config key set to `true` for a workload that accepts WebSocket connections,
+/// see [`websocket`](crate::host::http::websocket)
+pub const WEBSOCKET_CONFIG_KEY: &str = "websocket";
+/// Config key for the weight of a workload among those splitting a route, see
+/// [`split`](crate::host::http::split)
+pub const WEIGHT_CONFIG_KEY: &str = "weight";
+
+/// Typed routing configuration for a workload that exports `wasi:http/incoming-handler`.
+///
+/// Routes are still transported as the string `config` map on the requested
+/// [`WitInterface`] for compatibility. Use [`HttpRouteConfig::try_from`] to parse
+/// and validate that map and [`HttpRouteConfig::to_config`] to produce it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct HttpRouteConfig {
+    /// The `Host` header values routed to the workload, separated by commas.
+    /// A value may be a wildcard like `*.example.com`, see the
+    /// [HTTP server docs](crate::host::http#virtual-hosts).
+    pub host: String,
+    /// Optional path prefix that requests must match, e.g. `/api`. Segments
+    /// such as `{id}` match any segment, captured as a path parameter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// HTTP methods accepted by the route. An empty list accepts all methods.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    /// Headers requests must have to match the route. An empty list matches
+    /// all requests.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<RouteHeaderMatch>,
+    /// Media types the component produces, chosen between by the `Accept`
+    /// header of requests, see [`negotiate`]. An empty list serves requests
+    /// no other route on the same path is acceptable for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub produces: Vec<String>,
+    /// Maximum time in milliseconds the component may take to produce a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Maximum time in milliseconds between two chunks of a streamed response body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_idle_timeout_ms: Option<u64>,
+    /// Maximum time in milliseconds to stream a whole response body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_max_duration_ms: Option<u64>,
+    /// Time in milliseconds after which the host sends a comment on an event
+    /// stream the component hasn't written to, see [`sse`](crate::host::http::sse)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sse_keepalive_ms: Option<u64>,
+    /// Largest request body accepted, in bytes. Requests must then declare a
+    /// `Content-Length`, and larger ones are rejected before the body is sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    /// Most headers a request may have, see [`request_limits`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_headers: Option<u64>,
+    /// Largest size of the headers of a request, in bytes, see [`request_limits`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_header_bytes: Option<u64>,
+    /// Longest time in milliseconds a client may send nothing while the body
+    /// of its request is read, see [`request_limits`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_read_timeout_ms: Option<u64>,
+    /// Largest request body the host reads, spilling it to disk, before
+    /// invoking the component, in bytes. Larger bodies are rejected. Request
+    /// bodies are streamed to the component if not set, see [`spool`](crate::host::http::spool).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spool_max_bytes: Option<u64>,
+    /// Authentication requests must pass before reaching the component
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<RouteAuth>,
+    /// The `namespace/name` of a workload that receives a copy of every request
+    /// matching this route. Its responses are discarded, so a new version of a
+    /// component can be validated against real traffic before it is promoted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<String>,
+    /// The share of the requests of the route the workload gets, relative
+    /// to the other workloads serving it with a weight, see [`split`](crate::host::http::split)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    /// The A/B experiment this workload takes part in on this route
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<RouteExperiment>,
+    /// The check requests are only routed to the workload while it passes,
+    /// see [`health`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<RouteHealthCheck>,
+    /// Whether the route accepts WebSocket connections, exchanging their
+    /// messages with the component through the request and response bodies,
+    /// see [`websocket`](crate::host::http::websocket)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub websocket: bool,
+    /// The requests per second each client may send, see [`rate_limit`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RouteRateLimit>,
+    /// Caching of the responses of the component, see [`cache`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<RouteCache>,
+}
+
+impl HttpRouteConfig {
+    /// Creates a route for the given `Host` header that accepts all paths and methods.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Also routes requests for `host` to the workload. Can be called
+    /// multiple times.
+    pub fn with_host(mut self, host: impl AsRef<str>) -> Self {
+        if self.host.is_empty() {
+            self.host = host.as_ref().to_string();
+        } else {
+            self.host = format!("{},{}", self.host, host.as_ref());
+        }
+        self
+    }
+
+    /// Returns the hosts, or wildcards, the route serves.
+    pub fn hosts(&self) -> impl Iterator<Item = &str> {
+        self.host.split(',').map(str::trim)
+    }
+
+    /// Restricts the route to requests whose path starts with `path`.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Restricts the route to the given HTTP method. Can be called multiple times.
+    pub fn with_method(mut self, method: impl AsRef<str>) -> Self {
+        self.methods.push(method.as_ref().to_ascii_uppercase());
+        self
+    }
+
+    /// Restricts the route to requests with the header `name` set to `value`.
+    /// Can be called multiple times.
+    pub fn with_header(mut self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+        self.headers.push(RouteHeaderMatch {
+            name: name.as_ref().to_ascii_lowercase(),
+            value: Some(value.into()),
+        });
+        self
+    }
+
+    /// Restricts the route to requests with the header `name`, whatever its
+    /// value. Can be called multiple times.
+    pub fn with_header_present(mut self, name: impl AsRef<str>) -> Self {
+        self.headers.push(RouteHeaderMatch {
+            name: name.as_ref().to_ascii_lowercase(),
+            value: None,
+        });
+        self
+    }
+
+    /// Declares a media type the component produces. Can be called multiple
+    /// times.
+    pub fn with_produces(mut self, media_type: impl AsRef<str>) -> Self {
+        self.produces
+            .push(media_type.as_ref().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Sets the maximum time the component may take to produce a response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Requires requests to pass the given authentication scheme.
+    pub fn with_auth(mut self, auth: RouteAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Mirrors every request matching the route to the workload `namespace/name`.
+    pub fn with_shadow(mut self, namespace: impl AsRef<str>, name: impl AsRef<str>) -> Self {
+        self.shadow = Some(format!("{}/{}", namespace.as_ref(), name.as_ref()));
+        self
+    }
+
+    /// Shares the route with other workloads by weight, see [`split`](crate::host::http::split).
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Serves the route as one variant of an A/B experiment.
+    pub fn with_experiment(mut self, experiment: RouteExperiment) -> Self {
+        self.experiment = Some(experiment);
+        self
+    }
+
+    /// Only routes requests to the workload while it passes `check`, see
+    /// [`health`].
+    pub fn with_health_check(mut self, check: RouteHealthCheck) -> Self {
+        self.health_check = Some(check);
+        self
+    }
+
+    /// Accepts WebSocket connections on the route, see [`websocket`](crate::host::http::websocket).
+    pub fn with_websocket(mut self) -> Self {
+        self.websocket = true;
+        self
+    }
+
+    /// Limits the requests each client sends the route, see [`rate_limit`].
+    pub fn with_rate_limit(mut self, limit: RouteRateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Caches the responses of the component as `cache` says, see [`cache`].
+    pub fn with_cache(mut self, cache: RouteCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Aborts streamed response bodies that stall for longer than `idle_timeout`
+    /// between chunks or take longer than `max_duration` in total.
+    pub fn with_stream_limits(
+        mut self,
+        idle_timeout: Option<Duration>,
+        max_duration: Option<Duration>,
+    ) -> Self {
+        self.stream_idle_timeout_ms = idle_timeout.map(|t| t.as_millis() as u64);
+        self.stream_max_duration_ms = max_duration.map(|t| t.as_millis() as u64);
+        self
+    }
+
+    /// Sends a keepalive comment on event streams the component hasn't
+    /// written to for `interval`, see [`sse`](crate::host::http::sse).
+    pub fn with_sse_keepalive(mut self, interval: Duration) -> Self {
+        self.sse_keepalive_ms = Some(interval.as_millis() as u64);
+        self
+    }
+
+    /// Rejects requests with bodies larger than `max` bytes.
+    pub fn with_max_body_bytes(mut self, max: u64) -> Self {
+        self.max_body_bytes = Some(max);
+        self
+    }
+
+    /// Rejects requests with more than `max_headers` headers, or headers
+    /// larger than `max_bytes`, see [`request_limits`].
+    pub fn with_header_limits(mut self, max_headers: u64, max_bytes: u64) -> Self {
+        self.max_headers = Some(max_headers);
+        self.max_header_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Rejects requests whose client sends nothing for `timeout` while their
+    /// body is read, see [`request_limits`].
+    pub fn with_body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.body_read_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Has the host read request bodies of up to `max_bytes`, spilling them to
+    /// disk, before invoking the component, see
+    /// [`spool`](crate::host::http::spool). Larger bodies are rejected.
+    pub fn with_body_spooling(mut self, max_bytes: u64) -> Self {
+        self.spool_max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Returns the request limits this route sets, see [`request_limits`].
+    pub fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            max_body_bytes: self.max_body_bytes,
+            max_headers: self.max_headers,
+            max_header_bytes: self.max_header_bytes,
+            header_read_timeout_ms: None,
+            body_read_timeout_ms: self.body_read_timeout_ms,
+        }
+    }
+
+    /// Returns the request timeout for this route, if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Validates the route configuration.
+    ///
+    /// # Errors
+    /// Returns an error if a host is empty or an invalid wildcard, the path
+    /// does not start with `/` or has invalid parameters, a method is not a valid HTTP method, a
+    /// produced media type is invalid, the timeout or a limit is zero, or the
+    /// authentication scheme or health check is invalid.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for host in self.hosts() {
+            ensure!(!host.is_empty(), "route host must not be empty");
+            ensure!(
+                !host.contains(char::is_whitespace),
+                "route host '{host}' must not contain whitespace"
+            );
+            let name = host.strip_prefix("*.").unwrap_or(host);
+            ensure!(
+                !name.is_empty() && !name.contains('*'),
+                "route host '{host}' may only be a wildcard as '*.domain'"
+            );
+        }
+        if let Some(path) = &self.path {
+            ensure!(
+                path.starts_with('/'),
+                "route path '{path}' must start with '/'"
+            );
+            let mut params = Vec::new();
+            for segment in path.split('/') {
+                match path_param(segment) {
+                    Some(name) => {
+                        ensure!(
+                            !name.is_empty()
+                                && name
+                                    .chars()
+                                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                            "route path parameter '{segment}' must be a name of letters, digits, '_' and '-'"
+                        );
+                        ensure!(
+                            !params.contains(&name),
+                            "route path parameter '{segment}' appears twice in '{path}'"
+                        );
+                        params.push(name);
+                    }
+                    None => ensure!(
+                        !segment.contains(['{', '}']),
+                        "route path segment '{segment}' must be a literal or a whole {{parameter}}"
+                    ),
+                }
+            }
+        }
+        for method in &self.methods {
+            hyper::Method::from_bytes(method.as_bytes())
+                .with_context(|| format!("invalid HTTP method '{method}' in route"))?;
+        }
+        for (i, header) in self.headers.iter().enumerate() {
+            header.validate()?;
+            ensure!(
+                !self.headers[..i].iter().any(|h| h.name == header.name),
+                "route header '{}' appears twice",
+                header.name
+            );
+        }
+        for media_type in &self.produces {
+            negotiate::validate_media_type(media_type)?;
+        }
+        ensure!(
+            self.timeout_ms != Some(0),
+            "route timeout must be greater than zero"
+        );
+        ensure!(
+            self.stream_idle_timeout_ms != Some(0) && self.stream_max_duration_ms != Some(0),
+            "route stream limits must be greater than zero"
+        );
+        ensure!(
+            self.spool_max_bytes != Some(0),
+            "route spool limit must be greater than zero"
+        );
+        ensure!(
+            self.sse_keepalive_ms != Some(0),
+            "route SSE keepalive interval must be greater than zero"
+        );
+        self.request_limits()
+            .validate()
+            .context("invalid route request limits")?;
+        if let Some(auth) = &self.auth {
+            auth.validate().context("invalid route auth")?;
+        }
+        if let Some(shadow) = &self.shadow {
+            ensure!(
+                shadow
+                    .split_once('/')
+                    .is_some_and(|(ns, name)| !ns.is_empty() && !name.is_empty()),
+                "route shadow '{shadow}' must have the form namespace/name"
+            );
+        }
+        if let Some(experiment) = &self.experiment {
+            experiment.validate()?;
+            ensure!(
+                self.weight.is_none(),
+                "a route can't both take part in an experiment and have a weight"
+            );
+        }
+        if let Some(check) = &self.health_check {
+            check.validate()?;
+        }
+        if let Some(limit) = &self.rate_limit {
+            limit.validate()?;
+        }
+        if let Some(cache) = &self.cache {
+            cache.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether a request with the given method and path matches this route.
+    /// Neither the `Host` header nor the header predicates of the route are
+    /// checked, see [`Self::matches_request`].
+    pub fn matches(&self, method: &hyper::Method, path: &str) -> bool {
+        if !self.methods.is_empty() && !self.methods.iter().any(|m| m == method.as_str()) {
+            return false;
+        }
+        self.match_path(path).is_some()
+    }
+
+    /// Returns whether the method, path and headers of `req` match this route.
+    /// The `Host` header is not checked.
+    pub fn matches_request<B>(&self, req: &hyper::Request<B>) -> bool {
+        self.matches(req.method(), req.uri().path())
+            && self
+                .headers
+                .iter()
+                .all(|header| header.matches(req.headers()))
+    }
+
+    /// Matches `path` against the path prefix of the route, returning the
+    /// length of the part of `path` it matched and the captured parameters.
+    pub fn match_path(&self, path: &str) -> Option<(usize, Vec<(String, String)>)> {
+        let mut matched = 0;
+        let mut params = Vec::new();
+        for segment in self.normalized_path().split('/').skip(1) {
+            let rest = path[matched..].strip_prefix('/')?;
+            let value = rest.split('/').next().unwrap_or_default();
+            match path_param(segment) {
+                Some(name) if !value.is_empty() => {
+                    params.push((name.to_string(), value.to_string()));
+                }
+                None if value == segment => {}
+                _ => return None,
+            }
+            matched += 1 + value.len();
+        }
+        Some((matched, params))
+    }
+
+    /// Returns whether this route and `other` would both match some request, with
+    /// neither taking precedence over the other.
+    ///
+    /// Routes conflict when they share a host and the same path prefix, up to
+    /// the names of parameters, and are equally specific in the methods and
+    /// headers they match, accepting at least one common method and no
+    /// different values of a common header. A wildcard doesn't conflict with
+    /// the hosts it matches, which take precedence over it. Routes with
+    /// different path prefixes, methods or header predicates otherwise never
+    /// conflict, because the more specific route takes precedence, see
+    /// [`DynamicRouter`](crate::host::http::DynamicRouter), and neither do
+    /// variants of the same experiment or routes with a weight, which split
+    /// requests between them, see [`split`](crate::host::http::split).
+    /// Routes producing different media types, or of which only one declares
+    /// produced types, are chosen between by content negotiation instead, see
+    /// [`negotiate`].
+    pub fn conflicts_with(&self, other: &HttpRouteConfig) -> bool {
+        if !self
+            .hosts()
+            .any(|host| other.hosts().any(|other| other == host))
+            || self.specificity() != other.specificity()
+            || self.path_shape() != other.path_shape()
+        {
+            return false;
+        }
+        if self.headers.iter().any(|header| {
+            other.headers.iter().any(|h| {
+                h.name == header.name
+                    && h.value.is_some()
+                    && header.value.is_some()
+                    && h.value != header.value
+            })
+        }) {
+            return false;
+        }
+        if self.produces.is_empty() != other.produces.is_empty()
+            || !self.produces.is_empty()
+                && !self.produces.iter().any(|t| other.produces.contains(t))
+        {
+            return false;
+        }
+        if let (Some(a), Some(b)) = (&self.experiment, &other.experiment)
+            && a.name == b.name
+        {
+            return a.variant == b.variant;
+        }
+        if self.weight.is_some() && other.weight.is_some() {
+            return false;
+        }
+        self.methods.is_empty()
+            || other.methods.is_empty()
+            || self.methods.iter().any(|m| other.methods.contains(m))
+    }
+
+    /// The path prefix without a trailing `/`, where the empty string matches all paths.
+    pub(crate) fn normalized_path(&self) -> &str {
+        self.path
+            .as_deref()
+            .map(|p| p.trim_end_matches('/'))
+            .unwrap_or_default()
+    }
+
+    /// The normalized path prefix with parameters unnamed.
+    pub(crate) fn path_shape(&self) -> Vec<&str> {
+        self.normalized_path()
+            .split('/')
+            .map(|segment| path_param(segment).map_or(segment, |_| "{}"))
+            .collect()
+    }
+
+    /// How specific the route is, compared to others matching the same
+    /// request: more path segments, then more literal segments, then fewer
+    /// methods than all, then more header predicates, are more specific.
+    pub(crate) fn specificity(&self) -> (usize, usize, Option<std::cmp::Reverse<usize>>, usize) {
+        let segments = self.normalized_path().split('/').skip(1);
+        let literals = segments
+            .clone()
+            .filter(|segment| path_param(segment).is_none())
+            .count();
+        let methods = (!self.methods.is_empty()).then_some(std::cmp::Reverse(self.methods.len()));
+        (segments.count(), literals, methods, self.headers.len())
+    }
+
+    /// Converts the route into the string map carried on [`WitInterface::config`].
+    pub fn to_config(&self) -> HashMap<String, String> {
+        let mut config = HashMap::from([(HOST_CONFIG_KEY.to_string(), self.host.clone())]);
+        if let Some(path) = &self.path {
+            config.insert(PATH_CONFIG_KEY.to_string(), path.clone());
+        }
+        if !self.methods.is_empty() {
+            config.insert(METHODS_CONFIG_KEY.to_string(), self.methods.join(","));
+        }
+        if !self.headers.is_empty() {
+            let headers: Vec<String> = self.headers.iter().map(ToString::to_string).collect();
+            config.insert(HEADERS_CONFIG_KEY.to_string(), headers.join(","));
+        }
+        if !self.produces.is_empty() {
+            config.insert(
+                negotiate::PRODUCES_CONFIG_KEY.to_string(),
+                self.produces.join(","),
+            );
+        }
+        for (key, value) in [
+            (TIMEOUT_MS_CONFIG_KEY, self.timeout_ms),
+            (
+                STREAM_IDLE_TIMEOUT_MS_CONFIG_KEY,
+                self.stream_idle_timeout_ms,
+            ),
+            (
+                STREAM_MAX_DURATION_MS_CONFIG_KEY,
+                self.stream_max_duration_ms,
+            ),
+            (SSE_KEEPALIVE_MS_CONFIG_KEY, self.sse_keepalive_ms),
+            (MAX_BODY_BYTES_CONFIG_KEY, self.max_body_bytes),
+            (SPOOL_MAX_BYTES_CONFIG_KEY, self.spool_max_bytes),
+            (request_limits::MAX_HEADERS_CONFIG_KEY, self.max_headers),
+            (
+                request_limits::MAX_HEADER_BYTES_CONFIG_KEY,
+                self.max_header_bytes,
+            ),
+            (
+                request_limits::BODY_READ_TIMEOUT_MS_CONFIG_KEY,
+                self.body_read_timeout_ms,
+            ),
+            (WEIGHT_CONFIG_KEY, self.weight.map(u64::from)),
+        ] {
+            if let Some(value) = value {
+                config.insert(key.to_string(), value.to_string());
+            }
+        }
+        if let Some(auth) = &self.auth {
+            auth.write_config(&mut config);
+        }
+        if let Some(shadow) = &self.shadow {
+            config.insert(SHADOW_CONFIG_KEY.to_string(), shadow.clone());
+        }
+        if let Some(experiment) = &self.experiment {
+            experiment.write_config(&mut config);
+        }
+        if let Some(check) = &self.health_check {
+            check.write_config(&mut config);
+        }
+        if self.websocket {
+            config.insert(WEBSOCKET_CONFIG_KEY.to_string(), "true".to_string());
+        }
+        if let Some(limit) = &self.rate_limit {
+            limit.write_config(&mut config);
+        }
+        if let Some(cache) = &self.cache {
+            cache.write_config(&mut config);
+        }
+        config
+    }
+}
+
+impl std::fmt::Display for HttpRouteConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let methods = if self.methods.is_empty() {
+            "*".to_string()
+        } else {
+            self.methods.join(",")
+        };
+        write!(f, "{methods} {}{}/", self.host, self.normalized_path())?;
+        for header in &self.headers {
+            write!(f, " [{header}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// A header requests must have to match a route, with the given value if
+/// any.
+///
+/// In the string config map of a route, the predicates are a comma-separated
+/// list of `name:value` or `name`, e.g. `headers: x-api-version:2,x-beta`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteHeaderMatch {
+    /// Lowercase name of the header
+    pub name: String,
+    /// The value the header must have. Any value matches if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl RouteHeaderMatch {
+    /// Returns whether any value of the header in `headers` matches.
+    pub fn matches(&self, headers: &hyper::HeaderMap) -> bool {
+        let mut values = headers.get_all(self.name.as_str()).iter();
+        match &self.value {
+            Some(value) => values.any(|v| v.to_str().is_ok_and(|v| v.trim() == value)),
+            None => values.next().is_some(),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        hyper::header::HeaderName::from_bytes(self.name.as_bytes())
+            .with_context(|| format!("invalid header name '{}' in route", self.name))?;
+        if let Some(value) = &self.value {
+            ensure!(
+                !value.is_empty() && !value.contains(',') && value.trim() == value,
+                "route header '{}' must have a value without commas or surrounding whitespace",
+                self.name
+            );
+            hyper::header::HeaderValue::from_str(value).with_context(|| {
+                format!("invalid value '{value}' of header '{}' in route", self.name)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for RouteHeaderMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}:{value}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+impl std::str::FromStr for RouteHeaderMatch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once(':') {
+            Some((name, value)) => (name, Some(value.trim().to_string())),
+            None => (s, None),
+        };
+        let header = Self {
+            name: name.trim().to_ascii_lowercase(),
+            value,
+        };
+        header.validate()?;
+        Ok(header)
+    }
+}
+
+impl TryFrom<&HashMap<String, String>> for HttpRouteConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(config: &HashMap<String, String>) -> Result<Self, Self::Error> {
+        let host = config
+            .get(HOST_CONFIG_KEY)
+            .context("No host header found")?
+            .split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = config.get(PATH_CONFIG_KEY).cloned();
+        let methods = config
+            .get(METHODS_CONFIG_KEY)
+            .map(|m| {
+                m.split(',')
+                    .map(|s| s.trim().to_ascii_uppercase())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let headers = config
+            .get(HEADERS_CONFIG_KEY)
+            .map(|h| {
+                h.split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .map(str::parse)
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let produces = config
+            .get(negotiate::PRODUCES_CONFIG_KEY)
+            .map(|p| negotiate::parse_produces(p))
+            .unwrap_or_default();
+        let millis = |key: &str| {
+            config
+                .get(key)
+                .map(|t| {
+                    t.trim()
+                        .parse::<u64>()
+                        .with_context(|| format!("invalid route {key} '{t}'"))
+                })
+                .transpose()
+        };
+        let timeout_ms = millis(TIMEOUT_MS_CONFIG_KEY)?;
+        let stream_idle_timeout_ms = millis(STREAM_IDLE_TIMEOUT_MS_CONFIG_KEY)?;
+        let stream_max_duration_ms = millis(STREAM_MAX_DURATION_MS_CONFIG_KEY)?;
+        let sse_keepalive_ms = millis(SSE_KEEPALIVE_MS_CONFIG_KEY)?;
+        let max_body_bytes = millis(MAX_BODY_BYTES_CONFIG_KEY)?;
+        let spool_max_bytes = millis(SPOOL_MAX_BYTES_CONFIG_KEY)?;
+        let max_headers = millis(request_limits::MAX_HEADERS_CONFIG_KEY)?;
+        let max_header_bytes = millis(request_limits::MAX_HEADER_BYTES_CONFIG_KEY)?;
+        let body_read_timeout_ms = millis(request_limits::BODY_READ_TIMEOUT_MS_CONFIG_KEY)?;
+        let weight = millis(WEIGHT_CONFIG_KEY)?
+            .map(u32::try_from)
+            .transpose()
+            .context("route weight is too large")?;
+
+        let auth = RouteAuth::from_config(config)?;
+        let shadow = config.get(SHADOW_CONFIG_KEY).map(|s| s.trim().to_string());
+        let experiment = RouteExperiment::from_config(config)?;
+        let health_check = RouteHealthCheck::from_config(config)?;
+        let websocket = config
+            .get(WEBSOCKET_CONFIG_KEY)
+            .map(|w| {
+                w.trim()
+                    .parse::<bool>()
+                    .with_context(|| format!("invalid route {WEBSOCKET_CONFIG_KEY} '{w}'"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let rate_limit = RouteRateLimit::from_config(config)?;
+        let cache = RouteCache::from_config(config)?;
+
+        let route = Self {
+            host,
+            path,
+            methods,
+            headers,
+            produces,
+            timeout_ms,
+            stream_idle_timeout_ms,
+            stream_max_duration_ms,
+            sse_keepalive_ms,
+            max_body_bytes,
+            max_headers,
+            max_header_bytes,
+            body_read_timeout_ms,
+            spool_max_bytes,
+            auth,
+            shadow,
+            weight,
+            experiment,
+            health_check,
+            websocket,
+            rate_limit,
+            cache,
+        };
+        route.validate()?;
+        Ok(route)
+    }
+}
+
+impl TryFrom<&WitInterface> for HttpRouteConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(interface: &WitInterface) -> Result<Self, Self::Error> {
+        HttpRouteConfig::try_from(&interface.config)
+    }
+}
+
+/// Returns the name of a `{name}` path segment.
+pub(crate) fn path_param(segment: &str) -> Option<&str> {
+    segment.strip_prefix('{')?.strip_suffix('}')
+}
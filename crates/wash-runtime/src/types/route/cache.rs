@@ -0,0 +1,155 @@
+//! The response caching of a route, done by the HTTP server, see
+//! [`crate::host::http::cache`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as _, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Config key set to `true` for a route whose responses are cached
+pub const CACHE_CONFIG_KEY: &str = "cache";
+/// Config key for the largest response body cached, in bytes
+pub const CACHE_MAX_ENTRY_BYTES_CONFIG_KEY: &str = "cache_max_entry_bytes";
+/// Config key for the longest time a cached response is fresh, in seconds
+pub const CACHE_MAX_TTL_SECS_CONFIG_KEY: &str = "cache_max_ttl_secs";
+
+/// Largest response body cached for a route by default
+pub const DEFAULT_MAX_ENTRY_BYTES: u64 = 1024 * 1024;
+
+/// The response caching of a route, see [`crate::host::http::cache`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteCache {
+    /// Largest response body cached, in bytes, [`DEFAULT_MAX_ENTRY_BYTES`]
+    /// if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entry_bytes: Option<u64>,
+    /// Longest time a response is fresh, in seconds, whatever its
+    /// `Cache-Control` says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ttl_secs: Option<u64>,
+}
+
+impl RouteCache {
+    /// Caches responses of up to `max` bytes.
+    pub fn with_max_entry_bytes(mut self, max: u64) -> Self {
+        self.max_entry_bytes = Some(max);
+        self
+    }
+
+    /// Keeps responses fresh for at most `ttl`.
+    pub fn with_max_ttl(mut self, ttl: Duration) -> Self {
+        self.max_ttl_secs = Some(ttl.as_secs());
+        self
+    }
+
+    /// Returns the largest response body cached.
+    pub fn max_entry_bytes(&self) -> u64 {
+        self.max_entry_bytes.unwrap_or(DEFAULT_MAX_ENTRY_BYTES)
+    }
+
+    /// Validates the caching.
+    ///
+    /// # Errors
+    /// Returns an error if the largest entry or longest TTL is zero.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.max_entry_bytes != Some(0),
+            "route cache entry limit must be greater than zero"
+        );
+        ensure!(
+            self.max_ttl_secs != Some(0),
+            "route cache TTL must be greater than zero"
+        );
+        Ok(())
+    }
+
+    /// Parses the caching from the string config map of a route, returning
+    /// `None` if the route caches no responses.
+    ///
+    /// # Errors
+    /// Returns an error if a limit is configured without enabling the cache,
+    /// or a value is malformed.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        let enabled = config
+            .get(CACHE_CONFIG_KEY)
+            .map(|c| {
+                c.trim()
+                    .parse::<bool>()
+                    .with_context(|| format!("invalid route {CACHE_CONFIG_KEY} '{c}'"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        if !enabled {
+            for key in [
+                CACHE_MAX_ENTRY_BYTES_CONFIG_KEY,
+                CACHE_MAX_TTL_SECS_CONFIG_KEY,
+            ] {
+                ensure!(
+                    !config.contains_key(key),
+                    "{key} requires {CACHE_CONFIG_KEY} to be true"
+                );
+            }
+            return Ok(None);
+        }
+        let number = |key: &str| {
+            config
+                .get(key)
+                .map(|n| {
+                    n.trim()
+                        .parse::<u64>()
+                        .with_context(|| format!("invalid route {key} '{n}'"))
+                })
+                .transpose()
+        };
+        let cache = Self {
+            max_entry_bytes: number(CACHE_MAX_ENTRY_BYTES_CONFIG_KEY)?,
+            max_ttl_secs: number(CACHE_MAX_TTL_SECS_CONFIG_KEY)?,
+        };
+        cache.validate()?;
+        Ok(Some(cache))
+    }
+
+    /// Writes the caching into the string config map of a route.
+    pub fn write_config(&self, config: &mut HashMap<String, String>) {
+        config.insert(CACHE_CONFIG_KEY.to_string(), "true".to_string());
+        if let Some(max) = self.max_entry_bytes {
+            config.insert(
+                CACHE_MAX_ENTRY_BYTES_CONFIG_KEY.to_string(),
+                max.to_string(),
+            );
+        }
+        if let Some(ttl) = self.max_ttl_secs {
+            config.insert(CACHE_MAX_TTL_SECS_CONFIG_KEY.to_string(), ttl.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_config() {
+        let config = HashMap::from([
+            (CACHE_CONFIG_KEY.to_string(), "true".to_string()),
+            (CACHE_MAX_TTL_SECS_CONFIG_KEY.to_string(), "60".to_string()),
+        ]);
+        let cache = RouteCache::from_config(&config).unwrap().unwrap();
+        assert_eq!(
+            cache,
+            RouteCache::default().with_max_ttl(Duration::from_secs(60))
+        );
+        let mut written = HashMap::new();
+        cache.write_config(&mut written);
+        assert_eq!(written, config);
+
+        assert!(RouteCache::from_config(&HashMap::new()).unwrap().is_none());
+        let limit_only = HashMap::from([(
+            CACHE_MAX_ENTRY_BYTES_CONFIG_KEY.to_string(),
+            "10".to_string(),
+        )]);
+        assert!(RouteCache::from_config(&limit_only).is_err());
+    }
+}
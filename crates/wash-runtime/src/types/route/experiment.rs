@@ -0,0 +1,371 @@
+//! A/B experiment routing between workloads that serve the same route.
+//!
+//! Workloads taking part in an experiment declare the same host, path and
+//! experiment name, each with its own variant. Such routes do not conflict with
+//! each other. For every matching request the router picks one variant:
+//!
+//! 1. A variant whose `match` rule (a cookie or header value) matches the request wins.
+//! 2. Otherwise the request is placed in a bucket from 0 to 99 and assigned by the
+//!    variants' weights, in the order the workloads were started. Buckets not
+//!    covered by any weight go to the first workload, the control.
+//!
+//! If a `sticky` cookie or header is configured and present, the bucket is derived
+//! from its value, so a client keeps seeing the same variant. Requests without it
+//! get a random bucket. The guest learns its assignment from the
+//! [`EXPERIMENT_HEADER`] request header, `<experiment>=<variant>`.
+//!
+//! In the string config map of `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! experiment: checkout
+//! experiment_variant: new-flow
+//! experiment_weight: 20                  # optional, defaults to 0
+//! experiment_match: cookie:beta=1        # optional, or header:x-beta=1
+//! experiment_sticky: cookie:session_id   # optional, or header:x-user-id
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, bail, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Config key for the experiment name
+pub const EXPERIMENT_CONFIG_KEY: &str = "experiment";
+/// Config key for the variant served by the workload
+pub const EXPERIMENT_VARIANT_CONFIG_KEY: &str = "experiment_variant";
+/// Config key for the percentage of traffic assigned to the variant
+pub const EXPERIMENT_WEIGHT_CONFIG_KEY: &str = "experiment_weight";
+/// Config key for the `cookie:NAME=VALUE` or `header:NAME=VALUE` rule forcing the variant
+pub const EXPERIMENT_MATCH_CONFIG_KEY: &str = "experiment_match";
+/// Config key for the `cookie:NAME` or `header:NAME` the sticky bucket is derived from
+pub const EXPERIMENT_STICKY_CONFIG_KEY: &str = "experiment_sticky";
+
+/// Request header carrying the experiment assignment to the guest
+pub const EXPERIMENT_HEADER: &str = "x-wasmcloud-experiment";
+
+/// A cookie or header read from the request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKey {
+    Cookie(String),
+    Header(String),
+}
+
+impl RequestKey {
+    /// Returns the value of the cookie or header in the request, if present.
+    pub fn value<'a>(&self, headers: &'a hyper::HeaderMap) -> Option<&'a str> {
+        match self {
+            RequestKey::Header(name) => headers.get(name.as_str())?.to_str().ok(),
+            RequestKey::Cookie(name) => headers
+                .get_all(hyper::header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestKey::Cookie(name) => write!(f, "cookie:{name}"),
+            RequestKey::Header(name) => write!(f, "header:{name}"),
+        }
+    }
+}
+
+impl std::str::FromStr for RequestKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, name) = s
+            .trim()
+            .split_once(':')
+            .with_context(|| format!("'{s}' must have the form cookie:NAME or header:NAME"))?;
+        ensure!(!name.is_empty(), "'{s}' is missing a name");
+        match kind {
+            "cookie" => Ok(RequestKey::Cookie(name.to_string())),
+            "header" => {
+                hyper::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name '{name}'"))?;
+                Ok(RequestKey::Header(name.to_ascii_lowercase()))
+            }
+            other => bail!("unsupported request key '{other}', expected 'cookie' or 'header'"),
+        }
+    }
+}
+
+/// A rule that forces a variant when a cookie or header has a given value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExperimentMatch {
+    pub key: RequestKey,
+    pub value: String,
+}
+
+/// The part a workload plays in an A/B experiment on its route.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteExperiment {
+    /// Name of the experiment, shared by all workloads taking part in it
+    pub name: String,
+    /// Name of the variant served by this workload
+    pub variant: String,
+    /// Percentage of requests, from 0 to 100, assigned to this variant
+    #[serde(default)]
+    pub weight: u8,
+    /// Requests matching this rule are always assigned to this variant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<ExperimentMatch>,
+    /// Cookie or header whose value keeps a client in the same bucket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<RequestKey>,
+}
+
+impl RouteExperiment {
+    /// Creates a variant of the given experiment that receives no traffic by weight.
+    pub fn new(name: impl Into<String>, variant: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variant: variant.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the percentage of requests assigned to this variant.
+    pub fn with_weight(mut self, weight: u8) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Always assigns requests where `key` has the given value to this variant.
+    pub fn with_match(mut self, key: RequestKey, value: impl Into<String>) -> Self {
+        self.when = Some(ExperimentMatch {
+            key,
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Derives the bucket of a request from the value of `key`.
+    pub fn with_sticky(mut self, key: RequestKey) -> Self {
+        self.sticky = Some(key);
+        self
+    }
+
+    /// The value of the [`EXPERIMENT_HEADER`] for requests assigned to this variant.
+    pub fn assignment(&self) -> String {
+        format!("{}={}", self.name, self.variant)
+    }
+
+    /// Validates the experiment.
+    ///
+    /// # Errors
+    /// Returns an error if the name or variant is empty or the weight is above 100.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.name.is_empty(), "experiment name must not be empty");
+        ensure!(
+            !self.variant.is_empty(),
+            "experiment '{}' is missing a variant",
+            self.name
+        );
+        ensure!(
+            self.weight <= 100,
+            "experiment weight must be between 0 and 100, got {}",
+            self.weight
+        );
+        Ok(())
+    }
+
+    /// Parses the experiment from the string config map of a route, returning
+    /// `None` if the route does not take part in an experiment.
+    ///
+    /// # Errors
+    /// Returns an error if any experiment key is malformed.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        let Some(name) = config.get(EXPERIMENT_CONFIG_KEY) else {
+            return Ok(None);
+        };
+        let weight = config
+            .get(EXPERIMENT_WEIGHT_CONFIG_KEY)
+            .map(|w| {
+                w.trim()
+                    .parse::<u8>()
+                    .with_context(|| format!("invalid experiment weight '{w}'"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let when = config
+            .get(EXPERIMENT_MATCH_CONFIG_KEY)
+            .map(|rule| {
+                let (key, value) = rule.split_once('=').with_context(|| {
+                    format!("experiment match '{rule}' must have the form key=value")
+                })?;
+                anyhow::Ok(ExperimentMatch {
+                    key: key.parse()?,
+                    value: value.trim().to_string(),
+                })
+            })
+            .transpose()?;
+        let sticky = config
+            .get(EXPERIMENT_STICKY_CONFIG_KEY)
+            .map(|key| key.parse())
+            .transpose()?;
+        let experiment = Self {
+            name: name.trim().to_string(),
+            variant: config
+                .get(EXPERIMENT_VARIANT_CONFIG_KEY)
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default(),
+            weight,
+            when,
+            sticky,
+        };
+        experiment.validate()?;
+        Ok(Some(experiment))
+    }
+
+    /// Writes the experiment into the string config map of a route.
+    pub fn write_config(&self, config: &mut HashMap<String, String>) {
+        config.insert(EXPERIMENT_CONFIG_KEY.to_string(), self.name.clone());
+        config.insert(
+            EXPERIMENT_VARIANT_CONFIG_KEY.to_string(),
+            self.variant.clone(),
+        );
+        config.insert(
+            EXPERIMENT_WEIGHT_CONFIG_KEY.to_string(),
+            self.weight.to_string(),
+        );
+        if let Some(when) = &self.when {
+            config.insert(
+                EXPERIMENT_MATCH_CONFIG_KEY.to_string(),
+                format!("{}={}", when.key, when.value),
+            );
+        }
+        if let Some(sticky) = &self.sticky {
+            config.insert(EXPERIMENT_STICKY_CONFIG_KEY.to_string(), sticky.to_string());
+        }
+    }
+}
+
+/// Picks the variant for a request among the variants of one experiment, returning
+/// its index. `variants` must not be empty and is expected in start order, with
+/// the control first.
+pub fn assign(variants: &[&RouteExperiment], headers: &hyper::HeaderMap) -> usize {
+    if let Some(i) = variants.iter().position(|v| {
+        v.when
+            .as_ref()
+            .is_some_and(|when| when.key.value(headers) == Some(when.value.as_str()))
+    }) {
+        return i;
+    }
+
+    let sticky_value = variants
+        .iter()
+        .find_map(|v| v.sticky.as_ref())
+        .and_then(|key| key.value(headers));
+    let bucket = match sticky_value {
+        Some(value) => {
+            let digest = Sha256::new()
+                .chain_update(variants[0].name.as_bytes())
+                .chain_update(b"/")
+                .chain_update(value.as_bytes())
+                .finalize();
+            u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes")) % 100
+        }
+        None => uuid::Uuid::new_v4().as_u128() as u64 % 100,
+    };
+
+    let mut upper = 0u64;
+    for (i, variant) in variants.iter().enumerate() {
+        upper += u64::from(variant.weight);
+        if bucket < upper {
+            return i;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                hyper::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn test_request_key_value() {
+        let headers = headers(&[("cookie", "a=1; session=abc"), ("x-user", "42")]);
+        assert_eq!(
+            RequestKey::Cookie("session".to_string()).value(&headers),
+            Some("abc")
+        );
+        assert_eq!(RequestKey::Cookie("b".to_string()).value(&headers), None);
+        assert_eq!(
+            RequestKey::Header("x-user".to_string()).value(&headers),
+            Some("42")
+        );
+    }
+
+    #[test]
+    fn test_assign_by_match_and_weight() {
+        let control = RouteExperiment::new("checkout", "control");
+        let beta = RouteExperiment::new("checkout", "beta")
+            .with_match(RequestKey::Cookie("beta".to_string()), "1");
+        let variants = [&control, &beta];
+        assert_eq!(assign(&variants, &headers(&[("cookie", "beta=1")])), 1);
+        assert_eq!(assign(&variants, &headers(&[])), 0);
+
+        let all_in = RouteExperiment::new("checkout", "beta").with_weight(100);
+        assert_eq!(assign(&[&control, &all_in], &headers(&[])), 1);
+    }
+
+    #[test]
+    fn test_assign_sticky() {
+        let control = RouteExperiment::new("checkout", "control")
+            .with_sticky(RequestKey::Header("x-user".to_string()));
+        let beta = RouteExperiment::new("checkout", "beta").with_weight(50);
+        let variants = [&control, &beta];
+        for user in ["1", "2", "alice", "bob"] {
+            let headers = headers(&[("x-user", user)]);
+            let first = assign(&variants, &headers);
+            for _ in 0..10 {
+                assert_eq!(assign(&variants, &headers), first);
+            }
+        }
+    }
+
+    #[test]
+    fn test_experiment_config_round_trip() {
+        let experiment = RouteExperiment::new("checkout", "beta")
+            .with_weight(20)
+            .with_match(RequestKey::Header("x-beta".to_string()), "yes")
+            .with_sticky(RequestKey::Cookie("session".to_string()));
+        let mut config = HashMap::new();
+        experiment.write_config(&mut config);
+        assert_eq!(
+            RouteExperiment::from_config(&config).unwrap(),
+            Some(experiment)
+        );
+
+        config.insert(EXPERIMENT_WEIGHT_CONFIG_KEY.to_string(), "150".to_string());
+        assert!(RouteExperiment::from_config(&config).is_err());
+        config.insert(EXPERIMENT_WEIGHT_CONFIG_KEY.to_string(), "20".to_string());
+        config.insert(
+            EXPERIMENT_STICKY_CONFIG_KEY.to_string(),
+            "query:id".to_string(),
+        );
+        assert!(RouteExperiment::from_config(&config).is_err());
+    }
+}
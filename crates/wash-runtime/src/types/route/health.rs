@@ -0,0 +1,238 @@
+//! The health check of a route, run by the HTTP server while the workload
+//! serving the route runs, see [`crate::host::http::health`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context as _, bail, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Config key for the path a health check requests
+pub const HEALTH_PATH_CONFIG_KEY: &str = "health_path";
+/// Config key for the export a health check calls
+pub const HEALTH_EXPORT_CONFIG_KEY: &str = "health_export";
+/// Config key for the time between two health checks, in milliseconds
+pub const HEALTH_INTERVAL_MS_CONFIG_KEY: &str = "health_interval_ms";
+/// Config key for the number of failed health checks in a row after which a
+/// workload is not ready
+pub const HEALTH_THRESHOLD_CONFIG_KEY: &str = "health_threshold";
+
+/// Time between two health checks if not configured
+pub const DEFAULT_INTERVAL_MS: u64 = 10_000;
+/// Failed health checks in a row after which a workload is not ready, if not
+/// configured
+pub const DEFAULT_THRESHOLD: u32 = 3;
+
+/// What a health check does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthProbe {
+    /// A `GET` request to a path, passing with a `2xx` or `3xx` status
+    Path(String),
+    /// A call to an export taking no arguments, e.g. `health` or
+    /// `wasmcloud:example/health#check`, passing unless it fails, returns
+    /// `false` or returns an `err`
+    Export(String),
+}
+
+impl std::fmt::Display for HealthProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthProbe::Path(path) => write!(f, "GET {path}"),
+            HealthProbe::Export(export) => write!(f, "export {export}"),
+        }
+    }
+}
+
+/// The health check of a route, see [`crate::host::http::health`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteHealthCheck {
+    pub probe: HealthProbe,
+    /// Time between two checks, and that a check may take, in milliseconds
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    /// Failed checks in a row after which the workload is not ready
+    #[serde(default = "default_threshold")]
+    pub threshold: u32,
+}
+
+fn default_interval_ms() -> u64 {
+    DEFAULT_INTERVAL_MS
+}
+
+fn default_threshold() -> u32 {
+    DEFAULT_THRESHOLD
+}
+
+impl RouteHealthCheck {
+    /// Creates a health check requesting `path`.
+    pub fn path(path: impl Into<String>) -> Self {
+        Self::new(HealthProbe::Path(path.into()))
+    }
+
+    /// Creates a health check calling `export`.
+    pub fn export(export: impl Into<String>) -> Self {
+        Self::new(HealthProbe::Export(export.into()))
+    }
+
+    fn new(probe: HealthProbe) -> Self {
+        Self {
+            probe,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+
+    /// Sets the time between two checks.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval_ms = interval.as_millis().try_into().unwrap_or(u64::MAX);
+        self
+    }
+
+    /// Sets the number of failed checks in a row after which the workload is
+    /// not ready.
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns the time between two checks.
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    /// Validates the health check.
+    ///
+    /// # Errors
+    /// Returns an error if the path does not start with `/`, the export is
+    /// empty, or the interval or threshold is zero.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match &self.probe {
+            HealthProbe::Path(path) => ensure!(
+                path.starts_with('/'),
+                "health check path '{path}' must start with '/'"
+            ),
+            HealthProbe::Export(export) => {
+                ensure!(!export.is_empty(), "health check export must not be empty")
+            }
+        }
+        ensure!(
+            self.interval_ms > 0,
+            "health check interval must be greater than zero"
+        );
+        ensure!(
+            self.threshold > 0,
+            "health check threshold must be greater than zero"
+        );
+        Ok(())
+    }
+
+    /// Parses the health check from the string config map of a route,
+    /// returning `None` if the route has none.
+    ///
+    /// # Errors
+    /// Returns an error if both a path and an export are configured, an
+    /// interval or threshold is configured without either, or a value is
+    /// malformed.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        let path = config.get(HEALTH_PATH_CONFIG_KEY);
+        let export = config.get(HEALTH_EXPORT_CONFIG_KEY);
+        let mut check = match (path, export) {
+            (Some(path), None) => Self::path(path.trim()),
+            (None, Some(export)) => Self::export(export.trim()),
+            (Some(_), Some(_)) => bail!(
+                "a health check has either a {HEALTH_PATH_CONFIG_KEY} or a {HEALTH_EXPORT_CONFIG_KEY}, not both"
+            ),
+            (None, None) => {
+                for key in [HEALTH_INTERVAL_MS_CONFIG_KEY, HEALTH_THRESHOLD_CONFIG_KEY] {
+                    ensure!(
+                        !config.contains_key(key),
+                        "{key} requires a {HEALTH_PATH_CONFIG_KEY} or a {HEALTH_EXPORT_CONFIG_KEY}"
+                    );
+                }
+                return Ok(None);
+            }
+        };
+        if let Some(interval) = config.get(HEALTH_INTERVAL_MS_CONFIG_KEY) {
+            check.interval_ms = interval
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid health check interval '{interval}'"))?;
+        }
+        if let Some(threshold) = config.get(HEALTH_THRESHOLD_CONFIG_KEY) {
+            check.threshold = threshold
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid health check threshold '{threshold}'"))?;
+        }
+        check.validate()?;
+        Ok(Some(check))
+    }
+
+    /// Writes the health check into the string config map of a route.
+    pub fn write_config(&self, config: &mut HashMap<String, String>) {
+        let (key, value) = match &self.probe {
+            HealthProbe::Path(path) => (HEALTH_PATH_CONFIG_KEY, path),
+            HealthProbe::Export(export) => (HEALTH_EXPORT_CONFIG_KEY, export),
+        };
+        config.insert(key.to_string(), value.clone());
+        config.insert(
+            HEALTH_INTERVAL_MS_CONFIG_KEY.to_string(),
+            self.interval_ms.to_string(),
+        );
+        config.insert(
+            HEALTH_THRESHOLD_CONFIG_KEY.to_string(),
+            self.threshold.to_string(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_check_config() {
+        let config = HashMap::from([
+            (HEALTH_PATH_CONFIG_KEY.to_string(), "/ready".to_string()),
+            (HEALTH_THRESHOLD_CONFIG_KEY.to_string(), "5".to_string()),
+        ]);
+        let check = RouteHealthCheck::from_config(&config).unwrap().unwrap();
+        assert_eq!(check, RouteHealthCheck::path("/ready").with_threshold(5));
+        assert_eq!(check.interval(), Duration::from_millis(DEFAULT_INTERVAL_MS));
+        let mut written = HashMap::new();
+        check.write_config(&mut written);
+        assert_eq!(
+            RouteHealthCheck::from_config(&written).unwrap(),
+            Some(check)
+        );
+
+        assert_eq!(
+            RouteHealthCheck::from_config(&HashMap::new()).unwrap(),
+            None
+        );
+        for invalid in [
+            vec![(HEALTH_INTERVAL_MS_CONFIG_KEY, "1000")],
+            vec![
+                (HEALTH_PATH_CONFIG_KEY, "/a"),
+                (HEALTH_EXPORT_CONFIG_KEY, "b"),
+            ],
+            vec![(HEALTH_PATH_CONFIG_KEY, "ready")],
+            vec![
+                (HEALTH_EXPORT_CONFIG_KEY, "check"),
+                (HEALTH_THRESHOLD_CONFIG_KEY, "0"),
+            ],
+            vec![
+                (HEALTH_EXPORT_CONFIG_KEY, "check"),
+                (HEALTH_INTERVAL_MS_CONFIG_KEY, "soon"),
+            ],
+        ] {
+            let config = invalid
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            assert!(RouteHealthCheck::from_config(&config).is_err());
+        }
+    }
+}
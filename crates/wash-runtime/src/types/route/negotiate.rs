@@ -0,0 +1,259 @@
+//! Content negotiation between workloads that serve the same route.
+//!
+//! A route may declare the media types its component produces, so different
+//! workloads can serve the same host, path and methods, e.g. a JSON API and a
+//! server-side rendered frontend at `/users/{id}`. Such routes do not conflict
+//! with each other as long as they produce different media types. For every
+//! matching request the router picks the route producing the media type the
+//! `Accept` header prefers:
+//!
+//! 1. The route producing a media type with the highest quality in `Accept`
+//!    wins, in the order the workloads were started if several do. The
+//!    quality of a media type is the one of the most specific range matching
+//!    it, e.g. `application/json` over `application/*` over `*/*`.
+//! 2. Requests without an `Accept` header accept every media type.
+//! 3. If no route produces an acceptable media type, the route without
+//!    produced types, if any, serves the request. It also serves every
+//!    request no other route is acceptable for. Otherwise the first route
+//!    does, and its component decides how to answer.
+//!
+//! Responses of routes declaring produced types carry `Vary: Accept`, so
+//! caches keep the representations apart.
+//!
+//! In the string config map of `wasi:http/incoming-handler`:
+//!
+//! ```text
+//! produces: application/json, application/problem+json
+//! ```
+
+use anyhow::ensure;
+
+/// Config key for the comma-separated list of media types a workload produces
+pub const PRODUCES_CONFIG_KEY: &str = "produces";
+
+/// Parses a comma-separated list of media types, lowercased and without
+/// parameters.
+pub fn parse_produces(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|media_type| essence(media_type).to_ascii_lowercase())
+        .filter(|media_type| !media_type.is_empty())
+        .collect()
+}
+
+/// Validates a media type a route produces.
+///
+/// # Errors
+/// Returns an error if the media type is not of the form `type/subtype` or
+/// contains a wildcard.
+pub fn validate_media_type(media_type: &str) -> anyhow::Result<()> {
+    let valid = media_type.split_once('/').is_some_and(|(ty, subtype)| {
+        [ty, subtype].iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+        })
+    });
+    ensure!(
+        valid,
+        "route media type '{media_type}' must have the form type/subtype, without wildcards"
+    );
+    Ok(())
+}
+
+/// Picks the route a request is sent to among routes on the same path, given
+/// the media types each of them produces. Returns its index in `produces`.
+pub fn choose(produces: &[&[String]], headers: &hyper::HeaderMap) -> usize {
+    let accept = accepted_ranges(headers);
+    let mut best: Option<(usize, u16)> = None;
+    for (i, media_types) in produces.iter().enumerate() {
+        let quality = media_types
+            .iter()
+            .map(|media_type| quality(&accept, media_type))
+            .max()
+            .unwrap_or_default();
+        if quality > 0 && best.is_none_or(|(_, best)| quality > best) {
+            best = Some((i, quality));
+        }
+    }
+    match best {
+        Some((i, _)) => i,
+        None => produces
+            .iter()
+            .position(|media_types| media_types.is_empty())
+            .unwrap_or_default(),
+    }
+}
+
+/// A media range of an `Accept` header, with its quality in thousandths.
+#[derive(Debug, PartialEq, Eq)]
+struct MediaRange {
+    ty: String,
+    subtype: String,
+    quality: u16,
+}
+
+impl MediaRange {
+    /// Returns how specific the range is if it matches `media_type`.
+    fn specificity(&self, ty: &str, subtype: &str) -> Option<u8> {
+        match (self.ty.as_str(), self.subtype.as_str()) {
+            ("*", "*") => Some(0),
+            (range_ty, "*") if range_ty == ty => Some(1),
+            (range_ty, range_subtype) if range_ty == ty && range_subtype == subtype => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the media ranges of every `Accept` header of a request, skipping
+/// malformed ones. Requests without one accept every media type.
+fn accepted_ranges(headers: &hyper::HeaderMap) -> Vec<MediaRange> {
+    let mut ranges: Vec<MediaRange> = headers
+        .get_all(hyper::header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_range)
+        .collect();
+    if ranges.is_empty() {
+        ranges.push(MediaRange {
+            ty: "*".to_string(),
+            subtype: "*".to_string(),
+            quality: 1000,
+        });
+    }
+    ranges
+}
+
+fn parse_range(range: &str) -> Option<MediaRange> {
+    let mut parts = range.split(';');
+    let (ty, subtype) = parts.next()?.trim().split_once('/')?;
+    if ty.is_empty() || subtype.is_empty() || (ty == "*" && subtype != "*") {
+        return None;
+    }
+    let mut quality = 1000;
+    for param in parts {
+        if let Some((name, value)) = param.split_once('=')
+            && name.trim().eq_ignore_ascii_case("q")
+        {
+            quality = parse_quality(value.trim())?;
+        }
+    }
+    Some(MediaRange {
+        ty: ty.to_ascii_lowercase(),
+        subtype: subtype.to_ascii_lowercase(),
+        quality,
+    })
+}
+
+/// Parses a quality value between 0 and 1 with at most three decimals into
+/// thousandths.
+fn parse_quality(value: &str) -> Option<u16> {
+    let (whole, decimals) = value.split_once('.').unwrap_or((value, ""));
+    if decimals.len() > 3 || !decimals.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = match whole {
+        "0" => format!("{decimals:0<3}").parse().ok()?,
+        "1" if decimals.chars().all(|c| c == '0') => 1000,
+        _ => return None,
+    };
+    Some(thousandths)
+}
+
+/// Returns the quality of `media_type` given by the most specific range
+/// matching it, 0 if none does.
+fn quality(accept: &[MediaRange], media_type: &str) -> u16 {
+    let Some((ty, subtype)) = media_type.split_once('/') else {
+        return 0;
+    };
+    accept
+        .iter()
+        .filter_map(|range| Some((range.specificity(ty, subtype)?, range.quality)))
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, quality)| quality)
+        .unwrap_or_default()
+}
+
+/// The media type without parameters, e.g. `text/html` of
+/// `text/html; charset=utf-8`.
+fn essence(media_type: &str) -> &str {
+    media_type.split(';').next().unwrap_or_default().trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::ACCEPT, value.parse().unwrap());
+        headers
+    }
+
+    fn types(media_types: &[&str]) -> Vec<String> {
+        media_types.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_produces() {
+        assert_eq!(
+            parse_produces("Application/JSON, text/html; charset=utf-8,"),
+            types(&["application/json", "text/html"])
+        );
+        assert!(validate_media_type("application/problem+json").is_ok());
+        assert!(validate_media_type("text/*").is_err());
+        assert!(validate_media_type("json").is_err());
+        assert!(validate_media_type("text/ html").is_err());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range(" text/HTML;level=1; q=0.5"),
+            Some(MediaRange {
+                ty: "text".to_string(),
+                subtype: "html".to_string(),
+                quality: 500,
+            })
+        );
+        assert_eq!(parse_range("*/*;q=0").map(|r| r.quality), Some(0));
+        assert_eq!(parse_range("*/*;q=1.000").map(|r| r.quality), Some(1000));
+        assert_eq!(parse_range("text/html;q=0.05").map(|r| r.quality), Some(50));
+        assert!(parse_range("text/html;q=1.5").is_none());
+        assert!(parse_range("text/html;q=0.1234").is_none());
+        assert!(parse_range("*/html").is_none());
+        assert!(parse_range("html").is_none());
+    }
+
+    #[test]
+    fn test_choose() {
+        let api = types(&["application/json"]);
+        let frontend = types(&["text/html"]);
+        let fallback = Vec::new();
+        let routes = [api.as_slice(), frontend.as_slice()];
+
+        assert_eq!(choose(&routes, &accept("application/json")), 0);
+        assert_eq!(choose(&routes, &accept("text/html")), 1);
+        // Browsers accept anything, but prefer HTML
+        assert_eq!(
+            choose(
+                &routes,
+                &accept("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+            ),
+            1
+        );
+        assert_eq!(choose(&routes, &accept("text/*;q=0.5, */*;q=0.1")), 1);
+        // The most specific range gives the quality
+        assert_eq!(choose(&routes, &accept("text/html;q=0, */*")), 0);
+        // Ties go to the first route
+        assert_eq!(choose(&routes, &accept("*/*")), 0);
+        assert_eq!(choose(&routes, &hyper::HeaderMap::new()), 0);
+        // Nothing acceptable goes to the first route without produced types
+        assert_eq!(choose(&routes, &accept("image/png")), 0);
+        let routes = [api.as_slice(), fallback.as_slice(), frontend.as_slice()];
+        assert_eq!(choose(&routes, &accept("image/png")), 1);
+        assert_eq!(choose(&routes, &accept("text/html")), 2);
+    }
+}
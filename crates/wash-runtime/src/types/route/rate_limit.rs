@@ -0,0 +1,193 @@
+//! The rate limit of a route, enforced by the HTTP server, see
+//! [`crate::host::http::rate_limit`].
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, ensure};
+use hyper::header::HeaderName;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Config key for the requests a client may send per second
+pub const RATE_LIMIT_RPS_CONFIG_KEY: &str = "rate_limit_rps";
+/// Config key for the requests a client may send at once
+pub const RATE_LIMIT_BURST_CONFIG_KEY: &str = "rate_limit_burst";
+/// Config key for what clients are told apart by, `ip` or `header:<name>`
+pub const RATE_LIMIT_KEY_CONFIG_KEY: &str = "rate_limit_key";
+
+/// What the clients of a rate limit are told apart by.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// The IP address of the client
+    #[default]
+    ClientIp,
+    /// The value of a request header, e.g. an API key
+    Header(String),
+}
+
+impl std::fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitKey::ClientIp => f.write_str("ip"),
+            RateLimitKey::Header(name) => write!(f, "header:{name}"),
+        }
+    }
+}
+
+impl std::str::FromStr for RateLimitKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("ip") {
+            return Ok(RateLimitKey::ClientIp);
+        }
+        match s.split_once(':') {
+            Some((kind, name)) if kind.trim().eq_ignore_ascii_case("header") => {
+                Ok(RateLimitKey::Header(name.trim().to_ascii_lowercase()))
+            }
+            _ => anyhow::bail!("invalid rate limit key '{s}', expected 'ip' or 'header:<name>'"),
+        }
+    }
+}
+
+/// The rate limit of a route, see [`crate::host::http::rate_limit`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteRateLimit {
+    /// Requests a client may send per second
+    pub requests_per_second: u32,
+    /// Requests a client may send at once, `requests_per_second` if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+    /// What clients are told apart by
+    #[serde(default)]
+    pub key: RateLimitKey,
+}
+
+impl RouteRateLimit {
+    /// Creates a rate limit of `requests_per_second` per client IP address.
+    pub fn new(requests_per_second: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst: None,
+            key: RateLimitKey::ClientIp,
+        }
+    }
+
+    /// Sets the requests a client may send at once.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = Some(burst);
+        self
+    }
+
+    /// Tells clients apart by the value of the request header `name`.
+    pub fn with_header_key(mut self, name: impl AsRef<str>) -> Self {
+        self.key = RateLimitKey::Header(name.as_ref().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Returns the requests a client may send at once.
+    pub fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.requests_per_second)
+    }
+
+    /// Validates the rate limit.
+    ///
+    /// # Errors
+    /// Returns an error if the rate or burst is zero, or the key header is
+    /// not a valid header name.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.requests_per_second > 0,
+            "rate limit must allow more than zero requests per second"
+        );
+        ensure!(
+            self.burst != Some(0),
+            "rate limit burst must be greater than zero"
+        );
+        if let RateLimitKey::Header(name) = &self.key {
+            HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("invalid rate limit key header '{name}'"))?;
+        }
+        Ok(())
+    }
+
+    /// Parses the rate limit from the string config map of a route,
+    /// returning `None` if the route has none.
+    ///
+    /// # Errors
+    /// Returns an error if a burst or key is configured without a rate, or a
+    /// value is malformed.
+    pub fn from_config(config: &HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        let Some(rps) = config.get(RATE_LIMIT_RPS_CONFIG_KEY) else {
+            for key in [RATE_LIMIT_BURST_CONFIG_KEY, RATE_LIMIT_KEY_CONFIG_KEY] {
+                ensure!(
+                    !config.contains_key(key),
+                    "{key} requires a {RATE_LIMIT_RPS_CONFIG_KEY}"
+                );
+            }
+            return Ok(None);
+        };
+        let mut limit = Self::new(
+            rps.trim()
+                .parse()
+                .with_context(|| format!("invalid rate limit '{rps}'"))?,
+        );
+        if let Some(burst) = config.get(RATE_LIMIT_BURST_CONFIG_KEY) {
+            limit.burst = Some(
+                burst
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid rate limit burst '{burst}'"))?,
+            );
+        }
+        if let Some(key) = config.get(RATE_LIMIT_KEY_CONFIG_KEY) {
+            limit.key = key.parse()?;
+        }
+        limit.validate()?;
+        Ok(Some(limit))
+    }
+
+    /// Writes the rate limit into the string config map of a route.
+    pub fn write_config(&self, config: &mut HashMap<String, String>) {
+        config.insert(
+            RATE_LIMIT_RPS_CONFIG_KEY.to_string(),
+            self.requests_per_second.to_string(),
+        );
+        if let Some(burst) = self.burst {
+            config.insert(RATE_LIMIT_BURST_CONFIG_KEY.to_string(), burst.to_string());
+        }
+        config.insert(RATE_LIMIT_KEY_CONFIG_KEY.to_string(), self.key.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = HashMap::from([
+            (RATE_LIMIT_RPS_CONFIG_KEY.to_string(), "10".to_string()),
+            (
+                RATE_LIMIT_KEY_CONFIG_KEY.to_string(),
+                "header:X-Api-Key".to_string(),
+            ),
+        ]);
+        let limit = RouteRateLimit::from_config(&config).unwrap().unwrap();
+        assert_eq!(limit, RouteRateLimit::new(10).with_header_key("x-api-key"));
+        assert_eq!(limit.burst(), 10);
+
+        let mut written = HashMap::new();
+        limit.write_config(&mut written);
+        assert_eq!(RouteRateLimit::from_config(&written).unwrap(), Some(limit));
+
+        assert_eq!(RouteRateLimit::from_config(&HashMap::new()).unwrap(), None);
+        let burst_only =
+            HashMap::from([(RATE_LIMIT_BURST_CONFIG_KEY.to_string(), "5".to_string())]);
+        assert!(RouteRateLimit::from_config(&burst_only).is_err());
+        let zero = HashMap::from([(RATE_LIMIT_RPS_CONFIG_KEY.to_string(), "0".to_string())]);
+        assert!(RouteRateLimit::from_config(&zero).is_err());
+    }
+}
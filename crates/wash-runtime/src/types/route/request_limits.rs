@@ -0,0 +1,134 @@
+//! Limits of the requests of a route, and the defaults of the host, enforced
+//! by the HTTP server, see [`crate::host::http::request_limits`].
+
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Config key for the most headers a request may have
+pub const MAX_HEADERS_CONFIG_KEY: &str = "max_headers";
+/// Config key for the largest size of the headers of a request, in bytes
+pub const MAX_HEADER_BYTES_CONFIG_KEY: &str = "max_header_bytes";
+/// Config key for the longest time a client may send nothing while the body
+/// of its request is read, in milliseconds
+pub const BODY_READ_TIMEOUT_MS_CONFIG_KEY: &str = "body_read_timeout_ms";
+
+/// Limits of the requests the HTTP server reads. Unset limits aren't
+/// enforced, except for the header read timeout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RequestLimits {
+    /// Largest request body, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    /// Most headers a request may have
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_headers: Option<u64>,
+    /// Largest size of the headers of a request, names and values, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_header_bytes: Option<u64>,
+    /// Longest time a client may take to send the head of an HTTP/1.1
+    /// request, in milliseconds. Defaults to 30 seconds. Only applies to the
+    /// limits of the host.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_read_timeout_ms: Option<u64>,
+    /// Longest time a client may send nothing while the body of its request
+    /// is read, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_read_timeout_ms: Option<u64>,
+}
+
+impl RequestLimits {
+    /// Rejects request bodies larger than `max` bytes.
+    pub fn with_max_body_bytes(mut self, max: u64) -> Self {
+        self.max_body_bytes = Some(max);
+        self
+    }
+
+    /// Rejects requests with more than `max_headers` headers, or headers
+    /// larger than `max_bytes`.
+    pub fn with_header_limits(mut self, max_headers: u64, max_bytes: u64) -> Self {
+        self.max_headers = Some(max_headers);
+        self.max_header_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Closes connections that don't send the head of a request within
+    /// `timeout`.
+    pub fn with_header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Rejects requests whose client sends nothing for `timeout` while their
+    /// body is read.
+    pub fn with_body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.body_read_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Returns the header read timeout, if set.
+    pub fn header_read_timeout(&self) -> Option<Duration> {
+        self.header_read_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the body read timeout, if set.
+    pub fn body_read_timeout(&self) -> Option<Duration> {
+        self.body_read_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Validates that the set limits are greater than zero.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, limit) in [
+            ("max_body_bytes", self.max_body_bytes),
+            ("max_headers", self.max_headers),
+            ("max_header_bytes", self.max_header_bytes),
+            ("header_read_timeout_ms", self.header_read_timeout_ms),
+            ("body_read_timeout_ms", self.body_read_timeout_ms),
+        ] {
+            anyhow::ensure!(
+                limit != Some(0),
+                "request limit {name} must be greater than zero"
+            );
+        }
+        Ok(())
+    }
+
+    /// These limits, with the limits they don't set taken from `defaults`.
+    pub fn or(self, defaults: &RequestLimits) -> RequestLimits {
+        RequestLimits {
+            max_body_bytes: self.max_body_bytes.or(defaults.max_body_bytes),
+            max_headers: self.max_headers.or(defaults.max_headers),
+            max_header_bytes: self.max_header_bytes.or(defaults.max_header_bytes),
+            header_read_timeout_ms: self
+                .header_read_timeout_ms
+                .or(defaults.header_read_timeout_ms),
+            body_read_timeout_ms: self.body_read_timeout_ms.or(defaults.body_read_timeout_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_or() {
+        let host = RequestLimits::default()
+            .with_max_body_bytes(1024)
+            .with_header_limits(100, 16384)
+            .with_header_read_timeout(Duration::from_secs(10));
+        let route = RequestLimits {
+            max_headers: Some(10),
+            body_read_timeout_ms: Some(500),
+            ..Default::default()
+        };
+        let limits = route.or(&host);
+        assert_eq!(limits.max_body_bytes, Some(1024));
+        assert_eq!(limits.max_headers, Some(10));
+        assert_eq!(limits.max_header_bytes, Some(16384));
+        assert_eq!(limits.header_read_timeout(), Some(Duration::from_secs(10)));
+        assert_eq!(limits.body_read_timeout(), Some(Duration::from_millis(500)));
+    }
+}
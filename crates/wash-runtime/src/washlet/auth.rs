@@ -0,0 +1,369 @@
+//! Scoped tokens for the runtime API.
+//!
+//! When a cluster host is built with [`ApiTokens`], every request on its API
+//! must carry a token in an `Authorization: Bearer <token>` header. A token
+//! grants verbs in a set of namespaces, so a CI pipeline can, for example,
+//! deploy into its own namespace and nothing else:
+//!
+//! - `read` allows heartbeats, attestation reports, workload status, listing
+//!   workloads and their live instances and listing or inspecting volumes
+//! - `deploy` also allows starting, stopping and invoking workloads, and
+//!   serving the calls peers forward, see [`super::forward`]
+//! - `admin` also allows creating and deleting volumes, which are shared by all
+//!   namespaces, reloading the configuration of the host and dumping the core
+//!   of a live instance
+//!
+//! The verbs double as roles and can be given by their role names: `viewer`
+//! for `read` and `deployer` for `deploy`.
+//!
+//! A token is `<claims>.<signature>`, the base64url encoded JSON
+//! [`TokenClaims`] followed by their HMAC-SHA256 keyed with the secret of the
+//! issuer. Every host sharing the secret accepts the tokens issued with it.
+//!
+//! # Client certificates
+//!
+//! Clients of the gRPC API authenticated with mutual TLS can be granted claims
+//! by the SHA-256 fingerprint of their certificate instead of carrying a
+//! token, see [`ClientIdentities`] and [`super::grpc`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Context as _;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+/// Header carrying the token of an API request
+pub const AUTHORIZATION_HEADER: &str = "Authorization";
+/// Namespace granting a verb in every namespace
+pub const ALL_NAMESPACES: &str = "*";
+
+/// An action on the runtime API. Each verb includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVerb {
+    #[serde(alias = "viewer")]
+    Read,
+    #[serde(alias = "deployer")]
+    Deploy,
+    Admin,
+}
+
+impl ApiVerb {
+    /// Returns the name of the verb, e.g. `deploy`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVerb::Read => "read",
+            ApiVerb::Deploy => "deploy",
+            ApiVerb::Admin => "admin",
+        }
+    }
+
+    /// Returns the verb needed for an API command, e.g. `workload.start`.
+    /// Unknown commands need [`ApiVerb::Admin`].
+    pub fn for_command(command: &str) -> Self {
+        match command {
+            "heartbeat" | "host.attestation" | "workload.status" | "workload.list"
+            | "volume.list" | "volume.inspect" | "route.list" | "http.listener.list"
+            | "component.list" | "http.introspect" | "workload.instances" | "workload.logs"
+            | "workload.events" => ApiVerb::Read,
+            "workload.start"
+            | "workload.update"
+            | "workload.config.update"
+            | "workload.stop"
+            | "workload.invoke"
+            | "component.stage"
+            | "component.unstage"
+            | "plugin.invoke" => ApiVerb::Deploy,
+            _ => ApiVerb::Admin,
+        }
+    }
+}
+
+impl fmt::Display for ApiVerb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ApiVerb {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string())).map_err(|_| {
+            anyhow::anyhow!(
+                "unknown API verb '{s}', expected read (viewer), deploy (deployer) or admin"
+            )
+        })
+    }
+}
+
+/// What a token grants.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Who the token was issued to, e.g. the name of a pipeline
+    pub subject: String,
+    /// Namespaces the verbs are granted in, or [`ALL_NAMESPACES`]
+    pub namespaces: Vec<String>,
+    pub verbs: Vec<ApiVerb>,
+    /// When the token expires, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl TokenClaims {
+    /// Creates claims granting nothing that never expire.
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            namespaces: Vec::new(),
+            verbs: Vec::new(),
+            expires_at: None,
+        }
+    }
+
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespaces.push(namespace.into());
+        self
+    }
+
+    pub fn with_verb(mut self, verb: ApiVerb) -> Self {
+        self.verbs.push(verb);
+        self
+    }
+
+    pub fn with_expiry(mut self, expires_at: chrono::DateTime<chrono::Utc>) -> Self {
+        self.expires_at = Some(expires_at.timestamp());
+        self
+    }
+
+    /// Returns whether the claims allow `verb` in `namespace`. `None` is used
+    /// for requests that don't belong to a namespace, such as heartbeats.
+    pub fn allows(&self, verb: ApiVerb, namespace: Option<&str>) -> bool {
+        self.verbs.iter().any(|granted| *granted >= verb)
+            && namespace.is_none_or(|namespace| {
+                self.namespaces
+                    .iter()
+                    .any(|granted| granted == ALL_NAMESPACES || granted == namespace)
+            })
+    }
+
+    /// Returns whether the claims grant verbs in every namespace.
+    pub fn has_all_namespaces(&self) -> bool {
+        self.namespaces
+            .iter()
+            .any(|granted| granted == ALL_NAMESPACES)
+    }
+}
+
+/// Issues and validates tokens signed with a shared secret.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiTokens {
+    secret: Vec<u8>,
+}
+
+impl ApiTokens {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// Returns a token carrying `claims`.
+    pub fn issue(&self, claims: &TokenClaims) -> anyhow::Result<String> {
+        let claims = serde_json::to_vec(claims).context("failed to serialize token claims")?;
+        let claims = URL_SAFE_NO_PAD.encode(claims);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&claims).finalize().into_bytes());
+        Ok(format!("{claims}.{signature}"))
+    }
+
+    /// Returns the claims of `token` if it was issued with this secret and has
+    /// not expired.
+    pub fn validate(&self, token: &str) -> anyhow::Result<TokenClaims> {
+        let (claims, signature) = token.split_once('.').context("malformed token")?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .context("malformed token signature")?;
+        self.mac(claims)
+            .verify_slice(&signature)
+            .map_err(|_| anyhow::anyhow!("invalid token signature"))?;
+        let claims = URL_SAFE_NO_PAD
+            .decode(claims)
+            .context("malformed token claims")?;
+        let claims: TokenClaims =
+            serde_json::from_slice(&claims).context("malformed token claims")?;
+        if claims
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now().timestamp())
+        {
+            anyhow::bail!("token expired");
+        }
+        Ok(claims)
+    }
+
+    /// Validates the token of an `Authorization: Bearer <token>` header value.
+    pub fn validate_header(&self, value: &str) -> anyhow::Result<TokenClaims> {
+        let token = value
+            .strip_prefix("Bearer ")
+            .context("authorization is not a bearer token")?;
+        self.validate(token.trim())
+    }
+
+    fn mac(&self, claims: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(claims.as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for ApiTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiTokens").finish_non_exhaustive()
+    }
+}
+
+/// Claims granted to clients by their TLS certificate, see the
+/// [module docs](self#client-certificates).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientIdentities {
+    /// Claims by the hex encoded SHA-256 fingerprint of the certificate
+    by_fingerprint: HashMap<String, TokenClaims>,
+}
+
+impl ClientIdentities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants `claims` to clients presenting the certificate with the SHA-256
+    /// `fingerprint`, in hex with or without colons, e.g. as printed by
+    /// `openssl x509 -noout -fingerprint -sha256`.
+    pub fn with_certificate(mut self, fingerprint: &str, claims: TokenClaims) -> Self {
+        self.by_fingerprint
+            .insert(normalize_fingerprint(fingerprint), claims);
+        self
+    }
+
+    /// Returns whether no certificate is granted claims.
+    pub fn is_empty(&self) -> bool {
+        self.by_fingerprint.is_empty()
+    }
+
+    /// Returns the claims granted to the DER encoded certificate `cert`.
+    pub fn claims(&self, cert: &[u8]) -> Option<&TokenClaims> {
+        self.by_fingerprint.get(&certificate_fingerprint(cert))
+    }
+}
+
+/// Returns the hex encoded SHA-256 fingerprint of the DER encoded
+/// certificate `cert`.
+pub fn certificate_fingerprint(cert: &[u8]) -> String {
+    Sha256::digest(cert)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_and_validate() {
+        let tokens = ApiTokens::new("secret");
+        let claims = TokenClaims::new("ci")
+            .with_namespace("team-a")
+            .with_verb(ApiVerb::Deploy);
+        let token = tokens.issue(&claims).unwrap();
+        assert_eq!(tokens.validate(&token).unwrap(), claims);
+        assert_eq!(
+            tokens.validate_header(&format!("Bearer {token}")).unwrap(),
+            claims
+        );
+
+        assert!(ApiTokens::new("other").validate(&token).is_err());
+        assert!(tokens.validate_header(&token).is_err());
+        assert!(tokens.validate("garbage").is_err());
+
+        // Changing the claims invalidates the signature
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = TokenClaims::new("ci")
+            .with_namespace(ALL_NAMESPACES)
+            .with_verb(ApiVerb::Admin);
+        let forged = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        assert!(tokens.validate(&format!("{forged}.{signature}")).is_err());
+
+        let expired = claims.with_expiry(chrono::Utc::now() - chrono::Duration::seconds(1));
+        assert!(tokens.validate(&tokens.issue(&expired).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_allows() {
+        let claims = TokenClaims::new("ci")
+            .with_namespace("team-a")
+            .with_verb(ApiVerb::Deploy);
+        assert!(claims.allows(ApiVerb::Read, Some("team-a")));
+        assert!(claims.allows(ApiVerb::Deploy, Some("team-a")));
+        assert!(!claims.allows(ApiVerb::Admin, Some("team-a")));
+        assert!(!claims.allows(ApiVerb::Deploy, Some("team-b")));
+        assert!(claims.allows(ApiVerb::Read, None));
+        assert!(!claims.has_all_namespaces());
+
+        let admin = TokenClaims::new("ops")
+            .with_namespace(ALL_NAMESPACES)
+            .with_verb(ApiVerb::Admin);
+        assert!(admin.allows(ApiVerb::Admin, Some("team-b")));
+        assert!(admin.has_all_namespaces());
+
+        assert_eq!(ApiVerb::for_command("workload.stop"), ApiVerb::Deploy);
+        assert_eq!(ApiVerb::for_command("workload.update"), ApiVerb::Deploy);
+        assert_eq!(
+            ApiVerb::for_command("workload.config.update"),
+            ApiVerb::Deploy
+        );
+        assert_eq!(ApiVerb::for_command("volume.list"), ApiVerb::Read);
+        assert_eq!(ApiVerb::for_command("volume.delete"), ApiVerb::Admin);
+        assert_eq!(ApiVerb::for_command("component.stage"), ApiVerb::Deploy);
+        assert_eq!(ApiVerb::for_command("plugin.invoke"), ApiVerb::Deploy);
+        assert_eq!(ApiVerb::for_command("workload.events"), ApiVerb::Read);
+        assert_eq!("deploy".parse::<ApiVerb>().unwrap(), ApiVerb::Deploy);
+        assert_eq!("deployer".parse::<ApiVerb>().unwrap(), ApiVerb::Deploy);
+        assert_eq!("viewer".parse::<ApiVerb>().unwrap(), ApiVerb::Read);
+        assert!("write".parse::<ApiVerb>().is_err());
+    }
+
+    #[test]
+    fn test_client_identities() {
+        let cert = b"not really DER";
+        let fingerprint = certificate_fingerprint(cert);
+        assert_eq!(fingerprint.len(), 64);
+
+        // Fingerprints as printed by openssl are accepted too
+        let openssl = fingerprint
+            .to_ascii_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        let claims = TokenClaims::new("ci")
+            .with_namespace("team-a")
+            .with_verb(ApiVerb::Deploy);
+        let identities = ClientIdentities::new().with_certificate(&openssl, claims.clone());
+        assert_eq!(identities.claims(cert), Some(&claims));
+        assert!(identities.claims(b"another certificate").is_none());
+        assert!(ClientIdentities::new().is_empty());
+    }
+}
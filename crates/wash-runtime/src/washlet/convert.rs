@@ -0,0 +1,1712 @@
+//! Conversions between the versioned runtime API types in [`super::types`] and
+//! the internal host types in [`crate::types`].
+//!
+//! The internal types are the stable core of the runtime: each API version only
+//! converts into and out of them, and never into another API version directly.
+//! Supporting a new API version means adding its generated types next to
+//! `types::v2` and a matching set of conversions here, with round-trip tests so
+//! that existing controllers keep working while the gRPC surface evolves.
+//!
+//! Conversions go both ways for every type that crosses the wire in either
+//! direction, so requests and responses can be translated for clients as well
+//! as for the host.
+
+use anyhow::Context as _;
+use wasmtime::component::Val;
+
+use super::types;
+use crate::host::attestation::{AttestationRequest, SignedAttestationReport};
+use crate::host::http::{
+    HttpRouteConfig, InFlightRequest, RequestPhase, RouteActivity, RouteEntry, RouteHold,
+    RouteHoldMode, RoutePin, RouteWeight, SniFiles, TlsFiles,
+};
+
+// Conversions between API v2 and internal workload definition types
+
+impl From<types::v2::WitInterface> for crate::wit::WitInterface {
+    fn from(wi: types::v2::WitInterface) -> Self {
+        crate::wit::WitInterface {
+            namespace: wi.namespace,
+            package: wi.package,
+            version: if wi.version.is_empty() {
+                None
+            } else {
+                wi.version.parse::<semver::Version>().ok()
+            },
+            interfaces: wi.interfaces.into_iter().collect(),
+            config: wi.config,
+        }
+    }
+}
+impl From<types::v2::VolumeMount> for crate::types::VolumeMount {
+    fn from(vm: types::v2::VolumeMount) -> Self {
+        crate::types::VolumeMount {
+            name: vm.name,
+            mount_path: vm.mount_path,
+            read_only: vm.read_only,
+            max_bytes_written: vm.max_bytes_written,
+            max_files: vm.max_files,
+        }
+    }
+}
+
+impl From<types::v2::Volume> for crate::types::Volume {
+    fn from(v: types::v2::Volume) -> Self {
+        crate::types::Volume {
+            name: v.name,
+            volume_type: match v.volume_type {
+                Some(vt) => match vt {
+                    types::v2::volume::VolumeType::HostPath(hp) => {
+                        crate::types::VolumeType::HostPath(crate::types::HostPathVolume {
+                            local_path: hp.local_path,
+                        })
+                    }
+                    types::v2::volume::VolumeType::EmptyDir(ed) => {
+                        crate::types::VolumeType::EmptyDir(crate::types::EmptyDirVolume {
+                            medium: match ed.medium() {
+                                types::v2::empty_dir_volume::StorageMedium::Disk => {
+                                    crate::types::StorageMedium::Disk
+                                }
+                                types::v2::empty_dir_volume::StorageMedium::Memory => {
+                                    crate::types::StorageMedium::Memory
+                                }
+                            },
+                            size_limit_bytes: ed.size_limit_bytes,
+                        })
+                    }
+                    types::v2::volume::VolumeType::Named(named) => {
+                        crate::types::VolumeType::Named(crate::types::NamedVolume {
+                            name: named.name,
+                        })
+                    }
+                    types::v2::volume::VolumeType::Oci(oci) => {
+                        crate::types::VolumeType::Oci(crate::types::OciVolume {
+                            reference: oci.reference,
+                        })
+                    }
+                    types::v2::volume::VolumeType::Nfs(nfs) => {
+                        crate::types::VolumeType::Nfs(crate::types::NfsVolume {
+                            server: nfs.server,
+                            path: nfs.path,
+                            options: nfs.options,
+                        })
+                    }
+                    types::v2::volume::VolumeType::S3(s3) => {
+                        crate::types::VolumeType::S3(crate::types::S3Volume {
+                            bucket: s3.bucket,
+                            region: s3.region,
+                            prefix: s3.prefix,
+                            endpoint: s3.endpoint,
+                        })
+                    }
+                },
+                None => crate::types::VolumeType::EmptyDir(crate::types::EmptyDirVolume::default()),
+            },
+        }
+    }
+}
+
+impl TryFrom<types::v2::LocalResources> for crate::types::LocalResources {
+    type Error = anyhow::Error;
+
+    fn try_from(lr: types::v2::LocalResources) -> Result<Self, Self::Error> {
+        Ok(crate::types::LocalResources {
+            memory_limit_mb: lr.memory_limit_mb,
+            cpu_limit: lr.cpu_limit,
+            config: lr.config,
+            volume_mounts: lr.volume_mounts.into_iter().map(Into::into).collect(),
+            allowed_hosts: lr.allowed_hosts,
+            environment: lr.environment,
+            env_files: lr
+                .env_files
+                .into_iter()
+                .filter_map(|env_file| env_file.source.map(Into::into))
+                .collect(),
+            devices: lr
+                .devices
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<types::v2::DeviceRequest> for crate::types::DeviceRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(request: types::v2::DeviceRequest) -> Result<Self, Self::Error> {
+        Ok(crate::types::DeviceRequest {
+            class: request.class.parse()?,
+            // Unset counts request one device
+            count: request.count.max(1),
+        })
+    }
+}
+
+impl From<crate::types::DeviceRequest> for types::v2::DeviceRequest {
+    fn from(request: crate::types::DeviceRequest) -> Self {
+        types::v2::DeviceRequest {
+            class: request.class.to_string(),
+            count: request.count,
+        }
+    }
+}
+
+impl From<crate::host::devices::DeviceStatus> for types::v2::HostDevice {
+    fn from(status: crate::host::devices::DeviceStatus) -> Self {
+        types::v2::HostDevice {
+            name: status.device.name,
+            class: status.device.class.to_string(),
+            path: status.device.path.display().to_string(),
+            workload_id: status.workload_id.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<types::v2::env_file::Source> for crate::types::EnvFile {
+    fn from(source: types::v2::env_file::Source) -> Self {
+        match source {
+            types::v2::env_file::Source::HostPath(path) => crate::types::EnvFile::HostPath(path),
+            types::v2::env_file::Source::Secret(name) => crate::types::EnvFile::Secret(name),
+        }
+    }
+}
+
+impl From<crate::types::EnvFile> for types::v2::EnvFile {
+    fn from(env_file: crate::types::EnvFile) -> Self {
+        types::v2::EnvFile {
+            source: Some(match env_file {
+                crate::types::EnvFile::HostPath(path) => {
+                    types::v2::env_file::Source::HostPath(path)
+                }
+                crate::types::EnvFile::Secret(name) => types::v2::env_file::Source::Secret(name),
+            }),
+        }
+    }
+}
+
+impl From<types::v2::ComponentKind> for crate::types::ComponentKind {
+    fn from(kind: types::v2::ComponentKind) -> Self {
+        match kind {
+            types::v2::ComponentKind::Init => crate::types::ComponentKind::Init,
+            types::v2::ComponentKind::Job => crate::types::ComponentKind::Job,
+            types::v2::ComponentKind::Middleware => crate::types::ComponentKind::Middleware,
+            types::v2::ComponentKind::Unspecified | types::v2::ComponentKind::Serving => {
+                crate::types::ComponentKind::Serving
+            }
+        }
+    }
+}
+
+impl From<types::v2::Job> for crate::types::Job {
+    fn from(job: types::v2::Job) -> Self {
+        let defaults = crate::types::Job::default();
+        crate::types::Job {
+            export: (!job.export.is_empty()).then_some(job.export),
+            completions: if job.completions == 0 {
+                defaults.completions
+            } else {
+                job.completions
+            },
+            parallelism: if job.parallelism == 0 {
+                defaults.parallelism
+            } else {
+                job.parallelism
+            },
+            max_retries: job.max_retries,
+            backoff_ms: job.backoff_ms.unwrap_or(defaults.backoff_ms),
+        }
+    }
+}
+
+impl From<types::v2::Autoscaling> for crate::types::Autoscaling {
+    fn from(autoscaling: types::v2::Autoscaling) -> Self {
+        let defaults = crate::types::Autoscaling::default();
+        crate::types::Autoscaling {
+            min_instances: autoscaling.min_instances,
+            max_instances: if autoscaling.max_instances == 0 {
+                defaults.max_instances
+            } else {
+                autoscaling.max_instances
+            },
+            idle_ttl_secs: autoscaling.idle_ttl_secs.unwrap_or(defaults.idle_ttl_secs),
+            queue_depth: autoscaling.queue_depth,
+            queue_policy: autoscaling.queue_policy().into(),
+        }
+    }
+}
+
+impl From<types::v2::RollingUpdate> for crate::types::RollingUpdate {
+    fn from(update: types::v2::RollingUpdate) -> Self {
+        let defaults = crate::types::RollingUpdate::default();
+        crate::types::RollingUpdate {
+            max_surge: update
+                .max_surge
+                .and_then(rollout_limit)
+                .unwrap_or(defaults.max_surge),
+            max_unavailable: update
+                .max_unavailable
+                .and_then(rollout_limit)
+                .unwrap_or(defaults.max_unavailable),
+        }
+    }
+}
+
+fn rollout_limit(limit: types::v2::RolloutLimit) -> Option<crate::types::RolloutLimit> {
+    use types::v2::rollout_limit::Limit;
+
+    Some(match limit.limit? {
+        Limit::Count(count) => crate::types::RolloutLimit::Count(count),
+        Limit::Percent(percent) => crate::types::RolloutLimit::Percent(percent.min(100) as u8),
+    })
+}
+
+impl From<crate::types::RollingUpdate> for types::v2::RollingUpdate {
+    fn from(update: crate::types::RollingUpdate) -> Self {
+        use types::v2::rollout_limit::Limit;
+
+        let limit = |limit: crate::types::RolloutLimit| types::v2::RolloutLimit {
+            limit: Some(match limit {
+                crate::types::RolloutLimit::Count(count) => Limit::Count(count),
+                crate::types::RolloutLimit::Percent(percent) => Limit::Percent(percent.into()),
+            }),
+        };
+        types::v2::RollingUpdate {
+            max_surge: Some(limit(update.max_surge)),
+            max_unavailable: Some(limit(update.max_unavailable)),
+        }
+    }
+}
+
+impl From<types::v2::PreInit> for crate::types::PreInit {
+    fn from(pre_init: types::v2::PreInit) -> Self {
+        crate::types::PreInit {
+            export: (!pre_init.export.is_empty()).then_some(pre_init.export),
+            ready: if pre_init.ready == 0 {
+                crate::types::PreInit::default().ready
+            } else {
+                pre_init.ready
+            },
+        }
+    }
+}
+
+impl From<types::v2::QueuePolicy> for crate::types::QueuePolicy {
+    fn from(policy: types::v2::QueuePolicy) -> Self {
+        match policy {
+            types::v2::QueuePolicy::Queue => crate::types::QueuePolicy::Queue,
+            types::v2::QueuePolicy::Reject => crate::types::QueuePolicy::Reject,
+            types::v2::QueuePolicy::ShedOldest => crate::types::QueuePolicy::ShedOldest,
+        }
+    }
+}
+
+impl From<types::v2::OutgoingHttpLimits> for crate::types::OutgoingHttpLimits {
+    fn from(limits: types::v2::OutgoingHttpLimits) -> Self {
+        crate::types::OutgoingHttpLimits {
+            timeout_ms: limits.timeout_ms,
+            max_response_bytes: limits.max_response_bytes,
+            max_redirects: limits.max_redirects,
+        }
+    }
+}
+
+impl From<types::v2::OutboundLimits> for crate::types::OutboundLimits {
+    fn from(limits: types::v2::OutboundLimits) -> Self {
+        crate::types::OutboundLimits {
+            max_connections: limits.max_connections,
+            max_bytes_per_sec: limits.max_bytes_per_sec,
+        }
+    }
+}
+
+impl From<crate::types::HostHeartbeat> for types::v2::HostHeartbeat {
+    fn from(hb: crate::types::HostHeartbeat) -> Self {
+        types::v2::HostHeartbeat {
+            id: hb.id,
+            hostname: hb.hostname,
+            version: hb.version,
+            started_at: Some(hb.started_at.into()),
+            imports: hb.imports.into_iter().map(Into::into).collect(),
+            exports: hb.exports.into_iter().map(Into::into).collect(),
+            os_name: hb.os_name,
+            os_arch: hb.os_arch,
+            os_kernel: hb.os_kernel,
+            system_cpu_usage: hb.system_cpu_usage,
+            component_count: hb.component_count,
+            workload_count: hb.workload_count,
+            system_memory_total: hb.system_memory_total,
+            system_memory_free: hb.system_memory_free,
+            labels: hb.labels,
+            friendly_name: hb.friendly_name,
+            draining: hb.draining,
+            devices: hb.devices.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<crate::wit::WitInterface> for types::v2::WitInterface {
+    fn from(wi: crate::wit::WitInterface) -> Self {
+        types::v2::WitInterface {
+            namespace: wi.namespace,
+            package: wi.package,
+            version: wi.version.map(|v| v.to_string()).unwrap_or_default(),
+            interfaces: wi.interfaces.into_iter().collect(),
+            config: wi.config,
+        }
+    }
+}
+
+// Conversions from API v2 request types to runtime::host types
+
+impl From<types::v2::WorkloadStopRequest> for crate::types::WorkloadStopRequest {
+    fn from(req: types::v2::WorkloadStopRequest) -> Self {
+        crate::types::WorkloadStopRequest {
+            workload_id: req.workload_id,
+        }
+    }
+}
+
+impl From<types::v2::WorkloadConfigUpdateRequest> for crate::types::WorkloadConfigUpdateRequest {
+    fn from(req: types::v2::WorkloadConfigUpdateRequest) -> Self {
+        crate::types::WorkloadConfigUpdateRequest {
+            workload_id: req.workload_id,
+            component_id: (!req.component_id.is_empty()).then_some(req.component_id),
+            update: crate::types::ConfigUpdate {
+                set: req.set.into_iter().collect(),
+                remove: req.remove,
+            },
+        }
+    }
+}
+
+impl From<crate::types::WorkloadConfigUpdateResponse> for types::v2::WorkloadConfigUpdateResponse {
+    fn from(res: crate::types::WorkloadConfigUpdateResponse) -> Self {
+        types::v2::WorkloadConfigUpdateResponse {
+            component_ids: res.component_ids,
+            service_recycled: res.service_recycled,
+        }
+    }
+}
+
+impl From<types::v2::WorkloadStatusRequest> for crate::types::WorkloadStatusRequest {
+    fn from(req: types::v2::WorkloadStatusRequest) -> Self {
+        crate::types::WorkloadStatusRequest {
+            workload_id: req.workload_id,
+            namespace: (!req.namespace.is_empty()).then_some(req.namespace),
+        }
+    }
+}
+
+impl From<types::v2::WorkloadListRequest> for crate::types::WorkloadListRequest {
+    fn from(req: types::v2::WorkloadListRequest) -> Self {
+        crate::types::WorkloadListRequest {
+            namespace: (!req.namespace.is_empty()).then_some(req.namespace),
+        }
+    }
+}
+
+// Conversions from runtime::host response types to API v2 types
+
+impl From<crate::types::WorkloadStartResponse> for types::v2::WorkloadStartResponse {
+    fn from(resp: crate::types::WorkloadStartResponse) -> Self {
+        types::v2::WorkloadStartResponse {
+            workload_status: Some(resp.workload_status.into()),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadStopResponse> for types::v2::WorkloadStopResponse {
+    fn from(resp: crate::types::WorkloadStopResponse) -> Self {
+        types::v2::WorkloadStopResponse {
+            workload_status: Some(resp.workload_status.into()),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadUpdateResponse> for types::v2::WorkloadUpdateResponse {
+    fn from(resp: crate::types::WorkloadUpdateResponse) -> Self {
+        types::v2::WorkloadUpdateResponse {
+            workload_status: Some(resp.workload_status.into()),
+            abandoned_invocations: resp.abandoned_invocations,
+        }
+    }
+}
+
+impl From<crate::types::WorkloadStatusResponse> for types::v2::WorkloadStatusResponse {
+    fn from(resp: crate::types::WorkloadStatusResponse) -> Self {
+        types::v2::WorkloadStatusResponse {
+            workload_status: Some(resp.workload_status.into()),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadSummary> for types::v2::WorkloadSummary {
+    fn from(summary: crate::types::WorkloadSummary) -> Self {
+        types::v2::WorkloadSummary {
+            namespace: summary.namespace,
+            name: summary.name,
+            workload_status: Some(summary.workload_status.into()),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadStatus> for types::v2::WorkloadStatus {
+    fn from(status: crate::types::WorkloadStatus) -> Self {
+        types::v2::WorkloadStatus {
+            workload_id: status.workload_id,
+            workload_state: types::v2::WorkloadState::from(status.workload_state).into(),
+            message: status.message,
+            usage: status.usage.map(|usage| types::v2::WorkloadUsage {
+                cpu_usage_us: usage.cpu_usage_us,
+                cpu_throttled_us: usage.cpu_throttled_us,
+                cpu_throttled_periods: usage.cpu_throttled_periods,
+            }),
+            components: status.components.into_iter().map(Into::into).collect(),
+            activity: status.activity.map(Into::into),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadActivity> for types::v2::WorkloadActivity {
+    fn from(activity: crate::types::WorkloadActivity) -> Self {
+        types::v2::WorkloadActivity {
+            component_count: activity.component_count,
+            uptime_ms: activity.uptime.as_millis() as u64,
+            invocations: activity.invocations,
+            failed_invocations: activity.failed_invocations,
+            in_flight_invocations: activity.in_flight_invocations,
+            invocation_time_us: activity.invocation_time.as_micros() as u64,
+            fuel: activity.fuel,
+            cpu_throttled_us: activity.cpu_throttled.as_micros() as u64,
+            last_error: activity.last_error.unwrap_or_default(),
+            pools: activity
+                .pools
+                .into_iter()
+                .map(|pool| types::v2::PoolUtilization {
+                    name: pool.name,
+                    threads: pool.threads,
+                    busy: pool.busy,
+                })
+                .collect(),
+            routes: activity.routes.into_iter().map(Into::into).collect(),
+            interfaces: activity
+                .interfaces
+                .into_iter()
+                .map(|binding| types::v2::InterfaceBinding {
+                    component_id: binding.component_id,
+                    plugin_id: binding.plugin_id,
+                    requested: binding.requested,
+                    version: binding
+                        .version
+                        .map(|version| version.to_string())
+                        .unwrap_or_default(),
+                })
+                .collect(),
+            crash_loops: activity
+                .crash_loops
+                .into_iter()
+                .map(|crash_loop| types::v2::CrashLoop {
+                    component_id: crash_loop.component_id,
+                    consecutive_failures: crash_loop.consecutive_failures,
+                    retry_after_ms: crash_loop.retry_after.as_millis() as u64,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<crate::types::ComponentProvenance> for types::v2::ComponentProvenance {
+    fn from(provenance: crate::types::ComponentProvenance) -> Self {
+        types::v2::ComponentProvenance {
+            component_id: provenance.component_id,
+            digest: provenance.digest,
+            size_bytes: provenance.size_bytes,
+            version: provenance.version.unwrap_or_default(),
+            revision: provenance.revision.unwrap_or_default(),
+            source: provenance.source.unwrap_or_default(),
+            authors: provenance.authors.unwrap_or_default(),
+            licenses: provenance.licenses.unwrap_or_default(),
+            producers: provenance
+                .producers
+                .into_iter()
+                .map(|producer| types::v2::ComponentProducer {
+                    field: producer.field,
+                    name: producer.name,
+                    version: producer.version,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<crate::types::VolumeMount> for types::v2::VolumeMount {
+    fn from(vm: crate::types::VolumeMount) -> Self {
+        types::v2::VolumeMount {
+            name: vm.name,
+            mount_path: vm.mount_path,
+            read_only: vm.read_only,
+            max_bytes_written: vm.max_bytes_written,
+            max_files: vm.max_files,
+        }
+    }
+}
+
+impl From<crate::types::Volume> for types::v2::Volume {
+    fn from(v: crate::types::Volume) -> Self {
+        types::v2::Volume {
+            name: v.name,
+            volume_type: Some(match v.volume_type {
+                crate::types::VolumeType::HostPath(hp) => {
+                    types::v2::volume::VolumeType::HostPath(types::v2::HostPathVolume {
+                        local_path: hp.local_path,
+                    })
+                }
+                crate::types::VolumeType::EmptyDir(ed) => {
+                    types::v2::volume::VolumeType::EmptyDir(types::v2::EmptyDirVolume {
+                        medium: match ed.medium {
+                            crate::types::StorageMedium::Disk => {
+                                types::v2::empty_dir_volume::StorageMedium::Disk
+                            }
+                            crate::types::StorageMedium::Memory => {
+                                types::v2::empty_dir_volume::StorageMedium::Memory
+                            }
+                        } as i32,
+                        size_limit_bytes: ed.size_limit_bytes,
+                    })
+                }
+                crate::types::VolumeType::Named(named) => {
+                    types::v2::volume::VolumeType::Named(types::v2::NamedVolume {
+                        name: named.name,
+                    })
+                }
+                crate::types::VolumeType::Oci(oci) => {
+                    types::v2::volume::VolumeType::Oci(types::v2::OciVolume {
+                        reference: oci.reference,
+                    })
+                }
+                crate::types::VolumeType::Nfs(nfs) => {
+                    types::v2::volume::VolumeType::Nfs(types::v2::NfsVolume {
+                        server: nfs.server,
+                        path: nfs.path,
+                        options: nfs.options,
+                    })
+                }
+                crate::types::VolumeType::S3(s3) => {
+                    types::v2::volume::VolumeType::S3(types::v2::S3Volume {
+                        bucket: s3.bucket,
+                        region: s3.region,
+                        prefix: s3.prefix,
+                        endpoint: s3.endpoint,
+                    })
+                }
+            }),
+        }
+    }
+}
+
+impl From<crate::types::LocalResources> for types::v2::LocalResources {
+    fn from(lr: crate::types::LocalResources) -> Self {
+        types::v2::LocalResources {
+            memory_limit_mb: lr.memory_limit_mb,
+            cpu_limit: lr.cpu_limit,
+            config: lr.config,
+            environment: lr.environment,
+            env_files: lr.env_files.into_iter().map(Into::into).collect(),
+            volume_mounts: lr.volume_mounts.into_iter().map(Into::into).collect(),
+            allowed_hosts: lr.allowed_hosts,
+            devices: lr.devices.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadState> for types::v2::WorkloadState {
+    fn from(state: crate::types::WorkloadState) -> Self {
+        match state {
+            crate::types::WorkloadState::Unspecified => types::v2::WorkloadState::Unspecified,
+            crate::types::WorkloadState::Starting => types::v2::WorkloadState::Starting,
+            crate::types::WorkloadState::Running => types::v2::WorkloadState::Running,
+            crate::types::WorkloadState::Completed => types::v2::WorkloadState::Completed,
+            crate::types::WorkloadState::Stopping => types::v2::WorkloadState::Stopping,
+            crate::types::WorkloadState::Error => types::v2::WorkloadState::Error,
+        }
+    }
+}
+
+impl From<types::v2::WorkloadState> for crate::types::WorkloadState {
+    fn from(state: types::v2::WorkloadState) -> Self {
+        match state {
+            types::v2::WorkloadState::Unspecified => crate::types::WorkloadState::Unspecified,
+            types::v2::WorkloadState::Starting => crate::types::WorkloadState::Starting,
+            types::v2::WorkloadState::Running => crate::types::WorkloadState::Running,
+            types::v2::WorkloadState::Completed => crate::types::WorkloadState::Completed,
+            types::v2::WorkloadState::Stopping => crate::types::WorkloadState::Stopping,
+            types::v2::WorkloadState::Error => crate::types::WorkloadState::Error,
+        }
+    }
+}
+
+impl From<types::v2::WorkloadStatus> for crate::types::WorkloadStatus {
+    fn from(status: types::v2::WorkloadStatus) -> Self {
+        crate::types::WorkloadStatus {
+            workload_id: status.workload_id,
+            // Unknown states from newer peers are reported as unspecified
+            workload_state: types::v2::WorkloadState::try_from(status.workload_state)
+                .unwrap_or(types::v2::WorkloadState::Unspecified)
+                .into(),
+            message: status.message,
+            usage: status.usage.map(|usage| crate::types::WorkloadUsage {
+                cpu_usage_us: usage.cpu_usage_us,
+                cpu_throttled_us: usage.cpu_throttled_us,
+                cpu_throttled_periods: usage.cpu_throttled_periods,
+            }),
+            components: status.components.into_iter().map(Into::into).collect(),
+            activity: status.activity.map(Into::into),
+        }
+    }
+}
+
+impl From<types::v2::WorkloadActivity> for crate::types::WorkloadActivity {
+    fn from(activity: types::v2::WorkloadActivity) -> Self {
+        crate::types::WorkloadActivity {
+            component_count: activity.component_count,
+            uptime: std::time::Duration::from_millis(activity.uptime_ms),
+            invocations: activity.invocations,
+            failed_invocations: activity.failed_invocations,
+            in_flight_invocations: activity.in_flight_invocations,
+            invocation_time: std::time::Duration::from_micros(activity.invocation_time_us),
+            fuel: activity.fuel,
+            cpu_throttled: std::time::Duration::from_micros(activity.cpu_throttled_us),
+            last_error: (!activity.last_error.is_empty()).then_some(activity.last_error),
+            pools: activity
+                .pools
+                .into_iter()
+                .map(|pool| crate::types::PoolUtilization {
+                    name: pool.name,
+                    threads: pool.threads,
+                    busy: pool.busy,
+                })
+                .collect(),
+            routes: activity.routes.into_iter().map(Into::into).collect(),
+            interfaces: activity
+                .interfaces
+                .into_iter()
+                .map(|binding| crate::types::InterfaceBinding {
+                    component_id: binding.component_id,
+                    plugin_id: binding.plugin_id,
+                    requested: binding.requested,
+                    version: binding.version.parse().ok(),
+                })
+                .collect(),
+            crash_loops: activity
+                .crash_loops
+                .into_iter()
+                .map(|crash_loop| crate::types::CrashLoopStatus {
+                    component_id: crash_loop.component_id,
+                    consecutive_failures: crash_loop.consecutive_failures,
+                    retry_after: std::time::Duration::from_millis(crash_loop.retry_after_ms),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<types::v2::ComponentProvenance> for crate::types::ComponentProvenance {
+    fn from(provenance: types::v2::ComponentProvenance) -> Self {
+        let non_empty = |s: String| (!s.is_empty()).then_some(s);
+        crate::types::ComponentProvenance {
+            component_id: provenance.component_id,
+            digest: provenance.digest,
+            size_bytes: provenance.size_bytes,
+            version: non_empty(provenance.version),
+            revision: non_empty(provenance.revision),
+            source: non_empty(provenance.source),
+            authors: non_empty(provenance.authors),
+            licenses: non_empty(provenance.licenses),
+            producers: provenance
+                .producers
+                .into_iter()
+                .map(|producer| crate::types::ComponentProducer {
+                    field: producer.field,
+                    name: producer.name,
+                    version: producer.version,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Conversions from runtime::host request types to API v2 types
+
+impl From<crate::types::WorkloadStopRequest> for types::v2::WorkloadStopRequest {
+    fn from(req: crate::types::WorkloadStopRequest) -> Self {
+        types::v2::WorkloadStopRequest {
+            workload_id: req.workload_id,
+        }
+    }
+}
+
+impl From<crate::types::WorkloadStatusRequest> for types::v2::WorkloadStatusRequest {
+    fn from(req: crate::types::WorkloadStatusRequest) -> Self {
+        types::v2::WorkloadStatusRequest {
+            workload_id: req.workload_id,
+            namespace: req.namespace.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<crate::types::WorkloadListRequest> for types::v2::WorkloadListRequest {
+    fn from(req: crate::types::WorkloadListRequest) -> Self {
+        types::v2::WorkloadListRequest {
+            namespace: req.namespace.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<types::v2::WorkloadSummary> for crate::types::WorkloadSummary {
+    fn from(summary: types::v2::WorkloadSummary) -> Self {
+        crate::types::WorkloadSummary {
+            namespace: summary.namespace,
+            name: summary.name,
+            workload_status: summary.workload_status.unwrap_or_default().into(),
+        }
+    }
+}
+
+impl TryFrom<types::v2::WorkloadInvokeRequest> for crate::types::WorkloadInvokeRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(req: types::v2::WorkloadInvokeRequest) -> Result<Self, Self::Error> {
+        Ok(crate::types::WorkloadInvokeRequest {
+            workload_id: req.workload_id,
+            component_id: (!req.component_id.is_empty()).then_some(req.component_id),
+            export: req.export,
+            params: req
+                .params
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    value_from_api(value).with_context(|| format!("invalid parameter {i}"))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+#[cfg(feature = "instance-debug")]
+impl From<types::v2::WorkloadInstanceDumpRequest> for crate::types::WorkloadInstanceDumpRequest {
+    fn from(req: types::v2::WorkloadInstanceDumpRequest) -> Self {
+        crate::types::WorkloadInstanceDumpRequest {
+            workload_id: req.workload_id,
+            instance_id: req.instance_id,
+        }
+    }
+}
+
+#[cfg(feature = "instance-debug")]
+impl From<crate::engine::debug::InstanceInfo> for types::v2::WorkloadInstance {
+    fn from(info: crate::engine::debug::InstanceInfo) -> Self {
+        types::v2::WorkloadInstance {
+            instance_id: info.instance_id,
+            component_id: info.component_id,
+            age_ms: info.age.as_millis() as u64,
+            memory_bytes: info.memory_bytes,
+            invocation: info.invocation.unwrap_or_default(),
+            dump_pending: info.dump_pending,
+        }
+    }
+}
+
+impl TryFrom<types::v2::WorkloadLogsRequest> for crate::host::logs::LogQuery {
+    type Error = anyhow::Error;
+
+    fn try_from(req: types::v2::WorkloadLogsRequest) -> Result<Self, Self::Error> {
+        Ok(crate::host::logs::LogQuery {
+            workload_id: req.workload_id,
+            component_id: (!req.component_id.is_empty()).then_some(req.component_id),
+            min_level: match req.level.as_str() {
+                "" => Default::default(),
+                level => level.parse()?,
+            },
+            tail: (req.tail > 0).then_some(req.tail as usize),
+            follow: req.follow,
+        })
+    }
+}
+
+impl From<&crate::host::logs::LogRecord> for types::v2::WorkloadLogLine {
+    fn from(record: &crate::host::logs::LogRecord) -> Self {
+        types::v2::WorkloadLogLine {
+            timestamp: Some(record.timestamp.into()),
+            workload_id: record.workload_id.to_string(),
+            workload_name: record.workload_name.to_string(),
+            workload_namespace: record.workload_namespace.to_string(),
+            component_id: record.component_id.to_string(),
+            level: record.level.to_string(),
+            context: record.context.clone(),
+            message: record.message.clone(),
+        }
+    }
+}
+
+impl From<&crate::host::events::LifecycleEvent> for types::v2::WorkloadEvent {
+    fn from(event: &crate::host::events::LifecycleEvent) -> Self {
+        types::v2::WorkloadEvent {
+            id: event.id.clone(),
+            r#type: event.event_type.as_str().to_string(),
+            host_id: event.host_id.clone(),
+            workload_id: event.workload_id.clone(),
+            workload_name: event.workload_name.clone().unwrap_or_default(),
+            component_id: event.component_id.clone().unwrap_or_default(),
+            rollout_id: event.rollout_id.clone().unwrap_or_default(),
+            message: event.message.clone(),
+            timestamp: Some(std::time::SystemTime::from(event.timestamp).into()),
+            missed: 0,
+        }
+    }
+}
+
+// Conversions between API v2 and internal volume management types
+
+impl From<types::v2::VolumeCreateRequest> for crate::types::VolumeCreateRequest {
+    fn from(req: types::v2::VolumeCreateRequest) -> Self {
+        crate::types::VolumeCreateRequest {
+            name: req.name,
+            labels: req.labels,
+        }
+    }
+}
+
+impl From<types::v2::VolumeInspectRequest> for crate::types::VolumeInspectRequest {
+    fn from(req: types::v2::VolumeInspectRequest) -> Self {
+        crate::types::VolumeInspectRequest { name: req.name }
+    }
+}
+
+impl From<types::v2::VolumeDeleteRequest> for crate::types::VolumeDeleteRequest {
+    fn from(req: types::v2::VolumeDeleteRequest) -> Self {
+        crate::types::VolumeDeleteRequest { name: req.name }
+    }
+}
+
+impl From<crate::types::VolumeInfo> for types::v2::VolumeInfo {
+    fn from(info: crate::types::VolumeInfo) -> Self {
+        types::v2::VolumeInfo {
+            name: info.name,
+            labels: info.labels,
+            created_at: Some(info.created_at.into()),
+            path: info.path.to_string_lossy().to_string(),
+            size_bytes: info.size_bytes,
+            workloads: info.workloads,
+        }
+    }
+}
+
+impl From<crate::types::StagedComponent> for types::v2::StagedComponent {
+    fn from(staged: crate::types::StagedComponent) -> Self {
+        types::v2::StagedComponent {
+            image: staged.reference,
+            component_digest: staged.digest,
+            size_bytes: staged.bytes.len() as u64,
+            staged_at: Some(staged.staged_at.into()),
+        }
+    }
+}
+
+// Conversions between API v2 and internal HTTP route types
+
+impl From<RouteEntry> for types::v2::Route {
+    fn from(entry: RouteEntry) -> Self {
+        types::v2::Route {
+            host: entry.route.host,
+            path: entry.route.path.unwrap_or_default(),
+            methods: entry.route.methods,
+            produces: entry.route.produces,
+            weight: entry.route.weight,
+            headers: entry
+                .route
+                .headers
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            workload_id: entry.workload_id,
+            workload_name: entry.workload_name,
+        }
+    }
+}
+
+impl From<types::v2::Route> for RouteEntry {
+    fn from(route: types::v2::Route) -> Self {
+        RouteEntry {
+            route: HttpRouteConfig {
+                host: route.host,
+                path: (!route.path.is_empty()).then_some(route.path),
+                methods: route.methods,
+                headers: route
+                    .headers
+                    .iter()
+                    .filter_map(|header| header.parse().ok())
+                    .collect(),
+                produces: route.produces,
+                weight: route.weight,
+                ..Default::default()
+            },
+            workload_id: route.workload_id,
+            workload_name: route.workload_name,
+        }
+    }
+}
+
+impl From<RoutePin> for types::v2::RoutePin {
+    fn from(pin: RoutePin) -> Self {
+        types::v2::RoutePin {
+            host: pin.host,
+            path: pin.path.unwrap_or_default(),
+            workload_name: pin.workload_name,
+        }
+    }
+}
+
+impl From<types::v2::RoutePin> for RoutePin {
+    fn from(pin: types::v2::RoutePin) -> Self {
+        RoutePin {
+            host: pin.host,
+            path: (!pin.path.is_empty()).then_some(pin.path),
+            workload_name: pin.workload_name,
+        }
+    }
+}
+
+impl From<RouteHold> for types::v2::RouteHold {
+    fn from(hold: RouteHold) -> Self {
+        let mode = match hold.mode {
+            RouteHoldMode::Maintenance => types::v2::RouteHoldMode::Maintenance,
+            RouteHoldMode::Disabled => types::v2::RouteHoldMode::Disabled,
+        };
+        types::v2::RouteHold {
+            host: hold.host,
+            path: hold.path.unwrap_or_default(),
+            mode: mode.into(),
+            retry_after_secs: hold.retry_after_secs.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<types::v2::RouteHold> for RouteHold {
+    fn from(hold: types::v2::RouteHold) -> Self {
+        RouteHold {
+            mode: match hold.mode() {
+                types::v2::RouteHoldMode::Maintenance => RouteHoldMode::Maintenance,
+                types::v2::RouteHoldMode::Disabled => RouteHoldMode::Disabled,
+            },
+            host: hold.host,
+            path: (!hold.path.is_empty()).then_some(hold.path),
+            retry_after_secs: (hold.retry_after_secs > 0).then_some(hold.retry_after_secs),
+        }
+    }
+}
+
+impl From<crate::types::RoutingTable> for types::v2::RouteListResponse {
+    fn from(table: crate::types::RoutingTable) -> Self {
+        types::v2::RouteListResponse {
+            routes: table.routes.into_iter().map(Into::into).collect(),
+            pins: table.pins.into_iter().map(Into::into).collect(),
+            holds: table.holds.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<types::v2::RouteReleaseRequest> for crate::types::RouteReleaseRequest {
+    fn from(req: types::v2::RouteReleaseRequest) -> Self {
+        crate::types::RouteReleaseRequest {
+            host: req.host,
+            path: (!req.path.is_empty()).then_some(req.path),
+        }
+    }
+}
+
+impl From<types::v2::RouteWeightRequest> for RouteWeight {
+    fn from(req: types::v2::RouteWeightRequest) -> Self {
+        RouteWeight::new(req.workload_name, req.weight)
+    }
+}
+
+impl From<types::v2::RouteInvalidateRequest> for crate::types::RouteInvalidateRequest {
+    fn from(req: types::v2::RouteInvalidateRequest) -> Self {
+        crate::types::RouteInvalidateRequest {
+            host: (!req.host.is_empty()).then_some(req.host),
+            path: (!req.path.is_empty()).then_some(req.path),
+        }
+    }
+}
+
+impl From<types::v2::RouteUnpinRequest> for crate::types::RouteUnpinRequest {
+    fn from(req: types::v2::RouteUnpinRequest) -> Self {
+        crate::types::RouteUnpinRequest {
+            host: req.host,
+            path: (!req.path.is_empty()).then_some(req.path),
+        }
+    }
+}
+
+// Conversions between API v2 and internal HTTP listener types
+
+impl From<TlsFiles> for types::v2::HttpTlsFiles {
+    fn from(files: TlsFiles) -> Self {
+        types::v2::HttpTlsFiles {
+            cert: files.cert.to_string_lossy().into_owned(),
+            key: files.key.to_string_lossy().into_owned(),
+            ca: files
+                .ca
+                .map(|ca| ca.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            sni: files
+                .sni
+                .into_iter()
+                .map(|sni| types::v2::HttpSniFiles {
+                    host: sni.host,
+                    cert: sni.cert.to_string_lossy().into_owned(),
+                    key: sni.key.to_string_lossy().into_owned(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<types::v2::HttpTlsFiles> for TlsFiles {
+    fn from(files: types::v2::HttpTlsFiles) -> Self {
+        TlsFiles {
+            cert: files.cert.into(),
+            key: files.key.into(),
+            ca: (!files.ca.is_empty()).then(|| files.ca.into()),
+            sni: files
+                .sni
+                .into_iter()
+                .map(|sni| SniFiles::new(sni.host, sni.cert, sni.key))
+                .collect(),
+        }
+    }
+}
+
+impl From<crate::types::HttpListeners> for types::v2::HttpListenerListResponse {
+    fn from(listeners: crate::types::HttpListeners) -> Self {
+        types::v2::HttpListenerListResponse {
+            addrs: listeners.addrs.iter().map(ToString::to_string).collect(),
+            tls: listeners.tls.map(Into::into),
+        }
+    }
+}
+
+impl From<RequestPhase> for types::v2::HttpRequestPhase {
+    fn from(phase: RequestPhase) -> Self {
+        match phase {
+            RequestPhase::Queued => types::v2::HttpRequestPhase::Queued,
+            RequestPhase::Running => types::v2::HttpRequestPhase::Running,
+        }
+    }
+}
+
+impl From<InFlightRequest> for types::v2::HttpInFlightRequest {
+    fn from(request: InFlightRequest) -> Self {
+        types::v2::HttpInFlightRequest {
+            method: request.method,
+            host: request.host,
+            path: request.path,
+            request_id: request.request_id.unwrap_or_default(),
+            workload_id: request.workload_id.unwrap_or_default(),
+            route: request.route.unwrap_or_default(),
+            phase: types::v2::HttpRequestPhase::from(request.phase).into(),
+            received_at: Some(request.received_at.into()),
+            age_ms: request.age.as_millis() as u64,
+        }
+    }
+}
+
+impl From<RouteActivity> for types::v2::HttpRouteActivity {
+    fn from(activity: RouteActivity) -> Self {
+        types::v2::HttpRouteActivity {
+            workload_id: activity.workload_id,
+            route: activity.route.unwrap_or_default(),
+            queued: activity.queued as u64,
+            busy: activity.busy as u64,
+            oldest_ms: activity.oldest.as_millis() as u64,
+        }
+    }
+}
+
+impl From<crate::types::HttpIntrospection> for types::v2::HttpIntrospectResponse {
+    fn from(introspection: crate::types::HttpIntrospection) -> Self {
+        types::v2::HttpIntrospectResponse {
+            routes: introspection
+                .routing
+                .routes
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            pins: introspection
+                .routing
+                .pins
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            activity: introspection.activity.into_iter().map(Into::into).collect(),
+            requests: introspection.requests.into_iter().map(Into::into).collect(),
+            open_connections: introspection.open_connections as u64,
+            holds: introspection
+                .routing
+                .holds
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+}
+
+impl From<types::v2::HostAttestationRequest> for AttestationRequest {
+    fn from(req: types::v2::HostAttestationRequest) -> Self {
+        AttestationRequest { nonce: req.nonce }
+    }
+}
+
+impl From<AttestationRequest> for types::v2::HostAttestationRequest {
+    fn from(req: AttestationRequest) -> Self {
+        types::v2::HostAttestationRequest { nonce: req.nonce }
+    }
+}
+
+impl From<SignedAttestationReport> for types::v2::HostAttestationResponse {
+    fn from(signed: SignedAttestationReport) -> Self {
+        types::v2::HostAttestationResponse {
+            report: signed.report,
+            signature: signed.signature,
+            public_key: signed.public_key,
+        }
+    }
+}
+
+impl From<types::v2::HostAttestationResponse> for SignedAttestationReport {
+    fn from(res: types::v2::HostAttestationResponse) -> Self {
+        SignedAttestationReport {
+            report: res.report,
+            signature: res.signature,
+            public_key: res.public_key,
+        }
+    }
+}
+
+// Conversions between API v2 values and component model values
+
+/// Encodes a component model value for the API.
+///
+/// # Errors
+/// Returns an error for resources, futures and streams.
+pub(crate) fn value_to_api(value: &Val) -> anyhow::Result<types::v2::Value> {
+    use types::v2::value::Kind;
+
+    let optional = |value: &Option<Box<Val>>| -> anyhow::Result<Option<Box<types::v2::Value>>> {
+        value
+            .as_deref()
+            .map(|v| value_to_api(v).map(Box::new))
+            .transpose()
+    };
+    let list = |values: &[Val]| -> anyhow::Result<types::v2::ValueList> {
+        Ok(types::v2::ValueList {
+            values: values
+                .iter()
+                .map(value_to_api)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    };
+
+    let kind = match value {
+        Val::Bool(v) => Kind::Bool(*v),
+        Val::S8(v) => Kind::S8((*v).into()),
+        Val::U8(v) => Kind::U8((*v).into()),
+        Val::S16(v) => Kind::S16((*v).into()),
+        Val::U16(v) => Kind::U16((*v).into()),
+        Val::S32(v) => Kind::S32(*v),
+        Val::U32(v) => Kind::U32(*v),
+        Val::S64(v) => Kind::S64(*v),
+        Val::U64(v) => Kind::U64(*v),
+        Val::Float32(v) => Kind::F32(*v),
+        Val::Float64(v) => Kind::F64(*v),
+        Val::Char(v) => Kind::Char((*v).into()),
+        Val::String(v) => Kind::String(v.clone()),
+        Val::List(vs) => Kind::List(list(vs)?),
+        Val::Tuple(vs) => Kind::Tuple(list(vs)?),
+        Val::Record(fields) => Kind::Record(types::v2::Record {
+            fields: fields
+                .iter()
+                .map(|(name, v)| {
+                    Ok(types::v2::RecordField {
+                        name: name.clone(),
+                        value: Some(value_to_api(v)?),
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        }),
+        Val::Variant(case, v) => Kind::Variant(Box::new(types::v2::Variant {
+            case: case.clone(),
+            value: optional(v)?,
+        })),
+        Val::Enum(v) => Kind::Enum(v.clone()),
+        Val::Option(v) => Kind::Option(Box::new(types::v2::OptionValue {
+            value: optional(v)?,
+        })),
+        Val::Result(Ok(v)) => Kind::Result(Box::new(types::v2::ResultValue {
+            ok: true,
+            value: optional(v)?,
+        })),
+        Val::Result(Err(v)) => Kind::Result(Box::new(types::v2::ResultValue {
+            ok: false,
+            value: optional(v)?,
+        })),
+        Val::Flags(names) => Kind::Flags(types::v2::Flags {
+            names: names.clone(),
+        }),
+        other => anyhow::bail!("{other:?} cannot be encoded as an API value"),
+    };
+    Ok(types::v2::Value { kind: Some(kind) })
+}
+
+/// Decodes a component model value from the API.
+///
+/// # Errors
+/// Returns an error if the value is empty or an integer is out of range.
+pub(crate) fn value_from_api(value: types::v2::Value) -> anyhow::Result<Val> {
+    use types::v2::value::Kind;
+
+    let optional = |value: Option<Box<types::v2::Value>>| -> anyhow::Result<Option<Box<Val>>> {
+        value.map(|v| value_from_api(*v).map(Box::new)).transpose()
+    };
+    let list = |values: types::v2::ValueList| -> anyhow::Result<Vec<Val>> {
+        values.values.into_iter().map(value_from_api).collect()
+    };
+
+    Ok(match value.kind.context("empty value")? {
+        Kind::Bool(v) => Val::Bool(v),
+        Kind::S8(v) => Val::S8(v.try_into().context("s8 out of range")?),
+        Kind::U8(v) => Val::U8(v.try_into().context("u8 out of range")?),
+        Kind::S16(v) => Val::S16(v.try_into().context("s16 out of range")?),
+        Kind::U16(v) => Val::U16(v.try_into().context("u16 out of range")?),
+        Kind::S32(v) => Val::S32(v),
+        Kind::U32(v) => Val::U32(v),
+        Kind::S64(v) => Val::S64(v),
+        Kind::U64(v) => Val::U64(v),
+        Kind::F32(v) => Val::Float32(v),
+        Kind::F64(v) => Val::Float64(v),
+        Kind::Char(v) => Val::Char(char::from_u32(v).context("invalid char")?),
+        Kind::String(v) => Val::String(v),
+        Kind::List(vs) => Val::List(list(vs)?),
+        Kind::Tuple(vs) => Val::Tuple(list(vs)?),
+        Kind::Record(record) => Val::Record(
+            record
+                .fields
+                .into_iter()
+                .map(|field| {
+                    let value = field.value.context("record field has no value")?;
+                    Ok((field.name, value_from_api(value)?))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Kind::Variant(variant) => Val::Variant(variant.case, optional(variant.value)?),
+        Kind::Enum(v) => Val::Enum(v),
+        Kind::Option(option) => Val::Option(optional(option.value)?),
+        Kind::Result(result) if result.ok => Val::Result(Ok(optional(result.value)?)),
+        Kind::Result(result) => Val::Result(Err(optional(result.value)?)),
+        Kind::Flags(flags) => Val::Flags(flags.names),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::types::{
+        ComponentProducer, ComponentProvenance, CrashLoopStatus, DeviceClass, DeviceRequest,
+        EmptyDirVolume, EnvFile, HostPathVolume, InterfaceBinding, LocalResources, NfsVolume,
+        OciVolume, PoolUtilization, S3Volume, StorageMedium, Volume, VolumeMount, VolumeType,
+        WorkloadActivity, WorkloadListRequest, WorkloadState, WorkloadStatus,
+        WorkloadStatusRequest, WorkloadStopRequest, WorkloadSummary, WorkloadUsage,
+    };
+    use crate::wit::WitInterface;
+
+    fn round_trip<T, V>(value: T) -> T
+    where
+        T: Into<V>,
+        V: Into<T>,
+    {
+        let wire: V = value.into();
+        wire.into()
+    }
+
+    #[test]
+    fn test_wit_interface_round_trip() {
+        for iface in [
+            "wasi:http/incoming-handler@0.2.0",
+            "wasi:keyvalue/store,atomics@0.2.0-draft",
+            "wasmcloud:messaging/consumer",
+        ] {
+            let mut iface = WitInterface::from(iface);
+            iface
+                .config
+                .insert("host".to_string(), "example.com".to_string());
+            assert_eq!(
+                round_trip::<_, types::v2::WitInterface>(iface.clone()),
+                iface
+            );
+        }
+    }
+
+    #[test]
+    fn test_local_resources_round_trip() {
+        let resources = LocalResources {
+            memory_limit_mb: 256,
+            cpu_limit: 2,
+            config: HashMap::from([("key".to_string(), "value".to_string())]),
+            environment: HashMap::from([("RUST_LOG".to_string(), "debug".to_string())]),
+            env_files: vec![
+                EnvFile::HostPath("/etc/app.env".to_string()),
+                EnvFile::Secret("app-env".to_string()),
+            ],
+            volume_mounts: vec![VolumeMount {
+                name: "data".to_string(),
+                mount_path: "/data".to_string(),
+                read_only: true,
+                max_bytes_written: Some(1024),
+                max_files: Some(16),
+            }],
+            allowed_hosts: vec!["*.wasmcloud.dev".to_string()],
+            devices: vec![DeviceRequest {
+                class: DeviceClass::Gpu,
+                count: 2,
+            }],
+        };
+        let wire = types::v2::LocalResources::from(resources.clone());
+        assert_eq!(LocalResources::try_from(wire).unwrap(), resources);
+
+        let mut unknown = types::v2::LocalResources::from(resources);
+        unknown.devices[0].class = "tpu".to_string();
+        assert!(LocalResources::try_from(unknown).is_err());
+    }
+
+    #[test]
+    fn test_volume_round_trip() {
+        for volume in [
+            Volume {
+                name: "host".to_string(),
+                volume_type: VolumeType::HostPath(HostPathVolume {
+                    local_path: "/tmp".to_string(),
+                }),
+            },
+            Volume {
+                name: "scratch".to_string(),
+                volume_type: VolumeType::EmptyDir(EmptyDirVolume::default()),
+            },
+            Volume {
+                name: "tmpfs".to_string(),
+                volume_type: VolumeType::EmptyDir(EmptyDirVolume {
+                    medium: StorageMedium::Memory,
+                    size_limit_bytes: Some(64 << 20),
+                }),
+            },
+            Volume {
+                name: "models".to_string(),
+                volume_type: VolumeType::Oci(OciVolume {
+                    reference: "ghcr.io/org/models:v1".to_string(),
+                }),
+            },
+            Volume {
+                name: "shared".to_string(),
+                volume_type: VolumeType::Nfs(NfsVolume {
+                    server: "nfs.local".to_string(),
+                    path: "/exports/shared".to_string(),
+                    options: Some("nfsvers=4.1".to_string()),
+                }),
+            },
+            Volume {
+                name: "bucket".to_string(),
+                volume_type: VolumeType::S3(S3Volume {
+                    bucket: "data".to_string(),
+                    region: "us-east-1".to_string(),
+                    prefix: "app/".to_string(),
+                    endpoint: None,
+                }),
+            },
+        ] {
+            assert_eq!(round_trip::<_, types::v2::Volume>(volume.clone()), volume);
+        }
+    }
+
+    #[test]
+    fn test_workload_status_round_trip() {
+        for state in [
+            WorkloadState::Unspecified,
+            WorkloadState::Starting,
+            WorkloadState::Running,
+            WorkloadState::Completed,
+            WorkloadState::Stopping,
+            WorkloadState::Error,
+        ] {
+            let status = WorkloadStatus {
+                workload_id: "workload-1".to_string(),
+                workload_state: state,
+                message: "ok".to_string(),
+                usage: None,
+                components: Vec::new(),
+                activity: None,
+            };
+            assert_eq!(
+                round_trip::<_, types::v2::WorkloadStatus>(status.clone()),
+                status
+            );
+        }
+
+        // States from a newer API revision are reported as unspecified
+        let status = crate::types::WorkloadStatus::from(types::v2::WorkloadStatus {
+            workload_id: "workload-1".to_string(),
+            workload_state: 42,
+            message: String::new(),
+            usage: None,
+            components: Vec::new(),
+            activity: None,
+        });
+        assert_eq!(status.workload_state, WorkloadState::Unspecified);
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let stop = WorkloadStopRequest {
+            workload_id: "workload-1".to_string(),
+        };
+        let stop = round_trip::<_, types::v2::WorkloadStopRequest>(stop);
+        assert_eq!(stop.workload_id, "workload-1");
+
+        for status in [
+            WorkloadStatusRequest {
+                workload_id: "workload-1".to_string(),
+                ..Default::default()
+            },
+            WorkloadStatusRequest {
+                workload_id: "workload-1".to_string(),
+                namespace: Some("team-a".to_string()),
+            },
+        ] {
+            assert_eq!(
+                round_trip::<_, types::v2::WorkloadStatusRequest>(status.clone()),
+                status
+            );
+        }
+
+        for list in [
+            WorkloadListRequest::default(),
+            WorkloadListRequest {
+                namespace: Some("team-a".to_string()),
+            },
+        ] {
+            assert_eq!(
+                round_trip::<_, types::v2::WorkloadListRequest>(list.clone()),
+                list
+            );
+        }
+    }
+
+    #[test]
+    fn test_workload_summary_round_trip() {
+        let summary = WorkloadSummary {
+            namespace: "team-a".to_string(),
+            name: "api".to_string(),
+            workload_status: WorkloadStatus {
+                workload_id: "workload-1".to_string(),
+                workload_state: WorkloadState::Running,
+                message: "Workload is Running".to_string(),
+                usage: Some(WorkloadUsage {
+                    cpu_usage_us: 1_500_000,
+                    cpu_throttled_us: 340_000,
+                    cpu_throttled_periods: 12,
+                }),
+                components: vec![ComponentProvenance {
+                    component_id: "component-1".to_string(),
+                    digest: "9f86d081884c7d65".to_string(),
+                    size_bytes: 1024,
+                    version: Some("1.4.2".to_string()),
+                    revision: Some("9f1c2e7".to_string()),
+                    producers: vec![ComponentProducer {
+                        field: "processed-by".to_string(),
+                        name: "wit-component".to_string(),
+                        version: "0.239.0".to_string(),
+                    }],
+                    ..Default::default()
+                }],
+                activity: Some(WorkloadActivity {
+                    component_count: 2,
+                    uptime: std::time::Duration::from_secs(3_600),
+                    invocations: 1_204,
+                    failed_invocations: 3,
+                    in_flight_invocations: 1,
+                    invocation_time: std::time::Duration::from_millis(48_160),
+                    fuel: 9_600_000,
+                    cpu_throttled: std::time::Duration::from_millis(1_250),
+                    last_error: Some("handler returned an error: timeout".to_string()),
+                    pools: vec![PoolUtilization {
+                        name: "latency".to_string(),
+                        threads: 4,
+                        busy: 1,
+                    }],
+                    routes: vec![RouteEntry {
+                        route: HttpRouteConfig {
+                            path: Some("/api".to_string()),
+                            methods: vec!["GET".to_string()],
+                            ..HttpRouteConfig::new("api.example.com")
+                        },
+                        workload_id: "workload-1".to_string(),
+                        workload_name: "team-a/api".to_string(),
+                    }],
+                    interfaces: vec![InterfaceBinding {
+                        component_id: "api".to_string(),
+                        plugin_id: "wasi-keyvalue".to_string(),
+                        requested: "wasi:keyvalue/store@0.2.0-draft".to_string(),
+                        version: Some("0.2.0-draft".parse().unwrap()),
+                    }],
+                    crash_loops: vec![CrashLoopStatus {
+                        component_id: "worker".to_string(),
+                        consecutive_failures: 7,
+                        retry_after: std::time::Duration::from_secs(4),
+                    }],
+                }),
+            },
+        };
+        assert_eq!(
+            round_trip::<_, types::v2::WorkloadSummary>(summary.clone()),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_rolling_update_round_trip() {
+        let update = crate::types::RollingUpdate {
+            max_surge: crate::types::RolloutLimit::Count(2),
+            max_unavailable: crate::types::RolloutLimit::Percent(10),
+        };
+        assert_eq!(round_trip::<_, types::v2::RollingUpdate>(update), update);
+
+        // Limits left unset default to 25%
+        assert_eq!(
+            crate::types::RollingUpdate::from(types::v2::RollingUpdate::default()),
+            crate::types::RollingUpdate::default()
+        );
+    }
+
+    #[test]
+    fn test_attestation_round_trip() {
+        let request = AttestationRequest {
+            nonce: "nonce".to_string(),
+        };
+        assert_eq!(
+            round_trip::<_, types::v2::HostAttestationRequest>(request.clone()),
+            request
+        );
+
+        let signed = SignedAttestationReport {
+            report: b"{}".to_vec(),
+            signature: vec![1, 2, 3],
+            public_key: vec![4, 5, 6],
+        };
+        assert_eq!(
+            round_trip::<_, types::v2::HostAttestationResponse>(signed.clone()),
+            signed
+        );
+    }
+
+    #[test]
+    fn test_route_pin_round_trip() {
+        for pin in [
+            RoutePin::new("example.com", "default/api"),
+            RoutePin::new("example.com", "default/users").with_path("/users/{id}"),
+        ] {
+            assert_eq!(round_trip::<_, types::v2::RoutePin>(pin.clone()), pin);
+        }
+    }
+
+    #[test]
+    fn test_route_hold_round_trip() {
+        for hold in [
+            RouteHold::new("example.com"),
+            RouteHold::new("example.com")
+                .with_path("/users/{id}")
+                .with_retry_after_secs(300),
+            RouteHold::new("example.com").with_mode(RouteHoldMode::Disabled),
+        ] {
+            assert_eq!(round_trip::<_, types::v2::RouteHold>(hold.clone()), hold);
+        }
+    }
+
+    #[test]
+    fn test_tls_files_round_trip() {
+        for files in [
+            TlsFiles::new("/etc/wash/tls.crt", "/etc/wash/tls.key"),
+            TlsFiles::new("tls.crt", "tls.key").with_ca("ca.crt"),
+            TlsFiles::new("tls.crt", "tls.key").with_sni(SniFiles::new(
+                "*.example.com",
+                "wildcard.crt",
+                "wildcard.key",
+            )),
+        ] {
+            assert_eq!(
+                round_trip::<_, types::v2::HttpTlsFiles>(files.clone()),
+                files
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let value = Val::Record(vec![
+            ("id".to_string(), Val::U8(7)),
+            ("name".to_string(), Val::String("plugin".to_string())),
+            (
+                "tags".to_string(),
+                Val::List(vec![Val::Char('a'), Val::Char('ß')]),
+            ),
+            (
+                "status".to_string(),
+                Val::Result(Err(Some(Box::new(Val::Variant(
+                    "other".to_string(),
+                    Some(Box::new(Val::Option(None))),
+                ))))),
+            ),
+            (
+                "flags".to_string(),
+                Val::Flags(vec!["read".to_string(), "write".to_string()]),
+            ),
+            ("offset".to_string(), Val::S16(-300)),
+        ]);
+        let round_tripped = value_from_api(value_to_api(&value).unwrap()).unwrap();
+        assert_eq!(round_tripped, value);
+
+        let out_of_range = types::v2::Value {
+            kind: Some(types::v2::value::Kind::U8(256)),
+        };
+        assert!(value_from_api(out_of_range).is_err());
+    }
+
+    #[test]
+    fn test_invoke_request_from_api() {
+        let req = types::v2::WorkloadInvokeRequest {
+            workload_id: "workload-1".to_string(),
+            component_id: String::new(),
+            export: "greet".to_string(),
+            params: vec![value_to_api(&Val::String("world".to_string())).unwrap()],
+        };
+        let req = crate::types::WorkloadInvokeRequest::try_from(req).unwrap();
+        assert_eq!(req.component_id, None);
+        assert_eq!(req.params, vec![Val::String("world".to_string())]);
+
+        let invalid = types::v2::WorkloadInvokeRequest {
+            params: vec![types::v2::Value::default()],
+            ..Default::default()
+        };
+        assert!(crate::types::WorkloadInvokeRequest::try_from(invalid).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Forwarding calls to interfaces a cluster host lacks to its peers, see
+//! [`crate::plugin::forward`].
+//!
+//! A [`NatsCallForwarder`] learns which interfaces the other hosts provide from
+//! their heartbeats, and sends every call as a `plugin.invoke` request on the
+//! runtime API of a peer providing the interface that isn't draining. Of these
+//! peers, the one with the lowest latency is called, measured as the moving
+//! average of the round trips of earlier calls, so calls stay with close and
+//! idle peers. A peer that didn't answer is skipped until its next heartbeat.
+//! Calls are not retried on another peer, since they may not be idempotent.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use futures::StreamExt as _;
+use tracing::{debug, warn};
+use wasmtime::component::Val;
+
+use super::convert::{value_from_api, value_to_api};
+use super::types::v2;
+use crate::plugin::forward::{CallForwarder, ForwardedCall};
+use crate::wit::{WitInterface, WitWorld};
+
+/// Peers that missed this many heartbeats are no longer called
+const MISSED_HEARTBEATS: u32 = 3;
+/// Weight of the latest round trip in the latency of a peer
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// What the forwarder knows about a peer.
+#[derive(Debug, Clone)]
+struct Peer {
+    world: WitWorld,
+    draining: bool,
+    /// When its last heartbeat arrived
+    seen: Instant,
+    /// Moving average of the round trips of the calls it answered
+    latency: Option<Duration>,
+    /// Set when a call failed to reach it, until its next heartbeat
+    unreachable: bool,
+}
+
+/// The peers of a host, by ID.
+#[derive(Debug)]
+struct Peers {
+    heartbeat_interval: Duration,
+    peers: std::sync::Mutex<HashMap<String, Peer>>,
+}
+
+impl Peers {
+    fn peers(&self) -> std::sync::MutexGuard<'_, HashMap<String, Peer>> {
+        self.peers
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Records the interfaces a peer provides from its heartbeat.
+    fn observe(&self, heartbeat: v2::HostHeartbeat) {
+        let world = WitWorld {
+            imports: heartbeat.imports.into_iter().map(Into::into).collect(),
+            exports: HashSet::new(),
+        };
+        let mut peers = self.peers();
+        let latency = peers.get(&heartbeat.id).and_then(|peer| peer.latency);
+        peers.insert(
+            heartbeat.id,
+            Peer {
+                world,
+                draining: heartbeat.draining,
+                seen: Instant::now(),
+                latency,
+                unreachable: false,
+            },
+        );
+    }
+
+    /// Returns the peer to call for `interface`: the one with the lowest
+    /// latency, peers never called first.
+    fn select(&self, interface: &WitInterface) -> Option<String> {
+        let expiry = self.heartbeat_interval * MISSED_HEARTBEATS;
+        self.peers()
+            .iter()
+            .filter(|(_, peer)| {
+                !peer.draining
+                    && !peer.unreachable
+                    && peer.seen.elapsed() < expiry
+                    && peer.world.includes_bidirectional(interface)
+            })
+            .min_by(|(a_id, a), (b_id, b)| (a.latency, a_id).cmp(&(b.latency, b_id)))
+            .map(|(id, _)| id.clone())
+    }
+
+    /// Records the outcome of a call to a peer.
+    fn record(&self, peer_id: &str, round_trip: Option<Duration>) {
+        let mut peers = self.peers();
+        let Some(peer) = peers.get_mut(peer_id) else {
+            return;
+        };
+        match round_trip {
+            Some(round_trip) => {
+                peer.latency = Some(match peer.latency {
+                    Some(latency) => {
+                        latency.mul_f64(1.0 - LATENCY_WEIGHT) + round_trip.mul_f64(LATENCY_WEIGHT)
+                    }
+                    None => round_trip,
+                })
+            }
+            None => peer.unreachable = true,
+        }
+    }
+}
+
+/// A [`CallForwarder`] relaying calls to the hosts of a cluster over NATS, see
+/// the [module docs](self).
+#[derive(Clone)]
+pub struct NatsCallForwarder {
+    nats_client: Arc<async_nats::Client>,
+    /// Sent with every call, for peers requiring API tokens
+    token: Option<String>,
+    peers: Arc<Peers>,
+}
+
+impl NatsCallForwarder {
+    /// Creates a forwarder that knows no peer until [`NatsCallForwarder::run`]
+    /// received their heartbeats. `token` must grant `deploy` in every
+    /// namespace on peers requiring API tokens.
+    pub fn new(
+        nats_client: Arc<async_nats::Client>,
+        token: Option<String>,
+        heartbeat_interval: Duration,
+    ) -> Self {
+        Self {
+            nats_client,
+            token,
+            peers: Arc::new(Peers {
+                heartbeat_interval,
+                peers: Default::default(),
+            }),
+        }
+    }
+
+    /// Follows the heartbeats of the hosts of the cluster until the
+    /// subscription ends.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut heartbeats = self
+            .nats_client
+            .subscribe(format!("{}.heartbeat.*", super::OPERATOR_API_PREFIX))
+            .await
+            .context("failed to subscribe to heartbeats")?;
+        while let Some(msg) = heartbeats.next().await {
+            match serde_json::from_slice::<v2::HostHeartbeat>(&msg.payload) {
+                Ok(heartbeat) => self.peers.observe(heartbeat),
+                Err(e) => debug!(subject = %msg.subject, "ignoring malformed heartbeat: {e}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CallForwarder for NatsCallForwarder {
+    fn provides(&self, interface: &WitInterface) -> bool {
+        self.peers.select(interface).is_some()
+    }
+
+    async fn forward(&self, call: ForwardedCall) -> anyhow::Result<Vec<Val>> {
+        let interface = WitInterface::from(call.instance.as_str());
+        let peer_id = self
+            .peers
+            .select(&interface)
+            .with_context(|| format!("no peer provides {}", call.instance))?;
+        let request = v2::InvokeRequest {
+            workload_id: call.workload_id,
+            component_id: call.component_id,
+            instance: call.instance,
+            function: call.function,
+            params: call
+                .params
+                .iter()
+                .map(value_to_api)
+                .collect::<anyhow::Result<_>>()?,
+        };
+
+        let started = Instant::now();
+        let response: anyhow::Result<v2::InvokeResponse> = super::api_request(
+            &self.nats_client,
+            &peer_id,
+            "plugin.invoke",
+            &request,
+            self.token.as_deref(),
+        )
+        .await;
+        let response = match response {
+            Ok(response) => {
+                self.peers.record(&peer_id, Some(started.elapsed()));
+                response
+            }
+            Err(e) => {
+                // Refused calls took a round trip, unanswered ones didn't
+                let answered = e
+                    .chain()
+                    .all(|cause| cause.downcast_ref::<async_nats::RequestError>().is_none());
+                if !answered {
+                    warn!(peer_id, "peer did not answer forwarded call: {e:#}");
+                }
+                self.peers
+                    .record(&peer_id, answered.then(|| started.elapsed()));
+                return Err(e);
+            }
+        };
+        response.results.into_iter().map(value_from_api).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(id: &str, imports: &[&str], draining: bool) -> v2::HostHeartbeat {
+        v2::HostHeartbeat {
+            id: id.to_string(),
+            imports: imports
+                .iter()
+                .map(|import| WitInterface::from(*import).into())
+                .collect(),
+            draining,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select_peer() {
+        let peers = Peers {
+            heartbeat_interval: Duration::from_secs(15),
+            peers: Default::default(),
+        };
+        let keyvalue = WitInterface::from("wasi:keyvalue/store@0.2.0-draft");
+        assert_eq!(peers.select(&keyvalue), None);
+
+        peers.observe(heartbeat("a", &["wasi:keyvalue/store@0.2.0-draft"], false));
+        peers.observe(heartbeat("b", &["wasi:keyvalue/store@0.2.0-draft"], false));
+        peers.observe(heartbeat("c", &["wasi:keyvalue/store@0.2.0-draft"], true));
+        peers.observe(heartbeat(
+            "d",
+            &["wasi:blobstore/blobstore@0.2.0-draft"],
+            false,
+        ));
+        assert_eq!(peers.select(&keyvalue).as_deref(), Some("a"));
+
+        // The peer that answered faster is preferred, peers never called first
+        peers.record("a", Some(Duration::from_millis(20)));
+        assert_eq!(peers.select(&keyvalue).as_deref(), Some("b"));
+        peers.record("b", Some(Duration::from_millis(50)));
+        assert_eq!(peers.select(&keyvalue).as_deref(), Some("a"));
+
+        // Unreachable peers are skipped until their next heartbeat
+        peers.record("a", None);
+        assert_eq!(peers.select(&keyvalue).as_deref(), Some("b"));
+        peers.observe(heartbeat("a", &["wasi:keyvalue/store@0.2.0-draft"], false));
+        assert_eq!(peers.select(&keyvalue).as_deref(), Some("a"));
+
+        peers.observe(heartbeat("a", &[], false));
+        peers.observe(heartbeat("b", &[], true));
+        assert_eq!(peers.select(&keyvalue), None);
+    }
+}
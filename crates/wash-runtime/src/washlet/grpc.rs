@@ -0,0 +1,534 @@
+//! The runtime API served over gRPC.
+//!
+//! A cluster host serves the runtime API as messages over NATS. A control
+//! plane or CLI that can't reach the NATS cluster of the host, e.g. one on
+//! another machine, can manage workloads over gRPC instead: a cluster host
+//! built with [`ClusterHostBuilder::with_grpc_api`](super::ClusterHostBuilder::with_grpc_api)
+//! also serves the `WorkloadService` of
+//! `proto/wasmcloud/runtime/v2/workload_service.proto` on its own listener,
+//! taking the same requests and returning the same responses. Failures are
+//! returned as a gRPC status with the code of the [`HostError`], see
+//! [`HostError::grpc_code`].
+//!
+//! Unlike the NATS API, the gRPC API also streams the events of the host, see
+//! [`crate::host::events`], to clients calling `WorkloadEvents`.
+//!
+//! The listener serves plaintext HTTP/2 unless it is given a [`GrpcTls`]
+//! certificate. With a client CA, clients must present a certificate it
+//! issued (mutual TLS).
+//!
+//! # Authorization
+//!
+//! A listener given [`ApiTokens`] or [`ClientIdentities`] checks every
+//! request like the NATS API does, see [`super::auth`]: the client is granted
+//! the claims of its certificate, if [`ClientIdentities`] maps it, and else
+//! those of the token in the `authorization` metadata of the request. Requests
+//! without either are rejected as `UNAUTHENTICATED`, requests the claims don't
+//! allow as `PERMISSION_DENIED`. A cluster host built with API tokens hands
+//! them to its gRPC API, but reloading its configuration only updates the
+//! tokens of the NATS API.
+//!
+//! Without either, a client CA is the only thing guarding the listener and
+//! every client it issued a certificate to owns the host, so a listener other
+//! hosts or users can reach should always authorize requests.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use futures::{Stream, StreamExt as _};
+use tokio::sync::broadcast::error::RecvError;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use super::auth::{self, ApiTokens, ClientIdentities, TokenClaims};
+use super::types::v2::workload_service_server::{WorkloadService, WorkloadServiceServer};
+use super::types::v2::{
+    WorkloadConfigUpdateRequest, WorkloadConfigUpdateResponse, WorkloadEvent,
+    WorkloadEventsRequest, WorkloadInstanceDumpRequest, WorkloadInstanceDumpResponse,
+    WorkloadInstancesRequest, WorkloadInstancesResponse, WorkloadInvokeRequest,
+    WorkloadInvokeResponse, WorkloadListRequest, WorkloadListResponse, WorkloadLogsRequest,
+    WorkloadLogsResponse, WorkloadStartRequest, WorkloadStartResponse, WorkloadStatusRequest,
+    WorkloadStatusResponse, WorkloadStopRequest, WorkloadStopResponse, WorkloadUpdateRequest,
+    WorkloadUpdateResponse,
+};
+use super::{ApiTarget, check_access};
+use crate::host::error::HostError;
+use crate::host::events::LifecycleEventType;
+use crate::host::{Host, HostApi};
+
+/// The certificate the gRPC listener serves, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcTls {
+    /// PEM file of the certificate chain
+    pub cert: PathBuf,
+    /// PEM file of the private key of `cert`
+    pub key: PathBuf,
+    /// PEM file of the CAs client certificates must be issued by. Clients
+    /// don't need a certificate without one.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl GrpcTls {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+        }
+    }
+
+    /// Requires clients to present a certificate issued by the CAs in the
+    /// PEM file `client_ca`.
+    pub fn with_client_ca(mut self, client_ca: impl Into<PathBuf>) -> Self {
+        self.client_ca = Some(client_ca.into());
+        self
+    }
+
+    async fn server_config(&self) -> anyhow::Result<ServerTlsConfig> {
+        let cert = tokio::fs::read(&self.cert)
+            .await
+            .with_context(|| format!("failed to read {}", self.cert.display()))?;
+        let key = tokio::fs::read(&self.key)
+            .await
+            .with_context(|| format!("failed to read {}", self.key.display()))?;
+        let mut config = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+        if let Some(client_ca) = &self.client_ca {
+            let ca = tokio::fs::read(client_ca)
+                .await
+                .with_context(|| format!("failed to read {}", client_ca.display()))?;
+            config = config.client_ca_root(Certificate::from_pem(ca));
+        }
+        Ok(config)
+    }
+}
+
+/// Serves the runtime API of a host over gRPC, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcApi {
+    addr: SocketAddr,
+    tls: Option<GrpcTls>,
+    api_tokens: Option<ApiTokens>,
+    identities: ClientIdentities,
+}
+
+impl GrpcApi {
+    /// Serves the API on `addr` in plaintext.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            tls: None,
+            api_tokens: None,
+            identities: ClientIdentities::default(),
+        }
+    }
+
+    /// Serves the API over TLS.
+    pub fn with_tls(mut self, tls: GrpcTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Accepts requests carrying a token issued by `api_tokens`, see
+    /// [Authorization](self#authorization).
+    pub fn with_api_tokens(mut self, api_tokens: ApiTokens) -> Self {
+        self.api_tokens = Some(api_tokens);
+        self
+    }
+
+    /// Grants clients the claims mapped to their certificate, see
+    /// [Authorization](self#authorization). Only takes effect with a client
+    /// CA.
+    pub fn with_client_identities(mut self, identities: ClientIdentities) -> Self {
+        self.identities = identities;
+        self
+    }
+
+    /// Returns whether requests are checked against tokens or client
+    /// identities.
+    fn authorizes(&self) -> bool {
+        self.api_tokens.is_some() || !self.identities.is_empty()
+    }
+
+    /// Binds the listener and returns the future serving the API of `host`
+    /// on it until `shutdown` completes.
+    ///
+    /// # Errors
+    /// Returns an error if the certificate can't be read or the address
+    /// can't be bound.
+    pub async fn bind(
+        self,
+        host: Arc<Host>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>> + Send + 'static> {
+        let mut server = Server::builder();
+        if let Some(tls) = &self.tls {
+            server = server
+                .tls_config(tls.server_config().await?)
+                .context("invalid gRPC API TLS configuration")?;
+        }
+        let client_ca = self.tls.as_ref().is_some_and(|tls| tls.client_ca.is_some());
+        if !self.authorizes() && !client_ca {
+            warn!(
+                addr = %self.addr,
+                "gRPC API doesn't authenticate clients, anyone reaching it can manage workloads"
+            );
+        } else if !self.identities.is_empty() && !client_ca {
+            warn!(
+                addr = %self.addr,
+                "gRPC API has client identities but no client CA, clients can't present certificates"
+            );
+        }
+        let incoming = TcpIncoming::bind(self.addr)
+            .with_context(|| format!("failed to bind gRPC API to {}", self.addr))?;
+        info!(
+            addr = %self.addr,
+            tls = self.tls.is_some(),
+            authorizes = self.authorizes(),
+            "serving runtime API over gRPC"
+        );
+        let auth = self.authorizes().then(|| GrpcAuth {
+            api_tokens: self.api_tokens,
+            identities: self.identities,
+        });
+        let router = server.add_service(WorkloadServiceServer::new(WorkloadApi { host, auth }));
+        Ok(async move {
+            router
+                .serve_with_incoming_shutdown(incoming, shutdown)
+                .await
+                .context("gRPC API failed")
+        })
+    }
+}
+
+/// The `WorkloadService` of a host.
+struct WorkloadApi {
+    host: Arc<Host>,
+    /// How requests are authorized, `None` to serve every request
+    auth: Option<GrpcAuth>,
+}
+
+/// The credentials the gRPC API accepts, see
+/// [Authorization](self#authorization).
+struct GrpcAuth {
+    api_tokens: Option<ApiTokens>,
+    identities: ClientIdentities,
+}
+
+impl GrpcAuth {
+    /// Returns the claims of the client sending `request`.
+    fn claims<T>(&self, request: &Request<T>) -> Result<TokenClaims, Status> {
+        let identity = request
+            .peer_certs()
+            .and_then(|certs| certs.first().cloned())
+            .and_then(|cert| self.identities.claims(cert.as_ref()).cloned());
+        if let Some(claims) = identity {
+            return Ok(claims);
+        }
+        let Some(api_tokens) = &self.api_tokens else {
+            return Err(Status::unauthenticated(
+                "client certificate is not granted access",
+            ));
+        };
+        let header = request
+            .metadata()
+            .get(&auth::AUTHORIZATION_HEADER.to_ascii_lowercase())
+            .ok_or_else(|| Status::unauthenticated("request has no token"))?
+            .to_str()
+            .map_err(|_| Status::unauthenticated("malformed authorization"))?;
+        api_tokens
+            .validate_header(header)
+            .map_err(|e| Status::unauthenticated(format!("{e:#}")))
+    }
+}
+
+impl WorkloadApi {
+    /// Checks that the client sending `request` may run `command`, the
+    /// subject of the same request over NATS, on `target`.
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        command: &str,
+        target: ApiTarget,
+    ) -> Result<(), Status> {
+        let Some(auth) = &self.auth else {
+            return Ok(());
+        };
+        let claims = auth.claims(request)?;
+        check_access(self.host.as_ref(), &claims, command, target)
+            .await
+            .map_err(|e| Status::permission_denied(format!("{e:#}")))
+    }
+}
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// Returns the status of a failed request.
+fn status(e: anyhow::Error) -> Status {
+    HostError::from(e).into()
+}
+
+fn invalid(e: anyhow::Error) -> Status {
+    Status::invalid_argument(format!("{e:#}"))
+}
+
+#[tonic::async_trait]
+impl WorkloadService for WorkloadApi {
+    type WorkloadInvokeStream = ResponseStream<WorkloadInvokeResponse>;
+    type WorkloadLogsStream = ResponseStream<WorkloadLogsResponse>;
+    type WorkloadEventsStream = ResponseStream<WorkloadEvent>;
+
+    async fn workload_start(
+        &self,
+        request: Request<WorkloadStartRequest>,
+    ) -> Result<Response<WorkloadStartResponse>, Status> {
+        let Some(namespace) = request
+            .get_ref()
+            .workload
+            .as_ref()
+            .map(|workload| workload.namespace.clone())
+        else {
+            return Err(Status::invalid_argument("workload is required"));
+        };
+        self.authorize(&request, "workload.start", ApiTarget::Namespace(namespace))
+            .await?;
+        let request = request.into_inner();
+        super::workload_start(self.host.as_ref(), request)
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    async fn workload_status(
+        &self,
+        request: Request<WorkloadStatusRequest>,
+    ) -> Result<Response<WorkloadStatusResponse>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.status", target).await?;
+        super::workload_status(self.host.as_ref(), request.into_inner())
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    async fn workload_list(
+        &self,
+        request: Request<WorkloadListRequest>,
+    ) -> Result<Response<WorkloadListResponse>, Status> {
+        let target = ApiTarget::listing(request.get_ref().namespace.clone());
+        self.authorize(&request, "workload.list", target).await?;
+        super::workload_list(self.host.as_ref(), request.into_inner())
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    async fn workload_stop(
+        &self,
+        request: Request<WorkloadStopRequest>,
+    ) -> Result<Response<WorkloadStopResponse>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.stop", target).await?;
+        super::workload_stop(self.host.as_ref(), request.into_inner())
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    async fn workload_update(
+        &self,
+        request: Request<WorkloadUpdateRequest>,
+    ) -> Result<Response<WorkloadUpdateResponse>, Status> {
+        let Some(namespace) = request
+            .get_ref()
+            .workload
+            .as_ref()
+            .map(|workload| workload.namespace.clone())
+        else {
+            return Err(Status::invalid_argument("workload is required"));
+        };
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.update", target).await?;
+        // The replacement must stay in the namespace of the workload
+        self.authorize(&request, "workload.update", ApiTarget::Namespace(namespace))
+            .await?;
+        let request = request.into_inner();
+        super::workload_update(self.host.as_ref(), request)
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    async fn workload_config_update(
+        &self,
+        request: Request<WorkloadConfigUpdateRequest>,
+    ) -> Result<Response<WorkloadConfigUpdateResponse>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.config.update", target)
+            .await?;
+        self.host
+            .workload_config_update(request.into_inner().into())
+            .await
+            .map(|response| Response::new(response.into()))
+            .map_err(Status::from)
+    }
+
+    async fn workload_invoke(
+        &self,
+        request: Request<WorkloadInvokeRequest>,
+    ) -> Result<Response<Self::WorkloadInvokeStream>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.invoke", target).await?;
+        let request =
+            crate::types::WorkloadInvokeRequest::try_from(request.into_inner()).map_err(invalid)?;
+        let response = self
+            .host
+            .workload_invoke(request)
+            .await
+            .map_err(Status::from)?;
+        let results = response
+            .results
+            .iter()
+            .map(super::convert::value_to_api)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(status)?;
+        let responses: Vec<_> = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| WorkloadInvokeResponse {
+                index: index as u32,
+                result: Some(result),
+                ..Default::default()
+            })
+            .chain([WorkloadInvokeResponse {
+                done: true,
+                ..Default::default()
+            }])
+            .map(Ok)
+            .collect();
+        Ok(Response::new(Box::pin(futures::stream::iter(responses))))
+    }
+
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instances(
+        &self,
+        request: Request<WorkloadInstancesRequest>,
+    ) -> Result<Response<WorkloadInstancesResponse>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.instances", target)
+            .await?;
+        let instances = self
+            .host
+            .workload_instances(&request.into_inner().workload_id)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(WorkloadInstancesResponse {
+            instances: instances.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    #[cfg(not(feature = "instance-debug"))]
+    async fn workload_instances(
+        &self,
+        _request: Request<WorkloadInstancesRequest>,
+    ) -> Result<Response<WorkloadInstancesResponse>, Status> {
+        Err(Status::unimplemented(
+            "this host is built without the instance-debug feature",
+        ))
+    }
+
+    #[cfg(feature = "instance-debug")]
+    async fn workload_instance_dump(
+        &self,
+        request: Request<WorkloadInstanceDumpRequest>,
+    ) -> Result<Response<WorkloadInstanceDumpResponse>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.instance.dump", target)
+            .await?;
+        let core = self
+            .host
+            .workload_instance_dump(request.into_inner().into())
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(WorkloadInstanceDumpResponse { core }))
+    }
+
+    #[cfg(not(feature = "instance-debug"))]
+    async fn workload_instance_dump(
+        &self,
+        _request: Request<WorkloadInstanceDumpRequest>,
+    ) -> Result<Response<WorkloadInstanceDumpResponse>, Status> {
+        Err(Status::unimplemented(
+            "this host is built without the instance-debug feature",
+        ))
+    }
+
+    async fn workload_logs(
+        &self,
+        request: Request<WorkloadLogsRequest>,
+    ) -> Result<Response<Self::WorkloadLogsStream>, Status> {
+        let target = ApiTarget::Workload(request.get_ref().workload_id.clone());
+        self.authorize(&request, "workload.logs", target).await?;
+        let (tail, follow_for) = super::workload_log_tail(self.host.as_ref(), request.into_inner())
+            .await
+            .map_err(status)?;
+        let done = WorkloadLogsResponse {
+            done: true,
+            ..Default::default()
+        };
+        let responses = super::workload_log_responses(tail, follow_for)
+            .chain(futures::stream::iter([done]))
+            .map(Ok);
+        Ok(Response::new(Box::pin(responses)))
+    }
+
+    async fn workload_events(
+        &self,
+        request: Request<WorkloadEventsRequest>,
+    ) -> Result<Response<Self::WorkloadEventsStream>, Status> {
+        let target = match request.get_ref().workload_id.as_str() {
+            "" => ApiTarget::all_namespaces(),
+            workload_id => ApiTarget::Workload(workload_id.to_string()),
+        };
+        self.authorize(&request, "workload.events", target).await?;
+        let request = request.into_inner();
+        let types = request
+            .types
+            .iter()
+            .map(|t| t.parse::<LifecycleEventType>())
+            .collect::<anyhow::Result<HashSet<_>>>()
+            .map_err(invalid)?;
+        let workload_id = request.workload_id;
+        let receiver = self.host.subscribe_events();
+        let events = futures::stream::unfold(receiver, move |mut receiver| {
+            let workload_id = workload_id.clone();
+            let types = types.clone();
+            async move {
+                let mut missed = 0;
+                loop {
+                    match receiver.recv().await {
+                        Ok(event)
+                            if (workload_id.is_empty() || event.workload_id == workload_id)
+                                && (types.is_empty() || types.contains(&event.event_type)) =>
+                        {
+                            let event = WorkloadEvent {
+                                missed,
+                                ..event.as_ref().into()
+                            };
+                            return Some((Ok(event), receiver));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(skipped)) => missed += skipped,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
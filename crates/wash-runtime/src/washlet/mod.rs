@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::host::snapshot::HostSnapshot;
 use crate::host::{Host, HostApi};
 use crate::oci::{self, OciConfig};
 use crate::plugin::HostPlugin;
@@ -11,12 +12,23 @@ use opentelemetry_sdk::resource::{Resource, ResourceBuilder};
 use opentelemetry_semantic_conventions::resource;
 use sysinfo::System;
 use tokio::sync::oneshot;
+use tracing::{debug, error, info, warn};
 
+use auth::{ApiTokens, ApiVerb, TokenClaims};
+use reload::{ConfigSource, ReloadHandle};
+
+pub mod auth;
+pub(crate) mod convert;
+pub mod forward;
+pub mod grpc;
 pub mod plugins;
+pub mod reload;
 
 pub const HOST_API_PREFIX: &str = "runtime.host";
 pub const OPERATOR_API_PREFIX: &str = "runtime.operator";
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// How long a stopping host waits for requests in flight by default
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub mod types {
     pub mod v2 {
@@ -34,6 +46,13 @@ pub struct ClusterHostBuilder {
     host_group: Option<String>,
     host_name: Option<String>,
     heartbeat_interval: Option<Duration>,
+    api_tokens: Option<ApiTokens>,
+    config_source: Option<Arc<dyn ConfigSource>>,
+    snapshot_file: Option<std::path::PathBuf>,
+    drain_timeout: Option<Duration>,
+    call_forwarding: bool,
+    forwarding_token: Option<String>,
+    grpc_api: Option<grpc::GrpcApi>,
 }
 
 impl ClusterHostBuilder {
@@ -70,6 +89,140 @@ impl ClusterHostBuilder {
         self
     }
 
+    pub fn with_engine(mut self, engine: crate::engine::Engine) -> Self {
+        self.host_builder = self.host_builder.with_engine(engine);
+        self
+    }
+
+    pub fn with_volume_root(mut self, root: impl Into<std::path::PathBuf>) -> Self {
+        self.host_builder = self.host_builder.with_volume_root(root);
+        self
+    }
+
+    pub fn with_device(mut self, device: crate::host::devices::Device) -> Self {
+        self.host_builder = self.host_builder.with_device(device);
+        self
+    }
+
+    #[cfg(feature = "prometheus-metrics")]
+    pub fn with_metrics(mut self, addr: std::net::SocketAddr) -> Self {
+        self.host_builder = self.host_builder.with_metrics(addr);
+        self
+    }
+
+    #[cfg(feature = "otlp-tracing")]
+    pub fn with_tracing(mut self, tracing: crate::host::otlp_tracing::OtlpTracing) -> Self {
+        self.host_builder = self.host_builder.with_tracing(tracing);
+        self
+    }
+
+    pub fn with_event_sink(
+        mut self,
+        sink: Arc<dyn crate::host::events::LifecycleEventSink>,
+    ) -> Self {
+        self.host_builder = self.host_builder.with_event_sink(sink);
+        self
+    }
+
+    pub fn with_secret_store(
+        mut self,
+        secret_store: Arc<dyn crate::host::interpolate::SecretStore>,
+    ) -> Self {
+        self.host_builder = self.host_builder.with_secret_store(secret_store);
+        self
+    }
+
+    pub fn with_env_allowlist(
+        mut self,
+        env_allowlist: crate::host::interpolate::EnvAllowlist,
+    ) -> Self {
+        self.host_builder = self.host_builder.with_env_allowlist(env_allowlist);
+        self
+    }
+
+    pub fn with_identity_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.host_builder = self.host_builder.with_identity_file(path);
+        self
+    }
+
+    pub fn with_policy(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.host_builder = self.host_builder.with_policy(key, value);
+        self
+    }
+
+    pub fn with_tee_evidence(
+        mut self,
+        provider: Arc<dyn crate::host::attestation::TeeEvidenceProvider>,
+    ) -> Self {
+        self.host_builder = self.host_builder.with_tee_evidence(provider);
+        self
+    }
+
+    /// Requires every API request to carry a token issued by `api_tokens`,
+    /// see [`auth`]. Requests to the gRPC API are checked against them too.
+    pub fn with_api_tokens(mut self, api_tokens: ApiTokens) -> Self {
+        self.api_tokens = Some(api_tokens);
+        self
+    }
+
+    pub fn with_label(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.host_builder = self.host_builder.with_label(key, value);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: crate::host::limits::HostLimits) -> Self {
+        self.host_builder = self.host_builder.with_limits(limits);
+        self
+    }
+
+    /// Reloads the configuration from `source` while the host runs, see
+    /// [`reload`].
+    pub fn with_config_source(mut self, source: Arc<dyn ConfigSource>) -> Self {
+        self.config_source = Some(source);
+        self
+    }
+
+    /// Restores the workloads in the snapshot at `path` when the host starts,
+    /// if it exists, and snapshots the host there when it stops, see
+    /// [`crate::host::snapshot`].
+    pub fn with_snapshot_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.snapshot_file = Some(path.into());
+        self
+    }
+
+    /// Persists the state of the host at `path` as it changes and restores
+    /// it when the host starts, see [`crate::host::state`]. A snapshot
+    /// restored from [`Self::with_snapshot_file`] takes precedence.
+    pub fn with_state_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.host_builder = self
+            .host_builder
+            .with_state_file(crate::host::state::StateFile::new(path));
+        self
+    }
+
+    /// Sets how long the host drains before it stops, waiting for requests
+    /// and invocations in flight, see [`Host::shutdown`]. Defaults to 30
+    /// seconds, zero stops the host right away.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Forwards calls to the interfaces no plugin of the host provides to
+    /// the hosts of the cluster providing them, see [`forward`]. `token` is
+    /// sent to peers requiring API tokens.
+    pub fn with_call_forwarding(mut self, token: Option<String>) -> Self {
+        self.call_forwarding = true;
+        self.forwarding_token = token;
+        self
+    }
+
+    /// Also serves the runtime API over gRPC, see [`grpc`].
+    pub fn with_grpc_api(mut self, grpc_api: grpc::GrpcApi) -> Self {
+        self.grpc_api = Some(grpc_api);
+        self
+    }
+
     pub fn build(self) -> anyhow::Result<ClusterHost> {
         let Some(nats_client) = self.nats_client else {
             anyhow::bail!("nats_client is required");
@@ -86,11 +239,34 @@ impl ClusterHostBuilder {
             builder = builder.with_hostname(host_name)
         }
         let heartbeat_interval = self.heartbeat_interval.unwrap_or(HEARTBEAT_INTERVAL);
+        let call_forwarder = self.call_forwarding.then(|| {
+            forward::NatsCallForwarder::new(
+                nats_client.clone(),
+                self.forwarding_token,
+                heartbeat_interval,
+            )
+        });
+        if let Some(forwarder) = &call_forwarder {
+            builder = builder.with_call_forwarder(Arc::new(forwarder.clone()));
+        }
         let host = builder.build()?;
+        let grpc_api = match (self.grpc_api, &self.api_tokens) {
+            (Some(grpc_api), Some(api_tokens)) => {
+                Some(grpc_api.with_api_tokens(api_tokens.clone()))
+            }
+            (grpc_api, _) => grpc_api,
+        };
         Ok(ClusterHost {
             prepared_host: host,
             nats_client,
             heartbeat_interval,
+            api_tokens: self.api_tokens,
+            config_source: self.config_source,
+            snapshot_file: self.snapshot_file,
+            drain_timeout: self.drain_timeout.unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+            reload: ReloadHandle::default(),
+            call_forwarder,
+            grpc_api,
         })
     }
 }
@@ -99,12 +275,25 @@ pub struct ClusterHost {
     prepared_host: Host,
     nats_client: Arc<async_nats::Client>,
     heartbeat_interval: Duration,
+    api_tokens: Option<ApiTokens>,
+    config_source: Option<Arc<dyn ConfigSource>>,
+    snapshot_file: Option<std::path::PathBuf>,
+    drain_timeout: Duration,
+    reload: ReloadHandle,
+    call_forwarder: Option<forward::NatsCallForwarder>,
+    grpc_api: Option<grpc::GrpcApi>,
 }
 
 impl ClusterHost {
     pub fn host(&self) -> &Host {
         &self.prepared_host
     }
+
+    /// Returns a handle reloading the configuration of the running host. It
+    /// does nothing unless the host was built with a [`ConfigSource`].
+    pub fn reload_handle(&self) -> ReloadHandle {
+        self.reload.clone()
+    }
 }
 
 pub async fn run_cluster_host(
@@ -112,17 +301,73 @@ pub async fn run_cluster_host(
 ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>, anyhow::Error> {
     let (one_shot_tx, mut one_shot_rx) = oneshot::channel();
     let nats_client = cluster_host.nats_client.clone();
-    let host = cluster_host
-        .prepared_host
-        .start()
-        .await
-        .context("failed to start host")?;
+    let snapshot_file = cluster_host.snapshot_file;
+    let mut snapshot = None;
+    if let Some(path) = &snapshot_file
+        && tokio::fs::try_exists(path).await.unwrap_or(false)
+    {
+        match HostSnapshot::read(path).await {
+            Ok(read) => snapshot = Some(read),
+            Err(e) => warn!("failed to read host snapshot, starting empty: {e:#}"),
+        }
+    }
+
+    // Routes and pins are restored before the HTTP listener accepts requests
+    let host = match snapshot {
+        Some(snapshot) => {
+            let (host, report) = cluster_host
+                .prepared_host
+                .start_restored(snapshot)
+                .await
+                .context("failed to start host")?;
+            info!(
+                restored = report.restored.len(),
+                failed = report.failed.len(),
+                artifacts_loaded = report.artifacts_loaded,
+                "restored host snapshot"
+            );
+            host
+        }
+        None => cluster_host
+            .prepared_host
+            .start()
+            .await
+            .context("failed to start host")?,
+    };
+
+    if let Some(forwarder) = cluster_host.call_forwarder {
+        tokio::spawn(async move {
+            if let Err(e) = forwarder.run().await {
+                warn!("stopped following heartbeats of peers: {e:#}");
+            }
+        });
+    }
+
+    // The gRPC API stops with the task serving the API over NATS
+    let (grpc_stop, grpc_stopped) = oneshot::channel::<()>();
+    if let Some(grpc_api) = cluster_host.grpc_api {
+        let serving = grpc_api
+            .bind(host.clone(), async move {
+                _ = grpc_stopped.await;
+            })
+            .await?;
+        tokio::spawn(async move {
+            if let Err(e) = serving.await {
+                warn!("{e:#}");
+            }
+        });
+    }
 
     let heartbeat_interval = cluster_host.heartbeat_interval;
+    let drain_timeout = cluster_host.drain_timeout;
+    let mut api_tokens = cluster_host.api_tokens;
+    let config_source = cluster_host.config_source;
+    let reload_handle = cluster_host.reload;
     let host_id = host.id().to_string();
     let host = host.clone();
 
     let task = tokio::task::spawn(async move {
+        let _grpc_stop = grpc_stop;
         let host_subject = host_subject(host_id.as_ref());
 
         let heartbeat_subject = heartbeat_subject(host_id.as_ref());
@@ -138,7 +383,31 @@ pub async fn run_cluster_host(
                 // Shutdown signal
                 _ = &mut one_shot_rx => {
                     api_subscription.unsubscribe().await.context("failed to unsubscribe from API requests")?;
-                    return host.stop().await.context("failed to stop host");
+                    if !drain_timeout.is_zero() {
+                        host.drain();
+                        // Schedulers learn about the drain before the next heartbeat
+                        let published = async {
+                            let heartbeat = host_heartbeat(&host).await?;
+                            let heartbeat_bytes = serde_json::to_vec(&heartbeat)
+                                .context("failed to serialize heartbeat")?;
+                            nats_client.publish(heartbeat_subject.clone(), heartbeat_bytes.into()).await.context("failed to publish heartbeat")
+                        };
+                        if let Err(e) = published.await {
+                            warn!("failed to publish draining heartbeat: {e:#}");
+                        }
+                    }
+                    if let Some(path) = &snapshot_file {
+                        let result = match host.host_snapshot().await {
+                            Ok(snapshot) => snapshot.write(path).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        match result {
+                            Ok(()) => info!(path = %path.display(), "wrote host snapshot"),
+                            Err(e) => warn!("failed to write host snapshot: {e:#}"),
+                        }
+                    }
+                    // Requests in flight finish while the host stops accepting connections
+                    return host.shutdown(drain_timeout).await.context("failed to stop host");
                 }
                 // Send heartbeat
                 _ = heartbeat_timer.tick() => {
@@ -147,9 +416,120 @@ pub async fn run_cluster_host(
                         .context("failed to serialize heartbeat")?;
                     nats_client.publish(heartbeat_subject.clone(), heartbeat_bytes.into()).await.context("failed to publish heartbeat")?;
                 }
+                // Reload the configuration, e.g. on SIGHUP
+                _ = reload_handle.requested(), if config_source.is_some() => {
+                    if let Some(source) = &config_source {
+                        match reload::reload(source.as_ref(), &host, &mut api_tokens).await {
+                            Ok(changed) => info!(?changed, "reloaded host configuration"),
+                            Err(e) => warn!("failed to reload host configuration: {e:#}"),
+                        }
+                    }
+                }
                 // Handle API requests
                 Some(msg) = api_subscription.next() => {
+                    if let Some(api_tokens) = &api_tokens
+                        && let Err(e) = authorize(&host, api_tokens, &msg).await
+                    {
+                        warn!(subject = %msg.subject, "rejected API request: {e:#}");
+                        reject(&nats_client, &msg, &e).await?;
+                        continue;
+                    }
+                    // Reloading replaces the tokens requests are checked against
+                    if api_command(&msg.subject) == "host.reload" {
+                        let result = match &config_source {
+                            Some(source) => reload::reload(source.as_ref(), &host, &mut api_tokens).await,
+                            None => Err(anyhow::anyhow!("host has no configuration to reload")),
+                        };
+                        if let Some(reply_to) = msg.reply {
+                            match result {
+                                Ok(changed) => {
+                                    info!(?changed, "reloaded host configuration");
+                                    let response = types::v2::HostReloadResponse { changed };
+                                    nats_client.publish(reply_to, to_api(&response)?.into()).await.context("failed to publish API response")?;
+                                }
+                                Err(e) => {
+                                    warn!("failed to reload host configuration: {e:#}");
+                                    reply_error(&nats_client, reply_to, &format!("{e:#}"), "500").await?;
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    // Invocations can run for a long time, so they don't block other requests
+                    if api_command(&msg.subject) == "workload.invoke" {
+                        let host = host.clone();
+                        let nats_client = nats_client.clone();
+                        tokio::spawn(async move {
+                            let subject = msg.subject.clone();
+                            if let Err(e) = workload_invoke(host.as_ref(), &nats_client, msg).await {
+                                error!(%subject, "failed to handle API request: {e:#}");
+                            }
+                        });
+                        continue;
+                    }
+                    // Followers stream for a long time too
+                    if api_command(&msg.subject) == "workload.logs" {
+                        let host = host.clone();
+                        let nats_client = nats_client.clone();
+                        tokio::spawn(async move {
+                            let subject = msg.subject.clone();
+                            if let Err(e) = workload_logs(host.as_ref(), &nats_client, msg).await {
+                                error!(%subject, "failed to handle API request: {e:#}");
+                            }
+                        });
+                        continue;
+                    }
+                    if api_command(&msg.subject) == "plugin.invoke" {
+                        let host = host.clone();
+                        let nats_client = nats_client.clone();
+                        tokio::spawn(async move {
+                            let subject = msg.subject.clone();
+                            if let Err(e) = plugin_invoke(host.as_ref(), &nats_client, msg).await {
+                                error!(%subject, "failed to handle API request: {e:#}");
+                            }
+                        });
+                        continue;
+                    }
+                    // Dumps wait for the instance to reach an epoch tick
+                    #[cfg(feature = "instance-debug")]
+                    if api_command(&msg.subject) == "workload.instance.dump" {
+                        let host = host.clone();
+                        let nats_client = nats_client.clone();
+                        tokio::spawn(async move {
+                            match handle_command(host.as_ref(), &msg).await {
+                                Ok(response) => {
+                                    if let Some(reply_to) = msg.reply
+                                        && let Err(e) = nats_client.publish(reply_to, response.into()).await
+                                    {
+                                        warn!("failed to publish API response: {e}");
+                                    }
+                                }
+                                Err(e) => error!(subject = %msg.subject, "failed to handle API request: {e:#}"),
+                            }
+                        });
+                        continue;
+                    }
                     let response = handle_command(host.as_ref(), &msg).await;
+                    // Pins, holds and weights are persisted right away, so they survive crashes too
+                    if response.is_ok()
+                        && matches!(
+                            api_command(&msg.subject).as_str(),
+                            "route.pin"
+                                | "route.unpin"
+                                | "route.hold"
+                                | "route.release"
+                                | "route.weight"
+                        )
+                        && let Some(path) = &snapshot_file
+                    {
+                        let result = match host.host_snapshot().await {
+                            Ok(snapshot) => snapshot.write(path).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        if let Err(e) = result {
+                            warn!("failed to write host snapshot: {e:#}");
+                        }
+                    }
                     match response {
                         Ok(resp_bytes) => {
                             if let Some(reply_to) = msg.reply {
@@ -196,6 +576,48 @@ pub fn heartbeat_subject(host_id: &str) -> String {
     format!("{OPERATOR_API_PREFIX}.heartbeat.{host_id}")
 }
 
+/// Sends a request to the runtime API of a host and returns its response.
+/// `token` is sent as bearer token, for hosts requiring [`auth`] tokens.
+pub async fn api_request<Req, Resp>(
+    nats_client: &async_nats::Client,
+    host_id: &str,
+    command: &str,
+    request: &Req,
+    token: Option<&str>,
+) -> anyhow::Result<Resp>
+where
+    Req: prost::Message + serde::Serialize,
+    Resp: serde::de::DeserializeOwned,
+{
+    let mut headers = async_nats::HeaderMap::new();
+    if let Some(token) = token {
+        headers.insert(
+            auth::AUTHORIZATION_HEADER,
+            format!("Bearer {token}").as_str(),
+        );
+    }
+    let response = nats_client
+        .request_with_headers(
+            rpc_subject(host_id, command),
+            headers,
+            to_api(request)?.into(),
+        )
+        .await
+        .with_context(|| format!("{command} request to host {host_id} failed"))?;
+    if let Some(error) = response
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("Nats-Service-Error"))
+    {
+        anyhow::bail!(
+            "{command} request to host {host_id} failed: {}",
+            error.as_str()
+        );
+    }
+    serde_json::from_slice(&response.payload)
+        .with_context(|| format!("malformed {command} response from host {host_id}"))
+}
+
 /// Helper function to serialize a message to the API format.
 fn to_api<T: prost::Message + serde::Serialize>(msg: &T) -> Result<Vec<u8>, anyhow::Error> {
     serde_json::to_vec_pretty(msg).map_err(anyhow::Error::new)
@@ -206,11 +628,16 @@ fn from_api<'de, T: serde::Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, anyh
     serde_json::from_slice(bytes).map_err(anyhow::Error::new)
 }
 
+/// Returns the command of an API request subject, e.g. `workload.start`.
+fn api_command(subject: &str) -> String {
+    subject.split('.').skip(3).collect::<Vec<_>>().join(".")
+}
+
 async fn handle_command(
     host: &impl HostApi,
     msg: &async_nats::Message,
 ) -> Result<Vec<u8>, anyhow::Error> {
-    let command = msg.subject.split('.').skip(3).collect::<Vec<_>>().join(".");
+    let command = api_command(&msg.subject);
 
     let payload = &msg.payload;
 
@@ -219,11 +646,28 @@ async fn handle_command(
             let res = host_heartbeat(host).await?;
             to_api(&res)
         }
+        "host.attestation" => {
+            let req: types::v2::HostAttestationRequest = from_api(payload)?;
+            let res: types::v2::HostAttestationResponse =
+                host.attestation(req.into()).await?.into();
+            to_api(&res)
+        }
         "workload.start" => {
             let req: types::v2::WorkloadStartRequest = from_api(payload)?;
             let res = workload_start(host, req).await?;
             to_api(&res)
         }
+        "workload.update" => {
+            let req: types::v2::WorkloadUpdateRequest = from_api(payload)?;
+            let res = workload_update(host, req).await?;
+            to_api(&res)
+        }
+        "workload.config.update" => {
+            let req: types::v2::WorkloadConfigUpdateRequest = from_api(payload)?;
+            let res: types::v2::WorkloadConfigUpdateResponse =
+                host.workload_config_update(req.into()).await?.into();
+            to_api(&res)
+        }
         "workload.stop" => {
             let req: types::v2::WorkloadStopRequest = from_api(payload)?;
             let res = workload_stop(host, req).await?;
@@ -234,11 +678,333 @@ async fn handle_command(
             let res = workload_status(host, req).await?;
             to_api(&res)
         }
+        "workload.list" => {
+            let req: types::v2::WorkloadListRequest = from_api(payload)?;
+            let res = workload_list(host, req).await?;
+            to_api(&res)
+        }
+        "component.stage" => {
+            let req: types::v2::ComponentStageRequest = from_api(payload)?;
+            let res = component_stage(host, req).await?;
+            to_api(&res)
+        }
+        "component.list" => {
+            let res = types::v2::ComponentListResponse {
+                components: host
+                    .component_staged_list()
+                    .await?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            };
+            to_api(&res)
+        }
+        "component.unstage" => {
+            let req: types::v2::ComponentUnstageRequest = from_api(payload)?;
+            host.component_unstage(req.image).await?;
+            to_api(&types::v2::ComponentUnstageResponse {})
+        }
+        "volume.create" => {
+            let req: types::v2::VolumeCreateRequest = from_api(payload)?;
+            let res = volume_create(host, req).await?;
+            to_api(&res)
+        }
+        "volume.list" => {
+            let res = volume_list(host).await?;
+            to_api(&res)
+        }
+        "volume.inspect" => {
+            let req: types::v2::VolumeInspectRequest = from_api(payload)?;
+            let res = volume_inspect(host, req).await?;
+            to_api(&res)
+        }
+        "volume.delete" => {
+            let req: types::v2::VolumeDeleteRequest = from_api(payload)?;
+            host.volume_delete(req.into()).await?;
+            to_api(&types::v2::VolumeDeleteResponse {})
+        }
+        #[cfg(feature = "instance-debug")]
+        "workload.instances" => {
+            let req: types::v2::WorkloadInstancesRequest = from_api(payload)?;
+            let instances = host.workload_instances(&req.workload_id).await?;
+            to_api(&types::v2::WorkloadInstancesResponse {
+                instances: instances.into_iter().map(Into::into).collect(),
+            })
+        }
+        #[cfg(feature = "instance-debug")]
+        "workload.instance.dump" => {
+            let req: types::v2::WorkloadInstanceDumpRequest = from_api(payload)?;
+            let core = host.workload_instance_dump(req.into()).await?;
+            to_api(&types::v2::WorkloadInstanceDumpResponse { core })
+        }
+        "route.list" => {
+            let res: types::v2::RouteListResponse = host.route_list().await?.into();
+            to_api(&res)
+        }
+        "route.pin" => {
+            let req: types::v2::RoutePinRequest = from_api(payload)?;
+            let pin = req.pin.context("route pin request has no pin")?;
+            host.route_pin(pin.into()).await?;
+            to_api(&types::v2::RoutePinResponse {})
+        }
+        "route.unpin" => {
+            let req: types::v2::RouteUnpinRequest = from_api(payload)?;
+            host.route_unpin(req.into()).await?;
+            to_api(&types::v2::RouteUnpinResponse {})
+        }
+        "route.hold" => {
+            let req: types::v2::RouteHoldRequest = from_api(payload)?;
+            let hold = req.hold.context("route hold request has no hold")?;
+            host.route_hold(hold.into()).await?;
+            to_api(&types::v2::RouteHoldResponse {})
+        }
+        "route.release" => {
+            let req: types::v2::RouteReleaseRequest = from_api(payload)?;
+            host.route_release(req.into()).await?;
+            to_api(&types::v2::RouteReleaseResponse {})
+        }
+        "route.invalidate" => {
+            let req: types::v2::RouteInvalidateRequest = from_api(payload)?;
+            let entries = host.route_invalidate(req.into()).await?;
+            to_api(&types::v2::RouteInvalidateResponse { entries })
+        }
+        "route.weight" => {
+            let req: types::v2::RouteWeightRequest = from_api(payload)?;
+            host.route_weight(req.into()).await?;
+            to_api(&types::v2::RouteWeightResponse {})
+        }
+        "http.introspect" => {
+            let res: types::v2::HttpIntrospectResponse = host.http_introspect().await?.into();
+            to_api(&res)
+        }
+        "http.listener.list" => {
+            let res: types::v2::HttpListenerListResponse = host.http_listener_list().await?.into();
+            to_api(&res)
+        }
+        "http.listener.add" => {
+            let req: types::v2::HttpListenerAddRequest = from_api(payload)?;
+            let addr = req
+                .addr
+                .parse()
+                .with_context(|| format!("invalid listen address '{}'", req.addr))?;
+            host.http_listener_add(addr).await?;
+            to_api(&types::v2::HttpListenerAddResponse {})
+        }
+        "http.listener.remove" => {
+            let req: types::v2::HttpListenerRemoveRequest = from_api(payload)?;
+            let addr = req
+                .addr
+                .parse()
+                .with_context(|| format!("invalid listen address '{}'", req.addr))?;
+            host.http_listener_remove(addr).await?;
+            to_api(&types::v2::HttpListenerRemoveResponse {})
+        }
+        "http.tls.reload" => {
+            let req: types::v2::HttpTlsReloadRequest = from_api(payload)?;
+            host.http_tls_reload(req.tls.map(Into::into)).await?;
+            to_api(&types::v2::HttpTlsReloadResponse {})
+        }
         // catch-all
         _ => anyhow::bail!("unknown command: {command}"),
     }
 }
 
+/// Checks that an API request carries a token allowing its command, see
+/// [`check_access`].
+async fn authorize(
+    host: &Host,
+    api_tokens: &ApiTokens,
+    msg: &async_nats::Message,
+) -> anyhow::Result<()> {
+    let command = api_command(&msg.subject);
+    let header = msg
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get(auth::AUTHORIZATION_HEADER))
+        .context("request has no token")?;
+    let claims = api_tokens.validate_header(header.as_str())?;
+
+    let workload_id = match command.as_str() {
+        "workload.stop" => {
+            Some(from_api::<types::v2::WorkloadStopRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.update" => {
+            let req = from_api::<types::v2::WorkloadUpdateRequest>(&msg.payload)?;
+            // The replacement must stay in the namespace of the workload
+            if let Some(workload) = req.workload {
+                check_access(
+                    host,
+                    &claims,
+                    &command,
+                    ApiTarget::Namespace(workload.namespace),
+                )
+                .await?;
+            }
+            Some(req.workload_id)
+        }
+        "workload.config.update" => {
+            Some(from_api::<types::v2::WorkloadConfigUpdateRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.status" => {
+            Some(from_api::<types::v2::WorkloadStatusRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.invoke" => {
+            Some(from_api::<types::v2::WorkloadInvokeRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.instances" => {
+            Some(from_api::<types::v2::WorkloadInstancesRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.logs" => {
+            Some(from_api::<types::v2::WorkloadLogsRequest>(&msg.payload)?.workload_id)
+        }
+        "workload.instance.dump" => {
+            Some(from_api::<types::v2::WorkloadInstanceDumpRequest>(&msg.payload)?.workload_id)
+        }
+        _ => None,
+    };
+    let target = match (command.as_str(), workload_id) {
+        // Staged components are used by workloads of every namespace
+        ("component.stage" | "component.unstage", _) => ApiTarget::all_namespaces(),
+        // Forwarded calls come from workloads of other hosts
+        ("plugin.invoke", _) => ApiTarget::all_namespaces(),
+        ("workload.list", _) => {
+            ApiTarget::listing(from_api::<types::v2::WorkloadListRequest>(&msg.payload)?.namespace)
+        }
+        ("workload.start", _) => ApiTarget::Namespace(
+            from_api::<types::v2::WorkloadStartRequest>(&msg.payload)?
+                .workload
+                .map(|workload| workload.namespace)
+                .unwrap_or_default(),
+        ),
+        (_, Some(workload_id)) => ApiTarget::Workload(workload_id),
+        _ => ApiTarget::Host,
+    };
+    check_access(host, &claims, &command, target).await
+}
+
+/// What an API request acts on, which the namespaces granted to its caller
+/// must cover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ApiTarget {
+    /// The host itself, e.g. its volumes or configuration
+    Host,
+    /// A namespace, or every namespace as [`auth::ALL_NAMESPACES`]
+    Namespace(String),
+    /// A workload, checked against its namespace
+    Workload(String),
+}
+
+impl ApiTarget {
+    pub(crate) fn all_namespaces() -> Self {
+        Self::Namespace(auth::ALL_NAMESPACES.to_string())
+    }
+
+    /// Returns the target of listing the workloads of `namespace`. Listing
+    /// every namespace, with an empty one, takes every namespace.
+    pub(crate) fn listing(namespace: String) -> Self {
+        if namespace.is_empty() {
+            Self::all_namespaces()
+        } else {
+            Self::Namespace(namespace)
+        }
+    }
+}
+
+/// Checks that `claims` allow `command` on `target`. Workloads are checked
+/// against their namespace, which only callers granted every namespace may
+/// skip for workloads the host doesn't know (yet).
+pub(crate) async fn check_access(
+    host: &Host,
+    claims: &TokenClaims,
+    command: &str,
+    target: ApiTarget,
+) -> anyhow::Result<()> {
+    let namespace = match target {
+        ApiTarget::Host => None,
+        ApiTarget::Namespace(namespace) => Some(namespace),
+        ApiTarget::Workload(workload_id) => match host.workload_namespace(&workload_id).await {
+            Some(namespace) => Some(namespace),
+            // Unknown or starting workloads could be in any namespace
+            None if claims.has_all_namespaces() => None,
+            None => anyhow::bail!(
+                "'{}' is not granted access to workload {workload_id}",
+                claims.subject
+            ),
+        },
+    };
+
+    let verb = ApiVerb::for_command(command);
+    anyhow::ensure!(
+        claims.allows(verb, namespace.as_deref()),
+        "'{}' is not allowed to {verb}{}",
+        claims.subject,
+        namespace
+            .map(|namespace| format!(" in namespace {namespace}"))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Replies to a rejected API request. `workload.invoke` and `workload.logs`
+/// requests get a final response carrying the error, other requests an empty
+/// reply with `Nats-Service-Error` headers.
+async fn reject(
+    nats_client: &async_nats::Client,
+    msg: &async_nats::Message,
+    error: &anyhow::Error,
+) -> anyhow::Result<()> {
+    let Some(reply_to) = msg.reply.clone() else {
+        return Ok(());
+    };
+    if api_command(&msg.subject) == "workload.invoke" {
+        let done = types::v2::WorkloadInvokeResponse {
+            done: true,
+            error: format!("unauthorized: {error:#}"),
+            ..Default::default()
+        };
+        nats_client
+            .publish(reply_to, to_api(&done)?.into())
+            .await
+            .context("failed to publish API response")?;
+    } else if api_command(&msg.subject) == "workload.logs" {
+        let done = types::v2::WorkloadLogsResponse {
+            done: true,
+            error: format!("unauthorized: {error:#}"),
+            ..Default::default()
+        };
+        nats_client
+            .publish(reply_to, to_api(&done)?.into())
+            .await
+            .context("failed to publish API response")?;
+    } else {
+        reply_error(
+            nats_client,
+            reply_to,
+            &format!("unauthorized: {error:#}"),
+            "403",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Replies to an API request with an empty message carrying
+/// `Nats-Service-Error` headers.
+async fn reply_error(
+    nats_client: &async_nats::Client,
+    reply_to: async_nats::Subject,
+    error: &str,
+    code: &str,
+) -> anyhow::Result<()> {
+    let mut headers = async_nats::HeaderMap::new();
+    headers.insert("Nats-Service-Error", error);
+    headers.insert("Nats-Service-Error-Code", code);
+    nats_client
+        .publish_with_headers(reply_to, headers, bytes::Bytes::new())
+        .await
+        .context("failed to publish API response")
+}
+
 /// Convert ImagePullSecret from protobuf to OciConfig
 fn image_pull_secret_to_oci_config(
     pull_secret: &Option<types::v2::ImagePullSecret>,
@@ -249,6 +1015,51 @@ fn image_pull_secret_to_oci_config(
     }
 }
 
+/// Returns the bytes of a component image, those of the component staged
+/// under the image reference if there is one, otherwise pulled from its registry.
+async fn component_bytes(
+    host: &impl HostApi,
+    image: &str,
+    pull_secret: &Option<types::v2::ImagePullSecret>,
+) -> anyhow::Result<bytes::Bytes> {
+    if let Some(staged) = host.component_staged(image.to_string()).await? {
+        debug!(image, "using staged component");
+        return Ok(staged.bytes);
+    }
+    let (bytes, _digest) =
+        oci::pull_component(image, image_pull_secret_to_oci_config(pull_secret)).await?;
+    Ok(bytes.into())
+}
+
+/// Pulls a component image, verifies its digest if the request has one, and
+/// stages it on the host, see [`HostApi::component_stage`].
+async fn component_stage(
+    host: &impl HostApi,
+    req: types::v2::ComponentStageRequest,
+) -> anyhow::Result<types::v2::ComponentStageResponse> {
+    anyhow::ensure!(!req.image.is_empty(), "component image is required");
+    let oci_config = image_pull_secret_to_oci_config(&req.image_pull_secret);
+    let (bytes, digest) = oci::pull_component(&req.image, oci_config)
+        .await
+        .with_context(|| format!("failed to pull component image {}", req.image))?;
+    anyhow::ensure!(
+        req.digest.is_empty() || req.digest == digest,
+        "component image {} has digest {digest}, expected {}",
+        req.image,
+        req.digest
+    );
+    let staged = host
+        .component_stage(crate::types::ComponentStageRequest {
+            reference: req.image,
+            bytes: bytes.into(),
+        })
+        .await?;
+    Ok(types::v2::ComponentStageResponse {
+        component: Some(staged.into()),
+        digest,
+    })
+}
+
 async fn host_heartbeat(host: &impl HostApi) -> anyhow::Result<types::v2::HostHeartbeat> {
     let hb = host.heartbeat().await?;
 
@@ -259,45 +1070,113 @@ async fn workload_start(
     host: &impl HostApi,
     req: types::v2::WorkloadStartRequest,
 ) -> anyhow::Result<types::v2::WorkloadStartResponse> {
-    let Some(types::v2::Workload {
+    let Some(workload) = req.workload else {
+        anyhow::bail!("workload is required");
+    };
+    let workload = match pull_workload(host, workload).await? {
+        Ok(workload) => workload,
+        Err(status) => {
+            return Ok(types::v2::WorkloadStartResponse {
+                workload_status: Some(status),
+            });
+        }
+    };
+    let request = crate::types::WorkloadStartRequest {
+        workload_id: uuid::Uuid::new_v4().to_string(),
+        workload,
+    };
+
+    Ok(host.workload_start(request).await?.into())
+}
+
+async fn workload_update(
+    host: &impl HostApi,
+    req: types::v2::WorkloadUpdateRequest,
+) -> anyhow::Result<types::v2::WorkloadUpdateResponse> {
+    let Some(workload) = req.workload else {
+        anyhow::bail!("workload is required");
+    };
+    let workload = match pull_workload(host, workload).await? {
+        Ok(workload) => workload,
+        Err(status) => {
+            return Ok(types::v2::WorkloadUpdateResponse {
+                workload_status: Some(status),
+                abandoned_invocations: 0,
+            });
+        }
+    };
+    let request = crate::types::WorkloadUpdateRequest {
+        workload_id: req.workload_id,
+        replacement: crate::types::WorkloadStartRequest {
+            workload_id: uuid::Uuid::new_v4().to_string(),
+            workload,
+        },
+        drain_timeout: req.drain_timeout_ms.map(Duration::from_millis),
+        rolling_update: req.rolling_update.map(Into::into),
+    };
+
+    Ok(host.workload_update(request).await?.into())
+}
+
+/// Pulls the components of a workload from the API. A component that can't be
+/// pulled is returned as an error status.
+async fn pull_workload(
+    host: &impl HostApi,
+    workload: types::v2::Workload,
+) -> anyhow::Result<Result<crate::types::Workload, types::v2::WorkloadStatus>> {
+    let types::v2::Workload {
         namespace,
         name,
         annotations,
         service,
         wit_world,
         volumes,
-    }) = req.workload
-    else {
-        anyhow::bail!("workload is required");
+        outbound,
+    } = workload;
+    let pull_error = |message: String| types::v2::WorkloadStatus {
+        workload_id: "".into(),
+        workload_state: types::v2::WorkloadState::Error.into(),
+        message,
+        usage: None,
+        components: Vec::new(),
+        activity: None,
     };
     let (components, host_interfaces) = if let Some(wit_world) = wit_world {
         let mut pulled_components = Vec::with_capacity(wit_world.components.len());
         for component in &wit_world.components {
-            let oci_config = image_pull_secret_to_oci_config(&component.image_pull_secret);
-            let bytes = match oci::pull_component(&component.image, oci_config).await {
-                Ok(bytes) => bytes,
-                Err(e) => {
-                    return Ok(types::v2::WorkloadStartResponse {
-                        workload_status: Some(types::v2::WorkloadStatus {
-                            workload_id: "".into(),
-                            workload_state: types::v2::WorkloadState::Error.into(),
-                            message: format!(
-                                "failed to pull component image {}: {}",
-                                component.image, e
-                            ),
-                        }),
-                    });
-                }
-            };
+            let bytes =
+                match component_bytes(host, &component.image, &component.image_pull_secret).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        return Ok(Err(pull_error(format!(
+                            "failed to pull component image {}: {}",
+                            component.image, e
+                        ))));
+                    }
+                };
             pulled_components.push(crate::types::Component {
-                bytes: bytes.0.into(),
+                bytes,
+                image: None,
+                local_path: None,
                 local_resources: component
                     .local_resources
                     .clone()
-                    .map(Into::into)
+                    .map(TryInto::try_into)
+                    .transpose()?
                     .unwrap_or_default(),
                 pool_size: component.pool_size,
                 max_invocations: component.max_invocations,
+                autoscaling: component.autoscaling.clone().map(Into::into),
+                pre_init: component.pre_init.clone().map(Into::into),
+                kind: component.kind().into(),
+                init_export: (!component.init_export.is_empty())
+                    .then(|| component.init_export.clone()),
+                job: component.job.clone().map(Into::into),
+                outgoing_http: component
+                    .outgoing_http
+                    .clone()
+                    .map(Into::into)
+                    .unwrap_or_default(),
             })
         }
         (
@@ -313,25 +1192,22 @@ async fn workload_start(
     };
 
     let service = if let Some(service) = service {
-        let oci_config = image_pull_secret_to_oci_config(&service.image_pull_secret);
-        let bytes = match oci::pull_component(&service.image, oci_config).await {
+        let bytes = match component_bytes(host, &service.image, &service.image_pull_secret).await {
             Ok(bytes) => bytes,
             Err(e) => {
-                return Ok(types::v2::WorkloadStartResponse {
-                    workload_status: Some(types::v2::WorkloadStatus {
-                        workload_id: "".into(),
-                        workload_state: types::v2::WorkloadState::Error.into(),
-                        message: format!("failed to pull service image {}: {}", service.image, e),
-                    }),
-                });
+                return Ok(Err(pull_error(format!(
+                    "failed to pull service image {}: {}",
+                    service.image, e
+                ))));
             }
         };
         Some(crate::types::Service {
-            bytes: bytes.0.into(),
+            bytes,
             local_resources: service
                 .local_resources
                 .clone()
-                .map(Into::into)
+                .map(TryInto::try_into)
+                .transpose()?
                 .unwrap_or_default(),
             max_restarts: service.max_restarts,
         })
@@ -339,38 +1215,260 @@ async fn workload_start(
         None
     };
 
-    let volumes = volumes.into_iter().map(Into::into).collect();
-
-    let request = crate::types::WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: crate::types::Workload {
-            namespace,
-            name,
-            annotations,
-            service,
-            components,
-            host_interfaces,
-            volumes,
-        },
-    };
-
-    Ok(host.workload_start(request).await?.into())
+    Ok(Ok(crate::types::Workload {
+        namespace,
+        name,
+        annotations,
+        service,
+        components,
+        host_interfaces,
+        volumes: volumes.into_iter().map(Into::into).collect(),
+        outbound: outbound.map(Into::into).unwrap_or_default(),
+    }))
 }
 
 async fn workload_stop(
     host: &impl HostApi,
     req: types::v2::WorkloadStopRequest,
 ) -> anyhow::Result<types::v2::WorkloadStopResponse> {
-    host.workload_stop(req.into()).await.map(|resp| resp.into())
+    Ok(host.workload_stop(req.into()).await?.into())
 }
 
 async fn workload_status(
     host: &impl HostApi,
     req: types::v2::WorkloadStatusRequest,
 ) -> anyhow::Result<types::v2::WorkloadStatusResponse> {
-    host.workload_status(req.into())
+    Ok(host.workload_status(req.into()).await?.into())
+}
+
+async fn workload_list(
+    host: &impl HostApi,
+    req: types::v2::WorkloadListRequest,
+) -> anyhow::Result<types::v2::WorkloadListResponse> {
+    let workloads = host.workload_list(req.into()).await?;
+    Ok(types::v2::WorkloadListResponse {
+        workloads: workloads.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn volume_create(
+    host: &impl HostApi,
+    req: types::v2::VolumeCreateRequest,
+) -> anyhow::Result<types::v2::VolumeCreateResponse> {
+    let volume = host.volume_create(req.into()).await?;
+    Ok(types::v2::VolumeCreateResponse {
+        volume: Some(volume.into()),
+    })
+}
+
+async fn volume_list(host: &impl HostApi) -> anyhow::Result<types::v2::VolumeListResponse> {
+    let volumes = host.volume_list().await?;
+    Ok(types::v2::VolumeListResponse {
+        volumes: volumes.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn volume_inspect(
+    host: &impl HostApi,
+    req: types::v2::VolumeInspectRequest,
+) -> anyhow::Result<types::v2::VolumeInspectResponse> {
+    let volume = host.volume_inspect(req.into()).await?;
+    Ok(types::v2::VolumeInspectResponse {
+        volume: Some(volume.into()),
+    })
+}
+
+/// Calls an export for a `workload.invoke` request and streams the returned
+/// values to the reply subject, one per message, followed by a final message
+/// with `done` set that carries the error if the call failed.
+async fn workload_invoke(
+    host: &impl HostApi,
+    nats_client: &async_nats::Client,
+    msg: async_nats::Message,
+) -> anyhow::Result<()> {
+    let reply_to = msg
+        .reply
+        .context("workload.invoke request has no reply subject")?;
+    let results = async {
+        let req: types::v2::WorkloadInvokeRequest = from_api(&msg.payload)?;
+        let res = host
+            .workload_invoke(crate::types::WorkloadInvokeRequest::try_from(req)?)
+            .await?;
+        res.results
+            .iter()
+            .map(convert::value_to_api)
+            .collect::<anyhow::Result<Vec<_>>>()
+    }
+    .await;
+
+    let (results, error) = match results {
+        Ok(results) => (results, String::new()),
+        Err(e) => (Vec::new(), format!("{e:#}")),
+    };
+    for (index, result) in results.into_iter().enumerate() {
+        let response = types::v2::WorkloadInvokeResponse {
+            index: index as u32,
+            result: Some(result),
+            ..Default::default()
+        };
+        nats_client
+            .publish(reply_to.clone(), to_api(&response)?.into())
+            .await
+            .context("failed to publish invoke result")?;
+    }
+    let done = types::v2::WorkloadInvokeResponse {
+        done: true,
+        error,
+        ..Default::default()
+    };
+    nats_client
+        .publish(reply_to, to_api(&done)?.into())
         .await
-        .map(|resp| resp.into())
+        .context("failed to publish invoke response")?;
+    Ok(())
+}
+
+/// Lines sent in one `workload.logs` response
+const LOG_BATCH: usize = 100;
+/// How long a `workload.logs` request may follow the logs
+const MAX_LOG_FOLLOW: Duration = Duration::from_secs(60 * 60);
+/// How long a `workload.logs` request follows the logs if it doesn't say
+const DEFAULT_LOG_FOLLOW: Duration = Duration::from_secs(5 * 60);
+
+/// Reads the lines a `workload.logs` request asks for, returning them with
+/// how long to follow new lines for.
+async fn workload_log_tail(
+    host: &impl HostApi,
+    req: types::v2::WorkloadLogsRequest,
+) -> anyhow::Result<(crate::host::logs::LogTail, Duration)> {
+    let follow_for = req
+        .follow_ms
+        .map_or(DEFAULT_LOG_FOLLOW, Duration::from_millis)
+        .min(MAX_LOG_FOLLOW);
+    let query = crate::host::logs::LogQuery::try_from(req)?;
+    Ok((host.workload_logs(query).await?, follow_for))
+}
+
+/// Returns the responses streaming `tail`: the buffered lines in batches,
+/// then the lines logged while following them for `follow_for`. The final
+/// message with `done` set is left to the caller.
+fn workload_log_responses(
+    tail: crate::host::logs::LogTail,
+    follow_for: Duration,
+) -> impl futures::Stream<Item = types::v2::WorkloadLogsResponse> + Send + 'static {
+    let backlog: Vec<_> = tail
+        .lines
+        .chunks(LOG_BATCH)
+        .map(|lines| types::v2::WorkloadLogsResponse {
+            lines: lines.iter().map(|line| line.as_ref().into()).collect(),
+            ..Default::default()
+        })
+        .collect();
+    let following = tail
+        .follower
+        .map(|follower| (follower, 0, Box::pin(tokio::time::sleep(follow_for))));
+    let followed = futures::stream::unfold(following, |following| async move {
+        let (mut follower, missed, mut deadline) = following?;
+        let line = tokio::select! {
+            line = follower.next() => line?,
+            () = &mut deadline => return None,
+        };
+        let response = types::v2::WorkloadLogsResponse {
+            lines: vec![line.as_ref().into()],
+            missed: follower.missed() - missed,
+            ..Default::default()
+        };
+        let missed = follower.missed();
+        Some((response, Some((follower, missed, deadline))))
+    });
+    futures::stream::iter(backlog).chain(followed)
+}
+
+/// Streams the lines a `workload.logs` request asks for to the reply subject
+/// in batches, then the lines logged while it follows them, followed by a
+/// final message with `done` set that carries the error if reading failed.
+async fn workload_logs(
+    host: &impl HostApi,
+    nats_client: &async_nats::Client,
+    msg: async_nats::Message,
+) -> anyhow::Result<()> {
+    let reply_to = msg
+        .reply
+        .context("workload.logs request has no reply subject")?;
+    let tail = async {
+        let req: types::v2::WorkloadLogsRequest = from_api(&msg.payload)?;
+        workload_log_tail(host, req).await
+    }
+    .await;
+
+    let error = match tail {
+        Ok((tail, follow_for)) => {
+            let responses = workload_log_responses(tail, follow_for);
+            tokio::pin!(responses);
+            while let Some(response) = responses.next().await {
+                nats_client
+                    .publish(reply_to.clone(), to_api(&response)?.into())
+                    .await
+                    .context("failed to publish log lines")?;
+            }
+            String::new()
+        }
+        Err(e) => format!("{e:#}"),
+    };
+    let done = types::v2::WorkloadLogsResponse {
+        done: true,
+        error,
+        ..Default::default()
+    };
+    nats_client
+        .publish(reply_to, to_api(&done)?.into())
+        .await
+        .context("failed to publish log lines")?;
+    Ok(())
+}
+
+/// Serves a call a peer forwarded with a `plugin.invoke` request, replying
+/// with the returned values or with `Nats-Service-Error` headers if it failed.
+async fn plugin_invoke(
+    host: &impl HostApi,
+    nats_client: &async_nats::Client,
+    msg: async_nats::Message,
+) -> anyhow::Result<()> {
+    let reply_to = msg
+        .reply
+        .context("plugin.invoke request has no reply subject")?;
+    let results = async {
+        let req: types::v2::InvokeRequest = from_api(&msg.payload)?;
+        let call = crate::plugin::forward::ForwardedCall {
+            workload_id: req.workload_id,
+            component_id: req.component_id,
+            instance: req.instance,
+            function: req.function,
+            params: req
+                .params
+                .into_iter()
+                .map(convert::value_from_api)
+                .collect::<anyhow::Result<_>>()?,
+        };
+        let results = host.plugin_invoke(call).await?;
+        results
+            .iter()
+            .map(convert::value_to_api)
+            .collect::<anyhow::Result<Vec<_>>>()
+    }
+    .await;
+
+    match results {
+        Ok(results) => {
+            let response = types::v2::InvokeResponse { results };
+            nats_client
+                .publish(reply_to, to_api(&response)?.into())
+                .await
+                .context("failed to publish API response")?;
+        }
+        Err(e) => reply_error(nats_client, reply_to, &format!("{e:#}"), "500").await?,
+    }
+    Ok(())
 }
 
 /// Creates a tracing span for a host invocation with relevant attributes.
@@ -419,154 +1517,6 @@ pub fn resource_builder() -> ResourceBuilder {
         ])
 }
 
-impl From<types::v2::WitInterface> for crate::wit::WitInterface {
-    fn from(wi: types::v2::WitInterface) -> Self {
-        crate::wit::WitInterface {
-            namespace: wi.namespace,
-            package: wi.package,
-            version: if wi.version.is_empty() {
-                None
-            } else {
-                wi.version.parse::<semver::Version>().ok()
-            },
-            interfaces: wi.interfaces.into_iter().collect(),
-            config: wi.config,
-        }
-    }
-}
-impl From<types::v2::VolumeMount> for crate::types::VolumeMount {
-    fn from(vm: types::v2::VolumeMount) -> Self {
-        crate::types::VolumeMount {
-            name: vm.name,
-            mount_path: vm.mount_path,
-            read_only: vm.read_only,
-        }
-    }
-}
-
-impl From<types::v2::Volume> for crate::types::Volume {
-    fn from(v: types::v2::Volume) -> Self {
-        crate::types::Volume {
-            name: v.name,
-            volume_type: match v.volume_type {
-                Some(vt) => match vt {
-                    types::v2::volume::VolumeType::HostPath(hp) => {
-                        crate::types::VolumeType::HostPath(crate::types::HostPathVolume {
-                            local_path: hp.local_path,
-                        })
-                    }
-                    types::v2::volume::VolumeType::EmptyDir(_) => {
-                        crate::types::VolumeType::EmptyDir(crate::types::EmptyDirVolume {})
-                    }
-                },
-                None => crate::types::VolumeType::EmptyDir(crate::types::EmptyDirVolume {}),
-            },
-        }
-    }
-}
-
-impl From<types::v2::LocalResources> for crate::types::LocalResources {
-    fn from(lr: types::v2::LocalResources) -> Self {
-        crate::types::LocalResources {
-            memory_limit_mb: lr.memory_limit_mb,
-            cpu_limit: lr.cpu_limit,
-            config: lr.config,
-            volume_mounts: lr.volume_mounts.into_iter().map(Into::into).collect(),
-            allowed_hosts: lr.allowed_hosts,
-            environment: lr.environment,
-        }
-    }
-}
-
-impl From<crate::types::HostHeartbeat> for types::v2::HostHeartbeat {
-    fn from(hb: crate::types::HostHeartbeat) -> Self {
-        types::v2::HostHeartbeat {
-            id: hb.id,
-            hostname: hb.hostname,
-            version: hb.version,
-            started_at: Some(hb.started_at.into()),
-            imports: hb.imports.into_iter().map(Into::into).collect(),
-            exports: hb.exports.into_iter().map(Into::into).collect(),
-            os_name: hb.os_name,
-            os_arch: hb.os_arch,
-            os_kernel: hb.os_kernel,
-            system_cpu_usage: hb.system_cpu_usage,
-            component_count: hb.component_count,
-            workload_count: hb.workload_count,
-            system_memory_total: hb.system_memory_total,
-            system_memory_free: hb.system_memory_free,
-            labels: hb.labels,
-            friendly_name: hb.friendly_name,
-        }
-    }
-}
-
-impl From<crate::wit::WitInterface> for types::v2::WitInterface {
-    fn from(wi: crate::wit::WitInterface) -> Self {
-        types::v2::WitInterface {
-            namespace: wi.namespace,
-            package: wi.package,
-            version: wi.version.map(|v| v.to_string()).unwrap_or_default(),
-            interfaces: wi.interfaces.into_iter().collect(),
-            config: wi.config,
-        }
-    }
-}
-
-// Conversions from API v2 request types to runtime::host types
-
-impl From<types::v2::WorkloadStopRequest> for crate::types::WorkloadStopRequest {
-    fn from(req: types::v2::WorkloadStopRequest) -> Self {
-        crate::types::WorkloadStopRequest {
-            workload_id: req.workload_id,
-        }
-    }
-}
-
-impl From<types::v2::WorkloadStatusRequest> for crate::types::WorkloadStatusRequest {
-    fn from(req: types::v2::WorkloadStatusRequest) -> Self {
-        crate::types::WorkloadStatusRequest {
-            workload_id: req.workload_id,
-        }
-    }
-}
-
-// Conversions from runtime::host response types to API v2 types
-
-impl From<crate::types::WorkloadStartResponse> for types::v2::WorkloadStartResponse {
-    fn from(resp: crate::types::WorkloadStartResponse) -> Self {
-        types::v2::WorkloadStartResponse {
-            workload_status: Some(resp.workload_status.into()),
-        }
-    }
-}
-
-impl From<crate::types::WorkloadStopResponse> for types::v2::WorkloadStopResponse {
-    fn from(resp: crate::types::WorkloadStopResponse) -> Self {
-        types::v2::WorkloadStopResponse {
-            workload_status: Some(resp.workload_status.into()),
-        }
-    }
-}
-
-impl From<crate::types::WorkloadStatusResponse> for types::v2::WorkloadStatusResponse {
-    fn from(resp: crate::types::WorkloadStatusResponse) -> Self {
-        types::v2::WorkloadStatusResponse {
-            workload_status: Some(resp.workload_status.into()),
-        }
-    }
-}
-
-impl From<crate::types::WorkloadStatus> for types::v2::WorkloadStatus {
-    fn from(status: crate::types::WorkloadStatus) -> Self {
-        types::v2::WorkloadStatus {
-            workload_id: status.workload_id,
-            workload_state: status.workload_state as i32,
-            message: status.message,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,272 @@
+//! Cron schedules for [`super::wasmcloud_timers`].
+//!
+//! A schedule is a five-field cron expression, `minute hour day-of-month month
+//! day-of-week`, evaluated in UTC. Fields accept `*`, numbers, ranges `a-b`,
+//! steps `*/n` and `a-b/n`, and comma separated lists of these. Months and
+//! weekdays may also be given by their three-letter English names, and Sunday
+//! is both 0 and 7. As in Vixie cron, when both the day of month and the day
+//! of week are restricted, a day matching either of them matches. The
+//! shorthands `@yearly`, `@monthly`, `@weekly`, `@daily` and `@hourly` are
+//! accepted as well.
+
+use std::str::FromStr;
+
+use anyhow::{Context as _, bail, ensure};
+use chrono::{DateTime, Datelike as _, TimeDelta, TimeZone as _, Timelike as _, Utc};
+
+/// How far ahead the next tick of a schedule is searched, long enough to
+/// reach the next February 29th
+const SEARCH_DAYS: i64 = 8 * 366;
+
+/// The values and names of a field of a cron expression
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    /// Names of the values from `min` on
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+};
+const DAY_OF_MONTH: Field = Field {
+    name: "day of month",
+    min: 1,
+    max: 31,
+    names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &[
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ],
+};
+const DAY_OF_WEEK: Field = Field {
+    name: "day of week",
+    min: 0,
+    max: 7,
+    names: &["sun", "mon", "tue", "wed", "thu", "fri", "sat"],
+};
+
+impl Field {
+    fn value(&self, value: &str) -> anyhow::Result<u32> {
+        let value = match self
+            .names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+        {
+            Some(index) => self.min + index as u32,
+            None => value
+                .parse()
+                .with_context(|| format!("invalid {} '{value}'", self.name))?,
+        };
+        ensure!(
+            (self.min..=self.max).contains(&value),
+            "{} {value} is not between {} and {}",
+            self.name,
+            self.min,
+            self.max
+        );
+        Ok(value)
+    }
+
+    /// Returns the values matched by `expr` as a bit set.
+    fn parse(&self, expr: &str) -> anyhow::Result<u64> {
+        let mut values = 0u64;
+        for part in expr.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step
+                        .parse()
+                        .with_context(|| format!("invalid step '{step}'"))?;
+                    ensure!(step > 0, "step of {} must not be 0", self.name);
+                    (range, Some(step))
+                }
+                None => (part, None),
+            };
+            let (start, end) = match range.split_once('-') {
+                _ if range == "*" => (self.min, self.max),
+                Some((start, end)) => (self.value(start)?, self.value(end)?),
+                // `n/step` runs from n to the end of the field
+                None if step.is_some() => (self.value(range)?, self.max),
+                None => {
+                    let value = self.value(range)?;
+                    (value, value)
+                }
+            };
+            ensure!(start <= end, "invalid {} range '{range}'", self.name);
+            for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+                values |= 1 << value;
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A parsed cron expression, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Days of the week, from Sunday as 0
+    weekdays: u64,
+    /// The day of month field starts with `*`
+    any_day: bool,
+    /// The day of week field starts with `*`
+    any_weekday: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expanded = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other if other.starts_with('@') => bail!("unknown cron shorthand '{other}'"),
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!(
+                "cron expression '{expr}' must have 5 fields: minute hour day-of-month month day-of-week"
+            );
+        };
+
+        let mut weekdays = DAY_OF_WEEK.parse(weekday)?;
+        // Sunday is both 0 and 7
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        let schedule = Self {
+            minutes: MINUTE.parse(minute)?,
+            hours: HOUR.parse(hour)?,
+            days: DAY_OF_MONTH.parse(day)?,
+            months: MONTH.parse(month)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        };
+        ensure!(
+            schedule.next_after(Utc::now()).is_some(),
+            "cron expression '{expr}' never matches"
+        );
+        Ok(schedule)
+    }
+}
+
+impl CronSchedule {
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    /// Returns the first time after `after` the schedule matches, or `None` if
+    /// it does not match in the next eight years.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let limit = after + TimeDelta::days(SEARCH_DAYS);
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        while time <= limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(&time) {
+                time = (time.date_naive() + TimeDelta::days(1))
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += TimeDelta::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(expr: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        expr.parse::<CronSchedule>()
+            .unwrap()
+            .next_after(after)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2024-03-15 is a Friday
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 7, 30).unwrap();
+        assert_eq!(next("*/5 * * * *", now), at(2024, 3, 15, 10, 10));
+        assert_eq!(next("* * * * *", now), at(2024, 3, 15, 10, 8));
+        assert_eq!(next("0 9-17 * * mon-fri", now), at(2024, 3, 15, 11, 0));
+        assert_eq!(next("30 2 * * *", now), at(2024, 3, 16, 2, 30));
+        assert_eq!(next("0 0 * * 7", now), at(2024, 3, 17, 0, 0));
+        assert_eq!(next("@monthly", now), at(2024, 4, 1, 0, 0));
+        assert_eq!(next("15 8 1 jan *", now), at(2025, 1, 1, 8, 15));
+        assert_eq!(next("0 0 29 2 *", now), at(2028, 2, 29, 0, 0));
+        // Either the day of month or the day of week matches
+        assert_eq!(next("0 12 20 * fri", now), at(2024, 3, 15, 12, 0));
+        assert_eq!(next("0 0 1,15 * *", now), at(2024, 4, 1, 0, 0));
+        // Exactly on a tick, the next one is returned
+        assert_eq!(
+            next("0 * * * *", at(2024, 3, 15, 10, 0)),
+            at(2024, 3, 15, 11, 0)
+        );
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expr in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * foo *",
+            "@reboot",
+            "0 0 30 2 *",
+        ] {
+            assert!(expr.parse::<CronSchedule>().is_err(), "{expr}");
+        }
+    }
+}
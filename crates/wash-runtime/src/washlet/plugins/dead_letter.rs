@@ -0,0 +1,310 @@
+//! Dead-letter queue for trigger deliveries that could not be handled.
+//!
+//! Trigger plugins such as [`super::wasmcloud_messaging`] and
+//! [`super::wasmcloud_timers`] retry a failed delivery up to the `max_retries`
+//! set in the interface config. Once the retries are exhausted, the payload is
+//! written to the [`DeadLetterQueue`] with details about the failure. Letters
+//! stored in a bucket can be listed and handed back to the trigger that failed
+//! them with [`DeadLetterQueue::redrive`]. Letters published on a subject are
+//! left for an external consumer to persist and re-drive.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use async_nats::jetstream::object_store;
+use bytes::Bytes;
+use futures::StreamExt as _;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt as _;
+use tracing::{debug, warn};
+
+use crate::plugin::HostPlugin;
+
+/// Interface config key for the number of times a failed delivery is retried
+pub const MAX_RETRIES_CONFIG_KEY: &str = "max_retries";
+
+/// A delivery that failed after exhausting its retries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    /// Id of the plugin that made the delivery, e.g. `wasmcloud-messaging`
+    pub trigger: String,
+    pub workload_id: String,
+    pub component_id: String,
+    /// Where the payload came from, such as the message subject or timer id
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(with = "crate::types::base64_bytes")]
+    pub payload: Bytes,
+    /// The error of the last attempt
+    pub error: String,
+    pub attempts: u32,
+    /// When the last attempt failed, in milliseconds since the Unix epoch
+    pub failed_at_ms: u64,
+}
+
+impl DeadLetter {
+    /// Creates a letter for a delivery that just failed.
+    pub fn new(
+        trigger: impl Into<String>,
+        workload_id: impl Into<String>,
+        component_id: impl Into<String>,
+        source: impl Into<String>,
+        payload: impl Into<Bytes>,
+        error: &anyhow::Error,
+        attempts: u32,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            trigger: trigger.into(),
+            workload_id: workload_id.into(),
+            component_id: component_id.into(),
+            source: source.into(),
+            reply_to: None,
+            payload: payload.into(),
+            error: format!("{error:#}"),
+            attempts,
+            failed_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+
+    /// Sets the subject replies to the original message were expected on.
+    pub fn with_reply_to(mut self, reply_to: Option<String>) -> Self {
+        self.reply_to = reply_to;
+        self
+    }
+}
+
+/// A trigger plugin that can deliver dead letters again.
+#[async_trait::async_trait]
+pub trait Redrive: HostPlugin {
+    /// Delivers the payload of `letter` to its component again.
+    async fn redrive(&self, letter: &DeadLetter) -> anyhow::Result<()>;
+}
+
+/// Where dead letters are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadLetterSink {
+    /// Publish each letter as JSON on `<subject>.<workload_id>`
+    Subject(String),
+    /// Store each letter as `<prefix>/<workload_id>/<id>.json` in a JetStream
+    /// object store bucket, where it can be listed and re-driven
+    Bucket { bucket: String, prefix: String },
+}
+
+/// A dead-letter queue backed by NATS.
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    client: Arc<async_nats::Client>,
+    sink: DeadLetterSink,
+}
+
+impl DeadLetterQueue {
+    pub fn new(client: Arc<async_nats::Client>, sink: DeadLetterSink) -> Self {
+        Self { client, sink }
+    }
+
+    /// Writes a letter to the queue.
+    pub async fn put(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(letter).context("failed to serialize dead letter")?;
+        match &self.sink {
+            DeadLetterSink::Subject(subject) => {
+                self.client
+                    .publish(format!("{subject}.{}", letter.workload_id), json.into())
+                    .await
+                    .context("failed to publish dead letter")?;
+            }
+            DeadLetterSink::Bucket { prefix, .. } => {
+                self.bucket()
+                    .await?
+                    .put(
+                        letter_path(prefix, &letter.workload_id, &letter.id).as_str(),
+                        &mut json.as_slice(),
+                    )
+                    .await
+                    .context("failed to store dead letter")?;
+            }
+        }
+        debug!(
+            id = letter.id,
+            trigger = letter.trigger,
+            workload_id = letter.workload_id,
+            "wrote dead letter"
+        );
+        Ok(())
+    }
+
+    /// Lists the letters stored for a workload.
+    ///
+    /// # Errors
+    /// Returns an error if letters are published on a subject, as those are
+    /// not stored by the host.
+    pub async fn list(&self, workload_id: &str) -> anyhow::Result<Vec<DeadLetter>> {
+        let DeadLetterSink::Bucket { prefix, .. } = &self.sink else {
+            anyhow::bail!("dead letters published on a subject cannot be listed");
+        };
+        let store = self.bucket().await?;
+        let dir = format!("{}/{workload_id}/", prefix.trim_end_matches('/'));
+        let mut objects = store.list().await.context("failed to list dead letters")?;
+        let mut letters = Vec::new();
+        while let Some(info) = objects.next().await {
+            let info = info.context("failed to list dead letters")?;
+            if info.deleted || !info.name.starts_with(&dir) {
+                continue;
+            }
+            let mut object = store
+                .get(info.name.as_str())
+                .await
+                .context("failed to read dead letter")?;
+            let mut json = Vec::new();
+            object.read_to_end(&mut json).await?;
+            match serde_json::from_slice(&json) {
+                Ok(letter) => letters.push(letter),
+                Err(e) => warn!(name = info.name, "skipping malformed dead letter: {e}"),
+            }
+        }
+        letters.sort_by_key(|letter: &DeadLetter| letter.failed_at_ms);
+        Ok(letters)
+    }
+
+    /// Removes a letter from the queue.
+    pub async fn remove(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let DeadLetterSink::Bucket { prefix, .. } = &self.sink else {
+            anyhow::bail!("dead letters published on a subject cannot be removed");
+        };
+        self.bucket()
+            .await?
+            .delete(letter_path(prefix, &letter.workload_id, &letter.id).as_str())
+            .await
+            .context("failed to delete dead letter")?;
+        Ok(())
+    }
+
+    /// Hands the stored letters of a workload that `trigger` failed back to it,
+    /// removing each one that is delivered successfully. Returns the number of
+    /// letters delivered.
+    pub async fn redrive(&self, workload_id: &str, trigger: &dyn Redrive) -> anyhow::Result<usize> {
+        let mut delivered = 0;
+        for letter in self.list(workload_id).await? {
+            if letter.trigger != trigger.id() {
+                continue;
+            }
+            match trigger.redrive(&letter).await {
+                Ok(()) => {
+                    self.remove(&letter).await?;
+                    delivered += 1;
+                }
+                Err(e) => warn!(id = letter.id, "failed to re-drive dead letter: {e:#}"),
+            }
+        }
+        Ok(delivered)
+    }
+
+    async fn bucket(&self) -> anyhow::Result<object_store::ObjectStore> {
+        let DeadLetterSink::Bucket { bucket, .. } = &self.sink else {
+            anyhow::bail!("dead letters are not stored in a bucket");
+        };
+        async_nats::jetstream::new((*self.client).clone())
+            .create_object_store(object_store::Config {
+                bucket: bucket.clone(),
+                ..Default::default()
+            })
+            .await
+            .with_context(|| format!("failed to open dead letter bucket '{bucket}'"))
+    }
+}
+
+fn letter_path(prefix: &str, workload_id: &str, id: &str) -> String {
+    format!("{}/{workload_id}/{id}.json", prefix.trim_end_matches('/'))
+}
+
+/// Reads the number of retries for failed deliveries from an interface config.
+pub(crate) fn max_retries(config: &std::collections::HashMap<String, String>) -> u32 {
+    config
+        .get(MAX_RETRIES_CONFIG_KEY)
+        .and_then(|retries| retries.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Calls `deliver` until it succeeds or has been retried `max_retries` times.
+/// On failure, returns the last error and the number of attempts made.
+pub(crate) async fn deliver_with_retries<F, Fut>(
+    max_retries: u32,
+    mut deliver: F,
+) -> Result<(), (anyhow::Error, u32)>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match deliver().await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempts <= max_retries => {
+                warn!(attempts, "delivery failed, retrying: {e:#}");
+                tokio::time::sleep(retry_backoff(attempts)).await;
+            }
+            Err(e) => return Err((e, attempts)),
+        }
+    }
+}
+
+/// Returns the delay after the `attempts`th failed attempt of a delivery.
+fn retry_backoff(attempts: u32) -> Duration {
+    Duration::from_millis(100 << attempts.min(6))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deliver_with_retries() {
+        let calls = &AtomicU32::new(0);
+        let result = deliver_with_retries(2, || async move {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                anyhow::bail!("not yet")
+            }
+            Ok(())
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (error, attempts) = deliver_with_retries(1, || async { anyhow::bail!("broken") })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "broken");
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_dead_letter_serde() {
+        let letter = DeadLetter::new(
+            "wasmcloud-messaging",
+            "workload",
+            "component",
+            "orders.created",
+            &b"{}"[..],
+            &anyhow::anyhow!("handler returned an error"),
+            3,
+        )
+        .with_reply_to(Some("_INBOX.1".to_string()));
+        let json = serde_json::to_value(&letter).unwrap();
+        assert_eq!(json["payload"], "e30=");
+        assert_eq!(serde_json::from_value::<DeadLetter>(json).unwrap(), letter);
+        assert_eq!(
+            letter_path("dead-letters/", "workload", "id"),
+            "dead-letters/workload/id.json"
+        );
+    }
+}
@@ -1,8 +1,12 @@
+pub mod cron;
+pub mod dead_letter;
+pub mod multipart;
 pub mod wasi_blobstore;
 pub mod wasi_config;
 pub mod wasi_keyvalue;
 pub mod wasi_logging;
 pub mod wasmcloud_messaging;
+pub mod wasmcloud_timers;
 
 use std::collections::HashMap;
 use std::future::Future;
@@ -98,4 +102,22 @@ impl<T, Y> WorkloadTracker<T, Y> {
         let item = self.workloads.get(workload_id)?;
         item.components.get(component_id)
     }
+
+    pub fn get_component_data_mut(&mut self, component_id: &str) -> Option<&mut Y> {
+        let workload_id = self.components.get(component_id)?;
+        let item = self.workloads.get_mut(workload_id)?;
+        item.components.get_mut(component_id)
+    }
+
+    /// Returns the data of the components of `workload_id`, or of the
+    /// components of every workload if `None`.
+    pub fn components_of<'a>(
+        &'a self,
+        workload_id: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Y> {
+        self.workloads
+            .iter()
+            .filter(move |(id, _)| workload_id.is_none_or(|workload_id| workload_id == *id))
+            .flat_map(|(_, item)| item.components.values())
+    }
 }
@@ -0,0 +1,306 @@
+//! Resumable multipart upload state for the blobstore plugin.
+//!
+//! Components upload large objects as numbered parts through
+//! `wasmcloud:blobstore/multipart`. Parts are staged on disk rather than in
+//! memory, and the upload state lives in the host rather than in a component
+//! instance, so an upload survives the invocation that started it and can be
+//! resumed by a later one. Completing an upload assembles the parts, in
+//! ascending order, into a single file that is then streamed to the backend.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, bail, ensure};
+use tokio::io::AsyncWriteExt as _;
+use tokio::sync::RwLock;
+
+/// How long an upload may go without receiving a part before it is discarded
+pub const DEFAULT_UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Largest part number accepted, parts are numbered from 1
+pub const MAX_PART_NUMBER: u32 = 10_000;
+
+/// The progress of a multipart upload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadStatus {
+    pub id: String,
+    pub container: String,
+    pub object: String,
+    /// Part numbers received so far, in ascending order
+    pub parts: Vec<u32>,
+    /// Total number of bytes received
+    pub size: u64,
+}
+
+/// An upload whose parts have been assembled into a single file.
+pub struct CompletedUpload {
+    pub status: UploadStatus,
+    /// The assembled object, positioned at its start
+    pub file: tokio::fs::File,
+    // Keeps the staging directory alive until the object has been stored
+    _dir: tempfile::TempDir,
+}
+
+struct Upload {
+    owner: String,
+    container: String,
+    object: String,
+    dir: tempfile::TempDir,
+    /// Size of each received part, by part number
+    parts: BTreeMap<u32, u64>,
+    updated_at: Instant,
+}
+
+impl Upload {
+    fn status(&self, id: &str) -> UploadStatus {
+        UploadStatus {
+            id: id.to_string(),
+            container: self.container.clone(),
+            object: self.object.clone(),
+            parts: self.parts.keys().copied().collect(),
+            size: self.parts.values().sum(),
+        }
+    }
+}
+
+/// Multipart uploads in progress, keyed by upload id.
+///
+/// Every operation takes the id of the workload that owns the upload, so
+/// workloads cannot see or complete each other's uploads.
+pub struct MultipartUploads {
+    uploads: RwLock<HashMap<String, Upload>>,
+    ttl: Duration,
+}
+
+impl Default for MultipartUploads {
+    fn default() -> Self {
+        Self::new(DEFAULT_UPLOAD_TTL)
+    }
+}
+
+impl MultipartUploads {
+    /// Creates an empty set of uploads that expire after `ttl` without progress.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            uploads: RwLock::default(),
+            ttl,
+        }
+    }
+
+    /// Starts an upload of `object` into `container`, or returns the id of the
+    /// upload already in progress for it so an interrupted upload can resume.
+    pub async fn begin(
+        &self,
+        owner: &str,
+        container: &str,
+        object: &str,
+    ) -> anyhow::Result<String> {
+        let mut uploads = self.uploads.write().await;
+        let ttl = self.ttl;
+        uploads.retain(|_, upload| upload.updated_at.elapsed() < ttl);
+
+        if let Some((id, _)) = uploads.iter().find(|(_, upload)| {
+            upload.owner == owner && upload.container == container && upload.object == object
+        }) {
+            return Ok(id.clone());
+        }
+
+        let dir = tempfile::Builder::new()
+            .prefix("wasi-blobstore-upload")
+            .tempdir()
+            .context("failed to create upload staging directory")?;
+        let id = uuid::Uuid::new_v4().to_string();
+        uploads.insert(
+            id.clone(),
+            Upload {
+                owner: owner.to_string(),
+                container: container.to_string(),
+                object: object.to_string(),
+                dir,
+                parts: BTreeMap::new(),
+                updated_at: Instant::now(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Stores part number `part` of an upload. Re-sending a part replaces it.
+    pub async fn write_part(
+        &self,
+        owner: &str,
+        id: &str,
+        part: u32,
+        data: &[u8],
+    ) -> anyhow::Result<()> {
+        ensure!(
+            (1..=MAX_PART_NUMBER).contains(&part),
+            "part number must be between 1 and {MAX_PART_NUMBER}"
+        );
+        let path = {
+            let uploads = self.uploads.read().await;
+            part_path(lookup(&uploads, owner, id)?, part)
+        };
+
+        // Write outside of the lock so large parts don't block other uploads
+        tokio::fs::write(&path, data)
+            .await
+            .with_context(|| format!("failed to stage part {part} of upload '{id}'"))?;
+
+        let mut uploads = self.uploads.write().await;
+        let upload = lookup_mut(&mut uploads, owner, id)?;
+        upload.parts.insert(part, data.len() as u64);
+        upload.updated_at = Instant::now();
+        Ok(())
+    }
+
+    /// Returns the progress of an upload.
+    pub async fn status(&self, owner: &str, id: &str) -> anyhow::Result<UploadStatus> {
+        let uploads = self.uploads.read().await;
+        Ok(lookup(&uploads, owner, id)?.status(id))
+    }
+
+    /// Ends an upload and assembles its parts into a single file.
+    ///
+    /// # Errors
+    /// Returns an error if no parts were received or the parts could not be
+    /// assembled. The upload is left in place so it can be retried.
+    pub async fn complete(&self, owner: &str, id: &str) -> anyhow::Result<CompletedUpload> {
+        let upload = {
+            let mut uploads = self.uploads.write().await;
+            lookup(&uploads, owner, id)?;
+            uploads.remove(id).expect("upload exists")
+        };
+        match assemble(id, &upload).await {
+            Ok(file) => Ok(CompletedUpload {
+                status: upload.status(id),
+                file,
+                _dir: upload.dir,
+            }),
+            Err(e) => {
+                self.uploads.write().await.insert(id.to_string(), upload);
+                Err(e)
+            }
+        }
+    }
+
+    /// Discards an upload and its staged parts.
+    pub async fn abort(&self, owner: &str, id: &str) -> anyhow::Result<()> {
+        let mut uploads = self.uploads.write().await;
+        lookup(&uploads, owner, id)?;
+        uploads.remove(id);
+        Ok(())
+    }
+
+    /// Discards all uploads owned by a workload, e.g. when it is stopped.
+    pub async fn remove_owner(&self, owner: &str) {
+        self.uploads
+            .write()
+            .await
+            .retain(|_, upload| upload.owner != owner);
+    }
+}
+
+fn lookup<'a>(
+    uploads: &'a HashMap<String, Upload>,
+    owner: &str,
+    id: &str,
+) -> anyhow::Result<&'a Upload> {
+    match uploads.get(id) {
+        Some(upload) if upload.owner == owner => Ok(upload),
+        _ => bail!("upload '{id}' does not exist"),
+    }
+}
+
+fn lookup_mut<'a>(
+    uploads: &'a mut HashMap<String, Upload>,
+    owner: &str,
+    id: &str,
+) -> anyhow::Result<&'a mut Upload> {
+    match uploads.get_mut(id) {
+        Some(upload) if upload.owner == owner => Ok(upload),
+        _ => bail!("upload '{id}' does not exist"),
+    }
+}
+
+fn part_path(upload: &Upload, part: u32) -> PathBuf {
+    upload.dir.path().join(format!("part-{part:05}"))
+}
+
+async fn assemble(id: &str, upload: &Upload) -> anyhow::Result<tokio::fs::File> {
+    ensure!(!upload.parts.is_empty(), "upload '{id}' has no parts");
+    let path = upload.dir.path().join("object");
+    let mut object = tokio::fs::File::create(&path)
+        .await
+        .context("failed to create assembled object")?;
+    for part in upload.parts.keys() {
+        let mut file = tokio::fs::File::open(part_path(upload, *part))
+            .await
+            .with_context(|| format!("failed to open part {part} of upload '{id}'"))?;
+        tokio::io::copy(&mut file, &mut object).await?;
+    }
+    object.flush().await?;
+    tokio::fs::File::open(&path)
+        .await
+        .context("failed to reopen assembled object")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt as _;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_multipart_upload_resumes_and_assembles_in_order() {
+        let uploads = MultipartUploads::default();
+        let id = uploads.begin("workload", "bucket", "big").await.unwrap();
+        uploads
+            .write_part("workload", &id, 2, b"world")
+            .await
+            .unwrap();
+
+        // A later invocation resumes the same upload
+        let resumed = uploads.begin("workload", "bucket", "big").await.unwrap();
+        assert_eq!(resumed, id);
+        uploads
+            .write_part("workload", &id, 1, b"hello ")
+            .await
+            .unwrap();
+
+        let status = uploads.status("workload", &id).await.unwrap();
+        assert_eq!(status.parts, vec![1, 2]);
+        assert_eq!(status.size, 11);
+
+        let mut completed = uploads.complete("workload", &id).await.unwrap();
+        let mut contents = String::new();
+        completed.file.read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "hello world");
+        assert!(uploads.status("workload", &id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_is_scoped_to_owner() {
+        let uploads = MultipartUploads::default();
+        let id = uploads.begin("workload", "bucket", "big").await.unwrap();
+        assert!(uploads.write_part("other", &id, 1, b"x").await.is_err());
+        assert!(uploads.complete("other", &id).await.is_err());
+        assert!(uploads.write_part("workload", &id, 0, b"x").await.is_err());
+
+        // Completing an empty upload fails but keeps it resumable
+        assert!(uploads.complete("workload", &id).await.is_err());
+        uploads.write_part("workload", &id, 1, b"x").await.unwrap();
+
+        uploads.remove_owner("workload").await;
+        assert!(uploads.status("workload", &id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_expires() {
+        let uploads = MultipartUploads::new(Duration::ZERO);
+        let id = uploads.begin("workload", "bucket", "big").await.unwrap();
+        let next = uploads.begin("workload", "bucket", "big").await.unwrap();
+        assert_ne!(id, next);
+        assert!(uploads.abort("workload", &id).await.is_err());
+        uploads.abort("workload", &next).await.unwrap();
+    }
+}
@@ -5,6 +5,7 @@ use crate::engine::ctx::Ctx;
 use crate::engine::workload::WorkloadComponent;
 use crate::plugin::HostPlugin;
 use crate::washlet::plugins::WorkloadTracker;
+use crate::washlet::plugins::multipart::MultipartUploads;
 use crate::wit::{WitInterface, WitWorld};
 use anyhow::Context;
 use async_nats::jetstream::object_store::{self, List, Object, ObjectStore};
@@ -35,6 +36,7 @@ use bindings::wasi::blobstore::container::Error as ContainerError;
 use bindings::wasi::blobstore::types::{
     ContainerMetadata, ContainerName, Error as BlobstoreError, ObjectId, ObjectMetadata, ObjectName,
 };
+use bindings::wasmcloud::blobstore::multipart::{UploadId, UploadStatus};
 
 /// Metadata for an object stored in memory
 #[derive(Clone, Debug)]
@@ -87,6 +89,7 @@ pub struct StreamObjectNamesHandle {
 pub struct WasiBlobstore {
     client: Arc<async_nats::jetstream::Context>,
     tracker: Arc<RwLock<WorkloadTracker<WorkloadData, ()>>>,
+    uploads: Arc<MultipartUploads>,
 }
 
 impl WasiBlobstore {
@@ -94,6 +97,7 @@ impl WasiBlobstore {
         Self {
             client: async_nats::jetstream::new((*client).clone()).into(),
             tracker: Arc::default(),
+            uploads: Arc::default(),
         }
     }
 
@@ -764,6 +768,129 @@ impl bindings::wasi::blobstore::types::HostIncomingValue for Ctx {
     }
 }
 
+// Implementation for resumable multipart uploads
+impl bindings::wasmcloud::blobstore::multipart::Host for Ctx {
+    async fn begin(
+        &mut self,
+        object: ObjectId,
+    ) -> anyhow::Result<Result<UploadId, BlobstoreError>> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(PLUGIN_BLOBSTORE_ID) else {
+            return Ok(Err("blobstore plugin not available".to_string()));
+        };
+
+        if plugin
+            .workload_permit(&self.workload_id, &object.container, true)
+            .await
+            .is_none()
+        {
+            return Ok(Err("unauthorized".to_string()));
+        }
+
+        match plugin
+            .uploads
+            .begin(&self.workload_id, &object.container, &object.object)
+            .await
+        {
+            Ok(id) => Ok(Ok(id)),
+            Err(e) => Ok(Err(format!("{e:#}"))),
+        }
+    }
+
+    async fn upload_part(
+        &mut self,
+        id: UploadId,
+        part: u32,
+        data: Vec<u8>,
+    ) -> anyhow::Result<Result<(), BlobstoreError>> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(PLUGIN_BLOBSTORE_ID) else {
+            return Ok(Err("blobstore plugin not available".to_string()));
+        };
+
+        match plugin
+            .uploads
+            .write_part(&self.workload_id, &id, part, &data)
+            .await
+        {
+            Ok(()) => Ok(Ok(())),
+            Err(e) => Ok(Err(format!("{e:#}"))),
+        }
+    }
+
+    async fn status(
+        &mut self,
+        id: UploadId,
+    ) -> anyhow::Result<Result<UploadStatus, BlobstoreError>> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(PLUGIN_BLOBSTORE_ID) else {
+            return Ok(Err("blobstore plugin not available".to_string()));
+        };
+
+        match plugin.uploads.status(&self.workload_id, &id).await {
+            Ok(status) => Ok(Ok(UploadStatus {
+                id: status.id,
+                object: ObjectId {
+                    container: status.container,
+                    object: status.object,
+                },
+                parts: status.parts,
+                size: status.size,
+            })),
+            Err(e) => Ok(Err(format!("{e:#}"))),
+        }
+    }
+
+    async fn complete(&mut self, id: UploadId) -> anyhow::Result<Result<(), BlobstoreError>> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(PLUGIN_BLOBSTORE_ID) else {
+            return Ok(Err("blobstore plugin not available".to_string()));
+        };
+
+        let status = match plugin.uploads.status(&self.workload_id, &id).await {
+            Ok(status) => status,
+            Err(e) => return Ok(Err(format!("{e:#}"))),
+        };
+        // Permissions may have changed since the upload started
+        if plugin
+            .workload_permit(&self.workload_id, &status.container, true)
+            .await
+            .is_none()
+        {
+            return Ok(Err("unauthorized".to_string()));
+        }
+
+        let store = match plugin
+            .client
+            .get_object_store(status.container.clone())
+            .await
+        {
+            Ok(store) => store,
+            Err(e) => return Ok(Err(format!("failed to get bucket: {e}"))),
+        };
+
+        let mut completed = match plugin.uploads.complete(&self.workload_id, &id).await {
+            Ok(completed) => completed,
+            Err(e) => return Ok(Err(format!("{e:#}"))),
+        };
+
+        match store
+            .put(completed.status.object.as_str(), &mut completed.file)
+            .await
+        {
+            Ok(_) => Ok(Ok(())),
+            Err(e) => Ok(Err(format!("failed to write object data: {e}"))),
+        }
+    }
+
+    async fn abort(&mut self, id: UploadId) -> anyhow::Result<Result<(), BlobstoreError>> {
+        let Some(plugin) = self.get_plugin::<WasiBlobstore>(PLUGIN_BLOBSTORE_ID) else {
+            return Ok(Err("blobstore plugin not available".to_string()));
+        };
+
+        match plugin.uploads.abort(&self.workload_id, &id).await {
+            Ok(()) => Ok(Ok(())),
+            Err(e) => Ok(Err(format!("{e:#}"))),
+        }
+    }
+}
+
 // Implement the main types Host trait that combines all resource types
 impl bindings::wasi::blobstore::types::Host for Ctx {}
 
@@ -777,9 +904,10 @@ impl HostPlugin for WasiBlobstore {
     }
     fn world(&self) -> WitWorld {
         WitWorld {
-            imports: HashSet::from([WitInterface::from(
-                "wasi:blobstore/blobstore,container,types@0.2.0-draft",
-            )]),
+            imports: HashSet::from([
+                WitInterface::from("wasi:blobstore/blobstore,container,types@0.2.0-draft"),
+                WitInterface::from("wasmcloud:blobstore/multipart@0.1.0"),
+            ]),
             ..Default::default()
         }
     }
@@ -806,6 +934,15 @@ impl HostPlugin for WasiBlobstore {
         bindings::wasi::blobstore::blobstore::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
         bindings::wasi::blobstore::container::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
         bindings::wasi::blobstore::types::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        if interfaces
+            .iter()
+            .any(|i| i.namespace == "wasmcloud" && i.package == "blobstore")
+        {
+            bindings::wasmcloud::blobstore::multipart::add_to_linker::<_, HasSelf<Ctx>>(
+                linker,
+                |ctx| ctx,
+            )?;
+        }
 
         Ok(())
     }
@@ -861,6 +998,7 @@ impl HostPlugin for WasiBlobstore {
             .await
             .remove_workload_with_cleanup(workload_id, workload_cleanup, component_cleanup)
             .await;
+        self.uploads.remove_owner(workload_id).await;
 
         Ok(())
     }
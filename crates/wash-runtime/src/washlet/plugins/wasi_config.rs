@@ -1,10 +1,18 @@
 //! # WASI Runtime Config Plugin
 //!
 //! Copies the environment variables provided to each workload to
-//! `wasi:config/store`
+//! `wasi:config/store`, together with the config of the interface. Secrets
+//! referenced in the interface config are resolved when the component is
+//! bound and only kept by the plugin, see [`crate::host::secrets`].
+//!
+//! Host-level overrides take precedence over both, and the configuration of
+//! a running component can be changed with
+//! [`HostApi::workload_config_update`](crate::host::HostApi::workload_config_update),
+//! like with [`crate::plugin::wasi_config`].
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use anyhow::Context as _;
 use tokio::sync::RwLock;
 use wasmtime::component::HasSelf;
 
@@ -12,7 +20,9 @@ const PLUGIN_WASI_CONFIG_ID: &str = "wasi-config";
 
 use crate::engine::ctx::Ctx;
 use crate::engine::workload::WorkloadComponent;
+use crate::host::interpolate::{EnvAllowlist, SecretStore, interpolate_config};
 use crate::plugin::HostPlugin;
+use crate::types::ConfigUpdate;
 use crate::wit::{WitInterface, WitWorld};
 
 mod bindings {
@@ -33,6 +43,35 @@ use bindings::wasi::config::store::{Error as ConfigError, Host};
 pub struct WasiConfig {
     /// A map of configuration from workload id to key-value pairs
     config: Arc<RwLock<HashMap<String, HashMap<String, String>>>>,
+    /// Resolves the secrets referenced in the interface config
+    secrets: Option<Arc<dyn SecretStore>>,
+    /// Environment variables the interface config may reference
+    env: Arc<EnvAllowlist>,
+    /// Host-level values set for every component over its own configuration
+    overrides: Arc<HashMap<String, String>>,
+}
+
+impl WasiConfig {
+    /// Resolves `${secret:NAME}` references in the interface config from
+    /// `secrets`.
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretStore>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Resolves `${env:NAME}` references in the interface config to the environment
+    /// variables allowed by `env`.
+    pub fn with_env_allowlist(mut self, env: EnvAllowlist) -> Self {
+        self.env = Arc::new(env);
+        self
+    }
+
+    /// Sets `overrides` for every component, over its environment, the
+    /// interface config and its updates.
+    pub fn with_overrides(mut self, overrides: HashMap<String, String>) -> Self {
+        self.overrides = Arc::new(overrides);
+        self
+    }
 }
 
 impl Host for Ctx {
@@ -78,7 +117,7 @@ impl HostPlugin for WasiConfig {
         interfaces: std::collections::HashSet<crate::wit::WitInterface>,
     ) -> anyhow::Result<()> {
         // Find the "wasi:config/store" interface, if present
-        let Some(_) = interfaces.iter().find(|i| {
+        let Some(interface) = interfaces.iter().find(|i| {
             i.namespace == "wasi" && i.package == "config" && i.interfaces.contains("store")
         }) else {
             // Log a warning if the requested interfaces are not wasi:config/store
@@ -96,12 +135,39 @@ impl HostPlugin for WasiConfig {
         )?;
 
         // Store the configuration for lookups later
-        // This mirrors wasi:cli/env on wasi:config/store
-        self.config.write().await.insert(
-            component_handle.id().to_string(),
-            component_handle.local_resources().environment.clone(),
+        // This mirrors wasi:cli/env on wasi:config/store, overridden by the interface config
+        let mut config = component_handle.local_resources().environment.clone();
+        config.extend(
+            interpolate_config(&interface.config, &self.env, self.secrets.as_deref())
+                .await
+                .context("invalid wasi:config/store configuration")?,
         );
+        config.extend(self.overrides.as_ref().clone());
+        self.config
+            .write()
+            .await
+            .insert(component_handle.id().to_string(), config);
 
         Ok(())
     }
+
+    async fn on_config_update(
+        &self,
+        component_id: &str,
+        update: &ConfigUpdate,
+    ) -> anyhow::Result<bool> {
+        let set = interpolate_config(&update.set, &self.env, self.secrets.as_deref())
+            .await
+            .context("invalid wasi:config/store configuration")?;
+        let mut config = self.config.write().await;
+        let Some(values) = config.get_mut(component_id) else {
+            return Ok(false);
+        };
+        for key in &update.remove {
+            values.remove(key);
+        }
+        values.extend(set);
+        values.extend(self.overrides.as_ref().clone());
+        Ok(true)
+    }
 }
@@ -2,22 +2,29 @@
 //!
 //! This module implements `wasi:keyvalue@0.2.0-draft` interfaces using
 //! NATS JetStream as the backend storage.
-//! Atomics are stored in Network Byte Order (big-endian) format.
+//! Atomics are stored in Network Byte Order (big-endian) format and are made
+//! atomic with JetStream's per-key revisions. Batch operations are applied key
+//! by key and are not atomic.
 
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use bytes::{Buf, Bytes};
+use anyhow::{Context as _, bail};
+use async_nats::jetstream::kv;
+use bytes::Bytes;
 
 const PLUGIN_KEYVALUE_ID: &str = "wasi-keyvalue";
 use crate::engine::ctx::Ctx;
 use crate::engine::workload::WorkloadComponent;
 use crate::plugin::HostPlugin;
-use crate::wit::{WitInterface, WitWorld};
+use crate::plugin::keyvalue::KeyvalueCapabilities;
+use crate::wit::WitWorld;
 use futures::StreamExt;
 use wasmtime::component::{HasSelf, Resource};
 
 const LIST_KEYS_BATCH_SIZE: usize = 1000;
+/// How often a compare-and-swap is retried when the key changes concurrently
+const MAX_CAS_ATTEMPTS: usize = 16;
 
 mod bindings {
     wasmtime::component::bindgen!({
@@ -33,7 +40,7 @@ use bindings::wasi::keyvalue::store::{Error as StoreError, KeyResponse};
 
 /// Resource representation for a bucket (key-value store)
 pub struct BucketHandle {
-    kv: async_nats::jetstream::kv::Store,
+    kv: kv::Store,
 }
 
 /// Memory-based keyvalue plugin
@@ -67,6 +74,44 @@ impl WasiKeyvalue {
         }
     }
 
+    /// Returns the optional keyvalue features supported by this backend.
+    pub fn capabilities(&self) -> KeyvalueCapabilities {
+        KeyvalueCapabilities {
+            atomics: true,
+            compare_and_swap: true,
+            batch: true,
+            atomic_batch: false,
+        }
+    }
+
+    /// Atomically replaces the value of `key` in `bucket` with `value` if its
+    /// current value is `expected`, where `None` means the key does not exist.
+    ///
+    /// Returns whether the value was swapped.
+    ///
+    /// # Errors
+    /// Returns an error if the bucket cannot be opened or JetStream fails.
+    pub async fn compare_and_swap(
+        &self,
+        bucket: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        self.record_operation("compare_and_swap");
+        let kv = self
+            .client
+            .get_key_value(bucket)
+            .await
+            .with_context(|| format!("failed to open bucket '{bucket}'"))?;
+        let entry = kv.entry(key).await?;
+        let current = live_value(entry.as_ref());
+        if current.map(|v| v.as_ref()) != expected {
+            return Ok(false);
+        }
+        swap_revision(&kv, key, entry.map(|e| e.revision), value.into()).await
+    }
+
     fn record_operation(&self, operation: &str) {
         let attributes = [opentelemetry::KeyValue::new(
             "operation",
@@ -150,7 +195,7 @@ impl bindings::wasi::keyvalue::store::HostBucket for Ctx {
                 "keyvalue plugin not available".to_string(),
             )));
         };
-        plugin.record_operation("get");
+        plugin.record_operation("set");
 
         let bucket_handle = self.table.get(&bucket)?;
 
@@ -284,48 +329,69 @@ impl bindings::wasi::keyvalue::atomics::Host for Ctx {
 
         let bucket_handle = self.table.get(&bucket)?;
 
-        let (entry_revision, entry_value) = match bucket_handle.kv.entry(&key).await {
-            Ok(Some(mut e)) => {
-                let revision = Some(e.revision);
-                let value = e.value.get_u64();
-                (revision, value)
-            }
-            Ok(None) => (None, 0),
+        match increment(&bucket_handle.kv, &key, delta).await {
+            Ok(value) => Ok(Ok(value)),
             Err(e) => {
-                tracing::error!("JetStream error getting key entry: {}", e);
-                return Ok(Err(StoreError::Other(format!("JetStream error: {}", e))));
+                tracing::error!("JetStream error incrementing key: {e:#}");
+                Ok(Err(StoreError::Other(format!("JetStream error: {e:#}"))))
             }
-        };
+        }
+    }
+}
 
-        let new_value = entry_value + delta;
-        let entry_bytes = Bytes::from((new_value).to_be_bytes().to_vec());
-
-        // Here's were CAS happens
-        // If we have a revision, we try to update the entry with it
-        // If we don't have a revision, we try to create the entry
-        match entry_revision {
-            Some(rev) => {
-                let res = bucket_handle.kv.update(&key, entry_bytes, rev).await;
-                match res {
-                    Ok(_) => Ok(Ok(new_value)),
-                    Err(e) => {
-                        tracing::error!("JetStream error updating key: {}", e);
-                        Ok(Err(StoreError::Other(format!("JetStream error: {}", e))))
-                    }
-                }
-            }
-            None => {
-                let res = bucket_handle.kv.put(key.clone(), entry_bytes).await;
-                match res {
-                    Ok(_) => Ok(Ok(new_value)),
-                    Err(e) => {
-                        tracing::error!("JetStream error putting key: {}", e);
-                        Ok(Err(StoreError::Other(format!("JetStream error: {}", e))))
-                    }
-                }
-            }
+/// Returns the value of an entry, or `None` if the key was deleted or purged
+fn live_value(entry: Option<&kv::Entry>) -> Option<&Bytes> {
+    entry
+        .filter(|e| e.operation == kv::Operation::Put)
+        .map(|e| &e.value)
+}
+
+/// Writes `value` if the key is still at `revision`, or still absent when
+/// `revision` is `None`. Returns `false` if the key was changed concurrently.
+async fn swap_revision(
+    kv: &kv::Store,
+    key: &str,
+    revision: Option<u64>,
+    value: Bytes,
+) -> anyhow::Result<bool> {
+    match revision {
+        Some(revision) => match kv.update(key, value, revision).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == kv::UpdateErrorKind::WrongLastRevision => Ok(false),
+            Err(e) => Err(e.into()),
+        },
+        None => match kv.create(key, value).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        },
+    }
+}
+
+/// Atomically adds `delta` to the counter stored at `key`, retrying when the
+/// key is modified concurrently.
+async fn increment(kv: &kv::Store, key: &str, delta: u64) -> anyhow::Result<u64> {
+    for _ in 0..MAX_CAS_ATTEMPTS {
+        let entry = kv.entry(key).await?;
+        let current = match live_value(entry.as_ref()) {
+            Some(value) => decode_counter(value)
+                .with_context(|| format!("value of key '{key}' is not a counter"))?,
+            None => 0,
+        };
+        let new_value = current.saturating_add(delta);
+        let bytes = Bytes::copy_from_slice(&new_value.to_be_bytes());
+        if swap_revision(kv, key, entry.map(|e| e.revision), bytes).await? {
+            return Ok(new_value);
         }
     }
+    bail!("key '{key}' was modified concurrently {MAX_CAS_ATTEMPTS} times")
+}
+
+fn decode_counter(value: &[u8]) -> anyhow::Result<u64> {
+    let bytes: [u8; 8] = value
+        .try_into()
+        .with_context(|| format!("expected 8 bytes, found {}", value.len()))?;
+    Ok(u64::from_be_bytes(bytes))
 }
 
 // Implementation for the batch interface
@@ -456,7 +522,7 @@ impl HostPlugin for WasiKeyvalue {
 
     fn world(&self) -> WitWorld {
         WitWorld {
-            imports: HashSet::from([WitInterface::from("wasi:keyvalue/store,atomics,batch")]),
+            imports: HashSet::from([self.capabilities().interface(None)]),
             ..Default::default()
         }
     }
@@ -483,11 +549,16 @@ impl HostPlugin for WasiKeyvalue {
             workload_id = component.id(),
             "Adding keyvalue interfaces to linker for workload"
         );
+        let capabilities = self.capabilities();
         let linker = component.linker();
 
         bindings::wasi::keyvalue::store::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
-        bindings::wasi::keyvalue::atomics::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
-        bindings::wasi::keyvalue::batch::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        if capabilities.atomics {
+            bindings::wasi::keyvalue::atomics::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        }
+        if capabilities.batch {
+            bindings::wasi::keyvalue::batch::add_to_linker::<_, HasSelf<Ctx>>(linker, |ctx| ctx)?;
+        }
 
         let id = component.id();
         tracing::debug!(
@@ -510,3 +581,15 @@ impl HostPlugin for WasiKeyvalue {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_counter() {
+        assert_eq!(decode_counter(&42u64.to_be_bytes()).unwrap(), 42);
+        assert!(decode_counter(b"42").is_err());
+        assert!(decode_counter(&[]).is_err());
+    }
+}
@@ -3,7 +3,9 @@ use std::sync::Arc;
 
 use crate::engine::ctx::Ctx;
 use crate::engine::workload::{ResolvedWorkload, WorkloadComponent};
-use crate::plugin::HostPlugin;
+use crate::host::idempotency::{self, IdempotencyStore};
+use crate::host::metering;
+use crate::plugin::{HostPlugin, PluginRole};
 use crate::wit::{WitInterface, WitWorld};
 use anyhow::Context;
 use async_nats::Subscriber;
@@ -12,6 +14,10 @@ use tokio::sync::RwLock;
 use tracing::{debug, instrument, warn};
 
 const PLUGIN_MESSAGING_ID: &str = "wasmcloud-messaging";
+/// Interface config key for the message header carrying the idempotency key
+pub const IDEMPOTENCY_HEADER_CONFIG_KEY: &str = "idempotency_header";
+/// Header the idempotency key of a message is read from by default
+pub const DEFAULT_IDEMPOTENCY_HEADER: &str = "Nats-Msg-Id";
 
 mod bindings {
     crate::wasmtime::component::bindgen!({
@@ -26,16 +32,25 @@ use bindings::wasmcloud::messaging::types;
 use wasmtime::component::HasSelf;
 
 use crate::washlet::plugins::WorkloadTracker;
+use crate::washlet::plugins::dead_letter::{
+    DeadLetter, DeadLetterQueue, Redrive, deliver_with_retries, max_retries,
+};
 
 pub struct ComponentData {
     subscriptions: Vec<String>,
     cancel_token: tokio_util::sync::CancellationToken,
+    /// How many times a message the component failed to handle is redelivered
+    max_retries: u32,
+    /// Header whose value deduplicates redelivered messages
+    idempotency_header: String,
 }
 
 #[derive(Clone)]
 pub struct WasmcloudMessaging {
     tracker: Arc<RwLock<WorkloadTracker<(), ComponentData>>>,
     client: Arc<async_nats::Client>,
+    dead_letters: Option<Arc<DeadLetterQueue>>,
+    idempotency: Option<Arc<dyn IdempotencyStore>>,
 }
 
 impl WasmcloudMessaging {
@@ -43,8 +58,68 @@ impl WasmcloudMessaging {
         Self {
             client: client.clone(),
             tracker: Arc::new(RwLock::new(WorkloadTracker::default())),
+            dead_letters: None,
+            idempotency: None,
         }
     }
+
+    /// Writes messages that components failed to handle after all retries to
+    /// `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Skips messages whose idempotency key was already handled by the
+    /// component. The key is read from the `Nats-Msg-Id` header, or the header
+    /// named by the `idempotency_header` interface config.
+    pub fn with_idempotency(mut self, store: Arc<dyn IdempotencyStore>) -> Self {
+        self.idempotency = Some(store);
+        self
+    }
+
+    /// Stops delivering messages to the components of `workload_id`, or of
+    /// every workload if `None`.
+    async fn cancel_subscriptions(&self, workload_id: Option<&str>) {
+        for data in self.tracker.read().await.components_of(workload_id) {
+            data.cancel_token.cancel();
+        }
+    }
+}
+
+/// Delivers a message to a new instance of a component, on its execution pool.
+async fn handle_message(
+    workload: &ResolvedWorkload,
+    pre: &bindings::MessagingPre<Ctx>,
+    component_id: &str,
+    msg: &types::BrokerMessage,
+) -> anyhow::Result<()> {
+    let (handle, pre, id, msg) = (
+        workload.clone(),
+        pre.clone(),
+        component_id.to_string(),
+        msg.clone(),
+    );
+    workload
+        .run_invocation(component_id, async move {
+            let mut store = handle
+                .new_store(&id)
+                .await
+                .context("failed to create store")?;
+            let proxy = pre
+                .instantiate_async(&mut store)
+                .await
+                .context("failed to instantiate component")?;
+            let handled = proxy
+                .wasmcloud_messaging_handler()
+                .call_handle_message(&mut store, &msg)
+                .await;
+            metering::record_fuel(&mut store);
+            handled
+                .map_err(crate::engine::explain_trap)?
+                .map_err(|e| anyhow::anyhow!("handler returned an error: {e}"))
+        })
+        .await
 }
 
 impl Host for Ctx {
@@ -114,6 +189,10 @@ impl HostPlugin for WasmcloudMessaging {
         }
     }
 
+    fn role(&self) -> PluginRole {
+        PluginRole::Trigger
+    }
+
     async fn on_component_bind(
         &self,
         component_handle: &mut WorkloadComponent,
@@ -145,6 +224,12 @@ impl HostPlugin for WasmcloudMessaging {
                 ComponentData {
                     cancel_token: tokio_util::sync::CancellationToken::new(),
                     subscriptions: raw_subscriptions,
+                    max_retries: max_retries(&interface.config),
+                    idempotency_header: interface
+                        .config
+                        .get(IDEMPOTENCY_HEADER_CONFIG_KEY)
+                        .cloned()
+                        .unwrap_or_else(|| DEFAULT_IDEMPOTENCY_HEADER.to_string()),
                 },
             );
         }
@@ -157,10 +242,15 @@ impl HostPlugin for WasmcloudMessaging {
         workload: &ResolvedWorkload,
         component_id: &str,
     ) -> anyhow::Result<()> {
-        let (cancel_token, subjects) = {
+        let (cancel_token, subjects, max_retries, idempotency_header) = {
             let lock = self.tracker.read().await;
             match lock.get_component_data(component_id) {
-                Some(data) => (data.cancel_token.clone(), data.subscriptions.clone()),
+                Some(data) => (
+                    data.cancel_token.clone(),
+                    data.subscriptions.clone(),
+                    data.max_retries,
+                    data.idempotency_header.clone(),
+                ),
                 None => return Ok(()),
             }
         };
@@ -176,6 +266,9 @@ impl HostPlugin for WasmcloudMessaging {
 
         let workload = workload.clone();
         let component_id = component_id.to_string();
+        let dead_letters = self.dead_letters.clone();
+        let idempotency = self.idempotency.clone();
+        let idempotency_scope = format!("{}/{component_id}", workload.id());
 
         let mut subscriptions = Vec::<Subscriber>::new();
         for subject in subjects {
@@ -207,37 +300,65 @@ impl HostPlugin for WasmcloudMessaging {
                                 msg
                             }
                         };
-                        let mut store = match workload.new_store(&component_id).await {
-                            Err(e) => {
-                                warn!("failed to create store for component {component_id}: {e}");
-                                continue;
-                            }
-                            Ok(s) => s,
-                        };
-                        let proxy = match pre.instantiate_async(&mut store).await {
-                            Err(e) => {
-                                warn!("failed to instantiate component {component_id}: {e}");
-                                continue;
-                            }
-                            Ok(p) => p,
-                        };
                         let reply_to = msg.reply.as_ref().map(|r| r.to_string());
-                        let msg = types::BrokerMessage {
+                        let message = types::BrokerMessage {
                             subject: msg.subject.to_string(),
                             reply_to,
-                            body: msg.payload.into(),
+                            body: msg.payload.to_vec(),
                         };
-                        match proxy
-                        .wasmcloud_messaging_handler()
-                        .call_handle_message(store, &msg).await {
-                            Ok(_) => {
+                        let idempotency_key = idempotency.as_ref().and_then(|store| {
+                            let key = msg.headers.as_ref()?.get(idempotency_header.as_str())?;
+                            let key = idempotency::idempotency_key(key.as_str().as_bytes())?;
+                            Some((store, key.to_string()))
+                        });
+                        if let Some((store, key)) = &idempotency_key {
+                            match store.claim(&idempotency_scope, key).await {
+                                Ok(None) => {}
+                                Ok(Some(previous)) => {
+                                    debug!(key, ?previous, "skipping message with repeated idempotency key");
+                                    continue;
+                                }
+                                Err(e) => warn!(key, "failed to claim idempotency key: {e:#}"),
+                            }
+                        }
+                        let (workload, pre, component_id, message) =
+                            (&workload, &pre, component_id.as_str(), &message);
+                        let result = deliver_with_retries(max_retries, || {
+                            handle_message(workload, pre, component_id, message)
+                        })
+                        .await;
+                        if let Some((store, key)) = &idempotency_key {
+                            let recorded = match &result {
+                                Ok(()) => store.complete(&idempotency_scope, key, None).await,
+                                Err(_) => store.release(&idempotency_scope, key).await,
+                            };
+                            if let Err(e) = recorded {
+                                warn!(key, "failed to record idempotency key: {e:#}");
+                            }
+                        }
+                        match result {
+                            Ok(()) => {
                                 debug!("Message handled successfully");
                             }
-                            Err(e) => {
-                                warn!("Error handling message: {e}");
+                            Err((e, attempts)) => {
+                                warn!(attempts, "Error handling message: {e:#}");
+                                if let Some(dead_letters) = &dead_letters {
+                                    let letter = DeadLetter::new(
+                                        PLUGIN_MESSAGING_ID,
+                                        workload.id(),
+                                        component_id,
+                                        &message.subject,
+                                        msg.payload.clone(),
+                                        &e,
+                                        attempts,
+                                    )
+                                    .with_reply_to(message.reply_to.clone());
+                                    if let Err(e) = dead_letters.put(&letter).await {
+                                        warn!("failed to write dead letter: {e:#}");
+                                    }
+                                }
                             }
                         }
-
                     }
                     _ = cancel_token.cancelled() => {
                         break;
@@ -267,4 +388,47 @@ impl HostPlugin for WasmcloudMessaging {
 
         Ok(())
     }
+
+    async fn on_workload_stop(&self, workload_id: &str) -> anyhow::Result<()> {
+        self.cancel_subscriptions(Some(workload_id)).await;
+        Ok(())
+    }
+
+    async fn on_host_shutdown(&self) -> anyhow::Result<()> {
+        self.cancel_subscriptions(None).await;
+        Ok(())
+    }
+
+    /// Flushes messages components published before the host stops
+    async fn stop(&self) -> anyhow::Result<()> {
+        self.client
+            .flush()
+            .await
+            .context("failed to flush messages")
+    }
+}
+
+#[async_trait::async_trait]
+impl Redrive for WasmcloudMessaging {
+    /// Publishes the message again on its original subject, so it is delivered
+    /// to every subscribed component.
+    async fn redrive(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        match &letter.reply_to {
+            Some(reply_to) => {
+                self.client
+                    .publish_with_reply(
+                        letter.source.clone(),
+                        reply_to.clone(),
+                        letter.payload.clone(),
+                    )
+                    .await
+            }
+            None => {
+                self.client
+                    .publish(letter.source.clone(), letter.payload.clone())
+                    .await
+            }
+        }
+        .context("failed to republish message")
+    }
 }
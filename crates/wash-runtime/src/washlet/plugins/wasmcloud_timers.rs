@@ -0,0 +1,710 @@
+//! Timers and delayed callbacks for components.
+//!
+//! Components schedule timers through `wasmcloud:timers/scheduler` and receive
+//! them on their `wasmcloud:timers/handler` export. Pending timers are owned by
+//! the plugin rather than the instance that scheduled them, so they keep running
+//! when that instance is dropped, and each callback is delivered to a fresh
+//! instance. Timers do not survive a host restart.
+//!
+//! # Schedules
+//!
+//! The handler can also be fired on cron schedules, see [`super::cron`], set in
+//! the config of the `wasmcloud:timers` interface of a workload:
+//!
+//! ```json
+//! { "namespace": "wasmcloud", "package": "timers", "interfaces": ["handler"],
+//!   "config": { "schedule": "*/5 * * * *", "schedule.report": "0 6 * * mon",
+//!               "missed_ticks.report": "fire-once" } }
+//! ```
+//!
+//! `schedule` is fired with the timer id `schedule`, and every
+//! `schedule.<name>` with the id `<name>`. The payload is the time of the tick
+//! in milliseconds since the Unix epoch, as a decimal string. Ticks of a
+//! schedule are delivered one after another; `missed_ticks`, or
+//! `missed_ticks.<name>` for a single schedule, decides what happens to the
+//! ticks passing while a delivery is still running, see [`MissedTicks`].
+//! Schedules stop when the workload stops.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, bail};
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use wasmtime::component::HasSelf;
+
+use crate::engine::ctx::Ctx;
+use crate::engine::workload::{ResolvedWorkload, WorkloadComponent};
+use crate::host::metering;
+use crate::plugin::{HostPlugin, PluginRole};
+use crate::washlet::plugins::WorkloadTracker;
+use crate::washlet::plugins::cron::CronSchedule;
+use crate::washlet::plugins::dead_letter::{
+    DeadLetter, DeadLetterQueue, Redrive, deliver_with_retries, max_retries,
+};
+use crate::wit::{WitInterface, WitWorld};
+
+const PLUGIN_TIMERS_ID: &str = "wasmcloud-timers";
+/// Most timers a component may have pending at once
+pub const MAX_PENDING_TIMERS: usize = 1024;
+/// Interface config key of the cron schedule firing the handler, and prefix
+/// of the keys of named schedules
+pub const SCHEDULE_CONFIG_KEY: &str = "schedule";
+/// Interface config key of the [`MissedTicks`] policy of the schedules, and
+/// prefix of the keys of the policies of named schedules
+pub const MISSED_TICKS_CONFIG_KEY: &str = "missed_ticks";
+/// Most missed ticks of a schedule delivered with [`MissedTicks::CatchUp`];
+/// ticks beyond are skipped
+pub const MAX_CATCH_UP_TICKS: usize = 100;
+
+mod bindings {
+    crate::wasmtime::component::bindgen!({
+        world: "timers",
+        imports: { default: async | trappable },
+        exports: { default: async },
+    });
+}
+
+use bindings::wasmcloud::timers::{scheduler, types};
+
+/// Delivers fired timers to a component.
+#[derive(Clone)]
+struct TimerRunner {
+    workload: ResolvedWorkload,
+    pre: bindings::TimersPre<Ctx>,
+}
+
+impl TimerRunner {
+    /// Delivers a timer to a new instance of a component, on its execution pool.
+    async fn fire(&self, component_id: &str, id: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let (runner, component, id, payload) = (
+            self.clone(),
+            component_id.to_string(),
+            id.to_string(),
+            payload.to_vec(),
+        );
+        self.workload
+            .run_invocation(component_id, async move {
+                let mut store = runner.workload.new_store(&component).await?;
+                let timers = runner
+                    .pre
+                    .instantiate_async(&mut store)
+                    .await
+                    .context("failed to instantiate component")?;
+                let handled = timers
+                    .wasmcloud_timers_handler()
+                    .call_on_timer(&mut store, &id, &payload)
+                    .await;
+                metering::record_fuel(&mut store);
+                handled
+                    .map_err(crate::engine::explain_trap)?
+                    .map_err(|e| anyhow::anyhow!("handler returned an error: {e}"))
+            })
+            .await
+    }
+
+    /// Fires a timer with retries, writing it to `dead_letters` if the
+    /// component fails to handle it.
+    async fn deliver(
+        &self,
+        component_id: &str,
+        timer_id: &str,
+        payload: Vec<u8>,
+        max_retries: u32,
+        dead_letters: Option<&DeadLetterQueue>,
+    ) {
+        match deliver_with_retries(max_retries, || self.fire(component_id, timer_id, &payload))
+            .await
+        {
+            Ok(()) => debug!(component_id, timer_id, "timer handled"),
+            Err((e, attempts)) => {
+                warn!(
+                    component_id,
+                    timer_id, attempts, "failed to handle timer: {e:#}"
+                );
+                if let Some(dead_letters) = dead_letters {
+                    let letter = DeadLetter::new(
+                        PLUGIN_TIMERS_ID,
+                        self.workload.id(),
+                        component_id,
+                        timer_id,
+                        payload,
+                        &e,
+                        attempts,
+                    );
+                    if let Err(e) = dead_letters.put(&letter).await {
+                        warn!(component_id, timer_id, "failed to write dead letter: {e:#}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What happens to the ticks of a schedule that pass while an earlier tick is
+/// still being delivered, or the host is suspended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissedTicks {
+    /// Missed ticks are dropped and the schedule continues with the next
+    /// tick in the future
+    #[default]
+    Skip,
+    /// The latest missed tick is delivered right away, the others are dropped
+    FireOnce,
+    /// Every missed tick is delivered, up to [`MAX_CATCH_UP_TICKS`]
+    CatchUp,
+}
+
+impl std::str::FromStr for MissedTicks {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "fire-once" => Ok(Self::FireOnce),
+            "catch-up" => Ok(Self::CatchUp),
+            other => bail!("invalid missed_ticks '{other}', expected skip, fire-once or catch-up"),
+        }
+    }
+}
+
+/// A cron schedule firing the handler of a component.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Schedule {
+    /// The timer id the handler is called with
+    name: String,
+    cron: CronSchedule,
+    missed_ticks: MissedTicks,
+}
+
+impl Schedule {
+    /// Returns the tick to deliver after `tick` was delivered at `now`.
+    fn next_tick(&self, tick: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = self.cron.next_after(tick)?;
+        if next > now {
+            return Some(next);
+        }
+        match self.missed_ticks {
+            MissedTicks::Skip => self.cron.next_after(now),
+            MissedTicks::FireOnce => {
+                let mut latest = next;
+                while let Some(tick) = self.cron.next_after(latest).filter(|t| *t <= now) {
+                    latest = tick;
+                }
+                Some(latest)
+            }
+            MissedTicks::CatchUp => {
+                let missed = std::iter::successors(Some(next), |t| self.cron.next_after(*t))
+                    .take_while(|t| *t <= now)
+                    .take(MAX_CATCH_UP_TICKS + 1)
+                    .count();
+                if missed > MAX_CATCH_UP_TICKS {
+                    warn!(
+                        schedule = self.name,
+                        "more than {MAX_CATCH_UP_TICKS} ticks missed, skipping them"
+                    );
+                    self.cron.next_after(now)
+                } else {
+                    Some(next)
+                }
+            }
+        }
+    }
+}
+
+/// Reads the schedules from the config of a `wasmcloud:timers` interface,
+/// see the [module docs](self).
+fn schedules(config: &HashMap<String, String>) -> anyhow::Result<Vec<Schedule>> {
+    let default_missed_ticks = config
+        .get(MISSED_TICKS_CONFIG_KEY)
+        .map(|s| s.parse())
+        .transpose()?
+        .unwrap_or_default();
+    let mut schedules = config
+        .iter()
+        .filter_map(|(key, expr)| {
+            let name = match key.strip_prefix(SCHEDULE_CONFIG_KEY)? {
+                "" => SCHEDULE_CONFIG_KEY,
+                name => name.strip_prefix('.').filter(|name| !name.is_empty())?,
+            };
+            Some((name, expr))
+        })
+        .map(|(name, expr)| {
+            let cron = expr
+                .parse()
+                .with_context(|| format!("invalid schedule '{name}'"))?;
+            let missed_ticks = match config.get(&format!("{MISSED_TICKS_CONFIG_KEY}.{name}")) {
+                Some(s) => s.parse()?,
+                None => default_missed_ticks,
+            };
+            Ok(Schedule {
+                name: name.to_string(),
+                cron,
+                missed_ticks,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    schedules.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(schedules)
+}
+
+pub struct ComponentData {
+    cancel_token: CancellationToken,
+    /// Set once the workload is resolved and the component can be instantiated
+    runner: Option<TimerRunner>,
+    /// Pending timers by id
+    pending: HashMap<String, CancellationToken>,
+    /// How many times a timer the component failed to handle is redelivered
+    max_retries: u32,
+    /// Cron schedules firing the handler, started once the workload is resolved
+    schedules: Vec<Schedule>,
+}
+
+#[derive(Clone, Default)]
+pub struct WasmcloudTimers {
+    tracker: Arc<RwLock<WorkloadTracker<(), ComponentData>>>,
+    dead_letters: Option<Arc<DeadLetterQueue>>,
+}
+
+impl WasmcloudTimers {
+    /// Writes timers that components failed to handle after all retries to
+    /// `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    async fn schedule(
+        &self,
+        component_id: &str,
+        name: Option<String>,
+        delay: Duration,
+        payload: Vec<u8>,
+    ) -> Result<String, String> {
+        let mut tracker = self.tracker.write().await;
+        let Some(data) = tracker.get_component_data_mut(component_id) else {
+            return Err("component does not export wasmcloud:timers/handler".to_string());
+        };
+        let Some(runner) = data.runner.clone() else {
+            return Err("component is not ready to receive timers".to_string());
+        };
+
+        let id = name.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if let Some(previous) = data.pending.remove(&id) {
+            previous.cancel();
+        } else if data.pending.len() >= MAX_PENDING_TIMERS {
+            return Err(format!(
+                "component already has {MAX_PENDING_TIMERS} pending timers"
+            ));
+        }
+        let token = data.cancel_token.child_token();
+        data.pending.insert(id.clone(), token.clone());
+        let max_retries = data.max_retries;
+
+        let tracker = self.tracker.clone();
+        let dead_letters = self.dead_letters.clone();
+        let component_id = component_id.to_string();
+        let timer_id = id.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            // Remove the timer before firing so the callback can reschedule it
+            {
+                let mut tracker = tracker.write().await;
+                // The timer may have been replaced while waiting for the lock
+                if token.is_cancelled() {
+                    return;
+                }
+                if let Some(data) = tracker.get_component_data_mut(&component_id) {
+                    data.pending.remove(&timer_id);
+                }
+            }
+            runner
+                .deliver(
+                    &component_id,
+                    &timer_id,
+                    payload,
+                    max_retries,
+                    dead_letters.as_deref(),
+                )
+                .await;
+        });
+
+        Ok(id)
+    }
+
+    /// Fires the handler of a component on `schedule` until `token` is
+    /// cancelled.
+    fn run_schedule(
+        &self,
+        runner: TimerRunner,
+        component_id: &str,
+        schedule: Schedule,
+        max_retries: u32,
+        token: CancellationToken,
+    ) {
+        let dead_letters = self.dead_letters.clone();
+        let component_id = component_id.to_string();
+        tokio::spawn(async move {
+            let mut next = schedule.cron.next_after(Utc::now());
+            while let Some(tick) = next {
+                let delay = (tick - Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                let payload = tick.timestamp_millis().to_string().into_bytes();
+                runner
+                    .deliver(
+                        &component_id,
+                        &schedule.name,
+                        payload,
+                        max_retries,
+                        dead_letters.as_deref(),
+                    )
+                    .await;
+                next = schedule.next_tick(tick, Utc::now());
+            }
+            debug!(
+                component_id,
+                schedule = schedule.name,
+                "schedule has no more ticks"
+            );
+        });
+    }
+
+    async fn cancel(&self, component_id: &str, id: &str) -> bool {
+        let mut tracker = self.tracker.write().await;
+        match tracker
+            .get_component_data_mut(component_id)
+            .and_then(|data| data.pending.remove(id))
+        {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels the pending timers of the components of `workload_id`, or of
+    /// every workload if `None`.
+    async fn cancel_all(&self, workload_id: Option<&str>) {
+        for data in self.tracker.read().await.components_of(workload_id) {
+            data.cancel_token.cancel();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Redrive for WasmcloudTimers {
+    /// Fires the timer again on a new instance of its component.
+    async fn redrive(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let runner = self
+            .tracker
+            .read()
+            .await
+            .get_component_data(&letter.component_id)
+            .and_then(|data| data.runner.clone())
+            .context("component is not running")?;
+        runner
+            .fire(&letter.component_id, &letter.source, &letter.payload)
+            .await
+    }
+}
+
+/// Returns how long to wait until `timestamp_ms` milliseconds since the Unix
+/// epoch, or zero if it has passed.
+fn delay_until(timestamp_ms: u64, now: SystemTime) -> Duration {
+    (UNIX_EPOCH + Duration::from_millis(timestamp_ms))
+        .duration_since(now)
+        .unwrap_or_default()
+}
+
+impl scheduler::Host for Ctx {
+    async fn schedule_after(
+        &mut self,
+        name: Option<String>,
+        delay_ms: u64,
+        payload: Vec<u8>,
+    ) -> anyhow::Result<Result<types::TimerId, String>> {
+        let Some(plugin) = self.get_plugin::<WasmcloudTimers>(PLUGIN_TIMERS_ID) else {
+            return Ok(Err("plugin not available".to_string()));
+        };
+        Ok(plugin
+            .schedule(
+                &self.component_id,
+                name,
+                Duration::from_millis(delay_ms),
+                payload,
+            )
+            .await)
+    }
+
+    async fn schedule_at(
+        &mut self,
+        name: Option<String>,
+        timestamp_ms: u64,
+        payload: Vec<u8>,
+    ) -> anyhow::Result<Result<types::TimerId, String>> {
+        let Some(plugin) = self.get_plugin::<WasmcloudTimers>(PLUGIN_TIMERS_ID) else {
+            return Ok(Err("plugin not available".to_string()));
+        };
+        let delay = delay_until(timestamp_ms, SystemTime::now());
+        Ok(plugin
+            .schedule(&self.component_id, name, delay, payload)
+            .await)
+    }
+
+    async fn cancel(&mut self, id: types::TimerId) -> anyhow::Result<bool> {
+        let Some(plugin) = self.get_plugin::<WasmcloudTimers>(PLUGIN_TIMERS_ID) else {
+            return Ok(false);
+        };
+        Ok(plugin.cancel(&self.component_id, &id).await)
+    }
+
+    async fn pending(&mut self) -> anyhow::Result<Vec<types::TimerId>> {
+        let Some(plugin) = self.get_plugin::<WasmcloudTimers>(PLUGIN_TIMERS_ID) else {
+            return Ok(Vec::new());
+        };
+        let tracker = plugin.tracker.read().await;
+        let mut ids: Vec<_> = tracker
+            .get_component_data(&self.component_id)
+            .map(|data| data.pending.keys().cloned().collect())
+            .unwrap_or_default();
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+impl types::Host for Ctx {}
+
+#[async_trait::async_trait]
+impl HostPlugin for WasmcloudTimers {
+    fn id(&self) -> &'static str {
+        PLUGIN_TIMERS_ID
+    }
+
+    fn world(&self) -> WitWorld {
+        WitWorld {
+            imports: HashSet::from([WitInterface::from("wasmcloud:timers/types,scheduler@0.1.0")]),
+            exports: HashSet::from([WitInterface::from("wasmcloud:timers/handler@0.1.0")]),
+        }
+    }
+
+    fn role(&self) -> PluginRole {
+        PluginRole::Trigger
+    }
+
+    async fn on_component_bind(
+        &self,
+        component_handle: &mut WorkloadComponent,
+        interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let Some(interface) = interfaces
+            .iter()
+            .find(|i| i.namespace == "wasmcloud" && i.package == "timers")
+        else {
+            return Ok(());
+        };
+
+        types::add_to_linker::<_, HasSelf<Ctx>>(component_handle.linker(), |ctx| ctx)?;
+        scheduler::add_to_linker::<_, HasSelf<Ctx>>(component_handle.linker(), |ctx| ctx)?;
+
+        let schedules = schedules(&interface.config)?;
+        if interface.interfaces.iter().any(|i| i == "handler") {
+            self.tracker.write().await.add_component(
+                component_handle,
+                ComponentData {
+                    cancel_token: CancellationToken::new(),
+                    runner: None,
+                    pending: HashMap::new(),
+                    max_retries: max_retries(&interface.config),
+                    schedules,
+                },
+            );
+        } else if !schedules.is_empty() {
+            bail!("schedules need the component to export wasmcloud:timers/handler");
+        }
+
+        Ok(())
+    }
+
+    async fn on_workload_resolved(
+        &self,
+        workload: &ResolvedWorkload,
+        component_id: &str,
+    ) -> anyhow::Result<()> {
+        if self
+            .tracker
+            .read()
+            .await
+            .get_component_data(component_id)
+            .is_none()
+        {
+            return Ok(());
+        }
+
+        let instance_pre = workload.instantiate_pre(component_id).await?;
+        let pre =
+            bindings::TimersPre::new(instance_pre).context("failed to instantiate timers pre")?;
+
+        if let Some(data) = self
+            .tracker
+            .write()
+            .await
+            .get_component_data_mut(component_id)
+        {
+            let runner = TimerRunner {
+                workload: workload.clone(),
+                pre,
+            };
+            // Schedules run on children of the component token, so stopping
+            // the workload stops them
+            for schedule in &data.schedules {
+                self.run_schedule(
+                    runner.clone(),
+                    component_id,
+                    schedule.clone(),
+                    data.max_retries,
+                    data.cancel_token.child_token(),
+                );
+            }
+            data.runner = Some(runner);
+        }
+
+        Ok(())
+    }
+
+    async fn on_workload_unbind(
+        &self,
+        workload_id: &str,
+        _interfaces: HashSet<WitInterface>,
+    ) -> anyhow::Result<()> {
+        let workload_cleanup = |_| async {};
+        // Cancelling the component token cancels all of its pending timers
+        let component_cleanup = |component_data: ComponentData| async move {
+            component_data.cancel_token.cancel();
+        };
+
+        self.tracker
+            .write()
+            .await
+            .remove_workload_with_cleanup(workload_id, workload_cleanup, component_cleanup)
+            .await;
+
+        Ok(())
+    }
+
+    async fn on_workload_stop(&self, workload_id: &str) -> anyhow::Result<()> {
+        self.cancel_all(Some(workload_id)).await;
+        Ok(())
+    }
+
+    async fn on_host_shutdown(&self) -> anyhow::Result<()> {
+        self.cancel_all(None).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_until() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        assert_eq!(delay_until(1_500_000, now), Duration::from_secs(500));
+        assert_eq!(delay_until(1_000_000, now), Duration::ZERO);
+        assert_eq!(delay_until(0, now), Duration::ZERO);
+    }
+
+    fn config(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_schedules() {
+        let parsed = schedules(&config(&[
+            ("schedule", "*/5 * * * *"),
+            ("schedule.report", "@daily"),
+            ("missed_ticks", "catch-up"),
+            ("missed_ticks.report", "fire-once"),
+            ("max_retries", "3"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            [
+                Schedule {
+                    name: "report".to_string(),
+                    cron: "0 0 * * *".parse().unwrap(),
+                    missed_ticks: MissedTicks::FireOnce,
+                },
+                Schedule {
+                    name: "schedule".to_string(),
+                    cron: "*/5 * * * *".parse().unwrap(),
+                    missed_ticks: MissedTicks::CatchUp,
+                },
+            ]
+        );
+        assert!(schedules(&config(&[])).unwrap().is_empty());
+        assert!(
+            schedules(&config(&[("schedule.", "* * * * *")]))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(schedules(&config(&[("schedule", "every minute")])).is_err());
+        assert!(
+            schedules(&config(&[
+                ("schedule", "* * * * *"),
+                ("missed_ticks", "later")
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_next_tick() {
+        let at = |minute| DateTime::<Utc>::from_timestamp(minute * 60, 0).unwrap();
+        let schedule = |missed_ticks| Schedule {
+            name: "schedule".to_string(),
+            cron: "* * * * *".parse().unwrap(),
+            missed_ticks,
+        };
+        for policy in [
+            MissedTicks::Skip,
+            MissedTicks::FireOnce,
+            MissedTicks::CatchUp,
+        ] {
+            assert_eq!(schedule(policy).next_tick(at(10), at(10)), Some(at(11)));
+        }
+        // Ticks 11 to 13 passed during the delivery of tick 10
+        let now = at(13) + chrono::TimeDelta::seconds(30);
+        assert_eq!(
+            schedule(MissedTicks::Skip).next_tick(at(10), now),
+            Some(at(14))
+        );
+        assert_eq!(
+            schedule(MissedTicks::FireOnce).next_tick(at(10), now),
+            Some(at(13))
+        );
+        assert_eq!(
+            schedule(MissedTicks::CatchUp).next_tick(at(10), now),
+            Some(at(11))
+        );
+        let now = at(10 + MAX_CATCH_UP_TICKS as i64 + 1);
+        assert_eq!(
+            schedule(MissedTicks::CatchUp).next_tick(at(10), now),
+            Some(now + chrono::TimeDelta::minutes(1))
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Reloading the configuration of a running cluster host.
+//!
+//! A cluster host built with a [`ConfigSource`] re-applies the configuration
+//! it loads on `host.reload` API requests, which need the `admin` verb, and
+//! whenever its [`ReloadHandle`] is triggered, e.g. on `SIGHUP`. Only the
+//! parts of the configuration in [`LiveConfig`] can change while the host runs,
+//! anything else, such as plugins, takes a restart.
+//!
+//! The listeners of the HTTP server are added and removed without closing
+//! open connections, and its TLS certificate files are read again on every
+//! reload, so renewed certificates apply to the next connections.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use super::auth::ApiTokens;
+use crate::host::http::TlsFiles;
+use crate::host::limits::HostLimits;
+use crate::host::{Host, HostApi};
+
+/// The parts of the configuration of a cluster host that can change while it
+/// runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct LiveConfig {
+    pub limits: HostLimits,
+    /// Tokens API requests are checked against. Requests are not checked if
+    /// `None`.
+    pub api_tokens: Option<ApiTokens>,
+    /// Addresses the HTTP server listens on. Kept as they are if `None`.
+    pub http_addrs: Option<Vec<SocketAddr>>,
+    /// Certificate files of the TLS listeners of the HTTP server. Kept as
+    /// they are if `None`.
+    pub http_tls: Option<TlsFiles>,
+}
+
+impl LiveConfig {
+    pub fn new(limits: HostLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Checks API requests against `api_tokens`.
+    pub fn with_api_tokens(mut self, api_tokens: ApiTokens) -> Self {
+        self.api_tokens = Some(api_tokens);
+        self
+    }
+
+    /// Makes the HTTP server listen on exactly `addrs`.
+    pub fn with_http_addrs(mut self, addrs: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.http_addrs = Some(addrs.into_iter().collect());
+        self
+    }
+
+    /// Loads the certificate of the TLS listeners of the HTTP server from
+    /// `files`.
+    pub fn with_http_tls(mut self, files: TlsFiles) -> Self {
+        self.http_tls = Some(files);
+        self
+    }
+}
+
+/// Loads the configuration of a cluster host, e.g. from a file.
+#[async_trait::async_trait]
+pub trait ConfigSource: Send + Sync {
+    /// Returns the current configuration.
+    async fn load(&self) -> anyhow::Result<LiveConfig>;
+}
+
+/// Triggers a reload of the configuration of a running cluster host.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadHandle(Arc<Notify>);
+
+impl ReloadHandle {
+    /// Asks the host to reload its configuration. Failures are logged by the
+    /// host, which keeps its current configuration.
+    pub fn reload(&self) {
+        self.0.notify_one();
+    }
+
+    pub(crate) async fn requested(&self) {
+        self.0.notified().await
+    }
+}
+
+/// Loads the configuration from `source` and applies it, returning the parts
+/// that changed.
+pub(crate) async fn reload(
+    source: &dyn ConfigSource,
+    host: &Host,
+    api_tokens: &mut Option<ApiTokens>,
+) -> anyhow::Result<Vec<String>> {
+    let config = source.load().await?;
+    let mut changed = Vec::new();
+    // Applied first, as they are the likeliest to fail, e.g. on a port in use
+    if let Some(addrs) = &config.http_addrs {
+        let current = host.http_listener_list().await?.addrs;
+        // New listeners are added first, so the server always listens somewhere
+        for addr in addrs.iter().filter(|addr| !current.contains(addr)) {
+            host.http_listener_add(*addr).await?;
+        }
+        for addr in current.iter().filter(|addr| !addrs.contains(addr)) {
+            host.http_listener_remove(*addr).await?;
+        }
+        if current.len() != addrs.len() || current.iter().any(|addr| !addrs.contains(addr)) {
+            changed.push("http_listeners".to_string());
+        }
+    }
+    if let Some(files) = &config.http_tls {
+        host.http_tls_reload(Some(files.clone())).await?;
+        changed.push("http_tls".to_string());
+    }
+    if config.limits != host.limits() {
+        host.set_limits(config.limits);
+        changed.push("limits".to_string());
+    }
+    if config.api_tokens != *api_tokens {
+        *api_tokens = config.api_tokens;
+        changed.push("api_tokens".to_string());
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(LiveConfig);
+
+    #[async_trait::async_trait]
+    impl ConfigSource for Fixed {
+        async fn load(&self) -> anyhow::Result<LiveConfig> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let host = Host::builder().build().unwrap();
+        let mut api_tokens = None;
+
+        let source = Fixed(LiveConfig {
+            limits: HostLimits::default().with_max_workloads(5),
+            api_tokens: Some(ApiTokens::new("secret")),
+            ..Default::default()
+        });
+        assert_eq!(
+            reload(&source, &host, &mut api_tokens).await.unwrap(),
+            vec!["limits", "api_tokens"]
+        );
+        assert_eq!(host.limits().max_workloads(), Some(5));
+        assert!(api_tokens.is_some());
+
+        // Nothing changed the second time
+        assert!(
+            reload(&source, &host, &mut api_tokens)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Nothing is applied if the HTTP server can't be reconfigured
+        let source = Fixed(LiveConfig {
+            http_tls: Some(TlsFiles::new("tls.crt", "tls.key")),
+            ..Default::default()
+        });
+        assert!(reload(&source, &host, &mut api_tokens).await.is_err());
+        assert_eq!(host.limits().max_workloads(), Some(5));
+        assert!(api_tokens.is_some());
+    }
+}
@@ -8,18 +8,32 @@
 //!
 //! - [`WitWorld`] - A collection of imports and exports representing a WIT world
 //! - [`WitInterface`] - A specific interface specification with namespace, package, and version
+//! - [`CompatibilityReport`] - The result of checking a guest world against a host world
+//! - [`WorldMismatch`] - A way a workload doesn't fit the interfaces available to it
 //!
 //! # Interface Matching
 //!
 //! The [`WitInterface::contains`] method is used to determine if one interface
 //! specification can satisfy another. This is crucial for matching component
 //! requirements with plugin capabilities.
+//!
+//! Workloads are bound to plugins with [`WitWorld::includes_bidirectional`],
+//! which accepts semver compatible versions: a workload importing
+//! `wasi:http/types@0.2.2` can be served by a plugin providing
+//! `wasi:http/types@0.2.0`. Versions are compatible when they share the major
+//! version, or the minor version for `0.x` releases, and neither is a
+//! prerelease that differs from the other. Wasmtime links compatible versions
+//! to each other in the same way.
 
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
 };
 
+use anyhow::{Context as _, bail, ensure};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
 /// A collection of WIT interfaces representing a world definition.
 ///
 /// A WIT world describes the imports and exports that a component or
@@ -45,27 +59,19 @@ impl WitWorld {
 
     /// This function checks if the world includes a specific interface. This is
     /// different than [`WitWorld::includes`] because it considers that in one
-    /// [`WitInterface`] there may be both imports and exports.
+    /// [`WitInterface`] there may be both imports and exports. Versions only
+    /// need to be semver compatible, see [`WitInterface::version_compatible`].
     pub fn includes_bidirectional(&self, interface: &WitInterface) -> bool {
         let import_match = self.imports.iter().find(|i| {
-            if let Some(v) = &interface.version
-                && let Some(ov) = &i.version
-                && v != ov
-            {
-                return false;
-            }
-            i.namespace == interface.namespace && i.package == interface.package
+            i.version_compatible(interface)
+                && i.namespace == interface.namespace
+                && i.package == interface.package
         });
 
         let export_match = self.exports.iter().find(|e| {
-            // If both interfaces specify a version, they must match
-            if let Some(v) = &interface.version
-                && let Some(ov) = &e.version
-                && v != ov
-            {
-                return false;
-            }
-            e.namespace == interface.namespace && e.package == interface.package
+            e.version_compatible(interface)
+                && e.namespace == interface.namespace
+                && e.package == interface.package
         });
 
         // Ensure the interfaces are covered by either the import or export match
@@ -89,6 +95,22 @@ impl WitWorld {
         true
     }
 
+    /// Returns the highest version at which this world imports or exports a
+    /// version of the package of `interface` compatible with it. Used to pick
+    /// between plugins serving the same interface.
+    pub fn package_version(&self, interface: &WitInterface) -> Option<&semver::Version> {
+        self.imports
+            .iter()
+            .chain(&self.exports)
+            .filter(|i| {
+                i.namespace == interface.namespace
+                    && i.package == interface.package
+                    && i.version_compatible(interface)
+            })
+            .filter_map(|i| i.version.as_ref())
+            .max()
+    }
+
     /// Checks if a guest world (imports) can be satisfied by a host world (exports).
     ///
     /// A host world satisfies a guest world if all interfaces required by the guest
@@ -129,6 +151,207 @@ impl WitWorld {
 
         true
     }
+
+    /// Builds a [`CompatibilityReport`] describing how well this (host) world can
+    /// satisfy the imports of a guest world.
+    ///
+    /// Unlike [`WitWorld::satisfies`], which only answers yes or no, the report lists
+    /// every guest import and whether it is satisfied, missing entirely, or only
+    /// available at an incompatible version. Versions are considered compatible
+    /// when they are semver compatible (same major version, or same minor version
+    /// for `0.x` releases) and neither is a prerelease that differs from the other.
+    ///
+    /// # Arguments
+    /// * `guest` - The guest world to check
+    pub fn compatibility(&self, guest: &WitWorld) -> CompatibilityReport {
+        let mut report = CompatibilityReport::default();
+        let provided: Vec<&WitInterface> = self.imports.iter().chain(self.exports.iter()).collect();
+
+        for required in &guest.imports {
+            // A guest import may be served by several host entries of its package,
+            // e.g. `wasi:http/types` and `wasi:http/outgoing-handler` provided
+            // separately, so the interfaces are checked against their union
+            let candidates: Vec<&WitInterface> = provided
+                .iter()
+                .copied()
+                .filter(|p| {
+                    p.namespace == required.namespace
+                        && p.package == required.package
+                        && (required.interfaces.is_empty()
+                            || !p.interfaces.is_disjoint(&required.interfaces))
+                })
+                .collect();
+            let satisfied = covers(
+                required,
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|c| c.version_compatible(required)),
+            );
+
+            if satisfied {
+                report.satisfied.push(required.clone());
+            } else if !covers(required, candidates.iter().copied()) {
+                report.missing.push(required.clone());
+            } else {
+                let mut available: Vec<semver::Version> = candidates
+                    .iter()
+                    .filter_map(|c| c.version.clone())
+                    .collect();
+                available.sort();
+                available.dedup();
+                report.version_conflicts.push(VersionConflict {
+                    required: required.clone(),
+                    available,
+                });
+            }
+        }
+
+        report.satisfied.sort_by_key(|i| i.to_string());
+        report.missing.sort_by_key(|i| i.to_string());
+        report
+            .version_conflicts
+            .sort_by_key(|c| c.required.to_string());
+        report
+    }
+}
+
+/// Returns whether `candidates` together provide every interface of `required`.
+fn covers<'a>(required: &WitInterface, candidates: impl Iterator<Item = &'a WitInterface>) -> bool {
+    let mut candidates = candidates.peekable();
+    if candidates.peek().is_none() {
+        return false;
+    }
+    let provided: HashSet<&String> = candidates.flat_map(|c| c.interfaces.iter()).collect();
+    required.interfaces.iter().all(|i| provided.contains(i))
+}
+
+/// Returns whether two interface versions can be used interchangeably, see
+/// [`WitInterface::version`].
+fn versions_compatible(a: &semver::Version, b: &semver::Version) -> bool {
+    if !a.pre.is_empty() || !b.pre.is_empty() {
+        return a == b;
+    }
+    if a.major != b.major {
+        return false;
+    }
+    a.major != 0 || a.minor == b.minor
+}
+
+/// A structured report of whether a guest [`WitWorld`] can run against a host [`WitWorld`].
+///
+/// Returned by [`WitWorld::compatibility`] and [`crate::host::Host::check_compatibility`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Guest imports that the host can provide
+    pub satisfied: Vec<WitInterface>,
+    /// Guest imports for which the host provides no matching package or interface
+    pub missing: Vec<WitInterface>,
+    /// Guest imports that the host provides, but only at incompatible versions
+    pub version_conflicts: Vec<VersionConflict>,
+}
+
+impl CompatibilityReport {
+    /// Returns `true` if every guest import is satisfied.
+    pub fn is_compatible(&self) -> bool {
+        self.missing.is_empty() && self.version_conflicts.is_empty()
+    }
+}
+
+impl Display for CompatibilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for iface in &self.satisfied {
+            writeln!(f, "satisfied: {iface}")?;
+        }
+        for iface in &self.missing {
+            writeln!(f, "missing: {iface}")?;
+        }
+        for conflict in &self.version_conflicts {
+            let available: Vec<String> = conflict.available.iter().map(|v| v.to_string()).collect();
+            writeln!(
+                f,
+                "version conflict: {} (available: {})",
+                conflict.required,
+                available.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A guest import that is provided by the host, but not at a compatible version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionConflict {
+    /// The interface the guest requires
+    pub required: WitInterface,
+    /// The versions of the interface that the host provides
+    pub available: Vec<semver::Version>,
+}
+
+/// A way the components of a workload don't fit the interfaces available to
+/// them, found before the workload is bound to plugins, see
+/// [`crate::engine::workload::UnresolvedWorkload::validate_world`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorldMismatch {
+    /// A component imports an interface that plugins provide, but the
+    /// workload doesn't declare it in its host interfaces
+    UndeclaredImport {
+        component_id: String,
+        interface: WitInterface,
+    },
+    /// A component imports an interface that neither the host, its plugins
+    /// nor another component of the workload provide
+    MissingImport {
+        component_id: String,
+        interface: WitInterface,
+    },
+    /// A component imports an interface that plugins only provide at
+    /// incompatible versions
+    VersionConflict {
+        component_id: String,
+        conflict: VersionConflict,
+    },
+    /// The workload declares an interface the host calls into, but no
+    /// component exports it
+    MissingExport { interface: WitInterface },
+}
+
+impl Display for WorldMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldMismatch::UndeclaredImport {
+                component_id,
+                interface,
+            } => write!(
+                f,
+                "component {component_id} imports {interface}, which the workload does not declare in its host interfaces"
+            ),
+            WorldMismatch::MissingImport {
+                component_id,
+                interface,
+            } => write!(
+                f,
+                "component {component_id} imports {interface}, which no plugin of the host or component of the workload provides"
+            ),
+            WorldMismatch::VersionConflict {
+                component_id,
+                conflict,
+            } => {
+                let available: Vec<String> =
+                    conflict.available.iter().map(|v| v.to_string()).collect();
+                write!(
+                    f,
+                    "component {component_id} imports {}, which is only available at {}",
+                    conflict.required,
+                    available.join(", ")
+                )
+            }
+            WorldMismatch::MissingExport { interface } => write!(
+                f,
+                "the workload declares {interface}, which no component exports"
+            ),
+        }
+    }
 }
 
 /// Represents a WIT interface specification with namespace, package, and optional version.
@@ -141,19 +364,25 @@ impl WitWorld {
 /// - `wasi:http` - Just namespace and package
 /// - `wasi:http/incoming-handler` - With a single interface
 /// - `wasi:http/incoming-handler,outgoing-handler@0.2.0` - Multiple interfaces with version
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct WitInterface {
     /// The namespace of the interface (e.g., "wasi")
     pub namespace: String,
     /// The package name (e.g., "http", "blobstore")
     pub package: String,
     /// The specific interfaces within the package (e.g., "incoming-handler", "types")
+    #[serde(default)]
     pub interfaces: HashSet<String>,
-    // TODO: This is a nice way to represent a version, but it doesn't account for
-    // compatible versions. We should revisit this and implement https://docs.rs/semver/1.0.27/semver/struct.VersionReq.html
     /// Optional semantic version for the interface
+    ///
+    /// Versions are matched with caret semantics as WASI does: `0.x` versions
+    /// are compatible within the same minor release, e.g. `wasi:http@0.2.3`
+    /// binds to a host providing `0.2.2`, `1.x` and later within the same
+    /// major release, and prereleases only match exactly.
+    #[serde(default)]
     pub version: Option<semver::Version>,
     /// Additional configuration parameters for this interface
+    #[serde(default)]
     pub config: HashMap<String, String>,
 }
 
@@ -209,14 +438,28 @@ impl WitInterface {
 
         self.interfaces.is_superset(&other.interfaces)
     }
+
+    /// Returns whether the versions of this interface and `other` are semver
+    /// compatible. An interface without a version is compatible with every
+    /// version.
+    pub fn version_compatible(&self, other: &WitInterface) -> bool {
+        match (&self.version, &other.version) {
+            (Some(v), Some(ov)) => versions_compatible(v, ov),
+            _ => true,
+        }
+    }
 }
 
 impl Display for WitInterface {
+    /// Formats the interface in its canonical `namespace:package/interface1,interface2@version`
+    /// form. Interfaces are sorted so the output is stable and can be parsed back with
+    /// [`std::str::FromStr`]. Configuration is not included.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.namespace, self.package)?;
-        if !self.interfaces.is_empty() && !self.interfaces.is_empty() {
+        if !self.interfaces.is_empty() {
             write!(f, "/")?;
-            let interfaces: Vec<_> = self.interfaces.clone().into_iter().collect();
+            let mut interfaces: Vec<_> = self.interfaces.iter().map(String::as_str).collect();
+            interfaces.sort_unstable();
             write!(f, "{}", interfaces.join(","))?;
         }
         if let Some(v) = &self.version {
@@ -241,6 +484,64 @@ impl std::hash::Hash for WitInterface {
     }
 }
 
+impl std::str::FromStr for WitInterface {
+    type Err = anyhow::Error;
+
+    /// Parses a canonical interface string such as `wasi:http/incoming-handler@0.2.2`
+    /// or `wasi:keyvalue/store,atomics@0.2.0-draft`.
+    ///
+    /// Unlike the lenient `From<&str>` conversion, this requires both a namespace and
+    /// a package and rejects malformed versions and empty interface lists instead of
+    /// silently ignoring them.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (main, version) = match s.split_once('@') {
+            Some((m, v)) => {
+                let version = semver::Version::parse(v.trim())
+                    .with_context(|| format!("invalid version '{v}' in interface '{s}'"))?;
+                (m, Some(version))
+            }
+            None => (s, None),
+        };
+        let (namespace_package, interfaces) = match main.split_once('/') {
+            Some((np, ifaces)) => {
+                let interfaces: HashSet<String> = ifaces
+                    .split(',')
+                    .map(|i| i.trim().to_string())
+                    .filter(|i| !i.is_empty())
+                    .collect();
+                ensure!(
+                    !interfaces.is_empty(),
+                    "no interfaces listed after '/' in interface '{s}'"
+                );
+                (np, interfaces)
+            }
+            None => (main, HashSet::new()),
+        };
+        let Some((namespace, package)) = namespace_package.split_once(':') else {
+            bail!("interface '{s}' is missing a namespace, expected 'namespace:package'");
+        };
+        let (namespace, package) = (namespace.trim(), package.trim());
+        ensure!(
+            !namespace.is_empty(),
+            "interface '{s}' has an empty namespace"
+        );
+        ensure!(!package.is_empty(), "interface '{s}' has an empty package");
+        ensure!(
+            !package.contains(':'),
+            "interface '{s}' has more than one namespace separator"
+        );
+
+        Ok(WitInterface {
+            namespace: namespace.to_string(),
+            package: package.to_string(),
+            interfaces,
+            version,
+            config: HashMap::new(),
+        })
+    }
+}
+
 impl From<&str> for WitInterface {
     fn from(s: &str) -> Self {
         // Expected format: namespace:package/interface@version
@@ -540,6 +841,152 @@ mod tests {
         assert!(!host_world_subset.satisfies(&guest_world));
     }
 
+    #[test]
+    fn test_semver_compatible_matching() {
+        let plugin_world = WitWorld {
+            imports: HashSet::new(),
+            exports: [
+                WitInterface::from("wasi:http/types,outgoing-handler@0.2.0"),
+                WitInterface::from("wasi:keyvalue/store@0.2.0-draft"),
+                WitInterface::from("wasmcloud:messaging/consumer@1.2.0"),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        // Patch and minor releases of the same 0.x or 1.x line are compatible
+        let http = WitInterface::from("wasi:http/outgoing-handler@0.2.2");
+        assert!(plugin_world.includes_bidirectional(&http));
+        assert_eq!(
+            plugin_world.package_version(&http),
+            Some(&semver::Version::parse("0.2.0").unwrap())
+        );
+        assert!(
+            plugin_world
+                .includes_bidirectional(&WitInterface::from("wasmcloud:messaging/consumer@1.0.0"))
+        );
+
+        // Other 0.x minor versions, major versions and prereleases are not
+        assert!(!plugin_world.includes_bidirectional(&WitInterface::from("wasi:http/types@0.3.0")));
+        assert!(
+            !plugin_world
+                .includes_bidirectional(&WitInterface::from("wasmcloud:messaging/consumer@2.0.0"))
+        );
+        assert!(
+            !plugin_world
+                .includes_bidirectional(&WitInterface::from("wasi:keyvalue/store@0.2.0-draft2"))
+        );
+        assert_eq!(
+            plugin_world.package_version(&WitInterface::from("wasi:http/types@0.3.0")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_world_compatibility_report() {
+        let guest_world = WitWorld {
+            imports: [
+                WitInterface::from("wasi:logging/logging@0.1.0-draft"),
+                WitInterface::from("wasi:keyvalue/store@0.2.0-draft"),
+                WitInterface::from("wasi:io/streams@0.2.6"),
+                WitInterface::from("wasi:config/store@0.2.0-rc.1"),
+                WitInterface::from("wasmcloud:messaging/consumer@0.2.0"),
+            ]
+            .into_iter()
+            .collect(),
+            exports: HashSet::new(),
+        };
+
+        let host_world = WitWorld {
+            imports: [
+                WitInterface::from("wasi:logging/logging@0.1.0-draft"),
+                WitInterface::from("wasi:keyvalue/store,atomics@0.2.0-draft"),
+                WitInterface::from("wasi:config/store@0.1.0"),
+            ]
+            .into_iter()
+            .collect(),
+            exports: [WitInterface::from("wasi:io/poll,error,streams@0.2.0")]
+                .into_iter()
+                .collect(),
+        };
+
+        let report = host_world.compatibility(&guest_world);
+        assert!(!report.is_compatible());
+        assert_eq!(report.satisfied.len(), 3);
+        assert_eq!(
+            report.missing,
+            vec![WitInterface::from("wasmcloud:messaging/consumer@0.2.0")]
+        );
+        assert_eq!(report.version_conflicts.len(), 1);
+        assert_eq!(
+            report.version_conflicts[0].required,
+            WitInterface::from("wasi:config/store@0.2.0-rc.1")
+        );
+        assert_eq!(
+            report.version_conflicts[0].available,
+            vec![semver::Version::parse("0.1.0").unwrap()]
+        );
+
+        let empty_guest = WitWorld::default();
+        assert!(host_world.compatibility(&empty_guest).is_compatible());
+    }
+
+    #[test]
+    fn test_world_compatibility_across_entries() {
+        let guest_world = WitWorld {
+            imports: [WitInterface::from("wasi:http/types,outgoing-handler@0.2.2")]
+                .into_iter()
+                .collect(),
+            exports: HashSet::new(),
+        };
+
+        // The interfaces are provided by separate entries of the package
+        let host_world = WitWorld {
+            imports: [
+                WitInterface::from("wasi:http/types@0.2.0"),
+                WitInterface::from("wasi:http/outgoing-handler@0.2.0"),
+            ]
+            .into_iter()
+            .collect(),
+            exports: HashSet::new(),
+        };
+        let report = host_world.compatibility(&guest_world);
+        assert!(report.is_compatible(), "{report}");
+
+        // Only one of them at a compatible version
+        let host_world = WitWorld {
+            imports: [
+                WitInterface::from("wasi:http/types@0.2.0"),
+                WitInterface::from("wasi:http/outgoing-handler@0.3.0"),
+            ]
+            .into_iter()
+            .collect(),
+            exports: HashSet::new(),
+        };
+        let report = host_world.compatibility(&guest_world);
+        assert_eq!(report.version_conflicts.len(), 1, "{report}");
+        assert_eq!(
+            report.version_conflicts[0].available,
+            vec![
+                semver::Version::parse("0.2.0").unwrap(),
+                semver::Version::parse("0.3.0").unwrap()
+            ]
+        );
+
+        // One of them not at all
+        let host_world = WitWorld {
+            imports: [WitInterface::from("wasi:http/types@0.2.0")]
+                .into_iter()
+                .collect(),
+            exports: HashSet::new(),
+        };
+        let report = host_world.compatibility(&guest_world);
+        assert_eq!(
+            report.missing,
+            vec![WitInterface::from("wasi:http/types,outgoing-handler@0.2.2")]
+        );
+    }
+
     #[test]
     fn test_parse_basic_formats() {
         // Basic namespace:package
@@ -675,6 +1122,65 @@ mod tests {
         assert!(iface3.interfaces.contains("atomic"));
     }
 
+    #[test]
+    fn test_from_str_canonical() {
+        let iface: WitInterface = "wasi:http/incoming-handler@0.2.2".parse().unwrap();
+        assert_eq!(
+            iface,
+            WitInterface::from("wasi:http/incoming-handler@0.2.2")
+        );
+
+        let iface: WitInterface = "wasi:keyvalue/store, atomics ,batch@0.2.0-draft"
+            .parse()
+            .unwrap();
+        assert_eq!(iface.interfaces.len(), 3);
+        assert_eq!(
+            iface.version,
+            Some(semver::Version::parse("0.2.0-draft").unwrap())
+        );
+
+        let iface: WitInterface = "wasmcloud:messaging".parse().unwrap();
+        assert!(iface.interfaces.is_empty());
+        assert!(iface.version.is_none());
+    }
+
+    #[test]
+    fn test_from_str_rejects_malformed() {
+        for input in [
+            "",
+            "blobstore/types",
+            ":blobstore",
+            "wasi:",
+            "wasi:http/",
+            "wasi:http/,",
+            "foo:bar:baz/interface",
+            "wasi:blobstore/types@invalid-version",
+        ] {
+            assert!(
+                input.parse::<WitInterface>().is_err(),
+                "expected '{input}' to be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for input in [
+            "wasi:http/incoming-handler@0.2.2",
+            "wasi:keyvalue/atomics,batch,store@0.2.0-draft",
+            "wasi:cli/environment,exit,stderr,stdin,stdout",
+            "wasmcloud:messaging@0.2.0",
+        ] {
+            let iface: WitInterface = input.parse().unwrap();
+            assert_eq!(iface.to_string(), input);
+            assert_eq!(iface.to_string().parse::<WitInterface>().unwrap(), iface);
+        }
+
+        // Interfaces are always displayed in sorted order
+        let iface = create_interface("wasi", "keyvalue", &["store", "batch", "atomics"]);
+        assert_eq!(iface.to_string(), "wasi:keyvalue/atomics,batch,store");
+    }
+
     #[test]
     fn test_display() {
         let iface = create_interface("wasi", "http", &["incoming-handler"]);
@@ -7,13 +7,12 @@
 
 use anyhow::{Context, Result};
 use gag::BufferRedirect;
-use std::collections::HashMap;
 use std::io::Read;
 
 use wash_runtime::{
     engine::Engine,
     host::{HostApi, HostBuilder},
-    types::{Component, Service, Workload, WorkloadStartRequest},
+    types::{Component, Service, Workload},
 };
 
 const CRON_SERVICE_WASM: &[u8] = include_bytes!("fixtures/cron_service.wasm");
@@ -40,27 +39,10 @@ async fn test_cron_service_integration() -> Result<()> {
     println!("Host started");
 
     // Create a workload request with a service and component
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "cron-service-workload".to_string(),
-            annotations: HashMap::new(),
-            service: Some(Service {
-                bytes: bytes::Bytes::from_static(CRON_SERVICE_WASM),
-                local_resources: Default::default(),
-                max_restarts: 0,
-            }),
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(CRON_COMPONENT_WASM),
-                local_resources: Default::default(),
-                max_invocations: 1,
-                pool_size: 0,
-            }],
-            host_interfaces: vec![],
-            volumes: vec![],
-        },
-    };
+    let req = Workload::builder("test", "cron-service-workload")
+        .with_service(Service::new(CRON_SERVICE_WASM))
+        .with_component(Component::new(CRON_COMPONENT_WASM).with_max_invocations(1))
+        .start_request();
 
     // Start the workload
     let _workload_response = host
@@ -7,7 +7,7 @@
 //! 4. Testing round-trip data through blobstore functionality
 
 use anyhow::{Context, Result};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 mod common;
@@ -20,8 +20,7 @@ use wash_runtime::{
         http::{DevRouter, HttpServer},
     },
     plugin::{wasi_blobstore::WasiBlobstore, wasi_logging::WasiLogging},
-    types::{Component, LocalResources, Workload, WorkloadStartRequest, WorkloadStopRequest},
-    wit::WitInterface,
+    types::{Component, LocalResources, Workload, WorkloadStopRequest, route::HttpRouteConfig},
 };
 
 const BLOBBY_WASM: &[u8] = include_bytes!("fixtures/blobby.wasm");
@@ -64,62 +63,21 @@ async fn test_blobby_integration() -> Result<()> {
     println!("Host started, HTTP server listening on {addr}");
 
     // Create a workload request with the blobby component
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "blobby-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(BLOBBY_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("test", "blobby-workload")
+        .with_component(
+            Component::new(BLOBBY_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 256,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 100,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: None,
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "blobby-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: [
-                        "blobstore".to_string(),
-                        "container".to_string(),
-                        "types".to_string(),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(100),
+        )
+        .with_http_route_config(HttpRouteConfig::new("blobby-test"))
+        .with_host_interface("wasi:blobstore/blobstore,container,types@0.2.0-draft")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .start_request();
 
     // Start the workload
     let workload_response = host
@@ -265,62 +223,21 @@ async fn test_blobby_error_handling() -> Result<()> {
         .context("Failed to start host for error test")?;
 
     // Create workload
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "error-test".to_string(),
-            name: "blobby-error-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(BLOBBY_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("error-test", "blobby-error-workload")
+        .with_component(
+            Component::new(BLOBBY_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 128,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 50,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "blobby-error-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: [
-                        "blobstore".to_string(),
-                        "container".to_string(),
-                        "types".to_string(),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(50),
+        )
+        .with_http_route_config(HttpRouteConfig::new("blobby-error-test"))
+        .with_host_interface("wasi:blobstore/blobstore,container,types@0.2.0-draft")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .start_request();
 
     let workload_response = host
         .workload_start(req)
@@ -10,7 +10,7 @@
 //! Full component binding and request routing would require proper WIT interface configuration.
 
 use anyhow::{Context, Result};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 mod common;
@@ -23,8 +23,7 @@ use wash_runtime::{
         http::{DevRouter, HttpServer},
     },
     plugin::wasi_blobstore::WasiBlobstore,
-    types::{Component, LocalResources, Workload, WorkloadStartRequest},
-    wit::WitInterface,
+    types::{Component, LocalResources, Workload, route::HttpRouteConfig},
 };
 
 const HTTP_BLOBSTORE_WASM: &[u8] = include_bytes!("fixtures/http_blobstore.wasm");
@@ -64,55 +63,20 @@ async fn test_http_blobstore_integration() -> Result<()> {
     println!("Host started, HTTP server listening on {addr}");
 
     // Create a workload request with the HTTP component
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "test-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_BLOBSTORE_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("test", "test-workload")
+        .with_component(
+            Component::new(HTTP_BLOBSTORE_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 256,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 100,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "foo".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: [
-                        "blobstore".to_string(),
-                        "container".to_string(),
-                        "types".to_string(),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(100),
+        )
+        .with_http_route_config(HttpRouteConfig::new("foo"))
+        .with_host_interface("wasi:blobstore/blobstore,container,types@0.2.0-draft")
+        .start_request();
 
     // Start the workload
     let workload_response = host
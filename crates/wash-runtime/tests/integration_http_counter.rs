@@ -9,7 +9,7 @@
 //! 6. Testing error handling when external HTTP requests fail
 
 use anyhow::{Context, Result};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 mod common;
@@ -25,8 +25,7 @@ use wash_runtime::{
         wasi_blobstore::WasiBlobstore, wasi_config::WasiConfig, wasi_keyvalue::WasiKeyvalue,
         wasi_logging::WasiLogging,
     },
-    types::{Component, LocalResources, Workload, WorkloadStartRequest},
-    wit::WitInterface,
+    types::{Component, LocalResources, Workload, route::HttpRouteConfig},
 };
 
 const HTTP_COUNTER_WASM: &[u8] = include_bytes!("fixtures/http_counter.wasm");
@@ -77,83 +76,25 @@ async fn test_http_counter_integration() -> Result<()> {
     println!("Host started, HTTP server listening on {addr}");
 
     // Create a workload request with the HTTP counter component
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "http-counter-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_COUNTER_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("test", "http-counter-workload")
+        .with_component(
+            Component::new(HTTP_COUNTER_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 256,
                     cpu_limit: 1,
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("test_key".to_string(), "test_value".to_string());
-                        config.insert("counter_enabled".to_string(), "true".to_string());
-                        config
-                    },
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 100,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "foo".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: [
-                        "blobstore".to_string(),
-                        "container".to_string(),
-                        "types".to_string(),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_config("test_key", "test_value")
+                .with_config("counter_enabled", "true")
+                .with_pool_size(1)
+                .with_max_invocations(100),
+        )
+        .with_http_route_config(HttpRouteConfig::new("foo"))
+        .with_host_interface("wasi:blobstore/blobstore,container,types@0.2.0-draft")
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .start_request();
 
     // Start the workload
     let workload_response = host
@@ -416,78 +357,23 @@ async fn test_http_counter_error_scenarios() -> Result<()> {
         .context("Failed to start error test host")?;
 
     // Create workload with restricted configuration (no outbound hosts allowed)
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "error-test".to_string(),
-            name: "http-counter-error-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_COUNTER_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("error-test", "http-counter-error-workload")
+        .with_component(
+            Component::new(HTTP_COUNTER_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 128,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 50,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "error-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: [
-                        "blobstore".to_string(),
-                        "container".to_string(),
-                        "types".to_string(),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(50),
+        )
+        .with_http_route_config(HttpRouteConfig::new("error-test"))
+        .with_host_interface("wasi:blobstore/blobstore,container,types@0.2.0-draft")
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .start_request();
 
     let workload_response = host
         .workload_start(req)
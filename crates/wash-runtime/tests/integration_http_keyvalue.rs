@@ -8,7 +8,7 @@
 //! 5. Testing batch operations if supported by the component
 
 use anyhow::{Context, Result};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 mod common;
@@ -24,8 +24,7 @@ use wash_runtime::{
         wasi_blobstore::WasiBlobstore, wasi_config::WasiConfig, wasi_keyvalue::WasiKeyvalue,
         wasi_logging::WasiLogging,
     },
-    types::{Component, LocalResources, Workload, WorkloadStartRequest},
-    wit::WitInterface,
+    types::{Component, LocalResources, Workload, route::HttpRouteConfig},
 };
 
 const HTTP_KEYVALUE_COUNTER_WASM: &[u8] = include_bytes!("fixtures/http_keyvalue_counter.wasm");
@@ -76,72 +75,23 @@ async fn test_http_keyvalue_counter_integration() -> Result<()> {
     println!("Host started, HTTP server listening on {addr}");
 
     // Create a workload request with the counter component
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "keyvalue-counter-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_KEYVALUE_COUNTER_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("test", "keyvalue-counter-workload")
+        .with_component(
+            Component::new(HTTP_KEYVALUE_COUNTER_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 256,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 100,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "keyvalue-counter-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: ["blobstore".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(100),
+        )
+        .with_http_route_config(HttpRouteConfig::new("keyvalue-counter-test"))
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:blobstore/blobstore@0.2.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .start_request();
 
     // Start the workload
     let workload_response = host
@@ -345,72 +295,24 @@ async fn test_keyvalue_counter_concurrent_access() -> Result<()> {
         .context("Failed to start concurrent test host")?;
 
     // Create workload
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "concurrent-test".to_string(),
-            name: "concurrent-counter-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_KEYVALUE_COUNTER_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("concurrent-test", "concurrent-counter-workload")
+        .with_component(
+            Component::new(HTTP_KEYVALUE_COUNTER_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 256,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 3, // Higher pool size for concurrent testing
-                max_invocations: 200,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "concurrent-counter-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: ["blobstore".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                // Higher pool size for concurrent testing
+                .with_pool_size(3)
+                .with_max_invocations(200),
+        )
+        .with_http_route_config(HttpRouteConfig::new("concurrent-counter-test"))
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:blobstore/blobstore@0.2.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .start_request();
 
     let workload_response = host
         .workload_start(req)
@@ -525,72 +427,23 @@ async fn test_keyvalue_error_handling() -> Result<()> {
         .await
         .context("Failed to start error test host")?;
 
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "error-test".to_string(),
-            name: "keyvalue-error-workload".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![Component {
-                bytes: bytes::Bytes::from_static(HTTP_KEYVALUE_COUNTER_WASM),
-                local_resources: LocalResources {
+    let req = Workload::builder("error-test", "keyvalue-error-workload")
+        .with_component(
+            Component::new(HTTP_KEYVALUE_COUNTER_WASM)
+                .with_local_resources(LocalResources {
                     memory_limit_mb: 128,
                     cpu_limit: 1,
-                    config: HashMap::new(),
-                    environment: HashMap::new(),
-                    volume_mounts: vec![],
-                    allowed_hosts: vec![],
-                },
-                pool_size: 1,
-                max_invocations: 50,
-            }],
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.2").unwrap()),
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "keyvalue-error-test".to_string());
-                        config
-                    },
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "blobstore".to_string(),
-                    interfaces: ["blobstore".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            volumes: vec![],
-        },
-    };
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(50),
+        )
+        .with_http_route_config(HttpRouteConfig::new("keyvalue-error-test"))
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:blobstore/blobstore@0.2.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .start_request();
 
     let workload_response = host
         .workload_start(req)
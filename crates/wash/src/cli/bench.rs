@@ -0,0 +1,419 @@
+//! CLI command load testing an HTTP route served by a host
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use clap::Args;
+use serde::Serialize;
+use tokio::task::JoinSet;
+use tracing::{instrument, warn};
+use wash_runtime::washlet::types::v2;
+
+use crate::cli::{CliCommand, CliContext, CommandOutput};
+
+/// Send requests to a route for a while and report latency and errors
+#[derive(Args, Debug, Clone)]
+pub struct BenchCommand {
+    /// URL of the route, e.g. http://localhost:8000/
+    pub url: url::Url,
+
+    /// HTTP method of the requests
+    #[clap(long = "method", short = 'X', default_value = "GET")]
+    pub method: reqwest::Method,
+
+    /// Number of requests in flight at once
+    #[clap(long = "concurrency", short = 'c', default_value_t = 10)]
+    pub concurrency: usize,
+
+    /// Seconds to send requests for
+    #[clap(long = "duration", short = 'd', default_value_t = 10)]
+    pub duration_secs: u64,
+
+    /// File whose contents are sent as the body of every request
+    #[clap(long = "body-file")]
+    pub body_file: Option<PathBuf>,
+
+    /// Header sent with every request, as `name: value`. May be given more
+    /// than once.
+    #[clap(long = "header", short = 'H', value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Seconds after which a request counts as timed out
+    #[clap(long = "timeout", default_value_t = 30)]
+    pub timeout_secs: u64,
+
+    /// Host serving the route. Its heartbeats are sampled during the run to
+    /// report its load. May be given more than once.
+    #[clap(long = "host-id")]
+    pub host_ids: Vec<String>,
+
+    /// NATS URL of the runtime API, used with --host-id
+    #[clap(long = "nats-url", default_value = "nats://localhost:4222")]
+    pub nats_url: String,
+
+    /// Token for hosts started with `--api-token-secret`, see `wash token issue`
+    #[clap(long = "api-token", env = "WASH_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+}
+
+fn parse_header(s: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = s
+        .split_once(':')
+        .with_context(|| format!("invalid header '{s}', expected 'name: value'"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+impl CliCommand for BenchCommand {
+    #[instrument(level = "debug", skip_all, name = "bench")]
+    async fn handle(&self, _ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        anyhow::ensure!(self.concurrency > 0, "concurrency must be at least 1");
+        let body = match &self.body_file {
+            Some(path) => Bytes::from(
+                tokio::fs::read(path)
+                    .await
+                    .with_context(|| format!("failed to read body file {}", path.display()))?,
+            ),
+            None => Bytes::new(),
+        };
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.append(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name '{name}'"))?,
+                value
+                    .parse()
+                    .with_context(|| format!("invalid value of header '{name}'"))?,
+            );
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .build()
+            .context("failed to create HTTP client")?;
+
+        let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+        let sampler = if self.host_ids.is_empty() {
+            None
+        } else {
+            let nats_client = wash_runtime::washlet::connect_nats(self.nats_url.clone(), None)
+                .await
+                .context("failed to connect to NATS")?;
+            Some(tokio::spawn(sample_hosts(
+                nats_client,
+                self.host_ids.clone(),
+                self.api_token.clone(),
+                stop_rx,
+            )))
+        };
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(self.duration_secs);
+        let mut workers = JoinSet::new();
+        for _ in 0..self.concurrency {
+            let client = client.clone();
+            let method = self.method.clone();
+            let url = self.url.clone();
+            let body = body.clone();
+            workers.spawn(async move {
+                let mut samples = Vec::new();
+                while Instant::now() < deadline {
+                    samples.push(send(&client, method.clone(), url.clone(), body.clone()).await);
+                }
+                samples
+            });
+        }
+        let mut samples = Vec::new();
+        while let Some(worker) = workers.join_next().await {
+            samples.extend(worker.context("benchmark worker failed")?);
+        }
+        let elapsed = started.elapsed();
+
+        let _ = stop_tx.send(true);
+        let hosts = match sampler {
+            Some(sampler) => sampler.await.context("host sampler failed")?,
+            None => Vec::new(),
+        };
+
+        let report = BenchReport::new(&samples, elapsed, hosts);
+        Ok(CommandOutput::ok(
+            report.to_string(),
+            Some(serde_json::to_value(&report).context("failed to serialize report")?),
+        ))
+    }
+}
+
+/// The outcome of a single request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Time until the whole response was read
+    pub latency: Duration,
+    /// The status of the response, or what went wrong without one
+    pub outcome: Result<u16, &'static str>,
+}
+
+async fn send(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: url::Url,
+    body: Bytes,
+) -> Sample {
+    let started = Instant::now();
+    let outcome = async {
+        let response = client.request(method, url).body(body).send().await?;
+        let status = response.status().as_u16();
+        response.bytes().await?;
+        Ok(status)
+    }
+    .await
+    .map_err(|e: reqwest::Error| {
+        if e.is_timeout() {
+            "timeout"
+        } else if e.is_connect() {
+            "connect"
+        } else if e.is_body() || e.is_decode() {
+            "body"
+        } else {
+            "request"
+        }
+    });
+    Sample {
+        latency: started.elapsed(),
+        outcome,
+    }
+}
+
+/// Requests heartbeats from hosts every second until told to stop
+async fn sample_hosts(
+    nats_client: async_nats::Client,
+    host_ids: Vec<String>,
+    api_token: Option<String>,
+    mut stop: tokio::sync::watch::Receiver<bool>,
+) -> Vec<HostLoad> {
+    let mut heartbeats: BTreeMap<String, Vec<v2::HostHeartbeat>> = host_ids
+        .iter()
+        .map(|host_id| (host_id.clone(), Vec::new()))
+        .collect();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stop.changed() => break,
+        }
+        for host_id in &host_ids {
+            match wash_runtime::washlet::api_request::<_, v2::HostHeartbeat>(
+                &nats_client,
+                host_id,
+                "heartbeat",
+                &(),
+                api_token.as_deref(),
+            )
+            .await
+            {
+                Ok(heartbeat) => heartbeats
+                    .entry(host_id.clone())
+                    .or_default()
+                    .push(heartbeat),
+                Err(e) => warn!(host_id, error = %e, "failed to sample host"),
+            }
+        }
+    }
+    heartbeats
+        .into_iter()
+        .map(|(host_id, heartbeats)| HostLoad::new(host_id, &heartbeats))
+        .collect()
+}
+
+/// Latency of the requests that got a response, in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencySummary {
+    fn new(latencies: &mut [Duration]) -> Self {
+        if latencies.is_empty() {
+            return Self::default();
+        }
+        latencies.sort_unstable();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        // Nearest-rank percentile
+        let percentile = |p: usize| ms(latencies[(latencies.len() * p).div_ceil(100).max(1) - 1]);
+        Self {
+            min_ms: ms(latencies[0]),
+            mean_ms: ms(latencies.iter().sum::<Duration>()) / latencies.len() as f64,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: ms(latencies[latencies.len() - 1]),
+        }
+    }
+}
+
+/// The load of a host during a run, from the heartbeats sampled
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostLoad {
+    pub host_id: String,
+    pub samples: usize,
+    pub mean_cpu_usage: f32,
+    pub peak_cpu_usage: f32,
+    /// Least free memory seen, in bytes
+    pub min_memory_free: u64,
+    pub max_workloads: u64,
+    pub max_components: u64,
+}
+
+impl HostLoad {
+    fn new(host_id: String, heartbeats: &[v2::HostHeartbeat]) -> Self {
+        let cpu = heartbeats.iter().map(|h| h.system_cpu_usage);
+        Self {
+            host_id,
+            samples: heartbeats.len(),
+            mean_cpu_usage: cpu.clone().sum::<f32>() / heartbeats.len().max(1) as f32,
+            peak_cpu_usage: cpu.fold(0.0, f32::max),
+            min_memory_free: heartbeats
+                .iter()
+                .map(|h| h.system_memory_free)
+                .min()
+                .unwrap_or_default(),
+            max_workloads: heartbeats
+                .iter()
+                .map(|h| h.workload_count)
+                .max()
+                .unwrap_or_default(),
+            max_components: heartbeats
+                .iter()
+                .map(|h| h.component_count)
+                .max()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The results of a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchReport {
+    pub requests: usize,
+    /// Requests answered with a status below 400
+    pub successful: usize,
+    pub duration_secs: f64,
+    pub requests_per_sec: f64,
+    pub latency: LatencySummary,
+    /// Failed requests by status code or error kind
+    pub errors: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<HostLoad>,
+}
+
+impl BenchReport {
+    pub fn new(samples: &[Sample], elapsed: Duration, hosts: Vec<HostLoad>) -> Self {
+        let mut errors = BTreeMap::new();
+        let mut latencies = Vec::with_capacity(samples.len());
+        for sample in samples {
+            match sample.outcome {
+                Ok(status) => {
+                    latencies.push(sample.latency);
+                    if status >= 400 {
+                        *errors.entry(format!("HTTP {status}")).or_default() += 1;
+                    }
+                }
+                Err(kind) => *errors.entry(kind.to_string()).or_default() += 1,
+            }
+        }
+        Self {
+            requests: samples.len(),
+            successful: samples.len() - errors.values().sum::<usize>(),
+            duration_secs: elapsed.as_secs_f64(),
+            requests_per_sec: samples.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            latency: LatencySummary::new(&mut latencies),
+            errors,
+            hosts,
+        }
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} requests in {:.1}s, {:.1} requests/s, {} successful",
+            self.requests, self.duration_secs, self.requests_per_sec, self.successful
+        )?;
+        let l = &self.latency;
+        writeln!(
+            f,
+            "Latency: min {:.2}ms, mean {:.2}ms, p50 {:.2}ms, p90 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            l.min_ms, l.mean_ms, l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms
+        )?;
+        if !self.errors.is_empty() {
+            writeln!(f, "Errors:")?;
+            for (kind, count) in &self.errors {
+                writeln!(f, "  {kind:<12} {count}")?;
+            }
+        }
+        for host in &self.hosts {
+            writeln!(
+                f,
+                "Host {}: CPU mean {:.1}%, peak {:.1}%, min free memory {} MiB, {} workloads, {} components ({} samples)",
+                host.host_id,
+                host.mean_cpu_usage,
+                host.peak_cpu_usage,
+                host.min_memory_free / (1024 * 1024),
+                host.max_workloads,
+                host.max_components,
+                host.samples
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut samples = (1..=100)
+            .map(|ms| Sample {
+                latency: Duration::from_millis(ms),
+                outcome: Ok(200),
+            })
+            .collect::<Vec<_>>();
+        samples[0].outcome = Ok(503);
+        samples.push(Sample {
+            latency: Duration::from_secs(30),
+            outcome: Err("timeout"),
+        });
+
+        let report = BenchReport::new(&samples, Duration::from_secs(2), Vec::new());
+        assert_eq!(report.requests, 101);
+        assert_eq!(report.successful, 99);
+        assert_eq!(report.requests_per_sec, 50.5);
+        assert_eq!(
+            report.errors,
+            BTreeMap::from([("HTTP 503".to_string(), 1), ("timeout".to_string(), 1)])
+        );
+        // Requests without a response don't count towards latency
+        assert_eq!(report.latency.min_ms, 1.0);
+        assert_eq!(report.latency.p50_ms, 50.0);
+        assert_eq!(report.latency.p99_ms, 99.0);
+        assert_eq!(report.latency.max_ms, 100.0);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("Content-Type: application/json").unwrap(),
+            ("Content-Type".to_string(), "application/json".to_string())
+        );
+        assert!(parse_header("Content-Type").is_err());
+    }
+}
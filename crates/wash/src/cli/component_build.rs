@@ -1,4 +1,4 @@
-//! CLI command for building components, including Rust, TinyGo, and TypeScript projects
+//! CLI command for building components, including Rust, TinyGo, TypeScript, and Python projects
 
 use std::{
     env,
@@ -42,7 +42,7 @@ pub struct ComponentBuildCommand {
     #[clap(long = "skip-fetch")]
     skip_fetch: bool,
 
-    /// Additional arguments to pass to the underlying build command (cargo, tinygo, or npm/pnpm/yarn).
+    /// Additional arguments to pass to the underlying build command (cargo, tinygo, npm/pnpm/yarn, or componentize-py).
     /// These arguments must come after a '--' separator.
     /// Example: wash build -- --release --features extra
     #[clap(
@@ -304,11 +304,19 @@ impl ComponentBuilder {
             ProjectType::Rust => self.build_rust_component(config, args).await?,
             ProjectType::Go => self.build_tinygo_component(config, args).await?,
             ProjectType::TypeScript => self.build_typescript_component(config, args).await?,
+            ProjectType::Python => self.build_python_component(config, args).await?,
             ProjectType::Unknown => {
-                bail!("unknown project type. Expected to find Cargo.toml, go.mod, or package.json");
+                bail!(
+                    "unknown project type. Expected to find Cargo.toml, go.mod, package.json, or pyproject.toml"
+                );
             }
         };
 
+        // Toolchains targeting WASI preview 1 produce core modules
+        let component_path = self
+            .componentize_core_module(config, component_path)
+            .await?;
+
         // Run post-build hook
         self.run_post_build_hook().await?;
 
@@ -347,6 +355,13 @@ impl ComponentBuilder {
             return Ok(ProjectType::TypeScript);
         }
 
+        // Check for pyproject.toml or app.py (Python project)
+        if self.project_path.join("pyproject.toml").exists()
+            || self.project_path.join("app.py").exists()
+        {
+            return Ok(ProjectType::Python);
+        }
+
         Ok(ProjectType::Unknown)
     }
 
@@ -360,6 +375,10 @@ impl ComponentBuilder {
                 // Check for cargo
                 if !self.tool_exists("cargo", "--version").await {
                     missing_tools.push("cargo (Rust build tool)");
+                } else if self.uses_cargo_component()
+                    && !self.tool_exists("cargo-component", "--version").await
+                {
+                    missing_tools.push("cargo-component (Cargo subcommand for components)");
                 } else {
                     // Check for wasm32-wasip2 target
                     match tokio::process::Command::new("rustup")
@@ -399,6 +418,11 @@ impl ComponentBuilder {
                     missing_tools.push("npm (Node.js package manager)");
                 }
             }
+            ProjectType::Python => {
+                if !self.tool_exists("componentize-py", "--version").await {
+                    missing_tools.push("componentize-py (Python component builder)");
+                }
+            }
             ProjectType::Unknown => {
                 bail!("cannot check tools for unknown project type");
             }
@@ -556,7 +580,11 @@ impl ComponentBuilder {
         }
 
         // Build cargo command arguments
-        let mut cargo_args = vec!["build".to_string()];
+        let mut cargo_args = if self.uses_cargo_component() {
+            vec!["component".to_string(), "build".to_string()]
+        } else {
+            vec!["build".to_string()]
+        };
 
         // Apply release mode if configured
         if rust_config.release {
@@ -668,6 +696,13 @@ impl ComponentBuilder {
         ))
     }
 
+    /// Check if the Rust project is built with cargo-component, which it
+    /// declares with a `[package.metadata.component]` table in Cargo.toml
+    fn uses_cargo_component(&self) -> bool {
+        std::fs::read_to_string(self.project_path.join("Cargo.toml"))
+            .is_ok_and(|manifest| manifest.contains("[package.metadata.component]"))
+    }
+
     /// Build a TinyGo component using tinygo
     async fn build_tinygo_component(
         &self,
@@ -1076,6 +1111,153 @@ impl ComponentBuilder {
         }
     }
 
+    /// Build a Python component using componentize-py
+    async fn build_python_component(
+        &self,
+        config: &Config,
+        args: Option<&[String]>,
+    ) -> anyhow::Result<PathBuf> {
+        debug!("building python component with componentize-py");
+
+        // Get Python build configuration, use defaults if not specified
+        let python_config = config
+            .build
+            .as_ref()
+            .and_then(|b| b.python.as_ref())
+            .cloned()
+            .unwrap_or_default();
+
+        // Check if custom_command is specified - if so, use it instead of standard build
+        if let Some(custom_command) = &python_config.custom_command {
+            return self.execute_custom_command(custom_command).await;
+        }
+
+        // Create build directory if it doesn't exist
+        let build_dir = self.project_path.join("build");
+        if !build_dir.exists() {
+            fs::create_dir_all(&build_dir)
+                .await
+                .context("failed to create build directory")?;
+        }
+
+        let output_file = build_dir.join("output.wasm");
+
+        // WIT options come before the `componentize` subcommand
+        let mut python_args = Vec::new();
+        let wit_dir = self.get_wit_dir();
+        if wit_dir.exists() {
+            python_args.push("-d".to_string());
+            python_args.push(
+                wit_dir
+                    .strip_prefix(&self.project_path)
+                    .unwrap_or(&wit_dir)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+        if let Some(wit_world) = &python_config.wit_world {
+            python_args.push("-w".to_string());
+            python_args.push(wit_world.clone());
+        }
+
+        python_args.push("componentize".to_string());
+        python_args.push(python_config.module.clone());
+
+        // Add any additional build flags if configured
+        python_args.extend(python_config.build_flags.iter().cloned());
+
+        // Add any additional build flags provided via CLI
+        python_args.extend_from_slice(args.unwrap_or_default());
+
+        python_args.push("-o".to_string());
+        python_args.push("build/output.wasm".to_string());
+
+        debug!(python_args = ?python_args, "running componentize-py with args");
+
+        let output = self
+            .run_command_with_spinner(
+                Command::new("componentize-py")
+                    .args(&python_args)
+                    .current_dir(&self.project_path),
+                "componentize-py",
+                &python_args,
+            )
+            .await
+            .context("failed to execute componentize-py")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!(stderr = %stderr, "componentize-py failed");
+            bail!("Python build failed: {stderr}");
+        }
+
+        if !output_file.exists() {
+            bail!(
+                "componentize-py completed but no artifact found at: {}",
+                output_file.display()
+            );
+        }
+
+        debug!(component_path = %output_file.display(), "found component artifact");
+        Ok(output_file)
+    }
+
+    /// Turn a core module produced by a build into a component with
+    /// `wasm-tools component new`. Components are returned as is.
+    async fn componentize_core_module(
+        &self,
+        config: &Config,
+        path: PathBuf,
+    ) -> anyhow::Result<PathBuf> {
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read build artifact: {}", path.display()))?;
+        if !is_core_module(&bytes) {
+            return Ok(path);
+        }
+
+        debug!(path = %path.display(), "build produced a core module, creating component");
+        if !self.tool_exists("wasm-tools", "--version").await {
+            bail!(
+                "build produced a core module rather than a component at {}, install wasm-tools to turn it into a component",
+                path.display()
+            );
+        }
+
+        let output_file = path.with_extension("component.wasm");
+        let mut wasm_tools_args = vec![
+            "component".to_string(),
+            "new".to_string(),
+            path.to_string_lossy().to_string(),
+            "-o".to_string(),
+            output_file.to_string_lossy().to_string(),
+        ];
+        if let Some(adapter) = config.build.as_ref().and_then(|b| b.wasi_adapter.as_ref()) {
+            let adapter = self.project_path.join(adapter);
+            wasm_tools_args.push("--adapt".to_string());
+            wasm_tools_args.push(format!("wasi_snapshot_preview1={}", adapter.display()));
+        }
+
+        let output = self
+            .run_command_with_spinner(
+                Command::new("wasm-tools")
+                    .args(&wasm_tools_args)
+                    .current_dir(&self.project_path),
+                "wasm-tools",
+                &wasm_tools_args,
+            )
+            .await
+            .context("failed to execute wasm-tools component new")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!(stderr = %stderr, "wasm-tools component new failed");
+            bail!("failed to create component from core module: {stderr}");
+        }
+
+        Ok(output_file)
+    }
+
     /// Execute a custom build command, completely overriding standard build logic
     async fn execute_custom_command(&self, custom_command: &[String]) -> anyhow::Result<PathBuf> {
         let Some((command, args)) = custom_command.split_first() else {
@@ -1162,3 +1344,22 @@ impl ComponentBuilder {
         Ok(())
     }
 }
+
+/// Check if `bytes` are a core Wasm module rather than a component, which
+/// differ in the version and layer fields after the magic number
+fn is_core_module(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\0asm") && bytes.get(4..8) == Some(&[0x01, 0x00, 0x00, 0x00][..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_core_module() {
+        let module = wat::parse_str("(module)").unwrap();
+        assert!(is_core_module(&module));
+        assert!(!is_core_module(b"\0asm\x0d\x00\x01\x00"));
+        assert!(!is_core_module(b"not wasm"));
+    }
+}
@@ -640,12 +640,13 @@ fn create_workload(
             volume_mounts: vec![VolumeMount {
                 name: "dev".to_string(),
                 mount_path: "/tmp".to_string(),
-                read_only: false,
+                ..Default::default()
             }],
             ..Default::default()
         },
         pool_size: -1,
         max_invocations: -1,
+        ..Default::default()
     });
     components.extend(dev_register_components.into_iter().map(|bytes| Component {
         bytes,
@@ -673,6 +674,7 @@ fn create_workload(
                 local_path: volume_root.to_string_lossy().to_string(),
             }),
         }],
+        outbound: Default::default(),
     }
 }
 
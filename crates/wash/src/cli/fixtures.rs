@@ -0,0 +1,178 @@
+//! Building the Wasm fixtures of the test suites from the example components.
+//!
+//! The integration tests embed fixtures such as `tests/fixtures/http_counter.wasm`
+//! with `include_bytes!`. The fixtures built from source are listed in a
+//! manifest, `fixtures.toml` at the root of the repository, with the project
+//! each is built from and the directories it is copied to. `wash fixtures`
+//! builds the ones missing from any of their directories, so the tests run
+//! from a clean checkout.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use clap::Args;
+use figment::Figment;
+use figment::providers::{Format as _, Toml};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, instrument};
+
+use crate::cli::{CliCommand, CliContext, CommandOutput, component_build::build_component};
+
+/// A fixture built from a component project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    /// File name of the fixture, e.g. `http_counter.wasm`
+    pub name: String,
+    /// Directory of the project it is built from
+    pub source: PathBuf,
+    /// Directories it is copied to
+    pub outputs: Vec<PathBuf>,
+}
+
+/// The fixtures of a repository, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FixtureManifest {
+    pub fixtures: Vec<Fixture>,
+}
+
+impl FixtureManifest {
+    /// Loads a manifest, resolving its paths against the directory it is in.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut manifest: Self = Figment::from(Toml::file_exact(path))
+            .extract()
+            .with_context(|| format!("failed to load fixture manifest {}", path.display()))?;
+        let root = path.parent().unwrap_or(Path::new("."));
+        for fixture in &mut manifest.fixtures {
+            fixture.source = root.join(&fixture.source);
+            for output in &mut fixture.outputs {
+                *output = root.join(&*output);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Returns the fixtures to build, with the paths they are copied to.
+    /// Unless `force` is set, only fixtures missing from an output are built.
+    pub fn pending(&self, force: bool) -> Vec<(&Fixture, Vec<PathBuf>)> {
+        self.fixtures
+            .iter()
+            .filter_map(|fixture| {
+                let paths: Vec<PathBuf> = fixture
+                    .outputs
+                    .iter()
+                    .map(|output| output.join(&fixture.name))
+                    .collect();
+                (force || paths.iter().any(|path| !path.exists())).then_some((fixture, paths))
+            })
+            .collect()
+    }
+}
+
+/// Build the test fixtures missing from a checkout
+#[derive(Args, Debug, Clone)]
+pub struct FixturesCommand {
+    /// The manifest listing the fixtures and the projects they are built from
+    #[clap(long = "manifest", default_value = "fixtures.toml")]
+    pub manifest: PathBuf,
+
+    /// Rebuild every fixture, including those that exist
+    #[clap(long = "force", default_value_t = false)]
+    pub force: bool,
+}
+
+impl CliCommand for FixturesCommand {
+    #[instrument(level = "debug", skip_all, name = "fixtures")]
+    async fn handle(&self, ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        let manifest = FixtureManifest::load(&self.manifest)?;
+        let pending = manifest.pending(self.force);
+
+        let mut built = Vec::new();
+        for (fixture, paths) in pending {
+            info!(name = fixture.name, source = ?fixture.source, "building fixture");
+            let config = ctx
+                .ensure_config(Some(&fixture.source))
+                .await
+                .with_context(|| format!("failed to load config of {}", fixture.name))?;
+            let result = build_component(&fixture.source, ctx, &config, None)
+                .await
+                .with_context(|| format!("failed to build fixture {}", fixture.name))?;
+            for path in paths {
+                if let Some(dir) = path.parent() {
+                    tokio::fs::create_dir_all(dir)
+                        .await
+                        .with_context(|| format!("failed to create {}", dir.display()))?;
+                }
+                tokio::fs::copy(&result.component_path, &path)
+                    .await
+                    .with_context(|| format!("failed to copy fixture to {}", path.display()))?;
+            }
+            built.push(fixture.name.clone());
+        }
+
+        let message = if built.is_empty() {
+            "All fixtures are up to date".to_string()
+        } else {
+            format!("Built {} fixture(s): {}", built.len(), built.join(", "))
+        };
+        Ok(CommandOutput::ok(
+            message,
+            Some(json!({
+                "built": built,
+                "total": manifest.fixtures.len(),
+            })),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[fixtures]]
+name = "http_counter.wasm"
+source = "examples/http-counter"
+outputs = ["tests/fixtures", "crates/wash-runtime/tests/fixtures"]
+
+[[fixtures]]
+name = "cron_service.wasm"
+source = "examples/cron-service"
+outputs = ["tests/fixtures"]
+"#,
+        )
+        .unwrap();
+        let manifest = FixtureManifest::load(&path).unwrap();
+        assert_eq!(
+            manifest.fixtures[0].source,
+            dir.path().join("examples/http-counter")
+        );
+        assert_eq!(manifest.pending(false).len(), 2);
+
+        // A fixture is built until it exists in every output
+        let fixtures = dir.path().join("tests/fixtures");
+        std::fs::create_dir_all(&fixtures).unwrap();
+        std::fs::write(fixtures.join("http_counter.wasm"), b"").unwrap();
+        std::fs::write(fixtures.join("cron_service.wasm"), b"").unwrap();
+        let pending = manifest.pending(false);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0.name, "http_counter.wasm");
+        assert_eq!(
+            pending[0].1,
+            vec![
+                fixtures.join("http_counter.wasm"),
+                dir.path()
+                    .join("crates/wash-runtime/tests/fixtures/http_counter.wasm")
+            ]
+        );
+        assert_eq!(manifest.pending(true).len(), 2);
+
+        assert!(FixtureManifest::load(&dir.path().join("missing.toml")).is_err());
+    }
+}
@@ -1,12 +1,46 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Context as _;
 use clap::Args;
 use tracing::info;
+use wash_runtime::engine::Engine;
+use wash_runtime::engine::cache::{ComponentCache, DEFAULT_MAX_SIZE_MB};
+use wash_runtime::engine::cgroup::{Cgroups, DEFAULT_HOST_MEMORY_RESERVE_MB};
+use wash_runtime::engine::pool::{CpuSet, ExecutionPoolConfig};
+#[cfg(target_os = "linux")]
+use wash_runtime::host::attestation::ConfigfsTsm;
+use wash_runtime::host::devices::Device;
+use wash_runtime::host::events::LifecycleEventType;
+use wash_runtime::host::http::{
+    AccessLog, AccessLogConfig, ConnectionPoolConfig, EgressDefault, ErrorFormat, IpCidr,
+    RequestLimits, Resolver, ResolverConfig, SniFiles,
+};
+use wash_runtime::host::idempotency::{
+    DEFAULT_IDEMPOTENCY_TTL, IdempotencyStore, KeyvalueIdempotencyStore,
+};
+use wash_runtime::host::limits::HostLimits;
+use wash_runtime::host::metering::{CsvUsageSink, DEFAULT_EXPORT_INTERVAL, Metering};
+use wash_runtime::host::otlp::OtlpMetrics;
+use wash_runtime::host::otlp_tracing::OtlpTracing;
+use wash_runtime::host::tls::{TlsCryptoProvider, TlsPolicy, TlsVersion};
+use wash_runtime::host::webhook::Webhook;
+use wash_runtime::plugin::process::{self, ProcessPlugin};
 #[cfg(not(target_os = "windows"))]
 use wash_runtime::plugin::wasi_webgpu::WasiWebGpu;
+use wash_runtime::washlet::DEFAULT_DRAIN_TIMEOUT;
+use wash_runtime::washlet::auth::{ALL_NAMESPACES, ApiTokens, ApiVerb, TokenClaims};
+use wash_runtime::washlet::plugins::dead_letter::{DeadLetterQueue, DeadLetterSink};
 
 use crate::cli::{CliCommand, CliContext, CommandOutput};
+use crate::host_config::{
+    AttestationConfig, AuthConfig, CgroupsConfig, ComponentCacheConfig, GrpcConfig, HostConfig,
+    HostConfigFile, HttpConfig, MeteringConfig, MetricsConfig, OtlpMetricsConfig,
+    OtlpTracingConfig, PluginsConfig, SecretsConfig, TlsConfig, WebhooksConfig,
+};
+#[cfg(target_os = "linux")]
+use crate::service::systemd::Notifier;
+#[cfg(target_os = "windows")]
+use crate::service::windows::Service;
 
 #[derive(Debug, Clone, Args)]
 pub struct HostCommand {
@@ -26,35 +60,632 @@ pub struct HostCommand {
     #[clap(long = "host-name")]
     pub host_name: Option<String>,
 
-    /// The address on which the HTTP server will listen
+    /// The address on which the HTTP server will listen. May be given more
+    /// than once, e.g. once per address family. `[::]:<port>` also accepts
+    /// IPv4 connections unless an IPv4 address with the same port is given.
     #[clap(long = "http-addr")]
-    pub http_addr: Option<SocketAddr>,
+    pub http_addrs: Vec<SocketAddr>,
+
+    /// Expect a PROXY protocol v1/v2 header on every HTTP connection, e.g. behind an L4 load balancer
+    #[clap(long = "http-proxy-protocol", default_value_t = false)]
+    pub http_proxy_protocol: bool,
+
+    /// Networks (CIDR or address) whose X-Forwarded-For and Forwarded headers are trusted
+    #[clap(long = "http-trusted-proxy")]
+    pub http_trusted_proxies: Vec<IpCidr>,
+
+    /// Serve HTTPS with the certificate chain in this PEM file, read again on
+    /// reload
+    #[clap(long = "http-tls-cert", requires = "http_tls_key")]
+    pub http_tls_cert: Option<PathBuf>,
+
+    /// PEM file of the private key of `--http-tls-cert`
+    #[clap(long = "http-tls-key", requires = "http_tls_cert")]
+    pub http_tls_key: Option<PathBuf>,
+
+    /// Require HTTPS clients to present a certificate issued by a CA in this PEM file
+    #[clap(long = "http-tls-ca", requires = "http_tls_cert")]
+    pub http_tls_ca: Option<PathBuf>,
+
+    /// Serve HTTPS clients asking for a host name another certificate, as
+    /// HOST=CERT,KEY, e.g. `*.example.com=wildcard.crt,wildcard.key`
+    #[clap(long = "http-tls-sni", requires = "http_tls_cert")]
+    pub http_tls_sni: Vec<SniFiles>,
+
+    /// Link HTTP requests taking at least this many milliseconds to their trace in request metrics
+    #[clap(long = "http-slow-request-ms", default_value_t = 1000)]
+    pub http_slow_request_ms: u64,
+
+    /// Body of the HTTP responses the host answers with itself, e.g. when no
+    /// route matches or a component fails: empty, json (RFC 9457 problem
+    /// details) or html
+    #[clap(long = "http-error-format", default_value = "empty")]
+    pub http_error_format: ErrorFormat,
+
+    /// HTML template of html error responses, in which `{{status}}`,
+    /// `{{title}}` and `{{request_id}}` are replaced
+    #[clap(long = "http-error-template")]
+    pub http_error_template: Option<PathBuf>,
+
+    /// Include the id of the request, from its X-Request-Id header or
+    /// generated, in HTTP error responses
+    #[clap(long = "http-error-request-id", default_value_t = false)]
+    pub http_error_request_id: bool,
+
+    /// Translate gRPC-web and Connect requests of browsers to gRPC for the
+    /// components serving them
+    #[clap(long = "http-grpc-web", default_value_t = false)]
+    pub http_grpc_web: bool,
+
+    /// Path, e.g. /healthz, on which the HTTP server answers probes of
+    /// orchestrators with the health of the host and its workloads
+    #[clap(long = "http-health-path")]
+    pub http_health_path: Option<String>,
+    /// Directory large HTTP request bodies of routes with body spooling are
+    /// written to, instead of the system temporary directory
+    #[clap(long = "http-spool-dir")]
+    pub http_spool_dir: Option<PathBuf>,
+
+    /// Log every HTTP request to stdout as JSON lines
+    #[clap(long = "http-access-log-stdout", default_value_t = false)]
+    pub http_access_log_stdout: bool,
+
+    /// Log every HTTP request as a tracing event with the wash::access_log target
+    #[clap(long = "http-access-log-tracing", default_value_t = false)]
+    pub http_access_log_tracing: bool,
+
+    /// File every HTTP request is logged to as JSON lines, rotated at 100 MiB
+    #[clap(long = "http-access-log-file")]
+    pub http_access_log_file: Option<PathBuf>,
+
+    /// DNS server to resolve the hosts of outgoing HTTP requests with, instead of the system resolver. Can be repeated.
+    #[clap(long = "dns-server")]
+    pub dns_servers: Vec<SocketAddr>,
+
+    /// URL of a DNS over HTTPS server to resolve the hosts of outgoing HTTP requests with
+    #[clap(long = "dns-over-https", conflicts_with = "dns_servers")]
+    pub dns_over_https: Option<String>,
+
+    /// Network that the hosts of outgoing HTTP requests may not resolve to, e.g. `169.254.0.0/16`. Can be repeated.
+    #[clap(long = "egress-denied-network")]
+    pub egress_denied_networks: Vec<IpCidr>,
+
+    /// Whether components that don't list the hosts they may send outgoing
+    /// HTTP requests to may send them to every host, allow, or to none, deny
+    #[clap(long = "egress-default", default_value = "allow")]
+    pub egress_default: EgressDefault,
+
+    /// Connections outgoing HTTP requests of components may have open at once
+    /// to the same host. Requests beyond it wait for a connection.
+    #[clap(long = "outgoing-max-connections-per-host")]
+    pub outgoing_max_connections_per_host: Option<usize>,
+
+    /// Longest time, in milliseconds, outgoing HTTP requests of components may
+    /// take to connect
+    #[clap(long = "outgoing-connect-timeout-ms")]
+    pub outgoing_connect_timeout_ms: Option<u64>,
+
+    /// Longest time, in milliseconds, outgoing HTTP requests of components
+    /// wait for the first byte of a response and between its bytes
+    #[clap(long = "outgoing-read-timeout-ms")]
+    pub outgoing_read_timeout_ms: Option<u64>,
+
+    /// Run the guests of components whose `execution_pool` config is NAME on
+    /// threads pinned to CPUS, e.g. `latency=8-15`. Append `@NODE` to prefer
+    /// memory of that NUMA node, or give only `@NODE` to use its CPUs. Can be
+    /// repeated.
+    #[clap(long = "execution-pool", value_name = "NAME=CPUS[@NODE]", value_parser = parse_execution_pool)]
+    pub execution_pools: Vec<(String, ExecutionPoolConfig)>,
+
+    /// Cgroup v2 directory delegated to the host, e.g. with systemd's
+    /// `Delegate=yes`. The kernel then enforces the `cpu_limit` of components
+    /// and caps the host's memory at what its workloads reserve. Linux only.
+    #[clap(long = "cgroup-root")]
+    pub cgroup_root: Option<PathBuf>,
+
+    /// Memory, in MiB, left to the host on top of what its workloads reserve
+    /// when its memory is capped with `--cgroup-root`
+    #[clap(long = "cgroup-host-memory-reserve-mb", default_value_t = DEFAULT_HOST_MEMORY_RESERVE_MB)]
+    pub cgroup_host_memory_reserve_mb: u64,
+
+    /// Keep compiled components in this directory, so identical components
+    /// load without compiling again, also after the host restarts. The
+    /// directory must only be writable by the host.
+    #[clap(long = "component-cache-dir")]
+    pub component_cache_dir: Option<PathBuf>,
+
+    /// Size, in MiB, the component cache is kept under by deleting the
+    /// components used least recently
+    #[clap(long = "component-cache-max-mb", default_value_t = DEFAULT_MAX_SIZE_MB)]
+    pub component_cache_max_mb: u64,
+
+    /// Publish messages and timers that components failed to handle on `<subject>.<workload_id>`
+    #[clap(long = "dead-letter-subject", conflicts_with = "dead_letter_bucket")]
+    pub dead_letter_subject: Option<String>,
+
+    /// Store messages and timers that components failed to handle in this object store bucket
+    #[clap(long = "dead-letter-bucket")]
+    pub dead_letter_bucket: Option<String>,
+
+    /// Prefix of dead letters stored in the dead letter bucket
+    #[clap(long = "dead-letter-prefix", default_value = "dead-letters")]
+    pub dead_letter_prefix: String,
+
+    /// Deduplicate HTTP requests and messages by idempotency key across hosts,
+    /// using this key-value bucket
+    #[clap(long = "idempotency-bucket")]
+    pub idempotency_bucket: Option<String>,
+
+    /// Start workloads importing interfaces no plugin of the host provides
+    /// when another host of the cluster provides them, forwarding their calls
+    /// to it
+    #[clap(long = "forward-calls", default_value_t = false)]
+    pub forward_calls: bool,
+
+    /// Directory to keep named volumes in. Defaults to a directory in the
+    /// system temporary directory.
+    #[clap(long = "volume-dir")]
+    pub volume_dir: Option<std::path::PathBuf>,
+
+    /// Device workloads can request, as CLASS=PATH or CLASS:NAME=PATH, e.g.
+    /// `gpu=/dev/dri/renderD128` or `serial:gps=/dev/ttyUSB0`. Can be repeated.
+    #[clap(long = "device", value_name = "CLASS[:NAME]=PATH")]
+    pub devices: Vec<Device>,
+
+    /// Restore the workloads in this snapshot file when the host starts, and
+    /// snapshot running workloads to it when the host stops
+    #[clap(long = "snapshot-file")]
+    pub snapshot_file: Option<PathBuf>,
+
+    /// Keep the state of the host in this file as its workloads and routes
+    /// change, and restore it when the host starts, e.g. after a crash
+    #[clap(long = "state-file")]
+    pub state_file: Option<PathBuf>,
+
+    /// How long, in seconds, the host drains when it stops, e.g. on SIGTERM:
+    /// it rejects new workloads and waits for the requests in flight. Zero
+    /// stops it right away.
+    #[clap(long = "drain-timeout-secs", default_value_t = DEFAULT_DRAIN_TIMEOUT.as_secs())]
+    pub drain_timeout_secs: u64,
+
+    /// Interrupt invocations of components running longer than this many
+    /// milliseconds, answering HTTP requests with a 504. Components override
+    /// it with the `invocation_timeout_ms` key of their local resources config.
+    #[clap(long = "invocation-timeout-ms")]
+    pub invocation_timeout_ms: Option<u64>,
+
+    /// Enforce the `cpu_limit` of components with fuel, this being the fuel a
+    /// whole CPU burns through per second. Ignored with --cgroup-root, where
+    /// the kernel enforces it.
+    #[clap(long = "cpu-fuel-rate")]
+    pub cpu_fuel_rate: Option<u64>,
+
+    /// Run as a Windows service started by the service control manager
+    #[cfg(target_os = "windows")]
+    #[clap(long = "windows-service", default_value_t = false)]
+    pub windows_service: bool,
+
+    /// POST workload lifecycle events and crash reports as JSON to this URL.
+    /// May be given more than once.
+    #[clap(long = "webhook-url")]
+    pub webhook_urls: Vec<String>,
+
+    /// Sign webhook requests with an HMAC-SHA256 of this secret
+    #[clap(
+        long = "webhook-secret",
+        env = "WASH_WEBHOOK_SECRET",
+        hide_env_values = true
+    )]
+    pub webhook_secret: Option<String>,
+
+    /// Only send these lifecycle events to webhooks, e.g. `service_crashed`.
+    /// May be given more than once. Defaults to all events.
+    #[clap(long = "webhook-event")]
+    pub webhook_events: Vec<LifecycleEventType>,
+
+    /// Push host and workload metrics over OTLP/gRPC to the collector at this
+    /// URL, e.g. `http://localhost:4317`
+    #[clap(long = "otlp-metrics-endpoint")]
+    pub otlp_metrics_endpoint: Option<String>,
+
+    /// How often, in seconds, metrics are pushed to the OTLP collector
+    #[clap(long = "otlp-metrics-interval", default_value_t = 60)]
+    pub otlp_metrics_interval_secs: u64,
+
+    /// Export traces of the HTTP requests the host serves over OTLP/gRPC to
+    /// the collector at this URL, e.g. `http://localhost:4317`
+    #[clap(long = "otlp-traces-endpoint")]
+    pub otlp_traces_endpoint: Option<String>,
+
+    /// Fraction of the traces starting at the host that are exported, between
+    /// 0 and 1. Requests continuing a trace follow its sampling decision.
+    #[clap(long = "otlp-traces-sampling-ratio", default_value_t = 1.0)]
+    pub otlp_traces_sampling_ratio: f64,
+
+    /// Serve host and workload metrics to Prometheus on `GET /metrics` at this
+    /// address, separate from the HTTP listeners of workloads. Can't be
+    /// combined with `--otlp-metrics-endpoint`.
+    #[clap(long = "metrics-addr")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Meter the duration, memory and egress of every invocation per
+    /// namespace, exported as `wasmcloud.usage.*` metrics
+    #[clap(long = "usage-metering", default_value_t = false)]
+    pub usage_metering: bool,
+
+    /// Also meter the fuel, a deterministic count of instructions, guests
+    /// consume. Makes guests run slower.
+    #[clap(long = "usage-fuel", default_value_t = false)]
+    pub usage_fuel: bool,
+
+    /// Append usage reports to this CSV file. Implies `--usage-metering`.
+    #[clap(long = "usage-csv")]
+    pub usage_csv: Option<PathBuf>,
+
+    /// POST usage reports as JSON to this URL, signed with the webhook
+    /// secret. May be given more than once. Implies `--usage-metering`.
+    #[clap(long = "usage-webhook-url")]
+    pub usage_webhook_urls: Vec<String>,
+
+    /// How often, in seconds, usage reports are exported
+    #[clap(long = "usage-export-interval", default_value_t = DEFAULT_EXPORT_INTERVAL.as_secs())]
+    pub usage_export_interval_secs: u64,
+
+    /// Require a token issued with this secret on every runtime API request,
+    /// see `wash token issue`
+    #[clap(
+        long = "api-token-secret",
+        env = "WASH_API_TOKEN_SECRET",
+        hide_env_values = true
+    )]
+    pub api_token_secret: Option<String>,
+
+    /// Also serve the runtime API over gRPC on this address, for control
+    /// planes and CLIs that can't reach NATS
+    #[clap(long = "grpc-addr")]
+    pub grpc_addr: Option<SocketAddr>,
+
+    /// PEM file of the certificate chain the gRPC API serves
+    #[clap(long = "grpc-tls-cert", requires = "grpc_tls_key")]
+    pub grpc_tls_cert: Option<PathBuf>,
+
+    /// PEM file of the private key of `--grpc-tls-cert`
+    #[clap(long = "grpc-tls-key", requires = "grpc_tls_cert")]
+    pub grpc_tls_key: Option<PathBuf>,
+
+    /// PEM file of the CAs gRPC clients must present a certificate of
+    /// (mutual TLS). Without it, anyone reaching the gRPC API can manage
+    /// workloads.
+    #[clap(long = "grpc-tls-client-ca", requires = "grpc_tls_cert")]
+    pub grpc_tls_client_ca: Option<PathBuf>,
+
+    /// Directory of files workloads can reference as secrets, e.g.
+    /// `${secret:file:NAME}` for the file `NAME`
+    #[clap(long = "secrets-dir")]
+    pub secrets_dir: Option<PathBuf>,
+
+    /// Prefix of the environment variables of the host workloads can
+    /// reference as secrets, e.g. `${secret:env:NAME}` for `<prefix>NAME`
+    #[clap(long = "secrets-env-prefix")]
+    pub secrets_env_prefix: Option<String>,
+
+    /// Environment variable of the host workloads can reference with
+    /// `${env:NAME}`, or a prefix of them ending in `*`. Can be repeated.
+    #[clap(long = "env-allow")]
+    pub env_allowlist: Vec<String>,
+
+    /// File keeping the identity key attestation reports are signed with.
+    /// Defaults to a file in the wash data directory.
+    #[clap(long = "identity-file")]
+    pub identity_file: Option<std::path::PathBuf>,
+
+    /// Include evidence from the trusted execution environment (configfs-tsm)
+    /// the host runs in in attestation reports
+    #[cfg(target_os = "linux")]
+    #[clap(long = "tee-evidence", default_value_t = false)]
+    pub tee_evidence: bool,
+
+    /// Crypto provider used for HTTP listeners and outgoing HTTP requests of
+    /// components: aws-lc-rs, fips or ring
+    #[clap(long = "tls-crypto-provider", default_value_t = TlsCryptoProvider::AwsLcRs)]
+    pub tls_crypto_provider: TlsCryptoProvider,
+
+    /// Minimum TLS version for HTTP listeners and outgoing HTTP requests of
+    /// components: 1.2 or 1.3
+    #[clap(long = "tls-min-version", default_value_t = TlsVersion::Tls12)]
+    pub tls_min_version: TlsVersion,
+
+    /// Directory of manifests of plugin processes to start with the host.
+    /// No plugin processes are started unless it is set.
+    #[clap(long = "host-plugin-dir")]
+    pub host_plugin_dir: Option<PathBuf>,
+
+    /// Reject new workloads while the host runs this many
+    #[clap(long = "max-workloads")]
+    pub max_workloads: Option<usize>,
+
+    /// Reject new workloads while their components reserve this many instances
+    #[clap(long = "max-instances")]
+    pub max_instances: Option<usize>,
+
+    /// Reject new workloads while their components reserve this much guest
+    /// memory, in MiB. Components then need a memory limit.
+    #[clap(long = "max-memory-mb")]
+    pub max_memory_mb: Option<u64>,
+
+    /// Refuse HTTP connections while this many are open
+    #[clap(long = "max-connections")]
+    pub max_connections: Option<usize>,
 
     /// Enable WASI WebGPU support
     #[cfg(not(target_os = "windows"))]
     #[clap(long = "wasi-webgpu", default_value_t = false)]
     pub wasi_webgpu: bool,
+
+    /// TOML, YAML or JSON file with the host configuration. Its settings take
+    /// precedence over flags. Limits and the API token secret are reloaded
+    /// from it on SIGHUP.
+    #[clap(long = "config")]
+    pub config: Option<PathBuf>,
+}
+
+impl HostCommand {
+    /// Returns the configuration given by flags
+    fn flags(&self) -> HostConfig {
+        let mut limits = HostLimits::default();
+        if let Some(max_workloads) = self.max_workloads {
+            limits = limits.with_max_workloads(max_workloads);
+        }
+        if let Some(max_instances) = self.max_instances {
+            limits = limits.with_max_instances(max_instances);
+        }
+        if let Some(max_memory_mb) = self.max_memory_mb {
+            limits = limits.with_max_memory_mb(max_memory_mb);
+        }
+        if let Some(max_connections) = self.max_connections {
+            limits = limits.with_max_connections(max_connections);
+        }
+        HostConfig {
+            host_group: self.host_group.clone(),
+            host_name: self.host_name.clone(),
+            labels: Default::default(),
+            scheduler_nats_url: self.scheduler_nats_url.clone(),
+            data_nats_url: self.data_nats_url.clone(),
+            volume_dir: self.volume_dir.clone(),
+            devices: self.devices.clone(),
+            snapshot_file: self.snapshot_file.clone(),
+            state_file: self.state_file.clone(),
+            drain_timeout_secs: self.drain_timeout_secs,
+            invocation_timeout_ms: self.invocation_timeout_ms,
+            cpu_fuel_rate: self.cpu_fuel_rate,
+            execution_pools: self.execution_pools.iter().cloned().collect(),
+            cgroups: CgroupsConfig {
+                root: self.cgroup_root.clone(),
+                host_memory_reserve_mb: self.cgroup_host_memory_reserve_mb,
+            },
+            component_cache: ComponentCacheConfig {
+                dir: self.component_cache_dir.clone(),
+                max_size_mb: self.component_cache_max_mb,
+            },
+            http: HttpConfig {
+                addr: self.http_addrs.first().copied(),
+                extra_addrs: self.http_addrs.iter().skip(1).copied().collect(),
+                proxy_protocol: self.http_proxy_protocol,
+                trusted_proxies: self.http_trusted_proxies.clone(),
+                slow_request_ms: self.http_slow_request_ms,
+                resolver: {
+                    let resolver = ResolverConfig::default()
+                        .with_nameservers(self.dns_servers.iter().copied())
+                        .with_denied_networks(self.egress_denied_networks.iter().cloned());
+                    match &self.dns_over_https {
+                        Some(url) => resolver.with_doh_url(url),
+                        None => resolver,
+                    }
+                },
+                egress_default: self.egress_default,
+                connection_pool: {
+                    let mut pool = ConnectionPoolConfig::default();
+                    pool.max_connections_per_host = self.outgoing_max_connections_per_host;
+                    pool.connect_timeout_ms = self.outgoing_connect_timeout_ms;
+                    pool.read_timeout_ms = self.outgoing_read_timeout_ms;
+                    pool
+                },
+                tls_cert: self.http_tls_cert.clone(),
+                tls_key: self.http_tls_key.clone(),
+                tls_ca: self.http_tls_ca.clone(),
+                tls_sni: self.http_tls_sni.clone(),
+                error_format: self.http_error_format,
+                error_template: self.http_error_template.clone(),
+                error_request_id: self.http_error_request_id,
+                grpc_web: self.http_grpc_web,
+                health_path: self.http_health_path.clone(),
+                spool_dir: self.http_spool_dir.clone(),
+                signing: Vec::new(),
+                outbound: Vec::new(),
+                request_limits: RequestLimits::default(),
+                access_log: AccessLogConfig {
+                    stdout: self.http_access_log_stdout,
+                    tracing: self.http_access_log_tracing,
+                    file: self.http_access_log_file.clone(),
+                    ..Default::default()
+                },
+            },
+            plugins: PluginsConfig {
+                dead_letter_subject: self.dead_letter_subject.clone(),
+                dead_letter_bucket: self.dead_letter_bucket.clone(),
+                dead_letter_prefix: self.dead_letter_prefix.clone(),
+                idempotency_bucket: self.idempotency_bucket.clone(),
+                forward_calls: self.forward_calls,
+                #[cfg(not(target_os = "windows"))]
+                wasi_webgpu: self.wasi_webgpu,
+                #[cfg(target_os = "windows")]
+                wasi_webgpu: false,
+                dir: self.host_plugin_dir.clone(),
+                ..Default::default()
+            },
+            webhooks: WebhooksConfig {
+                urls: self.webhook_urls.clone(),
+                secret: self.webhook_secret.clone(),
+                events: self.webhook_events.clone(),
+            },
+            otlp_metrics: OtlpMetricsConfig {
+                endpoint: self.otlp_metrics_endpoint.clone(),
+                interval_secs: self.otlp_metrics_interval_secs,
+            },
+            metrics: MetricsConfig {
+                addr: self.metrics_addr,
+            },
+            otlp_tracing: OtlpTracingConfig {
+                endpoint: self.otlp_traces_endpoint.clone(),
+                sampling_ratio: self.otlp_traces_sampling_ratio,
+            },
+            metering: MeteringConfig {
+                enabled: self.usage_metering,
+                fuel: self.usage_fuel,
+                csv_file: self.usage_csv.clone(),
+                webhook_urls: self.usage_webhook_urls.clone(),
+                interval_secs: self.usage_export_interval_secs,
+            },
+            tls: TlsConfig {
+                crypto_provider: self.tls_crypto_provider,
+                min_version: self.tls_min_version,
+            },
+            auth: AuthConfig {
+                api_token_secret: self.api_token_secret.clone(),
+            },
+            grpc: GrpcConfig {
+                addr: self.grpc_addr,
+                tls_cert: self.grpc_tls_cert.clone(),
+                tls_key: self.grpc_tls_key.clone(),
+                tls_client_ca: self.grpc_tls_client_ca.clone(),
+            },
+            secrets: SecretsConfig {
+                env_prefix: self.secrets_env_prefix.clone(),
+                dir: self.secrets_dir.clone(),
+                env_allowlist: self.env_allowlist.clone(),
+                ..Default::default()
+            },
+            attestation: AttestationConfig {
+                identity_file: self.identity_file.clone(),
+                #[cfg(target_os = "linux")]
+                tee_evidence: self.tee_evidence,
+                #[cfg(not(target_os = "linux"))]
+                tee_evidence: false,
+            },
+            limits,
+        }
+    }
 }
 
 impl CliCommand for HostCommand {
-    async fn handle(&self, _ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+    async fn handle(&self, ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        // The service control manager only waits briefly for services to connect
+        #[cfg(target_os = "windows")]
+        let windows_service = self.windows_service.then(Service::start).transpose()?;
+
+        let flags = self.flags();
+        let config = match &self.config {
+            Some(path) => HostConfig::load(&flags, path)?,
+            None => flags.clone(),
+        };
+
+        // Must be installed before any TLS configuration is built
+        TlsPolicy::default()
+            .with_provider(config.tls.crypto_provider)
+            .with_min_version(config.tls.min_version)
+            .install()
+            .context("failed to set up TLS")?;
+
+        anyhow::ensure!(
+            config.otlp_metrics.endpoint.is_none() || config.metrics.addr.is_none(),
+            "metrics can be pushed over OTLP or served to Prometheus, not both"
+        );
+
+        // Installed before the host, instruments created before it would not be exported
+        let meter_provider = match &config.otlp_metrics.endpoint {
+            Some(endpoint) => Some(
+                OtlpMetrics::new(endpoint)
+                    .with_interval(std::time::Duration::from_secs(
+                        config.otlp_metrics.interval_secs,
+                    ))
+                    .install(wash_runtime::washlet::resource_builder().build())
+                    .context("failed to set up OTLP metrics")?,
+            ),
+            None => None,
+        };
+
         let scheduler_nats_client =
-            wash_runtime::washlet::connect_nats(self.scheduler_nats_url.clone(), None)
+            wash_runtime::washlet::connect_nats(config.scheduler_nats_url.clone(), None)
                 .await
                 .context("failed to connect to NATS Scheduler URL")?;
 
         let data_nats_client =
-            wash_runtime::washlet::connect_nats(self.data_nats_url.clone(), None)
+            wash_runtime::washlet::connect_nats(config.data_nats_url.clone(), None)
                 .await
                 .context("failed to connect to NATS")?;
         let data_nats_client = Arc::new(data_nats_client);
 
+        let dead_letter_sink = match (
+            &config.plugins.dead_letter_subject,
+            &config.plugins.dead_letter_bucket,
+        ) {
+            (Some(subject), _) => Some(DeadLetterSink::Subject(subject.clone())),
+            (None, Some(bucket)) => Some(DeadLetterSink::Bucket {
+                bucket: bucket.clone(),
+                prefix: config.plugins.dead_letter_prefix.clone(),
+            }),
+            (None, None) => None,
+        };
+        let dead_letters = dead_letter_sink
+            .map(|sink| Arc::new(DeadLetterQueue::new(data_nats_client.clone(), sink)));
+
+        let mut messaging =
+            wash_runtime::washlet::plugins::wasmcloud_messaging::WasmcloudMessaging::new(
+                data_nats_client.clone(),
+            );
+        let mut timers =
+            wash_runtime::washlet::plugins::wasmcloud_timers::WasmcloudTimers::default();
+        if let Some(dead_letters) = dead_letters {
+            messaging = messaging.with_dead_letters(dead_letters.clone());
+            timers = timers.with_dead_letters(dead_letters);
+        }
+
+        let idempotency: Option<Arc<dyn IdempotencyStore>> =
+            match &config.plugins.idempotency_bucket {
+                Some(bucket) => Some(Arc::new(
+                    KeyvalueIdempotencyStore::new(
+                        data_nats_client.clone(),
+                        bucket,
+                        DEFAULT_IDEMPOTENCY_TTL,
+                    )
+                    .await?,
+                )),
+                None => None,
+            };
+        if let Some(idempotency) = &idempotency {
+            messaging = messaging.with_idempotency(idempotency.clone());
+        }
+
+        let secret_store = config.secrets.store()?;
+        let env_allowlist = config.secrets.env_allowlist();
+        let mut wasi_config = wash_runtime::washlet::plugins::wasi_config::WasiConfig::default()
+            .with_env_allowlist(env_allowlist.clone())
+            .with_overrides(
+                config
+                    .plugins
+                    .config_overrides
+                    .clone()
+                    .into_iter()
+                    .collect(),
+            );
+        if let Some(secret_store) = &secret_store {
+            wasi_config = wasi_config.with_secrets(secret_store.clone());
+        }
+
         let mut cluster_host_builder = wash_runtime::washlet::ClusterHostBuilder::default()
             .with_nats_client(Arc::new(scheduler_nats_client))
-            .with_host_group(self.host_group.clone())
-            .with_plugin(Arc::new(
-                wash_runtime::washlet::plugins::wasi_config::WasiConfig::default(),
-            ))?
+            .with_host_group(config.host_group.clone())
+            .with_plugin(Arc::new(wasi_config))?
             .with_plugin(Arc::new(
                 wash_runtime::washlet::plugins::wasi_logging::TracingLogging::default(),
             ))?
@@ -63,32 +694,279 @@ impl CliCommand for HostCommand {
                     data_nats_client.clone(),
                 ),
             ))?
+            .with_plugin(Arc::new(messaging))?
             .with_plugin(Arc::new(
-                wash_runtime::washlet::plugins::wasmcloud_messaging::WasmcloudMessaging::new(
-                    data_nats_client.clone(),
-                ),
+                wash_runtime::plugin::wasi_messaging::WasiMessaging::new(data_nats_client.clone()),
             ))?
             .with_plugin(Arc::new(
                 wash_runtime::washlet::plugins::wasi_keyvalue::WasiKeyvalue::new(
                     data_nats_client.clone(),
                 ),
-            ))?;
+            ))?
+            .with_plugin(Arc::new(timers))?
+            .with_plugin(Arc::new(
+                wash_runtime::plugin::wash_metrics::WashMetrics::default(),
+            ))?
+            .with_plugin(Arc::new(config.plugins.sockets(&config.limits)?))?;
+
+        // Plugins installed on this host without rebuilding wash, only from a
+        // directory the operator chose
+        if let Some(plugin_dir) = &config.plugins.dir {
+            for command in process::discover(plugin_dir)? {
+                let plugin = ProcessPlugin::spawn(command)
+                    .await
+                    .context("failed to start plugin process")?;
+                cluster_host_builder = cluster_host_builder.with_plugin(Arc::new(plugin))?;
+            }
+        }
+
+        if let Some(secret_store) = secret_store {
+            cluster_host_builder = cluster_host_builder.with_secret_store(secret_store);
+        }
+        cluster_host_builder = cluster_host_builder.with_env_allowlist(env_allowlist);
+
+        for (key, value) in &config.labels {
+            cluster_host_builder = cluster_host_builder.with_label(key, value);
+        }
+        cluster_host_builder = cluster_host_builder.with_limits(config.limits.clone());
+        if let Some(path) = &self.config {
+            cluster_host_builder = cluster_host_builder
+                .with_config_source(Arc::new(HostConfigFile::new(path, flags, config.clone())));
+        }
 
-        if let Some(host_name) = &self.host_name {
+        if let Some(host_name) = &config.host_name {
             cluster_host_builder = cluster_host_builder.with_host_name(host_name);
         }
 
-        if let Some(addr) = self.http_addr {
-            tracing::info!(addr = ?addr, "Starting HTTP server for components");
+        for device in &config.devices {
+            cluster_host_builder = cluster_host_builder.with_device(device.clone());
+        }
+
+        if let Some(volume_dir) = &config.volume_dir {
+            cluster_host_builder = cluster_host_builder.with_volume_root(volume_dir);
+        }
+
+        if let Some(snapshot_file) = &config.snapshot_file {
+            cluster_host_builder = cluster_host_builder.with_snapshot_file(snapshot_file);
+        }
+
+        if let Some(state_file) = &config.state_file {
+            cluster_host_builder = cluster_host_builder.with_state_file(state_file);
+        }
+
+        if let Some(endpoint) = &config.otlp_tracing.endpoint {
+            info!(endpoint, "Exporting traces over OTLP");
+            cluster_host_builder = cluster_host_builder.with_tracing(
+                OtlpTracing::new(endpoint).with_sampling_ratio(config.otlp_tracing.sampling_ratio),
+            );
+        }
+
+        if let Some(addr) = config.metrics.addr {
+            info!(%addr, "Serving metrics to Prometheus");
+            cluster_host_builder = cluster_host_builder.with_metrics(addr);
+        }
+        let drain_timeout = Duration::from_secs(config.drain_timeout_secs);
+        cluster_host_builder = cluster_host_builder.with_drain_timeout(drain_timeout);
+
+        if !config.execution_pools.is_empty()
+            || config.cgroups.root.is_some()
+            || config.metering.is_enabled()
+            || config.component_cache.dir.is_some()
+            || config.invocation_timeout_ms.is_some()
+            || config.cpu_fuel_rate.is_some()
+            || config.crash_loop.policy().is_some()
+        {
+            let mut engine = Engine::builder();
+            if let Some(ms) = config.invocation_timeout_ms {
+                info!(
+                    timeout_ms = ms,
+                    "Interrupting invocations after their timeout"
+                );
+                engine = engine.with_invocation_timeout(Duration::from_millis(ms));
+            }
+            if let Some(rate) = config.cpu_fuel_rate {
+                info!(rate, "Enforcing component CPU limits with fuel");
+                engine = engine.with_cpu_fuel_rate(rate);
+            }
+            if let Some(policy) = config.crash_loop.policy() {
+                info!(
+                    threshold = policy.threshold,
+                    stop_after = ?policy.stop_after,
+                    "Backing off crash looping components"
+                );
+                engine = engine.with_crash_loop_policy(policy);
+            }
+            for (name, pool) in &config.execution_pools {
+                info!(pool = name, cpus = %pool.cpus, numa_node = ?pool.numa_node, "Starting execution pool");
+                engine = engine.with_execution_pool(name, pool.clone());
+            }
+            if let Some(root) = &config.cgroups.root {
+                info!(root = %root.display(), "Enforcing workload limits with cgroups");
+                let cgroups = Cgroups::new(root)
+                    .context("failed to set up cgroups")?
+                    .with_host_memory_reserve_mb(config.cgroups.host_memory_reserve_mb);
+                engine = engine.with_cgroups(cgroups);
+            }
+            if let Some(dir) = &config.component_cache.dir {
+                info!(dir = %dir.display(), "Caching compiled components");
+                engine = engine.with_component_cache(
+                    ComponentCache::new(dir).with_max_size_mb(config.component_cache.max_size_mb),
+                );
+            }
+            if config.metering.is_enabled() {
+                info!(fuel = config.metering.fuel, "Metering invocation usage");
+                let mut metering = Metering::new()
+                    .with_fuel(config.metering.fuel)
+                    .with_interval(Duration::from_secs(config.metering.interval_secs));
+                if let Some(path) = &config.metering.csv_file {
+                    metering = metering.with_sink(Arc::new(CsvUsageSink::new(path)));
+                }
+                for url in &config.metering.webhook_urls {
+                    let mut webhook = Webhook::new(url);
+                    if let Some(secret) = &config.webhooks.secret {
+                        webhook = webhook.with_secret(secret.as_bytes());
+                    }
+                    metering = metering.with_sink(Arc::new(webhook));
+                }
+                engine = engine.with_metering(metering);
+            }
+            cluster_host_builder =
+                cluster_host_builder.with_engine(engine.build().context("failed to build engine")?);
+        }
+
+        if let Some(secret) = &config.auth.api_token_secret {
+            cluster_host_builder =
+                cluster_host_builder.with_api_tokens(ApiTokens::new(secret.as_bytes()));
+        }
+
+        if let Some(grpc_api) = config.grpc.api()? {
+            cluster_host_builder = cluster_host_builder
+                .with_grpc_api(grpc_api.with_client_identities(config.auth.identities()));
+        }
+
+        if config.plugins.forward_calls {
+            // Peers sharing the secret accept forwarded calls with this token
+            let token = config
+                .auth
+                .api_token_secret
+                .as_ref()
+                .map(|secret| {
+                    ApiTokens::new(secret.as_bytes()).issue(
+                        &TokenClaims::new("call-forwarding")
+                            .with_namespace(ALL_NAMESPACES)
+                            .with_verb(ApiVerb::Deploy),
+                    )
+                })
+                .transpose()
+                .context("failed to issue call forwarding token")?;
+            cluster_host_builder = cluster_host_builder.with_call_forwarding(token);
+        }
+
+        let identity_file = config
+            .attestation
+            .identity_file
+            .clone()
+            .unwrap_or_else(|| ctx.data_dir().join("host-identity.pk8"));
+        cluster_host_builder = cluster_host_builder
+            .with_identity_file(identity_file)
+            .with_policy(
+                "api_tokens",
+                if config.auth.api_token_secret.is_some() {
+                    "required"
+                } else {
+                    "disabled"
+                },
+            )
+            .with_policy(
+                "http_proxy_protocol",
+                config.http.proxy_protocol.to_string(),
+            )
+            .with_policy(
+                "tls_crypto_provider",
+                config.tls.crypto_provider.to_string(),
+            )
+            .with_policy("tls_min_version", config.tls.min_version.to_string())
+            .with_policy(
+                "http_trusted_proxies",
+                config
+                    .http
+                    .trusted_proxies
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        #[cfg(target_os = "linux")]
+        if config.attestation.tee_evidence {
+            cluster_host_builder =
+                cluster_host_builder.with_tee_evidence(Arc::new(ConfigfsTsm::default()));
+        }
+
+        for url in &config.webhooks.urls {
+            let mut webhook = Webhook::new(url);
+            if let Some(secret) = &config.webhooks.secret {
+                webhook = webhook.with_secret(secret.as_bytes());
+            }
+            if !config.webhooks.events.is_empty() {
+                webhook = webhook.with_events(config.webhooks.events.iter().copied());
+            }
+            cluster_host_builder = cluster_host_builder.with_event_sink(Arc::new(webhook));
+        }
+
+        if let Some(addr) = config.http.addr {
+            tracing::info!(addr = ?addr, extra_addrs = ?config.http.extra_addrs, "Starting HTTP server for components");
+            config
+                .http
+                .request_limits
+                .validate()
+                .context("invalid HTTP request limits")?;
             let http_router = wash_runtime::host::http::DynamicRouter::default();
-            cluster_host_builder = cluster_host_builder.with_http_handler(Arc::new(
-                wash_runtime::host::http::HttpServer::new(http_router, addr),
-            ));
+            let http_server = match config.http.tls_files()? {
+                Some(tls) => wash_runtime::host::http::HttpServer::new(http_router, addr)
+                    .with_tls_files(tls)
+                    .await
+                    .context("failed to load HTTP TLS certificate")?,
+                None => wash_runtime::host::http::HttpServer::new(http_router, addr),
+            };
+            let mut http_server = http_server
+                .with_proxy_protocol(config.http.proxy_protocol)
+                .with_trusted_proxies(config.http.trusted_proxies.clone())
+                .with_slow_request_threshold(std::time::Duration::from_millis(
+                    config.http.slow_request_ms,
+                ))
+                .with_resolver(
+                    Resolver::new(config.http.resolver.clone())
+                        .context("invalid resolver configuration")?,
+                )
+                .with_egress_default(config.http.egress_default)
+                .with_connection_pool(config.http.connection_pool.clone())
+                .with_error_pages(config.http.error_pages()?)
+                .with_grpc_web(config.http.grpc_web)
+                .with_request_signing(config.http.signing.clone())
+                .with_outbound_resilience(config.http.outbound.clone())
+                .with_request_limits(config.http.request_limits.clone())
+                .with_access_log(
+                    AccessLog::from_config(&config.http.access_log)
+                        .context("failed to open HTTP access log")?,
+                );
+            for addr in &config.http.extra_addrs {
+                http_server = http_server.with_addr(*addr);
+            }
+            if let Some(dir) = &config.http.spool_dir {
+                http_server = http_server.with_spool_dir(dir);
+            }
+            if let Some(path) = &config.http.health_path {
+                http_server = http_server.with_health_path(path);
+            }
+            if let Some(idempotency) = idempotency {
+                http_server = http_server.with_idempotency(idempotency);
+            }
+            cluster_host_builder = cluster_host_builder.with_http_handler(Arc::new(http_server));
         }
 
         // Enable WASI WebGPU if requested
         #[cfg(not(target_os = "windows"))]
-        if self.wasi_webgpu {
+        if config.plugins.wasi_webgpu {
             tracing::info!("WASI WebGPU support enabled");
             cluster_host_builder =
                 cluster_host_builder.with_plugin(Arc::new(WasiWebGpu::default()))?;
@@ -97,21 +975,133 @@ impl CliCommand for HostCommand {
         let cluster_host = cluster_host_builder
             .build()
             .context("failed to build cluster host")?;
+        let reload = cluster_host.reload_handle();
         let host_cleanup = wash_runtime::washlet::run_cluster_host(cluster_host)
             .await
             .context("failed to start cluster node")?;
 
-        tokio::signal::ctrl_c()
-            .await
-            .context("failed to listen for shutdown signal")?;
+        #[cfg(target_os = "linux")]
+        let systemd = Notifier::from_env().map(Arc::new);
+        #[cfg(target_os = "linux")]
+        if let Some(systemd) = &systemd {
+            systemd.ready();
+            systemd.spawn_watchdog();
+        }
+        #[cfg(target_os = "windows")]
+        if let Some(service) = &windows_service {
+            service.running();
+        }
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = signal(SignalKind::hangup()).context("failed to listen for SIGHUP")?;
+            let mut terminate =
+                signal(SignalKind::terminate()).context("failed to listen for SIGTERM")?;
+            loop {
+                tokio::select! {
+                    result = tokio::signal::ctrl_c() => {
+                        result.context("failed to listen for shutdown signal")?;
+                        break;
+                    }
+                    _ = terminate.recv() => break,
+                    _ = hangup.recv() => {
+                        info!("Reloading host configuration...");
+                        #[cfg(target_os = "linux")]
+                        if let Some(systemd) = &systemd {
+                            systemd.reloading();
+                        }
+                        reload.reload();
+                        #[cfg(target_os = "linux")]
+                        if let Some(systemd) = &systemd {
+                            systemd.ready();
+                        }
+                    }
+                }
+            }
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let _ = reload;
+            let stop_requested = async {
+                match &windows_service {
+                    Some(service) => service.stop_requested().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => {
+                    result.context("failed to listen for shutdown signal")?;
+                }
+                _ = stop_requested => {}
+            }
+        }
+        #[cfg(not(any(unix, target_os = "windows")))]
+        {
+            let _ = reload;
+            tokio::signal::ctrl_c()
+                .await
+                .context("failed to listen for shutdown signal")?;
+        }
 
-        info!("Stopping host...");
+        info!(?drain_timeout, "Stopping host...");
+        #[cfg(target_os = "linux")]
+        if let Some(systemd) = &systemd {
+            systemd.stopping();
+        }
+        #[cfg(target_os = "windows")]
+        if let Some(service) = &windows_service {
+            // Stopping workloads after draining takes a while longer
+            service.stopping(drain_timeout + Duration::from_secs(30));
+        }
 
         host_cleanup.await?;
 
+        if let Some(meter_provider) = meter_provider
+            && let Err(e) = meter_provider.shutdown()
+        {
+            tracing::warn!("failed to push final metrics: {e}");
+        }
+
+        #[cfg(target_os = "windows")]
+        if let Some(service) = windows_service {
+            service.stopped();
+        }
+
         Ok(CommandOutput::ok(
             "Host exited successfully".to_string(),
             None,
         ))
     }
 }
+
+/// Parses an `--execution-pool` of the form `NAME=CPUS[@NODE]`
+fn parse_execution_pool(s: &str) -> anyhow::Result<(String, ExecutionPoolConfig)> {
+    let (name, pool) = s
+        .split_once('=')
+        .context("expected NAME=CPUS[@NODE], e.g. latency=8-15")?;
+    anyhow::ensure!(!name.is_empty(), "execution pool name is empty");
+    let (cpus, numa_node) = match pool.split_once('@') {
+        Some((cpus, node)) => (
+            cpus,
+            Some(
+                node.parse()
+                    .with_context(|| format!("invalid NUMA node '{node}'"))?,
+            ),
+        ),
+        None => (pool, None),
+    };
+    let cpus = match cpus {
+        "" if numa_node.is_some() => CpuSet::default(),
+        cpus => cpus.parse()?,
+    };
+    let config = ExecutionPoolConfig::new(cpus);
+    Ok((
+        name.to_string(),
+        match numa_node {
+            Some(node) => config.with_numa_node(node),
+            None => config,
+        },
+    ))
+}
@@ -34,17 +34,21 @@ use crate::{
     plugin::{PluginComponent, PluginManager, bindings::wasmcloud::wash::types::HookType},
 };
 
+pub mod bench;
 pub mod completion;
 pub mod component_build;
 pub mod config;
 /// Developer hot-reload loop for Wasm components
 pub mod dev;
 pub mod doctor;
+pub mod fixtures;
 pub mod host;
 pub mod inspect;
 pub mod new;
 pub mod oci;
 pub mod plugin;
+pub mod status;
+pub mod token;
 pub mod update;
 pub mod wit;
 
@@ -503,6 +507,7 @@ impl CliContext {
             // TODO: Messes with host interface parsing
             // host_interfaces: vec![WitInterface::from("wasmcloud:wash/plugin,types@0.0.2")],
             volumes: vec![],
+            outbound: Default::default(),
         };
 
         let res = self
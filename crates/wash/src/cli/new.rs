@@ -1,7 +1,7 @@
 //! CLI command for creating new component projects
 
 use anyhow::{Context, bail};
-use clap::Args;
+use clap::{Args, Subcommand};
 use dialoguer::{Confirm, FuzzySelect, theme::ColorfulTheme};
 use serde_json::json;
 use std::path::PathBuf;
@@ -12,11 +12,16 @@ use crate::{
     cli::{CliCommand, CliContext, CommandOutput},
     config::Config,
     new::{NewTemplate, TemplateLanguage, clone_template, copy_dir_recursive, extract_subfolder},
+    scaffold::{Scaffold, ScaffoldKind, ScaffoldLanguage},
 };
 
 /// Create a new component project from a template, git repository, or local path
 #[derive(Args, Debug, Clone)]
+#[clap(args_conflicts_with_subcommands = true)]
 pub struct NewCommand {
+    #[clap(subcommand)]
+    scaffold: Option<ScaffoldCommand>,
+
     #[clap(
         help = "Project name and local directory to create, defaults to repository / subfolder name"
     )]
@@ -42,6 +47,10 @@ pub struct NewCommand {
 impl CliCommand for NewCommand {
     #[instrument(level = "debug", skip(self, ctx), name = "new")]
     async fn handle(&self, ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        if let Some(scaffold) = &self.scaffold {
+            return scaffold.handle().await;
+        }
+
         let config = ctx.ensure_config(None).await.unwrap_or_else(|e| {
             warn!(error = %e, "Failed to load global configuration");
             Config::default()
@@ -324,6 +333,63 @@ impl NewCommand {
     }
 }
 
+/// Generate a project wired to the interfaces of wash hosts
+#[derive(Subcommand, Debug, Clone)]
+pub enum ScaffoldCommand {
+    /// Component serving HTTP requests through wasi:http
+    HttpComponent(ScaffoldArgs),
+    /// Component handling messages through wasmcloud:messaging
+    MessagingComponent(ScaffoldArgs),
+    /// Service running a job on an interval
+    CronJob(ScaffoldArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScaffoldArgs {
+    /// Project name and local directory to create, in kebab-case
+    name: String,
+
+    /// Language of the project: rust or tinygo
+    #[clap(long, default_value = "rust")]
+    language: ScaffoldLanguage,
+}
+
+impl ScaffoldCommand {
+    #[instrument(level = "debug", skip(self))]
+    async fn handle(&self) -> anyhow::Result<CommandOutput> {
+        let (kind, args) = match self {
+            ScaffoldCommand::HttpComponent(args) => (ScaffoldKind::HttpComponent, args),
+            ScaffoldCommand::MessagingComponent(args) => (ScaffoldKind::MessagingComponent, args),
+            ScaffoldCommand::CronJob(args) => (ScaffoldKind::CronJob, args),
+        };
+        let scaffold = Scaffold::new(&args.name, kind)?.with_language(args.language);
+        let output_dir = PathBuf::from(scaffold.name());
+
+        info!(
+            name = scaffold.name(),
+            world = kind.world(),
+            "generating project"
+        );
+        scaffold
+            .generate(&output_dir)
+            .await
+            .context("failed to generate project")?;
+
+        Ok(CommandOutput::ok(
+            format!(
+                "Project '{}' created successfully at {}, deploy it with manifests/workloaddeployment.yaml",
+                scaffold.name(),
+                output_dir.display()
+            ),
+            Some(json!({
+                "name": scaffold.name(),
+                "world": kind.world(),
+                "output_dir": output_dir,
+            })),
+        ))
+    }
+}
+
 /// Template source specification
 #[derive(Debug, Clone)]
 pub enum TemplateSource {
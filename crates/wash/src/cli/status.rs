@@ -0,0 +1,366 @@
+//! CLI command comparing the desired state of workloads with the actual state of hosts
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use clap::Args;
+use dialoguer::console::{Term, style};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use wash_runtime::types::{
+    ComponentProvenance, WorkloadListRequest, WorkloadState, WorkloadSummary,
+};
+use wash_runtime::washlet::types::v2;
+
+use crate::cli::{CliCommand, CliContext, CommandOutput};
+
+/// Show the desired state of workloads next to the actual state of hosts
+#[derive(Args, Debug, Clone)]
+pub struct StatusCommand {
+    /// NATS URL of the runtime API
+    #[clap(long = "nats-url", default_value = "nats://localhost:4222")]
+    pub nats_url: String,
+
+    /// Host to query. May be given more than once.
+    #[clap(long = "host-id", required = true)]
+    pub host_ids: Vec<String>,
+
+    /// JSON workload manifest describing the desired state. May be given more
+    /// than once. Without manifests only the actual state is shown.
+    #[clap(long = "manifest")]
+    pub manifests: Vec<PathBuf>,
+
+    /// Only show workloads in this namespace
+    #[clap(long = "namespace")]
+    pub namespace: Option<String>,
+
+    /// Token for hosts started with `--api-token-secret`, see `wash token issue`
+    #[clap(long = "api-token", env = "WASH_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    /// Keep refreshing until interrupted
+    #[clap(long = "watch", short = 'w', default_value_t = false)]
+    pub watch: bool,
+
+    /// Seconds between refreshes with --watch
+    #[clap(long = "interval", default_value_t = 2)]
+    pub interval_secs: u64,
+}
+
+impl CliCommand for StatusCommand {
+    #[instrument(level = "debug", skip_all, name = "status")]
+    async fn handle(&self, _ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        let desired = self.load_manifests().await?;
+        let nats_client = wash_runtime::washlet::connect_nats(self.nats_url.clone(), None)
+            .await
+            .context("failed to connect to NATS")?;
+
+        if !self.watch {
+            let rows = reconcile(&desired, &self.actual(&nats_client).await?);
+            return Ok(CommandOutput::ok(
+                render(&rows),
+                Some(serde_json::json!({ "workloads": rows })),
+            ));
+        }
+
+        let term = Term::stdout();
+        loop {
+            let view = match self.actual(&nats_client).await {
+                Ok(actual) => render(&reconcile(&desired, &actual)),
+                Err(e) => style(format!("{e:#}")).red().to_string(),
+            };
+            term.clear_screen()?;
+            term.write_line(&format!(
+                "{}\n\n{view}",
+                style(format!(
+                    "Every {}s, {}",
+                    self.interval_secs,
+                    chrono::Local::now().format("%H:%M:%S")
+                ))
+                .dim()
+            ))?;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(self.interval_secs.max(1))) => {}
+                _ = tokio::signal::ctrl_c() => break,
+            }
+        }
+        Ok(CommandOutput::ok("", None))
+    }
+}
+
+/// The fields of a workload manifest that identify it
+#[derive(Deserialize)]
+struct ManifestRef {
+    namespace: String,
+    name: String,
+}
+
+impl StatusCommand {
+    async fn load_manifests(&self) -> anyhow::Result<BTreeSet<(String, String)>> {
+        let mut desired = BTreeSet::new();
+        for path in &self.manifests {
+            let manifest = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("failed to read manifest {}", path.display()))?;
+            let manifest: ManifestRef = serde_json::from_slice(&manifest)
+                .with_context(|| format!("failed to parse manifest {}", path.display()))?;
+            if self
+                .namespace
+                .as_ref()
+                .is_none_or(|namespace| *namespace == manifest.namespace)
+            {
+                desired.insert((manifest.namespace, manifest.name));
+            }
+        }
+        Ok(desired)
+    }
+
+    /// Lists the workloads of every host
+    async fn actual(
+        &self,
+        nats_client: &async_nats::Client,
+    ) -> anyhow::Result<Vec<(String, WorkloadSummary)>> {
+        let request = v2::WorkloadListRequest::from(WorkloadListRequest {
+            namespace: self.namespace.clone(),
+        });
+        let mut actual = Vec::new();
+        for host_id in &self.host_ids {
+            let response: v2::WorkloadListResponse = wash_runtime::washlet::api_request(
+                nats_client,
+                host_id,
+                "workload.list",
+                &request,
+                self.api_token.as_deref(),
+            )
+            .await?;
+            actual.extend(
+                response
+                    .workloads
+                    .into_iter()
+                    .map(|workload| (host_id.clone(), workload.into())),
+            );
+        }
+        Ok(actual)
+    }
+}
+
+/// How the actual state of a workload differs from the desired state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Drift {
+    /// Running or completed as desired
+    InSync,
+    /// Starting or stopping
+    Pending,
+    /// Failed on its host
+    Unhealthy,
+    /// Desired but on no host
+    Missing,
+    /// On a host but not desired
+    Unexpected,
+}
+
+/// A line of the status view
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusRow {
+    pub namespace: String,
+    pub name: String,
+    pub drift: Drift,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload_id: Option<String>,
+    pub message: String,
+    /// Where the components of the workload come from
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<ComponentProvenance>,
+}
+
+/// Compares the desired workloads, by namespace and name, with the workloads
+/// listed by hosts. Without desired workloads, workloads are only judged by
+/// their state.
+pub fn reconcile(
+    desired: &BTreeSet<(String, String)>,
+    actual: &[(String, WorkloadSummary)],
+) -> Vec<StatusRow> {
+    let mut rows = Vec::new();
+    for (namespace, name) in desired {
+        if !actual
+            .iter()
+            .any(|(_, w)| w.namespace == *namespace && w.name == *name)
+        {
+            rows.push(StatusRow {
+                namespace: namespace.clone(),
+                name: name.clone(),
+                drift: Drift::Missing,
+                host_id: None,
+                workload_id: None,
+                message: "not running on any host".to_string(),
+                components: Vec::new(),
+            });
+        }
+    }
+    for (host_id, workload) in actual {
+        let key = (workload.namespace.clone(), workload.name.clone());
+        let drift = match workload.workload_status.workload_state {
+            WorkloadState::Error => Drift::Unhealthy,
+            WorkloadState::Running | WorkloadState::Completed
+                if desired.is_empty() || desired.contains(&key) =>
+            {
+                Drift::InSync
+            }
+            WorkloadState::Running | WorkloadState::Completed => Drift::Unexpected,
+            _ => Drift::Pending,
+        };
+        rows.push(StatusRow {
+            namespace: workload.namespace.clone(),
+            name: workload.name.clone(),
+            drift,
+            host_id: Some(host_id.clone()),
+            workload_id: Some(workload.workload_status.workload_id.clone()),
+            message: workload.workload_status.message.clone(),
+            components: workload.workload_status.components.clone(),
+        });
+    }
+    rows.sort_by(|a, b| {
+        (&a.namespace, &a.name, &a.host_id).cmp(&(&b.namespace, &b.name, &b.host_id))
+    });
+    rows
+}
+
+/// Renders rows as a colored diff, `-` for missing and `+` for unexpected
+/// workloads
+fn render(rows: &[StatusRow]) -> String {
+    if rows.is_empty() {
+        return "No workloads".to_string();
+    }
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    for row in rows {
+        let workload = if row.name.is_empty() {
+            row.workload_id.clone().unwrap_or_default()
+        } else {
+            format!("{}/{}", row.namespace, row.name)
+        };
+        let line = format!(
+            "{} {workload:<40} {:<24} {}{}",
+            match row.drift {
+                Drift::InSync => "=",
+                Drift::Pending => "~",
+                Drift::Unhealthy => "!",
+                Drift::Missing => "-",
+                Drift::Unexpected => "+",
+            },
+            row.host_id.as_deref().unwrap_or("-"),
+            row.message,
+            versions(&row.components)
+        );
+        lines.push(match row.drift {
+            Drift::InSync => style(line).green().to_string(),
+            Drift::Pending => style(line).cyan().to_string(),
+            Drift::Unhealthy | Drift::Missing => style(line).red().to_string(),
+            Drift::Unexpected => style(line).yellow().to_string(),
+        });
+    }
+    let count = |drift| rows.iter().filter(|row| row.drift == drift).count();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} in sync, {} pending, {} unhealthy, {} missing, {} unexpected",
+        count(Drift::InSync),
+        count(Drift::Pending),
+        count(Drift::Unhealthy),
+        count(Drift::Missing),
+        count(Drift::Unexpected)
+    ));
+    lines.join("\n")
+}
+
+/// Renders the version and short digest of components, e.g.
+/// ` (1.4.2@9f86d081884c)`
+fn versions(components: &[ComponentProvenance]) -> String {
+    if components.is_empty() {
+        return String::new();
+    }
+    let versions = components
+        .iter()
+        .map(|component| {
+            format!(
+                "{}@{}",
+                component.version.as_deref().unwrap_or("-"),
+                &component.digest[..component.digest.len().min(12)]
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(" ({})", versions.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use wash_runtime::types::WorkloadStatus;
+
+    use super::*;
+
+    fn workload(namespace: &str, name: &str, state: WorkloadState) -> WorkloadSummary {
+        WorkloadSummary {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            workload_status: WorkloadStatus {
+                workload_id: format!("{namespace}-{name}"),
+                workload_state: state,
+                message: format!("Workload is {state:?}"),
+                usage: None,
+                components: Vec::new(),
+                activity: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let desired = BTreeSet::from([
+            ("team-a".to_string(), "api".to_string()),
+            ("team-a".to_string(), "worker".to_string()),
+            ("team-a".to_string(), "cron".to_string()),
+        ]);
+        let actual = vec![
+            (
+                "host-1".to_string(),
+                workload("team-a", "api", WorkloadState::Running),
+            ),
+            (
+                "host-1".to_string(),
+                workload("team-a", "cron", WorkloadState::Error),
+            ),
+            (
+                "host-2".to_string(),
+                workload("team-a", "old", WorkloadState::Running),
+            ),
+            (
+                "host-2".to_string(),
+                workload("", "", WorkloadState::Starting),
+            ),
+        ];
+        let drift = reconcile(&desired, &actual)
+            .into_iter()
+            .map(|row| (row.name, row.drift))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            drift,
+            vec![
+                (String::new(), Drift::Pending),
+                ("api".to_string(), Drift::InSync),
+                ("cron".to_string(), Drift::Unhealthy),
+                ("old".to_string(), Drift::Unexpected),
+                ("worker".to_string(), Drift::Missing),
+            ]
+        );
+
+        // Without a desired state, workloads are judged by their state only
+        let rows = reconcile(&BTreeSet::new(), &actual);
+        assert_eq!(
+            rows.iter().filter(|row| row.drift == Drift::InSync).count(),
+            2
+        );
+    }
+}
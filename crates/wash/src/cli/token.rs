@@ -0,0 +1,72 @@
+use anyhow::Context as _;
+use clap::Subcommand;
+use tracing::instrument;
+use wash_runtime::washlet::auth::{ALL_NAMESPACES, ApiTokens, ApiVerb, TokenClaims};
+
+use crate::cli::{CliCommand, CliContext, CommandOutput};
+
+/// Issue tokens for the runtime API of hosts started with `--api-token-secret`
+#[derive(Subcommand, Debug, Clone)]
+pub enum TokenCommand {
+    /// Issue a token granting verbs in a set of namespaces
+    Issue {
+        /// Who the token is for, e.g. the name of a CI pipeline
+        subject: String,
+        /// Namespace the token grants access to. May be given more than once,
+        /// `*` grants every namespace.
+        #[clap(long = "namespace", required = true)]
+        namespaces: Vec<String>,
+        /// Verb the token grants: read, deploy or admin. Each verb includes the
+        /// ones before it.
+        #[clap(long = "verb", required = true)]
+        verbs: Vec<ApiVerb>,
+        /// Number of hours the token is valid for. Tokens never expire by default.
+        #[clap(long = "expires-in-hours")]
+        expires_in_hours: Option<u32>,
+        /// Secret shared with the hosts that should accept the token
+        #[clap(long = "secret", env = "WASH_API_TOKEN_SECRET", hide_env_values = true)]
+        secret: String,
+    },
+}
+
+impl CliCommand for TokenCommand {
+    #[instrument(level = "debug", skip_all, name = "token")]
+    async fn handle(&self, _ctx: &CliContext) -> anyhow::Result<CommandOutput> {
+        match self {
+            TokenCommand::Issue {
+                subject,
+                namespaces,
+                verbs,
+                expires_in_hours,
+                secret,
+            } => {
+                let mut claims = TokenClaims::new(subject);
+                for namespace in namespaces {
+                    claims = claims.with_namespace(namespace);
+                }
+                for verb in verbs {
+                    claims = claims.with_verb(*verb);
+                }
+                if let Some(hours) = expires_in_hours {
+                    claims = claims.with_expiry(
+                        chrono::Utc::now() + chrono::Duration::hours(i64::from(*hours)),
+                    );
+                }
+                let token = ApiTokens::new(secret.as_bytes())
+                    .issue(&claims)
+                    .context("failed to issue token")?;
+                if claims.has_all_namespaces() {
+                    tracing::warn!("token grants access to every namespace ({ALL_NAMESPACES})");
+                }
+
+                Ok(CommandOutput::ok(
+                    token.clone(),
+                    Some(serde_json::json!({
+                        "token": token,
+                        "claims": claims,
+                    })),
+                ))
+            }
+        }
+    }
+}
@@ -20,6 +20,16 @@ pub struct BuildConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub typescript: Option<TypeScriptBuildConfig>,
 
+    /// Python-specific build configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub python: Option<PythonBuildConfig>,
+
+    /// WASI preview 1 adapter used by `wasm-tools component new` when a build
+    /// produces a core module rather than a component, e.g. for the
+    /// `wasm32-wasip1` target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasi_adapter: Option<PathBuf>,
+
     /// Expected path to the built Wasm component artifact
     #[serde(skip_serializing_if = "Option::is_none")]
     pub component_path: Option<PathBuf>,
@@ -35,6 +45,8 @@ pub enum ProjectType {
     Go,
     /// TypeScript/JavaScript project (package.json found)
     TypeScript,
+    /// Python project (pyproject.toml or app.py found)
+    Python,
     /// Unknown project type
     Unknown,
 }
@@ -312,3 +324,40 @@ fn default_ts_package_manager() -> String {
 fn default_ts_build_command() -> String {
     "build".to_string()
 }
+
+/// Python-specific build configuration, built with componentize-py
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonBuildConfig {
+    /// Custom build command that overrides all other Python build settings
+    /// When specified, all other Python build flags are ignored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_command: Option<Vec<String>>,
+
+    /// The Python module implementing the WIT world (default: "app")
+    #[serde(default = "default_python_module")]
+    pub module: String,
+
+    /// The WIT world to use, if not provided it will assume only one world
+    /// is defined in the WIT package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wit_world: Option<String>,
+
+    /// Additional build flags passed to `componentize-py componentize` (default: empty)
+    #[serde(default)]
+    pub build_flags: Vec<String>,
+}
+
+impl Default for PythonBuildConfig {
+    fn default() -> Self {
+        Self {
+            custom_command: None,
+            module: default_python_module(),
+            wit_world: None,
+            build_flags: Vec::new(),
+        }
+    }
+}
+
+fn default_python_module() -> String {
+    "app".to_string()
+}
@@ -15,7 +15,8 @@ use tracing::info;
 use crate::{
     cli::CONFIG_FILE_NAME,
     component_build::{
-        BuildConfig, ProjectType, RustBuildConfig, TinyGoBuildConfig, TypeScriptBuildConfig,
+        BuildConfig, ProjectType, PythonBuildConfig, RustBuildConfig, TinyGoBuildConfig,
+        TypeScriptBuildConfig,
     },
     new::NewTemplate,
     wit::WitConfig,
@@ -224,6 +225,14 @@ where
                 });
             }
         }
+        ProjectType::Python => {
+            if let Ok(python_config) = figment.extract::<PythonBuildConfig>() {
+                config.build = Some(BuildConfig {
+                    python: Some(python_config),
+                    ..Default::default()
+                });
+            }
+        }
         ProjectType::Unknown => {
             // Unknown project type, skip config generation
             return Ok(());
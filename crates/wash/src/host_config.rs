@@ -0,0 +1,1251 @@
+//! Configuration file of `wash host`.
+//!
+//! Every flag of `wash host` can also be set in a TOML, YAML or JSON file
+//! passed with `--config`. Settings in the file take precedence over flags.
+//! The file is read again when the host receives `SIGHUP` or a `host.reload`
+//! API request, which re-applies the limits, the API token secret, the HTTP
+//! listen addresses and the HTTP TLS certificate, read again from its files
+//! so renewed certificates apply. Other settings, such as plugins, only
+//! change when the host restarts.
+//!
+//! ```toml
+//! host_group = "edge"
+//! drain_timeout_secs = 60
+//! invocation_timeout_ms = 30000
+//! cpu_fuel_rate = 2000000000
+//!
+//! [labels]
+//! region = "eu-west-1"
+//!
+//! [http]
+//! addr = "0.0.0.0:8000"
+//! extra_addrs = ["[::]:8000"]
+//! trusted_proxies = ["10.0.0.0/8"]
+//! tls_cert = "/etc/wash/tls/tls.crt"
+//! tls_key = "/etc/wash/tls/tls.key"
+//! tls_sni = [
+//!     { host = "api.example.com", cert = "/etc/wash/tls/api.crt", key = "/etc/wash/tls/api.key" },
+//! ]
+//! error_format = "html"
+//! error_template = "/etc/wash/error.html"
+//! error_request_id = true
+//!
+//! [http.request_limits]
+//! max_body_bytes = 104857600
+//! max_headers = 100
+//! max_header_bytes = 16384
+//! header_read_timeout_ms = 10000
+//! body_read_timeout_ms = 30000
+//!
+//! [http.access_log]
+//! stdout = true
+//! file = "/var/log/wash/access.log"
+//!
+//! [http.resolver]
+//! nameservers = ["1.1.1.1:53"]
+//! denied_networks = ["10.0.0.0/8", "169.254.0.0/16"]
+//!
+//! [[http.signing]]
+//! hosts = ["*.amazonaws.com"]
+//! namespaces = ["billing"]
+//! method = "sigv4"
+//! access_key_id = "AKIA..."
+//! secret_access_key = "..."
+//! region = "eu-west-1"
+//! service = "s3"
+//!
+//! [[http.outbound]]
+//! hosts = ["generativelanguage.googleapis.com"]
+//! max_attempts = 3
+//! backoff_ms = 200
+//! retry_statuses = [429, 502, 503, 504]
+//! failure_threshold = 5
+//! open_ms = 30000
+//!
+//! [plugins]
+//! dead_letter_subject = "dead-letters"
+//! forward_calls = true
+//! socket_ports = [10000, 10999]
+//!
+//! [plugins.config_overrides]
+//! otel_endpoint = "http://collector.eu-west-1:4318"
+//!
+//! [execution_pools.latency]
+//! cpus = "8-15"
+//! numa_node = 1
+//!
+//! [[devices]]
+//! class = "gpu"
+//! path = "/dev/dri/renderD128"
+//!
+//! [cgroups]
+//! root = "/sys/fs/cgroup/wash.slice/wash.service"
+//!
+//! [component_cache]
+//! dir = "/var/cache/wash/components"
+//! max_size_mb = 4096
+//!
+//! [crash_loop]
+//! threshold = 5
+//! max_backoff_ms = 60000
+//! stop_after = 50
+//!
+//! [metering]
+//! fuel = true
+//! csv_file = "/var/lib/wash/usage.csv"
+//! webhook_urls = ["https://billing.example.com/usage"]
+//!
+//! [metrics]
+//! addr = "0.0.0.0:9100"
+//!
+//! [otlp_tracing]
+//! endpoint = "http://localhost:4317"
+//! sampling_ratio = 0.1
+//!
+//! [auth]
+//! api_token_secret = "..."
+//!
+//! # gRPC clients presenting this certificate may deploy in team-a
+//! [auth.client_identities."5e:88:48:98:..."]
+//! subject = "team-a-ci"
+//! namespaces = ["team-a"]
+//! verbs = ["deployer"]
+//!
+//! [secrets]
+//! dir = "/run/secrets"
+//! default = "file"
+//! env_allowlist = ["DB_HOST", "APP_*"]
+//!
+//! [secrets.vault]
+//! addr = "https://vault.internal:8200"
+//!
+//! [grpc]
+//! addr = "0.0.0.0:7443"
+//! tls_cert = "/etc/wash/grpc/tls.crt"
+//! tls_key = "/etc/wash/grpc/tls.key"
+//! tls_client_ca = "/etc/wash/grpc/clients.crt"
+//!
+//! [limits]
+//! max_workloads = 100
+//! max_memory_mb = 8192
+//!
+//! [limits.namespaces.team-a]
+//! max_instances = 50
+//! max_connections = 200
+//! ```
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+use figment::Figment;
+use figment::providers::{Format as _, Json, Serialized, Toml, Yaml};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use wash_runtime::engine::cache::DEFAULT_MAX_SIZE_MB;
+use wash_runtime::engine::cgroup::DEFAULT_HOST_MEMORY_RESERVE_MB;
+use wash_runtime::engine::crash_loop::{
+    CrashLoopPolicy, DEFAULT_CRASH_LOOP_THRESHOLD, DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF,
+};
+use wash_runtime::engine::pool::ExecutionPoolConfig;
+use wash_runtime::host::devices::Device;
+use wash_runtime::host::events::LifecycleEventType;
+use wash_runtime::host::http::{
+    AccessLogConfig, ConnectionPoolConfig, EgressDefault, ErrorFormat, ErrorPages, IpCidr,
+    RequestLimits, ResilienceRule, ResolverConfig, SigningRule, SniFiles, TlsFiles,
+};
+use wash_runtime::host::interpolate::{EnvAllowlist, SecretStore};
+use wash_runtime::host::limits::HostLimits;
+use wash_runtime::host::metering::DEFAULT_EXPORT_INTERVAL;
+use wash_runtime::host::secrets::{
+    AwsSecretsManager, EnvSecrets, FileSecrets, SecretProviders, VaultSecrets,
+};
+use wash_runtime::host::tls::{TlsCryptoProvider, TlsVersion};
+use wash_runtime::plugin::wash_sockets::WashSockets;
+use wash_runtime::washlet::DEFAULT_DRAIN_TIMEOUT;
+use wash_runtime::washlet::auth::{ApiTokens, ClientIdentities, TokenClaims};
+use wash_runtime::washlet::grpc::{GrpcApi, GrpcTls};
+use wash_runtime::washlet::reload::{ConfigSource, LiveConfig};
+
+/// Configuration of a host started with `wash host`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostConfig {
+    /// The host group label to assign to the host
+    pub host_group: String,
+    /// The host name to assign to the host
+    pub host_name: Option<String>,
+    /// Labels to assign to the host, listed in heartbeats
+    pub labels: BTreeMap<String, String>,
+    /// NATS URL for Control Plane communications
+    pub scheduler_nats_url: String,
+    /// NATS URL for Data Plane communications
+    pub data_nats_url: String,
+    /// Directory to keep named volumes in
+    pub volume_dir: Option<PathBuf>,
+    /// Devices workloads can request
+    pub devices: Vec<Device>,
+    /// File the host snapshots its workloads to when it stops, and restores
+    /// them from when it starts
+    pub snapshot_file: Option<PathBuf>,
+    /// File the host keeps its state in as it changes, and restores it from
+    /// when it starts
+    pub state_file: Option<PathBuf>,
+    /// How long the host waits for requests in flight when it stops, in
+    /// seconds. Zero stops it right away.
+    pub drain_timeout_secs: u64,
+    /// How long, in milliseconds, an invocation of a component may run
+    /// unless the component sets its own `invocation_timeout_ms`
+    pub invocation_timeout_ms: Option<u64>,
+    /// Fuel a CPU burns through per second, to enforce the `cpu_limit` of
+    /// components with fuel on hosts without cgroups
+    pub cpu_fuel_rate: Option<u64>,
+    /// Pools of threads pinned to CPUs that components can run their guests
+    /// on, by name
+    pub execution_pools: BTreeMap<String, ExecutionPoolConfig>,
+    pub cgroups: CgroupsConfig,
+    pub component_cache: ComponentCacheConfig,
+    pub crash_loop: CrashLoopConfig,
+    pub http: HttpConfig,
+    pub plugins: PluginsConfig,
+    pub webhooks: WebhooksConfig,
+    pub otlp_metrics: OtlpMetricsConfig,
+    pub metrics: MetricsConfig,
+    pub otlp_tracing: OtlpTracingConfig,
+    pub metering: MeteringConfig,
+    pub tls: TlsConfig,
+    pub auth: AuthConfig,
+    pub grpc: GrpcConfig,
+    pub secrets: SecretsConfig,
+    pub attestation: AttestationConfig,
+    pub limits: HostLimits,
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        Self {
+            host_group: "default".to_string(),
+            host_name: None,
+            labels: BTreeMap::new(),
+            scheduler_nats_url: "nats://localhost:4222".to_string(),
+            data_nats_url: "nats://localhost:4222".to_string(),
+            volume_dir: None,
+            devices: Vec::new(),
+            snapshot_file: None,
+            state_file: None,
+            drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT.as_secs(),
+            invocation_timeout_ms: None,
+            cpu_fuel_rate: None,
+            execution_pools: BTreeMap::new(),
+            cgroups: CgroupsConfig::default(),
+            component_cache: ComponentCacheConfig::default(),
+            crash_loop: CrashLoopConfig::default(),
+            http: HttpConfig::default(),
+            plugins: PluginsConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            otlp_metrics: OtlpMetricsConfig::default(),
+            metrics: MetricsConfig::default(),
+            otlp_tracing: OtlpTracingConfig::default(),
+            metering: MeteringConfig::default(),
+            tls: TlsConfig::default(),
+            auth: AuthConfig::default(),
+            grpc: GrpcConfig::default(),
+            secrets: SecretsConfig::default(),
+            attestation: AttestationConfig::default(),
+            limits: HostLimits::default(),
+        }
+    }
+}
+
+/// Kernel enforcement of the limits of workloads with cgroup v2, on Linux
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CgroupsConfig {
+    /// Cgroup v2 directory delegated to the host. Limits are only enforced
+    /// by the kernel when set.
+    pub root: Option<PathBuf>,
+    /// Memory left to the host on top of what its workloads reserve, in MiB
+    pub host_memory_reserve_mb: u64,
+}
+
+impl Default for CgroupsConfig {
+    fn default() -> Self {
+        Self {
+            root: None,
+            host_memory_reserve_mb: DEFAULT_HOST_MEMORY_RESERVE_MB,
+        }
+    }
+}
+
+/// Compiled components kept across host restarts, see
+/// [`wash_runtime::engine::cache`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComponentCacheConfig {
+    /// Directory of the cache, components are only cached when set
+    pub dir: Option<PathBuf>,
+    /// Size, in MiB, the cache is kept under
+    pub max_size_mb: u64,
+}
+
+impl Default for ComponentCacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_size_mb: DEFAULT_MAX_SIZE_MB,
+        }
+    }
+}
+
+/// Backoff of components that keep failing, see
+/// [`wash_runtime::engine::crash_loop`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrashLoopConfig {
+    /// Back off crash looping components. Implied by any other setting.
+    pub enabled: bool,
+    /// Consecutive failures after which a component is crash looping
+    pub threshold: u32,
+    /// Backoff after the failure a crash loop is detected on, in milliseconds
+    pub initial_backoff_ms: u64,
+    /// Longest backoff, in milliseconds
+    pub max_backoff_ms: u64,
+    /// Stop a workload once one of its components failed this many times in
+    /// a row
+    pub stop_after: Option<u32>,
+}
+
+impl CrashLoopConfig {
+    /// Returns the crash loop policy of the engine, if crash loops are
+    /// backed off.
+    pub fn policy(&self) -> Option<CrashLoopPolicy> {
+        if !self.enabled && *self == Self::default() {
+            return None;
+        }
+        Some(CrashLoopPolicy {
+            threshold: self.threshold,
+            initial_backoff: std::time::Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: std::time::Duration::from_millis(self.max_backoff_ms),
+            stop_after: self.stop_after,
+        })
+    }
+}
+
+impl Default for CrashLoopConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: DEFAULT_CRASH_LOOP_THRESHOLD,
+            initial_backoff_ms: DEFAULT_INITIAL_BACKOFF.as_millis() as u64,
+            max_backoff_ms: DEFAULT_MAX_BACKOFF.as_millis() as u64,
+            stop_after: None,
+        }
+    }
+}
+
+/// The HTTP server components are served on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// The address on which the HTTP server will listen. No server is started
+    /// without one.
+    pub addr: Option<SocketAddr>,
+    /// Further addresses the HTTP server listens on, e.g. one per address
+    /// family. `[::]:<port>` also accepts IPv4 connections unless an IPv4
+    /// address with the same port is listed.
+    pub extra_addrs: Vec<SocketAddr>,
+    /// Expect a PROXY protocol v1/v2 header on every connection
+    pub proxy_protocol: bool,
+    /// Networks whose X-Forwarded-For and Forwarded headers are trusted
+    pub trusted_proxies: Vec<IpCidr>,
+    /// Link requests taking at least this many milliseconds to their trace
+    pub slow_request_ms: u64,
+    /// How the hosts of outgoing requests of components are resolved
+    pub resolver: ResolverConfig,
+    /// Whether components whose `allowed_hosts` allows no host may send
+    /// requests to every host, `allow`, or to none, `deny`
+    pub egress_default: EgressDefault,
+    /// How the connections of outgoing requests of components are pooled
+    pub connection_pool: ConnectionPoolConfig,
+    /// PEM file of the certificate chain to serve HTTPS with, together with
+    /// `tls_key`
+    pub tls_cert: Option<PathBuf>,
+    /// PEM file of the private key of `tls_cert`
+    pub tls_key: Option<PathBuf>,
+    /// PEM file of the CA certificates of clients, for mutual TLS
+    pub tls_ca: Option<PathBuf>,
+    /// Certificates of host names served another certificate than `tls_cert`
+    pub tls_sni: Vec<SniFiles>,
+    /// Body of the responses the host answers requests with itself, e.g.
+    /// when no route matches or a component fails: `empty`, `json` or `html`
+    pub error_format: ErrorFormat,
+    /// HTML template of `html` error responses
+    pub error_template: Option<PathBuf>,
+    /// Include the id of the request in error responses
+    pub error_request_id: bool,
+    /// Translate gRPC-web and Connect requests of browsers to gRPC
+    pub grpc_web: bool,
+    /// Path, e.g. `/healthz`, on which the server answers probes of
+    /// orchestrators with the health of the host instead of routing them
+    pub health_path: Option<String>,
+    /// Directory request bodies of routes with body spooling are written to
+    /// once they outgrow memory, instead of the system temporary directory
+    pub spool_dir: Option<PathBuf>,
+    /// Rules signing outgoing requests of components with credentials of the
+    /// host. Only set in the configuration file.
+    pub signing: Vec<SigningRule>,
+    /// Rules retrying outgoing requests of components and failing them fast
+    /// while their host keeps failing. Only set in the configuration file.
+    pub outbound: Vec<ResilienceRule>,
+    /// Limits of the requests of all routes, unless a route sets its own
+    pub request_limits: RequestLimits,
+    /// Where every HTTP request is logged to
+    pub access_log: AccessLogConfig,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            addr: None,
+            extra_addrs: Vec::new(),
+            proxy_protocol: false,
+            trusted_proxies: Vec::new(),
+            slow_request_ms: 1000,
+            resolver: ResolverConfig::default(),
+            egress_default: EgressDefault::default(),
+            connection_pool: ConnectionPoolConfig::default(),
+            tls_cert: None,
+            tls_key: None,
+            tls_ca: None,
+            tls_sni: Vec::new(),
+            error_format: ErrorFormat::default(),
+            error_template: None,
+            error_request_id: false,
+            grpc_web: false,
+            health_path: None,
+            spool_dir: None,
+            signing: Vec::new(),
+            outbound: Vec::new(),
+            request_limits: RequestLimits::default(),
+            access_log: AccessLogConfig::default(),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Returns every address the HTTP server listens on.
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.addr.iter().chain(&self.extra_addrs).copied().collect()
+    }
+
+    /// Returns the certificate files to serve HTTPS with, if any.
+    ///
+    /// # Errors
+    /// Returns an error if only one of `tls_cert` and `tls_key` is set, or
+    /// `tls_sni` is set without them.
+    pub fn tls_files(&self) -> anyhow::Result<Option<TlsFiles>> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                let mut files = TlsFiles::new(cert, key);
+                if let Some(ca) = &self.tls_ca {
+                    files = files.with_ca(ca);
+                }
+                for sni in &self.tls_sni {
+                    files = files.with_sni(sni.clone());
+                }
+                Ok(Some(files))
+            }
+            (None, None) if self.tls_sni.is_empty() => Ok(None),
+            _ => bail!("HTTP TLS needs both a certificate and a key"),
+        }
+    }
+
+    /// Returns how the host formats its error responses.
+    ///
+    /// # Errors
+    /// Returns an error if the `error_template` can't be read.
+    pub fn error_pages(&self) -> anyhow::Result<ErrorPages> {
+        let pages = ErrorPages::new(self.error_format).with_request_id(self.error_request_id);
+        match &self.error_template {
+            Some(path) => {
+                let template = std::fs::read_to_string(path).with_context(|| {
+                    format!("failed to read HTTP error template {}", path.display())
+                })?;
+                Ok(pages.with_html_template(template))
+            }
+            None => Ok(pages),
+        }
+    }
+}
+
+/// Optional behavior of the plugins of the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// Publish messages and timers that components failed to handle on
+    /// `<subject>.<workload_id>`
+    pub dead_letter_subject: Option<String>,
+    /// Store messages and timers that components failed to handle in this
+    /// object store bucket, unless `dead_letter_subject` is set
+    pub dead_letter_bucket: Option<String>,
+    /// Prefix of dead letters stored in the dead letter bucket
+    pub dead_letter_prefix: String,
+    /// Deduplicate HTTP requests and messages by idempotency key across
+    /// hosts, using this key-value bucket
+    pub idempotency_bucket: Option<String>,
+    /// Enable WASI WebGPU support, except on Windows
+    pub wasi_webgpu: bool,
+    /// Directory of manifests of plugin processes to start with the host.
+    /// No plugin processes are started unless it is set.
+    pub dir: Option<PathBuf>,
+    /// Forward calls to interfaces no plugin provides to other hosts of the
+    /// cluster providing them
+    pub forward_calls: bool,
+    /// Values set in the `wasi:config/store` configuration of every
+    /// component, over its own
+    pub config_overrides: BTreeMap<String, String>,
+    /// Address workloads accept TCP connections and UDP datagrams on, unless
+    /// they set their own. Defaults to all IPv4 addresses.
+    pub socket_address: Option<IpAddr>,
+    /// Lowest and highest port workloads may accept TCP connections and UDP
+    /// datagrams on. Defaults to the ports above 1023.
+    pub socket_ports: Option<[u16; 2]>,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            dead_letter_subject: None,
+            dead_letter_bucket: None,
+            dead_letter_prefix: "dead-letters".to_string(),
+            idempotency_bucket: None,
+            wasi_webgpu: false,
+            dir: None,
+            forward_calls: false,
+            config_overrides: BTreeMap::new(),
+            socket_address: None,
+            socket_ports: None,
+        }
+    }
+}
+
+impl PluginsConfig {
+    /// Returns the plugin serving TCP connections and UDP datagrams to
+    /// workloads, held to the connection quotas of `limits`.
+    ///
+    /// # Errors
+    /// Returns an error if the lowest socket port is above the highest.
+    pub fn sockets(&self, limits: &HostLimits) -> anyhow::Result<WashSockets> {
+        let mut sockets = WashSockets::default().with_limits(limits);
+        if let Some(address) = self.socket_address {
+            sockets = sockets.with_address(address);
+        }
+        if let Some([min, max]) = self.socket_ports {
+            ensure!(
+                min <= max,
+                "socket_ports must be the lowest and the highest port, got {min} and {max}"
+            );
+            sockets = sockets.with_ports(min..=max);
+        }
+        Ok(sockets)
+    }
+}
+
+/// Webhooks receiving workload lifecycle events
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub urls: Vec<String>,
+    /// Sign webhook requests with an HMAC-SHA256 of this secret
+    pub secret: Option<String>,
+    /// Only send these lifecycle events. Defaults to all events.
+    pub events: Vec<LifecycleEventType>,
+}
+
+/// Pushing metrics to an OTLP collector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpMetricsConfig {
+    /// URL of the OTLP/gRPC collector, e.g. `http://localhost:4317`
+    pub endpoint: Option<String>,
+    /// How often, in seconds, metrics are pushed
+    pub interval_secs: u64,
+}
+
+impl Default for OtlpMetricsConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+/// Exporting traces of HTTP requests to an OTLP collector, see
+/// [`wash_runtime::host::otlp_tracing`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpTracingConfig {
+    /// URL of the OTLP/gRPC collector, e.g. `http://localhost:4317`
+    pub endpoint: Option<String>,
+    /// Fraction of the traces starting at the host that are sampled
+    pub sampling_ratio: f64,
+}
+
+impl Default for OtlpTracingConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            sampling_ratio: 1.0,
+        }
+    }
+}
+
+/// Serving metrics to Prometheus, see [`wash_runtime::host::prometheus`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Address of the listener serving `GET /metrics`, separate from the
+    /// HTTP listeners of workloads
+    pub addr: Option<SocketAddr>,
+}
+
+/// Usage metering of invocations, see [`wash_runtime::host::metering`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeteringConfig {
+    /// Meter invocations. Implied by a CSV file or webhook to export usage to.
+    pub enabled: bool,
+    /// Also meter the fuel guests consume, which makes them run slower
+    pub fuel: bool,
+    /// CSV file usage reports are appended to
+    pub csv_file: Option<PathBuf>,
+    /// URLs usage reports are POSTed to, signed like lifecycle webhooks
+    pub webhook_urls: Vec<String>,
+    /// How often, in seconds, usage is exported
+    pub interval_secs: u64,
+}
+
+impl MeteringConfig {
+    /// Returns whether invocations are metered.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled || self.csv_file.is_some() || !self.webhook_urls.is_empty()
+    }
+}
+
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fuel: false,
+            csv_file: None,
+            webhook_urls: Vec::new(),
+            interval_secs: DEFAULT_EXPORT_INTERVAL.as_secs(),
+        }
+    }
+}
+
+/// TLS of HTTP listeners and outgoing HTTP requests of components
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub crypto_provider: TlsCryptoProvider,
+    pub min_version: TlsVersion,
+}
+
+/// Authentication of runtime API requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Require a token issued with this secret on every runtime API request
+    pub api_token_secret: Option<String>,
+    /// Claims granted to gRPC clients by the SHA-256 fingerprint of their
+    /// certificate, see [`wash_runtime::washlet::grpc`]
+    pub client_identities: BTreeMap<String, TokenClaims>,
+}
+
+impl AuthConfig {
+    /// Returns the claims granted to gRPC client certificates.
+    pub fn identities(&self) -> ClientIdentities {
+        self.client_identities.iter().fold(
+            ClientIdentities::new(),
+            |identities, (fingerprint, claims)| {
+                identities.with_certificate(fingerprint, claims.clone())
+            },
+        )
+    }
+}
+
+/// Serving the runtime API over gRPC, see [`wash_runtime::washlet::grpc`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// Address of the listener serving the runtime API over gRPC
+    pub addr: Option<SocketAddr>,
+    /// PEM file of the certificate chain the listener serves
+    pub tls_cert: Option<PathBuf>,
+    /// PEM file of the private key of `tls_cert`
+    pub tls_key: Option<PathBuf>,
+    /// PEM file of the CAs client certificates must be issued by
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+impl GrpcConfig {
+    /// Returns the gRPC API to serve, if any.
+    ///
+    /// # Errors
+    /// Returns an error if the TLS configuration is incomplete.
+    pub fn api(&self) -> anyhow::Result<Option<GrpcApi>> {
+        let Some(addr) = self.addr else {
+            return Ok(None);
+        };
+        let api = GrpcApi::new(addr);
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                let mut tls = GrpcTls::new(cert, key);
+                if let Some(client_ca) = &self.tls_client_ca {
+                    tls = tls.with_client_ca(client_ca);
+                }
+                Ok(Some(api.with_tls(tls)))
+            }
+            (None, None) if self.tls_client_ca.is_none() => Ok(Some(api)),
+            (None, None) => bail!("gRPC tls_client_ca requires tls_cert and tls_key"),
+            _ => bail!("gRPC tls_cert and tls_key must be set together"),
+        }
+    }
+}
+
+/// Providers of the secrets workloads reference with `${secret:provider:NAME}`,
+/// see [`wash_runtime::host::secrets`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    /// Reads secrets from the environment variables of the host with this
+    /// prefix, as provider `env`
+    pub env_prefix: Option<String>,
+    /// Reads secrets from the files in this directory, as provider `file`
+    pub dir: Option<PathBuf>,
+    /// Reads secrets from HashiCorp Vault, as provider `vault`
+    pub vault: Option<VaultSecretsConfig>,
+    /// Reads secrets from AWS Secrets Manager, as provider `aws`
+    pub aws: Option<AwsSecretsConfig>,
+    /// Provider of the references that don't name one
+    pub default: Option<String>,
+    /// Environment variables of the host workloads may reference with
+    /// `${env:NAME}`, by name or by a prefix ending in `*`. Workloads
+    /// referencing any other variable fail to start.
+    pub env_allowlist: Vec<String>,
+}
+
+/// HashiCorp Vault as a secret provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultSecretsConfig {
+    /// Address of Vault, e.g. `https://vault.internal:8200`
+    pub addr: String,
+    /// Token to read secrets with, `VAULT_TOKEN` when unset
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Mount of the KV version 2 engine, `secret` when unset
+    #[serde(default)]
+    pub mount: Option<String>,
+    /// Vault Enterprise namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+/// AWS Secrets Manager as a secret provider. Credentials are read from
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AwsSecretsConfig {
+    pub region: String,
+    /// Compatible endpoint to use instead of AWS, e.g. LocalStack
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+impl SecretsConfig {
+    /// Returns the environment variables workloads may reference.
+    pub fn env_allowlist(&self) -> EnvAllowlist {
+        EnvAllowlist::new(&self.env_allowlist)
+    }
+
+    /// Returns the secret store of the configured providers, if any.
+    ///
+    /// # Errors
+    /// Returns an error if a provider lacks its credentials or the default
+    /// provider isn't configured.
+    pub fn store(&self) -> anyhow::Result<Option<Arc<dyn SecretStore>>> {
+        let mut providers = SecretProviders::new();
+        if let Some(prefix) = &self.env_prefix {
+            providers = providers.with_provider("env", Arc::new(EnvSecrets::with_prefix(prefix)));
+        }
+        if let Some(dir) = &self.dir {
+            providers = providers.with_provider("file", Arc::new(FileSecrets::new(dir)));
+        }
+        if let Some(vault) = &self.vault {
+            let token = match &vault.token {
+                Some(token) => token.clone(),
+                None => std::env::var("VAULT_TOKEN")
+                    .context("secrets.vault.token or VAULT_TOKEN must be set")?,
+            };
+            let mut store = VaultSecrets::new(&vault.addr, token);
+            if let Some(mount) = &vault.mount {
+                store = store.with_mount(mount);
+            }
+            if let Some(namespace) = &vault.namespace {
+                store = store.with_namespace(namespace);
+            }
+            providers = providers.with_provider("vault", Arc::new(store));
+        }
+        if let Some(aws) = &self.aws {
+            let credentials = wash_runtime::plugin::blobstore::s3::S3Credentials::from_env()
+                .context("invalid AWS Secrets Manager credentials")?;
+            let mut store = AwsSecretsManager::new(credentials, &aws.region);
+            if let Some(endpoint) = &aws.endpoint {
+                store = store.with_endpoint(endpoint);
+            }
+            providers = providers.with_provider("aws", Arc::new(store));
+        }
+        if let Some(default) = &self.default {
+            let configured = match default.as_str() {
+                "env" => self.env_prefix.is_some(),
+                "file" => self.dir.is_some(),
+                "vault" => self.vault.is_some(),
+                "aws" => self.aws.is_some(),
+                _ => false,
+            };
+            if !configured {
+                bail!("default secret provider '{default}' is not configured");
+            }
+            providers = providers.with_default(default);
+        }
+        Ok((!providers.is_empty()).then(|| Arc::new(providers) as Arc<dyn SecretStore>))
+    }
+}
+
+/// Attestation reports of the host
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttestationConfig {
+    /// File keeping the identity key attestation reports are signed with
+    pub identity_file: Option<PathBuf>,
+    /// Include evidence from the trusted execution environment (configfs-tsm)
+    /// the host runs in, on Linux
+    pub tee_evidence: bool,
+}
+
+impl HostConfig {
+    /// Loads the configuration file at `path` over `base`, usually the
+    /// configuration given by flags. The format follows the extension of the
+    /// file: `.toml`, `.yaml`, `.yml` or `.json`.
+    pub fn load(base: &HostConfig, path: &Path) -> anyhow::Result<HostConfig> {
+        anyhow::ensure!(
+            path.is_file(),
+            "host configuration file {} does not exist",
+            path.display()
+        );
+        let figment = Figment::from(Serialized::defaults(base));
+        let figment = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => figment.merge(Toml::file_exact(path)),
+            Some("yaml" | "yml") => figment.merge(Yaml::file_exact(path)),
+            Some("json") => figment.merge(Json::file_exact(path)),
+            _ => bail!(
+                "unsupported host configuration file {}, expected .toml, .yaml or .json",
+                path.display()
+            ),
+        };
+        figment
+            .extract()
+            .with_context(|| format!("failed to load host configuration {}", path.display()))
+    }
+
+    /// Returns the part of the configuration that can change while the host
+    /// runs.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP TLS configuration is incomplete.
+    pub fn live(&self) -> anyhow::Result<LiveConfig> {
+        let mut live = LiveConfig::new(self.limits.clone());
+        if let Some(secret) = &self.auth.api_token_secret {
+            live = live.with_api_tokens(ApiTokens::new(secret.as_bytes()));
+        }
+        // A host started without an HTTP server can't get one
+        if self.http.addr.is_some() {
+            live = live.with_http_addrs(self.http.addrs());
+        }
+        if let Some(tls) = self.http.tls_files()? {
+            live = live.with_http_tls(tls);
+        }
+        Ok(live)
+    }
+}
+
+/// Reloads a host configuration file
+pub struct HostConfigFile {
+    path: PathBuf,
+    base: HostConfig,
+    /// The configuration the host started with
+    initial: HostConfig,
+}
+
+impl HostConfigFile {
+    pub fn new(path: impl Into<PathBuf>, base: HostConfig, initial: HostConfig) -> Self {
+        Self {
+            path: path.into(),
+            base,
+            initial,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigSource for HostConfigFile {
+    async fn load(&self) -> anyhow::Result<LiveConfig> {
+        let config = HostConfig::load(&self.base, &self.path)?;
+        let live = config.live()?;
+
+        // Warn about changes that the reload won't apply
+        let mut restart_only = config;
+        restart_only.limits = self.initial.limits.clone();
+        restart_only.auth = self.initial.auth.clone();
+        restart_only.http.addr = self.initial.http.addr;
+        restart_only.http.extra_addrs = self.initial.http.extra_addrs.clone();
+        restart_only.http.tls_cert = self.initial.http.tls_cert.clone();
+        restart_only.http.tls_key = self.initial.http.tls_key.clone();
+        restart_only.http.tls_ca = self.initial.http.tls_ca.clone();
+        restart_only.http.tls_sni = self.initial.http.tls_sni.clone();
+        if restart_only != self.initial {
+            warn!(
+                path = %self.path.display(),
+                "host configuration changed beyond limits, auth, HTTP listeners and TLS, restart the host to apply all changes"
+            );
+        }
+        Ok(live)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wash_runtime::host::http::SigningMethod;
+    use wash_runtime::types::DeviceClass;
+    use wash_runtime::washlet::auth::ApiVerb;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = HostConfig {
+            host_group: "from-flags".to_string(),
+            data_nats_url: "nats://data:4222".to_string(),
+            ..Default::default()
+        };
+
+        let toml = dir.path().join("host.toml");
+        std::fs::write(
+            &toml,
+            r#"
+host_group = "edge"
+drain_timeout_secs = 0
+invocation_timeout_ms = 5000
+cpu_fuel_rate = 1000000000
+
+[labels]
+region = "eu-west-1"
+
+[http]
+addr = "0.0.0.0:8000"
+extra_addrs = ["[::1]:8000"]
+trusted_proxies = ["10.0.0.0/8"]
+tls_cert = "/etc/wash/tls.crt"
+tls_key = "/etc/wash/tls.key"
+tls_sni = [{ host = "api.example.com", cert = "/etc/wash/api.crt", key = "/etc/wash/api.key" }]
+error_format = "json"
+error_request_id = true
+grpc_web = true
+health_path = "/healthz"
+egress_default = "deny"
+
+[http.access_log]
+tracing = true
+file = "/var/log/wash/access.log"
+file_max_files = 2
+
+[http.resolver]
+nameservers = ["1.1.1.1:53"]
+
+[http.connection_pool]
+max_connections_per_host = 64
+read_timeout_ms = 30000
+
+[http.request_limits]
+max_body_bytes = 1048576
+max_headers = 50
+body_read_timeout_ms = 5000
+
+[[http.signing]]
+hosts = ["api.example.com"]
+method = "hmac"
+secret = "s3cr3t"
+
+[[http.outbound]]
+hosts = ["*.googleapis.com"]
+max_attempts = 3
+failure_threshold = 5
+
+[execution_pools.latency]
+cpus = "8-11,14"
+numa_node = 1
+
+[[devices]]
+class = "serial"
+name = "gps"
+path = "/dev/ttyUSB0"
+
+[plugins]
+forward_calls = true
+socket_ports = [10000, 10999]
+
+[plugins.config_overrides]
+region = "eu-west-1"
+
+[cgroups]
+root = "/sys/fs/cgroup/wash"
+
+[component_cache]
+dir = "/var/cache/wash/components"
+
+[crash_loop]
+threshold = 3
+stop_after = 20
+
+[metering]
+fuel = true
+csv_file = "/var/lib/wash/usage.csv"
+
+[metrics]
+addr = "127.0.0.1:9100"
+
+[otlp_tracing]
+endpoint = "http://collector:4317"
+
+[tls]
+min_version = "1.3"
+
+[grpc]
+addr = "127.0.0.1:7443"
+tls_cert = "/etc/wash/grpc.crt"
+tls_key = "/etc/wash/grpc.key"
+tls_client_ca = "/etc/wash/clients.crt"
+
+[auth.client_identities."AB:CD"]
+subject = "ci"
+namespaces = ["team-a"]
+verbs = ["deployer", "viewer"]
+
+[secrets]
+dir = "/run/secrets"
+default = "file"
+env_allowlist = ["DB_HOST", "APP_*"]
+
+[secrets.vault]
+addr = "https://vault.internal:8200"
+token = "root"
+
+[limits]
+max_workloads = 5
+
+[limits.namespaces.team-a]
+max_memory_mb = 256
+"#,
+        )
+        .unwrap();
+        let config = HostConfig::load(&base, &toml).unwrap();
+        assert_eq!(config.host_group, "edge");
+        // Flags not set in the file are kept
+        assert_eq!(config.data_nats_url, "nats://data:4222");
+        assert_eq!(config.drain_timeout_secs, 0);
+        assert_eq!(config.invocation_timeout_ms, Some(5000));
+        assert_eq!(config.cpu_fuel_rate, Some(1_000_000_000));
+        assert_eq!(config.labels["region"], "eu-west-1");
+        assert_eq!(config.http.addr, Some("0.0.0.0:8000".parse().unwrap()));
+        assert_eq!(config.http.extra_addrs, vec!["[::1]:8000".parse().unwrap()]);
+        assert_eq!(
+            config.http.trusted_proxies,
+            vec!["10.0.0.0/8".parse().unwrap()]
+        );
+        assert_eq!(config.http.slow_request_ms, 1000);
+        assert_eq!(
+            config.http.error_pages().unwrap(),
+            ErrorPages::new(ErrorFormat::Json).with_request_id(true)
+        );
+        assert!(config.http.grpc_web);
+        assert_eq!(config.http.health_path.as_deref(), Some("/healthz"));
+        assert!(config.http.access_log.tracing);
+        assert!(!config.http.access_log.stdout);
+        assert_eq!(
+            config.http.access_log.file.as_deref(),
+            Some(Path::new("/var/log/wash/access.log"))
+        );
+        assert_eq!(config.http.access_log.file_max_files, 2);
+        assert_eq!(config.http.egress_default, EgressDefault::Deny);
+        assert_eq!(
+            config.http.connection_pool,
+            ConnectionPoolConfig::default()
+                .with_max_connections_per_host(64)
+                .with_read_timeout(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            config.http.tls_files().unwrap(),
+            Some(
+                TlsFiles::new("/etc/wash/tls.crt", "/etc/wash/tls.key").with_sni(SniFiles::new(
+                    "api.example.com",
+                    "/etc/wash/api.crt",
+                    "/etc/wash/api.key"
+                ))
+            )
+        );
+        let live = config.live().unwrap();
+        assert_eq!(
+            live.http_addrs,
+            Some(vec![
+                "0.0.0.0:8000".parse().unwrap(),
+                "[::1]:8000".parse().unwrap()
+            ])
+        );
+        assert_eq!(
+            config.http.resolver.nameservers,
+            vec!["1.1.1.1:53".parse().unwrap()]
+        );
+        assert_eq!(config.http.resolver.max_cache_secs, 300);
+        assert_eq!(
+            config.http.signing,
+            vec![SigningRule {
+                hosts: vec!["api.example.com".to_string()],
+                namespaces: Vec::new(),
+                method: SigningMethod::Hmac {
+                    secret: "s3cr3t".to_string(),
+                    header: "x-wasmcloud-signature".to_string(),
+                },
+            }]
+        );
+        assert_eq!(
+            config.http.request_limits,
+            RequestLimits {
+                max_headers: Some(50),
+                ..RequestLimits::default()
+                    .with_max_body_bytes(1048576)
+                    .with_body_read_timeout(std::time::Duration::from_secs(5))
+            }
+        );
+        assert_eq!(
+            config.http.outbound,
+            vec![ResilienceRule {
+                max_attempts: 3,
+                failure_threshold: Some(5),
+                ..ResilienceRule::new(["*.googleapis.com"])
+            }]
+        );
+        assert_eq!(
+            config.execution_pools["latency"],
+            ExecutionPoolConfig::new("8-11,14".parse().unwrap()).with_numa_node(1)
+        );
+        assert_eq!(
+            config.devices,
+            vec![Device::new(DeviceClass::Serial, "/dev/ttyUSB0").with_name("gps")]
+        );
+        assert!(config.plugins.forward_calls);
+        assert_eq!(config.plugins.socket_ports, Some([10000, 10999]));
+        assert_eq!(config.plugins.config_overrides["region"], "eu-west-1");
+        assert_eq!(config.plugins.dead_letter_prefix, "dead-letters");
+        assert_eq!(
+            config.cgroups.root,
+            Some(PathBuf::from("/sys/fs/cgroup/wash"))
+        );
+        assert_eq!(config.cgroups.host_memory_reserve_mb, 256);
+        assert_eq!(
+            config.component_cache,
+            ComponentCacheConfig {
+                dir: Some(PathBuf::from("/var/cache/wash/components")),
+                max_size_mb: 2048,
+            }
+        );
+        assert_eq!(
+            config.crash_loop.policy(),
+            Some(CrashLoopPolicy {
+                threshold: 3,
+                stop_after: Some(20),
+                ..Default::default()
+            })
+        );
+        assert_eq!(HostConfig::default().crash_loop.policy(), None);
+        assert_eq!(config.tls.min_version, TlsVersion::Tls13);
+        assert!(config.metering.is_enabled());
+        assert!(config.metering.fuel);
+        assert_eq!(config.metering.interval_secs, 60);
+        assert_eq!(config.metrics.addr, Some("127.0.0.1:9100".parse().unwrap()));
+        assert_eq!(
+            config.grpc.api().unwrap(),
+            Some(
+                GrpcApi::new("127.0.0.1:7443".parse().unwrap()).with_tls(
+                    GrpcTls::new("/etc/wash/grpc.crt", "/etc/wash/grpc.key")
+                        .with_client_ca("/etc/wash/clients.crt")
+                )
+            )
+        );
+        assert_eq!(
+            config.auth.identities(),
+            ClientIdentities::new().with_certificate(
+                "abcd",
+                TokenClaims::new("ci")
+                    .with_namespace("team-a")
+                    .with_verb(ApiVerb::Deploy)
+                    .with_verb(ApiVerb::Read)
+            )
+        );
+        assert_eq!(config.secrets.dir, Some(PathBuf::from("/run/secrets")));
+        assert_eq!(
+            config
+                .secrets
+                .vault
+                .as_ref()
+                .map(|vault| vault.addr.as_str()),
+            Some("https://vault.internal:8200")
+        );
+        assert!(config.secrets.store().unwrap().is_some());
+        let env_allowlist = config.secrets.env_allowlist();
+        assert!(env_allowlist.allows("DB_HOST"));
+        assert!(env_allowlist.allows("APP_PORT"));
+        assert!(!env_allowlist.allows("VAULT_TOKEN"));
+        assert!(
+            SecretsConfig {
+                default: Some("aws".to_string()),
+                ..config.secrets.clone()
+            }
+            .store()
+            .is_err()
+        );
+        assert_eq!(
+            config.otlp_tracing,
+            OtlpTracingConfig {
+                endpoint: Some("http://collector:4317".to_string()),
+                sampling_ratio: 1.0,
+            }
+        );
+        assert_eq!(config.limits.max_workloads(), Some(5));
+        assert_eq!(
+            config.limits.namespace("team-a").unwrap().max_memory_mb(),
+            Some(256)
+        );
+
+        let yaml = dir.path().join("host.yaml");
+        std::fs::write(&yaml, "auth:\n  api_token_secret: secret\n").unwrap();
+        let config = HostConfig::load(&base, &yaml).unwrap();
+        assert_eq!(config.host_group, "from-flags");
+        assert!(config.live().unwrap().api_tokens.is_some());
+
+        assert!(HostConfig::load(&base, &dir.path().join("host.ini")).is_err());
+        assert!(HostConfig::load(&base, &dir.path().join("missing.toml")).is_err());
+    }
+}
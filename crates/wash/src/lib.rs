@@ -9,11 +9,17 @@ pub mod cli;
 pub mod component_build;
 /// Configuration management for wash
 pub mod config;
+/// Configuration file of `wash host`
+pub mod host_config;
 /// Component inspection and analysis
 pub mod inspect;
 /// Create new wash projects
 pub mod new;
 /// Plugin management for wash
 pub mod plugin;
+/// Generate projects wired to host interfaces
+pub mod scaffold;
+/// Running `wash host` under systemd or as a Windows service
+pub mod service;
 /// Manage WebAssembly Interface Types (WIT) for wash components
 pub(crate) mod wit;
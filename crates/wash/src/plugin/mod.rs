@@ -114,12 +114,14 @@ impl PluginManager {
                     local_resources: LocalResources::default(),
                     pool_size: 1,
                     max_invocations: 1,
+                    ..Default::default()
                 }],
                 host_interfaces: vec![
                     WitInterface::from("wasmcloud:wash/types@0.0.2"),
                     WitInterface::from("wasi:config/store@0.2.0-rc.1"),
                 ],
                 volumes: vec![],
+                outbound: Default::default(),
             };
 
             let res = ctx
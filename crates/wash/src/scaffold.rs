@@ -0,0 +1,654 @@
+//! Project scaffolds wired to the interfaces of wash hosts
+//!
+//! Unlike templates, which are cloned from a git repository, scaffolds are
+//! generated by wash itself. Each [`ScaffoldKind`] targets one host plugin: the
+//! generated WIT world only uses interfaces the host provides, and the generated
+//! manifest binds them, so the project builds with `wash build` and deploys
+//! without further changes.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context as _, bail};
+use tracing::{debug, instrument};
+
+use crate::component_build::{BuildConfig, RustBuildConfig, TinyGoBuildConfig};
+use crate::config::{Config, local_config_path, save_config};
+
+/// Kinds of projects wash can scaffold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaffoldKind {
+    /// A component serving HTTP requests through `wasi:http`
+    HttpComponent,
+    /// A component handling messages through `wasmcloud:messaging`
+    MessagingComponent,
+    /// A service running a job on an interval, configured through `wasi:config`
+    CronJob,
+}
+
+impl ScaffoldKind {
+    /// The name of the WIT world of the scaffold
+    pub fn world(&self) -> &'static str {
+        match self {
+            ScaffoldKind::HttpComponent => "http-component",
+            ScaffoldKind::MessagingComponent => "messaging-component",
+            ScaffoldKind::CronJob => "cron-job",
+        }
+    }
+}
+
+/// Languages wash can scaffold projects in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScaffoldLanguage {
+    #[default]
+    Rust,
+    TinyGo,
+}
+
+impl FromStr for ScaffoldLanguage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rust" => Ok(ScaffoldLanguage::Rust),
+            "tinygo" | "go" => Ok(ScaffoldLanguage::TinyGo),
+            _ => bail!("unsupported scaffold language '{s}', expected rust or tinygo"),
+        }
+    }
+}
+
+/// A file of a scaffolded project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaffoldFile {
+    /// Path relative to the project directory
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// A project to scaffold
+#[derive(Debug, Clone)]
+pub struct Scaffold {
+    name: String,
+    kind: ScaffoldKind,
+    language: ScaffoldLanguage,
+}
+
+impl Scaffold {
+    /// Create a scaffold for the project `name`, which must be kebab-case so it
+    /// can be used as package, WIT and workload name alike.
+    pub fn new(name: impl Into<String>, kind: ScaffoldKind) -> anyhow::Result<Self> {
+        let name = name.into();
+        validate_name(&name)?;
+        Ok(Self {
+            name,
+            kind,
+            language: ScaffoldLanguage::default(),
+        })
+    }
+
+    pub fn with_language(mut self, language: ScaffoldLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The files of the project, excluding its `.wash/config.json`
+    pub fn files(&self) -> Vec<ScaffoldFile> {
+        let mut files = vec![
+            self.file("wit/world.wit", self.world_wit()),
+            self.file("manifests/workloaddeployment.yaml", self.manifest()),
+        ];
+        if self.kind == ScaffoldKind::MessagingComponent {
+            // wasmcloud:messaging is not published to a registry
+            files.push(self.file(
+                "wit/deps/wasmcloud-messaging-0.2.0/package.wit",
+                MESSAGING_WIT,
+            ));
+        }
+        match self.language {
+            ScaffoldLanguage::Rust => {
+                files.push(self.file("Cargo.toml", self.cargo_toml()));
+                let source = match self.kind {
+                    ScaffoldKind::HttpComponent => ("src/lib.rs", RUST_HTTP),
+                    ScaffoldKind::MessagingComponent => ("src/lib.rs", RUST_MESSAGING),
+                    ScaffoldKind::CronJob => ("src/main.rs", RUST_CRON),
+                };
+                files.push(self.file(source.0, source.1));
+                files.push(self.file(".gitignore", "target/\n"));
+            }
+            ScaffoldLanguage::TinyGo => {
+                files.push(self.file("go.mod", GO_MOD));
+                let source = match self.kind {
+                    ScaffoldKind::HttpComponent => GO_HTTP,
+                    ScaffoldKind::MessagingComponent => GO_MESSAGING,
+                    ScaffoldKind::CronJob => GO_CRON,
+                };
+                files.push(self.file("main.go", source));
+                files.push(self.file(".gitignore", "build/\ngen/\n"));
+            }
+        }
+        files
+    }
+
+    /// The project configuration building the scaffold
+    pub fn config(&self) -> Config {
+        let build = match self.language {
+            ScaffoldLanguage::Rust => BuildConfig {
+                rust: Some(RustBuildConfig {
+                    release: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ScaffoldLanguage::TinyGo => BuildConfig {
+                tinygo: Some(TinyGoBuildConfig {
+                    wit_world: Some(self.kind.world().to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        };
+        Config {
+            build: Some(build),
+            ..Default::default()
+        }
+    }
+
+    /// Write the project to `output_dir`, which must not exist yet.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn generate(&self, output_dir: &Path) -> anyhow::Result<()> {
+        if tokio::fs::metadata(output_dir).await.is_ok() {
+            bail!("Output directory already exists: {}", output_dir.display());
+        }
+        for file in self.files() {
+            let path = output_dir.join(&file.path);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            debug!(path = %path.display(), "writing scaffold file");
+            tokio::fs::write(&path, file.contents)
+                .await
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        save_config(&self.config(), &local_config_path(output_dir)).await
+    }
+
+    fn file(&self, path: &str, template: &str) -> ScaffoldFile {
+        ScaffoldFile {
+            path: PathBuf::from(path),
+            contents: template
+                .replace("{{name}}", &self.name)
+                .replace("{{world}}", self.kind.world()),
+        }
+    }
+
+    fn world_wit(&self) -> &'static str {
+        match (self.kind, self.language) {
+            (ScaffoldKind::HttpComponent, ScaffoldLanguage::Rust) => WIT_HTTP,
+            (ScaffoldKind::HttpComponent, ScaffoldLanguage::TinyGo) => WIT_HTTP_GO,
+            (ScaffoldKind::MessagingComponent, ScaffoldLanguage::Rust) => WIT_MESSAGING,
+            (ScaffoldKind::MessagingComponent, ScaffoldLanguage::TinyGo) => WIT_MESSAGING_GO,
+            (ScaffoldKind::CronJob, ScaffoldLanguage::Rust) => WIT_CRON,
+            (ScaffoldKind::CronJob, ScaffoldLanguage::TinyGo) => WIT_CRON_GO,
+        }
+    }
+
+    fn cargo_toml(&self) -> &'static str {
+        match self.kind {
+            ScaffoldKind::HttpComponent => CARGO_HTTP,
+            ScaffoldKind::MessagingComponent => CARGO_MESSAGING,
+            ScaffoldKind::CronJob => CARGO_CRON,
+        }
+    }
+
+    fn manifest(&self) -> &'static str {
+        match self.kind {
+            ScaffoldKind::HttpComponent => MANIFEST_HTTP,
+            ScaffoldKind::MessagingComponent => MANIFEST_MESSAGING,
+            ScaffoldKind::CronJob => MANIFEST_CRON,
+        }
+    }
+}
+
+/// Check that `name` is lowercase kebab-case, e.g. `my-component`
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && name.split('-').all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_lowercase())
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+    if !valid {
+        bail!("invalid project name '{name}', expected lowercase kebab-case like 'my-component'");
+    }
+    Ok(())
+}
+
+const WIT_HTTP: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    export wasi:http/incoming-handler@0.2.2;
+}
+"#;
+
+const WIT_HTTP_GO: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    include wasi:http/proxy@0.2.0;
+}
+"#;
+
+const WIT_MESSAGING: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    import wasmcloud:messaging/consumer@0.2.0;
+    export wasmcloud:messaging/handler@0.2.0;
+}
+"#;
+
+const WIT_MESSAGING_GO: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    include wasi:cli/imports@0.2.0;
+    import wasmcloud:messaging/consumer@0.2.0;
+    export wasmcloud:messaging/handler@0.2.0;
+}
+"#;
+
+const WIT_CRON: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    import wasi:config/store@0.2.0-rc.1;
+    import wasi:logging/logging@0.1.0-draft;
+}
+"#;
+
+const WIT_CRON_GO: &str = r#"package wasmcloud:{{name}};
+
+world {{world}} {
+    include wasi:cli/command@0.2.0;
+    import wasi:config/store@0.2.0-rc.1;
+    import wasi:logging/logging@0.1.0-draft;
+}
+"#;
+
+const MESSAGING_WIT: &str = r#"package wasmcloud:messaging@0.2.0;
+
+/// Types common to message broker interactions
+interface types {
+  /// A message sent to or received from a broker
+  record broker-message {
+    subject: string,
+    body: list<u8>,
+    reply-to: option<string>,
+  }
+}
+
+interface handler {
+  use types.{broker-message};
+
+  /// Callback handled to invoke a function when a message is received from a subscription
+  handle-message: func(msg: broker-message) -> result<_, string>;
+}
+
+interface consumer {
+  use types.{broker-message};
+
+  /// Perform a request operation on a subject
+  request: func(subject: string, body: list<u8>, timeout-ms: u32) -> result<broker-message, string>;
+
+  /// Publish a message to a subject without awaiting a response
+  publish: func(msg: broker-message) -> result<_, string>;
+}
+"#;
+
+const CARGO_HTTP: &str = r#"[package]
+name = "{{name}}"
+edition = "2024"
+version = "0.1.0"
+
+[workspace]
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+wasmcloud-component = "0.2.0"
+"#;
+
+const CARGO_MESSAGING: &str = r#"[package]
+name = "{{name}}"
+edition = "2024"
+version = "0.1.0"
+
+[workspace]
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+wit-bindgen = "0.46.0"
+"#;
+
+const CARGO_CRON: &str = r#"[package]
+name = "{{name}}"
+edition = "2024"
+version = "0.1.0"
+
+[workspace]
+
+[dependencies]
+wit-bindgen = "0.46.0"
+"#;
+
+const RUST_HTTP: &str = r#"use wasmcloud_component::http;
+
+struct Component;
+
+impl http::Server for Component {
+    fn handle(
+        _request: http::IncomingRequest,
+    ) -> http::Result<http::Response<impl http::OutgoingBody>> {
+        Ok(http::Response::new("Hello from {{name}}!\n"))
+    }
+}
+
+http::export!(Component);
+"#;
+
+const RUST_MESSAGING: &str = r#"wit_bindgen::generate!({
+    world: "{{world}}"
+});
+
+use exports::wasmcloud::messaging::handler::Guest;
+use wasmcloud::messaging::consumer;
+use wasmcloud::messaging::types::BrokerMessage;
+
+struct Component;
+
+impl Guest for Component {
+    /// Replies to requests with the body they were sent
+    fn handle_message(msg: BrokerMessage) -> Result<(), String> {
+        let Some(reply_to) = msg.reply_to else {
+            return Ok(());
+        };
+        consumer::publish(&BrokerMessage {
+            subject: reply_to,
+            body: msg.body,
+            reply_to: None,
+        })
+    }
+}
+
+export!(Component);
+"#;
+
+const RUST_CRON: &str = r#"wit_bindgen::generate!({
+    world: "{{world}}"
+});
+
+use std::time::Duration;
+
+use wasi::config::store;
+use wasi::logging::logging::{Level, log};
+
+/// Used when the `interval_secs` config is not set
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+
+fn main() {
+    let interval = store::get("interval_secs")
+        .ok()
+        .flatten()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+    loop {
+        if let Err(e) = run() {
+            log(Level::Error, "{{name}}", &e);
+        }
+        std::thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// The job, run once per interval
+fn run() -> Result<(), String> {
+    log(Level::Info, "{{name}}", "Hello from {{name}}!");
+    Ok(())
+}
+"#;
+
+const GO_MOD: &str = r#"module example.com/{{name}}
+
+go 1.24
+
+require (
+	go.bytecodealliance.org/cm v0.2.2
+	go.wasmcloud.dev/component v0.0.6
+)
+
+tool go.bytecodealliance.org/cmd/wit-bindgen-go
+"#;
+
+const GO_HTTP: &str = r#"//go:generate go tool wit-bindgen-go generate --world {{world}} --out gen ./wit
+package main
+
+import (
+	"fmt"
+	"net/http"
+
+	"go.wasmcloud.dev/component/net/wasihttp"
+)
+
+func init() {
+	wasihttp.HandleFunc(handler)
+}
+
+func handler(w http.ResponseWriter, r *http.Request) {
+	fmt.Fprintln(w, "Hello from {{name}}!")
+}
+
+func main() {}
+"#;
+
+const GO_MESSAGING: &str = r#"//go:generate go tool wit-bindgen-go generate --world {{world}} --out gen ./wit
+package main
+
+import (
+	"go.bytecodealliance.org/cm"
+
+	"example.com/{{name}}/gen/wasmcloud/messaging/consumer"
+	"example.com/{{name}}/gen/wasmcloud/messaging/handler"
+	"example.com/{{name}}/gen/wasmcloud/messaging/types"
+)
+
+type result = cm.Result[string, struct{}, string]
+
+func init() {
+	handler.Exports.HandleMessage = handleMessage
+}
+
+// handleMessage replies to requests with the body they were sent
+func handleMessage(msg types.BrokerMessage) result {
+	replyTo := msg.ReplyTo.Some()
+	if replyTo == nil {
+		return cm.OK[result](struct{}{})
+	}
+	return consumer.Publish(types.BrokerMessage{
+		Subject: *replyTo,
+		Body:    msg.Body,
+		ReplyTo: cm.None[string](),
+	})
+}
+
+func main() {}
+"#;
+
+const GO_CRON: &str = r#"//go:generate go tool wit-bindgen-go generate --world {{world}} --out gen ./wit
+package main
+
+import (
+	"strconv"
+	"time"
+
+	"example.com/{{name}}/gen/wasi/config/store"
+	"example.com/{{name}}/gen/wasi/logging/logging"
+)
+
+// Used when the interval_secs config is not set
+const defaultInterval = 60 * time.Second
+
+func main() {
+	interval := defaultInterval
+	if value := store.Get("interval_secs").OK(); value != nil {
+		if secs := value.Some(); secs != nil {
+			if n, err := strconv.Atoi(*secs); err == nil && n > 0 {
+				interval = time.Duration(n) * time.Second
+			}
+		}
+	}
+	for {
+		if err := run(); err != nil {
+			logging.Log(logging.LevelError, "{{name}}", err.Error())
+		}
+		time.Sleep(interval)
+	}
+}
+
+// run is the job, run once per interval
+func run() error {
+	logging.Log(logging.LevelInfo, "{{name}}", "Hello from {{name}}!")
+	return nil
+}
+"#;
+
+const MANIFEST_HTTP: &str = r#"apiVersion: runtime.wasmcloud.dev/v1alpha1
+kind: WorkloadDeployment
+metadata:
+  name: {{name}}
+spec:
+  replicas: 1
+  template:
+    spec:
+      components:
+        - name: {{name}}
+          image: localhost:5000/{{name}}:0.1.0
+      hostInterfaces:
+        - namespace: wasi
+          package: http
+          interfaces:
+            - incoming-handler
+          config:
+            host: localhost
+"#;
+
+const MANIFEST_MESSAGING: &str = r#"apiVersion: runtime.wasmcloud.dev/v1alpha1
+kind: WorkloadDeployment
+metadata:
+  name: {{name}}
+spec:
+  replicas: 1
+  template:
+    spec:
+      components:
+        - name: {{name}}
+          image: localhost:5000/{{name}}:0.1.0
+      hostInterfaces:
+        - namespace: wasmcloud
+          package: messaging
+          interfaces:
+            - consumer
+            - handler
+          config:
+            subscriptions: {{name}}.requests
+"#;
+
+const MANIFEST_CRON: &str = r#"apiVersion: runtime.wasmcloud.dev/v1alpha1
+kind: WorkloadDeployment
+metadata:
+  name: {{name}}
+spec:
+  replicas: 1
+  template:
+    spec:
+      service:
+        image: localhost:5000/{{name}}:0.1.0
+        maxRestarts: 3
+      hostInterfaces:
+        - namespace: wasi
+          package: config
+          interfaces:
+            - store
+          config:
+            interval_secs: "60"
+        - namespace: wasi
+          package: logging
+          interfaces:
+            - logging
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("my-component").is_ok());
+        assert!(validate_name("job2").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("My-Component").is_err());
+        assert!(validate_name("my_component").is_err());
+        assert!(validate_name("my--component").is_err());
+        assert!(validate_name("2-fast").is_err());
+    }
+
+    #[test]
+    fn test_files() {
+        let kinds = [
+            ScaffoldKind::HttpComponent,
+            ScaffoldKind::MessagingComponent,
+            ScaffoldKind::CronJob,
+        ];
+        for kind in kinds {
+            for language in [ScaffoldLanguage::Rust, ScaffoldLanguage::TinyGo] {
+                let scaffold = Scaffold::new("my-project", kind)
+                    .unwrap()
+                    .with_language(language);
+                let files = scaffold.files();
+                let world = files
+                    .iter()
+                    .find(|f| f.path == Path::new("wit/world.wit"))
+                    .expect("scaffold should have a WIT world");
+                assert!(
+                    world
+                        .contents
+                        .contains(&format!("world {} {{", kind.world()))
+                );
+                assert!(
+                    files.iter().all(|f| !f.contents.contains("{{")),
+                    "{kind:?} {language:?} has unrendered placeholders"
+                );
+                let manifest = files
+                    .iter()
+                    .find(|f| f.path == Path::new("manifests/workloaddeployment.yaml"))
+                    .expect("scaffold should have a manifest");
+                assert!(manifest.contents.contains("name: my-project"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("my-job");
+        let scaffold = Scaffold::new("my-job", ScaffoldKind::CronJob).unwrap();
+        scaffold.generate(&output_dir).await.unwrap();
+        assert!(output_dir.join("src/main.rs").is_file());
+        assert!(local_config_path(&output_dir).is_file());
+        assert!(scaffold.generate(&output_dir).await.is_err());
+    }
+}
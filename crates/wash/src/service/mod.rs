@@ -0,0 +1,16 @@
+//! Running `wash host` under an init system instead of in a terminal.
+//!
+//! - On Linux, [`systemd::Notifier`] reports readiness, reloads and shutdown
+//!   to systemd with `sd_notify` and pings its watchdog, for units with
+//!   `Type=notify` or `Type=notify-reload`.
+//! - On Windows, `wash host --windows-service` runs the host as a service of
+//!   the service control manager, see [`windows::Service`].
+//!
+//! On either, a stop request (`SIGTERM`, or a stop or shutdown control on
+//! Windows) drains the host before it stops: it rejects new workloads and
+//! waits for the requests in flight, for at most `--drain-timeout-secs`.
+
+#[cfg(target_os = "linux")]
+pub mod systemd;
+#[cfg(target_os = "windows")]
+pub mod windows;
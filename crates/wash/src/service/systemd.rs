@@ -0,0 +1,162 @@
+//! Notifying systemd of the state of `wash host` with `sd_notify`.
+//!
+//! systemd passes the socket to notify it on in `NOTIFY_SOCKET`, and the
+//! watchdog timeout in `WATCHDOG_USEC` when the unit sets `WatchdogSec`. A
+//! unit running a host could look like:
+//!
+//! ```ini
+//! [Service]
+//! Type=notify-reload
+//! ExecStart=/usr/local/bin/wash host --config /etc/wash/host.toml
+//! NotifyAccess=main
+//! WatchdogSec=30s
+//! # Longer than --drain-timeout-secs, so the host can drain and stop
+//! TimeoutStopSec=60s
+//! Delegate=yes
+//! ```
+//!
+//! With `Type=notify-reload`, `systemctl reload` sends `SIGHUP`, which
+//! reloads the host configuration, and `systemctl stop` sends `SIGTERM`,
+//! which drains the host and stops it.
+
+use std::os::linux::net::SocketAddrExt as _;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+/// Sends the state of the host to the service manager that started it.
+#[derive(Debug)]
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Returns a notifier for the socket in `NOTIFY_SOCKET`, or `None` if the
+    /// host was not started by systemd or the socket is invalid.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        let mut notifier = match Self::connect(&path) {
+            Ok(notifier) => notifier,
+            Err(e) => {
+                warn!(
+                    socket = path,
+                    "not notifying systemd, invalid NOTIFY_SOCKET: {e}"
+                );
+                return None;
+            }
+        };
+        // The watchdog may be meant for another process of the unit
+        let watchdog_pid = std::env::var("WATCHDOG_PID").ok();
+        if watchdog_pid.is_none_or(|pid| pid.parse() == Ok(std::process::id())) {
+            notifier.watchdog = std::env::var("WATCHDOG_USEC")
+                .ok()
+                .and_then(|usec| usec.parse().ok())
+                .filter(|usec| *usec > 0)
+                .map(Duration::from_micros);
+        }
+        Some(notifier)
+    }
+
+    /// Returns a notifier for the socket at `path`, a file system path or an
+    /// abstract socket name starting with `@`.
+    pub fn connect(path: &str) -> std::io::Result<Self> {
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            addr,
+            watchdog: None,
+        })
+    }
+
+    /// Tells systemd that the host is running.
+    pub fn ready(&self) {
+        self.notify("READY=1\nSTATUS=Running");
+    }
+
+    /// Tells systemd that the host reloads its configuration. It must be
+    /// followed by [`Notifier::ready`].
+    pub fn reloading(&self) {
+        self.notify(&format!(
+            "RELOADING=1\nSTATUS=Reloading\nMONOTONIC_USEC={}",
+            monotonic_usec()
+        ));
+    }
+
+    /// Tells systemd that the host drains and stops.
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1\nSTATUS=Draining");
+    }
+
+    /// Pings the watchdog of the unit every half of its timeout while the
+    /// host's runtime is responsive. Does nothing without a watchdog.
+    pub fn spawn_watchdog(self: &Arc<Self>) {
+        let Some(timeout) = self.watchdog else {
+            return;
+        };
+        debug!(?timeout, "pinging the systemd watchdog");
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timeout / 2);
+            loop {
+                interval.tick().await;
+                notifier.notify("WATCHDOG=1");
+            }
+        });
+    }
+
+    /// Sends `state`, logging failures, which systemd handles as the host
+    /// not notifying it.
+    fn notify(&self, state: &str) {
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.addr) {
+            warn!("failed to notify systemd: {e}");
+        }
+    }
+}
+
+/// Returns `CLOCK_MONOTONIC`, which systemd orders reloads by, in
+/// microseconds.
+fn monotonic_usec() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid timespec for clock_gettime to write to
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier::connect(path.to_str().unwrap()).unwrap();
+        notifier.ready();
+        notifier.reloading();
+        notifier.stopping();
+
+        let mut buf = [0; 256];
+        let n = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=Running");
+        let n = systemd.recv(&mut buf).unwrap();
+        let reloading = std::str::from_utf8(&buf[..n]).unwrap();
+        let monotonic = reloading
+            .strip_prefix("RELOADING=1\nSTATUS=Reloading\nMONOTONIC_USEC=")
+            .unwrap();
+        assert!(monotonic.parse::<u64>().unwrap() > 0);
+        let n = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"STOPPING=1\nSTATUS=Draining");
+    }
+}
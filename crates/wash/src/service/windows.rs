@@ -0,0 +1,229 @@
+//! Running `wash host` as a Windows service.
+//!
+//! The service control manager starts the host with `--windows-service`, e.g.
+//! after the service was created with:
+//!
+//! ```text
+//! sc.exe create wash binPath= "C:\wash\wash.exe host --windows-service --config C:\wash\host.toml" start= auto
+//! ```
+//!
+//! The host reports itself as starting until it runs, and drains and stops on
+//! the stop and shutdown controls. If it exits with an error, the service
+//! stops with a service-specific exit code, so recovery actions of the
+//! service apply.
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{SyncSender, sync_channel};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context as _, bail};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use windows_sys::Win32::Foundation::{
+    ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR,
+};
+use windows_sys::Win32::System::Services::{
+    RegisterServiceCtrlHandlerExW, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP,
+    SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING,
+    SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP_PENDING,
+    SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+    StartServiceCtrlDispatcherW,
+};
+
+/// Name of the service table entry, ignored for services in their own process
+const SERVICE_NAME: &str = "wash";
+/// How long the service control manager waits for the dispatcher to start
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the service control manager waits between status reports while
+/// the host starts
+const START_WAIT_HINT: Duration = Duration::from_secs(60);
+
+/// The status handle of the service, registered once the dispatcher calls
+/// `service_main`
+static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+static CHECKPOINT: AtomicU32 = AtomicU32::new(0);
+static STOP: LazyLock<Notify> = LazyLock::new(Notify::new);
+/// Tells [`Service::start`] whether `service_main` registered the service
+static STARTED: Mutex<Option<SyncSender<std::io::Result<()>>>> = Mutex::new(None);
+
+/// The host running as a Windows service. Dropping it reports the service as
+/// stopped with an error, unless [`Service::stopped`] was called.
+#[derive(Debug)]
+pub struct Service {
+    stopped: AtomicBool,
+}
+
+impl Service {
+    /// Connects to the service control manager and reports the service as
+    /// starting.
+    ///
+    /// # Errors
+    /// Returns an error if the process was not started by the service
+    /// control manager.
+    pub fn start() -> anyhow::Result<Self> {
+        let (tx, rx) = sync_channel(1);
+        *STARTED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(tx.clone());
+
+        // The dispatcher blocks its thread until the service stops
+        std::thread::Builder::new()
+            .name("wash-service-dispatcher".to_string())
+            .spawn(move || {
+                let mut name = wide(SERVICE_NAME);
+                let table = [
+                    SERVICE_TABLE_ENTRYW {
+                        lpServiceName: name.as_mut_ptr(),
+                        lpServiceProc: Some(service_main),
+                    },
+                    SERVICE_TABLE_ENTRYW {
+                        lpServiceName: std::ptr::null_mut(),
+                        lpServiceProc: None,
+                    },
+                ];
+                // SAFETY: the table is terminated by a null entry and outlives
+                // the call, which returns once every service stopped
+                if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+                    let _ = tx.send(Err(std::io::Error::last_os_error()));
+                }
+            })
+            .context("failed to spawn service dispatcher thread")?;
+
+        match rx.recv_timeout(START_TIMEOUT) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                return Err(e).context(
+                    "failed to connect to the service control manager, --windows-service is only for hosts started as a service",
+                );
+            }
+            Err(_) => bail!("the service control manager did not start the service"),
+        }
+        info!("Running as a Windows service");
+        Ok(Self {
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    /// Reports the service as running.
+    pub fn running(&self) {
+        report(SERVICE_RUNNING, NO_ERROR, 0, Duration::ZERO);
+    }
+
+    /// Waits until the service control manager asks the service to stop.
+    pub async fn stop_requested(&self) {
+        STOP.notified().await
+    }
+
+    /// Reports the service as stopping for at most `wait_hint`.
+    pub fn stopping(&self, wait_hint: Duration) {
+        report(SERVICE_STOP_PENDING, NO_ERROR, 0, wait_hint);
+    }
+
+    /// Reports the service as stopped after the host exited successfully.
+    pub fn stopped(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        report(SERVICE_STOPPED, NO_ERROR, 0, Duration::ZERO);
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        if !self.stopped.load(Ordering::SeqCst) {
+            report(
+                SERVICE_STOPPED,
+                ERROR_SERVICE_SPECIFIC_ERROR,
+                1,
+                Duration::ZERO,
+            );
+        }
+    }
+}
+
+/// Called by the dispatcher when the service control manager starts the
+/// service. The host keeps running after it returns.
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+    let name = wide(SERVICE_NAME);
+    // SAFETY: the name is a null-terminated wide string and the handler
+    // does not use its context
+    let handle = unsafe {
+        RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), std::ptr::null())
+    };
+    let result = if handle.is_null() {
+        Err(std::io::Error::last_os_error())
+    } else {
+        STATUS_HANDLE.store(handle as usize, Ordering::SeqCst);
+        report(SERVICE_START_PENDING, NO_ERROR, 0, START_WAIT_HINT);
+        Ok(())
+    };
+    let started = STARTED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take();
+    if let Some(started) = started {
+        let _ = started.send(result);
+    }
+}
+
+/// Handles controls sent to the service by the service control manager.
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut c_void,
+    _context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            STOP.notify_one();
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+/// Reports the state of the service to the service control manager.
+fn report(
+    state: SERVICE_STATUS_CURRENT_STATE,
+    exit_code: u32,
+    service_exit_code: u32,
+    wait_hint: Duration,
+) {
+    let handle = STATUS_HANDLE.load(Ordering::SeqCst);
+    if handle == 0 {
+        return;
+    }
+    let pending = matches!(state, SERVICE_START_PENDING | SERVICE_STOP_PENDING);
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        // Controls are only accepted once the host runs
+        dwControlsAccepted: if state == SERVICE_RUNNING {
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+        } else {
+            0
+        },
+        dwWin32ExitCode: exit_code,
+        dwServiceSpecificExitCode: service_exit_code,
+        dwCheckPoint: if pending {
+            CHECKPOINT.fetch_add(1, Ordering::SeqCst) + 1
+        } else {
+            0
+        },
+        dwWaitHint: u32::try_from(wait_hint.as_millis()).unwrap_or(u32::MAX),
+    };
+    // SAFETY: the handle was returned by RegisterServiceCtrlHandlerExW and is
+    // valid while the process runs
+    if unsafe { SetServiceStatus(handle as *mut c_void, &status) } == 0 {
+        warn!(
+            "failed to report service status: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Encodes `s` as a null-terminated wide string.
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
@@ -66,6 +66,9 @@ struct Cli {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Subcommand)]
 enum WashCliCommand {
+    /// Load test an HTTP route
+    #[clap(name = "bench")]
+    Bench(wash::cli::bench::BenchCommand),
     /// Build a Wasm component
     #[clap(name = "build")]
     Build(wash::cli::component_build::ComponentBuildCommand),
@@ -81,6 +84,9 @@ enum WashCliCommand {
     /// Check the health of your wash installation and environment
     #[clap(name = "doctor")]
     Doctor(wash::cli::doctor::DoctorCommand),
+    /// Build the test fixtures missing from a checkout of wash
+    #[clap(name = "fixtures", hide = true)]
+    Fixtures(wash::cli::fixtures::FixturesCommand),
     /// Inspect a Wasm component's embedded WIT
     #[clap(name = "inspect", hide = true)]
     Inspect(wash::cli::inspect::InspectCommand),
@@ -96,6 +102,12 @@ enum WashCliCommand {
     /// Manage wash plugins
     #[clap(name = "plugin", subcommand)]
     Plugin(wash::cli::plugin::PluginCommand),
+    /// Compare the desired state of workloads with the actual state of hosts
+    #[clap(name = "status")]
+    Status(wash::cli::status::StatusCommand),
+    /// Issue tokens for the runtime API
+    #[clap(name = "token", subcommand)]
+    Token(wash::cli::token::TokenCommand),
     /// Update wash to the latest version
     #[clap(name = "update", alias = "upgrade")]
     Update(wash::cli::update::UpdateCommand),
@@ -109,6 +121,7 @@ impl CliCommand for WashCliCommand {
     #[instrument(level = "debug", skip_all, name = "wash")]
     async fn handle(&self, ctx: &CliContext) -> anyhow::Result<CommandOutput> {
         match self {
+            WashCliCommand::Bench(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Build(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Completion(cmd) => {
                 // Handle completion generation directly here since we need access to the full CLI
@@ -131,11 +144,14 @@ impl CliCommand for WashCliCommand {
             WashCliCommand::Config(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Dev(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Doctor(cmd) => cmd.handle(ctx).await,
+            WashCliCommand::Fixtures(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Inspect(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Host(cmd) => cmd.handle(ctx).await,
             WashCliCommand::New(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Oci(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Plugin(cmd) => cmd.handle(ctx).await,
+            WashCliCommand::Status(cmd) => cmd.handle(ctx).await,
+            WashCliCommand::Token(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Update(cmd) => cmd.handle(ctx).await,
             WashCliCommand::Wit(cmd) => cmd.handle(ctx).await,
         }
@@ -143,32 +159,40 @@ impl CliCommand for WashCliCommand {
 
     fn enable_pre_hook(&self) -> Option<wash::plugin::bindings::wasmcloud::wash::types::HookType> {
         match self {
+            WashCliCommand::Bench(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Build(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Completion(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Config(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Dev(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Doctor(cmd) => cmd.enable_pre_hook(),
+            WashCliCommand::Fixtures(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Inspect(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Host(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::New(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Oci(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Plugin(cmd) => cmd.enable_pre_hook(),
+            WashCliCommand::Status(cmd) => cmd.enable_pre_hook(),
+            WashCliCommand::Token(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Update(cmd) => cmd.enable_pre_hook(),
             WashCliCommand::Wit(cmd) => cmd.enable_pre_hook(),
         }
     }
     fn enable_post_hook(&self) -> Option<wash::plugin::bindings::wasmcloud::wash::types::HookType> {
         match self {
+            WashCliCommand::Bench(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Build(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Completion(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Config(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Dev(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Doctor(cmd) => cmd.enable_post_hook(),
+            WashCliCommand::Fixtures(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Inspect(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Host(cmd) => cmd.enable_post_hook(),
             WashCliCommand::New(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Oci(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Plugin(cmd) => cmd.enable_post_hook(),
+            WashCliCommand::Status(cmd) => cmd.enable_post_hook(),
+            WashCliCommand::Token(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Update(cmd) => cmd.enable_post_hook(),
             WashCliCommand::Wit(cmd) => cmd.enable_post_hook(),
         }
@@ -255,12 +279,15 @@ async fn main() {
         let term = dialoguer::console::Term::stdout();
         let _ = term.show_cursor();
 
-        // Stop all running workloads and the host runtime
+        // Stop all running workloads and the host runtime, letting requests in
+        // flight finish for a few seconds
         // Note: Signal handlers run outside the normal runtime context,
         // so block_on is safe here and won't deadlock
         if let Ok(handle) = tokio::runtime::Handle::try_current() {
             let host = host_for_handler.clone();
-            if let Err(e) = handle.block_on(async move { host.stop().await }) {
+            if let Err(e) = handle
+                .block_on(async move { host.shutdown(std::time::Duration::from_secs(5)).await })
+            {
                 eprintln!("Error stopping host: {e:?}");
             }
         }
@@ -7,7 +7,7 @@
 //! 4. Testing the component resolution system that links them together
 
 use anyhow::{Context, Result};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 mod common;
@@ -22,9 +22,8 @@ use wash_runtime::{
     plugin::{wasi_config::WasiConfig, wasi_keyvalue::WasiKeyvalue, wasi_logging::WasiLogging},
     types::{
         Component, HostPathVolume, LocalResources, Volume, VolumeMount, VolumeType, Workload,
-        WorkloadStartRequest,
+        route::HttpRouteConfig,
     },
-    wit::WitInterface,
 };
 
 use wash::plugin::PluginManager;
@@ -90,110 +89,55 @@ async fn test_http_counter_with_blobstore_fs_plugin() -> Result<()> {
     // Create a workload with BOTH components:
     // 1. blobstore-filesystem component (provides wasi:blobstore)
     // 2. http-counter component (consumes wasi:blobstore)
-    let req = WorkloadStartRequest {
-        workload_id: uuid::Uuid::new_v4().to_string(),
-        workload: Workload {
-            namespace: "test".to_string(),
-            name: "http-counter-with-fs-blobstore".to_string(),
-            annotations: HashMap::new(),
-            service: None,
-            components: vec![
-                // Component 1: Blobstore filesystem plugin as a component
-                Component {
-                    bytes: bytes::Bytes::from_static(BLOBSTORE_FS_WASM),
-                    local_resources: LocalResources {
-                        memory_limit_mb: 128,
-                        cpu_limit: 1,
-                        config: HashMap::new(),
-                        environment: HashMap::new(),
-                        volume_mounts: vec![
-                            // Mount the temp directory for blobstore-filesystem to use
-                            VolumeMount {
-                                name: "blobstore-data".to_string(),
-                                mount_path: "/data".to_string(),
-                                read_only: false,
-                            },
-                        ],
-                        allowed_hosts: vec![],
-                    },
-                    pool_size: 1,
-                    max_invocations: 100,
-                },
-                // Component 2: HTTP counter that will use the blobstore
-                Component {
-                    bytes: bytes::Bytes::from_static(HTTP_COUNTER_WASM),
-                    local_resources: LocalResources {
-                        memory_limit_mb: 256,
-                        cpu_limit: 2,
-                        config: {
-                            let mut config = HashMap::new();
-                            config.insert("test_key".to_string(), "test_value".to_string());
-                            config.insert("counter_enabled".to_string(), "true".to_string());
-                            config
-                        },
-                        environment: HashMap::new(),
-                        volume_mounts: vec![],
-                        allowed_hosts: vec!["example.com".to_string()],
-                    },
-                    pool_size: 2,
-                    max_invocations: 100,
-                },
-            ],
-            // Host interfaces that the workload needs
-            host_interfaces: vec![
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "http".to_string(),
-                    interfaces: ["incoming-handler".to_string()].into_iter().collect(),
-                    version: None,
-                    config: {
-                        let mut config = HashMap::new();
-                        config.insert("host".to_string(), "test".to_string());
-                        config
-                    },
-                },
-                // NOTE: We DON'T include wasi:blobstore here because it will be
-                // provided by the blobstore-filesystem component, not the host
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "keyvalue".to_string(),
-                    interfaces: ["store".to_string(), "atomics".to_string()]
-                        .into_iter()
-                        .collect(),
-                    version: Some(semver::Version::parse("0.2.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "logging".to_string(),
-                    interfaces: ["logging".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.1.0-draft").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasi".to_string(),
-                    package: "config".to_string(),
-                    interfaces: ["store".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.2.0-rc.1").unwrap()),
-                    config: HashMap::new(),
-                },
-                WitInterface {
-                    namespace: "wasmcloud".to_string(),
-                    package: "wash".to_string(),
-                    interfaces: ["types".to_string()].into_iter().collect(),
-                    version: Some(semver::Version::parse("0.0.2").unwrap()),
-                    config: HashMap::new(),
-                },
-            ],
-            // Volume for blobstore-filesystem to use
-            volumes: vec![Volume {
-                name: "blobstore-data".to_string(),
-                volume_type: VolumeType::HostPath(HostPathVolume {
-                    local_path: blobstore_path.to_string_lossy().to_string(),
-                }),
-            }],
-        },
-    };
+    let req = Workload::builder("test", "http-counter-with-fs-blobstore")
+        // Component 1: Blobstore filesystem plugin as a component
+        .with_component(
+            Component::new(BLOBSTORE_FS_WASM)
+                .with_local_resources(LocalResources {
+                    memory_limit_mb: 128,
+                    cpu_limit: 1,
+                    ..Default::default()
+                })
+                // Mount the temp directory for blobstore-filesystem to use
+                .with_volume_mount(VolumeMount {
+                    name: "blobstore-data".to_string(),
+                    mount_path: "/data".to_string(),
+                    read_only: false,
+                    ..Default::default()
+                })
+                .with_pool_size(1)
+                .with_max_invocations(100),
+        )
+        // Component 2: HTTP counter that will use the blobstore
+        .with_component(
+            Component::new(HTTP_COUNTER_WASM)
+                .with_local_resources(LocalResources {
+                    memory_limit_mb: 256,
+                    cpu_limit: 2,
+                    ..Default::default()
+                })
+                .with_config("test_key", "test_value")
+                .with_config("counter_enabled", "true")
+                .with_allowed_host("example.com")
+                .with_pool_size(2)
+                .with_max_invocations(100),
+        )
+        // Host interfaces that the workload needs
+        .with_http_route_config(HttpRouteConfig::new("test"))
+        // NOTE: We DON'T include wasi:blobstore here because it will be
+        // provided by the blobstore-filesystem component, not the host
+        .with_host_interface("wasi:keyvalue/store,atomics@0.2.0-draft")
+        .with_host_interface("wasi:logging/logging@0.1.0-draft")
+        .with_host_interface("wasi:config/store@0.2.0-rc.1")
+        .with_host_interface("wasmcloud:wash/types@0.0.2")
+        // Volume for blobstore-filesystem to use
+        .with_volume(Volume {
+            name: "blobstore-data".to_string(),
+            volume_type: VolumeType::HostPath(HostPathVolume {
+                local_path: blobstore_path.to_string_lossy().to_string(),
+            }),
+        })
+        .start_request();
 
     // Start the workload - this should:
     // 1. Load both components